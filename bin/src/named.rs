@@ -39,47 +39,95 @@
 extern crate clap;
 
 use std::{
+    collections::HashMap,
     env, fmt,
     net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs},
     path::{Path, PathBuf},
     sync::Arc,
+    time::SystemTime,
 };
 
 use clap::{Arg, ArgMatches};
+use futures::future;
 use time::OffsetDateTime;
 use tokio::{
     net::{TcpListener, UdpSocket},
     runtime,
+    sync::{oneshot, Mutex, Notify},
 };
 use tracing::{debug, error, info, warn, Event, Subscriber};
 use tracing_subscriber::{
     fmt::{format, FmtContext, FormatEvent, FormatFields, FormattedFields},
-    layer::SubscriberExt,
+    layer::{Layer, SubscriberExt},
     registry::LookupSpan,
+    reload,
     util::SubscriberInitExt,
+    EnvFilter, Registry,
 };
 
-use trust_dns_client::rr::Name;
+use trust_dns_client::rr::{LowerName, Name};
 #[cfg(feature = "dns-over-tls")]
 use trust_dns_server::config::dnssec::{self, TlsCertConfig};
+#[cfg(feature = "blocklist")]
+use trust_dns_server::store::blocklist::BlocklistAuthority;
 #[cfg(feature = "resolver")]
 use trust_dns_server::store::forwarder::ForwardAuthority;
 #[cfg(feature = "recursor")]
 use trust_dns_server::store::recursor::RecursiveAuthority;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use trust_dns_server::store::sql::SqlAuthority;
 #[cfg(feature = "sqlite")]
 use trust_dns_server::store::sqlite::{SqliteAuthority, SqliteConfig};
+#[cfg(feature = "acme")]
 use trust_dns_server::{
-    authority::{AuthorityObject, Catalog, ZoneType},
-    config::{Config, ZoneConfig},
-    server::ServerFuture,
+    acme::CertificateManager,
+    server::{ReloadableCertificate, TlsCertificate},
+};
+use trust_dns_server::{
+    authority::{AuthorityObject, Catalog, FrozenZones, ResponsePolicyZone, View, ZoneType},
+    config::{AccessControlConfig, Config, ViewConfig, ZoneConfig},
+    dnstap::Dnstap,
+    server::{
+        AccessControl, Acl, AclOperation, ListenFds, QueryLog, ReloadHandle, ReloadableHandler,
+        RequestStats, ResponseRateLimiter, ServerFuture, ShutdownHandle,
+    },
     store::{
         file::{FileAuthority, FileConfig},
+        in_memory::InMemoryAuthority,
+        secondary::{CatalogMember, CatalogZone, SecondaryAuthority, SecondaryConfig},
         StoreConfig,
     },
 };
 
+#[cfg(unix)]
+use {
+    std::str::FromStr,
+    trust_dns_client::rr::{rdata::SOA, RData},
+    trust_dns_server::server::{
+        bind_control_socket, serve_control, ControlCommand, ControlHandler,
+    },
+};
+
 #[cfg(feature = "dnssec")]
-use {trust_dns_client::rr::rdata::key::KeyUsage, trust_dns_server::authority::DnssecAuthority};
+use {
+    std::time::Duration,
+    trust_dns_client::rr::rdata::key::KeyUsage,
+    trust_dns_server::authority::{spawn_key_maintenance, DnssecAuthority},
+};
+
+/// The upper bounds of the buckets of the histograms of durations, in seconds
+#[cfg(feature = "metrics")]
+const METRICS_BUCKETS: [f64; 12] = [
+    0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+];
+
+/// How often the serials of the zones are reported to the metrics endpoint
+#[cfg(feature = "metrics")]
+const ZONE_METRICS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// How often the keys of signed zones are rolled over as scheduled, and signatures refreshed
+#[cfg(feature = "dnssec")]
+const KEY_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(300);
 
 #[cfg(feature = "dnssec")]
 async fn load_keys<A, L>(
@@ -103,10 +151,21 @@ where
                 let zone_signer = key_config.try_into_signer(zone_name.clone()).map_err(|e| {
                     format!("failed to load key: {:?} msg: {}", key_config.key_path(), e)
                 })?;
-                authority
-                    .add_zone_signing_key(zone_signer)
-                    .await
-                    .expect("failed to add zone signing key to authority");
+
+                if let Some(schedule) = key_config.schedule() {
+                    let role = schedule
+                        .role()
+                        .map_err(|e| format!("bad key schedule: {}", e))?;
+                    authority
+                        .add_scheduled_signing_key(zone_signer, role, schedule.timings())
+                        .await
+                        .map_err(|e| format!("failed to add scheduled key to authority: {}", e))?;
+                } else {
+                    authority
+                        .add_zone_signing_key(zone_signer)
+                        .await
+                        .expect("failed to add zone signing key to authority");
+                }
             }
             if key_config.is_zone_update_auth() {
                 let update_auth_signer =
@@ -124,9 +183,32 @@ where
             }
         }
 
+        if let Some(nsec3_config) = zone_config.get_nsec3() {
+            let nsec3param = nsec3_config
+                .to_nsec3param()
+                .map_err(|e| format!("bad NSEC3 parameters: {}", e))?;
+            authority
+                .set_nsec3(nsec3param, nsec3_config.opt_out)
+                .await
+                .map_err(|e| format!("failed to set NSEC3 of authority: {}", e))?;
+        }
+
         info!("signing zone: {}", zone_config.get_zone()?);
         authority.secure_zone().await.expect("failed to sign zone");
     }
+
+    for tsig_key_config in zone_config.get_tsig_keys() {
+        info!(
+            "adding tsig key to zone: {}, from: {:?}",
+            tsig_key_config.key_name,
+            tsig_key_config.key_path()
+        );
+        let signer = tsig_key_config.try_into_signer()?;
+        authority
+            .add_update_tsig_key(signer)
+            .await
+            .map_err(|e| format!("failed to add tsig key to authority: {}", e))?;
+    }
     Ok(())
 }
 
@@ -140,11 +222,53 @@ async fn load_keys<T>(
     Ok(())
 }
 
+/// Rolls over the keys of a signed zone as scheduled, and keeps its signatures from expiring
+#[cfg(feature = "dnssec")]
+fn maintain_keys<A, L>(authority: &Arc<A>, zone_config: &ZoneConfig)
+where
+    A: DnssecAuthority<Lookup = L> + 'static,
+    L: Send + Sync + Sized + 'static,
+{
+    if zone_config.is_dnssec_enabled() {
+        spawn_key_maintenance(authority, KEY_MAINTENANCE_INTERVAL);
+    }
+}
+
+#[cfg(not(feature = "dnssec"))]
+fn maintain_keys<T>(_authority: &Arc<T>, _zone_config: &ZoneConfig) {}
+
+/// Applies the zone transfer and NOTIFY configuration of the zone
+fn configure_transfers(
+    authority: &mut InMemoryAuthority,
+    zone_config: &ZoneConfig,
+) -> Result<(), String> {
+    authority.set_transfer_acl(zone_config.get_allow_transfer().to_vec());
+    authority.set_also_notify(zone_config.get_also_notify().to_vec());
+
+    #[cfg(feature = "dnssec")]
+    for tsig_key_config in zone_config.get_transfer_tsig_keys() {
+        info!(
+            "adding transfer tsig key to zone: {}, from: {:?}",
+            tsig_key_config.key_name,
+            tsig_key_config.key_path()
+        );
+        authority.add_transfer_tsig_key_mut(tsig_key_config.try_into_signer()?);
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    if !zone_config.transfer_tsig_keys.is_empty() {
+        return Err("transfer_tsig_keys require the dnssec feature".to_string());
+    }
+
+    Ok(())
+}
+
 #[cfg_attr(not(feature = "dnssec"), allow(unused_mut, unused))]
 #[warn(clippy::wildcard_enum_match_arm)] // make sure all cases are handled despite of non_exhaustive
 async fn load_zone(
     zone_dir: &Path,
     zone_config: &ZoneConfig,
+    dnstap: Option<&Dnstap>,
 ) -> Result<Box<dyn AuthorityObject>, String> {
     debug!("loading zone with config: {:#?}", zone_config);
 
@@ -178,9 +302,40 @@ async fn load_zone(
             )
             .await?;
 
+            configure_transfers(&mut authority, zone_config)?;
+
             // load any keys for the Zone, if it is a dynamic update zone, then keys are required
             load_keys(&mut authority, zone_name_for_signer, zone_config).await?;
-            Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>
+            let authority = Arc::new(authority);
+            maintain_keys(&authority, zone_config);
+            Box::new(authority) as Box<dyn AuthorityObject>
+        }
+        #[cfg(any(feature = "postgres", feature = "mysql"))]
+        Some(StoreConfig::Sql(ref config)) => {
+            if zone_path.is_some() {
+                warn!("ignoring [[zones.file]] instead using [[zones.stores.zone_file_path]]");
+            }
+
+            let mut authority = SqlAuthority::try_from_config(
+                zone_name,
+                zone_type,
+                is_axfr_allowed,
+                is_dnssec_enabled,
+                Some(zone_dir),
+                config,
+            )
+            .await?;
+
+            configure_transfers(&mut authority, zone_config)?;
+
+            // load any keys for the Zone, if it is a dynamic update zone, then keys are required
+            load_keys(&mut authority, zone_name_for_signer, zone_config).await?;
+            let authority = Arc::new(authority);
+            maintain_keys(&authority, zone_config);
+
+            // follows the changes made to the zone by other servers sharing the database
+            authority.spawn_refresh();
+            Box::new(authority) as Box<dyn AuthorityObject>
         }
         Some(StoreConfig::File(ref config)) => {
             if zone_path.is_some() {
@@ -195,21 +350,53 @@ async fn load_zone(
                 config,
             )?;
 
+            configure_transfers(&mut authority, zone_config)?;
+
             // load any keys for the Zone, if it is a dynamic update zone, then keys are required
             load_keys(&mut authority, zone_name_for_signer, zone_config).await?;
-            Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>
+            let authority = Arc::new(authority);
+            maintain_keys(&authority, zone_config);
+            Box::new(authority) as Box<dyn AuthorityObject>
+        }
+        Some(StoreConfig::Secondary(ref config)) => {
+            if zone_path.is_some() {
+                warn!("ignoring [[zones.file]] instead using [[zones.stores.zone_file_path]]");
+            }
+
+            let authority = Arc::new(load_secondary(zone_dir, zone_config, config)?);
+
+            // transfers the zone, and keeps it up to date with the primaries
+            authority.spawn_refresh();
+            Box::new(authority) as Box<dyn AuthorityObject>
         }
         #[cfg(feature = "resolver")]
         Some(StoreConfig::Forward(ref config)) => {
-            let forwarder = ForwardAuthority::try_from_config(zone_name, zone_type, config)?;
+            let mut forwarder = ForwardAuthority::try_from_config(zone_name, zone_type, config)?;
+            forwarder.set_dnstap(dnstap.cloned());
 
             Box::new(Arc::new(forwarder)) as Box<dyn AuthorityObject>
         }
+        #[cfg(feature = "blocklist")]
+        Some(StoreConfig::Blocklist(ref config)) => {
+            let mut authority =
+                BlocklistAuthority::try_from_config(zone_name, zone_type, config, Some(zone_dir))?;
+            authority.set_dnstap(dnstap.cloned());
+
+            // the names of the lists which could be read are filtered, the others are read again
+            let authority = Arc::new(authority);
+            if let Err(e) = authority.reload().await {
+                warn!("failed to read the lists of zone {}: {}", zone_config.zone, e);
+            }
+            authority.spawn_reload();
+
+            Box::new(authority) as Box<dyn AuthorityObject>
+        }
         #[cfg(feature = "recursor")]
         Some(StoreConfig::Recursor(ref config)) => {
             let recursor =
                 RecursiveAuthority::try_from_config(zone_name, zone_type, config, Some(zone_dir));
-            let authority = recursor.await?;
+            let mut authority = recursor.await?;
+            authority.set_dnstap(dnstap.cloned());
 
             Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>
         }
@@ -229,6 +416,11 @@ async fn load_zone(
                 zone_file_path,
                 journal_file_path,
                 allow_update: zone_config.is_update_allowed(),
+                #[cfg(feature = "resolver")]
+                aname: None,
+                #[cfg(feature = "geoip")]
+                geo: None,
+                pools: Vec::new(),
             };
 
             let mut authority = SqliteAuthority::try_from_config(
@@ -241,14 +433,17 @@ async fn load_zone(
             )
             .await?;
 
+            configure_transfers(&mut authority, zone_config)?;
+
             // load any keys for the Zone, if it is a dynamic update zone, then keys are required
             load_keys(&mut authority, zone_name_for_signer, zone_config).await?;
-            Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>
+            let authority = Arc::new(authority);
+            maintain_keys(&authority, zone_config);
+            Box::new(authority) as Box<dyn AuthorityObject>
         }
         None => {
-            let config = FileConfig {
-                zone_file_path: zone_path.ok_or("file is a necessary parameter of zone_config")?,
-            };
+            let config =
+                FileConfig::new(zone_path.ok_or("file is a necessary parameter of zone_config")?);
 
             let mut authority = FileAuthority::try_from_config(
                 zone_name,
@@ -258,9 +453,13 @@ async fn load_zone(
                 &config,
             )?;
 
+            configure_transfers(&mut authority, zone_config)?;
+
             // load any keys for the Zone, if it is a dynamic update zone, then keys are required
             load_keys(&mut authority, zone_name_for_signer, zone_config).await?;
-            Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>
+            let authority = Arc::new(authority);
+            maintain_keys(&authority, zone_config);
+            Box::new(authority) as Box<dyn AuthorityObject>
         }
         Some(_) => {
             panic!("unrecognized authority type, check enabled features");
@@ -271,6 +470,845 @@ async fn load_zone(
     Ok(authority)
 }
 
+/// Loads a secondary zone, it is transferred once its refresh is spawned
+fn load_secondary(
+    zone_dir: &Path,
+    zone_config: &ZoneConfig,
+    config: &SecondaryConfig,
+) -> Result<SecondaryAuthority, String> {
+    let mut authority = SecondaryAuthority::try_from_config(
+        zone_config.get_zone()?,
+        zone_config.get_zone_type(),
+        zone_config.is_axfr_allowed(),
+        Some(zone_dir),
+        config,
+    )?;
+    configure_transfers(&mut authority, zone_config)?;
+    Ok(authority)
+}
+
+/// The zones loaded from a configuration, which are kept by a reload of the configuration unless
+///  their configuration or zone file changed
+struct LoadedZones {
+    config: Option<Arc<Config>>,
+    authorities: HashMap<ZoneKey, LoadedZone>,
+    /// The member zones of the catalog zones
+    members: HashMap<ZoneKey, LoadedMember>,
+    /// Notified when a catalog zone changes, shared by all catalog zones
+    catalog_changed: Arc<Notify>,
+}
+
+impl Default for LoadedZones {
+    fn default() -> Self {
+        Self {
+            config: None,
+            authorities: HashMap::new(),
+            members: HashMap::new(),
+            catalog_changed: Arc::new(Notify::new()),
+        }
+    }
+}
+
+/// The name of the view of the zone, if any, and the name of the zone
+type ZoneKey = (Option<String>, LowerName);
+
+struct LoadedZone {
+    modified: Option<SystemTime>,
+    authority: Box<dyn AuthorityObject>,
+    /// The authority of a catalog zone, whose members are served as well
+    catalog: Option<Arc<SecondaryAuthority>>,
+}
+
+/// A member zone of a catalog zone, see RFC 9432
+struct LoadedMember {
+    /// The name of the catalog zone which lists the member
+    catalog: LowerName,
+    member: CatalogMember,
+    config: SecondaryConfig,
+    allow_axfr: bool,
+    authority: Arc<SecondaryAuthority>,
+}
+
+impl LoadedZones {
+    /// Returns the zone loaded, if neither its configuration nor its zone file changed since
+    fn reusable(
+        &self,
+        view: Option<&str>,
+        zone_config: &ZoneConfig,
+        modified: Option<SystemTime>,
+    ) -> Option<&LoadedZone> {
+        let config = self.config.as_ref()?;
+        let zones = match view {
+            Some(view) => config
+                .get_views()
+                .iter()
+                .find(|v| v.get_name() == view)?
+                .get_zones(),
+            None => config.get_zones(),
+        };
+
+        let zone_name = zone_config.get_zone().ok()?;
+        let previous_config = zones
+            .iter()
+            .find(|z| z.get_zone().ok().as_ref() == Some(&zone_name))?;
+        let loaded = self
+            .authorities
+            .get(&(view.map(ToString::to_string), zone_name.into()))?;
+
+        if previous_config == zone_config && loaded.modified == modified {
+            Some(loaded)
+        } else {
+            None
+        }
+    }
+}
+
+/// The configuration of the catalog zone, if the zone is one
+fn catalog_zone_config(zone_config: &ZoneConfig) -> Option<&SecondaryConfig> {
+    match zone_config.stores {
+        Some(StoreConfig::Secondary(ref config)) if config.catalog.is_some() => Some(config),
+        _ => None,
+    }
+}
+
+/// The modification time of the zone file, for zones which are loaded from a file which only the
+///  operator changes
+fn zone_file_modified(zone_dir: &Path, zone_config: &ZoneConfig) -> Option<SystemTime> {
+    let zone_file_path = match zone_config.stores {
+        Some(StoreConfig::File(ref config)) if !config.allow_update => &config.zone_file_path,
+        None if !zone_config.is_update_allowed() => zone_config.file.as_ref()?,
+        _ => return None,
+    };
+
+    zone_dir
+        .join(zone_file_path)
+        .metadata()
+        .ok()?
+        .modified()
+        .ok()
+}
+
+/// Loads the zones into the catalog, reusing the unchanged zones of the previous configuration
+async fn load_zones(
+    zone_dir: &Path,
+    view: Option<&str>,
+    zones: &[ZoneConfig],
+    previous: &LoadedZones,
+    catalog: &mut Catalog,
+    loaded: &mut LoadedZones,
+    dnstap: Option<&Dnstap>,
+) -> Result<(), String> {
+    let mut catalog_zones = Vec::new();
+    for zone in zones {
+        let zone_name = zone
+            .get_zone()
+            .map_err(|_| format!("bad zone name: {}", zone.zone))?;
+
+        let modified = zone_file_modified(zone_dir, zone);
+        let (authority, catalog_zone) = match previous.reusable(view, zone, modified) {
+            Some(reused) => {
+                debug!("zone is unchanged: {}", zone_name);
+                (reused.authority.box_clone(), reused.catalog.clone())
+            }
+            None => match catalog_zone_config(zone) {
+                Some(config) => {
+                    let mut authority = load_secondary(zone_dir, zone, config)
+                        .map_err(|e| format!("could not load zone {}: {}", zone_name, e))?;
+                    authority.set_changed_notify(Arc::clone(&loaded.catalog_changed));
+
+                    let authority = Arc::new(authority);
+                    authority.spawn_refresh();
+                    info!("catalog zone successfully loaded: {}", zone_name);
+                    (
+                        Box::new(Arc::clone(&authority)) as Box<dyn AuthorityObject>,
+                        Some(authority),
+                    )
+                }
+                None => {
+                    let authority = load_zone(zone_dir, zone, dnstap)
+                        .await
+                        .map_err(|e| format!("could not load zone {}: {}", zone_name, e))?;
+                    (authority, None)
+                }
+            },
+        };
+
+        if let (Some(authority), Some(config)) = (&catalog_zone, catalog_zone_config(zone)) {
+            catalog_zones.push((Arc::clone(authority), config));
+        }
+
+        loaded.authorities.insert(
+            (view.map(ToString::to_string), zone_name.clone().into()),
+            LoadedZone {
+                modified,
+                authority: authority.box_clone(),
+                catalog: catalog_zone,
+            },
+        );
+
+        if zone.is_response_policy() {
+            info!("applying response policy zone: {}", zone_name);
+            catalog.add_response_policy(ResponsePolicyZone::new(authority))
+        } else {
+            catalog.upsert(zone_name.clone().into(), authority)
+        }
+
+        if let Some(acl_config) = zone.get_acl() {
+            info!("restricting access to zone: {}", zone_name);
+            catalog.set_zone_access_control(zone_name.clone().into(), access_control(acl_config)?);
+        }
+
+        if let Some(limits) = zone.get_rate_limit() {
+            info!("limiting the rate of responses for zone: {}", zone_name);
+            catalog.set_zone_rate_limiter(zone_name.into(), ResponseRateLimiter::new(*limits));
+        }
+    }
+
+    load_catalog_members(zone_dir, view, &catalog_zones, previous, catalog, loaded).await;
+    Ok(())
+}
+
+/// Loads the member zones of the catalog zones into the catalog, see RFC 9432
+///
+/// A catalog zone which is not loaded, or can't be read, keeps its previous members. A zone
+///  listed by several catalog zones belongs to the first of them, or to the one which listed it
+///  before, until that one allows it to move with a `coo` property. Members are transferred
+///  again when their unique id changes.
+async fn load_catalog_members(
+    zone_dir: &Path,
+    view: Option<&str>,
+    catalog_zones: &[(Arc<SecondaryAuthority>, &SecondaryConfig)],
+    previous: &LoadedZones,
+    catalog: &mut Catalog,
+    loaded: &mut LoadedZones,
+) {
+    let view_key = view.map(ToString::to_string);
+
+    // the members listed by each catalog zone
+    let mut listed = Vec::new();
+    for (authority, config) in catalog_zones {
+        let catalog_name = authority.origin().clone();
+        let read = if authority.is_current() {
+            CatalogZone::from_records(&catalog_name, &authority.records().await)
+        } else {
+            Err("the zone is not loaded".to_string())
+        };
+
+        let members = match read {
+            Ok(catalog_zone) => catalog_zone.members().to_vec(),
+            Err(e) => {
+                debug!(
+                    "keeping the members of catalog zone {}: {}",
+                    catalog_name, e
+                );
+                previous
+                    .members
+                    .iter()
+                    .filter(|((v, _), m)| *v == view_key && m.catalog == catalog_name)
+                    .map(|(_, m)| m.member.clone())
+                    .collect()
+            }
+        };
+
+        listed.push((catalog_name, *config, members));
+    }
+
+    // the catalog zone which owns each member
+    let mut owners: HashMap<LowerName, usize> = HashMap::new();
+    for (index, (catalog_name, _, members)) in listed.iter().enumerate() {
+        for member in members {
+            let zone = LowerName::from(&member.zone);
+            if catalog.contains(&zone) {
+                warn!(
+                    "member {} of catalog zone {} is configured, ignoring it",
+                    zone, catalog_name
+                );
+                continue;
+            }
+
+            let owner = match owners.get(&zone) {
+                Some(&owner) => owner,
+                None => {
+                    owners.insert(zone, index);
+                    continue;
+                }
+            };
+
+            let previous_owner = previous
+                .members
+                .get(&(view_key.clone(), zone.clone()))
+                .map(|m| &m.catalog);
+            let moves = listed[owner]
+                .2
+                .iter()
+                .find(|m| LowerName::from(&m.zone) == zone)
+                .and_then(|m| m.change_of_ownership.as_ref())
+                .map_or(false, |coo| LowerName::from(coo) == *catalog_name);
+
+            if previous_owner == Some(catalog_name) {
+                // the catalog zone which listed the member before keeps it
+                owners.insert(zone, index);
+            } else if moves {
+                info!(
+                    "member {} moves from catalog zone {} to {}",
+                    zone, listed[owner].0, catalog_name
+                );
+                owners.insert(zone, index);
+            } else {
+                warn!(
+                    "member {} of catalog zone {} belongs to {}, ignoring it",
+                    zone, catalog_name, listed[owner].0
+                );
+            }
+        }
+    }
+
+    for (zone, index) in owners {
+        let (catalog_name, catalog_config, members) = &listed[index];
+        let member = match members.iter().find(|m| LowerName::from(&m.zone) == zone) {
+            Some(member) => member,
+            None => continue,
+        };
+
+        let member_catalog = match catalog_config.catalog {
+            Some(ref member_catalog) => member_catalog,
+            None => continue,
+        };
+        let config = member_catalog.member_config(catalog_config, member);
+        let allow_axfr = member_catalog.is_member_axfr_allowed(member);
+
+        if let Some(ref zone_file_dir) = member_catalog.zone_file_dir {
+            if let Err(e) = std::fs::create_dir_all(zone_dir.join(zone_file_dir)) {
+                warn!("could not create directory {}: {}", zone_file_dir, e);
+            }
+        }
+
+        let key = (view_key.clone(), zone.clone());
+        let authority = match previous.members.get(&key) {
+            Some(loaded_member)
+                if loaded_member.member.unique_id == member.unique_id
+                    && loaded_member.config == config
+                    && loaded_member.allow_axfr == allow_axfr =>
+            {
+                Arc::clone(&loaded_member.authority)
+            }
+            _ => {
+                info!("loading member {} of catalog zone {}", zone, catalog_name);
+                let authority = SecondaryAuthority::try_from_config(
+                    Name::from(&zone),
+                    ZoneType::Secondary,
+                    allow_axfr,
+                    Some(zone_dir),
+                    &config,
+                );
+
+                let authority = match authority {
+                    Ok(authority) => Arc::new(authority),
+                    Err(e) => {
+                        error!("could not load member {}: {}", zone, e);
+                        continue;
+                    }
+                };
+                authority.spawn_refresh();
+                authority
+            }
+        };
+
+        catalog.upsert(zone.clone(), Box::new(Arc::clone(&authority)));
+        loaded.members.insert(
+            key,
+            LoadedMember {
+                catalog: catalog_name.clone(),
+                member: member.clone(),
+                config,
+                allow_axfr,
+                authority,
+            },
+        );
+    }
+
+    for (key, previous_member) in &previous.members {
+        if key.0 == view_key && !loaded.members.contains_key(key) {
+            info!(
+                "removed member {} of catalog zone {}",
+                key.1, previous_member.catalog
+            );
+        }
+    }
+}
+
+/// Builds the ACLs of the configuration, reading their TSIG keys
+fn access_control(config: &AccessControlConfig) -> Result<AccessControl, String> {
+    let acls = [
+        (AclOperation::Query, &config.query),
+        (AclOperation::Update, &config.update),
+        (AclOperation::Transfer, &config.transfer),
+        (AclOperation::Notify, &config.notify),
+    ];
+
+    let mut access_control = AccessControl::default();
+    for (operation, acl_config) in acls {
+        let acl_config = match acl_config {
+            Some(acl_config) => acl_config,
+            None => continue,
+        };
+
+        #[allow(unused_mut)]
+        let mut acl = Acl::new(acl_config.networks.clone());
+
+        #[cfg(feature = "dnssec")]
+        for key_config in &acl_config.keys {
+            acl.add_key(key_config.try_into_signer()?);
+        }
+
+        #[cfg(not(feature = "dnssec"))]
+        if !acl_config.keys.is_empty() {
+            return Err("acl keys require the dnssec feature".to_string());
+        }
+
+        access_control.set_acl(operation, Some(acl));
+    }
+
+    Ok(access_control)
+}
+
+/// Loads the zones of the view, and the addresses and keys of its clients
+#[cfg_attr(not(feature = "dnssec"), allow(unused_mut))]
+async fn load_view(
+    zone_dir: &Path,
+    view_config: &ViewConfig,
+    previous: &LoadedZones,
+    loaded: &mut LoadedZones,
+    dnstap: Option<&Dnstap>,
+) -> Result<View, String> {
+    let mut catalog = Catalog::new();
+    load_zones(
+        zone_dir,
+        Some(view_config.get_name()),
+        view_config.get_zones(),
+        previous,
+        &mut catalog,
+        loaded,
+        dnstap,
+    )
+    .await?;
+
+    let mut view = View::new(view_config.get_name().to_string(), catalog);
+    view.set_match_clients(view_config.get_match_clients().to_vec());
+
+    #[cfg(feature = "dnssec")]
+    for key_config in view_config.get_match_keys() {
+        info!(
+            "adding match key to view: {}, key: {}",
+            view_config.get_name(),
+            key_config.key_name
+        );
+        view.add_match_key(key_config.try_into_signer()?);
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    if !view_config.match_keys.is_empty() {
+        return Err("match_keys require the dnssec feature".to_string());
+    }
+
+    Ok(view)
+}
+
+/// Loads the catalog of all zones and views of the configuration
+///
+/// The zones of `previous` which are unchanged are reused, so that they keep their state, e.g.
+///  transferred secondary zones.
+async fn load_catalog(
+    zone_dir: &Path,
+    config: Arc<Config>,
+    previous: &LoadedZones,
+    request_stats: &Arc<RequestStats>,
+    frozen_zones: &FrozenZones,
+    dnstap: Option<&Dnstap>,
+    query_log: Option<&QueryLog>,
+) -> Result<(Catalog, LoadedZones), String> {
+    let mut catalog: Catalog = Catalog::new();
+    let mut loaded = LoadedZones {
+        config: Some(Arc::clone(&config)),
+        catalog_changed: Arc::clone(&previous.catalog_changed),
+        ..LoadedZones::default()
+    };
+    load_zones(
+        zone_dir,
+        None,
+        config.get_zones(),
+        previous,
+        &mut catalog,
+        &mut loaded,
+        dnstap,
+    )
+    .await?;
+
+    let rate_limiter = config
+        .get_rate_limit()
+        .copied()
+        .map(ResponseRateLimiter::new);
+    let access_control = config
+        .get_acl()
+        .map(access_control)
+        .transpose()?
+        .unwrap_or_default();
+    catalog.set_rate_limiter(rate_limiter.clone());
+    catalog.set_access_control(access_control.clone());
+    catalog.set_request_stats(Some(Arc::clone(request_stats)));
+    catalog.set_query_log(query_log.cloned());
+    catalog.set_frozen_zones(frozen_zones.clone());
+    for view_config in config.get_views() {
+        info!("loading view: {}", view_config.get_name());
+        let mut view = load_view(zone_dir, view_config, previous, &mut loaded, dnstap)
+            .await
+            .map_err(|e| format!("could not load view {}: {}", view_config.get_name(), e))?;
+        view.catalog_mut().set_rate_limiter(rate_limiter.clone());
+        view.catalog_mut()
+            .set_access_control(access_control.clone());
+        view.catalog_mut().set_frozen_zones(frozen_zones.clone());
+        catalog.add_view(view);
+    }
+
+    Ok((catalog, loaded))
+}
+
+/// Reloads the configuration and the zones while the server is running
+///
+/// The new catalog replaces the current one only if all zones loaded, requests in flight are
+///  completed with the previous catalog. Listeners are not changed by a reload, changes to the
+///  listen addresses, ports and TLS certificates require a restart.
+struct Reloader {
+    config_path: PathBuf,
+    zone_dir: Option<PathBuf>,
+    handle: ReloadHandle<Catalog>,
+    loaded: Mutex<LoadedZones>,
+    request_stats: Arc<RequestStats>,
+    frozen_zones: FrozenZones,
+    dnstap: Option<Dnstap>,
+    query_log: Option<QueryLog>,
+}
+
+impl Reloader {
+    /// Reads the configuration again and replaces the catalog, returns an error if the
+    ///  configuration or any zone can't be loaded, in which case the current catalog is kept
+    async fn reload(&self) -> Result<(), String> {
+        info!("reloading configuration from: {:?}", self.config_path);
+        let config = Config::read_config(&self.config_path)
+            .map(Arc::new)
+            .map_err(|e| {
+                format!(
+                    "could not read config {}: {:?}",
+                    self.config_path.display(),
+                    e
+                )
+            })?;
+
+        let mut loaded = self.loaded.lock().await;
+        self.replace_catalog(config, &mut loaded).await?;
+        info!(
+            "configuration reloaded, zones: {}",
+            loaded.authorities.len()
+        );
+        Ok(())
+    }
+
+    /// Loads the zones of the current configuration again, to serve the current members of the
+    ///  catalog zones
+    async fn update_catalog_zones(&self) -> Result<(), String> {
+        let mut loaded = self.loaded.lock().await;
+        let config = loaded.config.clone().ok_or("no configuration is loaded")?;
+
+        self.replace_catalog(config, &mut loaded).await?;
+        info!(
+            "catalog zones updated, member zones: {}",
+            loaded.members.len()
+        );
+        Ok(())
+    }
+
+    async fn replace_catalog(
+        &self,
+        config: Arc<Config>,
+        loaded: &mut LoadedZones,
+    ) -> Result<(), String> {
+        let zone_dir = self
+            .zone_dir
+            .clone()
+            .unwrap_or_else(|| config.get_directory().to_path_buf());
+
+        let (catalog, reloaded) = load_catalog(
+            &zone_dir,
+            config,
+            loaded,
+            &self.request_stats,
+            &self.frozen_zones,
+            self.dnstap.as_ref(),
+            self.query_log.as_ref(),
+        )
+        .await?;
+
+        self.handle.replace(catalog).await;
+        *loaded = reloaded;
+        Ok(())
+    }
+}
+
+/// Updates the member zones each time a catalog zone changes
+async fn update_catalog_zones(reloader: Arc<Reloader>) {
+    let catalog_changed = Arc::clone(&reloader.loaded.lock().await.catalog_changed);
+
+    loop {
+        catalog_changed.notified().await;
+        if let Err(e) = reloader.update_catalog_zones().await {
+            error!("could not update the catalog zones: {}", e);
+        }
+    }
+}
+
+/// Reloads the configuration when the process receives SIGHUP
+#[cfg(unix)]
+async fn reload_on_hangup(reloader: Arc<Reloader>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            warn!("could not handle SIGHUP, reload is disabled: {}", e);
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        if let Err(e) = reloader.reload().await {
+            error!(
+                "reload failed, still serving the previous configuration: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Shuts the server down gracefully when the process receives SIGINT, or SIGTERM
+///
+/// The in-flight requests are answered before the server stops.
+async fn shutdown_on_signal(shutdown: ShutdownHandle) {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminations) => {
+                terminations.recv().await;
+            }
+            Err(e) => {
+                warn!("could not handle SIGTERM: {}", e);
+                future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("could not handle SIGINT: {}", e);
+            future::pending::<()>().await;
+        }
+    };
+
+    futures::pin_mut!(terminate, interrupt);
+    future::select(terminate, interrupt).await;
+
+    info!("shutting down, answering the in-flight requests");
+    shutdown.shutdown();
+}
+
+/// Restarts `named` in place when the process receives SIGUSR2, e.g. once it was upgraded
+///
+/// The server shuts down gracefully and hands its sockets off to the new `named`, see `restart`.
+#[cfg(unix)]
+async fn restart_on_signal(shutdown: ShutdownHandle, restart: oneshot::Sender<ListenFds>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut restarts = match signal(SignalKind::user_defined2()) {
+        Ok(restarts) => restarts,
+        Err(e) => {
+            warn!("could not handle SIGUSR2, restarting is disabled: {}", e);
+            return;
+        }
+    };
+    restarts.recv().await;
+
+    info!("restarting, answering the in-flight requests");
+    let _ = restart.send(shutdown.hand_off());
+}
+
+/// Replaces this process with a new `named` started with the same arguments, which takes over
+///  the sockets, so that queries and connections are queued rather than refused while it starts
+///
+/// The process keeps its pid, so that the service manager keeps tracking it.
+#[cfg(unix)]
+fn restart(listen_fds: ListenFds) -> ! {
+    use std::os::unix::process::CommandExt;
+
+    // the program as it was started, the current executable may be gone once it was upgraded
+    let mut args = env::args_os();
+    let mut command = std::process::Command::new(args.next().expect("no program name"));
+    command.args(args);
+    listen_fds.pass_to(&mut command);
+
+    info!("Trust-DNS {} restarting", trust_dns_client::version());
+    let e = command.exec();
+    panic!("could not restart named: {}", e);
+}
+
+/// Executes the commands received on the control socket
+#[cfg(unix)]
+struct NamedControl {
+    reloader: Arc<Reloader>,
+    log_handle: LogHandle,
+}
+
+#[cfg(unix)]
+impl NamedControl {
+    /// Finds the zone in the catalog or any of its views
+    fn find_zone<'c>(
+        catalog: &'c Catalog,
+        zone: &LowerName,
+    ) -> Option<&'c (dyn AuthorityObject + 'static)> {
+        catalog
+            .authorities()
+            .chain(
+                catalog
+                    .views()
+                    .iter()
+                    .flat_map(|view| view.catalog().authorities()),
+            )
+            .find(|authority| authority.origin() == zone)
+    }
+
+    async fn stats(&self) -> String {
+        let catalog = self.reloader.handle.current().await;
+        let mut stats = self.reloader.request_stats.to_string();
+
+        let mut zones: Vec<(Option<&str>, &dyn AuthorityObject)> = Vec::new();
+        for authority in catalog.authorities() {
+            zones.push((None, authority));
+        }
+        for view in catalog.views() {
+            for authority in view.catalog().authorities() {
+                zones.push((Some(view.name()), authority));
+            }
+        }
+
+        for (view, authority) in zones {
+            let serial = authority.soa().await.ok().and_then(|soa| {
+                soa.iter()
+                    .find_map(|record| record.data().and_then(RData::as_soa))
+                    .map(SOA::serial)
+            });
+
+            stats.push_str(&format!("\nzone {}", authority.origin()));
+            if let Some(view) = view {
+                stats.push_str(&format!(" view {}", view));
+            }
+            stats.push_str(&format!(" type {:?}", authority.zone_type()));
+            if let Some(serial) = serial {
+                stats.push_str(&format!(" serial {}", serial));
+            }
+            if catalog.frozen_zones().is_frozen(authority.origin()) {
+                stats.push_str(" frozen");
+            }
+        }
+
+        stats
+    }
+
+    async fn freeze(&self, zone: LowerName) -> Result<String, String> {
+        let catalog = self.reloader.handle.current().await;
+        let authority =
+            Self::find_zone(&catalog, &zone).ok_or_else(|| format!("unknown zone: {}", zone))?;
+
+        // updates are refused before the journal is written to the zone file, so none are lost
+        if !self.reloader.frozen_zones.freeze(zone.clone()) {
+            return Err(format!("zone is already frozen: {}", zone));
+        }
+
+        if let Err(e) = authority.persist().await {
+            self.reloader.frozen_zones.thaw(&zone);
+            return Err(format!("could not write zone {}: {}", zone, e));
+        }
+
+        Ok(format!("zone frozen: {}", zone))
+    }
+
+    fn thaw(&self, zone: LowerName) -> Result<String, String> {
+        if self.reloader.frozen_zones.thaw(&zone) {
+            Ok(format!("zone thawed: {}", zone))
+        } else {
+            Err(format!("zone is not frozen: {}", zone))
+        }
+    }
+
+    fn log_level(&self, level: &str) -> Result<String, String> {
+        // a plain level applies to all of trust-dns, as with --debug, otherwise it's a filter
+        let directives = match tracing::Level::from_str(level) {
+            Ok(level) => all_trust_dns(level),
+            Err(_) => level.to_string(),
+        };
+
+        let filter = tracing_subscriber::EnvFilter::builder()
+            .with_default_directive(tracing::Level::WARN.into())
+            .parse(&directives)
+            .map_err(|e| format!("bad log level {}: {}", level, e))?;
+        self.log_handle
+            .reload(filter)
+            .map_err(|e| format!("could not change log level: {}", e))?;
+
+        Ok(format!("log level: {}", directives))
+    }
+}
+
+#[cfg(unix)]
+#[async_trait::async_trait]
+impl ControlHandler for NamedControl {
+    async fn handle_command(&self, command: ControlCommand) -> Result<String, String> {
+        match command {
+            ControlCommand::Reload => self
+                .reloader
+                .reload()
+                .await
+                .map(|()| "configuration reloaded".to_string()),
+            ControlCommand::Flush => {
+                self.reloader.handle.current().await.clear_caches();
+                Ok("caches flushed".to_string())
+            }
+            ControlCommand::FlushName(name) => {
+                self.reloader
+                    .handle
+                    .current()
+                    .await
+                    .flush_caches(&name, false);
+                Ok(format!("flushed {} from caches", name))
+            }
+            ControlCommand::FlushTree(name) => {
+                self.reloader
+                    .handle
+                    .current()
+                    .await
+                    .flush_caches(&name, true);
+                Ok(format!("flushed {} and names below it from caches", name))
+            }
+            ControlCommand::Stats => Ok(self.stats().await),
+            ControlCommand::Freeze(zone) => self.freeze(zone.into()).await,
+            ControlCommand::Thaw(zone) => self.thaw(zone.into()),
+            ControlCommand::LogLevel(level) => self.log_level(&level),
+            command => Err(format!("unsupported command: {}", command)),
+        }
+    }
+}
+
 // argument name constants for the CLI options
 const QUIET_ARG: &str = "quiet";
 const DEBUG_ARG: &str = "debug";
@@ -280,6 +1318,7 @@ const PORT_ARG: &str = "port";
 const TLS_PORT_ARG: &str = "tls-port";
 const HTTPS_PORT_ARG: &str = "https-port";
 const QUIC_PORT_ARG: &str = "quic-port";
+const CHECK_CONFIG_ARG: &str = "check-config";
 
 /// Args struct for all options
 #[allow(dead_code)]
@@ -292,6 +1331,7 @@ struct Args {
     pub(crate) flag_tls_port: Option<u16>,
     pub(crate) flag_https_port: Option<u16>,
     pub(crate) flag_quic_port: Option<u16>,
+    pub(crate) flag_check_config: bool,
 }
 
 impl From<ArgMatches> for Args {
@@ -316,6 +1356,7 @@ impl From<ArgMatches> for Args {
             flag_quic_port: matches
                 .value_of(QUIC_PORT_ARG)
                 .map(|s| s.parse().expect("bad quic-port argument")),
+            flag_check_config: matches.is_present(CHECK_CONFIG_ARG),
         }
     }
 }
@@ -385,18 +1426,34 @@ fn main() {
                 )
                 .value_name(QUIC_PORT_ARG),
         )
+        .arg(
+            Arg::new(CHECK_CONFIG_ARG)
+                .long(CHECK_CONFIG_ARG)
+                .help(
+                    "Check the configuration, and the zone files, keys and certificates it references, then exit without serving",
+                ),
+        )
         .get_matches();
 
     let args: Args = args.into();
 
+    if args.flag_check_config {
+        std::process::exit(check_config(&args));
+    }
+
+    // the sockets passed by systemd are taken before anything else could open files
+    // SAFETY: the file descriptors passed to the process are not used otherwise
+    let mut listen_fds = unsafe { ListenFds::from_env() }
+        .unwrap_or_else(|e| panic!("could not take the sockets passed to the process: {}", e));
+
     // TODO: this should be set after loading config, but it's necessary for initial log lines, no?
-    if args.flag_quiet {
-        quiet();
+    let log_handle = if args.flag_quiet {
+        quiet()
     } else if args.flag_debug {
-        debug();
+        debug()
     } else {
-        default();
-    }
+        default()
+    };
 
     info!("Trust-DNS {} starting", trust_dns_client::version());
     // start up the server for listening
@@ -405,6 +1462,7 @@ fn main() {
     let config_path = Path::new(&flag_config);
     info!("loading configuration from: {:?}", config_path);
     let config = Config::read_config(config_path)
+        .map(Arc::new)
         .unwrap_or_else(|e| panic!("could not read config {}: {:?}", config_path.display(), e));
     let directory_config = config.get_directory().to_path_buf();
     let flag_zonedir = args.flag_zonedir.clone();
@@ -420,18 +1478,6 @@ fn main() {
         .thread_name("trust-dns-server-runtime")
         .build()
         .expect("failed to initialize Tokio Runtime");
-    let mut catalog: Catalog = Catalog::new();
-    // configure our server based on the config_path
-    for zone in config.get_zones() {
-        let zone_name = zone
-            .get_zone()
-            .unwrap_or_else(|_| panic!("bad zone name in {:?}", config_path));
-
-        match runtime.block_on(load_zone(&zone_dir, zone)) {
-            Ok(authority) => catalog.upsert(zone_name.into(), authority),
-            Err(error) => panic!("could not load zone {}: {}", zone_name, error),
-        }
-    }
 
     // TODO: support all the IPs asked to listen on...
     // TODO:, there should be the option to listen on any port, IP and protocol option...
@@ -457,17 +1503,97 @@ fn main() {
         .flat_map(|x| (*x, listen_port).to_socket_addrs().unwrap())
         .collect();
 
+    // configure our server based on the config_path
+    let request_stats = Arc::new(RequestStats::new());
+    let frozen_zones = FrozenZones::new();
+    let dnstap = config.get_dnstap().map(|dnstap_config| {
+        Dnstap::from_config(dnstap_config)
+            .unwrap_or_else(|e| panic!("could not start dnstap: {}", e))
+    });
+    let query_log = config.get_query_log().map(|query_log_config| {
+        QueryLog::from_config(query_log_config)
+            .unwrap_or_else(|e| panic!("could not start the query log: {}", e))
+    });
+    let (catalog, loaded) = runtime
+        .block_on(load_catalog(
+            &zone_dir,
+            Arc::clone(&config),
+            &LoadedZones::default(),
+            &request_stats,
+            &frozen_zones,
+            dnstap.as_ref(),
+            query_log.as_ref(),
+        ))
+        .unwrap_or_else(|e| panic!("{}", e));
+    let handler = ReloadableHandler::new(catalog);
+    let reloader = Arc::new(Reloader {
+        config_path: config_path.to_path_buf(),
+        zone_dir: flag_zonedir.map(PathBuf::from),
+        handle: handler.reload_handle(),
+        loaded: Mutex::new(loaded),
+        request_stats,
+        frozen_zones,
+        dnstap: dnstap.clone(),
+        query_log,
+    });
+
+    #[cfg(unix)]
+    runtime.spawn(reload_on_hangup(Arc::clone(&reloader)));
+    runtime.spawn(update_catalog_zones(Arc::clone(&reloader)));
+
+    if let Some(_metrics_addr) = config.get_metrics_listen_addr() {
+        #[cfg(feature = "metrics")]
+        config_metrics(_metrics_addr, reloader.handle.clone(), &runtime);
+        #[cfg(not(feature = "metrics"))]
+        warn!("metrics_listen_addr is ignored, the metrics feature is not enabled");
+    }
+
+    #[cfg(unix)]
+    if let Some(control_socket) = config.get_control_socket() {
+        info!("binding control socket to {:?}", control_socket);
+        let listener = {
+            let _guard = runtime.enter();
+            bind_control_socket(control_socket).unwrap_or_else(|e| {
+                panic!(
+                    "could not bind control socket {}: {}",
+                    control_socket.display(),
+                    e
+                )
+            })
+        };
+
+        let control = NamedControl {
+            reloader: Arc::clone(&reloader),
+            log_handle,
+        };
+        runtime.spawn(serve_control(listener, Arc::new(control)));
+    }
+    #[cfg(not(unix))]
+    drop(log_handle);
+
     // now, run the server, based on the config
-    #[cfg_attr(not(feature = "dns-over-tls"), allow(unused_mut))]
-    let mut server = ServerFuture::new(catalog);
+    let mut server = ServerFuture::new(handler);
+    server.set_dnstap(dnstap);
+    if let Some(limits) = config.get_connection_limits() {
+        server.set_connection_limits(*limits);
+    }
+    server.set_minimal_responses(config.get_minimal_responses());
+    server.set_cookies(config.get_cookies().copied());
+    if let Some(proxy_protocol) = config.get_proxy_protocol() {
+        server.set_proxy_protocol(proxy_protocol.clone());
+    }
+    if let Some(max_udp_payload) = config.get_max_udp_payload() {
+        server.set_max_udp_payload(max_udp_payload);
+    }
+    server.set_shutdown_timeout(Some(config.get_shutdown_timeout()));
+    runtime.spawn(shutdown_on_signal(server.shutdown_handle()));
+    #[cfg(unix)]
+    let (restart_sender, mut restarting) = oneshot::channel();
+    #[cfg(unix)]
+    runtime.spawn(restart_on_signal(server.shutdown_handle(), restart_sender));
 
     // load all the listeners
-    for udp_socket in &sockaddrs {
-        info!("binding UDP to {:?}", udp_socket);
-        let udp_socket = runtime
-            .block_on(UdpSocket::bind(udp_socket))
-            .unwrap_or_else(|_| panic!("could not bind to udp: {}", udp_socket));
-
+    for udp_socket in udp_sockets(&mut listen_fds, ListenFds::UDP, &sockaddrs, &runtime) {
         info!(
             "listening for UDP on {:?}",
             udp_socket
@@ -480,12 +1606,7 @@ fn main() {
     }
 
     // and TCP as necessary
-    for tcp_listener in &sockaddrs {
-        info!("binding TCP to {:?}", tcp_listener);
-        let tcp_listener = runtime
-            .block_on(TcpListener::bind(tcp_listener))
-            .unwrap_or_else(|_| panic!("could not bind to tcp: {}", tcp_listener));
-
+    for tcp_listener in tcp_listeners(&mut listen_fds, ListenFds::TCP, &sockaddrs, &runtime) {
         info!(
             "listening for TCP on {:?}",
             tcp_listener
@@ -499,17 +1620,37 @@ fn main() {
 
     let tls_cert_config = config.get_tls_cert();
 
+    // a certificate from ACME is obtained, and renewed, in the background
+    #[cfg(feature = "acme")]
+    let acme_certificate = tls_cert_config
+        .filter(|tls_cert_config| tls_cert_config.get_acme().is_some())
+        .map(|tls_cert_config| {
+            let manager = CertificateManager::new(&zone_dir, tls_cert_config)
+                .unwrap_or_else(|e| panic!("could not configure ACME: {}", e));
+            let certificate = manager.certificate();
+            runtime.spawn(manager.run(reloader.handle.clone()));
+            certificate
+        });
+
     // and TLS as necessary
     // TODO: we should add some more control from configs to enable/disable TLS/HTTPS/QUIC
     if let Some(_tls_cert_config) = tls_cert_config {
+        #[cfg(feature = "dns-over-tls")]
+        let listener_cert = ListenerCert {
+            config: _tls_cert_config,
+            zone_dir: &zone_dir,
+            #[cfg(feature = "acme")]
+            acme: acme_certificate,
+        };
+
         // setup TLS listeners
         #[cfg(feature = "dns-over-tls")]
         config_tls(
             &args,
             &mut server,
+            &mut listen_fds,
             &config,
-            _tls_cert_config,
-            &zone_dir,
+            &listener_cert,
             &listen_addrs,
             &mut runtime,
         );
@@ -519,9 +1660,9 @@ fn main() {
         config_https(
             &args,
             &mut server,
+            &mut listen_fds,
             &config,
-            _tls_cert_config,
-            &zone_dir,
+            &listener_cert,
             &listen_addrs,
             &mut runtime,
         );
@@ -531,14 +1672,18 @@ fn main() {
         config_quic(
             &args,
             &mut server,
+            &mut listen_fds,
             &config,
-            _tls_cert_config,
-            &zone_dir,
+            &listener_cert,
             &listen_addrs,
             &mut runtime,
         );
     }
 
+    for name in listen_fds.names() {
+        warn!("the {} socket passed to the process is not used", name);
+    }
+
     // config complete, starting!
     banner();
     info!("awaiting connections...");
@@ -549,6 +1694,14 @@ fn main() {
     info!("Server starting up");
     match runtime.block_on(server.block_until_done()) {
         Ok(()) => {
+            #[cfg(unix)]
+            if let Ok(listen_fds) = restarting.try_recv() {
+                // dropping the catalogs flushes the query log, the zones are already written
+                drop(runtime);
+                drop(reloader);
+                restart(listen_fds);
+            }
+
             // we're exiting for some reason...
             info!("Trust-DNS {} stopping", trust_dns_client::version());
         }
@@ -565,18 +1718,108 @@ fn main() {
     };
 }
 
+/// Checks the configuration without loading nor serving it, returns the exit status
+///
+/// All the problems are printed, with the location of each in the configuration.
+fn check_config(args: &Args) -> i32 {
+    let config_path = Path::new(&args.flag_config);
+    let config = match Config::read_config(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}: {}", config_path.display(), e);
+            return 1;
+        }
+    };
+    let zone_dir = args
+        .flag_zonedir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| config.get_directory().to_path_buf());
+
+    let problems = config.check(&zone_dir);
+    for problem in &problems {
+        eprintln!("{}: {}", config_path.display(), problem);
+    }
+
+    if problems.is_empty() {
+        println!("{}: OK", config_path.display());
+        0
+    } else {
+        1
+    }
+}
+
+/// Serves the metrics of the server, the resolver and the recursor for Prometheus
+#[cfg(feature = "metrics")]
+fn config_metrics(
+    listen_addr: SocketAddr,
+    catalog: ReloadHandle<Catalog>,
+    runtime: &runtime::Runtime,
+) {
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    info!("binding metrics endpoint to {:?}", listen_addr);
+    let _guard = runtime.enter();
+    PrometheusBuilder::new()
+        .with_http_listener(listen_addr)
+        .set_buckets(&METRICS_BUCKETS)
+        .and_then(PrometheusBuilder::install)
+        .unwrap_or_else(|e| panic!("could not serve metrics on {}: {}", listen_addr, e));
+
+    trust_dns_server::describe_metrics();
+    trust_dns_resolver::describe_metrics();
+    trust_dns_recursor::describe_metrics();
+
+    // the serials change with updates and transfers, which the catalog doesn't see
+    runtime.spawn(async move {
+        loop {
+            catalog.current().await.record_zone_metrics().await;
+            tokio::time::sleep(ZONE_METRICS_INTERVAL).await;
+        }
+    });
+}
+
+/// The certificate of the TLS, HTTPS and QUIC listeners
+#[cfg(feature = "dns-over-tls")]
+struct ListenerCert<'a> {
+    config: &'a TlsCertConfig,
+    zone_dir: &'a Path,
+    /// The certificate which is obtained with ACME, and replaced when it's renewed
+    #[cfg(feature = "acme")]
+    acme: Option<Arc<ReloadableCertificate>>,
+}
+
+#[cfg(feature = "dns-over-tls")]
+impl ListenerCert<'_> {
+    /// The certificate from ACME if it's configured, else the one loaded from the files
+    #[cfg(feature = "acme")]
+    fn load(&self) -> TlsCertificate {
+        match &self.acme {
+            Some(certificate) => Arc::clone(certificate).into(),
+            None => dnssec::load_cert(self.zone_dir, self.config)
+                .expect("error loading tls certificate file")
+                .into(),
+        }
+    }
+
+    /// The certificate loaded from the files
+    #[cfg(not(feature = "acme"))]
+    fn load(&self) -> dnssec::TlsCert {
+        dnssec::load_cert(self.zone_dir, self.config).expect("error loading tls certificate file")
+    }
+}
+
 #[cfg(feature = "dns-over-tls")]
 fn config_tls(
     args: &Args,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<ReloadableHandler<Catalog>>,
+    listen_fds: &mut ListenFds,
     config: &Config,
-    tls_cert_config: &TlsCertConfig,
-    zone_dir: &Path,
+    listener_cert: &ListenerCert<'_>,
     listen_addrs: &[IpAddr],
     runtime: &mut runtime::Runtime,
 ) {
-    use futures::TryFutureExt;
-
+    let tls_cert_config = listener_cert.config;
     let tls_listen_port: u16 = args
         .flag_tls_port
         .unwrap_or_else(|| config.get_tls_listen_port());
@@ -585,24 +1828,18 @@ fn config_tls(
         .flat_map(|x| (*x, tls_listen_port).to_socket_addrs().unwrap())
         .collect();
 
-    if tls_sockaddrs.is_empty() {
+    let tls_listeners = tcp_listeners(listen_fds, ListenFds::TLS, &tls_sockaddrs, runtime);
+    if tls_listeners.is_empty() {
         warn!("a tls certificate was specified, but no TLS addresses configured to listen on");
     }
 
-    for tls_listener in &tls_sockaddrs {
+    for tls_listener in tls_listeners {
         info!(
             "loading cert for DNS over TLS: {:?}",
             tls_cert_config.get_path()
         );
 
-        let tls_cert = dnssec::load_cert(zone_dir, tls_cert_config)
-            .expect("error loading tls certificate file");
-
-        info!("binding TLS to {:?}", tls_listener);
-        let tls_listener = runtime.block_on(
-            TcpListener::bind(tls_listener)
-                .unwrap_or_else(|_| panic!("could not bind to tls: {}", tls_listener)),
-        );
+        let tls_cert = listener_cert.load();
 
         info!(
             "listening for TLS on {:?}",
@@ -621,15 +1858,14 @@ fn config_tls(
 #[cfg(feature = "dns-over-https")]
 fn config_https(
     args: &Args,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<ReloadableHandler<Catalog>>,
+    listen_fds: &mut ListenFds,
     config: &Config,
-    tls_cert_config: &TlsCertConfig,
-    zone_dir: &Path,
+    listener_cert: &ListenerCert<'_>,
     listen_addrs: &[IpAddr],
     runtime: &mut runtime::Runtime,
 ) {
-    use futures::TryFutureExt;
-
+    let tls_cert_config = listener_cert.config;
     let https_listen_port: u16 = args
         .flag_https_port
         .unwrap_or_else(|| config.get_https_listen_port());
@@ -638,25 +1874,19 @@ fn config_https(
         .flat_map(|x| (*x, https_listen_port).to_socket_addrs().unwrap())
         .collect();
 
-    if https_sockaddrs.is_empty() {
+    let https_listeners = tcp_listeners(listen_fds, ListenFds::HTTPS, &https_sockaddrs, runtime);
+    if https_listeners.is_empty() {
         warn!("a tls certificate was specified, but no HTTPS addresses configured to listen on");
     }
 
-    for https_listener in &https_sockaddrs {
+    for https_listener in https_listeners {
         info!(
             "loading cert for DNS over TLS named {} from {:?}",
             tls_cert_config.get_endpoint_name(),
             tls_cert_config.get_path()
         );
         // TODO: see about modifying native_tls to impl Clone for Pkcs12
-        let tls_cert = dnssec::load_cert(zone_dir, tls_cert_config)
-            .expect("error loading tls certificate file");
-
-        info!("binding HTTPS to {:?}", https_listener);
-        let https_listener = runtime.block_on(
-            TcpListener::bind(https_listener)
-                .unwrap_or_else(|_| panic!("could not bind to tls: {}", https_listener)),
-        );
+        let tls_cert = listener_cert.load();
 
         info!(
             "listening for HTTPS on {:?}",
@@ -680,15 +1910,14 @@ fn config_https(
 #[cfg(feature = "dns-over-quic")]
 fn config_quic(
     args: &Args,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<ReloadableHandler<Catalog>>,
+    listen_fds: &mut ListenFds,
     config: &Config,
-    tls_cert_config: &TlsCertConfig,
-    zone_dir: &Path,
+    listener_cert: &ListenerCert<'_>,
     listen_addrs: &[IpAddr],
     runtime: &mut runtime::Runtime,
 ) {
-    use futures::TryFutureExt;
-
+    let tls_cert_config = listener_cert.config;
     let quic_listen_port: u16 = args
         .flag_quic_port
         .unwrap_or_else(|| config.get_quic_listen_port());
@@ -697,25 +1926,19 @@ fn config_quic(
         .flat_map(|x| (*x, quic_listen_port).to_socket_addrs().unwrap())
         .collect();
 
-    if quic_sockaddrs.is_empty() {
+    let quic_listeners = udp_sockets(listen_fds, ListenFds::QUIC, &quic_sockaddrs, runtime);
+    if quic_listeners.is_empty() {
         warn!("a tls certificate was specified, but no QUIC addresses configured to listen on");
     }
 
-    for quic_listener in &quic_sockaddrs {
+    for quic_listener in quic_listeners {
         info!(
             "loading cert for DNS over TLS named {} from {:?}",
             tls_cert_config.get_endpoint_name(),
             tls_cert_config.get_path()
         );
         // TODO: see about modifying native_tls to impl Clone for Pkcs12
-        let tls_cert = dnssec::load_cert(zone_dir, tls_cert_config)
-            .expect("error loading tls certificate file");
-
-        info!("binding QUIC to {:?}", quic_listener);
-        let quic_listener = runtime.block_on(
-            UdpSocket::bind(quic_listener)
-                .unwrap_or_else(|_| panic!("could not bind to tls: {}", quic_listener)),
-        );
+        let tls_cert = listener_cert.load();
 
         info!(
             "listening for QUIC on {:?}",
@@ -725,15 +1948,90 @@ fn config_quic(
         );
 
         let _guard = runtime.enter();
-        server
-            .register_quic_listener(
+        let dns_hostname = tls_cert_config.get_endpoint_name().to_string();
+        let registered = match config.get_quic_max_connections() {
+            Some(max_connections) => server.register_quic_listener_with_limit(
                 quic_listener,
-                config.get_tcp_request_timeout(),
+                config.get_quic_idle_timeout(),
                 tls_cert,
-                tls_cert_config.get_endpoint_name().to_string(),
-            )
-            .expect("could not register QUIC listener");
+                dns_hostname,
+                max_connections,
+            ),
+            None => server.register_quic_listener(
+                quic_listener,
+                config.get_quic_idle_timeout(),
+                tls_cert,
+                dns_hostname,
+            ),
+        };
+        registered.expect("could not register QUIC listener");
+    }
+}
+
+/// The UDP sockets passed to the process with the name, or else bound to the addresses
+fn udp_sockets(
+    listen_fds: &mut ListenFds,
+    name: &str,
+    addrs: &[SocketAddr],
+    runtime: &runtime::Runtime,
+) -> Vec<UdpSocket> {
+    let sockets = listen_fds
+        .take_udp(name)
+        .unwrap_or_else(|e| panic!("could not take the {} sockets: {}", name, e));
+    if !sockets.is_empty() {
+        info!("using the {} sockets passed to the process", name);
+        let _guard = runtime.enter();
+        return sockets
+            .into_iter()
+            .map(|socket| {
+                UdpSocket::from_std(socket)
+                    .unwrap_or_else(|e| panic!("could not use the {} socket: {}", name, e))
+            })
+            .collect();
+    }
+
+    addrs
+        .iter()
+        .map(|addr| {
+            info!("binding {} to {:?}", name, addr);
+            runtime
+                .block_on(UdpSocket::bind(addr))
+                .unwrap_or_else(|_| panic!("could not bind to {}: {}", name, addr))
+        })
+        .collect()
+}
+
+/// The TCP listeners passed to the process with the name, or else bound to the addresses
+fn tcp_listeners(
+    listen_fds: &mut ListenFds,
+    name: &str,
+    addrs: &[SocketAddr],
+    runtime: &runtime::Runtime,
+) -> Vec<TcpListener> {
+    let listeners = listen_fds
+        .take_tcp(name)
+        .unwrap_or_else(|e| panic!("could not take the {} listeners: {}", name, e));
+    if !listeners.is_empty() {
+        info!("using the {} listeners passed to the process", name);
+        let _guard = runtime.enter();
+        return listeners
+            .into_iter()
+            .map(|listener| {
+                TcpListener::from_std(listener)
+                    .unwrap_or_else(|e| panic!("could not use the {} listener: {}", name, e))
+            })
+            .collect();
     }
+
+    addrs
+        .iter()
+        .map(|addr| {
+            info!("binding {} to {:?}", name, addr);
+            runtime
+                .block_on(TcpListener::bind(addr))
+                .unwrap_or_else(|_| panic!("could not bind to {}: {}", name, addr))
+        })
+        .collect()
 }
 
 fn banner() {
@@ -813,33 +2111,40 @@ fn all_trust_dns(level: impl ToString) -> String {
     )
 }
 
+/// A handle to change the log filter while the server is running
+type LogHandle = reload::Handle<EnvFilter, Registry>;
+
 /// appends trust-dns-server debug to RUST_LOG
-pub fn debug() {
-    logger(tracing::Level::DEBUG);
+pub fn debug() -> LogHandle {
+    logger(tracing::Level::DEBUG)
 }
 
 /// appends trust-dns-server info to RUST_LOG
-pub fn default() {
-    logger(tracing::Level::INFO);
+pub fn default() -> LogHandle {
+    logger(tracing::Level::INFO)
 }
 
 /// appends trust-dns-server error to RUST_LOG
-pub fn quiet() {
-    logger(tracing::Level::ERROR);
+pub fn quiet() -> LogHandle {
+    logger(tracing::Level::ERROR)
 }
 
 // TODO: add dep on util crate, share logging config...
-fn logger(level: tracing::Level) {
+fn logger(level: tracing::Level) -> LogHandle {
     // Setup tracing for logging based on input
-    let filter = tracing_subscriber::EnvFilter::builder()
+    let filter = EnvFilter::builder()
         .with_default_directive(tracing::Level::WARN.into())
         .parse(all_trust_dns(level))
         .expect("failed to configure tracing/logging");
+    let (filter, handle) = reload::Layer::new(filter);
 
     let formatter = tracing_subscriber::fmt::layer().event_format(TdnsFormatter);
 
-    tracing_subscriber::registry()
-        .with(formatter)
-        .with(filter)
-        .init();
+    // the filter only applies to the log, the console sees the tasks of the runtime
+    let registry = tracing_subscriber::registry().with(formatter.with_filter(filter));
+    #[cfg(feature = "tokio-console")]
+    let registry = registry.with(console_subscriber::spawn());
+    registry.init();
+
+    handle
 }
@@ -100,11 +100,9 @@ pub use crate::runtime::AsyncStdConnection;
 pub use crate::runtime::AsyncStdConnectionProvider;
 use crate::runtime::AsyncStdRuntimeHandle;
 
-mod net;
 mod runtime;
 #[cfg(test)]
 mod tests;
-mod time;
 
 pub use trust_dns_resolver::config;
 pub use trust_dns_resolver::error::ResolveError;
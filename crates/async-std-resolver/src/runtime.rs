@@ -6,7 +6,9 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::future::Future;
+use std::pin::Pin;
 
+use trust_dns_resolver::proto::async_std::{AsyncStdTcpStream, AsyncStdTime, AsyncStdUdpSocket};
 use trust_dns_resolver::proto::error::ProtoError;
 use trust_dns_resolver::proto::Executor;
 
@@ -14,9 +16,6 @@ use trust_dns_resolver::name_server::{
     GenericConnection, GenericConnectionProvider, RuntimeProvider, Spawn,
 };
 
-use crate::net::{AsyncStdTcpStream, AsyncStdUdpSocket};
-use crate::time::AsyncStdTime;
-
 /// The async_std runtime.
 ///
 /// The runtime provides an I/O [driver], task scheduler, [timer], and blocking
@@ -41,7 +40,7 @@ use crate::time::AsyncStdTime;
 /// result in an error.
 ///
 /// [driver]: crate::io::driver
-/// [timer]: crate::time
+/// [timer]: AsyncStdTime
 /// [mod]: index.html
 /// [`new`]: #method.new
 #[derive(Clone, Copy)]
@@ -66,6 +65,17 @@ impl Spawn for AsyncStdRuntimeHandle {
     {
         let _join = async_std::task::spawn(future);
     }
+
+    fn spawn_blocking<F, T>(
+        &mut self,
+        f: F,
+    ) -> Pin<Box<dyn Future<Output = Result<T, ProtoError>> + Send + 'static>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        Box::pin(async move { Ok(async_std::task::spawn_blocking(f).await) })
+    }
 }
 
 impl RuntimeProvider for AsyncStdRuntime {
@@ -12,20 +12,20 @@ use std::task::{Context, Poll};
 use std::time::Duration;
 
 use futures_util::ready;
-use futures_util::stream::{Stream, StreamExt};
+use futures_util::stream::{self, Stream, StreamExt};
 use rand;
 use tracing::debug;
 use trust_dns_proto::op::Edns;
 
-use crate::client::Signer;
+use crate::client::{RetryPolicy, Signer, ZoneTransfer};
 use crate::error::*;
-use crate::op::{update_message, Message, MessageType, OpCode, Query};
+use crate::op::{update_message, Message, MessageType, OpCode, Query, UpdateBuilder};
 use crate::proto::error::{ProtoError, ProtoErrorKind};
 use crate::proto::xfer::{
-    BufDnsStreamHandle, DnsClientStream, DnsExchange, DnsExchangeBackground, DnsExchangeSend,
-    DnsHandle, DnsMultiplexer, DnsRequest, DnsRequestOptions, DnsRequestSender, DnsResponse,
+    BufDnsStreamHandle, DnsClientStream, DnsExchange, DnsExchangeBackground, DnsHandle,
+    DnsMultiplexer, DnsRequest, DnsRequestOptions, DnsRequestSender, DnsResponse, FirstAnswer,
 };
-use crate::proto::TokioTime;
+use crate::proto::{Time, TokioTime};
 use crate::rr::rdata::SOA;
 use crate::rr::{DNSClass, Name, RData, Record, RecordSet, RecordType};
 
@@ -44,10 +44,16 @@ pub type ClientFuture = AsyncClient;
 ///
 /// This Client is generic and capable of wrapping UDP, TCP, and other underlying DNS protocol
 ///  implementations.
+///
+/// Requests are retried according to the [`RetryPolicy`] of the client, which can be overridden
+///  per request with [`AsyncClient::send_with_policy`].
 #[derive(Clone)]
 pub struct AsyncClient {
     exchange: DnsExchange,
     use_edns: bool,
+    retry_policy: RetryPolicy,
+    tcp_fallback: Option<DnsExchange>,
+    alternates: Arc<Vec<Self>>,
 }
 
 impl AsyncClient {
@@ -125,8 +131,136 @@ impl AsyncClient {
         F: Future<Output = Result<S, ProtoError>> + 'static + Send + Unpin,
     {
         let result = DnsExchange::connect(connect_future).await;
-        let use_edns = true;
-        result.map(|(exchange, bg)| (Self { exchange, use_edns }, bg))
+        result.map(|(exchange, bg)| {
+            let client = Self {
+                exchange,
+                use_edns: true,
+                retry_policy: RetryPolicy::default(),
+                tcp_fallback: None,
+                alternates: Arc::new(Vec::new()),
+            };
+
+            (client, bg)
+        })
+    }
+
+    /// Sets the connection over which truncated responses are retried, generally a TCP
+    ///  connection to the same server
+    ///
+    /// The background of the fallback client must be spawned, as for any client.
+    pub fn with_tcp_fallback(mut self, fallback: Self) -> Self {
+        self.tcp_fallback = Some(fallback.exchange);
+        self
+    }
+
+    /// Adds a client of an alternate server, to which requests are sent if they failed on this
+    ///  server, in the order the alternates were added
+    ///
+    /// The retry policy of this client applies to the alternates, with their TCP fallbacks.
+    pub fn with_alternate(mut self, alternate: Self) -> Self {
+        Arc::make_mut(&mut self.alternates).push(alternate);
+        self
+    }
+
+    /// Sets the policy for retrying requests, which is used unless a request is sent with
+    ///  [`AsyncClient::send_with_policy`]
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// The policy for retrying requests
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Sends a request, which is retried according to `retry_policy` instead of the policy of
+    ///  this client
+    pub fn send_with_policy<R: Into<DnsRequest>>(
+        &self,
+        request: R,
+        retry_policy: RetryPolicy,
+    ) -> <Self as DnsHandle>::Response {
+        let request = request.into();
+
+        let retries = retry_policy.attempts() > 1
+            || (retry_policy.tcp_on_truncation() && self.tcp_fallback.is_some())
+            || (retry_policy.failover() && !self.alternates.is_empty());
+        let is_xfr = request
+            .queries()
+            .iter()
+            .any(|q| matches!(q.query_type(), RecordType::AXFR | RecordType::IXFR));
+        if !retries || is_xfr || request.op_code() != OpCode::Query {
+            return Box::pin(self.exchange.clone().send(request));
+        }
+
+        let servers = if retry_policy.failover() {
+            Some(self.clone())
+                .into_iter()
+                .chain(self.alternates.iter().cloned())
+                .collect()
+        } else {
+            vec![self.clone()]
+        };
+
+        Box::pin(stream::once(async move {
+            let mut error = None;
+            for server in servers {
+                match server.send_retrying(request.clone(), &retry_policy).await {
+                    Ok(response) => return Ok(response),
+                    Err(e) if e.category().is_retryable() => {
+                        debug!("request failed, trying the next server: {}", e);
+                        error = Some(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            Err(error.unwrap_or_else(|| ProtoError::from("no servers to send the request to")))
+        }))
+    }
+
+    /// Sends the request to this server, retransmitting it on retryable errors, and retrying
+    ///  it over the TCP fallback if the response was truncated
+    async fn send_retrying(
+        &self,
+        request: DnsRequest,
+        retry_policy: &RetryPolicy,
+    ) -> Result<DnsResponse, ProtoError> {
+        let mut attempt = 0;
+        let response = loop {
+            match self
+                .exchange
+                .clone()
+                .send(request.clone())
+                .first_answer()
+                .await
+            {
+                Err(e) if e.category().is_retryable() && attempt + 1 < retry_policy.attempts() => {
+                    let backoff = retry_policy.backoff(attempt);
+                    debug!("request failed, retrying in {:?}: {}", backoff, e);
+                    TokioTime::delay_for(backoff).await;
+                    attempt += 1;
+                }
+                response => break response?,
+            }
+        };
+
+        let mut tcp_fallback = match self.tcp_fallback {
+            Some(ref tcp_fallback) if response.truncated() && retry_policy.tcp_on_truncation() => {
+                tcp_fallback.clone()
+            }
+            _ => return Ok(response),
+        };
+
+        debug!("truncated response received, retrying over TCP");
+        match tcp_fallback.send(request).first_answer().await {
+            Ok(tcp_response) => Ok(tcp_response),
+            Err(e) => {
+                // the truncated response is still better than nothing
+                debug!("request over TCP failed: {}", e);
+                Ok(response)
+            }
+        }
     }
 
     /// (Re-)enable usage of EDNS for outgoing messages
@@ -141,11 +275,11 @@ impl AsyncClient {
 }
 
 impl DnsHandle for AsyncClient {
-    type Response = DnsExchangeSend;
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
     type Error = ProtoError;
 
     fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, request: R) -> Self::Response {
-        self.exchange.send(request)
+        self.send_with_policy(request, self.retry_policy)
     }
 
     fn is_using_edns(&self) -> bool {
@@ -582,23 +716,39 @@ pub trait ClientHandle: 'static + Clone + DnsHandle<Error = ProtoError> + Send {
         ClientResponse(self.send(message))
     }
 
-    /// Download all records from a zone, or all records modified since given SOA was observed.
-    /// The request will either be a AXFR Query (ask for full zone transfer) if a SOA was not
-    /// provided, or a IXFR Query (incremental zone transfer) if a SOA was provided.
+    /// Sends an UPDATE with any prerequisites and updates, see [`UpdateBuilder`]
+    ///
+    /// EDNS is used if it is enabled for this client.
     ///
     /// # Arguments
-    /// * `zone_origin` - the zone name to update, i.e. SOA name
-    /// * `last_soa` - the last SOA known, if any. If provided, name must match `zone_origin`
+    ///
+    /// * `update` - the prerequisites and updates for the zone
+    ///
+    /// The update must go to a zone authority (i.e. the server used in the ClientConnection).
+    fn update(&mut self, update: UpdateBuilder) -> ClientResponse<<Self as DnsHandle>::Response> {
+        let message = update.use_edns(self.is_using_edns()).build();
+
+        ClientResponse(self.send(message))
+    }
 
+    /// Transfer a zone as a stream of typed record batches, see [`ZoneTransfer`]
+    ///
+    /// The request is an IXFR (incremental zone transfer) if `ixfr_serial` is provided,
+    ///  otherwise an AXFR (full zone transfer). Transient errors resume the transfer, see
+    ///  [`ZoneTransfer::with_attempts`].
+    ///
+    /// # Arguments
+    /// * `zone_origin` - the zone name to transfer, i.e. SOA name
+    /// * `ixfr_serial` - the serial of the SOA of the last known version of the zone, if any
+    /// * `signer` - signs the request and verifies the responses, e.g. with TSIG, instead of the
+    ///              signer of the connection
     fn zone_transfer(
         &mut self,
         zone_origin: Name,
-        last_soa: Option<SOA>,
-    ) -> ClientStreamXfr<<Self as DnsHandle>::Response> {
-        let ixfr = last_soa.is_some();
-        let message = update_message::zone_transfer(zone_origin, last_soa);
-
-        ClientStreamXfr::new(self.send(message), ixfr)
+        ixfr_serial: Option<u32>,
+        signer: Option<Arc<Signer>>,
+    ) -> ZoneTransfer<Self> {
+        ZoneTransfer::new(self.clone(), zone_origin, ixfr_serial, signer)
     }
 }
 
@@ -657,7 +807,7 @@ impl<R> ClientStreamXfr<R>
 where
     R: Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin + 'static,
 {
-    fn new(inner: R, maybe_incr: bool) -> Self {
+    pub(crate) fn new(inner: R, maybe_incr: bool) -> Self {
         Self {
             state: ClientStreamXfrState::Start { inner, maybe_incr },
         }
@@ -858,9 +1008,13 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use super::*;
 
+    use crate::proto::xfer::DnsResponseStream;
     use crate::rr::rdata::soa::SOA;
+    use futures_util::future;
     use futures_util::stream::iter;
     use ClientStreamXfrState::*;
 
@@ -1077,4 +1231,133 @@ mod tests {
 
         assert!(stream.next().await.is_none());
     }
+
+    /// A sender which answers each request, failing the first `timeouts` with a timeout
+    struct TestSender {
+        truncated: bool,
+        timeouts: usize,
+        sent: Arc<AtomicUsize>,
+    }
+
+    impl Stream for TestSender {
+        type Item = Result<(), ProtoError>;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Pending
+        }
+    }
+
+    impl DnsRequestSender for TestSender {
+        fn send_message(&mut self, request: DnsRequest) -> DnsResponseStream {
+            if self.sent.fetch_add(1, Ordering::SeqCst) < self.timeouts {
+                return ProtoError::from(ProtoErrorKind::Timeout).into();
+            }
+
+            let mut message = Message::new();
+            message
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_truncated(self.truncated)
+                .add_queries(request.queries().to_vec());
+            let response = DnsResponse::from(message);
+
+            Box::pin(async move { Ok(response) }).into()
+        }
+
+        fn shutdown(&mut self) {}
+
+        fn is_shutdown(&self) -> bool {
+            false
+        }
+    }
+
+    async fn test_client(truncated: bool, timeouts: usize) -> (AsyncClient, Arc<AtomicUsize>) {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sender = TestSender {
+            truncated,
+            timeouts,
+            sent: Arc::clone(&sent),
+        };
+
+        let (client, bg) = AsyncClient::connect(future::ok(sender)).await.unwrap();
+        tokio::spawn(bg);
+        (client, sent)
+    }
+
+    async fn query(client: &mut AsyncClient) -> ClientResult<DnsResponse> {
+        client
+            .query(
+                Name::from_ascii("www.example.com.").unwrap(),
+                DNSClass::IN,
+                RecordType::A,
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_retransmits() {
+        let (mut client, sent) = test_client(false, 2).await;
+        client.set_retry_policy(
+            RetryPolicy::default()
+                .with_attempts(3)
+                .with_backoff(Duration::from_millis(1), Duration::from_millis(1)),
+        );
+
+        query(&mut client).await.expect("query failed");
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_attempts_exhausted() {
+        let (mut client, sent) = test_client(false, 2).await;
+        client.set_retry_policy(
+            RetryPolicy::default()
+                .with_attempts(2)
+                .with_backoff(Duration::from_millis(1), Duration::from_millis(1)),
+        );
+
+        let error = query(&mut client).await.unwrap_err();
+        assert!(matches!(error.kind(), ClientErrorKind::Timeout));
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_fallback_on_truncation() {
+        let (udp, _) = test_client(true, 0).await;
+        let (tcp, tcp_sent) = test_client(false, 0).await;
+        let mut client = udp.with_tcp_fallback(tcp);
+
+        let response = query(&mut client).await.expect("query failed");
+        assert!(!response.truncated());
+        assert_eq!(tcp_sent.load(Ordering::SeqCst), 1);
+
+        // the fallback can be disabled per request
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_ascii("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let response = client
+            .send_with_policy(
+                DnsRequest::new(message, DnsRequestOptions::default()),
+                RetryPolicy::none(),
+            )
+            .next()
+            .await
+            .expect("no response")
+            .expect("query failed");
+        assert!(response.truncated());
+        assert_eq!(tcp_sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_alternate() {
+        let (primary, primary_sent) = test_client(false, usize::MAX).await;
+        let (alternate, alternate_sent) = test_client(false, 0).await;
+        let mut client = primary.with_alternate(alternate);
+
+        query(&mut client).await.expect("query failed");
+        assert_eq!(primary_sent.load(Ordering::SeqCst), 1);
+        assert_eq!(alternate_sent.load(Ordering::SeqCst), 1);
+    }
 }
@@ -12,13 +12,17 @@ use std::pin::Pin;
 use futures_util::stream::Stream;
 
 use crate::client::AsyncClient;
+use crate::error::ClientResult;
+use crate::op::Query;
 use crate::proto::error::ProtoError;
 use crate::proto::rr::dnssec::TrustAnchor;
 use crate::proto::xfer::{
-    DnsExchangeBackground, DnsHandle, DnsRequest, DnsRequestSender, DnsResponse,
+    DnsExchangeBackground, DnsHandle, DnsRequest, DnsRequestOptions, DnsRequestSender, DnsResponse,
+    ProvenResponse,
 };
 use crate::proto::DnssecDnsHandle;
 use crate::proto::TokioTime;
+use crate::rr::{DNSClass, Name, RecordType};
 
 /// A DNSSEC Client implemented over futures-rs.
 ///
@@ -52,6 +56,32 @@ impl AsyncDnssecClient {
         Self::builder(connect_future).build().await
     }
 
+    /// A query, which returns all records of the response with the result of their validation
+    ///
+    /// Unlike `query()`, which drops the records that failed to validate, each RRset is returned
+    ///  with its [`Proof`](crate::rr::dnssec::Proof) and the DNSKEY and DS records used to
+    ///  validate it. For negative responses, the NSEC or NSEC3 denial of existence is validated.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup
+    pub fn query_with_proof(
+        &self,
+        name: Name,
+        query_class: DNSClass,
+        query_type: RecordType,
+    ) -> impl Future<Output = ClientResult<ProvenResponse>> + Send + 'static {
+        let mut query = Query::query(name, query_type);
+        query.set_query_class(query_class);
+        let mut options = DnsRequestOptions::default();
+        options.use_edns = true;
+
+        let response = self.client.lookup_with_proof(query, options);
+        async move { response.await.map_err(Into::into) }
+    }
+
     fn from_client(client: AsyncClient, trust_anchor: TrustAnchor) -> Self {
         Self {
             client: DnssecDnsHandle::with_trust_anchor(client, trust_anchor),
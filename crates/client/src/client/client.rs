@@ -13,26 +13,35 @@
 // limitations under the License.
 
 use std::future::Future;
+use std::io;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
+use futures_util::future::FutureExt;
 use futures_util::stream::{Stream, StreamExt};
+use lazy_static::lazy_static;
 use tokio::runtime::{self, Runtime};
+use tracing::debug;
 use trust_dns_proto::xfer::DnsRequest;
 
 use crate::client::async_client::ClientStreamXfr;
 use crate::client::{AsyncClient, ClientConnection, ClientHandle, Signer};
 use crate::error::*;
+use crate::op::{update_message, UpdateBuilder};
 use crate::proto::{
     error::ProtoError,
-    xfer::{DnsExchangeSend, DnsHandle, DnsResponse},
+    xfer::{DnsHandle, DnsResponse},
 };
 use crate::rr::rdata::SOA;
 use crate::rr::{DNSClass, Name, Record, RecordSet, RecordType};
 #[cfg(feature = "dnssec")]
 use {
-    crate::client::AsyncDnssecClient,
-    crate::rr::dnssec::{tsig::TSigner, SigSigner, TrustAnchor},
+    crate::client::{AsyncDnssecClient, ProvenResponse},
+    crate::rr::dnssec::{gss_tsig::GssTSigner, tsig::TSigner, SigSigner, TrustAnchor},
 };
 
 use super::ClientStreamingResponse;
@@ -67,6 +76,11 @@ pub(crate) type NewFutureObj<H> = Pin<
 /// *note* When upgrading from previous usage, both `SyncClient` and `SyncDnssecClient` have an
 /// signer which can be optionally associated to the Client. This replaces the previous per-function
 /// parameter, and it will sign all update requests (this matches the `AsyncClient` API).
+///
+/// The requests of all clients are executed on a runtime shared by all clients, which is driven
+/// by a background thread, no runtime or executor needs to be managed by the caller. The
+/// connection of a client is established on the first request and reused by all following
+/// requests, until it's closed, e.g. by the server.
 #[allow(unreachable_code)]
 pub trait Client {
     /// The result stream that will resolve into a DnsResponse
@@ -79,7 +93,11 @@ pub trait Client {
     /// Consumes the connection and allows for future based operations afterward.
     fn new_future(&self) -> NewFutureObj<Self::Handle>;
 
+    /// The connection of this client, which is shared by all its requests
+    fn connection(&self) -> &SyncConnection<Self::Handle>;
+
     /// This will create a new AsyncClient and spawn it into a new Runtime
+    #[deprecated(note = "requests are executed on a shared runtime, see `Client::connection`")]
     fn spawn_client(&self) -> ClientResult<(Self::Handle, Runtime)> {
         let mut builder = runtime::Builder::new_current_thread();
         builder.enable_all();
@@ -100,11 +118,13 @@ pub trait Client {
         &self,
         msg: R,
     ) -> Vec<ClientResult<DnsResponse>> {
-        let (mut client, runtime) = match self.spawn_client() {
-            Ok(c_r) => c_r,
-            Err(e) => return vec![Err(e)],
-        };
-        runtime.block_on(ClientStreamingResponse(client.send(msg)).collect::<Vec<_>>())
+        let responses = execute(self, |client| {
+            ClientStreamingResponse(client.send(msg))
+                .collect::<Vec<_>>()
+                .map(Ok)
+        });
+
+        responses.unwrap_or_else(|e| vec![Err(e)])
     }
 
     /// A *classic* DNS query, i.e. does not perform any DNSSec operations
@@ -123,9 +143,9 @@ pub trait Client {
         query_class: DNSClass,
         query_type: RecordType,
     ) -> ClientResult<DnsResponse> {
-        let (mut client, runtime) = self.spawn_client()?;
-
-        runtime.block_on(client.query(name.clone(), query_class, query_type))
+        execute(self, |client| {
+            client.query(name.clone(), query_class, query_type)
+        })
     }
 
     /// Sends a NOTIFY message to the remote system
@@ -146,9 +166,9 @@ pub trait Client {
     where
         R: Into<RecordSet>,
     {
-        let (mut client, runtime) = self.spawn_client()?;
-
-        runtime.block_on(client.notify(name, query_class, query_type, rrset))
+        execute(&*self, |client| {
+            client.notify(name, query_class, query_type, rrset)
+        })
     }
 
     /// Sends a record to create on the server, this will fail if the record exists (atomicity
@@ -188,9 +208,7 @@ pub trait Client {
     where
         R: Into<RecordSet>,
     {
-        let (mut client, runtime) = self.spawn_client()?;
-
-        runtime.block_on(client.create(rrset, zone_origin))
+        execute(self, |client| client.create(rrset, zone_origin))
     }
 
     /// Appends a record to an existing rrset, optionally require the rrset to exist (atomicity
@@ -231,9 +249,7 @@ pub trait Client {
     where
         R: Into<RecordSet>,
     {
-        let (mut client, runtime) = self.spawn_client()?;
-
-        runtime.block_on(client.append(rrset, zone_origin, must_exist))
+        execute(self, |client| client.append(rrset, zone_origin, must_exist))
     }
 
     /// Compares and if it matches, swaps it for the new value (atomicity depends on the server)
@@ -287,9 +303,9 @@ pub trait Client {
         CR: Into<RecordSet>,
         NR: Into<RecordSet>,
     {
-        let (mut client, runtime) = self.spawn_client()?;
-
-        runtime.block_on(client.compare_and_swap(current, new, zone_origin))
+        execute(self, |client| {
+            client.compare_and_swap(current, new, zone_origin)
+        })
     }
 
     /// Deletes a record (by rdata) from an rrset, optionally require the rrset to exist.
@@ -331,9 +347,7 @@ pub trait Client {
     where
         R: Into<RecordSet>,
     {
-        let (mut client, runtime) = self.spawn_client()?;
-
-        runtime.block_on(client.delete_by_rdata(record, zone_origin))
+        execute(self, |client| client.delete_by_rdata(record, zone_origin))
     }
 
     /// Deletes an entire rrset, optionally require the rrset to exist.
@@ -372,9 +386,7 @@ pub trait Client {
     /// The update must go to a zone authority (i.e. the server used in the ClientConnection). If
     /// the rrset does not exist and must_exist is false, then the RRSet will be deleted.
     fn delete_rrset(&self, record: Record, zone_origin: Name) -> ClientResult<DnsResponse> {
-        let (mut client, runtime) = self.spawn_client()?;
-
-        runtime.block_on(client.delete_rrset(record, zone_origin))
+        execute(self, |client| client.delete_rrset(record, zone_origin))
     }
 
     /// Deletes all records at the specified name
@@ -407,9 +419,20 @@ pub trait Client {
         zone_origin: Name,
         dns_class: DNSClass,
     ) -> ClientResult<DnsResponse> {
-        let (mut client, runtime) = self.spawn_client()?;
+        execute(self, |client| {
+            client.delete_all(name_of_records, zone_origin, dns_class)
+        })
+    }
 
-        runtime.block_on(client.delete_all(name_of_records, zone_origin, dns_class))
+    /// Sends an UPDATE with any prerequisites and updates, see [`UpdateBuilder`]
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - the prerequisites and updates for the zone
+    ///
+    /// The update must go to a zone authority (i.e. the server used in the ClientConnection).
+    fn update(&self, update: UpdateBuilder) -> ClientResult<DnsResponse> {
+        execute(self, |client| client.update(update))
     }
 
     /// Download all records from a zone, or all records modified since given SOA was observed.
@@ -424,15 +447,155 @@ pub trait Client {
         name: &Name,
         last_soa: Option<SOA>,
     ) -> ClientResult<BlockingStream<ClientStreamXfr<<Self as Client>::Response>>> {
-        let (mut client, runtime) = self.spawn_client()?;
+        let mut client = connected_handle(self)?;
+        let runtime = shared_runtime()?;
+
+        let ixfr = last_soa.is_some();
+        let message = update_message::zone_transfer(name.clone(), last_soa);
+        let inner = {
+            let _guard = runtime.enter();
+            ClientStreamXfr::new(client.send(message), ixfr)
+        };
 
         Ok(BlockingStream {
-            inner: client.zone_transfer(name.clone(), last_soa),
+            inner: Some(inner),
             runtime,
+            timeout: self.connection().timeout,
         })
     }
 }
 
+/// The connection of a synchronous [`Client`]
+///
+/// The connection is established on the first request of the client, and reestablished on the
+///  next request after it was closed.
+pub struct SyncConnection<H> {
+    handle: Mutex<Option<(H, Arc<AtomicBool>)>>,
+    timeout: Option<Duration>,
+}
+
+impl<H> SyncConnection<H> {
+    /// The timeout for each request, `None` if the requests only time out in the connection
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Sets the timeout for each request, in addition to the timeout of the connection
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl<H> Default for SyncConnection<H> {
+    fn default() -> Self {
+        Self {
+            handle: Mutex::new(None),
+            timeout: None,
+        }
+    }
+}
+
+lazy_static! {
+    /// The runtime shared by all synchronous clients
+    static ref SHARED_RUNTIME: Result<runtime::Handle, String> =
+        spawn_runtime().map_err(|e| format!("failed to start the client runtime: {}", e));
+}
+
+/// Spawns a thread which drives a new runtime
+fn spawn_runtime() -> io::Result<runtime::Handle> {
+    let mut builder = runtime::Builder::new_current_thread();
+    builder.enable_all();
+
+    let runtime = builder.build()?;
+    let handle = runtime.handle().clone();
+
+    thread::Builder::new()
+        .name("trust-dns-client".to_string())
+        .spawn(move || runtime.block_on(std::future::pending::<()>()))?;
+
+    Ok(handle)
+}
+
+fn shared_runtime() -> ClientResult<runtime::Handle> {
+    SHARED_RUNTIME.clone().map_err(Into::into)
+}
+
+/// Runs the future on the runtime, and blocks until it completes or the timeout expires
+fn block_on<F>(
+    runtime: &runtime::Handle,
+    future: F,
+    timeout: Option<Duration>,
+) -> ClientResult<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let task = runtime.spawn(async move {
+        // the receiver is gone if the timeout expired
+        sender.send(future.await).ok();
+    });
+
+    let result = match timeout {
+        Some(timeout) => receiver.recv_timeout(timeout),
+        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+
+    result.map_err(|e| {
+        task.abort();
+        match e {
+            RecvTimeoutError::Timeout => ClientErrorKind::Timeout.into(),
+            RecvTimeoutError::Disconnected => "the request was cancelled".into(),
+        }
+    })
+}
+
+/// Returns the handle of the connection of the client, which is established if needed
+fn connected_handle<C: Client + ?Sized>(client: &C) -> ClientResult<C::Handle> {
+    let connection = client.connection();
+    let mut cached = connection.handle.lock().expect("connection lock poisoned");
+
+    if let Some((handle, open)) = &*cached {
+        if open.load(Ordering::Acquire) {
+            return Ok(handle.clone());
+        }
+    }
+
+    let runtime = shared_runtime()?;
+    let (handle, bg) = block_on(&runtime, client.new_future(), connection.timeout)??;
+
+    let open = Arc::new(AtomicBool::new(true));
+    let closed = Arc::clone(&open);
+    runtime.spawn(async move {
+        if let Err(e) = bg.await {
+            debug!("client connection failed: {}", e);
+        }
+        closed.store(false, Ordering::Release);
+    });
+
+    *cached = Some((handle.clone(), open));
+    Ok(handle)
+}
+
+/// Executes a request on the connection of the client
+fn execute<C, R, F, T>(client: &C, request: R) -> ClientResult<T>
+where
+    C: Client + ?Sized,
+    R: FnOnce(&mut C::Handle) -> F,
+    F: Future<Output = ClientResult<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut handle = connected_handle(client)?;
+    let runtime = shared_runtime()?;
+
+    let future = {
+        let _guard = runtime.enter();
+        request(&mut handle)
+    };
+
+    block_on(&runtime, future, client.connection().timeout)?
+}
+
 /// The Client is abstracted over either trust_dns_client::tcp::TcpClientConnection or
 ///  trust_dns_client::udp::UdpClientConnection.
 ///
@@ -441,6 +604,7 @@ pub trait Client {
 pub struct SyncClient<CC: ClientConnection> {
     conn: CC,
     signer: Option<Arc<Signer>>,
+    connection: SyncConnection<AsyncClient>,
 }
 
 impl<CC: ClientConnection> SyncClient<CC> {
@@ -450,7 +614,11 @@ impl<CC: ClientConnection> SyncClient<CC> {
     ///
     /// * `conn` - the [`ClientConnection`] to use for all communication
     pub fn new(conn: CC) -> Self {
-        Self { conn, signer: None }
+        Self {
+            conn,
+            signer: None,
+            connection: SyncConnection::default(),
+        }
     }
 
     /// Creates a new DNS client with the specified connection type and a SIG0 signer.
@@ -467,6 +635,7 @@ impl<CC: ClientConnection> SyncClient<CC> {
         Self {
             conn,
             signer: Some(Arc::new(signer.into())),
+            connection: SyncConnection::default(),
         }
     }
 
@@ -484,12 +653,37 @@ impl<CC: ClientConnection> SyncClient<CC> {
         Self {
             conn,
             signer: Some(Arc::new(signer.into())),
+            connection: SyncConnection::default(),
+        }
+    }
+
+    /// Creates a new DNS client with the specified connection type and GSS-TSIG signer.
+    ///
+    /// This is necessary for signed update requests to Active Directory integrated zones, the
+    ///  signer is obtained with [`GssTSigner::negotiate`] on a client without signer.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - the [`ClientConnection`] to use for all communication
+    /// * `signer` - signer to use
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn with_gss_tsigner(conn: CC, signer: GssTSigner) -> Self {
+        Self {
+            conn,
+            signer: Some(Arc::new(signer.into())),
+            connection: SyncConnection::default(),
         }
     }
+
+    /// Sets the timeout for each request, in addition to the timeout of the connection
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.connection.set_timeout(Some(timeout));
+    }
 }
 
 impl<CC: ClientConnection> Client for SyncClient<CC> {
-    type Response = DnsExchangeSend;
+    type Response = <AsyncClient as DnsHandle>::Response;
     type Handle = AsyncClient;
 
     fn new_future(&self) -> NewFutureObj<Self::Handle> {
@@ -504,23 +698,42 @@ impl<CC: ClientConnection> Client for SyncClient<CC> {
 
         Box::pin(connect)
     }
+
+    fn connection(&self) -> &SyncConnection<Self::Handle> {
+        &self.connection
+    }
 }
 
 /// An iterator based on a `Stream` of dns response.
 /// Calling `next` on this iterator is a blocking operation.
+///
+/// If the timeout of the client expires, the stream is cancelled and ends after the error.
 pub struct BlockingStream<T> {
-    inner: T,
-    runtime: Runtime,
+    inner: Option<T>,
+    runtime: runtime::Handle,
+    timeout: Option<Duration>,
 }
 
 impl<T, R> Iterator for BlockingStream<T>
 where
-    T: Stream<Item = R> + Unpin,
-    R: Into<ClientResult<DnsResponse>>,
+    T: Stream<Item = R> + Send + Unpin + 'static,
+    R: Into<ClientResult<DnsResponse>> + Send + 'static,
 {
     type Item = ClientResult<DnsResponse>;
     fn next(&mut self) -> Option<Self::Item> {
-        self.runtime.block_on(self.inner.next()).map(Into::into)
+        let mut inner = self.inner.take()?;
+        let next = async move {
+            let item = inner.next().await;
+            (inner, item)
+        };
+
+        match block_on(&self.runtime, next, self.timeout) {
+            Ok((inner, item)) => {
+                self.inner = Some(inner);
+                item.map(Into::into)
+            }
+            Err(e) => Some(Err(e)),
+        }
     }
 }
 
@@ -531,6 +744,7 @@ pub struct SyncDnssecClient<CC: ClientConnection> {
     conn: CC,
     signer: Option<Arc<Signer>>,
     trust_anchor: Option<TrustAnchor>,
+    connection: SyncConnection<AsyncDnssecClient>,
 }
 
 #[cfg(feature = "dnssec")]
@@ -546,8 +760,28 @@ impl<CC: ClientConnection> SyncDnssecClient<CC> {
             conn,
             trust_anchor: None,
             signer: None,
+            timeout: None,
         }
     }
+
+    /// A query, which returns all records of the response with the result of their validation,
+    ///  see [`AsyncDnssecClient::query_with_proof`]
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup
+    pub fn query_with_proof(
+        &self,
+        name: &Name,
+        query_class: DNSClass,
+        query_type: RecordType,
+    ) -> ClientResult<ProvenResponse> {
+        execute(self, |client| {
+            client.query_with_proof(name.clone(), query_class, query_type)
+        })
+    }
 }
 
 #[cfg(feature = "dnssec")]
@@ -573,6 +807,10 @@ impl<CC: ClientConnection> Client for SyncDnssecClient<CC> {
 
         Box::pin(connect)
     }
+
+    fn connection(&self) -> &SyncConnection<Self::Handle> {
+        &self.connection
+    }
 }
 
 #[cfg(feature = "dnssec")]
@@ -581,6 +819,7 @@ pub struct SecureSyncClientBuilder<CC: ClientConnection> {
     conn: CC,
     signer: Option<Arc<Signer>>,
     trust_anchor: Option<TrustAnchor>,
+    timeout: Option<Duration>,
 }
 
 #[cfg(feature = "dnssec")]
@@ -608,11 +847,21 @@ impl<CC: ClientConnection> SecureSyncClientBuilder<CC> {
         self
     }
 
+    /// Sets the timeout for each request, in addition to the timeout of the connection
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     pub fn build(self) -> SyncDnssecClient<CC> {
+        let mut connection = SyncConnection::default();
+        connection.set_timeout(self.timeout);
+
         SyncDnssecClient {
             conn: self.conn,
             signer: self.signer,
             trust_anchor: self.trust_anchor,
+            connection,
         }
     }
 }
@@ -636,3 +885,35 @@ fn test_secure_client_send_and_sync() {
     assert_send_and_sync::<SyncDnssecClient<UdpClientConnection>>();
     assert_send_and_sync::<SyncDnssecClient<TcpClientConnection>>();
 }
+
+#[test]
+fn test_sync_client_timeout_reuses_connection() {
+    use std::net::TcpListener;
+    use std::str::FromStr;
+
+    use crate::tcp::TcpClientConnection;
+
+    // accepts connections, but never responds
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (accepted, connections) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            accepted.send(stream.unwrap()).unwrap();
+        }
+    });
+
+    let mut client = SyncClient::new(TcpClientConnection::new(addr).unwrap());
+    client.set_timeout(Duration::from_millis(100));
+
+    let name = Name::from_str("www.example.com.").unwrap();
+    for _ in 0..2 {
+        let error = client
+            .query(&name, DNSClass::IN, RecordType::A)
+            .unwrap_err();
+        assert!(matches!(error.kind(), ClientErrorKind::Timeout));
+    }
+
+    connections.recv().unwrap();
+    assert!(connections.try_recv().is_err());
+}
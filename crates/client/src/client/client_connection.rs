@@ -21,6 +21,9 @@ use trust_dns_proto::{error::ProtoError, xfer::DnsRequestSender};
 use crate::op::{MessageFinalizer, MessageVerifier};
 #[cfg(feature = "dnssec")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+use crate::rr::dnssec::gss_tsig::GssTSigner;
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
 use crate::rr::dnssec::tsig::TSigner;
 #[cfg(feature = "dnssec")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
@@ -41,6 +44,10 @@ pub enum Signer {
     #[cfg(feature = "dnssec")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
     TSIG(TSigner),
+    /// A GSS-TSIG based signer
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    GssTSIG(GssTSigner),
 }
 
 #[cfg(feature = "dnssec")]
@@ -59,6 +66,14 @@ impl From<TSigner> for Signer {
     }
 }
 
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+impl From<GssTSigner> for Signer {
+    fn from(s: GssTSigner) -> Self {
+        Self::GssTSIG(s)
+    }
+}
+
 impl MessageFinalizer for Signer {
     #[allow(unreachable_patterns, unused_variables)]
     fn finalize_message(
@@ -73,6 +88,9 @@ impl MessageFinalizer for Signer {
             #[cfg(feature = "dnssec")]
             #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
             Self::TSIG(tsig) => tsig.finalize_message(message, time),
+            #[cfg(feature = "dnssec")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+            Self::GssTSIG(tsig) => tsig.finalize_message(message, time),
             _ => unreachable!("the feature `dnssec` is required for Message signing"),
         }
     }
@@ -0,0 +1,446 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A pool of client connections, which are reestablished when they fail
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::{BoxFuture, FutureExt, Shared, TryFutureExt};
+use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
+use tracing::{debug, warn};
+
+use crate::client::{AsyncClient, ClientConnection, Signer};
+use crate::proto::error::{ErrorCategory, ProtoError};
+use crate::proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+
+/// The default number of consecutive failed requests after which a connection is reestablished
+pub const DEFAULT_MAX_FAILURES: usize = 3;
+
+/// The health of a connection of an [`AsyncClientPool`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionHealth {
+    /// The connection is established
+    Connected {
+        /// The number of consecutive requests which failed with an IO error or a timeout
+        failures: usize,
+    },
+    /// The connection is being established
+    Connecting,
+    /// The connection is not established, it will be on the next request
+    Disconnected,
+}
+
+/// A pool of connections to one or more name servers
+///
+/// The connections are established lazily, or with [`AsyncClientPool::connect`]. A connection
+///  is reestablished when its background task ends, e.g. because the server closed the TCP
+///  connection, and after [`DEFAULT_MAX_FAILURES`] consecutive requests failed with an IO error
+///  or a timeout. Requests are distributed round-robin over the established connections.
+///
+/// The pool implements `ClientHandle`, it must be used within a Tokio runtime, to which the
+///  background tasks of the connections are spawned.
+#[derive(Clone)]
+pub struct AsyncClientPool {
+    slots: Arc<Vec<Arc<Slot>>>,
+    next: Arc<AtomicUsize>,
+    max_failures: usize,
+}
+
+impl AsyncClientPool {
+    /// Construct a new pool of `size` connections
+    ///
+    /// The connections are distributed over the `connections`, e.g. of different name servers.
+    ///
+    /// # Arguments
+    ///
+    /// * `connections` - the [`ClientConnection`]s to use, must not be empty
+    /// * `size` - the number of connections in the pool, must be greater than 0
+    /// * `signer` - an optional signer for requests, needed for Updates with Sig0 or TSIG
+    pub fn new<CC, I>(connections: I, size: usize, signer: Option<Arc<Signer>>) -> Self
+    where
+        CC: ClientConnection,
+        I: IntoIterator<Item = CC>,
+    {
+        let connections = connections
+            .into_iter()
+            .map(Arc::new)
+            .collect::<Vec<Arc<CC>>>();
+        assert!(!connections.is_empty(), "no connections for the pool");
+        assert!(size > 0, "the size of the pool must be greater than 0");
+
+        let slots = (0..size)
+            .map(|i| {
+                let conn = Arc::clone(&connections[i % connections.len()]);
+                let signer = signer.clone();
+                let connect: ConnectFn = Arc::new(move || {
+                    AsyncClient::connect(conn.new_stream(signer.clone()))
+                        .map_ok(|(client, bg)| (client, bg.boxed()))
+                        .boxed()
+                });
+
+                Arc::new(Slot {
+                    connect,
+                    inner: Mutex::new(SlotInner {
+                        generation: 0,
+                        state: SlotState::Disconnected,
+                    }),
+                })
+            })
+            .collect();
+
+        Self {
+            slots: Arc::new(slots),
+            next: Arc::new(AtomicUsize::new(0)),
+            max_failures: DEFAULT_MAX_FAILURES,
+        }
+    }
+
+    /// Sets the number of consecutive failed requests after which a connection is reestablished,
+    ///  the default is [`DEFAULT_MAX_FAILURES`]
+    pub fn with_max_failures(mut self, max_failures: usize) -> Self {
+        self.max_failures = max_failures.max(1);
+        self
+    }
+
+    /// Establish all connections which are not yet established
+    ///
+    /// Returns an error if none of the connections could be established.
+    pub async fn connect(&self) -> Result<(), ProtoError> {
+        let results =
+            futures_util::future::join_all(self.slots.iter().map(|slot| slot.client())).await;
+
+        let mut error = None;
+        for result in results {
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) => error = Some(e),
+            }
+        }
+
+        Err(error.expect("the pool is not empty"))
+    }
+
+    /// The health of each connection of the pool
+    pub fn health(&self) -> Vec<ConnectionHealth> {
+        self.slots.iter().map(|slot| slot.health()).collect()
+    }
+
+    /// The number of established connections
+    pub fn connected(&self) -> usize {
+        self.health()
+            .iter()
+            .filter(|health| matches!(health, ConnectionHealth::Connected { .. }))
+            .count()
+    }
+
+    /// Select the next established connection, round-robin
+    ///
+    /// If the next connection is not established, it is reestablished in the background and
+    ///  another one is used, if any.
+    fn select(&self) -> Arc<Slot> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.slots.len();
+        let next = &self.slots[start % len];
+
+        if next.health() == ConnectionHealth::Disconnected {
+            if let Some(slot) = (1..len)
+                .map(|i| &self.slots[(start + i) % len])
+                .find(|slot| matches!(slot.health(), ConnectionHealth::Connected { .. }))
+            {
+                tokio::spawn(next.client().map(drop));
+                return Arc::clone(slot);
+            }
+        }
+
+        Arc::clone(next)
+    }
+}
+
+impl DnsHandle for AsyncClientPool {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+    type Error = ProtoError;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, request: R) -> Self::Response {
+        let request = request.into();
+        let slot = self.select();
+        let max_failures = self.max_failures;
+
+        let send = async move {
+            let (mut client, generation) = slot.client().await?;
+            let responses = client
+                .send(request)
+                .inspect(move |response| slot.record(generation, response, max_failures));
+
+            Ok::<_, ProtoError>(responses)
+        };
+
+        Box::pin(stream::once(send).try_flatten())
+    }
+}
+
+type Background = BoxFuture<'static, Result<(), ProtoError>>;
+type ConnectFn = Arc<
+    dyn Fn() -> BoxFuture<'static, Result<(AsyncClient, Background), ProtoError>> + Send + Sync,
+>;
+type Connecting = Shared<BoxFuture<'static, Result<(AsyncClient, u64), ProtoError>>>;
+
+/// A connection of the pool
+struct Slot {
+    connect: ConnectFn,
+    inner: Mutex<SlotInner>,
+}
+
+struct SlotInner {
+    /// Incremented for each connection attempt, to ignore events of earlier connections
+    generation: u64,
+    state: SlotState,
+}
+
+enum SlotState {
+    Connected {
+        client: AsyncClient,
+        failures: usize,
+    },
+    Connecting(Connecting),
+    Disconnected,
+}
+
+impl Slot {
+    fn health(&self) -> ConnectionHealth {
+        match self.inner.lock().expect("slot lock poisoned").state {
+            SlotState::Connected { failures, .. } => ConnectionHealth::Connected { failures },
+            SlotState::Connecting(_) => ConnectionHealth::Connecting,
+            SlotState::Disconnected => ConnectionHealth::Disconnected,
+        }
+    }
+
+    /// The client of the established connection, or of the connection being established
+    fn client(self: &Arc<Self>) -> BoxFuture<'static, Result<(AsyncClient, u64), ProtoError>> {
+        let mut guard = self.inner.lock().expect("slot lock poisoned");
+        let inner = &mut *guard;
+        let generation = inner.generation;
+
+        let connecting = match inner.state {
+            SlotState::Connected { ref client, .. } => {
+                return futures_util::future::ok((client.clone(), generation)).boxed()
+            }
+            SlotState::Connecting(ref connecting) => connecting.clone(),
+            SlotState::Disconnected => {
+                let generation = generation + 1;
+                let slot = Arc::clone(self);
+                let connecting = (self.connect)()
+                    .map(move |result| slot.connected(generation, result))
+                    .boxed()
+                    .shared();
+
+                inner.generation = generation;
+                inner.state = SlotState::Connecting(connecting.clone());
+                connecting
+            }
+        };
+
+        connecting.boxed()
+    }
+
+    /// Store the result of a connection attempt, and spawn the background of the connection
+    fn connected(
+        self: Arc<Self>,
+        generation: u64,
+        result: Result<(AsyncClient, Background), ProtoError>,
+    ) -> Result<(AsyncClient, u64), ProtoError> {
+        let mut inner = self.inner.lock().expect("slot lock poisoned");
+        if inner.generation != generation {
+            return Err(ProtoError::from("connection was superseded"));
+        }
+
+        let (client, bg) = match result {
+            Ok(connected) => connected,
+            Err(e) => {
+                debug!("connection of the client pool failed: {}", e);
+                inner.state = SlotState::Disconnected;
+                return Err(e);
+            }
+        };
+
+        inner.state = SlotState::Connected {
+            client: client.clone(),
+            failures: 0,
+        };
+        drop(inner);
+
+        let slot = Arc::clone(&self);
+        tokio::spawn(async move {
+            if let Err(e) = bg.await {
+                warn!("connection of the client pool failed: {}", e);
+            }
+            slot.disconnected(generation);
+        });
+
+        Ok((client, generation))
+    }
+
+    /// The background of the connection ended
+    fn disconnected(&self, generation: u64) {
+        let mut inner = self.inner.lock().expect("slot lock poisoned");
+        if inner.generation == generation {
+            debug!("connection of the client pool ended");
+            inner.state = SlotState::Disconnected;
+        }
+    }
+
+    /// Record the response of a request, and reestablish the connection after too many failures
+    fn record(
+        &self,
+        generation: u64,
+        response: &Result<DnsResponse, ProtoError>,
+        max_failures: usize,
+    ) {
+        let mut guard = self.inner.lock().expect("slot lock poisoned");
+        let inner = &mut *guard;
+        if inner.generation != generation {
+            return;
+        }
+
+        let failed = if let SlotState::Connected {
+            ref mut failures, ..
+        } = inner.state
+        {
+            match response {
+                Ok(_) => *failures = 0,
+                Err(e) if matches!(e.category(), ErrorCategory::Io | ErrorCategory::Timeout) => {
+                    *failures += 1
+                }
+                Err(_) => (),
+            }
+
+            *failures >= max_failures
+        } else {
+            false
+        };
+
+        if failed {
+            debug!("too many failed requests, reconnecting");
+            inner.state = SlotState::Disconnected;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::str::FromStr;
+    use std::thread;
+
+    use super::*;
+    use crate::client::ClientHandle;
+    use crate::error::ClientError;
+    use crate::op::{Message, MessageType};
+    use crate::rr::{DNSClass, Name, RecordType};
+    use crate::tcp::TcpClientConnection;
+
+    /// A TCP server responding to all queries, which closes each connection after `per_connection` queries
+    fn server(per_connection: usize) -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        let count = Arc::clone(&accepted);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                count.fetch_add(1, Ordering::SeqCst);
+
+                thread::spawn(move || {
+                    for _ in 0..per_connection {
+                        let mut len = [0_u8; 2];
+                        if stream.read_exact(&mut len).is_err() {
+                            return;
+                        }
+                        let mut buffer = vec![0_u8; u16::from_be_bytes(len) as usize];
+                        stream.read_exact(&mut buffer).unwrap();
+
+                        let mut message = Message::from_vec(&buffer).unwrap();
+                        message.set_message_type(MessageType::Response);
+                        let buffer = message.to_vec().unwrap();
+                        stream
+                            .write_all(&(buffer.len() as u16).to_be_bytes())
+                            .unwrap();
+                        stream.write_all(&buffer).unwrap();
+                    }
+                });
+            }
+        });
+
+        (addr, accepted)
+    }
+
+    async fn query(pool: &mut AsyncClientPool) -> Result<DnsResponse, ClientError> {
+        pool.query(
+            Name::from_str("www.example.com.").unwrap(),
+            DNSClass::IN,
+            RecordType::A,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_pool_connect() {
+        let (addr, accepted) = server(usize::MAX);
+        let conn = TcpClientConnection::new(addr).unwrap();
+        let mut pool = AsyncClientPool::new(Some(conn), 2, None);
+        assert_eq!(pool.health(), vec![ConnectionHealth::Disconnected; 2]);
+
+        query(&mut pool).await.expect("query failed");
+        assert_eq!(pool.connected(), 1);
+
+        pool.connect().await.expect("connect failed");
+        assert_eq!(
+            pool.health(),
+            vec![ConnectionHealth::Connected { failures: 0 }; 2]
+        );
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pool_reconnect() {
+        let (addr, accepted) = server(1);
+        let conn = TcpClientConnection::new(addr).unwrap();
+        let mut pool = AsyncClientPool::new(Some(conn), 1, None).with_max_failures(1);
+
+        query(&mut pool).await.expect("query failed");
+
+        // the server closed the connection, the pool reconnects
+        let mut reconnected = false;
+        for _ in 0..10 {
+            if query(&mut pool).await.is_ok() {
+                reconnected = true;
+                break;
+            }
+        }
+
+        assert!(reconnected);
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pool_unreachable() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let conn = TcpClientConnection::new(addr).unwrap();
+        let mut pool = AsyncClientPool::new(Some(conn), 1, None);
+
+        query(&mut pool).await.unwrap_err();
+        assert_eq!(pool.health(), vec![ConnectionHealth::Disconnected]);
+        pool.connect().await.unwrap_err();
+    }
+}
@@ -23,8 +23,12 @@ pub(crate) mod async_secure_client;
 #[allow(clippy::module_inception)]
 mod client;
 pub mod client_connection;
+mod client_pool;
 mod memoize_client_handle;
+mod notify_listener;
 mod rc_stream;
+mod retry_policy;
+mod zone_transfer;
 
 #[allow(deprecated)]
 pub use self::async_client::{AsyncClient, ClientFuture, ClientHandle, ClientStreamingResponse};
@@ -38,4 +42,11 @@ pub use self::client::SyncDnssecClient;
 pub use self::client::{BlockingStream, Client, SyncClient};
 pub use self::client_connection::ClientConnection;
 pub use self::client_connection::Signer;
+pub use self::client_pool::{AsyncClientPool, ConnectionHealth, DEFAULT_MAX_FAILURES};
 pub use self::memoize_client_handle::MemoizeClientHandle;
+pub use self::notify_listener::{Notify, NotifyListener};
+pub use self::retry_policy::{RetryPolicy, DEFAULT_INITIAL_BACKOFF, DEFAULT_MAX_BACKOFF};
+pub use self::zone_transfer::{XfrBatch, ZoneTransfer, DEFAULT_XFR_ATTEMPTS};
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+pub use crate::proto::xfer::ProvenResponse;
@@ -0,0 +1,436 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A listener for NOTIFY messages, for acting as a secondary of a zone
+
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_channel::mpsc;
+use futures_util::stream::{Stream, StreamExt};
+use tokio::net;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::op::{Message, MessageType, OpCode};
+#[cfg(feature = "dnssec")]
+use crate::proto::error::ProtoError;
+use crate::proto::error::ProtoResult;
+use crate::proto::iocompat::AsyncIoTokioAsStd;
+use crate::proto::tcp::TcpStream;
+use crate::proto::udp::UdpStream;
+use crate::proto::xfer::{DnsStreamHandle, SerialMessage};
+#[cfg(feature = "dnssec")]
+use crate::rr::dnssec::tsig::TSigner;
+use crate::rr::rdata::SOA;
+use crate::rr::{DNSClass, Name, RData};
+use crate::serialize::binary::BinEncodable;
+
+/// A NOTIFY message which was received and accepted by a [`NotifyListener`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notify {
+    zone: Name,
+    dns_class: DNSClass,
+    soa: Option<SOA>,
+    source: SocketAddr,
+}
+
+impl Notify {
+    /// The zone which changed
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// The class of the zone
+    pub fn dns_class(&self) -> DNSClass {
+        self.dns_class
+    }
+
+    /// The new SOA of the zone, if the primary included it in the message
+    ///
+    /// ```text
+    /// RFC 1996                       DNS NOTIFY                    August 1996
+    ///
+    /// 3.7. A NOTIFY request has QDCOUNT>0, ANCOUNT>=0, AUCOUNT>=0,
+    ///    ADCOUNT>=0.  If ANCOUNT>0, then the answer section represents an
+    ///    unsecure hint at the new RRset for this <QNAME,QCLASS,QTYPE>.  A
+    ///    slave receiving such a hint is free to treat equivalence of this
+    ///    answer section with its local data as a "no further work needs to
+    ///    be done" indication.  If ANCOUNT=0, or ANCOUNT>0 and the answer
+    ///    section differs from the slave's local data, then the slave should
+    ///    query its known masters to retrieve the new data.
+    /// ```
+    pub fn soa(&self) -> Option<&SOA> {
+        self.soa.as_ref()
+    }
+
+    /// The address the message was received from
+    pub fn source(&self) -> SocketAddr {
+        self.source
+    }
+}
+
+/// Listens for NOTIFY messages from the primaries of zones, see [RFC 1996](https://tools.ietf.org/html/rfc1996)
+///
+/// The accepted messages are answered, and yielded by the `Stream` implementation, so that the
+///  zones can be transferred again, e.g. with [`ZoneTransfer`](crate::client::ZoneTransfer).
+///  Messages from sources which are not allowed, or which fail the TSIG validation, are
+///  dropped without an answer.
+///
+/// The sockets are served by tasks spawned on the tokio runtime, which are aborted when the
+///  listener is dropped.
+pub struct NotifyListener {
+    acl: Arc<NotifyAcl>,
+    sender: mpsc::UnboundedSender<Notify>,
+    receiver: mpsc::UnboundedReceiver<Notify>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+struct NotifyAcl {
+    sources: Vec<IpAddr>,
+    any_source: bool,
+    #[cfg(feature = "dnssec")]
+    signer: Option<TSigner>,
+}
+
+impl NotifyListener {
+    /// Creates a new listener, which doesn't accept messages from any source until they are
+    ///  allowed with `allow_source` or `allow_any_source`
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+
+        Self {
+            acl: Arc::new(NotifyAcl {
+                sources: Vec::new(),
+                any_source: false,
+                #[cfg(feature = "dnssec")]
+                signer: None,
+            }),
+            sender,
+            receiver,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Accept messages sent from `source`, usually the primary of the zones
+    ///
+    /// This must be called before any socket is registered.
+    pub fn allow_source(mut self, source: IpAddr) -> Self {
+        self.acl_mut().sources.push(source);
+        self
+    }
+
+    /// Accept messages from any source, this should only be used with a TSIG key
+    ///
+    /// This must be called before any socket is registered.
+    pub fn allow_any_source(mut self) -> Self {
+        self.acl_mut().any_source = true;
+        self
+    }
+
+    /// Only accept messages signed with the TSIG key of `signer`, the answers are signed with it
+    ///
+    /// This must be called before any socket is registered.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn with_tsigner(mut self, signer: TSigner) -> Self {
+        self.acl_mut().signer = Some(signer);
+        self
+    }
+
+    fn acl_mut(&mut self) -> &mut NotifyAcl {
+        Arc::get_mut(&mut self.acl)
+            .expect("the ACL must be configured before any socket is registered")
+    }
+
+    /// Register a UDP socket. Should be bound before calling this function.
+    pub fn register_socket(&mut self, socket: net::UdpSocket) {
+        debug!("registering notify udp: {:?}", socket);
+
+        // the remote address is replaced with the source of each message
+        let (mut buf_stream, stream_handle) =
+            UdpStream::with_bound(socket, ([127, 255, 255, 254], 0).into());
+        let acl = Arc::clone(&self.acl);
+        let sender = self.sender.clone();
+
+        self.tasks.push(tokio::spawn(async move {
+            while let Some(message) = buf_stream.next().await {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!("error receiving message on notify udp socket: {}", e);
+                        break;
+                    }
+                };
+
+                let src_addr = message.addr();
+                if let Some((response, notify)) = acl.handle(message.bytes(), src_addr) {
+                    let mut stream_handle = stream_handle.with_remote_addr(src_addr);
+                    if let Err(e) = stream_handle.send(SerialMessage::new(response, src_addr)) {
+                        warn!("error responding to notify from {}: {}", src_addr, e);
+                    }
+
+                    if sender.unbounded_send(notify).is_err() {
+                        break;
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Register a TcpListener. Should be bound before calling this function.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP socket
+    /// * `timeout` - connections which don't send a message within this period are closed
+    pub fn register_listener(&mut self, listener: net::TcpListener, timeout: Duration) {
+        debug!("registering notify tcp: {:?}", listener);
+
+        let acl = Arc::clone(&self.acl);
+        let sender = self.sender.clone();
+
+        self.tasks.push(tokio::spawn(async move {
+            loop {
+                let (tcp_stream, src_addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        debug!("error accepting notify tcp stream: {}", e);
+                        continue;
+                    }
+                };
+
+                if !acl.allows(src_addr.ip()) {
+                    debug!("refusing notify tcp connection from: {}", src_addr);
+                    continue;
+                }
+
+                let acl = Arc::clone(&acl);
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let (mut buf_stream, mut stream_handle) =
+                        TcpStream::from_stream(AsyncIoTokioAsStd(tcp_stream), src_addr);
+
+                    loop {
+                        let message = match tokio::time::timeout(timeout, buf_stream.next()).await {
+                            Ok(Some(Ok(message))) => message,
+                            Ok(Some(Err(e))) => {
+                                debug!("error in notify tcp stream from {}: {}", src_addr, e);
+                                return;
+                            }
+                            Ok(None) | Err(_) => return,
+                        };
+
+                        if let Some((response, notify)) = acl.handle(message.bytes(), src_addr) {
+                            if let Err(e) =
+                                stream_handle.send(SerialMessage::new(response, src_addr))
+                            {
+                                warn!("error responding to notify from {}: {}", src_addr, e);
+                                return;
+                            }
+
+                            if sender.unbounded_send(notify).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        }));
+    }
+}
+
+impl Default for NotifyListener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for NotifyListener {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Stream for NotifyListener {
+    type Item = Notify;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl NotifyAcl {
+    fn allows(&self, source: IpAddr) -> bool {
+        self.any_source || self.sources.contains(&source)
+    }
+
+    /// Validates the message, returning the serialized response and the notification to yield
+    fn handle(&self, bytes: &[u8], src_addr: SocketAddr) -> Option<(Vec<u8>, Notify)> {
+        if !self.allows(src_addr.ip()) {
+            debug!("dropping notify from disallowed source: {}", src_addr);
+            return None;
+        }
+
+        match self.accept(bytes, src_addr) {
+            Ok(accepted) => Some(accepted),
+            Err(e) => {
+                debug!("dropping notify from {}: {}", src_addr, e);
+                None
+            }
+        }
+    }
+
+    fn accept(&self, bytes: &[u8], src_addr: SocketAddr) -> ProtoResult<(Vec<u8>, Notify)> {
+        let request = Message::from_vec(bytes)?;
+        if request.message_type() != MessageType::Query || request.op_code() != OpCode::Notify {
+            return Err("not a notify request".into());
+        }
+
+        let query = match request.queries() {
+            [query] => query,
+            _ => return Err("notify must have a single query".into()),
+        };
+
+        #[cfg(feature = "dnssec")]
+        let request_mac = match self.signer {
+            Some(ref signer) => Some(Self::verify(signer, bytes)?),
+            None => None,
+        };
+
+        let soa = request
+            .answers()
+            .iter()
+            .filter(|record| record.name() == query.name())
+            .find_map(|record| match record.data() {
+                Some(RData::SOA(soa)) => Some(soa.clone()),
+                _ => None,
+            });
+
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(OpCode::Notify)
+            .set_authoritative(true)
+            .add_query(query.clone());
+
+        #[cfg(feature = "dnssec")]
+        if let (Some(signer), Some(request_mac)) = (&self.signer, request_mac) {
+            let tsig = signer.sign_response(&request_mac, &response, now()? as u32)?;
+            response.add_tsig(tsig);
+        }
+
+        let notify = Notify {
+            zone: query.name().clone(),
+            dns_class: query.query_class(),
+            soa,
+            source: src_addr,
+        };
+
+        Ok((response.to_bytes()?, notify))
+    }
+
+    #[cfg(feature = "dnssec")]
+    fn verify(signer: &TSigner, bytes: &[u8]) -> ProtoResult<Vec<u8>> {
+        let (mac, range, _) = signer.verify_message_byte(None, bytes, true)?;
+        if !range.contains(&now()?) {
+            return Err("tsig validation error: outdated request".into());
+        }
+
+        Ok(mac)
+    }
+}
+
+#[cfg(feature = "dnssec")]
+fn now() -> ProtoResult<u64> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .map_err(|_| ProtoError::from("current time is before the unix epoch"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::op::Query;
+    use crate::rr::RecordType;
+
+    fn notify_bytes(id: u16) -> Vec<u8> {
+        let mut query = Query::query(Name::from_ascii("example.com.").unwrap(), RecordType::SOA);
+        query.set_query_class(DNSClass::IN);
+
+        let mut message = Message::new();
+        message
+            .set_id(id)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Notify)
+            .add_query(query);
+        message.to_bytes().unwrap()
+    }
+
+    async fn send_notify(allowed: IpAddr) -> (NotifyListener, net::UdpSocket) {
+        let mut listener = NotifyListener::new().allow_source(allowed);
+        let socket = net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let addr = socket.local_addr().unwrap();
+        listener.register_socket(socket);
+
+        let client = net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        client.send_to(&notify_bytes(42), addr).await.unwrap();
+        (listener, client)
+    }
+
+    #[tokio::test]
+    async fn test_notify_udp() {
+        let (mut listener, client) = send_notify(Ipv4Addr::LOCALHOST.into()).await;
+
+        let notify = listener.next().await.expect("listener closed");
+        assert_eq!(notify.zone(), &Name::from_ascii("example.com.").unwrap());
+        assert_eq!(notify.dns_class(), DNSClass::IN);
+        assert_eq!(notify.source(), client.local_addr().unwrap());
+        assert!(notify.soa().is_none());
+
+        let mut buf = [0_u8; 512];
+        let (len, _) = tokio::time::timeout(Duration::from_secs(5), client.recv_from(&mut buf))
+            .await
+            .expect("response timed out")
+            .unwrap();
+        let response = Message::from_vec(&buf[..len]).unwrap();
+        assert_eq!(response.id(), 42);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert_eq!(response.op_code(), OpCode::Notify);
+    }
+
+    #[tokio::test]
+    async fn test_notify_disallowed_source() {
+        let (mut listener, client) = send_notify(Ipv4Addr::new(192, 0, 2, 1).into()).await;
+
+        let mut buf = [0_u8; 512];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), client.recv_from(&mut buf))
+                .await
+                .is_err()
+        );
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), listener.next())
+                .await
+                .is_err()
+        );
+    }
+}
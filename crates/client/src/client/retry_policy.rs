@@ -0,0 +1,130 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The policy for retransmitting requests of an `AsyncClient`
+
+use std::time::Duration;
+
+/// The default delay before the first retransmission of a request
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// The default maximum delay between retransmissions of a request
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// How the requests of an [`AsyncClient`](crate::client::AsyncClient) are retried
+///
+/// The default policy sends each request once, retries truncated responses over the TCP
+///  fallback connection, and fails over to the alternate servers, if the client has any, see
+///  [`AsyncClient::with_tcp_fallback`](crate::client::AsyncClient::with_tcp_fallback) and
+///  [`AsyncClient::with_alternate`](crate::client::AsyncClient::with_alternate).
+///
+/// Zone transfers are never retried, as their responses are streamed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    attempts: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    tcp_on_truncation: bool,
+    failover: bool,
+}
+
+impl RetryPolicy {
+    /// A policy which sends each request once, and doesn't fall back to TCP or alternate servers
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            tcp_on_truncation: false,
+            failover: false,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the number of times a request is sent to each server, if it fails with a retryable
+    ///  error, e.g. a timeout
+    pub fn with_attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retransmission, which doubles with each retransmission
+    ///  up to `max`
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Sets whether truncated responses are retried over the TCP fallback connection
+    pub fn with_tcp_on_truncation(mut self, tcp_on_truncation: bool) -> Self {
+        self.tcp_on_truncation = tcp_on_truncation;
+        self
+    }
+
+    /// Sets whether requests which failed are sent to the alternate servers
+    pub fn with_failover(mut self, failover: bool) -> Self {
+        self.failover = failover;
+        self
+    }
+
+    /// The number of times a request is sent to each server
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// The delay before the retransmission following `attempt`, counting from 0
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 1_u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+
+    /// True if truncated responses are retried over the TCP fallback connection
+    pub fn tcp_on_truncation(&self) -> bool {
+        self.tcp_on_truncation
+    }
+
+    /// True if requests which failed are sent to the alternate servers
+    pub fn failover(&self) -> bool {
+        self.failover
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            tcp_on_truncation: true,
+            failover: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default()
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(500));
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(64), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_attempts() {
+        assert_eq!(RetryPolicy::default().with_attempts(0).attempts(), 1);
+        assert_eq!(RetryPolicy::default().with_attempts(3).attempts(), 3);
+    }
+}
@@ -0,0 +1,659 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zone transfers (AXFR and IXFR) as a stream of typed record batches
+
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::ready;
+use futures_util::stream::{Stream, StreamExt};
+use tracing::debug;
+
+use crate::client::{ClientHandle, Signer};
+use crate::error::*;
+use crate::op::{update_message, MessageFinalizer};
+use crate::proto::error::ProtoError;
+use crate::proto::xfer::{DnsHandle, DnsRequest, DnsResponse};
+use crate::rr::rdata::SOA;
+use crate::rr::{Name, RData, Record};
+
+/// The default number of times a zone transfer is resumed after transient errors
+pub const DEFAULT_XFR_ATTEMPTS: usize = 2;
+
+/// A batch of records of a zone transfer
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum XfrBatch {
+    /// Records of a full zone transfer, in the order they were received
+    ///
+    /// There is one batch per response message. The first batch starts with the SOA of the zone,
+    ///  the closing SOA is not included. A full transfer may also be the response to an IXFR,
+    ///  in which case it replaces the zone.
+    Full(Vec<Record>),
+    /// One difference sequence of an incremental zone transfer, see RFC 1995
+    Incremental {
+        /// The SOA of the version of the zone the changes apply to
+        from: Record,
+        /// Records removed from the zone
+        deleted: Vec<Record>,
+        /// The SOA of the version of the zone after the changes
+        to: Record,
+        /// Records added to the zone
+        added: Vec<Record>,
+    },
+    /// The full zone transfer was interrupted and started again, all `Full` batches received
+    ///  before must be discarded
+    Restart,
+}
+
+/// A zone transfer, returned from [`ClientHandle::zone_transfer`]
+///
+/// The stream validates that the records are bracketed by the SOA of the zone, and that the
+///  difference sequences of an incremental transfer are contiguous. After transient errors,
+///  e.g. IO errors or timeouts, the request is sent again. Incremental transfers are resumed
+///  after the last complete difference sequence, full transfers start again after
+///  an [`XfrBatch::Restart`].
+#[must_use = "stream do nothing unless polled"]
+pub struct ZoneTransfer<H: ClientHandle> {
+    handle: H,
+    zone_origin: Name,
+    signer: Option<Arc<Signer>>,
+    request_serial: Option<u32>,
+    resume_serial: Option<u32>,
+    inner: <H as DnsHandle>::Response,
+    state: XfrState,
+    pending: VecDeque<XfrBatch>,
+    full_started: bool,
+    remaining_attempts: usize,
+}
+
+impl<H: ClientHandle> ZoneTransfer<H> {
+    pub(crate) fn new(
+        mut handle: H,
+        zone_origin: Name,
+        ixfr_serial: Option<u32>,
+        signer: Option<Arc<Signer>>,
+    ) -> Self {
+        let inner = send(&mut handle, &zone_origin, ixfr_serial, signer.as_ref());
+
+        Self {
+            handle,
+            zone_origin,
+            signer,
+            request_serial: ixfr_serial,
+            resume_serial: None,
+            inner,
+            state: XfrState::Start,
+            pending: VecDeque::new(),
+            full_started: false,
+            remaining_attempts: DEFAULT_XFR_ATTEMPTS,
+        }
+    }
+
+    /// Sets the number of times the transfer is resumed after transient errors, the default is
+    ///  [`DEFAULT_XFR_ATTEMPTS`]
+    pub fn with_attempts(mut self, attempts: usize) -> Self {
+        self.remaining_attempts = attempts;
+        self
+    }
+
+    /// Send the request again, after the last complete difference sequence if any
+    fn resume(&mut self) {
+        self.remaining_attempts -= 1;
+
+        if self.full_started {
+            self.pending.push_back(XfrBatch::Restart);
+            self.full_started = false;
+        }
+
+        let serial = self.resume_serial.or(self.request_serial);
+        self.inner = send(
+            &mut self.handle,
+            &self.zone_origin,
+            serial,
+            self.signer.as_ref(),
+        );
+        self.request_serial = serial;
+        self.state = XfrState::Start;
+    }
+
+    /// Collect the answers of a response into batches
+    fn process(&mut self, response: DnsResponse) -> ClientResult<()> {
+        let mut response = ProtoError::from_response(response, false)?;
+
+        let mut full = Vec::new();
+        for record in response.take_answers() {
+            let record_serial = soa_serial(&record);
+
+            self.state = match mem::replace(&mut self.state, XfrState::Ended) {
+                XfrState::Start => {
+                    if record_serial.is_none() {
+                        return Err(ClientErrorKind::Message(
+                            "invalid zone transfer, does not start with an SOA",
+                        )
+                        .into());
+                    }
+                    XfrState::Second { soa: record }
+                }
+                XfrState::Second { soa } => {
+                    let serial = soa_serial(&soa).expect("SOA of the zone transfer");
+                    match record_serial {
+                        // condensed response, the zone only contains the SOA
+                        Some(record_serial) if record_serial == serial => {
+                            full.push(soa);
+                            XfrState::Ended
+                        }
+                        Some(_) if self.request_serial.is_some() => XfrState::Deleting {
+                            serial,
+                            from: record,
+                            deleted: Vec::new(),
+                        },
+                        Some(_) => {
+                            return Err(ClientErrorKind::Message(
+                                "invalid zone transfer, expected AXFR, got IXFR",
+                            )
+                            .into())
+                        }
+                        None => {
+                            full.push(soa);
+                            full.push(record);
+                            XfrState::Full { serial }
+                        }
+                    }
+                }
+                XfrState::Full { serial } => match record_serial {
+                    Some(record_serial) if record_serial == serial => XfrState::Ended,
+                    Some(_) => {
+                        return Err(ClientErrorKind::Message(
+                            "invalid zone transfer, closing SOA does not match",
+                        )
+                        .into())
+                    }
+                    None => {
+                        full.push(record);
+                        XfrState::Full { serial }
+                    }
+                },
+                XfrState::Deleting {
+                    serial,
+                    from,
+                    mut deleted,
+                } => {
+                    if record_serial.is_some() {
+                        XfrState::Adding {
+                            serial,
+                            from,
+                            deleted,
+                            to: record,
+                            added: Vec::new(),
+                        }
+                    } else {
+                        deleted.push(record);
+                        XfrState::Deleting {
+                            serial,
+                            from,
+                            deleted,
+                        }
+                    }
+                }
+                XfrState::Adding {
+                    serial,
+                    from,
+                    deleted,
+                    to,
+                    mut added,
+                } => {
+                    if let Some(record_serial) = record_serial {
+                        let to_serial = soa_serial(&to).expect("SOA of the difference sequence");
+                        self.pending.push_back(XfrBatch::Incremental {
+                            from,
+                            deleted,
+                            to,
+                            added,
+                        });
+                        self.resume_serial = Some(to_serial);
+
+                        if to_serial == serial && record_serial == serial {
+                            XfrState::Ended
+                        } else if record_serial == to_serial {
+                            XfrState::Deleting {
+                                serial,
+                                from: record,
+                                deleted: Vec::new(),
+                            }
+                        } else {
+                            return Err(ClientErrorKind::Message(
+                                "invalid zone transfer, difference sequences are not contiguous",
+                            )
+                            .into());
+                        }
+                    } else {
+                        added.push(record);
+                        XfrState::Adding {
+                            serial,
+                            from,
+                            deleted,
+                            to,
+                            added,
+                        }
+                    }
+                }
+                XfrState::Ended => {
+                    return Err(ClientErrorKind::Message(
+                        "invalid zone transfer, contains trailing records",
+                    )
+                    .into())
+                }
+            };
+        }
+
+        if !full.is_empty() {
+            self.pending.push_back(XfrBatch::Full(full));
+            self.full_started = true;
+        }
+
+        // a single SOA with the requested serial, the zone is up to date
+        if let XfrState::Second { ref soa } = self.state {
+            if self.request_serial.is_some() && soa_serial(soa) == self.request_serial {
+                self.state = XfrState::Ended;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: ClientHandle> Stream for ZoneTransfer<H> {
+    type Item = ClientResult<XfrBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(batch) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(batch)));
+            }
+
+            if matches!(self.state, XfrState::Ended) {
+                return Poll::Ready(None);
+            }
+
+            let error = match ready!(self.inner.poll_next_unpin(cx)) {
+                Some(Ok(response)) => match self.process(response) {
+                    Ok(()) => continue,
+                    Err(e) => {
+                        self.state = XfrState::Ended;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                },
+                Some(Err(e)) => e,
+                None => ProtoError::from(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "zone transfer ended before the closing SOA",
+                )),
+            };
+
+            if self.remaining_attempts > 0 && error.category().is_retryable() {
+                debug!(
+                    "resuming zone transfer of {} after error: {}",
+                    self.zone_origin, error
+                );
+                self.resume();
+            } else {
+                self.state = XfrState::Ended;
+                return Poll::Ready(Some(Err(error.into())));
+            }
+        }
+    }
+}
+
+/// State of the zone transfer, at the granularity of records
+#[derive(Debug)]
+enum XfrState {
+    /// Awaiting the SOA of the zone
+    Start,
+    /// The next record determines if this is a full or an incremental transfer
+    Second { soa: Record },
+    /// Collecting the records of a full transfer, until the closing SOA with `serial`
+    Full { serial: u32 },
+    /// Collecting the deleted records of a difference sequence
+    Deleting {
+        serial: u32,
+        from: Record,
+        deleted: Vec<Record>,
+    },
+    /// Collecting the added records of a difference sequence
+    Adding {
+        serial: u32,
+        from: Record,
+        deleted: Vec<Record>,
+        to: Record,
+        added: Vec<Record>,
+    },
+    /// The closing SOA was received
+    Ended,
+}
+
+fn soa_serial(record: &Record) -> Option<u32> {
+    record.data().and_then(RData::as_soa).map(SOA::serial)
+}
+
+fn send<H: ClientHandle>(
+    handle: &mut H,
+    zone_origin: &Name,
+    ixfr_serial: Option<u32>,
+    signer: Option<&Arc<Signer>>,
+) -> <H as DnsHandle>::Response {
+    // only the serial of the SOA is used by the server
+    let last_soa =
+        ixfr_serial.map(|serial| SOA::new(zone_origin.clone(), Name::root(), serial, 0, 0, 0, 0));
+    let mut request =
+        DnsRequest::from(update_message::zone_transfer(zone_origin.clone(), last_soa));
+
+    if let Some(signer) = signer {
+        request.set_finalizer(Arc::clone(signer) as Arc<dyn MessageFinalizer>);
+    }
+
+    handle.send(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures_util::stream;
+
+    use super::*;
+    use crate::op::Message;
+    use crate::proto::error::ProtoErrorKind;
+    use crate::rr::RecordType;
+
+    type Responses = Vec<Result<Vec<Record>, ProtoError>>;
+
+    #[derive(Clone, Default)]
+    struct TestClient {
+        responses: Arc<Mutex<VecDeque<Responses>>>,
+        requests: Arc<Mutex<Vec<DnsRequest>>>,
+    }
+
+    impl TestClient {
+        fn new(responses: Vec<Responses>) -> Self {
+            Self {
+                responses: Arc::new(Mutex::new(responses.into())),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl DnsHandle for TestClient {
+        type Response = Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin>;
+        type Error = ProtoError;
+
+        fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+            let request = request.into();
+            let queries = request.queries().to_vec();
+            self.requests.lock().unwrap().push(request);
+            let responses = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_default();
+
+            Box::new(stream::iter(responses.into_iter().map(move |r| {
+                r.map(|answers| {
+                    let mut message = Message::new();
+                    message.add_queries(queries.clone());
+                    message.insert_answers(answers);
+                    message.into()
+                })
+            })))
+        }
+    }
+
+    fn soa_record(serial: u32) -> Record {
+        let soa = RData::SOA(SOA::new(
+            Name::from_ascii("example.com.").unwrap(),
+            Name::from_ascii("admin.example.com.").unwrap(),
+            serial,
+            60,
+            60,
+            60,
+            60,
+        ));
+        Record::from_rdata(Name::from_ascii("example.com.").unwrap(), 600, soa)
+    }
+
+    fn a_record(ip: u32) -> Record {
+        let a = RData::A(ip.into());
+        Record::from_rdata(Name::from_ascii("www.example.com.").unwrap(), 600, a)
+    }
+
+    fn request_serial(request: &DnsRequest) -> Option<u32> {
+        request.name_servers().first().and_then(soa_serial)
+    }
+
+    #[tokio::test]
+    async fn test_axfr_batches() {
+        let client = TestClient::new(vec![vec![
+            Ok(vec![soa_record(3), a_record(1)]),
+            Ok(vec![a_record(2), soa_record(3)]),
+        ]]);
+        let mut xfr =
+            client
+                .clone()
+                .zone_transfer(Name::from_ascii("example.com.").unwrap(), None, None);
+
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Full(vec![soa_record(3), a_record(1)])
+        );
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Full(vec![a_record(2)])
+        );
+        assert!(xfr.next().await.is_none());
+
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].queries()[0].query_type(), RecordType::AXFR);
+    }
+
+    #[tokio::test]
+    async fn test_ixfr_batches() {
+        let client = TestClient::new(vec![vec![Ok(vec![
+            soa_record(3),
+            soa_record(1),
+            a_record(1),
+            soa_record(2),
+            a_record(2),
+            soa_record(2),
+            soa_record(3),
+            a_record(3),
+            soa_record(3),
+        ])]]);
+        let mut xfr =
+            client
+                .clone()
+                .zone_transfer(Name::from_ascii("example.com.").unwrap(), Some(1), None);
+
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Incremental {
+                from: soa_record(1),
+                deleted: vec![a_record(1)],
+                to: soa_record(2),
+                added: vec![a_record(2)],
+            }
+        );
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Incremental {
+                from: soa_record(2),
+                deleted: vec![],
+                to: soa_record(3),
+                added: vec![a_record(3)],
+            }
+        );
+        assert!(xfr.next().await.is_none());
+
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests[0].queries()[0].query_type(), RecordType::IXFR);
+        assert_eq!(request_serial(&requests[0]), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_ixfr_up_to_date() {
+        let mut client = TestClient::new(vec![vec![Ok(vec![soa_record(3)])]]);
+        let mut xfr =
+            client.zone_transfer(Name::from_ascii("example.com.").unwrap(), Some(3), None);
+
+        assert!(xfr.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_axfr_with_ixfr_reply() {
+        let mut client = TestClient::new(vec![vec![Ok(vec![
+            soa_record(3),
+            soa_record(2),
+            a_record(1),
+            soa_record(3),
+            soa_record(3),
+        ])]]);
+        let mut xfr = client.zone_transfer(Name::from_ascii("example.com.").unwrap(), None, None);
+
+        xfr.next().await.unwrap().unwrap_err();
+        assert!(xfr.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_trailing_records() {
+        let mut client = TestClient::new(vec![vec![Ok(vec![
+            soa_record(3),
+            a_record(1),
+            soa_record(3),
+            a_record(2),
+        ])]]);
+        let mut xfr = client.zone_transfer(Name::from_ascii("example.com.").unwrap(), None, None);
+
+        xfr.next().await.unwrap().unwrap_err();
+        assert!(xfr.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_ixfr() {
+        let client = TestClient::new(vec![
+            vec![
+                Ok(vec![
+                    soa_record(3),
+                    soa_record(1),
+                    a_record(1),
+                    soa_record(2),
+                    a_record(2),
+                    soa_record(2),
+                ]),
+                Err(ProtoErrorKind::Timeout.into()),
+            ],
+            vec![Ok(vec![
+                soa_record(3),
+                soa_record(2),
+                soa_record(3),
+                a_record(3),
+                soa_record(3),
+            ])],
+        ]);
+        let mut xfr =
+            client
+                .clone()
+                .zone_transfer(Name::from_ascii("example.com.").unwrap(), Some(1), None);
+
+        assert!(matches!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Incremental { .. }
+        ));
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Incremental {
+                from: soa_record(2),
+                deleted: vec![],
+                to: soa_record(3),
+                added: vec![a_record(3)],
+            }
+        );
+        assert!(xfr.next().await.is_none());
+
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(request_serial(&requests[1]), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_restart_axfr() {
+        let mut client = TestClient::new(vec![
+            vec![Ok(vec![soa_record(3), a_record(1)])],
+            vec![Ok(vec![
+                soa_record(3),
+                a_record(1),
+                a_record(2),
+                soa_record(3),
+            ])],
+        ]);
+        let mut xfr = client.zone_transfer(Name::from_ascii("example.com.").unwrap(), None, None);
+
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Full(vec![soa_record(3), a_record(1)])
+        );
+        assert_eq!(xfr.next().await.unwrap().unwrap(), XfrBatch::Restart);
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Full(vec![soa_record(3), a_record(1), a_record(2)])
+        );
+        assert!(xfr.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_attempts_exhausted() {
+        let mut client = TestClient::new(vec![vec![Err(ProtoErrorKind::Timeout.into())]]);
+        let mut xfr = client
+            .zone_transfer(Name::from_ascii("example.com.").unwrap(), None, None)
+            .with_attempts(0);
+
+        xfr.next().await.unwrap().unwrap_err();
+        assert!(xfr.next().await.is_none());
+    }
+
+    #[cfg(any(feature = "dnssec-ring", feature = "dnssec-openssl"))]
+    #[tokio::test]
+    async fn test_signed_request() {
+        use crate::proto::rr::dnssec::rdata::tsig::TsigAlgorithm;
+        use crate::rr::dnssec::tsig::TSigner;
+
+        let signer = TSigner::new(
+            b"some_key".to_vec(),
+            TsigAlgorithm::HmacSha512,
+            Name::from_ascii("tsig.example.com.").unwrap(),
+            300,
+        )
+        .unwrap();
+        let client = TestClient::new(vec![vec![Ok(vec![soa_record(3), soa_record(3)])]]);
+        let mut xfr = client.clone().zone_transfer(
+            Name::from_ascii("example.com.").unwrap(),
+            None,
+            Some(Arc::new(signer.into())),
+        );
+
+        assert_eq!(
+            xfr.next().await.unwrap().unwrap(),
+            XfrBatch::Full(vec![soa_record(3)])
+        );
+        assert!(client.requests.lock().unwrap()[0].finalizer().is_some());
+    }
+}
@@ -11,7 +11,7 @@ use std::{fmt, io};
 
 use futures_channel::mpsc;
 use thiserror::Error;
-use trust_dns_proto::error::{ProtoError, ProtoErrorKind};
+use trust_dns_proto::error::{ErrorCategory, ProtoError, ProtoErrorKind};
 
 use crate::error::{DnsSecError, DnsSecErrorKind};
 #[cfg(feature = "backtrace")]
@@ -54,6 +54,20 @@ pub enum ErrorKind {
     Timeout,
 }
 
+impl ErrorKind {
+    /// The broad category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Message(_) | Self::Msg(_) => ErrorCategory::Other,
+            Self::DnsSec(e) => e.category(),
+            Self::Io(_) => ErrorCategory::Io,
+            Self::Proto(e) => e.category(),
+            Self::SendError(_) => ErrorCategory::Busy,
+            Self::Timeout => ErrorCategory::Timeout,
+        }
+    }
+}
+
 impl Clone for ErrorKind {
     fn clone(&self) -> Self {
         use self::ErrorKind::*;
@@ -83,6 +97,11 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+
+    /// The broad category of the error
+    pub fn category(&self) -> ErrorCategory {
+        self.kind.category()
+    }
 }
 
 impl fmt::Display for Error {
@@ -176,4 +195,5 @@ fn test_conversion() {
         ErrorKind::Timeout => (),
         _ => panic!("incorrect type: {}", error),
     }
+    assert_eq!(error.category(), ErrorCategory::Timeout);
 }
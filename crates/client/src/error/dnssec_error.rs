@@ -18,7 +18,7 @@ use openssl::error::ErrorStack as SslErrorStack;
 #[cfg(feature = "ring")]
 use ring::error::{KeyRejected, Unspecified};
 use thiserror::Error;
-use trust_dns_proto::error::{ProtoError, ProtoErrorKind};
+use trust_dns_proto::error::{ErrorCategory, ProtoError, ProtoErrorKind};
 
 #[cfg(feature = "backtrace")]
 use crate::proto::{trace, ExtBacktrace};
@@ -61,6 +61,18 @@ pub enum ErrorKind {
     Timeout,
 }
 
+impl ErrorKind {
+    /// The broad category of this error, all errors other than timeouts and those from the
+    ///  trust-dns-proto crate are DNSSEC errors
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Proto(e) => e.category(),
+            Self::Timeout => ErrorCategory::Timeout,
+            _ => ErrorCategory::Dnssec,
+        }
+    }
+}
+
 impl Clone for ErrorKind {
     fn clone(&self) -> Self {
         use self::ErrorKind::*;
@@ -91,6 +103,11 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+
+    /// The broad category of the error
+    pub fn category(&self) -> ErrorCategory {
+        self.kind.category()
+    }
 }
 
 impl fmt::Display for Error {
@@ -12,7 +12,7 @@ mod lower_query;
 pub mod update_message;
 
 pub use self::lower_query::LowerQuery;
-pub use self::update_message::UpdateMessage;
+pub use self::update_message::{UpdateBuilder, UpdateMessage};
 pub use crate::proto::{
     op::{
         Edns, Header, Message, MessageFinalizer, MessageType, MessageVerifier, OpCode, Query,
@@ -621,3 +621,252 @@ pub fn zone_transfer(zone_origin: Name, last_soa: Option<SOA>) -> Message {
 
     message
 }
+
+/// A builder for an UPDATE message with any prerequisites and updates
+///
+/// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+///
+/// The prerequisites all must be satisfied for the server to apply the updates, which are
+///  applied in the order they were added. Unlike the functions in this module, this allows the
+///  composition of e.g. multiple prerequisites with multiple additions and deletions in a
+///  single, atomic (depending on the server) UPDATE.
+///
+/// All names must be in the zone, the methods panic otherwise.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use trust_dns_client::op::UpdateBuilder;
+/// use trust_dns_client::rr::{Name, RData, Record, RecordType};
+///
+/// let zone = Name::from_str("example.com.").unwrap();
+/// let www = Name::from_str("www.example.com.").unwrap();
+///
+/// let message = UpdateBuilder::new(zone)
+///     .name_in_use(www.clone())
+///     .rrset_not_exists(www.clone(), RecordType::AAAA)
+///     .delete_rrset(www.clone(), RecordType::A)
+///     .add(Some(Record::from_rdata(www, 300, RData::A([192, 0, 2, 1].into()))))
+///     .build();
+///
+/// assert_eq!(message.answers().len(), 2);
+/// assert_eq!(message.name_servers().len(), 2);
+/// ```
+#[derive(Clone, Debug)]
+#[must_use = "the message must be built and sent"]
+pub struct UpdateBuilder {
+    zone_origin: Name,
+    dns_class: DNSClass,
+    prerequisites: Vec<Record>,
+    updates: Vec<Record>,
+    use_edns: bool,
+}
+
+impl UpdateBuilder {
+    /// Construct a new UPDATE for the zone, of class `IN` and with EDNS
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_origin` - the zone name to update, i.e. SOA name
+    pub fn new(zone_origin: Name) -> Self {
+        Self {
+            zone_origin,
+            dns_class: DNSClass::IN,
+            prerequisites: Vec::new(),
+            updates: Vec::new(),
+            use_edns: true,
+        }
+    }
+
+    /// The class of the zone, defaults to `IN`
+    ///
+    /// This must be set before any records are added, it is used for the class of the records
+    ///  of the value dependent prerequisites and of additions.
+    pub fn dns_class(mut self, dns_class: DNSClass) -> Self {
+        self.dns_class = dns_class;
+        self
+    }
+
+    /// Use EDNS for the message, defaults to `true`
+    pub fn use_edns(mut self, use_edns: bool) -> Self {
+        self.use_edns = use_edns;
+        self
+    }
+
+    /// Require at least one record of the type to exist at the name, section 2.4.1
+    pub fn rrset_exists(self, name: Name, record_type: RecordType) -> Self {
+        self.prerequisite(name, record_type, DNSClass::ANY)
+    }
+
+    /// Require the rrset to exist with exactly these records, section 2.4.2
+    ///
+    /// The TTL of the records is ignored.
+    pub fn rrset_matches<R>(mut self, records: R) -> Self
+    where
+        R: IntoIterator<Item = Record>,
+    {
+        for mut record in records {
+            assert!(self.zone_origin.zone_of(record.name()));
+            record.set_dns_class(self.dns_class);
+            record.set_ttl(0);
+            self.prerequisites.push(record);
+        }
+        self
+    }
+
+    /// Require no record of the type to exist at the name, section 2.4.3
+    pub fn rrset_not_exists(self, name: Name, record_type: RecordType) -> Self {
+        self.prerequisite(name, record_type, DNSClass::NONE)
+    }
+
+    /// Require at least one record of any type to exist at the name, section 2.4.4
+    pub fn name_in_use(self, name: Name) -> Self {
+        self.prerequisite(name, RecordType::ANY, DNSClass::ANY)
+    }
+
+    /// Require no record of any type to exist at the name, section 2.4.5
+    pub fn name_not_in_use(self, name: Name) -> Self {
+        self.prerequisite(name, RecordType::ANY, DNSClass::NONE)
+    }
+
+    /// Add the records to their rrsets, section 2.5.1
+    pub fn add<R>(mut self, records: R) -> Self
+    where
+        R: IntoIterator<Item = Record>,
+    {
+        for mut record in records {
+            assert!(self.zone_origin.zone_of(record.name()));
+            record.set_dns_class(self.dns_class);
+            self.updates.push(record);
+        }
+        self
+    }
+
+    /// Delete the entire rrset of the type at the name, section 2.5.2
+    pub fn delete_rrset(self, name: Name, record_type: RecordType) -> Self {
+        self.update(name, record_type, DNSClass::ANY)
+    }
+
+    /// Delete all rrsets at the name, section 2.5.3
+    pub fn delete_all(self, name: Name) -> Self {
+        self.update(name, RecordType::ANY, DNSClass::ANY)
+    }
+
+    /// Delete the records from their rrsets, the name, type and rdata must match, section 2.5.4
+    pub fn delete_records<R>(mut self, records: R) -> Self
+    where
+        R: IntoIterator<Item = Record>,
+    {
+        for mut record in records {
+            assert!(self.zone_origin.zone_of(record.name()));
+            record.set_dns_class(DNSClass::NONE);
+            record.set_ttl(0);
+            self.updates.push(record);
+        }
+        self
+    }
+
+    /// Build the UPDATE message
+    pub fn build(self) -> Message {
+        // for updates, the query section is used for the zone
+        let mut zone: Query = Query::new();
+        zone.set_name(self.zone_origin)
+            .set_query_class(self.dns_class)
+            .set_query_type(RecordType::SOA);
+
+        // build the message
+        let mut message: Message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Update)
+            .set_recursion_desired(false);
+        message.add_zone(zone);
+        message.add_pre_requisites(self.prerequisites);
+        message.add_updates(self.updates);
+
+        // Extended dns
+        if self.use_edns {
+            message
+                .extensions_mut()
+                .get_or_insert_with(Edns::new)
+                .set_max_payload(MAX_PAYLOAD_LEN)
+                .set_version(0);
+        }
+
+        message
+    }
+
+    /// A record without rdata and a TTL of 0 in the prerequisite section
+    fn prerequisite(mut self, name: Name, record_type: RecordType, dns_class: DNSClass) -> Self {
+        assert!(self.zone_origin.zone_of(&name));
+        let mut record = Record::with(name, record_type, 0);
+        record.set_dns_class(dns_class);
+        self.prerequisites.push(record);
+        self
+    }
+
+    /// A record without rdata and a TTL of 0 in the update section
+    fn update(mut self, name: Name, record_type: RecordType, dns_class: DNSClass) -> Self {
+        assert!(self.zone_origin.zone_of(&name));
+        let mut record = Record::with(name, record_type, 0);
+        record.set_dns_class(dns_class);
+        self.updates.push(record);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_update_builder() {
+        let zone = Name::from_str("example.com.").unwrap();
+        let www = Name::from_str("www.example.com.").unwrap();
+        let a = Record::from_rdata(www.clone(), 300, RData::A([192, 0, 2, 1].into()));
+
+        let message = UpdateBuilder::new(zone.clone())
+            .dns_class(DNSClass::CH)
+            .rrset_exists(www.clone(), RecordType::A)
+            .rrset_matches(Some(a.clone()))
+            .name_not_in_use(Name::from_str("new.example.com.").unwrap())
+            .delete_records(Some(a.clone()))
+            .add(Some(a))
+            .delete_all(www)
+            .use_edns(false)
+            .build();
+
+        assert_eq!(message.op_code(), OpCode::Update);
+        assert_eq!(message.zones()[0].name(), &zone);
+        assert_eq!(message.zones()[0].query_class(), DNSClass::CH);
+        assert!(message.extensions().is_none());
+
+        let prerequisites = message.prerequisites();
+        assert_eq!(prerequisites.len(), 3);
+        assert_eq!(prerequisites[0].dns_class(), DNSClass::ANY);
+        assert!(prerequisites[0].data().is_none());
+        assert_eq!(prerequisites[1].dns_class(), DNSClass::CH);
+        assert_eq!(prerequisites[1].ttl(), 0);
+        assert_eq!(prerequisites[2].dns_class(), DNSClass::NONE);
+        assert_eq!(prerequisites[2].rr_type(), RecordType::ANY);
+
+        let updates = message.updates();
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].dns_class(), DNSClass::NONE);
+        assert_eq!(updates[0].ttl(), 0);
+        assert_eq!(updates[1].dns_class(), DNSClass::CH);
+        assert_eq!(updates[1].ttl(), 300);
+        assert_eq!(updates[2].dns_class(), DNSClass::ANY);
+        assert_eq!(updates[2].rr_type(), RecordType::ANY);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_update_builder_out_of_zone() {
+        let _ = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+            .delete_all(Name::from_str("www.example.net.").unwrap());
+    }
+}
@@ -0,0 +1,478 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! GSS-TSIG, [RFC 3645](https://tools.ietf.org/html/rfc3645), authentication of dns transactions
+//!  with a security context established over GSS-API, e.g. Kerberos against Active Directory
+//!
+//! The GSS-API mechanism itself is not implemented here, it is provided by the caller through the
+//!  [`GssContext`] trait, usually by wrapping a system GSS-API or SSPI library.
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+
+use crate::proto::error::{ProtoError, ProtoResult};
+use crate::proto::rr::dnssec::rdata::tkey::{TkeyMode, TKEY};
+use crate::proto::rr::dnssec::rdata::tsig::{
+    make_tsig_record, message_tbs, signed_bitmessage_to_buf, TsigAlgorithm, TSIG,
+};
+use crate::proto::rr::dnssec::rdata::DNSSECRData;
+use crate::proto::rr::dnssec::tkey::{tkey_query, tkey_response};
+use crate::proto::xfer::{DnsHandle, FirstAnswer};
+
+use crate::op::{DnsResponse, Message, MessageFinalizer, MessageVerifier};
+use crate::rr::{Name, RData, Record};
+
+/// A GSS-API security context, as used by GSS-TSIG
+///
+/// Implementations wrap the platform GSS-API (or SSPI on Windows) of the client, the security
+///  context is negotiated with the server by [`GssTSigner::negotiate`] and then used to sign and
+///  verify messages.
+pub trait GssContext: Send + Sync + 'static {
+    /// Advance the context negotiation, `GSS_Init_sec_context` in GSS-API terms
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - the token received from the server, `None` on the first call
+    ///
+    /// # Returns
+    ///
+    /// The token to send to the server, if any
+    fn step(&mut self, input: Option<&[u8]>) -> ProtoResult<Option<Vec<u8>>>;
+
+    /// Returns true once the context is fully established and can be used for signing
+    fn is_complete(&self) -> bool;
+
+    /// Compute the message integrity code for a buffer, `GSS_GetMIC` in GSS-API terms
+    fn get_mic(&self, message: &[u8]) -> ProtoResult<Vec<u8>>;
+
+    /// Verify the message integrity code of a buffer, `GSS_VerifyMIC` in GSS-API terms
+    fn verify_mic(&self, message: &[u8], mic: &[u8]) -> ProtoResult<()>;
+}
+
+/// Struct to pass to a client for it to authenticate requests using GSS-TSIG.
+#[derive(Clone)]
+pub struct GssTSigner(Arc<GssTSignerInner>);
+
+struct GssTSignerInner {
+    context: Box<dyn GssContext>,
+    signer_name: Name,
+    fudge: u16,
+}
+
+impl GssTSigner {
+    /// Create a new GssTSigner from an established security context
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - the established GSS-API security context
+    /// * `signer_name` - name of the key, as used during the TKEY negotiation
+    /// * `fudge` - maximum difference between client and server time, in seconds
+    pub fn new<C: GssContext>(context: C, signer_name: Name, fudge: u16) -> ProtoResult<Self> {
+        if context.is_complete() {
+            Ok(Self(Arc::new(GssTSignerInner {
+                context: Box::new(context),
+                signer_name,
+                fudge,
+            })))
+        } else {
+            Err(ProtoError::from(
+                "GSS-API security context is not established",
+            ))
+        }
+    }
+
+    /// Negotiate a security context with the server using TKEY, and return the resulting signer
+    ///
+    /// [RFC 3645, GSS Algorithm for TSIG (GSS-TSIG)](https://tools.ietf.org/html/rfc3645#section-3.1)
+    ///
+    /// ```text
+    /// 3.1.  Negotiating Context
+    ///
+    ///    In GSS, establishing a security context involves the passing of
+    ///    opaque tokens between the client and the server.  The client
+    ///    generates the initial token and sends it to the server.  The server
+    ///    processes the token and, if necessary, generates a response token
+    ///    which it sends back to the client.  This exchange continues until
+    ///    the context is established.
+    /// ```
+    ///
+    /// The handle must not itself sign requests, the TKEY exchange is not authenticated with TSIG.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - connection to the server
+    /// * `context` - a new GSS-API context, targeting the server's service principal,
+    ///               e.g. `DNS/ns1.example.com`
+    /// * `key_name` - name of the key to establish, should be unique, e.g. `<random>.example.com.`
+    /// * `fudge` - maximum difference between client and server time, in seconds
+    pub async fn negotiate<H, C>(
+        handle: &mut H,
+        mut context: C,
+        key_name: Name,
+        fudge: u16,
+    ) -> ProtoResult<Self>
+    where
+        H: DnsHandle<Error = ProtoError>,
+        C: GssContext,
+    {
+        let mut token = context.step(None)?;
+
+        while let Some(output) = token.take().filter(|t| !t.is_empty()) {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| ProtoError::from("current time is before the unix epoch"))?
+                .as_secs() as u32;
+
+            debug!("sending TKEY negotiation for {}", key_name);
+            let request = gss_tkey_query(key_name.clone(), output, now);
+            let response = handle.send(request).first_answer().await?;
+            let input = gss_tkey_response_token(&response, &key_name)?;
+
+            if context.is_complete() {
+                break;
+            }
+
+            token = context.step(Some(&input))?;
+        }
+
+        Self::new(context, key_name, fudge)
+    }
+
+    /// Name of the key used by this signer
+    pub fn signer_name(&self) -> &Name {
+        &self.0.signer_name
+    }
+
+    /// Maximum time difference between client time when issuing a message, and server time when
+    /// receiving it, in second.
+    pub fn fudge(&self) -> u16 {
+        self.0.fudge
+    }
+
+    /// Compute authentication tag for a buffer
+    pub fn sign(&self, tbs: &[u8]) -> ProtoResult<Vec<u8>> {
+        self.0.context.get_mic(tbs)
+    }
+
+    /// Compute authentication tag for a message
+    pub fn sign_message(&self, message: &Message, pre_tsig: &TSIG) -> ProtoResult<Vec<u8>> {
+        message_tbs(None, message, pre_tsig, &self.0.signer_name).and_then(|tbs| self.sign(&tbs))
+    }
+
+    /// Verify the authentication tag of a buffer
+    pub fn verify(&self, tbv: &[u8], tag: &[u8]) -> ProtoResult<()> {
+        self.0.context.verify_mic(tbv, tag)
+    }
+
+    /// Verify the message is correctly signed
+    ///
+    /// See [`TSigner::verify_message_byte`](super::tsig::TSigner::verify_message_byte), the only
+    /// difference is the MAC being a GSS-API message integrity code.
+    pub fn verify_message_byte(
+        &self,
+        previous_hash: Option<&[u8]>,
+        message: &[u8],
+        first_message: bool,
+    ) -> ProtoResult<(Vec<u8>, Range<u64>, u64)> {
+        let (tbv, record) = signed_bitmessage_to_buf(previous_hash, message, first_message)?;
+        let tsig = if let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = record.data() {
+            tsig
+        } else {
+            unreachable!("tsig::signed_message_to_buff always returns a TSIG record")
+        };
+
+        // https://tools.ietf.org/html/rfc8945#section-5.2
+        // 1.  Check key
+        if record.name() != &self.0.signer_name || tsig.algorithm() != &TsigAlgorithm::Gss {
+            return Err(ProtoError::from("tsig validation error: wrong key"));
+        }
+
+        // 2.  Check MAC
+        self.verify(&tbv, tsig.mac())
+            .map_err(|_e| ProtoError::from("tsig validation error: invalid signature"))?;
+
+        // 3.  Check time values, left to the caller, as in TSigner
+
+        Ok((
+            tsig.mac().to_vec(),
+            Range {
+                start: tsig.time() - tsig.fudge() as u64,
+                end: tsig.time() + tsig.fudge() as u64,
+            },
+            tsig.time(),
+        ))
+    }
+}
+
+impl MessageFinalizer for GssTSigner {
+    fn finalize_message(
+        &self,
+        message: &Message,
+        current_time: u32,
+    ) -> ProtoResult<(Vec<Record>, Option<MessageVerifier>)> {
+        debug!("signing message with gss-tsig: {:?}", message);
+        let current_time = current_time as u64;
+
+        let pre_tsig = TSIG::new(
+            TsigAlgorithm::Gss,
+            current_time,
+            self.0.fudge,
+            Vec::new(),
+            message.id(),
+            0,
+            Vec::new(),
+        );
+        let mut signature: Vec<u8> = self.sign_message(message, &pre_tsig)?;
+        let tsig = make_tsig_record(
+            self.0.signer_name.clone(),
+            pre_tsig.set_mac(signature.clone()),
+        );
+        let self2 = self.clone();
+        let mut remote_time = 0;
+        let verifier = move |dns_response: &[u8]| {
+            let (last_sig, range, rt) = self2.verify_message_byte(
+                Some(signature.as_ref()),
+                dns_response,
+                remote_time == 0,
+            )?;
+            if rt >= remote_time && range.contains(&current_time)
+            // this assumes a no-latency answer
+            {
+                signature = last_sig;
+                remote_time = rt;
+                Message::from_vec(dns_response).map(DnsResponse::from)
+            } else {
+                Err(ProtoError::from("tsig validation error: outdated response"))
+            }
+        };
+        Ok((vec![tsig], Some(Box::new(verifier))))
+    }
+}
+
+/// Build a TKEY query carrying a GSS-API token
+///
+/// [RFC 3645, GSS Algorithm for TSIG (GSS-TSIG)](https://tools.ietf.org/html/rfc3645#section-3.1.1)
+///
+/// ```text
+///    The query MUST have TKEY RR in the Additional records section with
+///    the following fields:
+///       NAME = key_name
+///       RDATA
+///         Algorithm Name = gss-tsig
+///         Mode = 3 (GSS-API negotiation - per [RFC2930])
+///         Key Size = size of output_token in octets
+///         Key Data = output_token
+/// ```
+pub fn gss_tkey_query(key_name: Name, token: Vec<u8>, current_time: u32) -> Message {
+    let tkey = TKEY::new(
+        TsigAlgorithm::Gss,
+        current_time,
+        current_time,
+        TkeyMode::GssApi,
+        0,
+        token,
+        Vec::new(),
+    );
+
+    tkey_query(key_name, tkey, Vec::new())
+}
+
+/// Extract the GSS-API token from the server response to a [`gss_tkey_query`]
+///
+/// Returns an error if the server refused the negotiation.
+pub fn gss_tkey_response_token(response: &Message, key_name: &Name) -> ProtoResult<Vec<u8>> {
+    let tkey = tkey_response(response, key_name, TkeyMode::GssApi)?;
+
+    if tkey.algorithm() != &TsigAlgorithm::Gss {
+        return Err(format!("unexpected TKEY algorithm for {}", key_name).into());
+    }
+
+    Ok(tkey.key().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use std::pin::Pin;
+
+    use futures::executor::block_on;
+    use futures::{stream, Stream};
+
+    use crate::op::{MessageType, Query};
+    use crate::proto::rr::dnssec::rdata::tkey::make_tkey_record;
+    use crate::proto::xfer::DnsRequest;
+    use crate::rr::{DNSClass, RecordType};
+    use crate::serialize::binary::BinEncodable;
+
+    use super::*;
+
+    /// A toy mechanism, completes after two round trips and "signs" by summing bytes with a key
+    struct TestContext {
+        round: u8,
+        key: u8,
+    }
+
+    impl TestContext {
+        fn new() -> Self {
+            Self { round: 0, key: 0 }
+        }
+    }
+
+    impl GssContext for TestContext {
+        fn step(&mut self, input: Option<&[u8]>) -> ProtoResult<Option<Vec<u8>>> {
+            self.round += 1;
+            match (self.round, input) {
+                (1, None) => Ok(Some(vec![1])),
+                (2, Some(&[key])) => {
+                    self.key = key;
+                    Ok(Some(vec![2]))
+                }
+                _ => Err(ProtoError::from("unexpected token")),
+            }
+        }
+
+        fn is_complete(&self) -> bool {
+            self.round >= 2
+        }
+
+        fn get_mic(&self, message: &[u8]) -> ProtoResult<Vec<u8>> {
+            let sum = message
+                .iter()
+                .fold(self.key as u32, |sum, b| sum.wrapping_add(*b as u32));
+            Ok(sum.to_be_bytes().to_vec())
+        }
+
+        fn verify_mic(&self, message: &[u8], mic: &[u8]) -> ProtoResult<()> {
+            if self.get_mic(message)? == mic {
+                Ok(())
+            } else {
+                Err(ProtoError::from("bad mic"))
+            }
+        }
+    }
+
+    /// Plays the server side of the toy mechanism
+    #[derive(Clone)]
+    struct TestServer;
+
+    impl DnsHandle for TestServer {
+        type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+        type Error = ProtoError;
+
+        fn send<R: Into<DnsRequest> + Send + 'static>(&mut self, request: R) -> Self::Response {
+            let request: DnsRequest = request.into();
+            let additional = &request.additionals()[0];
+            let token = match additional.data() {
+                Some(RData::DNSSEC(DNSSECRData::TKEY(tkey))) => tkey.key().to_vec(),
+                _ => panic!("expected a TKEY"),
+            };
+
+            let reply = match token.as_slice() {
+                [1] => vec![42],
+                [2] => vec![],
+                _ => panic!("unexpected token"),
+            };
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .add_answer(make_tkey_record(
+                    additional.name().clone(),
+                    TKEY::new(TsigAlgorithm::Gss, 0, 0, TkeyMode::GssApi, 0, reply, vec![]),
+                ));
+
+            Box::pin(stream::once(async move { Ok(response.into()) }))
+        }
+    }
+
+    #[test]
+    fn test_tkey_query() {
+        let key_name = Name::from_ascii("1234.example.com.").unwrap();
+        let message = gss_tkey_query(key_name.clone(), vec![1, 2, 3], 1609459200);
+
+        let message = Message::from_vec(&message.to_bytes().unwrap()).unwrap();
+        assert_eq!(message.queries()[0].query_type(), RecordType::TKEY);
+        assert_eq!(message.queries()[0].query_class(), DNSClass::ANY);
+
+        let tkey = message.additionals()[0].data().unwrap();
+        if let RData::DNSSEC(DNSSECRData::TKEY(tkey)) = tkey {
+            assert_eq!(tkey.mode(), TkeyMode::GssApi);
+            assert_eq!(tkey.algorithm(), &TsigAlgorithm::Gss);
+            assert_eq!(tkey.key(), &[1, 2, 3]);
+        } else {
+            panic!("expected a TKEY");
+        }
+    }
+
+    #[test]
+    fn test_tkey_response_error() {
+        let key_name = Name::from_ascii("1234.example.com.").unwrap();
+        let mut response = Message::new();
+        response.add_answer(make_tkey_record(
+            key_name.clone(),
+            TKEY::new(
+                TsigAlgorithm::Gss,
+                0,
+                0,
+                TkeyMode::GssApi,
+                17, // BADKEY
+                vec![],
+                vec![],
+            ),
+        ));
+
+        assert!(gss_tkey_response_token(&response, &key_name).is_err());
+        assert!(gss_tkey_response_token(&Message::new(), &key_name).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_sign_and_verify() {
+        let key_name = Name::from_ascii("1234.example.com.").unwrap();
+        let signer = block_on(GssTSigner::negotiate(
+            &mut TestServer,
+            TestContext::new(),
+            key_name.clone(),
+            300,
+        ))
+        .expect("negotiation failed");
+        assert_eq!(signer.signer_name(), &key_name);
+
+        let mut question = Message::new();
+        question.add_query(Query::query(
+            Name::from_ascii("example.com.").unwrap(),
+            RecordType::SOA,
+        ));
+        question
+            .finalize(&signer, 1609459200)
+            .expect("should have signed");
+        assert!(!question.signature().is_empty());
+
+        let (_, validity_range, _) = signer
+            .verify_message_byte(None, &question.to_bytes().unwrap(), true)
+            .unwrap();
+        assert!(validity_range.contains(&1609459200));
+
+        // tampering is detected
+        question.add_query(Query::query(
+            Name::from_ascii("example.net.").unwrap(),
+            RecordType::SOA,
+        ));
+        assert!(signer
+            .verify_message_byte(None, &question.to_bytes().unwrap(), true)
+            .is_err());
+    }
+
+    #[test]
+    fn test_incomplete_context() {
+        let key_name = Name::from_ascii("1234.example.com.").unwrap();
+        assert!(GssTSigner::new(TestContext::new(), key_name, 300).is_err());
+    }
+}
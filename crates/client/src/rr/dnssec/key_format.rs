@@ -1,12 +1,15 @@
 #[cfg(feature = "openssl")]
 use openssl::ec::EcKey;
 #[cfg(feature = "openssl")]
+use openssl::pkey::PKey;
+#[cfg(feature = "openssl")]
 use openssl::rsa::Rsa;
 #[cfg(feature = "openssl")]
 use openssl::symm::Cipher;
 #[cfg(feature = "ring")]
 use ring::signature::{
-    EcdsaKeyPair, Ed25519KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING,
+    EcdsaKeyPair, Ed25519KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+    ECDSA_P384_SHA384_FIXED_SIGNING,
 };
 
 use crate::error::*;
@@ -43,31 +46,33 @@ impl KeyFormat {
             e @ Algorithm::RSASHA1 | e @ Algorithm::RSASHA1NSEC3SHA1 => {
                 Err(format!("unsupported Algorithm (insecure): {:?}", e).into())
             }
-            #[cfg(feature = "openssl")]
-            Algorithm::RSASHA256 | Algorithm::RSASHA512 => {
-                let key = match self {
-                    Self::Der => Rsa::private_key_from_der(bytes)
-                        .map_err(|e| format!("error reading RSA as DER: {}", e))?,
-                    Self::Pem => {
-                        let key = Rsa::private_key_from_pem_passphrase(bytes, password);
+            Algorithm::RSASHA256 | Algorithm::RSASHA512 => match self {
+                #[cfg(feature = "openssl")]
+                Self::Der => {
+                    let key = Rsa::private_key_from_der(bytes)
+                        .map_err(|e| format!("error reading RSA as DER: {}", e))?;
 
-                        key.map_err(|e| {
+                    Ok(KeyPair::from_rsa(key)
+                        .map_err(|e| format!("could not tranlate RSA to KeyPair: {}", e))?)
+                }
+                #[cfg(feature = "openssl")]
+                Self::Pem => {
+                    let key =
+                        Rsa::private_key_from_pem_passphrase(bytes, password).map_err(|e| {
                             format!("could not decode RSA from PEM, bad password?: {}", e)
-                        })?
-                    }
-                    e => {
-                        return Err(format!(
-                            "unsupported key format with RSA (DER or PEM only): \
-                             {:?}",
-                            e
-                        )
-                        .into())
-                    }
-                };
+                        })?;
 
-                Ok(KeyPair::from_rsa(key)
-                    .map_err(|e| format!("could not tranlate RSA to KeyPair: {}", e))?)
-            }
+                    Ok(KeyPair::from_rsa(key)
+                        .map_err(|e| format!("could not tranlate RSA to KeyPair: {}", e))?)
+                }
+                #[cfg(feature = "ring")]
+                Self::Pkcs8 => {
+                    let key = RsaKeyPair::from_pkcs8(bytes)?;
+
+                    Ok(KeyPair::from_rsa_pkcs1(key))
+                }
+                e => Err(format!("unsupported key format with RSA: {:?}", e).into()),
+            },
             Algorithm::ECDSAP256SHA256 | Algorithm::ECDSAP384SHA384 => match self {
                 #[cfg(feature = "openssl")]
                 Self::Der => {
@@ -113,6 +118,29 @@ impl KeyFormat {
                 )
                 .into()),
             },
+            Algorithm::ED448 => match self {
+                #[cfg(feature = "openssl")]
+                Self::Der => {
+                    let key = PKey::private_key_from_der(bytes)
+                        .map_err(|e| format!("error reading ED448 as DER: {}", e))?;
+
+                    Ok(KeyPair::from_ed448_pkey(key)?)
+                }
+                #[cfg(feature = "openssl")]
+                Self::Pem => {
+                    let key =
+                        PKey::private_key_from_pem_passphrase(bytes, password).map_err(|e| {
+                            format!("could not decode ED448 from PEM, bad password?: {}", e)
+                        })?;
+
+                    Ok(KeyPair::from_ed448_pkey(key)?)
+                }
+                e => Err(format!(
+                    "unsupported key format with ED448 (DER or PEM only): {:?}",
+                    e
+                )
+                .into()),
+            },
             e => Err(format!(
                 "unsupported Algorithm, enable openssl or ring feature: {:?}",
                 e
@@ -154,6 +182,11 @@ impl KeyFormat {
             },
             #[cfg(feature = "ring")]
             Algorithm::ED25519 => return KeyPair::generate_pkcs8(algorithm),
+            #[cfg(feature = "openssl")]
+            Algorithm::ED448 => match self {
+                Self::Der | Self::Pem => KeyPair::generate(algorithm)?,
+                e => return Err(format!("unsupported key format with ED448: {:?}", e).into()),
+            },
             e => {
                 return Err(format!(
                     "unsupported Algorithm, enable openssl or ring feature: {:?}",
@@ -167,7 +200,7 @@ impl KeyFormat {
         #[allow(unreachable_code)]
         match key_pair {
             #[cfg(feature = "openssl")]
-            KeyPair::EC(ref pkey) | KeyPair::RSA(ref pkey) => {
+            KeyPair::EC(ref pkey) | KeyPair::RSA(ref pkey) | KeyPair::ED448(ref pkey) => {
                 match self {
                     Self::Der => {
                         // to avoid accidentally storing a key where there was an expectation that it was password protected
@@ -190,7 +223,7 @@ impl KeyFormat {
                         key.map_err(|e| format!("error writing key as PEM: {}", e).into())
                     }
                     e => Err(format!(
-                        "unsupported key format with RSA or EC (DER or PEM \
+                        "unsupported key format with RSA, EC or ED448 (DER or PEM \
                          only): {:?}",
                         e
                     )
@@ -198,7 +231,9 @@ impl KeyFormat {
                 }
             }
             #[cfg(feature = "ring")]
-            KeyPair::ECDSA(..) | KeyPair::ED25519(..) => panic!("should have returned early"),
+            KeyPair::ECDSA(..) | KeyPair::ED25519(..) | KeyPair::RSAPKCS1(..) => {
+                panic!("should have returned early")
+            }
             #[cfg(not(feature = "openssl"))]
             KeyPair::Phantom(..) => panic!("Phantom disallowed"),
             #[cfg(not(any(feature = "openssl", feature = "ring")))]
@@ -226,7 +261,7 @@ impl KeyFormat {
 
         match *key_pair {
             #[cfg(feature = "openssl")]
-            KeyPair::EC(ref pkey) | KeyPair::RSA(ref pkey) => {
+            KeyPair::EC(ref pkey) | KeyPair::RSA(ref pkey) | KeyPair::ED448(ref pkey) => {
                 match self {
                     Self::Der => {
                         // to avoid accidentally storing a key where there was an expectation that it was password protected
@@ -249,7 +284,7 @@ impl KeyFormat {
                         key.map_err(|e| format!("error writing key as PEM: {}", e).into())
                     }
                     e => Err(format!(
-                        "unsupported key format with RSA or EC (DER or PEM \
+                        "unsupported key format with RSA, EC or ED448 (DER or PEM \
                          only): {:?}",
                         e
                     )
@@ -313,6 +348,20 @@ mod tests {
         encode_decode_with_format(KeyFormat::Pkcs8, algorithm, true, true);
     }
 
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn test_ed448_encode_decode_der() {
+        let algorithm = Algorithm::ED448;
+        encode_decode_with_format(KeyFormat::Der, algorithm, false, true);
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn test_ed448_encode_decode_pem() {
+        let algorithm = Algorithm::ED448;
+        encode_decode_with_format(KeyFormat::Pem, algorithm, true, true);
+    }
+
     #[cfg(test)]
     fn encode_decode_with_format(
         key_format: KeyFormat,
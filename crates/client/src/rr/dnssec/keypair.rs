@@ -15,7 +15,7 @@ use openssl::ec::{EcGroup, EcKey, PointConversionForm};
 #[cfg(feature = "openssl")]
 use openssl::nid::Nid;
 #[cfg(feature = "openssl")]
-use openssl::pkey::PKey;
+use openssl::pkey::{Id, PKey};
 #[cfg(feature = "openssl")]
 use openssl::rsa::Rsa as OpenSslRsa;
 #[cfg(feature = "openssl")]
@@ -25,8 +25,9 @@ use openssl::sign::Signer;
 use ring::{
     rand,
     signature::{
-        EcdsaKeyPair, Ed25519KeyPair, KeyPair as RingKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
-        ECDSA_P384_SHA384_FIXED_SIGNING,
+        EcdsaKeyPair, Ed25519KeyPair, KeyPair as RingKeyPair, RsaKeyPair,
+        ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING, RSA_PKCS1_SHA256,
+        RSA_PKCS1_SHA512,
     },
 };
 #[allow(deprecated)]
@@ -47,8 +48,8 @@ use crate::rr::Name;
 /// A public and private key pair, the private portion is not required.
 ///
 /// This supports all the various public/private keys which Trust-DNS is capable of using. Given
-///  differing features, some key types may not be available. The `openssl` feature will enable RSA and EC
-///  (P256 and P384). The `ring` feature enables ED25519, in the future, Ring will also be used for other keys.
+///  differing features, some key types may not be available. The `openssl` feature will enable RSA, EC
+///  (P256 and P384) and ED448. The `ring` feature enables ECDSA, ED25519 and signing with existing RSA keys.
 #[allow(clippy::large_enum_variant)]
 pub enum KeyPair<K> {
     /// RSA keypair, supported by OpenSSL
@@ -59,6 +60,10 @@ pub enum KeyPair<K> {
     #[cfg(feature = "openssl")]
     #[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
     EC(PKey<K>),
+    /// ED448 keypair, supported by OpenSSL
+    #[cfg(feature = "openssl")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+    ED448(PKey<K>),
     #[cfg(not(feature = "openssl"))]
     #[doc(hidden)]
     Phantom(PhantomData<K>),
@@ -70,6 +75,10 @@ pub enum KeyPair<K> {
     #[cfg(feature = "ring")]
     #[cfg_attr(docsrs, doc(cfg(feature = "ring")))]
     ED25519(Ed25519KeyPair),
+    /// *ring* RSA keypair, *ring* can only load existing keys, not generate them
+    #[cfg(feature = "ring")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ring")))]
+    RSAPKCS1(RsaKeyPair),
 }
 
 impl<K> KeyPair<K> {
@@ -101,6 +110,17 @@ impl<K> KeyPair<K> {
         Self::EC(pkey)
     }
 
+    /// Given a known pkey of an ED448 key, return the wrapped keypair
+    #[cfg(feature = "openssl")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+    pub fn from_ed448_pkey(pkey: PKey<K>) -> DnsSecResult<Self> {
+        if pkey.id() != Id::ED448 {
+            return Err(DnsSecErrorKind::Message("pkey is not an ED448 key").into());
+        }
+
+        Ok(Self::ED448(pkey))
+    }
+
     /// Creates an ECDSA keypair with ring.
     #[cfg(feature = "ring")]
     #[cfg_attr(docsrs, doc(cfg(feature = "ring")))]
@@ -114,6 +134,13 @@ impl<K> KeyPair<K> {
     pub fn from_ed25519(ed_key: Ed25519KeyPair) -> Self {
         Self::ED25519(ed_key)
     }
+
+    /// Creates an RSA keypair with ring.
+    #[cfg(feature = "ring")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ring")))]
+    pub fn from_rsa_pkcs1(rsa_key: RsaKeyPair) -> Self {
+        Self::RSAPKCS1(rsa_key)
+    }
 }
 
 impl<K: HasPublic> KeyPair<K> {
@@ -174,8 +201,31 @@ impl<K: HasPublic> KeyPair<K> {
                 bytes.remove(0);
                 Ok(bytes)
             }
+            #[cfg(feature = "openssl")]
+            Self::ED448(ref pkey) => pkey.raw_public_key().map_err(Into::into),
             #[cfg(feature = "ring")]
             Self::ED25519(ref ed_key) => Ok(ed_key.public_key().as_ref().to_vec()),
+            // see from_vec() RSA sections for reference
+            #[cfg(feature = "ring")]
+            Self::RSAPKCS1(ref rsa_key) => {
+                let public_key = rsa_key.public_key();
+                let e = public_key.exponent();
+                let e = e.big_endian_without_leading_zero();
+                let n = public_key.modulus();
+                let n = n.big_endian_without_leading_zero();
+
+                let mut bytes: Vec<u8> = Vec::with_capacity(e.len() + n.len() + 3);
+                if e.len() > 255 {
+                    bytes.push(0);
+                    bytes.push((e.len() >> 8) as u8);
+                }
+
+                bytes.push(e.len() as u8);
+                bytes.extend_from_slice(e);
+                bytes.extend_from_slice(n);
+
+                Ok(bytes)
+            }
             #[cfg(not(feature = "openssl"))]
             Self::Phantom(..) => panic!("Phantom disallowed"),
             #[cfg(not(any(feature = "openssl", feature = "ring")))]
@@ -437,8 +487,29 @@ impl<K: HasPrivate> KeyPair<K> {
                 let rng = rand::SystemRandom::new();
                 Ok(ec_key.sign(&rng, tbs.as_ref())?.as_ref().to_vec())
             }
+            #[cfg(feature = "openssl")]
+            Self::ED448(ref pkey) => {
+                // EdDSA hashes the message internally, so no digest is configured on the signer
+                let mut signer = Signer::new_without_digest(pkey)?;
+                signer.sign_oneshot_to_vec(tbs.as_ref()).map_err(Into::into)
+            }
             #[cfg(feature = "ring")]
             Self::ED25519(ref ed_key) => Ok(ed_key.sign(tbs.as_ref()).as_ref().to_vec()),
+            #[cfg(feature = "ring")]
+            Self::RSAPKCS1(ref rsa_key) => {
+                let padding_alg = match algorithm {
+                    Algorithm::RSASHA256 => &RSA_PKCS1_SHA256,
+                    Algorithm::RSASHA512 => &RSA_PKCS1_SHA512,
+                    _ => {
+                        return Err(format!("unsupported algorithm for RSA: {:?}", algorithm).into())
+                    }
+                };
+
+                let rng = rand::SystemRandom::new();
+                let mut signature = vec![0; rsa_key.public_modulus_len()];
+                rsa_key.sign(padding_alg, &rng, tbs.as_ref(), &mut signature)?;
+                Ok(signature)
+            }
             #[cfg(not(feature = "openssl"))]
             Self::Phantom(..) => panic!("Phantom disallowed"),
             #[cfg(not(any(feature = "openssl", feature = "ring")))]
@@ -475,6 +546,8 @@ impl KeyPair<Private> {
                 .and_then(|group| EcKey::generate(&group))
                 .map_err(Into::into)
                 .and_then(Self::from_ec_key),
+            #[cfg(feature = "openssl")]
+            Algorithm::ED448 => PKey::generate_ed448().map(Self::ED448).map_err(Into::into),
             #[cfg(feature = "ring")]
             Algorithm::ED25519 => Err(DnsSecErrorKind::Message(
                 "use generate_pkcs8 for generating private key and encoding",
@@ -565,6 +638,13 @@ mod tests {
         hash_test(Algorithm::ECDSAP384SHA384, KeyFormat::Pkcs8);
     }
 
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_rsa_sha512() {
+        public_key_test(Algorithm::RSASHA512, KeyFormat::Der);
+        hash_test(Algorithm::RSASHA512, KeyFormat::Der);
+    }
+
     #[cfg(feature = "ring")]
     #[test]
     fn test_ed25519() {
@@ -572,6 +652,47 @@ mod tests {
         hash_test(Algorithm::ED25519, KeyFormat::Pkcs8);
     }
 
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_ed448() {
+        public_key_test(Algorithm::ED448, KeyFormat::Der);
+        hash_test(Algorithm::ED448, KeyFormat::Pem);
+    }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn test_rsa_pkcs8() {
+        use openssl::pkey::PKey;
+        use openssl::rsa::Rsa;
+
+        // *ring* can't generate RSA keys, use openssl to create a pkcs8 document
+        let pem = PKey::from_rsa(Rsa::generate(2048).unwrap())
+            .unwrap()
+            .private_key_to_pem_pkcs8()
+            .unwrap();
+        let pem = String::from_utf8(pem).unwrap();
+        let base64: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        let pkcs8 = data_encoding::BASE64.decode(base64.as_bytes()).unwrap();
+
+        for algorithm in &[Algorithm::RSASHA256, Algorithm::RSASHA512] {
+            let key = KeyFormat::Pkcs8
+                .decode_key(&pkcs8, None, *algorithm)
+                .unwrap();
+            assert!(matches!(key, KeyPair::RSAPKCS1(..)));
+
+            let tbs = TBS::from(&b"www.example.com"[..]);
+            let sig = key.sign(*algorithm, &tbs).unwrap();
+            assert!(
+                key.to_dnskey(*algorithm)
+                    .unwrap()
+                    .verify(tbs.as_ref(), &sig)
+                    .is_ok(),
+                "algorithm: {:?} (dnskey)",
+                algorithm
+            );
+        }
+    }
+
     fn public_key_test(algorithm: Algorithm, key_format: KeyFormat) {
         let key = key_format
             .decode_key(
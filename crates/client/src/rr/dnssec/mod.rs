@@ -17,17 +17,23 @@
 //! dns security extension related modules
 
 #[cfg(any(feature = "openssl", feature = "ring"))]
+pub mod gss_tsig;
 mod key_format;
 mod keypair;
+pub mod sig0;
 mod signer;
 pub mod tsig;
 
 use crate::proto::rr::dnssec;
 
+pub use self::dnssec::nsec3_proof;
 pub use self::dnssec::tbs;
+pub use self::dnssec::tkey;
 pub use self::dnssec::Algorithm;
 pub use self::dnssec::DigestType;
 pub use self::dnssec::Nsec3HashAlgorithm;
+pub use self::dnssec::Proof;
+pub use self::dnssec::ProvenRrset;
 pub use self::dnssec::PublicKey;
 pub use self::dnssec::PublicKeyBuf;
 pub use self::dnssec::PublicKeyEnum;
@@ -39,6 +45,7 @@ pub use self::dnssec::TBS;
 #[cfg_attr(docsrs, doc(cfg(any(feature = "openssl", feature = "ring"))))]
 pub use self::key_format::KeyFormat;
 pub use self::keypair::KeyPair;
+pub use self::sig0::MessageSigner;
 #[allow(deprecated)]
 pub use self::signer::{SigSigner, Signer};
 
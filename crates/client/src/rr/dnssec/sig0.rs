@@ -0,0 +1,696 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! High level signing and verification of messages with SIG(0), [RFC 2931](https://tools.ietf.org/html/rfc2931)
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(feature = "openssl")]
+use openssl::bn::{BigNum, BigNumContext};
+#[cfg(feature = "openssl")]
+use openssl::ec::{EcGroup, EcKey, EcPoint};
+#[cfg(feature = "openssl")]
+use openssl::nid::Nid;
+#[cfg(feature = "openssl")]
+use openssl::pkey::{Id, PKey};
+#[cfg(feature = "openssl")]
+use openssl::rsa::Rsa;
+#[cfg(feature = "ring")]
+use ring::signature::Ed25519KeyPair;
+#[cfg(all(feature = "ring", not(feature = "openssl")))]
+use ring::signature::{
+    EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING,
+};
+use tracing::debug;
+
+use crate::error::*;
+use crate::op::{Message, MessageFinalizer, MessageVerifier};
+use crate::proto::error::ProtoResult;
+use crate::proto::rr::dnssec::rdata::key::{KeyTrust, KeyUsage, Protocol};
+use crate::proto::serialize::binary::BinEncodable;
+#[cfg(any(feature = "openssl", feature = "ring"))]
+use crate::rr::dnssec::KeyFormat;
+use crate::rr::dnssec::{tbs, Algorithm, KeyPair, Private, Verifier};
+use crate::rr::rdata::{DNSSECRData, KEY, SIG};
+use crate::rr::{DNSClass, Name, RData, Record, RecordType};
+
+/// The default validity of generated signatures, and allowed clock skew when verifying, 5 minutes
+pub const DEFAULT_SIG0_VALIDITY: Duration = Duration::from_secs(5 * 60);
+
+/// Signs and verifies messages with SIG(0), [RFC 2931](https://tools.ietf.org/html/rfc2931)
+///
+/// This wraps the KEY and, when signing, the private key of a SIG(0) signer. The same
+///  `MessageSigner` can be used as the [`MessageFinalizer`] of a client, and to verify requests
+///  on a server, in which case only the public KEY is required, see [`Self::verifier`].
+pub struct MessageSigner {
+    key: KEY,
+    key_pair: Option<KeyPair<Private>>,
+    signer_name: Name,
+    validity: Duration,
+    max_skew: Duration,
+}
+
+impl MessageSigner {
+    /// Creates a new signer from the private key
+    ///
+    /// # Arguments
+    ///
+    /// * `key_pair` - the private key used for signing
+    /// * `algorithm` - the algorithm of the key
+    /// * `signer_name` - the name of the KEY record in the zone, as the server will look it up
+    pub fn new(
+        key_pair: KeyPair<Private>,
+        algorithm: Algorithm,
+        signer_name: Name,
+    ) -> DnsSecResult<Self> {
+        let key = key_pair.to_sig0key(algorithm)?;
+
+        Ok(Self {
+            key,
+            key_pair: Some(key_pair),
+            signer_name,
+            validity: DEFAULT_SIG0_VALIDITY,
+            max_skew: DEFAULT_SIG0_VALIDITY,
+        })
+    }
+
+    /// Creates a signer which can only verify messages, e.g. from a KEY record stored in a zone
+    pub fn verifier(key: KEY, signer_name: Name) -> Self {
+        Self {
+            key,
+            key_pair: None,
+            signer_name,
+            validity: DEFAULT_SIG0_VALIDITY,
+            max_skew: DEFAULT_SIG0_VALIDITY,
+        }
+    }
+
+    /// Creates a new signer from an encoded private key, see [`KeyFormat::decode_key`]
+    #[cfg(any(feature = "openssl", feature = "ring"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "openssl", feature = "ring"))))]
+    pub fn from_key_bytes(
+        format: KeyFormat,
+        bytes: &[u8],
+        password: Option<&str>,
+        algorithm: Algorithm,
+        signer_name: Name,
+    ) -> DnsSecResult<Self> {
+        let key_pair = format.decode_key(bytes, password, algorithm)?;
+        Self::new(key_pair, algorithm, signer_name)
+    }
+
+    /// Creates a new signer from the contents of a BIND `K<name>+<alg>+<id>.key` public key file
+    ///  and the matching `.private` file, as generated by `dnssec-keygen`
+    ///
+    /// The signer name is the owner of the KEY record in the public key file.
+    pub fn from_bind_key(public_key: &str, private_key: &str) -> DnsSecResult<Self> {
+        let (signer_name, key) = parse_bind_public_key(public_key)?;
+        let key_pair = parse_bind_private_key(private_key, &key)?;
+
+        if key_pair.to_public_bytes()? != key.public_key() {
+            return Err("BIND private key does not match the public key".into());
+        }
+
+        Ok(Self {
+            key,
+            key_pair: Some(key_pair),
+            signer_name,
+            validity: DEFAULT_SIG0_VALIDITY,
+            max_skew: DEFAULT_SIG0_VALIDITY,
+        })
+    }
+
+    /// Reads a BIND key pair from disk, `path` is the key file without the `.key` or `.private` extension
+    pub fn from_bind_files(path: &Path) -> DnsSecResult<Self> {
+        let read = |extension: &str| {
+            let path = path.with_extension(extension);
+            fs::read_to_string(&path)
+                .map_err(|e| format!("could not read key file {}: {}", path.display(), e))
+        };
+
+        Self::from_bind_key(&read("key")?, &read("private")?)
+    }
+
+    /// Sets the validity of generated signatures, i.e. expiration minus inception, default is 5 minutes
+    pub fn with_validity(mut self, validity: Duration) -> Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Sets the clock skew allowed when verifying the inception and expiration, default is 5 minutes
+    pub fn with_max_skew(mut self, max_skew: Duration) -> Self {
+        self.max_skew = max_skew;
+        self
+    }
+
+    /// The KEY which verifies signatures
+    pub fn key(&self) -> &KEY {
+        &self.key
+    }
+
+    /// The name of the KEY in the zone
+    pub fn signer_name(&self) -> &Name {
+        &self.signer_name
+    }
+
+    /// The algorithm of the KEY
+    pub fn algorithm(&self) -> Algorithm {
+        self.key.algorithm()
+    }
+
+    /// True if this has the private key and can sign messages
+    pub fn can_sign(&self) -> bool {
+        self.key_pair.is_some()
+    }
+
+    /// The key tag of the KEY, see [`SigSigner::calculate_key_tag`](super::SigSigner::calculate_key_tag)
+    pub fn key_tag(&self) -> DnsSecResult<u16> {
+        Ok(self.key.calculate_key_tag()?)
+    }
+
+    /// Returns the SIG(0) record signing the message
+    ///
+    /// The message must be otherwise complete, any change after signing invalidates the signature.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message to sign
+    /// * `now` - the current time in seconds since the unix epoch, the inception of the signature
+    pub fn sign(&self, message: &Message, now: u32) -> DnsSecResult<Record> {
+        let key_pair = self
+            .key_pair
+            .as_ref()
+            .ok_or("MessageSigner has no private key, it can only verify")?;
+
+        // 'For all SIG(0) RRs, the owner name, class, TTL, and original TTL, are
+        //  meaningless.' - 2931
+        let mut sig0 = Record::new();
+        sig0.set_ttl(0);
+        sig0.set_dns_class(DNSClass::ANY);
+        sig0.set_name(Name::root());
+        sig0.set_rr_type(RecordType::SIG);
+
+        let expiration = now.saturating_add(self.validity.as_secs() as u32);
+        let pre_sig0 = SIG::new(
+            // type covered in SIG(0) is 0 which is what makes this SIG0 vs a standard SIG
+            RecordType::ZERO,
+            self.algorithm(),
+            sig0.name().num_labels(),
+            0,
+            expiration,
+            now,
+            self.key_tag()?,
+            self.signer_name.clone(),
+            Vec::new(),
+        );
+
+        let tbs = tbs::message_tbs(message, &pre_sig0)?;
+        let signature = key_pair.sign(self.algorithm(), &tbs)?;
+        sig0.set_data(Some(RData::DNSSEC(DNSSECRData::SIG(
+            pre_sig0.set_sig(signature),
+        ))));
+
+        Ok(sig0)
+    }
+
+    /// Verifies a SIG(0) of the message
+    ///
+    /// The signer name, key tag and algorithm must match the KEY, and `now` must be within the
+    ///  inception and expiration of the signature, allowing for the max skew.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the signed message, e.g. a `Message` or a server's `MessageRequest`
+    /// * `sig` - the SIG(0) from the additional section of the message
+    /// * `now` - the current time in seconds since the unix epoch
+    pub fn verify_sig0<M: BinEncodable>(
+        &self,
+        message: &M,
+        sig: &SIG,
+        now: u32,
+    ) -> ProtoResult<()> {
+        if sig.type_covered() != RecordType::ZERO {
+            return Err(format!("not a SIG(0), covers: {}", sig.type_covered()).into());
+        }
+
+        if sig.signer_name() != &self.signer_name {
+            return Err(format!(
+                "SIG(0) signer {} does not match key {}",
+                sig.signer_name(),
+                self.signer_name
+            )
+            .into());
+        }
+
+        if sig.algorithm() != self.algorithm() {
+            return Err(format!(
+                "SIG(0) algorithm {} does not match key {}",
+                sig.algorithm(),
+                self.algorithm()
+            )
+            .into());
+        }
+
+        let key_tag = self.key.calculate_key_tag()?;
+        if sig.key_tag() != key_tag {
+            return Err(format!(
+                "SIG(0) key tag {} does not match key {}",
+                sig.key_tag(),
+                key_tag
+            )
+            .into());
+        }
+
+        check_validity(sig, now, self.max_skew)?;
+        self.key.verify_message(message, sig.sig(), sig)
+    }
+
+    /// Verifies the message, succeeding if any of its SIG(0) records verify, see [`Self::verify_sig0`]
+    pub fn verify(&self, message: &Message, now: u32) -> ProtoResult<()> {
+        let mut result = Err("message is not signed with SIG(0)".into());
+
+        for sig in message.sig0().iter().filter_map(|sig0| {
+            sig0.data()
+                .and_then(RData::as_dnssec)
+                .and_then(DNSSECRData::as_sig)
+        }) {
+            result = self.verify_sig0(message, sig, now);
+            match result {
+                Ok(()) => return Ok(()),
+                Err(ref e) => debug!("did not verify SIG(0) {:?}: {}", sig, e),
+            }
+        }
+
+        result
+    }
+}
+
+impl MessageFinalizer for MessageSigner {
+    fn finalize_message(
+        &self,
+        message: &Message,
+        current_time: u32,
+    ) -> ProtoResult<(Vec<Record>, Option<MessageVerifier>)> {
+        debug!("signing message with SIG(0): {:?}", message);
+        let sig0 = self
+            .sign(message, current_time)
+            .map_err(|e| format!("could not sign message with SIG(0): {}", e))?;
+
+        Ok((vec![sig0], None))
+    }
+}
+
+/// Checks that `now` is within the inception and expiration of the signature
+///
+/// Both bounds are extended by `max_skew` to allow for clocks which are not in sync.
+pub fn check_validity(sig: &SIG, now: u32, max_skew: Duration) -> ProtoResult<()> {
+    let now = u64::from(now);
+    let max_skew = max_skew.as_secs();
+
+    if u64::from(sig.sig_inception()) > now + max_skew {
+        return Err(format!(
+            "signature inception {} is in the future, now: {}",
+            sig.sig_inception(),
+            now
+        )
+        .into());
+    }
+
+    if u64::from(sig.sig_expiration()) + max_skew < now {
+        return Err(format!(
+            "signature expired at {}, now: {}",
+            sig.sig_expiration(),
+            now
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Parses the KEY record of a BIND public key file, e.g.
+///
+/// ```text
+/// ; This is a key-signing key, keyid 12345, for update.example.com.
+/// update.example.com. IN KEY 512 3 13 GmDbYmN+zxwC/Z6jA1OLNQpTGKcdM9Ej...
+/// ```
+fn parse_bind_public_key(public_key: &str) -> DnsSecResult<(Name, KEY)> {
+    let line = public_key
+        .lines()
+        .map(|line| line.split(';').next().unwrap_or_default().trim())
+        .find(|line| !line.is_empty())
+        .ok_or("no KEY record in BIND public key")?;
+
+    let mut tokens = line
+        .split_whitespace()
+        .filter(|token| *token != "(" && *token != ")");
+    let name = tokens.next().ok_or("no name in BIND public key")?;
+    let name = Name::from_ascii(name).map_err(|e| format!("bad name in BIND public key: {}", e))?;
+
+    // skip the optional ttl and class
+    let mut tokens = tokens.skip_while(|token| !token.eq_ignore_ascii_case("KEY"));
+    tokens.next().ok_or("BIND public key is not a KEY record")?;
+
+    let mut next = |field: &str| -> DnsSecResult<&str> {
+        tokens
+            .next()
+            .ok_or_else(|| format!("no {} in BIND public key", field).into())
+    };
+    let bad = |field: &str, e: std::num::ParseIntError| -> DnsSecError {
+        format!("bad {} in BIND public key: {}", field, e).into()
+    };
+
+    let flags = next("flags")?.parse::<u16>().map_err(|e| bad("flags", e))?;
+    // the signatory field is deprecated by RFC 3007, dropping it would change the key tag
+    if flags & 0b0000_0000_0000_1111 != 0 {
+        return Err("signatory field of BIND public key is not supported".into());
+    }
+    let protocol = next("protocol")?
+        .parse::<u8>()
+        .map_err(|e| bad("protocol", e))?;
+    let algorithm = next("algorithm")?
+        .parse::<u8>()
+        .map_err(|e| bad("algorithm", e))?;
+
+    let encoded = tokens.collect::<String>();
+    let public_key = data_encoding::BASE64
+        .decode(encoded.as_bytes())
+        .map_err(|e| format!("bad public key in BIND public key: {}", e))?;
+
+    let key = KEY::with_usage(
+        KeyTrust::from(flags),
+        KeyUsage::from(flags),
+        Protocol::from(protocol),
+        Algorithm::from_u8(algorithm),
+        public_key,
+    );
+
+    Ok((name, key))
+}
+
+/// Parses a BIND `Private-key-format: v1.x` file into the private key for the KEY
+#[allow(unused_variables)]
+fn parse_bind_private_key(private_key: &str, key: &KEY) -> DnsSecResult<KeyPair<Private>> {
+    let fields = private_key
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(field, value)| (field.trim(), value.trim()))
+        .collect::<HashMap<_, _>>();
+
+    let algorithm = fields
+        .get("Algorithm")
+        .and_then(|value| value.split_whitespace().next())
+        .ok_or("no Algorithm in BIND private key")?
+        .parse::<u8>()
+        .map_err(|e| format!("bad Algorithm in BIND private key: {}", e))?;
+    let algorithm = Algorithm::from_u8(algorithm);
+
+    if algorithm != key.algorithm() {
+        return Err(format!(
+            "BIND private key algorithm {} does not match public key {}",
+            algorithm,
+            key.algorithm()
+        )
+        .into());
+    }
+
+    let field = |name: &str| -> DnsSecResult<Vec<u8>> {
+        let value = fields
+            .get(name)
+            .ok_or_else(|| format!("no {} in BIND private key", name))?;
+
+        data_encoding::BASE64
+            .decode(value.as_bytes())
+            .map_err(|e| format!("bad {} in BIND private key: {}", name, e).into())
+    };
+
+    match algorithm {
+        #[cfg(feature = "openssl")]
+        Algorithm::RSASHA256 | Algorithm::RSASHA512 => {
+            let bn =
+                |name: &str| -> DnsSecResult<BigNum> { Ok(BigNum::from_slice(&field(name)?)?) };
+
+            let rsa = Rsa::from_private_components(
+                bn("Modulus")?,
+                bn("PublicExponent")?,
+                bn("PrivateExponent")?,
+                bn("Prime1")?,
+                bn("Prime2")?,
+                bn("Exponent1")?,
+                bn("Exponent2")?,
+                bn("Coefficient")?,
+            )?;
+
+            KeyPair::from_rsa(rsa)
+        }
+        #[cfg(feature = "openssl")]
+        Algorithm::ECDSAP256SHA256 | Algorithm::ECDSAP384SHA384 => {
+            let nid = if algorithm == Algorithm::ECDSAP256SHA256 {
+                Nid::X9_62_PRIME256V1
+            } else {
+                Nid::SECP384R1
+            };
+
+            let group = EcGroup::from_curve_name(nid)?;
+            let private = BigNum::from_slice(&field("PrivateKey")?)?;
+            let context = BigNumContext::new()?;
+            let mut public = EcPoint::new(&group)?;
+            public.mul_generator(&group, &private, &context)?;
+
+            KeyPair::from_ec_key(EcKey::from_private_components(&group, &private, &public)?)
+        }
+        #[cfg(all(feature = "ring", not(feature = "openssl")))]
+        Algorithm::ECDSAP256SHA256 | Algorithm::ECDSAP384SHA384 => {
+            let signing = if algorithm == Algorithm::ECDSAP256SHA256 {
+                &ECDSA_P256_SHA256_FIXED_SIGNING
+            } else {
+                &ECDSA_P384_SHA384_FIXED_SIGNING
+            };
+
+            // ring expects the uncompressed point, the KEY only has the coordinates
+            let mut public = Vec::with_capacity(key.public_key().len() + 1);
+            public.push(0x04);
+            public.extend_from_slice(key.public_key());
+
+            let key_pair = EcdsaKeyPair::from_private_key_and_public_key(
+                signing,
+                &field("PrivateKey")?,
+                &public,
+            )?;
+            Ok(KeyPair::from_ecdsa(key_pair))
+        }
+        #[cfg(feature = "ring")]
+        Algorithm::ED25519 => {
+            let key_pair =
+                Ed25519KeyPair::from_seed_and_public_key(&field("PrivateKey")?, key.public_key())?;
+            Ok(KeyPair::from_ed25519(key_pair))
+        }
+        #[cfg(feature = "openssl")]
+        Algorithm::ED448 => {
+            let pkey = PKey::private_key_from_raw_bytes(&field("PrivateKey")?, Id::ED448)?;
+            KeyPair::from_ed448_pkey(pkey)
+        }
+        _ => Err(format!("unsupported algorithm for BIND private key: {}", algorithm).into()),
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "openssl")]
+mod tests {
+    use openssl::rsa::Rsa;
+
+    use crate::op::{Message, Query};
+    use crate::rr::Name;
+
+    use super::*;
+
+    fn message() -> Message {
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_ascii("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        message
+    }
+
+    fn signer() -> MessageSigner {
+        let rsa = Rsa::generate(2048).unwrap();
+        let key_pair = KeyPair::from_rsa(rsa).unwrap();
+
+        MessageSigner::new(
+            key_pair,
+            Algorithm::RSASHA256,
+            Name::from_ascii("update.example.com.").unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = signer();
+        let mut message = message();
+        message.finalize(&signer, 1_000).unwrap();
+
+        assert_eq!(message.sig0().len(), 1);
+        signer.verify(&message, 1_000).unwrap();
+
+        let verifier = MessageSigner::verifier(signer.key().clone(), signer.signer_name().clone());
+        assert!(!verifier.can_sign());
+        verifier.verify(&message, 1_100).unwrap();
+    }
+
+    #[test]
+    fn test_verify_window() {
+        let signer = signer().with_max_skew(Duration::from_secs(10));
+        let mut message = message();
+        message.finalize(&signer, 1_000).unwrap();
+
+        // expiration is inception + 300
+        signer.verify(&message, 990).unwrap();
+        signer.verify(&message, 1_310).unwrap();
+        assert!(signer.verify(&message, 989).is_err());
+        assert!(signer.verify(&message, 1_311).is_err());
+    }
+
+    #[test]
+    fn test_verify_wrong_signer() {
+        let signer = signer();
+        let mut message = message();
+        message.finalize(&signer, 1_000).unwrap();
+
+        let other = MessageSigner::verifier(
+            signer.key().clone(),
+            Name::from_ascii("other.example.com.").unwrap(),
+        );
+        assert!(other.verify(&message, 1_000).is_err());
+        assert!(self::signer().verify(&message, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_verify_modified() {
+        let signer = signer();
+        let mut message = message();
+        message.finalize(&signer, 1_000).unwrap();
+
+        message.set_id(message.id().wrapping_add(1));
+        assert!(signer.verify(&message, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_verify_wrong_key_tag() {
+        let signer = signer();
+        let mut message = message();
+        message.finalize(&signer, 1_000).unwrap();
+
+        // the same public key, published with other flags, has another key tag
+        let key = signer
+            .key_pair
+            .as_ref()
+            .unwrap()
+            .to_sig0key_with_usage(Algorithm::RSASHA256, KeyUsage::Host)
+            .unwrap();
+        assert_ne!(
+            key.calculate_key_tag().unwrap(),
+            signer.key().calculate_key_tag().unwrap()
+        );
+
+        let verifier = MessageSigner::verifier(key, signer.signer_name().clone());
+        let sig = message.sig0()[0]
+            .data()
+            .and_then(RData::as_dnssec)
+            .and_then(DNSSECRData::as_sig)
+            .unwrap();
+        let err = verifier.verify_sig0(&message, sig, 1_000).unwrap_err();
+        assert!(err.to_string().contains("key tag"), "{}", err);
+    }
+
+    #[test]
+    fn test_from_key_bytes() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let pem = rsa.private_key_to_pem().unwrap();
+
+        let signer = MessageSigner::from_key_bytes(
+            KeyFormat::Pem,
+            &pem,
+            None,
+            Algorithm::RSASHA256,
+            Name::from_ascii("update.example.com.").unwrap(),
+        )
+        .unwrap();
+
+        let mut message = message();
+        message.finalize(&signer, 1_000).unwrap();
+        signer.verify(&message, 1_000).unwrap();
+    }
+
+    #[test]
+    fn test_from_bind_key() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let b64 = |bn: &openssl::bn::BigNumRef| data_encoding::BASE64.encode(&bn.to_vec());
+
+        let private_key = format!(
+            "Private-key-format: v1.3\n\
+             Algorithm: 8 (RSASHA256)\n\
+             Modulus: {}\n\
+             PublicExponent: {}\n\
+             PrivateExponent: {}\n\
+             Prime1: {}\n\
+             Prime2: {}\n\
+             Exponent1: {}\n\
+             Exponent2: {}\n\
+             Coefficient: {}\n\
+             Created: 20230101000000\n",
+            b64(rsa.n()),
+            b64(rsa.e()),
+            b64(rsa.d()),
+            b64(rsa.p().unwrap()),
+            b64(rsa.q().unwrap()),
+            b64(rsa.dmp1().unwrap()),
+            b64(rsa.dmq1().unwrap()),
+            b64(rsa.iqmp().unwrap()),
+        );
+
+        let key = KeyPair::from_rsa(rsa)
+            .unwrap()
+            .to_sig0key(Algorithm::RSASHA256)
+            .unwrap();
+        let public_key = format!(
+            "; This is a key-signing key, for update.example.com.\n\
+             update.example.com. 3600 IN KEY {} 3 8 {}\n",
+            key.flags(),
+            data_encoding::BASE64.encode(key.public_key()),
+        );
+
+        let signer = MessageSigner::from_bind_key(&public_key, &private_key).unwrap();
+        assert_eq!(
+            signer.signer_name(),
+            &Name::from_ascii("update.example.com.").unwrap()
+        );
+        assert_eq!(signer.key(), &key);
+
+        let mut message = message();
+        message.finalize(&signer, 1_000).unwrap();
+        MessageSigner::verifier(key, signer.signer_name().clone())
+            .verify(&message, 1_000)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_from_bind_key_mismatch() {
+        let public_key = format!(
+            "update.example.com. IN KEY 512 3 8 {}\n",
+            data_encoding::BASE64.encode(&[3, 1, 0, 1, 0xFF]),
+        );
+
+        assert!(
+            MessageSigner::from_bind_key(&public_key, "Algorithm: 13 (ECDSAP256SHA256)\n").is_err()
+        );
+    }
+}
@@ -17,6 +17,7 @@ use crate::rr::Record;
 #[cfg(feature = "dnssec")]
 use {
     crate::error::DnsSecResult,
+    crate::proto::rr::dnssec::rdata::key::{KeyTrust, KeyUsage, Protocol},
     crate::proto::rr::dnssec::{tbs, TBS},
     crate::rr::dnssec::{Algorithm, KeyPair, Private},
     crate::rr::rdata::{DNSSECRData, DNSKEY, KEY, SIG},
@@ -430,6 +431,25 @@ impl SigSigner {
         Ok(DNSKEY::calculate_key_tag_internal(&bytes))
     }
 
+    /// The key tag of the KEY which verifies the SIG(0) of this signer
+    ///
+    /// The KEY of a signer created from a DNSKEY is published for a host, see
+    ///  [`KeyPair::to_sig0key_with_usage`], its key tag is calculated over the flags of that KEY
+    ///  rather than the flags of the DNSKEY.
+    pub fn calculate_sig0_key_tag(&self) -> ProtoResult<u16> {
+        match self.key_rdata {
+            RData::DNSSEC(DNSSECRData::DNSKEY(ref dnskey)) => KEY::with_usage(
+                KeyTrust::default(),
+                KeyUsage::Host,
+                Protocol::default(),
+                dnskey.algorithm(),
+                dnskey.public_key().to_vec(),
+            )
+            .calculate_key_tag(),
+            _ => self.calculate_key_tag(),
+        }
+    }
+
     /// Signs the given message, returning the signature bytes.
     ///
     /// # Arguments
@@ -520,7 +540,7 @@ impl MessageFinalizer for SigSigner {
         current_time: u32,
     ) -> ProtoResult<(Vec<Record>, Option<MessageVerifier>)> {
         debug!("signing message: {:?}", message);
-        let key_tag: u16 = self.calculate_key_tag()?;
+        let key_tag: u16 = self.calculate_sig0_key_tag()?;
 
         // this is based on RFCs 2535, 2931 and 3007
 
@@ -657,6 +677,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sig0_key_tag_of_dnskey_signer() {
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(
+            Name::parse("example.com.", None).unwrap(),
+            RecordType::A,
+        ));
+
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = KeyPair::from_rsa(rsa).unwrap();
+        let dnskey = key.to_dnskey(Algorithm::RSASHA256).unwrap();
+        let signer_name = Name::parse("update.example.com.", None).unwrap();
+        let sig0key = key
+            .to_sig0key_with_usage(Algorithm::RSASHA256, KeyUsage::Host)
+            .unwrap();
+        let signer = SigSigner::dnssec(dnskey, key, signer_name.clone(), Duration::new(0, 0));
+
+        // the SIG(0) names the published host KEY, not the DNSKEY
+        assert_ne!(
+            signer.calculate_key_tag().unwrap(),
+            signer.calculate_sig0_key_tag().unwrap()
+        );
+        assert_eq!(
+            signer.calculate_sig0_key_tag().unwrap(),
+            sig0key.calculate_key_tag().unwrap()
+        );
+
+        question.finalize(&signer, 0).expect("should have signed");
+        MessageSigner::verifier(sig0key, signer_name)
+            .verify(&question, 0)
+            .unwrap();
+    }
+
     #[test]
     #[allow(deprecated)]
     fn test_sign_and_verify_rrset() {
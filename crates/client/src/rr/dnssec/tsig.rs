@@ -18,6 +18,7 @@ use std::sync::Arc;
 
 use crate::op::{DnsResponse, Message, MessageFinalizer, MessageVerifier};
 use crate::rr::{Name, RData, Record};
+use crate::serialize::binary::BinEncoder;
 
 /// Struct to pass to a client for it to authenticate requests using TSIG.
 #[derive(Clone)]
@@ -90,6 +91,92 @@ impl TSigner {
         message_tbs(None, message, pre_tsig, &self.0.signer_name).and_then(|tbs| self.sign(&tbs))
     }
 
+    /// Compute the TSIG record for a response to a request which was signed with this key
+    ///
+    /// # Arguments
+    /// * `request_mac` - the MAC of the request, as returned by `verify_message_byte`
+    /// * `response` - the response to sign, without any TSIG record
+    /// * `current_time` - the signing time, in seconds since the unix epoch
+    pub fn sign_response(
+        &self,
+        request_mac: &[u8],
+        response: &Message,
+        current_time: u32,
+    ) -> ProtoResult<Record> {
+        let pre_tsig = TSIG::new(
+            self.0.algorithm.clone(),
+            current_time as u64,
+            self.0.fudge,
+            Vec::new(),
+            response.id(),
+            0,
+            Vec::new(),
+        );
+        let tbs = message_tbs(Some(request_mac), response, &pre_tsig, &self.0.signer_name)?;
+        let signature = self.sign(&tbs)?;
+
+        Ok(make_tsig_record(
+            self.0.signer_name.clone(),
+            pre_tsig.set_mac(signature),
+        ))
+    }
+
+    /// Compute the TSIG record for a response which is already serialized, without any TSIG record
+    ///
+    /// This allows signing each message of a response which spans multiple messages, e.g. a zone
+    ///  transfer, see RFC 8945, section 5.3.1. The first message is signed with the MAC of the
+    ///  request and all TSIG variables, each subsequent message with the MAC of the previous
+    ///  message and only the timers.
+    ///
+    /// # Arguments
+    /// * `previous_mac` - the MAC of the request for the first message, otherwise the MAC of the
+    ///                    previous message
+    /// * `response` - the serialized response message
+    /// * `first_message` - true if this is the first message of the response
+    /// * `current_time` - the signing time, in seconds since the unix epoch
+    pub fn sign_response_bytes(
+        &self,
+        previous_mac: &[u8],
+        response: &[u8],
+        first_message: bool,
+        current_time: u32,
+    ) -> ProtoResult<Record> {
+        if response.len() < 2 {
+            return Err(ProtoError::from("response is too short to be signed"));
+        }
+
+        let pre_tsig = TSIG::new(
+            self.0.algorithm.clone(),
+            current_time as u64,
+            self.0.fudge,
+            Vec::new(),
+            u16::from_be_bytes([response[0], response[1]]),
+            0,
+            Vec::new(),
+        );
+
+        let mut tbs: Vec<u8> = Vec::with_capacity(response.len() + 128);
+        {
+            let mut encoder = BinEncoder::new(&mut tbs);
+            encoder.emit_u16(previous_mac.len() as u16)?;
+            encoder.emit_vec(previous_mac)?;
+            encoder.emit_vec(response)?;
+            if first_message {
+                pre_tsig.emit_tsig_for_mac(&mut encoder, &self.0.signer_name)?;
+            } else {
+                encoder.emit_u16((pre_tsig.time() >> 32) as u16)?;
+                encoder.emit_u32(pre_tsig.time() as u32)?;
+                encoder.emit_u16(pre_tsig.fudge())?;
+            }
+        }
+        let signature = self.sign(&tbs)?;
+
+        Ok(make_tsig_record(
+            self.0.signer_name.clone(),
+            pre_tsig.set_mac(signature),
+        ))
+    }
+
     /// Verify hmac in constant time to prevent timing attacks
     pub fn verify(&self, tbv: &[u8], tag: &[u8]) -> ProtoResult<()> {
         self.0.algorithm.verify_mac(&self.0.key, tbv, tag)
@@ -262,6 +349,62 @@ mod tests {
         assert!(!validity_range.contains(&(time_begin - fudge * 2))); // too soon to be accepted
     }
 
+    #[test]
+    fn test_sign_response_verifies_with_request_mac() {
+        let (question, signer) = get_message_and_signer();
+        let (request_mac, _, _) = signer
+            .verify_message_byte(None, &question.to_bytes().unwrap(), true)
+            .unwrap();
+
+        let mut response = Message::new();
+        response.set_id(question.id());
+        response.add_queries(question.queries().to_vec());
+        let tsig = signer
+            .sign_response(&request_mac, &response, 1609459200)
+            .expect("should have signed");
+        response.add_tsig(tsig);
+
+        let response = response.to_bytes().unwrap();
+        assert!(signer
+            .verify_message_byte(Some(&request_mac), &response, true)
+            .is_ok());
+        assert!(signer.verify_message_byte(None, &response, true).is_err());
+    }
+
+    #[test]
+    fn test_sign_response_bytes_verifies_chained_messages() {
+        let (question, signer) = get_message_and_signer();
+        let (request_mac, _, _) = signer
+            .verify_message_byte(None, &question.to_bytes().unwrap(), true)
+            .unwrap();
+
+        let mut previous_mac = request_mac;
+        for (i, name) in ["a.example.com.", "b.example.com."].iter().enumerate() {
+            let mut response = Message::new();
+            response.set_id(question.id());
+            response.add_answer(Record::from_rdata(
+                Name::parse(name, None).unwrap(),
+                300,
+                RData::A([127, 0, 0, 1].into()),
+            ));
+
+            let tsig = signer
+                .sign_response_bytes(
+                    &previous_mac,
+                    &response.to_bytes().unwrap(),
+                    i == 0,
+                    1609459200,
+                )
+                .expect("should have signed");
+            response.add_tsig(tsig);
+
+            let (mac, _, _) = signer
+                .verify_message_byte(Some(&previous_mac), &response.to_bytes().unwrap(), i == 0)
+                .expect("should have verified");
+            previous_mac = mac;
+        }
+    }
+
     // make rejection tests shorter by centralizing common setup code
     fn get_message_and_signer() -> (Message, TSigner) {
         let time_begin = 1609459200u64;
@@ -20,8 +20,10 @@ mod parse_rdata;
 mod rdata_parsers;
 mod zone;
 mod zone_lex;
+mod zone_writer;
 
 pub use self::parse_rdata::RDataParser;
 pub use self::zone::Parser;
 pub use self::zone_lex::Lexer;
 pub use self::zone_lex::Token;
+pub use self::zone_writer::Writer;
@@ -19,6 +19,7 @@
 use crate::error::*;
 #[cfg(feature = "dnssec")]
 use crate::proto::rr::dnssec::rdata::DNSSECRData;
+use crate::proto::serialize::binary::{BinDecoder, Restrict};
 use crate::rr::{Name, RData, RecordType};
 use crate::serialize::txt::rdata_parsers::*;
 
@@ -66,18 +67,29 @@ impl RDataParser for RData {
         tokens: I,
         origin: Option<&Name>,
     ) -> ParseResult<Self> {
+        let mut tokens = tokens.peekable();
+        if tokens.peek() == Some(&"\\#") {
+            tokens.next();
+            return parse_generic(record_type, tokens);
+        }
+
         let rdata = match record_type {
             RecordType::A => Self::A(a::parse(tokens)?),
             RecordType::AAAA => Self::AAAA(aaaa::parse(tokens)?),
             RecordType::ANAME => Self::ANAME(name::parse(tokens, origin)?),
+            RecordType::APL => Self::APL(apl::parse(tokens)?),
             RecordType::ANY => return Err(ParseError::from("parsing ANY doesn't make sense")),
             RecordType::AXFR => return Err(ParseError::from("parsing AXFR doesn't make sense")),
             RecordType::CAA => caa::parse(tokens).map(Self::CAA)?,
             RecordType::CNAME => Self::CNAME(name::parse(tokens, origin)?),
             RecordType::CSYNC => csync::parse(tokens).map(Self::CSYNC)?,
+            RecordType::DHCID => Self::DHCID(dhcid::parse(tokens)?),
+            RecordType::EUI48 => Self::EUI48(eui::parse_eui48(tokens)?),
+            RecordType::EUI64 => Self::EUI64(eui::parse_eui64(tokens)?),
             RecordType::HINFO => Self::HINFO(hinfo::parse(tokens)?),
             RecordType::HTTPS => svcb::parse(tokens).map(Self::SVCB)?,
             RecordType::IXFR => return Err(ParseError::from("parsing IXFR doesn't make sense")),
+            RecordType::LOC => Self::LOC(loc::parse(tokens)?),
             RecordType::MX => Self::MX(mx::parse(tokens, origin)?),
             RecordType::NAPTR => Self::NAPTR(naptr::parse(tokens, origin)?),
             RecordType::NULL => Self::NULL(null::parse(tokens)?),
@@ -85,12 +97,15 @@ impl RDataParser for RData {
             RecordType::OPENPGPKEY => Self::OPENPGPKEY(openpgpkey::parse(tokens)?),
             RecordType::OPT => return Err(ParseError::from("parsing OPT doesn't make sense")),
             RecordType::PTR => Self::PTR(name::parse(tokens, origin)?),
+            RecordType::SMIMEA => Self::SMIMEA(tlsa::parse(tokens)?),
             RecordType::SOA => Self::SOA(soa::parse(tokens, origin)?),
             RecordType::SRV => Self::SRV(srv::parse(tokens, origin)?),
             RecordType::SSHFP => Self::SSHFP(sshfp::parse(tokens)?),
             RecordType::SVCB => svcb::parse(tokens).map(Self::SVCB)?,
             RecordType::TLSA => Self::TLSA(tlsa::parse(tokens)?),
             RecordType::TXT => Self::TXT(txt::parse(tokens)?),
+            RecordType::URI => Self::URI(uri::parse(tokens)?),
+            RecordType::ZONEMD => Self::ZONEMD(zonemd::parse(tokens)?),
             RecordType::SIG => return Err(ParseError::from("parsing SIG doesn't make sense")),
             RecordType::DNSKEY => {
                 return Err(ParseError::from("DNSKEY should be dynamically generated"))
@@ -115,14 +130,20 @@ impl RDataParser for RData {
                     "NSEC3PARAM should be dynamically generated",
                 ))
             }
+            #[cfg(feature = "dnssec")]
+            RecordType::RRSIG => Self::DNSSEC(DNSSECRData::SIG(sig::parse(tokens, origin)?)),
+            #[cfg(not(feature = "dnssec"))]
             RecordType::RRSIG => {
                 return Err(ParseError::from("RRSIG should be dynamically generated"))
             }
+            RecordType::TKEY => {
+                return Err(ParseError::from("TKEY is only used during key negotiation"))
+            }
             RecordType::TSIG => return Err(ParseError::from("TSIG is only used during AXFR")),
             #[allow(deprecated)]
             RecordType::ZERO => Self::ZERO,
             r @ RecordType::Unknown(..) | r => {
-                // TODO: add a way to associate generic record types to the zone
+                // unknown types are only supported in the generic \# form
                 return Err(ParseError::from(ParseErrorKind::UnsupportedRecordType(r)));
             }
         };
@@ -131,6 +152,53 @@ impl RDataParser for RData {
     }
 }
 
+/// Parse the generic RData, after the `\#`, and decode it as the record type
+///
+/// [RFC 3597](https://tools.ietf.org/html/rfc3597#section-5)
+///
+/// ```text
+///    The RDATA section of an RR of unknown type is represented as a
+///    sequence of white space separated words as follows:
+///
+///       The special token \# (a backslash immediately followed by a hash
+///       sign), which identifies the RDATA as having the generic encoding
+///       defined herein rather than a traditional type-specific encoding.
+///
+///       An unsigned decimal integer specifying the RDATA length in octets.
+///
+///       Zero or more words of hexadecimal data encoding the actual RDATA
+///       field, each containing an even number of hexadecimal digits.
+/// ```
+fn parse_generic<'i, I: Iterator<Item = &'i str>>(
+    record_type: RecordType,
+    mut tokens: I,
+) -> ParseResult<RData> {
+    let length: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("length".to_string())))
+        .and_then(|s| s.parse().map_err(Into::into))?;
+
+    let mut data = Vec::with_capacity(length as usize);
+    for token in tokens {
+        data.extend(data_encoding::HEXUPPER_PERMISSIVE.decode(token.as_bytes())?);
+    }
+
+    if data.len() != length as usize {
+        return Err(ParseError::from(format!(
+            "generic rdata length {} does not match the data length {}",
+            length,
+            data.len()
+        )));
+    }
+
+    let mut decoder = BinDecoder::new(&data);
+    Ok(RData::read(
+        &mut decoder,
+        record_type,
+        Restrict::new(length),
+    )?)
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
@@ -138,6 +206,7 @@ mod tests {
     use super::*;
     use crate::rr::domain::Name;
     use crate::rr::rdata::*;
+    use std::net::Ipv4Addr;
     use std::str::FromStr;
 
     #[test]
@@ -298,4 +367,27 @@ mod tests {
             assert!(result.is_err());
         }
     }
+    #[test]
+    fn test_generic() {
+        let record = RData::try_from_str(RecordType::Unknown(65280), "\\# 4 0A000001").unwrap();
+        assert_eq!(
+            record,
+            RData::Unknown {
+                code: 65280,
+                rdata: NULL::with(vec![0x0A, 0, 0, 0x01])
+            }
+        );
+        assert_eq!(record.to_string(), "\\# 4 0A000001");
+
+        let record = RData::try_from_str(RecordType::A, "\\# 4 0A 00 00 01").unwrap();
+        assert_eq!(record, RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+
+        let record = RData::try_from_str(RecordType::NULL, "\\# 0").unwrap();
+        assert_eq!(record, RData::NULL(NULL::new()));
+
+        assert!(RData::try_from_str(RecordType::Unknown(65280), "\\# 3 0A000001").is_err());
+        assert!(RData::try_from_str(RecordType::Unknown(65280), "\\# 4 0A00000").is_err());
+        assert!(RData::try_from_str(RecordType::Unknown(65280), "0A000001").is_err());
+        assert!(RData::try_from_str(RecordType::A, "\\# 3 0A0000").is_err());
+    }
 }
@@ -0,0 +1,45 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! APL records for lists of address prefixes
+
+use std::str::FromStr;
+
+use crate::error::*;
+use crate::rr::rdata::{APLItem, APL};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 3123](https://tools.ietf.org/html/rfc3123#section-5)
+///
+/// ```text
+///    <owner>   IN   <TTL>   APL   {[!]afi:address/prefix}*
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<APL> {
+    let items = tokens
+        .map(|token| APLItem::from_str(token).map_err(ParseError::from))
+        .collect::<ParseResult<Vec<_>>>()?;
+
+    Ok(APL::new(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        let apl = parse(vec!["1:192.168.32.0/21", "!1:192.168.38.0/28"].into_iter()).unwrap();
+        assert_eq!(apl.items().len(), 2);
+        assert!(!apl.items()[0].negation());
+        assert!(apl.items()[1].negation());
+
+        assert!(parse(::std::iter::empty()).unwrap().items().is_empty());
+        assert!(parse(vec!["1:192.168.32.0"].into_iter()).is_err());
+        assert!(parse(vec!["2:192.168.32.0/21"].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DHCID records for associating DHCP clients with their names
+
+use crate::error::*;
+use crate::rr::rdata::DHCID;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4701](https://tools.ietf.org/html/rfc4701#section-3.2)
+///
+/// ```text
+///    In DNS master files, the RDATA is represented as a single block in
+///    base-64 encoding identical to that used for representing binary data
+///    in [3], Section 3.  The data may be divided up into any number of
+///    white-space-separated substrings, each of which is a valid base-64
+///    string, and which are concatenated to obtain the full signature.
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<DHCID> {
+    let encoded = tokens.fold(String::new(), |mut encoded, data| {
+        encoded.push_str(data);
+        encoded
+    });
+
+    if encoded.is_empty() {
+        return Err(ParseErrorKind::Message("DHCID data is missing").into());
+    }
+
+    let data = data_encoding::BASE64.decode(encoded.as_bytes())?;
+    Ok(DHCID::new(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        let dhcid = parse(vec!["AAIBY2/AuCccgoJbsaxcQc9TUapptP69", "lOjxfNuVAA2kjEA="].into_iter())
+            .unwrap();
+        assert_eq!(dhcid.identifier_type(), Some(2));
+        assert_eq!(dhcid.digest_type(), Some(1));
+        assert_eq!(dhcid.digest().len(), 32);
+
+        assert!(parse(::std::iter::empty()).is_err());
+        assert!(parse(vec!["äöüäööüä"].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! EUI48 and EUI64 records for storing MAC addresses
+
+use std::str::FromStr;
+
+use crate::error::*;
+use crate::rr::rdata::{EUI48, EUI64};
+
+/// Parse the EUI48 RData from a set of Tokens
+///
+/// [RFC 7043](https://tools.ietf.org/html/rfc7043#section-3.2)
+///
+/// ```text
+///    host.example. 86400 IN EUI48 00-00-5e-00-53-2a
+/// ```
+pub(crate) fn parse_eui48<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<EUI48> {
+    EUI48::from_str(single_token(tokens)?).map_err(Into::into)
+}
+
+/// Parse the EUI64 RData from a set of Tokens
+///
+/// [RFC 7043](https://tools.ietf.org/html/rfc7043#section-4.2)
+///
+/// ```text
+///    host.example. 86400 IN EUI64 00-00-5e-ef-10-00-00-2a
+/// ```
+pub(crate) fn parse_eui64<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<EUI64> {
+    EUI64::from_str(single_token(tokens)?).map_err(Into::into)
+}
+
+fn single_token<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<&'i str> {
+    let address = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("address".to_string())))?;
+
+    if tokens.next().is_some() {
+        return Err(ParseErrorKind::Message("too many fields for EUI address").into());
+    }
+
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!(
+            parse_eui48(vec!["00-00-5e-00-53-2a"].into_iter()).unwrap(),
+            EUI48::new([0x00, 0x00, 0x5e, 0x00, 0x53, 0x2a])
+        );
+        assert_eq!(
+            parse_eui64(vec!["00-00-5e-ef-10-00-00-2a"].into_iter()).unwrap(),
+            EUI64::new([0x00, 0x00, 0x5e, 0xef, 0x10, 0x00, 0x00, 0x2a])
+        );
+
+        assert!(parse_eui48(::std::iter::empty()).is_err());
+        assert!(parse_eui48(vec!["00-00-5e-00-53-2a", "00"].into_iter()).is_err());
+        assert!(parse_eui48(vec!["00-00-5e-ef-10-00-00-2a"].into_iter()).is_err());
+        assert!(parse_eui64(vec!["00-00-5e-00-53-2a"].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! LOC records for expressing location information
+
+use std::str::FromStr;
+
+use crate::error::*;
+use crate::rr::rdata::LOC;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 1876](https://tools.ietf.org/html/rfc1876#section-3)
+///
+/// ```text
+///    <owner> <TTL> <class> LOC ( d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]]
+///                                {"E"|"W"} alt["m"] [siz["m"] [hp["m"]
+///                                [vp["m"]]]] )
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<LOC> {
+    let loc = tokens.collect::<Vec<_>>().join(" ");
+    LOC::from_str(&loc).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!(
+            parse(vec!["42", "21", "54", "N", "71", "06", "18", "W", "-24m", "30m"].into_iter())
+                .unwrap(),
+            LOC::from_degrees(42.365, -71.105, -24.0)
+                .unwrap()
+                .with_precision(3000, 1_000_000, 1000)
+        );
+
+        assert!(parse(::std::iter::empty()).is_err());
+        assert!(parse(vec!["42", "21", "54", "N", "71", "06", "18", "W"].into_iter()).is_err());
+    }
+}
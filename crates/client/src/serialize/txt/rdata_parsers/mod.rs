@@ -21,19 +21,27 @@
 // each of these module's has the parser for that rdata embedded, to keep the file sizes down...
 pub(crate) mod a;
 pub(crate) mod aaaa;
+pub(crate) mod apl;
 pub(crate) mod caa;
 pub(crate) mod csync;
+pub(crate) mod dhcid;
 #[cfg(feature = "dnssec")]
 pub(crate) mod ds;
+pub(crate) mod eui;
 pub(crate) mod hinfo;
+pub(crate) mod loc;
 pub(crate) mod mx;
 pub(crate) mod name;
 pub(crate) mod naptr;
 pub(crate) mod null;
 pub(crate) mod openpgpkey;
+#[cfg(feature = "dnssec")]
+pub(crate) mod sig;
 pub(crate) mod soa;
 pub(crate) mod srv;
 pub(crate) mod sshfp;
 pub(crate) mod svcb;
 pub(crate) mod tlsa;
 pub(crate) mod txt;
+pub(crate) mod uri;
+pub(crate) mod zonemd;
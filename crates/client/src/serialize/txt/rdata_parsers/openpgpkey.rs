@@ -22,14 +22,19 @@ use crate::rr::rdata::OPENPGPKEY;
 ///    Section 11.1 of [RFC4880] encoded in base64 as defined in Section 4
 ///    of [RFC4648].
 /// ```
-pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<OPENPGPKEY> {
-    let encoded_public_key = tokens.next().ok_or(ParseErrorKind::Message(
-        "OPENPGPKEY public key field is missing",
-    ))?;
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(tokens: I) -> ParseResult<OPENPGPKEY> {
+    // the base64 may be split over multiple tokens, like the TLSA hex data
+    let encoded_public_key = tokens.fold(String::new(), |mut encoded, data| {
+        encoded.push_str(data);
+        encoded
+    });
+
+    if encoded_public_key.is_empty() {
+        return Err(ParseErrorKind::Message("OPENPGPKEY public key field is missing").into());
+    }
+
     let public_key = data_encoding::BASE64.decode(encoded_public_key.as_bytes())?;
-    Some(OPENPGPKEY::new(public_key))
-        .filter(|_| tokens.next().is_none())
-        .ok_or_else(|| ParseErrorKind::Message("too many fields for OPENPGPKEY").into())
+    Ok(OPENPGPKEY::new(public_key))
 }
 
 #[test]
@@ -44,4 +49,7 @@ fn test_parsing() {
     assert!(parse(vec!["c2VsZi1wcmFpc2Ugc3Rpbmtz"].into_iter())
         .map(|rd| rd == OPENPGPKEY::new(b"self-praise stinks".to_vec()))
         .unwrap_or(false));
+    assert!(parse(vec!["c2VsZi1wcmFp", "c2Ugc3Rpbmtz"].into_iter())
+        .map(|rd| rd == OPENPGPKEY::new(b"self-praise stinks".to_vec()))
+        .unwrap_or(false));
 }
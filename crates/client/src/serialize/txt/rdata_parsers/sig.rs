@@ -0,0 +1,196 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Parser for RRSIG text form, e.g. of zones which were signed offline
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+use crate::error::*;
+use crate::proto::rr::dnssec::rdata::SIG;
+use crate::proto::rr::dnssec::Algorithm;
+use crate::rr::{Name, RecordType};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 4034, Resource Records for the DNS Security Extensions](https://datatracker.ietf.org/doc/html/rfc4034#section-3.2)
+/// ```text
+/// 3.2.  The RRSIG RR Presentation Format
+///
+///    The Type Covered field is represented as an RR type mnemonic.
+///
+///    The Algorithm field value MUST be represented either as an unsigned
+///    decimal integer or as an algorithm mnemonic, as specified in Appendix
+///    A.1.
+///
+///    The Labels field value MUST be represented as an unsigned decimal
+///    integer.
+///
+///    The Original TTL field value MUST be represented as an unsigned
+///    decimal integer.
+///
+///    The Signature Expiration Time and Inception Time field values MUST be
+///    represented either as an unsigned decimal integer indicating seconds
+///    since 1 January 1970 00:00:00 UTC, or in the form YYYYMMDDHHmmSS in
+///    UTC, where:
+///
+///       YYYY is the year (0001-9999, but see Section 3.1.5);
+///       MM is the month number (01-12);
+///       DD is the day of the month (01-31);
+///       HH is the hour, in 24 hour notation (00-23);
+///       mm is the minute (00-59); and
+///       SS is the second (00-59).
+///
+///    Note that it is always possible to distinguish between these two
+///    formats because the YYYYMMDDHHmmSS format will always be exactly 14
+///    digits, while the decimal representation of a 32-bit unsigned integer
+///    can never be longer than 10 digits.
+///
+///    The Key Tag field MUST be represented as an unsigned decimal integer.
+///
+///    The Signer's Name field value MUST be represented as a domain name.
+///
+///    The Signature field is represented as a Base64 encoding of the
+///    signature.  Whitespace is allowed within the Base64 text.
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(
+    mut tokens: I,
+    origin: Option<&Name>,
+) -> ParseResult<SIG> {
+    let mut next = |field: &str| {
+        tokens
+            .next()
+            .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken(field.to_string())))
+    };
+
+    let type_covered = RecordType::from_str(next("type covered")?)?;
+    let algorithm = parse_algorithm(next("algorithm")?)?;
+    let num_labels: u8 = next("labels")?.parse()?;
+    let original_ttl: u32 = next("original ttl")?.parse()?;
+    let sig_expiration = parse_sig_time(next("signature expiration")?)?;
+    let sig_inception = parse_sig_time(next("signature inception")?)?;
+    let key_tag: u16 = next("key tag")?.parse()?;
+    let signer_name = Name::parse(next("signer's name")?, origin)?;
+
+    let signature = tokens.collect::<String>();
+    if signature.is_empty() {
+        return Err(ParseErrorKind::Message("signature not present").into());
+    }
+    let sig = data_encoding::BASE64.decode(signature.as_bytes())?;
+
+    Ok(SIG::new(
+        type_covered,
+        algorithm,
+        num_labels,
+        original_ttl,
+        sig_expiration,
+        sig_inception,
+        key_tag,
+        signer_name,
+        sig,
+    ))
+}
+
+#[allow(deprecated)]
+fn parse_algorithm(algorithm: &str) -> ParseResult<Algorithm> {
+    const MNEMONICS: [Algorithm; 10] = [
+        Algorithm::RSAMD5,
+        Algorithm::DSA,
+        Algorithm::RSASHA1,
+        Algorithm::RSASHA1NSEC3SHA1,
+        Algorithm::RSASHA256,
+        Algorithm::RSASHA512,
+        Algorithm::ECDSAP256SHA256,
+        Algorithm::ECDSAP384SHA384,
+        Algorithm::ED25519,
+        Algorithm::ED448,
+    ];
+
+    match MNEMONICS
+        .iter()
+        .find(|known| known.as_str().eq_ignore_ascii_case(algorithm))
+    {
+        Some(known) => Ok(*known),
+        None => Ok(Algorithm::from_u8(algorithm.parse()?)),
+    }
+}
+
+/// Seconds since the epoch, in serial number arithmetic for dates past 2106, see RFC 4034
+fn parse_sig_time(time: &str) -> ParseResult<u32> {
+    if time.len() != 14 {
+        return Ok(time.parse()?);
+    }
+
+    let invalid = || ParseError::from(ParseErrorKind::ParseTime(time.to_string()));
+    let field = |range: std::ops::Range<usize>| -> ParseResult<u16> {
+        time.get(range)
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(invalid)
+    };
+
+    let month = Month::try_from(field(4..6)? as u8).map_err(|_| invalid())?;
+    let date = Date::from_calendar_date(i32::from(field(0..4)?), month, field(6..8)? as u8)
+        .map_err(|_| invalid())?;
+    let time_of_day = Time::from_hms(
+        field(8..10)? as u8,
+        field(10..12)? as u8,
+        field(12..14)? as u8,
+    )
+    .map_err(|_| invalid())?;
+
+    let timestamp = PrimitiveDateTime::new(date, time_of_day)
+        .assume_utc()
+        .unix_timestamp();
+    Ok(timestamp as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let sig = parse(
+            "A 13 3 3600 20300101000000 1893456000 12345 example.com. AAEC AwQ=".split(' '),
+            Some(&origin),
+        )
+        .unwrap();
+
+        assert_eq!(sig.type_covered(), RecordType::A);
+        assert_eq!(sig.algorithm(), Algorithm::ECDSAP256SHA256);
+        assert_eq!(sig.num_labels(), 3);
+        assert_eq!(sig.original_ttl(), 3600);
+        assert_eq!(sig.sig_expiration(), 1_893_456_000);
+        assert_eq!(sig.sig_inception(), 1_893_456_000);
+        assert_eq!(sig.key_tag(), 12345);
+        assert_eq!(sig.signer_name(), &origin);
+        assert_eq!(sig.sig(), &[0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_parsing_mnemonic_and_display() {
+        let sig = parse(
+            "SOA RSASHA256 2 86400 1893456000 1262304000 1 . AAEC".split(' '),
+            None,
+        )
+        .unwrap();
+        assert_eq!(sig.algorithm(), Algorithm::RSASHA256);
+
+        // the displayed form is parsed back to the same RRSIG
+        let text = sig.to_string();
+        assert_eq!(parse(text.split(' '), None).unwrap(), sig);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse("A 13 3 3600 20301301000000 1 1 . AAEC".split(' '), None).is_err());
+        assert!(parse("A 13 3 3600 1 1 1 .".split(' '), None).is_err());
+    }
+}
@@ -116,12 +116,12 @@ fn parse_value(key: SvcParamKey, value: Option<&str>) -> Result<SvcParamValue, P
         SvcParamKey::Ipv4Hint => parse_ipv4_hint(value),
         SvcParamKey::EchConfig => parse_ech_config(value),
         SvcParamKey::Ipv6Hint => parse_ipv6_hint(value),
-        SvcParamKey::Key(_) => parse_unknown(value),
-        SvcParamKey::Key65535 | SvcParamKey::Unknown(_) => {
-            Err(ParseError::from(ParseErrorKind::Message(
-                "Bad Key type or unsupported, see generic key option, e.g. key1234",
-            )))
-        }
+        SvcParamKey::DohPath => parse_doh_path(value),
+        SvcParamKey::Ohttp => parse_ohttp(value),
+        SvcParamKey::Key(_) | SvcParamKey::Unknown(_) => parse_unknown(value),
+        SvcParamKey::Key65535 => Err(ParseError::from(ParseErrorKind::Message(
+            "key65535 is reserved and must not be used",
+        ))),
     }
 }
 
@@ -275,6 +275,36 @@ fn parse_ipv6_hint(value: Option<&str>) -> Result<SvcParamValue, ParseError> {
     Ok(SvcParamValue::Ipv6Hint(IpHint(hints)))
 }
 
+/// [RFC 9461 Service Binding Mapping for DNS Servers, November 2023](https://www.rfc-editor.org/rfc/rfc9461#section-5)
+/// ```text
+/// "dohpath" is a single-valued SvcParamKey whose value (in both
+///   presentation format and wire format) MUST be a URI Template in
+///   relative form ([RFC6570], Section 1.1) encoded in UTF-8 [RFC3629].
+/// ```
+fn parse_doh_path(value: Option<&str>) -> Result<SvcParamValue, ParseError> {
+    let value = value.ok_or_else(|| {
+        ParseError::from(ParseErrorKind::Message(
+            "expected a URI template for dohpath",
+        ))
+    })?;
+
+    let value = parse_char_data(value)?;
+    Ok(SvcParamValue::DohPath(DohPath(value)))
+}
+
+/// [RFC 9540 Discovery of Oblivious Services via SVCB, February 2024](https://www.rfc-editor.org/rfc/rfc9540#section-4)
+/// ```text
+/// Both the presentation and wire-format values for the "ohttp"
+///   parameter MUST be empty.
+/// ```
+fn parse_ohttp(value: Option<&str>) -> Result<SvcParamValue, ParseError> {
+    if value.is_some() {
+        return Err(ParseErrorKind::Message("no value expected for ohttp").into());
+    }
+
+    Ok(SvcParamValue::Ohttp)
+}
+
 /// [draft-ietf-dnsop-svcb-https-03 SVCB and HTTPS RRs for DNS, February 2021](https://datatracker.ietf.org/doc/html/draft-ietf-dnsop-svcb-https-03#section-2.1)
 /// ```text
 /// Unrecognized keys are represented in presentation format as
@@ -292,7 +322,7 @@ fn parse_ipv6_hint(value: Option<&str>) -> Result<SvcParamValue, ParseError> {
 /// ```
 fn parse_unknown(value: Option<&str>) -> Result<SvcParamValue, ParseError> {
     let unknown: Vec<u8> = if let Some(value) = value {
-        value.as_bytes().to_vec()
+        parse_char_data(value)?.into_bytes()
     } else {
         Vec::new()
     };
@@ -405,6 +435,32 @@ mod tests {
         assert_eq!(svcb, svcb_display);
     }
 
+    #[test]
+    fn test_parsing_rfc9460_params() {
+        let svcb = parse_record("doh.example.net. 300 IN SVCB 1 . alpn=h2 dohpath=/dns-query{?dns} ohttp key667=\"hello\"");
+
+        let mut params = svcb.svc_params().iter();
+        assert_eq!(params.next().expect("alpn").0, SvcParamKey::Alpn);
+
+        let param = params.next().expect("dohpath");
+        assert_eq!(SvcParamKey::DohPath, param.0);
+        assert_eq!(
+            param.1.as_doh_path().expect("dohpath").0,
+            "/dns-query{?dns}"
+        );
+
+        let param = params.next().expect("ohttp");
+        assert_eq!((SvcParamKey::Ohttp, SvcParamValue::Ohttp), *param);
+
+        let param = params.next().expect("key667");
+        assert_eq!(SvcParamKey::Unknown(667), param.0);
+        assert_eq!(param.1.as_unknown().expect("key667").0, b"hello");
+
+        // and back from the presentation format
+        let svcb_display = format!("doh.example.net. 300 IN SVCB {}", svcb);
+        assert_eq!(svcb, parse_record(&svcb_display));
+    }
+
     /// sanity check for https
     #[test]
     fn test_parsing_https() {
@@ -0,0 +1,61 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! URI records for publishing mappings from hostnames to URIs
+
+use std::str::FromStr;
+
+use crate::error::*;
+use crate::rr::rdata::URI;
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 7553](https://tools.ietf.org/html/rfc7553#section-4.4)
+///
+/// ```text
+///    _ftp._tcp    IN URI 10 1 "ftp://ftp1.example.com/public"
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<URI> {
+    let priority: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("priority".to_string())))
+        .and_then(|s| u16::from_str(s).map_err(Into::into))?;
+
+    let weight: u16 = tokens
+        .next()
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("weight".to_string())))
+        .and_then(|s| u16::from_str(s).map_err(Into::into))?;
+
+    let target = tokens
+        .next()
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| ParseError::from(ParseErrorKind::MissingToken("target".to_string())))?;
+
+    if tokens.next().is_some() {
+        return Err(ParseErrorKind::Message("too many fields for URI").into());
+    }
+
+    Ok(URI::new(priority, weight, target.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!(
+            parse(vec!["10", "1", "ftp://ftp1.example.com/public"].into_iter()).unwrap(),
+            URI::new(10, 1, "ftp://ftp1.example.com/public".to_string())
+        );
+
+        assert!(parse(vec!["10", "1"].into_iter()).is_err());
+        assert!(parse(vec!["10", "1", ""].into_iter()).is_err());
+        assert!(parse(vec!["10", "x", "ftp://a"].into_iter()).is_err());
+        assert!(parse(vec!["10", "1", "ftp://a", "ftp://b"].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! ZONEMD records for message digests of zones
+
+use crate::error::*;
+use crate::rr::rdata::{sshfp, ZONEMD};
+
+/// Parse the RData from a set of Tokens
+///
+/// [RFC 8976](https://tools.ietf.org/html/rfc8976#section-2.3)
+///
+/// ```text
+/// 2.3.  ZONEMD Presentation Format
+///
+///    The presentation format of the RDATA portion is as follows:
+///
+///    *  The Serial field is represented as an unsigned decimal integer.
+///
+///    *  The Scheme field is represented as an unsigned decimal integer.
+///
+///    *  The Hash Algorithm field is represented as an unsigned decimal
+///       integer.
+///
+///    *  The Digest is represented as a sequence of case-insensitive
+///       hexadecimal digits.  Whitespace is allowed within the hexadecimal
+///       text.
+/// ```
+pub(crate) fn parse<'i, I: Iterator<Item = &'i str>>(mut tokens: I) -> ParseResult<ZONEMD> {
+    fn missing_field<E: From<ParseErrorKind>>(field: &str) -> E {
+        ParseErrorKind::Msg(format!("ZONEMD {} field missing", field)).into()
+    }
+
+    let serial = tokens
+        .next()
+        .ok_or_else(|| missing_field::<ParseError>("serial"))?
+        .parse::<u32>()?;
+    let (scheme, hash_algorithm) = {
+        let mut parse_u8 = |field: &str| {
+            tokens
+                .next()
+                .ok_or_else(|| missing_field(field))
+                .and_then(|t| t.parse::<u8>().map_err(ParseError::from))
+        };
+        (
+            parse_u8("scheme")?.into(),
+            parse_u8("hash algorithm")?.into(),
+        )
+    };
+
+    let digest: String = tokens.collect();
+    if digest.is_empty() {
+        return Err(missing_field("digest"));
+    }
+    let digest = sshfp::HEX.decode(digest.as_bytes())?;
+
+    Ok(ZONEMD::new(serial, scheme, hash_algorithm, digest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rr::rdata::zonemd::{HashAlgorithm, Scheme};
+
+    #[test]
+    fn test_parsing() {
+        assert!(parse(::std::iter::empty()).is_err());
+        assert!(parse(vec!["2018031900", "1"].into_iter()).is_err());
+        assert!(parse(vec!["2018031900", "1", "1"].into_iter()).is_err());
+        assert!(parse(vec!["2018031900", "1", "1", "xyz"].into_iter()).is_err());
+
+        // RFC 8976 appendix A.1, the digest is split over several tokens
+        assert_eq!(
+            parse(
+                vec![
+                    "2018031900",
+                    "1",
+                    "1",
+                    "c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3",
+                    "a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c",
+                ]
+                .into_iter()
+            )
+            .unwrap(),
+            ZONEMD::new(
+                2018031900,
+                Scheme::Simple,
+                HashAlgorithm::SHA384,
+                sshfp::HEX
+                    .decode(
+                        b"c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3\
+                          a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c"
+                    )
+                    .unwrap(),
+            )
+        );
+    }
+}
@@ -14,8 +14,12 @@
  * limitations under the License.
  */
 use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use tracing::debug;
+
 use crate::error::*;
 use crate::rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
 use crate::serialize::txt::parse_rdata::RDataParser;
@@ -124,6 +128,29 @@ use crate::serialize::txt::zone_lex::{Lexer, Token};
 #[derive(Clone, Copy, Default)]
 pub struct Parser;
 
+/// Max traversal depth for $INCLUDE files
+const MAX_INCLUDE_LEVEL: u16 = 256;
+
+/// State shared by a file and all the files it includes
+struct Context {
+    class: Option<DNSClass>,
+    ttl: Option<u32>,
+    records: BTreeMap<RrKey, RecordSet>,
+    /// The files being parsed, from the outermost one to the current $INCLUDE
+    files: Vec<PathBuf>,
+}
+
+impl Context {
+    fn new(class: Option<DNSClass>) -> Self {
+        Self {
+            class,
+            ttl: None,
+            records: BTreeMap::new(),
+            files: Vec::new(),
+        }
+    }
+}
+
 impl Parser {
     /// Returns a new Zone file parser
     pub fn new() -> Self {
@@ -132,6 +159,9 @@ impl Parser {
 
     /// Parse a file from the Lexer
     ///
+    /// `$INCLUDE` is not supported, as there is no file to resolve the included path against, see
+    ///  [`Self::parse_file`].
+    ///
     /// # Return
     ///
     /// A pair of the Zone origin name and a map of all Keys to RecordSets
@@ -141,14 +171,59 @@ impl Parser {
         origin: Option<Name>,
         class: Option<DNSClass>,
     ) -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
+        let mut cx = Context::new(class);
+        let origin = self.parse_lexer(lexer, origin, None, &mut cx)?;
+
+        //
+        // build the Authority and return.
+        let origin = origin.ok_or_else(|| {
+            ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))
+        })?;
+        Ok((origin, cx.records))
+    }
+
+    /// Parse the zone file at `path`, including any files referenced with `$INCLUDE`
+    ///
+    /// Relative `$INCLUDE` paths are resolved against the directory of the file containing the
+    ///  directive.
+    ///
+    /// # Return
+    ///
+    /// A pair of the Zone origin name and a map of all Keys to RecordSets
+    pub fn parse_file(
+        &mut self,
+        path: &Path,
+        origin: Option<Name>,
+        class: Option<DNSClass>,
+    ) -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
+        let buf = Self::read_file(path)?;
+
+        let mut cx = Context::new(class);
+        cx.files.push(Self::canonical_path(path));
+        let origin = self.parse_lexer(Lexer::new(&buf), origin, Some(path), &mut cx)?;
+
+        let origin = origin.ok_or_else(|| {
+            ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))
+        })?;
+        Ok((origin, cx.records))
+    }
+
+    /// Parses all entries from the lexer into the context, returning the last origin
+    ///
+    /// The origin and owner name are scoped to the lexer, i.e. an included file, while the
+    ///  records, class and TTL are shared through the context.
+    fn parse_lexer(
+        &mut self,
+        lexer: Lexer<'_>,
+        origin: Option<Name>,
+        path: Option<&Path>,
+        cx: &mut Context,
+    ) -> ParseResult<Option<Name>> {
         let mut lexer = lexer;
-        let mut records: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
 
         let mut origin: Option<Name> = origin;
-        let mut class: Option<DNSClass> = class;
         let mut current_name: Option<Name> = None;
         let mut rtype: Option<RecordType> = None;
-        let mut ttl: Option<u32> = None;
         let mut state = State::StartLine;
 
         while let Some(t) = lexer.next_token()? {
@@ -158,12 +233,11 @@ impl Parser {
                     rtype = None;
 
                     match t {
-                        // if Dollar, then $INCLUDE or $ORIGIN
-                        Token::Include => {
-                            return Err(ParseError::from(ParseErrorKind::Message("The parser does not support $INCLUDE. Consider inlining file before parsing")))
-                        },
+                        // if Dollar, then $INCLUDE, $ORIGIN, $TTL or $GENERATE
+                        Token::Include => State::Include(None),
                         Token::Origin => State::Origin,
                         Token::Ttl => State::Ttl,
+                        Token::Generate => State::Generate(vec![]),
 
                         // if CharData, then Name then ttl_class_type
                         Token::CharData(data) => {
@@ -185,7 +259,7 @@ impl Parser {
                 }
                 State::Ttl => match t {
                     Token::CharData(data) => {
-                        ttl = Some(Self::parse_time(&data)?);
+                        cx.ttl = Some(Self::parse_time(&data)?);
                         State::StartLine
                     }
                     _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
@@ -200,9 +274,42 @@ impl Parser {
                         _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
                     }
                 }
-                State::Include => return Err(ParseError::from(ParseErrorKind::Message(
-                    "The parser does not support $INCLUDE. Consider inlining file before parsing",
-                ))),
+                State::Include(None) => match t {
+                    Token::CharData(include) => State::Include(Some(include)),
+                    _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+                },
+                State::Include(Some(include)) => match t {
+                    // the domain name only sets the origin of the included file, never this one
+                    Token::CharData(data) => {
+                        let include_origin = Name::parse(&data, origin.as_ref())?;
+                        self.include(&include, Some(include_origin), path, cx)?;
+                        State::StartLine
+                    }
+                    Token::At => {
+                        self.include(&include, origin.clone(), path, cx)?;
+                        State::StartLine
+                    }
+                    Token::EOL => {
+                        self.include(&include, origin.clone(), path, cx)?;
+                        State::StartLine
+                    }
+                    _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+                },
+                State::Generate(mut parts) => match t {
+                    Token::EOL => {
+                        Self::generate(parts, &origin, cx)?;
+                        State::StartLine
+                    }
+                    Token::CharData(part) => {
+                        parts.push(part);
+                        State::Generate(parts)
+                    }
+                    Token::At => {
+                        parts.push("@".to_string());
+                        State::Generate(parts)
+                    }
+                    _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+                },
                 State::TtlClassType => {
                     match t {
                         // if number, TTL
@@ -212,14 +319,14 @@ impl Parser {
                             // if it's a number it's a ttl
                             let result: ParseResult<u32> = Self::parse_time(&data);
                             if result.is_ok() {
-                                ttl = result.ok();
+                                cx.ttl = result.ok();
                                 State::TtlClassType // hm, should this go to just ClassType?
                             } else {
                                 // if can parse DNSClass, then class
                                 data.make_ascii_uppercase();
                                 let result = DNSClass::from_str(&data);
                                 if result.is_ok() {
-                                    class = result.ok();
+                                    cx.class = result.ok();
                                     State::TtlClassType
                                 } else {
                                     // if can parse RecordType, then RecordType
@@ -245,9 +352,9 @@ impl Parser {
                                 &origin,
                                 &current_name,
                                 rtype,
-                                &mut ttl,
-                                class,
-                                &mut records,
+                                &mut cx.ttl,
+                                cx.class,
+                                &mut cx.records,
                             )?;
                             State::StartLine
                         }
@@ -269,24 +376,297 @@ impl Parser {
         }
 
         //Extra flush at the end for the case of missing endline
-        if let State::Record(record_parts) = state {
-            Self::flush_record(
+        match state {
+            State::Record(record_parts) => Self::flush_record(
                 record_parts,
                 &origin,
                 &current_name,
                 rtype,
+                &mut cx.ttl,
+                cx.class,
+                &mut cx.records,
+            )?,
+            State::Include(Some(include)) => self.include(&include, origin.clone(), path, cx)?,
+            State::Generate(parts) => Self::generate(parts, &origin, cx)?,
+            _ => (),
+        }
+
+        Ok(origin)
+    }
+
+    fn read_file(path: &Path) -> ParseResult<String> {
+        fs::read_to_string(path).map_err(|e| {
+            ParseErrorKind::Msg(format!("failed to read {}: {}", path.display(), e)).into()
+        })
+    }
+
+    /// Parses the included file into the context, with `origin` as its initial origin
+    fn include(
+        &mut self,
+        include: &str,
+        origin: Option<Name>,
+        path: Option<&Path>,
+        cx: &mut Context,
+    ) -> ParseResult<()> {
+        let path = path.ok_or_else(|| {
+            ParseError::from(ParseErrorKind::Message(
+                "$INCLUDE is only supported when parsing a file, see Parser::parse_file",
+            ))
+        })?;
+
+        // RFC1035 (section 5) does not specify how filename for $INCLUDE
+        // should be resolved into file path. The underlying code implements the
+        // following:
+        // * if the path is absolute (relies on Path::is_absolute), it uses normalized path
+        // * otherwise, it joins the path with parent root of the current file
+        //
+        // TODO: Inlining files specified using non-relative path might potentially introduce
+        // security issue in some cases (e.g. when working with zone files from untrusted sources)
+        // and should probably be configurable by user.
+        let include = Path::new(include);
+        let include_path = match path.parent() {
+            Some(parent) if !include.is_absolute() => parent.join(include),
+            _ => include.to_path_buf(),
+        };
+
+        let canonical_path = Self::canonical_path(&include_path);
+        if cx.files.contains(&canonical_path) {
+            return Err(ParseErrorKind::Msg(format!(
+                "cycle of $INCLUDE at {}, trying to include {}",
+                path.display(),
+                include_path.display()
+            ))
+            .into());
+        }
+
+        if cx.files.len() > usize::from(MAX_INCLUDE_LEVEL) {
+            return Err(ParseErrorKind::Msg(format!(
+                "max depth level for nested $INCLUDE is reached at {}, trying to include {}",
+                path.display(),
+                include_path.display()
+            ))
+            .into());
+        }
+
+        debug!(
+            "including file {} into {}",
+            include_path.display(),
+            path.display()
+        );
+
+        let buf = Self::read_file(&include_path)?;
+
+        cx.files.push(canonical_path);
+        let result = self.parse_lexer(Lexer::new(&buf), origin, Some(&include_path), cx);
+        cx.files.pop();
+
+        result.map(drop)
+    }
+
+    /// The path used to detect cycles of $INCLUDE, the same file may be reached by different paths
+    fn canonical_path(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// Generates the records of a BIND `$GENERATE` entry
+    ///
+    /// ```text
+    /// $GENERATE range lhs [ttl] [class] type rhs [comment]
+    /// ```
+    ///
+    /// The `range` is `start-stop[/step]`, and for each value in it a record is created with the
+    ///  owner name from `lhs` and the RData from `rhs`, after substituting `$` in both with the
+    ///  value, see [`Self::generate_text`]. The TTL and class default as for other records.
+    fn generate(parts: Vec<String>, origin: &Option<Name>, cx: &mut Context) -> ParseResult<()> {
+        let mut parts = parts.into_iter();
+        let mut next = |what: &str| {
+            parts.next().ok_or_else(|| {
+                ParseError::from(ParseErrorKind::MissingToken(format!("$GENERATE {}", what)))
+            })
+        };
+
+        let range = next("range")?;
+        let (start, stop, step) = Self::parse_range(&range)?;
+        let lhs = next("lhs")?;
+
+        let mut ttl = cx.ttl;
+        let mut class = cx.class;
+        let rtype = loop {
+            let mut data = next("type")?;
+            if let Ok(generate_ttl) = Self::parse_time(&data) {
+                ttl = Some(generate_ttl);
+                continue;
+            }
+
+            data.make_ascii_uppercase();
+            if let Ok(generate_class) = DNSClass::from_str(&data) {
+                class = Some(generate_class);
+                continue;
+            }
+
+            break RecordType::from_str(&data)?;
+        };
+
+        let rhs = parts.collect::<Vec<_>>();
+        if rhs.is_empty() {
+            return Err(ParseErrorKind::MissingToken("$GENERATE rhs".to_string()).into());
+        }
+
+        for value in (start..=stop).step_by(step) {
+            let owner = Self::generate_text(&lhs, value)?;
+            let owner = if owner == "@" {
+                origin.clone()
+            } else {
+                Some(Name::parse(&owner, origin.as_ref())?)
+            };
+
+            let record_parts = rhs
+                .iter()
+                .map(|part| Self::generate_text(part, value))
+                .collect::<ParseResult<Vec<_>>>()?;
+
+            Self::flush_record(
+                record_parts,
+                origin,
+                &owner,
+                Some(rtype),
                 &mut ttl,
                 class,
-                &mut records,
+                &mut cx.records,
             )?;
         }
 
-        //
-        // build the Authority and return.
-        let origin = origin.ok_or_else(|| {
-            ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))
-        })?;
-        Ok((origin, records))
+        Ok(())
+    }
+
+    /// Parses the `start-stop[/step]` range of a `$GENERATE`
+    fn parse_range(range: &str) -> ParseResult<(u32, u32, usize)> {
+        let invalid = || {
+            ParseError::from(ParseErrorKind::Msg(format!(
+                "invalid $GENERATE range: {}",
+                range
+            )))
+        };
+
+        let (range, step) = match range.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().map_err(|_| invalid())?),
+            None => (range, 1),
+        };
+        let (start, stop) = range.split_once('-').ok_or_else(invalid)?;
+        let start = start.parse::<u32>().map_err(|_| invalid())?;
+        let stop = stop.parse::<u32>().map_err(|_| invalid())?;
+
+        if step == 0 || stop < start {
+            return Err(invalid());
+        }
+
+        Ok((start, stop, step))
+    }
+
+    /// Substitutes the `$GENERATE` iterator into the text
+    ///
+    /// `$` is replaced with the value, and `${offset[,width[,base]]}` with the value plus the
+    ///  offset, zero padded to the width, in the base `d` (decimal), `o` (octal), `x` (lowercase
+    ///  hexadecimal) or `X` (uppercase hexadecimal). `\$` is a literal `$`, other escapes are
+    ///  left for the name or RData parser.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use trust_dns_client::serialize::txt::Parser;
+    ///
+    /// assert_eq!(Parser::generate_text("host-$", 10).unwrap(), "host-10");
+    /// assert_eq!(Parser::generate_text("host-${5}", 10).unwrap(), "host-15");
+    /// assert_eq!(Parser::generate_text("host-${-1,3}", 10).unwrap(), "host-009");
+    /// assert_eq!(Parser::generate_text("host-${0,4,X}", 255).unwrap(), "host-00FF");
+    /// assert_eq!(Parser::generate_text("\\$-$", 1).unwrap(), "$-1");
+    /// assert!(Parser::generate_text("host-${-11}", 10).is_err());
+    /// assert!(Parser::generate_text("host-${0", 10).is_err());
+    /// ```
+    pub fn generate_text(template: &str, value: u32) -> ParseResult<String> {
+        let mut text = String::with_capacity(template.len());
+        let mut chars = template.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => match chars.next() {
+                    Some('$') => text.push('$'),
+                    Some(escaped) => {
+                        text.push('\\');
+                        text.push(escaped);
+                    }
+                    None => text.push('\\'),
+                },
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+
+                    let mut modifier = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => modifier.push(ch),
+                            None => {
+                                return Err(ParseErrorKind::Msg(format!(
+                                    "unclosed $GENERATE modifier: {}",
+                                    template
+                                ))
+                                .into())
+                            }
+                        }
+                    }
+
+                    Self::generate_modifier(&mut text, &modifier, value)?;
+                }
+                '$' => text.push_str(&value.to_string()),
+                ch => text.push(ch),
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Writes the value with the `offset[,width[,base]]` modifier applied
+    fn generate_modifier(text: &mut String, modifier: &str, value: u32) -> ParseResult<()> {
+        let invalid = || {
+            ParseError::from(ParseErrorKind::Msg(format!(
+                "invalid $GENERATE modifier: {}",
+                modifier
+            )))
+        };
+
+        let mut modifier_parts = modifier.split(',');
+        let offset = match modifier_parts.next() {
+            Some(offset) if !offset.is_empty() => offset.parse::<i64>().map_err(|_| invalid())?,
+            _ => 0,
+        };
+        let width = match modifier_parts.next() {
+            Some(width) => width.parse::<usize>().map_err(|_| invalid())?,
+            None => 0,
+        };
+        let base = modifier_parts.next().unwrap_or("d");
+        if modifier_parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let value = i64::from(value) + offset;
+        if value < 0 {
+            return Err(ParseErrorKind::Msg(format!(
+                "negative $GENERATE value with modifier: {}",
+                modifier
+            ))
+            .into());
+        }
+
+        let formatted = match base {
+            "d" => format!("{:0width$}", value, width = width),
+            "o" => format!("{:0width$o}", value, width = width),
+            "x" => format!("{:0width$x}", value, width = width),
+            "X" => format!("{:0width$X}", value, width = width),
+            _ => return Err(invalid()),
+        };
+
+        text.push_str(&formatted);
+        Ok(())
     }
 
     fn flush_record(
@@ -453,12 +833,12 @@ impl Parser {
     }
 }
 
-#[allow(unused)]
 enum State {
     StartLine,    // start of line, @, $<WORD>, Name, Blank
     TtlClassType, // [<TTL>] [<class>] <type>,
     Ttl,          // $TTL <time>
     Record(Vec<String>),
-    Include, // $INCLUDE <filename>
+    Include(Option<String>), // $INCLUDE <filename> [<domain-name>]
+    Generate(Vec<String>),   // $GENERATE <range> <lhs> [<ttl>] [<class>] <type> <rhs>
     Origin,
 }
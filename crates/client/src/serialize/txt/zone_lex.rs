@@ -78,7 +78,7 @@ impl<'a> Lexer<'a> {
                         Some('"') => {
                             self.txt.next();
                             char_data = Some(String::new());
-                            self.state = State::Quote;
+                            self.state = State::Quote { is_list: false };
                         }
                         Some(';') => self.state = State::Comment { is_list: false },
                         Some(ch) if ch.is_whitespace() => {
@@ -113,9 +113,21 @@ impl<'a> Lexer<'a> {
                         }
                     }
                 }
-                State::Quote => {
+                State::Quote { is_list } => {
                     match ch {
                         // end and gobble the '"'
+                        Some('"') if is_list => {
+                            self.txt.next();
+                            char_data_vec
+                                .as_mut()
+                                .ok_or_else(|| {
+                                    LexerError::from(LexerErrorKind::IllegalState(
+                                        "char_data_vec is None",
+                                    ))
+                                })?
+                                .push(char_data.take().unwrap_or_else(|| "".into()));
+                            self.state = State::List;
+                        }
                         Some('"') => {
                             self.state = State::RestOfLine;
                             self.txt.next();
@@ -154,12 +166,14 @@ impl<'a> Lexer<'a> {
                                 "INCLUDE" => Token::Include,
                                 "ORIGIN" => Token::Origin,
                                 "TTL" => Token::Ttl,
-                                _ => {
-                                    return Err(LexerErrorKind::UnrecognizedDollar(
-                                        char_data.take().unwrap_or_else(|| "".into()),
-                                    )
-                                    .into())
+                                "GENERATE" => Token::Generate,
+                                // a lone $, e.g. the $GENERATE iterator in `$ CNAME $.0`
+                                "" => {
+                                    char_data = Some("$".to_string());
+                                    self.state = State::CharData { is_list: false };
+                                    continue;
                                 }
+                                _ => return Err(LexerErrorKind::UnrecognizedDollar(dollar).into()),
                             }));
                         }
                     }
@@ -179,6 +193,11 @@ impl<'a> Lexer<'a> {
                             })
                             .map(|v| Some(Token::List(v)));
                     }
+                    Some('"') => {
+                        self.txt.next();
+                        char_data = Some(String::new());
+                        self.state = State::Quote { is_list: true };
+                    }
                     Some(ch) if ch.is_whitespace() => {
                         self.txt.next();
                     }
@@ -285,7 +304,7 @@ impl<'a> Lexer<'a> {
 
         if !ch.is_control() {
             if ch.is_numeric() {
-                // in this case it's an escaped octal: \DDD
+                // in this case it's an escaped octet: \DDD, in decimal
                 let d1: u32 = self
                     .txt
                     .next()
@@ -311,7 +330,10 @@ impl<'a> Lexer<'a> {
                             .ok_or_else(|| LexerError::from(LexerErrorKind::IllegalCharacter(c)))
                     })??; // gobble
 
-                let val: u32 = (d1 << 16) + (d2 << 8) + d3;
+                let val: u32 = d1 * 100 + d2 * 10 + d3;
+                if val > u32::from(u8::max_value()) {
+                    return Err(LexerErrorKind::UnrecognizedOctet(val).into());
+                }
                 let ch: char = char::from_u32(val)
                     .ok_or_else(|| LexerError::from(LexerErrorKind::UnrecognizedOctet(val)))?;
 
@@ -342,7 +364,7 @@ pub(crate) enum State {
     //  Name,              // CharData + '.' + CharData
     Comment { is_list: bool }, // ;.*
     At,                        // @
-    Quote,                     // ".*"
+    Quote { is_list: bool },   // ".*"
     Dollar,                    // $
     EOL,                       // \n or \r\n
     EOF,
@@ -365,6 +387,8 @@ pub enum Token {
     Origin,
     /// $TTL
     Ttl,
+    /// $GENERATE
+    Generate,
     /// \n or \r\n
     EOL,
 }
@@ -481,8 +505,13 @@ mod lex_test {
         );
         assert_eq!(
             Lexer::new("\"a\\077\"").next_token().unwrap().unwrap(),
-            Token::CharData("a\u{707}".to_string())
+            Token::CharData("aM".to_string())
+        );
+        assert_eq!(
+            Lexer::new("\"a\\009b\"").next_token().unwrap().unwrap(),
+            Token::CharData("a\tb".to_string())
         );
+        assert!(Lexer::new("\"a\\256\"").next_token().is_err());
 
         assert!(Lexer::new("\"a\\\"").next_token().is_err());
         assert!(Lexer::new("\"a\\0\"").next_token().is_err());
@@ -551,6 +580,11 @@ mod lex_test {
             Token::Origin
         );
         assert_eq!(next_token(&mut Lexer::new("$TTL")).unwrap(), Token::Ttl);
+        assert_eq!(
+            next_token(&mut Lexer::new("$GENERATE")).unwrap(),
+            Token::Generate
+        );
+        assert!(Lexer::new("$UNKNOWN").next_token().is_err());
         assert_eq!(next_token(&mut Lexer::new("\n")), Some(Token::EOL));
         assert_eq!(next_token(&mut Lexer::new("\r\n")), Some(Token::EOL));
     }
@@ -593,6 +627,19 @@ mod lex_test {
             Token::List(vec!["abc".to_string()])
         );
         assert_eq!(next_token(&mut lexer), None);
+
+        let mut lexer = Lexer::new("( \"quoted (text)\"\n\"a\\\"b\" abc )");
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::List(vec![
+                "quoted (text)".to_string(),
+                "a\"b".to_string(),
+                "abc".to_string()
+            ])
+        );
+        assert_eq!(next_token(&mut lexer), None);
+
+        assert!(Lexer::new("( \"abc )").next_token().is_err());
     }
 
     #[test]
@@ -759,4 +806,27 @@ $INCLUDE <SUBSYS>ISI-MAILBOXES.TXT",
         );
         assert!(next_token(&mut lexer).is_none());
     }
+
+    #[test]
+    fn generate() {
+        let mut lexer = Lexer::new("$GENERATE 1-127/2 $ CNAME ${0,3}.host-$.0.");
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::Generate);
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("1-127/2".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("$".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("CNAME".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("${0,3}.host-$.0.".to_string())
+        );
+        assert!(next_token(&mut lexer).is_none());
+    }
 }
@@ -0,0 +1,542 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zone file writer, the counterpart to the [`Parser`](super::Parser)
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use crate::op::Message;
+#[cfg(feature = "dnssec")]
+use crate::proto::rr::dnssec::rdata::{DNSSECRData, DNSKEY};
+use crate::rr::rdata::TXT;
+use crate::rr::{Name, RData, Record, RecordSet, RecordType, RrKey};
+
+/// RData longer than this is split across lines with parentheses, where the type allows it
+const MAX_RDATA_WIDTH: usize = 56;
+
+/// Indentation for the continuation lines of multi-line RData
+const CONTINUATION: &str = "\t\t\t\t";
+
+/// Writes records in the RFC 1035 master file format, see [`Parser`](super::Parser) for the
+///  format.
+///
+/// Owner names in the current `$ORIGIN` are written relative to it, and an owner which is the
+///  same as the previous record's is left blank. TTL and class are always written, so that the
+///  output does not depend on how a reader defaults them. Long TXT and DNSKEY RData is split
+///  across lines in parentheses.
+///
+/// # Example
+///
+/// ```
+/// use std::str::FromStr;
+/// use trust_dns_client::rr::{Name, RData, Record};
+/// use trust_dns_client::serialize::txt::Writer;
+///
+/// let origin = Name::from_str("example.com.").unwrap();
+/// let record = Record::from_rdata(
+///     Name::from_str("www.example.com.").unwrap(),
+///     3600,
+///     RData::A("192.0.2.1".parse().unwrap()),
+/// );
+///
+/// let mut writer = Writer::new(String::new());
+/// writer.write_origin(&origin).unwrap();
+/// writer.write_record(&record).unwrap();
+///
+/// assert_eq!(
+///     writer.into_inner(),
+///     "$ORIGIN example.com.\nwww\t3600\tIN\tA\t192.0.2.1\n"
+/// );
+/// ```
+pub struct Writer<W: Write> {
+    out: W,
+    origin: Option<Name>,
+    last_owner: Option<Name>,
+}
+
+impl<W: Write> Writer<W> {
+    /// Returns a new writer for the zone file text, writing to `out`
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            origin: None,
+            last_owner: None,
+        }
+    }
+
+    /// Consumes the writer, returning the written output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Writes an `$ORIGIN` entry, subsequent owner names in `origin` will be written relative to it
+    pub fn write_origin(&mut self, origin: &Name) -> fmt::Result {
+        writeln!(self.out, "$ORIGIN {}", origin.to_ascii())?;
+        self.origin = Some(origin.clone());
+        self.last_owner = None;
+        Ok(())
+    }
+
+    /// Writes a `$TTL` entry, see RFC 2308
+    pub fn write_ttl(&mut self, ttl: u32) -> fmt::Result {
+        writeln!(self.out, "$TTL {}", ttl)
+    }
+
+    /// Writes a comment line, each line of `comment` is written as a separate comment
+    pub fn write_comment(&mut self, comment: &str) -> fmt::Result {
+        for line in comment.lines() {
+            writeln!(self.out, ";{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a single record entry
+    pub fn write_record(&mut self, record: &Record) -> fmt::Result {
+        self.write_owner(record.name())?;
+        write!(
+            self.out,
+            "\t{ttl}\t{class}\t{ty}",
+            ttl = record.ttl(),
+            class = record.dns_class(),
+            ty = record.rr_type(),
+        )?;
+
+        if let Some(rdata) = record.data() {
+            self.out.write_char('\t')?;
+            self.write_rdata(rdata)?;
+        }
+
+        self.out.write_char('\n')
+    }
+
+    /// Writes all the records, followed by the RRSIGs, of the record set
+    pub fn write_record_set(&mut self, rrset: &RecordSet) -> fmt::Result {
+        for record in rrset.records_without_rrsigs() {
+            self.write_record(record)?;
+        }
+
+        for rrsig in rrset.rrsigs() {
+            self.write_record(rrsig)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a zone, as returned from the [`Parser`](super::Parser)
+    ///
+    /// The `$ORIGIN` is written first, followed by `$TTL` with the SOA minimum, and then the SOA
+    ///  record set followed by all others in the order of the map.
+    pub fn write_zone(
+        &mut self,
+        origin: &Name,
+        records: &BTreeMap<RrKey, RecordSet>,
+    ) -> fmt::Result {
+        self.write_origin(origin)?;
+
+        let soa = records
+            .values()
+            .find(|rrset| rrset.record_type() == RecordType::SOA);
+        if let Some(soa) = soa {
+            let minimum = soa
+                .records_without_rrsigs()
+                .find_map(|record| match record.data() {
+                    Some(RData::SOA(soa)) => Some(soa.minimum()),
+                    _ => None,
+                });
+            if let Some(minimum) = minimum {
+                self.write_ttl(minimum)?;
+            }
+
+            self.write_record_set(soa)?;
+        }
+
+        for rrset in records
+            .values()
+            .filter(|rrset| rrset.record_type() != RecordType::SOA)
+        {
+            self.write_record_set(rrset)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the sections of the message, with each preceded by a comment naming it
+    ///
+    /// The queries are written as comments, as they have no RData. EDNS is not written.
+    pub fn write_message(&mut self, message: &Message) -> fmt::Result {
+        if !message.queries().is_empty() {
+            self.write_comment("; QUESTION SECTION:")?;
+            for query in message.queries() {
+                writeln!(
+                    self.out,
+                    ";{name}\t{class}\t{ty}",
+                    name = query.name().to_ascii(),
+                    class = query.query_class(),
+                    ty = query.query_type(),
+                )?;
+            }
+        }
+
+        let sections = [
+            ("; ANSWER SECTION:", message.answers()),
+            ("; AUTHORITY SECTION:", message.name_servers()),
+            ("; ADDITIONAL SECTION:", message.additionals()),
+        ];
+
+        for (title, records) in sections.iter() {
+            if records.is_empty() {
+                continue;
+            }
+
+            self.out.write_char('\n')?;
+            self.write_comment(title)?;
+            self.last_owner = None;
+            for record in records.iter() {
+                self.write_record(record)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_owner(&mut self, name: &Name) -> fmt::Result {
+        if self.last_owner.as_ref() == Some(name) {
+            return Ok(());
+        }
+
+        let relative = self
+            .origin
+            .as_ref()
+            .and_then(|origin| name.relative_to(origin));
+        match relative {
+            Some(ref relative) if relative.num_labels() == 0 => self.out.write_char('@')?,
+            Some(ref relative) => self.out.write_str(&relative.to_ascii())?,
+            None => self.out.write_str(&name.to_ascii())?,
+        }
+
+        self.last_owner = Some(name.clone());
+        Ok(())
+    }
+
+    fn write_rdata(&mut self, rdata: &RData) -> fmt::Result {
+        match *rdata {
+            RData::TXT(ref txt) => self.write_txt(txt),
+            #[cfg(feature = "dnssec")]
+            RData::DNSSEC(DNSSECRData::DNSKEY(ref dnskey))
+            | RData::DNSSEC(DNSSECRData::CDNSKEY(ref dnskey)) => self.write_dnskey(dnskey),
+            ref rdata => write!(self.out, "{}", rdata),
+        }
+    }
+
+    /// Each character-string is quoted, and when they do not fit on a single line each is put on
+    ///  its own line.
+    fn write_txt(&mut self, txt: &TXT) -> fmt::Result {
+        let mut strings = Vec::with_capacity(txt.txt_data().len());
+        for data in txt.iter() {
+            let mut string = String::with_capacity(data.len() + 2);
+            write_character_string(&mut string, data)?;
+            strings.push(string);
+        }
+
+        let width = strings.iter().map(|s| s.len() + 1).sum::<usize>();
+        if strings.len() < 2 || width <= MAX_RDATA_WIDTH {
+            return self.out.write_str(&strings.join(" "));
+        }
+
+        self.out.write_str("( ")?;
+        self.out
+            .write_str(&strings.join(&format!("\n{}", CONTINUATION)))?;
+        self.out.write_str(" )")
+    }
+
+    /// The base64 public key is split across lines, with a comment containing the key tag
+    #[cfg(feature = "dnssec")]
+    fn write_dnskey(&mut self, dnskey: &DNSKEY) -> fmt::Result {
+        let key = data_encoding::BASE64.encode(dnskey.public_key());
+        write!(
+            self.out,
+            "{flags} 3 {alg} (",
+            flags = dnskey.flags(),
+            alg = u8::from(dnskey.algorithm()),
+        )?;
+
+        // base64 is all ascii, so the chunks are always valid utf8
+        for chunk in key.as_bytes().chunks(MAX_RDATA_WIDTH) {
+            self.out.write_char('\n')?;
+            self.out.write_str(CONTINUATION)?;
+            self.out
+                .write_str(std::str::from_utf8(chunk).map_err(|_| fmt::Error)?)?;
+        }
+
+        self.out.write_str(" )")?;
+        if let Ok(key_tag) = dnskey.calculate_key_tag() {
+            write!(self.out, " ; key id = {}", key_tag)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the data as a quoted `<character-string>`
+///
+/// `"` and `\` are escaped with `\`, and control characters with `\DDD`. Data that is not
+///  valid UTF-8 has each non-ASCII octet escaped with `\DDD`.
+fn write_character_string(out: &mut impl Write, data: &[u8]) -> fmt::Result {
+    fn write_char(out: &mut impl Write, ch: char) -> fmt::Result {
+        match ch {
+            '"' | '\\' => write!(out, "\\{}", ch),
+            ch if ch.is_control() => write!(out, "\\{:03}", u32::from(ch)),
+            ch => out.write_char(ch),
+        }
+    }
+
+    out.write_char('"')?;
+    match std::str::from_utf8(data) {
+        Ok(string) => {
+            for ch in string.chars() {
+                write_char(out, ch)?;
+            }
+        }
+        Err(_) => {
+            for &byte in data {
+                if byte.is_ascii() {
+                    write_char(out, char::from(byte))?;
+                } else {
+                    write!(out, "\\{:03}", byte)?;
+                }
+            }
+        }
+    }
+    out.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::op::Query;
+    use crate::rr::rdata::{MX, SOA};
+    use crate::rr::LowerName;
+    use crate::serialize::txt::{Lexer, Parser};
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn zone() -> (Name, BTreeMap<RrKey, RecordSet>) {
+        let origin = name("example.com.");
+        let records = vec![
+            // the parser uses the expire for the SOA TTL
+            Record::from_rdata(
+                origin.clone(),
+                604_800,
+                RData::SOA(SOA::new(
+                    name("ns.example.com."),
+                    name("hostmaster.example.com."),
+                    1,
+                    3600,
+                    600,
+                    604_800,
+                    300,
+                )),
+            ),
+            Record::from_rdata(origin.clone(), 3600, RData::NS(name("ns.example.com."))),
+            Record::from_rdata(
+                origin.clone(),
+                3600,
+                RData::MX(MX::new(10, name("mail.example.com."))),
+            ),
+            Record::from_rdata(
+                name("ns.example.com."),
+                300,
+                RData::A("192.0.2.1".parse().unwrap()),
+            ),
+            Record::from_rdata(
+                name("www.example.com."),
+                300,
+                RData::AAAA("2001:db8::1".parse().unwrap()),
+            ),
+            Record::from_rdata(
+                name("txt.example.com."),
+                300,
+                RData::TXT(TXT::new(vec![
+                    "v=spf1 -all".to_string(),
+                    "with \"quotes\"; \\ and (parens)".to_string(),
+                    "tab\tand\nnewline".to_string(),
+                    "a string long enough to wrap the record onto multiple lines".to_string(),
+                ])),
+            ),
+            Record::from_rdata(
+                name("other.example.net."),
+                300,
+                RData::CNAME(name("www.example.com.")),
+            ),
+        ];
+
+        let mut zone = BTreeMap::new();
+        for record in records {
+            zone.entry(RrKey::new(LowerName::new(record.name()), record.rr_type()))
+                .or_insert_with(|| RecordSet::new(record.name(), record.rr_type(), 0))
+                .insert(record, 0);
+        }
+
+        (origin, zone)
+    }
+
+    #[test]
+    fn test_zone_round_trip() {
+        let (origin, zone) = zone();
+
+        let mut writer = Writer::new(String::new());
+        writer.write_zone(&origin, &zone).unwrap();
+        let text = writer.into_inner();
+
+        let (parsed_origin, parsed) = Parser::new()
+            .parse(Lexer::new(&text), None, None)
+            .unwrap_or_else(|e| panic!("failed to parse {}: {}", text, e));
+
+        assert_eq!(parsed_origin, origin);
+        assert_eq!(parsed.len(), zone.len());
+        for (key, rrset) in &zone {
+            let parsed = parsed.get(key).expect("record set missing");
+            assert_eq!(
+                parsed.records_without_rrsigs().collect::<Vec<_>>(),
+                rrset.records_without_rrsigs().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_zone_layout() {
+        let (origin, zone) = zone();
+
+        let mut writer = Writer::new(String::new());
+        writer.write_zone(&origin, &zone).unwrap();
+        let text = writer.into_inner();
+        let mut lines = text.lines();
+
+        assert_eq!(lines.next().unwrap(), "$ORIGIN example.com.");
+        assert_eq!(lines.next().unwrap(), "$TTL 300");
+        assert!(lines.next().unwrap().starts_with("@\t604800\tIN\tSOA\t"));
+
+        // the same owner is left blank
+        assert!(text.contains("\n\t3600\tIN\tNS\tns.example.com.\n"));
+        assert!(text.contains("\n\t3600\tIN\tMX\t10 mail.example.com.\n"));
+
+        assert!(text.contains("\nwww\t300\tIN\tAAAA\t2001:db8::1\n"));
+        assert!(text.contains("\nother.example.net.\t300\tIN\tCNAME\twww.example.com.\n"));
+    }
+
+    #[test]
+    fn test_txt() {
+        let record = Record::from_rdata(
+            name("txt.example.com."),
+            300,
+            RData::TXT(TXT::new(vec!["short".to_string(), "txt".to_string()])),
+        );
+
+        let mut writer = Writer::new(String::new());
+        writer.write_record(&record).unwrap();
+        assert_eq!(
+            writer.into_inner(),
+            "txt.example.com.\t300\tIN\tTXT\t\"short\" \"txt\"\n"
+        );
+
+        let (_, zone) = zone();
+        let txt = zone
+            .values()
+            .find(|rrset| rrset.record_type() == RecordType::TXT)
+            .unwrap();
+
+        let mut writer = Writer::new(String::new());
+        writer.write_origin(&name("example.com.")).unwrap();
+        writer.write_record_set(txt).unwrap();
+        assert_eq!(
+            writer.into_inner(),
+            "$ORIGIN example.com.
+txt\t300\tIN\tTXT\t( \"v=spf1 -all\"
+\t\t\t\t\"with \\\"quotes\\\"; \\\\ and (parens)\"
+\t\t\t\t\"tab\\009and\\010newline\"
+\t\t\t\t\"a string long enough to wrap the record onto multiple lines\" )
+"
+        );
+    }
+
+    #[test]
+    fn test_character_string() {
+        let escape = |data: &[u8]| {
+            let mut string = String::new();
+            write_character_string(&mut string, data).unwrap();
+            string
+        };
+
+        assert_eq!(escape(b""), "\"\"");
+        assert_eq!(escape(b"a b"), "\"a b\"");
+        assert_eq!(escape(b"\"\\"), "\"\\\"\\\\\"");
+        assert_eq!(escape(b"\x00\x7f"), "\"\\000\\127\"");
+        assert_eq!(escape("♥".as_bytes()), "\"♥\"");
+        assert_eq!(escape(b"a\xff\"b"), "\"a\\255\\\"b\"");
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec")]
+    fn test_dnskey() {
+        use crate::proto::rr::dnssec::Algorithm;
+
+        let dnskey = DNSKEY::new(true, true, false, Algorithm::RSASHA256, vec![0xAB; 96]);
+        let key_tag = dnskey.calculate_key_tag().unwrap();
+        let record = Record::from_rdata(
+            name("example.com."),
+            3600,
+            RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)),
+        );
+
+        let mut writer = Writer::new(String::new());
+        writer.write_origin(&name("example.com.")).unwrap();
+        writer.write_record(&record).unwrap();
+        let text = writer.into_inner();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], "@\t3600\tIN\tDNSKEY\t257 3 8 (");
+        assert_eq!(lines[2].len(), CONTINUATION.len() + MAX_RDATA_WIDTH);
+        assert!(lines[4].ends_with(&format!(" ) ; key id = {}", key_tag)));
+    }
+
+    #[test]
+    fn test_message() {
+        let mut message = Message::new();
+        message.add_query(Query::query(name("www.example.com."), RecordType::A));
+        message.add_answer(Record::from_rdata(
+            name("www.example.com."),
+            300,
+            RData::A("192.0.2.1".parse().unwrap()),
+        ));
+        message.add_name_server(Record::from_rdata(
+            name("example.com."),
+            3600,
+            RData::NS(name("ns.example.com.")),
+        ));
+
+        let mut writer = Writer::new(String::new());
+        writer.write_message(&message).unwrap();
+        assert_eq!(
+            writer.into_inner(),
+            ";; QUESTION SECTION:
+;www.example.com.\tIN\tA
+
+;; ANSWER SECTION:
+www.example.com.\t300\tIN\tA\t192.0.2.1
+
+;; AUTHORITY SECTION:
+example.com.\t3600\tIN\tNS\tns.example.com.
+"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! UDP and TCP transports, and the timer, for the async-std runtime
+//!
+//! These implement the [`UdpSocket`](crate::udp::UdpSocket), [`Connect`](crate::tcp::Connect)
+//!  and [`Time`](crate::Time) traits, so they can be used anywhere the Tokio types are, e.g.
+//!  `UdpClientStream::<AsyncStdUdpSocket>::new(addr)` or
+//!  `TcpClientStream::<AsyncStdTcpStream>::new(addr)`. DNS over TLS runs on top of the TCP
+//!  stream, e.g. `tls_client_connect::<AsyncStdTcpStream>(..)` with `dns-over-rustls`.
+
+mod net;
+mod time;
+
+pub use self::net::{AsyncStdTcpStream, AsyncStdUdpSocket};
+pub use self::time::AsyncStdTime;
+
+/// A DNS over TLS client stream on async-std, see [`tls_client_connect`](crate::rustls::tls_client_connect)
+#[cfg(feature = "dns-over-rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
+pub type AsyncStdTlsClientStream = crate::rustls::TlsClientStream<AsyncStdTcpStream>;
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::tests::*;
+    use crate::Executor;
+
+    struct AsyncStdExecutor;
+
+    impl Executor for AsyncStdExecutor {
+        fn new() -> Self {
+            Self
+        }
+
+        fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+            async_std::task::block_on(future)
+        }
+    }
+
+    #[test]
+    fn test_next_random_socket() {
+        next_random_socket_test::<AsyncStdUdpSocket, _>(AsyncStdExecutor)
+    }
+
+    #[test]
+    fn test_udp_stream_ipv4() {
+        async_std::task::block_on(udp_stream_test::<AsyncStdUdpSocket>(IpAddr::V4(
+            Ipv4Addr::new(127, 0, 0, 1),
+        )))
+    }
+
+    #[test]
+    fn test_udp_client_stream_ipv4() {
+        udp_client_stream_test::<AsyncStdUdpSocket, _, AsyncStdTime>(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            AsyncStdExecutor,
+        )
+    }
+
+    #[test]
+    fn test_tcp_stream_ipv4() {
+        tcp_stream_test::<AsyncStdTcpStream, _, AsyncStdTime>(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            AsyncStdExecutor,
+        )
+    }
+
+    #[test]
+    fn test_tcp_client_stream_ipv4() {
+        tcp_client_stream_test::<AsyncStdTcpStream, _, AsyncStdTime>(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            AsyncStdExecutor,
+        )
+    }
+}
@@ -13,22 +13,25 @@ use std::task::{Context, Poll};
 use async_std::task::spawn_blocking;
 use async_trait::async_trait;
 use futures_io::{AsyncRead, AsyncWrite};
-use futures_util::future::FutureExt;
-use pin_utils::pin_mut;
+use futures_util::ready;
 use socket2::{Domain, Protocol, Socket, Type};
-use trust_dns_resolver::proto::tcp::{Connect, DnsTcpStream};
-use trust_dns_resolver::proto::udp::UdpSocket;
 
-use crate::time::AsyncStdTime;
+use super::time::AsyncStdTime;
+use crate::tcp::{Connect, DnsTcpStream};
+use crate::udp::UdpSocket;
 
-pub struct AsyncStdUdpSocket(async_std::net::UdpSocket);
+/// An async-std UDP socket
+///
+/// The socket is polled for readiness, dropping an I/O future between polls would lose its waker.
+#[derive(Debug)]
+pub struct AsyncStdUdpSocket(async_io::Async<std::net::UdpSocket>);
 
 #[async_trait]
 impl UdpSocket for AsyncStdUdpSocket {
     type Time = AsyncStdTime;
 
     async fn connect_with_bind(_addr: SocketAddr, bind_addr: SocketAddr) -> io::Result<Self> {
-        let socket = async_std::net::UdpSocket::bind(bind_addr).await?;
+        let socket = async_io::Async::<std::net::UdpSocket>::bind(bind_addr)?;
 
         // TODO: research connect more, it appears to break receive tests on UDP
         // socket.connect(addr).await?;
@@ -45,9 +48,7 @@ impl UdpSocket for AsyncStdUdpSocket {
     }
 
     async fn bind(addr: SocketAddr) -> io::Result<Self> {
-        async_std::net::UdpSocket::bind(addr)
-            .await
-            .map(AsyncStdUdpSocket)
+        async_io::Async::<std::net::UdpSocket>::bind(addr).map(Self)
     }
 
     fn poll_recv_from(
@@ -55,10 +56,14 @@ impl UdpSocket for AsyncStdUdpSocket {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<io::Result<(usize, SocketAddr)>> {
-        let fut = self.0.recv_from(buf);
-        pin_mut!(fut);
-
-        fut.poll_unpin(cx)
+        loop {
+            match self.0.get_ref().recv_from(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(self.0.poll_readable(cx))?
+                }
+                res => return Poll::Ready(res),
+            }
+        }
     }
 
     async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
@@ -71,10 +76,14 @@ impl UdpSocket for AsyncStdUdpSocket {
         buf: &[u8],
         target: SocketAddr,
     ) -> Poll<io::Result<usize>> {
-        let fut = self.0.send_to(buf, target);
-        pin_mut!(fut);
-
-        fut.poll_unpin(cx)
+        loop {
+            match self.0.get_ref().send_to(buf, target) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(self.0.poll_writable(cx))?
+                }
+                res => return Poll::Ready(res),
+            }
+        }
     }
 
     async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
@@ -82,6 +91,8 @@ impl UdpSocket for AsyncStdUdpSocket {
     }
 }
 
+/// An async-std TCP stream
+#[derive(Debug)]
 pub struct AsyncStdTcpStream(async_std::net::TcpStream);
 
 impl DnsTcpStream for AsyncStdTcpStream {
@@ -119,34 +130,28 @@ impl Connect for AsyncStdTcpStream {
 
 impl AsyncWrite for AsyncStdTcpStream {
     fn poll_write(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
         bytes: &[u8],
-    ) -> std::task::Poll<std::result::Result<usize, std::io::Error>> {
+    ) -> Poll<io::Result<usize>> {
         Pin::new(&mut self.0).poll_write(cx, bytes)
     }
 
-    fn poll_flush(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<std::result::Result<(), std::io::Error>> {
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         Pin::new(&mut self.0).poll_flush(cx)
     }
 
-    fn poll_close(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<std::result::Result<(), std::io::Error>> {
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         Pin::new(&mut self.0).poll_close(cx)
     }
 }
 
 impl AsyncRead for AsyncStdTcpStream {
     fn poll_read(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
         bytes: &mut [u8],
-    ) -> std::task::Poll<std::result::Result<usize, std::io::Error>> {
+    ) -> Poll<io::Result<usize>> {
         Pin::new(&mut self.0).poll_read(cx, bytes)
     }
 }
@@ -9,10 +9,11 @@ use std::future::Future;
 use std::time::Duration;
 
 use async_trait::async_trait;
-use trust_dns_resolver::proto::Time;
+
+use crate::Time;
 
 /// AsyncStd backed timer implementation
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct AsyncStdTime;
 
 #[async_trait]
@@ -0,0 +1,19 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS-based Service Discovery, [RFC 6763](https://tools.ietf.org/html/rfc6763)
+//!
+//! Browsing and resolving work over any [`crate::xfer::DnsHandle`], i.e. multicast DNS for the
+//!  `local.` domain, or unicast DNS for wide-area service discovery.
+
+mod service;
+mod txt_properties;
+
+pub use self::service::{
+    browse, instances_from_records, resolve, service_types, ServiceInstance, ServiceType,
+};
+pub use self::txt_properties::TxtProperties;
@@ -0,0 +1,414 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Service types, instances and the browse and resolve operations
+
+use std::borrow::Cow;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::dnssd::TxtProperties;
+use crate::error::*;
+use crate::op::Query;
+use crate::rr::{Name, RData, Record, RecordType};
+use crate::xfer::{DnsHandle, DnsRequestOptions, FirstAnswer};
+
+/// The maximum length of a service name, [RFC 6335](https://tools.ietf.org/html/rfc6335#section-5.1)
+const MAX_SERVICE_NAME_LEN: usize = 15;
+
+/// A DNS-SD service type, e.g. `_http._tcp.local.`, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-7)
+///
+/// ```text
+///    The <Service> portion of a Service Instance Name consists of a pair
+///    of DNS labels, following the convention already established for SRV
+///    records [RFC2782].  The first label of the pair is an underscore
+///    character followed by the Service Name [RFC6335].  The Service Name
+///    identifies what the service does and what application protocol it
+///    uses to do it.  The second label is either "_tcp" (for application
+///    protocols that run over TCP) or "_udp" (for all others).
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ServiceType {
+    name: Name,
+}
+
+impl ServiceType {
+    /// Creates a new service type
+    ///
+    /// # Arguments
+    ///
+    /// * `service` - the service name without the leading underscore, e.g. `http`
+    /// * `protocol` - `tcp` or `udp`, without the leading underscore
+    /// * `domain` - the domain of the service, e.g. `local.` for multicast DNS
+    pub fn new(service: &str, protocol: &str, domain: &Name) -> ProtoResult<Self> {
+        let name = Name::from_labels(vec![
+            format!("_{}", service).as_bytes(),
+            format!("_{}", protocol).as_bytes(),
+        ])?
+        .append_domain(domain)?;
+
+        Self::from_name(name)
+    }
+
+    /// Creates a service type from its full name, e.g. `_http._tcp.local.`
+    pub fn from_name(name: Name) -> ProtoResult<Self> {
+        let mut labels = name.iter();
+        let service = labels.next().unwrap_or_default();
+        let protocol = labels.next().unwrap_or_default();
+
+        if service.len() < 2 || service.len() > MAX_SERVICE_NAME_LEN + 1 || service[0] != b'_' {
+            return Err(format!("invalid service name: {}", name).into());
+        }
+
+        if !protocol.eq_ignore_ascii_case(b"_tcp") && !protocol.eq_ignore_ascii_case(b"_udp") {
+            return Err(format!("invalid service protocol: {}", name).into());
+        }
+
+        Ok(Self { name })
+    }
+
+    /// The name of the service type, the name PTR records are browsed on
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The name of a subtype of the service, e.g. `_printer._sub._http._tcp.local.`, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-7.1)
+    pub fn subtype(&self, subtype: &str) -> ProtoResult<Name> {
+        Name::from_labels(vec![subtype.as_bytes(), &b"_sub"[..]])?.append_domain(&self.name)
+    }
+
+    /// The name of an instance of the service
+    ///
+    /// # Arguments
+    ///
+    /// * `instance` - the user-friendly name of the instance, any UTF-8 is allowed, e.g. `My Printer`
+    pub fn instance_name(&self, instance: &str) -> ProtoResult<Name> {
+        Name::from_labels(vec![instance.as_bytes()])?.append_domain(&self.name)
+    }
+
+    /// The name for enumerating all service types of a domain, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-9)
+    pub fn enumeration_name(domain: &Name) -> ProtoResult<Name> {
+        Name::from_labels(vec!["_services", "_dns-sd", "_udp"])?.append_domain(domain)
+    }
+}
+
+impl FromStr for ServiceType {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> ProtoResult<Self> {
+        Self::from_name(Name::from_ascii(s)?)
+    }
+}
+
+impl fmt::Display for ServiceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// A resolved instance of a service, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-4)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceInstance {
+    name: Name,
+    target: Name,
+    port: u16,
+    priority: u16,
+    weight: u16,
+    txt: TxtProperties,
+    addresses: Vec<IpAddr>,
+}
+
+impl ServiceInstance {
+    /// Collects the instance from the records of one or more responses
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the full name of the instance, e.g. `My Printer._ipp._tcp.local.`
+    /// * `records` - the records containing the SRV, TXT and address records of the instance
+    ///
+    /// # Return
+    ///
+    /// `None` if there is no SRV record for the instance
+    pub fn from_records<'a, I>(name: &Name, records: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'a Record> + Clone,
+    {
+        let srv = records
+            .clone()
+            .into_iter()
+            .filter(|record| record.name() == name)
+            .filter_map(|record| match record.data() {
+                Some(RData::SRV(srv)) => Some(srv),
+                _ => None,
+            })
+            .min_by_key(|srv| srv.priority())?;
+
+        let txt = records
+            .clone()
+            .into_iter()
+            .filter(|record| record.name() == name)
+            .find_map(|record| match record.data() {
+                Some(RData::TXT(txt)) => Some(TxtProperties::from(txt)),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let mut addresses = Vec::new();
+        for record in records {
+            if record.name() != srv.target() {
+                continue;
+            }
+
+            let address = match record.data() {
+                Some(RData::A(ip)) => IpAddr::V4(*ip),
+                Some(RData::AAAA(ip)) => IpAddr::V6(*ip),
+                _ => continue,
+            };
+
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+
+        Some(Self {
+            name: name.clone(),
+            target: srv.target().clone(),
+            port: srv.port(),
+            priority: srv.priority(),
+            weight: srv.weight(),
+            txt,
+            addresses,
+        })
+    }
+
+    /// The full name of the instance
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The user-friendly name of the instance, i.e. the first label
+    pub fn instance_name(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.name.iter().next().unwrap_or_default())
+    }
+
+    /// The name of the service type, e.g. `_ipp._tcp.local.`
+    pub fn service_type(&self) -> Name {
+        self.name.base_name()
+    }
+
+    /// The host providing the service
+    pub fn target(&self) -> &Name {
+        &self.target
+    }
+
+    /// The port of the service on the host
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The priority of the SRV record
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// The weight of the SRV record
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// The key/value pairs of the TXT record
+    pub fn txt(&self) -> &TxtProperties {
+        &self.txt
+    }
+
+    /// The addresses of the host
+    pub fn addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+}
+
+/// Returns the instance names of the PTR records of the service type, without duplicates
+pub fn instances_from_records<'a, I>(service: &Name, records: I) -> Vec<Name>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let mut instances = Vec::new();
+    for record in records {
+        if record.name() != service {
+            continue;
+        }
+
+        if let Some(RData::PTR(instance)) = record.data() {
+            if !instances.contains(instance) {
+                instances.push(instance.clone());
+            }
+        }
+    }
+
+    instances
+}
+
+/// Looks up the answers and additional records of a query
+async fn lookup<H: DnsHandle>(handle: &mut H, query: Query) -> Result<Vec<Record>, H::Error> {
+    let mut response = handle
+        .lookup(query, DnsRequestOptions::default())
+        .first_answer()
+        .await?;
+
+    let mut records = response.take_answers();
+    records.append(&mut response.take_additionals());
+    Ok(records)
+}
+
+/// Browses for the instances of a service, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-4.1)
+///
+/// # Arguments
+///
+/// * `handle` - the handle used for the lookups, over multicast or unicast DNS
+/// * `service` - the service type, or a subtype, e.g. `_http._tcp.local.`
+///
+/// # Return
+///
+/// The names of the instances, which can be passed to [`resolve`]
+pub async fn browse<H: DnsHandle>(handle: &mut H, service: &Name) -> Result<Vec<Name>, H::Error> {
+    let records = lookup(handle, Query::query(service.clone(), RecordType::PTR)).await?;
+    Ok(instances_from_records(service, &records))
+}
+
+/// Enumerates the service types of the domain, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-9)
+pub async fn service_types<H: DnsHandle>(
+    handle: &mut H,
+    domain: &Name,
+) -> Result<Vec<Name>, H::Error> {
+    browse(handle, &ServiceType::enumeration_name(domain)?).await
+}
+
+/// Resolves the SRV, TXT and address records of a service instance, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-4.2)
+///
+/// Records already returned as additional records aren't looked up again.
+pub async fn resolve<H: DnsHandle>(
+    handle: &mut H,
+    instance: &Name,
+) -> Result<ServiceInstance, H::Error> {
+    let mut records = lookup(handle, Query::query(instance.clone(), RecordType::SRV)).await?;
+
+    let has_txt = records
+        .iter()
+        .any(|r| r.name() == instance && r.record_type() == RecordType::TXT);
+    if !has_txt {
+        records.append(&mut lookup(handle, Query::query(instance.clone(), RecordType::TXT)).await?);
+    }
+
+    let resolved = ServiceInstance::from_records(instance, &records)
+        .ok_or_else(|| ProtoError::from(format!("no SRV record for: {}", instance)))?;
+    if !resolved.addresses().is_empty() {
+        return Ok(resolved);
+    }
+
+    for record_type in &[RecordType::A, RecordType::AAAA] {
+        let query = Query::query(resolved.target().clone(), *record_type);
+        records.append(&mut lookup(handle, query).await?);
+    }
+
+    Ok(ServiceInstance::from_records(instance, &records).unwrap_or(resolved))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::rr::rdata::{SRV, TXT};
+
+    #[test]
+    fn test_service_type() {
+        let local = Name::from_ascii("local.").unwrap();
+        let http = ServiceType::new("http", "tcp", &local).unwrap();
+
+        assert_eq!(http.name(), &Name::from_ascii("_http._tcp.local.").unwrap());
+        assert_eq!(http, "_http._tcp.local.".parse().unwrap());
+        assert_eq!(
+            http.subtype("_printer").unwrap(),
+            Name::from_ascii("_printer._sub._http._tcp.local.").unwrap()
+        );
+        assert_eq!(
+            ServiceType::enumeration_name(&local).unwrap(),
+            Name::from_ascii("_services._dns-sd._udp.local.").unwrap()
+        );
+
+        let instance = http.instance_name("My Printer.2").unwrap();
+        assert_eq!(instance.num_labels(), 4);
+        assert_eq!(instance.iter().next().unwrap(), b"My Printer.2");
+
+        assert!(ServiceType::new("http", "sctp", &local).is_err());
+        assert!(ServiceType::new("a-very-long-service", "tcp", &local).is_err());
+        assert!("http.tcp.local.".parse::<ServiceType>().is_err());
+    }
+
+    #[test]
+    fn test_instances_from_records() {
+        let service = Name::from_ascii("_http._tcp.local.").unwrap();
+        let first = Name::from_ascii("a._http._tcp.local.").unwrap();
+        let second = Name::from_ascii("b._http._tcp.local.").unwrap();
+
+        let records = vec![
+            Record::from_rdata(service.clone(), 120, RData::PTR(first.clone())),
+            Record::from_rdata(service.clone(), 120, RData::PTR(second.clone())),
+            Record::from_rdata(service.clone(), 120, RData::PTR(first.clone())),
+            Record::from_rdata(
+                Name::from_ascii("_ipp._tcp.local.").unwrap(),
+                120,
+                RData::PTR(first.clone()),
+            ),
+        ];
+
+        assert_eq!(
+            instances_from_records(&service, &records),
+            vec![first, second]
+        );
+    }
+
+    #[test]
+    fn test_service_instance_from_records() {
+        let service = ServiceType::from_str("_ipp._tcp.local.").unwrap();
+        let name = service.instance_name("My Printer").unwrap();
+        let host = Name::from_ascii("printer.local.").unwrap();
+
+        let mut records = vec![
+            Record::from_rdata(
+                name.clone(),
+                120,
+                RData::TXT(TXT::new(vec!["rp=ipp/print".to_string()])),
+            ),
+            Record::from_rdata(host.clone(), 120, RData::A(Ipv4Addr::new(10, 0, 0, 7))),
+            Record::from_rdata(host.clone(), 120, RData::AAAA(Ipv6Addr::LOCALHOST)),
+            Record::from_rdata(host.clone(), 120, RData::A(Ipv4Addr::new(10, 0, 0, 7))),
+        ];
+        assert!(ServiceInstance::from_records(&name, &records).is_none());
+
+        records.push(Record::from_rdata(
+            name.clone(),
+            120,
+            RData::SRV(SRV::new(0, 0, 631, host.clone())),
+        ));
+        let instance = ServiceInstance::from_records(&name, &records).unwrap();
+
+        assert_eq!(instance.instance_name(), "My Printer");
+        assert_eq!(instance.service_type(), *service.name());
+        assert_eq!(instance.target(), &host);
+        assert_eq!(instance.port(), 631);
+        assert_eq!(instance.txt().get_str("rp"), Some("ipp/print"));
+        assert_eq!(
+            instance.addresses(),
+            &[
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7)),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            ]
+        );
+    }
+}
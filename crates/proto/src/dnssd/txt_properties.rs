@@ -0,0 +1,204 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Key/value pairs of DNS-SD TXT records
+
+use std::str;
+
+use crate::rr::rdata::TXT;
+
+/// The key/value pairs of a service instance TXT record, [RFC 6763](https://tools.ietf.org/html/rfc6763#section-6)
+///
+/// ```text
+/// 6.3.  Rules for Keys in DNS-SD Key/Value Pairs
+///
+///    The key MUST be at least one character.  DNS-SD TXT record strings
+///    beginning with an '=' character (i.e., the key is missing) MUST be
+///    silently ignored.
+///    ...
+///    Case is ignored when interpreting a key, so "papersize=A4",
+///    "PAPERSIZE=A4", and "Papersize=A4" are all identical.
+///
+///    If there is no '=' in a DNS-SD TXT record string, then it is a
+///    boolean attribute, simply identified as being present, with no value.
+///    ...
+///    If a client receives a TXT record containing the same key more than
+///    once, then the client MUST silently ignore all but the first
+///    occurrence of that attribute.
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxtProperties {
+    properties: Vec<(String, Option<Box<[u8]>>)>,
+}
+
+impl TxtProperties {
+    /// Creates an empty set of properties
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the key is present, either as boolean attribute or with a value
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// Returns the value of the key
+    ///
+    /// # Return
+    ///
+    /// `None` if the key is missing or is a boolean attribute without value, the empty value of
+    ///  "key=" is `Some(&[])`.
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.find(key).and_then(|(_, value)| value.as_deref())
+    }
+
+    /// Returns the value of the key as a string, `None` if it is not valid UTF-8
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(|value| str::from_utf8(value).ok())
+    }
+
+    /// Adds a property, ignored if the key is already present or invalid
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - printable US-ASCII, excluding '='
+    /// * `value` - the value, `None` for a boolean attribute
+    ///
+    /// # Return
+    ///
+    /// true if the property was added
+    pub fn insert(&mut self, key: &str, value: Option<&[u8]>) -> bool {
+        let valid = !key.is_empty() && key.bytes().all(|b| (0x20..=0x7E).contains(&b) && b != b'=');
+
+        if !valid || self.contains_key(key) {
+            return false;
+        }
+
+        self.properties
+            .push((key.to_string(), value.map(Box::from)));
+        true
+    }
+
+    /// Iterates over the keys and values in the order of the TXT record
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&[u8]>)> {
+        self.properties
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_deref()))
+    }
+
+    /// The number of properties
+    pub fn len(&self) -> usize {
+        self.properties.len()
+    }
+
+    /// Returns true if there are no properties
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    fn find(&self, key: &str) -> Option<&(String, Option<Box<[u8]>>)> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+    }
+}
+
+impl<'a> From<&'a TXT> for TxtProperties {
+    fn from(txt: &'a TXT) -> Self {
+        let mut properties = Self::new();
+
+        for data in txt.iter() {
+            let (key, value) = match data.iter().position(|b| *b == b'=') {
+                Some(idx) => (&data[..idx], Some(&data[idx + 1..])),
+                None => (&data[..], None),
+            };
+
+            // keys which are not printable US-ASCII are ignored by insert
+            if let Ok(key) = str::from_utf8(key) {
+                properties.insert(key, value);
+            }
+        }
+
+        properties
+    }
+}
+
+impl From<TxtProperties> for TXT {
+    /// The TXT record data, a single empty string if there are no properties
+    fn from(properties: TxtProperties) -> Self {
+        if properties.is_empty() {
+            return Self::from_bytes(vec![&[][..]]);
+        }
+
+        let data = properties
+            .iter()
+            .map(|(key, value)| {
+                let mut data = key.as_bytes().to_vec();
+                if let Some(value) = value {
+                    data.push(b'=');
+                    data.extend_from_slice(value);
+                }
+                data
+            })
+            .collect::<Vec<_>>();
+
+        Self::from_bytes(data.iter().map(Vec::as_slice).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let txt = TXT::from_bytes(vec![
+            &b"txtvers=1"[..],
+            &b"PaperSize=A4"[..],
+            &b"papersize=Letter"[..],
+            &b"Color"[..],
+            &b"note="[..],
+            &b"=ignored"[..],
+            &b"bin=\xff\x00"[..],
+        ]);
+        let properties = TxtProperties::from(&txt);
+
+        assert_eq!(properties.len(), 5);
+        assert_eq!(properties.get_str("txtvers"), Some("1"));
+        assert_eq!(properties.get_str("papersize"), Some("A4"));
+        assert!(properties.contains_key("color"));
+        assert_eq!(properties.get("color"), None);
+        assert_eq!(properties.get("note"), Some(&b""[..]));
+        assert_eq!(properties.get("bin"), Some(&b"\xff\x00"[..]));
+        assert_eq!(properties.get_str("bin"), None);
+        assert!(!properties.contains_key("missing"));
+    }
+
+    #[test]
+    fn test_to_txt() {
+        let mut properties = TxtProperties::new();
+        assert!(properties.insert("path", Some(&b"/printer"[..])));
+        assert!(properties.insert("duplex", None));
+        assert!(!properties.insert("PATH", Some(&b"/other"[..])));
+        assert!(!properties.insert("bad=key", None));
+        assert!(!properties.insert("", None));
+
+        let txt = TXT::from(properties.clone());
+        assert_eq!(
+            txt.txt_data(),
+            &[
+                b"path=/printer".to_vec().into_boxed_slice(),
+                b"duplex".to_vec().into_boxed_slice(),
+            ]
+        );
+        assert_eq!(TxtProperties::from(&txt), properties);
+
+        let empty = TXT::from(TxtProperties::new());
+        assert_eq!(empty.txt_data().len(), 1);
+        assert!(empty.txt_data()[0].is_empty());
+    }
+}
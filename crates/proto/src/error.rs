@@ -9,7 +9,10 @@
 
 #![deny(missing_docs)]
 
-use std::{fmt, io, sync};
+use std::{
+    fmt, io,
+    sync::{self, Arc},
+};
 
 #[cfg(not(feature = "openssl"))]
 use self::not_openssl::SslErrorStack;
@@ -27,9 +30,10 @@ use openssl::error::ErrorStack as SslErrorStack;
 use ring::error::Unspecified;
 use thiserror::Error;
 
-use crate::op::Header;
-use crate::rr::{Name, RecordType};
+use crate::op::{Header, Query, ResponseCode};
+use crate::rr::{Name, Record, RecordType};
 use crate::serialize::binary::DecodeError;
+use crate::xfer::DnsResponse;
 
 #[cfg(feature = "backtrace")]
 #[cfg_attr(docsrs, doc(cfg(feature = "backtrace")))]
@@ -63,6 +67,39 @@ macro_rules! trace {
 /// An alias for results returned by functions of this crate
 pub type ProtoResult<T> = ::std::result::Result<T, ProtoError>;
 
+/// The broad category of an error, for callers which need to decide how to react to it, e.g. if
+///  a request should be retried, without matching on every specific kind
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCategory {
+    /// An I/O error from the underlying socket or stream
+    Io,
+    /// The request timed out
+    Timeout,
+    /// An internal resource is too busy, the request was not attempted
+    Busy,
+    /// The data on the wire or in a file could not be parsed or is invalid
+    Protocol,
+    /// The name server responded, but without any records for the query, see
+    ///  [`ProtoErrorKind::NoRecordsFound`]
+    NoRecords,
+    /// DNSSEC validation or the proof of a negative response failed
+    Dnssec,
+    /// An error specific to an encrypted or multiplexed transport, e.g. TLS or QUIC
+    Transport,
+    /// Any other error, e.g. with an arbitrary message
+    Other,
+}
+
+impl ErrorCategory {
+    /// Whether the request may succeed if it is sent again, possibly to another name server
+    ///
+    /// Negative responses and DNSSEC failures are final, all other errors are retryable.
+    pub fn is_retryable(self) -> bool {
+        !matches!(self, Self::NoRecords | Self::Dnssec)
+    }
+}
+
 /// The error kind for errors that get returned in the crate
 #[derive(Debug, EnumAsInner, Error)]
 #[non_exhaustive]
@@ -164,6 +201,37 @@ pub enum ProtoErrorKind {
     #[error("no error specified")]
     NoError,
 
+    /// No records were found for a query
+    #[error("no record found for {query}, response code: {response_code}")]
+    NoRecordsFound {
+        /// The query for which no records were found
+        query: Box<Query>,
+        /// If an SOA is present, then this is an authoritative response or a referral to another
+        ///  nameserver
+        soa: Option<Box<Record>>,
+        /// Negative TTL, as determined from `DnsResponse::negative_ttl`, this will only be present
+        ///  if the SOA was also present
+        negative_ttl: Option<u32>,
+        /// If `NXDOMAIN`, the domain does not exist (and no other types). If `NoError`, then the
+        ///  domain exists but there exist either other types at the same label, or subzones of
+        ///  that label.
+        response_code: ResponseCode,
+        /// If `NXDOMAIN` errors from this server are trusted
+        trusted: bool,
+        /// The records of the authority section of the response, e.g. the NSEC or NSEC3 records
+        ///  proving the denial of existence, `None` if the error was not built from a response
+        authorities: Option<Arc<[Record]>>,
+    },
+
+    /// A negative response could not be proven
+    #[error("could not validate negative response for {query} with {proof}")]
+    NegativeProofInvalid {
+        /// The query of the negative response
+        query: Box<Query>,
+        /// The record type of the missing or invalid proof, `NSEC`, `NSEC3` or `SOA`
+        proof: RecordType,
+    },
+
     /// Not all records were able to be written
     #[error("not all records could be written, wrote: {count}")]
     NotAllRecordsWritten {
@@ -180,6 +248,15 @@ pub enum ProtoErrorKind {
         record_type: RecordType,
     },
 
+    /// None of the signatures of a record set could be verified
+    #[error("rrset could not be verified name: {name} record_type: {record_type}")]
+    RrsetNotVerified {
+        /// The record set name
+        name: Name,
+        /// The record type
+        record_type: RecordType,
+    },
+
     /// An unknown algorithm type was found
     #[error("algorithm type value unknown: {0}")]
     UnknownAlgorithmTypeValue(u8),
@@ -305,16 +382,158 @@ pub struct ProtoError {
     pub backtrack: Option<ExtBacktrace>,
 }
 
+impl ProtoErrorKind {
+    /// The broad category of this error
+    pub fn category(&self) -> ErrorCategory {
+        use self::ProtoErrorKind::*;
+        match *self {
+            Io(..) | Poisoned => ErrorCategory::Io,
+            Timeout | Timer => ErrorCategory::Timeout,
+            Busy => ErrorCategory::Busy,
+            NoRecordsFound { .. } => ErrorCategory::NoRecords,
+            NegativeProofInvalid { .. }
+            | RrsetNotVerified { .. }
+            | RrsigsNotPresent { .. }
+            | HmacInvalid() => ErrorCategory::Dnssec,
+            Ring(..) | SSL(..) => ErrorCategory::Transport,
+            #[cfg(feature = "quinn")]
+            QuinnConnect(..)
+            | QuinnConnection(..)
+            | QuinnWriteError(..)
+            | QuinnReadError(..)
+            | QuinnConfigError(..)
+            | QuinnUnknownStreamError
+            | QuicMessageIdNot0(..) => ErrorCategory::Transport,
+            #[cfg(feature = "rustls")]
+            RustlsError(..) => ErrorCategory::Transport,
+            BadQueryCount(..)
+            | CharacterDataTooLong { .. }
+            | LabelOverlapsWithOther { .. }
+            | DnsKeyProtocolNot3(..)
+            | DomainNameTooLong(..)
+            | EdnsNameNotRoot(..)
+            | FormError { .. }
+            | IncorrectRDataLengthRead { .. }
+            | LabelBytesTooLong(..)
+            | PointerNotPriorToLabel { .. }
+            | MaxBufferSizeExceeded(..)
+            | NotAllRecordsWritten { .. }
+            | UnknownAlgorithmTypeValue(..)
+            | UnknownDnsClassStr(..)
+            | UnknownDnsClassValue(..)
+            | UnknownRecordTypeStr(..)
+            | UnknownRecordTypeValue(..)
+            | UnrecognizedLabelCode(..)
+            | UnrecognizedNsec3Flags(..)
+            | UnrecognizedCsyncFlags(..)
+            | UrlParsing(..)
+            | Utf8(..)
+            | FromUtf8(..)
+            | ParseInt(..) => ErrorCategory::Protocol,
+            Canceled(..) | Message(..) | Msg(..) | NoError => ErrorCategory::Other,
+        }
+    }
+}
+
 impl ProtoError {
+    /// Construct a [`ProtoErrorKind::NoRecordsFound`] error
+    pub fn nx_error(
+        query: Query,
+        soa: Option<Record>,
+        negative_ttl: Option<u32>,
+        response_code: ResponseCode,
+        trusted: bool,
+    ) -> Self {
+        ProtoErrorKind::NoRecordsFound {
+            query: Box::new(query),
+            soa: soa.map(Box::new),
+            negative_ttl,
+            response_code,
+            trusted,
+            authorities: None,
+        }
+        .into()
+    }
+
     /// Get the kind of the error
     pub fn kind(&self) -> &ProtoErrorKind {
         &self.kind
     }
 
+    /// The broad category of the error
+    pub fn category(&self) -> ErrorCategory {
+        self.kind.category()
+    }
+
     /// If this is a ProtoErrorKind::Busy
     pub fn is_busy(&self) -> bool {
         matches!(*self.kind, ProtoErrorKind::Busy)
     }
+
+    /// If this is a ProtoErrorKind::Timeout
+    pub fn is_timeout(&self) -> bool {
+        matches!(*self.kind, ProtoErrorKind::Timeout)
+    }
+
+    /// If this is a ProtoErrorKind::NoRecordsFound
+    pub fn is_no_records_found(&self) -> bool {
+        matches!(*self.kind, ProtoErrorKind::NoRecordsFound { .. })
+    }
+
+    /// If this is a ProtoErrorKind::NoRecordsFound with an `NXDOMAIN` response code
+    pub fn is_nx_domain(&self) -> bool {
+        matches!(
+            *self.kind,
+            ProtoErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NXDomain,
+                ..
+            }
+        )
+    }
+
+    /// Converts a response into a [`ProtoErrorKind::NoRecordsFound`] error if its response code
+    ///  is an error, or if it has no answers
+    ///
+    /// Responses with `NXDOMAIN` or `NoError` and answers, e.g. `CNAME` referrals, and truncated
+    ///  responses are returned as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - the response to check
+    /// * `trust_nx` - if `NXDOMAIN` responses from this server are trusted
+    pub fn from_response(response: DnsResponse, trust_nx: bool) -> Result<DnsResponse, Self> {
+        match response.response_code() {
+            ResponseCode::NXDomain | ResponseCode::NoError
+                if response.contains_answer() || response.truncated() =>
+            {
+                Ok(response)
+            }
+            ResponseCode::Unknown(_) => Ok(response),
+            response_code => {
+                let mut response = response;
+                let soa = response.soa().cloned();
+                let query = response.take_queries().drain(..).next().unwrap_or_default();
+
+                // only negative responses to the query carry a negative TTL
+                let (negative_ttl, trusted) = match response_code {
+                    ResponseCode::NXDomain => (response.negative_ttl(), trust_nx),
+                    ResponseCode::NoError => (response.negative_ttl(), false),
+                    _ => (None, false),
+                };
+                let authorities = Arc::from(response.take_name_servers());
+
+                Err(ProtoErrorKind::NoRecordsFound {
+                    query: Box::new(query),
+                    soa: soa.map(Box::new),
+                    negative_ttl,
+                    response_code,
+                    trusted,
+                    authorities: Some(authorities),
+                }
+                .into())
+            }
+        }
+    }
 }
 
 impl fmt::Display for ProtoError {
@@ -487,6 +706,25 @@ impl Clone for ProtoErrorKind {
             Message(msg) => Message(msg),
             Msg(ref msg) => Msg(msg.clone()),
             NoError => NoError,
+            NoRecordsFound {
+                ref query,
+                ref soa,
+                negative_ttl,
+                response_code,
+                trusted,
+                ref authorities,
+            } => NoRecordsFound {
+                query: query.clone(),
+                soa: soa.clone(),
+                negative_ttl,
+                response_code,
+                trusted,
+                authorities: authorities.clone(),
+            },
+            NegativeProofInvalid { ref query, proof } => NegativeProofInvalid {
+                query: query.clone(),
+                proof,
+            },
             NotAllRecordsWritten { count } => NotAllRecordsWritten { count },
             RrsigsNotPresent {
                 ref name,
@@ -495,6 +733,13 @@ impl Clone for ProtoErrorKind {
                 name: name.clone(),
                 record_type: *record_type,
             },
+            RrsetNotVerified {
+                ref name,
+                record_type,
+            } => RrsetNotVerified {
+                name: name.clone(),
+                record_type,
+            },
             UnknownAlgorithmTypeValue(value) => UnknownAlgorithmTypeValue(value),
             UnknownDnsClassStr(ref value) => UnknownDnsClassStr(value.clone()),
             UnknownDnsClassValue(value) => UnknownDnsClassValue(value),
@@ -5,44 +5,75 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::convert::TryInto;
 use std::fmt::{self, Display};
 use std::future::Future;
-use std::io;
+use std::marker::PhantomData;
 use std::net::SocketAddr;
-use std::ops::DerefMut;
 use std::pin::Pin;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 use bytes::{Buf, Bytes, BytesMut};
+#[cfg(feature = "dns-over-rustls")]
+use futures_util::future;
 use futures_util::future::{FutureExt, TryFutureExt};
-use futures_util::ready;
 use futures_util::stream::Stream;
-use h2::client::{Connection, SendRequest};
-use http::header::{self, CONTENT_LENGTH};
+use h2::client::SendRequest;
+use http::header::{self, CONTENT_LENGTH, RETRY_AFTER};
+use http::{HeaderMap, StatusCode};
+#[cfg(feature = "dns-over-rustls")]
 use rustls::ClientConfig;
-use tokio_rustls::{
-    client::TlsStream as TokioTlsClientStream, Connect as TokioTlsConnect, TlsConnector,
-};
+#[cfg(feature = "dns-over-rustls")]
+use tokio_rustls::TlsConnector;
 use tracing::{debug, warn};
 
-use crate::error::ProtoError;
-use crate::iocompat::AsyncIoStdAsTokio;
+use crate::error::{ProtoError, ProtoErrorKind};
+use crate::https::request::{self, HttpsMethod};
+use crate::op::PaddingPolicy;
 use crate::tcp::Connect;
 use crate::xfer::{DnsRequest, DnsRequestSender, DnsResponse, DnsResponseStream, SerialMessage};
 
+#[cfg(feature = "dns-over-rustls")]
 const ALPN_H2: &[u8] = b"h2";
 
+/// Default maximum number of HTTP/2 connections opened to a name server
+const DEFAULT_MAX_CONNECTIONS: usize = 2;
+
+/// Default maximum number of concurrent requests on one connection before another is opened,
+///  RFC 7540 recommends servers allow at least 100 concurrent streams
+const DEFAULT_MAX_CONCURRENT_STREAMS: usize = 100;
+
+/// Delay before retrying a server which answered 429 or 503 without a usable `Retry-After`
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Upper bound of the `Retry-After` delay honored by the client
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// Spawns the background task driving the HTTP/2 connection, see [`HttpsClientStreamBuilder::spawner`]
+pub type HttpsSpawner = Arc<dyn Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync>;
+
+type H2Connect = Pin<Box<dyn Future<Output = Result<SendRequest<Bytes>, ProtoError>> + Send>>;
+type H2Connector = Arc<dyn Fn() -> H2Connect + Send + Sync>;
+
 /// A DNS client connection for DNS-over-HTTPS
+///
+/// Queries are multiplexed over a pool of HTTP/2 connections to the name server, a new
+///  connection is only opened when all existing ones carry the maximum number of concurrent
+///  requests, see [`HttpsClientStreamBuilder::max_connections`].
 #[derive(Clone)]
 #[must_use = "futures do nothing unless polled"]
 pub struct HttpsClientStream {
     // Corresponds to the dns-name of the HTTPS server
     name_server_name: Arc<str>,
+    // Path of the DoH endpoint on the server, e.g. `/dns-query`
+    query_path: Arc<str>,
     name_server: SocketAddr,
-    h2: SendRequest<Bytes>,
+    pool: Arc<H2Pool>,
+    method: HttpsMethod,
+    padding: PaddingPolicy,
     is_shutdown: bool,
 }
 
@@ -57,36 +88,55 @@ impl Display for HttpsClientStream {
 }
 
 impl HttpsClientStream {
+    /// Returns the number of HTTP/2 connections currently open to the name server
+    pub fn connections(&self) -> usize {
+        self.pool.lock().connections.len()
+    }
+
     async fn inner_send(
-        h2: SendRequest<Bytes>,
+        pool: Arc<H2Pool>,
         message: Bytes,
+        method: HttpsMethod,
         name_server_name: Arc<str>,
+        query_path: Arc<str>,
         name_server: SocketAddr,
     ) -> Result<DnsResponse, ProtoError> {
+        pool.check_retry_after()?;
+
+        let (h2, slot) = pool.acquire().await?;
         let mut h2 = match h2.ready().await {
             Ok(h2) => h2,
             Err(err) => {
+                // the connection is dead, following requests will open a new one
+                pool.remove(slot.connection_id);
+
                 // TODO: make specific error
                 return Err(ProtoError::from(format!("h2 send_request error: {}", err)));
             }
         };
 
         // build up the http request
-        let request = crate::https::request::new(&name_server_name, message.remaining());
+        let request = match method {
+            HttpsMethod::Post => request::new(&name_server_name, &query_path, message.remaining()),
+            HttpsMethod::Get => request::new_get(&name_server_name, &query_path, &message),
+        };
 
         let request =
             request.map_err(|err| ProtoError::from(format!("bad http request: {}", err)))?;
 
         debug!("request: {:#?}", request);
 
-        // Send the request
+        // Send the request, GET requests have no body
+        let end_of_stream = method == HttpsMethod::Get;
         let (response_future, mut send_stream) = h2
-            .send_request(request, false)
+            .send_request(request, end_of_stream)
             .map_err(|err| ProtoError::from(format!("h2 send_request error: {}", err)))?;
 
-        send_stream
-            .send_data(message, true)
-            .map_err(|e| ProtoError::from(format!("h2 send_data error: {}", e)))?;
+        if !end_of_stream {
+            send_stream
+                .send_data(message, true)
+                .map_err(|e| ProtoError::from(format!("h2 send_data error: {}", e)))?;
+        }
 
         let mut response_stream = response_future
             .await
@@ -94,6 +144,19 @@ impl HttpsClientStream {
 
         debug!("got response: {:#?}", response_stream);
 
+        // the server is overloaded, stop sending it requests for a while
+        let status = response_stream.status();
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+            let delay = retry_after(response_stream.headers());
+            warn!(
+                "{} responded with {}, retrying after {:?}",
+                name_server, status, delay
+            );
+
+            pool.set_retry_after(delay);
+            return Err(ProtoErrorKind::Busy.into());
+        }
+
         // get the length of packet
         let content_length = response_stream
             .headers()
@@ -126,6 +189,9 @@ impl HttpsClientStream {
             }
         }
 
+        // the stream is complete, the slot can be used by another request
+        drop(slot);
+
         // assert the length
         if let Some(content_length) = content_length {
             if response_bytes.len() != content_length {
@@ -235,15 +301,21 @@ impl DnsRequestSender for HttpsClientStream {
         // per the RFC, a zero id allows for the HTTP packet to be cached better
         message.set_id(0);
 
+        if let Err(err) = message.pad(self.padding) {
+            return err.into();
+        }
+
         let bytes = match message.to_vec() {
             Ok(bytes) => bytes,
             Err(err) => return err.into(),
         };
 
         Box::pin(Self::inner_send(
-            self.h2.clone(),
+            Arc::clone(&self.pool),
             Bytes::from(bytes),
+            self.method,
             Arc::clone(&self.name_server_name),
+            Arc::clone(&self.query_path),
             self.name_server,
         ))
         .into()
@@ -261,36 +333,228 @@ impl DnsRequestSender for HttpsClientStream {
 impl Stream for HttpsClientStream {
     type Item = Result<(), ProtoError>;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         if self.is_shutdown {
             return Poll::Ready(None);
         }
 
-        // just checking if the connection is ok
-        match self.h2.poll_ready(cx) {
-            Poll::Ready(Ok(())) => Poll::Ready(Some(Ok(()))),
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(ProtoError::from(format!(
-                "h2 stream errored: {}",
-                e
-            ))))),
+        // dead connections are replaced by the pool when the next request is sent
+        Poll::Ready(Some(Ok(())))
+    }
+}
+
+/// Returns the delay requested by the `Retry-After` header of a 429 or 503 response
+///
+/// Only the delay-seconds form is supported, an HTTP-date falls back to the default delay.
+fn retry_after(headers: &HeaderMap) -> Duration {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| u64::from_str(value.trim()).ok())
+        .map_or(DEFAULT_RETRY_AFTER, |secs| {
+            Duration::from_secs(secs).min(MAX_RETRY_AFTER)
+        })
+}
+
+/// The HTTP/2 connections of an HttpsClientStream
+struct H2Pool {
+    connector: H2Connector,
+    max_connections: usize,
+    max_concurrent_streams: usize,
+    state: Mutex<H2PoolState>,
+}
+
+struct H2PoolState {
+    connections: Vec<PooledConnection>,
+    // connections currently being established
+    connecting: usize,
+    next_id: u64,
+    // the server asked not to be sent requests before this instant
+    retry_after: Option<Instant>,
+}
+
+struct PooledConnection {
+    id: u64,
+    h2: SendRequest<Bytes>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl PooledConnection {
+    fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    fn slot(&self) -> (SendRequest<Bytes>, StreamSlot) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+
+        (
+            self.h2.clone(),
+            StreamSlot {
+                connection_id: self.id,
+                in_flight: Arc::clone(&self.in_flight),
+            },
+        )
+    }
+}
+
+/// A request in flight on a pooled connection, the slot is released on drop
+struct StreamSlot {
+    connection_id: u64,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl H2Pool {
+    fn new(connector: H2Connector, max_connections: usize, max_concurrent_streams: usize) -> Self {
+        Self {
+            connector,
+            max_connections: max_connections.max(1),
+            max_concurrent_streams: max_concurrent_streams.max(1),
+            state: Mutex::new(H2PoolState {
+                connections: Vec::new(),
+                connecting: 0,
+                next_id: 0,
+                retry_after: None,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, H2PoolState> {
+        self.state.lock().expect("h2 pool lock poisoned")
+    }
+
+    /// Adds an established connection to the pool
+    fn insert(&self, h2: SendRequest<Bytes>) -> (SendRequest<Bytes>, StreamSlot) {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let connection = PooledConnection {
+            id,
+            h2,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        };
+        let slot = connection.slot();
+        state.connections.push(connection);
+
+        slot
+    }
+
+    /// Removes a failed connection from the pool
+    fn remove(&self, connection_id: u64) {
+        self.lock()
+            .connections
+            .retain(|connection| connection.id != connection_id);
+    }
+
+    /// Returns the least loaded connection, a new one is opened if all connections are at the
+    ///  maximum of concurrent streams and the pool is not full.
+    async fn acquire(&self) -> Result<(SendRequest<Bytes>, StreamSlot), ProtoError> {
+        {
+            let mut state = self.lock();
+            let pool_full = state.connections.len() + state.connecting >= self.max_connections;
+            if let Some(connection) = state
+                .connections
+                .iter()
+                .min_by_key(|connection| connection.in_flight())
+                .filter(|connection| {
+                    pool_full || connection.in_flight() < self.max_concurrent_streams
+                })
+            {
+                return Ok(connection.slot());
+            }
+
+            state.connecting += 1;
+        }
+
+        debug!("opening new h2 connection");
+        let connected = (self.connector)().await;
+        self.lock().connecting -= 1;
+
+        match connected {
+            Ok(h2) => Ok(self.insert(h2)),
+            Err(err) => {
+                // fall back to the existing connections, even though they are busy
+                let state = self.lock();
+                match state
+                    .connections
+                    .iter()
+                    .min_by_key(|connection| connection.in_flight())
+                {
+                    Some(connection) => {
+                        warn!("failed to open additional h2 connection: {}", err);
+                        Ok(connection.slot())
+                    }
+                    None => Err(err),
+                }
+            }
         }
     }
+
+    /// Fails with `Busy` if the server asked to back off
+    fn check_retry_after(&self) -> Result<(), ProtoError> {
+        let mut state = self.lock();
+        match state.retry_after {
+            Some(retry_after) if Instant::now() < retry_after => {
+                debug!("server asked to back off, not sending request");
+                Err(ProtoErrorKind::Busy.into())
+            }
+            Some(_) => {
+                state.retry_after = None;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn set_retry_after(&self, delay: Duration) {
+        self.lock().retry_after = Some(Instant::now() + delay);
+    }
 }
 
 /// A HTTPS connection builder for DNS-over-HTTPS
 #[derive(Clone)]
 pub struct HttpsClientStreamBuilder {
-    client_config: Arc<ClientConfig>,
+    #[cfg(feature = "dns-over-rustls")]
+    client_config: Option<Arc<ClientConfig>>,
     bind_addr: Option<SocketAddr>,
+    query_path: Option<String>,
+    spawner: Option<HttpsSpawner>,
+    padding: PaddingPolicy,
+    method: HttpsMethod,
+    max_connections: usize,
+    max_concurrent_streams: usize,
 }
 
 impl HttpsClientStreamBuilder {
+    /// Constructs a new HttpsClientStreamBuilder, the TLS library is passed to
+    ///  [`Self::build_with_connector`]
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "dns-over-rustls")]
+            client_config: None,
+            bind_addr: None,
+            query_path: None,
+            spawner: None,
+            padding: PaddingPolicy::client(),
+            method: HttpsMethod::default(),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            max_concurrent_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+        }
+    }
+
     /// Constructs a new TlsStreamBuilder with the associated ClientConfig
+    #[cfg(feature = "dns-over-rustls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
     pub fn with_client_config(client_config: Arc<ClientConfig>) -> Self {
         Self {
-            client_config,
-            bind_addr: None,
+            client_config: Some(client_config),
+            ..Self::new()
         }
     }
 
@@ -299,40 +563,162 @@ impl HttpsClientStreamBuilder {
         self.bind_addr = Some(bind_addr);
     }
 
-    /// Creates a new HttpsStream to the specified name_server
+    /// Sets the path of the DoH endpoint on the server, defaults to `/dns-query`.
+    pub fn query_path(&mut self, query_path: String) {
+        self.query_path = Some(query_path);
+    }
+
+    /// Sets the spawner for the background task which drives the HTTP/2 connection.
+    ///
+    /// This allows the stream to be used on any runtime, without a spawner the task is spawned
+    ///  with `tokio::spawn`, which requires the `tokio-runtime` feature.
+    pub fn spawner(&mut self, spawner: HttpsSpawner) {
+        self.spawner = Some(spawner);
+    }
+
+    /// Sets the padding of requests, defaults to the block-length padding recommended for clients
+    ///  by RFC 8467.
+    pub fn padding(&mut self, padding: PaddingPolicy) {
+        self.padding = padding;
+    }
+
+    /// Sets the HTTP method of requests, defaults to POST.
+    ///
+    /// GET requests allow responses to be cached by HTTP caches between the client and the server.
+    pub fn method(&mut self, method: HttpsMethod) {
+        self.method = method;
+    }
+
+    /// Sets the maximum number of HTTP/2 connections opened to the name server, defaults to 2.
+    pub fn max_connections(&mut self, max_connections: usize) {
+        self.max_connections = max_connections;
+    }
+
+    /// Sets the number of concurrent requests on a connection after which another connection
+    ///  is opened, defaults to 100.
+    ///
+    /// Once the pool is full requests are sent on the least loaded connection, where they may wait
+    ///  for the concurrent stream limit announced by the server.
+    pub fn max_concurrent_streams(&mut self, max_concurrent_streams: usize) {
+        self.max_concurrent_streams = max_concurrent_streams;
+    }
+
+    /// Creates a new HttpsStream to the specified name_server, secured with rustls
     ///
     /// # Arguments
     ///
     /// * `name_server` - IP and Port for the remote DNS resolver
     /// * `dns_name` - The DNS name, Subject Public Key Info (SPKI) name, as associated to a certificate
+    #[cfg(feature = "dns-over-rustls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
     pub fn build<S: Connect>(
         mut self,
         name_server: SocketAddr,
         dns_name: String,
     ) -> HttpsClientConnect<S> {
+        let client_config =
+            match self.client_config.take() {
+                Some(client_config) => client_config,
+                None => return HttpsClientConnect(
+                    Box::pin(future::err(ProtoError::from(
+                        "no rustls ClientConfig, see HttpsClientStreamBuilder::with_client_config",
+                    ))),
+                    PhantomData,
+                ),
+            };
+
         // ensure the ALPN protocol is set correctly
-        if self.client_config.alpn_protocols.is_empty() {
-            let mut client_config = (*self.client_config).clone();
+        let client_config = if client_config.alpn_protocols.is_empty() {
+            let mut client_config = (*client_config).clone();
             client_config.alpn_protocols = vec![ALPN_H2.to_vec()];
 
-            self.client_config = Arc::new(client_config);
-        }
+            Arc::new(client_config)
+        } else {
+            client_config
+        };
+
+        self.build_with_connector(name_server, dns_name, TlsConnector::from(client_config))
+    }
 
-        let tls = TlsConfig {
-            client_config: self.client_config,
-            dns_name: Arc::from(dns_name),
+    /// Creates a new HttpsStream to the specified name_server, secured by any TLS library
+    ///
+    /// The connector must offer the `h2` ALPN protocol, e.g. with
+    ///  `native_tls::TlsConnectorBuilder::request_alpns` or
+    ///  `openssl::ssl::SslConnectorBuilder::set_alpn_protos`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - IP and Port for the remote DNS resolver
+    /// * `dns_name` - The DNS name, Subject Public Key Info (SPKI) name, as associated to a certificate
+    /// * `connector` - the TLS library and its configuration
+    pub fn build_with_connector<S, T>(
+        self,
+        name_server: SocketAddr,
+        dns_name: String,
+        connector: T,
+    ) -> HttpsClientConnect<S>
+    where
+        S: Connect,
+        T: crate::tls::TlsConnector<S>,
+    {
+        let name_server_name: Arc<str> = Arc::from(dns_name);
+        let bind_addr = self.bind_addr;
+        let spawner = self.spawner;
+        let connector: H2Connector = {
+            let name_server_name = Arc::clone(&name_server_name);
+            Arc::new(move || -> H2Connect {
+                Box::pin(connect_h2::<S, T>(
+                    name_server,
+                    bind_addr,
+                    Arc::clone(&name_server_name),
+                    connector.clone(),
+                    spawner.clone(),
+                ))
+            })
         };
 
-        HttpsClientConnect::<S>(HttpsClientConnectState::ConnectTcp {
-            name_server,
-            bind_addr: self.bind_addr,
-            tls: Some(tls),
-        })
+        let pool = H2Pool::new(
+            Arc::clone(&connector),
+            self.max_connections,
+            self.max_concurrent_streams,
+        );
+        let query_path = self
+            .query_path
+            .map_or_else(|| Arc::from(crate::https::DNS_QUERY_PATH), Arc::from);
+        let method = self.method;
+        let padding = self.padding;
+
+        // the first connection is established eagerly, to report connection errors early
+        let connect = async move {
+            let h2 = connector().await?;
+            drop(pool.insert(h2));
+
+            Ok(HttpsClientStream {
+                name_server_name,
+                query_path,
+                name_server,
+                pool: Arc::new(pool),
+                method,
+                padding,
+                is_shutdown: false,
+            })
+        };
+
+        HttpsClientConnect(Box::pin(connect), PhantomData)
+    }
+}
+
+impl Default for HttpsClientStreamBuilder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 /// A future that resolves to an HttpsClientStream
-pub struct HttpsClientConnect<S>(HttpsClientConnectState<S>)
+pub struct HttpsClientConnect<S>(
+    Pin<Box<dyn Future<Output = Result<HttpsClientStream, ProtoError>> + Send>>,
+    PhantomData<fn() -> S>,
+)
 where
     S: Connect;
 
@@ -343,161 +729,54 @@ where
     type Output = Result<HttpsClientStream, ProtoError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.0.poll_unpin(cx)
+        self.0.as_mut().poll(cx)
     }
 }
 
-struct TlsConfig {
-    client_config: Arc<ClientConfig>,
+/// Establishes one HTTP/2 connection of the pool
+async fn connect_h2<S, T>(
+    name_server: SocketAddr,
+    bind_addr: Option<SocketAddr>,
     dns_name: Arc<str>,
-}
-
-#[allow(clippy::large_enum_variant)]
-#[allow(clippy::type_complexity)]
-enum HttpsClientConnectState<S>
+    connector: T,
+    spawner: Option<HttpsSpawner>,
+) -> Result<SendRequest<Bytes>, ProtoError>
 where
     S: Connect,
+    T: crate::tls::TlsConnector<S>,
 {
-    ConnectTcp {
-        name_server: SocketAddr,
-        bind_addr: Option<SocketAddr>,
-        tls: Option<TlsConfig>,
-    },
-    TcpConnecting {
-        connect: Pin<Box<dyn Future<Output = io::Result<S>> + Send>>,
-        name_server: SocketAddr,
-        tls: Option<TlsConfig>,
-    },
-    TlsConnecting {
-        // TODO: also abstract away Tokio TLS in RuntimeProvider.
-        tls: TokioTlsConnect<AsyncIoStdAsTokio<S>>,
-        name_server_name: Arc<str>,
-        name_server: SocketAddr,
-    },
-    H2Handshake {
-        handshake: Pin<
-            Box<
-                dyn Future<
-                        Output = Result<
-                            (
-                                SendRequest<Bytes>,
-                                Connection<TokioTlsClientStream<AsyncIoStdAsTokio<S>>, Bytes>,
-                            ),
-                            h2::Error,
-                        >,
-                    > + Send,
-            >,
-        >,
-        name_server_name: Arc<str>,
-        name_server: SocketAddr,
-    },
-    Connected(Option<HttpsClientStream>),
-    Errored(Option<ProtoError>),
-}
-
-impl<S> Future for HttpsClientConnectState<S>
-where
-    S: Connect,
-{
-    type Output = Result<HttpsClientStream, ProtoError>;
-
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        loop {
-            let next = match *self {
-                Self::ConnectTcp {
-                    name_server,
-                    bind_addr,
-                    ref mut tls,
-                } => {
-                    debug!("tcp connecting to: {}", name_server);
-                    let connect = S::connect_with_bind(name_server, bind_addr);
-                    Self::TcpConnecting {
-                        connect,
-                        name_server,
-                        tls: tls.take(),
-                    }
-                }
-                Self::TcpConnecting {
-                    ref mut connect,
-                    name_server,
-                    ref mut tls,
-                } => {
-                    let tcp = ready!(connect.poll_unpin(cx))?;
-
-                    debug!("tcp connection established to: {}", name_server);
-                    let tls = tls
-                        .take()
-                        .expect("programming error, tls should not be None here");
-                    let name_server_name = Arc::clone(&tls.dns_name);
-
-                    match tls.dns_name.as_ref().try_into() {
-                        Ok(dns_name) => {
-                            let tls = TlsConnector::from(tls.client_config);
-                            let tls = tls.connect(dns_name, AsyncIoStdAsTokio(tcp));
-                            Self::TlsConnecting {
-                                name_server_name,
-                                name_server,
-                                tls,
-                            }
-                        }
-                        Err(_) => Self::Errored(Some(ProtoError::from(format!(
-                            "bad dns_name: {}",
-                            &tls.dns_name
-                        )))),
-                    }
-                }
-                Self::TlsConnecting {
-                    ref name_server_name,
-                    name_server,
-                    ref mut tls,
-                } => {
-                    let tls = ready!(tls.poll_unpin(cx))?;
-                    debug!("tls connection established to: {}", name_server);
-                    let mut handshake = h2::client::Builder::new();
-                    handshake.enable_push(false);
-
-                    let handshake = handshake.handshake(tls);
-                    Self::H2Handshake {
-                        name_server_name: Arc::clone(name_server_name),
-                        name_server,
-                        handshake: Box::pin(handshake),
-                    }
-                }
-                Self::H2Handshake {
-                    ref name_server_name,
-                    name_server,
-                    ref mut handshake,
-                } => {
-                    let (send_request, connection) = ready!(handshake
-                        .poll_unpin(cx)
-                        .map_err(|e| ProtoError::from(format!("h2 handshake error: {}", e))))?;
-
-                    // TODO: hand this back for others to run rather than spawning here?
-                    debug!("h2 connection established to: {}", name_server);
-                    tokio::spawn(
-                        connection
-                            .map_err(|e| warn!("h2 connection failed: {}", e))
-                            .map(|_: Result<(), ()>| ()),
-                    );
-
-                    Self::Connected(Some(HttpsClientStream {
-                        name_server_name: Arc::clone(name_server_name),
-                        name_server,
-                        h2: send_request,
-                        is_shutdown: false,
-                    }))
-                }
-                Self::Connected(ref mut conn) => {
-                    return Poll::Ready(Ok(conn.take().expect("cannot poll after complete")))
-                }
-                Self::Errored(ref mut err) => {
-                    return Poll::Ready(Err(err.take().expect("cannot poll after complete")))
-                }
-            };
-
-            *self.as_mut().deref_mut() = next;
+    debug!("tcp connecting to: {}", name_server);
+    let tcp = S::connect_with_bind(name_server, bind_addr).await?;
+
+    debug!("tcp connection established to: {}", name_server);
+    let tls = connector.connect(&dns_name, tcp).await?;
+
+    debug!("tls connection established to: {}", name_server);
+    let mut handshake = h2::client::Builder::new();
+    handshake.enable_push(false);
+
+    let (send_request, connection) = handshake
+        .handshake(tls)
+        .await
+        .map_err(|e| ProtoError::from(format!("h2 handshake error: {}", e)))?;
+
+    debug!("h2 connection established to: {}", name_server);
+    let connection = connection
+        .map_err(|e| warn!("h2 connection failed: {}", e))
+        .map(|_: Result<(), ()>| ());
+    match spawner {
+        Some(spawner) => spawner(Box::pin(connection)),
+        #[cfg(feature = "tokio-runtime")]
+        None => drop(tokio::spawn(connection)),
+        #[cfg(not(feature = "tokio-runtime"))]
+        None => {
+            return Err(ProtoError::from(
+                "no spawner for the h2 connection, see HttpsClientStreamBuilder::spawner",
+            ))
         }
     }
+
+    Ok(send_request)
 }
 
 /// A future that resolves to
@@ -513,7 +792,7 @@ impl Future for HttpsClientResponse {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "dns-over-https-rustls"))]
 mod tests {
     use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
     use std::str::FromStr;
@@ -594,6 +873,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_https_google_get() {
+        let google = SocketAddr::from(([8, 8, 8, 8], 443));
+        let mut request = Message::new();
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        request.add_query(query);
+
+        let request = DnsRequest::new(request, DnsRequestOptions::default());
+
+        let client_config = client_config_tls12_webpki_roots();
+        let mut https_builder =
+            HttpsClientStreamBuilder::with_client_config(Arc::new(client_config));
+        https_builder.method(HttpsMethod::Get);
+        let connect = https_builder
+            .build::<AsyncIoTokioAsStd<TokioTcpStream>>(google, "dns.google".to_string());
+
+        // tokio runtime stuff...
+        let runtime = Runtime::new().expect("could not start runtime");
+        let mut https = runtime.block_on(connect).expect("https connect failed");
+
+        let response = runtime
+            .block_on(https.send_message(request).first_answer())
+            .expect("send_message failed");
+
+        let record = &response.answers()[0];
+        let addr = record
+            .data()
+            .and_then(RData::as_a)
+            .expect("Expected A record");
+
+        assert_eq!(addr, &Ipv4Addr::new(93, 184, 216, 34));
+        assert_eq!(https.connections(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-native-tls")]
+    fn test_https_google_native_tls() {
+        let google = SocketAddr::from(([8, 8, 8, 8], 443));
+        let mut request = Message::new();
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        request.add_query(query);
+
+        let request = DnsRequest::new(request, DnsRequestOptions::default());
+
+        let mut tls = native_tls::TlsConnector::builder();
+        tls.request_alpns(&["h2"]);
+        let tls = tls.build().expect("failed to build native-tls connector");
+
+        let connect = HttpsClientStreamBuilder::new()
+            .build_with_connector::<AsyncIoTokioAsStd<TokioTcpStream>, _>(
+                google,
+                "dns.google".to_string(),
+                tokio_native_tls::TlsConnector::from(tls),
+            );
+
+        // tokio runtime stuff...
+        let runtime = Runtime::new().expect("could not start runtime");
+        let mut https = runtime.block_on(connect).expect("https connect failed");
+
+        let response = runtime
+            .block_on(https.send_message(request).first_answer())
+            .expect("send_message failed");
+
+        let record = &response.answers()[0];
+        let addr = record
+            .data()
+            .and_then(RData::as_a)
+            .expect("Expected A record");
+
+        assert_eq!(addr, &Ipv4Addr::new(93, 184, 216, 34));
+    }
+
     #[test]
     #[ignore] // cloudflare has been unreliable as a public test service.
     fn test_https_cloudflare() {
@@ -655,6 +1006,87 @@ mod tests {
         );
     }
 
+    /// Connects to an in-memory h2 server which accepts all streams and never responds
+    fn duplex_connector() -> H2Connector {
+        Arc::new(|| -> H2Connect {
+            Box::pin(async {
+                let (client, server) = tokio::io::duplex(4096);
+                tokio::spawn(async move {
+                    let mut connection = h2::server::handshake(server)
+                        .await
+                        .expect("server handshake failed");
+                    while connection.accept().await.is_some() {}
+                });
+
+                let (h2, connection) = h2::client::handshake(client)
+                    .await
+                    .map_err(|e| ProtoError::from(format!("h2 handshake error: {}", e)))?;
+                tokio::spawn(connection);
+
+                Ok(h2)
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pool_max_connections() {
+        let pool = H2Pool::new(duplex_connector(), 2, 1);
+
+        // each connection carries one stream, so a second connection is opened
+        let (_, first) = pool.acquire().await.expect("acquire failed");
+        let (_, second) = pool.acquire().await.expect("acquire failed");
+        assert_ne!(first.connection_id, second.connection_id);
+        assert_eq!(pool.lock().connections.len(), 2);
+
+        // the pool is full, the least loaded connection is reused
+        drop(second);
+        let (_, third) = pool.acquire().await.expect("acquire failed");
+        assert_eq!(third.connection_id, 1);
+        let (_, fourth) = pool.acquire().await.expect("acquire failed");
+        assert_eq!(fourth.connection_id, 0);
+        assert_eq!(pool.lock().connections.len(), 2);
+        assert_eq!(pool.lock().connections[0].in_flight(), 2);
+
+        // removed connections are not handed out anymore
+        pool.remove(first.connection_id);
+        drop(third);
+        assert_eq!(pool.lock().connections.len(), 1);
+        let (_, fifth) = pool.acquire().await.expect("acquire failed");
+        assert_eq!(fifth.connection_id, 1);
+    }
+
+    #[test]
+    fn test_pool_check_retry_after() {
+        let pool = H2Pool::new(duplex_connector(), 1, 1);
+        assert!(pool.check_retry_after().is_ok());
+
+        pool.set_retry_after(Duration::from_secs(60));
+        assert!(pool.check_retry_after().unwrap_err().is_busy());
+
+        pool.set_retry_after(Duration::from_secs(0));
+        assert!(pool.check_retry_after().is_ok());
+        assert!(pool.lock().retry_after.is_none());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), DEFAULT_RETRY_AFTER);
+
+        headers.insert(RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(retry_after(&headers), Duration::from_secs(120));
+
+        headers.insert(RETRY_AFTER, "86400".parse().unwrap());
+        assert_eq!(retry_after(&headers), MAX_RETRY_AFTER);
+
+        // HTTP-dates are not supported
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), DEFAULT_RETRY_AFTER);
+    }
+
     fn client_config_tls12_webpki_roots() -> ClientConfig {
         use rustls::{OwnedTrustAnchor, RootCertStore};
         let mut root_store = RootCertStore::empty();
@@ -16,7 +16,7 @@ use bytes::{Bytes, BytesMut};
 use futures_util::stream::{Stream, StreamExt};
 use h2;
 use http::header::CONTENT_LENGTH;
-use http::{Method, Request};
+use http::{Method, Request, Uri};
 use tracing::debug;
 
 use crate::https::HttpsError;
@@ -49,12 +49,20 @@ where
     }
 
     match *request.method() {
-        Method::GET => Err(format!("GET unimplemented: {}", request.method()).into()),
+        Method::GET => message_from_get(request.uri()),
         Method::POST => message_from_post(request.into_body(), content_length).await,
         _ => Err(format!("bad method: {}", request.method()).into()),
     }
 }
 
+/// Deserialize the message from the `dns` parameter of a GET message
+pub(crate) fn message_from_get(uri: &Uri) -> Result<BytesMut, HttpsError> {
+    let bytes = crate::https::request::dns_param(uri)?;
+    debug!("got message from GET: {}", bytes.len());
+
+    Ok(BytesMut::from(&bytes[..]))
+}
+
 /// Deserialize the message from a POST message
 pub(crate) async fn message_from_post<R>(
     mut request_stream: R,
@@ -124,7 +132,7 @@ mod tests {
         let msg_bytes = message.to_vec().unwrap();
         let len = msg_bytes.len();
         let stream = TestBytesStream(vec![Ok(Bytes::from(msg_bytes))]);
-        let request = request::new("ns.example.com", len).unwrap();
+        let request = request::new("ns.example.com", "/dns-query", len).unwrap();
         let request = request.map(|()| stream);
 
         let from_post = message_from(Arc::from("ns.example.com"), request);
@@ -136,4 +144,22 @@ mod tests {
         let msg_from_post = Message::from_vec(bytes.as_ref()).expect("bytes failed");
         assert_eq!(message, msg_from_post);
     }
+
+    #[test]
+    fn test_from_get() {
+        let message = Message::new();
+        let msg_bytes = message.to_vec().unwrap();
+        let stream = TestBytesStream(vec![]);
+        let request = request::new_get("ns.example.com", "/dns-query", &msg_bytes).unwrap();
+        let request = request.map(|()| stream);
+
+        let from_get = message_from(Arc::from("ns.example.com"), request);
+        let bytes = match block_on(from_get) {
+            Ok(bytes) => bytes,
+            e => panic!("{:#?}", e),
+        };
+
+        let msg_from_get = Message::from_vec(bytes.as_ref()).expect("bytes failed");
+        assert_eq!(message, msg_from_get);
+    }
 }
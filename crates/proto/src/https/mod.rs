@@ -17,7 +17,9 @@ pub mod request;
 pub mod response;
 
 pub use self::error::{Error as HttpsError, Result as HttpsResult};
+pub use self::request::HttpsMethod;
 
 pub use self::https_client_stream::{
     HttpsClientConnect, HttpsClientResponse, HttpsClientStream, HttpsClientStreamBuilder,
+    HttpsSpawner,
 };
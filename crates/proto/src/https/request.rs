@@ -9,13 +9,46 @@
 
 use std::str::FromStr;
 
+use data_encoding::BASE64URL_NOPAD;
 use http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
-use http::{header, uri, Request, Uri, Version};
+use http::{header, uri, Method, Request, Uri, Version};
 use tracing::debug;
 
 use crate::error::ProtoError;
 use crate::https::HttpsResult;
 
+/// The HTTP method used to send DNS queries
+///
+/// ```text
+/// https://tools.ietf.org/html/rfc8484#section-4.1
+/// DoH servers MUST implement both the POST and GET methods.
+///
+/// When using the POST method, the DNS query is included as the message
+/// body of the HTTP request, and the Content-Type request header field
+/// indicates the media type of the message.
+///
+/// When using the GET method, the data payload for this media type MUST
+/// be encoded with base64url [RFC4648] and then provided as a variable
+/// named "dns" to the URI Template expansion.
+///
+/// Using the GET method is friendlier to many HTTP cache
+/// implementations.
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpsMethod {
+    /// The query is sent as the body of the request
+    Post,
+    /// The query is sent base64url encoded in the `dns` parameter of the URI, this allows
+    ///  responses to be cached by HTTP caches
+    Get,
+}
+
+impl Default for HttpsMethod {
+    fn default() -> Self {
+        Self::Post
+    }
+}
+
 /// Create a new Request for an http/2 dns-message request
 ///
 /// ```text
@@ -26,34 +59,22 @@ use crate::https::HttpsResult;
 /// request (as described in Section 7), encoded with base64url
 /// [RFC4648].
 /// ```
-#[allow(clippy::field_reassign_with_default)] // https://github.com/rust-lang/rust-clippy/issues/6527
-pub fn new(name_server_name: &str, message_len: usize) -> HttpsResult<Request<()>> {
-    // TODO: this is basically the GET version, but it is more expensive than POST
-    //   perhaps add an option if people want better HTTP caching options.
-
-    // let query = BASE64URL_NOPAD.encode(&message);
-    // let url = format!("/dns-query?dns={}", query);
-    // let request = Request::get(&url)
-    //     .header(header::CONTENT_TYPE, ::MIME_DNS_BINARY)
-    //     .header(header::HOST, &self.name_server_name as &str)
-    //     .header("authority", &self.name_server_name as &str)
-    //     .header(header::USER_AGENT, USER_AGENT)
-    //     .body(());
-
-    let mut parts = uri::Parts::default();
-    parts.path_and_query = Some(uri::PathAndQuery::from_static(crate::https::DNS_QUERY_PATH));
-    parts.scheme = Some(uri::Scheme::HTTPS);
-    parts.authority = Some(
-        uri::Authority::from_str(name_server_name)
-            .map_err(|e| ProtoError::from(format!("invalid authority: {}", e)))?,
-    );
-
-    let url =
-        Uri::from_parts(parts).map_err(|e| ProtoError::from(format!("uri parse error: {}", e)))?;
+///
+/// # Arguments
+///
+/// * `name_server_name` - the authority of the DoH server, e.g. `dns.google`
+/// * `query_path` - path of the DoH endpoint on the server, generally `/dns-query`
+/// * `message_len` - length of the DNS message which will be sent as the body
+pub fn new(
+    name_server_name: &str,
+    query_path: &str,
+    message_len: usize,
+) -> HttpsResult<Request<()>> {
+    let url = build_uri(name_server_name, query_path)?;
 
     // TODO: add user agent to TypedHeaders
     let request = Request::builder()
-        .method("POST")
+        .method(Method::POST)
         .uri(url)
         .version(Version::HTTP_2)
         .header(CONTENT_TYPE, crate::https::MIME_APPLICATION_DNS)
@@ -65,6 +86,83 @@ pub fn new(name_server_name: &str, message_len: usize) -> HttpsResult<Request<()
     Ok(request)
 }
 
+/// Create a new GET Request for an http/2 dns-message request, the message is carried in the
+///  `dns` parameter of the URI
+///
+/// ```text
+/// https://tools.ietf.org/html/rfc8484#section-4.1.1
+/// In this example, the 33 bytes of a DNS query for www.example.com are
+/// encoded with base64url and the query is sent with the GET method.
+///
+/// :method = GET
+/// :scheme = https
+/// :authority = dnsserver.example.net
+/// :path = /dns-query?dns=AAABAAABAAAAAAAAA3d3dwdleGFtcGxlA2NvbQAAAQAB
+/// accept = application/dns-message
+/// ```
+///
+/// # Arguments
+///
+/// * `name_server_name` - the authority of the DoH server, e.g. `dns.google`
+/// * `query_path` - path of the DoH endpoint on the server, generally `/dns-query`
+/// * `message` - the DNS message, which should have an id of `0` for better cacheability
+pub fn new_get(
+    name_server_name: &str,
+    query_path: &str,
+    message: &[u8],
+) -> HttpsResult<Request<()>> {
+    let separator = if query_path.contains('?') { '&' } else { '?' };
+    let query_path = format!(
+        "{}{}dns={}",
+        query_path,
+        separator,
+        BASE64URL_NOPAD.encode(message)
+    );
+    let url = build_uri(name_server_name, &query_path)?;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri(url)
+        .version(Version::HTTP_2)
+        .header(ACCEPT, crate::https::MIME_APPLICATION_DNS)
+        .body(())
+        .map_err(|e| ProtoError::from(format!("h2 stream errored: {}", e)))?;
+
+    Ok(request)
+}
+
+#[allow(clippy::field_reassign_with_default)] // https://github.com/rust-lang/rust-clippy/issues/6527
+fn build_uri(name_server_name: &str, path_and_query: &str) -> HttpsResult<Uri> {
+    let mut parts = uri::Parts::default();
+    parts.path_and_query = Some(
+        uri::PathAndQuery::from_str(path_and_query)
+            .map_err(|e| ProtoError::from(format!("invalid query path: {}", e)))?,
+    );
+    parts.scheme = Some(uri::Scheme::HTTPS);
+    parts.authority = Some(
+        uri::Authority::from_str(name_server_name)
+            .map_err(|e| ProtoError::from(format!("invalid authority: {}", e)))?,
+    );
+
+    Ok(Uri::from_parts(parts).map_err(|e| ProtoError::from(format!("uri parse error: {}", e)))?)
+}
+
+/// Returns the base64url decoded `dns` parameter of a GET request's URI
+pub fn dns_param(uri: &Uri) -> HttpsResult<Vec<u8>> {
+    let dns = uri
+        .query()
+        .and_then(|query| {
+            query
+                .split('&')
+                .find_map(|param| param.strip_prefix("dns="))
+        })
+        .ok_or("no dns parameter in GET request")?;
+
+    BASE64URL_NOPAD
+        .decode(dns.as_bytes())
+        .map_err(|e| format!("dns parameter is not base64url: {}", e).into())
+}
+
 /// Verifies the request is something we know what to deal with
 pub fn verify<T>(name_server: &str, request: &Request<T>) -> HttpsResult<()> {
     // Verify all HTTP parameters
@@ -94,11 +192,14 @@ pub fn verify<T>(name_server: &str, request: &Request<T>) -> HttpsResult<()> {
         return Err("no authority in HTTPS request".into());
     }
 
-    // TODO: switch to mime::APPLICATION_DNS when that stabilizes
-    match request.headers().get(CONTENT_TYPE).map(|v| v.to_str()) {
-        Some(Ok(ctype)) if ctype == crate::https::MIME_APPLICATION_DNS => {}
-        _ => return Err("unsupported content type".into()),
-    };
+    // GET requests carry the message in the URI, there is no content
+    if request.method() != Method::GET {
+        // TODO: switch to mime::APPLICATION_DNS when that stabilizes
+        match request.headers().get(CONTENT_TYPE).map(|v| v.to_str()) {
+            Some(Ok(ctype)) if ctype == crate::https::MIME_APPLICATION_DNS => {}
+            _ => return Err("unsupported content type".into()),
+        };
+    }
 
     // TODO: switch to mime::APPLICATION_DNS when that stabilizes
     match request.headers().get(ACCEPT).map(|v| v.to_str()) {
@@ -149,7 +250,43 @@ mod tests {
 
     #[test]
     fn test_new_verify() {
-        let request = new("ns.example.com", 512).expect("error converting to http");
+        let request = new("ns.example.com", crate::https::DNS_QUERY_PATH, 512)
+            .expect("error converting to http");
         assert!(verify("ns.example.com", &request).is_ok());
     }
+
+    #[test]
+    fn test_new_custom_path() {
+        let request =
+            new("ns.example.com", "/custom/dns-query", 512).expect("error converting to http");
+        assert_eq!(request.uri().path(), "/custom/dns-query");
+        assert!(verify("ns.example.com", &request).is_err());
+    }
+
+    #[test]
+    fn test_new_get_verify() {
+        let message = [0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0xff, 0xfe];
+        let request = new_get("ns.example.com", crate::https::DNS_QUERY_PATH, &message)
+            .expect("error converting to http");
+
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.uri().path(), crate::https::DNS_QUERY_PATH);
+        assert_eq!(request.uri().query(), Some("dns=AAABAAAB__4"));
+        assert!(request.headers().get(CONTENT_TYPE).is_none());
+        assert!(verify("ns.example.com", &request).is_ok());
+
+        assert_eq!(dns_param(request.uri()).unwrap(), message);
+    }
+
+    #[test]
+    fn test_dns_param() {
+        let uri = Uri::from_static("https://ns.example.com/dns-query?ct=x&dns=AAABAAAB");
+        assert_eq!(dns_param(&uri).unwrap(), [0, 0, 1, 0, 0, 1]);
+
+        let uri = Uri::from_static("https://ns.example.com/dns-query?ct=x");
+        assert!(dns_param(&uri).is_err());
+
+        let uri = Uri::from_static("https://ns.example.com/dns-query?dns=AA+=");
+        assert!(dns_param(&uri).is_err());
+    }
 }
@@ -60,6 +60,10 @@ pub fn spawn_bg<F: Future<Output = R> + Send + 'static, R: Send + 'static>(
     runtime.spawn(background)
 }
 
+#[cfg(feature = "async-std-runtime")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-std-runtime")))]
+pub mod async_std;
+pub mod dnssd;
 pub mod error;
 #[cfg(feature = "dns-over-https")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-https")))]
@@ -85,10 +89,16 @@ pub mod rr;
 #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
 pub mod rustls;
 pub mod serialize;
+#[cfg(feature = "smol-runtime")]
+#[cfg_attr(docsrs, doc(cfg(feature = "smol-runtime")))]
+pub mod smol;
 pub mod tcp;
 #[cfg(any(test, feature = "testing"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
 pub mod tests;
+#[cfg(feature = "dns-over-tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dns-over-tls")))]
+pub mod tls;
 pub mod udp;
 pub mod xfer;
 
@@ -107,7 +117,7 @@ pub use crate::xfer::BufDnsStreamHandle;
 #[cfg_attr(docsrs, doc(cfg(feature = "backtrace")))]
 pub use error::ExtBacktrace;
 
-#[cfg(feature = "tokio-runtime")]
+#[cfg(any(feature = "tokio-runtime", feature = "dns-over-tls"))]
 #[doc(hidden)]
 pub mod iocompat {
     use std::io;
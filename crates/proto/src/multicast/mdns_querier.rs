@@ -0,0 +1,193 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Querier side of multicast DNS, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5)
+
+use std::time::Duration;
+
+use rand::distributions::{uniform::Uniform, Distribution};
+
+use crate::op::{Message, Query};
+use crate::rr::Record;
+
+/// The minimum interval between the first two queries of a continuous query
+pub const MIN_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The interval at which queries of a continuous query are capped
+pub const MAX_QUERY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The kind of response a question asks for, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5.4)
+///
+/// ```text
+///    To avoid large floods of potentially unnecessary responses in these
+///    cases, Multicast DNS defines the top bit in the class field of a DNS
+///    question as the unicast-response bit.  When this bit is set in a
+///    question, it indicates that the querier is willing to accept unicast
+///    replies in response to this specific query, as well as the usual
+///    multicast responses.  These questions requesting unicast responses
+///    are referred to as "QU" questions, to distinguish them from the more
+///    usual questions requesting multicast responses ("QM" questions).
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MdnsQuestionType {
+    /// A "QU" question, unicast responses are accepted
+    Unicast,
+    /// A "QM" question, responses are multicast
+    Multicast,
+}
+
+impl MdnsQuestionType {
+    /// Returns the type of the question
+    pub fn of(query: &Query) -> Self {
+        if query.mdns_unicast_response() {
+            Self::Unicast
+        } else {
+            Self::Multicast
+        }
+    }
+
+    /// Sets the unicast-response bit of the question accordingly
+    pub fn apply(self, query: &mut Query) {
+        query.set_mdns_unicast_response(self == Self::Unicast);
+    }
+}
+
+/// The delays before each query of a continuous query, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5.2)
+///
+/// ```text
+///    When a Multicast DNS querier issues a continuous query, it SHOULD
+///    delay the first query of the series by a randomly chosen amount in
+///    the range 20-120 ms. ... The interval between the first two queries
+///    MUST be at least one second, the intervals between successive queries
+///    MUST increase by at least a factor of two.  When the interval between
+///    queries reaches or exceeds 60 minutes, a querier MAY cap the interval
+///    to a maximum of 60 minutes, and perform subsequent queries at a
+///    steady-state rate of one query per hour.
+/// ```
+///
+/// The iterator never ends. The first query of the series should be a "QU" question, the
+///  following ones "QM" questions.
+#[derive(Clone, Debug)]
+pub struct QueryIntervals {
+    next: Option<Duration>,
+}
+
+impl QueryIntervals {
+    /// Creates the intervals of a new continuous query
+    pub fn new() -> Self {
+        Self { next: None }
+    }
+}
+
+impl Default for QueryIntervals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for QueryIntervals {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = match self.next {
+            None => {
+                self.next = Some(MIN_QUERY_INTERVAL);
+                let millis = Uniform::new_inclusive(20, 120).sample(&mut rand::thread_rng());
+                return Some(Duration::from_millis(millis));
+            }
+            Some(delay) => delay,
+        };
+
+        self.next = Some((delay * 2).min(MAX_QUERY_INTERVAL));
+        Some(delay)
+    }
+}
+
+/// Returns true if a cached record should be included in the Known-Answer list of a query
+///
+/// [RFC 6762](https://tools.ietf.org/html/rfc6762#section-7.1), a Multicast DNS querier
+///  MUST NOT include records in the Known-Answer list whose remaining TTL is less than half of
+///  their original TTL.
+///
+/// # Arguments
+///
+/// * `remaining_ttl` - the TTL of the record still remaining in the cache
+/// * `original_ttl` - the TTL of the record when it was received
+pub fn is_known_answer(remaining_ttl: u32, original_ttl: u32) -> bool {
+    u64::from(remaining_ttl) * 2 >= u64::from(original_ttl)
+}
+
+/// Adds the cached answers to the Known-Answer list, i.e. answer section, of a query
+///
+/// # Arguments
+///
+/// * `message` - the query being sent
+/// * `answers` - the cached records with their remaining TTL set, and the TTL originally received
+pub fn add_known_answers<I>(message: &mut Message, answers: I)
+where
+    I: IntoIterator<Item = (Record, u32)>,
+{
+    message.add_answers(
+        answers
+            .into_iter()
+            .filter(|(record, original_ttl)| is_known_answer(record.ttl(), *original_ttl))
+            .map(|(record, _)| record),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::rr::{Name, RData, RecordType};
+
+    #[test]
+    fn test_question_type() {
+        let mut query = Query::query(Name::from_str("host.local.").unwrap(), RecordType::A);
+        assert_eq!(MdnsQuestionType::of(&query), MdnsQuestionType::Multicast);
+
+        MdnsQuestionType::Unicast.apply(&mut query);
+        assert!(query.mdns_unicast_response());
+        assert_eq!(MdnsQuestionType::of(&query), MdnsQuestionType::Unicast);
+
+        MdnsQuestionType::Multicast.apply(&mut query);
+        assert!(!query.mdns_unicast_response());
+    }
+
+    #[test]
+    fn test_query_intervals() {
+        let mut intervals = QueryIntervals::new();
+
+        let first = intervals.next().unwrap();
+        assert!(first >= Duration::from_millis(20) && first <= Duration::from_millis(120));
+
+        assert_eq!(intervals.next(), Some(Duration::from_secs(1)));
+        assert_eq!(intervals.next(), Some(Duration::from_secs(2)));
+        assert_eq!(intervals.next(), Some(Duration::from_secs(4)));
+
+        let last = intervals.nth(20).unwrap();
+        assert_eq!(last, MAX_QUERY_INTERVAL);
+        assert_eq!(intervals.next(), Some(MAX_QUERY_INTERVAL));
+    }
+
+    #[test]
+    fn test_known_answers() {
+        assert!(is_known_answer(60, 120));
+        assert!(is_known_answer(120, 120));
+        assert!(!is_known_answer(59, 120));
+
+        let name = Name::from_str("host.local.").unwrap();
+        let fresh = Record::from_rdata(name.clone(), 100, RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+        let stale = Record::from_rdata(name, 10, RData::A(Ipv4Addr::new(10, 0, 0, 2)));
+
+        let mut message = Message::new();
+        add_known_answers(&mut message, vec![(fresh.clone(), 120), (stale, 120)]);
+        assert_eq!(message.answers(), &[fresh]);
+    }
+}
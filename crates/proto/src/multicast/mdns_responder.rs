@@ -0,0 +1,263 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Responder side of multicast DNS, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-6)
+
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use rand::distributions::{uniform::Uniform, Distribution};
+
+use crate::error::*;
+use crate::op::{Message, MessageType, OpCode, Query};
+use crate::rr::{Name, Record, RecordType};
+use crate::serialize::binary::{BinEncodable, BinEncoder};
+
+/// The interval between probes
+pub const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The number of probes sent before a name is claimed
+pub const PROBE_COUNT: usize = 3;
+
+/// The minimum interval between the first two announcements
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum number of announcements
+pub const MAX_ANNOUNCEMENTS: usize = 8;
+
+/// Removes the answers the querier already knows, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-7.1)
+///
+/// ```text
+///    A Multicast DNS responder MUST NOT answer a Multicast DNS query if
+///    the answer it would give is already included in the Answer Section
+///    with an RR TTL at least half the correct value.
+/// ```
+///
+/// # Arguments
+///
+/// * `query` - the received query, the answer section holds the Known-Answer list
+/// * `answers` - the answers of the responder, with the correct TTLs
+pub fn suppress_known_answers(query: &Message, answers: &mut Vec<Record>) {
+    answers.retain(|answer| {
+        !query
+            .answers()
+            .iter()
+            .any(|known| known == answer && u64::from(known.ttl()) * 2 >= u64::from(answer.ttl()))
+    });
+}
+
+/// The delays before each probe, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-8.1)
+///
+/// ```text
+///    When the host is ready to send his probe query he SHOULD delay
+///    its transmission with a randomly chosen time interval from 0 to
+///    250 ms. ... 250 ms after the first query, the host should send a
+///    second; then, 250 ms after that, a third.  If, by 250 ms after the
+///    third probe, no conflicting Multicast DNS responses have been
+///    received, the host may move to the next step, announcing.
+/// ```
+pub fn probe_delays() -> Vec<Duration> {
+    let initial = Uniform::new_inclusive(0, 250).sample(&mut rand::thread_rng());
+
+    let mut delays = Vec::with_capacity(PROBE_COUNT);
+    delays.push(Duration::from_millis(initial));
+    delays.resize(PROBE_COUNT, PROBE_INTERVAL);
+    delays
+}
+
+/// Creates a probe for the records the responder wants to claim
+///
+/// The probe is a "QU" question for all types of the name, the proposed records are in the
+///  authority section for simultaneous probe tiebreaking.
+///
+/// # Arguments
+///
+/// * `name` - the name to claim
+/// * `records` - all records of the name the responder proposes
+pub fn probe_message(name: Name, records: Vec<Record>) -> Message {
+    let mut query = Query::query(name, RecordType::ANY);
+    query.set_mdns_unicast_response(true);
+
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .add_query(query)
+        .add_name_servers(records);
+    message
+}
+
+/// Compares the proposed records of two simultaneous probes, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-8.2)
+///
+/// The records are compared in canonical order by class, type and rdata, the probe with the
+///  lexicographically later data wins.
+///
+/// # Return
+///
+/// `Ordering::Greater` if `ours` wins, `Ordering::Less` if `theirs` wins and the responder must
+///  wait one second before probing again, `Ordering::Equal` if there is no conflict.
+pub fn probe_tiebreak(ours: &[Record], theirs: &[Record]) -> ProtoResult<Ordering> {
+    let ours = tiebreak_keys(ours)?;
+    let theirs = tiebreak_keys(theirs)?;
+
+    Ok(ours.cmp(&theirs))
+}
+
+/// the class without the cache-flush bit, the type and the uncompressed rdata
+fn tiebreak_keys(records: &[Record]) -> ProtoResult<Vec<(u16, u16, Vec<u8>)>> {
+    let mut keys = records
+        .iter()
+        .map(|record| {
+            let mut rdata = Vec::new();
+            if let Some(data) = record.data() {
+                let mut encoder = BinEncoder::new(&mut rdata);
+                encoder.with_canonical_names(|encoder| data.emit(encoder))?;
+            }
+
+            Ok((
+                u16::from(record.dns_class()),
+                u16::from(record.record_type()),
+                rdata,
+            ))
+        })
+        .collect::<ProtoResult<Vec<_>>>()?;
+
+    keys.sort();
+    Ok(keys)
+}
+
+/// The delays before each announcement, [RFC 6762](https://tools.ietf.org/html/rfc6762#section-8.3)
+///
+/// ```text
+///    The Multicast DNS responder MUST send at least two unsolicited
+///    responses, one second apart.  To provide increased robustness against
+///    packet loss, a responder MAY send up to eight unsolicited responses,
+///    provided that the interval between unsolicited responses increases by
+///    at least a factor of two with every response sent.
+/// ```
+///
+/// # Arguments
+///
+/// * `count` - the number of announcements, between 2 and 8
+pub fn announce_delays(count: usize) -> Vec<Duration> {
+    let count = count.max(2).min(MAX_ANNOUNCEMENTS);
+
+    let mut delays = Vec::with_capacity(count);
+    delays.push(Duration::from_secs(0));
+
+    let mut interval = ANNOUNCE_INTERVAL;
+    while delays.len() < count {
+        delays.push(interval);
+        interval *= 2;
+    }
+
+    delays
+}
+
+/// Creates an unsolicited response announcing the records
+///
+/// # Arguments
+///
+/// * `records` - all records claimed by the responder
+/// * `unique` - true if the records are unique to the responder, i.e. not shared with other
+///              responders like PTR records of services, the cache-flush bit is set for these
+pub fn announce_message(records: Vec<Record>, unique: bool) -> Message {
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Response)
+        .set_op_code(OpCode::Query)
+        .set_authoritative(true)
+        .add_answers(records.into_iter().map(|mut record| {
+            record.set_mdns_cache_flush(unique);
+            record
+        }));
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::rr::RData;
+
+    fn a_record(last: u8, ttl: u32) -> Record {
+        Record::from_rdata(
+            Name::from_str("host.local.").unwrap(),
+            ttl,
+            RData::A(Ipv4Addr::new(10, 0, 0, last)),
+        )
+    }
+
+    #[test]
+    fn test_suppress_known_answers() {
+        let mut query = Message::new();
+        query
+            .add_answer(a_record(1, 60))
+            .add_answer(a_record(2, 59));
+
+        let mut answers = vec![a_record(1, 120), a_record(2, 120), a_record(3, 120)];
+        suppress_known_answers(&query, &mut answers);
+
+        assert_eq!(answers, vec![a_record(2, 120), a_record(3, 120)]);
+    }
+
+    #[test]
+    fn test_probe() {
+        let delays = probe_delays();
+        assert_eq!(delays.len(), PROBE_COUNT);
+        assert!(delays[0] <= PROBE_INTERVAL);
+        assert_eq!(&delays[1..], &[PROBE_INTERVAL, PROBE_INTERVAL]);
+
+        let probe = probe_message(
+            Name::from_str("host.local.").unwrap(),
+            vec![a_record(1, 120)],
+        );
+        assert_eq!(probe.queries()[0].query_type(), RecordType::ANY);
+        assert!(probe.queries()[0].mdns_unicast_response());
+        assert_eq!(probe.name_servers(), &[a_record(1, 120)]);
+    }
+
+    #[test]
+    fn test_probe_tiebreak() {
+        let ours = vec![a_record(2, 120)];
+        let theirs = vec![a_record(1, 120)];
+
+        assert_eq!(probe_tiebreak(&ours, &theirs).unwrap(), Ordering::Greater);
+        assert_eq!(probe_tiebreak(&theirs, &ours).unwrap(), Ordering::Less);
+        assert_eq!(probe_tiebreak(&ours, &ours).unwrap(), Ordering::Equal);
+
+        // the order of the records doesn't matter
+        let ours = vec![a_record(3, 120), a_record(1, 120)];
+        let theirs = vec![a_record(1, 120), a_record(2, 120)];
+        assert_eq!(probe_tiebreak(&ours, &theirs).unwrap(), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_announce() {
+        assert_eq!(
+            announce_delays(0),
+            vec![Duration::from_secs(0), Duration::from_secs(1)]
+        );
+        assert_eq!(
+            announce_delays(4),
+            vec![
+                Duration::from_secs(0),
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(4)
+            ]
+        );
+        assert_eq!(announce_delays(20).len(), MAX_ANNOUNCEMENTS);
+
+        let announcement = announce_message(vec![a_record(1, 120)], true);
+        assert_eq!(announcement.message_type(), MessageType::Response);
+        assert!(announcement.authoritative());
+        assert!(announcement.answers()[0].mdns_cache_flush());
+    }
+}
@@ -84,16 +84,15 @@ impl MdnsStream {
     ///  the default multicast DNS addresses. See <https://tools.ietf.org/html/rfc6762#section-5>
     ///  for details.
     ///
-    /// When sending ipv6 multicast packets, the interface being used is required,
-    ///  this will panic if the interface is not specified for all MdnsQueryType except Passive
-    ///  (which does not allow sending data)
+    /// The interfaces are used both for joining the multicast group and for sending multicast
+    ///  packets, if not specified the default interface chosen by the OS is used.
     ///
     /// # Arguments
     ///
     /// * `multicast_addr` - address to use for multicast requests
     /// * `mdns_query_type` - true if the querier using this socket will only perform standard DNS queries over multicast.
-    /// * `ipv4_if` - Address to bind to for sending multicast packets, defaults to `0.0.0.0` if not specified (not relevant for ipv6)
-    /// * `ipv6_if` - Interface index for the interface to be used when sending ipv6 packets.
+    /// * `ipv4_if` - Address of the interface for joining and sending multicast packets, defaults to `0.0.0.0` if not specified (not relevant for ipv6)
+    /// * `ipv6_if` - Interface index for joining and sending multicast packets, defaults to `0` if not specified (not relevant for ipv4)
     ///
     /// # Return
     ///
@@ -110,10 +109,11 @@ impl MdnsStream {
         BufDnsStreamHandle,
     ) {
         let (message_sender, outbound_messages) = BufDnsStreamHandle::new(multicast_addr);
-        let multicast_socket = match Self::join_multicast(&multicast_addr, mdns_query_type) {
-            Ok(socket) => socket,
-            Err(err) => return (Box::new(future::err(err)), message_sender),
-        };
+        let multicast_socket =
+            match Self::join_multicast(&multicast_addr, mdns_query_type, ipv4_if, ipv6_if) {
+                Ok(socket) => socket,
+                Err(err) => return (Box::new(future::err(err)), message_sender),
+            };
 
         // TODO: allow the bind address to be specified...
         // constructs a future for getting the next randomly bound port to a UdpSocket
@@ -183,10 +183,12 @@ impl MdnsStream {
         socket.bind(&socket2::SockAddr::from(*multicast_addr))
     }
 
-    /// Returns a socket joined to the multicast address
+    /// Returns a socket joined to the multicast address on the interface
     fn join_multicast(
         multicast_addr: &SocketAddr,
         mdns_query_type: MdnsQueryType,
+        ipv4_if: Option<Ipv4Addr>,
+        ipv6_if: Option<u32>,
     ) -> Result<Option<std::net::UdpSocket>, io::Error> {
         if !mdns_query_type.join_multicast() {
             return Ok(None);
@@ -203,7 +205,6 @@ impl MdnsStream {
 
         // binding the UdpSocket to the multicast address tells the OS to filter all packets on this socket to just this
         //   multicast address
-        // TODO: allow the binding interface to be specified
         let socket = match ip_addr {
             IpAddr::V4(ref mdns_v4) => {
                 let socket = Socket::new(
@@ -211,7 +212,10 @@ impl MdnsStream {
                     socket2::Type::DGRAM,
                     Some(socket2::Protocol::UDP),
                 )?;
-                socket.join_multicast_v4(mdns_v4, &Ipv4Addr::new(0, 0, 0, 0))?;
+                socket.join_multicast_v4(
+                    mdns_v4,
+                    &ipv4_if.unwrap_or_else(|| Ipv4Addr::new(0, 0, 0, 0)),
+                )?;
                 socket
             }
             IpAddr::V6(ref mdns_v6) => {
@@ -222,7 +226,7 @@ impl MdnsStream {
                 )?;
 
                 socket.set_only_v6(true)?;
-                socket.join_multicast_v6(mdns_v6, 0)?;
+                socket.join_multicast_v6(mdns_v6, ipv6_if.unwrap_or(0))?;
                 socket
             }
         };
@@ -339,12 +343,9 @@ impl NextRandomUdpSocket {
                 }
             }
             SocketAddr::V6(..) => {
-                let ipv6_if = self.ipv6_if.unwrap_or_else(|| {
-                    panic!("for ipv6 multicasting the interface must be specified")
-                });
-
+                // 0 lets the OS choose the interface
                 socket.set_multicast_loop_v6(true)?;
-                socket.set_multicast_if_v6(ipv6_if)?;
+                socket.set_multicast_if_v6(self.ipv6_if.unwrap_or(0))?;
                 if let Some(ttl) = self.packet_ttl {
                     socket.set_unicast_hops_v6(ttl)?;
                     socket.set_multicast_hops_v6(ttl)?;
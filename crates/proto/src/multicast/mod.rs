@@ -8,9 +8,19 @@
 //! Multicast protocol related components for DNS
 
 mod mdns_client_stream;
+mod mdns_querier;
+mod mdns_responder;
 mod mdns_stream;
 
 pub use self::mdns_client_stream::{MdnsClientConnect, MdnsClientStream};
+pub use self::mdns_querier::{
+    add_known_answers, is_known_answer, MdnsQuestionType, QueryIntervals, MAX_QUERY_INTERVAL,
+    MIN_QUERY_INTERVAL,
+};
+pub use self::mdns_responder::{
+    announce_delays, announce_message, probe_delays, probe_message, probe_tiebreak,
+    suppress_known_answers, ANNOUNCE_INTERVAL, MAX_ANNOUNCEMENTS, PROBE_COUNT, PROBE_INTERVAL,
+};
 pub use self::mdns_stream::{MdnsStream, MDNS_IPV4, MDNS_IPV6};
 
 /// See [rfc6762](https://tools.ietf.org/html/rfc6762#section-5) details on these different types.
@@ -12,7 +12,7 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use std::{future::Future, marker::PhantomData};
 
-use futures_util::TryFutureExt;
+use futures_util::future;
 use native_tls::Protocol::Tlsv12;
 use native_tls::{Certificate, Identity, TlsConnector};
 use tokio_native_tls::{TlsConnector as TokioTlsConnector, TlsStream as TokioTlsStream};
@@ -20,7 +20,7 @@ use tokio_native_tls::{TlsConnector as TokioTlsConnector, TlsStream as TokioTlsS
 use crate::iocompat::{AsyncIoStdAsTokio, AsyncIoTokioAsStd};
 use crate::tcp::Connect;
 use crate::tcp::TcpStream;
-use crate::xfer::{BufDnsStreamHandle, StreamReceiver};
+use crate::xfer::BufDnsStreamHandle;
 
 /// A TlsStream counterpart to the TcpStream which embeds a secure TlsStream
 pub type TlsStream<S> = TcpStream<AsyncIoTokioAsStd<TokioTlsStream<AsyncIoStdAsTokio<S>>>>;
@@ -135,56 +135,38 @@ impl<S: Connect> TlsStreamBuilder<S> {
         Pin<Box<dyn Future<Output = Result<TlsStream<S>, io::Error>> + Send>>,
         BufDnsStreamHandle,
     ) {
-        let (message_sender, outbound_messages) = BufDnsStreamHandle::new(name_server);
+        let tls_connector = match tls_new(self.ca_chain, self.identity) {
+            Ok(tls_connector) => TokioTlsConnector::from(tls_connector),
+            Err(e) => {
+                let (message_sender, _) = BufDnsStreamHandle::new(name_server);
+                return (Box::pin(future::err(e)), message_sender);
+            }
+        };
 
-        let stream = self.inner_build(name_server, dns_name, outbound_messages);
-        (Box::pin(stream), message_sender)
+        crate::tls::tls_connect(name_server, self.bind_addr, dns_name, tls_connector)
     }
+}
 
-    async fn inner_build(
-        self,
-        name_server: SocketAddr,
-        dns_name: String,
-        outbound_messages: StreamReceiver,
-    ) -> Result<TlsStream<S>, io::Error> {
-        use crate::native_tls::tls_stream;
-
-        let ca_chain = self.ca_chain.clone();
-        let identity = self.identity;
-
-        let tcp_stream = S::connect_with_bind(name_server, self.bind_addr).await;
-
-        // TODO: for some reason the above wouldn't accept a ?
-        let tcp_stream = match tcp_stream {
-            Ok(tcp_stream) => AsyncIoStdAsTokio(tcp_stream),
-            Err(err) => return Err(err),
-        };
-
-        // This set of futures collapses the next tcp socket into a stream which can be used for
-        //  sending and receiving tcp packets.
-        let tls_connector = tls_stream::tls_new(ca_chain, identity)
-            .map(TokioTlsConnector::from)
-            .map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::ConnectionRefused,
-                    format!("tls error: {}", e),
-                )
-            })?;
-
-        let tls_connected = tls_connector
-            .connect(&dns_name, tcp_stream)
-            .map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::ConnectionRefused,
-                    format!("tls error: {}", e),
-                )
-            })
-            .await?;
-
-        Ok(TcpStream::from_stream_with_receiver(
-            AsyncIoTokioAsStd(tls_connected),
-            name_server,
-            outbound_messages,
-        ))
+impl<S: Connect> crate::tls::TlsConnector<S> for TokioTlsConnector {
+    type Stream = TokioTlsStream<AsyncIoStdAsTokio<S>>;
+
+    fn connect(
+        &self,
+        dns_name: &str,
+        tcp: S,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Self::Stream>> + Send>> {
+        let tls_connector = self.clone();
+        let dns_name = dns_name.to_owned();
+
+        Box::pin(async move {
+            Self::connect(&tls_connector, &dns_name, AsyncIoStdAsTokio(tcp))
+                .await
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        format!("tls error: {}", e),
+                    )
+                })
+        })
     }
 }
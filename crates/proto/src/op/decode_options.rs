@@ -0,0 +1,187 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Options for decoding messages, and the diagnostics of lenient decoding
+
+use std::fmt;
+
+use crate::error::{ProtoError, ProtoResult};
+
+/// Options for decoding a [`Message`](super::Message), see
+///  [`Message::read_with_options`](super::Message::read_with_options)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// When true, records which fail to decode are skipped and reported as [`DecodeDiagnostic`]s,
+    ///  rather than failing the whole message.
+    ///
+    /// A record can only be skipped if its owner name and RDLENGTH can be decoded, otherwise
+    ///  decoding stops and the message contains only what was decoded before the failure.
+    pub lenient: bool,
+    /// When lenient, decoding stops after this many errors
+    ///
+    /// This bounds the work spent on garbage input.
+    pub max_errors: usize,
+}
+
+impl DecodeOptions {
+    /// Options which fail decoding on the first error, the default
+    pub fn strict() -> Self {
+        Self {
+            lenient: false,
+            max_errors: 0,
+        }
+    }
+
+    /// Options which skip records that fail to decode, up to 16 errors
+    pub fn lenient() -> Self {
+        Self {
+            lenient: true,
+            max_errors: 16,
+        }
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+/// The section of a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageSection {
+    /// The question section, i.e. the queries
+    Query,
+    /// The answer section
+    Answer,
+    /// The authority section, i.e. the name servers
+    NameServer,
+    /// The additional section, including any EDNS and SIG0 or TSIG records
+    Additional,
+}
+
+impl fmt::Display for MessageSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let section = match self {
+            Self::Query => "query",
+            Self::Answer => "answer",
+            Self::NameServer => "name server",
+            Self::Additional => "additional",
+        };
+
+        f.write_str(section)
+    }
+}
+
+/// An error for a single entry of a message decoded with [`DecodeOptions::lenient`]
+#[derive(Clone, Debug)]
+pub struct DecodeDiagnostic {
+    section: MessageSection,
+    index: usize,
+    offset: usize,
+    error: ProtoError,
+    skipped: bool,
+}
+
+impl DecodeDiagnostic {
+    /// The section of the entry which failed
+    pub fn section(&self) -> MessageSection {
+        self.section
+    }
+
+    /// The index of the entry in its section, as counted in the header
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The offset of the start of the entry in the message
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The error from decoding the entry
+    pub fn error(&self) -> &ProtoError {
+        &self.error
+    }
+
+    /// True if the entry was skipped and decoding continued, false if decoding stopped here
+    pub fn skipped(&self) -> bool {
+        self.skipped
+    }
+}
+
+impl fmt::Display for DecodeDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{section} {index} at offset {offset}{skipped}: {error}",
+            section = self.section,
+            index = self.index,
+            offset = self.offset,
+            skipped = if self.skipped { " skipped" } else { "" },
+            error = self.error,
+        )
+    }
+}
+
+/// Collects the diagnostics of lenient decoding, when strict the first error is returned
+pub(crate) struct Diagnostics {
+    options: DecodeOptions,
+    diagnostics: Vec<DecodeDiagnostic>,
+    stopped: bool,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(options: DecodeOptions) -> Self {
+        Self {
+            options,
+            diagnostics: Vec::new(),
+            stopped: false,
+        }
+    }
+
+    /// Returns the error when strict, otherwise records it
+    ///
+    /// Decoding must stop once [`Self::stopped`] is true, which is the case if the entry could not
+    ///  be `skipped` or the max errors are reached.
+    pub(crate) fn push(
+        &mut self,
+        section: MessageSection,
+        index: usize,
+        offset: usize,
+        error: ProtoError,
+        skipped: bool,
+    ) -> ProtoResult<()> {
+        if !self.options.lenient {
+            return Err(error);
+        }
+
+        self.diagnostics.push(DecodeDiagnostic {
+            section,
+            index,
+            offset,
+            error,
+            skipped,
+        });
+
+        if !skipped || self.diagnostics.len() >= self.options.max_errors {
+            self.stopped = true;
+        }
+
+        Ok(())
+    }
+
+    /// True once decoding should stop
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub(crate) fn into_inner(self) -> Vec<DecodeDiagnostic> {
+        self.diagnostics
+    }
+}
@@ -0,0 +1,920 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS Stateful Operations, [RFC 8490](https://tools.ietf.org/html/rfc8490)
+//!
+//! DSO messages share the header of regular DNS messages, but all section counts are zero and the
+//!  header is followed by a sequence of TLVs instead of records. They are only valid on
+//!  connection-oriented transports, i.e. TCP and TLS.
+
+use std::time::{Duration, Instant};
+
+use crate::error::*;
+use crate::op::{Header, MessageType, OpCode, Query, ResponseCode};
+use crate::rr::{DNSClass, Name, RData, Record, RecordType};
+use crate::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder, Restrict};
+
+/// The DSO-TYPE of a TLV, [RFC 8490](https://tools.ietf.org/html/rfc8490#section-10.3)
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum DsoType {
+    /// [RFC 8490, Keepalive TLV](https://tools.ietf.org/html/rfc8490#section-7.1)
+    KeepAlive,
+    /// [RFC 8490, Retry Delay TLV](https://tools.ietf.org/html/rfc8490#section-7.2)
+    RetryDelay,
+    /// [RFC 8490, Encryption Padding TLV](https://tools.ietf.org/html/rfc8490#section-7.3)
+    EncryptionPadding,
+    /// [RFC 8765, SUBSCRIBE](https://tools.ietf.org/html/rfc8765#section-6.2)
+    Subscribe,
+    /// [RFC 8765, PUSH](https://tools.ietf.org/html/rfc8765#section-6.3)
+    Push,
+    /// [RFC 8765, UNSUBSCRIBE](https://tools.ietf.org/html/rfc8765#section-6.4)
+    Unsubscribe,
+    /// [RFC 8765, RECONFIRM](https://tools.ietf.org/html/rfc8765#section-6.5)
+    Reconfirm,
+    /// An unknown or unsupported DSO-TYPE
+    Unknown(u16),
+}
+
+impl From<u16> for DsoType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::KeepAlive,
+            2 => Self::RetryDelay,
+            3 => Self::EncryptionPadding,
+            0x40 => Self::Subscribe,
+            0x41 => Self::Push,
+            0x42 => Self::Unsubscribe,
+            0x43 => Self::Reconfirm,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<DsoType> for u16 {
+    fn from(value: DsoType) -> Self {
+        match value {
+            DsoType::KeepAlive => 1,
+            DsoType::RetryDelay => 2,
+            DsoType::EncryptionPadding => 3,
+            DsoType::Subscribe => 0x40,
+            DsoType::Push => 0x41,
+            DsoType::Unsubscribe => 0x42,
+            DsoType::Reconfirm => 0x43,
+            DsoType::Unknown(value) => value,
+        }
+    }
+}
+
+/// The timers of a DSO session, [RFC 8490](https://tools.ietf.org/html/rfc8490#section-7.1)
+///
+/// ```text
+///                      1   1   1   1   1   1
+///      0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    |           DSO-TYPE = KeepAlive (0x0001)                       |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    |           DSO-LENGTH (8)                                      |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    |           INACTIVITY TIMEOUT (32 bits)                        |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    |           KEEPALIVE INTERVAL (32 bits)                        |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+///
+/// Both values are in milliseconds, `0xFFFFFFFF` represents an infinite value.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct KeepAlive {
+    inactivity_timeout: u32,
+    keepalive_interval: u32,
+}
+
+impl KeepAlive {
+    /// The value of an infinite timeout or interval
+    pub const INFINITE: u32 = u32::MAX;
+
+    /// Creates a new KeepAlive from the timeout and interval in milliseconds
+    pub fn new(inactivity_timeout: u32, keepalive_interval: u32) -> Self {
+        Self {
+            inactivity_timeout,
+            keepalive_interval,
+        }
+    }
+
+    /// The inactivity timeout in milliseconds
+    pub fn inactivity_timeout(&self) -> u32 {
+        self.inactivity_timeout
+    }
+
+    /// The keepalive interval in milliseconds
+    pub fn keepalive_interval(&self) -> u32 {
+        self.keepalive_interval
+    }
+
+    /// The inactivity timeout, `None` if infinite
+    pub fn inactivity_duration(&self) -> Option<Duration> {
+        to_duration(self.inactivity_timeout)
+    }
+
+    /// The keepalive interval, `None` if infinite
+    pub fn keepalive_duration(&self) -> Option<Duration> {
+        to_duration(self.keepalive_interval)
+    }
+}
+
+impl Default for KeepAlive {
+    /// The timers of a connection before the DSO session is established, 15 seconds each
+    fn default() -> Self {
+        Self::new(15_000, 15_000)
+    }
+}
+
+fn to_duration(millis: u32) -> Option<Duration> {
+    if millis == KeepAlive::INFINITE {
+        None
+    } else {
+        Some(Duration::from_millis(u64::from(millis)))
+    }
+}
+
+/// A DSO TLV, [RFC 8490](https://tools.ietf.org/html/rfc8490#section-5.4)
+///
+/// ```text
+///                                              1   1   1   1   1   1
+///      0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    |                           DSO-TYPE                            |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    |                          DSO-LENGTH                           |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    |                                                               |
+///    /                           DSO-DATA                            /
+///    /                                                               /
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum DsoTlv {
+    /// The session timers, see [`KeepAlive`]
+    KeepAlive(KeepAlive),
+    /// The time in milliseconds the client must wait before reconnecting
+    RetryDelay(u32),
+    /// The number of padding octets, their content is ignored
+    EncryptionPadding(u16),
+    /// Subscribe to changes of the records matching the query
+    Subscribe(Query),
+    /// Records changed since the last notification, see [`crate::xfer::dns_push::PushChange`]
+    Push(Vec<Record>),
+    /// Cancel the subscription with the message id of the SUBSCRIBE request
+    Unsubscribe(u16),
+    /// Ask the server to verify that a record still exists, the TTL is ignored
+    Reconfirm(Record),
+    /// An unknown or unsupported TLV
+    Unknown(u16, Vec<u8>),
+}
+
+impl DsoTlv {
+    /// The DSO-TYPE of the TLV
+    pub fn dso_type(&self) -> DsoType {
+        match *self {
+            Self::KeepAlive(..) => DsoType::KeepAlive,
+            Self::RetryDelay(..) => DsoType::RetryDelay,
+            Self::EncryptionPadding(..) => DsoType::EncryptionPadding,
+            Self::Subscribe(..) => DsoType::Subscribe,
+            Self::Push(..) => DsoType::Push,
+            Self::Unsubscribe(..) => DsoType::Unsubscribe,
+            Self::Reconfirm(..) => DsoType::Reconfirm,
+            Self::Unknown(code, _) => DsoType::Unknown(code),
+        }
+    }
+
+    /// The length of the DSO-DATA
+    pub fn len(&self) -> u16 {
+        match *self {
+            Self::KeepAlive(..) => 8,
+            Self::RetryDelay(..) => 4,
+            Self::EncryptionPadding(len) => len,
+            Self::Unsubscribe(..) => 2,
+            Self::Unknown(_, ref data) => data.len() as u16,
+            Self::Subscribe(..) | Self::Push(..) | Self::Reconfirm(..) => {
+                let mut buf = Vec::new();
+                let mut encoder = BinEncoder::new(&mut buf);
+                // names are never compressed in DSO-DATA
+                match encoder.with_canonical_names(|encoder| self.emit_data(encoder)) {
+                    Ok(()) => buf.len() as u16,
+                    Err(_) => 0,
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the DSO-DATA is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl BinEncodable for DsoTlv {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_u16(self.dso_type().into())?;
+
+        let place = encoder.place::<u16>()?;
+        encoder.with_canonical_names(|encoder| self.emit_data(encoder))?;
+
+        let len = encoder.len_since_place(&place);
+        if len > usize::from(u16::max_value()) {
+            return Err(format!("DSO-DATA too long: {}", len).into());
+        }
+
+        place.replace(encoder, len as u16)
+    }
+}
+
+impl DsoTlv {
+    fn emit_data(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        match *self {
+            Self::KeepAlive(keepalive) => {
+                encoder.emit_u32(keepalive.inactivity_timeout)?;
+                encoder.emit_u32(keepalive.keepalive_interval)
+            }
+            Self::RetryDelay(delay) => encoder.emit_u32(delay),
+            Self::EncryptionPadding(len) => encoder.emit_vec(&vec![0; usize::from(len)]),
+            Self::Subscribe(ref query) => query.emit(encoder),
+            Self::Push(ref records) => {
+                for record in records {
+                    record.emit(encoder)?;
+                }
+                Ok(())
+            }
+            Self::Unsubscribe(id) => encoder.emit_u16(id),
+            Self::Reconfirm(ref record) => {
+                record.name().emit(encoder)?;
+                record.record_type().emit(encoder)?;
+                record.dns_class().emit(encoder)?;
+                match record.data() {
+                    Some(rdata) => rdata.emit(encoder),
+                    None => Ok(()),
+                }
+            }
+            Self::Unknown(_, ref data) => encoder.emit_vec(data),
+        }
+    }
+
+    fn read_data(dso_type: DsoType, data: &[u8]) -> ProtoResult<Self> {
+        let mut decoder = BinDecoder::new(data);
+
+        let tlv = match dso_type {
+            DsoType::Subscribe => Self::Subscribe(Query::read(&mut decoder)?),
+            DsoType::Push => {
+                let mut records = Vec::new();
+                while !decoder.is_empty() {
+                    records.push(Record::read(&mut decoder)?);
+                }
+                Self::Push(records)
+            }
+            DsoType::Unsubscribe => Self::Unsubscribe(decoder.read_u16()?.unverified(/*any id*/)),
+            DsoType::Reconfirm => {
+                let name = Name::read(&mut decoder)?;
+                let record_type = RecordType::read(&mut decoder)?;
+                let dns_class = DNSClass::read(&mut decoder)?;
+                let rdata_length = Restrict::new(decoder.len() as u16);
+                let rdata = RData::read(&mut decoder, record_type, rdata_length)?;
+
+                let mut record = Record::from_rdata(name, 0, rdata);
+                record.set_dns_class(dns_class);
+                Self::Reconfirm(record)
+            }
+            _ => return Err(format!("unexpected DSO-TYPE: {:?}", dso_type).into()),
+        };
+
+        if !decoder.is_empty() {
+            return Err(format!("trailing bytes in DSO-DATA of {:?}", dso_type).into());
+        }
+
+        Ok(tlv)
+    }
+}
+
+impl<'r> BinDecodable<'r> for DsoTlv {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        let dso_type: DsoType = decoder.read_u16()?.unverified(/*any type is valid*/).into();
+        let len = decoder.read_u16()?.unverified(/*verified by read_slice*/);
+        let data = decoder
+            .read_slice(usize::from(len))?
+            .unverified(/*verified by the length checks below*/);
+
+        let tlv = match dso_type {
+            DsoType::KeepAlive if data.len() == 8 => Self::KeepAlive(KeepAlive::new(
+                u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            )),
+            DsoType::RetryDelay if data.len() == 4 => {
+                Self::RetryDelay(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+            }
+            DsoType::KeepAlive | DsoType::RetryDelay => {
+                return Err(format!("invalid DSO-LENGTH {} for {:?}", len, dso_type).into())
+            }
+            DsoType::EncryptionPadding => Self::EncryptionPadding(len),
+            DsoType::Subscribe | DsoType::Push | DsoType::Unsubscribe | DsoType::Reconfirm => {
+                Self::read_data(dso_type, data)?
+            }
+            DsoType::Unknown(code) => Self::Unknown(code, data.to_vec()),
+        };
+
+        Ok(tlv)
+    }
+}
+
+/// A DSO message, [RFC 8490](https://tools.ietf.org/html/rfc8490#section-5.4)
+///
+/// ```text
+///    A DSO message begins with the standard twelve-byte DNS message header
+///    [RFC1035] with the OPCODE field set to the DSO OPCODE (6).  However,
+///    unlike standard DNS messages, the question section, answer section,
+///    authority records section, and additional records sections are not
+///    present.  The corresponding count fields (QDCOUNT, ANCOUNT, NSCOUNT,
+///    ARCOUNT) MUST be set to zero on transmission.
+/// ```
+///
+/// The first TLV of a request or unidirectional message is the Primary TLV, all others are
+///  Additional TLVs. A response may omit the Primary TLV.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DsoMessage {
+    header: Header,
+    tlvs: Vec<DsoTlv>,
+}
+
+impl DsoMessage {
+    /// Creates a new DSO request, which must be acknowledged with a response
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the message id, must not be zero
+    /// * `primary` - the Primary TLV of the request
+    pub fn new_request(id: u16, primary: DsoTlv) -> Self {
+        Self::new(id, MessageType::Query, ResponseCode::NoError, Some(primary))
+    }
+
+    /// Creates a new unidirectional DSO message, which has a zero id and no response
+    pub fn new_unidirectional(primary: DsoTlv) -> Self {
+        Self::new(0, MessageType::Query, ResponseCode::NoError, Some(primary))
+    }
+
+    /// Creates the response to a DSO request
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request being responded to
+    /// * `response_code` - the result of the request
+    /// * `primary` - the Primary TLV, if present it must be of the same type as the request's
+    pub fn new_response(
+        request: &Self,
+        response_code: ResponseCode,
+        primary: Option<DsoTlv>,
+    ) -> Self {
+        Self::new(request.id(), MessageType::Response, response_code, primary)
+    }
+
+    fn new(
+        id: u16,
+        message_type: MessageType,
+        response_code: ResponseCode,
+        primary: Option<DsoTlv>,
+    ) -> Self {
+        let mut header = Header::new();
+        header
+            .set_id(id)
+            .set_message_type(message_type)
+            .set_op_code(OpCode::Dso)
+            .set_response_code(response_code);
+
+        Self {
+            header,
+            tlvs: primary.into_iter().collect(),
+        }
+    }
+
+    /// The header of the message
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The message id, zero for unidirectional messages
+    pub fn id(&self) -> u16 {
+        self.header.id()
+    }
+
+    /// Returns `true` if this is a response to a DSO request
+    pub fn is_response(&self) -> bool {
+        self.header.message_type() == MessageType::Response
+    }
+
+    /// Returns `true` if this is a unidirectional message
+    pub fn is_unidirectional(&self) -> bool {
+        !self.is_response() && self.id() == 0
+    }
+
+    /// The response code of the message
+    pub fn response_code(&self) -> ResponseCode {
+        self.header.response_code()
+    }
+
+    /// The Primary TLV, for responses this is the first TLV if any
+    pub fn primary_tlv(&self) -> Option<&DsoTlv> {
+        self.tlvs.first()
+    }
+
+    /// The Additional TLVs
+    pub fn additional_tlvs(&self) -> &[DsoTlv] {
+        self.tlvs.get(1..).unwrap_or(&[])
+    }
+
+    /// All TLVs, starting with the Primary TLV
+    pub fn tlvs(&self) -> &[DsoTlv] {
+        &self.tlvs
+    }
+
+    /// Adds an Additional TLV
+    ///
+    /// The Encryption Padding TLV must be the last TLV of the message.
+    pub fn add_tlv(&mut self, tlv: DsoTlv) -> &mut Self {
+        self.tlvs.push(tlv);
+        self
+    }
+}
+
+impl BinEncodable for DsoMessage {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        let mut header = self.header;
+        header
+            .set_query_count(0)
+            .set_answer_count(0)
+            .set_name_server_count(0)
+            .set_additional_count(0);
+        header.emit(encoder)?;
+
+        for tlv in &self.tlvs {
+            tlv.emit(encoder)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'r> BinDecodable<'r> for DsoMessage {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        let header = Header::read(decoder)?;
+
+        if header.op_code() != OpCode::Dso {
+            return Err(format!("not a DSO message: {}", header.op_code()).into());
+        }
+
+        if header.query_count() != 0
+            || header.answer_count() != 0
+            || header.name_server_count() != 0
+            || header.additional_count() != 0
+        {
+            return Err("DSO message section counts must be zero".into());
+        }
+
+        let mut tlvs = Vec::new();
+        while !decoder.is_empty() {
+            tlvs.push(DsoTlv::read(decoder)?);
+        }
+
+        if header.message_type() == MessageType::Query && tlvs.is_empty() {
+            return Err("DSO request is missing the Primary TLV".into());
+        }
+
+        Ok(Self { header, tlvs })
+    }
+}
+
+/// The state of a DSO session
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DsoSessionState {
+    /// No DSO session has been established on the connection yet
+    Connected,
+    /// The client sent a DSO request and is waiting for the response
+    Establishing,
+    /// The DSO session is established, unidirectional messages may be exchanged
+    Established,
+    /// The server does not support DSO, the connection may still be used for other queries
+    Unsupported,
+    /// The server asked the client to close the connection and not to reconnect before the
+    ///  delay passed
+    Retry(Duration),
+    /// The session was aborted due to a protocol error, the connection must be closed
+    Closed,
+}
+
+/// The state machine of a DSO session, [RFC 8490](https://tools.ietf.org/html/rfc8490#section-5)
+///
+/// The session doesn't perform any I/O, all messages sent on the connection are created through it
+///  and all messages received are passed to [`DsoSession::receive`].
+#[derive(Debug, Clone, Copy)]
+pub struct DsoSession {
+    is_server: bool,
+    state: DsoSessionState,
+    establishing_id: Option<u16>,
+    keepalive: KeepAlive,
+    last_activity: Instant,
+}
+
+impl DsoSession {
+    /// Creates the client side of a session on a new connection
+    pub fn client(now: Instant) -> Self {
+        Self::new(false, KeepAlive::default(), now)
+    }
+
+    /// Creates the server side of a session on a new connection
+    ///
+    /// # Arguments
+    ///
+    /// * `keepalive` - the timers the server sends to clients establishing a session
+    /// * `now` - the time the connection was accepted
+    pub fn server(keepalive: KeepAlive, now: Instant) -> Self {
+        Self::new(true, keepalive, now)
+    }
+
+    fn new(is_server: bool, keepalive: KeepAlive, now: Instant) -> Self {
+        Self {
+            is_server,
+            state: DsoSessionState::Connected,
+            establishing_id: None,
+            keepalive,
+            last_activity: now,
+        }
+    }
+
+    /// The current state of the session
+    pub fn state(&self) -> DsoSessionState {
+        self.state
+    }
+
+    /// Returns `true` if the session is established
+    pub fn is_established(&self) -> bool {
+        self.state == DsoSessionState::Established
+    }
+
+    /// The timers of the session, as set by the server
+    pub fn keepalive(&self) -> KeepAlive {
+        self.keepalive
+    }
+
+    /// Records traffic on the connection which isn't a DSO message, e.g. a regular query
+    pub fn touch(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+
+    /// The time after which an idle connection should be closed, `None` if never
+    pub fn inactivity_deadline(&self) -> Option<Instant> {
+        self.keepalive
+            .inactivity_duration()
+            .map(|timeout| self.last_activity + timeout)
+    }
+
+    /// The time at which the client should send traffic to keep the connection alive, `None` if
+    ///  never
+    pub fn keepalive_deadline(&self) -> Option<Instant> {
+        self.keepalive
+            .keepalive_duration()
+            .map(|interval| self.last_activity + interval)
+    }
+
+    /// Creates a DSO request, the first request of a client establishes the session
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the message id, must not be zero
+    /// * `primary` - the Primary TLV of the request
+    /// * `now` - the time the request is sent
+    pub fn request(&mut self, id: u16, primary: DsoTlv, now: Instant) -> ProtoResult<DsoMessage> {
+        if id == 0 {
+            return Err("DSO requests must have a non-zero message id".into());
+        }
+
+        match self.state {
+            DsoSessionState::Connected if !self.is_server => {
+                self.state = DsoSessionState::Establishing;
+                self.establishing_id = Some(id);
+            }
+            DsoSessionState::Establishing if !self.is_server => (),
+            DsoSessionState::Established => (),
+            state => return Err(format!("can not send DSO request in state {:?}", state).into()),
+        }
+
+        self.last_activity = now;
+        Ok(DsoMessage::new_request(id, primary))
+    }
+
+    /// Creates a unidirectional DSO message, only allowed once the session is established
+    pub fn unidirectional(&mut self, primary: DsoTlv, now: Instant) -> ProtoResult<DsoMessage> {
+        if !self.is_established() {
+            return Err(format!(
+                "can not send DSO unidirectional message in state {:?}",
+                self.state
+            )
+            .into());
+        }
+
+        self.last_activity = now;
+        Ok(DsoMessage::new_unidirectional(primary))
+    }
+
+    /// Processes a received DSO message
+    ///
+    /// # Return
+    ///
+    /// The response to send for requests. An error is returned on protocol violations, after which
+    ///  the session is closed and the connection must be closed as well.
+    pub fn receive(
+        &mut self,
+        message: &DsoMessage,
+        now: Instant,
+    ) -> ProtoResult<Option<DsoMessage>> {
+        self.last_activity = now;
+
+        let result = if message.is_response() {
+            self.receive_response(message)
+        } else if message.is_unidirectional() {
+            self.receive_unidirectional(message)
+        } else {
+            self.receive_request(message)
+        };
+
+        if result.is_err() {
+            self.state = DsoSessionState::Closed;
+        }
+
+        result
+    }
+
+    fn receive_response(&mut self, response: &DsoMessage) -> ProtoResult<Option<DsoMessage>> {
+        // responses to other pipelined requests don't change the state
+        if self.establishing_id == Some(response.id()) {
+            self.establishing_id = None;
+            self.state = match response.response_code() {
+                ResponseCode::NoError => DsoSessionState::Established,
+                ResponseCode::DSOTYPENI | ResponseCode::NotImp | ResponseCode::FormErr => {
+                    DsoSessionState::Unsupported
+                }
+                // the request failed, but another may still establish the session
+                _ => DsoSessionState::Connected,
+            };
+        }
+
+        if let Some(DsoTlv::KeepAlive(keepalive)) = response.primary_tlv() {
+            if !self.is_server && response.response_code() == ResponseCode::NoError {
+                self.keepalive = *keepalive;
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn receive_unidirectional(&mut self, message: &DsoMessage) -> ProtoResult<Option<DsoMessage>> {
+        if !self.is_established() {
+            return Err("DSO unidirectional message before the session was established".into());
+        }
+
+        match message.primary_tlv() {
+            Some(DsoTlv::KeepAlive(keepalive)) if !self.is_server => {
+                self.keepalive = *keepalive;
+                Ok(None)
+            }
+            Some(DsoTlv::RetryDelay(delay)) if !self.is_server => {
+                self.state = DsoSessionState::Retry(Duration::from_millis(u64::from(*delay)));
+                Ok(None)
+            }
+            Some(DsoTlv::Push(..)) if !self.is_server => Ok(None),
+            Some(DsoTlv::Unsubscribe(..)) | Some(DsoTlv::Reconfirm(..)) if self.is_server => {
+                Ok(None)
+            }
+            tlv => Err(format!("invalid DSO unidirectional message: {:?}", tlv).into()),
+        }
+    }
+
+    fn receive_request(&mut self, request: &DsoMessage) -> ProtoResult<Option<DsoMessage>> {
+        if !self.is_server && !self.is_established() {
+            return Err("DSO request from server before the session was established".into());
+        }
+
+        let response = match request.primary_tlv() {
+            Some(DsoTlv::KeepAlive(_)) if self.is_server => {
+                self.state = DsoSessionState::Established;
+                DsoMessage::new_response(
+                    request,
+                    ResponseCode::NoError,
+                    Some(DsoTlv::KeepAlive(self.keepalive)),
+                )
+            }
+            // serving push notifications is not supported
+            Some(DsoTlv::Unknown(..)) | Some(DsoTlv::Subscribe(..)) => {
+                DsoMessage::new_response(request, ResponseCode::DSOTYPENI, None)
+            }
+            tlv => return Err(format!("invalid DSO request: {:?}", tlv).into()),
+        };
+
+        Ok(Some(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keepalive_request() {
+        let mut request = DsoMessage::new_request(
+            0x1234,
+            DsoTlv::KeepAlive(KeepAlive::new(15_000, KeepAlive::INFINITE)),
+        );
+        request.add_tlv(DsoTlv::EncryptionPadding(2));
+
+        let bytes = request.to_bytes().unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0x12, 0x34, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, // header
+                0x00, 0x01, 0x00, 0x08, 0x00, 0x00, 0x3a, 0x98, 0xff, 0xff, 0xff,
+                0xff, // keepalive
+                0x00, 0x03, 0x00, 0x02, 0x00, 0x00, // padding
+            ]
+        );
+
+        let read = DsoMessage::from_bytes(&bytes).unwrap();
+        assert_eq!(read, request);
+        assert!(!read.is_response());
+        assert!(!read.is_unidirectional());
+        assert_eq!(read.additional_tlvs(), &[DsoTlv::EncryptionPadding(2)]);
+
+        match read.primary_tlv() {
+            Some(DsoTlv::KeepAlive(keepalive)) => {
+                assert_eq!(
+                    keepalive.inactivity_duration(),
+                    Some(Duration::from_secs(15))
+                );
+                assert_eq!(keepalive.keepalive_duration(), None);
+            }
+            tlv => panic!("unexpected tlv: {:?}", tlv),
+        }
+    }
+
+    #[test]
+    fn test_read_unknown_tlv() {
+        let message = DsoMessage::new_unidirectional(DsoTlv::Unknown(0xf900, vec![1, 2, 3]));
+        let read = DsoMessage::from_bytes(&message.to_bytes().unwrap()).unwrap();
+
+        assert!(read.is_unidirectional());
+        assert_eq!(
+            read.primary_tlv(),
+            Some(&DsoTlv::Unknown(0xf900, vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn test_push_tlvs() {
+        use std::net::Ipv4Addr;
+        use std::str::FromStr;
+
+        let name = Name::from_str("_ipp._tcp.example.com.").unwrap();
+        let record = Record::from_rdata(name.clone(), 3600, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        let mut removed = Record::with(name.clone(), RecordType::ANY, 0xFFFF_FFFE);
+        removed.set_dns_class(DNSClass::IN);
+
+        let tlvs = vec![
+            DsoTlv::Subscribe(Query::query(name.clone(), RecordType::PTR)),
+            DsoTlv::Push(vec![record.clone(), record.clone(), removed]),
+            DsoTlv::Unsubscribe(0x1234),
+            DsoTlv::Reconfirm(Record::from_rdata(
+                name,
+                0,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            )),
+        ];
+
+        for tlv in tlvs {
+            let bytes = tlv.to_bytes().unwrap();
+            assert_eq!(usize::from(tlv.len()) + 4, bytes.len());
+
+            let read = DsoTlv::from_bytes(&bytes).unwrap();
+            assert_eq!(read, tlv);
+        }
+
+        // names must not be compressed, the second record would otherwise point to the first
+        let push = DsoTlv::Push(vec![record.clone(), record]);
+        let bytes = push.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 4 + 2 * (23 + 10 + 4));
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        // wrong op code
+        let mut bytes = DsoMessage::new_request(1, DsoTlv::RetryDelay(0))
+            .to_bytes()
+            .unwrap();
+        bytes[2] = 0;
+        assert!(DsoMessage::from_bytes(&bytes).is_err());
+
+        // non-zero question count
+        let mut bytes = DsoMessage::new_request(1, DsoTlv::RetryDelay(0))
+            .to_bytes()
+            .unwrap();
+        bytes[5] = 1;
+        assert!(DsoMessage::from_bytes(&bytes).is_err());
+
+        // bad keepalive length
+        let bytes = vec![
+            0x00, 0x01, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert!(DsoMessage::from_bytes(&bytes).is_err());
+
+        // request without a primary tlv
+        let bytes = vec![
+            0x00, 0x01, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert!(DsoMessage::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_session_establish() {
+        let now = Instant::now();
+        let server_keepalive = KeepAlive::new(30_000, 20_000);
+        let mut client = DsoSession::client(now);
+        let mut server = DsoSession::server(server_keepalive, now);
+
+        // unidirectional messages are only allowed once established
+        assert!(client.unidirectional(DsoTlv::RetryDelay(0), now).is_err());
+
+        let request = client
+            .request(1, DsoTlv::KeepAlive(KeepAlive::default()), now)
+            .unwrap();
+        assert_eq!(client.state(), DsoSessionState::Establishing);
+
+        let response = server.receive(&request, now).unwrap().unwrap();
+        assert!(server.is_established());
+        assert_eq!(response.id(), 1);
+        assert_eq!(
+            response.primary_tlv(),
+            Some(&DsoTlv::KeepAlive(server_keepalive))
+        );
+
+        assert!(client.receive(&response, now).unwrap().is_none());
+        assert!(client.is_established());
+        assert_eq!(client.keepalive(), server_keepalive);
+        assert_eq!(
+            client.inactivity_deadline(),
+            Some(now + Duration::from_secs(30))
+        );
+        assert_eq!(
+            client.keepalive_deadline(),
+            Some(now + Duration::from_secs(20))
+        );
+
+        // the server asks the client to go away
+        let retry = server
+            .unidirectional(DsoTlv::RetryDelay(5_000), now)
+            .unwrap();
+        client.receive(&retry, now).unwrap();
+        assert_eq!(
+            client.state(),
+            DsoSessionState::Retry(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn test_session_unsupported() {
+        let now = Instant::now();
+        let mut client = DsoSession::client(now);
+        let mut server = DsoSession::server(KeepAlive::default(), now);
+
+        let request = client
+            .request(2, DsoTlv::Unknown(0xf901, vec![]), now)
+            .unwrap();
+        let response = server.receive(&request, now).unwrap().unwrap();
+        assert_eq!(response.response_code(), ResponseCode::DSOTYPENI);
+        assert_eq!(server.state(), DsoSessionState::Connected);
+
+        client.receive(&response, now).unwrap();
+        assert_eq!(client.state(), DsoSessionState::Unsupported);
+        assert!(client
+            .request(3, DsoTlv::KeepAlive(KeepAlive::default()), now)
+            .is_err());
+    }
+
+    #[test]
+    fn test_session_violation() {
+        let now = Instant::now();
+        let mut server = DsoSession::server(KeepAlive::default(), now);
+
+        let message = DsoMessage::new_unidirectional(DsoTlv::RetryDelay(0));
+        assert!(server.receive(&message, now).is_err());
+        assert_eq!(server.state(), DsoSessionState::Closed);
+    }
+}
@@ -7,14 +7,20 @@
 
 //! Basic protocol message for DNS
 
-use std::{fmt, iter, mem, ops::Deref, sync::Arc};
+use std::{borrow::Borrow, convert::TryFrom, fmt, iter, mem, ops::Deref, sync::Arc};
 
 use tracing::{debug, warn};
 
 use crate::{
     error::*,
-    op::{Edns, Header, MessageType, OpCode, Query, ResponseCode},
-    rr::{Record, RecordType},
+    op::{
+        decode_options::Diagnostics, DecodeDiagnostic, DecodeOptions, Edns, Header, MessageSection,
+        MessageType, OpCode, PaddingPolicy, Query, ResponseCode,
+    },
+    rr::{
+        rdata::opt::{EdnsCode, EdnsOption},
+        Name, Record, RecordType,
+    },
     serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder, EncodeMode},
     xfer::DnsResponse,
 };
@@ -649,12 +655,35 @@ impl Message {
     /// # Returns
     ///
     /// This returns a tuple of first standard Records, then a possibly associated Edns, and then finally any optionally associated SIG0 and TSIG records.
-    #[cfg_attr(not(feature = "dnssec"), allow(unused_mut))]
     pub fn read_records(
         decoder: &mut BinDecoder<'_>,
         count: usize,
         is_additional: bool,
     ) -> ProtoResult<(Vec<Record>, Option<Edns>, Vec<Record>)> {
+        let section = if is_additional {
+            MessageSection::Additional
+        } else {
+            MessageSection::Answer
+        };
+
+        Self::read_section(
+            decoder,
+            count,
+            section,
+            &mut Diagnostics::new(DecodeOptions::strict()),
+        )
+    }
+
+    /// Reads the records of a section, errors are passed to the diagnostics
+    #[cfg_attr(not(feature = "dnssec"), allow(unused_mut))]
+    fn read_section(
+        decoder: &mut BinDecoder<'_>,
+        count: usize,
+        section: MessageSection,
+        diagnostics: &mut Diagnostics,
+    ) -> ProtoResult<(Vec<Record>, Option<Edns>, Vec<Record>)> {
+        let is_additional = section == MessageSection::Additional;
+
         let mut records: Vec<Record> = Vec::with_capacity(count);
         let mut edns: Option<Edns> = None;
         let mut sigs: Vec<Record> = Vec::with_capacity(if is_additional { 1 } else { 0 });
@@ -663,14 +692,33 @@ impl Message {
         let mut saw_sig0 = false;
         // tsig must be last, once this is set, disable.
         let mut saw_tsig = false;
-        for _ in 0..count {
-            let record = Record::read(decoder)?;
+        for index in 0..count {
+            if diagnostics.stopped() {
+                break;
+            }
+
+            let offset = decoder.index();
+            let record = match Record::read(decoder) {
+                Ok(record) => record,
+                Err(e) => {
+                    let skipped = Self::skip_record(decoder, offset).is_ok();
+                    diagnostics.push(section, index, offset, e, skipped)?;
+                    continue;
+                }
+            };
+
+            // a misplaced record is dropped when lenient
+            let mut misplaced =
+                |msg: &'static str| diagnostics.push(section, index, offset, msg.into(), true);
+
             if saw_tsig {
-                return Err("tsig must be final resource record".into());
+                misplaced("tsig must be final resource record")?;
+                continue;
             } // TSIG must be last and multiple TSIG records are not allowed
             if !is_additional {
                 if saw_sig0 {
-                    return Err("sig0 must be final resource record".into());
+                    misplaced("sig0 must be final resource record")?;
+                    continue;
                 } // SIG0 must be last
                 records.push(record)
             } else {
@@ -683,23 +731,27 @@ impl Message {
                     #[cfg(feature = "dnssec")]
                     RecordType::TSIG => {
                         if saw_sig0 {
-                            return Err("sig0 must be final resource record".into());
+                            misplaced("sig0 must be final resource record")?;
+                            continue;
                         } // SIG0 must be last
                         saw_tsig = true;
                         sigs.push(record);
                     }
                     RecordType::OPT => {
                         if saw_sig0 {
-                            return Err("sig0 must be final resource record".into());
+                            misplaced("sig0 must be final resource record")?;
+                            continue;
                         } // SIG0 must be last
                         if edns.is_some() {
-                            return Err("more than one edns record present".into());
+                            misplaced("more than one edns record present")?;
+                            continue;
                         }
                         edns = Some((&record).into());
                     }
                     _ => {
                         if saw_sig0 {
-                            return Err("sig0 must be final resource record".into());
+                            misplaced("sig0 must be final resource record")?;
+                            continue;
                         } // SIG0 must be last
                         records.push(record);
                     }
@@ -710,6 +762,122 @@ impl Message {
         Ok((records, edns, sigs))
     }
 
+    /// Moves the decoder past the record starting at `offset`, using only the owner name and
+    ///  RDLENGTH, such that a record with bad RData can be skipped.
+    fn skip_record(decoder: &mut BinDecoder<'_>, offset: usize) -> ProtoResult<()> {
+        let offset = u16::try_from(offset)
+            .map_err(|_| ProtoError::from(format!("record offset too large: {}", offset)))?;
+        let mut skip = decoder.clone(offset);
+
+        Name::read(&mut skip)?;
+        // TYPE, CLASS and TTL
+        skip.read_slice(8)?;
+        let rd_length = skip.read_u16()?.unverified(/*only used to skip the rdata*/);
+        skip.read_slice(rd_length as usize)?;
+
+        *decoder = skip;
+        Ok(())
+    }
+
+    /// Decodes a message with the options, see [`DecodeOptions::lenient`]
+    ///
+    /// When lenient, entries which fail to decode are left out of the message and returned as
+    ///  diagnostics, with the header as it was decoded, i.e. its counts may not match the
+    ///  message. An error is only returned if the header fails to decode.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use trust_dns_proto::op::{DecodeOptions, Message};
+    ///
+    /// // a response with one answer, whose A record has a 3 byte address
+    /// let buf = [
+    ///     0x10, 0x00, 0x81, 0x80, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // header
+    ///     0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x03, 0x5D, 0xB8, 0xD8,
+    /// ];
+    ///
+    /// assert!(Message::from_vec(&buf).is_err());
+    ///
+    /// let (message, diagnostics) =
+    ///     Message::from_vec_with_options(&buf, DecodeOptions::lenient()).unwrap();
+    /// assert_eq!(message.id(), 0x1000);
+    /// assert!(message.answers().is_empty());
+    /// assert_eq!(diagnostics.len(), 1);
+    /// assert!(diagnostics[0].skipped());
+    /// ```
+    pub fn read_with_options(
+        decoder: &mut BinDecoder<'_>,
+        options: DecodeOptions,
+    ) -> ProtoResult<(Self, Vec<DecodeDiagnostic>)> {
+        let mut diagnostics = Diagnostics::new(options);
+        let mut header = Header::read(decoder)?;
+
+        // get the questions, these can not be skipped as they have no length
+        let count = header.query_count() as usize;
+        let mut queries = Vec::with_capacity(count);
+        for index in 0..count {
+            let offset = decoder.index();
+            match Query::read(decoder) {
+                Ok(query) => queries.push(query),
+                Err(e) => {
+                    diagnostics.push(MessageSection::Query, index, offset, e, false)?;
+                    break;
+                }
+            }
+        }
+
+        // get all counts before header moves
+        let answer_count = header.answer_count() as usize;
+        let name_server_count = header.name_server_count() as usize;
+        let additional_count = header.additional_count() as usize;
+
+        let (answers, _, _) = Self::read_section(
+            decoder,
+            answer_count,
+            MessageSection::Answer,
+            &mut diagnostics,
+        )?;
+        let (name_servers, _, _) = Self::read_section(
+            decoder,
+            name_server_count,
+            MessageSection::NameServer,
+            &mut diagnostics,
+        )?;
+        let (additionals, edns, signature) = Self::read_section(
+            decoder,
+            additional_count,
+            MessageSection::Additional,
+            &mut diagnostics,
+        )?;
+
+        // need to grab error code from EDNS (which might have a higher value)
+        if let Some(edns) = &edns {
+            let high_response_code = edns.rcode_high();
+            header.merge_response_code(high_response_code);
+        }
+
+        let message = Self {
+            header,
+            queries,
+            answers,
+            name_servers,
+            additionals,
+            signature,
+            edns,
+        };
+
+        Ok((message, diagnostics.into_inner()))
+    }
+
+    /// Decodes a message from the buffer with the options, see [`Self::read_with_options`]
+    pub fn from_vec_with_options(
+        buffer: &[u8],
+        options: DecodeOptions,
+    ) -> ProtoResult<(Self, Vec<DecodeDiagnostic>)> {
+        let mut decoder = BinDecoder::new(buffer);
+        Self::read_with_options(&mut decoder, options)
+    }
+
     /// Decodes a message from the buffer.
     pub fn from_vec(buffer: &[u8]) -> ProtoResult<Self> {
         let mut decoder = BinDecoder::new(buffer);
@@ -730,11 +898,76 @@ impl Message {
         Ok(buffer)
     }
 
+    /// Encodes the Message into a buffer of at most `max_size` bytes, e.g. the UDP payload size
+    ///  of the requestor
+    ///
+    /// Records are only omitted at RRset boundaries. If an RRset in the answer or name server
+    ///  sections does not fit, it and all following records are omitted and the TC bit is set.
+    ///  RRsets which do not fit in the additional section are omitted without setting the TC bit,
+    ///  see [RFC 2181, section 9](https://tools.ietf.org/html/rfc2181#section-9). Space for the
+    ///  EDNS OPT record and any SIG0 signature is always reserved, an error is returned if those
+    ///  and the queries can not fit.
+    pub fn to_vec_with_max_size(&self, max_size: u16) -> ProtoResult<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(max_size as usize);
+        {
+            let mut encoder = BinEncoder::new(&mut buffer);
+            self.emit_with_max_size(&mut encoder, max_size)?;
+        }
+
+        Ok(buffer)
+    }
+
+    fn emit_with_max_size(&self, encoder: &mut BinEncoder<'_>, max_size: u16) -> ProtoResult<()> {
+        emit_message_parts_with_max_size(
+            &self.header,
+            &mut self.queries.iter(),
+            &self.answers,
+            &self.name_servers,
+            &self.additionals,
+            self.edns.as_ref(),
+            &self.signature,
+            encoder,
+            max_size,
+        )?;
+
+        Ok(())
+    }
+
+    /// Pads the message with the EDNS(0) Padding option, [RFC 7830](https://tools.ietf.org/html/rfc7830)
+    ///
+    /// An existing Padding option is replaced, and an EDNS section is added if there is none. This
+    ///  must be the last change before the message is finalized, i.e. signed.
+    pub fn pad(&mut self, policy: PaddingPolicy) -> ProtoResult<()> {
+        if policy == PaddingPolicy::None {
+            return Ok(());
+        }
+
+        self.edns
+            .get_or_insert_with(Edns::new)
+            .options_mut()
+            .remove(EdnsCode::Padding);
+
+        if let Some(len) = policy.padding_len(self.to_vec()?.len()) {
+            if let Some(edns) = self.edns.as_mut() {
+                edns.options_mut().insert(EdnsOption::Padding(len));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the message contains the EDNS(0) Padding option
+    pub fn is_padded(&self) -> bool {
+        self.edns
+            .as_ref()
+            .map_or(false, |edns| edns.option(EdnsCode::Padding).is_some())
+    }
+
     /// Finalize the message prior to sending.
     ///
     /// Subsequent to calling this, the Message should not change.
     #[allow(clippy::match_single_binding)]
-    pub fn finalize<MF: MessageFinalizer>(
+    pub fn finalize<MF: MessageFinalizer + ?Sized>(
         &mut self,
         finalizer: &MF,
         inception_time: u32,
@@ -920,6 +1153,48 @@ pub fn count_was_truncated(result: ProtoResult<usize>) -> ProtoResult<(usize, bo
     })
 }
 
+/// Emits the records an RRset at a time, stopping at the first RRset which does not completely fit
+///
+/// Returns the count of records written and true if any were omitted
+fn emit_rrsets<R: Borrow<Record>>(
+    encoder: &mut BinEncoder<'_>,
+    records: &[R],
+) -> ProtoResult<(usize, bool)> {
+    let mut count = 0;
+    let mut remaining = records;
+
+    while let Some(first) = remaining.first() {
+        let first = first.borrow();
+        let rrset_len = remaining
+            .iter()
+            .map(Borrow::borrow)
+            .take_while(|r: &&Record| {
+                r.record_type() == first.record_type()
+                    && r.dns_class() == first.dns_class()
+                    && r.name() == first.name()
+            })
+            .count();
+        let (rrset, rest) = remaining.split_at(rrset_len);
+
+        let rollback = encoder.set_rollback();
+        for record in rrset {
+            if let Err(e) = record.borrow().emit(encoder) {
+                if let ProtoErrorKind::MaxBufferSizeExceeded(_) = e.kind() {
+                    rollback.rollback(encoder);
+                    return Ok((count, true));
+                }
+
+                return Err(e);
+            }
+        }
+
+        count += rrset.len();
+        remaining = rest;
+    }
+
+    Ok((count, false))
+}
+
 /// A trait that defines types which can be emitted as a set, with the associated count returned.
 pub trait EmitAndCount {
     /// Emit self to the encoder and return the count of items
@@ -1002,6 +1277,85 @@ where
     Ok(final_header)
 }
 
+/// Emits the different sections of a message, truncated to the `max_size` at RRset boundaries
+///
+/// The answers and name servers which do not fit are omitted and the message is marked as
+///  truncated, missing additional records do not require the TC bit, see
+///  [RFC 2181, section 9](https://tools.ietf.org/html/rfc2181#section-9). Space for the EDNS OPT
+///  record and any signature is always reserved, an error is returned if those and the queries
+///  can not fit.
+///
+/// # Return
+///
+/// In the case of a successful emit, the final header (updated counts, etc) is returned for help with logging, etc.
+#[allow(clippy::too_many_arguments)]
+pub fn emit_message_parts_with_max_size<Q, R>(
+    header: &Header,
+    queries: &mut Q,
+    answers: &[R],
+    name_servers: &[R],
+    additionals: &[R],
+    edns: Option<&Edns>,
+    signature: &[Record],
+    encoder: &mut BinEncoder<'_>,
+    max_size: u16,
+) -> ProtoResult<Header>
+where
+    Q: EmitAndCount,
+    R: Borrow<Record>,
+{
+    let edns = edns.map(|edns| {
+        // need to commit the error code
+        let mut edns = edns.clone();
+        edns.set_rcode_high(header.response_code().high());
+        Record::from(&edns)
+    });
+    let signature: &[Record] = if encoder.mode() != EncodeMode::Signing {
+        signature
+    } else {
+        &[]
+    };
+
+    // the uncompressed size is an upper bound on the space the trailing records need
+    let mut reserved = 0;
+    for record in edns.iter().chain(signature) {
+        reserved += record.to_bytes()?.len();
+    }
+    let budget = (max_size as usize)
+        .checked_sub(reserved)
+        .ok_or(ProtoErrorKind::MaxBufferSizeExceeded(max_size as usize))?;
+    encoder.set_max_size(budget as u16);
+
+    let place = encoder.place::<Header>()?;
+    let query_count = queries.emit(encoder)?;
+
+    let (answer_count, mut truncated) = emit_rrsets(encoder, answers)?;
+    let mut nameserver_count = 0;
+    let mut additional_count = 0;
+    if !truncated {
+        let (count, ns_truncated) = emit_rrsets(encoder, name_servers)?;
+        nameserver_count = count;
+        truncated = ns_truncated;
+    }
+    if !truncated {
+        // missing additional records do not require the TC bit
+        additional_count = emit_rrsets(encoder, additionals)?.0;
+    }
+
+    encoder.set_max_size(max_size);
+    additional_count += encoder.emit_all(edns.iter().chain(signature))?;
+
+    let counts = HeaderCounts {
+        query_count,
+        answer_count,
+        nameserver_count,
+        additional_count,
+    };
+    let final_header = update_header_counts(header, header.truncated() || truncated, counts);
+    place.replace(encoder, final_header)?;
+    Ok(final_header)
+}
+
 impl BinEncodable for Message {
     fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
         emit_message_parts(
@@ -1021,42 +1375,7 @@ impl BinEncodable for Message {
 
 impl<'r> BinDecodable<'r> for Message {
     fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
-        let mut header = Header::read(decoder)?;
-
-        // TODO: return just header, and in the case of the rest of message getting an error.
-        //  this could improve error detection while decoding.
-
-        // get the questions
-        let count = header.query_count() as usize;
-        let mut queries = Vec::with_capacity(count);
-        for _ in 0..count {
-            queries.push(Query::read(decoder)?);
-        }
-
-        // get all counts before header moves
-        let answer_count = header.answer_count() as usize;
-        let name_server_count = header.name_server_count() as usize;
-        let additional_count = header.additional_count() as usize;
-
-        let (answers, _, _) = Self::read_records(decoder, answer_count, false)?;
-        let (name_servers, _, _) = Self::read_records(decoder, name_server_count, false)?;
-        let (additionals, edns, signature) = Self::read_records(decoder, additional_count, true)?;
-
-        // need to grab error code from EDNS (which might have a higher value)
-        if let Some(edns) = &edns {
-            let high_response_code = edns.rcode_high();
-            header.merge_response_code(high_response_code);
-        }
-
-        Ok(Self {
-            header,
-            queries,
-            answers,
-            name_servers,
-            additionals,
-            signature,
-            edns,
-        })
+        Self::read_with_options(decoder, DecodeOptions::strict()).map(|(message, _)| message)
     }
 }
 
@@ -1220,3 +1539,212 @@ fn rdata_zero_roundtrip() {
 
     assert!(Message::from_bytes(buf).is_err());
 }
+
+#[test]
+fn test_pad() {
+    use std::str::FromStr;
+
+    use crate::rr::Name;
+
+    let mut message = Message::new();
+    message.add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    assert!(!message.is_padded());
+
+    message.pad(PaddingPolicy::None).unwrap();
+    assert!(!message.is_padded());
+
+    message.pad(PaddingPolicy::client()).unwrap();
+    assert!(message.is_padded());
+    assert_eq!(message.to_vec().unwrap().len(), 128);
+
+    // the existing padding is replaced
+    message.pad(PaddingPolicy::client()).unwrap();
+    assert_eq!(message.to_vec().unwrap().len(), 128);
+
+    let read = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+    assert!(read.is_padded());
+}
+
+#[test]
+fn test_to_vec_with_max_size() {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use crate::rr::{Name, RData};
+
+    let a = |name: &str, last_octet: u8| {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            300,
+            RData::A(Ipv4Addr::new(192, 0, 2, last_octet)),
+        )
+    };
+
+    let mut message = Message::new();
+    message
+        .add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ))
+        .add_answers(vec![
+            a("www.example.com.", 1),
+            a("www.example.com.", 2),
+            a("www2.example.com.", 1),
+            a("www2.example.com.", 2),
+        ])
+        .add_additional(a("ns.example.com.", 1))
+        .set_edns(Edns::new());
+
+    let full = message.to_vec().unwrap();
+    let unlimited = message.to_vec_with_max_size(u16::max_value()).unwrap();
+    assert_eq!(full, unlimited);
+
+    // the second RRset and the additional record do not fit
+    let truncated = message
+        .to_vec_with_max_size(full.len() as u16 - 30)
+        .unwrap();
+    assert!(truncated.len() <= full.len() - 30);
+
+    let truncated = Message::from_vec(&truncated).unwrap();
+    assert!(truncated.truncated());
+    assert_eq!(truncated.answers().len(), 2);
+    assert!(truncated
+        .answers()
+        .iter()
+        .all(|r| r.name() == &Name::from_str("www.example.com.").unwrap()));
+    assert!(truncated.additionals().is_empty());
+    assert!(truncated.extensions().is_some());
+
+    // only the additional record does not fit, which doesn't require TC
+    message.take_additionals();
+    message.add_additional(a("ns.example.com.", 1));
+    let without_additional = message.to_vec_with_max_size(full.len() as u16 - 1).unwrap();
+    let without_additional = Message::from_vec(&without_additional).unwrap();
+    assert!(!without_additional.truncated());
+    assert_eq!(without_additional.answers().len(), 4);
+    assert!(without_additional.additionals().is_empty());
+    assert!(without_additional.extensions().is_some());
+
+    // the query alone doesn't fit
+    assert!(message.to_vec_with_max_size(20).is_err());
+}
+
+#[cfg(test)]
+fn lenient_test_message(answers: &[&[u8]], additionals: &[&[u8]]) -> Vec<u8> {
+    // id = 4096, response, op=query, recursion_desired, recursion_available, no_error, 0 queries
+    let mut buf: Vec<u8> = vec![0x10, 0x00, 0x81, 0x80, 0x00, 0x00];
+    buf.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+    buf.extend_from_slice(&[0x00, 0x00]); // 0 name servers
+    buf.extend_from_slice(&(additionals.len() as u16).to_be_bytes());
+
+    for record in answers.iter().chain(additionals) {
+        buf.extend_from_slice(record);
+    }
+    buf
+}
+
+#[cfg(test)]
+const LENIENT_GOOD_A: &[u8] = &[
+    0x00, // root
+    0x00, 0x01, 0x00, 0x01, // RecordType = A, Class = IN
+    0x00, 0x00, 0x00, 0x02, // TTL = 2 seconds
+    0x00, 0x04, // record length = 4 (ipv4 address)
+    0x5D, 0xB8, 0xD8, 0x22, // address = 93.184.216.34
+];
+
+#[cfg(test)]
+const LENIENT_SHORT_A: &[u8] = &[
+    0x00, // root
+    0x00, 0x01, 0x00, 0x01, // RecordType = A, Class = IN
+    0x00, 0x00, 0x00, 0x02, // TTL = 2 seconds
+    0x00, 0x03, // record length = 3, too short for an ipv4 address
+    0x5D, 0xB8, 0xD8,
+];
+
+#[test]
+fn test_lenient_skips_bad_rdata() {
+    let buf = lenient_test_message(&[LENIENT_SHORT_A, LENIENT_GOOD_A], &[]);
+
+    assert!(Message::from_vec(&buf).is_err());
+    assert!(Message::from_vec_with_options(&buf, DecodeOptions::strict()).is_err());
+
+    let (message, diagnostics) =
+        Message::from_vec_with_options(&buf, DecodeOptions::lenient()).unwrap();
+    assert_eq!(message.answer_count(), 2);
+    assert_eq!(message.answers().len(), 1);
+    assert_eq!(
+        message.answers()[0]
+            .data()
+            .and_then(|rdata| rdata.to_ip_addr()),
+        Some("93.184.216.34".parse().unwrap())
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].section(), MessageSection::Answer);
+    assert_eq!(diagnostics[0].index(), 0);
+    assert_eq!(diagnostics[0].offset(), 12);
+    assert!(diagnostics[0].skipped());
+}
+
+#[test]
+fn test_lenient_stops_on_bad_name() {
+    let bad_name: &[u8] = &[
+        0xC0, 0xFF, // pointer past the end of the message
+        0x00, 0x01, 0x00, 0x01, // RecordType = A, Class = IN
+        0x00, 0x00, 0x00, 0x02, // TTL = 2 seconds
+        0x00, 0x04, // record length = 4 (ipv4 address)
+        0x5D, 0xB8, 0xD8, 0x22, // address = 93.184.216.34
+    ];
+    let buf = lenient_test_message(&[LENIENT_GOOD_A, bad_name], &[LENIENT_GOOD_A]);
+
+    let (message, diagnostics) =
+        Message::from_vec_with_options(&buf, DecodeOptions::lenient()).unwrap();
+    assert_eq!(message.answers().len(), 1);
+    assert!(message.additionals().is_empty());
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].index(), 1);
+    assert!(!diagnostics[0].skipped());
+}
+
+#[test]
+fn test_lenient_max_errors() {
+    let buf = lenient_test_message(
+        &[LENIENT_SHORT_A, LENIENT_SHORT_A, LENIENT_SHORT_A],
+        &[LENIENT_GOOD_A],
+    );
+
+    let mut options = DecodeOptions::lenient();
+    options.max_errors = 2;
+
+    let (message, diagnostics) = Message::from_vec_with_options(&buf, options).unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert!(message.answers().is_empty());
+    assert!(message.additionals().is_empty());
+}
+
+#[test]
+fn test_lenient_duplicate_edns() {
+    let opt: &[u8] = &[
+        0x00, // root
+        0x00, 0x29, 0x04, 0xD0, // RecordType = OPT, max payload = 1232
+        0x00, 0x00, 0x00, 0x00, // extended rcode, version and flags
+        0x00, 0x00, // no options
+    ];
+    let buf = lenient_test_message(&[], &[opt, opt, LENIENT_GOOD_A]);
+
+    assert!(Message::from_vec(&buf).is_err());
+
+    let (message, diagnostics) =
+        Message::from_vec_with_options(&buf, DecodeOptions::lenient()).unwrap();
+    assert_eq!(message.max_payload(), 1232);
+    assert_eq!(message.additionals().len(), 1);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].section(), MessageSection::Additional);
+    assert_eq!(diagnostics[0].index(), 1);
+    assert!(diagnostics[0].skipped());
+}
@@ -0,0 +1,524 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A builder of arbitrary messages, e.g. for test harnesses and fuzzers
+
+use crate::error::{ProtoError, ProtoResult};
+use crate::op::{Edns, Header, Message, MessageParts, MessageType, OpCode, Query, ResponseCode};
+use crate::rr::{DNSClass, Name, Record, RecordType};
+
+/// The counts of the header, when they are not those of the sections
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Counts {
+    query: Option<u16>,
+    answer: Option<u16>,
+    name_server: Option<u16>,
+    additional: Option<u16>,
+}
+
+impl Counts {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A builder of queries, responses and updates, with explicit control over every flag of the
+///  header, the EDNS record and the records of each section
+///
+/// ```rust
+/// use trust_dns_proto::op::{Edns, MessageBuilder, Query};
+/// use trust_dns_proto::rr::{Name, RecordType};
+///
+/// let query = Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A);
+/// let message = MessageBuilder::query(query)
+///     .id(1234)
+///     .checking_disabled(true)
+///     .edns(Edns::new())
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(message.id(), 1234);
+/// assert!(message.recursion_desired());
+/// ```
+///
+/// By default the message is validated, e.g. a query has exactly one question, and an update has
+///  exactly one zone. With validation disabled, any message is built, e.g. to test how peers
+///  handle malformed messages: OPT records may be added to any section, and [`Self::to_vec`]
+///  encodes header counts which don't match the sections.
+#[derive(Clone, Debug)]
+pub struct MessageBuilder {
+    parts: MessageParts,
+    counts: Counts,
+    validate: bool,
+}
+
+impl Default for MessageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageBuilder {
+    /// A builder of an empty query, with the id 0 and all flags cleared
+    pub fn new() -> Self {
+        Self {
+            parts: MessageParts::default(),
+            counts: Counts::default(),
+            validate: true,
+        }
+    }
+
+    /// A builder of a standard query of the question, with recursion desired
+    pub fn query(query: Query) -> Self {
+        let mut builder = Self::new();
+        builder.recursion_desired(true).add_query(query);
+        builder
+    }
+
+    /// A builder of a response to the request, with its id, op code, questions, and RD and CD
+    ///  flags, see [`Header::response_from_request`]
+    pub fn response(request: &Message) -> Self {
+        let mut builder = Self::new();
+        builder
+            .header(Header::response_from_request(request.header()))
+            .add_queries(request.queries().iter().cloned());
+        builder
+    }
+
+    /// A builder of a dynamic update of the zone, [RFC 2136](https://tools.ietf.org/html/rfc2136)
+    ///
+    /// The zone is the question, and the prerequisites and updates are added to the answer and
+    ///  name server sections.
+    pub fn update(zone: Name, dns_class: DNSClass) -> Self {
+        let mut zone = Query::query(zone, RecordType::SOA);
+        zone.set_query_class(dns_class);
+
+        let mut builder = Self::new();
+        builder.op_code(OpCode::Update).add_query(zone);
+        builder
+    }
+
+    /// Validate the message when it is built, the default
+    pub fn validate(&mut self, validate: bool) -> &mut Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Replaces the header, its counts are those of the sections unless they are set explicitly
+    pub fn header(&mut self, header: Header) -> &mut Self {
+        self.parts.header = header;
+        self
+    }
+
+    /// Sets the id of the message
+    pub fn id(&mut self, id: u16) -> &mut Self {
+        self.parts.header.set_id(id);
+        self
+    }
+
+    /// Sets a random id
+    pub fn random_id(&mut self) -> &mut Self {
+        self.id(rand::random())
+    }
+
+    /// Sets the message type, query or response
+    pub fn message_type(&mut self, message_type: MessageType) -> &mut Self {
+        self.parts.header.set_message_type(message_type);
+        self
+    }
+
+    /// Sets the op code
+    pub fn op_code(&mut self, op_code: OpCode) -> &mut Self {
+        self.parts.header.set_op_code(op_code);
+        self
+    }
+
+    /// Sets the Authoritative Answer flag
+    pub fn authoritative(&mut self, authoritative: bool) -> &mut Self {
+        self.parts.header.set_authoritative(authoritative);
+        self
+    }
+
+    /// Sets the TrunCation flag
+    pub fn truncated(&mut self, truncated: bool) -> &mut Self {
+        self.parts.header.set_truncated(truncated);
+        self
+    }
+
+    /// Sets the Recursion Desired flag
+    pub fn recursion_desired(&mut self, recursion_desired: bool) -> &mut Self {
+        self.parts.header.set_recursion_desired(recursion_desired);
+        self
+    }
+
+    /// Sets the Recursion Available flag
+    pub fn recursion_available(&mut self, recursion_available: bool) -> &mut Self {
+        self.parts
+            .header
+            .set_recursion_available(recursion_available);
+        self
+    }
+
+    /// Sets the Authentic Data flag
+    pub fn authentic_data(&mut self, authentic_data: bool) -> &mut Self {
+        self.parts.header.set_authentic_data(authentic_data);
+        self
+    }
+
+    /// Sets the Checking Disabled flag
+    pub fn checking_disabled(&mut self, checking_disabled: bool) -> &mut Self {
+        self.parts.header.set_checking_disabled(checking_disabled);
+        self
+    }
+
+    /// Sets the response code, the high bits of extended codes are encoded in the EDNS record
+    pub fn response_code(&mut self, response_code: ResponseCode) -> &mut Self {
+        self.parts.header.set_response_code(response_code);
+        self
+    }
+
+    /// Sets the EDNS record, its extended response code is replaced by that of the header
+    pub fn edns(&mut self, edns: Edns) -> &mut Self {
+        self.parts.edns = Some(edns);
+        self
+    }
+
+    /// Removes the EDNS record
+    pub fn clear_edns(&mut self) -> &mut Self {
+        self.parts.edns = None;
+        self
+    }
+
+    /// Adds a question, or the zone of an update
+    pub fn add_query(&mut self, query: Query) -> &mut Self {
+        self.parts.queries.push(query);
+        self
+    }
+
+    /// Adds the questions
+    pub fn add_queries<I: IntoIterator<Item = Query>>(&mut self, queries: I) -> &mut Self {
+        self.parts.queries.extend(queries);
+        self
+    }
+
+    /// Adds a record to the answer section, or the prerequisites of an update
+    pub fn add_answer(&mut self, record: Record) -> &mut Self {
+        self.parts.answers.push(record);
+        self
+    }
+
+    /// Adds the records to the answer section
+    pub fn add_answers<I: IntoIterator<Item = Record>>(&mut self, records: I) -> &mut Self {
+        self.parts.answers.extend(records);
+        self
+    }
+
+    /// Adds a record to the name server section, or the updates of an update
+    pub fn add_name_server(&mut self, record: Record) -> &mut Self {
+        self.parts.name_servers.push(record);
+        self
+    }
+
+    /// Adds the records to the name server section
+    pub fn add_name_servers<I: IntoIterator<Item = Record>>(&mut self, records: I) -> &mut Self {
+        self.parts.name_servers.extend(records);
+        self
+    }
+
+    /// Adds a record to the additional section, before the EDNS record
+    pub fn add_additional(&mut self, record: Record) -> &mut Self {
+        self.parts.additionals.push(record);
+        self
+    }
+
+    /// Adds the records to the additional section
+    pub fn add_additionals<I: IntoIterator<Item = Record>>(&mut self, records: I) -> &mut Self {
+        self.parts.additionals.extend(records);
+        self
+    }
+
+    /// Adds a SIG(0) or TSIG record, at the end of the additional section
+    ///
+    /// The record is added as is, use [`Message::finalize`] to sign the message.
+    pub fn add_signature(&mut self, record: Record) -> &mut Self {
+        self.parts.sig0.push(record);
+        self
+    }
+
+    /// Sets the question count of the header, instead of the number of questions
+    ///
+    /// Only [`Self::to_vec`] encodes it, validation fails if it is not the number of questions.
+    pub fn query_count(&mut self, count: u16) -> &mut Self {
+        self.counts.query = Some(count);
+        self
+    }
+
+    /// Sets the answer count of the header, instead of the number of answers
+    pub fn answer_count(&mut self, count: u16) -> &mut Self {
+        self.counts.answer = Some(count);
+        self
+    }
+
+    /// Sets the name server count of the header, instead of the number of name server records
+    pub fn name_server_count(&mut self, count: u16) -> &mut Self {
+        self.counts.name_server = Some(count);
+        self
+    }
+
+    /// Sets the additional count of the header, instead of the number of additional records,
+    ///  including the EDNS record and the signatures
+    pub fn additional_count(&mut self, count: u16) -> &mut Self {
+        self.counts.additional = Some(count);
+        self
+    }
+
+    /// Builds the message, after validating it unless validation is disabled
+    ///
+    /// The header counts of the message are always those of its sections, see [`Self::to_vec`].
+    pub fn build(&self) -> ProtoResult<Message> {
+        if self.validate {
+            self.check()?;
+        }
+
+        Ok(Message::from(self.parts.clone()))
+    }
+
+    /// Builds and encodes the message, with the header counts set explicitly if any
+    pub fn to_vec(&self) -> ProtoResult<Vec<u8>> {
+        let mut buffer = self.build()?.to_vec()?;
+        if self.counts.is_empty() {
+            return Ok(buffer);
+        }
+
+        // the counts follow the id and the flags
+        let counts = [
+            self.counts.query,
+            self.counts.answer,
+            self.counts.name_server,
+            self.counts.additional,
+        ];
+        for (i, count) in counts.iter().enumerate() {
+            if let Some(count) = count {
+                let offset = 4 + i * 2;
+                buffer[offset..offset + 2].copy_from_slice(&count.to_be_bytes());
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Returns an error if the message is malformed
+    fn check(&self) -> ProtoResult<()> {
+        let parts = &self.parts;
+        let header = &parts.header;
+
+        if header.response_code().high() > 0 && parts.edns.is_none() {
+            return Err(ProtoError::from(format!(
+                "response code {} requires EDNS",
+                header.response_code()
+            )));
+        }
+
+        if parts
+            .answers
+            .iter()
+            .chain(&parts.name_servers)
+            .chain(&parts.additionals)
+            .any(|r| r.record_type() == RecordType::OPT)
+        {
+            return Err(ProtoError::from("the EDNS record must be set with edns"));
+        }
+
+        if let Some(r) = parts
+            .sig0
+            .iter()
+            .find(|r| !matches!(r.record_type(), RecordType::SIG | RecordType::TSIG))
+        {
+            return Err(ProtoError::from(format!(
+                "{} is not a signature",
+                r.record_type()
+            )));
+        }
+
+        match header.op_code() {
+            OpCode::Query
+                if header.message_type() == MessageType::Query && parts.queries.len() != 1 =>
+            {
+                return Err(ProtoError::from(format!(
+                    "a query has one question, not {}",
+                    parts.queries.len()
+                )));
+            }
+            OpCode::Update
+                if parts.queries.len() != 1 || parts.queries[0].query_type() != RecordType::SOA =>
+            {
+                return Err(ProtoError::from("an update has one zone, of type SOA"));
+            }
+            _ => (),
+        }
+
+        let additional_count =
+            parts.additionals.len() + parts.sig0.len() + if parts.edns.is_some() { 1 } else { 0 };
+        let counts = [
+            ("question", self.counts.query, parts.queries.len()),
+            ("answer", self.counts.answer, parts.answers.len()),
+            (
+                "name server",
+                self.counts.name_server,
+                parts.name_servers.len(),
+            ),
+            ("additional", self.counts.additional, additional_count),
+        ];
+        for (section, count, len) in counts.iter() {
+            if len > &usize::from(u16::max_value()) {
+                return Err(ProtoError::from(format!(
+                    "too many {} records: {}",
+                    section, len
+                )));
+            }
+            if let Some(count) = count {
+                if usize::from(*count) != *len {
+                    return Err(ProtoError::from(format!(
+                        "{} count {} does not match the {} records",
+                        section, count, len
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::rr::rdata::OPT;
+    use crate::rr::RData;
+    use crate::serialize::binary::BinDecodable;
+
+    fn query() -> Query {
+        Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A)
+    }
+
+    fn a_record() -> Record {
+        Record::from_rdata(
+            Name::from_ascii("www.example.com.").unwrap(),
+            300,
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        )
+    }
+
+    #[test]
+    fn test_query() {
+        let mut edns = Edns::new();
+        edns.set_max_payload(1232).set_dnssec_ok(true);
+
+        let message = MessageBuilder::query(query())
+            .id(1234)
+            .authentic_data(true)
+            .edns(edns)
+            .build()
+            .unwrap();
+        assert_eq!(message.id(), 1234);
+        assert_eq!(message.message_type(), MessageType::Query);
+        assert!(message.recursion_desired());
+        assert!(message.authentic_data());
+        assert_eq!(message.queries(), &[query()]);
+        assert_eq!(message.max_payload(), 1232);
+
+        let decoded = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        assert!(decoded.extensions().as_ref().unwrap().dnssec_ok());
+    }
+
+    #[test]
+    fn test_response() {
+        let request = MessageBuilder::query(query()).id(7).build().unwrap();
+        let response = MessageBuilder::response(&request)
+            .authoritative(true)
+            .response_code(ResponseCode::NXDomain)
+            .build()
+            .unwrap();
+
+        assert_eq!(response.id(), 7);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert!(response.authoritative());
+        assert!(response.recursion_desired());
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert_eq!(response.queries(), request.queries());
+    }
+
+    #[test]
+    fn test_update() {
+        let zone = Name::from_ascii("example.com.").unwrap();
+        let message = MessageBuilder::update(zone.clone(), DNSClass::IN)
+            .add_name_server(a_record())
+            .build()
+            .unwrap();
+
+        assert_eq!(message.op_code(), OpCode::Update);
+        assert_eq!(message.queries()[0].name(), &zone);
+        assert_eq!(message.queries()[0].query_type(), RecordType::SOA);
+        assert_eq!(message.name_servers().len(), 1);
+
+        // the zone of an update must be its only question
+        assert!(MessageBuilder::update(zone, DNSClass::IN)
+            .add_query(query())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(MessageBuilder::new().build().is_err());
+        assert!(MessageBuilder::query(query())
+            .response_code(ResponseCode::BADVERS)
+            .build()
+            .is_err());
+        assert!(MessageBuilder::query(query())
+            .add_additional(Record::from_rdata(
+                Name::root(),
+                0,
+                RData::OPT(OPT::default())
+            ))
+            .build()
+            .is_err());
+        assert!(MessageBuilder::query(query())
+            .add_signature(a_record())
+            .build()
+            .is_err());
+        assert!(MessageBuilder::query(query())
+            .answer_count(1)
+            .build()
+            .is_err());
+
+        // without validation, anything goes
+        assert!(MessageBuilder::new().validate(false).build().is_ok());
+    }
+
+    #[test]
+    fn test_counts() {
+        let bytes = MessageBuilder::query(query())
+            .add_answer(a_record())
+            .validate(false)
+            .query_count(2)
+            .additional_count(3)
+            .to_vec()
+            .unwrap();
+
+        let header = Header::from_bytes(&bytes[..Header::len()]).unwrap();
+        assert_eq!(header.query_count(), 2);
+        assert_eq!(header.answer_count(), 1);
+        assert_eq!(header.name_server_count(), 0);
+        assert_eq!(header.additional_count(), 3);
+
+        // the sections don't match the header
+        assert!(Message::from_vec(&bytes).is_err());
+    }
+}
@@ -0,0 +1,661 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Borrowed view of a DNS message, decoding records lazily
+
+use crate::error::*;
+use crate::op::{Edns, Header, Message, Query};
+use crate::rr::{DNSClass, Name, RData, Record, RecordType};
+use crate::serialize::binary::*;
+
+#[cfg(feature = "mdns")]
+use crate::op::query::MDNS_UNICAST_RESPONSE;
+#[cfg(feature = "mdns")]
+use crate::rr::resource::MDNS_ENABLE_CACHE_FLUSH;
+
+/// A DNS message borrowed from the buffer it was received in
+///
+/// Only the header is decoded up front, the sections are validated to be well framed, but the
+///  names and rdata of the records are only decoded, and allocated, when they are requested. This
+///  allows large responses, e.g. zone transfers, to be scanned for the records of interest without
+///  the cost of decoding a full `Message`.
+///
+/// ```
+/// use trust_dns_proto::op::{Message, MessageRef, Query};
+/// use trust_dns_proto::rr::{Name, RecordType};
+///
+/// let mut message = Message::new();
+/// message.add_query(Query::query(Name::from_ascii("example.com.").unwrap(), RecordType::A));
+/// let bytes = message.to_vec().unwrap();
+///
+/// let message = MessageRef::from_slice(&bytes).unwrap();
+/// let query = message.queries().next().unwrap();
+/// assert!(query.name().eq_name(&Name::from_ascii("EXAMPLE.com.").unwrap()));
+/// assert_eq!(query.query_type(), RecordType::A);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageRef<'a> {
+    buffer: &'a [u8],
+    header: Header,
+    answers: usize,
+    name_servers: usize,
+    additionals: usize,
+    edns: Option<RecordRef<'a>>,
+}
+
+impl<'a> MessageRef<'a> {
+    /// Reads the header and validates the framing of all sections of the message
+    pub fn from_slice(buffer: &'a [u8]) -> ProtoResult<Self> {
+        if buffer.len() > u16::max_value() as usize {
+            return Err(format!("message exceeds maximum length: {}", buffer.len()).into());
+        }
+
+        let mut decoder = BinDecoder::new(buffer);
+        let mut header = Header::read(&mut decoder)?;
+
+        let mut offset = decoder.index();
+        for _ in 0..header.query_count() {
+            offset = QueryRef::read(buffer, offset)?.end;
+        }
+
+        let answers = offset;
+        for _ in 0..header.answer_count() {
+            offset = RecordRef::read(buffer, offset)?.end();
+        }
+
+        let name_servers = offset;
+        for _ in 0..header.name_server_count() {
+            offset = RecordRef::read(buffer, offset)?.end();
+        }
+
+        let additionals = offset;
+        let mut edns = None;
+        for _ in 0..header.additional_count() {
+            let record = RecordRef::read(buffer, offset)?;
+            if record.record_type() == RecordType::OPT {
+                if edns.is_some() {
+                    return Err("more than one edns record present".into());
+                }
+                edns = Some(record);
+            }
+            offset = record.end();
+        }
+
+        // need to grab error code from EDNS (which might have a higher value)
+        if let Some(edns) = &edns {
+            header.merge_response_code((edns.ttl() >> 24) as u8);
+        }
+
+        Ok(Self {
+            buffer,
+            header,
+            answers,
+            name_servers,
+            additionals,
+            edns,
+        })
+    }
+
+    /// The buffer the message is read from
+    pub fn buffer(&self) -> &'a [u8] {
+        self.buffer
+    }
+
+    /// The header of the message, the response code includes the high bits from EDNS
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The queries of the message
+    pub fn queries(&self) -> QueryRefs<'a> {
+        QueryRefs {
+            buffer: self.buffer,
+            offset: Header::len(),
+            remaining: self.header.query_count(),
+        }
+    }
+
+    /// The records of the answer section
+    pub fn answers(&self) -> RecordRefs<'a> {
+        RecordRefs {
+            buffer: self.buffer,
+            offset: self.answers,
+            remaining: self.header.answer_count(),
+        }
+    }
+
+    /// The records of the authority section
+    pub fn name_servers(&self) -> RecordRefs<'a> {
+        RecordRefs {
+            buffer: self.buffer,
+            offset: self.name_servers,
+            remaining: self.header.name_server_count(),
+        }
+    }
+
+    /// The records of the additional section
+    ///
+    /// Unlike `Message::additionals`, this includes the OPT record and any SIG0 or TSIG records.
+    pub fn additionals(&self) -> RecordRefs<'a> {
+        RecordRefs {
+            buffer: self.buffer,
+            offset: self.additionals,
+            remaining: self.header.additional_count(),
+        }
+    }
+
+    /// Decodes the EDNS of the message, if present
+    pub fn edns(&self) -> ProtoResult<Option<Edns>> {
+        self.edns
+            .map(|record| record.to_record().map(|record| Edns::from(&record)))
+            .transpose()
+    }
+
+    /// Decodes the full message
+    pub fn to_message(&self) -> ProtoResult<Message> {
+        Message::from_vec(self.buffer)
+    }
+}
+
+/// A domain name in a message, the labels are read directly from the buffer
+#[derive(Clone, Copy, Debug)]
+pub struct NameRef<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NameRef<'a> {
+    /// Validates the name at the offset like `Name::read`, returning the offset following it
+    fn read(buffer: &'a [u8], offset: usize) -> ProtoResult<(Self, usize)> {
+        let mut name_start = offset;
+        let mut max_idx = None;
+        let mut idx = offset;
+        let mut end = None;
+        let mut len = 0;
+
+        loop {
+            // this protects against overlapping labels
+            if let Some(max_idx) = max_idx {
+                if idx >= max_idx {
+                    return Err(DecodeError::LabelOverlapsWithOther {
+                        label: name_start,
+                        other: max_idx,
+                    }
+                    .into());
+                }
+            }
+
+            let byte = *buffer.get(idx).ok_or(DecodeError::InsufficientBytes)?;
+            match byte & 0b1100_0000 {
+                0b0000_0000 if byte == 0 => {
+                    let end = end.unwrap_or(idx + 1);
+                    return Ok((Self { buffer, offset }, end));
+                }
+                0b0000_0000 => {
+                    // labels must have a maximum length of 63, guaranteed by the high bits
+                    idx += 1 + byte as usize;
+                    if idx > buffer.len() {
+                        return Err(DecodeError::InsufficientBytes.into());
+                    }
+
+                    len += 1 + byte as usize;
+                    if len > 255 {
+                        return Err(DecodeError::DomainNameTooLong(byte as usize).into());
+                    }
+                }
+                0b1100_0000 => {
+                    let low = *buffer.get(idx + 1).ok_or(DecodeError::InsufficientBytes)?;
+                    let ptr = u16::from_be_bytes([byte, low]) & 0x3FFF;
+
+                    // all labels must appear "prior" to this Name
+                    if ptr as usize >= name_start {
+                        return Err(DecodeError::PointerNotPriorToLabel { idx, ptr }.into());
+                    }
+
+                    end.get_or_insert(idx + 2);
+                    max_idx = Some(name_start);
+                    name_start = ptr as usize;
+                    idx = ptr as usize;
+                }
+                _ => return Err(DecodeError::UnrecognizedLabelCode(byte).into()),
+            }
+        }
+    }
+
+    /// The labels of the name, following compression pointers
+    pub fn labels(&self) -> LabelRefs<'a> {
+        LabelRefs {
+            buffer: self.buffer,
+            idx: self.offset,
+        }
+    }
+
+    /// Returns true if this is the root name
+    pub fn is_root(&self) -> bool {
+        self.labels().next().is_none()
+    }
+
+    /// Compares the name to an owned name, ignoring case like `Name`'s `PartialEq`
+    pub fn eq_name(&self, name: &Name) -> bool {
+        let mut labels = self.labels();
+
+        name.iter().all(|label| {
+            labels
+                .next()
+                .map_or(false, |other| other.eq_ignore_ascii_case(label))
+        }) && labels.next().is_none()
+    }
+
+    /// Decodes the name
+    pub fn to_name(&self) -> ProtoResult<Name> {
+        Name::read(&mut BinDecoder::new(self.buffer).clone(self.offset as u16))
+    }
+}
+
+/// Iterator over the labels of a `NameRef`
+#[derive(Clone, Debug)]
+pub struct LabelRefs<'a> {
+    buffer: &'a [u8],
+    idx: usize,
+}
+
+impl<'a> Iterator for LabelRefs<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let byte = *self.buffer.get(self.idx)?;
+            if byte == 0 {
+                return None;
+            }
+
+            if byte & 0b1100_0000 == 0b1100_0000 {
+                let low = *self.buffer.get(self.idx + 1)?;
+                let ptr = (u16::from_be_bytes([byte, low]) & 0x3FFF) as usize;
+
+                // the name was validated, but pointers must always go backwards to terminate
+                if ptr >= self.idx {
+                    return None;
+                }
+
+                self.idx = ptr;
+                continue;
+            }
+
+            let start = self.idx + 1;
+            let label = self.buffer.get(start..start + byte as usize)?;
+            self.idx = start + label.len();
+            return Some(label);
+        }
+    }
+}
+
+/// A query in a message, the name is not decoded
+#[derive(Clone, Copy, Debug)]
+pub struct QueryRef<'a> {
+    name: NameRef<'a>,
+    query_type: RecordType,
+    query_class: DNSClass,
+    #[cfg(feature = "mdns")]
+    mdns_unicast_response: bool,
+    end: usize,
+}
+
+impl<'a> QueryRef<'a> {
+    fn read(buffer: &'a [u8], offset: usize) -> ProtoResult<Self> {
+        let (name, name_end) = NameRef::read(buffer, offset)?;
+
+        let mut decoder = BinDecoder::new(&buffer[name_end..]);
+        let query_type = RecordType::read(&mut decoder)?;
+        let query_class_value =
+            decoder.read_u16()?.unverified(/*DNSClass::from_u16 will verify the value*/);
+
+        #[cfg(feature = "mdns")]
+        let mdns_unicast_response = query_class_value & MDNS_UNICAST_RESPONSE > 0;
+        #[cfg(feature = "mdns")]
+        let query_class_value = query_class_value & !MDNS_UNICAST_RESPONSE;
+
+        Ok(Self {
+            name,
+            query_type,
+            query_class: DNSClass::from_u16(query_class_value)?,
+            #[cfg(feature = "mdns")]
+            mdns_unicast_response,
+            end: name_end + decoder.index(),
+        })
+    }
+
+    /// The name being queried
+    pub fn name(&self) -> NameRef<'a> {
+        self.name
+    }
+
+    /// The type of the query
+    pub fn query_type(&self) -> RecordType {
+        self.query_type
+    }
+
+    /// The class of the query
+    pub fn query_class(&self) -> DNSClass {
+        self.query_class
+    }
+
+    /// Returns true if the unicast-response bit of the multicast DNS question is set
+    #[cfg(feature = "mdns")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "mdns")))]
+    pub fn mdns_unicast_response(&self) -> bool {
+        self.mdns_unicast_response
+    }
+
+    /// Decodes the query
+    pub fn to_query(&self) -> ProtoResult<Query> {
+        Query::read(&mut BinDecoder::new(self.name.buffer).clone(self.name.offset as u16))
+    }
+}
+
+/// A record in a message, the name and rdata are not decoded
+#[derive(Clone, Copy, Debug)]
+pub struct RecordRef<'a> {
+    name: NameRef<'a>,
+    record_type: RecordType,
+    dns_class: DNSClass,
+    #[cfg(feature = "mdns")]
+    mdns_cache_flush: bool,
+    ttl: u32,
+    rdata_offset: usize,
+    rdata_length: u16,
+}
+
+impl<'a> RecordRef<'a> {
+    fn read(buffer: &'a [u8], offset: usize) -> ProtoResult<Self> {
+        let (name, name_end) = NameRef::read(buffer, offset)?;
+
+        let mut decoder = BinDecoder::new(&buffer[name_end..]);
+        let record_type = RecordType::read(&mut decoder)?;
+        let class_value = decoder.read_u16()?.unverified(/*verified by DNSClass*/);
+        let ttl = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+        let rdata_length = decoder
+            .read_u16()?
+            .verify_unwrap(|u| (*u as usize) <= decoder.len())
+            .map_err(|u| {
+                ProtoError::from(format!(
+                    "rdata length too large for remaining bytes, need: {} remain: {}",
+                    u,
+                    decoder.len()
+                ))
+            })?;
+
+        #[cfg(feature = "mdns")]
+        let mut mdns_cache_flush = false;
+
+        let dns_class = if record_type == RecordType::OPT {
+            // verify that the OPT record is Root
+            if !name.is_root() {
+                return Err(ProtoErrorKind::EdnsNameNotRoot(name.to_name()?).into());
+            }
+
+            //  DNS Class is overloaded for OPT records in EDNS - RFC 6891
+            DNSClass::for_opt(class_value)
+        } else {
+            #[cfg(feature = "mdns")]
+            let class_value = {
+                mdns_cache_flush = class_value & MDNS_ENABLE_CACHE_FLUSH > 0;
+                class_value & !MDNS_ENABLE_CACHE_FLUSH
+            };
+
+            DNSClass::from_u16(class_value)?
+        };
+
+        Ok(Self {
+            name,
+            record_type,
+            dns_class,
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush,
+            ttl,
+            rdata_offset: name_end + decoder.index(),
+            rdata_length,
+        })
+    }
+
+    /// The offset following the record
+    fn end(&self) -> usize {
+        self.rdata_offset + self.rdata_length as usize
+    }
+
+    /// The owner name of the record
+    pub fn name(&self) -> NameRef<'a> {
+        self.name
+    }
+
+    /// The type of the record
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+
+    /// The class of the record, the maximum payload for OPT records
+    pub fn dns_class(&self) -> DNSClass {
+        self.dns_class
+    }
+
+    /// Returns true if the cache-flush bit of the multicast DNS record is set
+    #[cfg(feature = "mdns")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "mdns")))]
+    pub fn mdns_cache_flush(&self) -> bool {
+        self.mdns_cache_flush
+    }
+
+    /// The time to live of the record
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// The undecoded rdata, names in it may be compressed
+    pub fn rdata_bytes(&self) -> &'a [u8] {
+        &self.name.buffer[self.rdata_offset..self.end()]
+    }
+
+    /// Decodes the rdata, `None` for empty rdata as used by updates
+    pub fn rdata(&self) -> ProtoResult<Option<RData>> {
+        if self.rdata_length == 0 {
+            return Ok(None);
+        }
+
+        let mut decoder = BinDecoder::new(self.name.buffer).clone(self.rdata_offset as u16);
+        RData::read(
+            &mut decoder,
+            self.record_type,
+            Restrict::new(self.rdata_length),
+        )
+        .map(Some)
+    }
+
+    /// Decodes the record
+    pub fn to_record(&self) -> ProtoResult<Record> {
+        Record::read(&mut BinDecoder::new(self.name.buffer).clone(self.name.offset as u16))
+    }
+}
+
+/// Iterator over the queries of a `MessageRef`
+#[derive(Clone, Debug)]
+pub struct QueryRefs<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> Iterator for QueryRefs<'a> {
+    type Item = QueryRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // the framing was validated in MessageRef::from_slice
+        let query = QueryRef::read(self.buffer, self.offset).ok()?;
+        self.offset = query.end;
+        self.remaining -= 1;
+        Some(query)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl<'a> ExactSizeIterator for QueryRefs<'a> {}
+
+/// Iterator over the records of a section of a `MessageRef`
+#[derive(Clone, Debug)]
+pub struct RecordRefs<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> Iterator for RecordRefs<'a> {
+    type Item = RecordRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // the framing was validated in MessageRef::from_slice
+        let record = RecordRef::read(self.buffer, self.offset).ok()?;
+        self.offset = record.end();
+        self.remaining -= 1;
+        Some(record)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl<'a> ExactSizeIterator for RecordRefs<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::op::{MessageType, ResponseCode};
+
+    fn message() -> Message {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let alias = Name::from_ascii("cdn.example.com.").unwrap();
+
+        let mut message = Message::new();
+        message
+            .set_id(10)
+            .set_message_type(MessageType::Response)
+            .set_response_code(ResponseCode::BADCOOKIE)
+            .add_query(Query::query(name.clone(), RecordType::A))
+            .add_answer(Record::from_rdata(name, 300, RData::CNAME(alias.clone())))
+            .add_answer(Record::from_rdata(
+                alias.clone(),
+                60,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ))
+            .add_name_server(Record::from_rdata(
+                Name::from_ascii("example.com.").unwrap(),
+                3600,
+                RData::NS(Name::from_ascii("ns.example.com.").unwrap()),
+            ));
+
+        let mut edns = Edns::new();
+        edns.set_max_payload(1232);
+        message.set_edns(edns);
+        message
+    }
+
+    #[test]
+    fn test_read() {
+        let message = message();
+        let bytes = message.to_vec().unwrap();
+        let message_ref = MessageRef::from_slice(&bytes).unwrap();
+
+        assert_eq!(message_ref.header().id(), 10);
+        assert_eq!(message_ref.header().response_code(), ResponseCode::BADCOOKIE);
+        assert_eq!(message_ref.queries().len(), 1);
+        assert_eq!(
+            message_ref.queries().next().unwrap().to_query().unwrap(),
+            message.queries()[0]
+        );
+
+        let answers = message_ref
+            .answers()
+            .map(|record| record.to_record().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(answers, message.answers());
+
+        let name_servers = message_ref
+            .name_servers()
+            .map(|record| record.to_record().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(name_servers, message.name_servers());
+
+        assert_eq!(message_ref.additionals().len(), 1);
+        assert_eq!(
+            message_ref.edns().unwrap().unwrap().max_payload(),
+            message.extensions().as_ref().unwrap().max_payload()
+        );
+        assert_eq!(
+            message_ref.to_message().unwrap().answers(),
+            message.answers()
+        );
+    }
+
+    #[test]
+    fn test_lazy_record() {
+        let bytes = message().to_vec().unwrap();
+        let message_ref = MessageRef::from_slice(&bytes).unwrap();
+        let alias = Name::from_ascii("CDN.example.com.").unwrap();
+
+        // the A record's name is compressed, pointing into the CNAME rdata
+        let record = message_ref.answers().nth(1).unwrap();
+        assert!(record.name().eq_name(&alias));
+        assert!(!record
+            .name()
+            .eq_name(&Name::from_ascii("example.com.").unwrap()));
+        assert_eq!(
+            record.name().labels().collect::<Vec<_>>(),
+            vec![&b"cdn"[..], &b"example"[..], &b"com"[..]]
+        );
+        assert_eq!(record.record_type(), RecordType::A);
+        assert_eq!(record.dns_class(), DNSClass::IN);
+        assert_eq!(record.ttl(), 60);
+        assert_eq!(record.rdata_bytes(), &[192, 0, 2, 1]);
+        assert_eq!(
+            record.rdata().unwrap(),
+            Some(RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+
+        let cname = message_ref.answers().next().unwrap();
+        assert_eq!(cname.rdata().unwrap(), Some(RData::CNAME(alias)));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let bytes = message().to_vec().unwrap();
+
+        for len in Header::len()..bytes.len() {
+            assert!(MessageRef::from_slice(&bytes[..len]).is_err());
+        }
+
+        // a pointer to itself
+        let mut bytes = Message::new()
+            .add_query(Query::query(Name::root(), RecordType::A))
+            .to_vec()
+            .unwrap();
+        let offset = Header::len();
+        bytes.splice(offset..offset + 1, vec![0xC0, offset as u8]);
+        assert!(MessageRef::from_slice(&bytes).is_err());
+    }
+}
@@ -17,19 +17,29 @@
 //! Operations to send with a `Client` or server, e.g. `Query`, `Message`, or `UpdateMessage` can
 //! be used together to either query or update resource records sets.
 
+mod decode_options;
+pub mod dso;
 mod edns;
 pub mod header;
 pub mod message;
+mod message_builder;
+pub mod message_ref;
 pub mod op_code;
+mod padding;
 pub mod query;
 pub mod response_code;
 
+pub use self::decode_options::{DecodeDiagnostic, DecodeOptions, MessageSection};
+pub use self::dso::DsoMessage;
 pub use self::edns::Edns;
 pub use self::header::Header;
 pub use self::header::MessageType;
 pub use self::message::{
     Message, MessageFinalizer, MessageParts, MessageVerifier, NoopMessageFinalizer,
 };
+pub use self::message_builder::MessageBuilder;
+pub use self::message_ref::MessageRef;
 pub use self::op_code::OpCode;
+pub use self::padding::PaddingPolicy;
 pub use self::query::Query;
 pub use self::response_code::ResponseCode;
@@ -0,0 +1,121 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Padding of messages on encrypted transports, [RFC 8467](https://tools.ietf.org/html/rfc8467)
+
+/// The length of the option code and option length of the padding option
+const OPTION_HEADER_LEN: usize = 4;
+
+/// How messages are padded with the EDNS(0) Padding option, [RFC 7830](https://tools.ietf.org/html/rfc7830)
+///
+/// Padding only hides the size of messages on encrypted transports, e.g. DNS-over-TLS and
+///  DNS-over-HTTPS, on other transports it only wastes bandwidth.
+///
+/// ```text
+/// 4.1.  Block-Length Padding
+///
+///    Clients SHOULD pad queries to the closest multiple of 128 octets.
+///
+///    If the server is capable of padding, it MUST pad a response to the
+///    closest multiple of 468 octets, up to the maximum allowed length of
+///    the response, if the query it is responding to was padded.
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingPolicy {
+    /// Messages are not padded
+    None,
+    /// Messages are padded to a multiple of the block length
+    BlockLength(u16),
+}
+
+impl PaddingPolicy {
+    /// The block length recommended for queries, RFC 8467 section 4.1
+    pub const QUERY_BLOCK_LENGTH: u16 = 128;
+    /// The block length recommended for responses, RFC 8467 section 4.1
+    pub const RESPONSE_BLOCK_LENGTH: u16 = 468;
+
+    /// The recommended padding of queries from clients
+    pub fn client() -> Self {
+        Self::BlockLength(Self::QUERY_BLOCK_LENGTH)
+    }
+
+    /// The recommended padding of responses from servers
+    ///
+    /// Servers only pad the responses to padded queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_padded` - true if the query contained the Padding option
+    pub fn server(query_padded: bool) -> Self {
+        if query_padded {
+            Self::BlockLength(Self::RESPONSE_BLOCK_LENGTH)
+        } else {
+            Self::None
+        }
+    }
+
+    /// Returns the number of padding octets to add to a message
+    ///
+    /// # Arguments
+    ///
+    /// * `message_len` - the length of the encoded message, including an OPT record but without
+    ///                   the padding option
+    ///
+    /// # Return
+    ///
+    /// `None` if the message should not be padded, otherwise the length of the padding option
+    ///  data, which may be 0 when the option itself completes the block.
+    pub fn padding_len(self, message_len: usize) -> Option<u16> {
+        let block_length = match self {
+            Self::None | Self::BlockLength(0) => return None,
+            Self::BlockLength(block_length) => usize::from(block_length),
+        };
+
+        let unpadded = message_len + OPTION_HEADER_LEN;
+        if unpadded > usize::from(u16::max_value()) {
+            return None;
+        }
+
+        let padded = match unpadded % block_length {
+            0 => unpadded,
+            rem => unpadded + block_length - rem,
+        };
+
+        // the message must still fit, padding up to the maximum length is allowed
+        let padded = padded.min(usize::from(u16::max_value()));
+        Some((padded - unpadded) as u16)
+    }
+}
+
+impl Default for PaddingPolicy {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padding_len() {
+        let client = PaddingPolicy::client();
+        assert_eq!(client.padding_len(0), Some(124));
+        assert_eq!(client.padding_len(50), Some(74));
+        assert_eq!(client.padding_len(124), Some(0));
+        assert_eq!(client.padding_len(125), Some(127));
+
+        let server = PaddingPolicy::server(true);
+        assert_eq!(server.padding_len(100), Some(364));
+        assert_eq!(server.padding_len(65_530), Some(1));
+        assert_eq!(server.padding_len(65_535), None);
+
+        assert_eq!(PaddingPolicy::server(false).padding_len(100), None);
+        assert_eq!(PaddingPolicy::None.padding_len(100), None);
+        assert_eq!(PaddingPolicy::BlockLength(0).padding_len(100), None);
+    }
+}
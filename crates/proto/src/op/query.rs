@@ -32,7 +32,7 @@ use crate::serialize::binary::*;
 // cases, Multicast DNS defines the top bit in the class field of a DNS
 // question as the unicast-response bit.
 /// ```
-const MDNS_UNICAST_RESPONSE: u16 = 1 << 15;
+pub(crate) const MDNS_UNICAST_RESPONSE: u16 = 1 << 15;
 
 /// Query struct for looking up resource records, basically a resource record without RDATA.
 ///
@@ -10,10 +10,10 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use std::{future::Future, marker::PhantomData};
 
-use futures_util::{future, TryFutureExt};
+use futures_util::future;
 use openssl::pkcs12::ParsedPkcs12;
 use openssl::pkey::{PKeyRef, Private};
-use openssl::ssl::{ConnectConfiguration, SslConnector, SslContextBuilder, SslMethod, SslOptions};
+use openssl::ssl::{SslConnector, SslContextBuilder, SslMethod, SslOptions};
 use openssl::stack::Stack;
 use openssl::x509::store::X509StoreBuilder;
 use openssl::x509::{X509Ref, X509};
@@ -125,34 +125,34 @@ pub fn tls_stream_from_existing_tls_stream<S: Connect>(
     (stream, message_sender)
 }
 
-async fn connect_tls<S: Connect>(
-    tls_config: ConnectConfiguration,
-    dns_name: String,
-    name_server: SocketAddr,
-    bind_addr: Option<SocketAddr>,
-) -> Result<TokioTlsStream<AsyncIoStdAsTokio<S>>, io::Error> {
-    let tcp = S::connect_with_bind(name_server, bind_addr)
-        .await
-        .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::ConnectionRefused,
-                format!("tls error: {}", e),
-            )
-        })?;
-    let mut stream = tls_config
-        .into_ssl(&dns_name)
-        .and_then(|ssl| TokioTlsStream::new(ssl, AsyncIoStdAsTokio(tcp)))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("tls error: {}", e)))?;
-    Pin::new(&mut stream).connect().await.map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::ConnectionRefused,
-            format!("tls error: {}", e),
-        )
-    })?;
-    Ok(stream)
+impl<S: Connect> crate::tls::TlsConnector<S> for SslConnector {
+    type Stream = TokioTlsStream<AsyncIoStdAsTokio<S>>;
+
+    fn connect(
+        &self,
+        dns_name: &str,
+        tcp: S,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Self::Stream>> + Send>> {
+        let stream = self
+            .configure()
+            .and_then(|tls_config| tls_config.into_ssl(dns_name))
+            .and_then(|ssl| TokioTlsStream::new(ssl, AsyncIoStdAsTokio(tcp)))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("tls error: {}", e)));
+
+        Box::pin(async move {
+            let mut stream = stream?;
+            Pin::new(&mut stream).connect().await.map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("tls error: {}", e),
+                )
+            })?;
+
+            Ok(stream)
+        })
+    }
 }
 
-/// A builder for the TlsStream
 #[derive(Default)]
 pub struct TlsStreamBuilder<S> {
     ca_chain: Vec<X509>,
@@ -224,49 +224,14 @@ impl<S: Connect> TlsStreamBuilder<S> {
         Pin<Box<dyn Future<Output = Result<CompatTlsStream<S>, io::Error>> + Send>>,
         BufDnsStreamHandle,
     ) {
-        let (message_sender, outbound_messages) = BufDnsStreamHandle::new(name_server);
-        let tls_config = match new(self.ca_chain, self.identity) {
-            Ok(c) => c,
+        let tls_connector = match new(self.ca_chain, self.identity) {
+            Ok(tls_connector) => tls_connector,
             Err(e) => {
-                return (
-                    Box::pin(future::err(e).map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::ConnectionRefused,
-                            format!("tls error: {}", e),
-                        )
-                    })),
-                    message_sender,
-                )
+                let (message_sender, _) = BufDnsStreamHandle::new(name_server);
+                return (Box::pin(future::err(e)), message_sender);
             }
         };
 
-        let tls_config = match tls_config.configure() {
-            Ok(c) => c,
-            Err(e) => {
-                return (
-                    Box::pin(future::err(e).map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::ConnectionRefused,
-                            format!("tls config error: {}", e),
-                        )
-                    })),
-                    message_sender,
-                )
-            }
-        };
-
-        // This set of futures collapses the next tcp socket into a stream which can be used for
-        //  sending and receiving tcp packets.
-        let stream = Box::pin(
-            connect_tls(tls_config, dns_name, name_server, self.bind_addr).map_ok(move |s| {
-                TcpStream::from_stream_with_receiver(
-                    AsyncIoTokioAsStd(s),
-                    name_server,
-                    outbound_messages,
-                )
-            }),
-        );
-
-        (stream, message_sender)
+        crate::tls::tls_connect(name_server, self.bind_addr, dns_name, tls_connector)
     }
 }
@@ -12,14 +12,23 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
-use futures_util::{future::FutureExt, stream::Stream};
-use quinn::{ClientConfig, Connection, Endpoint, NewConnection, OpenBi, TransportConfig, VarInt};
-use rustls::{version::TLS13, ClientConfig as TlsClientConfig};
+use futures_util::{
+    future::{FutureExt, Shared},
+    stream::Stream,
+};
+use quinn::{
+    ClientConfig, Connection, Endpoint, NewConnection, TransportConfig, VarInt, ZeroRttAccepted,
+};
+use quinn_proto::ConnectionStats;
+use rustls::{client::StoresClientSessions, version::TLS13, ClientConfig as TlsClientConfig};
+use tracing::debug;
 
 use crate::{
     error::ProtoError,
+    op::{MessageType, OpCode},
     quic::quic_stream::{DoqErrorCode, QuicStream},
     udp::UdpSocket,
     xfer::{DnsRequest, DnsRequestSender, DnsResponse, DnsResponseStream},
@@ -33,6 +42,7 @@ pub struct QuicClientStream {
     quic_connection: Connection,
     name_server_name: Arc<str>,
     name_server: SocketAddr,
+    zero_rtt: Option<Shared<ZeroRttAccepted>>,
     is_shutdown: bool,
 }
 
@@ -52,8 +62,70 @@ impl QuicClientStream {
         QuicClientStreamBuilder::default()
     }
 
-    async fn inner_send(stream: OpenBi, message: DnsRequest) -> Result<DnsResponse, ProtoError> {
-        let (send_stream, recv_stream) = stream.await?;
+    /// Returns the statistics of the underlying QUIC connection
+    pub fn stats(&self) -> ConnectionStats {
+        self.quic_connection.stats()
+    }
+
+    /// Returns the current estimate of the round trip time to the name server
+    pub fn rtt(&self) -> Duration {
+        self.quic_connection.rtt()
+    }
+
+    /// Returns true if this connection resumed a previous session and sent 0-RTT early data
+    pub fn is_0rtt(&self) -> bool {
+        self.zero_rtt.is_some()
+    }
+
+    /// Returns true if the request is replayable, and thus may be sent as 0-RTT early data
+    ///
+    /// ```text
+    /// 4.5. Session Resumption and 0-RTT
+    ///
+    /// The 0-RTT mechanism MUST NOT be used to send DNS requests that are not "replayable"
+    /// transactions. In this specification, only transactions that have an OPCODE of QUERY or
+    /// NOTIFY are considered replayable; therefore, other OPCODES MUST NOT be sent in 0-RTT data.
+    /// ```
+    fn is_replay_safe(message: &DnsRequest) -> bool {
+        message.message_type() == MessageType::Query
+            && matches!(message.op_code(), OpCode::Query | OpCode::Notify)
+    }
+
+    async fn inner_send(
+        connection: Connection,
+        message: DnsRequest,
+        zero_rtt: Option<Shared<ZeroRttAccepted>>,
+    ) -> Result<DnsResponse, ProtoError> {
+        let zero_rtt = match zero_rtt {
+            Some(zero_rtt) if !Self::is_replay_safe(&message) => {
+                // hold the request back until the handshake completes, it must not be early data
+                zero_rtt.await;
+                None
+            }
+            zero_rtt => zero_rtt,
+        };
+
+        let result = Self::send_on_new_stream(&connection, message.clone()).await;
+
+        match (result, zero_rtt) {
+            (Err(e), Some(zero_rtt)) => {
+                if zero_rtt.await {
+                    return Err(e);
+                }
+
+                // the server rejected the early data, the stream was lost with it
+                debug!("0-RTT rejected, resending request: {}", e);
+                Self::send_on_new_stream(&connection, message).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    async fn send_on_new_stream(
+        connection: &Connection,
+        message: DnsRequest,
+    ) -> Result<DnsResponse, ProtoError> {
+        let (send_stream, recv_stream) = connection.open_bi().await?;
 
         // RFC: The mapping specified here requires that the client selects a separate
         //  QUIC stream for each query. The server then uses the same stream to provide all the response messages for that query.
@@ -115,9 +187,10 @@ impl DnsRequestSender for QuicClientStream {
             panic!("can not send messages after stream is shutdown")
         }
 
-        let connection = self.quic_connection.open_bi();
+        let connection = self.quic_connection.clone();
+        let zero_rtt = self.zero_rtt.clone();
 
-        Box::pin(Self::inner_send(connection, message)).into()
+        Box::pin(Self::inner_send(connection, message, zero_rtt)).into()
     }
 
     fn shutdown(&mut self) {
@@ -149,6 +222,8 @@ pub struct QuicClientStreamBuilder {
     crypto_config: TlsClientConfig,
     transport_config: Arc<TransportConfig>,
     bind_addr: Option<SocketAddr>,
+    enable_0rtt: bool,
+    session_storage: Option<Arc<dyn StoresClientSessions>>,
 }
 
 impl QuicClientStreamBuilder {
@@ -164,6 +239,22 @@ impl QuicClientStreamBuilder {
         self
     }
 
+    /// Send replayable queries as 0-RTT early data when resuming a session with the name server
+    ///
+    /// Sessions are resumed from the session storage of the crypto config, which is shared by all
+    ///  the connections built from clones of this builder. Requests which are not replayable,
+    ///  e.g. dynamic updates, are held back until the handshake completes.
+    pub fn enable_0rtt(&mut self, enable_0rtt: bool) -> &mut Self {
+        self.enable_0rtt = enable_0rtt;
+        self
+    }
+
+    /// Sets the storage of the sessions to resume, e.g. to share them between builders
+    pub fn session_storage(&mut self, storage: Arc<dyn StoresClientSessions>) -> &mut Self {
+        self.session_storage = Some(storage);
+        self
+    }
+
     /// Creates a new QuicStream to the specified name_server
     ///
     /// # Arguments
@@ -196,6 +287,12 @@ impl QuicClientStreamBuilder {
         if crypto_config.alpn_protocols.is_empty() {
             crypto_config.alpn_protocols = vec![quic_stream::DOQ_ALPN.to_vec()];
         }
+        if self.enable_0rtt {
+            crypto_config.enable_early_data = true;
+        }
+        if let Some(session_storage) = self.session_storage {
+            crypto_config.session_storage = session_storage;
+        }
         let early_data_enabled = crypto_config.enable_early_data;
 
         let mut client_config = ClientConfig::new(Arc::new(crypto_config));
@@ -204,15 +301,14 @@ impl QuicClientStreamBuilder {
         endpoint.set_default_client_config(client_config);
 
         let connecting = endpoint.connect(name_server, &dns_name)?;
-        // TODO: for Client/Dynamic update, don't use RTT, for queries, do use it.
 
-        let connection = if early_data_enabled {
+        let (connection, zero_rtt) = if early_data_enabled {
             match connecting.into_0rtt() {
-                Ok((new_connection, _)) => new_connection,
-                Err(connecting) => connecting.await?,
+                Ok((new_connection, zero_rtt)) => (new_connection, Some(zero_rtt.shared())),
+                Err(connecting) => (connecting.await?, None),
             }
         } else {
-            connecting.await?
+            (connecting.await?, None)
         };
         let NewConnection {
             connection: quic_connection,
@@ -223,6 +319,7 @@ impl QuicClientStreamBuilder {
             quic_connection,
             name_server_name: Arc::from(dns_name),
             name_server,
+            zero_rtt,
             is_shutdown: false,
         })
     }
@@ -261,6 +358,8 @@ impl Default for QuicClientStreamBuilder {
             crypto_config: client_config,
             transport_config: Arc::new(transport_config),
             bind_addr: None,
+            enable_0rtt: false,
+            session_storage: None,
         }
     }
 }
@@ -5,11 +5,15 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{io, net::SocketAddr, sync::Arc};
+use std::{convert::TryFrom, io, net::SocketAddr, sync::Arc, time::Duration};
 
 use futures_util::StreamExt;
-use quinn::{Endpoint, Incoming, IncomingBiStreams, ServerConfig};
-use rustls::{server::ServerConfig as TlsServerConfig, version::TLS13, Certificate, PrivateKey};
+use quinn::{Endpoint, IdleTimeout, Incoming, IncomingBiStreams, ServerConfig};
+use rustls::{
+    server::{ResolvesServerCert, ServerConfig as TlsServerConfig},
+    version::TLS13,
+    Certificate, PrivateKey,
+};
 
 use crate::{error::ProtoError, udp::UdpSocket};
 
@@ -18,6 +22,26 @@ use super::{
     quic_stream::{self, QuicStream},
 };
 
+/// TLS 1.3 without client authentication, as required by DoQ
+fn tls_config() -> rustls::ConfigBuilder<TlsServerConfig, rustls::server::WantsServerCert> {
+    TlsServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&TLS13])
+        .expect("TLS1.3 not supported")
+        .with_no_client_auth()
+}
+
+/// The transport of connections which are closed after the `idle_timeout` without any activity
+fn idle_transport(idle_timeout: Duration) -> Result<quinn::TransportConfig, ProtoError> {
+    let idle_timeout = IdleTimeout::try_from(idle_timeout)
+        .map_err(|_| ProtoError::from("idle timeout is too large"))?;
+
+    let mut transport = quic_config::transport();
+    transport.max_idle_timeout(Some(idle_timeout));
+    Ok(transport)
+}
+
 /// A DNS-over-QUIC Server, see QuicClientStream for the client counterpart
 pub struct QuicServer {
     endpoint: Endpoint,
@@ -42,18 +66,46 @@ impl QuicServer {
         cert: Vec<Certificate>,
         key: PrivateKey,
     ) -> Result<Self, ProtoError> {
-        let mut config = TlsServerConfig::builder()
-            .with_safe_default_cipher_suites()
-            .with_safe_default_kx_groups()
-            .with_protocol_versions(&[&TLS13])
-            .expect("TLS1.3 not supported")
-            .with_no_client_auth()
-            .with_single_cert(cert, key)?;
+        let config = tls_config().with_single_cert(cert, key)?;
+        Self::with_transport(socket, config, quic_config::transport())
+    }
+
+    /// Construct the new server with an existing socket, connections without any activity for
+    ///  the `idle_timeout` are closed
+    pub fn with_socket_and_idle_timeout(
+        socket: tokio::net::UdpSocket,
+        cert: Vec<Certificate>,
+        key: PrivateKey,
+        idle_timeout: Duration,
+    ) -> Result<Self, ProtoError> {
+        let config = tls_config().with_single_cert(cert, key)?;
+        Self::with_transport(socket, config, idle_transport(idle_timeout)?)
+    }
+
+    /// Construct the new server with an existing socket, with the certificate of each connection
+    ///  from the resolver, connections without any activity for the `idle_timeout` are closed
+    ///
+    /// The resolver may change the certificate while the server is running, e.g. when it's renewed.
+    pub fn with_socket_cert_resolver_and_idle_timeout(
+        socket: tokio::net::UdpSocket,
+        resolver: Arc<dyn ResolvesServerCert>,
+        idle_timeout: Duration,
+    ) -> Result<Self, ProtoError> {
+        let config = tls_config().with_cert_resolver(resolver);
+        Self::with_transport(socket, config, idle_transport(idle_timeout)?)
+    }
 
+    fn with_transport(
+        socket: tokio::net::UdpSocket,
+        mut config: TlsServerConfig,
+        transport: quinn::TransportConfig,
+    ) -> Result<Self, ProtoError> {
         config.alpn_protocols = vec![quic_stream::DOQ_ALPN.to_vec()];
+        // accept 0-RTT early data from resumed sessions, quinn requires the maximum here
+        config.max_early_data_size = u32::MAX;
 
         let mut server_config = ServerConfig::with_crypto(Arc::new(config));
-        server_config.transport = Arc::new(quic_config::transport());
+        server_config.transport = Arc::new(transport);
 
         let socket = socket.into_std()?;
 
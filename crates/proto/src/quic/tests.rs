@@ -29,7 +29,14 @@ async fn server_responder(mut server: QuicServer) {
         println!("received client request {addr}");
 
         while let Some(stream) = conn.next().await {
-            let mut stream = stream.expect("new client stream failed");
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    // the client closed the connection
+                    println!("client connection ended: {}", e);
+                    break;
+                }
+            };
 
             let client_message = stream.receive().await.expect("failed to receive");
 
@@ -42,10 +49,8 @@ async fn server_responder(mut server: QuicServer) {
     }
 }
 
-#[tokio::test]
-async fn test_quic_stream() {
-    let dns_name = "ns.example.com";
-
+/// Starts an echo server, returns its address and a client config trusting it
+async fn start_server() -> (SocketAddr, ClientConfig, tokio::task::JoinHandle<()>) {
     let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
     println!("using server src path: {}", server_path);
 
@@ -89,6 +94,26 @@ async fn test_quic_stream() {
 
     client_config.key_log = Arc::new(KeyLogFile::new());
 
+    (server_addr, client_config, server_join)
+}
+
+fn test_message() -> Message {
+    let mut message = Message::default();
+    message.add_query(Query::query(
+        Name::from_str("www.example.test.").unwrap(),
+        RecordType::AAAA,
+    ));
+
+    // TODO: we should make the finalizer easier to call so this round-trip serialization isn't necessary.
+    let bytes = message.to_vec().unwrap();
+    Message::from_vec(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_quic_stream() {
+    let dns_name = "ns.example.com";
+    let (server_addr, client_config, server_join) = start_server().await;
+
     let mut builder = QuicClientStreamBuilder::default();
     builder.crypto_config(client_config);
 
@@ -101,15 +126,7 @@ async fn test_quic_stream() {
     println!("connected client to server");
 
     // create a test message, send and then receive...
-    let mut message = Message::default();
-    message.add_query(Query::query(
-        Name::from_str("www.example.test.").unwrap(),
-        RecordType::AAAA,
-    ));
-
-    // TODO: we should make the finalizer easier to call so this round-trip serialization isn't necessary.
-    let bytes = message.to_vec().unwrap();
-    let message = Message::from_vec(&bytes).unwrap();
+    let message = test_message();
 
     let response = client_stream
         .send_message(message.clone().into())
@@ -123,3 +140,49 @@ async fn test_quic_stream() {
     // and finally kill the server
     server_join.abort();
 }
+
+#[tokio::test]
+async fn test_quic_0rtt() {
+    let dns_name = "ns.example.com";
+    let (server_addr, client_config, server_join) = start_server().await;
+
+    let mut builder = QuicClientStreamBuilder::default();
+    builder.crypto_config(client_config).enable_0rtt(true);
+
+    let message = test_message();
+
+    // the first connection has no session to resume
+    let mut client_stream = builder
+        .clone()
+        .build(server_addr, dns_name.to_string())
+        .await
+        .expect("failed to connect");
+    assert!(!client_stream.is_0rtt());
+
+    let response = client_stream
+        .send_message(message.clone().into())
+        .next()
+        .await
+        .expect("no response received")
+        .expect("failed to read response");
+    assert_eq!(*response, message);
+    client_stream.shutdown();
+
+    // the second one resumes the session from the shared session storage
+    let mut client_stream = builder
+        .build(server_addr, dns_name.to_string())
+        .await
+        .expect("failed to connect");
+    assert!(client_stream.is_0rtt());
+
+    let response = client_stream
+        .send_message(message.clone().into())
+        .next()
+        .await
+        .expect("no response received")
+        .expect("failed to read response");
+    assert_eq!(*response, message);
+    assert!(client_stream.stats().udp_tx.datagrams > 0);
+
+    server_join.abort();
+}
@@ -38,6 +38,8 @@ pub enum DNSClass {
     /// Special class for OPT Version, it was overloaded for EDNS - RFC 6891
     /// From the RFC: `Values lower than 512 MUST be treated as equal to 512`
     OPT(u16),
+    /// Unknown DNSClass was parsed
+    Unknown(u16),
 }
 
 impl FromStr for DNSClass {
@@ -53,20 +55,26 @@ impl FromStr for DNSClass {
     /// assert_eq!(DNSClass::IN, var);
     /// ```
     fn from_str(str: &str) -> ProtoResult<Self> {
-        debug_assert!(str.chars().all(|x| char::is_ascii_uppercase(&x)));
+        debug_assert!(!str.chars().any(|x| char::is_ascii_lowercase(&x)));
         match str {
             "IN" => Ok(Self::IN),
             "CH" => Ok(Self::CH),
             "HS" => Ok(Self::HS),
             "NONE" => Ok(Self::NONE),
             "ANY" | "*" => Ok(Self::ANY),
-            _ => Err(ProtoErrorKind::UnknownDnsClassStr(str.to_string()).into()),
+            // RFC 3597, the generic CLASS### representation for any class
+            _ => str
+                .strip_prefix("CLASS")
+                .filter(|code| !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|code| u16::from_str(code).ok())
+                .map(Self::from)
+                .ok_or_else(|| ProtoErrorKind::UnknownDnsClassStr(str.to_string()).into()),
         }
     }
 }
 
 impl DNSClass {
-    /// Convert from `u16` to `DNSClass`
+    /// Convert from `u16` to `DNSClass`, unrecognized values are `DNSClass::Unknown`
     ///
     /// ```
     /// use trust_dns_proto::rr::dns_class::DNSClass;
@@ -75,14 +83,7 @@ impl DNSClass {
     /// assert_eq!(DNSClass::IN, var);
     /// ```
     pub fn from_u16(value: u16) -> ProtoResult<Self> {
-        match value {
-            1 => Ok(Self::IN),
-            3 => Ok(Self::CH),
-            4 => Ok(Self::HS),
-            254 => Ok(Self::NONE),
-            255 => Ok(Self::ANY),
-            _ => Err(ProtoErrorKind::UnknownDnsClassValue(value).into()),
-        }
+        Ok(Self::from(value))
     }
 
     /// Return the OPT version from value
@@ -126,6 +127,7 @@ impl From<DNSClass> for &'static str {
             DNSClass::NONE => "NONE",
             DNSClass::ANY => "ANY",
             DNSClass::OPT(_) => "OPT",
+            DNSClass::Unknown(_) => "Unknown",
         }
     }
 }
@@ -148,6 +150,28 @@ impl From<DNSClass> for u16 {
             DNSClass::ANY => 255,
             // see https://tools.ietf.org/html/rfc6891#section-6.1.2
             DNSClass::OPT(max_payload_len) => max_payload_len.max(512),
+            DNSClass::Unknown(unknown) => unknown,
+        }
+    }
+}
+
+/// Convert from `u16` to `DNSClass`
+///
+/// ```
+/// use trust_dns_proto::rr::dns_class::DNSClass;
+///
+/// let var: DNSClass = 3.into();
+/// assert_eq!(DNSClass::CH, var);
+/// ```
+impl From<u16> for DNSClass {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::IN,
+            3 => Self::CH,
+            4 => Self::HS,
+            254 => Self::NONE,
+            255 => Self::ANY,
+            _ => Self::Unknown(value),
         }
     }
 }
@@ -166,7 +190,11 @@ impl Ord for DNSClass {
 
 impl Display for DNSClass {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        f.write_str(Into::<&str>::into(*self))
+        match *self {
+            // RFC 3597, unknown classes are displayed as CLASS###
+            Self::Unknown(code) => write!(f, "CLASS{}", code),
+            _ => f.write_str(Into::<&str>::into(*self)),
+        }
     }
 }
 
@@ -191,3 +219,18 @@ fn test_order() {
 
     assert_eq!(unordered, ordered);
 }
+
+#[test]
+fn test_generic_class() {
+    assert_eq!(
+        DNSClass::from_str("CLASS32").unwrap(),
+        DNSClass::Unknown(32)
+    );
+    assert_eq!(DNSClass::Unknown(32).to_string(), "CLASS32");
+    assert_eq!(DNSClass::from_str("CLASS1").unwrap(), DNSClass::IN);
+    assert_eq!(DNSClass::from_u16(32).unwrap(), DNSClass::Unknown(32));
+    assert_eq!(u16::from(DNSClass::Unknown(32)), 32);
+
+    assert!(DNSClass::from_str("CLASS").is_err());
+    assert!(DNSClass::from_str("CLASS65536").is_err());
+}
@@ -133,6 +133,8 @@ pub enum Algorithm {
     ECDSAP384SHA384,
     /// [draft-ietf-curdle-dnskey-eddsa-03](https://tools.ietf.org/html/draft-ietf-curdle-dnskey-eddsa-03)
     ED25519,
+    /// [rfc8080](https://tools.ietf.org/html/rfc8080)
+    ED448,
     /// An unknown algorithm identifier
     Unknown(u8),
 }
@@ -151,6 +153,7 @@ impl Algorithm {
             13 => Self::ECDSAP256SHA256,
             14 => Self::ECDSAP384SHA384,
             15 => Self::ED25519,
+            16 => Self::ED448,
             _ => Self::Unknown(value),
         }
     }
@@ -162,7 +165,7 @@ impl Algorithm {
             Self::DSA | Self::RSASHA1 | Self::RSASHA1NSEC3SHA1 => Some(20), // 160 bits
             Self::RSASHA256 | Self::ECDSAP256SHA256 | Self::ED25519 => Some(32), // 256 bits
             Self::ECDSAP384SHA384 => Some(48),
-            Self::RSASHA512 => Some(64), // 512 bites
+            Self::RSASHA512 | Self::ED448 => Some(64), // 512 bites
             Self::Unknown(_) => None,
         }
     }
@@ -185,6 +188,7 @@ impl Algorithm {
             Self::ECDSAP256SHA256 => "ECDSAP256SHA256",
             Self::ECDSAP384SHA384 => "ECDSAP384SHA384",
             Self::ED25519 => "ED25519",
+            Self::ED448 => "ED448",
             Self::Unknown(_) => "Unknown",
         }
     }
@@ -223,6 +227,7 @@ impl From<Algorithm> for u8 {
             Algorithm::ECDSAP256SHA256 => 13,
             Algorithm::ECDSAP384SHA384 => 14,
             Algorithm::ED25519 => 15,
+            Algorithm::ED448 => 16,
             Algorithm::Unknown(v) => v,
         }
     }
@@ -246,6 +251,7 @@ fn test_into() {
         Algorithm::ECDSAP256SHA256,
         Algorithm::ECDSAP384SHA384,
         Algorithm::ED25519,
+        Algorithm::ED448,
     ] {
         assert_eq!(*algorithm, Algorithm::from_u8(Into::<u8>::into(*algorithm)))
     }
@@ -263,6 +269,7 @@ fn test_order() {
         Algorithm::ECDSAP256SHA256,
         Algorithm::ECDSAP384SHA384,
         Algorithm::ED25519,
+        Algorithm::ED448,
     ];
 
     algorithms.sort();
@@ -278,6 +285,7 @@ fn test_order() {
             Algorithm::ECDSAP256SHA256,
             Algorithm::ECDSAP384SHA384,
             Algorithm::ED25519,
+            Algorithm::ED448,
         ]
         .iter(),
     ) {
@@ -21,18 +21,25 @@ mod digest_type;
 #[cfg(any(feature = "openssl", feature = "ring"))]
 mod ec_public_key;
 mod nsec3;
+pub mod nsec3_proof;
+mod proof;
 pub mod public_key;
 pub mod rdata;
 #[cfg(any(feature = "openssl", feature = "ring"))]
 mod rsa_public_key;
 mod supported_algorithm;
 pub mod tbs;
+pub mod tkey;
 mod trust_anchor;
 mod verifier;
+#[cfg(any(feature = "openssl", feature = "ring"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "openssl", feature = "ring"))))]
+pub mod zone_digest;
 
 pub use self::algorithm::Algorithm;
 pub use self::digest_type::DigestType;
 pub use self::nsec3::Nsec3HashAlgorithm;
+pub use self::proof::{Proof, ProvenRrset};
 pub use self::public_key::PublicKey;
 pub use self::public_key::PublicKeyBuf;
 pub use self::public_key::PublicKeyEnum;
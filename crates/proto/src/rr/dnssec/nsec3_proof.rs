@@ -0,0 +1,556 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NSEC3 hashed denial of existence, [RFC 5155](https://tools.ietf.org/html/rfc5155)
+//!
+//! The same chain logic is used by validators to check the proofs in a response, and by
+//!  authoritative servers to select the NSEC3 records which make up those proofs.
+
+use data_encoding::BASE32_DNSSEC;
+
+use super::rdata::{DNSSECRData, NSEC3};
+use super::Nsec3HashAlgorithm;
+use crate::error::*;
+use crate::rr::{Name, RData, Record, RecordType};
+
+/// NSEC3 chains with more iterations than this are refused
+///
+/// ```text
+/// RFC 5155                         NSEC3                        March 2008
+///
+/// 10.3.  Iterations
+///
+///    ...
+///
+///    A zone owner MUST NOT use a value higher than shown in the table
+///    below for iterations for the given key size.  A resolver MAY treat a
+///    response with a higher value as insecure, after the validator has
+///    verified that the signature over the NSEC3 RR is correct.
+///
+///             +----------+------------+
+///             | Key Size | Iterations |
+///             +----------+------------+
+///             | 1024     | 150        |
+///             | 2048     | 500        |
+///             | 4096     | 2,500      |
+///             +----------+------------+
+/// ```
+pub const MAX_ITERATIONS: u16 = 2500;
+
+/// Returns the owner name of the NSEC3 record for `hash` in `zone`
+///
+/// ```text
+/// RFC 5155                         NSEC3                        March 2008
+///
+/// 3.  The NSEC3 Resource Record
+///
+///    The owner name for the NSEC3 RR is the base32 encoding of the hashed
+///    owner name prepended as a single label to the name of the zone.
+/// ```
+pub fn hashed_owner_name(hash: &[u8], zone: &Name) -> ProtoResult<Name> {
+    let label = BASE32_DNSSEC.encode(hash);
+    Name::from_labels(Some(label.as_str()))?.append_domain(zone)
+}
+
+/// Decodes the hash in the first label of an NSEC3 owner name
+pub fn decode_hashed_owner_name(owner: &Name) -> ProtoResult<Vec<u8>> {
+    let label = owner
+        .iter()
+        .next()
+        .ok_or_else(|| ProtoError::from("NSEC3 owner name is the root"))?;
+
+    BASE32_DNSSEC
+        .decode(label)
+        .map_err(|e| format!("invalid NSEC3 owner name {}: {}", owner, e).into())
+}
+
+/// Hashes the name, returning the hash as stored in the Next Hashed Owner Name of an NSEC3
+#[cfg(any(feature = "openssl", feature = "ring"))]
+pub fn hash_name(
+    hash_algorithm: Nsec3HashAlgorithm,
+    salt: &[u8],
+    iterations: u16,
+    name: &Name,
+) -> ProtoResult<Vec<u8>> {
+    hash_algorithm
+        .hash(salt, name, iterations)
+        .map(|digest| digest.as_ref().to_vec())
+}
+
+/// This will always error, enable openssl or ring feature at compile time
+#[cfg(not(any(feature = "openssl", feature = "ring")))]
+pub fn hash_name(_: Nsec3HashAlgorithm, _: &[u8], _: u16, _: &Name) -> ProtoResult<Vec<u8>> {
+    Err("The openssl and ring features are both disabled".into())
+}
+
+/// The kind of denial of existence proven by a set of NSEC3 records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nsec3Proof {
+    /// The name exists, but not with the requested type, RFC 5155 Section 8.5
+    NoData,
+    /// The name does not exist, nor a wildcard which could have matched it, RFC 5155 Section 8.4
+    NameError,
+    /// The name does not exist, but the wildcard which matches it does, without the requested
+    ///  type, RFC 5155 Section 8.7
+    WildcardNoData,
+    /// The next closer name is covered by an opt-out NSEC3, an unsigned delegation may exist,
+    ///  RFC 5155 Section 8.6
+    OptOut,
+}
+
+struct Link<'a> {
+    owner: &'a Name,
+    hash: Vec<u8>,
+    nsec3: &'a NSEC3,
+}
+
+impl<'a> Link<'a> {
+    /// True if the hash falls strictly between this owner and the next hashed owner name
+    fn covers(&self, hash: &[u8]) -> bool {
+        let next = self.nsec3.next_hashed_owner_name();
+        if self.hash.as_slice() < next {
+            self.hash.as_slice() < hash && hash < next
+        } else {
+            // the last record in the chain wraps around to the first
+            self.hash.as_slice() < hash || hash < next
+        }
+    }
+
+    fn has_type(&self, record_type: RecordType) -> bool {
+        self.nsec3.type_bit_maps().contains(&record_type)
+    }
+
+    /// True if the type, or a CNAME which would have been followed, is listed at this name
+    fn has_type_or_cname(&self, query_type: RecordType) -> bool {
+        self.has_type(query_type) || self.has_type(RecordType::CNAME)
+    }
+}
+
+/// The NSEC3 records of a single zone, either the entire chain or the subset in a response
+pub struct Nsec3Chain<'a> {
+    zone: Name,
+    hash_algorithm: Nsec3HashAlgorithm,
+    salt: &'a [u8],
+    iterations: u16,
+    links: Vec<Link<'a>>,
+}
+
+impl<'a> Nsec3Chain<'a> {
+    /// Collects the NSEC3 records for `zone`
+    ///
+    /// Records which are not NSEC3, or not owned directly under the zone, are ignored. All the
+    ///  NSEC3 records must share the same hash algorithm, salt and iterations.
+    pub fn new<I>(zone: &Name, records: I) -> ProtoResult<Self>
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let mut links = Vec::new();
+        for record in records {
+            let nsec3 = match record
+                .data()
+                .and_then(RData::as_dnssec)
+                .and_then(DNSSECRData::as_nsec3)
+            {
+                Some(nsec3) => nsec3,
+                None => continue,
+            };
+
+            if record.name().base_name() != *zone {
+                continue;
+            }
+
+            links.push(Link {
+                owner: record.name(),
+                hash: decode_hashed_owner_name(record.name())?,
+                nsec3,
+            });
+        }
+
+        let first = links
+            .first()
+            .map(|link| link.nsec3)
+            .ok_or_else(|| ProtoError::from(format!("no NSEC3 records for zone {}", zone)))?;
+
+        if links.iter().any(|link| {
+            link.nsec3.hash_algorithm() != first.hash_algorithm()
+                || link.nsec3.salt() != first.salt()
+                || link.nsec3.iterations() != first.iterations()
+        }) {
+            return Err(format!("NSEC3 records for {} have differing parameters", zone).into());
+        }
+
+        if first.iterations() > MAX_ITERATIONS {
+            return Err(format!(
+                "NSEC3 iterations exceed maximum {}: {}",
+                MAX_ITERATIONS,
+                first.iterations()
+            )
+            .into());
+        }
+
+        links.sort_by(|a, b| a.hash.cmp(&b.hash));
+        links.dedup_by(|a, b| a.hash == b.hash);
+
+        Ok(Self {
+            zone: zone.clone(),
+            hash_algorithm: first.hash_algorithm(),
+            salt: first.salt(),
+            iterations: first.iterations(),
+            links,
+        })
+    }
+
+    /// The zone of this chain
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// Hashes the name with the parameters of this chain
+    pub fn hash(&self, name: &Name) -> ProtoResult<Vec<u8>> {
+        hash_name(self.hash_algorithm, self.salt, self.iterations, name)
+    }
+
+    fn matching(&self, hash: &[u8]) -> Option<&Link<'a>> {
+        self.links
+            .binary_search_by(|link| link.hash.as_slice().cmp(hash))
+            .ok()
+            .map(|idx| &self.links[idx])
+    }
+
+    fn covering(&self, hash: &[u8]) -> Option<&Link<'a>> {
+        self.links.iter().find(|link| link.covers(hash))
+    }
+
+    /// Verifies that the records prove `query_type` does not exist at `name`
+    pub fn verify(&self, name: &Name, query_type: RecordType) -> ProtoResult<Nsec3Proof> {
+        self.prove(name, Some(query_type)).map(|(proof, _)| proof)
+    }
+
+    /// Returns the owner names of the NSEC3 records which prove that `name` does not exist, or
+    ///  has no data for the type which was not found, for inclusion in a response
+    pub fn proof_owners(&self, name: &Name) -> ProtoResult<(Nsec3Proof, Vec<&'a Name>)> {
+        self.prove(name, None)
+            .map(|(proof, links)| (proof, links.into_iter().map(|l| l.owner).collect()))
+    }
+
+    /// True if the records prove that `name` is an unsigned delegation, i.e. the NSEC3 matching
+    ///  it lists NS but neither DS nor SOA, or the name is covered by an opt-out NSEC3,
+    ///  RFC 5155 Section 8.9
+    pub fn proves_insecure_delegation(&self, name: &Name) -> bool {
+        let hash = match self.hash(name) {
+            Ok(hash) => hash,
+            Err(_) => return false,
+        };
+
+        if let Some(link) = self.matching(&hash) {
+            return link.has_type(RecordType::NS)
+                && !link.has_type(RecordType::DS)
+                && !link.has_type(RecordType::SOA);
+        }
+
+        matches!(self.prove(name, None), Ok((Nsec3Proof::OptOut, _)))
+    }
+
+    /// Builds the proof, if `query_type` is specified, checks that it's not present at the name
+    fn prove(
+        &self,
+        name: &Name,
+        query_type: Option<RecordType>,
+    ) -> ProtoResult<(Nsec3Proof, Vec<&Link<'a>>)> {
+        if !self.zone.zone_of(name) {
+            return Err(format!("{} is not in zone {}", name, self.zone).into());
+        }
+
+        // 8.5: the NSEC3 matching the name must not list the type, nor CNAME
+        if let Some(link) = self.matching(&self.hash(name)?) {
+            if let Some(query_type) = query_type.filter(|t| link.has_type_or_cname(*t)) {
+                return Err(format!("NSEC3 proves {} {} exists", name, query_type).into());
+            }
+
+            return Ok((Nsec3Proof::NoData, vec![link]));
+        }
+
+        let (closest_encloser, encloser, next_closer) = self.closest_encloser_proof(name)?;
+        let mut links = vec![encloser, next_closer];
+
+        // 8.6: an opt-out span may contain unsigned delegations, there is no wildcard to deny
+        if next_closer.nsec3.opt_out() {
+            return Ok((Nsec3Proof::OptOut, links));
+        }
+
+        let wildcard = Name::from_ascii("*")?.append_domain(&closest_encloser)?;
+        let wildcard_hash = self.hash(&wildcard)?;
+
+        // 8.7: the wildcard exists, but doesn't have the type
+        if let Some(link) = self.matching(&wildcard_hash) {
+            if let Some(query_type) = query_type.filter(|t| link.has_type_or_cname(*t)) {
+                return Err(
+                    format!("NSEC3 proves wildcard {} {} exists", wildcard, query_type).into(),
+                );
+            }
+
+            push_unique(&mut links, link);
+            return Ok((Nsec3Proof::WildcardNoData, links));
+        }
+
+        // 8.4: no wildcard at the closest encloser
+        let link = self
+            .covering(&wildcard_hash)
+            .ok_or_else(|| ProtoError::from(format!("no NSEC3 covers wildcard {}", wildcard)))?;
+        push_unique(&mut links, link);
+        Ok((Nsec3Proof::NameError, links))
+    }
+
+    /// Finds the closest encloser of `name`, returning it, the NSEC3 matching it and the NSEC3
+    ///  covering the next closer name
+    ///
+    /// ```text
+    /// RFC 5155                         NSEC3                        March 2008
+    ///
+    /// 8.3.  Closest Encloser Proof
+    ///
+    ///    For some NSEC3 responses, the validator MUST verify the closest
+    ///    encloser proof.  This proof is contained in a set of NSEC3 RRs in
+    ///    the response.
+    ///
+    ///    1.  Set SNAME=QNAME.  Clear the flag.
+    ///
+    ///    2.  Check whether SNAME exists:
+    ///
+    ///        *  If there is no NSEC3 RR in the response that matches SNAME
+    ///           (i.e., an NSEC3 RR whose owner name is the same as the hash of
+    ///           SNAME, prepended as a single label to the zone name), set the
+    ///           flag.
+    ///
+    ///        *  If there is an NSEC3 RR in the response that covers SNAME, set
+    ///           the flag.
+    ///
+    ///        *  If a matching NSEC3 RR has the Type Bit Maps field set to
+    ///           indicate that there is no SOA RR present and that there is an
+    ///           NS RR present, the closest encloser proof is for a delegation
+    ///           point of another zone.  The DNAME bit is treated similarly.
+    ///
+    ///    3.  Truncate SNAME by one label from the left, go to step 2.
+    /// ```
+    fn closest_encloser_proof(&self, name: &Name) -> ProtoResult<(Name, &Link<'a>, &Link<'a>)> {
+        let zone_labels = self.zone.num_labels() as usize;
+        let mut next_closer = name.clone();
+
+        for num_labels in (zone_labels..name.num_labels() as usize).rev() {
+            let candidate = name.trim_to(num_labels);
+
+            if let Some(encloser) = self.matching(&self.hash(&candidate)?) {
+                if encloser.has_type(RecordType::DNAME)
+                    || (encloser.has_type(RecordType::NS) && !encloser.has_type(RecordType::SOA))
+                {
+                    return Err(format!(
+                        "closest encloser {} of {} is a delegation",
+                        candidate, name
+                    )
+                    .into());
+                }
+
+                let covering = self.covering(&self.hash(&next_closer)?).ok_or_else(|| {
+                    ProtoError::from(format!("no NSEC3 covers next closer name {}", next_closer))
+                })?;
+
+                return Ok((candidate, encloser, covering));
+            }
+
+            next_closer = candidate;
+        }
+
+        Err(format!("no closest encloser found for {}", name).into())
+    }
+}
+
+fn push_unique<'c, 'a>(links: &mut Vec<&'c Link<'a>>, link: &'c Link<'a>) {
+    if !links.iter().any(|l| l.hash == link.hash) {
+        links.push(link);
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const SALT: [u8; 4] = [0xAA, 0xBB, 0xCC, 0xDD];
+
+    fn hash(name: &str) -> Vec<u8> {
+        Nsec3HashAlgorithm::SHA1
+            .hash(&SALT, &Name::from_str(name).unwrap(), 12)
+            .unwrap()
+            .as_ref()
+            .to_vec()
+    }
+
+    /// builds the chain for a zone from the names and their types
+    fn zone(names: &[(&str, &[RecordType])], opt_out: bool) -> Vec<Record> {
+        let origin = Name::from_str("example.").unwrap();
+        let mut hashed = names
+            .iter()
+            .map(|(name, types)| (hash(name), types.to_vec()))
+            .collect::<Vec<_>>();
+        hashed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        (0..hashed.len())
+            .map(|i| {
+                let (ref owner, ref types) = hashed[i];
+                let (ref next, _) = hashed[(i + 1) % hashed.len()];
+                let nsec3 = NSEC3::new(
+                    Nsec3HashAlgorithm::SHA1,
+                    opt_out,
+                    12,
+                    SALT.to_vec(),
+                    next.clone(),
+                    types.clone(),
+                );
+
+                Record::from_rdata(
+                    hashed_owner_name(owner, &origin).unwrap(),
+                    3600,
+                    RData::DNSSEC(DNSSECRData::NSEC3(nsec3)),
+                )
+            })
+            .collect()
+    }
+
+    fn example_zone(opt_out: bool) -> Vec<Record> {
+        use RecordType::*;
+
+        zone(
+            &[
+                ("example.", &[SOA, NS, DNSKEY, NSEC3PARAM, RRSIG]),
+                ("a.example.", &[A, RRSIG]),
+                ("b.a.example.", &[TXT, RRSIG]),
+                ("*.w.example.", &[MX, RRSIG]),
+                ("w.example.", &[]),
+            ],
+            opt_out,
+        )
+    }
+
+    #[test]
+    fn test_hashed_owner_name() {
+        let origin = Name::from_str("example.").unwrap();
+        let owner = hashed_owner_name(&hash("example."), &origin).unwrap();
+
+        assert_eq!(
+            owner,
+            Name::from_str("0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example.").unwrap()
+        );
+        assert_eq!(decode_hashed_owner_name(&owner).unwrap(), hash("example."));
+    }
+
+    #[test]
+    fn test_no_data() {
+        let records = example_zone(false);
+        let chain = Nsec3Chain::new(&Name::from_str("example.").unwrap(), &records).unwrap();
+        let name = Name::from_str("a.example.").unwrap();
+
+        assert_eq!(
+            chain.verify(&name, RecordType::MX).unwrap(),
+            Nsec3Proof::NoData
+        );
+        assert!(chain.verify(&name, RecordType::A).is_err());
+
+        // empty non-terminal
+        let name = Name::from_str("w.example.").unwrap();
+        assert_eq!(
+            chain.verify(&name, RecordType::A).unwrap(),
+            Nsec3Proof::NoData
+        );
+    }
+
+    #[test]
+    fn test_name_error() {
+        let records = example_zone(false);
+        let chain = Nsec3Chain::new(&Name::from_str("example.").unwrap(), &records).unwrap();
+        let name = Name::from_str("c.b.a.example.").unwrap();
+
+        let (proof, owners) = chain.proof_owners(&name).unwrap();
+        assert_eq!(proof, Nsec3Proof::NameError);
+        assert!(owners.len() >= 2 && owners.len() <= 3);
+
+        // only the proof should be necessary to validate
+        let proof_records = records
+            .iter()
+            .filter(|r| owners.contains(&r.name()))
+            .collect::<Vec<_>>();
+        let proof_chain =
+            Nsec3Chain::new(&Name::from_str("example.").unwrap(), proof_records).unwrap();
+        assert_eq!(
+            proof_chain.verify(&name, RecordType::A).unwrap(),
+            Nsec3Proof::NameError
+        );
+    }
+
+    #[test]
+    fn test_wildcard_no_data() {
+        let records = example_zone(false);
+        let chain = Nsec3Chain::new(&Name::from_str("example.").unwrap(), &records).unwrap();
+        let name = Name::from_str("x.w.example.").unwrap();
+
+        assert_eq!(
+            chain.verify(&name, RecordType::A).unwrap(),
+            Nsec3Proof::WildcardNoData
+        );
+        assert!(chain.verify(&name, RecordType::MX).is_err());
+    }
+
+    #[test]
+    fn test_opt_out() {
+        let records = example_zone(true);
+        let chain = Nsec3Chain::new(&Name::from_str("example.").unwrap(), &records).unwrap();
+        let name = Name::from_str("unsigned.example.").unwrap();
+
+        assert_eq!(
+            chain.verify(&name, RecordType::DS).unwrap(),
+            Nsec3Proof::OptOut
+        );
+    }
+
+    #[test]
+    fn test_insecure_delegation() {
+        use RecordType::*;
+
+        let records = zone(
+            &[
+                ("example.", &[SOA, NS, DNSKEY, NSEC3PARAM, RRSIG]),
+                ("signed.example.", &[NS, DS, RRSIG]),
+                ("unsigned.example.", &[NS]),
+            ],
+            false,
+        );
+        let chain = Nsec3Chain::new(&Name::from_str("example.").unwrap(), &records).unwrap();
+
+        assert!(chain.proves_insecure_delegation(&Name::from_str("unsigned.example.").unwrap()));
+        assert!(!chain.proves_insecure_delegation(&Name::from_str("signed.example.").unwrap()));
+        assert!(!chain.proves_insecure_delegation(&Name::from_str("example.").unwrap()));
+
+        let records = example_zone(true);
+        let chain = Nsec3Chain::new(&Name::from_str("example.").unwrap(), &records).unwrap();
+        assert!(chain.proves_insecure_delegation(&Name::from_str("unsigned.example.").unwrap()));
+    }
+
+    #[test]
+    fn test_missing_proof() {
+        let records = example_zone(false);
+        let name = Name::from_str("c.b.a.example.").unwrap();
+
+        // only the apex record, which can't cover the next closer name
+        let apex =
+            hashed_owner_name(&hash("example."), &Name::from_str("example.").unwrap()).unwrap();
+        let partial = records.iter().filter(|r| *r.name() == apex);
+        let chain = Nsec3Chain::new(&Name::from_str("example.").unwrap(), partial).unwrap();
+
+        assert!(chain.verify(&name, RecordType::A).is_err());
+    }
+}
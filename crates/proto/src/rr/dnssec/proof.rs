@@ -0,0 +1,188 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The result of the DNSSEC validation of an RRset
+
+use std::fmt;
+
+use crate::rr::{Name, Record, RecordType};
+
+/// The security status of an RRset after validation
+///
+/// ```text
+/// RFC 4035             DNSSEC Protocol Modifications            March 2005
+///
+/// 4.3.  Determining Security Status of Data
+///
+///    A security-aware resolver MUST be able to determine whether it should
+///    expect a particular RRset to be signed.  More precisely, a
+///    security-aware resolver must be able to distinguish between four
+///    cases:
+///
+///    Secure: An RRset for which the resolver is able to build a chain of
+///       signed DNSKEY and DS RRs from a trusted security anchor to the
+///       RRset.  In this case, the RRset should be signed and is subject to
+///       signature validation, as described above.
+///
+///    Insecure: An RRset for which the resolver knows that it has no chain
+///       of signed DNSKEY and DS RRs from any trusted starting point to the
+///       RRset.  This can occur when the target RRset lies in an unsigned
+///       zone or in a descendent of an unsigned zone.  In this case, the
+///       RRset may or may not be signed, but the resolver will not be able
+///       to verify the signature.
+///
+///    Bogus: An RRset for which the resolver believes that it ought to be
+///       able to establish a chain of trust but for which it is unable to do
+///       so, either due to signatures that for some reason fail to validate
+///       or due to missing data that the relevant DNSSEC RRs indicate should
+///       be present.  This case may indicate an attack but may also indicate
+///       a configuration error or some form of data corruption.
+///
+///    Indeterminate: An RRset for which the resolver is not able to
+///       determine whether the RRset should be signed, as the resolver is
+///       not able to obtain the necessary DNSSEC RRs.  This can occur when
+///       the security-aware resolver is not able to contact security-aware
+///       name servers for the relevant zones.
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Proof {
+    /// A chain of trust from a trust anchor to the RRset was validated
+    Secure,
+    /// The RRset is proven to be in an unsigned zone
+    Insecure,
+    /// The RRset should be signed, but the validation failed
+    Bogus,
+    /// The DNSSEC records needed for the validation could not be obtained
+    Indeterminate,
+}
+
+impl Proof {
+    /// True if the RRset is [`Proof::Secure`]
+    pub fn is_secure(&self) -> bool {
+        *self == Self::Secure
+    }
+
+    /// True if the RRset is [`Proof::Bogus`]
+    pub fn is_bogus(&self) -> bool {
+        *self == Self::Bogus
+    }
+
+    /// The weaker of the two proofs, for data which relies on both
+    ///
+    /// Bogus is weaker than Indeterminate, which is weaker than Insecure, which is weaker than
+    ///  Secure.
+    pub fn min(self, other: Self) -> Self {
+        fn strength(proof: Proof) -> u8 {
+            match proof {
+                Proof::Bogus => 0,
+                Proof::Indeterminate => 1,
+                Proof::Insecure => 2,
+                Proof::Secure => 3,
+            }
+        }
+
+        if strength(other) < strength(self) {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Secure => "Secure",
+            Self::Insecure => "Insecure",
+            Self::Bogus => "Bogus",
+            Self::Indeterminate => "Indeterminate",
+        };
+
+        f.write_str(s)
+    }
+}
+
+/// An RRset of a response, with the result of its validation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProvenRrset {
+    name: Name,
+    record_type: RecordType,
+    proof: Proof,
+    records: Vec<Record>,
+    chain: Vec<Record>,
+}
+
+impl ProvenRrset {
+    /// Construct a new proven RRset
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the owner name of the RRset
+    /// * `record_type` - the type of the RRset
+    /// * `proof` - the result of the validation
+    /// * `records` - the records of the RRset, and the RRSIGs covering it
+    /// * `chain` - the DNSKEY and DS records used to validate the RRset
+    pub fn new(
+        name: Name,
+        record_type: RecordType,
+        proof: Proof,
+        records: Vec<Record>,
+        chain: Vec<Record>,
+    ) -> Self {
+        Self {
+            name,
+            record_type,
+            proof,
+            records,
+            chain,
+        }
+    }
+
+    /// The owner name of the RRset
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The type of the RRset
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+
+    /// The result of the validation
+    pub fn proof(&self) -> Proof {
+        self.proof
+    }
+
+    /// The records of the RRset, and the RRSIGs covering it
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// The DNSKEY and DS records which were used to validate the RRset, i.e. the trust anchor,
+    ///  and the keys and DS records of each zone down to the RRset, empty if it's not secure
+    pub fn chain(&self) -> &[Record] {
+        &self.chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_min() {
+        assert_eq!(Proof::Secure.min(Proof::Insecure), Proof::Insecure);
+        assert_eq!(Proof::Insecure.min(Proof::Secure), Proof::Insecure);
+        assert_eq!(
+            Proof::Indeterminate.min(Proof::Insecure),
+            Proof::Indeterminate
+        );
+        assert_eq!(Proof::Secure.min(Proof::Bogus), Proof::Bogus);
+        assert_eq!(Proof::Bogus.min(Proof::Indeterminate), Proof::Bogus);
+        assert_eq!(Proof::Secure.min(Proof::Secure), Proof::Secure);
+    }
+}
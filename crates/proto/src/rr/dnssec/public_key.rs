@@ -17,11 +17,11 @@ use openssl::bn::BigNumContext;
 use openssl::ec::{EcGroup, EcKey, EcPoint};
 #[cfg(all(not(feature = "ring"), feature = "openssl"))]
 use openssl::nid::Nid;
-#[cfg(all(not(feature = "ring"), feature = "openssl"))]
-use openssl::pkey::{PKey, Public};
+#[cfg(feature = "openssl")]
+use openssl::pkey::{Id, PKey, Public};
 #[cfg(all(not(feature = "ring"), feature = "openssl"))]
 use openssl::rsa::Rsa as OpenSslRsa;
-#[cfg(all(not(feature = "ring"), feature = "openssl"))]
+#[cfg(feature = "openssl")]
 use openssl::sign::Verifier;
 #[cfg(feature = "ring")]
 use ring::signature::{self, ED25519_PUBLIC_KEY_LEN};
@@ -318,6 +318,68 @@ impl<'k> PublicKey for Ed25519<'k> {
     }
 }
 
+/// Ed448 Public key
+#[cfg(feature = "openssl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+pub struct Ed448<'k> {
+    raw: &'k [u8],
+    pkey: PKey<Public>,
+}
+
+#[cfg(feature = "openssl")]
+const ED448_PUBLIC_KEY_LEN: usize = 57;
+
+#[cfg(feature = "openssl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+impl<'k> Ed448<'k> {
+    /// ```text
+    /// RFC 8080                    EdDSA for DNSSEC               February 2017
+    ///
+    ///  An Ed448 public key consists of a 57-octet value, which is encoded
+    ///  into the Public Key field of a DNSKEY resource record as a simple bit
+    ///  string.  The generation of a public key is defined in Section 5.2.5
+    ///  of [RFC8032].
+    /// ```
+    pub fn from_public_bytes(public_key: &'k [u8]) -> ProtoResult<Self> {
+        if public_key.len() != ED448_PUBLIC_KEY_LEN {
+            return Err(format!(
+                "expected {} byte public_key: {}",
+                ED448_PUBLIC_KEY_LEN,
+                public_key.len()
+            )
+            .into());
+        }
+
+        let pkey = PKey::public_key_from_raw_bytes(public_key, Id::ED448)?;
+        Ok(Ed448 {
+            raw: public_key,
+            pkey,
+        })
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl<'k> PublicKey for Ed448<'k> {
+    fn public_bytes(&self) -> &[u8] {
+        self.raw
+    }
+
+    fn verify(&self, _: Algorithm, message: &[u8], signature: &[u8]) -> ProtoResult<()> {
+        // EdDSA hashes the message internally, so no digest is configured on the verifier
+        let mut verifier = Verifier::new_without_digest(&self.pkey)?;
+        verifier
+            .verify_oneshot(signature, message)
+            .map_err(Into::into)
+            .and_then(|b| {
+                if b {
+                    Ok(())
+                } else {
+                    Err("could not verify".into())
+                }
+            })
+    }
+}
+
 /// Rsa public key
 #[cfg(any(feature = "openssl", feature = "ring"))]
 #[cfg_attr(docsrs, doc(cfg(any(feature = "openssl", feature = "ring"))))]
@@ -442,6 +504,10 @@ pub enum PublicKeyEnum<'k> {
     #[cfg(feature = "ring")]
     #[cfg_attr(docsrs, doc(cfg(feature = "ring")))]
     Ed25519(Ed25519<'k>),
+    /// Ed448 public key for the Algorithm::ED448
+    #[cfg(feature = "openssl")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+    Ed448(Ed448<'k>),
     /// PhatomData for compiler when ring and or openssl not defined, do not use...
     #[cfg(not(any(feature = "ring", feature = "openssl")))]
     #[cfg_attr(docsrs, doc(cfg(not(any(feature = "ring", feature = "openssl")))))]
@@ -462,6 +528,8 @@ impl<'k> PublicKeyEnum<'k> {
             Algorithm::ED25519 => Ok(PublicKeyEnum::Ed25519(Ed25519::from_public_bytes(
                 public_key,
             )?)),
+            #[cfg(feature = "openssl")]
+            Algorithm::ED448 => Ok(PublicKeyEnum::Ed448(Ed448::from_public_bytes(public_key)?)),
             #[cfg(any(feature = "openssl", feature = "ring"))]
             Algorithm::RSASHA1
             | Algorithm::RSASHA1NSEC3SHA1
@@ -480,6 +548,8 @@ impl<'k> PublicKey for PublicKeyEnum<'k> {
             PublicKeyEnum::Ec(ref ec) => ec.public_bytes(),
             #[cfg(feature = "ring")]
             PublicKeyEnum::Ed25519(ref ed) => ed.public_bytes(),
+            #[cfg(feature = "openssl")]
+            PublicKeyEnum::Ed448(ref ed) => ed.public_bytes(),
             #[cfg(any(feature = "openssl", feature = "ring"))]
             PublicKeyEnum::Rsa(ref rsa) => rsa.public_bytes(),
             #[cfg(not(any(feature = "ring", feature = "openssl")))]
@@ -494,6 +564,8 @@ impl<'k> PublicKey for PublicKeyEnum<'k> {
             PublicKeyEnum::Ec(ref ec) => ec.verify(algorithm, message, signature),
             #[cfg(feature = "ring")]
             PublicKeyEnum::Ed25519(ref ed) => ed.verify(algorithm, message, signature),
+            #[cfg(feature = "openssl")]
+            PublicKeyEnum::Ed448(ref ed) => ed.verify(algorithm, message, signature),
             #[cfg(any(feature = "openssl", feature = "ring"))]
             PublicKeyEnum::Rsa(ref rsa) => rsa.verify(algorithm, message, signature),
             #[cfg(not(any(feature = "ring", feature = "openssl")))]
@@ -657,6 +657,34 @@ impl KEY {
         }
     }
 
+    /// Construct a new KEY RData without the signatory field, which is deprecated by RFC 3007
+    ///
+    /// The signatory bits of the flags are zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_trust` - declare the security level of this key
+    /// * `key_usage` - what type of thing is this key associated to
+    /// * `protocol` - the protocol this key is used with, RFC 3445 only allows `Protocol::DNSSec`
+    /// * `algorithm` - specifies the algorithm which this Key uses to sign records
+    /// * `public_key` - the public key material, in native endian, the emitter will perform any necessary conversion
+    pub fn with_usage(
+        key_trust: KeyTrust,
+        key_usage: KeyUsage,
+        protocol: Protocol,
+        algorithm: Algorithm,
+        public_key: Vec<u8>,
+    ) -> Self {
+        Self::new(
+            key_trust,
+            key_usage,
+            UpdateScope::default(),
+            protocol,
+            algorithm,
+            public_key,
+        )
+    }
+
     /// Returns the trust level of the key
     pub fn key_trust(&self) -> KeyTrust {
         self.key_trust
@@ -718,6 +746,16 @@ impl KEY {
         flags
     }
 
+    /// The key tag calculated as per RFC 4034 Appendix B, see [`DNSKEY::calculate_key_tag`](super::DNSKEY::calculate_key_tag)
+    pub fn calculate_key_tag(&self) -> ProtoResult<u16> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(512);
+        {
+            let mut e = BinEncoder::new(&mut bytes);
+            self::emit(&mut e, self)?;
+        }
+        Ok(super::DNSKEY::calculate_key_tag_internal(&bytes))
+    }
+
     // /// Creates a message digest for this KEY record.
     // ///
     // /// ```text
@@ -934,4 +972,18 @@ mod tests {
         //                        DigestType::SHA256)
         //             .is_ok());
     }
+
+    #[test]
+    fn test_with_usage() {
+        let rdata = KEY::with_usage(
+            KeyTrust::NotPrivate,
+            KeyUsage::Entity,
+            Protocol::DNSSec,
+            Algorithm::RSASHA256,
+            vec![0, 1, 2, 3, 4, 5, 6, 7],
+        );
+
+        assert_eq!(rdata.flags(), 0b0100_0010_0000_0000);
+        assert_eq!(rdata.signatory(), UpdateScope::default());
+    }
 }
@@ -32,6 +32,7 @@ pub mod nsec;
 pub mod nsec3;
 pub mod nsec3param;
 pub mod sig;
+pub mod tkey;
 pub mod tsig;
 
 use enum_as_inner::EnumAsInner;
@@ -50,6 +51,7 @@ pub use self::nsec::NSEC;
 pub use self::nsec3::NSEC3;
 pub use self::nsec3param::NSEC3PARAM;
 pub use self::sig::SIG;
+pub use self::tkey::TKEY;
 pub use self::tsig::TSIG;
 
 /// The type of the resource record, for DNSSEC-specific records.
@@ -419,6 +421,35 @@ pub enum DNSSECRData {
     /// ```
     SIG(SIG),
 
+    /// [RFC 2930, Secret Key Establishment for DNS (TKEY RR)](https://tools.ietf.org/html/rfc2930#section-2)
+    ///
+    /// ```text
+    /// 2. The TKEY Resource Record
+    ///
+    ///    The TKEY resource record (RR) has the structure given below.  Its RR
+    ///    type code is 249.
+    ///
+    ///       Field       Type         Comment
+    ///       -----       ----         -------
+    ///
+    ///       NAME         domain      see description below
+    ///       TTYPE        u_int16_t   TKEY = 249
+    ///       CLASS        u_int16_t   ignored, SHOULD be 255 (ANY)
+    ///       TTL          u_int32_t   ignored, SHOULD be zero
+    ///       RDLEN        u_int16_t   size of RDATA
+    ///       RDATA:
+    ///            Algorithm:   domain
+    ///            Inception:   u_int32_t
+    ///            Expiration:  u_int32_t
+    ///            Mode:        u_int16_t
+    ///            Error:       u_int16_t
+    ///            Key Size:    u_int16_t
+    ///            Key Data:    octet-stream
+    ///            Other Size:  u_int16_t
+    ///            Other Data:  octet-stream  undefined by this specification
+    /// ```
+    TKEY(TKEY),
+
     /// [RFC 8945, Secret Key Transaction Authentication for DNS](https://tools.ietf.org/html/rfc8945#section-4.2)
     ///
     /// ```text
@@ -589,6 +620,10 @@ impl DNSSECRData {
                 trace!("reading SIG");
                 sig::read(decoder, rdata_length).map(Self::SIG)
             }
+            RecordType::TKEY => {
+                trace!("reading TKEY");
+                tkey::read(decoder, rdata_length).map(Self::TKEY)
+            }
             RecordType::TSIG => {
                 trace!("reading TSIG");
                 tsig::read(decoder, rdata_length).map(Self::TSIG)
@@ -620,6 +655,7 @@ impl DNSSECRData {
                 encoder.with_canonical_names(|encoder| nsec3param::emit(encoder, nsec3param))
             }
             Self::SIG(ref sig) => encoder.with_canonical_names(|encoder| sig::emit(encoder, sig)),
+            Self::TKEY(ref tkey) => tkey::emit(encoder, tkey),
             Self::TSIG(ref tsig) => tsig::emit(encoder, tsig),
             Self::Unknown { ref rdata, .. } => {
                 encoder.with_canonical_names(|encoder| null::emit(encoder, rdata))
@@ -638,6 +674,7 @@ impl DNSSECRData {
             Self::NSEC3(..) => RecordType::NSEC3,
             Self::NSEC3PARAM(..) => RecordType::NSEC3PARAM,
             Self::SIG(..) => RecordType::SIG,
+            Self::TKEY(..) => RecordType::TKEY,
             Self::TSIG(..) => RecordType::TSIG,
             Self::Unknown { code, .. } => RecordType::Unknown(code),
         }
@@ -660,6 +697,7 @@ impl fmt::Display for DNSSECRData {
             Self::NSEC3(nsec3) => w(f, nsec3),
             Self::NSEC3PARAM(nsec3param) => w(f, nsec3param),
             Self::SIG(sig) => w(f, sig),
+            Self::TKEY(ref tkey) => w(f, tkey),
             Self::TSIG(ref tsig) => w(f, tsig),
             Self::Unknown { rdata, .. } => w(f, rdata),
         }
@@ -0,0 +1,392 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TKEY for establishing shared secret keys between a client and a server
+#![allow(clippy::use_self)]
+
+use std::convert::TryInto;
+use std::fmt;
+
+#[cfg(feature = "serde-config")]
+use serde::{Deserialize, Serialize};
+
+use crate::rr::rdata::sshfp;
+
+use crate::error::*;
+use crate::rr::dns_class::DNSClass;
+use crate::rr::dnssec::rdata::tsig::TsigAlgorithm;
+use crate::rr::dnssec::rdata::DNSSECRData;
+use crate::rr::record_type::RecordType;
+use crate::rr::{Name, Record};
+use crate::serialize::binary::*;
+
+/// [RFC 2930, Secret Key Establishment for DNS (TKEY RR)](https://tools.ietf.org/html/rfc2930#section-2)
+///
+/// ```text
+/// 2. The TKEY Resource Record
+///
+///    The TKEY resource record (RR) has the structure given below.  Its RR
+///    type code is 249.
+///
+///       Field       Type         Comment
+///       -----       ----         -------
+///
+///       NAME         domain      see description below
+///       TTYPE        u_int16_t   TKEY = 249
+///       CLASS        u_int16_t   ignored, SHOULD be 255 (ANY)
+///       TTL          u_int32_t   ignored, SHOULD be zero
+///       RDLEN        u_int16_t   size of RDATA
+///       RDATA:
+///            Algorithm:   domain
+///            Inception:   u_int32_t
+///            Expiration:  u_int32_t
+///            Mode:        u_int16_t
+///            Error:       u_int16_t
+///            Key Size:    u_int16_t
+///            Key Data:    octet-stream
+///            Other Size:  u_int16_t
+///            Other Data:  octet-stream  undefined by this specification
+///
+/// 2.1 The Name Field
+///
+///    The Name field relates to naming keys.  Its meaning differs somewhat
+///    with mode and context as explained in subsequent sections.
+///
+///    At any DNS server or resolver only one octet string of keying
+///    material may be in place for any particular key name.  An attempt to
+///    establish another set of keying material at a server for an existing
+///    name returns a BADNAME error.
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TKEY {
+    algorithm: TsigAlgorithm,
+    inception: u32,
+    expiration: u32,
+    mode: TkeyMode,
+    error: u16,
+    key: Vec<u8>,
+    other: Vec<u8>,
+}
+
+/// The mode of key establishment used in a TKEY exchange
+///
+/// [RFC 2930, Secret Key Establishment for DNS (TKEY RR)](https://tools.ietf.org/html/rfc2930#section-2.5)
+///
+/// ```text
+/// 2.5 The Mode Field
+///
+///    The mode field specifies the general scheme for key agreement or the
+///    purpose of the TKEY DNS message.  Servers and resolvers supporting
+///    this specification MUST implement the Diffie-Hellman key agreement
+///    mode and the key deletion mode for queries.  All other modes are
+///    OPTIONAL.  A server supporting TKEY that receives a TKEY request with
+///    a mode it does not support returns the BADMODE error.  The following
+///    values of the Mode octet are defined, available, or reserved:
+///
+///          Value    Description
+///          -----    -----------
+///           0        - reserved, see section 7
+///           1       server assignment
+///           2       Diffie-Hellman exchange
+///           3       GSS-API negotiation
+///           4       resolver assignment
+///           5       key deletion
+///          6-65534   - available, see section 7
+///          65535     - reserved, see section 7
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum TkeyMode {
+    /// The server assigns the key, encrypted for the resolver
+    ServerAssignment,
+    /// Diffie-Hellman key agreement
+    DiffieHellman,
+    /// GSS-API negotiation, [RFC 3645](https://tools.ietf.org/html/rfc3645)
+    GssApi,
+    /// The resolver assigns the key, encrypted for the server
+    ResolverAssignment,
+    /// Deletion of a previously established key
+    KeyDeletion,
+    /// Reserved or unassigned mode
+    Unknown(u16),
+}
+
+impl TKEY {
+    /// Constructs a new TKEY
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - the algorithm the established key will be used with, e.g. `gss-tsig`
+    /// * `inception` - start of the validity period of the key, in seconds since the epoch
+    /// * `expiration` - end of the validity period of the key, in seconds since the epoch
+    /// * `mode` - the key establishment scheme
+    /// * `error` - extended RCODE, only meaningful in responses
+    /// * `key` - the keying material, or the GSS-API token in [`TkeyMode::GssApi`]
+    /// * `other` - other data, undefined by RFC 2930
+    pub fn new(
+        algorithm: TsigAlgorithm,
+        inception: u32,
+        expiration: u32,
+        mode: TkeyMode,
+        error: u16,
+        key: Vec<u8>,
+        other: Vec<u8>,
+    ) -> Self {
+        Self {
+            algorithm,
+            inception,
+            expiration,
+            mode,
+            error,
+            key,
+            other,
+        }
+    }
+
+    /// Returns the algorithm the established key is used with
+    pub fn algorithm(&self) -> &TsigAlgorithm {
+        &self.algorithm
+    }
+
+    /// Returns the start of the validity period of the key
+    pub fn inception(&self) -> u32 {
+        self.inception
+    }
+
+    /// Returns the end of the validity period of the key
+    pub fn expiration(&self) -> u32 {
+        self.expiration
+    }
+
+    /// Returns the key establishment scheme
+    pub fn mode(&self) -> TkeyMode {
+        self.mode
+    }
+
+    /// Returns the extended RCODE covering TKEY processing, zero on success
+    pub fn error(&self) -> u16 {
+        self.error
+    }
+
+    /// Returns the keying material, for GSS-API negotiation this is the context token
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the other data
+    pub fn other(&self) -> &[u8] {
+        &self.other
+    }
+}
+
+impl From<u16> for TkeyMode {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => Self::ServerAssignment,
+            2 => Self::DiffieHellman,
+            3 => Self::GssApi,
+            4 => Self::ResolverAssignment,
+            5 => Self::KeyDeletion,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<TkeyMode> for u16 {
+    fn from(mode: TkeyMode) -> Self {
+        match mode {
+            TkeyMode::ServerAssignment => 1,
+            TkeyMode::DiffieHellman => 2,
+            TkeyMode::GssApi => 3,
+            TkeyMode::ResolverAssignment => 4,
+            TkeyMode::KeyDeletion => 5,
+            TkeyMode::Unknown(value) => value,
+        }
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<TKEY> {
+    let end_idx = rdata_length.map(|rdl| rdl as usize)
+        .checked_add(decoder.index())
+        .map_err(|_| ProtoError::from("rdata end position overflow"))? // no legal message is long enough to trigger that
+        .unverified(/*used only as length safely*/);
+
+    let algorithm = TsigAlgorithm::read(decoder)?;
+    let inception = decoder.read_u32()?.unverified(/*valid as any u32*/);
+    let expiration = decoder.read_u32()?.unverified(/*valid as any u32*/);
+    let mode = TkeyMode::from(decoder.read_u16()?.unverified(/*unknown modes are preserved*/));
+    let error = decoder.read_u16()?.unverified(/*valid as any u16*/);
+    let key_size = decoder
+        .read_u16()?
+        .verify_unwrap(|&size| decoder.index() + size as usize + 2 /* other size */ <= end_idx)
+        .map_err(|_| ProtoError::from("invalid key length in TKEY"))?;
+    let key =
+        decoder.read_vec(key_size as usize)?.unverified(/*valid as any vec of the right size*/);
+    let other_size = decoder
+        .read_u16()?
+        .verify_unwrap(|&size| decoder.index() + size as usize == end_idx)
+        .map_err(|_| ProtoError::from("invalid other length in TKEY"))?;
+    let other =
+        decoder.read_vec(other_size as usize)?.unverified(/*valid as any vec of the right size*/);
+
+    Ok(TKEY {
+        algorithm,
+        inception,
+        expiration,
+        mode,
+        error,
+        key,
+        other,
+    })
+}
+
+/// Write the RData from the given Encoder
+pub fn emit(encoder: &mut BinEncoder<'_>, tkey: &TKEY) -> ProtoResult<()> {
+    tkey.algorithm.emit(encoder)?;
+    encoder.emit_u32(tkey.inception)?;
+    encoder.emit_u32(tkey.expiration)?;
+    encoder.emit_u16(tkey.mode.into())?;
+    encoder.emit_u16(tkey.error)?;
+    encoder.emit_u16(
+        tkey.key
+            .len()
+            .try_into()
+            .map_err(|_| ProtoError::from("invalid key, longer than 65535 B in TKEY"))?,
+    )?;
+    encoder.emit_vec(&tkey.key)?;
+    encoder.emit_u16(
+        tkey.other
+            .len()
+            .try_into()
+            .map_err(|_| ProtoError::from("invalid other_buffer, longer than 65535 B in TKEY"))?,
+    )?;
+    encoder.emit_vec(&tkey.other)?;
+    Ok(())
+}
+
+// Does not appear to have a normalized text representation
+impl fmt::Display for TKEY {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{algorithm} {inception} {expiration} {mode} {error} {key} {other}",
+            algorithm = self.algorithm,
+            inception = self.inception,
+            expiration = self.expiration,
+            mode = u16::from(self.mode),
+            error = self.error,
+            key = sshfp::HEX.encode(&self.key),
+            other = sshfp::HEX.encode(&self.other),
+        )
+    }
+}
+
+/// Helper function to make a TKEY record from the name of the key, and the TKEY RData
+pub fn make_tkey_record(name: Name, rdata: TKEY) -> Record {
+    // https://tools.ietf.org/html/rfc2930#section-2
+
+    let mut tkey = Record::new();
+
+    //   NAME:  see description below
+    tkey.set_name(name)
+        //   TTYPE:  TKEY = 249
+        .set_record_type(RecordType::TKEY)
+        //   CLASS:  ignored, SHOULD be 255 (ANY)
+        .set_dns_class(DNSClass::ANY)
+        //   TTL:  ignored, SHOULD be zero
+        .set_ttl(0)
+        .set_data(Some(DNSSECRData::TKEY(rdata).into()));
+    tkey
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    fn test_encode_decode(rdata: TKEY) {
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &rdata).expect("failed to emit tkey");
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(bytes.len() as u16)).expect("failed to read back");
+        assert_eq!(rdata, read_rdata);
+    }
+
+    #[test]
+    fn test_encode_decode_tkey() {
+        test_encode_decode(TKEY::new(
+            TsigAlgorithm::Gss,
+            1609459200,
+            1609545600,
+            TkeyMode::GssApi,
+            0,
+            vec![0x60, 0x82, 0x01, 0x02],
+            vec![],
+        ));
+        test_encode_decode(TKEY::new(
+            TsigAlgorithm::HmacSha256,
+            0,
+            0,
+            TkeyMode::KeyDeletion,
+            17,
+            vec![],
+            vec![0, 1, 2, 3],
+        ));
+        test_encode_decode(TKEY::new(
+            TsigAlgorithm::Unknown(Name::from_ascii("unkown_algorithm").unwrap()),
+            1,
+            2,
+            TkeyMode::Unknown(42),
+            0,
+            vec![9, 8, 7],
+            vec![6, 5],
+        ));
+    }
+
+    #[test]
+    fn test_invalid_key_length() {
+        let bytes = {
+            let mut bytes = Vec::new();
+            let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+            emit(
+                &mut encoder,
+                &TKEY::new(
+                    TsigAlgorithm::Gss,
+                    0,
+                    0,
+                    TkeyMode::GssApi,
+                    0,
+                    vec![1, 2, 3, 4],
+                    vec![],
+                ),
+            )
+            .unwrap();
+            bytes
+        };
+
+        // truncate the other size and the tail of the key
+        let truncated = &bytes[..bytes.len() - 4];
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(truncated);
+        assert!(read(&mut decoder, Restrict::new(truncated.len() as u16)).is_err());
+    }
+
+    #[test]
+    fn test_mode_round_trip() {
+        for value in 0..8_u16 {
+            assert_eq!(u16::from(TkeyMode::from(value)), value);
+        }
+    }
+}
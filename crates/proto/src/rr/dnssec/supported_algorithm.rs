@@ -46,7 +46,7 @@ impl SupportedAlgorithms {
     /// Specify the entire set is supported
     pub fn all() -> Self {
         Self {
-            bit_map: 0b0111_1111,
+            bit_map: 0b1111_1111,
         }
     }
 
@@ -72,6 +72,7 @@ impl SupportedAlgorithms {
             Algorithm::ECDSAP256SHA256 => Some(4),
             Algorithm::ECDSAP384SHA384 => Some(5),
             Algorithm::ED25519 => Some(6),
+            Algorithm::ED448 => Some(7),
             Algorithm::RSAMD5 | Algorithm::DSA | Algorithm::Unknown(_) => None,
         };
 
@@ -89,6 +90,7 @@ impl SupportedAlgorithms {
             4 => Some(Algorithm::ECDSAP256SHA256),
             5 => Some(Algorithm::ECDSAP384SHA384),
             6 => Some(Algorithm::ED25519),
+            7 => Some(Algorithm::ED448),
             _ => None,
         }
     }
@@ -160,7 +162,7 @@ impl<'a> From<&'a [u8]> for SupportedAlgorithms {
 
 impl<'a> From<&'a SupportedAlgorithms> for Vec<u8> {
     fn from(value: &'a SupportedAlgorithms) -> Self {
-        let mut bytes = Self::with_capacity(8); // today this is less than 8
+        let mut bytes = Self::with_capacity(8); // today this is at most 8
 
         for a in value.iter() {
             bytes.push(a.into());
@@ -242,7 +244,7 @@ fn test_has() {
 
 fn test_iterator() {
     let supported = SupportedAlgorithms::all();
-    assert_eq!(supported.iter().count(), 7);
+    assert_eq!(supported.iter().count(), 8);
 
     // it just so happens that the iterator has a fixed order...
     let supported = SupportedAlgorithms::all();
@@ -254,6 +256,7 @@ fn test_iterator() {
     assert_eq!(iter.next(), Some(Algorithm::ECDSAP256SHA256));
     assert_eq!(iter.next(), Some(Algorithm::ECDSAP384SHA384));
     assert_eq!(iter.next(), Some(Algorithm::ED25519));
+    assert_eq!(iter.next(), Some(Algorithm::ED448));
 
     let mut supported = SupportedAlgorithms::new();
     supported.set(Algorithm::RSASHA256);
@@ -278,6 +281,7 @@ fn test_vec() {
     supported.set(Algorithm::ECDSAP256SHA256);
     supported.set(Algorithm::ECDSAP384SHA384);
     supported.set(Algorithm::ED25519);
+    supported.set(Algorithm::ED448);
     let array: Vec<u8> = (&supported).into();
     let decoded: SupportedAlgorithms = (&array as &[_]).into();
 
@@ -288,4 +292,5 @@ fn test_vec() {
     assert!(supported.has(Algorithm::ECDSAP256SHA256));
     assert!(supported.has(Algorithm::ECDSAP384SHA384));
     assert!(supported.has(Algorithm::ED25519));
+    assert!(supported.has(Algorithm::ED448));
 }
@@ -0,0 +1,564 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Key establishment exchanges with TKEY, [RFC 2930](https://tools.ietf.org/html/rfc2930)
+//!
+//! A TKEY exchange is a query of type TKEY for the name of the key, carrying a TKEY record in the
+//!  additional section, to which the server responds with a TKEY record in the answer section.
+//!  Some modes, like GSS-API negotiation, chain several such exchanges until the key is
+//!  established.
+
+use std::convert::TryFrom;
+
+#[cfg(feature = "openssl")]
+use openssl::{bn::BigNum, dh::Dh, hash::MessageDigest, pkey::Private};
+
+use crate::error::*;
+use crate::op::{Message, MessageType, OpCode, Query, ResponseCode};
+#[cfg(feature = "openssl")]
+use crate::rr::dnssec::rdata::key::{KeyTrust, KeyUsage, Protocol};
+use crate::rr::dnssec::rdata::tkey::{make_tkey_record, TkeyMode, TKEY};
+use crate::rr::dnssec::rdata::tsig::TsigAlgorithm;
+use crate::rr::dnssec::rdata::DNSSECRData;
+#[cfg(feature = "openssl")]
+use crate::rr::dnssec::rdata::KEY;
+#[cfg(feature = "openssl")]
+use crate::rr::dnssec::Algorithm;
+use crate::rr::{DNSClass, Name, RData, Record, RecordType};
+
+/// The KEY algorithm number of Diffie-Hellman keys, [RFC 2539](https://tools.ietf.org/html/rfc2539)
+pub const DH_ALGORITHM: u8 = 2;
+
+/// Length of the requester nonce sent in Diffie-Hellman exchanges
+#[cfg(feature = "openssl")]
+const DH_NONCE_LEN: usize = 16;
+
+/// Build a TKEY query
+///
+/// [RFC 2930, Secret Key Establishment for DNS (TKEY RR)](https://tools.ietf.org/html/rfc2930#section-4)
+///
+/// ```text
+/// 4. Exchange via Resolver Query
+///
+///    One method for a resolver and a server to agree about shared secret
+///    keying material for use in TSIG is through DNS requests from the
+///    resolver which are syntactically DNS queries for type TKEY.  Such
+///    queries MUST be accompanied by a TKEY RR in the additional
+///    information section to indicate the mode in use and accompanied by
+///    other information where required.
+///
+///    Type TKEY queries SHOULD NOT be flagged as recursive and servers MAY
+///    ignore the recursive header bit in TKEY queries they receive.
+/// ```
+///
+/// # Arguments
+///
+/// * `key_name` - the name of the key, used as the query name and owner of the TKEY
+/// * `tkey` - the TKEY RData describing the requested key
+/// * `additionals` - other records required by the mode, e.g. the Diffie-Hellman KEY
+pub fn tkey_query(key_name: Name, tkey: TKEY, additionals: Vec<Record>) -> Message {
+    let mut query = Query::query(key_name.clone(), RecordType::TKEY);
+    query.set_query_class(DNSClass::ANY);
+
+    let mut message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false)
+        .add_query(query)
+        .add_additional(make_tkey_record(key_name, tkey))
+        .add_additionals(additionals);
+    message
+}
+
+/// Build a query deleting a previously established key
+///
+/// [RFC 2930, Secret Key Establishment for DNS (TKEY RR)](https://tools.ietf.org/html/rfc2930#section-4.2)
+///
+/// ```text
+/// 4.2 Query for Key Deletion
+///
+///    Keying material may be deleted by a query with a TKEY RR in the
+///    additional information section specifying mode = 5 (key deletion)
+///    and giving the name and algorithm of the key to be deleted.
+///
+///    The request SHOULD be authenticated with the key being deleted.
+/// ```
+pub fn key_deletion_query(key_name: Name, algorithm: TsigAlgorithm) -> Message {
+    tkey_query(
+        key_name,
+        TKEY::new(
+            algorithm,
+            0,
+            0,
+            TkeyMode::KeyDeletion,
+            0,
+            Vec::new(),
+            Vec::new(),
+        ),
+        Vec::new(),
+    )
+}
+
+/// Extract the TKEY of the answer to a TKEY query
+///
+/// Returns an error if the server refused the request, or if the TKEY is not of the expected mode.
+pub fn tkey_response<'a>(
+    response: &'a Message,
+    key_name: &Name,
+    mode: TkeyMode,
+) -> ProtoResult<&'a TKEY> {
+    if response.response_code() != ResponseCode::NoError {
+        return Err(format!(
+            "TKEY request for {} failed: {}",
+            key_name,
+            response.response_code()
+        )
+        .into());
+    }
+
+    let tkey = response
+        .answers()
+        .iter()
+        .filter(|r| r.name() == key_name)
+        .find_map(|r| match r.data() {
+            Some(RData::DNSSEC(DNSSECRData::TKEY(tkey))) => Some(tkey),
+            _ => None,
+        })
+        .ok_or_else(|| ProtoError::from(format!("no TKEY for {} in response", key_name)))?;
+
+    if tkey.error() != 0 {
+        return Err(format!(
+            "TKEY request for {} failed: {}",
+            key_name,
+            ResponseCode::from_low(tkey.error() as u8)
+        )
+        .into());
+    }
+
+    if tkey.mode() != mode {
+        return Err(format!(
+            "unexpected TKEY mode for {}: {}",
+            key_name,
+            u16::from(tkey.mode())
+        )
+        .into());
+    }
+
+    Ok(tkey)
+}
+
+/// A Diffie-Hellman public key, as stored in the public key field of a KEY record
+///
+/// [RFC 2539, Storage of Diffie-Hellman Keys in the DNS](https://tools.ietf.org/html/rfc2539#section-2)
+///
+/// ```text
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |           KEY flags           |    protocol   |  algorithm=2  |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |     prime length (or flag)    |            prime (p)          .
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    .                 prime (p) (variable length)                   |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |       generator length        |        generator (g)          |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |    public value length        |      public value (y)         |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// The well-known prime forms, with a prime length of 1 or 2, are not supported.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DhPublicKey {
+    prime: Vec<u8>,
+    generator: Vec<u8>,
+    public_value: Vec<u8>,
+}
+
+impl DhPublicKey {
+    /// Construct a new public key from its big endian components
+    pub fn new(prime: Vec<u8>, generator: Vec<u8>, public_value: Vec<u8>) -> Self {
+        Self {
+            prime,
+            generator,
+            public_value,
+        }
+    }
+
+    /// Read the public key from the public key field of a KEY record
+    pub fn from_bytes(bytes: &[u8]) -> ProtoResult<Self> {
+        fn field<'a>(bytes: &mut &'a [u8]) -> ProtoResult<&'a [u8]> {
+            if bytes.len() < 2 {
+                return Err("truncated Diffie-Hellman public key".into());
+            }
+            let len = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+            if bytes.len() < 2 + len {
+                return Err("truncated Diffie-Hellman public key".into());
+            }
+            let (value, rest) = bytes[2..].split_at(len);
+            *bytes = rest;
+            Ok(value)
+        }
+
+        let mut bytes = bytes;
+        let prime = field(&mut bytes)?;
+        if prime.len() < 16 {
+            return Err("well-known Diffie-Hellman primes are not supported".into());
+        }
+        let generator = field(&mut bytes)?;
+        let public_value = field(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err("trailing data after Diffie-Hellman public key".into());
+        }
+
+        Ok(Self::new(
+            prime.to_vec(),
+            generator.to_vec(),
+            public_value.to_vec(),
+        ))
+    }
+
+    /// Write the public key as expected in the public key field of a KEY record
+    pub fn to_bytes(&self) -> ProtoResult<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(
+            6 + self.prime.len() + self.generator.len() + self.public_value.len(),
+        );
+        for value in &[&self.prime, &self.generator, &self.public_value] {
+            let len = u16::try_from(value.len())
+                .map_err(|_| ProtoError::from("Diffie-Hellman value longer than 65535 B"))?;
+            bytes.extend_from_slice(&len.to_be_bytes());
+            bytes.extend_from_slice(value);
+        }
+        Ok(bytes)
+    }
+
+    /// The prime, p
+    pub fn prime(&self) -> &[u8] {
+        &self.prime
+    }
+
+    /// The generator, g
+    pub fn generator(&self) -> &[u8] {
+        &self.generator
+    }
+
+    /// The public value, y
+    pub fn public_value(&self) -> &[u8] {
+        &self.public_value
+    }
+}
+
+/// Derive the keying material of a Diffie-Hellman exchange
+///
+/// [RFC 2930, Secret Key Establishment for DNS (TKEY RR)](https://tools.ietf.org/html/rfc2930#section-4.1)
+///
+/// ```text
+///       keying material =
+///            XOR ( DH value, MD5 ( query data | DH value ) |
+///                            MD5 ( server data | DH value ) )
+///
+///    Where XOR is an exclusive-OR operation and "|" is byte-stream
+///    concatenation.  The shorter of the two operands to XOR should be
+///    byte-wise left justified and padded with zero-valued bytes to match
+///    the length of the other operand.  "DH value" is the Diffie-Hellman
+///    value derived from the KEY RRs.  Query data and server data are the
+///    values sent in the TKEY RR data fields.
+/// ```
+#[cfg(feature = "openssl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+pub fn dh_keying_material(
+    dh_value: &[u8],
+    query_data: &[u8],
+    server_data: &[u8],
+) -> ProtoResult<Vec<u8>> {
+    use openssl::hash::hash;
+
+    let mut digests = Vec::with_capacity(32);
+    for data in &[query_data, server_data] {
+        let mut buf = Vec::with_capacity(data.len() + dh_value.len());
+        buf.extend_from_slice(data);
+        buf.extend_from_slice(dh_value);
+        digests.extend_from_slice(&hash(MessageDigest::md5(), &buf)?);
+    }
+
+    let len = std::cmp::max(dh_value.len(), digests.len());
+    Ok((0..len)
+        .map(|i| dh_value.get(i).unwrap_or(&0) ^ digests.get(i).unwrap_or(&0))
+        .collect())
+}
+
+/// The requester side of a Diffie-Hellman TKEY exchange
+///
+/// [RFC 2930, Secret Key Establishment for DNS (TKEY RR)](https://tools.ietf.org/html/rfc2930#section-4.1)
+///
+/// ```text
+/// 4.1 Query for Diffie-Hellman Exchanged Keying
+///
+///    The query for a Diffie-Hellman exchange, mode 2, MUST contain the
+///    requester's Diffie-Hellman KEY RR in the additional information
+///    section, and the TKEY RR key data field contains a nonce. The reply
+///    contains the server's Diffie-Hellman KEY RR in the answer section,
+///    along with the TKEY RR, whose key data field contains a nonce.
+/// ```
+#[cfg(feature = "openssl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+pub struct DhKeyExchange {
+    key_name: Name,
+    algorithm: TsigAlgorithm,
+    nonce: Vec<u8>,
+    dh: Dh<Private>,
+}
+
+#[cfg(feature = "openssl")]
+#[cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
+impl DhKeyExchange {
+    /// Generate a new Diffie-Hellman key pair, in the 2048-bit MODP group of RFC 5114
+    ///
+    /// # Arguments
+    ///
+    /// * `key_name` - the name of the key to establish
+    /// * `algorithm` - the TSIG algorithm the key will be used with
+    pub fn new(key_name: Name, algorithm: TsigAlgorithm) -> ProtoResult<Self> {
+        let dh = Dh::get_2048_256()?.generate_key()?;
+        let nonce = (0..DH_NONCE_LEN).map(|_| rand::random()).collect();
+
+        Ok(Self {
+            key_name,
+            algorithm,
+            nonce,
+            dh,
+        })
+    }
+
+    /// The name of the key being established
+    pub fn key_name(&self) -> &Name {
+        &self.key_name
+    }
+
+    /// The public key of the requester
+    pub fn public_key(&self) -> DhPublicKey {
+        DhPublicKey::new(
+            self.dh.prime_p().to_vec(),
+            self.dh.generator().to_vec(),
+            self.dh.public_key().to_vec(),
+        )
+    }
+
+    /// Build the query to send to the server
+    ///
+    /// # Arguments
+    ///
+    /// * `inception` - requested start of the validity period of the key
+    /// * `expiration` - requested end of the validity period of the key
+    pub fn query(&self, inception: u32, expiration: u32) -> ProtoResult<Message> {
+        let key = KEY::with_usage(
+            KeyTrust::default(),
+            KeyUsage::Host,
+            Protocol::DNSSec,
+            Algorithm::from_u8(DH_ALGORITHM),
+            self.public_key().to_bytes()?,
+        );
+        let key = Record::from_rdata(
+            self.key_name.clone(),
+            0,
+            RData::DNSSEC(DNSSECRData::KEY(key)),
+        );
+
+        let tkey = TKEY::new(
+            self.algorithm.clone(),
+            inception,
+            expiration,
+            TkeyMode::DiffieHellman,
+            0,
+            self.nonce.clone(),
+            Vec::new(),
+        );
+
+        Ok(tkey_query(self.key_name.clone(), tkey, vec![key]))
+    }
+
+    /// Compute the shared secret from the server response
+    pub fn shared_secret(&self, response: &Message) -> ProtoResult<Vec<u8>> {
+        let tkey = tkey_response(response, &self.key_name, TkeyMode::DiffieHellman)?;
+        if tkey.algorithm() != &self.algorithm {
+            return Err(format!(
+                "server changed the algorithm of {} to {}",
+                self.key_name,
+                tkey.algorithm()
+            )
+            .into());
+        }
+
+        let server_key = response
+            .answers()
+            .iter()
+            .find_map(|r| match r.data() {
+                Some(RData::DNSSEC(DNSSECRData::KEY(key)))
+                    if key.algorithm() == Algorithm::from_u8(DH_ALGORITHM) =>
+                {
+                    Some(key)
+                }
+                _ => None,
+            })
+            .ok_or_else(|| ProtoError::from("no Diffie-Hellman KEY in TKEY response"))?;
+        let server_key = DhPublicKey::from_bytes(server_key.public_key())?;
+
+        if server_key.prime() != self.dh.prime_p().to_vec().as_slice()
+            || server_key.generator() != self.dh.generator().to_vec().as_slice()
+        {
+            return Err("server Diffie-Hellman KEY is not in the requested group".into());
+        }
+
+        let server_value = BigNum::from_slice(server_key.public_value())?;
+        let dh_value = self.dh.compute_key(&server_value)?;
+        dh_keying_material(&dh_value, &self.nonce, tkey.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dh_public_key_round_trip() {
+        let key = DhPublicKey::new(vec![0xFF; 64], vec![2], vec![0x55; 64]);
+        let bytes = key.to_bytes().unwrap();
+        assert_eq!(DhPublicKey::from_bytes(&bytes).unwrap(), key);
+
+        assert!(DhPublicKey::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(DhPublicKey::from_bytes(&[0, 1, 2, 0, 1, 2, 0, 1, 9]).is_err());
+    }
+
+    #[test]
+    fn test_key_deletion_query() {
+        let key_name = Name::from_ascii("key.example.com.").unwrap();
+        let message = key_deletion_query(key_name.clone(), TsigAlgorithm::HmacSha256);
+
+        assert_eq!(message.queries()[0].name(), &key_name);
+        assert_eq!(message.queries()[0].query_type(), RecordType::TKEY);
+        match message.additionals()[0].data() {
+            Some(RData::DNSSEC(DNSSECRData::TKEY(tkey))) => {
+                assert_eq!(tkey.mode(), TkeyMode::KeyDeletion);
+                assert_eq!(tkey.algorithm(), &TsigAlgorithm::HmacSha256);
+            }
+            _ => panic!("expected a TKEY"),
+        }
+    }
+
+    #[test]
+    fn test_tkey_response() {
+        let key_name = Name::from_ascii("key.example.com.").unwrap();
+        let response_with = |mode, error| {
+            let mut response = Message::new();
+            response.set_message_type(MessageType::Response);
+            response.add_answer(make_tkey_record(
+                key_name.clone(),
+                TKEY::new(TsigAlgorithm::Gss, 0, 0, mode, error, vec![1], vec![]),
+            ));
+            response
+        };
+
+        let response = response_with(TkeyMode::GssApi, 0);
+        assert_eq!(
+            tkey_response(&response, &key_name, TkeyMode::GssApi)
+                .unwrap()
+                .key(),
+            &[1]
+        );
+        assert!(tkey_response(&response, &key_name, TkeyMode::DiffieHellman).is_err());
+
+        let response = response_with(TkeyMode::GssApi, 17);
+        assert!(tkey_response(&response, &key_name, TkeyMode::GssApi).is_err());
+
+        let other_name = Name::from_ascii("other.example.com.").unwrap();
+        let response = response_with(TkeyMode::GssApi, 0);
+        assert!(tkey_response(&response, &other_name, TkeyMode::GssApi).is_err());
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_dh_exchange() {
+        let key_name = Name::from_ascii("key.example.com.").unwrap();
+        let requester = DhKeyExchange::new(key_name.clone(), TsigAlgorithm::HmacSha256).unwrap();
+        let query = requester.query(1609459200, 1609545600).unwrap();
+
+        // server side, from the requester KEY
+        let (requester_key, requester_nonce) = {
+            let key = query
+                .additionals()
+                .iter()
+                .find_map(|r| match r.data() {
+                    Some(RData::DNSSEC(DNSSECRData::KEY(key))) => Some(key),
+                    _ => None,
+                })
+                .unwrap();
+            let nonce = match query.additionals()[0].data() {
+                Some(RData::DNSSEC(DNSSECRData::TKEY(tkey))) => tkey.key().to_vec(),
+                _ => panic!("expected a TKEY"),
+            };
+            (DhPublicKey::from_bytes(key.public_key()).unwrap(), nonce)
+        };
+
+        let server = Dh::from_pqg(
+            BigNum::from_slice(requester_key.prime()).unwrap(),
+            None,
+            BigNum::from_slice(requester_key.generator()).unwrap(),
+        )
+        .unwrap()
+        .generate_key()
+        .unwrap();
+        let server_nonce = vec![7; 16];
+        let server_secret = dh_keying_material(
+            &server
+                .compute_key(&BigNum::from_slice(requester_key.public_value()).unwrap())
+                .unwrap(),
+            &requester_nonce,
+            &server_nonce,
+        )
+        .unwrap();
+
+        let mut response = Message::new();
+        response
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .add_answer(make_tkey_record(
+                key_name.clone(),
+                TKEY::new(
+                    TsigAlgorithm::HmacSha256,
+                    1609459200,
+                    1609545600,
+                    TkeyMode::DiffieHellman,
+                    0,
+                    server_nonce,
+                    vec![],
+                ),
+            ))
+            .add_answer(Record::from_rdata(
+                key_name,
+                0,
+                RData::DNSSEC(DNSSECRData::KEY(KEY::with_usage(
+                    KeyTrust::default(),
+                    KeyUsage::Host,
+                    Protocol::DNSSec,
+                    Algorithm::from_u8(DH_ALGORITHM),
+                    DhPublicKey::new(
+                        server.prime_p().to_vec(),
+                        server.generator().to_vec(),
+                        server.public_key().to_vec(),
+                    )
+                    .to_bytes()
+                    .unwrap(),
+                ))),
+            ));
+
+        let requester_secret = requester.shared_secret(&response).unwrap();
+        assert_eq!(requester_secret, server_secret);
+        assert_eq!(requester_secret.len(), 256);
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zone digests, [RFC 8976](https://tools.ietf.org/html/rfc8976), Message Digest for DNS Zones
+//!
+//! The digest is computed over the records of a full zone, e.g. after an AXFR, with the SIMPLE
+//!  scheme.
+
+use super::rdata::DNSSECRData;
+use super::DigestType;
+use crate::error::*;
+use crate::rr::rdata::zonemd::{HashAlgorithm, Scheme};
+use crate::rr::rdata::ZONEMD;
+use crate::rr::{Name, RData, Record, RecordType};
+use crate::serialize::binary::{BinEncodable, BinEncoder};
+
+/// Computes the digest of the zone with the SIMPLE scheme
+///
+/// ```text
+/// 3.3.1.  The SIMPLE Scheme
+///
+///    For the SIMPLE scheme, the digest is calculated over the zone as a
+///    whole.  This means that a change to a single RR in the zone will
+///    result in a new digest.
+///
+/// 3.3.1.1.  SIMPLE Scheme Inclusion/Exclusion Rules
+///
+///    When iterating over records in the zone, the following inclusion/
+///    exclusion rules apply:
+///
+///    *  All records in the zone, including glue records, MUST be included
+///       unless excluded by a subsequent rule.
+///
+///    *  Occluded data ([RFC5936], Section 3.5) MUST be included.
+///
+///    *  If there are duplicate RRs with equal owner, class, type, and
+///       RDATA, only one instance is included ([RFC4034], Section 6.3) and
+///       the duplicates MUST be omitted.
+///
+///    *  The placeholder apex ZONEMD RR(s) MUST NOT be included.
+///
+///    *  If the zone is signed, DNSSEC RRs MUST be included, except:
+///
+///    *  The RRSIG covering the apex ZONEMD RRset MUST NOT be included
+///       because the RRSIG will be updated after all digests have been
+///       calculated.
+///
+/// 3.3.1.2.  SIMPLE Scheme Digest Calculation
+///
+///    A zone digest using the SIMPLE scheme is calculated by concatenating
+///    all RRs in the zone, in the format given by the scheme, and then
+///    applying the hash algorithm:
+///
+///        digest = hash( RR(1) | RR(2) | RR(3) | ... )
+///
+///    where "|" denotes concatenation.
+///
+///    The RRs in the zone are in the order described in Section 3.3.
+/// ```
+///
+/// # Arguments
+///
+/// * `origin` - the apex of the zone
+/// * `records` - all the records of the zone, records not in the zone are ignored
+/// * `hash_algorithm` - SHA384 or SHA512, other algorithms are an error
+pub fn digest(
+    origin: &Name,
+    records: &[Record],
+    hash_algorithm: HashAlgorithm,
+) -> ProtoResult<Vec<u8>> {
+    let digest_type = digest_type(hash_algorithm)?;
+
+    // (owner, type, rdata) in canonical form, for the ordering, and the canonical RR
+    let mut canonical = Vec::with_capacity(records.len());
+    for record in records {
+        if !origin.zone_of(record.name()) || is_apex_zonemd(origin, record) {
+            continue;
+        }
+
+        let mut rdata = Vec::new();
+        if let Some(data) = record.data() {
+            let mut encoder = BinEncoder::new(&mut rdata);
+            encoder.set_canonical_names(true);
+            data.emit(&mut encoder)?;
+        }
+
+        canonical.push((
+            record.name().to_lowercase(),
+            record.record_type(),
+            rdata,
+            record,
+        ));
+    }
+
+    // RFC 4034 section 6, canonical order of the owner names, then the types and the RDATA
+    canonical.sort_by(
+        |(name_a, type_a, rdata_a, _), (name_b, type_b, rdata_b, _)| {
+            name_a
+                .cmp(name_b)
+                .then_with(|| type_a.cmp(type_b))
+                .then_with(|| rdata_a.cmp(rdata_b))
+        },
+    );
+    canonical.dedup_by(
+        |(name_a, type_a, rdata_a, _), (name_b, type_b, rdata_b, _)| {
+            name_a == name_b && type_a == type_b && rdata_a == rdata_b
+        },
+    );
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut buf);
+        encoder.set_canonical_names(true);
+        for (name, record_type, rdata, record) in &canonical {
+            name.emit(&mut encoder)?;
+            record_type.emit(&mut encoder)?;
+            record.dns_class().emit(&mut encoder)?;
+            encoder.emit_u32(record.ttl())?;
+            encoder.emit_u16(rdata.len() as u16)?;
+            encoder.emit_vec(rdata)?;
+        }
+    }
+
+    digest_type
+        .digest_all(&[buf.as_slice()])
+        .map(|digest| digest.as_ref().to_vec())
+}
+
+/// Creates the ZONEMD record data for the zone, with the SIMPLE scheme
+///
+/// # Arguments
+///
+/// * `origin` - the apex of the zone
+/// * `records` - all the records of the zone, including the SOA record of the apex
+/// * `hash_algorithm` - SHA384 or SHA512, other algorithms are an error
+pub fn zonemd(
+    origin: &Name,
+    records: &[Record],
+    hash_algorithm: HashAlgorithm,
+) -> ProtoResult<ZONEMD> {
+    let serial = soa_serial(origin, records)?;
+    let digest = digest(origin, records, hash_algorithm)?;
+
+    Ok(ZONEMD::new(serial, Scheme::Simple, hash_algorithm, digest))
+}
+
+/// Verifies the zone against the ZONEMD records at its apex
+///
+/// ```text
+/// 4.  Verifying Zone Digest
+///
+///    ...
+///
+///    3.  Verify that the zone is complete and that the SOA serial and
+///        the ZONEMD serial match.
+///
+///    4.  If the apex ZONEMD RRset contains more than one RR with the same
+///        Scheme and Hash Algorithm, verification of those RRs fails.
+///
+///    5.  Otherwise, for each RR in the ZONEMD RRset with a supported Scheme
+///        and Hash Algorithm, compute the digest and compare it with the
+///        Digest field.  If any of them match, verification succeeds.
+/// ```
+///
+/// # Arguments
+///
+/// * `origin` - the apex of the zone
+/// * `records` - all the records of the zone, e.g. as received in an AXFR
+///
+/// # Returns
+///
+/// `Ok(())` if one of the ZONEMD records matches the digest of the zone, an error otherwise,
+///  including when there is no ZONEMD record with a supported scheme and hash algorithm.
+pub fn verify(origin: &Name, records: &[Record]) -> ProtoResult<()> {
+    let serial = soa_serial(origin, records)?;
+    let zonemds = records
+        .iter()
+        .filter(|record| is_apex_zonemd(origin, record))
+        .filter_map(|record| match record.data() {
+            Some(RData::ZONEMD(zonemd)) => Some(zonemd),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if zonemds.is_empty() {
+        return Err(format!("no ZONEMD records at the apex of {}", origin).into());
+    }
+
+    for zonemd in &zonemds {
+        if zonemd.scheme() != Scheme::Simple || digest_type(zonemd.hash_algorithm()).is_err() {
+            continue;
+        }
+
+        if zonemd.serial() != serial {
+            continue;
+        }
+
+        let duplicates = zonemds
+            .iter()
+            .filter(|other| {
+                other.scheme() == zonemd.scheme()
+                    && other.hash_algorithm() == zonemd.hash_algorithm()
+            })
+            .count();
+        if duplicates > 1 {
+            continue;
+        }
+
+        if zonemd.hash_algorithm().digest_len() != Some(zonemd.digest().len()) {
+            continue;
+        }
+
+        if digest(origin, records, zonemd.hash_algorithm())? == zonemd.digest() {
+            return Ok(());
+        }
+    }
+
+    Err(format!("zone digest of {} could not be verified", origin).into())
+}
+
+fn digest_type(hash_algorithm: HashAlgorithm) -> ProtoResult<DigestType> {
+    match hash_algorithm {
+        HashAlgorithm::SHA384 => Ok(DigestType::SHA384),
+        HashAlgorithm::SHA512 => Ok(DigestType::SHA512),
+        _ => Err(format!("unsupported ZONEMD hash algorithm: {:?}", hash_algorithm).into()),
+    }
+}
+
+fn soa_serial(origin: &Name, records: &[Record]) -> ProtoResult<u32> {
+    records
+        .iter()
+        .filter(|record| record.name() == origin)
+        .find_map(|record| match record.data() {
+            Some(RData::SOA(soa)) => Some(soa.serial()),
+            _ => None,
+        })
+        .ok_or_else(|| format!("no SOA record at the apex of {}", origin).into())
+}
+
+/// True for the apex ZONEMD records, and the RRSIGs covering them
+fn is_apex_zonemd(origin: &Name, record: &Record) -> bool {
+    if record.name() != origin {
+        return false;
+    }
+
+    match record.record_type() {
+        RecordType::ZONEMD => true,
+        RecordType::RRSIG => matches!(
+            record.data(),
+            Some(RData::DNSSEC(DNSSECRData::SIG(sig))) if sig.type_covered() == RecordType::ZONEMD
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::rr::rdata::sshfp::HEX;
+    use crate::rr::rdata::SOA;
+
+    /// The zone of RFC 8976 appendix A.1
+    fn simple_zone() -> (Name, Vec<Record>) {
+        let origin = Name::from_str("example.").unwrap();
+        let name = |s: &str| Name::from_str(s).unwrap();
+        let digest = HEX
+            .decode(
+                b"c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3\
+                  a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c",
+            )
+            .unwrap();
+
+        let records = vec![
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RData::SOA(SOA::new(
+                    name("ns1.example."),
+                    name("admin.example."),
+                    2018031900,
+                    1800,
+                    900,
+                    604800,
+                    86400,
+                )),
+            ),
+            Record::from_rdata(origin.clone(), 86400, RData::NS(name("ns1.example."))),
+            Record::from_rdata(origin.clone(), 86400, RData::NS(name("ns2.example."))),
+            Record::from_rdata(
+                origin.clone(),
+                86400,
+                RData::ZONEMD(ZONEMD::new(
+                    2018031900,
+                    Scheme::Simple,
+                    HashAlgorithm::SHA384,
+                    digest,
+                )),
+            ),
+            Record::from_rdata(
+                name("ns1.example."),
+                3600,
+                RData::A("203.0.113.63".parse().unwrap()),
+            ),
+            Record::from_rdata(
+                name("ns2.example."),
+                3600,
+                RData::AAAA("2001:db8::63".parse().unwrap()),
+            ),
+        ];
+
+        (origin, records)
+    }
+
+    #[test]
+    fn test_verify_simple_zone() {
+        let (origin, records) = simple_zone();
+        verify(&origin, &records).expect("zone digest failed");
+
+        // order, case and duplicates do not matter
+        let mut shuffled = records.clone();
+        shuffled.reverse();
+        shuffled.push(Record::from_rdata(
+            Name::from_str("NS1.Example.").unwrap(),
+            3600,
+            RData::A("203.0.113.63".parse().unwrap()),
+        ));
+        verify(&origin, &shuffled).expect("zone digest failed");
+    }
+
+    #[test]
+    fn test_verify_modified_zone() {
+        let (origin, mut records) = simple_zone();
+        records.push(Record::from_rdata(
+            Name::from_str("ns3.example.").unwrap(),
+            3600,
+            RData::A("203.0.113.64".parse().unwrap()),
+        ));
+        assert!(verify(&origin, &records).is_err());
+    }
+
+    #[test]
+    fn test_verify_serial_mismatch() {
+        let (origin, mut records) = simple_zone();
+        records.retain(|r| r.record_type() != RecordType::ZONEMD);
+        let zonemd = zonemd(&origin, &records, HashAlgorithm::SHA384).unwrap();
+        records.push(Record::from_rdata(
+            origin.clone(),
+            86400,
+            RData::ZONEMD(ZONEMD::new(
+                zonemd.serial() + 1,
+                zonemd.scheme(),
+                zonemd.hash_algorithm(),
+                zonemd.digest().to_vec(),
+            )),
+        ));
+        assert!(verify(&origin, &records).is_err());
+    }
+
+    #[test]
+    fn test_verify_no_zonemd() {
+        let (origin, mut records) = simple_zone();
+        records.retain(|r| r.record_type() != RecordType::ZONEMD);
+        assert!(verify(&origin, &records).is_err());
+    }
+
+    #[test]
+    fn test_zonemd_sha512() {
+        let (origin, mut records) = simple_zone();
+        records.retain(|r| r.record_type() != RecordType::ZONEMD);
+
+        let zonemd = zonemd(&origin, &records, HashAlgorithm::SHA512).unwrap();
+        assert_eq!(zonemd.serial(), 2018031900);
+        assert_eq!(zonemd.digest().len(), 64);
+
+        records.push(Record::from_rdata(
+            origin.clone(),
+            86400,
+            RData::ZONEMD(zonemd),
+        ));
+        verify(&origin, &records).expect("zone digest failed");
+    }
+
+    #[test]
+    fn test_unsupported_hash_algorithm() {
+        let (origin, records) = simple_zone();
+        assert!(digest(&origin, &records, HashAlgorithm::Unassigned(240)).is_err());
+    }
+}
@@ -105,7 +105,22 @@ impl Name {
         self.is_fqdn = val
     }
 
-    /// Returns an iterator over the labels
+    /// Returns an iterator over the labels, borrowed from the name
+    ///
+    /// The iterator is double ended, iterating in reverse goes from the root towards the leftmost
+    ///  label.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let name = Name::from_str("www.example.com.").unwrap();
+    /// let labels: Vec<&[u8]> = name.iter().collect();
+    /// assert_eq!(labels, vec![&b"www"[..], &b"example"[..], &b"com"[..]]);
+    /// assert_eq!(name.iter().rev().next(), Some(&b"com"[..]));
+    /// ```
     pub fn iter(&self) -> LabelIter<'_> {
         LabelIter {
             name: self,
@@ -268,6 +283,31 @@ impl Name {
         }
     }
 
+    /// Randomizes the case of each ascii letter in the name, for DNS 0x20 encoding
+    ///
+    /// The result is equal to this name in the case insensitive comparisons.
+    pub(crate) fn to_random_case(&self) -> Self {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+
+        let new_label_data = self
+            .label_data
+            .iter()
+            .map(|c| {
+                if rng.gen() {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect();
+        Self {
+            is_fqdn: self.is_fqdn,
+            label_data: new_label_data,
+            label_ends: self.label_ends.clone(),
+        }
+    }
+
     /// Trims off the first part of the name, to help with searching for the domain piece
     ///
     /// # Examples
@@ -363,6 +403,108 @@ impl Name {
         self_lower.zone_of_case(&name_lower)
     }
 
+    /// Returns the labels of this name preceding `suffix`, or `None` if `suffix` is not a suffix of
+    ///  this name. Labels are compared case insensitively.
+    ///
+    /// The returned name is relative, stripping a name from itself results in an empty name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let name = Name::from_str("www.Example.com.").unwrap();
+    /// let suffix = Name::from_str("example.com.").unwrap();
+    ///
+    /// let www = name.strip_suffix(&suffix).unwrap();
+    /// assert_eq!(www, Name::from_str("www").unwrap());
+    /// assert!(!www.is_fqdn());
+    ///
+    /// assert_eq!(name.strip_suffix(&name).unwrap().num_labels(), 0);
+    /// assert!(suffix.strip_suffix(&name).is_none());
+    /// assert!(name.strip_suffix(&Name::from_str("example.net.").unwrap()).is_none());
+    /// ```
+    pub fn strip_suffix(&self, suffix: &Self) -> Option<Self> {
+        if !suffix.zone_of(self) {
+            return None;
+        }
+
+        let num_labels = self.label_ends.len() - suffix.label_ends.len();
+        let mut name = Self::new();
+        for label in self.iter().take(num_labels) {
+            // the labels are a subset of this name, so the length can not be exceeded
+            name.extend_name(label)
+                .expect("labels of a valid name exceeded the maximum length");
+        }
+
+        Some(name)
+    }
+
+    /// Returns this fully qualified name relative to the fully qualified `origin`, as used in zone
+    ///  files, or `None` if this name is not in the `origin` zone.
+    ///
+    /// This is the inverse of [`Self::to_absolute`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let origin = Name::from_str("example.com.").unwrap();
+    /// let name = Name::from_str("www.example.com.").unwrap();
+    ///
+    /// let relative = name.relative_to(&origin).unwrap();
+    /// assert_eq!(relative.to_string(), "www");
+    /// assert_eq!(relative.to_absolute(&origin).unwrap(), name);
+    ///
+    /// // relative names have no origin
+    /// assert!(relative.relative_to(&origin).is_none());
+    /// ```
+    pub fn relative_to(&self, origin: &Self) -> Option<Self> {
+        if !self.is_fqdn() || !origin.is_fqdn() {
+            return None;
+        }
+
+        self.strip_suffix(origin)
+    }
+
+    /// Returns this name qualified with `origin`, as used in zone files, fully qualified names are
+    ///  returned unchanged.
+    ///
+    /// An error is returned if `origin` is not fully qualified, or the resulting name exceeds the
+    ///  maximum length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let origin = Name::from_str("example.com.").unwrap();
+    ///
+    /// let name = Name::from_str("www").unwrap().to_absolute(&origin).unwrap();
+    /// assert_eq!(name, Name::from_str("www.example.com.").unwrap());
+    /// assert!(name.is_fqdn());
+    ///
+    /// let name = Name::from_str("www.example.net.").unwrap();
+    /// assert_eq!(name.to_absolute(&origin).unwrap(), name);
+    ///
+    /// assert!(Name::from_str("www").unwrap().to_absolute(&Name::from_str("example.com").unwrap()).is_err());
+    /// ```
+    pub fn to_absolute(&self, origin: &Self) -> ProtoResult<Self> {
+        if self.is_fqdn() {
+            return Ok(self.clone());
+        }
+
+        if !origin.is_fqdn() {
+            return Err(format!("origin is not fully qualified: {}", origin).into());
+        }
+
+        self.clone().append_domain(origin)
+    }
+
     /// Returns the number of labels in the name, discounting `*`.
     ///
     /// # Examples
@@ -602,59 +744,57 @@ impl Name {
         encoder: &mut BinEncoder<'_>,
         canonical: bool,
     ) -> ProtoResult<()> {
-        let buf_len = encoder.len(); // lazily assert the size is less than 255...
-                                     // lookup the label in the BinEncoder
-                                     // if it exists, write the Pointer
-        let labels = self.iter();
-
-        // start index of each label
-        let mut labels_written = Vec::with_capacity(self.label_ends.len());
-        // we're going to write out each label, tracking the indexes of the start to each label
-        //   then we'll look to see if we can remove them and recapture the capacity in the buffer...
-        for label in labels {
+        // the uncompressed wire form of the name, each suffix of it can be compressed
+        let mut wire = Vec::with_capacity(self.len());
+        // start index of each label in the wire form
+        let mut label_starts = Vec::with_capacity(self.label_ends.len());
+        for label in self.iter() {
             if label.len() > 63 {
                 return Err(ProtoErrorKind::LabelBytesTooLong(label.len()).into());
             }
 
-            labels_written.push(encoder.offset());
-            encoder.emit_character_data(label)?;
+            label_starts.push(wire.len());
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
         }
-        let last_index = encoder.offset();
-        // now search for other labels already stored matching from the beginning label, strip then to the end
-        //   if it's not found, then store this as a new label
-        for label_idx in &labels_written {
-            match encoder.get_label_pointer(*label_idx, last_index) {
-                // if writing canonical and already found, continue
-                Some(_) if canonical => continue,
-                Some(loc) if !canonical => {
-                    // reset back to the beginning of this label, and then write the pointer...
-                    encoder.set_offset(*label_idx);
-                    encoder.trim();
-
-                    // write out the pointer marker
-                    //  or'd with the location which shouldn't be larger than this 2^14 or 16k
-                    encoder.emit_u16(0xC000u16 | (loc & 0x3FFFu16))?;
-
-                    // we found a pointer don't write more, break
-                    return Ok(());
-                }
-                _ => {
-                    // no existing label exists, store this new one.
-                    encoder.store_label_pointer(*label_idx, last_index);
-                }
-            }
-        }
-
-        // if we're getting here, then we didn't write out a pointer and are ending the name
-        // the end of the list of names
-        encoder.emit(0)?;
 
-        // the entire name needs to be less than 256.
-        let length = encoder.len() - buf_len;
+        // the entire name needs to be less than 256, including the root label.
+        let length = wire.len() + 1;
         if length > 255 {
             return Err(ProtoErrorKind::DomainNameTooLong(length).into());
         }
 
+        // find the longest suffix which has already been written, the labels before it are written out
+        let (written_labels, pointer) = if canonical {
+            (label_starts.len(), None)
+        } else {
+            label_starts
+                .iter()
+                .enumerate()
+                .find_map(|(i, start)| {
+                    encoder
+                        .get_name_pointer(&wire[*start..])
+                        .map(|pointer| (i, Some(pointer)))
+                })
+                .unwrap_or((label_starts.len(), None))
+        };
+
+        for (i, start) in label_starts.iter().take(written_labels).enumerate() {
+            let end = label_starts.get(i + 1).copied().unwrap_or(wire.len());
+            let offset = encoder.offset();
+
+            encoder.emit_vec(&wire[*start..end])?;
+            encoder.store_name_pointer(offset, wire[*start..].to_vec());
+        }
+
+        match pointer {
+            // write out the pointer marker
+            //  or'd with the location which shouldn't be larger than this 2^14 or 16k
+            Some(loc) => encoder.emit_u16(0xC000u16 | (loc & 0x3FFFu16))?,
+            // the end of the list of names
+            None => encoder.emit(0)?,
+        }
+
         Ok(())
     }
 
@@ -710,6 +850,35 @@ impl Name {
         self.cmp_with_f::<CaseSensitive>(other) == Ordering::Equal
     }
 
+    /// Compares the Names in canonical DNS name order, as used to order NSEC records and the
+    ///  owner names of a signed zone
+    ///
+    /// This is the same ordering as the `Ord` implementation of `Name`, see its documentation for
+    ///  the details of RFC 4034, section 6.1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let mut names: Vec<Name> = ["z.example.", "*.z.example.", "z.a.example.", "example.", "a.example."]
+    ///     .iter()
+    ///     .map(|n| Name::from_str(n).unwrap())
+    ///     .collect();
+    /// names.sort_by(Name::cmp_canonical);
+    ///
+    /// let names: Vec<String> = names.iter().map(Name::to_string).collect();
+    /// assert_eq!(names, ["example.", "a.example.", "z.a.example.", "z.example.", "*.z.example."]);
+    ///
+    /// let a = Name::from_str("A.example.").unwrap();
+    /// assert_eq!(a.cmp_canonical(&Name::from_str("a.example.").unwrap()), Ordering::Equal);
+    /// ```
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        self.cmp_with_f::<CaseInsensitive>(other)
+    }
+
     /// Converts this name into an ascii safe string.
     ///
     /// If the name is an IDNA name, then the name labels will be returned with the `xn--` prefix.
@@ -885,6 +1054,98 @@ impl Name {
             is_fqdn: self.is_fqdn,
         }
     }
+
+    /// Returns the wildcard name of the domain this name is the closest encloser of, i.e. `*.`
+    ///  prepended to this name
+    ///
+    /// ```text
+    /// RFC 4592                  DNS Wildcards                       July 2006
+    ///
+    /// 3.3.1.  The Closest Encloser and the Source of Synthesis
+    ///
+    ///    The closest encloser is the node in the zone's tree of existing
+    ///    domain names that has the most labels matching the query name
+    ///    (consecutively, counting from the root label downward).  Each match
+    ///    is a "label match" and the order of the labels is the same.
+    ///
+    ///    The closest encloser is, by definition, an existing name in the
+    ///    zone.  The closest encloser might be an empty non-terminal or even
+    ///    be a wildcard domain name itself.  In no circumstances is the
+    ///    closest encloser to be used to synthesize records for the current
+    ///    query.
+    ///
+    ///    The source of synthesis is defined in the context of a query
+    ///    process as that wildcard domain name immediately descending from
+    ///    the closest encloser, provided that this wildcard domain name
+    ///    exists.  "Immediately descending" means that the source of
+    ///    synthesis has a name of the form:
+    ///
+    ///       <asterisk label>.<closest encloser>.
+    /// ```
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::Name;
+    ///
+    /// let closest_encloser = Name::from_str("example.com.").unwrap();
+    /// let wildcard = closest_encloser.prepend_wildcard().unwrap();
+    /// assert_eq!(wildcard, Name::from_str("*.example.com.").unwrap());
+    /// ```
+    pub fn prepend_wildcard(&self) -> ProtoResult<Self> {
+        Self::from_labels(Some(Label::wildcard()))?.append_name(self)
+    }
+
+    /// Returns true if this is a wildcard name which matches `name`
+    ///
+    /// Only the names themselves are compared: the wildcard also must not match if a name between
+    ///  `name` and the closest encloser exists in the zone, which is left to the caller.
+    ///
+    /// ```text
+    /// RFC 4592                  DNS Wildcards                       July 2006
+    ///
+    /// 2.1.1.  Wildcard Domain Name and Asterisk Label
+    ///
+    ///    A "wildcard domain name" is defined by having its initial (i.e.,
+    ///    leftmost or least significant) label be, in binary format:
+    ///
+    ///       0000 0001 0010 1010 (binary) = 0x012a (hexadecimal)
+    ///
+    ///    The first octet is the normal label type and length for a 1-octet-
+    ///    long label, and the second octet is the ASCII representation [RFC20]
+    ///    for the '*' character.
+    /// ```
+    ///
+    /// The asterisk label only makes a wildcard domain name if it is the leftmost label, and any
+    ///  number of labels may take its place, i.e. `*.example.com.` matches all names below
+    ///  `example.com.`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::Name;
+    ///
+    /// let wildcard = Name::from_str("*.example.com.").unwrap();
+    /// assert!(wildcard.wildcard_matches(&Name::from_str("www.example.com.").unwrap()));
+    /// assert!(wildcard.wildcard_matches(&Name::from_str("a.b.Example.com.").unwrap()));
+    ///
+    /// // the wildcard does not match its parent
+    /// assert!(!wildcard.wildcard_matches(&Name::from_str("example.com.").unwrap()));
+    ///
+    /// // asterisks not in the leftmost label are ordinary labels
+    /// let name = Name::from_str("www.*.example.com.").unwrap();
+    /// assert!(!name.wildcard_matches(&Name::from_str("www.a.example.com.").unwrap()));
+    /// ```
+    pub fn wildcard_matches(&self, name: &Self) -> bool {
+        if !self.is_wildcard() {
+            return false;
+        }
+
+        let closest_encloser = self.base_name();
+        name.label_ends.len() > closest_encloser.label_ends.len() && closest_encloser.zone_of(name)
+    }
 }
 
 impl std::fmt::Debug for Name {
@@ -926,6 +1187,7 @@ impl LabelEnc for LabelEncUtf8 {
 }
 
 /// An iterator over labels in a name
+#[derive(Clone)]
 pub struct LabelIter<'a> {
     name: &'a Name,
     start: usize,
@@ -1271,7 +1533,7 @@ impl Ord for Name {
     ///            \200.z.example
     /// ```
     fn cmp(&self, other: &Self) -> Ordering {
-        self.cmp_with_f::<CaseInsensitive>(other)
+        self.cmp_canonical(other)
     }
 }
 
@@ -1928,4 +2190,127 @@ mod tests {
         assert!(iter.next().is_none());
         assert_eq!(iter.size_hint().0, 0);
     }
+
+    #[test]
+    fn test_strip_suffix() {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+
+        assert_eq!(
+            name.strip_suffix(&Name::root()).unwrap(),
+            Name::from_ascii("www.example.com").unwrap()
+        );
+        assert_eq!(
+            name.strip_suffix(&Name::from_ascii("COM.").unwrap())
+                .unwrap(),
+            Name::from_ascii("www.example").unwrap()
+        );
+        assert!(name
+            .strip_suffix(&Name::from_ascii("ww.example.com.").unwrap())
+            .is_none());
+
+        // relative names can be stripped as well
+        let relative = Name::from_ascii("a.b.c").unwrap();
+        assert_eq!(
+            relative
+                .strip_suffix(&Name::from_ascii("b.c").unwrap())
+                .unwrap(),
+            Name::from_ascii("a").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_relative_to_absolute() {
+        let origin = Name::from_ascii("example.com.").unwrap();
+
+        for name in &["example.com.", "www.example.com.", "a.b.example.com."] {
+            let name = Name::from_ascii(name).unwrap();
+            let relative = name.relative_to(&origin).unwrap();
+            assert!(!relative.is_fqdn());
+
+            let absolute = relative.to_absolute(&origin).unwrap();
+            assert!(absolute.eq_case(&name));
+            assert!(absolute.is_fqdn());
+        }
+
+        assert!(Name::from_ascii("example.net.")
+            .unwrap()
+            .relative_to(&origin)
+            .is_none());
+        assert!(Name::from_ascii("www.example.com")
+            .unwrap()
+            .relative_to(&origin)
+            .is_none());
+    }
+
+    #[test]
+    fn test_wildcard_matches() {
+        let wildcard = Name::from_ascii("*.example.com.").unwrap();
+
+        assert!(wildcard.wildcard_matches(&wildcard));
+        assert!(wildcard.wildcard_matches(&Name::from_ascii("WWW.example.com.").unwrap()));
+        assert!(!wildcard.wildcard_matches(&Name::from_ascii("www.example.net.").unwrap()));
+        assert!(!wildcard.wildcard_matches(&Name::root()));
+
+        let not_wildcard = Name::from_ascii("www.example.com.").unwrap();
+        assert!(!not_wildcard.wildcard_matches(&Name::from_ascii("a.example.com.").unwrap()));
+
+        // the root wildcard matches everything but the root
+        let root_wildcard = Name::root().prepend_wildcard().unwrap();
+        assert_eq!(root_wildcard, Name::from_ascii("*.").unwrap());
+        assert!(root_wildcard.wildcard_matches(&Name::from_ascii("com.").unwrap()));
+        assert!(!root_wildcard.wildcard_matches(&Name::root()));
+    }
+
+    #[test]
+    fn test_prepend_wildcard() {
+        let name = Name::from_ascii("example.com").unwrap();
+        let wildcard = name.prepend_wildcard().unwrap();
+        assert!(wildcard.is_wildcard());
+        assert!(!wildcard.is_fqdn());
+        assert_eq!(wildcard.base_name(), name);
+
+        // a name of the maximum length can not be extended
+        let label = "a".repeat(63);
+        let long = Name::from_ascii(format!("{0}.{0}.{0}.{1}.", label, "a".repeat(62))).unwrap();
+        assert_eq!(long.len(), 255);
+        assert!(long.prepend_wildcard().is_err());
+    }
+
+    #[test]
+    fn test_cmp_canonical() {
+        // RFC 4034, section 6.1
+        let names: Vec<Vec<&[u8]>> = vec![
+            vec![&b"example"[..]],
+            vec![&b"a"[..], &b"example"[..]],
+            vec![&b"yljkjljk"[..], &b"a"[..], &b"example"[..]],
+            vec![&b"Z"[..], &b"a"[..], &b"example"[..]],
+            vec![&b"zABC"[..], &b"a"[..], &b"EXAMPLE"[..]],
+            vec![&b"z"[..], &b"example"[..]],
+            vec![&b"\x01"[..], &b"z"[..], &b"example"[..]],
+            vec![&b"*"[..], &b"z"[..], &b"example"[..]],
+            vec![&b"\x80"[..], &b"z"[..], &b"example"[..]],
+        ];
+        let names: Vec<Name> = names
+            .into_iter()
+            .map(|labels| Name::from_labels(labels).unwrap())
+            .collect();
+
+        let mut sorted = names.clone();
+        sorted.reverse();
+        sorted.sort_by(Name::cmp_canonical);
+
+        for (expected, sorted) in names.iter().zip(sorted.iter()) {
+            assert!(expected.eq_case(sorted), "{} != {}", expected, sorted);
+        }
+    }
+
+    #[test]
+    fn test_to_random_case() {
+        let name = Name::from_ascii("www.Example-1.com.").unwrap();
+        let random = name.to_random_case();
+
+        assert_eq!(random, name);
+        assert!(random.to_lowercase().eq_case(&name.to_lowercase()));
+        assert!(random.is_fqdn());
+    }
 }
@@ -0,0 +1,283 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! APL records for lists of address prefixes
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+#[cfg(feature = "serde-config")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::*;
+use crate::serialize::binary::*;
+
+/// [RFC 3123, A DNS RR Type for Lists of Address Prefixes](https://tools.ietf.org/html/rfc3123#section-4)
+///
+/// ```text
+/// 4. APL RDATA format
+///
+///    The RDATA section consists of zero or more items (<apitem>) of the
+///    form
+///
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |                          ADDRESSFAMILY                |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |             PREFIX    | N|         AFDLENGTH  |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                            AFDPART            /
+///       |                                               |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+/// ```
+///
+/// Only the IPv4 and IPv6 address families are supported.
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct APL {
+    items: Vec<APLItem>,
+}
+
+impl APL {
+    /// Creates a new APL record data.
+    pub fn new(items: Vec<APLItem>) -> Self {
+        Self { items }
+    }
+
+    /// The address prefixes of the list
+    pub fn items(&self) -> &[APLItem] {
+        &self.items
+    }
+}
+
+/// An address prefix of an APL record, e.g. `1:192.168.32.0/21` or `!1:192.168.38.0/28`
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct APLItem {
+    negation: bool,
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl APLItem {
+    /// Creates a new item, returns an error if the prefix is longer than the address
+    ///
+    /// # Arguments
+    ///
+    /// * `negation` - true if the prefix is excluded from the list
+    /// * `address` - the address, bits beyond the prefix should be zero
+    /// * `prefix` - the length of the prefix in bits
+    pub fn new(negation: bool, address: IpAddr, prefix: u8) -> ProtoResult<Self> {
+        if prefix > max_prefix(address) {
+            return Err(format!("APL prefix exceeds the address: {}/{}", address, prefix).into());
+        }
+
+        Ok(Self {
+            negation,
+            address,
+            prefix,
+        })
+    }
+
+    /// Returns true if the prefix is excluded from the list
+    pub fn negation(&self) -> bool {
+        self.negation
+    }
+
+    /// The address of the prefix
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The length of the prefix in bits
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// The IANA address family, 1 for IPv4 and 2 for IPv6
+    pub fn address_family(&self) -> u16 {
+        match self.address {
+            IpAddr::V4(..) => 1,
+            IpAddr::V6(..) => 2,
+        }
+    }
+}
+
+fn max_prefix(address: IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(..) => 32,
+        IpAddr::V6(..) => 128,
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<APL> {
+    let end = decoder.index() + rdata_length.map(usize::from).unverified(/*bounded by decoder*/);
+    let mut items = Vec::new();
+
+    while decoder.index() < end {
+        let family = decoder.read_u16()?.unverified(/*verified in match*/);
+        let prefix = decoder.read_u8()?.unverified(/*verified in APLItem::new*/);
+        let negation_length = decoder.read_u8()?.unverified(/*any u8 is valid*/);
+        let negation = negation_length & 0b1000_0000 != 0;
+        let length = (negation_length & 0b0111_1111) as usize;
+        let afd = decoder.read_slice(length)?.unverified(/*length verified in match*/);
+
+        // trailing zero octets of the address are not sent
+        let address = match family {
+            1 if afd.len() <= 4 => {
+                let mut octets = [0_u8; 4];
+                octets[..afd.len()].copy_from_slice(afd);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            2 if afd.len() <= 16 => {
+                let mut octets = [0_u8; 16];
+                octets[..afd.len()].copy_from_slice(afd);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            1 | 2 => return Err("APL address is too long".into()),
+            _ => return Err(format!("unsupported APL address family: {}", family).into()),
+        };
+
+        items.push(APLItem::new(negation, address, prefix)?);
+    }
+
+    Ok(APL { items })
+}
+
+/// Write the RData from the given Encoder
+pub fn emit(encoder: &mut BinEncoder<'_>, apl: &APL) -> ProtoResult<()> {
+    for item in &apl.items {
+        let octets = match item.address {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        };
+        let length = octets.iter().rposition(|o| *o != 0).map_or(0, |i| i + 1);
+
+        encoder.emit_u16(item.address_family())?;
+        encoder.emit_u8(item.prefix)?;
+        encoder.emit_u8(if item.negation { 0b1000_0000 } else { 0 } | length as u8)?;
+        encoder.emit_vec(&octets[..length])?;
+    }
+
+    Ok(())
+}
+
+/// [RFC 3123](https://tools.ietf.org/html/rfc3123#section-5)
+///
+/// ```text
+/// 5. APL RR Master File Format
+///
+///    The textual representation of an APL RR in a DNS zone file is as
+///    follows:
+///
+///    <owner>   IN   <TTL>   APL   {[!]afi:address/prefix}*
+///
+///    The data consists of zero or more strings of the address family
+///    indicator <afi>, immediately followed by a colon ":", an address,
+///    immediately followed by the "/" character, immediately followed by a
+///    decimal numeric value for the prefix length.  Any such string may be
+///    preceded by a "!" character.  The strings are separated by
+///    whitespace.
+/// ```
+impl FromStr for APLItem {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ProtoError::from(format!("invalid APL item: {}", s));
+
+        let (negation, item) = match s.strip_prefix('!') {
+            Some(item) => (true, item),
+            None => (false, s),
+        };
+        let (family, item) = item.split_once(':').ok_or_else(invalid)?;
+        let (address, prefix) = item.rsplit_once('/').ok_or_else(invalid)?;
+        let prefix = u8::from_str(prefix).map_err(|_| invalid())?;
+
+        let address = match family {
+            "1" => IpAddr::V4(Ipv4Addr::from_str(address).map_err(|_| invalid())?),
+            "2" => IpAddr::V6(Ipv6Addr::from_str(address).map_err(|_| invalid())?),
+            _ => return Err(format!("unsupported APL address family: {}", family).into()),
+        };
+
+        Self::new(negation, address, prefix)
+    }
+}
+
+impl fmt::Display for APLItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{negation}{family}:{address}/{prefix}",
+            negation = if self.negation { "!" } else { "" },
+            family = self.address_family(),
+            address = self.address,
+            prefix = self.prefix,
+        )
+    }
+}
+
+impl fmt::Display for APL {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        let rdata = APL::new(vec![
+            APLItem::from_str("1:192.168.32.0/21").unwrap(),
+            APLItem::from_str("!1:192.168.38.0/28").unwrap(),
+            APLItem::from_str("1:224.0.0.0/4").unwrap(),
+            APLItem::from_str("2:ff00::/8").unwrap(),
+            APLItem::from_str("1:0.0.0.0/0").unwrap(),
+        ]);
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+        assert_eq!(&bytes[..7], &[0, 1, 21, 3, 192, 168, 32]);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(bytes.len() as u16)).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+        assert_eq!(
+            read_rdata.to_string(),
+            "1:192.168.32.0/21 !1:192.168.38.0/28 1:224.0.0.0/4 2:ff00::/8 1:0.0.0.0/0"
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(APLItem::from_str("1:192.168.32.0/33").is_err());
+        assert!(APLItem::from_str("2:192.168.32.0/21").is_err());
+        assert!(APLItem::from_str("3:192.168.32.0/21").is_err());
+        assert!(APLItem::from_str("192.168.32.0/21").is_err());
+
+        // an IPv4 address with 5 octets
+        let bytes = [0, 1, 8, 5, 1, 2, 3, 4, 5];
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(&bytes);
+        assert!(read(&mut decoder, Restrict::new(bytes.len() as u16)).is_err());
+    }
+}
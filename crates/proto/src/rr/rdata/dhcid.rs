@@ -0,0 +1,138 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DHCID records for associating DHCP clients with their names
+
+use std::fmt;
+
+#[cfg(feature = "serde-config")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::*;
+use crate::serialize::binary::*;
+
+/// [RFC 4701, A DNS Resource Record (RR) for Encoding DHCP Information](https://tools.ietf.org/html/rfc4701#section-3.1)
+///
+/// ```text
+/// 3.1.  DHCID RDATA Format
+///
+///    The RDATA section of a DHCID RR in transmission contains RDLENGTH
+///    octets of binary data.  The format of this data and its
+///    interpretation by DHCP servers and clients are described below.
+///    DNS software should consider the RDATA section to be opaque.
+///
+/// 3.3.  DHCID RDATA Wire Format
+///
+///    The DHCID RDATA has the following structure:
+///
+///       < identifier-type > < digest-type > < digest >
+///
+///    The identifier-type code is 2 octets in network byte order.  The
+///    digest-type code is 1 octet.
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct DHCID {
+    data: Vec<u8>,
+}
+
+impl DHCID {
+    /// Creates a new DHCID record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - the identifier type, digest type and digest. This will NOT be checked.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    /// The opaque data of the record
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The identifier type, e.g. 1 for a DHCPv4 client identifier option
+    pub fn identifier_type(&self) -> Option<u16> {
+        self.data
+            .get(..2)
+            .map(|code| u16::from_be_bytes([code[0], code[1]]))
+    }
+
+    /// The digest type, 1 for SHA-256
+    pub fn digest_type(&self) -> Option<u8> {
+        self.data.get(2).copied()
+    }
+
+    /// The digest of the client identity
+    pub fn digest(&self) -> &[u8] {
+        self.data.get(3..).unwrap_or_default()
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<DHCID> {
+    let rdata_length = rdata_length.map(usize::from).unverified();
+    let data = decoder.read_vec(rdata_length)?.unverified(/*opaque to DNS*/);
+    Ok(DHCID::new(data))
+}
+
+/// Write the RData from the given Encoder
+pub fn emit(encoder: &mut BinEncoder<'_>, dhcid: &DHCID) -> ProtoResult<()> {
+    encoder.emit_vec(&dhcid.data)
+}
+
+/// [RFC 4701](https://tools.ietf.org/html/rfc4701#section-3.2)
+///
+/// ```text
+/// 3.2.  DHCID Presentation Format
+///
+///    In DNS master files, the RDATA is represented as a single block in
+///    base-64 encoding identical to that used for representing binary data
+///    in [3], Section 3.  The data may be divided up into any number of
+///    white-space-separated substrings, each of which is a valid base-64
+///    string, and which are concatenated to obtain the full signature.
+/// ```
+impl fmt::Display for DHCID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str(&data_encoding::BASE64.encode(&self.data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        // RFC 4701, Section 3.6.1, DHCPv6 client
+        let data = data_encoding::BASE64
+            .decode(b"AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA=")
+            .unwrap();
+        let rdata = DHCID::new(data);
+        assert_eq!(rdata.identifier_type(), Some(2));
+        assert_eq!(rdata.digest_type(), Some(1));
+        assert_eq!(rdata.digest().len(), 32);
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(bytes.len() as u16)).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+        assert_eq!(
+            read_rdata.to_string(),
+            "AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA="
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! EUI48 and EUI64 records for storing MAC addresses
+//!
+//! [RFC 7043, Resource Records for EUI-48 and EUI-64 Addresses in the DNS](https://tools.ietf.org/html/rfc7043)
+//!
+//! ```text
+//! 3.1.  EUI48 RDATA Wire Format
+//!
+//!    The EUI48 RR encodes a 6-octet value.
+//!
+//!     0                   1                   2                   3
+//!     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//!    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//!    |                          EUI-48 Address                       |
+//!    |                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//!    |                               |
+//!    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//!
+//! 3.2.  EUI48 RR Presentation Format
+//!
+//!    The Address field MUST be represented as six two-digit hexadecimal
+//!    numbers separated by hyphens.  The hexadecimal digits "A" through "F"
+//!    MAY be represented in either uppercase or lowercase.
+//! ```
+//!
+//! EUI64 is the same with an 8-octet value.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "serde-config")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::*;
+use crate::serialize::binary::*;
+
+/// An EUI-48 address, e.g. `00-00-5e-00-53-2a`, [RFC 7043](https://tools.ietf.org/html/rfc7043#section-3)
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct EUI48([u8; 6]);
+
+impl EUI48 {
+    /// Creates a new EUI48 record data.
+    pub fn new(address: [u8; 6]) -> Self {
+        Self(address)
+    }
+
+    /// The octets of the address
+    pub fn address(&self) -> &[u8; 6] {
+        &self.0
+    }
+}
+
+/// An EUI-64 address, e.g. `00-00-5e-ef-10-00-00-2a`, [RFC 7043](https://tools.ietf.org/html/rfc7043#section-4)
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct EUI64([u8; 8]);
+
+impl EUI64 {
+    /// Creates a new EUI64 record data.
+    pub fn new(address: [u8; 8]) -> Self {
+        Self(address)
+    }
+
+    /// The octets of the address
+    pub fn address(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+/// Read the EUI48 RData from the given Decoder
+pub fn read_eui48(decoder: &mut BinDecoder<'_>) -> ProtoResult<EUI48> {
+    let mut address = [0; 6];
+    address.copy_from_slice(decoder.read_slice(6)?.unverified(/*any address is valid*/));
+    Ok(EUI48(address))
+}
+
+/// Write the EUI48 RData from the given Encoder
+pub fn emit_eui48(encoder: &mut BinEncoder<'_>, eui48: &EUI48) -> ProtoResult<()> {
+    encoder.emit_vec(&eui48.0)
+}
+
+/// Read the EUI64 RData from the given Decoder
+pub fn read_eui64(decoder: &mut BinDecoder<'_>) -> ProtoResult<EUI64> {
+    let mut address = [0; 8];
+    address.copy_from_slice(decoder.read_slice(8)?.unverified(/*any address is valid*/));
+    Ok(EUI64(address))
+}
+
+/// Write the EUI64 RData from the given Encoder
+pub fn emit_eui64(encoder: &mut BinEncoder<'_>, eui64: &EUI64) -> ProtoResult<()> {
+    encoder.emit_vec(&eui64.0)
+}
+
+/// Parses hyphen separated two-digit hexadecimal numbers into the address
+fn parse_address(s: &str, address: &mut [u8]) -> ProtoResult<()> {
+    let mut octets = s.split('-');
+
+    for octet in address.iter_mut() {
+        *octet = octets
+            .next()
+            .filter(|hex| hex.len() == 2 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| ProtoError::from(format!("invalid EUI address: {}", s)))?;
+    }
+
+    if octets.next().is_some() {
+        return Err(format!("invalid EUI address: {}", s).into());
+    }
+
+    Ok(())
+}
+
+fn fmt_address(address: &[u8], f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+    for (i, octet) in address.iter().enumerate() {
+        if i > 0 {
+            f.write_str("-")?;
+        }
+        write!(f, "{:02x}", octet)?;
+    }
+
+    Ok(())
+}
+
+impl FromStr for EUI48 {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut address = [0; 6];
+        parse_address(s, &mut address)?;
+        Ok(Self(address))
+    }
+}
+
+impl fmt::Display for EUI48 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt_address(&self.0, f)
+    }
+}
+
+impl FromStr for EUI64 {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut address = [0; 8];
+        parse_address(s, &mut address)?;
+        Ok(Self(address))
+    }
+}
+
+impl fmt::Display for EUI64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt_address(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    #[test]
+    fn test_eui48() {
+        let rdata = EUI48::from_str("00-00-5E-00-53-2a").unwrap();
+        assert_eq!(rdata.address(), &[0x00, 0x00, 0x5e, 0x00, 0x53, 0x2a]);
+        assert_eq!(rdata.to_string(), "00-00-5e-00-53-2a");
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit_eui48(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata = read_eui48(&mut decoder).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+    }
+
+    #[test]
+    fn test_eui64() {
+        let rdata = EUI64::from_str("00-00-5e-ef-10-00-00-2a").unwrap();
+        assert_eq!(rdata.to_string(), "00-00-5e-ef-10-00-00-2a");
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit_eui64(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata = read_eui64(&mut decoder).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(EUI48::from_str("00-00-5e-00-53").is_err());
+        assert!(EUI48::from_str("00-00-5e-00-53-2a-00").is_err());
+        assert!(EUI48::from_str("00:00:5e:00:53:2a").is_err());
+        assert!(EUI48::from_str("0-00-5e-00-53-2a").is_err());
+        assert!(EUI64::from_str("00-00-5e-00-53-2a").is_err());
+    }
+}
@@ -0,0 +1,600 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! LOC records for expressing location information
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "serde-config")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::*;
+use crate::serialize::binary::*;
+
+/// The equator and the prime meridian, latitudes and longitudes are offsets from this
+const EQUATOR: u32 = 1 << 31;
+/// The altitude is stored in centimeters above a base 100,000m below the WGS 84 spheroid
+const ALTITUDE_BASE: i64 = 10_000_000;
+/// Thousandths of a second of arc per degree
+const MILLIS_PER_DEGREE: u32 = 3_600_000;
+
+/// Default size, 1m
+const DEFAULT_SIZE: u8 = 0x12;
+/// Default horizontal precision, 10,000m
+const DEFAULT_HORIZ_PRE: u8 = 0x16;
+/// Default vertical precision, 10m
+const DEFAULT_VERT_PRE: u8 = 0x13;
+
+/// [RFC 1876, A Means for Expressing Location Information in the DNS](https://tools.ietf.org/html/rfc1876#section-2)
+///
+/// ```text
+/// 2. RDATA Format
+///
+///        MSB                                           LSB
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       0|        VERSION        |         SIZE          |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       2|       HORIZ PRE       |       VERT PRE        |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       4|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       6|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       8|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      10|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      12|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      14|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///                                                    (octet)
+/// ```
+///
+/// Only version 0 is defined, and is the only version supported.
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct LOC {
+    size: u8,
+    horiz_pre: u8,
+    vert_pre: u8,
+    latitude: u32,
+    longitude: u32,
+    altitude: u32,
+}
+
+impl LOC {
+    /// Creates a new LOC record data from the wire values.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - diameter of the sphere enclosing the entity, see `encode_precision`
+    /// * `horiz_pre` - horizontal precision, see `encode_precision`
+    /// * `vert_pre` - vertical precision, see `encode_precision`
+    /// * `latitude` - thousandths of a second of arc, 2^31 is the equator
+    /// * `longitude` - thousandths of a second of arc, 2^31 is the prime meridian
+    /// * `altitude` - centimeters from a base 100,000m below the WGS 84 reference spheroid
+    pub fn new(
+        size: u8,
+        horiz_pre: u8,
+        vert_pre: u8,
+        latitude: u32,
+        longitude: u32,
+        altitude: u32,
+    ) -> Self {
+        Self {
+            size,
+            horiz_pre,
+            vert_pre,
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+
+    /// Creates a new LOC record data from decimal degrees and an altitude in meters,
+    ///  with the default size of 1m, horizontal precision of 10,000m and vertical precision of 10m.
+    ///
+    /// # Arguments
+    ///
+    /// * `latitude` - degrees north of the equator, negative for south
+    /// * `longitude` - degrees east of the prime meridian, negative for west
+    /// * `altitude` - meters above the WGS 84 reference spheroid
+    pub fn from_degrees(latitude: f64, longitude: f64, altitude: f64) -> ProtoResult<Self> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(format!("LOC latitude out of range: {}", latitude).into());
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(format!("LOC longitude out of range: {}", longitude).into());
+        }
+
+        let altitude = (altitude * 100.0).round() + ALTITUDE_BASE as f64;
+        if !(0.0..=f64::from(u32::MAX)).contains(&altitude) {
+            return Err(format!("LOC altitude out of range: {}", altitude).into());
+        }
+
+        let to_millis = |degrees: f64| (degrees * f64::from(MILLIS_PER_DEGREE)).round() as i64;
+
+        Ok(Self {
+            size: DEFAULT_SIZE,
+            horiz_pre: DEFAULT_HORIZ_PRE,
+            vert_pre: DEFAULT_VERT_PRE,
+            latitude: (i64::from(EQUATOR) + to_millis(latitude)) as u32,
+            longitude: (i64::from(EQUATOR) + to_millis(longitude)) as u32,
+            altitude: altitude as u32,
+        })
+    }
+
+    /// Returns a new LOC with the size and precisions, given in centimeters
+    pub fn with_precision(mut self, size: u64, horiz_pre: u64, vert_pre: u64) -> Self {
+        self.size = encode_precision(size);
+        self.horiz_pre = encode_precision(horiz_pre);
+        self.vert_pre = encode_precision(vert_pre);
+        self
+    }
+
+    /// The encoded diameter of a sphere enclosing the described entity
+    pub fn size(&self) -> u8 {
+        self.size
+    }
+
+    /// The encoded horizontal precision of the data
+    pub fn horiz_pre(&self) -> u8 {
+        self.horiz_pre
+    }
+
+    /// The encoded vertical precision of the data
+    pub fn vert_pre(&self) -> u8 {
+        self.vert_pre
+    }
+
+    /// The latitude in thousandths of a second of arc, 2^31 is the equator
+    pub fn latitude(&self) -> u32 {
+        self.latitude
+    }
+
+    /// The longitude in thousandths of a second of arc, 2^31 is the prime meridian
+    pub fn longitude(&self) -> u32 {
+        self.longitude
+    }
+
+    /// The altitude in centimeters from a base 100,000m below the WGS 84 reference spheroid
+    pub fn altitude(&self) -> u32 {
+        self.altitude
+    }
+
+    /// The latitude in decimal degrees, negative for south
+    pub fn latitude_degrees(&self) -> f64 {
+        f64::from(self.latitude.wrapping_sub(EQUATOR) as i32) / f64::from(MILLIS_PER_DEGREE)
+    }
+
+    /// The longitude in decimal degrees, negative for west
+    pub fn longitude_degrees(&self) -> f64 {
+        f64::from(self.longitude.wrapping_sub(EQUATOR) as i32) / f64::from(MILLIS_PER_DEGREE)
+    }
+
+    /// The altitude in meters above the WGS 84 reference spheroid
+    pub fn altitude_meters(&self) -> f64 {
+        (i64::from(self.altitude) - ALTITUDE_BASE) as f64 / 100.0
+    }
+}
+
+/// Encodes centimeters into the size and precision format
+///
+/// [RFC 1876](https://tools.ietf.org/html/rfc1876#section-2)
+///
+/// ```text
+///    The diameter of a sphere enclosing the described entity, in
+///    centimeters, expressed as a pair of four-bit unsigned
+///    integers, each ranging from zero to nine, with the most
+///    significant four bits representing the base and the second
+///    number representing the power of ten by which to multiply
+///    the base.
+/// ```
+///
+/// Values which can not be represented exactly are truncated, values too large saturate at 9e9.
+pub fn encode_precision(centimeters: u64) -> u8 {
+    let mut mantissa = centimeters;
+    let mut exponent = 0_u8;
+
+    while mantissa > 9 && exponent < 9 {
+        mantissa /= 10;
+        exponent += 1;
+    }
+
+    ((mantissa.min(9) as u8) << 4) | exponent
+}
+
+/// Decodes the size and precision format into centimeters, see `encode_precision`
+pub fn decode_precision(precision: u8) -> ProtoResult<u64> {
+    let mantissa = u64::from(precision >> 4);
+    let exponent = u32::from(precision & 0x0F);
+
+    if mantissa > 9 || exponent > 9 {
+        return Err(format!("invalid LOC precision: {:#04x}", precision).into());
+    }
+
+    Ok(mantissa * 10_u64.pow(exponent))
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<LOC> {
+    rdata_length
+        .verify_unwrap(|length| *length == 16)
+        .map_err(|length| ProtoError::from(format!("invalid LOC rdata length: {}", length)))?;
+
+    let version = decoder.read_u8()?.unverified(/*verified below*/);
+    if version != 0 {
+        return Err(format!("unsupported LOC version: {}", version).into());
+    }
+
+    let size = decoder
+        .read_u8()?
+        .verify_unwrap(|p| decode_precision(*p).is_ok())
+        .map_err(|p| ProtoError::from(format!("invalid LOC size: {:#04x}", p)))?;
+    let horiz_pre = decoder
+        .read_u8()?
+        .verify_unwrap(|p| decode_precision(*p).is_ok())
+        .map_err(|p| ProtoError::from(format!("invalid LOC horizontal precision: {:#04x}", p)))?;
+    let vert_pre = decoder
+        .read_u8()?
+        .verify_unwrap(|p| decode_precision(*p).is_ok())
+        .map_err(|p| ProtoError::from(format!("invalid LOC vertical precision: {:#04x}", p)))?;
+    let latitude = decoder.read_u32()?.unverified(/*out of range values are displayed as is*/);
+    let longitude = decoder.read_u32()?.unverified(/*out of range values are displayed as is*/);
+    let altitude = decoder.read_u32()?.unverified(/*any u32 is valid*/);
+
+    Ok(LOC {
+        size,
+        horiz_pre,
+        vert_pre,
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+/// Write the RData from the given Encoder
+pub fn emit(encoder: &mut BinEncoder<'_>, loc: &LOC) -> ProtoResult<()> {
+    encoder.emit_u8(0)?;
+    encoder.emit_u8(loc.size)?;
+    encoder.emit_u8(loc.horiz_pre)?;
+    encoder.emit_u8(loc.vert_pre)?;
+    encoder.emit_u32(loc.latitude)?;
+    encoder.emit_u32(loc.longitude)?;
+    encoder.emit_u32(loc.altitude)?;
+    Ok(())
+}
+
+/// Parses an unsigned decimal number with at most `scale` fraction digits, returning it
+///  multiplied by `10^scale`
+fn parse_decimal(s: &str, scale: u32) -> Option<u64> {
+    let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if integer.is_empty()
+        || fraction.len() > scale as usize
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let integer = u64::from_str(integer).ok()?;
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        u64::from_str(fraction).ok()? * 10_u64.pow(scale - fraction.len() as u32)
+    };
+
+    integer
+        .checked_mul(10_u64.pow(scale))?
+        .checked_add(fraction)
+}
+
+/// Parses `d [m [s]] {hemispheres}` into thousandths of a second of arc from 2^31
+fn parse_coordinate<'i, I: Iterator<Item = &'i str>>(
+    tokens: &mut I,
+    max_degrees: u32,
+    hemispheres: [char; 2],
+) -> ProtoResult<u32> {
+    let invalid = |s: &str| ProtoError::from(format!("invalid LOC coordinate: {}", s));
+    let is_hemisphere = |s: &str| {
+        hemispheres
+            .iter()
+            .any(|h| s.eq_ignore_ascii_case(&h.to_string()))
+    };
+
+    let degrees = tokens.next().ok_or_else(|| invalid("missing degrees"))?;
+    let degrees = u32::from_str(degrees).map_err(|_| invalid(degrees))?;
+    let mut minutes = 0;
+    let mut millis = 0;
+
+    let mut token = tokens.next().ok_or_else(|| invalid("missing hemisphere"))?;
+    if !is_hemisphere(token) {
+        minutes = u32::from_str(token).map_err(|_| invalid(token))?;
+        token = tokens.next().ok_or_else(|| invalid("missing hemisphere"))?;
+
+        if !is_hemisphere(token) {
+            millis = parse_decimal(token, 3)
+                .filter(|millis| *millis < 60_000)
+                .ok_or_else(|| invalid(token))? as u32;
+            token = tokens.next().ok_or_else(|| invalid("missing hemisphere"))?;
+        }
+    }
+
+    if !is_hemisphere(token) {
+        return Err(invalid(token));
+    }
+    if minutes >= 60 || degrees > max_degrees || (degrees == max_degrees && minutes + millis > 0) {
+        return Err(format!(
+            "LOC coordinate out of range: {} {} {} {}",
+            degrees, minutes, millis, token
+        )
+        .into());
+    }
+
+    let offset = degrees * MILLIS_PER_DEGREE + minutes * 60_000 + millis;
+    if token.eq_ignore_ascii_case(&hemispheres[0].to_string()) {
+        Ok(EQUATOR + offset)
+    } else {
+        Ok(EQUATOR - offset)
+    }
+}
+
+/// Parses a distance in meters with an optional `m` suffix into centimeters
+fn parse_meters(s: &str) -> ProtoResult<u64> {
+    parse_decimal(s.strip_suffix('m').unwrap_or(s), 2)
+        .ok_or_else(|| format!("invalid LOC distance: {}", s).into())
+}
+
+/// [RFC 1876](https://tools.ietf.org/html/rfc1876#section-3)
+///
+/// ```text
+/// 3. Master File Format
+///
+///    The LOC record is expressed in a master file in the following format:
+///
+///    <owner> <TTL> <class> LOC ( d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]]
+///                                {"E"|"W"} alt["m"] [siz["m"] [hp["m"]
+///                                [vp["m"]]]] )
+///
+///    where:
+///
+///        d1:     [0 .. 90]            (degrees latitude)
+///        d2:     [0 .. 180]           (degrees longitude)
+///        m1, m2: [0 .. 59]            (minutes latitude/longitude)
+///        s1, s2: [0 .. 59.999]        (seconds latitude/longitude)
+///        alt:    [-100000.00 .. 42849672.95] BY .01 (altitude in meters)
+///        siz, hp, vp: [0 .. 90000000.00] (size/precision in meters)
+///
+///    If omitted, minutes and seconds default to zero, size defaults to 1m,
+///    horizontal precision defaults to 10000m, and vertical precision
+///    defaults to 10m.
+/// ```
+impl FromStr for LOC {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+
+        let latitude = parse_coordinate(&mut tokens, 90, ['N', 'S'])?;
+        let longitude = parse_coordinate(&mut tokens, 180, ['E', 'W'])?;
+
+        let altitude = tokens
+            .next()
+            .ok_or_else(|| ProtoError::from("LOC altitude is missing"))?;
+        let altitude = match altitude.strip_prefix('-') {
+            Some(below) => ALTITUDE_BASE - parse_meters(below)? as i64,
+            None => ALTITUDE_BASE + parse_meters(altitude)? as i64,
+        };
+        if !(0..=i64::from(u32::MAX)).contains(&altitude) {
+            return Err("LOC altitude out of range".into());
+        }
+
+        let mut precision = |default| {
+            tokens
+                .next()
+                .map_or(Ok(default), |p| parse_meters(p).map(encode_precision))
+        };
+        let size = precision(DEFAULT_SIZE)?;
+        let horiz_pre = precision(DEFAULT_HORIZ_PRE)?;
+        let vert_pre = precision(DEFAULT_VERT_PRE)?;
+
+        if tokens.next().is_some() {
+            return Err("too many fields for LOC".into());
+        }
+
+        Ok(Self {
+            size,
+            horiz_pre,
+            vert_pre,
+            latitude,
+            longitude,
+            altitude: altitude as u32,
+        })
+    }
+}
+
+fn fmt_coordinate(
+    f: &mut fmt::Formatter<'_>,
+    coordinate: u32,
+    hemispheres: [char; 2],
+) -> Result<(), fmt::Error> {
+    let (offset, hemisphere) = if coordinate >= EQUATOR {
+        (coordinate - EQUATOR, hemispheres[0])
+    } else {
+        (EQUATOR - coordinate, hemispheres[1])
+    };
+
+    write!(
+        f,
+        "{} {} {}.{:03} {}",
+        offset / MILLIS_PER_DEGREE,
+        offset % MILLIS_PER_DEGREE / 60_000,
+        offset % 60_000 / 1_000,
+        offset % 1_000,
+        hemisphere
+    )
+}
+
+fn fmt_precision(f: &mut fmt::Formatter<'_>, precision: u8) -> Result<(), fmt::Error> {
+    // invalid precisions can only be constructed with LOC::new, and are displayed as is
+    let centimeters = u64::from(precision >> 4) * 10_u64.pow(u32::from(precision & 0x0F));
+
+    if centimeters % 100 == 0 {
+        write!(f, "{}m", centimeters / 100)
+    } else {
+        write!(f, "{}.{:02}m", centimeters / 100, centimeters % 100)
+    }
+}
+
+/// Displays the LOC in the master file format, e.g. `42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m`
+impl fmt::Display for LOC {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt_coordinate(f, self.latitude, ['N', 'S'])?;
+        f.write_str(" ")?;
+        fmt_coordinate(f, self.longitude, ['E', 'W'])?;
+
+        let altitude = i64::from(self.altitude) - ALTITUDE_BASE;
+        write!(
+            f,
+            " {}{}.{:02}m ",
+            if altitude < 0 { "-" } else { "" },
+            altitude.abs() / 100,
+            altitude.abs() % 100
+        )?;
+
+        fmt_precision(f, self.size)?;
+        f.write_str(" ")?;
+        fmt_precision(f, self.horiz_pre)?;
+        f.write_str(" ")?;
+        fmt_precision(f, self.vert_pre)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        // RFC 1876, Section 4, cambridge-net.kei.com
+        let rdata = LOC::from_str("42 21 54 N 71 06 18 W -24m 30m").unwrap();
+        assert_eq!(rdata.size(), 0x33);
+        assert_eq!(rdata.horiz_pre(), DEFAULT_HORIZ_PRE);
+        assert_eq!(rdata.vert_pre(), DEFAULT_VERT_PRE);
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+        assert_eq!(bytes.len(), 16);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(bytes.len() as u16)).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+        assert_eq!(
+            read_rdata.to_string(),
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m"
+        );
+    }
+
+    #[test]
+    fn test_parse_forms() {
+        // RFC 1876, Section 4
+        assert_eq!(
+            LOC::from_str("42 21 43.952 N 71 5 6.344 W -24m 1m 200m")
+                .unwrap()
+                .to_string(),
+            "42 21 43.952 N 71 5 6.344 W -24.00m 1m 200m 10m"
+        );
+        assert_eq!(
+            LOC::from_str("52 14 05 N 00 08 50 E 10m")
+                .unwrap()
+                .to_string(),
+            "52 14 5.000 N 0 8 50.000 E 10.00m 1m 10000m 10m"
+        );
+        assert_eq!(
+            LOC::from_str("32 7 19 S 116 2 25 E 10m")
+                .unwrap()
+                .to_string(),
+            "32 7 19.000 S 116 2 25.000 E 10.00m 1m 10000m 10m"
+        );
+        assert_eq!(
+            LOC::from_str("42 21 28.764 N 71 00 51.617 W -44m 2000m")
+                .unwrap()
+                .to_string(),
+            "42 21 28.764 N 71 0 51.617 W -44.00m 2000m 10000m 10m"
+        );
+        assert_eq!(
+            LOC::from_str("90 S 180 e 0.5 0.10m 1 1")
+                .unwrap()
+                .to_string(),
+            "90 0 0.000 S 180 0 0.000 E 0.50m 0.10m 1m 1m"
+        );
+    }
+
+    #[test]
+    fn test_from_degrees() {
+        let rdata = LOC::from_degrees(42.365, -71.105, -24.0).unwrap();
+        assert_eq!(rdata, LOC::from_str("42 21 54 N 71 06 18 W -24m").unwrap());
+        assert!((rdata.latitude_degrees() - 42.365).abs() < 1e-9);
+        assert!((rdata.longitude_degrees() + 71.105).abs() < 1e-9);
+        assert!((rdata.altitude_meters() + 24.0).abs() < 1e-9);
+
+        let rdata = rdata.with_precision(3000, 20_000, 1_000);
+        assert_eq!(rdata.size(), 0x33);
+        assert_eq!(rdata.horiz_pre(), 0x24);
+        assert_eq!(rdata.vert_pre(), 0x13);
+
+        assert!(LOC::from_degrees(90.5, 0.0, 0.0).is_err());
+        assert!(LOC::from_degrees(0.0, -180.5, 0.0).is_err());
+        assert!(LOC::from_degrees(0.0, 0.0, -100_000.01).is_err());
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(encode_precision(0), 0x00);
+        assert_eq!(encode_precision(100), 0x12);
+        assert_eq!(encode_precision(1_000_000), 0x16);
+        assert_eq!(encode_precision(u64::MAX), 0x99);
+        assert_eq!(decode_precision(0x33).unwrap(), 3000);
+        assert!(decode_precision(0xA0).is_err());
+        assert!(decode_precision(0x0A).is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(LOC::from_str("91 N 0 E 0m").is_err());
+        assert!(LOC::from_str("90 1 N 0 E 0m").is_err());
+        assert!(LOC::from_str("42 60 N 0 E 0m").is_err());
+        assert!(LOC::from_str("42 0 60 N 0 E 0m").is_err());
+        assert!(LOC::from_str("42 0 0 E 0 N 0m").is_err());
+        assert!(LOC::from_str("42 N 181 E 0m").is_err());
+        assert!(LOC::from_str("42 N 0 E").is_err());
+        assert!(LOC::from_str("42 N 0 E -100000.01m").is_err());
+        assert!(LOC::from_str("42 N 0 E 0m 1m 1m 1m 1m").is_err());
+        assert!(LOC::from_str("42 N 0 E 0.001m").is_err());
+
+        // version 1
+        let bytes = [1, 0x12, 0x16, 0x13, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(&bytes);
+        assert!(read(&mut decoder, Restrict::new(16)).is_err());
+
+        // invalid size
+        let bytes = [0, 0xA2, 0x16, 0x13, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(&bytes);
+        assert!(read(&mut decoder, Restrict::new(16)).is_err());
+    }
+}
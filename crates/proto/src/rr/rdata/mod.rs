@@ -21,9 +21,13 @@
 // each of these module's has the parser for that rdata embedded, to keep the file sizes down...
 pub mod a;
 pub mod aaaa;
+pub mod apl;
 pub mod caa;
 pub mod csync;
+pub mod dhcid;
+pub mod eui;
 pub mod hinfo;
+pub mod loc;
 pub mod mx;
 pub mod name;
 pub mod naptr;
@@ -36,10 +40,16 @@ pub mod sshfp;
 pub mod svcb;
 pub mod tlsa;
 pub mod txt;
+pub mod uri;
+pub mod zonemd;
 
+pub use self::apl::{APLItem, APL};
 pub use self::caa::CAA;
 pub use self::csync::CSYNC;
+pub use self::dhcid::DHCID;
+pub use self::eui::{EUI48, EUI64};
 pub use self::hinfo::HINFO;
+pub use self::loc::LOC;
 pub use self::mx::MX;
 pub use self::naptr::NAPTR;
 pub use self::null::NULL;
@@ -51,3 +61,5 @@ pub use self::sshfp::SSHFP;
 pub use self::svcb::SVCB;
 pub use self::tlsa::TLSA;
 pub use self::txt::TXT;
+pub use self::uri::URI;
+pub use self::zonemd::ZONEMD;
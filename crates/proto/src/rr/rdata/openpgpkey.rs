@@ -76,4 +76,27 @@ impl fmt::Display for OPENPGPKEY {
     }
 }
 
-// TODO test
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        let rdata = OPENPGPKEY::new(b"trust_dns is awesome".to_vec());
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(bytes.len() as u16)).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+        assert_eq!(read_rdata.to_string(), "dHJ1c3RfZG5zIGlzIGF3ZXNvbWU=");
+    }
+}
@@ -18,6 +18,10 @@
 #![allow(clippy::use_self)]
 
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 
 #[cfg(feature = "serde-config")]
 use serde::{Deserialize, Serialize};
@@ -25,6 +29,7 @@ use serde::{Deserialize, Serialize};
 use tracing::warn;
 
 use crate::error::*;
+use crate::rr::Name;
 use crate::serialize::binary::*;
 
 #[cfg(feature = "dnssec")]
@@ -360,6 +365,9 @@ pub enum EdnsCode {
     /// [RFC 7901, CHAIN Query Requests in DNS, Optional](https://tools.ietf.org/html/rfc7901)
     Chain,
 
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedError,
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16),
 }
@@ -382,6 +390,7 @@ impl From<u16> for EdnsCode {
             11 => Self::Keepalive,
             12 => Self::Padding,
             13 => Self::Chain,
+            15 => Self::ExtendedError,
             _ => Self::Unknown(value),
         }
     }
@@ -404,6 +413,7 @@ impl From<EdnsCode> for u16 {
             EdnsCode::Keepalive => 11,
             EdnsCode::Padding => 12,
             EdnsCode::Chain => 13,
+            EdnsCode::ExtendedError => 15,
             EdnsCode::Unknown(value) => value,
         }
     }
@@ -433,6 +443,45 @@ pub enum EdnsOption {
     #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
     N3U(SupportedAlgorithms),
 
+    /// [RFC 7871, Client Subnet, Optional](https://tools.ietf.org/html/rfc7871)
+    Subnet(ClientSubnet),
+
+    /// [RFC 7830, The EDNS(0) Padding](https://tools.ietf.org/html/rfc7830), the number of
+    ///  padding octets
+    Padding(u16),
+
+    /// [RFC 7901, CHAIN Query Requests in DNS](https://tools.ietf.org/html/rfc7901), the closest
+    ///  trust point of the requestor, from which the DNSSEC chain of the answer is requested
+    ///
+    /// A server which supports the option returns it with the trust point the returned chain
+    ///  starts from.
+    Chain(Name),
+
+    /// [RFC 7828, The edns-tcp-keepalive EDNS0 Option](https://tools.ietf.org/html/rfc7828), the
+    ///  idle timeout of the connection in units of 100 milliseconds
+    ///
+    /// Clients send the option without a timeout, servers answer with the timeout they apply.
+    TcpKeepalive(Option<u16>),
+
+    /// [RFC 7873, Domain Name System (DNS) Cookies](https://tools.ietf.org/html/rfc7873)
+    ///
+    /// Clients send their cookie, with the server cookie of an earlier response of the server if
+    ///  they have one, servers answer with a new server cookie.
+    Cookie {
+        /// The client cookie
+        client: [u8; 8],
+        /// The server cookie, 8 to 32 bytes, empty if none
+        server: Vec<u8>,
+    },
+
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedError {
+        /// The reason for the error
+        info_code: ExtendedErrorCode,
+        /// Additional, human readable, information about the error, empty if none
+        extra_text: String,
+    },
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
@@ -445,6 +494,12 @@ impl EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.len(),
+            EdnsOption::Subnet(ref subnet) => subnet.len(),
+            EdnsOption::Padding(len) => len,
+            EdnsOption::Chain(ref name) => chain_len(name),
+            EdnsOption::TcpKeepalive(timeout) => timeout.map_or(0, |_| 2),
+            EdnsOption::Cookie { ref server, .. } => 8 + server.len() as u16,
+            EdnsOption::ExtendedError { ref extra_text, .. } => 2 + extra_text.len() as u16,
             EdnsOption::Unknown(_, ref data) => data.len() as u16, // TODO: should we verify?
         }
     }
@@ -456,6 +511,12 @@ impl EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.is_empty(),
+            EdnsOption::Subnet(_)
+            | EdnsOption::Chain(_)
+            | EdnsOption::Cookie { .. }
+            | EdnsOption::ExtendedError { .. } => false,
+            EdnsOption::Padding(len) => len == 0,
+            EdnsOption::TcpKeepalive(timeout) => timeout.is_none(),
             EdnsOption::Unknown(_, ref data) => data.is_empty(),
         }
     }
@@ -468,6 +529,28 @@ impl BinEncodable for EdnsOption {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.emit(encoder),
+            EdnsOption::Subnet(ref subnet) => subnet.emit(encoder),
+            EdnsOption::Padding(len) => encoder.emit_vec(&vec![0; usize::from(len)]),
+            // the trust point must not be compressed, RFC 7901 section 4
+            EdnsOption::Chain(ref name) => name.emit_as_canonical(encoder, true),
+            EdnsOption::TcpKeepalive(timeout) => match timeout {
+                Some(timeout) => encoder.emit_u16(timeout),
+                None => Ok(()),
+            },
+            EdnsOption::Cookie {
+                ref client,
+                ref server,
+            } => {
+                encoder.emit_vec(client)?;
+                encoder.emit_vec(server)
+            }
+            EdnsOption::ExtendedError {
+                info_code,
+                ref extra_text,
+            } => {
+                encoder.emit_u16(info_code.into())?;
+                encoder.emit_vec(extra_text.as_bytes())
+            }
             EdnsOption::Unknown(_, ref data) => encoder.emit_vec(data), // gah, clone needed or make a crazy api.
         }
     }
@@ -484,6 +567,38 @@ impl<'a> From<(EdnsCode, &'a [u8])> for EdnsOption {
             EdnsCode::DHU => Self::DHU(value.1.into()),
             #[cfg(feature = "dnssec")]
             EdnsCode::N3U => Self::N3U(value.1.into()),
+            EdnsCode::Subnet => match ClientSubnet::try_from(value.1) {
+                Ok(subnet) => Self::Subnet(subnet),
+                Err(e) => {
+                    warn!("invalid client subnet option: {}", e);
+                    Self::Unknown(value.0.into(), value.1.to_vec())
+                }
+            },
+            // the content of the padding is ignored, RFC 7830 section 3
+            EdnsCode::Padding => Self::Padding(value.1.len() as u16),
+            EdnsCode::Chain => match read_chain(value.1) {
+                Ok(name) => Self::Chain(name),
+                Err(e) => {
+                    warn!("invalid chain option: {}", e);
+                    Self::Unknown(value.0.into(), value.1.to_vec())
+                }
+            },
+            EdnsCode::Keepalive if value.1.is_empty() => Self::TcpKeepalive(None),
+            EdnsCode::Keepalive if value.1.len() == 2 => {
+                Self::TcpKeepalive(Some(u16::from_be_bytes([value.1[0], value.1[1]])))
+            }
+            // the server cookie is absent or 8 to 32 bytes, RFC 7873 section 4
+            EdnsCode::Cookie if value.1.len() == 8 || (16..=40).contains(&value.1.len()) => {
+                let (client, server) = value.1.split_at(8);
+                Self::Cookie {
+                    client: <[u8; 8]>::try_from(client).expect("client cookie is 8 bytes"),
+                    server: server.to_vec(),
+                }
+            }
+            EdnsCode::ExtendedError if value.1.len() >= 2 => Self::ExtendedError {
+                info_code: u16::from_be_bytes([value.1[0], value.1[1]]).into(),
+                extra_text: String::from_utf8_lossy(&value.1[2..]).into_owned(),
+            },
             _ => Self::Unknown(value.0.into(), value.1.to_vec()),
         }
     }
@@ -496,6 +611,34 @@ impl<'a> From<&'a EdnsOption> for Vec<u8> {
             EdnsOption::DAU(ref algorithms)
             | EdnsOption::DHU(ref algorithms)
             | EdnsOption::N3U(ref algorithms) => algorithms.into(),
+            EdnsOption::Subnet(ref subnet) => subnet.into(),
+            EdnsOption::Padding(len) => vec![0; usize::from(len)],
+            EdnsOption::Chain(ref name) => {
+                let mut bytes = Vec::with_capacity(usize::from(chain_len(name)));
+                let mut encoder = BinEncoder::new(&mut bytes);
+                name.emit_as_canonical(&mut encoder, true)
+                    .expect("chain trust point is always encodable");
+                bytes
+            }
+            EdnsOption::TcpKeepalive(timeout) => timeout
+                .map(|t| t.to_be_bytes().to_vec())
+                .unwrap_or_default(),
+            EdnsOption::Cookie {
+                ref client,
+                ref server,
+            } => {
+                let mut bytes = client.to_vec();
+                bytes.extend_from_slice(server);
+                bytes
+            }
+            EdnsOption::ExtendedError {
+                info_code,
+                ref extra_text,
+            } => {
+                let mut bytes = u16::from(info_code).to_be_bytes().to_vec();
+                bytes.extend_from_slice(extra_text.as_bytes());
+                bytes
+            }
             EdnsOption::Unknown(_, ref data) => data.clone(), // gah, clone needed or make a crazy api.
         }
     }
@@ -510,11 +653,445 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
             EdnsOption::DHU(..) => Self::DHU,
             #[cfg(feature = "dnssec")]
             EdnsOption::N3U(..) => Self::N3U,
+            EdnsOption::Subnet(..) => Self::Subnet,
+            EdnsOption::Padding(..) => Self::Padding,
+            EdnsOption::Chain(..) => Self::Chain,
+            EdnsOption::TcpKeepalive(..) => Self::Keepalive,
+            EdnsOption::Cookie { .. } => Self::Cookie,
+            EdnsOption::ExtendedError { .. } => Self::ExtendedError,
             EdnsOption::Unknown(code, _) => code.into(),
         }
     }
 }
 
+/// The length of the uncompressed wire form of the trust point of the CHAIN option
+fn chain_len(name: &Name) -> u16 {
+    (name.iter().map(|label| label.len() + 1).sum::<usize>() + 1) as u16
+}
+
+/// Reads the trust point of the CHAIN option, which must be the entire option data
+fn read_chain(data: &[u8]) -> ProtoResult<Name> {
+    let mut decoder = BinDecoder::new(data);
+    let name = Name::read(&mut decoder)?;
+
+    if !decoder.is_empty() {
+        return Err("trailing data after chain trust point".into());
+    }
+
+    if !name.is_fqdn() {
+        return Err("chain trust point is not fully qualified".into());
+    }
+
+    Ok(name)
+}
+
+/// [RFC 7871, Client Subnet](https://tools.ietf.org/html/rfc7871), the network of the client on
+///  whose behalf the query is sent
+///
+/// ```text
+/// 6.  Option Format
+///
+///                 +0 (MSB)                            +1 (LSB)
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    0: |                          OPTION-CODE                          |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    2: |                         OPTION-LENGTH                         |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    4: |                            FAMILY                             |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    6: |     SOURCE PREFIX-LENGTH      |     SCOPE PREFIX-LENGTH       |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    8: |                           ADDRESS...                          /
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix: u8,
+    scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    /// Creates the option for the network of the address
+    ///
+    /// The prefix lengths are limited to the length of the address, and the bits of the address
+    ///  beyond the source prefix length are cleared, as RFC 7871 requires.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - the address of the client, or of its network
+    /// * `source_prefix` - the number of significant bits of the address, e.g. 24 for IPv4
+    /// * `scope_prefix` - the number of bits the answer applies to, this must be 0 in queries
+    pub fn new(address: IpAddr, source_prefix: u8, scope_prefix: u8) -> Self {
+        let max_prefix = max_prefix(address);
+        let source_prefix = source_prefix.min(max_prefix);
+
+        Self {
+            address: truncate(address, source_prefix),
+            source_prefix,
+            scope_prefix: scope_prefix.min(max_prefix),
+        }
+    }
+
+    /// The network address, only the bits of the source prefix length may be set
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The number of significant bits of the address
+    pub fn source_prefix(&self) -> u8 {
+        self.source_prefix
+    }
+
+    /// The number of bits of the address the answer applies to, set by the server
+    pub fn scope_prefix(&self) -> u8 {
+        self.scope_prefix
+    }
+
+    fn family(&self) -> u16 {
+        match self.address {
+            IpAddr::V4(_) => 1,
+            IpAddr::V6(_) => 2,
+        }
+    }
+
+    /// The number of bytes of the address on the wire
+    fn address_len(&self) -> usize {
+        (usize::from(self.source_prefix) + 7) / 8
+    }
+
+    fn len(&self) -> u16 {
+        4 + self.address_len() as u16
+    }
+}
+
+impl BinEncodable for ClientSubnet {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        encoder.emit_u16(self.family())?;
+        encoder.emit_u8(self.source_prefix)?;
+        encoder.emit_u8(self.scope_prefix)?;
+
+        let address = match self.address {
+            IpAddr::V4(address) => address.octets().to_vec(),
+            IpAddr::V6(address) => address.octets().to_vec(),
+        };
+        encoder.emit_vec(&address[..self.address_len()])
+    }
+}
+
+impl<'a> From<&'a ClientSubnet> for Vec<u8> {
+    fn from(value: &'a ClientSubnet) -> Self {
+        let mut bytes = Vec::with_capacity(value.len() as usize);
+        let mut encoder = BinEncoder::new(&mut bytes);
+        value
+            .emit(&mut encoder)
+            .expect("client subnet is always encodable");
+        bytes
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ClientSubnet {
+    type Error = ProtoError;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        if value.len() < 4 {
+            return Err(ProtoError::from("client subnet option is too short"));
+        }
+
+        let family = u16::from_be_bytes([value[0], value[1]]);
+        let (source_prefix, scope_prefix) = (value[2], value[3]);
+        let address = &value[4..];
+
+        let address = match family {
+            1 if address.len() <= 4 => {
+                let mut octets = [0_u8; 4];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            2 if address.len() <= 16 => {
+                let mut octets = [0_u8; 16];
+                octets[..address.len()].copy_from_slice(address);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            1 | 2 => return Err(ProtoError::from("client subnet address is too long")),
+            _ => {
+                return Err(ProtoError::from(format!(
+                    "unsupported client subnet family: {}",
+                    family
+                )))
+            }
+        };
+
+        if source_prefix > max_prefix(address) || scope_prefix > max_prefix(address) {
+            return Err(ProtoError::from("client subnet prefix exceeds the address"));
+        }
+
+        Ok(Self::new(address, source_prefix, scope_prefix))
+    }
+}
+
+impl FromStr for ClientSubnet {
+    type Err = ProtoError;
+
+    /// Parses a network in CIDR notation, e.g. `192.0.2.0/24`, the scope prefix length is 0
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = s
+            .split_once('/')
+            .ok_or_else(|| ProtoError::from(format!("missing prefix length: {}", s)))?;
+        let address = IpAddr::from_str(address)
+            .map_err(|e| ProtoError::from(format!("invalid address {}: {}", address, e)))?;
+        let prefix = u8::from_str(prefix)
+            .ok()
+            .filter(|prefix| *prefix <= max_prefix(address))
+            .ok_or_else(|| ProtoError::from(format!("invalid prefix length: {}", prefix)))?;
+
+        Ok(Self::new(address, prefix, 0))
+    }
+}
+
+impl fmt::Display for ClientSubnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}",
+            self.address, self.source_prefix, self.scope_prefix
+        )
+    }
+}
+
+/// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914#section-2), the INFO-CODE
+///  of the option
+///
+/// ```text
+/// 2.  Extended DNS Error EDNS0 Option Format
+///
+///                                                 1   1   1   1   1   1
+///         0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    0: |                            OPTION-CODE                        |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    2: |                           OPTION-LENGTH                       |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    4: | INFO-CODE                                                     |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    6: / EXTRA-TEXT ...                                                /
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+///
+/// The codes are those of the IANA registry,
+///  <https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#extended-dns-error-codes>
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum ExtendedErrorCode {
+    /// 0, the error does not match any other code, see the extra text
+    Other,
+    /// 1, the DNSKEY RRset contains only unsupported DNSSEC algorithms
+    UnsupportedDnskeyAlgorithm,
+    /// 2, the DS RRset contains only unsupported digest types
+    UnsupportedDsDigestType,
+    /// 3, the answer is stale, [RFC 8767](https://tools.ietf.org/html/rfc8767)
+    StaleAnswer,
+    /// 4, the answer was forged by policy, e.g. of a blocklist
+    ForgedAnswer,
+    /// 5, the DNSSEC validation ended in the Indeterminate state
+    DnssecIndeterminate,
+    /// 6, the DNSSEC validation ended in the Bogus state
+    DnssecBogus,
+    /// 7, all RRSIGs have expired
+    SignatureExpired,
+    /// 8, all RRSIGs are not yet valid
+    SignatureNotYetValid,
+    /// 9, no DNSKEY matches the DS of the parent
+    DnskeyMissing,
+    /// 10, no RRSIGs were found for an RRset of a signed zone
+    RrsigsMissing,
+    /// 11, no DNSKEY has the Zone Key Bit set
+    NoZoneKeyBitSet,
+    /// 12, no NSEC or NSEC3 records proved the denial of existence
+    NsecMissing,
+    /// 13, the error was cached
+    CachedError,
+    /// 14, the server is not ready, e.g. it has not loaded its zones
+    NotReady,
+    /// 15, the domain is blocked by the operator of the server
+    Blocked,
+    /// 16, the domain is blocked because of a requirement of an external entity
+    Censored,
+    /// 17, the domain is blocked as requested by the client
+    Filtered,
+    /// 18, the client is not authorized to query the server
+    Prohibited,
+    /// 19, the NXDOMAIN answer is stale
+    StaleNxdomainAnswer,
+    /// 20, the server is not authoritative for the zone, and does not recurse
+    NotAuthoritative,
+    /// 21, the requested operation or query is not supported
+    NotSupported,
+    /// 22, no authoritative name server could be reached
+    NoReachableAuthority,
+    /// 23, an unrecoverable network error occurred
+    NetworkError,
+    /// 24, the data of the zone is invalid, e.g. it has expired
+    InvalidData,
+    /// 25, the RRSIG expired before it became valid
+    SignatureExpiredBeforeValid,
+    /// 26, the data was received too early, [RFC 9250](https://tools.ietf.org/html/rfc9250)
+    TooEarly,
+    /// 27, the NSEC3 iterations value is not supported, [RFC 9276](https://tools.ietf.org/html/rfc9276)
+    UnsupportedNsec3IterationsValue,
+    /// 28, the server can not conform to the policy, e.g. of a DNS filter
+    UnableToConformToPolicy,
+    /// 29, the answer was synthesized, e.g. from an aggressive NSEC cache
+    Synthesized,
+    /// 30, the query type is not allowed, e.g. a meta type
+    InvalidQueryType,
+    /// An unassigned or private use code
+    Unknown(u16),
+}
+
+impl From<u16> for ExtendedErrorCode {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => Self::Other,
+            1 => Self::UnsupportedDnskeyAlgorithm,
+            2 => Self::UnsupportedDsDigestType,
+            3 => Self::StaleAnswer,
+            4 => Self::ForgedAnswer,
+            5 => Self::DnssecIndeterminate,
+            6 => Self::DnssecBogus,
+            7 => Self::SignatureExpired,
+            8 => Self::SignatureNotYetValid,
+            9 => Self::DnskeyMissing,
+            10 => Self::RrsigsMissing,
+            11 => Self::NoZoneKeyBitSet,
+            12 => Self::NsecMissing,
+            13 => Self::CachedError,
+            14 => Self::NotReady,
+            15 => Self::Blocked,
+            16 => Self::Censored,
+            17 => Self::Filtered,
+            18 => Self::Prohibited,
+            19 => Self::StaleNxdomainAnswer,
+            20 => Self::NotAuthoritative,
+            21 => Self::NotSupported,
+            22 => Self::NoReachableAuthority,
+            23 => Self::NetworkError,
+            24 => Self::InvalidData,
+            25 => Self::SignatureExpiredBeforeValid,
+            26 => Self::TooEarly,
+            27 => Self::UnsupportedNsec3IterationsValue,
+            28 => Self::UnableToConformToPolicy,
+            29 => Self::Synthesized,
+            30 => Self::InvalidQueryType,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<ExtendedErrorCode> for u16 {
+    fn from(value: ExtendedErrorCode) -> Self {
+        match value {
+            ExtendedErrorCode::Other => 0,
+            ExtendedErrorCode::UnsupportedDnskeyAlgorithm => 1,
+            ExtendedErrorCode::UnsupportedDsDigestType => 2,
+            ExtendedErrorCode::StaleAnswer => 3,
+            ExtendedErrorCode::ForgedAnswer => 4,
+            ExtendedErrorCode::DnssecIndeterminate => 5,
+            ExtendedErrorCode::DnssecBogus => 6,
+            ExtendedErrorCode::SignatureExpired => 7,
+            ExtendedErrorCode::SignatureNotYetValid => 8,
+            ExtendedErrorCode::DnskeyMissing => 9,
+            ExtendedErrorCode::RrsigsMissing => 10,
+            ExtendedErrorCode::NoZoneKeyBitSet => 11,
+            ExtendedErrorCode::NsecMissing => 12,
+            ExtendedErrorCode::CachedError => 13,
+            ExtendedErrorCode::NotReady => 14,
+            ExtendedErrorCode::Blocked => 15,
+            ExtendedErrorCode::Censored => 16,
+            ExtendedErrorCode::Filtered => 17,
+            ExtendedErrorCode::Prohibited => 18,
+            ExtendedErrorCode::StaleNxdomainAnswer => 19,
+            ExtendedErrorCode::NotAuthoritative => 20,
+            ExtendedErrorCode::NotSupported => 21,
+            ExtendedErrorCode::NoReachableAuthority => 22,
+            ExtendedErrorCode::NetworkError => 23,
+            ExtendedErrorCode::InvalidData => 24,
+            ExtendedErrorCode::SignatureExpiredBeforeValid => 25,
+            ExtendedErrorCode::TooEarly => 26,
+            ExtendedErrorCode::UnsupportedNsec3IterationsValue => 27,
+            ExtendedErrorCode::UnableToConformToPolicy => 28,
+            ExtendedErrorCode::Synthesized => 29,
+            ExtendedErrorCode::InvalidQueryType => 30,
+            ExtendedErrorCode::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for ExtendedErrorCode {
+    /// The purpose of the code, as named in the IANA registry
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let purpose = match *self {
+            Self::Other => "Other Error",
+            Self::UnsupportedDnskeyAlgorithm => "Unsupported DNSKEY Algorithm",
+            Self::UnsupportedDsDigestType => "Unsupported DS Digest Type",
+            Self::StaleAnswer => "Stale Answer",
+            Self::ForgedAnswer => "Forged Answer",
+            Self::DnssecIndeterminate => "DNSSEC Indeterminate",
+            Self::DnssecBogus => "DNSSEC Bogus",
+            Self::SignatureExpired => "Signature Expired",
+            Self::SignatureNotYetValid => "Signature Not Yet Valid",
+            Self::DnskeyMissing => "DNSKEY Missing",
+            Self::RrsigsMissing => "RRSIGs Missing",
+            Self::NoZoneKeyBitSet => "No Zone Key Bit Set",
+            Self::NsecMissing => "NSEC Missing",
+            Self::CachedError => "Cached Error",
+            Self::NotReady => "Not Ready",
+            Self::Blocked => "Blocked",
+            Self::Censored => "Censored",
+            Self::Filtered => "Filtered",
+            Self::Prohibited => "Prohibited",
+            Self::StaleNxdomainAnswer => "Stale NXDOMAIN Answer",
+            Self::NotAuthoritative => "Not Authoritative",
+            Self::NotSupported => "Not Supported",
+            Self::NoReachableAuthority => "No Reachable Authority",
+            Self::NetworkError => "Network Error",
+            Self::InvalidData => "Invalid Data",
+            Self::SignatureExpiredBeforeValid => "Signature Expired before Valid",
+            Self::TooEarly => "Too Early",
+            Self::UnsupportedNsec3IterationsValue => "Unsupported NSEC3 Iterations Value",
+            Self::UnableToConformToPolicy => "Unable to conform to policy",
+            Self::Synthesized => "Synthesized",
+            Self::InvalidQueryType => "Invalid Query Type",
+            Self::Unknown(code) => return write!(f, "Unknown Error ({})", code),
+        };
+
+        f.write_str(purpose)
+    }
+}
+
+fn max_prefix(address: IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// Clears the bits of the address beyond the prefix length
+fn truncate(address: IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(address) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(address) & mask))
+        }
+        IpAddr::V6(address) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(address) & mask))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
@@ -557,13 +1134,177 @@ mod tests {
 
         let opt = read_rdata.unwrap();
         let mut options = HashMap::default();
-        options.insert(EdnsCode::Subnet, EdnsOption::Unknown(8, vec![0, 1, 0, 0]));
+        options.insert(
+            EdnsCode::Subnet,
+            EdnsOption::Subnet("0.0.0.0/0".parse().unwrap()),
+        );
         options.insert(
             EdnsCode::Cookie,
-            EdnsOption::Unknown(10, vec![0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f]),
+            EdnsOption::Cookie {
+                client: [0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f],
+                server: vec![],
+            },
         );
-        options.insert(EdnsCode::Keepalive, EdnsOption::Unknown(11, vec![]));
+        options.insert(EdnsCode::Keepalive, EdnsOption::TcpKeepalive(None));
         let options = OPT::new(options);
         assert_eq!(opt, options);
     }
+
+    #[test]
+    fn test_cookie() {
+        let option = EdnsOption::Cookie {
+            client: [1, 2, 3, 4, 5, 6, 7, 8],
+            server: vec![9; 16],
+        };
+        let bytes = Vec::<u8>::from(&option);
+        assert_eq!(bytes.len(), 24);
+        assert_eq!(option.len(), 24);
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Cookie, &bytes as &[u8])),
+            option
+        );
+
+        // server cookies are at least 8 bytes
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Cookie, &bytes[..12])),
+            EdnsOption::Unknown(10, bytes[..12].to_vec())
+        );
+    }
+
+    #[test]
+    fn test_tcp_keepalive() {
+        let option = EdnsOption::TcpKeepalive(Some(1200));
+        let bytes = Vec::<u8>::from(&option);
+        assert_eq!(bytes, vec![0x04, 0xb0]);
+        assert_eq!(option.len(), 2);
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Keepalive, &bytes as &[u8])),
+            option
+        );
+
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::TcpKeepalive(None));
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &rdata).unwrap();
+        assert_eq!(bytes, vec![0, 11, 0, 0]);
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let read_rdata = read(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+        assert_eq!(read_rdata, rdata);
+
+        // a timeout must be two octets long
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Keepalive, &[0_u8] as &[u8])),
+            EdnsOption::Unknown(11, vec![0])
+        );
+    }
+
+    #[test]
+    fn test_client_subnet() {
+        let subnet = ClientSubnet::new("192.0.2.123".parse().unwrap(), 24, 0);
+        assert_eq!(subnet.address(), "192.0.2.0".parse::<IpAddr>().unwrap());
+        assert_eq!(subnet, "192.0.2.0/24".parse().unwrap());
+
+        let bytes = Vec::<u8>::from(&subnet);
+        assert_eq!(bytes, vec![0, 1, 24, 0, 192, 0, 2]);
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Subnet, &bytes as &[u8])),
+            EdnsOption::Subnet(subnet)
+        );
+
+        let subnet = ClientSubnet::new("2001:db8:1:2::1".parse().unwrap(), 56, 48);
+        let bytes = Vec::<u8>::from(&subnet);
+        assert_eq!(bytes, vec![0, 2, 56, 48, 0x20, 0x01, 0x0d, 0xb8, 0, 1, 0]);
+        assert_eq!(ClientSubnet::try_from(&bytes as &[u8]).unwrap(), subnet);
+
+        // the address is longer than the family
+        assert!(ClientSubnet::try_from(&[0_u8, 1, 32, 0, 1, 2, 3, 4, 5][..]).is_err());
+        assert!("192.0.2.0/33".parse::<ClientSubnet>().is_err());
+    }
+
+    #[test]
+    fn test_chain() {
+        let option = EdnsOption::Chain(Name::from_ascii("example.com.").unwrap());
+        let bytes = Vec::<u8>::from(&option);
+        assert_eq!(bytes, b"\x07example\x03com\x00");
+        assert_eq!(option.len() as usize, bytes.len());
+        assert_eq!(EdnsOption::from((EdnsCode::Chain, &bytes as &[u8])), option);
+
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::Chain(Name::root()));
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &rdata).unwrap();
+        assert_eq!(bytes, vec![0, 13, 0, 1, 0]);
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let read_rdata = read(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+        assert_eq!(read_rdata, rdata);
+
+        // trailing data is not a valid trust point
+        assert_eq!(
+            EdnsOption::from((EdnsCode::Chain, &[0_u8, 0] as &[u8])),
+            EdnsOption::Unknown(13, vec![0, 0])
+        );
+    }
+
+    #[test]
+    fn test_extended_error() {
+        let mut rdata = OPT::default();
+        rdata.insert(EdnsOption::ExtendedError {
+            info_code: ExtendedErrorCode::Blocked,
+            extra_text: "blocked by policy".to_string(),
+        });
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &rdata).unwrap();
+        let bytes = encoder.into_bytes();
+        assert_eq!(&bytes[..6], &[0, 15, 0, 19, 0, 15]);
+        assert_eq!(&bytes[6..], b"blocked by policy");
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata = read(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+        assert_eq!(rdata, read_rdata);
+
+        // without extra text
+        let option = EdnsOption::from((EdnsCode::ExtendedError, &[0_u8, 3][..]));
+        assert_eq!(
+            option,
+            EdnsOption::ExtendedError {
+                info_code: ExtendedErrorCode::StaleAnswer,
+                extra_text: String::new(),
+            }
+        );
+        assert_eq!(option.len(), 2);
+        assert_eq!(Vec::<u8>::from(&option), vec![0, 3]);
+
+        // too short for the info code
+        assert_eq!(
+            EdnsOption::from((EdnsCode::ExtendedError, &[0_u8][..])),
+            EdnsOption::Unknown(15, vec![0])
+        );
+    }
+
+    #[test]
+    fn test_extended_error_code() {
+        for code in 0..=u16::from(ExtendedErrorCode::InvalidQueryType) {
+            let info_code = ExtendedErrorCode::from(code);
+            assert_ne!(info_code, ExtendedErrorCode::Unknown(code));
+            assert_eq!(u16::from(info_code), code);
+        }
+
+        assert_eq!(
+            ExtendedErrorCode::from(49152),
+            ExtendedErrorCode::Unknown(49152)
+        );
+        assert_eq!(ExtendedErrorCode::DnssecBogus.to_string(), "DNSSEC Bogus");
+        assert_eq!(
+            ExtendedErrorCode::Unknown(49152).to_string(),
+            "Unknown Error (49152)"
+        );
+    }
 }
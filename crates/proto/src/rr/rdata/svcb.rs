@@ -5,7 +5,10 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-//! SVCB records, see [draft-ietf-dnsop-svcb-https-03 SVCB and HTTPS RRs for DNS, February 2021](https://datatracker.ietf.org/doc/html/draft-ietf-dnsop-svcb-https-03)
+//! SVCB records, see [RFC 9460 SVCB and HTTPS RRs for DNS, November 2023](https://www.rfc-editor.org/rfc/rfc9460)
+//!
+//! The `dohpath` parameter is defined in [RFC 9461](https://www.rfc-editor.org/rfc/rfc9461), and
+//!  `ohttp` in [RFC 9540](https://www.rfc-editor.org/rfc/rfc9540).
 #![allow(clippy::use_self)]
 
 use std::{
@@ -151,6 +154,76 @@ impl SVCB {
     pub fn svc_params(&self) -> &[(SvcParamKey, SvcParamValue)] {
         &self.svc_params
     }
+
+    /// Returns true if the record is in AliasMode, i.e. the SvcPriority is 0
+    pub fn is_alias_mode(&self) -> bool {
+        self.svc_priority == 0
+    }
+
+    /// Returns true if the record is in ServiceMode, i.e. the SvcPriority is not 0
+    pub fn is_service_mode(&self) -> bool {
+        !self.is_alias_mode()
+    }
+
+    /// Validates that the record is well formed and self-consistent, RFC 9460 sections 2.4 and 8
+    ///
+    /// * an AliasMode record has no SvcParams
+    /// * the SvcParamKeys are in strictly increasing order, and do not include the reserved `key65535`
+    /// * each SvcParamValue has the format of its SvcParamKey
+    /// * the keys listed in `mandatory` are present, and `mandatory` does not list itself
+    /// * `no-default-alpn` is only present together with `alpn`
+    ///
+    /// Records which fail this validation are rejected when emitted.
+    pub fn validate(&self) -> ProtoResult<()> {
+        if self.is_alias_mode() && !self.svc_params.is_empty() {
+            return Err(ProtoError::from("SvcParams are not allowed in AliasMode"));
+        }
+
+        let mut last_key: Option<SvcParamKey> = None;
+        for (key, value) in self.svc_params.iter() {
+            if let Some(last_key) = last_key {
+                if *key <= last_key {
+                    return Err(ProtoError::from("SvcParams out of order"));
+                }
+            }
+            if *key == SvcParamKey::Key65535 {
+                return Err(ProtoError::from("key65535 is reserved"));
+            }
+            if !value.is_value_of(*key) {
+                return Err(ProtoError::from(format!(
+                    "SvcParamValue does not match the SvcParamKey {}",
+                    key
+                )));
+            }
+
+            last_key = Some(*key);
+        }
+
+        let has_key = |key: SvcParamKey| self.svc_params.iter().any(|(k, _)| *k == key);
+        for (_, value) in self.svc_params.iter() {
+            match value {
+                SvcParamValue::Mandatory(Mandatory(keys)) => {
+                    for key in keys {
+                        if *key == SvcParamKey::Mandatory {
+                            return Err(ProtoError::from("mandatory must not list itself"));
+                        }
+                        if !has_key(*key) {
+                            return Err(ProtoError::from(format!(
+                                "mandatory key {} is missing from the SvcParams",
+                                key
+                            )));
+                        }
+                    }
+                }
+                SvcParamValue::NoDefaultAlpn if !has_key(SvcParamKey::Alpn) => {
+                    return Err(ProtoError::from("no-default-alpn requires alpn"));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// ```text
@@ -183,6 +256,12 @@ impl SVCB {
 ///   | 6           | ipv6hint        | IPv6 address hints   | (This     |
 ///   |             |                 |                      | document) |
 ///   +-------------+-----------------+----------------------+-----------+
+///   | 7           | dohpath         | DNS over HTTPS path  | RFC 9461  |
+///   |             |                 | template             |           |
+///   +-------------+-----------------+----------------------+-----------+
+///   | 8           | ohttp           | Oblivious HTTP       | RFC 9540  |
+///   |             |                 | gateway support      |           |
+///   +-------------+-----------------+----------------------+-----------+
 ///   | 65280-65534 | keyNNNNN        | Private Use          | (This     |
 ///   |             |                 |                      | document) |
 ///   +-------------+-----------------+----------------------+-----------+
@@ -207,10 +286,14 @@ pub enum SvcParamKey {
     Port,
     /// IPv4 address hints
     Ipv4Hint,
-    /// Encrypted ClientHello info
+    /// Encrypted ClientHello info, `ech`
     EchConfig,
     /// IPv6 address hints
     Ipv6Hint,
+    /// URI template of a DNS over HTTPS endpoint
+    DohPath,
+    /// Oblivious HTTP gateway support
+    Ohttp,
     /// Private Use
     Key(u16),
     /// Reserved ("Invalid key")
@@ -229,6 +312,8 @@ impl From<u16> for SvcParamKey {
             4 => Self::Ipv4Hint,
             5 => Self::EchConfig,
             6 => Self::Ipv6Hint,
+            7 => Self::DohPath,
+            8 => Self::Ohttp,
             65280..=65534 => Self::Key(val),
             65535 => Self::Key65535,
             _ => Self::Unknown(val),
@@ -246,6 +331,8 @@ impl From<SvcParamKey> for u16 {
             SvcParamKey::Ipv4Hint => 4,
             SvcParamKey::EchConfig => 5,
             SvcParamKey::Ipv6Hint => 6,
+            SvcParamKey::DohPath => 7,
+            SvcParamKey::Ohttp => 8,
             SvcParamKey::Key(val) => val,
             SvcParamKey::Key65535 => 65535,
             SvcParamKey::Unknown(val) => val,
@@ -277,11 +364,13 @@ impl fmt::Display for SvcParamKey {
             Self::NoDefaultAlpn => f.write_str("no-default-alpn")?,
             Self::Port => f.write_str("port")?,
             Self::Ipv4Hint => f.write_str("ipv4hint")?,
-            Self::EchConfig => f.write_str("echconfig")?,
+            Self::EchConfig => f.write_str("ech")?,
             Self::Ipv6Hint => f.write_str("ipv6hint")?,
+            Self::DohPath => f.write_str("dohpath")?,
+            Self::Ohttp => f.write_str("ohttp")?,
             Self::Key(val) => write!(f, "key{}", val)?,
             Self::Key65535 => f.write_str("key65535")?,
-            Self::Unknown(val) => write!(f, "unknown{}", val)?,
+            Self::Unknown(val) => write!(f, "key{}", val)?,
         }
 
         Ok(())
@@ -312,8 +401,11 @@ impl std::str::FromStr for SvcParamKey {
             "no-default-alpn" => Self::NoDefaultAlpn,
             "port" => Self::Port,
             "ipv4hint" => Self::Ipv4Hint,
-            "echconfig" => Self::EchConfig,
+            // echconfig is the name in early drafts
+            "ech" | "echconfig" => Self::EchConfig,
             "ipv6hint" => Self::Ipv6Hint,
+            "dohpath" => Self::DohPath,
+            "ohttp" => Self::Ohttp,
             "key65535" => Self::Key65535,
             _ => parse_unknown_key(s)?,
         };
@@ -422,6 +514,15 @@ pub enum SvcParamValue {
     EchConfig(EchConfig),
     /// See `IpHint`
     Ipv6Hint(IpHint<Ipv6Addr>),
+    /// See `DohPath`
+    DohPath(DohPath),
+    /// [RFC 9540 Discovery of Oblivious Services via SVCB, February 2024](https://www.rfc-editor.org/rfc/rfc9540#section-4)
+    ///
+    /// ```text
+    ///   Both the presentation and wire-format values for the "ohttp"
+    ///   parameter MUST be empty.
+    /// ```
+    Ohttp,
     /// Unparsed network data. Refer to documents on the associated key value
     ///
     /// This will be left as is when read off the wire, and encoded in bas64
@@ -453,13 +554,7 @@ impl SvcParamValue {
             SvcParamKey::Mandatory => Self::Mandatory(Mandatory::read(&mut decoder)?),
             SvcParamKey::Alpn => Self::Alpn(Alpn::read(&mut decoder)?),
             // should always be empty
-            SvcParamKey::NoDefaultAlpn => {
-                if len > 0 {
-                    return Err(ProtoError::from("Alpn expects at least one value"));
-                }
-
-                Self::NoDefaultAlpn
-            }
+            SvcParamKey::NoDefaultAlpn => Self::NoDefaultAlpn,
             // The wire format of the SvcParamValue is the corresponding 2 octet
             // numeric value in network byte order.
             SvcParamKey::Port => {
@@ -469,13 +564,48 @@ impl SvcParamValue {
             SvcParamKey::Ipv4Hint => Self::Ipv4Hint(IpHint::<Ipv4Addr>::read(&mut decoder)?),
             SvcParamKey::EchConfig => Self::EchConfig(EchConfig::read(&mut decoder)?),
             SvcParamKey::Ipv6Hint => Self::Ipv6Hint(IpHint::<Ipv6Addr>::read(&mut decoder)?),
-            SvcParamKey::Key(_) | SvcParamKey::Key65535 | SvcParamKey::Unknown(_) => {
+            SvcParamKey::DohPath => Self::DohPath(DohPath::read(&mut decoder)?),
+            // should always be empty
+            SvcParamKey::Ohttp => Self::Ohttp,
+            SvcParamKey::Key65535 => return Err(ProtoError::from("key65535 is reserved")),
+            SvcParamKey::Key(_) | SvcParamKey::Unknown(_) => {
                 Self::Unknown(Unknown::read(&mut decoder)?)
             }
         };
 
+        // the SvcParamValue must be exactly filled by the value
+        if decoder.peek().is_some() {
+            return Err(ProtoError::from(format!(
+                "unexpected data in the SvcParamValue of {}",
+                key
+            )));
+        }
+
         Ok(value)
     }
+
+    /// Returns true if this is a value for the key
+    fn is_value_of(&self, key: SvcParamKey) -> bool {
+        matches!(
+            (key, self),
+            (SvcParamKey::Mandatory, Self::Mandatory(_))
+                | (SvcParamKey::Alpn, Self::Alpn(_))
+                | (SvcParamKey::NoDefaultAlpn, Self::NoDefaultAlpn)
+                | (SvcParamKey::Port, Self::Port(_))
+                | (SvcParamKey::Ipv4Hint, Self::Ipv4Hint(_))
+                | (SvcParamKey::EchConfig, Self::EchConfig(_))
+                | (SvcParamKey::Ipv6Hint, Self::Ipv6Hint(_))
+                | (SvcParamKey::DohPath, Self::DohPath(_))
+                | (SvcParamKey::Ohttp, Self::Ohttp)
+                | (SvcParamKey::Key(_), Self::Unknown(_))
+                | (SvcParamKey::Unknown(_), Self::Unknown(_))
+        )
+    }
+
+    /// Returns true if the value is empty in both presentation and wire format
+    fn is_empty(&self) -> bool {
+        matches!(self, Self::NoDefaultAlpn | Self::Ohttp)
+    }
 }
 
 impl BinEncodable for SvcParamValue {
@@ -494,6 +624,8 @@ impl BinEncodable for SvcParamValue {
             Self::Ipv4Hint(ip_hint) => ip_hint.emit(encoder)?,
             Self::EchConfig(ech_config) => ech_config.emit(encoder)?,
             Self::Ipv6Hint(ip_hint) => ip_hint.emit(encoder)?,
+            Self::DohPath(doh_path) => doh_path.emit(encoder)?,
+            Self::Ohttp => (),
             Self::Unknown(unknown) => unknown.emit(encoder)?,
         }
 
@@ -516,6 +648,8 @@ impl fmt::Display for SvcParamValue {
             Self::Ipv4Hint(ip_hint) => write!(f, "{}", ip_hint)?,
             Self::EchConfig(ech_config) => write!(f, "{}", ech_config)?,
             Self::Ipv6Hint(ip_hint) => write!(f, "{}", ip_hint)?,
+            Self::DohPath(doh_path) => write!(f, "{}", doh_path)?,
+            Self::Ohttp => (),
             Self::Unknown(unknown) => write!(f, "{}", unknown)?,
         }
 
@@ -576,10 +710,18 @@ impl<'r> BinDecodable<'r> for Mandatory {
     ///    network byte order, concatenated in ascending order.
     /// ```
     fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
-        let mut keys = Vec::with_capacity(1);
+        let mut keys: Vec<SvcParamKey> = Vec::with_capacity(1);
 
         while decoder.peek().is_some() {
-            keys.push(SvcParamKey::read(decoder)?);
+            let key = SvcParamKey::read(decoder)?;
+            if keys.last().map_or(false, |last| *last >= key) {
+                return Err(ProtoError::from("Mandatory keys out of order"));
+            }
+            if key == SvcParamKey::Mandatory {
+                return Err(ProtoError::from("Mandatory must not list itself"));
+            }
+
+            keys.push(key);
         }
 
         if keys.is_empty() {
@@ -600,11 +742,16 @@ impl BinEncodable for Mandatory {
     /// ```
     fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
         if self.0.is_empty() {
-            return Err(ProtoError::from("Alpn expects at least one value"));
+            return Err(ProtoError::from("Mandatory expects at least one value"));
         }
 
-        // TODO: order by key value
-        for key in self.0.iter() {
+        let mut keys = self.0.clone();
+        keys.sort();
+        for (i, key) in keys.iter().enumerate() {
+            if i > 0 && keys[i - 1] == *key {
+                return Err(ProtoError::from("Mandatory keys must not be repeated"));
+            }
+
             key.emit(encoder)?
         }
 
@@ -627,9 +774,11 @@ impl fmt::Display for Mandatory {
     ///
     ///    echconfig=... key65333=ex1 key65444=ex2 mandatory=key65444,echconfig
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        for key in self.0.iter() {
-            // TODO: confirm in the RFC that trailing commas are ok
-            write!(f, "{},", key)?;
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", key)?;
         }
 
         Ok(())
@@ -747,6 +896,10 @@ impl<'r> BinDecodable<'r> for Alpn {
 
         while decoder.peek().is_some() {
             let alpn = decoder.read_character_data()?.unverified(/*will rely on string parser*/);
+            if alpn.is_empty() {
+                return Err(ProtoError::from("Alpn ids must not be empty"));
+            }
+
             let alpn = String::from_utf8(alpn.to_vec())?;
             alpns.push(alpn);
         }
@@ -771,6 +924,10 @@ impl BinEncodable for Alpn {
         }
 
         for alpn in self.0.iter() {
+            if alpn.is_empty() {
+                return Err(ProtoError::from("Alpn ids must not be empty"));
+            }
+
             encoder.emit_character_data(alpn)?
         }
 
@@ -781,72 +938,76 @@ impl BinEncodable for Alpn {
 impl fmt::Display for Alpn {
     ///   The presentation "value" SHALL be a comma-separated list
     ///   (Appendix A.1) of one or more "alpn-id"s.
+    ///
+    /// Commas and backslashes within an alpn-id are escaped with a backslash, and the list is
+    ///  then written as a character-string, e.g. `alpn="f\\\\oo\\,bar,h2"` for `f\oo,bar` and `h2`.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        for alpn in self.0.iter() {
-            // TODO: confirm in the RFC that trailing commas are ok
-            write!(f, "{},", alpn)?;
+        let mut value = Vec::new();
+        for (i, alpn) in self.0.iter().enumerate() {
+            if i > 0 {
+                value.push(b',');
+            }
+            for b in alpn.bytes() {
+                if b == b',' || b == b'\\' {
+                    value.push(b'\\');
+                }
+                value.push(b);
+            }
         }
 
-        Ok(())
+        fmt_char_string(f, &value)
     }
 }
 
+/// [RFC 9460 SVCB and HTTPS RRs for DNS, November 2023](https://www.rfc-editor.org/rfc/rfc9460#section-7.3)
+///
 /// ```text
-/// 9.  SVCB/HTTPS RR parameter for ECH configuration
+/// 7.3.  "ech"
 ///
-///   The SVCB "echconfig" parameter is defined for conveying the ECH
-///   configuration of an alternative endpoint.  In wire format, the value
-///   of the parameter is an ECHConfigs vector [ECH], including the
-///   redundant length prefix.  In presentation format, the value is a
-///   single ECHConfigs encoded in Base64 [base64].  Base64 is used here to
-///   simplify integration with TLS server software.  To enable simpler
-///   parsing, this SvcParam MUST NOT contain escape sequences.
+///   The SvcParamKey to enable Encrypted ClientHello (ECH) is "ech".  Its
+///   value is defined in Section 9.  It is applicable to most TLS-based
+///   protocols.
 ///
-///   When ECH is in use, the TLS ClientHello is divided into an
-///   unencrypted "outer" and an encrypted "inner" ClientHello.  The outer
-///   ClientHello is an implementation detail of ECH, and its contents are
-///   controlled by the ECHConfig in accordance with [ECH].  The inner
-///   ClientHello is used for establishing a connection to the service, so
-///   its contents may be influenced by other SVCB parameters.  For
-///   example, the requirements on the ProtocolNameList in Section 6.1
-///   apply only to the inner ClientHello.  Similarly, it is the inner
-///   ClientHello whose Server Name Indication identifies the desired
+///   In wire format, the value of the parameter is an ECHConfigList
+///   [ECH], including the redundant length prefix.  In presentation
+///   format, the value is the ECHConfigList in Base 64 Encoding
+///   (Section 4 of [RFC4648]).  Base 64 is used here to simplify
+///   integration with TLS server software.  To enable simpler parsing,
+///   this SvcParam MUST NOT contain escape sequences.
 /// ```
+///
+/// The data is the complete ECHConfigList, including its length prefix.
 #[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
 #[derive(PartialEq, Eq, Hash, Clone)]
 #[repr(transparent)]
 pub struct EchConfig(pub Vec<u8>);
 
 impl<'r> BinDecodable<'r> for EchConfig {
-    /// In wire format, the value
-    ///   of the parameter is an ECHConfigs vector (ECH), including the
-    ///   redundant length prefix (a 2 octet field containing the length of the SvcParamValue
-    ///   as an integer between 0 and 65535 in network byte order).
+    /// In wire format, the value of the parameter is an ECHConfigList (ECH), including the
+    ///   redundant length prefix (a 2 octet field containing the length of the remainder of
+    ///   the list as an integer in network byte order).
     fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
-        let redundant_len = decoder
-            .read_u16()?
-            .map(|len| len as usize)
-            .verify_unwrap(|len| *len <= decoder.len())
-            .map_err(|_| ProtoError::from("ECH value length exceeds max size of u16::MAX"))?;
-
-        let data =
-            decoder.read_vec(redundant_len)?.unverified(/*up to consumer to validate this data*/);
+        let data = decoder.read_vec(decoder.len())?.unverified(/*verified below*/);
+
+        let redundant_len = data
+            .get(..2)
+            .map(|len| u16::from_be_bytes([len[0], len[1]]) as usize)
+            .ok_or_else(|| ProtoError::from("ECHConfigList is missing its length"))?;
+        if redundant_len != data.len() - 2 {
+            return Err(ProtoError::from(format!(
+                "ECHConfigList length ({}) does not match the SvcParamValue ({})",
+                redundant_len,
+                data.len() - 2
+            )));
+        }
 
         Ok(Self(data))
     }
 }
 
 impl BinEncodable for EchConfig {
-    /// In wire format, the value
-    ///   of the parameter is an ECHConfigs vector (ECH), including the
-    ///   redundant length prefix (a 2 octet field containing the length of the SvcParamValue
-    ///   as an integer between 0 and 65535 in network byte order).
+    /// The ECHConfigList is emitted as is, it already contains the redundant length
     fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
-        let len = u16::try_from(self.0.len())
-            .map_err(|_| ProtoError::from("ECH value length exceeds max size of u16::MAX"))?;
-
-        // redundant length...
-        encoder.emit_u16(len)?;
         encoder.emit_vec(&self.0)?;
 
         Ok(())
@@ -857,17 +1018,11 @@ impl fmt::Display for EchConfig {
     /// As the documentation states, the presentation format (what this function outputs) must be a BASE64 encoded string.
     ///   trust-dns will encode to BASE64 during formatting of the internal data, and output the BASE64 value.
     ///
-    /// [draft-ietf-dnsop-svcb-https-03 SVCB and HTTPS RRs for DNS, February 2021](https://datatracker.ietf.org/doc/html/draft-ietf-dnsop-svcb-https-03#section-9)
+    /// [RFC 9460 SVCB and HTTPS RRs for DNS, November 2023](https://www.rfc-editor.org/rfc/rfc9460#section-7.3)
     /// ```text
-    /// In presentation format, the value is a
-    ///   single ECHConfigs encoded in Base64 [base64].  Base64 is used here to
-    ///   simplify integration with TLS server software.  To enable simpler
-    ///   parsing, this SvcParam MUST NOT contain escape sequences.
+    ///   In presentation format, the value is the ECHConfigList in Base 64
+    ///   Encoding (Section 4 of [RFC4648]).
     /// ```
-    ///
-    /// *note* while the on the wire the EchConfig has a redundant length,
-    ///   the RFC is not explicit about including it in the BASE64 encoded value,
-    ///   trust-dns will encode the data as it is stored, i.e. without the length encoding.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         write!(f, "\"{}\"", data_encoding::BASE64.encode(&self.0))
     }
@@ -945,6 +1100,10 @@ where
             ips.push(T::read(decoder)?)
         }
 
+        if ips.is_empty() {
+            return Err(ProtoError::from("IpHint expects at least one value"));
+        }
+
         Ok(Self(ips))
     }
 }
@@ -958,6 +1117,10 @@ where
     ///   represents an unordered collection, and clients SHOULD pick addresses
     ///   to use in a random order.  An empty list of addresses is invalid.
     fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        if self.0.is_empty() {
+            return Err(ProtoError::from("IpHint expects at least one value"));
+        }
+
         for ip in self.0.iter() {
             ip.emit(encoder)?;
         }
@@ -975,14 +1138,99 @@ where
     ///   in standard textual format [RFC 5952](https://tools.ietf.org/html/rfc5952).  To enable simpler parsing,
     ///   this SvcParamValue MUST NOT contain escape sequences.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        for ip in self.0.iter() {
-            write!(f, "{},", ip)?;
+        for (i, ip) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", ip)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// [RFC 9461 Service Binding Mapping for DNS Servers, November 2023](https://www.rfc-editor.org/rfc/rfc9461#section-5)
+///
+/// ```text
+/// 5.  New SvcParamKey: dohpath
+///
+///   "dohpath" is a single-valued SvcParamKey whose value (in both
+///   presentation format and wire format) MUST be a URI Template in
+///   relative form ([RFC6570], Section 1.1) encoded in UTF-8 [RFC3629].
+///   If the "alpn" SvcParam indicates support for HTTP, "dohpath" MUST be
+///   present.  The URI Template MUST contain a "dns" variable, and MUST be
+///   chosen such that the result after DoH URI Template expansion
+///   (Section 6 of [RFC8484]) is always a valid and functional ":path"
+///   value ([RFC9113], Section 8.3.1).
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[repr(transparent)]
+pub struct DohPath(pub String);
+
+impl DohPath {
+    /// The path of the URI template without any expressions, e.g. `/dns-query` for `/dns-query{?dns}`
+    pub fn path(&self) -> &str {
+        self.0.split('{').next().unwrap_or_default()
+    }
+
+    /// Returns an error if this is not a relative URI template with a `dns` variable
+    fn validate(&self) -> ProtoResult<()> {
+        if !self.0.starts_with('/') {
+            return Err(ProtoError::from(format!(
+                "dohpath must be a relative path: {}",
+                self.0
+            )));
+        }
+
+        // the variables of each expression, e.g. `{?dns,other*}`
+        let has_dns_variable = self
+            .0
+            .split('{')
+            .skip(1)
+            .filter_map(|expression| expression.split('}').next())
+            .flat_map(|expression| {
+                expression
+                    .trim_start_matches(|c| "+#./;?&".contains(c))
+                    .split(',')
+            })
+            .any(|variable| variable.trim_end_matches('*').split(':').next() == Some("dns"));
+        if !has_dns_variable {
+            return Err(ProtoError::from(format!(
+                "dohpath must contain a dns variable: {}",
+                self.0
+            )));
         }
 
         Ok(())
     }
 }
 
+impl<'r> BinDecodable<'r> for DohPath {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        let template = decoder.read_vec(decoder.len())?.unverified(/*verified below*/);
+        let doh_path = Self(String::from_utf8(template)?);
+        doh_path.validate()?;
+
+        Ok(doh_path)
+    }
+}
+
+impl BinEncodable for DohPath {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        self.validate()?;
+        encoder.emit_vec(self.0.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for DohPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt_char_string(f, self.0.as_bytes())
+    }
+}
+
 /// [draft-ietf-dnsop-svcb-https-03 SVCB and HTTPS RRs for DNS, February 2021](https://datatracker.ietf.org/doc/html/draft-ietf-dnsop-svcb-https-03#section-2.1)
 /// ```text
 /// Unrecognized keys are represented in presentation format as
@@ -1017,8 +1265,9 @@ impl<'r> BinDecodable<'r> for Unknown {
 }
 
 impl BinEncodable for Unknown {
+    /// The value is used as is for the wire format
     fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
-        encoder.emit_character_data(&self.0)?;
+        encoder.emit_vec(&self.0)?;
 
         Ok(())
     }
@@ -1026,11 +1275,36 @@ impl BinEncodable for Unknown {
 
 impl fmt::Display for Unknown {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        // TODO: this needs to be properly encoded
-        write!(f, "\"{}\",", String::from_utf8_lossy(&self.0))?;
+        fmt_char_string(f, &self.0)
+    }
+}
 
-        Ok(())
+/// Writes the value in the character-string presentation format, RFC 9460 Appendix A
+///
+/// Values which contain whitespace or special characters are quoted, `"` and `\` are escaped, and
+///  non-printable octets are written as `\DDD`.
+fn fmt_char_string(f: &mut fmt::Formatter<'_>, value: &[u8]) -> Result<(), fmt::Error> {
+    let quote = value.is_empty()
+        || value
+            .iter()
+            .any(|b| !b.is_ascii_graphic() || b"\"\\;()".contains(b));
+
+    if quote {
+        f.write_str("\"")?;
+    }
+    for b in value {
+        match *b {
+            b'"' | b'\\' => write!(f, "\\{}", *b as char)?,
+            b' ' => f.write_str(" ")?,
+            b if b.is_ascii_graphic() => write!(f, "{}", b as char)?,
+            b => write!(f, "\\{:03}", b)?,
+        }
     }
+    if quote {
+        f.write_str("\"")?;
+    }
+
+    Ok(())
 }
 
 /// Reads the SVCB record from the decoder.
@@ -1049,6 +1323,13 @@ impl fmt::Display for Unknown {
 ///   If any RRs are malformed, the client MUST reject the entire RRSet and
 ///   fall back to non-SVCB connection establishment.
 /// ```
+///
+/// The SvcParams of AliasMode records are validated, and then discarded, RFC 9460 section 2.4.2:
+///
+/// ```text
+///   In AliasMode, records SHOULD NOT include any SvcParams, and recipients
+///   MUST ignore any SvcParams that are present.
+/// ```
 pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<SVCB> {
     let start_index = decoder.index();
 
@@ -1087,6 +1368,14 @@ pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoR
             .unverified(); // valid len
     }
 
+    if remainder_len > 0 {
+        return Err(ProtoError::from("end of RDATA within a SvcParam"));
+    }
+
+    if svc_priority == 0 {
+        svc_params.clear();
+    }
+
     Ok(SVCB {
         svc_priority,
         target_name,
@@ -1095,22 +1384,17 @@ pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoR
 }
 
 /// Write the RData from the given Decoder
+///
+/// The record is validated first, see [`SVCB::validate`]
 pub fn emit(encoder: &mut BinEncoder<'_>, svcb: &SVCB) -> ProtoResult<()> {
+    svcb.validate()?;
+
     svcb.svc_priority.emit(encoder)?;
     svcb.target_name.emit(encoder)?;
 
-    let mut last_key: Option<SvcParamKey> = None;
     for (key, param) in svcb.svc_params.iter() {
-        if let Some(last_key) = last_key {
-            if key <= &last_key {
-                return Err(ProtoError::from("SvcParams out of order"));
-            }
-        }
-
         key.emit(encoder)?;
         param.emit(encoder)?;
-
-        last_key = Some(*key);
     }
 
     Ok(())
@@ -1135,7 +1419,12 @@ impl fmt::Display for SVCB {
         )?;
 
         for (key, param) in self.svc_params.iter() {
-            write!(f, " {key}={param}", key = key, param = param)?
+            // an empty value is written as the standalone key
+            if param.is_empty() {
+                write!(f, " {key}", key = key)?
+            } else {
+                write!(f, " {key}={param}", key = key, param = param)?
+            }
         }
 
         Ok(())
@@ -1155,6 +1444,9 @@ mod tests {
         assert_eq!(SvcParamKey::Ipv4Hint, 4.into());
         assert_eq!(SvcParamKey::EchConfig, 5.into());
         assert_eq!(SvcParamKey::Ipv6Hint, 6.into());
+        assert_eq!(SvcParamKey::DohPath, 7.into());
+        assert_eq!(SvcParamKey::Ohttp, 8.into());
+        assert_eq!(SvcParamKey::Unknown(9), 9.into());
         assert_eq!(SvcParamKey::Key(65280), 65280.into());
         assert_eq!(SvcParamKey::Key(65534), 65534.into());
         assert_eq!(SvcParamKey::Key65535, 65535.into());
@@ -1170,6 +1462,8 @@ mod tests {
         assert_eq!(u16::from(SvcParamKey::Ipv4Hint), 4);
         assert_eq!(u16::from(SvcParamKey::EchConfig), 5);
         assert_eq!(u16::from(SvcParamKey::Ipv6Hint), 6);
+        assert_eq!(u16::from(SvcParamKey::DohPath), 7);
+        assert_eq!(u16::from(SvcParamKey::Ohttp), 8);
         assert_eq!(u16::from(SvcParamKey::Key(65280)), 65280);
         assert_eq!(u16::from(SvcParamKey::Key(65534)), 65534);
         assert_eq!(u16::from(SvcParamKey::Key65535), 65535);
@@ -1200,7 +1494,7 @@ mod tests {
             vec![],
         ));
         test_encode_decode(SVCB::new(
-            0,
+            1,
             Name::from_utf8(".").unwrap(),
             vec![(
                 SvcParamKey::Alpn,
@@ -1208,7 +1502,7 @@ mod tests {
             )],
         ));
         test_encode_decode(SVCB::new(
-            0,
+            1,
             Name::from_utf8("example.com.").unwrap(),
             vec![
                 (
@@ -1227,7 +1521,7 @@ mod tests {
     #[should_panic]
     fn test_encode_decode_svcb_bad_order() {
         test_encode_decode(SVCB::new(
-            0,
+            1,
             Name::from_utf8(".").unwrap(),
             vec![
                 (
@@ -1242,6 +1536,379 @@ mod tests {
         ));
     }
 
+    fn decode(bytes: &[u8]) -> ProtoResult<SVCB> {
+        let mut decoder = BinDecoder::new(bytes);
+        read(&mut decoder, Restrict::new(bytes.len() as u16))
+    }
+
+    fn encode(svcb: &SVCB) -> ProtoResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, svcb)?;
+        Ok(bytes)
+    }
+
+    const FOO_EXAMPLE_COM: &[u8] = b"\x03foo\x07example\x03com\x00";
+
+    fn wire(priority: u16, target: &[u8], params: &[u8]) -> Vec<u8> {
+        let mut bytes = priority.to_be_bytes().to_vec();
+        bytes.extend_from_slice(target);
+        bytes.extend_from_slice(params);
+        bytes
+    }
+
+    /// The test vectors of RFC 9460 appendix D
+    #[test]
+    fn test_rfc9460_vectors() {
+        let vectors = vec![
+            // D.1 AliasMode
+            (
+                wire(0, FOO_EXAMPLE_COM, &[]),
+                SVCB::new(0, Name::from_ascii("foo.example.com.").unwrap(), vec![]),
+                "0 foo.example.com.",
+            ),
+            // D.2 ServiceMode, use the ownername
+            (
+                wire(1, b"\x00", &[]),
+                SVCB::new(1, Name::root(), vec![]),
+                "1 .",
+            ),
+            // a port
+            (
+                wire(16, FOO_EXAMPLE_COM, b"\x00\x03\x00\x02\x00\x35"),
+                SVCB::new(
+                    16,
+                    Name::from_ascii("foo.example.com.").unwrap(),
+                    vec![(SvcParamKey::Port, SvcParamValue::Port(53))],
+                ),
+                "16 foo.example.com. port=53",
+            ),
+            // a generic key and value
+            (
+                wire(1, FOO_EXAMPLE_COM, b"\x02\x9b\x00\x05hello"),
+                SVCB::new(
+                    1,
+                    Name::from_ascii("foo.example.com.").unwrap(),
+                    vec![(
+                        SvcParamKey::Unknown(667),
+                        SvcParamValue::Unknown(Unknown(b"hello".to_vec())),
+                    )],
+                ),
+                "1 foo.example.com. key667=hello",
+            ),
+            // a generic key and quoted value with a decimal escape
+            (
+                wire(1, FOO_EXAMPLE_COM, b"\x02\x9b\x00\x09hello\xd2qoo"),
+                SVCB::new(
+                    1,
+                    Name::from_ascii("foo.example.com.").unwrap(),
+                    vec![(
+                        SvcParamKey::Unknown(667),
+                        SvcParamValue::Unknown(Unknown(b"hello\xd2qoo".to_vec())),
+                    )],
+                ),
+                "1 foo.example.com. key667=\"hello\\210qoo\"",
+            ),
+            // two IPv6 hints in quoted presentation format
+            (
+                wire(
+                    1,
+                    FOO_EXAMPLE_COM,
+                    &[
+                        0x00, 0x06, 0x00, 0x20, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00,
+                        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x20, 0x01, 0x0d, 0xb8,
+                        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x53, 0x00, 0x01,
+                    ],
+                ),
+                SVCB::new(
+                    1,
+                    Name::from_ascii("foo.example.com.").unwrap(),
+                    vec![(
+                        SvcParamKey::Ipv6Hint,
+                        SvcParamValue::Ipv6Hint(IpHint(vec![
+                            "2001:db8::1".parse().unwrap(),
+                            "2001:db8::53:1".parse().unwrap(),
+                        ])),
+                    )],
+                ),
+                "1 foo.example.com. ipv6hint=2001:db8::1,2001:db8::53:1",
+            ),
+            // an IPv6 hint using the embedded IPv4 syntax
+            (
+                wire(
+                    1,
+                    b"\x07example\x03com\x00",
+                    &[
+                        0x00, 0x06, 0x00, 0x10, 0x20, 0x01, 0x0d, 0xb8, 0x01, 0x22, 0x03, 0x44,
+                        0x00, 0x00, 0x00, 0x00, 0xc0, 0x00, 0x02, 0x21,
+                    ],
+                ),
+                SVCB::new(
+                    1,
+                    Name::from_ascii("example.com.").unwrap(),
+                    vec![(
+                        SvcParamKey::Ipv6Hint,
+                        SvcParamValue::Ipv6Hint(IpHint(vec!["2001:db8:122:344::192.0.2.33"
+                            .parse()
+                            .unwrap()])),
+                    )],
+                ),
+                "1 example.com. ipv6hint=2001:db8:122:344::c000:221",
+            ),
+            // SvcParamKey ordering is arbitrary in presentation format but sorted in wire format
+            (
+                wire(
+                    16,
+                    b"\x03foo\x07example\x03org\x00",
+                    b"\x00\x00\x00\x04\x00\x01\x00\x04\x00\x01\x00\x09\x02h2\x05h3-19\x00\x04\x00\x04\xc0\x00\x02\x01",
+                ),
+                SVCB::new(
+                    16,
+                    Name::from_ascii("foo.example.org.").unwrap(),
+                    vec![
+                        (
+                            SvcParamKey::Mandatory,
+                            SvcParamValue::Mandatory(Mandatory(vec![
+                                SvcParamKey::Alpn,
+                                SvcParamKey::Ipv4Hint,
+                            ])),
+                        ),
+                        (
+                            SvcParamKey::Alpn,
+                            SvcParamValue::Alpn(Alpn(vec!["h2".to_string(), "h3-19".to_string()])),
+                        ),
+                        (
+                            SvcParamKey::Ipv4Hint,
+                            SvcParamValue::Ipv4Hint(IpHint(vec![Ipv4Addr::new(192, 0, 2, 1)])),
+                        ),
+                    ],
+                ),
+                "16 foo.example.org. mandatory=alpn,ipv4hint alpn=h2,h3-19 ipv4hint=192.0.2.1",
+            ),
+            // an alpn value with an escaped comma and an escaped backslash in two presentation formats
+            (
+                wire(
+                    16,
+                    b"\x03foo\x07example\x03org\x00",
+                    b"\x00\x01\x00\x0c\x08f\\oo,bar\x02h2",
+                ),
+                SVCB::new(
+                    16,
+                    Name::from_ascii("foo.example.org.").unwrap(),
+                    vec![(
+                        SvcParamKey::Alpn,
+                        SvcParamValue::Alpn(Alpn(vec!["f\\oo,bar".to_string(), "h2".to_string()])),
+                    )],
+                ),
+                r#"16 foo.example.org. alpn="f\\\\oo\\,bar,h2""#,
+            ),
+        ];
+
+        for (bytes, svcb, presentation) in vectors {
+            assert_eq!(decode(&bytes).expect("failed to decode"), svcb);
+            assert_eq!(encode(&svcb).expect("failed to encode"), bytes);
+            assert_eq!(svcb.to_string(), presentation);
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        let malformed: &[&[u8]] = &[
+            // duplicate keys
+            b"\x00\x03\x00\x02\x00\x35\x00\x03\x00\x02\x00\x36",
+            // keys out of order
+            b"\x00\x03\x00\x02\x00\x35\x00\x01\x00\x03\x02h2",
+            // no-default-alpn with a value
+            b"\x00\x02\x00\x01\x00",
+            // ohttp with a value
+            b"\x00\x08\x00\x01\x00",
+            // port with trailing data
+            b"\x00\x03\x00\x03\x00\x35\x00",
+            // empty ipv6hint
+            b"\x00\x06\x00\x00",
+            // empty alpn-id
+            b"\x00\x01\x00\x03\x00\x02h2",
+            // mandatory listing itself
+            b"\x00\x00\x00\x02\x00\x00",
+            // mandatory out of order
+            b"\x00\x00\x00\x04\x00\x04\x00\x01",
+            // the reserved key65535
+            b"\xff\xff\x00\x00",
+            // the end of the RDATA within a SvcParam
+            b"\x00\x03\x00",
+            // an ECHConfigList with a bad length
+            b"\x00\x05\x00\x04\x00\x03\xfe\x0d",
+            // a dohpath without a dns variable
+            b"\x00\x07\x00\x0a/dns-query",
+        ];
+
+        for params in malformed {
+            assert!(
+                decode(&wire(1, FOO_EXAMPLE_COM, params)).is_err(),
+                "expected an error for {:?}",
+                params
+            );
+        }
+    }
+
+    #[test]
+    fn test_alias_mode_ignores_params() {
+        let svcb = decode(&wire(0, FOO_EXAMPLE_COM, b"\x00\x03\x00\x02\x00\x35")).unwrap();
+
+        assert!(svcb.is_alias_mode());
+        assert!(svcb.svc_params().is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let name = Name::from_ascii("foo.example.com.").unwrap();
+        let alpn = (
+            SvcParamKey::Alpn,
+            SvcParamValue::Alpn(Alpn(vec!["h2".to_string()])),
+        );
+        let port = (SvcParamKey::Port, SvcParamValue::Port(443));
+        let mandatory = |keys| {
+            (
+                SvcParamKey::Mandatory,
+                SvcParamValue::Mandatory(Mandatory(keys)),
+            )
+        };
+
+        let invalid = vec![
+            // AliasMode with SvcParams
+            SVCB::new(0, name.clone(), vec![port.clone()]),
+            // a mandatory key which is missing
+            SVCB::new(
+                1,
+                name.clone(),
+                vec![mandatory(vec![SvcParamKey::Alpn]), port.clone()],
+            ),
+            // mandatory listing itself
+            SVCB::new(
+                1,
+                name.clone(),
+                vec![mandatory(vec![SvcParamKey::Mandatory]), port.clone()],
+            ),
+            // no-default-alpn without alpn
+            SVCB::new(
+                1,
+                name.clone(),
+                vec![(SvcParamKey::NoDefaultAlpn, SvcParamValue::NoDefaultAlpn)],
+            ),
+            // a value of another key
+            SVCB::new(
+                1,
+                name.clone(),
+                vec![(SvcParamKey::Port, SvcParamValue::Ohttp)],
+            ),
+            // the reserved key65535
+            SVCB::new(
+                1,
+                name.clone(),
+                vec![(
+                    SvcParamKey::Key65535,
+                    SvcParamValue::Unknown(Unknown(vec![])),
+                )],
+            ),
+        ];
+        for svcb in invalid {
+            assert!(svcb.validate().is_err(), "expected {} to be invalid", svcb);
+            assert!(encode(&svcb).is_err());
+        }
+
+        let valid = SVCB::new(
+            1,
+            name,
+            vec![
+                mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Port]),
+                alpn,
+                (SvcParamKey::NoDefaultAlpn, SvcParamValue::NoDefaultAlpn),
+                port,
+            ],
+        );
+        valid.validate().unwrap();
+        test_encode_decode(valid);
+    }
+
+    #[test]
+    fn test_doh_path_and_ohttp() {
+        let svcb = SVCB::new(
+            1,
+            Name::from_ascii("doh.example.net.").unwrap(),
+            vec![
+                (
+                    SvcParamKey::Alpn,
+                    SvcParamValue::Alpn(Alpn(vec!["h2".to_string(), "h3".to_string()])),
+                ),
+                (
+                    SvcParamKey::DohPath,
+                    SvcParamValue::DohPath(DohPath("/dns-query{?dns}".to_string())),
+                ),
+                (SvcParamKey::Ohttp, SvcParamValue::Ohttp),
+            ],
+        );
+
+        assert_eq!(
+            svcb.to_string(),
+            "1 doh.example.net. alpn=h2,h3 dohpath=/dns-query{?dns} ohttp"
+        );
+        test_encode_decode(svcb);
+
+        assert_eq!(DohPath("/dns-query{?dns}".to_string()).path(), "/dns-query");
+        assert!(DohPath("/q{?name,dns*}".to_string()).validate().is_ok());
+        assert!(DohPath("/q{?dnssec}".to_string()).validate().is_err());
+        assert!(DohPath("https://example.net/q{?dns}".to_string())
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_ech() {
+        let ech = b"\x00\x04\xfe\x0d\x00\x00".to_vec();
+        let svcb = SVCB::new(
+            1,
+            Name::root(),
+            vec![(
+                SvcParamKey::EchConfig,
+                SvcParamValue::EchConfig(EchConfig(ech.clone())),
+            )],
+        );
+
+        let bytes = encode(&svcb).unwrap();
+        assert_eq!(&bytes[3..], b"\x00\x05\x00\x06\x00\x04\xfe\x0d\x00\x00");
+        assert_eq!(decode(&bytes).unwrap(), svcb);
+        assert_eq!(
+            svcb.to_string(),
+            format!("1 . ech=\"{}\"", data_encoding::BASE64.encode(&ech))
+        );
+    }
+
+    #[test]
+    fn test_key_presentation() {
+        for (name, key) in [
+            ("mandatory", SvcParamKey::Mandatory),
+            ("alpn", SvcParamKey::Alpn),
+            ("no-default-alpn", SvcParamKey::NoDefaultAlpn),
+            ("port", SvcParamKey::Port),
+            ("ipv4hint", SvcParamKey::Ipv4Hint),
+            ("ech", SvcParamKey::EchConfig),
+            ("ipv6hint", SvcParamKey::Ipv6Hint),
+            ("dohpath", SvcParamKey::DohPath),
+            ("ohttp", SvcParamKey::Ohttp),
+            ("key667", SvcParamKey::Unknown(667)),
+            ("key65333", SvcParamKey::Key(65333)),
+        ] {
+            assert_eq!(key.to_string(), name);
+            assert_eq!(name.parse::<SvcParamKey>().unwrap(), key);
+        }
+
+        assert_eq!(
+            "echconfig".parse::<SvcParamKey>().unwrap(),
+            SvcParamKey::EchConfig
+        );
+        assert_eq!("key7".parse::<SvcParamKey>().unwrap(), SvcParamKey::DohPath);
+    }
+
     #[test]
     fn test_no_panic() {
         const BUF: &[u8] = &[
@@ -0,0 +1,190 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! URI records for publishing mappings from hostnames to URIs
+
+use std::fmt;
+
+#[cfg(feature = "serde-config")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::*;
+use crate::serialize::binary::*;
+
+/// [RFC 7553, The Uniform Resource Identifier (URI) DNS Resource Record](https://tools.ietf.org/html/rfc7553#section-4.5)
+///
+/// ```text
+/// 4.5.  URI RDATA Wire Format
+///
+///    The RDATA for a URI RR consists of a 2-octet Priority field, a
+///    2-octet Weight field, and a variable-length Target field.
+///
+///    Priority and Weight are unsigned integers in network byte order.
+///
+///    The remaining data in the RDATA contains the Target field.  The
+///    Target field contains the URI as a sequence of octets (without the
+///    enclosing double-quote characters used in the presentation format).
+///
+///    The length of the Target field MUST be greater than zero.
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          Priority             |          Weight               |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    /                                                               /
+///    /                             Target                            /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct URI {
+    priority: u16,
+    weight: u16,
+    target: String,
+}
+
+impl URI {
+    /// Creates a new URI record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - lower values are preferred, like SRV
+    /// * `weight` - relative weight for entries with the same priority, like SRV
+    /// * `target` - the URI, e.g. `ftp://ftp1.example.com/public`
+    pub fn new(priority: u16, weight: u16, target: String) -> Self {
+        Self {
+            priority,
+            weight,
+            target,
+        }
+    }
+
+    /// [RFC 7553](https://tools.ietf.org/html/rfc7553#section-4.2)
+    ///
+    /// ```text
+    ///    This field holds the priority of the target URI in this RR.  Its
+    ///    range is 0-65535.  A client MUST attempt to contact the URI with the
+    ///    lowest-numbered priority it can reach; URIs with the same priority
+    ///    SHOULD be selected according to probabilities defined by the weight
+    ///    field.
+    /// ```
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// [RFC 7553](https://tools.ietf.org/html/rfc7553#section-4.3)
+    ///
+    /// ```text
+    ///    This field holds the server selection mechanism.  The weight field
+    ///    specifies a relative weight for entries with the same priority.
+    ///    Larger weights SHOULD be given a proportionately higher probability
+    ///    of being selected.  The range of this number is 0-65535.
+    /// ```
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// [RFC 7553](https://tools.ietf.org/html/rfc7553#section-4.4)
+    ///
+    /// ```text
+    ///    This field holds the URI of the target, enclosed in double-quote
+    ///    characters ('"'), where the URI is as specified in RFC 3986
+    ///    [RFC3986].
+    /// ```
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<URI> {
+    let priority = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+    let weight = decoder.read_u16()?.unverified(/*any u16 is valid*/);
+
+    // the remaining data is for the target
+    let target_len = rdata_length
+        .map(|u| u as usize)
+        .checked_sub(4)
+        .map_err(|_| ProtoError::from("invalid rdata length in URI"))?
+        .verify_unwrap(|len| *len > 0)
+        .map_err(|_| ProtoError::from("URI target must not be empty"))?;
+    let target = decoder.read_vec(target_len)?.unverified(/*verified as utf8*/);
+    let target =
+        String::from_utf8(target).map_err(|_| ProtoError::from("URI target is not valid UTF-8"))?;
+
+    Ok(URI {
+        priority,
+        weight,
+        target,
+    })
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder<'_>, uri: &URI) -> ProtoResult<()> {
+    encoder.emit_u16(uri.priority)?;
+    encoder.emit_u16(uri.weight)?;
+    encoder.emit_vec(uri.target.as_bytes())?;
+    Ok(())
+}
+
+/// [RFC 7553](https://tools.ietf.org/html/rfc7553#section-4.4)
+///
+/// ```text
+///    The presentation format of the RDATA portion of a URI RR is
+///    "Priority Weight Target", with the Target enclosed in double-quote
+///    characters, e.g.:
+///
+///    _ftp._tcp    IN URI 10 1 "ftp://ftp1.example.com/public"
+/// ```
+impl fmt::Display for URI {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{priority} {weight} \"{target}\"",
+            priority = self.priority,
+            weight = self.weight,
+            target = self.target,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::dbg_macro, clippy::print_stdout)]
+
+    use super::*;
+
+    #[test]
+    fn test() {
+        let rdata = URI::new(10, 1, "ftp://ftp1.example.com/public".to_string());
+
+        let mut bytes = Vec::new();
+        let mut encoder: BinEncoder<'_> = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+        let bytes = encoder.into_bytes();
+
+        println!("bytes: {:?}", bytes);
+
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(bytes);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(bytes.len() as u16)).expect("Decoding error");
+        assert_eq!(rdata, read_rdata);
+        assert_eq!(
+            read_rdata.to_string(),
+            "10 1 \"ftp://ftp1.example.com/public\""
+        );
+    }
+
+    #[test]
+    fn test_empty_target() {
+        let bytes = [0, 10, 0, 1];
+        let mut decoder: BinDecoder<'_> = BinDecoder::new(&bytes);
+        assert!(read(&mut decoder, Restrict::new(4)).is_err());
+    }
+}
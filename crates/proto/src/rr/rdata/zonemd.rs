@@ -0,0 +1,360 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! ZONEMD records for message digests of zones
+#![allow(clippy::use_self)]
+
+use std::fmt;
+
+#[cfg(feature = "serde-config")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::*;
+use crate::rr::rdata::sshfp::HEX;
+use crate::serialize::binary::*;
+
+/// The minimum length of the digest, RFC 8976 section 2.2.4
+pub const MIN_DIGEST_LEN: usize = 12;
+
+/// [RFC 8976](https://tools.ietf.org/html/rfc8976#section-2.2)
+///
+/// ```text
+/// 2.2.  ZONEMD RDATA Wire Format
+///
+///    The ZONEMD RDATA wire format is encoded as follows:
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                             Serial                            |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |    Scheme     |Hash Algorithm |                               |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+///    |                             Digest                            |
+///    /                                                               /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 2.2.1.  The Serial Field
+///
+///    The Serial field is a 32-bit unsigned integer in network byte order.
+///    It is the serial number from the zone's SOA record ([RFC1035],
+///    Section 3.3.13) for which the zone digest was generated.
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ZONEMD {
+    serial: u32,
+    scheme: Scheme,
+    hash_algorithm: HashAlgorithm,
+    digest: Vec<u8>,
+}
+
+impl ZONEMD {
+    /// Creates a new ZONEMD record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the serial of the SOA record of the zone which was digested.
+    /// * `scheme` - the methods by which data is collated and presented to the hash function.
+    /// * `hash_algorithm` - the cryptographic hash algorithm of the digest.
+    /// * `digest` - the digest of the zone.
+    pub fn new(
+        serial: u32,
+        scheme: Scheme,
+        hash_algorithm: HashAlgorithm,
+        digest: Vec<u8>,
+    ) -> Self {
+        Self {
+            serial,
+            scheme,
+            hash_algorithm,
+            digest,
+        }
+    }
+
+    /// The serial of the SOA record of the zone which was digested.
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// The methods by which data is collated and presented to the hash function.
+    pub fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    /// The cryptographic hash algorithm of the digest.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// The digest of the zone.
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+}
+
+/// ```text
+/// 2.2.2.  The Scheme Field
+///
+///    The Scheme field is an 8-bit unsigned integer that identifies the
+///    methods by which data is collated and presented as input to the
+///    hashing function.
+///
+///    Herein, SIMPLE, with Scheme value 1, is the only standardized Scheme
+///    defined for ZONEMD records and it MUST be implemented.
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Scheme {
+    /// Reserved value
+    Reserved,
+
+    /// The SIMPLE scheme, RFC 8976 section 3.3
+    Simple,
+
+    /// Unassigned value, including the private use values 240 to 254
+    Unassigned(u8),
+}
+
+impl From<u8> for Scheme {
+    fn from(scheme: u8) -> Self {
+        match scheme {
+            0 => Self::Reserved,
+            1 => Self::Simple,
+            _ => Self::Unassigned(scheme),
+        }
+    }
+}
+
+impl From<Scheme> for u8 {
+    fn from(scheme: Scheme) -> Self {
+        match scheme {
+            Scheme::Reserved => 0,
+            Scheme::Simple => 1,
+            Scheme::Unassigned(scheme) => scheme,
+        }
+    }
+}
+
+/// ```text
+/// 2.2.3.  The Hash Algorithm Field
+///
+///    The Hash Algorithm field is an 8-bit unsigned integer that identifies
+///    the cryptographic hash algorithm used to construct the digest.
+///
+///    Herein, SHA384 [RFC6234], with Hash Algorithm value 1, is the only
+///    standardized Hash Algorithm defined for ZONEMD records that MUST be
+///    implemented.  When SHA384 is used, the size of the Digest field is 48
+///    octets.  The result of the SHA384 digest algorithm MUST NOT be
+///    truncated, and the entire 48-octet digest is published in the ZONEMD
+///    record.
+///
+///    SHA512 [RFC6234], with Hash Algorithm value 2, is also defined for
+///    ZONEMD records and SHOULD be implemented.  When SHA512 is used, the
+///    size of the Digest field is 64 octets.
+/// ```
+#[cfg_attr(feature = "serde-config", derive(Deserialize, Serialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum HashAlgorithm {
+    /// Reserved value
+    Reserved,
+
+    /// SHA-384
+    SHA384,
+
+    /// SHA-512
+    SHA512,
+
+    /// Unassigned value, including the private use values 240 to 254
+    Unassigned(u8),
+}
+
+impl HashAlgorithm {
+    /// The length of the digest, `None` if the algorithm is unknown
+    pub fn digest_len(self) -> Option<usize> {
+        match self {
+            Self::SHA384 => Some(48),
+            Self::SHA512 => Some(64),
+            Self::Reserved | Self::Unassigned(_) => None,
+        }
+    }
+}
+
+impl From<u8> for HashAlgorithm {
+    fn from(alg: u8) -> Self {
+        match alg {
+            0 => Self::Reserved,
+            1 => Self::SHA384,
+            2 => Self::SHA512,
+            _ => Self::Unassigned(alg),
+        }
+    }
+}
+
+impl From<HashAlgorithm> for u8 {
+    fn from(alg: HashAlgorithm) -> Self {
+        match alg {
+            HashAlgorithm::Reserved => 0,
+            HashAlgorithm::SHA384 => 1,
+            HashAlgorithm::SHA512 => 2,
+            HashAlgorithm::Unassigned(alg) => alg,
+        }
+    }
+}
+
+/// Read the RData from the given decoder.
+///
+/// ```text
+/// 2.2.4.  The Digest Field
+///
+///    The Digest field MUST NOT be shorter than 12 octets.  Digests for the
+///    SHA384 and SHA512 hash algorithms specified herein are never
+///    truncated.
+/// ```
+pub fn read(decoder: &mut BinDecoder<'_>, rdata_length: Restrict<u16>) -> ProtoResult<ZONEMD> {
+    let serial = decoder.read_u32()?.unverified();
+    let scheme = decoder.read_u8()?.unverified().into();
+    let hash_algorithm = decoder.read_u8()?.unverified().into();
+    let digest_len = rdata_length
+        .map(|l| l as usize)
+        .checked_sub(6)
+        .map_err(|_| ProtoError::from("invalid rdata length in ZONEMD"))?
+        .verify_unwrap(|l| *l >= MIN_DIGEST_LEN)
+        .map_err(|l| ProtoError::from(format!("ZONEMD digest too short: {}", l)))?;
+    let digest = decoder.read_vec(digest_len)?.unverified();
+    Ok(ZONEMD::new(serial, scheme, hash_algorithm, digest))
+}
+
+/// Write the RData using the given encoder.
+pub fn emit(encoder: &mut BinEncoder<'_>, zonemd: &ZONEMD) -> ProtoResult<()> {
+    encoder.emit_u32(zonemd.serial())?;
+    encoder.emit_u8(zonemd.scheme().into())?;
+    encoder.emit_u8(zonemd.hash_algorithm().into())?;
+    encoder.emit_vec(zonemd.digest())
+}
+
+/// [RFC 8976](https://tools.ietf.org/html/rfc8976#section-2.3)
+///
+/// ```text
+/// 2.3.  ZONEMD Presentation Format
+///
+///    The presentation format of the RDATA portion is as follows:
+///
+///    *  The Serial field is represented as an unsigned decimal integer.
+///
+///    *  The Scheme field is represented as an unsigned decimal integer.
+///
+///    *  The Hash Algorithm field is represented as an unsigned decimal
+///       integer.
+///
+///    *  The Digest is represented as a sequence of case-insensitive
+///       hexadecimal digits.  Whitespace is allowed within the hexadecimal
+///       text.
+/// ```
+impl fmt::Display for ZONEMD {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{serial} {scheme} {hash_algorithm} {digest}",
+            serial = self.serial,
+            scheme = u8::from(self.scheme),
+            hash_algorithm = u8::from(self.hash_algorithm),
+            digest = HEX.encode(&self.digest),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_scheme() {
+        assert_eq!(Scheme::Reserved, 0.into());
+        assert_eq!(Scheme::Simple, 1.into());
+        assert_eq!(Scheme::Unassigned(240), 240.into());
+
+        assert_eq!(0u8, Scheme::Reserved.into());
+        assert_eq!(1u8, Scheme::Simple.into());
+        assert_eq!(240u8, Scheme::Unassigned(240).into());
+    }
+
+    #[test]
+    fn read_hash_algorithm() {
+        assert_eq!(HashAlgorithm::Reserved, 0.into());
+        assert_eq!(HashAlgorithm::SHA384, 1.into());
+        assert_eq!(HashAlgorithm::SHA512, 2.into());
+        assert_eq!(HashAlgorithm::Unassigned(3), 3.into());
+
+        assert_eq!(0u8, HashAlgorithm::Reserved.into());
+        assert_eq!(1u8, HashAlgorithm::SHA384.into());
+        assert_eq!(2u8, HashAlgorithm::SHA512.into());
+        assert_eq!(3u8, HashAlgorithm::Unassigned(3).into());
+    }
+
+    fn test_encode_decode(rdata: ZONEMD, result: &[u8]) {
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        emit(&mut encoder, &rdata).expect("failed to emit ZONEMD");
+        let bytes = encoder.into_bytes();
+        assert_eq!(bytes, &result);
+
+        let mut decoder = BinDecoder::new(result);
+        let read_rdata =
+            read(&mut decoder, Restrict::new(result.len() as u16)).expect("failed to read ZONEMD");
+        assert_eq!(read_rdata, rdata)
+    }
+
+    #[test]
+    fn test_encode_decode_zonemd() {
+        test_encode_decode(
+            ZONEMD::new(
+                2018031900,
+                Scheme::Simple,
+                HashAlgorithm::SHA384,
+                vec![0xa5; 48],
+            ),
+            &[
+                0x78, 0x48, 0xb9, 0x1c, 1, 1, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5,
+                0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5,
+                0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5,
+                0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5, 0xa5,
+            ],
+        );
+        test_encode_decode(
+            ZONEMD::new(
+                1,
+                Scheme::Unassigned(240),
+                HashAlgorithm::Unassigned(241),
+                vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+            ),
+            &[0, 0, 0, 1, 240, 241, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        );
+    }
+
+    #[test]
+    fn test_short_digest() {
+        let bytes = [0, 0, 0, 1, 1, 1, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+        let mut decoder = BinDecoder::new(&bytes);
+        assert!(read(&mut decoder, Restrict::new(bytes.len() as u16)).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let zonemd = ZONEMD::new(
+            2018031900,
+            Scheme::Simple,
+            HashAlgorithm::SHA512,
+            vec![0xAB; 12],
+        );
+        assert_eq!(
+            zonemd.to_string(),
+            "2018031900 1 2 abababababababababababab"
+        );
+    }
+}
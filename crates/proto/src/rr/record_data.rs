@@ -23,7 +23,8 @@ use tracing::{trace, warn};
 use super::domain::Name;
 use super::rdata;
 use super::rdata::{
-    CAA, CSYNC, HINFO, MX, NAPTR, NULL, OPENPGPKEY, OPT, SOA, SRV, SSHFP, SVCB, TLSA, TXT,
+    APL, CAA, CSYNC, DHCID, EUI48, EUI64, HINFO, LOC, MX, NAPTR, NULL, OPENPGPKEY, OPT, SOA, SRV,
+    SSHFP, SVCB, TLSA, TXT, URI, ZONEMD,
 };
 use super::record_type::RecordType;
 use crate::error::*;
@@ -105,6 +106,23 @@ pub enum RData {
     /// ```
     ANAME(Name),
 
+    /// [RFC 3123, A DNS RR Type for Lists of Address Prefixes](https://tools.ietf.org/html/rfc3123#section-4)
+    ///
+    /// ```text
+    ///    The RDATA section consists of zero or more items (<apitem>) of the
+    ///    form
+    ///
+    ///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       |                          ADDRESSFAMILY                |
+    ///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       |             PREFIX    | N|         AFDLENGTH  |
+    ///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       /                            AFDPART            /
+    ///       |                                               |
+    ///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /// ```
+    APL(APL),
+
     /// ```text
     /// -- RFC 6844          Certification Authority Authorization     January 2013
     ///
@@ -187,6 +205,29 @@ pub enum RData {
     /// ```
     CSYNC(CSYNC),
 
+    /// [RFC 4701, A DNS Resource Record (RR) for Encoding DHCP Information](https://tools.ietf.org/html/rfc4701#section-3.3)
+    ///
+    /// ```text
+    ///    The DHCID RDATA has the following structure:
+    ///
+    ///       < identifier-type > < digest-type > < digest >
+    /// ```
+    DHCID(DHCID),
+
+    /// [RFC 7043, Resource Records for EUI-48 and EUI-64 Addresses in the DNS](https://tools.ietf.org/html/rfc7043#section-3.1)
+    ///
+    /// ```text
+    ///    The EUI48 RR encodes a 6-octet value.
+    /// ```
+    EUI48(EUI48),
+
+    /// [RFC 7043, Resource Records for EUI-48 and EUI-64 Addresses in the DNS](https://tools.ietf.org/html/rfc7043#section-4.1)
+    ///
+    /// ```text
+    ///    The EUI64 RR encodes an 8-octet value.
+    /// ```
+    EUI64(EUI64),
+
     /// ```text
     /// 3.3.2. HINFO RDATA format
     ///
@@ -236,6 +277,30 @@ pub enum RData {
     /// ```
     HTTPS(SVCB),
 
+    /// [RFC 1876, A Means for Expressing Location Information in the DNS](https://tools.ietf.org/html/rfc1876#section-2)
+    ///
+    /// ```text
+    ///        MSB                                           LSB
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       0|        VERSION        |         SIZE          |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       2|       HORIZ PRE       |       VERT PRE        |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       4|                   LATITUDE                    |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       6|                   LATITUDE                    |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///       8|                   LONGITUDE                   |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///      10|                   LONGITUDE                   |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///      12|                   ALTITUDE                    |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///      14|                   ALTITUDE                    |
+    ///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /// ```
+    LOC(LOC),
+
     /// ```text
     /// 3.3.9. MX RDATA format
     ///
@@ -615,6 +680,14 @@ pub enum RData {
     /// [RFC 7479](https://tools.ietf.org/html/rfc7479).
     SSHFP(SSHFP),
 
+    /// [RFC 8162, Using Secure DNS to Associate Certificates with Domain Names for S/MIME](https://tools.ietf.org/html/rfc8162#section-2)
+    ///
+    /// ```text
+    ///    The SMIMEA wire format and presentation format are the same as for
+    ///    the TLSA record as described in Section 2.1 of [RFC6698].
+    /// ```
+    SMIMEA(TLSA),
+
     /// [RFC draft-ietf-dnsop-svcb-https-03, DNS SVCB and HTTPS RRs](https://datatracker.ietf.org/doc/html/draft-ietf-dnsop-svcb-https-03#section-2)
     ///
     /// ```text
@@ -671,6 +744,38 @@ pub enum RData {
     /// ```
     TXT(TXT),
 
+    /// [RFC 7553, The Uniform Resource Identifier (URI) DNS Resource Record](https://tools.ietf.org/html/rfc7553#section-4.5)
+    ///
+    /// ```text
+    ///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+    ///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |          Priority             |          Weight               |
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    /                                                               /
+    ///    /                             Target                            /
+    ///    /                                                               /
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    /// ```
+    URI(URI),
+
+    /// [RFC 8976, Message Digest for DNS Zones](https://tools.ietf.org/html/rfc8976#section-2.2)
+    ///
+    /// ```text
+    ///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+    ///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |                             Serial                            |
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    ///    |    Scheme     |Hash Algorithm |                               |
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+    ///    |                             Digest                            |
+    ///    /                                                               /
+    ///    /                                                               /
+    ///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    /// ```
+    ZONEMD(ZONEMD),
+
     /// A DNSSEC- or SIG(0)- specific record. See `DNSSECRData` for details.
     ///
     /// These types are in `DNSSECRData` to make them easy to disable when
@@ -725,6 +830,10 @@ impl RData {
                 trace!("reading ANAME");
                 rdata::name::read(decoder).map(Self::ANAME)
             }
+            RecordType::APL => {
+                trace!("reading APL");
+                rdata::apl::read(decoder, rdata_length).map(Self::APL)
+            }
             rt @ RecordType::ANY | rt @ RecordType::AXFR | rt @ RecordType::IXFR => {
                 return Err(ProtoErrorKind::UnknownRecordTypeValue(rt.into()).into());
             }
@@ -740,6 +849,18 @@ impl RData {
                 trace!("reading CSYNC");
                 rdata::csync::read(decoder, rdata_length).map(Self::CSYNC)
             }
+            RecordType::DHCID => {
+                trace!("reading DHCID");
+                rdata::dhcid::read(decoder, rdata_length).map(Self::DHCID)
+            }
+            RecordType::EUI48 => {
+                trace!("reading EUI48");
+                rdata::eui::read_eui48(decoder).map(Self::EUI48)
+            }
+            RecordType::EUI64 => {
+                trace!("reading EUI64");
+                rdata::eui::read_eui64(decoder).map(Self::EUI64)
+            }
             RecordType::HINFO => {
                 trace!("reading HINFO");
                 rdata::hinfo::read(decoder).map(Self::HINFO)
@@ -748,6 +869,10 @@ impl RData {
                 trace!("reading HTTPS");
                 rdata::svcb::read(decoder, rdata_length).map(Self::HTTPS)
             }
+            RecordType::LOC => {
+                trace!("reading LOC");
+                rdata::loc::read(decoder, rdata_length).map(Self::LOC)
+            }
             RecordType::ZERO => {
                 trace!("reading EMPTY");
                 // we should never get here, since ZERO should be 0 length, and None in the Record.
@@ -783,6 +908,10 @@ impl RData {
                 trace!("reading PTR");
                 rdata::name::read(decoder).map(Self::PTR)
             }
+            RecordType::SMIMEA => {
+                trace!("reading SMIMEA");
+                rdata::tlsa::read(decoder, rdata_length).map(Self::SMIMEA)
+            }
             RecordType::SOA => {
                 trace!("reading SOA");
                 rdata::soa::read(decoder).map(Self::SOA)
@@ -807,6 +936,14 @@ impl RData {
                 trace!("reading TXT");
                 rdata::txt::read(decoder, rdata_length).map(Self::TXT)
             }
+            RecordType::URI => {
+                trace!("reading URI");
+                rdata::uri::read(decoder, rdata_length).map(Self::URI)
+            }
+            RecordType::ZONEMD => {
+                trace!("reading ZONEMD");
+                rdata::zonemd::read(decoder, rdata_length).map(Self::ZONEMD)
+            }
             #[cfg(feature = "dnssec")]
             r if r.is_dnssec() => {
                 DNSSECRData::read(decoder, record_type, rdata_length).map(Self::DNSSEC)
@@ -909,6 +1046,7 @@ impl RData {
             Self::ANAME(ref name) => {
                 encoder.with_canonical_names(|encoder| rdata::name::emit(encoder, name))
             }
+            Self::APL(ref apl) => rdata::apl::emit(encoder, apl),
             Self::CAA(ref caa) => {
                 encoder.with_canonical_names(|encoder| rdata::caa::emit(encoder, caa))
             }
@@ -917,10 +1055,14 @@ impl RData {
                 rdata::name::emit(encoder, name)
             }
             Self::CSYNC(ref csync) => rdata::csync::emit(encoder, csync),
+            Self::DHCID(ref dhcid) => rdata::dhcid::emit(encoder, dhcid),
+            Self::EUI48(ref eui48) => rdata::eui::emit_eui48(encoder, eui48),
+            Self::EUI64(ref eui64) => rdata::eui::emit_eui64(encoder, eui64),
             Self::HINFO(ref hinfo) => rdata::hinfo::emit(encoder, hinfo),
             Self::HTTPS(ref svcb) => rdata::svcb::emit(encoder, svcb),
             Self::ZERO => Ok(()),
             // to_lowercase for rfc4034 and rfc6840
+            Self::LOC(ref loc) => rdata::loc::emit(encoder, loc),
             Self::MX(ref mx) => rdata::mx::emit(encoder, mx),
             Self::NAPTR(ref naptr) => {
                 encoder.with_canonical_names(|encoder| rdata::naptr::emit(encoder, naptr))
@@ -931,6 +1073,7 @@ impl RData {
             }
             Self::OPT(ref opt) => rdata::opt::emit(encoder, opt),
             // to_lowercase for rfc4034 and rfc6840
+            Self::SMIMEA(ref smimea) => rdata::tlsa::emit(encoder, smimea),
             Self::SOA(ref soa) => rdata::soa::emit(encoder, soa),
             // to_lowercase for rfc4034 and rfc6840
             Self::SRV(ref srv) => {
@@ -944,6 +1087,8 @@ impl RData {
                 encoder.with_canonical_names(|encoder| rdata::tlsa::emit(encoder, tlsa))
             }
             Self::TXT(ref txt) => rdata::txt::emit(encoder, txt),
+            Self::URI(ref uri) => rdata::uri::emit(encoder, uri),
+            Self::ZONEMD(ref zonemd) => rdata::zonemd::emit(encoder, zonemd),
             #[cfg(feature = "dnssec")]
             Self::DNSSEC(ref rdata) => encoder.with_canonical_names(|encoder| rdata.emit(encoder)),
             Self::Unknown { ref rdata, .. } => rdata::null::emit(encoder, rdata),
@@ -956,11 +1101,16 @@ impl RData {
             Self::A(..) => RecordType::A,
             Self::AAAA(..) => RecordType::AAAA,
             Self::ANAME(..) => RecordType::ANAME,
+            Self::APL(..) => RecordType::APL,
             Self::CAA(..) => RecordType::CAA,
             Self::CNAME(..) => RecordType::CNAME,
             Self::CSYNC(..) => RecordType::CSYNC,
+            Self::DHCID(..) => RecordType::DHCID,
+            Self::EUI48(..) => RecordType::EUI48,
+            Self::EUI64(..) => RecordType::EUI64,
             Self::HINFO(..) => RecordType::HINFO,
             Self::HTTPS(..) => RecordType::HTTPS,
+            Self::LOC(..) => RecordType::LOC,
             Self::MX(..) => RecordType::MX,
             Self::NAPTR(..) => RecordType::NAPTR,
             Self::NS(..) => RecordType::NS,
@@ -970,14 +1120,17 @@ impl RData {
             Self::PTR(..) => RecordType::PTR,
             Self::SOA(..) => RecordType::SOA,
             Self::SRV(..) => RecordType::SRV,
+            Self::SMIMEA(..) => RecordType::SMIMEA,
             Self::SSHFP(..) => RecordType::SSHFP,
             Self::SVCB(..) => RecordType::SVCB,
             Self::TLSA(..) => RecordType::TLSA,
             Self::TXT(..) => RecordType::TXT,
+            Self::URI(..) => RecordType::URI,
             #[cfg(feature = "dnssec")]
             Self::DNSSEC(ref rdata) => DNSSECRData::to_record_type(rdata),
-            Self::Unknown { code, .. } => RecordType::Unknown(code),
+            Self::Unknown { code, .. } => RecordType::from(code),
             Self::ZERO => RecordType::ZERO,
+            Self::ZONEMD(..) => RecordType::ZONEMD,
         }
     }
 
@@ -1002,21 +1155,41 @@ impl fmt::Display for RData {
             write!(f, "{rdata}", rdata = d)
         }
 
+        // RFC 3597, the generic representation for types without a presentation format
+        fn generic(f: &mut fmt::Formatter<'_>, null: &NULL) -> Result<(), fmt::Error> {
+            let data = null.anything();
+            if data.is_empty() {
+                f.write_str("\\# 0")
+            } else {
+                write!(
+                    f,
+                    "\\# {} {}",
+                    data.len(),
+                    data_encoding::HEXUPPER.encode(data)
+                )
+            }
+        }
+
         match *self {
             Self::A(address) => w(f, address),
             Self::AAAA(ref address) => w(f, address),
             Self::ANAME(ref name) => w(f, name),
+            Self::APL(ref apl) => w(f, apl),
             Self::CAA(ref caa) => w(f, caa),
             // to_lowercase for rfc4034 and rfc6840
             Self::CNAME(ref name) | RData::NS(ref name) | RData::PTR(ref name) => w(f, name),
             Self::CSYNC(ref csync) => w(f, csync),
+            Self::DHCID(ref dhcid) => w(f, dhcid),
+            Self::EUI48(ref eui48) => w(f, eui48),
+            Self::EUI64(ref eui64) => w(f, eui64),
             Self::HINFO(ref hinfo) => w(f, hinfo),
             Self::HTTPS(ref svcb) => w(f, svcb),
             Self::ZERO => Ok(()),
             // to_lowercase for rfc4034 and rfc6840
+            Self::LOC(ref loc) => w(f, loc),
             Self::MX(ref mx) => w(f, mx),
             Self::NAPTR(ref naptr) => w(f, naptr),
-            Self::NULL(ref null) => w(f, null),
+            Self::NULL(ref null) => generic(f, null),
             Self::OPENPGPKEY(ref openpgpkey) => w(f, openpgpkey),
             // Opt has no display representation
             Self::OPT(_) => Err(fmt::Error),
@@ -1024,13 +1197,16 @@ impl fmt::Display for RData {
             Self::SOA(ref soa) => w(f, soa),
             // to_lowercase for rfc4034 and rfc6840
             Self::SRV(ref srv) => w(f, srv),
+            Self::SMIMEA(ref smimea) => w(f, smimea),
             Self::SSHFP(ref sshfp) => w(f, sshfp),
             Self::SVCB(ref svcb) => w(f, svcb),
             Self::TLSA(ref tlsa) => w(f, tlsa),
             Self::TXT(ref txt) => w(f, txt),
+            Self::URI(ref uri) => w(f, uri),
+            Self::ZONEMD(ref zonemd) => w(f, zonemd),
             #[cfg(feature = "dnssec")]
             Self::DNSSEC(ref rdata) => w(f, rdata),
-            Self::Unknown { ref rdata, .. } => w(f, rdata),
+            Self::Unknown { ref rdata, .. } => generic(f, rdata),
         }
     }
 }
@@ -1077,7 +1253,7 @@ mod tests {
 
     use super::*;
     use crate::rr::domain::Name;
-    use crate::rr::rdata::{MX, SOA, SRV, TXT};
+    use crate::rr::rdata::{EUI48, MX, SOA, SRV, TXT, URI};
     use crate::serialize::binary::bin_tests::test_emit_data_set;
     #[allow(clippy::useless_attribute)]
     #[allow(unused)]
@@ -1162,6 +1338,14 @@ mod tests {
                 RData::HINFO(HINFO::new("cpu".to_string(), "os".to_string())),
                 vec![3, b'c', b'p', b'u', 2, b'o', b's'],
             ),
+            (
+                RData::EUI48(EUI48::new([0, 0, 0x5e, 0, 0x53, 0x2a])),
+                vec![0, 0, 0x5e, 0, 0x53, 0x2a],
+            ),
+            (
+                RData::URI(URI::new(1, 2, "ftp://a".to_string())),
+                vec![0, 1, 0, 2, b'f', b't', b'p', b':', b'/', b'/', b'a'],
+            ),
         ]
     }
 
@@ -1255,11 +1439,16 @@ mod tests {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::ANAME(..) => RecordType::ANAME,
+            RData::APL(..) => RecordType::APL,
             RData::CAA(..) => RecordType::CAA,
             RData::CNAME(..) => RecordType::CNAME,
             RData::CSYNC(..) => RecordType::CSYNC,
+            RData::DHCID(..) => RecordType::DHCID,
+            RData::EUI48(..) => RecordType::EUI48,
+            RData::EUI64(..) => RecordType::EUI64,
             RData::HINFO(..) => RecordType::HINFO,
             RData::HTTPS(..) => RecordType::HTTPS,
+            RData::LOC(..) => RecordType::LOC,
             RData::MX(..) => RecordType::MX,
             RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
@@ -1269,14 +1458,17 @@ mod tests {
             RData::PTR(..) => RecordType::PTR,
             RData::SOA(..) => RecordType::SOA,
             RData::SRV(..) => RecordType::SRV,
+            RData::SMIMEA(..) => RecordType::SMIMEA,
             RData::SSHFP(..) => RecordType::SSHFP,
             RData::SVCB(..) => RecordType::SVCB,
             RData::TLSA(..) => RecordType::TLSA,
             RData::TXT(..) => RecordType::TXT,
+            RData::URI(..) => RecordType::URI,
             #[cfg(feature = "dnssec")]
             RData::DNSSEC(ref rdata) => rdata.to_record_type(),
             RData::Unknown { code, .. } => RecordType::Unknown(code),
             RData::ZERO => RecordType::ZERO,
+            RData::ZONEMD(..) => RecordType::ZONEMD,
         }
     }
 
@@ -1284,4 +1476,16 @@ mod tests {
     fn test_write_to() {
         test_emit_data_set(get_data(), |e, d| d.emit(e));
     }
+
+    #[test]
+    fn test_generic_display() {
+        let rdata = RData::Unknown {
+            code: 65_280,
+            rdata: NULL::with(vec![0x0A, 0, 0, 0x01]),
+        };
+        assert_eq!(rdata.to_string(), "\\# 4 0A000001");
+
+        let rdata = RData::NULL(NULL::new());
+        assert_eq!(rdata.to_string(), "\\# 0");
+    }
 }
@@ -41,7 +41,8 @@ pub enum RecordType {
     //  AFSDB,      //	18	RFC 1183	AFS database record
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) All cached records, aka ANY
     ANY,
-    //  APL,        //	42	RFC 3123	Address Prefix List
+    /// [RFC 3123](https://tools.ietf.org/html/rfc3123) Address Prefix List
+    APL,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Authoritative Zone Transfer
     AXFR,
     /// [RFC 6844](https://tools.ietf.org/html/rfc6844) Certification Authority Authorization
@@ -53,15 +54,21 @@ pub enum RecordType {
     //  CERT,       // 37 RFC 4398 Certificate record
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Canonical name record
     CNAME,
-    //  DHCID,      // 49 RFC 4701 DHCP identifier
+    /// [RFC 4701](https://tools.ietf.org/html/rfc4701) DHCP identifier
+    DHCID,
     //  DLV,        //	32769	RFC 4431	DNSSEC Lookaside Validation record
-    //  DNAME,      // 39 RFC 2672 Delegation Name
+    /// [RFC 6672](https://tools.ietf.org/html/rfc6672) Delegation name, its data is not supported
+    DNAME,
     /// [RFC 7477](https://tools.ietf.org/html/rfc4034) Child-to-parent synchronization record
     CSYNC,
     /// [RFC 4034](https://tools.ietf.org/html/rfc4034) DNS Key record: RSASHA256 and RSASHA512, RFC5702
     DNSKEY,
     /// [RFC 4034](https://tools.ietf.org/html/rfc4034) Delegation signer: RSASHA256 and RSASHA512, RFC5702
     DS,
+    /// [RFC 7043](https://tools.ietf.org/html/rfc7043) MAC address (EUI-48)
+    EUI48,
+    /// [RFC 7043](https://tools.ietf.org/html/rfc7043) MAC address (EUI-64)
+    EUI64,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) host information
     HINFO,
     //  HIP,        // 55 RFC 5205 Host Identity Protocol
@@ -73,7 +80,8 @@ pub enum RecordType {
     //  KX,         // 36 RFC 2230 Key eXchanger record
     /// [RFC 2535](https://tools.ietf.org/html/rfc2535) and [RFC 2930](https://tools.ietf.org/html/rfc2930) Key record
     KEY,
-    //  LOC,        // 29 RFC 1876 Location record
+    /// [RFC 1876](https://tools.ietf.org/html/rfc1876) Location record
+    LOC,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Mail exchange record
     MX,
     /// [RFC 3403](https://tools.ietf.org/html/rfc3403) Naming Authority Pointer
@@ -99,6 +107,8 @@ pub enum RecordType {
     RRSIG,
     /// [RFC 2535](https://tools.ietf.org/html/rfc2535) (and [RFC 2931](https://tools.ietf.org/html/rfc2931)) Signature, to support [RFC 2137](https://tools.ietf.org/html/rfc2137) Update.
     SIG,
+    /// [RFC 8162](https://tools.ietf.org/html/rfc8162) S/MIME certificate association
+    SMIMEA,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) and [RFC 2308](https://tools.ietf.org/html/rfc2308) Start of [a zone of] authority record
     SOA,
     /// [RFC 2782](https://tools.ietf.org/html/rfc2782) Service locator
@@ -108,18 +118,23 @@ pub enum RecordType {
     /// [RFC draft-ietf-dnsop-svcb-https-03](https://tools.ietf.org/html/draft-ietf-dnsop-svcb-httpssvc-03) DNS SVCB and HTTPS RRs
     SVCB,
     //  TA,         // 32768 N/A DNSSEC Trust Authorities
-    //  TKEY,       // 249 RFC 2930 Secret key record
+    /// [RFC 2930](https://tools.ietf.org/html/rfc2930) Secret key record
+    TKEY,
     /// [RFC 6698](https://tools.ietf.org/html/rfc6698) TLSA certificate association
     TLSA,
     /// [RFC 8945](https://tools.ietf.org/html/rfc8945) Transaction Signature
     TSIG,
     /// [RFC 1035](https://tools.ietf.org/html/rfc1035) Text record
     TXT,
+    /// [RFC 7553](https://tools.ietf.org/html/rfc7553) Uniform Resource Identifier
+    URI,
     /// Unknown Record type, or unsupported
     Unknown(u16),
 
     /// This corresponds to a record type of 0, unspecified
     ZERO,
+    /// [RFC 8976](https://tools.ietf.org/html/rfc8976) Message Digest for DNS Zones
+    ZONEMD,
 }
 
 impl RecordType {
@@ -174,6 +189,7 @@ impl RecordType {
                 | Self::NSEC3PARAM
                 | Self::RRSIG
                 | Self::SIG
+                | Self::TKEY
                 | Self::TSIG
         )
     }
@@ -203,18 +219,25 @@ impl FromStr for RecordType {
         match str {
             "A" => Ok(Self::A),
             "AAAA" => Ok(Self::AAAA),
-            "ANAME" => Ok(Self::ANAME),
+            // ALIAS is the name of ANAME in some other servers
+            "ALIAS" | "ANAME" => Ok(Self::ANAME),
+            "APL" => Ok(Self::APL),
             "AXFR" => Ok(Self::AXFR),
             "CAA" => Ok(Self::CAA),
             "CDNSKEY" => Ok(Self::CDNSKEY),
             "CDS" => Ok(Self::CDS),
             "CNAME" => Ok(Self::CNAME),
             "CSYNC" => Ok(Self::CSYNC),
+            "DHCID" => Ok(Self::DHCID),
+            "DNAME" => Ok(Self::DNAME),
             "DNSKEY" => Ok(Self::DNSKEY),
             "DS" => Ok(Self::DS),
+            "EUI48" => Ok(Self::EUI48),
+            "EUI64" => Ok(Self::EUI64),
             "HINFO" => Ok(Self::HINFO),
             "HTTPS" => Ok(Self::HTTPS),
             "KEY" => Ok(Self::KEY),
+            "LOC" => Ok(Self::LOC),
             "MX" => Ok(Self::MX),
             "NAPTR" => Ok(Self::NAPTR),
             "NSEC" => Ok(Self::NSEC),
@@ -226,15 +249,25 @@ impl FromStr for RecordType {
             "PTR" => Ok(Self::PTR),
             "RRSIG" => Ok(Self::RRSIG),
             "SIG" => Ok(Self::SIG),
+            "SMIMEA" => Ok(Self::SMIMEA),
             "SOA" => Ok(Self::SOA),
             "SRV" => Ok(Self::SRV),
             "SSHFP" => Ok(Self::SSHFP),
             "SVCB" => Ok(Self::SVCB),
             "TLSA" => Ok(Self::TLSA),
             "TXT" => Ok(Self::TXT),
+            "TKEY" => Ok(Self::TKEY),
             "TSIG" => Ok(Self::TSIG),
+            "URI" => Ok(Self::URI),
+            "ZONEMD" => Ok(Self::ZONEMD),
             "ANY" | "*" => Ok(Self::ANY),
-            _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
+            // RFC 3597, the generic TYPE### representation for any type
+            _ => str
+                .strip_prefix("TYPE")
+                .filter(|code| !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|code| u16::from_str(code).ok())
+                .map(Self::from)
+                .ok_or_else(|| ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
         }
     }
 }
@@ -255,6 +288,7 @@ impl From<u16> for RecordType {
             // TODO: wrong value here, see https://github.com/bluejekyll/trust-dns/issues/723
             65305 => Self::ANAME,
             255 => Self::ANY,
+            42 => Self::APL,
             251 => Self::IXFR,
             252 => Self::AXFR,
             257 => Self::CAA,
@@ -262,11 +296,16 @@ impl From<u16> for RecordType {
             60 => Self::CDNSKEY,
             5 => Self::CNAME,
             62 => Self::CSYNC,
+            49 => Self::DHCID,
+            39 => Self::DNAME,
             48 => Self::DNSKEY,
             43 => Self::DS,
+            108 => Self::EUI48,
+            109 => Self::EUI64,
             13 => Self::HINFO,
             65 => Self::HTTPS,
             25 => Self::KEY,
+            29 => Self::LOC,
             15 => Self::MX,
             35 => Self::NAPTR,
             2 => Self::NS,
@@ -279,14 +318,18 @@ impl From<u16> for RecordType {
             12 => Self::PTR,
             46 => Self::RRSIG,
             24 => Self::SIG,
+            53 => Self::SMIMEA,
             6 => Self::SOA,
             33 => Self::SRV,
             44 => Self::SSHFP,
             64 => Self::SVCB,
             52 => Self::TLSA,
+            249 => Self::TKEY,
             250 => Self::TSIG,
             16 => Self::TXT,
+            256 => Self::URI,
             0 => Self::ZERO,
+            63 => Self::ZONEMD,
             // all unknown record types
             _ => Self::Unknown(value),
         }
@@ -331,18 +374,24 @@ impl From<RecordType> for &'static str {
             RecordType::AAAA => "AAAA",
             RecordType::ANAME => "ANAME",
             RecordType::ANY => "ANY",
+            RecordType::APL => "APL",
             RecordType::AXFR => "AXFR",
             RecordType::CAA => "CAA",
             RecordType::CDNSKEY => "CDNSKEY",
             RecordType::CDS => "CDS",
             RecordType::CNAME => "CNAME",
             RecordType::CSYNC => "CSYNC",
+            RecordType::DHCID => "DHCID",
+            RecordType::DNAME => "DNAME",
             RecordType::DNSKEY => "DNSKEY",
             RecordType::DS => "DS",
+            RecordType::EUI48 => "EUI48",
+            RecordType::EUI64 => "EUI64",
             RecordType::HINFO => "HINFO",
             RecordType::HTTPS => "HTTPS",
             RecordType::KEY => "KEY",
             RecordType::IXFR => "IXFR",
+            RecordType::LOC => "LOC",
             RecordType::MX => "MX",
             RecordType::NAPTR => "NAPTR",
             RecordType::NS => "NS",
@@ -355,14 +404,18 @@ impl From<RecordType> for &'static str {
             RecordType::PTR => "PTR",
             RecordType::RRSIG => "RRSIG",
             RecordType::SIG => "SIG",
+            RecordType::SMIMEA => "SMIMEA",
             RecordType::SOA => "SOA",
             RecordType::SRV => "SRV",
             RecordType::SSHFP => "SSHFP",
             RecordType::SVCB => "SVCB",
             RecordType::TLSA => "TLSA",
+            RecordType::TKEY => "TKEY",
             RecordType::TSIG => "TSIG",
             RecordType::TXT => "TXT",
+            RecordType::URI => "URI",
             RecordType::ZERO => "ZERO",
+            RecordType::ZONEMD => "ZONEMD",
             RecordType::Unknown(_) => "Unknown",
         }
     }
@@ -385,18 +438,24 @@ impl From<RecordType> for u16 {
             // TODO: wrong value here, see https://github.com/bluejekyll/trust-dns/issues/723
             RecordType::ANAME => 65305,
             RecordType::ANY => 255,
+            RecordType::APL => 42,
             RecordType::AXFR => 252,
             RecordType::CAA => 257,
             RecordType::CDNSKEY => 60,
             RecordType::CDS => 59,
             RecordType::CNAME => 5,
             RecordType::CSYNC => 62,
+            RecordType::DHCID => 49,
+            RecordType::DNAME => 39,
             RecordType::DNSKEY => 48,
             RecordType::DS => 43,
+            RecordType::EUI48 => 108,
+            RecordType::EUI64 => 109,
             RecordType::HINFO => 13,
             RecordType::HTTPS => 65,
             RecordType::KEY => 25,
             RecordType::IXFR => 251,
+            RecordType::LOC => 29,
             RecordType::MX => 15,
             RecordType::NAPTR => 35,
             RecordType::NS => 2,
@@ -409,14 +468,18 @@ impl From<RecordType> for u16 {
             RecordType::PTR => 12,
             RecordType::RRSIG => 46,
             RecordType::SIG => 24,
+            RecordType::SMIMEA => 53,
             RecordType::SOA => 6,
             RecordType::SRV => 33,
             RecordType::SSHFP => 44,
             RecordType::SVCB => 64,
             RecordType::TLSA => 52,
+            RecordType::TKEY => 249,
             RecordType::TSIG => 250,
             RecordType::TXT => 16,
+            RecordType::URI => 256,
             RecordType::ZERO => 0,
+            RecordType::ZONEMD => 63,
             RecordType::Unknown(code) => code,
         }
     }
@@ -438,7 +501,11 @@ impl Ord for RecordType {
 
 impl Display for RecordType {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        f.write_str(Into::<&str>::into(*self))
+        match *self {
+            // RFC 3597, unknown types are displayed as TYPE###
+            Self::Unknown(code) => write!(f, "TYPE{}", code),
+            _ => f.write_str(Into::<&str>::into(*self)),
+        }
     }
 }
 
@@ -501,21 +568,30 @@ mod tests {
             "A",
             "AAAA",
             "ANAME",
+            "APL",
             "CAA",
             "CNAME",
             "CSYNC",
+            "DHCID",
+            "DNAME",
+            "EUI48",
+            "EUI64",
             "HINFO",
             "NULL",
+            "LOC",
             "MX",
             "NAPTR",
             "NS",
             "OPENPGPKEY",
             "PTR",
+            "SMIMEA",
             "SOA",
             "SRV",
             "SSHFP",
             "TLSA",
             "TXT",
+            "URI",
+            "ZONEMD",
             "ANY",
             "AXFR",
         ];
@@ -532,6 +608,7 @@ mod tests {
             "NSEC3PARAM",
             "RRSIG",
             "SIG",
+            "TKEY",
             "TSIG",
         ];
         #[cfg(not(feature = "dnssec"))]
@@ -544,4 +621,23 @@ mod tests {
             assert!(rtypes.insert(rtype));
         }
     }
+
+    #[test]
+    fn test_alias_type() {
+        assert_eq!(RecordType::from_str("ALIAS").unwrap(), RecordType::ANAME);
+    }
+
+    #[test]
+    fn test_generic_type() {
+        assert_eq!(
+            RecordType::from_str("TYPE65280").unwrap(),
+            RecordType::Unknown(65280)
+        );
+        assert_eq!(RecordType::Unknown(65280).to_string(), "TYPE65280");
+        assert_eq!(RecordType::from_str("TYPE1").unwrap(), RecordType::A);
+
+        assert!(RecordType::from_str("TYPE").is_err());
+        assert!(RecordType::from_str("TYPE1A").is_err());
+        assert!(RecordType::from_str("TYPE65536").is_err());
+    }
 }
@@ -42,7 +42,7 @@ use crate::serialize::binary::*;
 /// rrclass field), and the actual resource record class is the least
 /// significant fifteen bits of this field.
 /// ```
-const MDNS_ENABLE_CACHE_FLUSH: u16 = 1 << 15;
+pub(crate) const MDNS_ENABLE_CACHE_FLUSH: u16 = 1 << 15;
 
 const NULL_RDATA: &RData = &RData::NULL(NULL::new());
 /// Resource records are storage value in DNS, into which all key/value pair data is stored.
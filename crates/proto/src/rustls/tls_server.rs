@@ -10,8 +10,9 @@
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::path::Path;
+use std::sync::Arc;
 
-use rustls::{self, Certificate, PrivateKey, ServerConfig};
+use rustls::{self, server::ResolvesServerCert, Certificate, PrivateKey, ServerConfig};
 use rustls_pemfile::{certs, read_one, Item};
 
 use crate::error::{ProtoError, ProtoResult};
@@ -84,3 +85,16 @@ pub fn new_acceptor(
     config.alpn_protocols = vec![b"h2".to_vec()];
     Ok(config)
 }
+
+/// Construct the new Acceptor, with the certificate of each connection from the resolver
+///
+/// The resolver may change the certificate while the acceptor is in use, e.g. when it's renewed.
+pub fn new_acceptor_with_cert_resolver(resolver: Arc<dyn ResolvesServerCert>) -> ServerConfig {
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    config
+}
@@ -14,21 +14,24 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 
-use futures_util::TryFutureExt;
+use futures_util::{future, TryFutureExt};
 use rustls::ClientConfig;
 use tokio;
+#[cfg(feature = "tokio-runtime")]
 use tokio::net::TcpStream as TokioTcpStream;
 use tokio_rustls::TlsConnector;
 
 use crate::iocompat::{AsyncIoStdAsTokio, AsyncIoTokioAsStd};
 use crate::tcp::Connect;
 use crate::tcp::{DnsTcpStream, TcpStream};
-use crate::xfer::{BufDnsStreamHandle, StreamReceiver};
+use crate::xfer::BufDnsStreamHandle;
 
 /// Predefined type for abstracting the TlsClientStream with TokioTls
 pub type TokioTlsClientStream<S> = tokio_rustls::client::TlsStream<AsyncIoStdAsTokio<S>>;
 
 /// Predefined type for abstracting the TlsServerStream with TokioTls
+#[cfg(feature = "tokio-runtime")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-runtime")))]
 pub type TokioTlsServerStream = tokio_rustls::server::TlsStream<TokioTcpStream>;
 
 /// Predefined type for abstracting the base I/O TlsStream with TokioTls
@@ -119,50 +122,37 @@ pub fn tls_connect_with_bind_addr<S: Connect>(
     >,
     BufDnsStreamHandle,
 ) {
-    let (message_sender, outbound_messages) = BufDnsStreamHandle::new(name_server);
     let early_data_enabled = client_config.enable_early_data;
     let tls_connector = TlsConnector::from(client_config).early_data(early_data_enabled);
 
-    // This set of futures collapses the next tcp socket into a stream which can be used for
-    //  sending and receiving tcp packets.
-    let stream = Box::pin(connect_tls(
-        tls_connector,
-        name_server,
-        bind_addr,
-        dns_name,
-        outbound_messages,
-    ));
-
-    (stream, message_sender)
+    crate::tls::tls_connect(name_server, bind_addr, dns_name, tls_connector)
 }
 
-async fn connect_tls<S: Connect>(
-    tls_connector: TlsConnector,
-    name_server: SocketAddr,
-    bind_addr: Option<SocketAddr>,
-    dns_name: String,
-    outbound_messages: StreamReceiver,
-) -> io::Result<TcpStream<AsyncIoTokioAsStd<TokioTlsClientStream<S>>>> {
-    let tcp = S::connect_with_bind(name_server, bind_addr).await?;
-
-    let dns_name = match dns_name.as_str().try_into() {
-        Ok(name) => name,
-        Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "bad dns_name")),
-    };
-
-    let s = tls_connector
-        .connect(dns_name, AsyncIoStdAsTokio(tcp))
-        .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::ConnectionRefused,
-                format!("tls error: {}", e),
-            )
-        })
-        .await?;
-
-    Ok(TcpStream::from_stream_with_receiver(
-        AsyncIoTokioAsStd(s),
-        name_server,
-        outbound_messages,
-    ))
+impl<S: Connect> crate::tls::TlsConnector<S> for TlsConnector {
+    type Stream = TokioTlsClientStream<S>;
+
+    fn connect(
+        &self,
+        dns_name: &str,
+        tcp: S,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Self::Stream>> + Send>> {
+        let dns_name = match dns_name.try_into() {
+            Ok(name) => name,
+            Err(_) => {
+                return Box::pin(future::err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "bad dns_name",
+                )))
+            }
+        };
+
+        Box::pin(
+            Self::connect(self, dns_name, AsyncIoStdAsTokio(tcp)).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("tls error: {}", e),
+                )
+            }),
+        )
+    }
 }
@@ -13,6 +13,7 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
 use crate::error::{ProtoErrorKind, ProtoResult};
@@ -89,8 +90,8 @@ mod private {
 pub struct BinEncoder<'a> {
     offset: usize,
     buffer: private::MaximalBuf<'a>,
-    /// start of label pointers with their labels in fully decompressed form for easy comparison, smallvec here?
-    name_pointers: Vec<(usize, Vec<u8>)>,
+    /// name suffixes in fully decompressed wire form, mapped to the offset they were written at
+    name_pointers: HashMap<Vec<u8>, u16>,
     mode: EncodeMode,
     canonical_names: bool,
 }
@@ -129,7 +130,7 @@ impl<'a> BinEncoder<'a> {
             offset: offset as usize,
             // TODO: add max_size to signature
             buffer: private::MaximalBuf::new(u16::max_value(), buf),
-            name_pointers: Vec::new(),
+            name_pointers: HashMap::new(),
             mode,
             canonical_names: false,
         }
@@ -209,7 +210,8 @@ impl<'a> BinEncoder<'a> {
     pub fn trim(&mut self) {
         let offset = self.offset;
         self.buffer.truncate(offset);
-        self.name_pointers.retain(|&(start, _)| start < offset);
+        self.name_pointers
+            .retain(|_, start| (*start as usize) < offset);
     }
 
     // /// returns an error if the maximum buffer size would be exceeded with the addition number of elements
@@ -239,24 +241,29 @@ impl<'a> BinEncoder<'a> {
         assert!(start <= (u16::max_value() as usize));
         assert!(end <= (u16::max_value() as usize));
         assert!(start <= end);
-        if self.offset < 0x3FFF_usize {
-            self.name_pointers
-                .push((start, self.slice_of(start, end).to_vec())); // the next char will be at the len() location
-        }
+        let suffix = self.slice_of(start, end).to_vec();
+        self.store_name_pointer(start, suffix);
     }
 
     /// Looks up the index of an already written label
     pub fn get_label_pointer(&self, start: usize, end: usize) -> Option<u16> {
-        let search = self.slice_of(start, end);
+        self.get_name_pointer(self.slice_of(start, end))
+    }
 
-        for (match_start, matcher) in &self.name_pointers {
-            if matcher.as_slice() == search {
-                assert!(match_start <= &(u16::max_value() as usize));
-                return Some(*match_start as u16);
-            }
+    /// Stores the offset of a name suffix written at `start`
+    ///
+    /// The suffix is the uncompressed wire form of the labels, without the terminating root label.
+    ///  Only the first offset of a suffix is kept, and offsets beyond the reach of a 14 bit
+    ///  pointer are ignored.
+    pub fn store_name_pointer(&mut self, start: usize, suffix: Vec<u8>) {
+        if start <= 0x3FFF_usize {
+            self.name_pointers.entry(suffix).or_insert(start as u16);
         }
+    }
 
-        None
+    /// Looks up the offset of an already written name suffix, see `store_name_pointer`
+    pub fn get_name_pointer(&self, suffix: &[u8]) -> Option<u16> {
+        self.name_pointers.get(suffix).copied()
     }
 
     /// Emit one byte into the buffer
@@ -439,7 +446,7 @@ impl<'a> BinEncoder<'a> {
         emit_result
     }
 
-    fn set_rollback(&self) -> Rollback {
+    pub(crate) fn set_rollback(&self) -> Rollback {
         Rollback {
             rollback_index: self.offset(),
         }
@@ -489,8 +496,10 @@ pub(crate) struct Rollback {
 }
 
 impl Rollback {
+    /// Discards everything written since the rollback point, including stored name pointers
     pub(crate) fn rollback(self, encoder: &mut BinEncoder<'_>) {
-        encoder.set_offset(self.rollback_index)
+        encoder.set_offset(self.rollback_index);
+        encoder.trim();
     }
 }
 
@@ -662,4 +671,48 @@ mod tests {
         // check re-serializing
         assert!(Message::from_vec(&bytes).is_ok());
     }
+
+    #[test]
+    fn test_suffix_compression() {
+        let mut buf = vec![];
+        let mut encoder = BinEncoder::new(&mut buf);
+
+        Name::from_str("www.example.com.")
+            .unwrap()
+            .emit(&mut encoder)
+            .unwrap();
+        assert_eq!(encoder.len(), 17);
+
+        // mail + pointer to example.com.
+        Name::from_str("mail.example.com.")
+            .unwrap()
+            .emit(&mut encoder)
+            .unwrap();
+        assert_eq!(encoder.len(), 17 + 7);
+        assert_eq!(&encoder.slice_of(17, 24)[5..], &[0xC0, 4]);
+
+        // pointer to the mail label
+        Name::from_str("mail.example.com.")
+            .unwrap()
+            .emit(&mut encoder)
+            .unwrap();
+        assert_eq!(encoder.len(), 17 + 7 + 2);
+        assert_eq!(encoder.slice_of(24, 26), &[0xC0, 17]);
+    }
+
+    #[test]
+    fn test_rollback_discards_name_pointers() {
+        let mut buf = vec![];
+        let mut encoder = BinEncoder::new(&mut buf);
+        let name = Name::from_str("www.example.com.").unwrap();
+
+        let rollback = encoder.set_rollback();
+        name.emit(&mut encoder).unwrap();
+        rollback.rollback(&mut encoder);
+        assert!(encoder.is_empty());
+
+        // the name must be written in full again, not point to the discarded data
+        name.emit(&mut encoder).unwrap();
+        assert_eq!(encoder.len(), 17);
+    }
 }
@@ -0,0 +1,83 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! UDP and TCP transports, and the timer, for the smol runtime
+//!
+//! These implement the [`UdpSocket`](crate::udp::UdpSocket), [`Connect`](crate::tcp::Connect)
+//!  and [`Time`](crate::Time) traits, so they can be used anywhere the Tokio types are, e.g.
+//!  `UdpClientStream::<SmolUdpSocket>::new(addr)` or
+//!  `TcpClientStream::<SmolTcpStream>::new(addr)`. DNS over TLS runs on top of the TCP
+//!  stream, e.g. `tls_client_connect::<SmolTcpStream>(..)` with `dns-over-rustls`.
+
+mod net;
+mod time;
+
+pub use self::net::{SmolTcpStream, SmolUdpSocket};
+pub use self::time::SmolTime;
+
+/// A DNS over TLS client stream on smol, see [`tls_client_connect`](crate::rustls::tls_client_connect)
+#[cfg(feature = "dns-over-rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
+pub type SmolTlsClientStream = crate::rustls::TlsClientStream<SmolTcpStream>;
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::tests::*;
+    use crate::Executor;
+
+    struct SmolExecutor;
+
+    impl Executor for SmolExecutor {
+        fn new() -> Self {
+            Self
+        }
+
+        fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+            smol::block_on(future)
+        }
+    }
+
+    #[test]
+    fn test_next_random_socket() {
+        next_random_socket_test::<SmolUdpSocket, _>(SmolExecutor)
+    }
+
+    #[test]
+    fn test_udp_stream_ipv4() {
+        smol::block_on(udp_stream_test::<SmolUdpSocket>(IpAddr::V4(Ipv4Addr::new(
+            127, 0, 0, 1,
+        ))))
+    }
+
+    #[test]
+    fn test_udp_client_stream_ipv4() {
+        udp_client_stream_test::<SmolUdpSocket, _, SmolTime>(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            SmolExecutor,
+        )
+    }
+
+    #[test]
+    fn test_tcp_stream_ipv4() {
+        tcp_stream_test::<SmolTcpStream, _, SmolTime>(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            SmolExecutor,
+        )
+    }
+
+    #[test]
+    fn test_tcp_client_stream_ipv4() {
+        tcp_client_stream_test::<SmolTcpStream, _, SmolTime>(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            SmolExecutor,
+        )
+    }
+}
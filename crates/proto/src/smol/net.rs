@@ -0,0 +1,157 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::convert::TryFrom;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::ready;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::time::SmolTime;
+use crate::tcp::{Connect, DnsTcpStream};
+use crate::udp::UdpSocket;
+
+/// A smol UDP socket
+///
+/// The socket is polled for readiness, dropping an I/O future between polls would lose its waker.
+#[derive(Debug)]
+pub struct SmolUdpSocket(smol::Async<std::net::UdpSocket>);
+
+#[async_trait]
+impl UdpSocket for SmolUdpSocket {
+    type Time = SmolTime;
+
+    async fn connect_with_bind(_addr: SocketAddr, bind_addr: SocketAddr) -> io::Result<Self> {
+        let socket = smol::Async::<std::net::UdpSocket>::bind(bind_addr)?;
+
+        // TODO: research connect more, it appears to break receive tests on UDP
+        // socket.connect(addr).await?;
+        Ok(Self(socket))
+    }
+
+    async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let bind_addr: SocketAddr = match addr {
+            SocketAddr::V4(_addr) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_addr) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+
+        Self::connect_with_bind(addr, bind_addr).await
+    }
+
+    async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        smol::Async::<std::net::UdpSocket>::bind(addr).map(Self)
+    }
+
+    fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        loop {
+            match self.0.get_ref().recv_from(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(self.0.poll_readable(cx))?
+                }
+                res => return Poll::Ready(res),
+            }
+        }
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.0.recv_from(buf).await
+    }
+
+    fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match self.0.get_ref().send_to(buf, target) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    ready!(self.0.poll_writable(cx))?
+                }
+                res => return Poll::Ready(res),
+            }
+        }
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        self.0.send_to(buf, target).await
+    }
+}
+
+/// A smol TCP stream
+#[derive(Debug)]
+pub struct SmolTcpStream(smol::net::TcpStream);
+
+impl DnsTcpStream for SmolTcpStream {
+    type Time = SmolTime;
+}
+
+#[async_trait]
+impl Connect for SmolTcpStream {
+    async fn connect_with_bind(
+        addr: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+    ) -> io::Result<Self> {
+        let stream = match bind_addr {
+            Some(bind_addr) => {
+                let std_stream = smol::unblock(move || {
+                    let domain = match bind_addr {
+                        SocketAddr::V4(_) => Domain::IPV4,
+                        SocketAddr::V6(_) => Domain::IPV6,
+                    };
+                    let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+                    socket.bind(&bind_addr.into())?;
+                    socket.connect(&addr.into())?;
+                    Ok::<_, io::Error>(std::net::TcpStream::from(socket))
+                })
+                .await?;
+
+                smol::net::TcpStream::try_from(std_stream)?
+            }
+            None => smol::net::TcpStream::connect(addr).await?,
+        };
+        stream.set_nodelay(true)?;
+        Ok(Self(stream))
+    }
+}
+
+impl AsyncWrite for SmolTcpStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bytes: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, bytes)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+impl AsyncRead for SmolTcpStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bytes: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, bytes)
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use smol::Timer;
+
+use crate::Time;
+
+/// Smol backed timer implementation
+#[derive(Clone, Copy, Debug)]
+pub struct SmolTime;
+
+#[async_trait]
+impl Time for SmolTime {
+    async fn delay_for(duration: Duration) {
+        Timer::after(duration).await;
+    }
+
+    async fn timeout<F: 'static + Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, io::Error> {
+        smol::future::or(async { Ok(future.await) }, async {
+            Timer::after(duration).await;
+            Err(io::Error::new(io::ErrorKind::TimedOut, "future timed out"))
+        })
+        .await
+    }
+}
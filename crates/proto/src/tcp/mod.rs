@@ -16,9 +16,11 @@
 
 //! TCP protocol related components for DNS
 mod tcp_client_stream;
+mod tcp_decoder;
 mod tcp_stream;
 
 pub use self::tcp_client_stream::{TcpClientConnect, TcpClientStream};
+pub use self::tcp_decoder::TcpMessageDecoder;
 pub use self::tcp_stream::{Connect, DnsTcpStream, TcpStream};
 
 #[cfg(feature = "tokio-runtime")]
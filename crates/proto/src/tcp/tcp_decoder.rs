@@ -0,0 +1,217 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Incremental decoding of length-prefixed DNS messages from a TCP byte stream
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_io::AsyncRead;
+
+/// The minimum number of bytes requested from the socket on each read
+const READ_CHUNK: usize = 4096;
+
+/// Decodes the length-prefixed DNS messages of a TCP connection, [RFC 1035](https://tools.ietf.org/html/rfc1035#section-4.2.2)
+///
+/// ```text
+///    Messages sent over TCP connections use server port 53 (decimal).  The
+///    message is prefixed with a two byte length field which gives the message
+///    length, excluding the two byte length field.
+/// ```
+///
+/// The bytes may arrive with arbitrary segment boundaries, a read may contain a partial length,
+///  or the end of one message together with any number of following messages. This makes it
+///  suitable for pipelined queries, [RFC 7766](https://tools.ietf.org/html/rfc7766#section-6.2.1.1),
+///  where responses may arrive in any order and must be matched to the queries by their ID.
+///
+/// ```
+/// use trust_dns_proto::tcp::TcpMessageDecoder;
+///
+/// let mut decoder = TcpMessageDecoder::new();
+/// decoder.extend_from_slice(&[0, 2, 0xAB]);
+/// assert!(decoder.decode().is_none());
+///
+/// decoder.extend_from_slice(&[0xCD, 0, 1, 0xEF]);
+/// assert_eq!(decoder.decode().unwrap(), vec![0xAB, 0xCD]);
+/// assert_eq!(decoder.decode().unwrap(), vec![0xEF]);
+/// assert!(decoder.is_empty());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TcpMessageDecoder {
+    buffer: Vec<u8>,
+    start: usize,
+}
+
+impl TcpMessageDecoder {
+    /// Creates a new decoder without any buffered bytes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of bytes buffered, but not yet decoded
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+
+    /// Returns true if no bytes are buffered, i.e. the stream is between messages
+    pub fn is_empty(&self) -> bool {
+        self.buffered() == 0
+    }
+
+    /// Appends bytes received from the stream
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        self.compact();
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Reads available bytes from the stream into the decoder
+    ///
+    /// # Return
+    ///
+    /// The number of bytes read, zero if the stream was closed
+    pub fn poll_read<R: AsyncRead>(
+        &mut self,
+        reader: Pin<&mut R>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<usize>> {
+        self.compact();
+
+        // read at least the remainder of the current message at once
+        let chunk = self.missing().max(READ_CHUNK);
+        let len = self.buffer.len();
+        self.buffer.resize(len + chunk, 0);
+
+        let read = reader.poll_read(cx, &mut self.buffer[len..]);
+        let read_len = match read {
+            Poll::Ready(Ok(read)) => read,
+            _ => 0,
+        };
+
+        self.buffer.truncate(len + read_len);
+        read
+    }
+
+    /// Returns the next complete message, without the length prefix
+    ///
+    /// # Return
+    ///
+    /// `None` if more bytes are needed for the next message
+    pub fn decode(&mut self) -> Option<Vec<u8>> {
+        let length = self.length()?;
+        let end = self.start + 2 + length;
+        if end > self.buffer.len() {
+            return None;
+        }
+
+        let message = self.buffer[self.start + 2..end].to_vec();
+        self.start = end;
+        Some(message)
+    }
+
+    /// The length of the next message, if the length prefix is complete
+    fn length(&self) -> Option<usize> {
+        let prefix = self.buffer.get(self.start..self.start + 2)?;
+        Some(u16::from_be_bytes([prefix[0], prefix[1]]) as usize)
+    }
+
+    /// The number of bytes missing to complete the next message
+    fn missing(&self) -> usize {
+        match self.length() {
+            Some(length) => (2 + length).saturating_sub(self.buffered()),
+            None => 2 - self.buffered(),
+        }
+    }
+
+    /// Drops the decoded bytes from the front of the buffer
+    fn compact(&mut self) {
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::Message;
+
+    fn framed(id: u16) -> (Vec<u8>, Vec<u8>) {
+        let mut message = Message::new();
+        message.set_id(id);
+        let bytes = message.to_vec().unwrap();
+
+        let mut framed = (bytes.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&bytes);
+        (bytes, framed)
+    }
+
+    #[test]
+    fn test_arbitrary_segments() {
+        let messages = vec![framed(3), framed(1), framed(2)];
+        let stream = messages
+            .iter()
+            .flat_map(|(_, framed)| framed.iter().copied())
+            .collect::<Vec<_>>();
+
+        for segment in 1..stream.len() {
+            let mut decoder = TcpMessageDecoder::new();
+            let mut decoded = Vec::new();
+
+            for chunk in stream.chunks(segment) {
+                decoder.extend_from_slice(chunk);
+                while let Some(message) = decoder.decode() {
+                    decoded.push(message);
+                }
+            }
+
+            assert!(decoder.is_empty());
+            assert_eq!(
+                decoded,
+                messages
+                    .iter()
+                    .map(|(bytes, _)| bytes.clone())
+                    .collect::<Vec<_>>()
+            );
+
+            // responses arrive out of order, to be matched by ID
+            let ids = decoded
+                .iter()
+                .map(|bytes| Message::from_vec(bytes).unwrap().id())
+                .collect::<Vec<_>>();
+            assert_eq!(ids, vec![3, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn test_partial() {
+        let (_, framed) = framed(7);
+        let mut decoder = TcpMessageDecoder::new();
+
+        decoder.extend_from_slice(&framed[..1]);
+        assert_eq!(decoder.missing(), 1);
+        assert!(decoder.decode().is_none());
+
+        decoder.extend_from_slice(&framed[1..5]);
+        assert_eq!(decoder.missing(), framed.len() - 5);
+        assert!(decoder.decode().is_none());
+        assert_eq!(decoder.buffered(), 5);
+
+        decoder.extend_from_slice(&framed[5..]);
+        assert!(decoder.decode().is_some());
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_empty_message() {
+        let mut decoder = TcpMessageDecoder::new();
+        decoder.extend_from_slice(&[0, 0]);
+        assert_eq!(decoder.decode(), Some(vec![]));
+        assert!(decoder.decode().is_none());
+    }
+}
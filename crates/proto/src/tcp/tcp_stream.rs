@@ -8,7 +8,6 @@
 //! This module contains all the TCP structures for demuxing TCP into streams of DNS packets.
 
 use std::io;
-use std::mem;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -21,6 +20,7 @@ use futures_util::{self, future::Future, ready, FutureExt};
 use tracing::debug;
 
 use crate::error::*;
+use crate::tcp::TcpMessageDecoder;
 use crate::xfer::{SerialMessage, StreamReceiver};
 use crate::BufDnsStreamHandle;
 use crate::Time;
@@ -66,31 +66,13 @@ enum WriteTcpState {
     Flushing,
 }
 
-/// Current state of a TCP stream as it's being read.
-pub(crate) enum ReadTcpState {
-    /// Currently reading the length of the TCP packet
-    LenBytes {
-        /// Current position in the buffer
-        pos: usize,
-        /// Buffer of the length to read
-        bytes: [u8; 2],
-    },
-    /// Currently reading the bytes of the DNS packet
-    Bytes {
-        /// Current position while reading the buffer
-        pos: usize,
-        /// buffer being read into
-        bytes: Vec<u8>,
-    },
-}
-
 /// A Stream used for sending data to and from a remote DNS endpoint (client or server).
 #[must_use = "futures do nothing unless polled"]
 pub struct TcpStream<S: DnsTcpStream> {
     socket: S,
     outbound_messages: StreamReceiver,
     send_state: Option<WriteTcpState>,
-    read_state: ReadTcpState,
+    read_state: TcpMessageDecoder,
     peer_addr: SocketAddr,
 }
 
@@ -177,10 +159,7 @@ impl<S: Connect> TcpStream<S> {
                             socket: tcp_stream,
                             outbound_messages,
                             send_state: None,
-                            read_state: ReadTcpState::LenBytes {
-                                pos: 0,
-                                bytes: [0u8; 2],
-                            },
+                            read_state: TcpMessageDecoder::new(),
                             peer_addr: name_server,
                         }
                     })
@@ -201,7 +180,7 @@ impl<S: DnsTcpStream> TcpStream<S> {
         &mut S,
         &mut StreamReceiver,
         &mut Option<WriteTcpState>,
-        &mut ReadTcpState,
+        &mut TcpMessageDecoder,
     ) {
         (
             &mut self.socket,
@@ -235,10 +214,7 @@ impl<S: DnsTcpStream> TcpStream<S> {
             socket,
             outbound_messages,
             send_state: None,
-            read_state: ReadTcpState::LenBytes {
-                pos: 0,
-                bytes: [0u8; 2],
-            },
+            read_state: TcpMessageDecoder::new(),
             peer_addr,
         }
     }
@@ -351,106 +327,41 @@ impl<S: DnsTcpStream> Stream for TcpStream<S> {
             }
         }
 
-        let mut ret_buf: Option<Vec<u8>> = None;
-
-        // this will loop while there is data to read, or the data has been read, or an IO
-        //  event would block
-        while ret_buf.is_none() {
-            // Evaluates the next state. If None is the result, then no state change occurs,
-            //  if Some(_) is returned, then that will be used as the next state.
-            let new_state: Option<ReadTcpState> = match read_state {
-                ReadTcpState::LenBytes {
-                    ref mut pos,
-                    ref mut bytes,
-                } => {
-                    // debug!("reading length {}", bytes.len());
-                    let read = ready!(socket.as_mut().poll_read(cx, &mut bytes[*pos..]))?;
-                    if read == 0 {
-                        // the Stream was closed!
-                        debug!("zero bytes read, stream closed?");
-                        //try!(self.socket.shutdown(Shutdown::Both)); // TODO: add generic shutdown function
-
-                        if *pos == 0 {
-                            // Since this is the start of the next message, we have a clean end
-                            return Poll::Ready(None);
-                        } else {
-                            return Poll::Ready(Some(Err(io::Error::new(
-                                io::ErrorKind::BrokenPipe,
-                                "closed while reading length",
-                            ))));
-                        }
-                    }
-                    debug!("in ReadTcpState::LenBytes: {}", pos);
-                    *pos += read;
-
-                    if *pos < bytes.len() {
-                        debug!("remain ReadTcpState::LenBytes: {}", pos);
-                        None
-                    } else {
-                        let length = u16::from_be_bytes(*bytes);
-                        debug!("got length: {}", length);
-                        let mut bytes = vec![0; length as usize];
-                        bytes.resize(length as usize, 0);
-
-                        debug!("move ReadTcpState::Bytes: {}", bytes.len());
-                        Some(ReadTcpState::Bytes { pos: 0, bytes })
-                    }
-                }
-                ReadTcpState::Bytes {
-                    ref mut pos,
-                    ref mut bytes,
-                } => {
-                    let read = ready!(socket.as_mut().poll_read(cx, &mut bytes[*pos..]))?;
-                    if read == 0 {
-                        // the Stream was closed!
-                        debug!("zero bytes read for message, stream closed?");
-
-                        // Since this is the start of the next message, we have a clean end
-                        // try!(self.socket.shutdown(Shutdown::Both));  // TODO: add generic shutdown function
-                        return Poll::Ready(Some(Err(io::Error::new(
-                            io::ErrorKind::BrokenPipe,
-                            "closed while reading message",
-                        ))));
-                    }
-
-                    debug!("in ReadTcpState::Bytes: {}", bytes.len());
-                    *pos += read;
+        // this will loop while there is data to read, or until an IO event would block, any
+        //  further messages already read are returned by the following polls
+        let buffer = loop {
+            if let Some(buffer) = read_state.decode() {
+                break buffer;
+            }
 
-                    if *pos < bytes.len() {
-                        debug!("remain ReadTcpState::Bytes: {}", bytes.len());
-                        None
-                    } else {
-                        debug!("reset ReadTcpState::LenBytes: {}", 0);
-                        Some(ReadTcpState::LenBytes {
-                            pos: 0,
-                            bytes: [0u8; 2],
-                        })
-                    }
-                }
-            };
-
-            // this will move to the next state,
-            //  if it was a completed receipt of bytes, then it will move out the bytes
-            if let Some(state) = new_state {
-                if let ReadTcpState::Bytes { pos, bytes } = mem::replace(read_state, state) {
-                    debug!("returning bytes");
-                    assert_eq!(pos, bytes.len());
-                    ret_buf = Some(bytes);
+            let read = ready!(read_state.poll_read(socket.as_mut(), cx))?;
+            if read == 0 {
+                // the Stream was closed!
+                debug!("zero bytes read, stream closed?");
+                //try!(self.socket.shutdown(Shutdown::Both)); // TODO: add generic shutdown function
+
+                if read_state.is_empty() {
+                    // Since this is the start of the next message, we have a clean end
+                    return Poll::Ready(None);
+                } else if read_state.buffered() < 2 {
+                    return Poll::Ready(Some(Err(io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "closed while reading length",
+                    ))));
+                } else {
+                    return Poll::Ready(Some(Err(io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "closed while reading message",
+                    ))));
                 }
             }
-        }
 
-        // if the buffer is ready, return it, if not we're Pending
-        if let Some(buffer) = ret_buf {
-            debug!("returning buffer");
-            let src_addr = self.peer_addr;
-            Poll::Ready(Some(Ok(SerialMessage::new(buffer, src_addr))))
-        } else {
-            debug!("bottomed out");
-            // at a minimum the outbound_messages should have been polled,
-            //  which will wake this future up later...
-            Poll::Pending
-        }
+            debug!("read bytes: {}, buffered: {}", read, read_state.buffered());
+        };
+
+        debug!("returning buffer");
+        let src_addr = self.peer_addr;
+        Poll::Ready(Some(Ok(SerialMessage::new(buffer, src_addr))))
     }
 }
 
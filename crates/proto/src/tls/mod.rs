@@ -0,0 +1,17 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TLS backend abstraction for DNS over TLS and DNS over HTTPS
+//!
+//! All TLS libraries supported by trust-dns implement [`TlsConnector`], the same connection logic
+//!  is used with each of them.
+
+mod tls_connector;
+
+pub use self::tls_connector::{
+    tls_client_connect, tls_connect, TlsClientStream, TlsConnector, TlsStream,
+};
@@ -0,0 +1,116 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use futures_util::TryFutureExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::error::ProtoError;
+use crate::iocompat::AsyncIoTokioAsStd;
+use crate::tcp::{Connect, TcpClientStream, TcpStream};
+use crate::xfer::BufDnsStreamHandle;
+
+/// A TLS stream established by the connector `T`
+pub type TlsStream<T> = TcpStream<AsyncIoTokioAsStd<T>>;
+
+/// A TLS client stream established by the connector `T`
+pub type TlsClientStream<T> = TcpClientStream<AsyncIoTokioAsStd<T>>;
+
+/// A TLS library securing established TCP connections
+///
+/// This is implemented for the connectors of all supported TLS libraries:
+///
+/// * `tokio_rustls::TlsConnector`, with the `dns-over-rustls` feature
+/// * `tokio_native_tls::TlsConnector`, with the `dns-over-native-tls` feature
+/// * `openssl::ssl::SslConnector`, with the `dns-over-openssl` feature
+///
+/// The trust anchors, client identity and ALPN protocols are configured on the connector before
+///  it is passed to [`tls_connect`] or `HttpsClientStreamBuilder::build_with_connector`.
+pub trait TlsConnector<S: Connect>: Clone + Send + Sync + 'static {
+    /// The encrypted stream established over the TCP connection
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static;
+
+    /// Performs the TLS handshake over `tcp`, the certificate of the server is verified against
+    ///  `dns_name`
+    fn connect(
+        &self,
+        dns_name: &str,
+        tcp: S,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Self::Stream>> + Send>>;
+}
+
+/// Creates a new TlsStream to the specified name_server, secured by `connector`
+///
+/// # Arguments
+///
+/// * `name_server` - IP and Port for the remote DNS resolver
+/// * `bind_addr` - IP and port to connect from
+/// * `dns_name` - The DNS name, Subject Public Key Info (SPKI) name, as associated to a certificate
+/// * `connector` - the TLS library and its configuration
+#[allow(clippy::type_complexity)]
+pub fn tls_connect<S, T>(
+    name_server: SocketAddr,
+    bind_addr: Option<SocketAddr>,
+    dns_name: String,
+    connector: T,
+) -> (
+    Pin<Box<dyn Future<Output = io::Result<TlsStream<T::Stream>>> + Send>>,
+    BufDnsStreamHandle,
+)
+where
+    S: Connect,
+    T: TlsConnector<S>,
+{
+    let (message_sender, outbound_messages) = BufDnsStreamHandle::new(name_server);
+
+    // This set of futures collapses the next tcp socket into a stream which can be used for
+    //  sending and receiving tcp packets.
+    let stream = async move {
+        let tcp = S::connect_with_bind(name_server, bind_addr).await?;
+        let tls = connector.connect(&dns_name, tcp).await?;
+
+        Ok(TcpStream::from_stream_with_receiver(
+            AsyncIoTokioAsStd(tls),
+            name_server,
+            outbound_messages,
+        ))
+    };
+
+    (Box::pin(stream), message_sender)
+}
+
+/// Creates a new TlsClientStream to the specified name_server, secured by `connector`
+///
+/// See [`tls_connect`] for the arguments.
+#[allow(clippy::type_complexity)]
+pub fn tls_client_connect<S, T>(
+    name_server: SocketAddr,
+    bind_addr: Option<SocketAddr>,
+    dns_name: String,
+    connector: T,
+) -> (
+    Pin<Box<dyn Future<Output = Result<TlsClientStream<T::Stream>, ProtoError>> + Send>>,
+    BufDnsStreamHandle,
+)
+where
+    S: Connect,
+    T: TlsConnector<S>,
+{
+    let (stream_future, sender) = tls_connect(name_server, bind_addr, dns_name, connector);
+
+    let new_future = Box::pin(
+        stream_future
+            .map_ok(TcpClientStream::from_stream)
+            .map_err(ProtoError::from),
+    );
+
+    (new_future, sender)
+}
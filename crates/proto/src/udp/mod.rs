@@ -20,4 +20,4 @@ mod udp_client_stream;
 mod udp_stream;
 
 pub use self::udp_client_stream::{UdpClientConnect, UdpClientStream};
-pub use self::udp_stream::{UdpSocket, UdpStream};
+pub use self::udp_stream::{UdpSocket, UdpStream, DEFAULT_PORT_RANGE};
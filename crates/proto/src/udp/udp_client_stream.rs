@@ -9,6 +9,7 @@ use std::borrow::Borrow;
 use std::fmt::{self, Display};
 use std::marker::PhantomData;
 use std::net::SocketAddr;
+use std::ops::RangeInclusive;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -19,8 +20,8 @@ use tracing::{debug, warn};
 
 use crate::error::ProtoError;
 use crate::op::message::NoopMessageFinalizer;
-use crate::op::{MessageFinalizer, MessageVerifier};
-use crate::udp::udp_stream::{NextRandomUdpSocket, UdpSocket};
+use crate::op::{MessageFinalizer, MessageVerifier, Query};
+use crate::udp::udp_stream::{NextRandomUdpSocket, UdpSocket, DEFAULT_PORT_RANGE};
 use crate::xfer::{DnsRequest, DnsRequestSender, DnsResponse, DnsResponseStream, SerialMessage};
 use crate::Time;
 
@@ -36,6 +37,7 @@ where
 {
     name_server: SocketAddr,
     bind_addr: Option<SocketAddr>,
+    port_range: RangeInclusive<u16>,
     timeout: Duration,
     is_shutdown: bool,
     signer: Option<Arc<MF>>,
@@ -100,6 +102,7 @@ impl<S: Send, MF: MessageFinalizer> UdpClientStream<S, MF> {
         UdpClientConnect {
             name_server,
             bind_addr: None,
+            port_range: DEFAULT_PORT_RANGE,
             timeout,
             signer,
             marker: PhantomData::<S>,
@@ -122,6 +125,7 @@ impl<S: Send, MF: MessageFinalizer> UdpClientStream<S, MF> {
         UdpClientConnect {
             name_server,
             bind_addr,
+            port_range: DEFAULT_PORT_RANGE,
             timeout,
             signer,
             marker: PhantomData::<S>,
@@ -154,6 +158,22 @@ impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer> DnsRequestSender
         // associated the ID for this request, b/c this connection is unique to socket port, the ID
         //   does not need to be globally unique
         message.set_id(random_query_id());
+        let timeout = message.options().timeout_or(self.timeout);
+
+        // with 0x20 encoding, the response must echo the randomized case of the query names
+        let case_queries = if message.options().case_randomization {
+            let original = message.queries().to_vec();
+            for query in message.queries_mut() {
+                let name = query.name().to_random_case();
+                query.set_name(name);
+            }
+            Some(CaseRandomizedQueries {
+                original,
+                sent: message.queries().to_vec(),
+            })
+        } else {
+            None
+        };
 
         let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
             Ok(now) => now.as_secs(),
@@ -163,15 +183,30 @@ impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer> DnsRequestSender
         // TODO: truncates u64 to u32, error on overflow?
         let now = now as u32;
 
-        let mut verifier = None;
-        if let Some(ref signer) = self.signer {
+        // the finalizer of the request takes precedence over the signer of the stream
+        let finalized = if let Some(finalizer) = message.finalizer().cloned() {
+            if finalizer.should_finalize_message(&message) {
+                Some(message.finalize(finalizer.as_ref(), now))
+            } else {
+                None
+            }
+        } else if let Some(ref signer) = self.signer {
             if signer.should_finalize_message(&message) {
-                match message.finalize::<MF>(signer.borrow(), now) {
-                    Ok(answer_verifier) => verifier = answer_verifier,
-                    Err(e) => {
-                        debug!("could not sign message: {}", e);
-                        return e.into();
-                    }
+                Some(message.finalize::<MF>(signer.borrow(), now))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let mut verifier = None;
+        if let Some(finalized) = finalized {
+            match finalized {
+                Ok(answer_verifier) => verifier = answer_verifier,
+                Err(e) => {
+                    debug!("could not sign message: {}", e);
+                    return e.into();
                 }
             }
         }
@@ -186,6 +221,7 @@ impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer> DnsRequestSender
         let message_id = message.id();
         let message = SerialMessage::new(bytes, self.name_server);
         let bind_addr = self.bind_addr;
+        let port_range = self.port_range.clone();
 
         debug!(
             "final message: {}",
@@ -195,9 +231,14 @@ impl<S: UdpSocket + Send + 'static, MF: MessageFinalizer> DnsRequestSender
         );
 
         S::Time::timeout::<Pin<Box<dyn Future<Output = Result<DnsResponse, ProtoError>> + Send>>>(
-            self.timeout,
+            timeout,
             Box::pin(send_serial_message::<S>(
-                message, message_id, verifier, bind_addr,
+                message,
+                message_id,
+                verifier,
+                bind_addr,
+                port_range,
+                case_queries,
             )),
         )
         .into()
@@ -234,11 +275,23 @@ where
 {
     name_server: SocketAddr,
     bind_addr: Option<SocketAddr>,
+    port_range: RangeInclusive<u16>,
     timeout: Duration,
     signer: Option<Arc<MF>>,
     marker: PhantomData<S>,
 }
 
+impl<S: Send, MF: MessageFinalizer> UdpClientConnect<S, MF> {
+    /// Chooses the random local port of each request from `port_range`, instead of
+    ///  [`DEFAULT_PORT_RANGE`], e.g. for firewalls which only allow specific ports
+    ///
+    /// This has no effect if a port is specified in the bind address.
+    pub fn with_port_range(mut self, port_range: RangeInclusive<u16>) -> Self {
+        self.port_range = port_range;
+        self
+    }
+}
+
 impl<S: Send + Unpin, MF: MessageFinalizer> Future for UdpClientConnect<S, MF> {
     type Output = Result<UdpClientStream<S, MF>, ProtoError>;
 
@@ -247,6 +300,7 @@ impl<S: Send + Unpin, MF: MessageFinalizer> Future for UdpClientConnect<S, MF> {
         Poll::Ready(Ok(UdpClientStream::<S, MF> {
             name_server: self.name_server,
             bind_addr: self.bind_addr,
+            port_range: self.port_range.clone(),
             is_shutdown: false,
             timeout: self.timeout,
             signer: self.signer.take(),
@@ -255,14 +309,37 @@ impl<S: Send + Unpin, MF: MessageFinalizer> Future for UdpClientConnect<S, MF> {
     }
 }
 
+/// The queries of a request sent with 0x20 encoding
+struct CaseRandomizedQueries {
+    /// The queries as requested, these are restored in the response
+    original: Vec<Query>,
+    /// The queries with the randomized case, as sent
+    sent: Vec<Query>,
+}
+
+impl CaseRandomizedQueries {
+    /// Returns true if the queries of the response match the sent queries, case sensitively
+    fn matches(&self, response: &[Query]) -> bool {
+        self.sent.len() == response.len()
+            && self.sent.iter().zip(response).all(|(sent, received)| {
+                sent.name().eq_case(received.name())
+                    && sent.query_type() == received.query_type()
+                    && sent.query_class() == received.query_class()
+            })
+    }
+}
+
 async fn send_serial_message<S: UdpSocket + Send>(
     msg: SerialMessage,
     msg_id: u16,
     verifier: Option<MessageVerifier>,
     bind_addr: Option<SocketAddr>,
+    port_range: RangeInclusive<u16>,
+    case_queries: Option<CaseRandomizedQueries>,
 ) -> Result<DnsResponse, ProtoError> {
     let name_server = msg.addr();
-    let socket: S = NextRandomUdpSocket::new(&name_server, &bind_addr).await?;
+    let socket: S =
+        NextRandomUdpSocket::with_port_range(&name_server, &bind_addr, port_range).await?;
     let bytes = msg.bytes();
     let addr = msg.addr();
     let len_sent: usize = socket.send_to(bytes, addr).await?;
@@ -297,17 +374,35 @@ async fn send_serial_message<S: UdpSocket + Send>(
             continue;
         }
 
-        // TODO: match query strings from request and response?
-
         match response.to_message() {
             Ok(message) => {
                 if msg_id == message.id() {
                     debug!("received message id: {}", message.id());
-                    if let Some(mut verifier) = verifier {
-                        return verifier(response.bytes());
+
+                    if let Some(ref case_queries) = case_queries {
+                        if !case_queries.matches(message.queries()) {
+                            // on mismatched case, attempted poison?
+                            warn!(
+                                "expected query case: {:?} got: {:?}, dropped",
+                                case_queries.sent,
+                                message.queries()
+                            );
+
+                            continue;
+                        }
+                    }
+
+                    let mut response = if let Some(mut verifier) = verifier {
+                        verifier(response.bytes())?
                     } else {
-                        return Ok(DnsResponse::from(message));
+                        DnsResponse::from(message)
+                    };
+
+                    if let Some(case_queries) = case_queries {
+                        *response.queries_mut() = case_queries.original;
                     }
+
+                    return Ok(response);
                 } else {
                     // on wrong id, attempted poison?
                     warn!(
@@ -341,8 +436,16 @@ mod tests {
     #[cfg(not(target_os = "linux"))]
     use std::net::Ipv6Addr;
     use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use futures_util::stream::StreamExt;
     use tokio::{net::UdpSocket as TokioUdpSocket, runtime::Runtime};
 
+    use super::*;
+    use crate::op::{Message, MessageType, Query};
+    use crate::rr::{Name, RData, Record, RecordType};
+    use crate::xfer::{DnsRequest, DnsRequestOptions};
+
     #[test]
     fn test_udp_client_stream_ipv4() {
         let io_loop = Runtime::new().expect("failed to create tokio runtime");
@@ -361,4 +464,80 @@ mod tests {
             io_loop,
         )
     }
+
+    #[test]
+    fn test_case_randomized_response() {
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let server = io_loop
+            .block_on(TokioUdpSocket::bind((Ipv4Addr::LOCALHOST, 0)))
+            .unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        // answers first with the case of the query name changed, then with the case as queried
+        io_loop.spawn(async move {
+            let mut buf = [0_u8; 512];
+            let (len, src) = server.recv_from(&mut buf).await.unwrap();
+            let request = Message::from_vec(&buf[..len]).unwrap();
+            let sent = request.queries()[0].name().to_ascii();
+            let swapped = sent
+                .chars()
+                .enumerate()
+                .map(|(i, c)| match c {
+                    _ if i > 0 => c,
+                    _ if c.is_ascii_lowercase() => c.to_ascii_uppercase(),
+                    _ => c.to_ascii_lowercase(),
+                })
+                .collect::<String>();
+
+            for (name, addr) in [
+                (swapped, Ipv4Addr::new(192, 0, 2, 66)),
+                (sent, Ipv4Addr::new(192, 0, 2, 1)),
+            ] {
+                let mut query = request.queries()[0].clone();
+                query.set_name(Name::from_ascii(&name).unwrap());
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .add_answer(Record::from_rdata(
+                        query.name().clone(),
+                        300,
+                        RData::A(addr),
+                    ))
+                    .add_query(query);
+                server
+                    .send_to(&response.to_vec().unwrap(), src)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut stream = io_loop
+            .block_on(UdpClientStream::<TokioUdpSocket>::with_timeout(
+                server_addr,
+                Duration::from_secs(5),
+            ))
+            .unwrap();
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::A));
+        let mut options = DnsRequestOptions::default();
+        options.case_randomization = true;
+
+        let response = io_loop
+            .block_on(
+                stream
+                    .send_message(DnsRequest::new(message, options))
+                    .next(),
+            )
+            .expect("no response")
+            .expect("response failed");
+
+        // the response with the changed case is dropped
+        assert_eq!(
+            response.answers()[0].data(),
+            Some(&RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+        assert!(response.queries()[0].name().eq_case(&name));
+    }
 }
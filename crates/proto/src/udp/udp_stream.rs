@@ -8,6 +8,7 @@
 use std::io;
 use std::marker::PhantomData;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
@@ -21,6 +22,14 @@ use tracing::{debug, warn};
 use crate::xfer::{BufDnsStreamHandle, SerialMessage, StreamReceiver};
 use crate::Time;
 
+/// The range of local ports for client connections, if no port is specified in the bind address
+///
+/// Per RFC 6056 Section 2.1:
+///
+///    The dynamic port range defined by IANA consists of the 49152-65535
+///    range, and is meant for the selection of ephemeral ports.
+pub const DEFAULT_PORT_RANGE: RangeInclusive<u16> = 49152..=u16::MAX;
+
 /// Trait for UdpSocket
 #[async_trait]
 pub trait UdpSocket
@@ -197,6 +206,7 @@ impl<S: UdpSocket + Send + 'static> Stream for UdpStream<S> {
 #[must_use = "futures do nothing unless polled"]
 pub(crate) struct NextRandomUdpSocket<S> {
     bind_address: SocketAddr,
+    port_range: RangeInclusive<u16>,
     marker: PhantomData<S>,
 }
 
@@ -206,6 +216,15 @@ impl<S: UdpSocket> NextRandomUdpSocket<S> {
     ///
     /// If a port is specified in the bind address it is used.
     pub(crate) fn new(name_server: &SocketAddr, bind_addr: &Option<SocketAddr>) -> Self {
+        Self::with_port_range(name_server, bind_addr, DEFAULT_PORT_RANGE)
+    }
+
+    /// Same as `new`, but the random port is chosen from `port_range`
+    pub(crate) fn with_port_range(
+        name_server: &SocketAddr,
+        bind_addr: &Option<SocketAddr>,
+        port_range: RangeInclusive<u16>,
+    ) -> Self {
         let bind_address = match bind_addr {
             Some(ba) => *ba,
             None => match *name_server {
@@ -218,6 +237,7 @@ impl<S: UdpSocket> NextRandomUdpSocket<S> {
 
         Self {
             bind_address,
+            port_range,
             marker: PhantomData,
         }
     }
@@ -236,11 +256,15 @@ impl<S: UdpSocket> Future for NextRandomUdpSocket<S> {
     /// if there is no port available after 10 attempts, returns NotReady
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         if self.bind_address.port() == 0 {
-            // Per RFC 6056 Section 2.1:
-            //
-            //    The dynamic port range defined by IANA consists of the 49152-65535
-            //    range, and is meant for the selection of ephemeral ports.
-            let rand_port_range = Uniform::new_inclusive(49152_u16, u16::max_value());
+            if self.port_range.is_empty() || *self.port_range.start() == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid port range: {:?}", self.port_range),
+                )));
+            }
+
+            let rand_port_range =
+                Uniform::new_inclusive(*self.port_range.start(), *self.port_range.end());
             let mut rand = rand::thread_rng();
 
             for attempt in 0..10 {
@@ -367,4 +391,34 @@ mod tests {
             Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1),
         )));
     }
+
+    #[test]
+    fn test_next_random_socket_port_range() {
+        use super::NextRandomUdpSocket;
+        use std::net::SocketAddr;
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let name_server = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53);
+        let bind_addr = Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0));
+
+        let socket = io_loop
+            .block_on(NextRandomUdpSocket::<TokioUdpSocket>::with_port_range(
+                &name_server,
+                &bind_addr,
+                50000..=50100,
+            ))
+            .expect("failed to bind");
+        let port = socket.local_addr().expect("no local addr").port();
+        assert!((50000..=50100).contains(&port), "port: {}", port);
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 50100..=50000;
+        assert!(io_loop
+            .block_on(NextRandomUdpSocket::<TokioUdpSocket>::with_port_range(
+                &name_server,
+                &bind_addr,
+                empty,
+            ))
+            .is_err());
+    }
 }
@@ -13,6 +13,7 @@ use rand;
 use tracing::debug;
 
 use crate::op::{Message, MessageType, OpCode, Query};
+use crate::rr::rdata::opt::EdnsOption;
 use crate::xfer::{DnsRequest, DnsRequestOptions, DnsResponse, SerialMessage};
 use crate::{error::*, op::Edns};
 
@@ -69,7 +70,7 @@ pub trait DnsHandle: 'static + Clone + Send + Sync + Unpin {
     }
 }
 
-fn build_message(query: Query, options: DnsRequestOptions) -> Message {
+pub(crate) fn build_message(query: Query, options: DnsRequestOptions) -> Message {
     // build the message
     let mut message: Message = Message::new();
     // TODO: This is not the final ID, it's actually set in the poll method of DNS future
@@ -83,12 +84,16 @@ fn build_message(query: Query, options: DnsRequestOptions) -> Message {
         .set_recursion_desired(options.recursion_desired);
 
     // Extended dns
-    if options.use_edns {
-        message
+    if options.use_edns || options.client_subnet.is_some() {
+        let edns = message
             .extensions_mut()
             .get_or_insert_with(Edns::new)
             .set_max_payload(MAX_PAYLOAD_LEN)
             .set_version(0);
+
+        if let Some(client_subnet) = options.client_subnet {
+            edns.options_mut().insert(EdnsOption::Subnet(client_subnet));
+        }
     }
     message
 }
@@ -25,7 +25,8 @@ use rand::distributions::{Distribution, Standard};
 use tracing::{debug, warn};
 
 use crate::error::*;
-use crate::op::{MessageFinalizer, MessageVerifier};
+use crate::op::{Message, MessageFinalizer, MessageVerifier, PaddingPolicy, Query};
+use crate::rr::RecordType;
 use crate::xfer::{
     ignore_send, BufDnsStreamHandle, DnsClientStream, DnsRequest, DnsRequestSender, DnsResponse,
     DnsResponseStream, SerialMessage, CHANNEL_BUFFER_SIZE,
@@ -39,6 +40,10 @@ struct ActiveRequest {
     // the completion is the channel for a response to the original request
     completion: mpsc::Sender<Result<DnsResponse, ProtoError>>,
     request_id: u16,
+    // the questions of the request, which the responses must echo
+    queries: Vec<Query>,
+    // zone transfers and multicast queries may have many responses, until the timeout
+    multi_response: bool,
     timeout: Box<dyn Future<Output = ()> + Send + Unpin>,
     verifier: Option<MessageVerifier>,
 }
@@ -46,14 +51,16 @@ struct ActiveRequest {
 impl ActiveRequest {
     fn new(
         completion: mpsc::Sender<Result<DnsResponse, ProtoError>>,
-        request_id: u16,
+        request: &Message,
+        multi_response: bool,
         timeout: Box<dyn Future<Output = ()> + Send + Unpin>,
         verifier: Option<MessageVerifier>,
     ) -> Self {
         Self {
             completion,
-            request_id,
-            // request,
+            request_id: request.id(),
+            queries: request.queries().to_vec(),
+            multi_response,
             timeout,
             verifier,
         }
@@ -74,6 +81,38 @@ impl ActiveRequest {
         self.request_id
     }
 
+    /// Returns true if the response is for this request, see RFC 7766 section 7
+    ///
+    /// With many requests outstanding on a connection, the id of a late response could match a
+    ///  later request with the same id, so the question of the response must match the request's.
+    ///  Responses without a question, e.g. the later messages of a zone transfer, match by id.
+    fn matches(&self, response: &Message) -> bool {
+        let queries = response.queries();
+        if queries.is_empty() {
+            return true;
+        }
+
+        queries.len() == self.queries.len()
+            && queries
+                .iter()
+                .zip(&self.queries)
+                .all(|(response, request)| {
+                    response.name() == request.name()
+                        && response.query_type() == request.query_type()
+                        && response.query_class() == request.query_class()
+                })
+    }
+
+    /// Sends the response, or the error of its verification
+    fn send_response(&mut self, message: Message, bytes: &[u8]) {
+        let response = match self.verifier {
+            Some(ref mut verifier) => verifier(bytes),
+            None => Ok(message.into()),
+        };
+
+        ignore_send(self.completion.try_send(response));
+    }
+
     /// Sends an error
     fn complete_with_error(mut self, error: ProtoError) {
         ignore_send(self.completion.try_send(Err(error)));
@@ -85,6 +124,11 @@ impl ActiveRequest {
 /// This Client is generic and capable of wrapping UDP, TCP, and other underlying DNS protocol
 ///  implementations. This should be used for underlying protocols that do not natively support
 ///  multiplexed sessions.
+///
+/// Many requests may be outstanding at once on the stream, e.g. pipelined over a TCP or TLS
+///  connection, and their responses may arrive in any order. Responses are matched to the requests
+///  by their id and question. A request is complete on its response, except zone transfers and
+///  multicast queries, which may have many responses until they time out.
 #[must_use = "futures do nothing unless polled"]
 pub struct DnsMultiplexer<S, MF>
 where
@@ -96,6 +140,7 @@ where
     stream_handle: BufDnsStreamHandle,
     active_requests: HashMap<u16, ActiveRequest>,
     signer: Option<Arc<MF>>,
+    padding: PaddingPolicy,
     is_shutdown: bool,
 }
 
@@ -148,6 +193,7 @@ where
             stream_handle: Some(stream_handle),
             timeout_duration,
             signer,
+            padding: PaddingPolicy::None,
         }
     }
 
@@ -223,6 +269,22 @@ where
     stream_handle: Option<BufDnsStreamHandle>,
     timeout_duration: Duration,
     signer: Option<Arc<MF>>,
+    padding: PaddingPolicy,
+}
+
+impl<F, S, MF> DnsMultiplexerConnect<F, S, MF>
+where
+    F: Future<Output = Result<S, ProtoError>> + Send + Unpin + 'static,
+    S: Stream<Item = Result<SerialMessage, ProtoError>> + Unpin,
+    MF: MessageFinalizer + Send + Sync + 'static,
+{
+    /// Pads all requests with the EDNS(0) Padding option, the default is no padding
+    ///
+    /// This should only be used over encrypted streams, e.g. DNS-over-TLS.
+    pub fn with_padding(mut self, padding: PaddingPolicy) -> Self {
+        self.padding = padding;
+        self
+    }
 }
 
 impl<F, S, MF> Future for DnsMultiplexerConnect<F, S, MF>
@@ -245,6 +307,7 @@ where
                 .expect("must not poll after complete"),
             active_requests: HashMap::new(),
             signer: self.signer.clone(),
+            padding: self.padding,
             is_shutdown: false,
         }))
     }
@@ -279,9 +342,15 @@ where
             Err(e) => return e.into(),
         };
 
-        let (mut request, _) = request.into_parts();
+        let finalizer = request.finalizer().cloned();
+        let (mut request, options) = request.into_parts();
         request.set_id(query_id);
 
+        // padding must precede the signature
+        if let Err(e) = request.pad(self.padding) {
+            return e.into();
+        }
+
         let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
             Ok(now) => now.as_secs(),
             Err(_) => return ProtoError::from("Current time is before the Unix epoch.").into(),
@@ -290,27 +359,53 @@ where
         // TODO: truncates u64 to u32, error on overflow?
         let now = now as u32;
 
-        let mut verifier = None;
-        if let Some(ref signer) = self.signer {
+        // the finalizer of the request takes precedence over the signer of the connection
+        let finalized = if let Some(ref finalizer) = finalizer {
+            if finalizer.should_finalize_message(&request) {
+                Some(request.finalize(finalizer.as_ref(), now))
+            } else {
+                None
+            }
+        } else if let Some(ref signer) = self.signer {
             if signer.should_finalize_message(&request) {
-                match request.finalize::<MF>(signer.borrow(), now) {
-                    Ok(answer_verifier) => verifier = answer_verifier,
-                    Err(e) => {
-                        debug!("could not sign message: {}", e);
-                        return e.into();
-                    }
+                Some(request.finalize::<MF>(signer.borrow(), now))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let mut verifier = None;
+        if let Some(finalized) = finalized {
+            match finalized {
+                Ok(answer_verifier) => verifier = answer_verifier,
+                Err(e) => {
+                    debug!("could not sign message: {}", e);
+                    return e.into();
                 }
             }
         }
 
         // store a Timeout for this message before sending
-        let timeout = S::Time::delay_for(self.timeout_duration);
+        let timeout = S::Time::delay_for(options.timeout_or(self.timeout_duration));
 
         let (complete, receiver) = mpsc::channel(CHANNEL_BUFFER_SIZE);
 
+        let multi_response = self.stream.name_server_addr().ip().is_multicast()
+            || request
+                .queries()
+                .iter()
+                .any(|q| matches!(q.query_type(), RecordType::AXFR | RecordType::IXFR));
+
         // send the message
-        let active_request =
-            ActiveRequest::new(complete, request.id(), Box::new(timeout), verifier);
+        let active_request = ActiveRequest::new(
+            complete,
+            &request,
+            multi_response,
+            Box::new(timeout),
+            verifier,
+        );
 
         match request.to_vec() {
             Ok(buffer) => {
@@ -384,19 +479,24 @@ where
                     //   deserialize or log decode_error
                     match buffer.to_message() {
                         Ok(message) => match self.active_requests.entry(message.id()) {
+                            Entry::Occupied(request_entry)
+                                if !request_entry.get().matches(&message) =>
+                            {
+                                debug!(
+                                    "question of response does not match request_id: {}",
+                                    message.id()
+                                );
+                            }
                             Entry::Occupied(mut request_entry) => {
-                                // send the response, complete the request...
-                                let active_request = request_entry.get_mut();
-                                if let Some(ref mut verifier) = active_request.verifier {
-                                    ignore_send(
-                                        active_request
-                                            .completion
-                                            .try_send(verifier(buffer.bytes())),
-                                    );
+                                if request_entry.get().multi_response {
+                                    request_entry
+                                        .get_mut()
+                                        .send_response(message, buffer.bytes());
                                 } else {
-                                    ignore_send(
-                                        active_request.completion.try_send(Ok(message.into())),
-                                    );
+                                    // send the response, complete the request, freeing its id
+                                    request_entry
+                                        .remove()
+                                        .send_response(message, buffer.bytes());
                                 }
                             }
                             Entry::Vacant(..) => debug!("unexpected request_id: {}", message.id()),
@@ -662,6 +762,120 @@ mod test {
         )
     }
 
+    /// Answers the expected number of pipelined requests in the reverse order, each after a
+    ///  response with its id but another question
+    struct PipelinedClientStream {
+        expected: usize,
+        requests: Vec<Message>,
+        responses: Option<Vec<Message>>,
+        addr: SocketAddr,
+        receiver: StreamReceiver,
+    }
+
+    impl fmt::Display for PipelinedClientStream {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+            write!(formatter, "PipelinedClientStream")
+        }
+    }
+
+    impl Stream for PipelinedClientStream {
+        type Item = Result<SerialMessage, ProtoError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            while self.requests.len() < self.expected {
+                let serial = ready!(self.receiver.poll_next_unpin(cx)).unwrap();
+                self.requests.push(serial.to_message().unwrap());
+            }
+
+            if self.responses.is_none() {
+                let mut responses = Vec::new();
+                for request in &self.requests {
+                    let mut response = request.clone();
+                    response.set_message_type(MessageType::Response);
+                    responses.push(response.clone());
+
+                    let mut mismatched = Message::new();
+                    mismatched
+                        .set_id(request.id())
+                        .set_message_type(MessageType::Response)
+                        .add_query(Query::query(
+                            Name::from_ascii("other.example.com.").unwrap(),
+                            RecordType::A,
+                        ));
+                    responses.push(mismatched);
+                }
+                self.responses = Some(responses);
+            }
+
+            match self.responses.as_mut().unwrap().pop() {
+                Some(response) => Poll::Ready(Some(Ok(SerialMessage::new(
+                    response.to_bytes().unwrap(),
+                    self.addr,
+                )))),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    impl DnsClientStream for PipelinedClientStream {
+        type Time = crate::TokioTime;
+
+        fn name_server_addr(&self) -> SocketAddr {
+            self.addr
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multiplexer_pipelined() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 1234));
+        let (handle, receiver) = BufDnsStreamHandle::new(addr);
+        let stream = Box::pin(future::ok(PipelinedClientStream {
+            expected: 2,
+            requests: Vec::new(),
+            responses: None,
+            addr,
+            receiver,
+        }));
+        let mut multiplexer = DnsMultiplexer::<_, NoopMessageFinalizer>::with_timeout(
+            stream,
+            handle,
+            Duration::from_secs(60),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let request = |name: &str| {
+            let mut msg = Message::new();
+            msg.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+            DnsRequest::new(msg, DnsRequestOptions::default())
+        };
+        let first = multiplexer.send_message(request("first.example.com."));
+        let second = multiplexer.send_message(request("second.example.com."));
+
+        let (first, second) = tokio::select! {
+            _ = multiplexer.next() => panic!("should never end"),
+            r = future::join(
+                first.try_collect::<Vec<_>>(),
+                second.try_collect::<Vec<_>>(),
+            ) => r,
+        };
+
+        // each request has its own response, and completes without waiting for the timeout
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.len(), 1);
+        assert_eq!(
+            first[0].queries()[0].name(),
+            &Name::from_ascii("first.example.com.").unwrap()
+        );
+        assert_eq!(second.len(), 1);
+        assert_eq!(
+            second[0].queries()[0].name(),
+            &Name::from_ascii("second.example.com.").unwrap()
+        );
+        assert!(multiplexer.active_requests.is_empty());
+    }
+
     #[tokio::test]
     async fn test_multiplexer_a() {
         let (query, answer) = a_query_answer();
@@ -0,0 +1,536 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS Push Notifications, [RFC 8765](https://tools.ietf.org/html/rfc8765)
+//!
+//! Instead of polling, a client subscribes to a name and type over a DSO session and the server
+//!  pushes all changes of the matching records, e.g. for watching DNS-SD services.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::future::Future;
+use futures_util::stream::{Stream, StreamExt};
+use rand::distributions::{Distribution, Standard};
+use tracing::debug;
+
+use crate::error::*;
+use crate::op::dso::{DsoSession, DsoSessionState, DsoTlv};
+use crate::op::{DsoMessage, Query, ResponseCode};
+use crate::rr::{DNSClass, Name, Record, RecordType};
+use crate::serialize::binary::{BinDecodable, BinEncodable};
+use crate::xfer::{BufDnsStreamHandle, DnsClientStream, DnsStreamHandle, SerialMessage};
+use crate::Time;
+
+/// The TTL of a pushed record which was removed
+const REMOVE_RECORD_TTL: u32 = 0xFFFF_FFFF;
+/// The TTL of a pushed record which removes all records of a name, type and class
+const REMOVE_COLLECTIVE_TTL: u32 = 0xFFFF_FFFE;
+
+/// A change of records pushed by the server, [RFC 8765](https://tools.ietf.org/html/rfc8765#section-6.3.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushChange {
+    /// The record was added, or its TTL changed
+    Add(Record),
+    /// The record was removed
+    Remove(Record),
+    /// All records of the name, type and class were removed, `RecordType::ANY` for all types
+    RemoveAll {
+        /// The owner of the removed records
+        name: Name,
+        /// The type of the removed records
+        record_type: RecordType,
+        /// The class of the removed records
+        dns_class: DNSClass,
+    },
+}
+
+impl PushChange {
+    /// The owner of the changed records
+    pub fn name(&self) -> &Name {
+        match *self {
+            Self::Add(ref record) | Self::Remove(ref record) => record.name(),
+            Self::RemoveAll { ref name, .. } => name,
+        }
+    }
+
+    /// The type of the changed records
+    pub fn record_type(&self) -> RecordType {
+        match *self {
+            Self::Add(ref record) | Self::Remove(ref record) => record.record_type(),
+            Self::RemoveAll { record_type, .. } => record_type,
+        }
+    }
+}
+
+impl From<Record> for PushChange {
+    fn from(record: Record) -> Self {
+        match record.ttl() {
+            REMOVE_RECORD_TTL => Self::Remove(record),
+            REMOVE_COLLECTIVE_TTL => Self::RemoveAll {
+                name: record.name().clone(),
+                record_type: record.record_type(),
+                dns_class: record.dns_class(),
+            },
+            _ => Self::Add(record),
+        }
+    }
+}
+
+/// An event of a [`DnsPushClient`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PushEvent {
+    /// The server accepted the subscription with the id
+    Subscribed(u16),
+    /// The server rejected the subscription with the id, it is removed
+    Rejected(u16, ResponseCode),
+    /// Records of the subscriptions changed
+    Changed(Vec<PushChange>),
+    /// The server asked the client to disconnect and not to reconnect before the delay passed,
+    ///  this is the last event of the stream
+    Retry(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SubscriptionState {
+    Pending,
+    Active,
+    Cancelled,
+}
+
+struct Subscription {
+    query: Query,
+    state: SubscriptionState,
+}
+
+/// A client for DNS Push Notifications
+///
+/// The client is a `Stream` of [`PushEvent`]s and must be polled to drive the connection, which
+///  includes sending the keepalive traffic required by the server.
+#[must_use = "streams do nothing unless polled"]
+pub struct DnsPushClient<S>
+where
+    S: DnsClientStream + Unpin,
+{
+    stream: S,
+    stream_handle: BufDnsStreamHandle,
+    session: DsoSession,
+    subscriptions: HashMap<u16, Subscription>,
+    keepalive: Option<(Instant, Pin<Box<dyn Future<Output = ()> + Send>>)>,
+    is_shutdown: bool,
+}
+
+impl<S> DnsPushClient<S>
+where
+    S: DnsClientStream + Unpin,
+{
+    /// Creates a new client on a connected stream
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - A connected TCP or TLS stream to the push server (see TcpClientStream)
+    /// * `stream_handle` - The handle for the `stream` on which bytes can be sent/received.
+    pub fn new(stream: S, stream_handle: BufDnsStreamHandle) -> Self {
+        Self {
+            stream,
+            stream_handle,
+            session: DsoSession::client(Instant::now()),
+            subscriptions: HashMap::new(),
+            keepalive: None,
+            is_shutdown: false,
+        }
+    }
+
+    /// The DSO session of the connection
+    pub fn session(&self) -> &DsoSession {
+        &self.session
+    }
+
+    /// All subscriptions which were not cancelled, including those not yet accepted by the server
+    pub fn subscriptions(&self) -> impl Iterator<Item = (u16, &Query)> + '_ {
+        self.subscriptions
+            .iter()
+            .filter(|(_, subscription)| subscription.state != SubscriptionState::Cancelled)
+            .map(|(id, subscription)| (*id, &subscription.query))
+    }
+
+    /// Subscribes to changes of the records matching the query
+    ///
+    /// # Return
+    ///
+    /// The id of the subscription, the result is reported as a [`PushEvent::Subscribed`] or
+    ///  [`PushEvent::Rejected`] event.
+    pub fn subscribe(&mut self, query: Query) -> ProtoResult<u16> {
+        let id = self.next_random_id();
+        let request = self
+            .session
+            .request(id, DsoTlv::Subscribe(query.clone()), Instant::now())?;
+        self.send(&request)?;
+
+        self.subscriptions.insert(
+            id,
+            Subscription {
+                query,
+                state: SubscriptionState::Pending,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Cancels the subscription with the id
+    pub fn unsubscribe(&mut self, id: u16) -> ProtoResult<()> {
+        let subscription = self
+            .subscriptions
+            .get_mut(&id)
+            .ok_or_else(|| ProtoError::from(format!("unknown subscription: {}", id)))?;
+
+        match subscription.state {
+            // the server must accept the subscription before it can be cancelled
+            SubscriptionState::Pending => {
+                subscription.state = SubscriptionState::Cancelled;
+                Ok(())
+            }
+            SubscriptionState::Active => {
+                self.subscriptions.remove(&id);
+                self.send_unsubscribe(id)
+            }
+            SubscriptionState::Cancelled => Ok(()),
+        }
+    }
+
+    /// Asks the server to verify that the record still exists, e.g. after failing to connect to a
+    ///  service
+    pub fn reconfirm(&mut self, record: Record) -> ProtoResult<()> {
+        let message = self
+            .session
+            .unidirectional(DsoTlv::Reconfirm(record), Instant::now())?;
+        self.send(&message)
+    }
+
+    /// creates a random id, not used by any subscription
+    fn next_random_id(&self) -> u16 {
+        let mut rand = rand::thread_rng();
+
+        loop {
+            let id: u16 = Standard.sample(&mut rand);
+            if id != 0 && !self.subscriptions.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    fn send(&mut self, message: &DsoMessage) -> ProtoResult<()> {
+        let bytes = message.to_bytes()?;
+        let serial_message = SerialMessage::new(bytes, self.stream.name_server_addr());
+        self.stream_handle.send(serial_message)
+    }
+
+    fn send_unsubscribe(&mut self, id: u16) -> ProtoResult<()> {
+        let message = self
+            .session
+            .unidirectional(DsoTlv::Unsubscribe(id), Instant::now())?;
+        self.send(&message)
+    }
+
+    fn receive(&mut self, message: &DsoMessage) -> ProtoResult<Option<PushEvent>> {
+        if let Some(response) = self.session.receive(message, Instant::now())? {
+            self.send(&response)?;
+        }
+
+        if let DsoSessionState::Retry(delay) = self.session.state() {
+            self.is_shutdown = true;
+            return Ok(Some(PushEvent::Retry(delay)));
+        }
+
+        if message.is_response() {
+            return self.receive_response(message);
+        }
+
+        match message.primary_tlv() {
+            Some(DsoTlv::Push(records)) => Ok(Some(PushEvent::Changed(
+                records.iter().cloned().map(PushChange::from).collect(),
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    fn receive_response(&mut self, response: &DsoMessage) -> ProtoResult<Option<PushEvent>> {
+        let id = response.id();
+
+        // responses to keepalive requests are not subscriptions
+        let state = match self.subscriptions.get_mut(&id) {
+            Some(subscription) => subscription.state,
+            None => return Ok(None),
+        };
+
+        if response.response_code() != ResponseCode::NoError {
+            self.subscriptions.remove(&id);
+
+            return match state {
+                SubscriptionState::Cancelled => Ok(None),
+                _ => Ok(Some(PushEvent::Rejected(id, response.response_code()))),
+            };
+        }
+
+        match state {
+            SubscriptionState::Cancelled => {
+                self.subscriptions.remove(&id);
+                self.send_unsubscribe(id)?;
+                Ok(None)
+            }
+            _ => {
+                if let Some(subscription) = self.subscriptions.get_mut(&id) {
+                    subscription.state = SubscriptionState::Active;
+                }
+                Ok(Some(PushEvent::Subscribed(id)))
+            }
+        }
+    }
+
+    /// sends a keepalive request whenever the connection was idle for the keepalive interval
+    fn poll_keepalive(&mut self, cx: &mut Context<'_>) -> ProtoResult<()> {
+        loop {
+            let deadline = match self.session.keepalive_deadline() {
+                Some(deadline) if self.session.is_established() => deadline,
+                _ => {
+                    self.keepalive = None;
+                    return Ok(());
+                }
+            };
+
+            if self.keepalive.as_ref().map(|(at, _)| *at) != Some(deadline) {
+                let delay = deadline.saturating_duration_since(Instant::now());
+                self.keepalive = Some((deadline, S::Time::delay_for(delay)));
+            }
+
+            let (_, timer) = self
+                .keepalive
+                .as_mut()
+                .expect("keepalive timer was just set");
+            match timer.as_mut().poll(cx) {
+                Poll::Ready(()) => {
+                    let id = self.next_random_id();
+                    let keepalive = DsoTlv::KeepAlive(self.session.keepalive());
+                    let request = self.session.request(id, keepalive, Instant::now())?;
+                    self.send(&request)?;
+                }
+                Poll::Pending => return Ok(()),
+            }
+        }
+    }
+}
+
+impl<S> Display for DnsPushClient<S>
+where
+    S: DnsClientStream + Unpin,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.stream)
+    }
+}
+
+impl<S> Stream for DnsPushClient<S>
+where
+    S: DnsClientStream + Unpin,
+{
+    type Item = Result<PushEvent, ProtoError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.is_shutdown {
+                return Poll::Ready(None);
+            }
+
+            match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(buffer))) => {
+                    let message = match DsoMessage::from_bytes(buffer.bytes()) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            debug!("error decoding DSO message: {}", e);
+                            continue;
+                        }
+                    };
+
+                    match self.receive(&message) {
+                        Ok(Some(event)) => return Poll::Ready(Some(Ok(event))),
+                        Ok(None) => continue,
+                        Err(e) => {
+                            self.is_shutdown = true;
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    self.is_shutdown = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => {
+                    debug!("push stream closed: {}", self);
+                    self.is_shutdown = true;
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        if let Err(e) = self.poll_keepalive(cx) {
+            self.is_shutdown = true;
+            return Poll::Ready(Some(Err(e)));
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use futures_channel::mpsc;
+
+    use super::*;
+    use crate::rr::RData;
+    use crate::xfer::StreamReceiver;
+
+    struct MockPushStream {
+        receiver: mpsc::UnboundedReceiver<SerialMessage>,
+        addr: SocketAddr,
+    }
+
+    impl Display for MockPushStream {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(formatter, "MockPushStream")
+        }
+    }
+
+    impl Stream for MockPushStream {
+        type Item = Result<SerialMessage, ProtoError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.receiver
+                .poll_next_unpin(cx)
+                .map(|message| message.map(Ok))
+        }
+    }
+
+    impl DnsClientStream for MockPushStream {
+        type Time = crate::TokioTime;
+
+        fn name_server_addr(&self) -> SocketAddr {
+            self.addr
+        }
+    }
+
+    async fn next_sent(sent: &mut StreamReceiver) -> DsoMessage {
+        let serial = sent.next().await.expect("no message sent");
+        DsoMessage::from_bytes(serial.bytes()).unwrap()
+    }
+
+    fn server_send(server: &mpsc::UnboundedSender<SerialMessage>, message: DsoMessage) {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 5353));
+        server
+            .unbounded_send(SerialMessage::new(message.to_bytes().unwrap(), addr))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_push_client() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 5353));
+        let (server, receiver) = mpsc::unbounded();
+        let (stream_handle, mut sent) = BufDnsStreamHandle::new(addr);
+        let mut client = DnsPushClient::new(MockPushStream { receiver, addr }, stream_handle);
+
+        let name = Name::from_str("_ipp._tcp.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let id = client.subscribe(query.clone()).unwrap();
+
+        let request = next_sent(&mut sent).await;
+        assert_eq!(request.id(), id);
+        assert_eq!(request.primary_tlv(), Some(&DsoTlv::Subscribe(query)));
+
+        server_send(
+            &server,
+            DsoMessage::new_response(&request, ResponseCode::NoError, None),
+        );
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            PushEvent::Subscribed(id)
+        );
+        assert!(client.session().is_established());
+
+        let added = Record::from_rdata(name.clone(), 60, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        let mut removed = added.clone();
+        removed.set_ttl(REMOVE_RECORD_TTL);
+        let mut removed_all = Record::with(name.clone(), RecordType::ANY, REMOVE_COLLECTIVE_TTL);
+        removed_all.set_dns_class(DNSClass::IN);
+
+        server_send(
+            &server,
+            DsoMessage::new_unidirectional(DsoTlv::Push(vec![
+                added.clone(),
+                removed.clone(),
+                removed_all,
+            ])),
+        );
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            PushEvent::Changed(vec![
+                PushChange::Add(added),
+                PushChange::Remove(removed),
+                PushChange::RemoveAll {
+                    name,
+                    record_type: RecordType::ANY,
+                    dns_class: DNSClass::IN,
+                },
+            ])
+        );
+
+        client.unsubscribe(id).unwrap();
+        assert_eq!(client.subscriptions().count(), 0);
+        let unsubscribe = next_sent(&mut sent).await;
+        assert!(unsubscribe.is_unidirectional());
+        assert_eq!(unsubscribe.primary_tlv(), Some(&DsoTlv::Unsubscribe(id)));
+
+        server_send(
+            &server,
+            DsoMessage::new_unidirectional(DsoTlv::RetryDelay(10_000)),
+        );
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            PushEvent::Retry(Duration::from_secs(10))
+        );
+        assert!(client.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_push_client_rejected() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 5353));
+        let (server, receiver) = mpsc::unbounded();
+        let (stream_handle, mut sent) = BufDnsStreamHandle::new(addr);
+        let mut client = DnsPushClient::new(MockPushStream { receiver, addr }, stream_handle);
+
+        let query = Query::query(Name::from_str("example.com.").unwrap(), RecordType::SRV);
+        let id = client.subscribe(query).unwrap();
+
+        let request = next_sent(&mut sent).await;
+        server_send(
+            &server,
+            DsoMessage::new_response(&request, ResponseCode::NotAuth, None),
+        );
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            PushEvent::Rejected(id, ResponseCode::NotAuth)
+        );
+        assert_eq!(client.subscriptions().count(), 0);
+        assert!(!client.session().is_established());
+    }
+}
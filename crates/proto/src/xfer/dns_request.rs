@@ -8,8 +8,11 @@
 //! `DnsRequest` wraps a `Message` and associates a set of `DnsRequestOptions` for specifying different transfer options.
 
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::op::Message;
+use crate::op::{Message, MessageFinalizer};
+use crate::rr::rdata::opt::ClientSubnet;
 
 /// A set of options for expressing options to how requests should be treated
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -29,6 +32,20 @@ pub struct DnsRequestOptions {
     pub max_request_depth: usize,
     /// set recursion desired (or not) for any requests
     pub recursion_desired: bool,
+    /// Overrides the timeout of the underlying connection for this request.
+    ///
+    /// This can only shorten the timeout, the connection timeout remains the upper bound.
+    pub timeout: Option<Duration>,
+    /// Randomizes the case of the query names, DNS 0x20 encoding, and drops responses which do not
+    ///  echo the exact case.
+    ///
+    /// This is an additional protection against off-path spoofing, it only applies to UDP.
+    pub case_randomization: bool,
+    /// The network of the client on whose behalf the request is sent, the EDNS Client Subnet
+    ///  option of [RFC 7871](https://tools.ietf.org/html/rfc7871)
+    ///
+    /// Setting this adds EDNS to the request, regardless of `use_edns`.
+    pub client_subnet: Option<ClientSubnet>,
 }
 
 impl Default for DnsRequestOptions {
@@ -39,6 +56,9 @@ impl Default for DnsRequestOptions {
             expects_multiple_responses: false,
             use_edns: false,
             recursion_desired: true,
+            timeout: None,
+            case_randomization: false,
+            client_subnet: None,
         }
     }
 }
@@ -46,16 +66,28 @@ impl Default for DnsRequestOptions {
 /// A DNS request object
 ///
 /// This wraps a DNS Message for requests. It also has request options associated for controlling certain features of the DNS protocol handlers.
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 pub struct DnsRequest {
     message: Message,
     options: DnsRequestOptions,
+    finalizer: Option<Arc<dyn MessageFinalizer>>,
+}
+
+impl DnsRequestOptions {
+    /// Returns the timeout to use for this request, bounded by the timeout of the connection
+    pub fn timeout_or(&self, default: Duration) -> Duration {
+        self.timeout.map_or(default, |timeout| timeout.min(default))
+    }
 }
 
 impl DnsRequest {
     /// Returns a new DnsRequest object
     pub fn new(message: Message, options: DnsRequestOptions) -> Self {
-        Self { message, options }
+        Self {
+            message,
+            options,
+            finalizer: None,
+        }
     }
 
     /// Get the set of request options associated with this request
@@ -63,12 +95,39 @@ impl DnsRequest {
         &self.options
     }
 
+    /// Sign this request with the finalizer, e.g. TSIG, instead of the signer of the connection
+    ///
+    /// The responses are verified with the verifier returned by the finalizer.
+    pub fn set_finalizer(&mut self, finalizer: Arc<dyn MessageFinalizer>) -> &mut Self {
+        self.finalizer = Some(finalizer);
+        self
+    }
+
+    /// The finalizer for this request, if it should not be signed with the signer of the connection
+    pub fn finalizer(&self) -> Option<&Arc<dyn MessageFinalizer>> {
+        self.finalizer.as_ref()
+    }
+
     /// Unwraps the raw message
     pub fn into_parts(self) -> (Message, DnsRequestOptions) {
         (self.message, self.options)
     }
 }
 
+impl PartialEq for DnsRequest {
+    fn eq(&self, other: &Self) -> bool {
+        let same_finalizer = match (&self.finalizer, &other.finalizer) {
+            (Some(finalizer), Some(other)) => Arc::ptr_eq(finalizer, other),
+            (None, None) => true,
+            _ => false,
+        };
+
+        self.message == other.message && self.options == other.options && same_finalizer
+    }
+}
+
+impl Eq for DnsRequest {}
+
 impl Deref for DnsRequest {
     type Target = Message;
     fn deref(&self) -> &Self::Target {
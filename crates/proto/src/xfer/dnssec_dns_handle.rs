@@ -11,7 +11,7 @@ use std::clone::Clone;
 use std::collections::HashSet;
 use std::error::Error;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, RwLock};
 
 use futures_util::future;
 use futures_util::future::{Future, FutureExt, TryFutureExt};
@@ -19,17 +19,21 @@ use futures_util::stream;
 use futures_util::stream::{Stream, TryStreamExt};
 use tracing::{debug, trace};
 
-use crate::op::{OpCode, Query};
+use crate::op::{Message, MessageType, OpCode, Query};
+use crate::rr::dnssec::nsec3_proof::Nsec3Chain;
 use crate::rr::dnssec::rdata::{DNSSECRData, DNSKEY, SIG};
 #[cfg(feature = "dnssec")]
 use crate::rr::dnssec::Verifier;
-use crate::rr::dnssec::{Algorithm, SupportedAlgorithms, TrustAnchor};
-use crate::rr::rdata::opt::EdnsOption;
+use crate::rr::dnssec::{Algorithm, Proof, ProvenRrset, SupportedAlgorithms, TrustAnchor};
+use crate::rr::rdata::opt::{EdnsCode, EdnsOption};
 use crate::rr::{DNSClass, Name, RData, Record, RecordType};
-use crate::xfer::dns_handle::DnsHandle;
+use crate::xfer::dns_handle::{build_message, DnsHandle};
 use crate::xfer::{DnsRequest, DnsRequestOptions, DnsResponse, FirstAnswer};
 use crate::{error::*, op::Edns};
 
+/// Collects the DNSKEY and DS records used to validate an RRset
+type ProofChain = Arc<Mutex<Vec<Record>>>;
+
 #[derive(Debug)]
 struct Rrset {
     pub(crate) name: Name,
@@ -50,10 +54,13 @@ where
     H: DnsHandle + Unpin + 'static,
 {
     handle: H,
-    trust_anchor: Arc<TrustAnchor>,
+    trust_anchor: Arc<RwLock<TrustAnchor>>,
     request_depth: usize,
     minimum_key_len: usize,
     minimum_algorithm: Algorithm, // used to prevent down grade attacks...
+    chain_trust_point: Option<Name>,
+    chain: Arc<Vec<Record>>,
+    proof_chain: Option<ProofChain>,
 }
 
 impl<H> DnssecDnsHandle<H>
@@ -78,15 +85,103 @@ where
     /// * `handle` - handle to use for all connections to a remote server.
     /// * `trust_anchor` - custom DNSKEYs that will be trusted, can be used to pin trusted keys.
     pub fn with_trust_anchor(handle: H, trust_anchor: TrustAnchor) -> Self {
+        Self::with_shared_trust_anchor(handle, Arc::new(RwLock::new(trust_anchor)))
+    }
+
+    /// Create a new DnssecDnsHandle wrapping the specified handle, with a TrustAnchor shared with
+    ///  the caller.
+    ///
+    /// The trust anchor can be replaced in place, e.g. after an RFC 5011 update of the keys, all
+    ///  clones of this handle then validate with the new keys.
+    ///
+    /// # Arguments
+    /// * `handle` - handle to use for all connections to a remote server.
+    /// * `trust_anchor` - custom DNSKEYs that will be trusted, can be used to pin trusted keys.
+    pub fn with_shared_trust_anchor(handle: H, trust_anchor: Arc<RwLock<TrustAnchor>>) -> Self {
         Self {
             handle,
-            trust_anchor: Arc::new(trust_anchor),
+            trust_anchor,
             request_depth: 0,
             minimum_key_len: 0,
             minimum_algorithm: Algorithm::RSASHA256,
+            chain_trust_point: None,
+            chain: Arc::new(Vec::new()),
+            proof_chain: None,
         }
     }
 
+    /// Requests the DNSSEC chain of each answer with the EDNS CHAIN option,
+    ///  [RFC 7901](https://tools.ietf.org/html/rfc7901)
+    ///
+    /// The DNSKEY and DS records of the chain returned by the upstream server are used to validate
+    ///  the answer, instead of querying each of them separately. Servers only return the chain
+    ///  over TCP, or with DNS cookies, to prevent amplification attacks.
+    ///
+    /// # Arguments
+    /// * `closest_trust_point` - the closest name to the answers with a trusted key, generally the
+    ///   root, as the trust anchors are the keys of the root zone
+    pub fn with_chain_query(mut self, closest_trust_point: Name) -> Self {
+        self.chain_trust_point = Some(closest_trust_point);
+        self
+    }
+
+    /// Adds the DNSSEC chain of the response, if the server returned the CHAIN option
+    fn with_chain_of(mut self, response: &DnsResponse) -> Self {
+        let has_chain = response
+            .extensions()
+            .as_ref()
+            .map_or(false, |edns| edns.option(EdnsCode::Chain).is_some());
+        if self.chain_trust_point.is_none() || !has_chain {
+            return self;
+        }
+
+        let chain = response
+            .name_servers()
+            .iter()
+            .chain(response.additionals())
+            .filter(|rr| is_chain_record(rr))
+            .cloned();
+
+        let mut records = self.chain.as_ref().clone();
+        records.extend(chain);
+        debug!("received chain of {} records", records.len());
+
+        self.chain = Arc::new(records);
+        self
+    }
+
+    /// Returns the response to the query from the received DNSSEC chain, only DNSKEY and DS
+    ///  queries can be answered
+    fn chain_response(&self, query: &Query) -> Option<DnsResponse> {
+        if !matches!(query.query_type(), RecordType::DNSKEY | RecordType::DS) {
+            return None;
+        }
+
+        let records = self
+            .chain
+            .iter()
+            .filter(|rr| rr.name() == query.name())
+            .filter(|rr| rr.record_type() == query.query_type() || covers(rr, query.query_type()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !records
+            .iter()
+            .any(|rr| rr.record_type() == query.query_type())
+        {
+            return None;
+        }
+
+        debug!("answering {} from the chain", query);
+        let mut message = Message::new();
+        message
+            .set_message_type(MessageType::Response)
+            .add_query(query.clone());
+        message.insert_answers(records);
+
+        Some(DnsResponse::from(message))
+    }
+
     /// An internal function used to clone the handle, but maintain some information back to the
     ///  original handle, such as the request_depth such that infinite recursion does
     ///  not occur.
@@ -97,7 +192,176 @@ where
             request_depth: self.request_depth + 1,
             minimum_key_len: self.minimum_key_len,
             minimum_algorithm: self.minimum_algorithm,
+            chain_trust_point: self.chain_trust_point.clone(),
+            chain: Arc::clone(&self.chain),
+            proof_chain: self.proof_chain.clone(),
+        }
+    }
+
+    /// Sets the DNSSEC options of a query
+    fn prepare_request(&self, request: &mut DnsRequest) {
+        // TODO: cache response of the server about understood algorithms
+        #[cfg(feature = "dnssec")]
+        {
+            let edns = request.extensions_mut().get_or_insert_with(Edns::new);
+            edns.set_dnssec_ok(true);
+
+            // send along the algorithms which are supported by this handle
+            let mut algorithms = SupportedAlgorithms::new();
+            #[cfg(feature = "ring")]
+            {
+                algorithms.set(Algorithm::ED25519);
+            }
+            #[cfg(feature = "openssl")]
+            {
+                algorithms.set(Algorithm::ED448);
+            }
+            algorithms.set(Algorithm::ECDSAP256SHA256);
+            algorithms.set(Algorithm::ECDSAP384SHA384);
+            algorithms.set(Algorithm::RSASHA256);
+            algorithms.set(Algorithm::RSASHA512);
+
+            let dau = EdnsOption::DAU(algorithms);
+            let dhu = EdnsOption::DHU(algorithms);
+
+            edns.options_mut().insert(dau);
+            edns.options_mut().insert(dhu);
+
+            if let Some(ref trust_point) = self.chain_trust_point {
+                edns.options_mut()
+                    .insert(EdnsOption::Chain(trust_point.clone()));
+            }
+        }
+
+        request.set_authentic_data(true);
+        request.set_checking_disabled(false);
+    }
+}
+
+impl<H> DnssecDnsHandle<H>
+where
+    H: DnsHandle<Error = ProtoError> + Sync + Unpin,
+{
+    /// Looks up the query, and returns the response with the result of the validation of each
+    ///  RRset
+    ///
+    /// Unlike `send()`, which drops the RRsets which failed to validate, all RRsets of the
+    ///  response are returned with their [`Proof`], and the DNSKEY and DS records used for their
+    ///  validation. For negative responses, the denial of existence by the NSEC or NSEC3 records
+    ///  is validated as well.
+    pub fn lookup_with_proof(
+        &self,
+        query: Query,
+        options: DnsRequestOptions,
+    ) -> impl Future<Output = ProtoResult<ProvenResponse>> + Send + 'static {
+        let base = self.clone();
+        let mut handle = self.clone_with_context();
+
+        async move {
+            let mut request = DnsRequest::new(build_message(query.clone(), options), options);
+            handle.prepare_request(&mut request);
+
+            let response = match handle.chain_response(&query) {
+                Some(response) => response,
+                None => handle.handle.send(request).first_answer().await?,
+            };
+            let handle = handle.with_chain_of(&response);
+
+            let mut rrset_types: Vec<(Name, RecordType)> = Vec::new();
+            for rr in response
+                .answers()
+                .iter()
+                .chain(response.name_servers())
+                .filter(|rr| !is_dnssec(rr, RecordType::RRSIG))
+            {
+                let rrset_type = (rr.name().clone(), rr.rr_type());
+                if !rrset_types.contains(&rrset_type) {
+                    rrset_types.push(rrset_type);
+                }
+            }
+
+            let proofs = rrset_types
+                .into_iter()
+                .map(|(name, record_type)| {
+                    let (rrset, rrsigs) =
+                        collect_rrset(&response, name, record_type, query.query_class());
+                    prove_rrset(
+                        handle.clone_with_context(),
+                        base.clone(),
+                        rrset,
+                        rrsigs,
+                        options,
+                    )
+                    .boxed()
+                })
+                .collect::<Vec<_>>();
+            let rrsets = future::join_all(proofs).await;
+
+            let denial = if response.answers().is_empty() {
+                Some(prove_denial(&query, &response, &rrsets))
+            } else {
+                None
+            };
+
+            Ok(ProvenResponse {
+                response,
+                rrsets,
+                denial,
+            })
+        }
+    }
+}
+
+/// A response, with the result of the validation of each of its RRsets
+#[derive(Clone, Debug)]
+pub struct ProvenResponse {
+    response: DnsResponse,
+    rrsets: Vec<ProvenRrset>,
+    denial: Option<Proof>,
+}
+
+impl ProvenResponse {
+    /// The response, including the RRsets which failed to validate
+    pub fn response(&self) -> &DnsResponse {
+        &self.response
+    }
+
+    /// Consumes this, returning the response
+    pub fn into_response(self) -> DnsResponse {
+        self.response
+    }
+
+    /// All RRsets of the answer and authority sections of the response
+    pub fn rrsets(&self) -> &[ProvenRrset] {
+        &self.rrsets
+    }
+
+    /// The RRsets of the answer section of the response
+    pub fn answers(&self) -> impl Iterator<Item = &ProvenRrset> + '_ {
+        self.rrsets.iter().filter(move |rrset| {
+            self.response
+                .answers()
+                .iter()
+                .any(|rr| rr.name() == rrset.name() && rr.rr_type() == rrset.record_type())
+        })
+    }
+
+    /// The result of the validation of the NSEC or NSEC3 records denying the existence of the
+    ///  queried records, `None` if the response has answers
+    pub fn denial(&self) -> Option<Proof> {
+        self.denial
+    }
+
+    /// The weakest proof of the answers, or the proof of the denial for negative responses
+    pub fn proof(&self) -> Proof {
+        if let Some(denial) = self.denial {
+            return denial;
         }
+
+        self.answers()
+            .map(ProvenRrset::proof)
+            .reduce(Proof::min)
+            .unwrap_or(Proof::Bogus)
     }
 }
 
@@ -134,54 +398,39 @@ where
                 .expect("no queries in request");
             let handle: Self = self.clone_with_context();
 
-            // TODO: cache response of the server about understood algorithms
-            #[cfg(feature = "dnssec")]
-            {
-                let edns = request.extensions_mut().get_or_insert_with(Edns::new);
-                edns.set_dnssec_ok(true);
-
-                // send along the algorithms which are supported by this handle
-                let mut algorithms = SupportedAlgorithms::new();
-                #[cfg(feature = "ring")]
-                {
-                    algorithms.set(Algorithm::ED25519);
-                }
-                algorithms.set(Algorithm::ECDSAP256SHA256);
-                algorithms.set(Algorithm::ECDSAP384SHA384);
-                algorithms.set(Algorithm::RSASHA256);
-
-                let dau = EdnsOption::DAU(algorithms);
-                let dhu = EdnsOption::DHU(algorithms);
-
-                edns.options_mut().insert(dau);
-                edns.options_mut().insert(dhu);
-            }
-
-            request.set_authentic_data(true);
-            request.set_checking_disabled(false);
+            self.prepare_request(&mut request);
             let dns_class = request
                 .queries()
                 .first()
                 .map_or(DNSClass::IN, Query::query_class);
             let options = *request.options();
 
+            // keys and DS records of the chain already received don't need to be queried
+            let responses: Self::Response = match self.chain_response(&query) {
+                Some(response) => Box::pin(stream::once(future::ok(response))),
+                None => Box::pin(self.handle.send(request)),
+            };
+
             return Box::pin(
-                self.handle
-                    .send(request)
+                responses
                     .and_then(move |message_response| {
                         // group the record sets by name and type
                         //  each rrset type needs to validated independently
                         debug!(
                             "validating message_response: {}, with {} trust_anchors",
                             message_response.id(),
-                            handle.trust_anchor.len(),
+                            handle
+                                .trust_anchor
+                                .read()
+                                .expect("trust anchor poisoned")
+                                .len(),
                         );
-                        verify_rrsets(handle.clone(), message_response, dns_class, options)
+                        let handle = handle.clone().with_chain_of(&message_response);
+                        verify_rrsets(handle, message_response, dns_class, options)
                     })
                     .and_then(move |verified_message| {
                         // at this point all of the message is verified.
-                        //  This is where NSEC (and possibly NSEC3) validation occurs
-                        // As of now, only NSEC is supported.
+                        //  This is where NSEC and NSEC3 validation occurs
                         if verified_message.answers().is_empty() {
                             // get SOA name
                             let soa_name = if let Some(soa_name) = verified_message
@@ -194,7 +443,10 @@ where
                                 soa_name
                             } else {
                                 return future::err(Self::Error::from(ProtoError::from(
-                                    "could not validate negative response missing SOA",
+                                    ProtoErrorKind::NegativeProofInvalid {
+                                        query: Box::new(query.clone()),
+                                        proof: RecordType::SOA,
+                                    },
                                 )));
                             };
 
@@ -204,10 +456,29 @@ where
                                 .filter(|rr| is_dnssec(rr, RecordType::NSEC))
                                 .collect::<Vec<_>>();
 
-                            if !verify_nsec(&query, soa_name, nsecs.as_slice()) {
+                            // zones signed with NSEC3 don't return any NSEC records
+                            if nsecs.is_empty() {
+                                let nsec3s = verified_message
+                                    .name_servers()
+                                    .iter()
+                                    .filter(|rr| is_dnssec(rr, RecordType::NSEC3))
+                                    .collect::<Vec<_>>();
+
+                                if !verify_nsec3(&query, soa_name, nsec3s.as_slice()) {
+                                    return future::err(Self::Error::from(ProtoError::from(
+                                        ProtoErrorKind::NegativeProofInvalid {
+                                            query: Box::new(query.clone()),
+                                            proof: RecordType::NSEC3,
+                                        },
+                                    )));
+                                }
+                            } else if !verify_nsec(&query, soa_name, nsecs.as_slice()) {
                                 // TODO change this to remove the NSECs, like we do for the others?
                                 return future::err(Self::Error::from(ProtoError::from(
-                                    "could not validate negative response with NSEC",
+                                    ProtoErrorKind::NegativeProofInvalid {
+                                        query: Box::new(query.clone()),
+                                        proof: RecordType::NSEC,
+                                    },
                                 )));
                             }
                         }
@@ -272,40 +543,9 @@ where
     // TODO: is there a way to get rid of this clone() safely?
     let mut rrsets_to_verify = Vec::with_capacity(rrset_types.len());
     for (name, record_type) in rrset_types {
-        // TODO: should we evaluate the different sections (answers and name_servers) separately?
-        let records: Vec<Record> = message_result
-            .answers()
-            .iter()
-            .chain(message_result.name_servers())
-            .chain(message_result.additionals())
-            .filter(|rr| rr.rr_type() == record_type && rr.name() == &name)
-            .cloned()
-            .collect();
-
-        let rrsigs: Vec<Record> = message_result
-            .answers()
-            .iter()
-            .chain(message_result.name_servers())
-            .chain(message_result.additionals())
-            .filter(|rr| is_dnssec(rr, RecordType::RRSIG))
-            .filter(|rr| {
-                if let Some(RData::DNSSEC(DNSSECRData::SIG(ref rrsig))) = rr.data() {
-                    rrsig.type_covered() == record_type
-                } else {
-                    false
-                }
-            })
-            .cloned()
-            .collect();
-
         // if there is already an active validation going on, assume the other validation will
         //  complete properly or error if it is invalid
-        let rrset = Rrset {
-            name,
-            record_type,
-            record_class: dns_class,
-            records,
-        };
+        let (rrset, rrsigs) = collect_rrset(&message_result, name, record_type, dns_class);
 
         // TODO: support non-IN classes?
         debug!(
@@ -322,10 +562,254 @@ where
     verify_all_rrsets(message_result, rrsets_to_verify).await
 }
 
+/// Collects the records of the RRset from all sections of the message, and the RRSIGs covering it
+fn collect_rrset(
+    message: &DnsResponse,
+    name: Name,
+    record_type: RecordType,
+    dns_class: DNSClass,
+) -> (Rrset, Vec<Record>) {
+    // TODO: should we evaluate the different sections (answers and name_servers) separately?
+    let records: Vec<Record> = message
+        .answers()
+        .iter()
+        .chain(message.name_servers())
+        .chain(message.additionals())
+        .filter(|rr| rr.rr_type() == record_type && rr.name() == &name)
+        .cloned()
+        .collect();
+
+    let rrsigs: Vec<Record> = message
+        .answers()
+        .iter()
+        .chain(message.name_servers())
+        .chain(message.additionals())
+        .filter(|rr| is_dnssec(rr, RecordType::RRSIG))
+        .filter(|rr| {
+            if let Some(RData::DNSSEC(DNSSECRData::SIG(ref rrsig))) = rr.data() {
+                rrsig.type_covered() == record_type
+            } else {
+                false
+            }
+        })
+        .cloned()
+        .collect();
+
+    let rrset = Rrset {
+        name,
+        record_type,
+        record_class: dns_class,
+        records,
+    };
+
+    (rrset, rrsigs)
+}
+
+/// Validates the RRset, and determines its proof if the validation failed
+async fn prove_rrset<H>(
+    mut handle: DnssecDnsHandle<H>,
+    base: DnssecDnsHandle<H>,
+    rrset: Rrset,
+    rrsigs: Vec<Record>,
+    options: DnsRequestOptions,
+) -> ProvenRrset
+where
+    H: DnsHandle<Error = ProtoError> + Sync + Unpin,
+{
+    let name = rrset.name.clone();
+    let record_type = rrset.record_type;
+    let records = rrset
+        .records
+        .iter()
+        .chain(rrsigs.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let chain = ProofChain::default();
+    handle.proof_chain = Some(Arc::clone(&chain));
+
+    let proof = match verify_rrset(handle, rrset, rrsigs, options).await {
+        Ok(_) => Proof::Secure,
+        Err(e) => {
+            debug!("failed to verify {} {}: {}", name, record_type, e);
+            match e.kind() {
+                ProtoErrorKind::RrsigsNotPresent { .. } => {
+                    prove_insecure(&base, &name, options).await
+                }
+                _ => proof_of_error(&e),
+            }
+        }
+    };
+
+    let mut unique = Vec::new();
+    if proof.is_secure() {
+        for record in chain.lock().expect("proof chain poisoned").iter() {
+            if !unique.contains(record) {
+                unique.push(record.clone());
+            }
+        }
+    }
+
+    ProvenRrset::new(name, record_type, proof, records, unique)
+}
+
+/// The proof of an RRset, which failed to validate with the error
+fn proof_of_error(error: &ProtoError) -> Proof {
+    match error.category() {
+        ErrorCategory::Io | ErrorCategory::Timeout | ErrorCategory::Busy => Proof::Indeterminate,
+        _ => Proof::Bogus,
+    }
+}
+
+/// Determines the proof of an unsigned RRset
+///
+/// The RRset is insecure if an unsigned delegation is proven between it and the closest zone
+///  with DS records, otherwise it should have been signed. The proof is searched for by
+///  querying the DS records of each name from the owner name of the RRset towards the root.
+async fn prove_insecure<H>(
+    handle: &DnssecDnsHandle<H>,
+    name: &Name,
+    options: DnsRequestOptions,
+) -> Proof
+where
+    H: DnsHandle<Error = ProtoError> + Sync + Unpin,
+{
+    let mut zone = name.clone();
+    while !zone.is_root() {
+        let response = handle
+            .clone()
+            .lookup(Query::query(zone.clone(), RecordType::DS), options)
+            .first_answer()
+            .await;
+
+        match response {
+            Ok(response) => {
+                if response
+                    .answers()
+                    .iter()
+                    .any(|rr| is_dnssec(rr, RecordType::DS))
+                {
+                    debug!("{} is signed, but {} is not", zone, name);
+                    return Proof::Bogus;
+                }
+
+                if proves_insecure_delegation(&response, &zone) {
+                    debug!("{} is an unsigned delegation", zone);
+                    return Proof::Insecure;
+                }
+            }
+            Err(e) if e.is_no_records_found() => (),
+            Err(e) => return proof_of_error(&e),
+        }
+
+        zone = zone.base_name();
+    }
+
+    // the root is always signed
+    Proof::Bogus
+}
+
+/// True if the validated NSEC or NSEC3 records of the response prove that the name is an
+///  unsigned delegation
+fn proves_insecure_delegation(response: &DnsResponse, name: &Name) -> bool {
+    let nsec = response
+        .name_servers()
+        .iter()
+        .filter(|rr| rr.name() == name)
+        .filter_map(|rr| {
+            rr.data()
+                .and_then(RData::as_dnssec)
+                .and_then(DNSSECRData::as_nsec)
+        })
+        .any(|nsec| {
+            let types = nsec.type_bit_maps();
+            types.contains(&RecordType::NS)
+                && !types.contains(&RecordType::DS)
+                && !types.contains(&RecordType::SOA)
+        });
+
+    nsec || response
+        .name_servers()
+        .iter()
+        .find(|rr| rr.record_type() == RecordType::SOA)
+        .and_then(|soa| Nsec3Chain::new(soa.name(), response.name_servers()).ok())
+        .map_or(false, |chain| chain.proves_insecure_delegation(name))
+}
+
+/// Determines the proof of the denial of existence of a negative response
+fn prove_denial(query: &Query, response: &DnsResponse, rrsets: &[ProvenRrset]) -> Proof {
+    let soa = match rrsets
+        .iter()
+        .find(|rrset| rrset.record_type() == RecordType::SOA)
+    {
+        Some(soa) => soa,
+        None => return Proof::Bogus,
+    };
+
+    let nsecs = response
+        .name_servers()
+        .iter()
+        .filter(|rr| is_dnssec(rr, RecordType::NSEC))
+        .collect::<Vec<_>>();
+    let nsec3s = response
+        .name_servers()
+        .iter()
+        .filter(|rr| is_dnssec(rr, RecordType::NSEC3))
+        .collect::<Vec<_>>();
+
+    let (proof_type, valid) = if !nsecs.is_empty() {
+        (RecordType::NSEC, verify_nsec(query, soa.name(), &nsecs))
+    } else if !nsec3s.is_empty() {
+        (RecordType::NSEC3, verify_nsec3(query, soa.name(), &nsec3s))
+    } else {
+        // signed zones must prove the denial
+        return match soa.proof() {
+            Proof::Secure => Proof::Bogus,
+            proof => proof,
+        };
+    };
+
+    if !valid {
+        return Proof::Bogus;
+    }
+
+    rrsets
+        .iter()
+        .filter(|rrset| rrset.record_type() == proof_type)
+        .fold(soa.proof(), |proof, rrset| proof.min(rrset.proof()))
+}
+
+/// Adds the record to the chain used to validate the RRset, if the chain is collected
+fn record_chain<'r>(chain: &Option<ProofChain>, records: impl IntoIterator<Item = &'r Record>) {
+    if let Some(chain) = chain {
+        chain
+            .lock()
+            .expect("proof chain poisoned")
+            .extend(records.into_iter().cloned());
+    }
+}
+
 fn is_dnssec(rr: &Record, dnssec_type: RecordType) -> bool {
     rr.rr_type().is_dnssec() && dnssec_type.is_dnssec() && rr.record_type() == dnssec_type
 }
 
+/// True if the record is an RRSIG covering the record type
+fn covers(rr: &Record, record_type: RecordType) -> bool {
+    if let Some(RData::DNSSEC(DNSSECRData::SIG(ref rrsig))) = rr.data() {
+        is_dnssec(rr, RecordType::RRSIG) && rrsig.type_covered() == record_type
+    } else {
+        false
+    }
+}
+
+/// True if the record is part of a DNSSEC chain, a DNSKEY or DS, or the RRSIG of one
+fn is_chain_record(rr: &Record) -> bool {
+    is_dnssec(rr, RecordType::DNSKEY)
+        || is_dnssec(rr, RecordType::DS)
+        || covers(rr, RecordType::DNSKEY)
+        || covers(rr, RecordType::DS)
+}
+
 async fn verify_all_rrsets<F, E>(
     message_result: DnsResponse,
     rrsets: Vec<F>,
@@ -494,6 +978,8 @@ where
             .filter_map(|(i, rdata)| {
                 if handle
                     .trust_anchor
+                    .read()
+                    .expect("trust anchor poisoned")
                     .contains_dnskey_bytes(rdata.public_key())
                 {
                     debug!(
@@ -511,6 +997,7 @@ where
         if !anchored_keys.is_empty() {
             let mut rrset = rrset;
             preserve(&mut rrset.records, anchored_keys);
+            record_chain(&handle.proof_chain, &rrset.records);
             return Ok(rrset);
         }
     }
@@ -564,13 +1051,24 @@ where
     if !valid_keys.is_empty() {
         let mut rrset = rrset;
         preserve(&mut rrset.records, valid_keys);
+        record_chain(
+            &handle.proof_chain,
+            ds_message
+                .answers()
+                .iter()
+                .filter(|ds| is_dnssec(ds, RecordType::DS))
+                .chain(&rrset.records),
+        );
 
         trace!("validated dnskey: {}", rrset.name);
         Ok(rrset)
     } else {
-        Err(E::from(ProtoError::from(ProtoErrorKind::Message(
-            "Could not validate all DNSKEYs",
-        ))))
+        Err(E::from(ProtoError::from(
+            ProtoErrorKind::RrsetNotVerified {
+                name: rrset.name,
+                record_type: rrset.record_type,
+            },
+        )))
     }
 }
 
@@ -692,7 +1190,12 @@ where
                     if rrset.records.iter().any(|r| {
                         if let Some(RData::DNSSEC(DNSSECRData::DNSKEY(ref dnskey))) = r.data() {
                             let dnskey_name = r.name();
-                            verify_rrset_with_dnskey(dnskey_name, dnskey, &sig, &rrset).is_ok()
+                            let verified =
+                                verify_rrset_with_dnskey(dnskey_name, dnskey, &sig, &rrset).is_ok();
+                            if verified {
+                                record_chain(&handle.proof_chain, Some(r));
+                            }
+                            verified
                         } else {
                             panic!("expected a DNSKEY here: {:?}", r.data());
                         }
@@ -704,9 +1207,10 @@ where
                 })
                 .next()
                 .ok_or_else(|| {
-                    E::from(ProtoError::from(ProtoErrorKind::Message(
-                        "self-signed dnskey is invalid",
-                    )))
+                    E::from(ProtoError::from(ProtoErrorKind::RrsetNotVerified {
+                        name: rrset.name.clone(),
+                        record_type: rrset.record_type,
+                    }))
                 }),
         )
         .map_ok(move |_| Arc::try_unwrap(rrset).expect("unable to unwrap Arc"))
@@ -722,20 +1226,22 @@ where
     //         susceptible until that algorithm is removed as an option.
     //        dns over TLS will mitigate this.
     //  TODO: strip RRSIGS to accepted algorithms and make algorithms configurable.
-    let verifications = rrsigs.into_iter()
+    let verifications = rrsigs
+        .into_iter()
         // this filter is technically unnecessary, can probably remove it...
         .filter(|rrsig| is_dnssec(rrsig, RecordType::RRSIG))
-        .map(|rrsig|
+        .map(|rrsig| {
             if let Some(RData::DNSSEC(DNSSECRData::SIG(sig))) = rrsig.into_data() {
                 // setting up the context explicitly.
                 sig
             } else {
                 panic!("expected a SIG here");
             }
-        )
+        })
         .map(|sig| {
             let rrset = Arc::clone(&rrset);
             let mut handle = handle.clone_with_context();
+            let proof_chain = handle.proof_chain.clone();
 
             handle
                 .lookup(
@@ -757,9 +1263,11 @@ where
                                 panic!("expected a DNSKEY here: {:?}", r.data());
                             }
                         )
-                        .map(|_| ())
-                        .ok_or_else(|| E::from(ProtoError::from(ProtoErrorKind::Message("validation failed")))))
-                )
+                        .map(|dnskey| record_chain(&proof_chain, Some(dnskey)))
+                        .ok_or_else(|| E::from(ProtoError::from(ProtoErrorKind::RrsetNotVerified {
+                            name: rrset.name.clone(),
+                            record_type: rrset.record_type,
+                        })))))
         })
         .collect::<Vec<_>>();
 
@@ -941,3 +1449,207 @@ pub fn verify_nsec(query: &Query, soa_name: &Name, nsecs: &[&Record]) -> bool {
         verify_nsec_coverage(&wildcard)
     }
 }
+
+/// Verifies NSEC3 records
+///
+/// ```text
+/// RFC 5155                         NSEC3                        March 2008
+///
+/// 8.  Validator Considerations
+///
+///    A validator MUST ignore NSEC3 RRs with unknown hash types.  The
+///    practical result of this is that responses containing only such NSEC3
+///    RRs will generally be considered bogus.
+///
+///    ...
+///
+///    In order to validate a response, validators MUST verify the closest
+///    encloser proof, and either the absence of the QTYPE at a matching
+///    NSEC3 RR, or that the next closer name and the wildcard at the
+///    closest encloser are covered.
+/// ```
+///
+/// See `nsec3_proof::Nsec3Chain::verify` for the individual proofs which are accepted.
+#[doc(hidden)]
+pub fn verify_nsec3(query: &Query, soa_name: &Name, nsec3s: &[&Record]) -> bool {
+    use crate::rr::dnssec::nsec3_proof::Nsec3Chain;
+
+    Nsec3Chain::new(soa_name, nsec3s.iter().copied())
+        .and_then(|chain| chain.verify(query.name(), query.query_type()))
+        .map(|proof| debug!("validated {:?} with NSEC3 for {}", proof, query.name()))
+        .map_err(|e| debug!("failed NSEC3 validation of {}: {}", query.name(), e))
+        .is_ok()
+}
+
+#[cfg(test)]
+#[cfg(feature = "dnssec")]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures_executor::block_on;
+
+    use super::*;
+    use crate::op::Edns;
+    use crate::xfer::FirstAnswer;
+
+    #[derive(Clone, Default)]
+    struct TestClient {
+        requests: Arc<Mutex<Vec<DnsRequest>>>,
+        answer: Option<Record>,
+    }
+
+    impl DnsHandle for TestClient {
+        type Response = Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send + Unpin>;
+        type Error = ProtoError;
+
+        fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
+            let request = request.into();
+            let query = request.queries().first().cloned();
+            self.requests.lock().unwrap().push(request);
+
+            // answers queries of the type of the answer, all others time out
+            match (query, &self.answer) {
+                (Some(query), Some(answer)) if query.query_type() == answer.record_type() => {
+                    let mut message = Message::new();
+                    message
+                        .set_message_type(MessageType::Response)
+                        .add_query(query)
+                        .add_answer(answer.clone());
+                    Box::new(stream::once(future::ok(DnsResponse::from(message))))
+                }
+                (_, Some(_)) => Box::new(stream::once(future::err(ProtoError::from(
+                    ProtoErrorKind::Timeout,
+                )))),
+                _ => Box::new(stream::once(future::err(ProtoError::from("no upstream")))),
+            }
+        }
+    }
+
+    fn chain_response(with_option: bool) -> DnsResponse {
+        let name = Name::from_ascii("example.com.").unwrap();
+        let dnskey = DNSKEY::new(true, true, false, Algorithm::ED25519, vec![1; 32]);
+        let rrsig = SIG::new(
+            RecordType::DNSKEY,
+            Algorithm::ED25519,
+            2,
+            3600,
+            1,
+            0,
+            1,
+            name.clone(),
+            vec![2; 64],
+        );
+
+        let mut rrsig =
+            Record::from_rdata(name.clone(), 3600, RData::DNSSEC(DNSSECRData::SIG(rrsig)));
+        rrsig.set_rr_type(RecordType::RRSIG);
+
+        let mut message = Message::new();
+        message.set_message_type(MessageType::Response);
+        message.add_name_server(Record::from_rdata(
+            name.clone(),
+            3600,
+            RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)),
+        ));
+        message.add_name_server(rrsig);
+        message.add_name_server(Record::from_rdata(
+            name,
+            3600,
+            RData::A([192, 0, 2, 1].into()),
+        ));
+
+        if with_option {
+            let mut edns = Edns::new();
+            edns.options_mut().insert(EdnsOption::Chain(Name::root()));
+            message.set_edns(edns);
+        }
+
+        DnsResponse::from(message)
+    }
+
+    #[test]
+    fn test_chain_query_option() {
+        let client = TestClient::default();
+        let mut handle = DnssecDnsHandle::new(client.clone()).with_chain_query(Name::root());
+
+        let query = Query::query(Name::from_ascii("www.example.com.").unwrap(), RecordType::A);
+        assert!(block_on(
+            handle
+                .lookup(query, DnsRequestOptions::default())
+                .first_answer()
+        )
+        .is_err());
+
+        let requests = client.requests.lock().unwrap();
+        let edns = requests[0].extensions().as_ref().expect("no edns");
+        assert_eq!(
+            edns.option(EdnsCode::Chain),
+            Some(&EdnsOption::Chain(Name::root()))
+        );
+    }
+
+    #[test]
+    fn test_chain_response() {
+        let handle = DnssecDnsHandle::new(TestClient::default()).with_chain_query(Name::root());
+        let dnskey_query = Query::query(
+            Name::from_ascii("example.com.").unwrap(),
+            RecordType::DNSKEY,
+        );
+
+        // the chain is only used if the server returned the option
+        let without_option = handle.clone().with_chain_of(&chain_response(false));
+        assert!(without_option.chain_response(&dnskey_query).is_none());
+
+        let handle = handle.with_chain_of(&chain_response(true));
+        let response = handle
+            .chain_response(&dnskey_query)
+            .expect("DNSKEY not in chain");
+        assert_eq!(response.answers().len(), 2);
+        assert!(response
+            .answers()
+            .iter()
+            .any(|rr| is_dnssec(rr, RecordType::DNSKEY)));
+        assert!(response
+            .answers()
+            .iter()
+            .any(|rr| covers(rr, RecordType::DNSKEY)));
+
+        // only DNSKEY and DS are part of the chain
+        let ds_query = Query::query(Name::from_ascii("example.com.").unwrap(), RecordType::DS);
+        assert!(handle.chain_response(&ds_query).is_none());
+        let a_query = Query::query(Name::from_ascii("example.com.").unwrap(), RecordType::A);
+        assert!(handle.chain_response(&a_query).is_none());
+
+        // the chain is shared with the handles of the validation
+        let child = handle.clone_with_context();
+        assert!(child.chain_response(&dnskey_query).is_some());
+    }
+
+    #[test]
+    fn test_lookup_with_proof_indeterminate() {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let answer = Record::from_rdata(name.clone(), 3600, RData::A([192, 0, 2, 1].into()));
+        let client = TestClient {
+            answer: Some(answer.clone()),
+            ..TestClient::default()
+        };
+        let handle = DnssecDnsHandle::new(client);
+
+        let response = block_on(handle.lookup_with_proof(
+            Query::query(name.clone(), RecordType::A),
+            DnsRequestOptions::default(),
+        ))
+        .expect("lookup failed");
+
+        // the A record is unsigned, and whether its zone is signed can't be determined
+        assert_eq!(response.rrsets().len(), 1);
+        let rrset = response.answers().next().expect("no answers");
+        assert_eq!(rrset.name(), &name);
+        assert_eq!(rrset.record_type(), RecordType::A);
+        assert_eq!(rrset.records(), &[answer]);
+        assert_eq!(rrset.proof(), Proof::Indeterminate);
+        assert!(rrset.chain().is_empty());
+        assert_eq!(response.denial(), None);
+        assert_eq!(response.proof(), Proof::Indeterminate);
+    }
+}
@@ -22,6 +22,7 @@ use crate::Time;
 mod dns_exchange;
 pub mod dns_handle;
 pub mod dns_multiplexer;
+pub mod dns_push;
 pub mod dns_request;
 pub mod dns_response;
 #[cfg(feature = "dnssec")]
@@ -35,11 +36,12 @@ pub use self::dns_exchange::{
 };
 pub use self::dns_handle::{DnsHandle, DnsStreamHandle};
 pub use self::dns_multiplexer::{DnsMultiplexer, DnsMultiplexerConnect};
+pub use self::dns_push::{DnsPushClient, PushChange, PushEvent};
 pub use self::dns_request::{DnsRequest, DnsRequestOptions};
 pub use self::dns_response::{DnsResponse, DnsResponseStream};
 #[cfg(feature = "dnssec")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
-pub use self::dnssec_dns_handle::DnssecDnsHandle;
+pub use self::dnssec_dns_handle::{DnssecDnsHandle, ProvenResponse};
 pub use self::retry_dns_handle::RetryDnsHandle;
 pub use self::serial_message::SerialMessage;
 
@@ -131,7 +131,7 @@ pub trait RetryableError {
 
 impl RetryableError for ProtoError {
     fn should_retry(&self) -> bool {
-        true
+        self.category().is_retryable()
     }
 
     fn attempted(&self) -> bool {
@@ -207,4 +207,14 @@ mod test {
         let test1 = Message::new();
         assert!(block_on(client.send(test1).first_answer()).is_err());
     }
+
+    #[test]
+    fn test_no_retry_on_negative_response() {
+        let error =
+            ProtoError::nx_error(Query::default(), None, None, ResponseCode::NXDomain, false);
+
+        assert!(!error.should_retry());
+        assert!(error.attempted());
+        assert!(ProtoError::from(ProtoErrorKind::Timeout).should_retry());
+    }
 }
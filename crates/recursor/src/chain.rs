@@ -0,0 +1,317 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The CNAME and DNAME chains followed from the query name to the canonical name
+
+use std::{sync::Arc, time::Instant};
+
+use tracing::debug;
+use trust_dns_proto::{
+    op::Query,
+    rr::{Name, RData, Record, RecordType},
+    serialize::binary::{BinDecodable, BinDecoder},
+};
+use trust_dns_resolver::lookup::Lookup;
+
+use crate::Error;
+
+/// The default maximum number of CNAMEs followed to answer a query
+pub const DEFAULT_MAX_CHAIN_LENGTH: usize = 8;
+
+/// The CNAME and DNAME records followed from the query name, RFC 1034 section 3.6.2 and RFC 6672
+///
+/// Each name is only followed once, and no more than the maximum length of CNAMEs.
+pub(crate) struct Chain {
+    max_length: usize,
+    names: Vec<Name>,
+    records: Vec<Record>,
+    valid_until: Option<Instant>,
+}
+
+impl Chain {
+    pub(crate) fn new(name: Name, max_length: usize) -> Self {
+        Self {
+            max_length,
+            names: vec![name],
+            records: Vec::new(),
+            valid_until: None,
+        }
+    }
+
+    /// Follows the CNAMEs of the lookup from the name of the query, returns the canonical name to
+    ///  query next, none if the lookup is the answer
+    ///
+    /// Returns an error if a name is followed twice, or if the chain is longer than the maximum.
+    pub(crate) fn follow(&mut self, query: &Query, lookup: &Lookup) -> Result<Option<Name>, Error> {
+        let query_type = query.query_type();
+        if matches!(
+            query_type,
+            RecordType::CNAME | RecordType::ANY | RecordType::DNAME
+        ) {
+            return Ok(None);
+        }
+
+        let mut name = query.name().clone();
+        loop {
+            let records = lookup.records().iter().filter(|r| r.name() == &name);
+            let mut target = None;
+            for record in records {
+                match record.data() {
+                    // the records of the name in the lookup are the answer, with the CNAMEs to it
+                    _ if record.record_type() == query_type => return Ok(None),
+                    Some(RData::CNAME(cname)) => target = Some(cname.clone()),
+                    _ => (),
+                }
+            }
+
+            match target {
+                Some(target) => {
+                    self.push(target.clone())?;
+                    name = target;
+                }
+                None => break,
+            }
+        }
+
+        if &name == query.name() {
+            return Ok(None);
+        }
+
+        debug!("following {} to {}", query.name(), name);
+        self.records.extend(
+            lookup
+                .records()
+                .iter()
+                .filter(|r| is_chain_record(r))
+                .cloned(),
+        );
+        self.valid_until = Some(
+            self.valid_until
+                .map_or(lookup.valid_until(), |v| v.min(lookup.valid_until())),
+        );
+        Ok(Some(name))
+    }
+
+    /// The answer of the query, the records of the chain followed by those of the lookup of the
+    ///  canonical name
+    pub(crate) fn answer(self, query: Query, lookup: Lookup) -> Lookup {
+        if self.records.is_empty() {
+            return lookup;
+        }
+
+        let valid_until = self
+            .valid_until
+            .map_or(lookup.valid_until(), |v| v.min(lookup.valid_until()));
+        let records = self
+            .records
+            .into_iter()
+            .chain(lookup.records().iter().cloned())
+            .collect::<Vec<_>>();
+
+        Lookup::new_with_deadline(query, Arc::from(records), valid_until)
+    }
+
+    fn push(&mut self, name: Name) -> Result<(), Error> {
+        if self.names.contains(&name) {
+            return Err(format!("CNAME loop at {}", name).into());
+        }
+        if self.names.len() > self.max_length {
+            return Err(format!(
+                "CNAME chain of {} longer than {}",
+                self.names[0], self.max_length
+            )
+            .into());
+        }
+
+        self.names.push(name);
+        Ok(())
+    }
+}
+
+/// The target of the DNAME record
+pub(crate) fn dname_target(record: &Record) -> Option<Name> {
+    match record.data() {
+        Some(RData::Unknown { code: 39, rdata }) => {
+            Name::read(&mut BinDecoder::new(rdata.anything())).ok()
+        }
+        _ => None,
+    }
+}
+
+/// The CNAME of the name synthesized from the DNAME record of one of its ancestors, RFC 6672
+///  section 2.2
+///
+/// Returns none if the record is not a DNAME above the name, or if the synthesized name is too
+///  long.
+pub(crate) fn synthesize_cname(dname: &Record, name: &Name) -> Option<Record> {
+    let owner = dname.name();
+    if owner == name || !owner.zone_of(name) {
+        return None;
+    }
+
+    let target = dname_target(dname)?;
+    let prefix = usize::from(name.num_labels() - owner.num_labels());
+    let cname = Name::from_labels(name.iter().take(prefix).chain(target.iter())).ok()?;
+
+    let mut record = Record::from_rdata(name.clone(), dname.ttl(), RData::CNAME(cname));
+    record.set_dns_class(dname.dns_class());
+    Some(record)
+}
+
+/// True if the record is a CNAME or a DNAME, or an RRSIG of them
+fn is_chain_record(record: &Record) -> bool {
+    #[cfg(feature = "dnssec")]
+    {
+        use trust_dns_proto::rr::dnssec::rdata::DNSSECRData;
+
+        if let Some(RData::DNSSEC(DNSSECRData::SIG(rrsig))) = record.data() {
+            return matches!(rrsig.type_covered(), RecordType::CNAME | RecordType::DNAME);
+        }
+    }
+
+    matches!(record.record_type(), RecordType::CNAME | RecordType::DNAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, str::FromStr, time::Duration};
+
+    use trust_dns_proto::{rr::rdata::NULL, serialize::binary::BinEncodable};
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn cname(from: &str, to: &str) -> Record {
+        Record::from_rdata(name(from), 300, RData::CNAME(name(to)))
+    }
+
+    fn a(owner: &str) -> Record {
+        Record::from_rdata(name(owner), 300, RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+    }
+
+    fn dname(owner: &str, target: &str) -> Record {
+        let rdata = NULL::with(name(target).to_bytes().unwrap());
+        Record::from_rdata(name(owner), 300, RData::Unknown { code: 39, rdata })
+    }
+
+    fn lookup(owner: &str, records: Vec<Record>) -> Lookup {
+        Lookup::new_with_deadline(
+            Query::query(name(owner), RecordType::A),
+            Arc::from(records),
+            Instant::now() + Duration::from_secs(300),
+        )
+    }
+
+    #[test]
+    fn test_follow_chain() {
+        let query = Query::query(name("www.example.com."), RecordType::A);
+        let mut chain = Chain::new(query.name().clone(), DEFAULT_MAX_CHAIN_LENGTH);
+
+        // the CNAMEs in the lookup are followed to the name which is not in it
+        let first = lookup(
+            "www.example.com.",
+            vec![
+                cname("www.example.com.", "cdn.example.com."),
+                cname("cdn.example.com.", "edge.example.net."),
+            ],
+        );
+        assert_eq!(
+            chain.follow(&query, &first).unwrap(),
+            Some(name("edge.example.net."))
+        );
+
+        let next = Query::query(name("edge.example.net."), RecordType::A);
+        let last = lookup("edge.example.net.", vec![a("edge.example.net.")]);
+        assert_eq!(chain.follow(&next, &last).unwrap(), None);
+
+        let answer = chain.answer(query, last);
+        let types = answer
+            .records()
+            .iter()
+            .map(Record::record_type)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![RecordType::CNAME, RecordType::CNAME, RecordType::A]
+        );
+    }
+
+    #[test]
+    fn test_answer_in_lookup() {
+        let query = Query::query(name("www.example.com."), RecordType::A);
+        let mut chain = Chain::new(query.name().clone(), DEFAULT_MAX_CHAIN_LENGTH);
+
+        let answer = lookup(
+            "www.example.com.",
+            vec![
+                cname("www.example.com.", "cdn.example.com."),
+                a("cdn.example.com."),
+            ],
+        );
+        assert_eq!(chain.follow(&query, &answer).unwrap(), None);
+        assert_eq!(chain.answer(query, answer.clone()), answer);
+    }
+
+    #[test]
+    fn test_loop() {
+        let query = Query::query(name("a.example.com."), RecordType::A);
+        let mut chain = Chain::new(query.name().clone(), DEFAULT_MAX_CHAIN_LENGTH);
+
+        let looping = lookup(
+            "a.example.com.",
+            vec![
+                cname("a.example.com.", "b.example.com."),
+                cname("b.example.com.", "a.example.com."),
+            ],
+        );
+        let error = chain.follow(&query, &looping).unwrap_err();
+        assert!(error.to_string().contains("loop"), "{}", error);
+    }
+
+    #[test]
+    fn test_max_length() {
+        let query = Query::query(name("a.example.com."), RecordType::A);
+        let mut chain = Chain::new(query.name().clone(), 2);
+
+        let first = lookup(
+            "a.example.com.",
+            vec![
+                cname("a.example.com.", "b.example.com."),
+                cname("b.example.com.", "c.example.com."),
+            ],
+        );
+        assert!(chain.follow(&query, &first).unwrap().is_some());
+
+        // the chain is followed across lookups
+        let next = Query::query(name("c.example.com."), RecordType::A);
+        let second = lookup(
+            "c.example.com.",
+            vec![cname("c.example.com.", "d.example.com.")],
+        );
+        assert!(chain.follow(&next, &second).is_err());
+    }
+
+    #[test]
+    fn test_synthesize_cname() {
+        let dname = dname("example.com.", "example.net.");
+        assert_eq!(dname_target(&dname), Some(name("example.net.")));
+
+        let cname = synthesize_cname(&dname, &name("www.sub.example.com.")).unwrap();
+        assert_eq!(cname.name(), &name("www.sub.example.com."));
+        assert_eq!(
+            cname.data().and_then(RData::as_cname),
+            Some(&name("www.sub.example.net."))
+        );
+
+        // the owner of the DNAME itself is not redirected
+        assert!(synthesize_cname(&dname, &name("example.com.")).is_none());
+        assert!(synthesize_cname(&dname, &name("www.example.org.")).is_none());
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNSSEC validation of the answers of the recursor
+
+use std::{io, pin::Pin, sync::Arc, time::Instant};
+
+use futures_util::{
+    future::FutureExt,
+    stream::{self, Stream},
+};
+use tracing::debug;
+use trust_dns_proto::{
+    error::{ProtoError, ProtoErrorKind},
+    op::{Message, MessageType, Query},
+    rr::{Record, RecordType},
+    xfer::{DnsRequest, DnsResponse},
+    DnsHandle,
+};
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+
+use crate::{Error, ErrorKind, Recursor};
+
+/// Resolves the requests of the DNSSEC validation with the recursor
+///
+/// The DNSKEY and DS records needed to validate an answer are resolved like any other query, and
+///  share the caches of the recursor.
+#[derive(Clone)]
+pub(crate) struct RecursorHandle {
+    recursor: Recursor,
+    query: Query,
+    request_time: Instant,
+}
+
+impl RecursorHandle {
+    /// Creates a handle validating the answer of the query
+    pub(crate) fn new(recursor: Recursor, query: Query, request_time: Instant) -> Self {
+        Self {
+            recursor,
+            query,
+            request_time,
+        }
+    }
+
+    async fn resolve(self, query: Query) -> Result<DnsResponse, ProtoError> {
+        match self
+            .recursor
+            .resolve_unvalidated(query.clone(), self.request_time)
+            .await
+        {
+            Ok(lookup) => {
+                let mut message = response(query);
+                message.insert_answers(lookup.records().to_vec());
+                Ok(DnsResponse::from(message))
+            }
+            Err(e) => {
+                // the negative response, with its authority section, is in the cache
+                let negative = self
+                    .recursor
+                    .record_cache
+                    .get(&query, self.request_time)
+                    .and_then(Result::err);
+
+                match negative {
+                    Some(negative) => self.negative_response(query, negative),
+                    None => Err(proto_error(&e)),
+                }
+            }
+        }
+    }
+
+    /// Returns the negative response of the query
+    ///
+    /// The denial of the answer to the validated query is always returned as a response, so that
+    ///  it is proven. Others, e.g. of DS queries, are only responses if they have NSEC or NSEC3
+    ///  records which may prove an unsigned delegation, they are otherwise returned as errors so
+    ///  that the validation continues with the parent zone.
+    fn negative_response(
+        &self,
+        query: Query,
+        negative: ResolveError,
+    ) -> Result<DnsResponse, ProtoError> {
+        let (response_code, soa, authorities) = match negative.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                response_code,
+                soa,
+                authorities,
+                ..
+            } => (*response_code, soa.clone(), authorities.clone()),
+            _ => return Err(proto_error(&negative.into())),
+        };
+
+        let authorities = match authorities {
+            Some(authorities) => authorities,
+            None => Arc::from(soa.into_iter().map(|soa| *soa).collect::<Vec<_>>()),
+        };
+
+        if query != self.query && !authorities.iter().any(is_denial) {
+            debug!("no proof of the denial of {}", query);
+            return Err(ProtoError::nx_error(
+                query,
+                None,
+                None,
+                response_code,
+                false,
+            ));
+        }
+
+        let mut message = response(query);
+        message
+            .set_response_code(response_code)
+            .insert_name_servers(authorities.to_vec());
+        Ok(DnsResponse::from(message))
+    }
+}
+
+impl DnsHandle for RecursorHandle {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+    type Error = ProtoError;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, request: R) -> Self::Response {
+        let request = request.into();
+        let query = match request.queries().first() {
+            Some(query) => query.clone(),
+            None => return Box::pin(stream::once(async { Err("no query in request".into()) })),
+        };
+
+        Box::pin(stream::once(self.clone().resolve(query).boxed()))
+    }
+}
+
+/// An empty response to the query
+fn response(query: Query) -> Message {
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Response)
+        .set_authentic_data(false)
+        .add_query(query);
+    message
+}
+
+fn is_denial(record: &Record) -> bool {
+    matches!(record.record_type(), RecordType::NSEC | RecordType::NSEC3)
+}
+
+/// Converts the error of the recursor, timeouts and network errors are `Indeterminate` for the
+///  validation, others `Bogus`
+fn proto_error(error: &Error) -> ProtoError {
+    match error.kind() {
+        ErrorKind::Timeout => ProtoErrorKind::Timeout.into(),
+        ErrorKind::Io(e) => ProtoErrorKind::from(io::Error::from(e.kind())).into(),
+        ErrorKind::Proto(e) => e.clone(),
+        ErrorKind::Resolve(e) => match e.kind() {
+            ResolveErrorKind::Timeout => ProtoErrorKind::Timeout.into(),
+            ResolveErrorKind::Io(e) => ProtoErrorKind::from(io::Error::from(e.kind())).into(),
+            ResolveErrorKind::NoConnections => {
+                ProtoErrorKind::from(io::Error::from(io::ErrorKind::NotConnected)).into()
+            }
+            ResolveErrorKind::Proto(e) => e.clone(),
+            _ => ProtoError::from(e.to_string()),
+        },
+        _ => ProtoError::from(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use trust_dns_proto::op::ResponseCode;
+    use trust_dns_proto::rr::{
+        dnssec::rdata::{DNSSECRData, NSEC},
+        rdata::SOA,
+        Name, RData,
+    };
+    use trust_dns_resolver::config::{NameServerConfigGroup, ResolverOpts};
+
+    use super::*;
+
+    fn soa() -> Record {
+        let origin = Name::from_str("example.com.").unwrap();
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(origin.clone(), origin, 1, 3600, 600, 86400, 300)),
+        )
+    }
+
+    fn negative(query: &Query, authorities: Vec<Record>) -> ResolveError {
+        ResolveErrorKind::NoRecordsFound {
+            query: Box::new(query.clone()),
+            soa: Some(Box::new(soa())),
+            negative_ttl: Some(300),
+            response_code: ResponseCode::NXDomain,
+            trusted: true,
+            authorities: Some(Arc::from(authorities)),
+        }
+        .into()
+    }
+
+    fn handle(query: Query) -> RecursorHandle {
+        let roots =
+            NameServerConfigGroup::from_ips_clear(&["192.0.2.1".parse().unwrap()], 53, true);
+        let recursor = Recursor::new_with_options(roots, ResolverOpts::default()).unwrap();
+        RecursorHandle::new(recursor, query, Instant::now())
+    }
+
+    #[test]
+    fn test_negative_response_of_validated_query() {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let handle = handle(query.clone());
+
+        let response = handle
+            .negative_response(query.clone(), negative(&query, vec![soa()]))
+            .expect("the denial of the validated query is a response");
+
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert_eq!(response.queries(), &[query]);
+        assert_eq!(response.name_servers(), &[soa()]);
+    }
+
+    #[test]
+    fn test_negative_response_without_denial() {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ds = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::DS);
+        let handle = handle(query);
+
+        let error = handle
+            .negative_response(ds.clone(), negative(&ds, vec![soa()]))
+            .expect_err("unproven denials of other queries are errors");
+        assert!(error.is_no_records_found());
+    }
+
+    #[test]
+    fn test_negative_response_with_denial() {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let ds = Query::query(Name::from_str("sub.example.com.").unwrap(), RecordType::DS);
+        let handle = handle(query);
+
+        let nsec = Record::from_rdata(
+            ds.name().clone(),
+            300,
+            RData::DNSSEC(DNSSECRData::NSEC(NSEC::new(
+                Name::from_str("www.example.com.").unwrap(),
+                vec![RecordType::NS, RecordType::NSEC, RecordType::RRSIG],
+            ))),
+        );
+
+        let response = handle
+            .negative_response(ds.clone(), negative(&ds, vec![soa(), nsec.clone()]))
+            .expect("denials with NSEC records are responses");
+        assert_eq!(response.name_servers(), &[soa(), nsec]);
+    }
+}
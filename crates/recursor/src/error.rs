@@ -15,6 +15,8 @@ use enum_as_inner::EnumAsInner;
 use thiserror::Error;
 use trust_dns_resolver::Name;
 
+#[cfg(feature = "dnssec")]
+use crate::proto::{op::Query, rr::dnssec::Proof};
 #[cfg(feature = "backtrace")]
 use crate::proto::{trace, ExtBacktrace};
 use crate::{
@@ -53,6 +55,26 @@ pub enum ErrorKind {
     /// A request timed out
     #[error("request timed out")]
     Timeout,
+
+    /// The work to answer the query exceeded one of the [`RecursorLimits`](crate::RecursorLimits)
+    #[error("{limit} limit of {max} exceeded")]
+    LimitExceeded {
+        /// The limit, `queries` or `depth`
+        limit: &'static str,
+        /// The value of the limit
+        max: usize,
+    },
+
+    /// The DNSSEC validation of the answer failed, it is `Bogus` or `Indeterminate`
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    #[error("validation of {query} failed: {proof}")]
+    ValidationFailed {
+        /// The query whose answer failed to validate
+        query: Box<Query>,
+        /// The result of the validation
+        proof: Proof,
+    },
 }
 
 /// The error type for errors that get returned in the crate
@@ -156,6 +178,12 @@ impl Clone for ErrorKind {
             Proto(ref proto) => Proto(proto.clone()),
             Resolve(ref resolve) => Resolve(resolve.clone()),
             Timeout => Self::Timeout,
+            LimitExceeded { limit, max } => LimitExceeded { limit, max },
+            #[cfg(feature = "dnssec")]
+            ValidationFailed { ref query, proof } => ValidationFailed {
+                query: query.clone(),
+                proof,
+            },
         }
     }
 }
@@ -0,0 +1,371 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The delegations of the zones and the addresses of their name servers, cached apart from the
+//!  records of the answers
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use lru_cache::LruCache;
+use parking_lot::Mutex;
+use tracing::debug;
+
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+
+/// The number of delegations, and of name servers whose addresses are cached
+pub(crate) const DEFAULT_INFRA_CACHE_SIZE: usize = 4096;
+
+/// The longest time a delegation is used before it is revalidated at the parent zone, whatever
+///  its TTL
+pub(crate) const MAX_DELEGATION_TTL: Duration = Duration::from_secs(6 * 3600);
+
+/// How much the addresses of a name server are trusted, see RFC 2181 section 5.4.1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Trust {
+    /// From the additional section of a referral, in the bailiwick of the referring zone
+    Glue,
+    /// From an answer for the name of the name server
+    Authoritative,
+}
+
+/// The name servers of a zone, from the NS records of the parent zone
+#[derive(Clone, Debug)]
+pub(crate) struct Delegation {
+    name_servers: Arc<[Name]>,
+    valid_until: Instant,
+}
+
+impl Delegation {
+    pub(crate) fn name_servers(&self) -> &[Name] {
+        &self.name_servers
+    }
+
+    pub(crate) fn valid_until(&self) -> Instant {
+        self.valid_until
+    }
+
+    /// True if the delegation must be revalidated at the parent zone
+    pub(crate) fn is_expired(&self, now: Instant) -> bool {
+        self.valid_until <= now
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Addresses {
+    ips: Arc<[IpAddr]>,
+    trust: Trust,
+    valid_until: Instant,
+}
+
+/// The infrastructure cache, of the delegations and of the addresses of the name servers
+///
+/// The entries expire independently of each other and of the record cache. Glue is only kept for
+///  the names in the bailiwick of the zone which referred to them, and never replaces the
+///  addresses of an answer before they expire, so that a zone can't redirect the queries for the
+///  name servers of other zones. Expired delegations are kept, to be revalidated at the parent
+///  zone instead of trusted from the record cache.
+pub(crate) struct InfraCache {
+    delegations: Mutex<LruCache<Name, Delegation>>,
+    addresses: Mutex<LruCache<Name, Addresses>>,
+}
+
+impl InfraCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            delegations: Mutex::new(LruCache::new(capacity)),
+            addresses: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// The delegation of the zone, expired or not
+    pub(crate) fn delegation(&self, zone: &Name) -> Option<Delegation> {
+        self.delegations.lock().get_mut(zone).cloned()
+    }
+
+    /// Caches the name servers of the zone, valid until `valid_until` but no longer than
+    ///  [`MAX_DELEGATION_TTL`]
+    pub(crate) fn insert_delegation(
+        &self,
+        zone: Name,
+        name_servers: Vec<Name>,
+        valid_until: Instant,
+        now: Instant,
+    ) -> Delegation {
+        let delegation = Delegation {
+            name_servers: Arc::from(name_servers),
+            valid_until: valid_until.min(now + MAX_DELEGATION_TTL),
+        };
+
+        self.delegations.lock().insert(zone, delegation.clone());
+        delegation
+    }
+
+    /// The addresses of the name server, and until when they are valid
+    pub(crate) fn addresses(&self, name: &Name, now: Instant) -> Option<(Arc<[IpAddr]>, Instant)> {
+        let mut addresses = self.addresses.lock();
+        let entry = addresses.get_mut(name)?;
+        if entry.valid_until <= now {
+            addresses.remove(name);
+            return None;
+        }
+
+        Some((Arc::clone(&entry.ips), entry.valid_until))
+    }
+
+    /// Caches the addresses of the name server, unless more trusted addresses are cached
+    pub(crate) fn insert_addresses(
+        &self,
+        name: Name,
+        ips: Vec<IpAddr>,
+        trust: Trust,
+        valid_until: Instant,
+        now: Instant,
+    ) {
+        if ips.is_empty() {
+            return;
+        }
+
+        let mut addresses = self.addresses.lock();
+        if let Some(entry) = addresses.get_mut(&name) {
+            if entry.trust > trust && entry.valid_until > now {
+                debug!("ignoring {:?} addresses of {}", trust, name);
+                return;
+            }
+        }
+
+        addresses.insert(
+            name,
+            Addresses {
+                ips: Arc::from(ips),
+                trust,
+                valid_until: valid_until.min(now + MAX_DELEGATION_TTL),
+            },
+        );
+    }
+
+    /// Caches the A and AAAA records of the additional section of a response of the zone's name
+    ///  servers, which are in its bailiwick
+    pub(crate) fn insert_glue(&self, zone: &Name, records: Vec<Record>, now: Instant) {
+        let mut glue = HashMap::<Name, (Vec<IpAddr>, u32)>::new();
+        for record in records {
+            if !zone.zone_of(record.name()) {
+                debug!("ignoring glue of {} out of {}", record.name(), zone);
+                continue;
+            }
+
+            if let Some(ip) = record.data().and_then(RData::to_ip_addr) {
+                let (ips, ttl) = glue
+                    .entry(record.name().clone())
+                    .or_insert((Vec::new(), u32::MAX));
+                ips.push(ip);
+                *ttl = (*ttl).min(record.ttl());
+            }
+        }
+
+        for (name, (ips, ttl)) in glue {
+            let valid_until = now + Duration::from_secs(u64::from(ttl));
+            self.insert_addresses(name, ips, Trust::Glue, valid_until, now);
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        self.delegations.lock().clear();
+        self.addresses.lock().clear();
+    }
+
+    /// Removes the delegation of the name, and the addresses of the name server of that name
+    pub(crate) fn remove(&self, name: &Name) {
+        self.delegations.lock().remove(name);
+        self.addresses.lock().remove(name);
+    }
+
+    /// Removes the delegations and addresses of the name and all names below it
+    pub(crate) fn remove_subtree(&self, name: &Name) {
+        let mut delegations = self.delegations.lock();
+        let zones = delegations
+            .iter()
+            .map(|(zone, _)| zone)
+            .filter(|zone| name.zone_of(zone))
+            .cloned()
+            .collect::<Vec<_>>();
+        for zone in zones {
+            delegations.remove(&zone);
+        }
+
+        let mut addresses = self.addresses.lock();
+        let names = addresses
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|ns| name.zone_of(ns))
+            .cloned()
+            .collect::<Vec<_>>();
+        for ns in names {
+            addresses.remove(&ns);
+        }
+    }
+}
+
+/// True if the record is an address, or the RRSIG of addresses, which are glue in the additional
+///  section of referrals
+pub(crate) fn is_address(record: &Record) -> bool {
+    #[cfg(feature = "dnssec")]
+    {
+        use trust_dns_proto::rr::dnssec::rdata::DNSSECRData;
+
+        if let Some(RData::DNSSEC(DNSSECRData::SIG(rrsig))) = record.data() {
+            return matches!(rrsig.type_covered(), RecordType::A | RecordType::AAAA);
+        }
+    }
+
+    matches!(record.record_type(), RecordType::A | RecordType::AAAA)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    };
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, last))
+    }
+
+    #[test]
+    fn test_delegation_revalidation() {
+        let now = Instant::now();
+        let cache = InfraCache::new(8);
+        assert!(cache.delegation(&name("example.com.")).is_none());
+
+        let delegation = cache.insert_delegation(
+            name("example.com."),
+            vec![name("ns1.example.com."), name("ns2.example.net.")],
+            now + Duration::from_secs(2 * 86400),
+            now,
+        );
+        assert_eq!(delegation.valid_until(), now + MAX_DELEGATION_TTL);
+
+        let delegation = cache.delegation(&name("example.com.")).unwrap();
+        assert_eq!(delegation.name_servers().len(), 2);
+        assert!(!delegation.is_expired(now));
+        // expired delegations are kept, to be revalidated
+        assert!(delegation.is_expired(now + MAX_DELEGATION_TTL));
+    }
+
+    #[test]
+    fn test_glue_bailiwick() {
+        let now = Instant::now();
+        let cache = InfraCache::new(8);
+        cache.insert_glue(
+            &name("example.com."),
+            vec![
+                Record::from_rdata(
+                    name("ns1.example.com."),
+                    300,
+                    RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+                ),
+                Record::from_rdata(
+                    name("ns1.example.com."),
+                    60,
+                    RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+                ),
+                Record::from_rdata(
+                    name("ns.example.net."),
+                    300,
+                    RData::A(Ipv4Addr::new(192, 0, 2, 2)),
+                ),
+            ],
+            now,
+        );
+
+        let (ips, valid_until) = cache.addresses(&name("ns1.example.com."), now).unwrap();
+        assert_eq!(ips.len(), 2);
+        assert_eq!(valid_until, now + Duration::from_secs(60));
+        // the glue of other zones is ignored
+        assert!(cache.addresses(&name("ns.example.net."), now).is_none());
+
+        // expired addresses are removed
+        assert!(cache
+            .addresses(&name("ns1.example.com."), now + Duration::from_secs(60))
+            .is_none());
+    }
+
+    #[test]
+    fn test_glue_does_not_replace_answers() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(300);
+        let cache = InfraCache::new(8);
+        let ns = name("ns.example.com.");
+
+        cache.insert_addresses(ns.clone(), vec![ip(1)], Trust::Authoritative, later, now);
+        cache.insert_addresses(ns.clone(), vec![ip(2)], Trust::Glue, later, now);
+        assert_eq!(&*cache.addresses(&ns, now).unwrap().0, &[ip(1)]);
+
+        // once the answer expires, glue is accepted again
+        let expired = later + Duration::from_secs(1);
+        cache.insert_addresses(
+            ns.clone(),
+            vec![ip(2)],
+            Trust::Glue,
+            expired + Duration::from_secs(300),
+            later,
+        );
+        assert_eq!(&*cache.addresses(&ns, later).unwrap().0, &[ip(2)]);
+
+        // answers replace glue
+        cache.insert_addresses(
+            ns.clone(),
+            vec![ip(3)],
+            Trust::Authoritative,
+            expired,
+            later,
+        );
+        assert_eq!(&*cache.addresses(&ns, later).unwrap().0, &[ip(3)]);
+    }
+
+    #[test]
+    fn test_remove_subtree() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(300);
+        let cache = InfraCache::new(8);
+        cache.insert_delegation(
+            name("example.com."),
+            vec![name("ns.example.com.")],
+            later,
+            now,
+        );
+        cache.insert_delegation(
+            name("example.net."),
+            vec![name("ns.example.com.")],
+            later,
+            now,
+        );
+        cache.insert_addresses(
+            name("ns.example.com."),
+            vec![ip(1)],
+            Trust::Glue,
+            later,
+            now,
+        );
+
+        cache.remove_subtree(&name("example.com."));
+        assert!(cache.delegation(&name("example.com.")).is_none());
+        assert!(cache.addresses(&name("ns.example.com."), now).is_none());
+        assert!(cache.delegation(&name("example.net.")).is_some());
+    }
+}
@@ -26,12 +26,28 @@
 #![recursion_limit = "2048"]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+mod chain;
+#[cfg(feature = "dnssec")]
+mod dnssec;
 pub mod error;
+mod infra_cache;
+mod limits;
+mod metrics;
+mod name_server_stats;
+#[cfg(feature = "dnssec")]
+mod nsec_cache;
+mod query_observer;
 mod recursor;
 pub(crate) mod recursor_pool;
 
+pub use chain::DEFAULT_MAX_CHAIN_LENGTH;
 pub use error::{Error, ErrorKind};
-pub use recursor::Recursor;
+pub use limits::RecursorLimits;
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use metrics::describe_metrics;
+pub use query_observer::QueryObserver;
+pub use recursor::{Recursor, DEFAULT_NAME_SERVER_CACHE_SIZE};
 pub use trust_dns_proto as proto;
 pub use trust_dns_resolver as resolver;
 pub use trust_dns_resolver::config::NameServerConfig;
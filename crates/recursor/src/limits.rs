@@ -0,0 +1,145 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The limits of the work done to answer a query, against NXNSAttack and similar abuse
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{Error, ErrorKind};
+
+/// The limits of the work of the recursor to answer one query
+///
+/// Delegations to many name servers without glue, or to name servers whose names are themselves
+///  delegated without glue, would otherwise make the recursor send many queries for each query it
+///  receives, e.g. to flood the name servers of a victim, see NXNSAttack. Queries which exceed a
+///  limit fail with [`ErrorKind::LimitExceeded`], or [`ErrorKind::Timeout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecursorLimits {
+    /// The maximum number of queries sent to name servers, including those for the addresses of
+    ///  the name servers
+    pub max_queries: usize,
+    /// The maximum depth of the resolutions of the addresses of name servers without glue, whose
+    ///  own name servers may have no glue either
+    pub max_depth: usize,
+    /// The maximum number of name servers of a zone whose addresses are resolved, when the
+    ///  delegation has no glue
+    pub max_ns_fanout: usize,
+    /// The maximum time to answer the query
+    pub timeout: Duration,
+}
+
+impl Default for RecursorLimits {
+    fn default() -> Self {
+        Self {
+            max_queries: 64,
+            max_depth: 7,
+            max_ns_fanout: 2,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The work left to answer a query, shared by the resolutions of the addresses of name servers
+#[derive(Clone, Debug)]
+pub(crate) struct Budget {
+    limits: RecursorLimits,
+    queries: Arc<AtomicUsize>,
+    depth: usize,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: RecursorLimits) -> Self {
+        Self {
+            limits,
+            queries: Arc::new(AtomicUsize::new(0)),
+            depth: 0,
+        }
+    }
+
+    pub(crate) fn limits(&self) -> &RecursorLimits {
+        &self.limits
+    }
+
+    /// Counts a query sent to name servers, fails if there were too many
+    pub(crate) fn query(&self) -> Result<(), Error> {
+        let queries = self.queries.fetch_add(1, Ordering::Relaxed) + 1;
+        if queries > self.limits.max_queries {
+            return Err(ErrorKind::LimitExceeded {
+                limit: "queries",
+                max: self.limits.max_queries,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// The budget of the resolution of the addresses of a name server, one level deeper
+    pub(crate) fn nested(&self) -> Result<Self, Error> {
+        if self.depth >= self.limits.max_depth {
+            return Err(ErrorKind::LimitExceeded {
+                limit: "depth",
+                max: self.limits.max_depth,
+            }
+            .into());
+        }
+
+        Ok(Self {
+            limits: self.limits,
+            queries: Arc::clone(&self.queries),
+            depth: self.depth + 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries_are_shared() {
+        let budget = Budget::new(RecursorLimits {
+            max_queries: 3,
+            ..RecursorLimits::default()
+        });
+        let nested = budget.nested().unwrap();
+
+        budget.query().unwrap();
+        nested.query().unwrap();
+        budget.query().unwrap();
+        assert!(matches!(
+            nested.query().unwrap_err().kind(),
+            ErrorKind::LimitExceeded {
+                limit: "queries",
+                max: 3
+            }
+        ));
+    }
+
+    #[test]
+    fn test_depth() {
+        let budget = Budget::new(RecursorLimits {
+            max_depth: 2,
+            ..RecursorLimits::default()
+        });
+
+        let nested = budget.nested().unwrap().nested().unwrap();
+        assert!(matches!(
+            nested.nested().unwrap_err().kind(),
+            ErrorKind::LimitExceeded {
+                limit: "depth",
+                max: 2
+            }
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Recursor instrumentation, exported through the `metrics` facade
+//!
+//! All functions are no-ops unless the `metrics` feature is enabled.
+
+#[cfg(feature = "metrics")]
+const CACHE_HITS: &str = "trust_dns_recursor_cache_hits_total";
+#[cfg(feature = "metrics")]
+const CACHE_MISSES: &str = "trust_dns_recursor_cache_misses_total";
+
+/// Registers the descriptions of all metrics emitted by the recursor with the installed recorder
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub fn describe_metrics() {
+    ::metrics::describe_counter!(CACHE_HITS, "resolutions answered from the cache");
+    ::metrics::describe_counter!(CACHE_MISSES, "resolutions which queried name servers");
+}
+
+/// The resolution was answered from the cache
+pub(crate) fn cache_hit() {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(CACHE_HITS);
+}
+
+/// The resolution was not in the cache
+pub(crate) fn cache_miss() {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(CACHE_MISSES);
+}
@@ -0,0 +1,219 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The smoothed round trip times and failures of the name servers, to query the fastest first
+
+use std::{net::IpAddr, time::Duration};
+
+use lru_cache::LruCache;
+use parking_lot::Mutex;
+
+/// The number of name servers whose round trip times are kept
+pub(crate) const DEFAULT_NAME_SERVER_STATS_SIZE: usize = 4096;
+
+/// The least delay before the next name server is queried, if the previous one has not answered
+const MIN_STAGGER: Duration = Duration::from_millis(50);
+/// The greatest delay before the next name server is queried
+const MAX_STAGGER: Duration = Duration::from_millis(800);
+/// The delay after a query to a name server which was never queried
+const UNKNOWN_STAGGER: Duration = Duration::from_millis(200);
+/// The least smoothed round trip time of a name server after a failure
+const FAILURE_SRTT: Duration = Duration::from_millis(400);
+/// The greatest smoothed round trip time, of name servers which keep failing
+const MAX_SRTT: Duration = Duration::from_secs(10);
+/// The decay, in thousandths, of the smoothed round trip times of the name servers which are not
+///  queried first, so that slow or failed name servers are tried again eventually
+const DECAY: u32 = 980;
+
+#[derive(Clone, Copy, Debug)]
+struct ServerStats {
+    srtt: Duration,
+    /// The failures since the last answer
+    failures: u32,
+}
+
+/// The smoothed round trip times of the name servers, by address, shared by the zones they serve
+///
+/// The round trip times are smoothed as in RFC 6298, with a factor of 1/8. A failure, e.g. a
+///  timeout, doubles the round trip time, so that the name servers which keep failing are queried
+///  last, but they are queried again as the times of the others decay.
+pub(crate) struct NameServerStats {
+    servers: Mutex<LruCache<IpAddr, ServerStats>>,
+}
+
+impl NameServerStats {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            servers: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// The smoothed round trip time of the name server, none if it was never queried
+    pub(crate) fn srtt(&self, ip: IpAddr) -> Option<Duration> {
+        self.servers.lock().get_mut(&ip).map(|stats| stats.srtt)
+    }
+
+    /// The number of failures of the name server since its last answer
+    pub(crate) fn failures(&self, ip: IpAddr) -> u32 {
+        self.servers
+            .lock()
+            .get_mut(&ip)
+            .map_or(0, |stats| stats.failures)
+    }
+
+    /// Records the round trip time of an answer of the name server
+    pub(crate) fn record_rtt(&self, ip: IpAddr, rtt: Duration) {
+        let mut servers = self.servers.lock();
+        let srtt = match servers.get_mut(&ip) {
+            // the first measurement is the smoothed round trip time
+            None => rtt,
+            Some(stats) => stats.srtt - stats.srtt / 8 + rtt / 8,
+        };
+
+        servers.insert(
+            ip,
+            ServerStats {
+                srtt: srtt.min(MAX_SRTT),
+                failures: 0,
+            },
+        );
+    }
+
+    /// Records a failure of the name server, e.g. a timeout
+    pub(crate) fn record_failure(&self, ip: IpAddr) {
+        let mut servers = self.servers.lock();
+        let stats = servers.get_mut(&ip).copied().unwrap_or(ServerStats {
+            srtt: FAILURE_SRTT,
+            failures: 0,
+        });
+
+        servers.insert(
+            ip,
+            ServerStats {
+                srtt: (stats.srtt * 2).max(FAILURE_SRTT).min(MAX_SRTT),
+                failures: stats.failures.saturating_add(1),
+            },
+        );
+    }
+
+    /// The order in which the name servers are queried, as indexes of `ips`
+    ///
+    /// The name servers which were never queried are first, so that their round trip times are
+    ///  measured, then the fastest. The round trip times of all but the first decay.
+    pub(crate) fn order(&self, ips: &[IpAddr]) -> Vec<usize> {
+        let mut servers = self.servers.lock();
+        let srtts = ips
+            .iter()
+            .map(|ip| servers.get_mut(ip).map(|stats| stats.srtt))
+            .collect::<Vec<_>>();
+
+        let mut order = (0..ips.len()).collect::<Vec<_>>();
+        // the sort is stable, the name servers with the same times stay in the order of `ips`
+        order.sort_by_key(|i| srtts[*i]);
+
+        for i in order.iter().skip(1) {
+            if let Some(stats) = servers.get_mut(&ips[*i]) {
+                stats.srtt = stats.srtt * DECAY / 1000;
+            }
+        }
+
+        order
+    }
+
+    /// The delay before the next name server is queried, if this one has not answered yet
+    pub(crate) fn stagger(&self, ip: IpAddr) -> Duration {
+        self.srtt(ip)
+            .map_or(UNKNOWN_STAGGER, |srtt| srtt * 2)
+            .max(MIN_STAGGER)
+            .min(MAX_STAGGER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+    const B: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2));
+    const C: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 3));
+
+    #[test]
+    fn test_smoothed_rtt() {
+        let stats = NameServerStats::new(8);
+        assert_eq!(stats.srtt(A), None);
+
+        stats.record_rtt(A, Duration::from_millis(80));
+        assert_eq!(stats.srtt(A), Some(Duration::from_millis(80)));
+
+        stats.record_rtt(A, Duration::from_millis(160));
+        assert_eq!(stats.srtt(A), Some(Duration::from_millis(90)));
+    }
+
+    #[test]
+    fn test_failures() {
+        let stats = NameServerStats::new(8);
+        stats.record_rtt(A, Duration::from_millis(10));
+
+        stats.record_failure(A);
+        assert_eq!(stats.srtt(A), Some(FAILURE_SRTT));
+        stats.record_failure(A);
+        assert_eq!(stats.srtt(A), Some(FAILURE_SRTT * 2));
+        assert_eq!(stats.failures(A), 2);
+
+        for _ in 0..10 {
+            stats.record_failure(A);
+        }
+        assert_eq!(stats.srtt(A), Some(MAX_SRTT));
+
+        // an answer resets the failures
+        stats.record_rtt(A, Duration::from_millis(10));
+        assert_eq!(stats.failures(A), 0);
+    }
+
+    #[test]
+    fn test_order() {
+        let stats = NameServerStats::new(8);
+        stats.record_rtt(A, Duration::from_millis(100));
+        stats.record_rtt(B, Duration::from_millis(20));
+
+        // the name servers which were never queried are first
+        assert_eq!(stats.order(&[A, B, C]), vec![2, 1, 0]);
+        stats.record_rtt(C, Duration::from_millis(50));
+        assert_eq!(stats.order(&[A, B, C]), vec![1, 2, 0]);
+
+        // the failed name server is queried last
+        stats.record_failure(B);
+        assert_eq!(stats.order(&[A, B, C]), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn test_decay() {
+        let stats = NameServerStats::new(8);
+        stats.record_rtt(A, Duration::from_millis(20));
+        stats.record_failure(B);
+
+        // the failed name server is queried first again once its time has decayed
+        let mut orders = 1;
+        while stats.order(&[A, B]) != vec![1, 0] {
+            orders += 1;
+        }
+        assert!((100..200).contains(&orders), "{}", orders);
+    }
+
+    #[test]
+    fn test_stagger() {
+        let stats = NameServerStats::new(8);
+        assert_eq!(stats.stagger(A), UNKNOWN_STAGGER);
+
+        stats.record_rtt(A, Duration::from_millis(1));
+        assert_eq!(stats.stagger(A), MIN_STAGGER);
+        stats.record_rtt(B, Duration::from_millis(100));
+        assert_eq!(stats.stagger(B), Duration::from_millis(200));
+        stats.record_failure(B);
+        assert_eq!(stats.stagger(B), MAX_STAGGER);
+    }
+}
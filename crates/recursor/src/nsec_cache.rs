@@ -0,0 +1,773 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Aggressive use of the DNSSEC validated cache, [RFC 8198](https://tools.ietf.org/html/rfc8198)
+//!
+//! The validated NSEC and NSEC3 records of negative responses are cached by zone. They prove that
+//!  other names, or types, do not exist in the zone, without queries to its name servers.
+
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use lru_cache::LruCache;
+use parking_lot::Mutex;
+use tracing::debug;
+use trust_dns_proto::{
+    op::{Query, ResponseCode},
+    rr::{
+        dnssec::{
+            nsec3_proof::{Nsec3Chain, Nsec3Proof},
+            rdata::DNSSECRData,
+            Nsec3HashAlgorithm, Proof, ProvenRrset,
+        },
+        Name, RData, Record, RecordType,
+    },
+    xfer::DnsResponse,
+};
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+
+/// The default number of zones whose NSEC and NSEC3 records are cached
+pub(crate) const DEFAULT_NSEC_CACHE_SIZE: usize = 100;
+
+/// The maximum number of NSEC or NSEC3 records cached for a zone, those expiring first are
+///  removed beyond it
+const MAX_ZONE_RECORDS: usize = 1000;
+
+/// Records cached until they expire
+struct Cached {
+    /// The NSEC or NSEC3 record, and its RRSIGs, or the SOA record
+    records: Vec<Record>,
+    valid_until: Instant,
+}
+
+impl Cached {
+    fn is_valid(&self, now: Instant) -> bool {
+        self.valid_until > now
+    }
+
+    /// The first record, which isn't an RRSIG
+    fn record(&self) -> Option<&Record> {
+        self.records.first()
+    }
+}
+
+/// The SOA and the denial records of a zone
+#[derive(Default)]
+struct ZoneDenials {
+    soa: Option<Cached>,
+    /// The NSEC records by owner, in canonical order
+    nsec: BTreeMap<Name, Cached>,
+    /// The NSEC3 records by owner, they all have the same parameters
+    nsec3: BTreeMap<Name, Cached>,
+}
+
+/// The cache of the validated NSEC and NSEC3 records of each zone
+///
+/// Clones share the cache.
+#[derive(Clone)]
+pub(crate) struct NsecCache(Arc<Mutex<LruCache<Name, ZoneDenials>>>);
+
+impl NsecCache {
+    /// Creates a cache of the records of at most `capacity` zones
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(LruCache::new(capacity))))
+    }
+
+    /// Caches the NSEC or NSEC3 records of a validated negative response
+    ///
+    /// Only the records which are secure, and in the zone of the secure SOA, are cached. The
+    ///  records are cached for at most the TTL and the minimum of the SOA, RFC 8198 section 5.4.
+    pub(crate) fn insert(&self, response: &DnsResponse, rrsets: &[ProvenRrset], now: Instant) {
+        let secure = |record_type: RecordType| {
+            rrsets.iter().filter(move |rrset| {
+                rrset.record_type() == record_type && rrset.proof() == Proof::Secure
+            })
+        };
+
+        let soa = match secure(RecordType::SOA).next() {
+            Some(soa) => soa,
+            None => return,
+        };
+        let (soa_record, minimum) = match soa.records().first().and_then(|record| {
+            record
+                .data()
+                .and_then(RData::as_soa)
+                .map(|rdata| (record, rdata.minimum()))
+        }) {
+            Some(soa) => soa,
+            None => return,
+        };
+
+        let zone = soa.name();
+        let max_ttl = soa_record.ttl().min(minimum);
+        let valid_until = |records: &[Record]| {
+            let ttl = records
+                .iter()
+                .map(Record::ttl)
+                .min()
+                .unwrap_or(0)
+                .min(max_ttl);
+            now + Duration::from_secs(u64::from(ttl))
+        };
+
+        let mut cache = self.0.lock();
+        if !cache.contains_key(zone) {
+            cache.insert(zone.clone(), ZoneDenials::default());
+        }
+        let denials = cache.get_mut(zone).expect("zone was inserted");
+
+        denials.soa = Some(Cached {
+            records: vec![soa_record.clone()],
+            valid_until: valid_until(soa.records()),
+        });
+
+        for rrset in secure(RecordType::NSEC).filter(|rrset| zone.zone_of(rrset.name())) {
+            debug!("caching NSEC of {} in {}", rrset.name(), zone);
+            denials.nsec.insert(
+                rrset.name().clone(),
+                Cached {
+                    records: with_rrsigs(response, rrset),
+                    valid_until: valid_until(rrset.records()),
+                },
+            );
+        }
+
+        for rrset in secure(RecordType::NSEC3).filter(|rrset| rrset.name().base_name() == *zone) {
+            // the zone was signed again with other parameters
+            if !denials
+                .nsec3
+                .values()
+                .next()
+                .map_or(true, |cached| same_parameters(cached, rrset.records()))
+            {
+                debug!("NSEC3 parameters of {} changed", zone);
+                denials.nsec3.clear();
+            }
+
+            debug!("caching NSEC3 of {} in {}", rrset.name(), zone);
+            denials.nsec3.insert(
+                rrset.name().clone(),
+                Cached {
+                    records: with_rrsigs(response, rrset),
+                    valid_until: valid_until(rrset.records()),
+                },
+            );
+        }
+
+        evict(&mut denials.nsec, now);
+        evict(&mut denials.nsec3, now);
+    }
+
+    /// Returns the negative response to the query, if the cached records prove it
+    ///
+    /// The query is answered with NXDOMAIN if the name and the wildcard which could match it are
+    ///  covered by NSEC or NSEC3 records, or with NODATA if the records of the name, or of its
+    ///  wildcard, don't have the type. The response has the records of the proof as authorities.
+    pub(crate) fn denial(&self, query: &Query, now: Instant) -> Option<ResolveError> {
+        if query.query_type() == RecordType::ANY {
+            return None;
+        }
+
+        // the DS records of a zone are in its parent zone
+        let name = match query.query_type() {
+            RecordType::DS => query.name().base_name(),
+            _ => query.name().clone(),
+        };
+
+        let mut cache = self.0.lock();
+        let zone = (0..=name.num_labels() as usize)
+            .rev()
+            .map(|num_labels| name.trim_to(num_labels))
+            .find(|zone| cache.contains_key(zone))?;
+        let denials = cache.get_mut(&zone)?;
+
+        let soa = denials.soa.as_ref().filter(|soa| soa.is_valid(now))?;
+        let (response_code, records, valid_until) = denials
+            .nsec_denial(query, now)
+            .or_else(|| denials.nsec3_denial(&zone, query, now))?;
+
+        let ttl = valid_until
+            .min(soa.valid_until)
+            .saturating_duration_since(now)
+            .as_secs() as u32;
+        let mut soa = soa.record()?.clone();
+        soa.set_ttl(ttl);
+
+        debug!(
+            "{} of {} synthesized from the cache of {}",
+            response_code, query, zone
+        );
+        Some(
+            ResolveErrorKind::NoRecordsFound {
+                query: Box::new(query.clone()),
+                soa: Some(Box::new(soa)),
+                negative_ttl: Some(ttl),
+                response_code,
+                trusted: true,
+                authorities: Some(Arc::from(records)),
+            }
+            .into(),
+        )
+    }
+
+    /// Removes the records of the zones which may deny the name, and of the zones below it if
+    ///  `subtree`
+    pub(crate) fn remove(&self, name: &Name, subtree: bool) {
+        let mut cache = self.0.lock();
+        let zones = cache
+            .iter()
+            .map(|(zone, _)| zone)
+            .filter(|zone| zone.zone_of(name) || (subtree && name.zone_of(zone)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for zone in zones {
+            cache.remove(&zone);
+        }
+    }
+
+    /// Clears the records of all zones
+    pub(crate) fn clear(&self) {
+        self.0.lock().clear();
+    }
+}
+
+impl ZoneDenials {
+    /// The denial of the query with NSEC records, RFC 4035 section 5.4
+    fn nsec_denial(
+        &self,
+        query: &Query,
+        now: Instant,
+    ) -> Option<(ResponseCode, Vec<Record>, Instant)> {
+        let name = query.name();
+        let query_type = query.query_type();
+
+        if let Some(matching) = self.nsec.get(name) {
+            let (_, types) = nsec(matching).filter(|_| matching.is_valid(now))?;
+            if !proves_no_data(types, query_type) {
+                return None;
+            }
+
+            return Some((
+                ResponseCode::NoError,
+                matching.records.clone(),
+                matching.valid_until,
+            ));
+        }
+
+        let (owner, covering) = self.covering_nsec(name, now)?;
+        let (next, types) = nsec(covering)?;
+
+        // names below a delegation, or a DNAME, are not in the zone
+        if owner.zone_of(name) && (is_delegation(types) || types.contains(&RecordType::DNAME)) {
+            return None;
+        }
+
+        // the wildcard at the closest encloser could match the name
+        let (by_owner, by_next) = (common_ancestor(name, owner), common_ancestor(name, next));
+        let closest_encloser = if by_owner.num_labels() >= by_next.num_labels() {
+            by_owner
+        } else {
+            by_next
+        };
+        let wildcard = Name::from_ascii("*")
+            .ok()?
+            .append_domain(&closest_encloser)
+            .ok()?;
+
+        let mut records = covering.records.clone();
+        let mut valid_until = covering.valid_until;
+
+        if let Some(matching) = self.nsec.get(&wildcard) {
+            let (_, types) = nsec(matching).filter(|_| matching.is_valid(now))?;
+            if !proves_no_data(types, query_type) {
+                return None;
+            }
+
+            if wildcard != *owner {
+                records.extend(matching.records.iter().cloned());
+                valid_until = valid_until.min(matching.valid_until);
+            }
+            return Some((ResponseCode::NoError, records, valid_until));
+        }
+
+        let (wildcard_owner, wildcard_covering) = self.covering_nsec(&wildcard, now)?;
+        if wildcard_owner != owner {
+            records.extend(wildcard_covering.records.iter().cloned());
+            valid_until = valid_until.min(wildcard_covering.valid_until);
+        }
+
+        Some((ResponseCode::NXDomain, records, valid_until))
+    }
+
+    /// The valid NSEC record which covers the name, with its owner
+    fn covering_nsec(&self, name: &Name, now: Instant) -> Option<(&Name, &Cached)> {
+        // the owners of the chain are in canonical order, the record covering the name is the
+        //  one preceding it
+        let (owner, cached) = self
+            .nsec
+            .range(..name.clone())
+            .next_back()
+            .filter(|(_, cached)| cached.is_valid(now))?;
+        let (next, _) = nsec(cached)?;
+
+        // the last record of the chain wraps around to the apex of the zone
+        if name < next || next <= owner {
+            Some((owner, cached))
+        } else {
+            None
+        }
+    }
+
+    /// The denial of the query with NSEC3 records, RFC 5155 section 8
+    fn nsec3_denial(
+        &self,
+        zone: &Name,
+        query: &Query,
+        now: Instant,
+    ) -> Option<(ResponseCode, Vec<Record>, Instant)> {
+        let valid = self
+            .nsec3
+            .values()
+            .filter(|cached| cached.is_valid(now))
+            .flat_map(|cached| cached.record());
+        let chain = Nsec3Chain::new(zone, valid).ok()?;
+
+        let proof = chain.verify(query.name(), query.query_type()).ok()?;
+        let (_, owners) = chain.proof_owners(query.name()).ok()?;
+        let proofs = owners
+            .into_iter()
+            .filter_map(|owner| self.nsec3.get(owner))
+            .collect::<Vec<_>>();
+
+        let response_code = match proof {
+            Nsec3Proof::NoData => {
+                // the records of the parent side of a delegation only prove the absence of DS
+                let types = proofs.first().and_then(|cached| nsec3_types(cached))?;
+                if query.query_type() != RecordType::DS && is_delegation(types) {
+                    return None;
+                }
+                ResponseCode::NoError
+            }
+            Nsec3Proof::WildcardNoData => ResponseCode::NoError,
+            Nsec3Proof::NameError => ResponseCode::NXDomain,
+            // unsigned delegations may exist in the span of an opt-out NSEC3, RFC 8198 section 4.5
+            Nsec3Proof::OptOut => return None,
+        };
+
+        let valid_until = proofs.iter().map(|cached| cached.valid_until).min()?;
+        let records = proofs
+            .into_iter()
+            .flat_map(|cached| cached.records.iter().cloned())
+            .collect();
+        Some((response_code, records, valid_until))
+    }
+}
+
+/// The records of the RRset, followed by the RRSIGs covering them in the response
+fn with_rrsigs(response: &DnsResponse, rrset: &ProvenRrset) -> Vec<Record> {
+    let rrsigs = response.name_servers().iter().filter(|record| {
+        record.record_type() == RecordType::RRSIG
+            && record.name() == rrset.name()
+            && matches!(
+                record.data(),
+                Some(RData::DNSSEC(DNSSECRData::SIG(rrsig)))
+                    if rrsig.type_covered() == rrset.record_type()
+            )
+    });
+
+    rrset
+        .records()
+        .iter()
+        .chain(rrsigs)
+        .cloned()
+        .collect::<Vec<_>>()
+}
+
+/// True if the NSEC3 records have the same hash algorithm, salt and iterations
+fn same_parameters(cached: &Cached, records: &[Record]) -> bool {
+    match (
+        nsec3_parameters(cached.record()),
+        nsec3_parameters(records.first()),
+    ) {
+        (Some(cached), Some(record)) => cached == record,
+        _ => false,
+    }
+}
+
+fn nsec3_parameters(record: Option<&Record>) -> Option<(Nsec3HashAlgorithm, &[u8], u16)> {
+    record
+        .and_then(Record::data)
+        .and_then(RData::as_dnssec)
+        .and_then(DNSSECRData::as_nsec3)
+        .map(|nsec3| (nsec3.hash_algorithm(), nsec3.salt(), nsec3.iterations()))
+}
+
+/// Removes the expired records, and those expiring first beyond the maximum
+fn evict(records: &mut BTreeMap<Name, Cached>, now: Instant) {
+    if records.len() <= MAX_ZONE_RECORDS {
+        return;
+    }
+
+    records.retain(|_, cached| cached.is_valid(now));
+    while records.len() > MAX_ZONE_RECORDS {
+        let first = records
+            .iter()
+            .min_by_key(|(_, cached)| cached.valid_until)
+            .map(|(owner, _)| owner.clone());
+        match first {
+            Some(owner) => records.remove(&owner),
+            None => break,
+        };
+    }
+}
+
+/// The next domain name and the types of the NSEC record
+fn nsec(cached: &Cached) -> Option<(&Name, &[RecordType])> {
+    cached
+        .record()
+        .and_then(Record::data)
+        .and_then(RData::as_dnssec)
+        .and_then(DNSSECRData::as_nsec)
+        .map(|nsec| (nsec.next_domain_name(), nsec.type_bit_maps()))
+}
+
+fn nsec3_types(cached: &Cached) -> Option<&[RecordType]> {
+    cached
+        .record()
+        .and_then(Record::data)
+        .and_then(RData::as_dnssec)
+        .and_then(DNSSECRData::as_nsec3)
+        .map(|nsec3| nsec3.type_bit_maps())
+}
+
+/// True if the types of the name prove it has no records of the type
+///
+/// The NSEC records at a delegation are from the parent side, they only prove the absence of DS
+///  records, RFC 4035 section 5.4.
+fn proves_no_data(types: &[RecordType], query_type: RecordType) -> bool {
+    !types.contains(&query_type)
+        && !types.contains(&RecordType::CNAME)
+        && (query_type == RecordType::DS || !is_delegation(types))
+}
+
+/// True if the types are those of a delegation to another zone, NS without SOA
+fn is_delegation(types: &[RecordType]) -> bool {
+    types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA)
+}
+
+/// The longest name which is an ancestor of both names, or one of them
+fn common_ancestor(name: &Name, other: &Name) -> Name {
+    let mut ancestor = name.clone();
+    while !ancestor.zone_of(other) && !ancestor.is_root() {
+        ancestor = ancestor.base_name();
+    }
+    ancestor
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use trust_dns_proto::{
+        op::{Message, MessageType},
+        rr::{
+            dnssec::rdata::{NSEC, SIG},
+            dnssec::Algorithm,
+            rdata::SOA,
+        },
+    };
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    fn soa() -> Record {
+        let origin = name("example.");
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RData::SOA(SOA::new(origin.clone(), origin, 1, 3600, 600, 86400, 300)),
+        )
+    }
+
+    fn nsec(owner: &str, next: &str, types: &[RecordType]) -> Record {
+        Record::from_rdata(
+            name(owner),
+            3600,
+            RData::DNSSEC(DNSSECRData::NSEC(NSEC::new(name(next), types.to_vec()))),
+        )
+    }
+
+    fn rrsig(record: &Record) -> Record {
+        let sig = SIG::new(
+            record.record_type(),
+            Algorithm::ED25519,
+            record.name().num_labels(),
+            record.ttl(),
+            0,
+            0,
+            1,
+            name("example."),
+            vec![0; 64],
+        );
+        let mut rrsig = Record::from_rdata(
+            record.name().clone(),
+            record.ttl(),
+            RData::DNSSEC(DNSSECRData::SIG(sig)),
+        );
+        rrsig.set_rr_type(RecordType::RRSIG);
+        rrsig
+    }
+
+    /// Caches the records as the secure authorities of a negative response
+    fn insert(cache: &NsecCache, records: Vec<Record>, now: Instant) {
+        let mut message = Message::new();
+        message.set_message_type(MessageType::Response);
+        for record in &records {
+            message.add_name_server(record.clone());
+            message.add_name_server(rrsig(record));
+        }
+
+        let rrsets = records
+            .into_iter()
+            .map(|record| {
+                ProvenRrset::new(
+                    record.name().clone(),
+                    record.record_type(),
+                    Proof::Secure,
+                    vec![record],
+                    vec![],
+                )
+            })
+            .collect::<Vec<_>>();
+        cache.insert(&DnsResponse::from(message), &rrsets, now);
+    }
+
+    fn denial(
+        cache: &NsecCache,
+        query: &str,
+        query_type: RecordType,
+        now: Instant,
+    ) -> Option<(ResponseCode, usize)> {
+        let query = Query::query(name(query), query_type);
+        cache.denial(&query, now).map(|e| match e.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                response_code,
+                authorities,
+                negative_ttl,
+                ..
+            } => {
+                assert!(negative_ttl.unwrap() <= 300);
+                (*response_code, authorities.as_ref().unwrap().len())
+            }
+            kind => panic!("unexpected error: {:?}", kind),
+        })
+    }
+
+    /// The zone example. has a.example., the wildcard *.c.example. below the empty non-terminal
+    ///  c.example., and the delegation d.example.
+    fn example(cache: &NsecCache, now: Instant) {
+        use RecordType::*;
+
+        insert(
+            cache,
+            vec![
+                soa(),
+                nsec("example.", "a.example.", &[SOA, NS, RRSIG, NSEC, DNSKEY]),
+                nsec("a.example.", "*.c.example.", &[A, RRSIG, NSEC]),
+                nsec("*.c.example.", "d.example.", &[TXT, RRSIG, NSEC]),
+                nsec("d.example.", "example.", &[NS, RRSIG, NSEC]),
+            ],
+            now,
+        );
+    }
+
+    #[test]
+    fn test_name_error() {
+        let cache = NsecCache::new(10);
+        let now = Instant::now();
+        example(&cache, now);
+
+        // b.example. is covered by a.example., and *.example. by example.
+        assert_eq!(
+            denial(&cache, "b.example.", RecordType::A, now),
+            Some((ResponseCode::NXDomain, 4))
+        );
+
+        // the apex exists, without A records
+        assert_eq!(
+            denial(&cache, "example.", RecordType::A, now),
+            Some((ResponseCode::NoError, 2))
+        );
+        assert_eq!(denial(&cache, "example.", RecordType::SOA, now), None);
+    }
+
+    #[test]
+    fn test_no_data() {
+        let cache = NsecCache::new(10);
+        let now = Instant::now();
+        example(&cache, now);
+
+        assert_eq!(
+            denial(&cache, "a.example.", RecordType::MX, now),
+            Some((ResponseCode::NoError, 2))
+        );
+        assert_eq!(denial(&cache, "a.example.", RecordType::A, now), None);
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let cache = NsecCache::new(10);
+        let now = Instant::now();
+        example(&cache, now);
+
+        // the wildcard matches the name, but doesn't have the type
+        assert_eq!(
+            denial(&cache, "b.c.example.", RecordType::A, now),
+            Some((ResponseCode::NoError, 2))
+        );
+        // the wildcard answers the name
+        assert_eq!(denial(&cache, "b.c.example.", RecordType::TXT, now), None);
+    }
+
+    #[test]
+    fn test_delegation() {
+        let cache = NsecCache::new(10);
+        let now = Instant::now();
+        example(&cache, now);
+
+        // only the absence of DS records is proven at the delegation
+        assert_eq!(
+            denial(&cache, "d.example.", RecordType::DS, now),
+            Some((ResponseCode::NoError, 2))
+        );
+        assert_eq!(denial(&cache, "d.example.", RecordType::A, now), None);
+        assert_eq!(denial(&cache, "www.d.example.", RecordType::A, now), None);
+    }
+
+    #[test]
+    fn test_expiry_and_removal() {
+        let cache = NsecCache::new(10);
+        let now = Instant::now();
+        example(&cache, now);
+
+        // the records expire with the minimum of the SOA
+        let later = now + Duration::from_secs(301);
+        assert_eq!(denial(&cache, "b.example.", RecordType::A, later), None);
+
+        cache.remove(&name("b.example."), false);
+        assert_eq!(denial(&cache, "b.example.", RecordType::A, now), None);
+
+        example(&cache, now);
+        cache.remove(&name("net."), true);
+        assert!(denial(&cache, "b.example.", RecordType::A, now).is_some());
+        cache.remove(&name("."), true);
+        assert_eq!(denial(&cache, "b.example.", RecordType::A, now), None);
+    }
+
+    /// The NSEC3 records of example. with a.example. and the wildcard *.w.example.
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    fn nsec3_example(opt_out: bool) -> Vec<Record> {
+        use trust_dns_proto::rr::dnssec::{nsec3_proof::hashed_owner_name, rdata::NSEC3};
+        use RecordType::*;
+
+        let salt = vec![0xAA, 0xBB];
+        let names: [(&str, &[RecordType]); 4] = [
+            ("example.", &[SOA, NS, DNSKEY, NSEC3PARAM, RRSIG]),
+            ("a.example.", &[A, RRSIG]),
+            ("w.example.", &[]),
+            ("*.w.example.", &[TXT, RRSIG]),
+        ];
+        let mut hashed = names
+            .iter()
+            .map(|(owner, types)| {
+                let hash = Nsec3HashAlgorithm::SHA1
+                    .hash(&salt, &name(owner), 2)
+                    .unwrap()
+                    .as_ref()
+                    .to_vec();
+                (hash, types.to_vec())
+            })
+            .collect::<Vec<_>>();
+        hashed.sort();
+
+        (0..hashed.len())
+            .map(|i| {
+                let (ref hash, ref types) = hashed[i];
+                let (ref next, _) = hashed[(i + 1) % hashed.len()];
+                let nsec3 = NSEC3::new(
+                    Nsec3HashAlgorithm::SHA1,
+                    opt_out,
+                    2,
+                    salt.clone(),
+                    next.clone(),
+                    types.clone(),
+                );
+                Record::from_rdata(
+                    hashed_owner_name(hash, &name("example.")).unwrap(),
+                    3600,
+                    RData::DNSSEC(DNSSECRData::NSEC3(nsec3)),
+                )
+            })
+            .collect()
+    }
+
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    #[test]
+    fn test_nsec3() {
+        let cache = NsecCache::new(10);
+        let now = Instant::now();
+        insert(
+            &cache,
+            Some(soa())
+                .into_iter()
+                .chain(nsec3_example(false))
+                .collect(),
+            now,
+        );
+
+        assert_eq!(
+            denial(&cache, "b.example.", RecordType::A, now).map(|(code, _)| code),
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(
+            denial(&cache, "a.example.", RecordType::MX, now),
+            Some((ResponseCode::NoError, 2))
+        );
+        assert_eq!(denial(&cache, "a.example.", RecordType::A, now), None);
+        assert_eq!(
+            denial(&cache, "x.w.example.", RecordType::A, now).map(|(code, _)| code),
+            Some(ResponseCode::NoError)
+        );
+        assert_eq!(denial(&cache, "x.w.example.", RecordType::TXT, now), None);
+    }
+
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    #[test]
+    fn test_nsec3_opt_out() {
+        let cache = NsecCache::new(10);
+        let now = Instant::now();
+        insert(
+            &cache,
+            Some(soa()).into_iter().chain(nsec3_example(true)).collect(),
+            now,
+        );
+
+        // an unsigned delegation may exist in the span
+        assert_eq!(denial(&cache, "b.example.", RecordType::A, now), None);
+        assert_eq!(
+            denial(&cache, "a.example.", RecordType::MX, now),
+            Some((ResponseCode::NoError, 2))
+        );
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use trust_dns_proto::{op::Message, rr::Name};
+
+/// Observes the queries which the `Recursor` sends to the name servers of zones, and their
+///  responses, e.g. to log them
+///
+/// The observer is called on the task of the lookup, so it should not block.
+pub trait QueryObserver: Send + Sync + 'static {
+    /// The query is about to be sent to the name servers of the zone
+    fn query(&self, zone: &Name, query: &Message);
+
+    /// The response to the query was received from the name servers of the zone
+    fn response(&self, zone: &Name, response: &Message);
+}
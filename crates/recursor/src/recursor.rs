@@ -5,39 +5,82 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{net::SocketAddr, time::Instant};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Instant,
+};
 
 use async_recursion::async_recursion;
-use futures_util::{future::select_all, FutureExt};
+use futures_util::{
+    future::{self, select_all, Either},
+    FutureExt,
+};
 use lru_cache::LruCache;
 use parking_lot::Mutex;
 use tracing::{debug, info, warn};
 
 use trust_dns_proto::{
-    op::Query,
-    rr::{RData, RecordType},
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{RData, Record, RecordType},
+};
+#[cfg(feature = "dnssec")]
+use trust_dns_proto::{
+    rr::dnssec::{Proof, TrustAnchor},
+    xfer::{DnsRequestOptions, DnssecDnsHandle},
 };
+use trust_dns_proto::{Time, TokioTime};
 use trust_dns_resolver::{
-    config::{NameServerConfig, NameServerConfigGroup, Protocol, ResolverOpts},
+    config::{NameServerConfig, NameServerConfigGroup, Protocol, QnameMinimization, ResolverOpts},
     dns_lru::{DnsLru, TtlConfig},
-    error::ResolveError,
+    error::{ResolveError, ResolveErrorKind},
     lookup::Lookup,
-    name_server::NameServerPool,
-    Name, TokioConnection, TokioConnectionProvider, TokioHandle,
+    Name, TokioConnection, TokioConnectionProvider,
 };
 
-use crate::{recursor_pool::RecursorPool, Error, ErrorKind};
+use crate::{
+    chain::{synthesize_cname, Chain, DEFAULT_MAX_CHAIN_LENGTH},
+    infra_cache::{is_address, InfraCache, Trust, DEFAULT_INFRA_CACHE_SIZE},
+    limits::{Budget, RecursorLimits},
+    metrics,
+    name_server_stats::{NameServerStats, DEFAULT_NAME_SERVER_STATS_SIZE},
+    recursor_pool::RecursorPool,
+    Error, ErrorKind, QueryObserver,
+};
+#[cfg(feature = "dnssec")]
+use crate::{
+    dnssec::RecursorHandle,
+    nsec_cache::{NsecCache, DEFAULT_NSEC_CACHE_SIZE},
+};
 
-/// Set of nameservers by the zone name
-type NameServerCache<C, P> = LruCache<Name, RecursorPool<C, P>>;
+/// Set of nameservers by the zone name, valid until their delegation or one of their addresses
+///  expires
+type NameServerCache<C, P> = LruCache<Name, (RecursorPool<C, P>, Instant)>;
+
+/// The default number of zones whose name servers are cached
+pub const DEFAULT_NAME_SERVER_CACHE_SIZE: usize = 100;
 
 /// A top down recursive resolver which operates off a list of roots for initial recursive requests.
 ///
 /// This is the well known root nodes, refered to as hints in RFCs. See the IANA [Root Servers](https://www.iana.org/domains/root/servers) list.
+///
+/// Clones of the recursor share its caches.
+#[derive(Clone)]
 pub struct Recursor {
     roots: RecursorPool<TokioConnection, TokioConnectionProvider>,
-    name_server_cache: Mutex<NameServerCache<TokioConnection, TokioConnectionProvider>>,
-    record_cache: DnsLru,
+    name_server_cache: Arc<Mutex<NameServerCache<TokioConnection, TokioConnectionProvider>>>,
+    name_server_stats: Arc<NameServerStats>,
+    infra_cache: Arc<InfraCache>,
+    pub(crate) record_cache: DnsLru,
+    options: ResolverOpts,
+    max_chain_length: usize,
+    limits: RecursorLimits,
+    query_observer: Option<Arc<dyn QueryObserver>>,
+    #[cfg(feature = "dnssec")]
+    trust_anchor: Arc<TrustAnchor>,
+    #[cfg(feature = "dnssec")]
+    nsec_cache: Option<NsecCache>,
 }
 
 impl Recursor {
@@ -47,28 +90,162 @@ impl Recursor {
     ///
     /// This will panic if the roots are empty.
     pub fn new(roots: impl Into<NameServerConfigGroup>) -> Result<Self, ResolveError> {
+        Self::new_with_options(roots, ResolverOpts::default())
+    }
+
+    /// Construct a new recursor using the list of NameServerConfigs for the root node list, and the
+    ///  options for the iterative lookups
+    ///
+    /// The options which are required for iterative lookups, e.g. `recursion_desired`, are
+    ///  overridden. The `qname_minimization` option selects the [`QnameMinimization`] mode.
+    ///
+    /// The records are cached in a cache of `cache_size` entries, whose TTLs are clamped to the
+    ///  `positive_min_ttl`, `positive_max_ttl`, `negative_min_ttl` and `negative_max_ttl`
+    ///  options. Negative responses are cached for the TTL of their SOA record, RFC 2308.
+    ///
+    /// The delegations of the zones and the addresses of their name servers are cached apart, for
+    ///  their own TTLs. Delegations are revalidated at the parent zone once they expire, at least
+    ///  every 6 hours, and glue is only trusted in the bailiwick of the zone which referred to it.
+    ///
+    /// With the `validate` option, the answers are validated with DNSSEC against the root trust
+    ///  anchor, see `resolve_with_proof`. This requires the `dnssec` feature.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the roots are empty.
+    pub fn new_with_options(
+        roots: impl Into<NameServerConfigGroup>,
+        options: ResolverOpts,
+    ) -> Result<Self, ResolveError> {
         // configure the trust-dns-resolver
         let roots: NameServerConfigGroup = roots.into();
 
         assert!(!roots.is_empty(), "roots must not be empty");
 
-        let opts = recursor_opts();
-        let roots = NameServerPool::from_config(
+        let options = recursor_opts(options);
+        let name_server_stats = Arc::new(NameServerStats::new(DEFAULT_NAME_SERVER_STATS_SIZE));
+        let roots = RecursorPool::from(
+            Name::root(),
             roots,
-            &opts,
-            TokioConnectionProvider::new(TokioHandle::default()),
+            &options,
+            Arc::clone(&name_server_stats),
         );
-        let roots = RecursorPool::from(Name::root(), roots);
-        let name_server_cache = Mutex::new(NameServerCache::new(100)); // TODO: make this configurable
-        let record_cache = DnsLru::new(100, TtlConfig::default());
+        let name_server_cache = Arc::new(Mutex::new(NameServerCache::new(
+            DEFAULT_NAME_SERVER_CACHE_SIZE,
+        )));
+        let infra_cache = Arc::new(InfraCache::new(DEFAULT_INFRA_CACHE_SIZE));
+        let record_cache = DnsLru::new(options.cache_size, TtlConfig::from_opts(&options));
 
         Ok(Self {
             roots,
             name_server_cache,
+            name_server_stats,
+            infra_cache,
             record_cache,
+            options,
+            max_chain_length: DEFAULT_MAX_CHAIN_LENGTH,
+            limits: RecursorLimits::default(),
+            query_observer: None,
+            #[cfg(feature = "dnssec")]
+            trust_anchor: Arc::new(TrustAnchor::default()),
+            #[cfg(feature = "dnssec")]
+            nsec_cache: Some(NsecCache::new(DEFAULT_NSEC_CACHE_SIZE)),
         })
     }
 
+    /// Sets the keys trusted for DNSSEC validation, by default the keys of the root zone
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn set_trust_anchor(&mut self, trust_anchor: TrustAnchor) {
+        self.trust_anchor = Arc::new(trust_anchor);
+    }
+
+    /// Enables the aggressive use of the validated NSEC and NSEC3 records, RFC 8198, enabled by
+    ///  default
+    ///
+    /// With validation, NXDOMAIN and NODATA answers are synthesized from the cached NSEC and NSEC3
+    ///  records of negative responses which prove them, without queries to the name servers.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn set_aggressive_nsec(&mut self, enabled: bool) {
+        self.nsec_cache = if enabled {
+            Some(NsecCache::new(DEFAULT_NSEC_CACHE_SIZE))
+        } else {
+            None
+        };
+    }
+
+    /// Sets the observer of the queries sent to name servers, `None` removes it
+    pub fn set_query_observer(&mut self, query_observer: Option<Arc<dyn QueryObserver>>) {
+        self.query_observer = query_observer;
+    }
+
+    /// Sets the maximum number of CNAMEs followed to answer a query, by default
+    ///  [`DEFAULT_MAX_CHAIN_LENGTH`]
+    ///
+    /// Longer chains, and chains which loop, fail to resolve.
+    pub fn set_max_chain_length(&mut self, max_chain_length: usize) {
+        self.max_chain_length = max_chain_length;
+    }
+
+    /// Sets the limits of the work done to answer a query, see [`RecursorLimits`]
+    pub fn set_limits(&mut self, limits: RecursorLimits) {
+        self.limits = limits;
+    }
+
+    /// Sets the maximum number of zones whose name servers are cached
+    ///
+    /// The least recently used zones are removed if there are more.
+    pub fn set_name_server_cache_size(&mut self, capacity: usize) {
+        self.name_server_cache.lock().set_capacity(capacity);
+    }
+
+    /// Clears the cached records, and the cached name servers of all zones below the roots
+    pub fn clear_cache(&self) {
+        self.record_cache.clear();
+        self.name_server_cache.lock().clear();
+        self.infra_cache.clear();
+        #[cfg(feature = "dnssec")]
+        if let Some(ref nsec_cache) = self.nsec_cache {
+            nsec_cache.clear();
+        }
+    }
+
+    /// Removes the cached records of the name, and its cached name servers if it is a zone
+    ///
+    /// The cached NSEC and NSEC3 records of the zones which could deny the name are removed.
+    pub fn flush_name(&self, name: &Name) {
+        self.record_cache.remove(name);
+        self.name_server_cache.lock().remove(name);
+        self.infra_cache.remove(name);
+        #[cfg(feature = "dnssec")]
+        if let Some(ref nsec_cache) = self.nsec_cache {
+            nsec_cache.remove(name, false);
+        }
+    }
+
+    /// Removes the cached records and name servers of the name and all names below it
+    pub fn flush_subtree(&self, name: &Name) {
+        self.record_cache.remove_subtree(name);
+        self.infra_cache.remove_subtree(name);
+        #[cfg(feature = "dnssec")]
+        if let Some(ref nsec_cache) = self.nsec_cache {
+            nsec_cache.remove(name, true);
+        }
+
+        let mut name_server_cache = self.name_server_cache.lock();
+        let zones = name_server_cache
+            .iter()
+            .map(|(zone, _)| zone)
+            .filter(|zone| name.zone_of(zone))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for zone in zones {
+            name_server_cache.remove(&zone);
+        }
+    }
+
     /// Permform a recursive resolution
     ///
     /// [RFC 1034](https://datatracker.ietf.org/doc/html/rfc1034#section-5.3.3), Domain Concepts and Facilities, November 1987
@@ -230,14 +407,170 @@ impl Recursor {
     /// has contiguous zones at the root and MIL domains, but also has a non-
     /// contiguous zone at ISI.EDU.
     /// ```
+    ///
+    /// If DNSSEC validation is enabled, answers which fail to validate are returned as
+    ///  `ErrorKind::ValidationFailed`.
     pub async fn resolve(&self, query: Query, request_time: Instant) -> Result<Lookup, Error> {
-        if let Some(lookup) = self.record_cache.get(&query, request_time) {
+        #[cfg(feature = "dnssec")]
+        if self.is_validating() {
+            return self
+                .resolve_with_proof(query, request_time)
+                .await
+                .map(|(lookup, _)| lookup);
+        }
+
+        self.resolve_unvalidated(query, request_time).await
+    }
+
+    /// Performs a recursive resolution, and validates the answer with DNSSEC
+    ///
+    /// The answer is validated bottom-up: the RRSIGs of the answer are verified with the DNSKEYs
+    ///  of the zone, which are verified with the DS records of the parent zone, up to the trust
+    ///  anchor. Negative responses are validated with their NSEC or NSEC3 records. Unsigned answers
+    ///  are insecure if an unsigned delegation is proven above them, [RFC 4035, section
+    ///  5](https://tools.ietf.org/html/rfc4035#section-5).
+    ///
+    /// Returns the answer and its [`Proof`], `Secure` or `Insecure`, or
+    ///  [`ErrorKind::ValidationFailed`] if the answer is `Bogus` or `Indeterminate`. The proof of
+    ///  negative responses only determines if they are returned, they are never secure answers.
+    ///
+    /// NS queries are answered from the unsigned delegations of the parent zones, they are not
+    ///  validated and their proof is `Indeterminate`.
+    ///
+    /// Names and types which the cached NSEC and NSEC3 records of their zone prove not to exist
+    ///  are denied without queries, see `set_aggressive_nsec`.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub async fn resolve_with_proof(
+        &self,
+        query: Query,
+        request_time: Instant,
+    ) -> Result<(Lookup, Proof), Error> {
+        if query.query_type() == RecordType::NS {
+            return self
+                .resolve_unvalidated(query, request_time)
+                .await
+                .map(|lookup| (lookup, Proof::Indeterminate));
+        }
+
+        if let Some(ref nsec_cache) = self.nsec_cache {
+            if self.record_cache.get(&query, request_time).is_none() {
+                if let Some(denial) = nsec_cache.denial(&query, request_time) {
+                    metrics::cache_hit();
+                    return Err(denial.into());
+                }
+            }
+        }
+
+        let handle = RecursorHandle::new(self.clone(), query.clone(), request_time);
+        let handle = DnssecDnsHandle::with_trust_anchor(handle, (*self.trust_anchor).clone());
+
+        let proven = handle
+            .lookup_with_proof(query.clone(), DnsRequestOptions::default())
+            .await?;
+
+        let proof = proven.proof();
+        if !matches!(proof, Proof::Secure | Proof::Insecure) {
+            warn!("validation of {} failed: {}", query, proof);
+            return Err(ErrorKind::ValidationFailed {
+                query: Box::new(query),
+                proof,
+            }
+            .into());
+        }
+
+        if proven.denial().is_some() {
+            if let (Some(nsec_cache), Proof::Secure) = (&self.nsec_cache, proof) {
+                nsec_cache.insert(proven.response(), proven.rrsets(), request_time);
+            }
+
+            // the denial was proven, the negative response is returned from the cache
+            return self
+                .resolve_unvalidated(query, request_time)
+                .await
+                .map(|lookup| (lookup, proof));
+        }
+
+        debug!("{} is {}", query, proof);
+        let answers = proven.into_response().take_answers();
+        Ok((Lookup::new_with_max_ttl(query, Arc::from(answers)), proof))
+    }
+
+    /// Performs a recursive resolution, without DNSSEC validation
+    ///
+    /// This is for requests with the Checking Disabled bit, and for the addresses of name servers.
+    ///
+    /// The CNAMEs, and the CNAMEs synthesized from DNAMEs, are followed to the canonical name, and
+    ///  the answer starts with the whole chain, see `set_max_chain_length`.
+    ///
+    /// The resolution fails with `ErrorKind::LimitExceeded` or `ErrorKind::Timeout` if it exceeds
+    ///  the limits, see `set_limits`.
+    pub async fn resolve_unvalidated(
+        &self,
+        query: Query,
+        request_time: Instant,
+    ) -> Result<Lookup, Error> {
+        let budget = Budget::new(self.limits);
+        let resolve = self.resolve_chain(query.clone(), request_time, &budget);
+        let timeout = TokioTime::delay_for(self.limits.timeout);
+
+        // the resolution borrows the budget, it is dropped with this local before the budget, as
+        //  the temporaries of a tail expression are dropped after the locals
+        let selected = future::select(Box::pin(resolve), timeout).await;
+        match selected {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => {
+                warn!("resolution of {} timed out", query);
+                Err(ErrorKind::Timeout.into())
+            }
+        }
+    }
+
+    /// Resolves the query, following the CNAMEs of the answers
+    async fn resolve_chain(
+        &self,
+        query: Query,
+        request_time: Instant,
+        budget: &Budget,
+    ) -> Result<Lookup, Error> {
+        let mut chain = Chain::new(query.name().clone(), self.max_chain_length);
+        let mut next = query.clone();
+
+        loop {
+            let lookup = self
+                .resolve_name(next.clone(), request_time, budget)
+                .await?;
+            match chain.follow(&next, &lookup)? {
+                Some(name) => {
+                    next = Query::query(name, query.query_type());
+                    next.set_query_class(query.query_class());
+                }
+                None => return Ok(chain.answer(query, lookup)),
+            }
+        }
+    }
+
+    /// Resolves the records of the name, or its CNAME
+    async fn resolve_name(
+        &self,
+        query: Query,
+        request_time: Instant,
+        budget: &Budget,
+    ) -> Result<Lookup, Error> {
+        if let Some(lookup) = self.cached(&query, request_time) {
+            metrics::cache_hit();
             return lookup.map_err(Into::into);
         }
+        metrics::cache_miss();
+
+        if self.options.qname_minimization == QnameMinimization::Off {
+            return self.resolve_unminimized(query, request_time, budget).await;
+        }
 
         // not in cache, let's look for an ns record for lookup
         let zone = match query.query_type() {
-            RecordType::NS => query.name().base_name(),
+            // the DS records of a zone are in its parent zone
+            RecordType::NS | RecordType::DS => query.name().base_name(),
             // look for the NS records "inside" the zone
             _ => query.name().clone(),
         };
@@ -247,7 +580,10 @@ impl Recursor {
 
         // max number of forwarding processes
         'max_forward: for _ in 0..20 {
-            match self.ns_pool_for_zone(zone.clone(), request_time).await {
+            match self
+                .ns_pool_for_zone(zone.clone(), request_time, budget)
+                .await
+            {
                 Ok(found) => {
                     // found the nameserver
                     ns = Some(found);
@@ -272,191 +608,618 @@ impl Recursor {
         let ns = ns.ok_or_else(|| Error::from(format!("no nameserver found for {}", zone)))?;
         debug!("found zone {} for {}", ns.zone(), query);
 
-        let response = self.lookup(query, ns, request_time).await?;
+        let response = self.lookup(query, ns, request_time, budget).await?;
         Ok(response)
     }
 
+    /// Resolves the records of the name without minimized queries, [`QnameMinimization::Off`]
+    ///
+    /// The full query is sent to the name servers of the closest zone with a cached delegation, or
+    ///  the roots, and then to the name servers of each zone it is referred to.
+    async fn resolve_unminimized(
+        &self,
+        query: Query,
+        request_time: Instant,
+        budget: &Budget,
+    ) -> Result<Lookup, Error> {
+        let mut ns = self.closest_pool(&query, request_time, budget).await?;
+
+        loop {
+            let e = match self
+                .lookup(query.clone(), ns.clone(), request_time, budget)
+                .await
+            {
+                Ok(lookup) => return Ok(lookup),
+                Err(e) => e,
+            };
+
+            // each referral is to a zone below the last one, this ends at the query name
+            let zone = match referral(&e, ns.zone(), query.name()) {
+                Some(zone) => zone,
+                None => return Err(e),
+            };
+
+            debug!("{} referred {} to {}", ns.zone(), query, zone);
+            ns = self.ns_pool_for_zone(zone, request_time, budget).await?;
+        }
+    }
+
+    /// The name servers of the closest zone of the query whose delegation is cached, or the roots
+    async fn closest_pool(
+        &self,
+        query: &Query,
+        request_time: Instant,
+        budget: &Budget,
+    ) -> Result<RecursorPool<TokioConnection, TokioConnectionProvider>, Error> {
+        let mut zone = match query.query_type() {
+            // the DS records of a zone are in its parent zone
+            RecordType::NS | RecordType::DS => query.name().base_name(),
+            _ => query.name().clone(),
+        };
+
+        while !zone.is_root() {
+            if matches!(self.infra_cache.delegation(&zone), Some(delegation) if !delegation.is_expired(request_time))
+            {
+                return self.ns_pool_for_zone(zone, request_time, budget).await;
+            }
+            zone = zone.base_name();
+        }
+
+        Ok(self.roots.clone())
+    }
+
     async fn lookup(
         &self,
         query: Query,
         ns: RecursorPool<TokioConnection, TokioConnectionProvider>,
         now: Instant,
+        budget: &Budget,
     ) -> Result<Lookup, Error> {
         if let Some(lookup) = self.record_cache.get(&query, now) {
             debug!("cached data {:?}", lookup);
             return lookup.map_err(Into::into);
         }
 
-        let response = ns.lookup(query.clone());
+        self.query(query, ns, now, budget).await
+    }
+
+    /// Queries the name servers, without looking in the cache first
+    async fn query(
+        &self,
+        query: Query,
+        ns: RecursorPool<TokioConnection, TokioConnectionProvider>,
+        now: Instant,
+        budget: &Budget,
+    ) -> Result<Lookup, Error> {
+        budget.query()?;
+
+        if let Some(ref observer) = self.query_observer {
+            // the message of the lookup, the pool assigns its id
+            let mut message = Message::new();
+            message
+                .set_message_type(MessageType::Query)
+                .set_op_code(OpCode::Query)
+                .add_query(query.clone());
+            observer.query(ns.zone(), &message);
+        }
+
+        let response = ns.lookup(query.clone(), self.is_validating());
 
         // TODO: we are only expecting one response
         // TODO: should we change DnsHandle to always be a single response? And build a totally custom handler for other situations?
-        // TODO: check if data is "authentic"
         match response.await {
             Ok(mut r) => {
                 info!("response: {}", r.header());
-                let records = r
-                    .take_answers()
+                if let Some(ref observer) = self.query_observer {
+                    observer.response(ns.zone(), &r);
+                }
+                // the glue is cached apart, it is not an answer for the names of the name servers
+                let (glue, additionals): (Vec<_>, Vec<_>) =
+                    r.take_additionals().into_iter().partition(is_address);
+                self.infra_cache.insert_glue(ns.zone(), glue, now);
+
+                // the CNAME is synthesized from the DNAME, if the name server did not, RFC 6672
+                let mut answers = r.take_answers();
+                if !answers.iter().any(|a| is_cname_of(a, query.name())) {
+                    let cname = answers
+                        .iter()
+                        .find_map(|a| synthesize_cname(a, query.name()));
+                    answers.extend(cname);
+                }
+
+                // the CNAME chain in the answers is followed by the caller
+                let chain = if answers.iter().any(|a| is_cname_of(a, query.name())) {
+                    Some(answers.clone())
+                } else {
+                    None
+                };
+
+                let records = answers
                     .into_iter()
                     .chain(r.take_name_servers())
-                    .chain(r.take_additionals());
+                    .chain(additionals);
 
-                let lookup = self.record_cache.insert_records(query, records, now);
+                let lookup = self
+                    .record_cache
+                    .insert_records(query.clone(), records, now);
+                if let Some(lookup) = lookup {
+                    return Ok(lookup);
+                }
 
-                lookup.ok_or_else(|| Error::from("no records found"))
+                let cname_query = Query::query(query.name().clone(), RecordType::CNAME);
+                match (chain, self.record_cache.get(&cname_query, now)) {
+                    (Some(chain), Some(Ok(cname))) => Ok(Lookup::new_with_deadline(
+                        query,
+                        Arc::from(chain),
+                        cname.valid_until(),
+                    )),
+                    _ => Err(Error::from("no records found")),
+                }
             }
             Err(e) => {
                 warn!("lookup error: {}", e);
+
+                // NXDOMAIN and NODATA responses are cached for the TTL of their SOA, RFC 2308
+                let e = self.record_cache.negative(query, None, e, now);
                 Err(Error::from(e))
             }
         }
     }
 
+    /// The cached records of the query, or the CNAME of its name, synthesized from the DNAME of an
+    ///  ancestor if there is one
+    fn cached(&self, query: &Query, now: Instant) -> Option<Result<Lookup, ResolveError>> {
+        let lookup = self.record_cache.get(query, now);
+        if lookup.is_some() || matches!(query.query_type(), RecordType::CNAME | RecordType::DNAME) {
+            return lookup;
+        }
+
+        let name = query.name();
+        for num_labels in 1..name.num_labels() {
+            let dname_query =
+                Query::query(name.trim_to(usize::from(num_labels)), RecordType::DNAME);
+            let dname = match self.record_cache.get(&dname_query, now) {
+                Some(Ok(dname)) => dname,
+                _ => continue,
+            };
+
+            let records = dname.records().iter().find_map(|record| {
+                synthesize_cname(record, name).map(|cname| vec![record.clone(), cname])
+            });
+            if let Some(records) = records {
+                let lookup = Lookup::new_with_deadline(
+                    query.clone(),
+                    Arc::from(records),
+                    dname.valid_until(),
+                );
+                return Some(Ok(lookup));
+            }
+        }
+
+        let cname_query = Query::query(name.clone(), RecordType::CNAME);
+        match self.record_cache.get(&cname_query, now) {
+            Some(Ok(cname)) => Some(Ok(cname)),
+            _ => None,
+        }
+    }
+
+    /// True if the answers are validated with DNSSEC
+    fn is_validating(&self) -> bool {
+        cfg!(feature = "dnssec") && self.options.validate
+    }
+
     #[async_recursion]
     async fn ns_pool_for_zone(
         &self,
         zone: Name,
         request_time: Instant,
+        budget: &Budget,
     ) -> Result<RecursorPool<TokioConnection, TokioConnectionProvider>, Error> {
-        // TODO: need to check TTLs here.
-        if let Some(ns) = self.name_server_cache.lock().get_mut(&zone) {
-            return Ok(ns.clone());
+        if let Some((ns, valid_until)) = self.name_server_cache.lock().get_mut(&zone) {
+            if *valid_until > request_time {
+                return Ok(ns.clone());
+            }
         };
 
-        let parent_zone = zone.base_name();
+        let delegation = match self.infra_cache.delegation(&zone) {
+            Some(delegation) if !delegation.is_expired(request_time) => delegation,
+            cached => {
+                let parent_zone = zone.base_name();
 
-        let nameserver_pool = if parent_zone.is_root() {
-            debug!("using roots for {} nameservers", zone);
-            self.roots.clone()
-        } else {
-            self.ns_pool_for_zone(parent_zone, request_time).await?
-        };
+                let nameserver_pool = if parent_zone.is_root() {
+                    debug!("using roots for {} nameservers", zone);
+                    self.roots.clone()
+                } else {
+                    self.ns_pool_for_zone(parent_zone, request_time, budget)
+                        .await?
+                };
 
-        // TODO: check for cached ns pool for this zone
+                // this is the minimized query, RFC 9156, only one label more than the parent zone is sent
+                let lookup = Query::query(zone.clone(), RecordType::NS);
+                let response = if cached.is_some() {
+                    // the expired delegation is revalidated at the parent zone, not in the record cache
+                    debug!("revalidating the delegation of {}", zone);
+                    self.query(lookup, nameserver_pool.clone(), request_time, budget)
+                        .await
+                } else {
+                    self.lookup(lookup, nameserver_pool.clone(), request_time, budget)
+                        .await
+                };
 
-        let lookup = Query::query(zone.clone(), RecordType::NS);
-        let response = self
-            .lookup(lookup.clone(), nameserver_pool.clone(), request_time)
-            .await?;
+                // the CNAME of a name which is not a zone is not a delegation
+                let response = response.and_then(|response| {
+                    let name_servers = response
+                        .record_iter()
+                        .filter_map(|zns| zns.data().and_then(RData::as_ns))
+                        .cloned()
+                        .collect::<Vec<_>>();
 
-        // let zone_nameservers = response.name_servers();
-        // let glue = response.additionals();
+                    if name_servers.is_empty() {
+                        return Err(Error::from("no records found"));
+                    }
+                    Ok((name_servers, response.valid_until()))
+                });
 
-        // TODO: grab TTL and use for cache
-        // get all the NS records and glue
-        let mut config_group = NameServerConfigGroup::new();
-        let mut need_ips_for_names = Vec::new();
+                let (name_servers, valid_until) = match response {
+                    Ok(response) => response,
+                    // referrals and negative responses from the zone are handled by the caller
+                    Err(e) if e.kind().as_forward().is_some() => return Err(e),
+                    Err(e) => match self.options.qname_minimization {
+                        QnameMinimization::Relaxed | QnameMinimization::Off => {
+                            // RFC 9156 section 2.3, the parent zone's name servers will receive the full name
+                            debug!(
+                                "minimized query for {} failed, falling back to {}: {}",
+                                zone,
+                                nameserver_pool.zone(),
+                                e
+                            );
+                            return Ok(nameserver_pool);
+                        }
+                        QnameMinimization::Strict => return Err(e),
+                    },
+                };
 
-        // unpack all glued records
-        for zns in response.record_iter() {
-            if let Some(ns_data) = zns.data().and_then(RData::as_ns) {
-                // let glue_ips = glue
-                //     .iter()
-                //     .filter(|g| g.name() == ns_data)
-                //     .filter_map(Record::data)
-                //     .filter_map(RData::to_ip_addr);
-
-                let cached_a = self
-                    .record_cache
-                    .get(&Query::query(ns_data.clone(), RecordType::A), request_time);
-                let cached_aaaa = self.record_cache.get(
-                    &Query::query(ns_data.clone(), RecordType::AAAA),
+                self.infra_cache.insert_delegation(
+                    zone.clone(),
+                    name_servers,
+                    valid_until,
                     request_time,
-                );
-
-                let cached_a = cached_a.and_then(Result::ok).map(Lookup::into_iter);
-                let cached_aaaa = cached_aaaa.and_then(Result::ok).map(Lookup::into_iter);
-
-                let glue_ips = cached_a
-                    .into_iter()
-                    .flatten()
-                    .chain(cached_aaaa.into_iter().flatten())
-                    .filter_map(|r| RData::to_ip_addr(&r));
-
-                let mut had_glue = false;
-                for ip in glue_ips {
-                    let mut udp = NameServerConfig::new(SocketAddr::from((ip, 53)), Protocol::Udp);
-                    let mut tcp = NameServerConfig::new(SocketAddr::from((ip, 53)), Protocol::Tcp);
+                )
+            }
+        };
 
-                    udp.trust_nx_responses = true;
-                    tcp.trust_nx_responses = true;
+        // the pool is rebuilt once the delegation or one of the addresses expires
+        let mut valid_until = delegation.valid_until();
+        let mut config_group = NameServerConfigGroup::new();
+        let mut need_ips_for_names = Vec::new();
 
-                    config_group.push(udp);
-                    config_group.push(tcp);
-                    had_glue = true;
+        // unpack all glued records
+        for ns_name in delegation.name_servers() {
+            match self.name_server_addresses(ns_name, request_time) {
+                Some((ips, ips_valid_until)) => {
+                    for ip in ips.iter() {
+                        push_name_server(&mut config_group, *ip);
+                    }
+                    valid_until = valid_until.min(ips_valid_until);
                 }
-
-                if !had_glue {
-                    debug!("glue not found for {}", ns_data);
-                    need_ips_for_names.push(ns_data);
+                None => {
+                    debug!("glue not found for {}", ns_name);
+                    need_ips_for_names.push(ns_name);
                 }
             }
         }
 
         // collect missing IP addresses, select over them all, get the addresses
-        // make it configurable to query for all records?
+        // only a few name servers are resolved, a delegation to many name servers without glue
+        //  must not make the recursor send as many queries, see NXNSAttack
         if config_group.is_empty() && !need_ips_for_names.is_empty() {
             debug!("need glue for {}", zone);
-            let a_resolves = need_ips_for_names.iter().take(1).map(|name| {
-                let a_query = Query::query((*name).clone(), RecordType::A);
-                self.resolve(a_query, request_time).boxed()
-            });
+            let nested = budget.nested()?;
+            let fanout = need_ips_for_names.len().min(budget.limits().max_ns_fanout);
 
-            let aaaa_resolves = need_ips_for_names.iter().take(1).map(|name| {
-                let aaaa_query = Query::query((*name).clone(), RecordType::AAAA);
-                self.resolve(aaaa_query, request_time).boxed()
-            });
+            let mut a_resolves = Vec::new();
+            for name in &need_ips_for_names[..fanout] {
+                for rtype in &[RecordType::A, RecordType::AAAA] {
+                    let query = Query::query((*name).clone(), *rtype);
+                    let nested = &nested;
+                    a_resolves.push(
+                        async move { (*name, self.resolve_chain(query, request_time, nested).await) }
+                            .boxed(),
+                    );
+                }
+            }
 
-            let mut a_resolves: Vec<_> = a_resolves.chain(aaaa_resolves).collect();
+            let mut resolved = HashMap::<&Name, (Vec<IpAddr>, Instant)>::new();
             while !a_resolves.is_empty() {
-                let (next, _, rest) = select_all(a_resolves).await;
+                let ((name, next), _, rest) = select_all(a_resolves).await;
                 a_resolves = rest;
 
                 match next {
                     Ok(response) => {
                         debug!("A or AAAA response: {:?}", response);
-                        let ips = response.iter().filter_map(RData::to_ip_addr);
-
-                        for ip in ips {
-                            let udp =
-                                NameServerConfig::new(SocketAddr::from((ip, 53)), Protocol::Udp);
-                            let tcp =
-                                NameServerConfig::new(SocketAddr::from((ip, 53)), Protocol::Tcp);
-
-                            config_group.push(udp);
-                            config_group.push(tcp);
-                        }
+                        let (ips, resolved_valid_until) = resolved
+                            .entry(name)
+                            .or_insert_with(|| (Vec::new(), response.valid_until()));
+                        ips.extend(response.iter().filter_map(RData::to_ip_addr));
+                        *resolved_valid_until = response.valid_until().min(*resolved_valid_until);
                     }
+                    // the limits are those of the whole query
+                    Err(e) if matches!(e.kind(), ErrorKind::LimitExceeded { .. }) => return Err(e),
                     Err(e) => {
                         warn!("resolve failed {}", e);
                     }
                 }
             }
+
+            for (name, (ips, resolved_valid_until)) in resolved {
+                for ip in &ips {
+                    push_name_server(&mut config_group, *ip);
+                }
+                valid_until = valid_until.min(resolved_valid_until);
+                self.infra_cache.insert_addresses(
+                    name.clone(),
+                    ips,
+                    Trust::Authoritative,
+                    resolved_valid_until,
+                    request_time,
+                );
+            }
         }
 
         // now construct a namesever pool based off the NS and glue records
-        let ns = NameServerPool::from_config(
+        let ns = RecursorPool::from(
+            zone.clone(),
             config_group,
-            &recursor_opts(),
-            TokioConnectionProvider::new(TokioHandle::default()),
+            &self.options,
+            Arc::clone(&self.name_server_stats),
         );
-        let ns = RecursorPool::from(zone.clone(), ns);
 
         // store in cache for future usage
         debug!("found nameservers for {}", zone);
-        self.name_server_cache.lock().insert(zone, ns.clone());
+        self.name_server_cache
+            .lock()
+            .insert(zone, (ns.clone(), valid_until));
         Ok(ns)
     }
+
+    /// The addresses of the name server, from the infrastructure cache or from the cached answers
+    ///  for its name, and until when they are valid
+    fn name_server_addresses(&self, name: &Name, now: Instant) -> Option<(Arc<[IpAddr]>, Instant)> {
+        if let Some(addresses) = self.infra_cache.addresses(name, now) {
+            return Some(addresses);
+        }
+
+        let lookups = [RecordType::A, RecordType::AAAA]
+            .iter()
+            .filter_map(|rtype| {
+                self.record_cache
+                    .get(&Query::query(name.clone(), *rtype), now)
+            })
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+        let ips = lookups
+            .iter()
+            .flat_map(Lookup::iter)
+            .filter_map(RData::to_ip_addr)
+            .collect::<Vec<_>>();
+        let valid_until = lookups.iter().map(Lookup::valid_until).min()?;
+
+        self.infra_cache.insert_addresses(
+            name.clone(),
+            ips,
+            Trust::Authoritative,
+            valid_until,
+            now,
+        );
+        self.infra_cache.addresses(name, now)
+    }
 }
 
-fn recursor_opts() -> ResolverOpts {
-    let mut options = ResolverOpts::default();
+/// True if the record is the CNAME of the name
+fn is_cname_of(record: &Record, name: &Name) -> bool {
+    record.record_type() == RecordType::CNAME && record.name() == name
+}
+
+/// The zone which the name servers of `from` referred the name to in the error of a lookup, a
+///  zone below `from` and of the name
+fn referral(error: &Error, from: &Name, name: &Name) -> Option<Name> {
+    let authorities = match error.kind() {
+        ErrorKind::Resolve(e) => match e.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NoError,
+                soa: None,
+                authorities: Some(authorities),
+                ..
+            } => authorities,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    authorities
+        .iter()
+        .filter(|record| record.record_type() == RecordType::NS)
+        .map(Record::name)
+        .find(|zone| *zone != from && from.zone_of(zone) && zone.zone_of(name))
+        .cloned()
+}
+
+/// Adds the UDP and TCP configurations of the name server, whose negative responses are trusted
+fn push_name_server(config_group: &mut NameServerConfigGroup, ip: IpAddr) {
+    let mut udp = NameServerConfig::new(SocketAddr::from((ip, 53)), Protocol::Udp);
+    let mut tcp = NameServerConfig::new(SocketAddr::from((ip, 53)), Protocol::Tcp);
+
+    udp.trust_nx_responses = true;
+    tcp.trust_nx_responses = true;
+
+    config_group.push(udp);
+    config_group.push(tcp);
+}
+
+fn recursor_opts(mut options: ResolverOpts) -> ResolverOpts {
     options.ndots = 0;
     options.edns0 = true;
-    options.validate = false; // we'll need to do any dnssec validation differently in a recursor (top-down rather than bottom-up)
     options.preserve_intermediates = true;
     options.recursion_desired = false;
     options.num_concurrent_reqs = 1;
 
     options
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::Ipv4Addr,
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use tokio::net::UdpSocket;
+    use trust_dns_proto::serialize::binary::{BinDecodable, BinEncodable};
+
+    use super::*;
+
+    /// Answers the `A` queries, and responds to the minimized `NS` queries with the response code
+    async fn root_server(
+        socket: UdpSocket,
+        ns_response: ResponseCode,
+        ns_queries: Arc<AtomicUsize>,
+        a_queries: Arc<AtomicUsize>,
+    ) {
+        let mut buf = [0_u8; 512];
+        loop {
+            let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+            let request = Message::from_bytes(&buf[..len]).unwrap();
+            let query = request.queries()[0].clone();
+
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .add_query(query.clone());
+            if query.query_type() == RecordType::NS {
+                ns_queries.fetch_add(1, Ordering::SeqCst);
+                response.set_response_code(ns_response);
+            } else {
+                a_queries.fetch_add(1, Ordering::SeqCst);
+                response
+                    .set_authoritative(true)
+                    .add_answer(Record::from_rdata(
+                        query.name().clone(),
+                        300,
+                        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+                    ));
+            }
+
+            socket
+                .send_to(&response.to_bytes().unwrap(), src)
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Resolves `www.example.com.` at a root server, returns the result and the numbers of the
+    ///  `NS` and `A` queries it received
+    async fn resolve(
+        qname_minimization: QnameMinimization,
+        ns_response: ResponseCode,
+    ) -> (Result<Lookup, Error>, usize, usize) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let ns_queries = Arc::new(AtomicUsize::new(0));
+        let a_queries = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(root_server(
+            socket,
+            ns_response,
+            Arc::clone(&ns_queries),
+            Arc::clone(&a_queries),
+        ));
+
+        let mut roots = NameServerConfigGroup::new();
+        roots.push(NameServerConfig::new(addr, Protocol::Udp));
+        let mut options = ResolverOpts::default();
+        options.qname_minimization = qname_minimization;
+        let recursor = Recursor::new_with_options(roots, options).unwrap();
+
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let result = recursor.resolve(query, Instant::now()).await;
+        (
+            result,
+            ns_queries.load(Ordering::SeqCst),
+            a_queries.load(Ordering::SeqCst),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_relaxed_falls_back_to_full_name() {
+        for ns_response in &[ResponseCode::NXDomain, ResponseCode::ServFail] {
+            let (result, ns_queries, a_queries) =
+                resolve(QnameMinimization::Relaxed, *ns_response).await;
+
+            let lookup = result.unwrap();
+            assert_eq!(
+                lookup.iter().next(),
+                Some(&RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+            );
+            assert!(ns_queries > 0);
+            assert_eq!(a_queries, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_strict_returns_error() {
+        for ns_response in &[ResponseCode::NXDomain, ResponseCode::ServFail] {
+            let (result, ns_queries, a_queries) =
+                resolve(QnameMinimization::Strict, *ns_response).await;
+
+            assert!(result.is_err());
+            assert!(ns_queries > 0);
+            // the full name is never sent to the roots
+            assert_eq!(a_queries, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_off_sends_full_name() {
+        let (result, ns_queries, a_queries) =
+            resolve(QnameMinimization::Off, ResponseCode::ServFail).await;
+
+        assert!(result.is_ok());
+        assert_eq!(ns_queries, 0);
+        assert_eq!(a_queries, 1);
+    }
+
+    #[test]
+    fn test_referral() {
+        let com = Name::from_str("com.").unwrap();
+        let example = Name::from_str("example.com.").unwrap();
+        let www = Name::from_str("www.example.com.").unwrap();
+
+        let ns = Record::from_rdata(
+            example.clone(),
+            300,
+            RData::NS(Name::from_str("ns.example.com.").unwrap()),
+        );
+        let error = |authorities: Vec<Record>| {
+            Error::from(ResolveError::from(ResolveErrorKind::NoRecordsFound {
+                query: Box::new(Query::query(www.clone(), RecordType::A)),
+                soa: None,
+                negative_ttl: None,
+                response_code: ResponseCode::NoError,
+                trusted: false,
+                authorities: Some(Arc::from(authorities)),
+            }))
+        };
+
+        assert_eq!(
+            referral(&error(vec![ns.clone()]), &com, &www),
+            Some(example.clone())
+        );
+        // the referral must be below the zone which sent it
+        assert_eq!(referral(&error(vec![ns.clone()]), &example, &www), None);
+        // and a zone of the query name
+        let other = Name::from_str("www.example.net.").unwrap();
+        assert_eq!(referral(&error(vec![ns]), &com, &other), None);
+        assert_eq!(referral(&error(vec![]), &com, &www), None);
+    }
+}
@@ -7,25 +7,37 @@
 
 use std::{
     collections::HashMap,
+    net::IpAddr,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Instant,
 };
 
-use futures_util::{future::Shared, Future, FutureExt, StreamExt};
+use futures_util::{
+    future::{self, Either, Shared},
+    stream::FuturesUnordered,
+    Future, FutureExt, StreamExt,
+};
 use parking_lot::Mutex;
-use tracing::info;
+use tracing::{debug, debug_span, info, Instrument};
 use trust_dns_proto::{
-    op::Query,
-    xfer::{DnsRequestOptions, DnsResponse},
-    DnsHandle,
+    op::{Edns, Message, MessageType, OpCode, Query},
+    xfer::{DnsRequest, DnsRequestOptions, DnsResponse, FirstAnswer},
+    DnsHandle, Time,
 };
 use trust_dns_resolver::{
+    config::{NameServerConfigGroup, ResolverOpts},
     error::{ResolveError, ResolveErrorKind},
     name_server::NameServerPool,
-    ConnectionProvider, Name, TokioConnection, TokioConnectionProvider,
+    ConnectionProvider, Name, TokioConnection, TokioConnectionProvider, TokioHandle,
 };
 
+use crate::name_server_stats::NameServerStats;
+
+/// The maximum UDP payload advertised with EDNS, the DNS flag day 2020 recommendation
+const MAX_PAYLOAD_LEN: u16 = 1232;
+
 /// Active request cache
 ///
 /// The futures are Shared so any waiting on these results will resolve to the same result
@@ -48,27 +60,61 @@ impl Future for SharedLookup {
     }
 }
 
+/// The name servers of a zone
+///
+/// Each address of the name servers has its own pool, of its UDP and TCP connections. The
+///  addresses are queried in the order of their smoothed round trip times, see
+///  [`NameServerStats`], and the next one is queried if the previous one has not answered after
+///  twice its round trip time, while the queries to the previous ones are still awaited.
 #[derive(Clone)]
 pub(crate) struct RecursorPool<
     C: DnsHandle<Error = ResolveError> + Send + Sync + 'static,
     P: ConnectionProvider<Conn = C> + Send + 'static,
 > {
     zone: Name,
-    ns: NameServerPool<C, P>,
+    servers: Arc<[(IpAddr, NameServerPool<C, P>)]>,
+    stats: Arc<NameServerStats>,
     active_requests: Arc<Mutex<ActiveRequests>>,
 }
 
 impl RecursorPool<TokioConnection, TokioConnectionProvider> {
     pub(crate) fn from(
         zone: Name,
-        ns: NameServerPool<TokioConnection, TokioConnectionProvider>,
+        name_servers: NameServerConfigGroup,
+        options: &ResolverOpts,
+        stats: Arc<NameServerStats>,
     ) -> Self {
-        let active_requests = Arc::new(Mutex::new(ActiveRequests::default()));
+        // the connections of each address, in the order of the addresses
+        let mut addresses = Vec::<(IpAddr, NameServerConfigGroup)>::new();
+        for config in name_servers.into_inner() {
+            let ip = config.socket_addr.ip();
+            match addresses.iter_mut().find(|(other, _)| *other == ip) {
+                Some((_, configs)) => configs.push(config),
+                None => {
+                    let mut configs = NameServerConfigGroup::new();
+                    configs.push(config);
+                    addresses.push((ip, configs));
+                }
+            }
+        }
+
+        let servers = addresses
+            .into_iter()
+            .map(|(ip, configs)| {
+                let pool = NameServerPool::from_config(
+                    configs,
+                    options,
+                    TokioConnectionProvider::new(TokioHandle::default()),
+                );
+                (ip, pool)
+            })
+            .collect::<Vec<_>>();
 
         Self {
             zone,
-            ns,
-            active_requests,
+            servers: Arc::from(servers),
+            stats,
+            active_requests: Arc::new(Mutex::new(ActiveRequests::default())),
         }
     }
 }
@@ -82,9 +128,13 @@ where
         &self.zone
     }
 
-    pub(crate) async fn lookup(&self, query: Query) -> Result<DnsResponse, ResolveError> {
-        let mut ns = self.ns.clone();
-
+    /// Looks up the query, with the DNSSEC OK bit set if `dnssec_ok`, so that the RRSIGs, and the
+    ///  NSEC or NSEC3 records of negative responses, are returned
+    pub(crate) async fn lookup(
+        &self,
+        query: Query,
+        dnssec_ok: bool,
+    ) -> Result<DnsResponse, ResolveError> {
         let query_cpy = query.clone();
 
         // block concurrent requests
@@ -96,16 +146,34 @@ where
                 info!("querying {} for {}", self.zone, query_cpy);
 
                 let mut options = DnsRequestOptions::default();
-                options.use_edns = false; // TODO: this should be configurable
+                options.use_edns = dnssec_ok; // TODO: this should be configurable
                 options.recursion_desired = false;
 
+                let mut message = Message::new();
+                message
+                    .add_query(query_cpy)
+                    .set_message_type(MessageType::Query)
+                    .set_op_code(OpCode::Query)
+                    .set_recursion_desired(false);
+                if dnssec_ok {
+                    let edns = message.extensions_mut().get_or_insert_with(Edns::new);
+                    edns.set_max_payload(MAX_PAYLOAD_LEN).set_dnssec_ok(true);
+                }
+
+                // the query is in the span of the request which sent it first, the others share it
+                let span =
+                    debug_span!("upstream", zone = %self.zone, query = %message.queries()[0]);
+
                 // convert the lookup into a shared future
-                let lookup = ns
-                    .lookup(query_cpy, options)
-                    .into_future()
-                    .map(|(next, _)| next)
-                    .boxed()
-                    .shared();
+                let lookup = staggered_send(
+                    Arc::clone(&self.servers),
+                    Arc::clone(&self.stats),
+                    DnsRequest::new(message, options),
+                )
+                .map(Some)
+                .instrument(span)
+                .boxed()
+                .shared();
 
                 SharedLookup(lookup)
             })
@@ -119,3 +187,176 @@ where
         result
     }
 }
+
+/// Sends the request to the name servers, fastest first, and to the next one each time the
+///  previous ones have not answered within their stagger delay, returns the first answer
+async fn staggered_send<C, P>(
+    servers: Arc<[(IpAddr, NameServerPool<C, P>)]>,
+    stats: Arc<NameServerStats>,
+    request: DnsRequest,
+) -> Result<DnsResponse, ResolveError>
+where
+    C: DnsHandle<Error = ResolveError> + Send + Sync + 'static,
+    P: ConnectionProvider<Conn = C> + Send + 'static,
+{
+    let ips = servers.iter().map(|(ip, _)| *ip).collect::<Vec<_>>();
+    let mut order = stats.order(&ips).into_iter();
+    let mut queries = FuturesUnordered::new();
+    let mut err = ResolveError::from(ResolveErrorKind::Message("no name servers"));
+
+    loop {
+        let stagger = match order.next() {
+            Some(i) => {
+                let (ip, pool) = &servers[i];
+                debug!(
+                    "sending {:?} to {}, failures: {}",
+                    request.queries(),
+                    ip,
+                    stats.failures(*ip)
+                );
+                queries.push(send(*ip, pool.clone(), &stats, request.clone()));
+                Some(P::Time::delay_for(stats.stagger(*ip)))
+            }
+            None if queries.is_empty() => return Err(err),
+            None => None,
+        };
+
+        // wait for an answer, or for the next name server to be queried
+        let result = match stagger {
+            Some(stagger) => match future::select(queries.next(), stagger).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => continue,
+            },
+            None => queries.next().await,
+        };
+
+        match result {
+            Some(Ok(response)) => return Ok(response),
+            // negative answers from the zone are answers
+            Some(Err(e)) if is_answer(&e) => return Err(e),
+            // the next name server is queried without waiting
+            Some(Err(e)) => err = e,
+            None => (),
+        }
+    }
+}
+
+/// Sends the request to the name server, and records its round trip time or its failure
+async fn send<C, P>(
+    ip: IpAddr,
+    mut pool: NameServerPool<C, P>,
+    stats: &NameServerStats,
+    request: DnsRequest,
+) -> Result<DnsResponse, ResolveError>
+where
+    C: DnsHandle<Error = ResolveError> + Send + Sync + 'static,
+    P: ConnectionProvider<Conn = C> + Send + 'static,
+{
+    let started = Instant::now();
+    let result = pool.send(request).first_answer().await;
+
+    match &result {
+        Ok(_) => stats.record_rtt(ip, started.elapsed()),
+        Err(e) if is_answer(e) => stats.record_rtt(ip, started.elapsed()),
+        Err(e) => {
+            debug!("name server {} failed: {}", ip, e);
+            stats.record_failure(ip);
+        }
+    }
+
+    result
+}
+
+/// True if the error is a negative answer of the name server, e.g. NXDOMAIN
+fn is_answer(error: &ResolveError) -> bool {
+    matches!(
+        error.kind(),
+        ResolveErrorKind::NoRecordsFound { trusted: true, .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use tokio::net::UdpSocket;
+    use trust_dns_proto::{
+        rr::{RData, Record, RecordType},
+        serialize::binary::{BinDecodable, BinEncodable},
+    };
+    use trust_dns_resolver::config::{NameServerConfig, Protocol};
+
+    use super::*;
+
+    /// Counts the queries, and answers them if `answer`
+    async fn name_server(socket: UdpSocket, queries: Arc<AtomicUsize>, answer: bool) {
+        let mut buf = [0_u8; 512];
+        loop {
+            let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+            queries.fetch_add(1, Ordering::SeqCst);
+            if !answer {
+                continue;
+            }
+
+            let request = Message::from_bytes(&buf[..len]).unwrap();
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_authoritative(true)
+                .add_queries(request.queries().to_vec())
+                .add_answer(Record::from_rdata(
+                    request.queries()[0].name().clone(),
+                    300,
+                    RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+                ));
+            socket
+                .send_to(&response.to_bytes().unwrap(), src)
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn spawn_name_server(ip: Ipv4Addr, answer: bool) -> (SocketAddr, Arc<AtomicUsize>) {
+        let socket = UdpSocket::bind((ip, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let queries = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(name_server(socket, Arc::clone(&queries), answer));
+        (addr, queries)
+    }
+
+    #[tokio::test]
+    async fn test_fastest_name_server() {
+        let (silent, silent_queries) = spawn_name_server(Ipv4Addr::new(127, 0, 0, 1), false).await;
+        let (answering, answering_queries) =
+            spawn_name_server(Ipv4Addr::new(127, 0, 0, 2), true).await;
+
+        let mut name_servers = NameServerConfigGroup::new();
+        name_servers.push(NameServerConfig::new(silent, Protocol::Udp));
+        name_servers.push(NameServerConfig::new(answering, Protocol::Udp));
+
+        let mut options = ResolverOpts::default();
+        options.timeout = Duration::from_millis(100);
+        let stats = Arc::new(NameServerStats::new(8));
+        let pool = RecursorPool::from(Name::root(), name_servers, &options, Arc::clone(&stats));
+
+        // the name servers are queried in order, the next one once the first has failed
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        assert!(pool.lookup(query, false).await.is_ok());
+        assert_eq!(silent_queries.load(Ordering::SeqCst), 1);
+        assert_eq!(answering_queries.load(Ordering::SeqCst), 1);
+        assert_eq!(stats.failures(silent.ip()), 1);
+        assert!(stats.srtt(answering.ip()).unwrap() < stats.srtt(silent.ip()).unwrap());
+
+        // the fastest name server is queried first
+        let query = Query::query(Name::from_str("www.example.net.").unwrap(), RecordType::A);
+        assert!(pool.lookup(query, false).await.is_ok());
+        assert_eq!(silent_queries.load(Ordering::SeqCst), 1);
+        assert_eq!(answering_queries.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Destination address selection, [RFC 6724](https://tools.ietf.org/html/rfc6724)
+
+use std::cmp::Ordering;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+/// The default policy table, RFC 6724 section 2.1
+///
+/// `(prefix, prefix length, precedence, label)`, IPv4 addresses are matched as IPv4-mapped IPv6.
+const POLICY_TABLE: &[(Ipv6Addr, u8, u8, u8)] = &[
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 128, 50, 0),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96, 35, 4),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 96, 1, 3),
+    (Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 0), 32, 5, 5),
+    (Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 0), 16, 30, 2),
+    (Ipv6Addr::new(0x3ffe, 0, 0, 0, 0, 0, 0, 0), 16, 1, 12),
+    (Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 0), 10, 1, 11),
+    (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7, 3, 13),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 40, 1),
+];
+
+const SCOPE_LINK_LOCAL: u8 = 0x2;
+const SCOPE_SITE_LOCAL: u8 = 0x5;
+const SCOPE_GLOBAL: u8 = 0xe;
+
+/// Sorts the items by their destination address, in the order of preference of RFC 6724
+///
+/// The source address for each destination is the one the OS routes from, no packets are sent
+///  to determine it. The sort is stable, so equally preferred addresses keep their order, RFC
+///  6724 rule 10.
+///
+/// This blocks on the system calls, on an executor it is run with
+///  `ConnectionProvider::spawn_blocking`.
+pub(crate) fn sort_by_destination<T>(items: &mut Vec<T>, addr: impl Fn(&T) -> IpAddr) {
+    if items.len() < 2 {
+        return;
+    }
+
+    let mut destinations = items
+        .drain(..)
+        .map(|item| (Destination::new(addr(&item)), item))
+        .collect::<Vec<_>>();
+    destinations.sort_by(|(a, _), (b, _)| a.cmp_preference(b));
+    items.extend(destinations.into_iter().map(|(_, item)| item));
+}
+
+/// A destination address, and the source address which would be used for it
+#[derive(Clone, Copy, Debug)]
+struct Destination {
+    addr: IpAddr,
+    /// `None` if the destination is unreachable
+    source: Option<IpAddr>,
+}
+
+impl Destination {
+    fn new(addr: IpAddr) -> Self {
+        Self {
+            addr,
+            source: source_addr(addr),
+        }
+    }
+
+    /// Compares the destinations, `Ordering::Less` is preferred, RFC 6724 section 6
+    ///
+    /// Rules 3, 4 and 7 require information of the interfaces which is not available, and are
+    ///  skipped.
+    fn cmp_preference(&self, other: &Self) -> Ordering {
+        // Rule 1: Avoid unusable destinations.
+        let usable = |d: &Self| d.source.is_some();
+        // Rule 2: Prefer matching scope.
+        let matching_scope = |d: &Self| d.source.map_or(false, |s| scope(s) == scope(d.addr));
+        // Rule 5: Prefer matching label.
+        let matching_label = |d: &Self| d.source.map_or(false, |s| label(s) == label(d.addr));
+
+        usable(other)
+            .cmp(&usable(self))
+            .then_with(|| matching_scope(other).cmp(&matching_scope(self)))
+            .then_with(|| matching_label(other).cmp(&matching_label(self)))
+            // Rule 6: Prefer higher precedence.
+            .then_with(|| precedence(other.addr).cmp(&precedence(self.addr)))
+            // Rule 8: Prefer smaller scope.
+            .then_with(|| scope(self.addr).cmp(&scope(other.addr)))
+            // Rule 9: Use longest matching prefix, only for IPv6 as the prefix of IPv4 addresses
+            //  defeats the round robin of DNS
+            .then_with(|| match (self, other) {
+                (
+                    Self {
+                        addr: IpAddr::V6(a),
+                        source: Some(IpAddr::V6(source_a)),
+                    },
+                    Self {
+                        addr: IpAddr::V6(b),
+                        source: Some(IpAddr::V6(source_b)),
+                    },
+                ) => common_prefix_len(b, source_b).cmp(&common_prefix_len(a, source_a)),
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+/// The source address the OS would use to reach the destination
+///
+/// This connects a UDP socket, which only consults the routing table.
+fn source_addr(destination: IpAddr) -> Option<IpAddr> {
+    let unspecified = match destination {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).ok()?;
+    socket.connect(SocketAddr::new(destination, 53)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+fn policy(addr: IpAddr) -> (u8, u8) {
+    let addr = match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+        IpAddr::V6(addr) => addr,
+    };
+
+    POLICY_TABLE
+        .iter()
+        .find(|(prefix, len, _, _)| common_prefix_len(&addr, prefix) >= *len)
+        .map_or((40, 1), |(_, _, precedence, label)| (*precedence, *label))
+}
+
+fn precedence(addr: IpAddr) -> u8 {
+    policy(addr).0
+}
+
+fn label(addr: IpAddr) -> u8 {
+    policy(addr).1
+}
+
+/// The scope of the address, RFC 6724 section 3.1
+fn scope(addr: IpAddr) -> u8 {
+    match addr {
+        // RFC 6724 section 3.2, loopback and auto-configuration addresses are link-local
+        IpAddr::V4(addr) if addr.is_loopback() || addr.is_link_local() => SCOPE_LINK_LOCAL,
+        IpAddr::V4(_) => SCOPE_GLOBAL,
+        IpAddr::V6(addr) if addr.is_multicast() => addr.segments()[0] as u8 & 0x0f,
+        IpAddr::V6(addr) if addr.is_loopback() => SCOPE_LINK_LOCAL,
+        IpAddr::V6(addr) if addr.segments()[0] & 0xffc0 == 0xfe80 => SCOPE_LINK_LOCAL,
+        IpAddr::V6(addr) if addr.segments()[0] & 0xffc0 == 0xfec0 => SCOPE_SITE_LOCAL,
+        IpAddr::V6(_) => SCOPE_GLOBAL,
+    }
+}
+
+/// The number of leading bits the addresses have in common
+fn common_prefix_len(a: &Ipv6Addr, b: &Ipv6Addr) -> u8 {
+    let diff = u128::from_be_bytes(a.octets()) ^ u128::from_be_bytes(b.octets());
+    diff.leading_zeros() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(destinations: &[(IpAddr, Option<IpAddr>)]) -> Vec<IpAddr> {
+        let mut destinations = destinations
+            .iter()
+            .map(|(addr, source)| Destination {
+                addr: *addr,
+                source: *source,
+            })
+            .collect::<Vec<_>>();
+        destinations.sort_by(Destination::cmp_preference);
+        destinations.into_iter().map(|d| d.addr).collect()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    // the examples of RFC 6724 section 10.2
+
+    #[test]
+    fn test_prefer_usable() {
+        // Rule 1, no IPv6 connectivity
+        assert_eq!(
+            sorted(&[
+                (ip("2001:db8:1::1"), None),
+                (ip("198.51.100.121"), Some(ip("198.51.100.117"))),
+            ]),
+            vec![ip("198.51.100.121"), ip("2001:db8:1::1")]
+        );
+    }
+
+    #[test]
+    fn test_prefer_precedence() {
+        // Rule 6, both usable
+        assert_eq!(
+            sorted(&[
+                (ip("198.51.100.121"), Some(ip("198.51.100.117"))),
+                (ip("2001:db8:1::1"), Some(ip("2001:db8:1::2"))),
+            ]),
+            vec![ip("2001:db8:1::1"), ip("198.51.100.121")]
+        );
+    }
+
+    #[test]
+    fn test_prefer_matching_scope() {
+        // Rule 2, only a link-local IPv6 source
+        assert_eq!(
+            sorted(&[
+                (ip("2001:db8:1::1"), Some(ip("fe80::1"))),
+                (ip("198.51.100.121"), Some(ip("198.51.100.117"))),
+            ]),
+            vec![ip("198.51.100.121"), ip("2001:db8:1::1")]
+        );
+    }
+
+    #[test]
+    fn test_prefer_smaller_scope() {
+        // Rule 8
+        assert_eq!(
+            sorted(&[
+                (ip("2001:db8:1::1"), Some(ip("2001:db8:1::2"))),
+                (ip("fe80::1"), Some(ip("fe80::2"))),
+            ]),
+            vec![ip("fe80::1"), ip("2001:db8:1::1")]
+        );
+    }
+
+    #[test]
+    fn test_prefer_matching_label() {
+        // Rule 5, a ULA source prefers the ULA destination
+        assert_eq!(
+            sorted(&[
+                (ip("2001:db8:1::1"), Some(ip("fd11::2"))),
+                (ip("fd11::1"), Some(ip("fd11::2"))),
+            ]),
+            vec![ip("fd11::1"), ip("2001:db8:1::1")]
+        );
+    }
+
+    #[test]
+    fn test_longest_matching_prefix() {
+        // Rule 9
+        assert_eq!(
+            sorted(&[
+                (ip("2001:db8:1::1"), Some(ip("2001:db8:3f44::2"))),
+                (ip("2001:db8:3ffe::1"), Some(ip("2001:db8:3f44::2"))),
+            ]),
+            vec![ip("2001:db8:3ffe::1"), ip("2001:db8:1::1")]
+        );
+    }
+
+    #[test]
+    fn test_stable() {
+        let a = ip("192.0.2.1");
+        let b = ip("198.51.100.1");
+        let source = Some(ip("192.0.2.100"));
+
+        assert_eq!(sorted(&[(a, source), (b, source)]), vec![a, b]);
+        assert_eq!(sorted(&[(b, source), (a, source)]), vec![b, a]);
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(scope(ip("fe80::1")), SCOPE_LINK_LOCAL);
+        assert_eq!(scope(ip("fec0::1")), SCOPE_SITE_LOCAL);
+        assert_eq!(scope(ip("2001:db8::1")), SCOPE_GLOBAL);
+        assert_eq!(scope(ip("ff02::1")), SCOPE_LINK_LOCAL);
+        assert_eq!(scope(ip("127.0.0.1")), SCOPE_LINK_LOCAL);
+        assert_eq!(scope(ip("10.0.0.1")), SCOPE_GLOBAL);
+    }
+}
@@ -6,21 +6,34 @@
 // copied, modified, or distributed except according to those terms.
 
 //! Structs for creating and using a AsyncResolver
+use std::collections::HashMap;
 use std::fmt;
-use std::net::IpAddr;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
+#[cfg(feature = "dnssec")]
+use parking_lot::Mutex;
+use parking_lot::RwLock;
 use proto::error::ProtoResult;
 use proto::op::Query;
+#[cfg(feature = "dnssec")]
+use proto::rr::dnssec::TrustAnchor;
 use proto::rr::domain::usage::ONION;
 use proto::rr::domain::TryParseIp;
-use proto::rr::{IntoName, Name, Record, RecordType};
-use proto::xfer::{DnsRequestOptions, RetryDnsHandle};
-use proto::DnsHandle;
+use proto::rr::{IntoName, Name, RData, Record, RecordType};
+use proto::xfer::{DnsRequestOptions, FirstAnswer, RetryDnsHandle};
+use proto::{DnsHandle, Time};
 use tracing::{debug, trace};
 
+use crate::address_selection;
+use crate::blocklist::Blocklist;
 use crate::caching_client::CachingClient;
-use crate::config::{ResolverConfig, ResolverOpts};
+use crate::config::{
+    EncryptionPolicy, LookupOptions, NameServerConfig, NameServerConfigGroup, ResolverConfig,
+    ResolverOpts, ServiceHints, SocketType,
+};
+use crate::ddr;
 use crate::dns_lru::{self, DnsLru};
 use crate::error::*;
 use crate::lookup::{self, Lookup, LookupEither, LookupFuture};
@@ -32,6 +45,9 @@ use crate::name_server::{
 #[cfg(feature = "tokio-runtime")]
 use crate::name_server::{TokioConnection, TokioConnectionProvider, TokioHandle};
 
+use crate::services::SYSTEM_SERVICES;
+#[cfg(feature = "dnssec")]
+use crate::trust_anchor::TrustAnchors;
 use crate::Hosts;
 
 /// An asynchronous resolver for DNS generic over async Runtimes.
@@ -66,6 +82,13 @@ pub struct AsyncResolver<C: DnsHandle<Error = ResolveError>, P: ConnectionProvid
     options: ResolverOpts,
     client_cache: CachingClient<LookupEither<C, P>, ResolveError>,
     hosts: Option<Arc<Hosts>>,
+    conn_provider: P,
+    #[cfg(feature = "dnssec")]
+    trust_anchors: Option<Arc<Mutex<TrustAnchors>>>,
+    /// The trust anchor of the validator, replaced when the trusted keys of `trust_anchors` change
+    #[cfg(feature = "dnssec")]
+    validator_trust_anchor: Option<Arc<std::sync::RwLock<TrustAnchor>>>,
+    blocklist: Arc<RwLock<Option<Blocklist>>>,
 }
 
 /// An AsyncResolver used with Tokio
@@ -182,11 +205,6 @@ impl<R: RuntimeProvider> AsyncResolver<GenericConnection, GenericConnectionProvi
     pub fn from_system_conf(runtime: R::Handle) -> Result<Self, ResolveError> {
         Self::from_system_conf_with_provider(GenericConnectionProvider::<R>::new(runtime))
     }
-
-    /// Flushes/Removes all entries from the cache
-    pub fn clear_cache(&self) {
-        self.client_cache.clear_cache();
-    }
 }
 
 impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncResolver<C, P> {
@@ -209,14 +227,45 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
         options: ResolverOpts,
         conn_provider: P,
     ) -> Result<Self, ResolveError> {
-        let pool = NameServerPool::from_config_with_provider(&config, &options, conn_provider);
+        let pool =
+            NameServerPool::from_config_with_provider(&config, &options, conn_provider.clone());
         let either;
         let client = RetryDnsHandle::new(pool, options.attempts);
+        #[cfg(feature = "dnssec")]
+        let trust_anchors = match config.trust_anchor_file() {
+            Some(path) if options.validate => Some(TrustAnchors::from_file(path)?),
+            _ => None,
+        };
+        #[cfg(feature = "dnssec")]
+        let validator_trust_anchor = trust_anchors
+            .as_ref()
+            .map(|anchors| Arc::new(std::sync::RwLock::new(anchors.trust_anchor())));
         if options.validate {
             #[cfg(feature = "dnssec")]
             {
                 use proto::xfer::DnssecDnsHandle;
-                either = LookupEither::Secure(DnssecDnsHandle::new(client));
+                let secure = match validator_trust_anchor {
+                    Some(ref trust_anchor) => DnssecDnsHandle::with_shared_trust_anchor(
+                        client.clone(),
+                        Arc::clone(trust_anchor),
+                    ),
+                    None => DnssecDnsHandle::new(client.clone()),
+                };
+                let secure = if options.chain_query {
+                    secure.with_chain_query(Name::root())
+                } else {
+                    secure
+                };
+
+                either = if config.dnssec_policies().is_empty() {
+                    LookupEither::Secure(secure)
+                } else {
+                    LookupEither::SecureWithPolicies {
+                        secure,
+                        insecure: client,
+                        policies: Arc::new(config.dnssec_policies().clone()),
+                    }
+                };
             }
 
             #[cfg(not(feature = "dnssec"))]
@@ -237,11 +286,19 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
 
         trace!("handle passed back");
         let lru = DnsLru::new(options.cache_size, dns_lru::TtlConfig::from_opts(&options));
+        let blocklist = Arc::new(RwLock::new(None));
         Ok(Self {
             config,
             options,
-            client_cache: CachingClient::with_cache(lru, either, options.preserve_intermediates),
+            client_cache: CachingClient::with_cache(lru, either, options.preserve_intermediates)
+                .with_blocklist(blocklist.clone()),
             hosts,
+            conn_provider,
+            #[cfg(feature = "dnssec")]
+            trust_anchors: trust_anchors.map(|anchors| Arc::new(Mutex::new(anchors))),
+            #[cfg(feature = "dnssec")]
+            validator_trust_anchor,
+            blocklist,
         })
     }
 
@@ -259,15 +316,302 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
         Self::new_with_conn(config, options, conn_provider)
     }
 
+    /// Flushes/Removes all entries from the cache
+    pub fn clear_cache(&self) {
+        self.client_cache.clear_cache();
+    }
+
+    /// Filters all lookups by the blocklist, see [`crate::blocklist`], `None` disables filtering
+    ///
+    /// The blocklist is shared by all clones of this resolver, and replaced immediately. It is
+    ///  checked before the cache, so cached answers for newly blocked names are not returned.
+    pub fn set_blocklist(&self, blocklist: Option<Blocklist>) {
+        *self.blocklist.write() = blocklist;
+    }
+
+    /// Reads the files of the blocklist again, e.g. after they were updated, see [`Blocklist::reload`]
+    ///
+    /// The current blocklist remains in use if any of the files can not be read.
+    pub fn reload_blocklist(&self) -> Result<(), ResolveError> {
+        let reloaded = match *self.blocklist.read() {
+            Some(ref blocklist) => blocklist.reload()?,
+            None => return Ok(()),
+        };
+
+        self.set_blocklist(Some(reloaded));
+        Ok(())
+    }
+
+    /// Upgrades the unencrypted name servers to their designated encrypted resolvers, as
+    ///  specified by `ResolverOpts::encryption_policy`.
+    ///
+    /// This performs Discovery of Designated Resolvers (DDR), see [`crate::ddr`], by querying each
+    ///  unencrypted name server for `_dns.resolver.arpa. SVCB`. A new resolver with the upgraded
+    ///  configuration is returned, the cache of this resolver is not carried over.
+    ///
+    /// With [`EncryptionPolicy::RequireEncrypted`] an error is returned if no encrypted name
+    ///  servers remain after discovery.
+    pub async fn with_designated_resolvers(self) -> Result<Self, ResolveError> {
+        let policy = self.options.encryption_policy;
+        if policy == EncryptionPolicy::AsConfigured {
+            return Ok(self);
+        }
+
+        let mut name_servers =
+            NameServerConfigGroup::with_capacity(self.config.name_servers().len());
+        let mut discovered = HashMap::<IpAddr, bool>::new();
+
+        for ns_config in self.config.name_servers() {
+            if ns_config.protocol.is_encrypted() {
+                name_servers.push(ns_config.clone());
+                continue;
+            }
+
+            let ip = ns_config.socket_addr.ip();
+            let upgraded = match discovered.get(&ip) {
+                Some(upgraded) => *upgraded,
+                None => {
+                    let designated = self.discover_designated_resolvers(ns_config).await;
+                    let upgraded = !designated.is_empty();
+
+                    debug!(
+                        "discovered {} designated resolvers for {}",
+                        designated.len(),
+                        ns_config
+                    );
+                    name_servers.extend(designated);
+                    discovered.insert(ip, upgraded);
+                    upgraded
+                }
+            };
+
+            if !upgraded && policy == EncryptionPolicy::PreferEncrypted {
+                name_servers.push(ns_config.clone());
+            }
+        }
+
+        if name_servers.is_empty() {
+            return Err(
+                ResolveErrorKind::Message("no encrypted name servers were discovered").into(),
+            );
+        }
+
+        #[cfg(feature = "dns-over-rustls")]
+        {
+            if let Some(crate::config::TlsClientConfig(client_config)) = self.config.client_config()
+            {
+                name_servers = name_servers.with_client_config(client_config.clone());
+            }
+        }
+
+        let mut config = ResolverConfig::from_parts(
+            self.config.domain().cloned(),
+            self.config.search().to_vec(),
+            name_servers,
+        );
+        for rule in self.config.forward_rules() {
+            config.add_forward_rule(rule.clone());
+        }
+        #[cfg(feature = "dnssec")]
+        {
+            if let Some(path) = self.config.trust_anchor_file() {
+                config.set_trust_anchor_file(path);
+            }
+            for (domain, policy) in self.config.dnssec_policies().iter() {
+                config.set_dnssec_policy(domain.clone(), *policy);
+            }
+        }
+
+        let mut resolver = Self::new_with_conn(config, self.options, self.conn_provider)?;
+        resolver.hosts = self.hosts;
+        resolver.client_cache = resolver.client_cache.with_blocklist(self.blocklist.clone());
+        resolver.blocklist = self.blocklist;
+        Ok(resolver)
+    }
+
+    /// Refreshes the managed DNSSEC trust anchors, as specified in RFC 5011
+    ///
+    /// The validated DNSKEY RRset of each zone with trust anchors, generally only the root, is
+    ///  looked up. The state of the anchors is updated, and persisted to the
+    ///  [`ResolverConfig::trust_anchor_file`]. This should be called periodically, e.g. daily, RFC
+    ///  5011 requires at least one refresh every 15 days.
+    ///
+    /// Returns true if the set of trusted keys changed, this resolver then validates with the new
+    ///  keys right away.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub async fn refresh_trust_anchors(&self) -> Result<bool, ResolveError> {
+        use proto::rr::dnssec::rdata::DNSSECRData;
+        use std::time::SystemTime;
+
+        let (trust_anchors, path) = match (&self.trust_anchors, self.config.trust_anchor_file()) {
+            (Some(trust_anchors), Some(path)) => (trust_anchors, path),
+            _ => {
+                return Err(ResolveErrorKind::Message(
+                    "trust anchors are only managed with validate and a trust anchor file",
+                )
+                .into())
+            }
+        };
+
+        let mut zones = trust_anchors
+            .lock()
+            .keys()
+            .iter()
+            .map(|key| key.zone().clone())
+            .collect::<Vec<_>>();
+        zones.sort();
+        zones.dedup();
+
+        let mut changed = false;
+        for zone in zones {
+            let lookup = self.lookup(zone.clone(), RecordType::DNSKEY).await?;
+            let dnskeys = lookup
+                .record_iter()
+                .filter_map(Record::data)
+                .filter_map(RData::as_dnssec)
+                .filter_map(DNSSECRData::as_dnskey)
+                .cloned()
+                .collect::<Vec<_>>();
+            let rrsigs = self.dnskey_rrsigs(&zone).await?;
+
+            changed |= trust_anchors
+                .lock()
+                .update(&zone, &dnskeys, &rrsigs, SystemTime::now());
+        }
+
+        let trust_anchors = trust_anchors.lock();
+        if changed {
+            if let Some(ref validator_trust_anchor) = self.validator_trust_anchor {
+                *validator_trust_anchor
+                    .write()
+                    .expect("trust anchor poisoned") = trust_anchors.trust_anchor();
+            }
+        }
+
+        trust_anchors.save(path)?;
+        Ok(changed)
+    }
+
+    /// Queries the RRSIGs of the DNSKEY RRset of the zone
+    ///
+    /// Validated lookups only return the DNSKEYs, the RRSIGs are needed to check that a revoked
+    ///  key signed the RRset, which is verified with the key itself.
+    #[cfg(feature = "dnssec")]
+    async fn dnskey_rrsigs(
+        &self,
+        zone: &Name,
+    ) -> Result<Vec<proto::rr::dnssec::rdata::SIG>, ResolveError> {
+        use proto::op::{Edns, MessageBuilder};
+        use proto::rr::dnssec::rdata::DNSSECRData;
+        use proto::xfer::DnsRequest;
+
+        let mut pool = NameServerPool::from_config_with_provider(
+            &self.config,
+            &self.options,
+            self.conn_provider.clone(),
+        );
+
+        let mut edns = Edns::new();
+        edns.set_max_payload(1232).set_dnssec_ok(true);
+        let message = MessageBuilder::query(Query::query(zone.clone(), RecordType::DNSKEY))
+            .random_id()
+            .recursion_desired(self.options.recursion_desired)
+            .edns(edns)
+            .build()?;
+
+        let response = pool
+            .send(DnsRequest::new(message, self.request_options()))
+            .first_answer()
+            .await?;
+
+        Ok(response
+            .answers()
+            .iter()
+            .filter(|record| record.name() == zone)
+            .filter_map(Record::data)
+            .filter_map(RData::as_dnssec)
+            .filter_map(DNSSECRData::as_sig)
+            .filter(|sig| sig.type_covered() == RecordType::DNSKEY)
+            .cloned()
+            .collect())
+    }
+
+    /// Queries the unencrypted name server for its designated resolvers
+    async fn discover_designated_resolvers(
+        &self,
+        unencrypted: &NameServerConfig,
+    ) -> Vec<NameServerConfig> {
+        let mut pool = NameServerPool::from_config(
+            vec![unencrypted.clone()].into(),
+            &self.options,
+            self.conn_provider.clone(),
+        );
+
+        let query = Query::query(ddr::RESOLVER_ARPA.clone(), RecordType::SVCB);
+        match pool
+            .lookup(query, self.request_options())
+            .first_answer()
+            .await
+        {
+            Ok(response) => {
+                let records = response
+                    .answers()
+                    .iter()
+                    .filter_map(Record::data)
+                    .filter_map(RData::as_svcb);
+
+                ddr::designated_name_servers(unencrypted, records)
+            }
+            Err(e) => {
+                debug!(
+                    "failed to discover designated resolvers for {}: {}",
+                    unencrypted, e
+                );
+                vec![]
+            }
+        }
+    }
+
     /// Per request options based on the ResolverOpts
     pub(crate) fn request_options(&self) -> DnsRequestOptions {
         let mut request_opts = DnsRequestOptions::default();
         request_opts.recursion_desired = self.options.recursion_desired;
         request_opts.use_edns = self.options.edns0;
+        request_opts.case_randomization = self.options.case_randomization;
+
+        request_opts
+    }
+
+    /// Per request options based on the ResolverOpts, with the overrides from the LookupOptions
+    fn request_options_for(&self, lookup_options: &LookupOptions) -> DnsRequestOptions {
+        let mut request_opts = self.request_options();
+        request_opts.timeout = lookup_options.request_timeout;
+        request_opts.client_subnet = lookup_options.client_subnet;
 
         request_opts
     }
 
+    /// Runs the lookup to completion, or until the deadline of the LookupOptions has passed
+    ///
+    /// When the deadline passes the lookup future is dropped, which cancels any outstanding requests.
+    async fn with_deadline<F, T>(
+        lookup_options: &LookupOptions,
+        lookup: F,
+    ) -> Result<T, ResolveError>
+    where
+        F: Future<Output = Result<T, ResolveError>> + Send + 'static,
+    {
+        match lookup_options.remaining() {
+            // the deadline already passed, don't start any requests
+            Some(remaining) if remaining.is_zero() => Err(ResolveErrorKind::Timeout.into()),
+            Some(remaining) => P::Time::timeout(remaining, lookup)
+                .await
+                .unwrap_or_else(|_| Err(ResolveErrorKind::Timeout.into())),
+            None => lookup.await,
+        }
+    }
+
     /// Generic lookup for any RecordType
     ///
     /// *WARNING* this interface may change in the future, see if one of the specializations would be better.
@@ -294,6 +638,37 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
             .await
     }
 
+    /// Generic lookup for any RecordType, with options specific to this lookup
+    ///
+    /// See [`Self::lookup`], the `lookup_options` allow for a deadline, request timeout and client subnet per lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
+    /// * `record_type` - type of record to lookup, all RecordData responses will be filtered to this type
+    /// * `lookup_options` - options overriding the `ResolverOpts` for this lookup
+    pub async fn lookup_with_options<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+        lookup_options: LookupOptions,
+    ) -> Result<Lookup, ResolveError> {
+        let name = match name.into_name() {
+            Ok(name) => name,
+            Err(err) => return Err(err.into()),
+        };
+
+        let names = self.build_names(name);
+        let lookup = LookupFuture::lookup(
+            names,
+            record_type,
+            self.request_options_for(&lookup_options),
+            self.client_cache.clone(),
+        );
+
+        Self::with_deadline(&lookup_options, lookup).await
+    }
+
     fn push_name(name: Name, names: &mut Vec<Name>) {
         if !names.contains(&name) {
             names.push(name);
@@ -387,6 +762,22 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
     pub async fn lookup_ip<N: IntoName + TryParseIp>(
         &self,
         host: N,
+    ) -> Result<LookupIp, ResolveError> {
+        self.lookup_ip_with_options(host, LookupOptions::default())
+            .await
+    }
+
+    /// Performs a dual-stack DNS lookup for the IP for the given hostname, with options specific to this lookup
+    ///
+    /// See [`Self::lookup_ip`], the `lookup_options` allow for a deadline, request timeout, IP strategy and client subnet per lookup.
+    ///
+    /// # Arguments
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    /// * `lookup_options` - options overriding the `ResolverOpts` for this lookup
+    pub async fn lookup_ip_with_options<N: IntoName + TryParseIp>(
+        &self,
+        host: N,
+        lookup_options: LookupOptions,
     ) -> Result<LookupIp, ResolveError> {
         let mut finally_ip_addr: Option<Record> = None;
         let maybe_ip = host.try_parse_ip();
@@ -426,17 +817,165 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> AsyncR
         let names = self.build_names(name);
         let hosts = self.hosts.as_ref().cloned();
 
-        LookupIpFuture::lookup(
+        let conn_provider = self.conn_provider.clone();
+        let lookup = LookupIpFuture::lookup(
             names,
-            self.options.ip_strategy,
+            lookup_options
+                .ip_strategy
+                .unwrap_or(self.options.ip_strategy),
             self.client_cache.clone(),
-            self.request_options(),
+            self.request_options_for(&lookup_options),
             hosts,
             finally_ip_addr.and_then(Record::into_data),
         )
-        .await
+        .with_spawner(Arc::new(move |future| conn_provider.spawn_bg(future)));
+
+        let lookup = Self::with_deadline(&lookup_options, lookup).await?;
+        if self.options.sort_addresses && lookup.iter().nth(1).is_some() {
+            // the source addresses are queried from the OS with blocking system calls
+            self.conn_provider
+                .spawn_blocking(move || lookup.sort_by_destination())
+                .await
+        } else {
+            Ok(lookup)
+        }
+    }
+
+    /// Looks up the socket addresses of a service on the host, like `getaddrinfo`
+    ///
+    /// The `service` is a port number, or the name of a service in the services file of the
+    ///  system, e.g. `/etc/services`. IP addresses are returned without performing any lookup. With
+    ///  [`ServiceHints::use_srv`], the SRV records of named services are used if they exist, in the
+    ///  order of their priority and weight.
+    ///
+    /// The addresses of each host are ordered by RFC 6724 destination address selection,
+    ///  regardless of [`ResolverOpts::sort_addresses`].
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname or IP address, if this is an invalid hostname, an error will be returned.
+    /// * `service` - the port number or name of the service
+    /// * `hints` - the type of socket, and restrictions on the lookup
+    pub async fn lookup_service<N: IntoName + TryParseIp>(
+        &self,
+        host: N,
+        service: &str,
+        hints: ServiceHints,
+    ) -> Result<Vec<SocketAddr>, ResolveError> {
+        let numeric_port = service.parse::<u16>().ok();
+        if numeric_port.is_none() && hints.numeric_service {
+            return Err(ResolveErrorKind::Msg(format!(
+                "service is not a port number: {}",
+                service
+            ))
+            .into());
+        }
+        let port = numeric_port.or_else(|| SYSTEM_SERVICES.port(service, hints.socket_type));
+
+        if let Some(ip) = host.try_parse_ip().as_ref().and_then(RData::to_ip_addr) {
+            let port = port.ok_or_else(|| unknown_service(service, hints.socket_type))?;
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        } else if hints.numeric_host {
+            return Err(ResolveErrorKind::Message("host is not an IP address").into());
+        }
+
+        let name = host.into_name()?;
+        if hints.use_srv && numeric_port.is_none() {
+            if let Some(addrs) = self
+                .lookup_srv_addrs(&name, service, hints.socket_type)
+                .await?
+            {
+                return Ok(addrs);
+            }
+        }
+
+        let port = port.ok_or_else(|| unknown_service(service, hints.socket_type))?;
+        self.lookup_socket_addrs(name, port).await
+    }
+
+    /// The addresses of the host with the port, ordered by RFC 6724 destination address selection
+    async fn lookup_socket_addrs(
+        &self,
+        host: Name,
+        port: u16,
+    ) -> Result<Vec<SocketAddr>, ResolveError> {
+        let addrs = self
+            .lookup_ip(host)
+            .await?
+            .iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect::<Vec<_>>();
+
+        // lookup_ip already ordered them with `sort_addresses`
+        if self.options.sort_addresses || addrs.len() < 2 {
+            return Ok(addrs);
+        }
+
+        self.conn_provider
+            .spawn_blocking(move || {
+                let mut addrs = addrs;
+                address_selection::sort_by_destination(&mut addrs, SocketAddr::ip);
+                addrs
+            })
+            .await
+    }
+
+    /// Resolves the targets of the SRV records of the service, `None` if there are no records
+    async fn lookup_srv_addrs(
+        &self,
+        name: &Name,
+        service: &str,
+        socket_type: SocketType,
+    ) -> Result<Option<Vec<SocketAddr>>, ResolveError> {
+        let srv_name = Name::from_ascii(format!("_{}._{}", service, socket_type.protocol()))?
+            .append_domain(name)?;
+
+        let mut srvs = match self.srv_lookup(srv_name).await {
+            Ok(lookup) => lookup.iter().cloned().collect::<Vec<_>>(),
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        // RFC 2782, a target of "." means the service is decidedly not available
+        if srvs.iter().all(|srv| srv.target().is_root()) {
+            return Err(ResolveErrorKind::Msg(format!(
+                "service is not available: {} on {}",
+                service, name
+            ))
+            .into());
+        }
+
+        // weighted selection is left to the caller, the heaviest targets are tried first
+        srvs.sort_by(|a, b| {
+            a.priority()
+                .cmp(&b.priority())
+                .then(b.weight().cmp(&a.weight()))
+        });
+
+        let mut addrs = Vec::new();
+        let mut last_err = None;
+        for srv in srvs.iter().filter(|srv| !srv.target().is_root()) {
+            match self
+                .lookup_socket_addrs(srv.target().clone(), srv.port())
+                .await
+            {
+                Ok(target_addrs) => addrs.extend(target_addrs),
+                Err(e) => {
+                    debug!("failed to lookup SRV target {}: {}", srv.target(), e);
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        match last_err {
+            Some(e) if addrs.is_empty() => Err(e),
+            _ => Ok(Some(addrs)),
+        }
     }
 
+    /// Customizes the static hosts used in this resolver.
     /// Customizes the static hosts used in this resolver.
     pub fn set_hosts(&mut self, hosts: Option<Hosts>) {
         self.hosts = hosts.map(Arc::new);
@@ -468,6 +1007,15 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> fmt::D
     }
 }
 
+fn unknown_service(service: &str, socket_type: SocketType) -> ResolveError {
+    ResolveErrorKind::Msg(format!(
+        "unknown service: {}/{}",
+        service,
+        socket_type.protocol()
+    ))
+    .into()
+}
+
 /// Unit tests compatible with different runtime.
 #[cfg(any(test, feature = "testing"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
@@ -1107,7 +1655,7 @@ mod tests {
     use proto::xfer::DnsRequest;
     use tokio::runtime::Runtime;
 
-    use crate::config::{ResolverConfig, ResolverOpts};
+    use crate::config::{LookupOptions, ResolverConfig, ResolverOpts};
     use crate::name_server::{TokioConnection, TokioConnectionProvider, TokioRuntime};
 
     use super::*;
@@ -1340,4 +1888,128 @@ mod tests {
             assert_eq!(resolver.build_names(name.clone()).len(), 2);
         }
     }
+
+    #[test]
+    fn test_lookup_deadline_elapsed() {
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let resolver =
+            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+                .expect("failed to create resolver");
+
+        let lookup_options = LookupOptions::with_deadline(std::time::Instant::now());
+        let error = io_loop
+            .block_on(resolver.lookup_with_options(
+                "www.example.com.",
+                RecordType::A,
+                lookup_options,
+            ))
+            .expect_err("lookup should have timed out");
+
+        assert!(matches!(error.kind(), ResolveErrorKind::Timeout));
+    }
+
+    #[test]
+    fn test_lookup_service_numeric() {
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        let resolver =
+            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+                .expect("failed to create resolver");
+
+        let addrs = io_loop
+            .block_on(resolver.lookup_service("2001:db8::1", "8443", ServiceHints::default()))
+            .expect("numeric lookup failed");
+        assert_eq!(addrs, vec!["[2001:db8::1]:8443".parse().unwrap()]);
+
+        let hints = ServiceHints {
+            numeric_host: true,
+            ..ServiceHints::default()
+        };
+        assert!(io_loop
+            .block_on(resolver.lookup_service("www.example.com.", "443", hints))
+            .is_err());
+
+        let hints = ServiceHints {
+            numeric_service: true,
+            ..ServiceHints::default()
+        };
+        assert!(io_loop
+            .block_on(resolver.lookup_service("192.0.2.1", "https", hints))
+            .is_err());
+        assert!(io_loop
+            .block_on(resolver.lookup_service(
+                "192.0.2.1",
+                "no-such-service",
+                ServiceHints::default()
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn test_lookup_service_sorted() {
+        use crate::Hosts;
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        // the addresses of lookup_service are sorted with or without `sort_addresses`
+        for sort_addresses in &[true, false] {
+            let mut options = ResolverOpts::default();
+            options.sort_addresses = *sort_addresses;
+            let mut resolver = TokioAsyncResolver::tokio(ResolverConfig::new(), options)
+                .expect("failed to create resolver");
+            let hosts = Hosts::default()
+                .read_hosts_conf(
+                    "192.0.2.1 sorted.example.\n127.0.0.1 sorted.example.\n".as_bytes(),
+                )
+                .unwrap();
+            resolver.set_hosts(Some(hosts));
+
+            // the loopback address is preferred for its smaller scope, or as the only usable one
+            let addrs = io_loop
+                .block_on(resolver.lookup_service(
+                    "sorted.example.",
+                    "443",
+                    ServiceHints::default(),
+                ))
+                .expect("lookup failed");
+            assert_eq!(
+                addrs,
+                vec![
+                    "127.0.0.1:443".parse().unwrap(),
+                    "192.0.2.1:443".parse().unwrap()
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_blocklist() {
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        use crate::blocklist::{BlockAction, Blocklist, BlocklistFormat};
+
+        let io_loop = Runtime::new().expect("failed to create tokio runtime");
+        // no name servers, blocked names must be answered locally
+        let resolver = TokioAsyncResolver::tokio(ResolverConfig::new(), ResolverOpts::default())
+            .expect("failed to create resolver");
+
+        let blocklist = Blocklist::new(BlockAction::Sinkhole {
+            ipv4: Ipv4Addr::UNSPECIFIED,
+            ipv6: Ipv6Addr::UNSPECIFIED,
+        })
+        .read(BlocklistFormat::Domains, "ads.example.com\n".as_bytes())
+        .unwrap();
+        resolver.set_blocklist(Some(blocklist));
+
+        let lookup = io_loop
+            .block_on(resolver.ipv4_lookup("tracker.ads.example.com."))
+            .expect("blocked name should be sinkholed");
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![Ipv4Addr::UNSPECIFIED]
+        );
+
+        resolver.set_blocklist(None);
+        assert!(io_loop
+            .block_on(resolver.ipv4_lookup("tracker.ads.example.com."))
+            .is_err());
+    }
 }
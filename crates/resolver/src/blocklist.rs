@@ -0,0 +1,583 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Filtering of lookups by a blocklist, similar to DNS Response Policy Zones (RPZ)
+//!
+//! A [`Blocklist`] is loaded from hosts files, domain lists or RPZ zone files, and set on the
+//!  resolver with [`crate::AsyncResolver::set_blocklist`]. Lookups of blocked names are answered
+//!  locally, with NXDOMAIN or sinkhole addresses, and never sent to the name servers.
+//!
+//! ```rust
+//! use trust_dns_resolver::blocklist::{BlockAction, Blocklist, BlocklistFormat};
+//!
+//! let list = "# ads\nads.example.com\ntracker.example.net\n";
+//! let blocklist = Blocklist::new(BlockAction::NxDomain)
+//!     .read(BlocklistFormat::Domains, list.as_bytes())
+//!     .unwrap();
+//! assert_eq!(blocklist.len(), 2);
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use proto::op::{Query, ResponseCode};
+use proto::rr::domain::TryParseIp;
+use proto::rr::{DNSClass, Name, RData, Record};
+use tracing::warn;
+
+use crate::dns_lru::MAX_TTL;
+use crate::error::ResolveError;
+use crate::lookup::Lookup;
+
+/// The format of a blocklist file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlocklistFormat {
+    /// A hosts file, `0.0.0.0 ads.example.com`, the names are blocked and the addresses ignored
+    Hosts,
+    /// A list of domains, one per line, each blocks the domain and all names below it
+    Domains,
+    /// An RPZ zone file, see [`Blocklist::read`] for the supported triggers and actions
+    Rpz,
+}
+
+/// The answer to lookups of names in the blocklist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockAction {
+    /// Answer with NXDOMAIN, the name does not exist
+    NxDomain,
+    /// Answer A and AAAA lookups with the addresses, e.g. of a local web server, other record
+    ///  types with NODATA
+    Sinkhole {
+        /// The address for A lookups
+        ipv4: Ipv4Addr,
+        /// The address for AAAA lookups
+        ipv6: Ipv6Addr,
+    },
+}
+
+impl Default for BlockAction {
+    /// Returns [`BlockAction::NxDomain`] as the default.
+    fn default() -> Self {
+        Self::NxDomain
+    }
+}
+
+/// The policy for a name in the blocklist
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Policy {
+    /// Answer with the `BlockAction` of the blocklist
+    Block,
+    /// RPZ `CNAME .`, and `CNAME rpz-drop.` as responses are never dropped
+    NxDomain,
+    /// RPZ `CNAME *.`
+    NoData,
+    /// RPZ `CNAME rpz-passthru.`, the name is exempt from the blocklist
+    PassThru,
+    /// RPZ local data, the records are the answer
+    LocalData(Vec<RData>),
+}
+
+/// A set of blocked names, and the answer for their lookups
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    action: BlockAction,
+    /// policies for exactly the name
+    names: HashMap<Name, Policy>,
+    /// policies for all names below the name
+    subdomains: HashMap<Name, Policy>,
+    /// the files this was read from, for reloading
+    sources: Vec<(PathBuf, BlocklistFormat)>,
+}
+
+impl Blocklist {
+    /// Creates an empty blocklist, blocked names are answered according to the `action`
+    pub fn new(action: BlockAction) -> Self {
+        Self {
+            action,
+            ..Self::default()
+        }
+    }
+
+    /// The answer to lookups of blocked names
+    pub fn action(&self) -> BlockAction {
+        self.action
+    }
+
+    /// The number of names and domains in the blocklist
+    pub fn len(&self) -> usize {
+        let domains = self.subdomains.keys();
+        self.names.len()
+            + domains
+                .filter(|name| !self.names.contains_key(name))
+                .count()
+    }
+
+    /// Returns `true` if the blocklist is empty
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.subdomains.is_empty()
+    }
+
+    /// Blocks the name, and if `include_subdomains` is set all names below it
+    pub fn block(&mut self, name: Name, include_subdomains: bool) {
+        let name = normalize(name);
+        if include_subdomains {
+            self.subdomains.insert(name.clone(), Policy::Block);
+        }
+        self.names.insert(name, Policy::Block);
+    }
+
+    /// Exempts the name from the blocklist, e.g. a name below a blocked domain
+    pub fn allow(&mut self, name: Name) {
+        self.names.insert(normalize(name), Policy::PassThru);
+    }
+
+    /// Parses the blocklist from `src`, adding its names to this blocklist
+    ///
+    /// Lines starting with `#`, or `;` for RPZ, are comments. Unparsable lines are skipped with a
+    ///  warning. RPZ zone files support QNAME triggers, relative to the `$ORIGIN` or the name of
+    ///  the first SOA record, with the actions:
+    ///
+    /// * `CNAME .` - NXDOMAIN
+    /// * `CNAME *.` - NODATA
+    /// * `CNAME rpz-passthru.` - exempt from the blocklist
+    /// * `CNAME rpz-drop.` - NXDOMAIN, the lookup is answered rather than dropped
+    /// * `A` and `AAAA` records - local data
+    ///
+    /// Other actions, e.g. CNAME rewrites, and records spanning lines other than the SOA are not
+    ///  supported.
+    pub fn read(mut self, format: BlocklistFormat, src: impl io::Read) -> io::Result<Self> {
+        match format {
+            BlocklistFormat::Hosts => self.read_hosts(src)?,
+            BlocklistFormat::Domains => self.read_domains(src)?,
+            BlocklistFormat::Rpz => self.read_rpz(src)?,
+        }
+
+        Ok(self)
+    }
+
+    /// Reads the blocklist file, see [`Self::read`], the file is read again on [`Self::reload`]
+    pub fn read_file<P: AsRef<Path>>(self, path: P, format: BlocklistFormat) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut blocklist = self.read(format, File::open(path)?)?;
+        blocklist.sources.push((path.to_owned(), format));
+
+        Ok(blocklist)
+    }
+
+    /// Reads all files of this blocklist again, into a new blocklist with the same action
+    ///
+    /// Names added with [`Self::block`] and [`Self::allow`], or read from other sources, are not
+    ///  carried over.
+    pub fn reload(&self) -> io::Result<Self> {
+        self.sources
+            .iter()
+            .try_fold(Self::new(self.action), |blocklist, (path, format)| {
+                blocklist.read_file(path, *format)
+            })
+    }
+
+    /// The answer to the query if the name is in the blocklist, `None` if it is not blocked
+    pub(crate) fn lookup(&self, query: &Query) -> Option<Result<Lookup, ResolveError>> {
+        if query.query_class() != DNSClass::IN || self.is_empty() {
+            return None;
+        }
+
+        let name = normalize(query.name().clone());
+        let policy = self.names.get(&name).or_else(|| {
+            // the most specific domain takes precedence
+            let mut domain = name.base_name();
+            loop {
+                if let Some(policy) = self.subdomains.get(&domain) {
+                    return Some(policy);
+                }
+                if domain.is_root() {
+                    return None;
+                }
+                domain = domain.base_name();
+            }
+        })?;
+
+        let rdatas = match (policy, self.action) {
+            (Policy::PassThru, _) => return None,
+            (Policy::Block, BlockAction::NxDomain) | (Policy::NxDomain, _) => {
+                return Some(Err(nx_error(query, ResponseCode::NXDomain)))
+            }
+            (Policy::NoData, _) => return Some(Err(nx_error(query, ResponseCode::NoError))),
+            (Policy::Block, BlockAction::Sinkhole { ipv4, ipv6 }) => {
+                vec![RData::A(ipv4), RData::AAAA(ipv6)]
+            }
+            (Policy::LocalData(rdatas), _) => rdatas.clone(),
+        };
+
+        let records = rdatas
+            .into_iter()
+            .filter(|rdata| {
+                query.query_type().is_any() || rdata.to_record_type() == query.query_type()
+            })
+            .map(|rdata| Record::from_rdata(query.name().clone(), MAX_TTL, rdata))
+            .collect::<Vec<_>>();
+
+        if records.is_empty() {
+            Some(Err(nx_error(query, ResponseCode::NoError)))
+        } else {
+            Some(Ok(Lookup::new_with_max_ttl(
+                query.clone(),
+                Arc::from(records),
+            )))
+        }
+    }
+
+    fn read_hosts(&mut self, src: impl io::Read) -> io::Result<()> {
+        for line in BufReader::new(src).lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // lines have the form `addr host1 host2 ...`, the address is ignored
+            let mut fields = line.split_whitespace();
+            if fields.next().and_then(|addr| addr.try_parse_ip()).is_none() {
+                warn!("could not parse an IP from blocklist: {}", line);
+                continue;
+            }
+
+            for host in fields {
+                match Name::from_str(host) {
+                    Ok(name) => self.block(name, false),
+                    Err(e) => warn!("could not parse a name from blocklist: {}: {}", host, e),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_domains(&mut self, src: impl io::Read) -> io::Result<()> {
+        for line in BufReader::new(src).lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match Name::from_str(line) {
+                Ok(name) => self.block(name, true),
+                Err(e) => warn!("could not parse a name from blocklist: {}: {}", line, e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_rpz(&mut self, src: impl io::Read) -> io::Result<()> {
+        let mut origin: Option<Name> = None;
+        let mut owner: Option<Name> = None;
+        let mut in_parens = false;
+
+        for line in BufReader::new(src).lines() {
+            let line = line?;
+            let line = line.split(';').next().unwrap();
+
+            // multi-line records, i.e. the SOA, are skipped
+            if in_parens {
+                in_parens = !line.contains(')');
+                continue;
+            }
+            in_parens = line.contains('(') && !line.contains(')');
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace().peekable();
+            if line.starts_with('$') {
+                match (fields.next(), fields.next()) {
+                    (Some("$ORIGIN"), Some(name)) => origin = Name::from_str(name).ok(),
+                    (Some("$TTL"), _) => (),
+                    _ => warn!("unsupported directive in RPZ: {}", line),
+                }
+                continue;
+            }
+
+            // a line starting with whitespace continues the previous owner
+            if !line.starts_with(char::is_whitespace) {
+                owner = fields.next().and_then(|name| Name::from_str(name).ok());
+            }
+            let owner = match owner {
+                Some(ref owner) => owner.clone(),
+                None => {
+                    warn!("could not parse an owner name from RPZ: {}", line);
+                    continue;
+                }
+            };
+
+            // skip the optional TTL and class
+            while let Some(field) = fields.peek() {
+                if field.parse::<u32>().is_ok() || field.eq_ignore_ascii_case("IN") {
+                    fields.next();
+                } else {
+                    break;
+                }
+            }
+
+            let (record_type, rdata) = match (fields.next(), fields.next()) {
+                (Some(record_type), Some(rdata)) => (record_type.to_uppercase(), rdata),
+                _ => {
+                    warn!("could not parse a record from RPZ: {}", line);
+                    continue;
+                }
+            };
+
+            // the SOA names the policy zone, the NS records are only required by the zone format
+            if record_type == "SOA" {
+                if origin.is_none() && owner.is_fqdn() {
+                    origin = Some(owner);
+                }
+                continue;
+            }
+            if record_type == "NS" {
+                continue;
+            }
+
+            let (trigger, subdomains) = match trigger(&owner, origin.as_ref()) {
+                Some(trigger) => trigger,
+                None => {
+                    warn!("RPZ trigger is outside of the policy zone: {}", owner);
+                    continue;
+                }
+            };
+
+            let policy = match (record_type.as_str(), rdata) {
+                ("CNAME", ".") | ("CNAME", "rpz-drop.") => Policy::NxDomain,
+                ("CNAME", "*.") => Policy::NoData,
+                ("CNAME", "rpz-passthru.") => Policy::PassThru,
+                ("A", addr) => match addr.parse::<Ipv4Addr>() {
+                    Ok(addr) => Policy::LocalData(vec![RData::A(addr)]),
+                    Err(e) => {
+                        warn!("could not parse an IP from RPZ: {}: {}", addr, e);
+                        continue;
+                    }
+                },
+                ("AAAA", addr) => match addr.parse::<Ipv6Addr>() {
+                    Ok(addr) => Policy::LocalData(vec![RData::AAAA(addr)]),
+                    Err(e) => {
+                        warn!("could not parse an IP from RPZ: {}: {}", addr, e);
+                        continue;
+                    }
+                },
+                _ => {
+                    warn!("unsupported RPZ action: {}", line.trim());
+                    continue;
+                }
+            };
+
+            let policies = if subdomains {
+                &mut self.subdomains
+            } else {
+                &mut self.names
+            };
+
+            // local data for several records, e.g. A and AAAA
+            if let (Some(Policy::LocalData(rdatas)), Policy::LocalData(more)) =
+                (policies.get_mut(&trigger), &policy)
+            {
+                rdatas.extend(more.iter().cloned());
+                continue;
+            }
+            policies.insert(trigger, policy);
+        }
+
+        Ok(())
+    }
+}
+
+/// The name blocked by the RPZ owner name, and whether it is a wildcard for the names below it
+fn trigger(owner: &Name, origin: Option<&Name>) -> Option<(Name, bool)> {
+    let name = match origin {
+        Some(origin) if owner.is_fqdn() => {
+            if !origin.zone_of(owner) || owner.num_labels() == origin.num_labels() {
+                return None;
+            }
+            let labels = usize::from(owner.num_labels() - origin.num_labels());
+            Name::from_labels(owner.iter().take(labels)).ok()?
+        }
+        // relative names are triggers for the name itself
+        _ => owner.clone(),
+    };
+
+    if name.is_wildcard() {
+        Some((normalize(name.base_name()), true))
+    } else {
+        Some((normalize(name), false))
+    }
+}
+
+/// Names are stored and compared as lowercase FQDNs
+fn normalize(name: Name) -> Name {
+    let mut name = name.to_lowercase();
+    name.set_fqdn(true);
+    name
+}
+
+fn nx_error(query: &Query, response_code: ResponseCode) -> ResolveError {
+    ResolveError::nx_error(query.clone(), None, None, response_code, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ResolveErrorKind;
+    use proto::rr::RecordType;
+
+    fn lookup(blocklist: &Blocklist, name: &str, record_type: RecordType) -> Vec<RData> {
+        let query = Query::query(Name::from_str(name).unwrap(), record_type);
+        blocklist
+            .lookup(&query)
+            .expect("name should be blocked")
+            .expect("blocked name should have records")
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn response_code(blocklist: &Blocklist, name: &str) -> Option<ResponseCode> {
+        let query = Query::query(Name::from_str(name).unwrap(), RecordType::A);
+        match blocklist.lookup(&query)? {
+            Ok(_) => Some(ResponseCode::NoError),
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => Some(*response_code),
+                _ => panic!("unexpected error: {}", e),
+            },
+        }
+    }
+
+    #[test]
+    fn test_hosts() {
+        let hosts =
+            "# comment\n0.0.0.0 ads.example.com tracker.example.com\n::1 Other.Example.com\n";
+        let blocklist = Blocklist::new(BlockAction::NxDomain)
+            .read(BlocklistFormat::Hosts, hosts.as_bytes())
+            .unwrap();
+
+        assert_eq!(blocklist.len(), 3);
+        assert_eq!(
+            response_code(&blocklist, "ads.example.com."),
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(
+            response_code(&blocklist, "other.example.com"),
+            Some(ResponseCode::NXDomain)
+        );
+        // hosts entries are exact names
+        assert_eq!(response_code(&blocklist, "www.ads.example.com."), None);
+        assert_eq!(response_code(&blocklist, "example.com."), None);
+    }
+
+    #[test]
+    fn test_domains_sinkhole() {
+        let domains = "ads.example.com\n\n# comment\n";
+        let blocklist = Blocklist::new(BlockAction::Sinkhole {
+            ipv4: Ipv4Addr::UNSPECIFIED,
+            ipv6: Ipv6Addr::UNSPECIFIED,
+        })
+        .read(BlocklistFormat::Domains, domains.as_bytes())
+        .unwrap();
+
+        for name in &["ads.example.com.", "www.ads.example.com."] {
+            assert_eq!(
+                lookup(&blocklist, name, RecordType::A),
+                vec![RData::A(Ipv4Addr::UNSPECIFIED)]
+            );
+            assert_eq!(
+                lookup(&blocklist, name, RecordType::AAAA),
+                vec![RData::AAAA(Ipv6Addr::UNSPECIFIED)]
+            );
+        }
+        assert_eq!(
+            response_code(&blocklist, "example.com."),
+            None,
+            "parent names are not blocked"
+        );
+
+        // other record types are NODATA
+        let query = Query::query(Name::from_str("ads.example.com.").unwrap(), RecordType::MX);
+        match blocklist.lookup(&query).unwrap().unwrap_err().kind() {
+            ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                assert_eq!(*response_code, ResponseCode::NoError)
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_rpz() {
+        let rpz = r#"
+$TTL 300
+@ SOA localhost. root.localhost. (
+        1 ; serial
+        3600 900 86400 300 )
+  NS localhost.
+$ORIGIN rpz.local.
+; block the domain and everything below it
+bad.example.com CNAME .
+*.bad.example.com CNAME .
+nodata.example.com 300 IN CNAME *.
+ok.bad.example.com CNAME rpz-passthru.
+local.example.com A 192.0.2.1
+                  AAAA 2001:db8::1
+redirect.example.com CNAME www.example.net.
+"#;
+        let blocklist = Blocklist::new(BlockAction::NxDomain)
+            .read(BlocklistFormat::Rpz, rpz.as_bytes())
+            .unwrap();
+
+        assert_eq!(
+            response_code(&blocklist, "bad.example.com."),
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(
+            response_code(&blocklist, "www.bad.example.com."),
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(response_code(&blocklist, "ok.bad.example.com."), None);
+        assert_eq!(
+            response_code(&blocklist, "nodata.example.com."),
+            Some(ResponseCode::NoError)
+        );
+        assert_eq!(
+            lookup(&blocklist, "local.example.com.", RecordType::A),
+            vec![RData::A(Ipv4Addr::new(192, 0, 2, 1))]
+        );
+        assert_eq!(
+            lookup(&blocklist, "local.example.com.", RecordType::AAAA),
+            vec![RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))]
+        );
+        // rewrites are not supported
+        assert_eq!(response_code(&blocklist, "redirect.example.com."), None);
+    }
+
+    #[test]
+    fn test_block_and_allow() {
+        let mut blocklist = Blocklist::new(BlockAction::NxDomain);
+        assert_eq!(response_code(&blocklist, "www.example.com."), None);
+
+        blocklist.block(Name::from_str("example.com").unwrap(), true);
+        blocklist.allow(Name::from_str("www.example.com").unwrap());
+
+        assert_eq!(
+            response_code(&blocklist, "mail.example.com."),
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(response_code(&blocklist, "www.example.com."), None);
+    }
+}
@@ -16,6 +16,7 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use futures_util::future::Future;
+use parking_lot::RwLock;
 
 use proto::error::ProtoError;
 use proto::op::{Query, ResponseCode};
@@ -23,13 +24,16 @@ use proto::rr::domain::usage::{
     ResolverUsage, DEFAULT, INVALID, IN_ADDR_ARPA_127, IP6_ARPA_1, LOCAL,
     LOCALHOST as LOCALHOST_usage, ONION,
 };
+use proto::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
 use proto::rr::{DNSClass, Name, RData, Record, RecordType};
 use proto::xfer::{DnsHandle, DnsRequestOptions, DnsResponse, FirstAnswer};
 
+use crate::blocklist::Blocklist;
 use crate::dns_lru::DnsLru;
 use crate::dns_lru::{self, TtlConfig};
 use crate::error::*;
 use crate::lookup::Lookup;
+use crate::metrics;
 
 const MAX_QUERY_DEPTH: u8 = 8; // arbitrarily chosen number...
 
@@ -69,6 +73,7 @@ where
     client: C,
     query_depth: Arc<AtomicU8>,
     preserve_intermediates: bool,
+    blocklist: Arc<RwLock<Option<Blocklist>>>,
 }
 
 impl<C, E> CachingClient<C, E>
@@ -92,16 +97,32 @@ where
             client,
             query_depth,
             preserve_intermediates,
+            blocklist: Arc::default(),
         }
     }
 
+    /// Filters the lookups by the blocklist, which may be replaced at any time
+    pub(crate) fn with_blocklist(mut self, blocklist: Arc<RwLock<Option<Blocklist>>>) -> Self {
+        self.blocklist = blocklist;
+        self
+    }
+
     /// Perform a lookup against this caching client, looking first in the cache for a result
     pub fn lookup(
         &mut self,
         query: Query,
         options: DnsRequestOptions,
     ) -> Pin<Box<dyn Future<Output = Result<Lookup, ResolveError>> + Send>> {
-        Box::pin(Self::inner_lookup(query, options, self.clone(), vec![]))
+        metrics::lookup(query.query_type());
+
+        let lookup = Self::inner_lookup(query, options, self.clone(), vec![]);
+        Box::pin(async move {
+            let result = lookup.await;
+            if let Err(e) = &result {
+                metrics::error(e);
+            }
+            result
+        })
     }
 
     async fn inner_lookup(
@@ -167,13 +188,27 @@ where
             }
         }
 
+        // the blocklist applies to each name, including the targets of CNAMEs
+        let blocked = client
+            .blocklist
+            .read()
+            .as_ref()
+            .and_then(|blocklist| blocklist.lookup(&query));
+        if let Some(blocked) = blocked {
+            return blocked;
+        }
+
         let _tracker = DepthTracker::track(client.query_depth.clone());
         let is_dnssec = client.client.is_verifying_dnssec();
 
         // first transition any polling that is needed (mutable refs...)
-        if let Some(cached_lookup) = client.lookup_from_cache(&query) {
+        if let Some(cached_lookup) =
+            client.lookup_from_cache(&query, options.client_subnet.as_ref())
+        {
+            metrics::cache_hit();
             return cached_lookup;
         };
+        metrics::cache_miss();
 
         let response_message = client
             .client
@@ -182,6 +217,16 @@ where
             .await
             .map_err(E::into);
 
+        // RFC 7871, answers to client subnet queries are cached for the network of their scope
+        let scope = options.client_subnet.and_then(|subnet| {
+            let scope_prefix = match response_message {
+                Ok(ref response) => Self::scope_prefix(response),
+                // the scope is unknown, so only the same subnet may use the answer
+                Err(_) => subnet.source_prefix(),
+            };
+            dns_lru::scoped(&subnet, scope_prefix)
+        });
+
         // TODO: technically this might be duplicating work, as name_server already performs this evaluation.
         //  we may want to create a new type, if evaluated... but this is most generic to support any impl in LookupState...
         let response_message = if let Ok(response) = response_message {
@@ -202,6 +247,7 @@ where
                         negative_ttl,
                         response_code,
                         trusted,
+                        authorities,
                     },
                 ..
             }) => {
@@ -213,6 +259,7 @@ where
                     negative_ttl,
                     response_code,
                     trusted,
+                    authorities,
                 ))
             }
             Err(e) => return Err(e),
@@ -236,15 +283,33 @@ where
             Ok(Records::CnameChain {
                 next: future,
                 min_ttl: ttl,
-            }) => client.cname(future.await?, query, ttl),
-            Ok(Records::Exists(rdata)) => client.cache(query, Ok(rdata)),
-            Err(e) => client.cache(query, Err(e)),
+            }) => client.cname(future.await?, query, scope, ttl),
+            Ok(Records::Exists(rdata)) => client.cache(query, scope, Ok(rdata)),
+            Err(e) => client.cache(query, scope, Err(e)),
         }
     }
 
-    /// Check if this query is already cached
-    fn lookup_from_cache(&self, query: &Query) -> Option<Result<Lookup, ResolveError>> {
-        self.lru.get(query, Instant::now())
+    /// Check if this query is already cached, for a client in the subnet
+    fn lookup_from_cache(
+        &self,
+        query: &Query,
+        subnet: Option<&ClientSubnet>,
+    ) -> Option<Result<Lookup, ResolveError>> {
+        self.lru.get_for_subnet(query, subnet, Instant::now())
+    }
+
+    /// The scope prefix length of the client subnet option of the response
+    ///
+    /// A response without the option is valid for all clients, RFC 7871 section 7.3.1.
+    fn scope_prefix(response: &DnsResponse) -> u8 {
+        match response
+            .extensions()
+            .as_ref()
+            .and_then(|edns| edns.option(EdnsCode::Subnet))
+        {
+            Some(EdnsOption::Subnet(subnet)) => subnet.scope_prefix(),
+            _ => 0,
+        }
     }
 
     /// See https://tools.ietf.org/html/rfc2308
@@ -264,6 +329,7 @@ where
     /// * `message` - message to extract SOA, etc, from for caching failed requests
     /// * `valid_nsec` - species that in DNSSec mode, this request is safe to cache
     /// * `negative_ttl` - this should be the SOA minimum for negative ttl
+    #[allow(clippy::too_many_arguments)]
     fn handle_nxdomain(
         is_dnssec: bool,
         valid_nsec: bool,
@@ -272,6 +338,7 @@ where
         negative_ttl: Option<u32>,
         response_code: ResponseCode,
         trusted: bool,
+        authorities: Option<Arc<[Record]>>,
     ) -> ResolveError {
         if valid_nsec || !is_dnssec {
             // only trust if there were validated NSEC records
@@ -281,6 +348,7 @@ where
                 negative_ttl,
                 response_code,
                 trusted: true,
+                authorities,
             }
             .into()
         } else {
@@ -291,6 +359,7 @@ where
                 negative_ttl: None,
                 response_code,
                 trusted,
+                authorities,
             }
             .into()
         }
@@ -430,7 +499,20 @@ where
         // TODO: for SRV records we *could* do an implicit lookup, but, this requires knowing the type of IP desired
         //    for now, we'll make the API require the user to perform a follow up to the lookups.
         // It was a CNAME, but not included in the request...
-        if was_cname && client.query_depth.load(Ordering::Acquire) < MAX_QUERY_DEPTH {
+        if was_cname && negative_ttl.is_some() {
+            // The SOA shows that the upstream already followed the CNAME chain to a negative response,
+            //  NODATA or NXDOMAIN for the target, which can be cached no longer than the chain itself.
+            Err(Self::handle_nxdomain(
+                is_dnssec,
+                true,
+                Query::query(search_name, query.query_type()),
+                soa,
+                negative_ttl.map(|ttl| ttl.min(cname_ttl)),
+                response_code,
+                false,
+                None,
+            ))
+        } else if was_cname && client.query_depth.load(Ordering::Acquire) < MAX_QUERY_DEPTH {
             let next_query = Query::query(search_name, query.query_type());
             Ok(Records::CnameChain {
                 next: Box::pin(Self::inner_lookup(
@@ -453,25 +535,35 @@ where
                 negative_ttl,
                 response_code,
                 false,
+                None,
             ))
         }
     }
 
     #[allow(clippy::unnecessary_wraps)]
-    fn cname(&self, lookup: Lookup, query: Query, cname_ttl: u32) -> Result<Lookup, ResolveError> {
+    fn cname(
+        &self,
+        lookup: Lookup,
+        query: Query,
+        scope: Option<ClientSubnet>,
+        cname_ttl: u32,
+    ) -> Result<Lookup, ResolveError> {
         // this duplicates the cache entry under the original query
-        Ok(self.lru.duplicate(query, lookup, cname_ttl, Instant::now()))
+        Ok(self
+            .lru
+            .duplicate(query, scope, lookup, cname_ttl, Instant::now()))
     }
 
     fn cache(
         &self,
         query: Query,
+        scope: Option<ClientSubnet>,
         records: Result<Vec<(Record, u32)>, ResolveError>,
     ) -> Result<Lookup, ResolveError> {
         // this will put this object into an inconsistent state, but no one should call poll again...
         match records {
-            Ok(rdata) => Ok(self.lru.insert(query, rdata, Instant::now())),
-            Err(err) => Err(self.lru.negative(query, err, Instant::now())),
+            Ok(rdata) => Ok(self.lru.insert(query, scope, rdata, Instant::now())),
+            Err(err) => Err(self.lru.negative(query, scope, err, Instant::now())),
         }
     }
 
@@ -499,8 +591,8 @@ mod tests {
     use std::time::*;
 
     use futures_executor::block_on;
-    use proto::op::{Message, Query};
-    use proto::rr::rdata::SRV;
+    use proto::op::{Edns, Message, Query};
+    use proto::rr::rdata::{SOA, SRV};
     use proto::rr::{Name, Record};
 
     use super::*;
@@ -538,6 +630,7 @@ mod tests {
         let query = Query::new();
         cache.insert(
             query.clone(),
+            None,
             vec![(
                 Record::from_rdata(
                     query.name().clone(),
@@ -604,6 +697,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_client_subnet_cache_scope() {
+        let cache = DnsLru::new(4, dns_lru::TtlConfig::default());
+        let subnet = |s: &str| {
+            let mut options = DnsRequestOptions::default();
+            options.client_subnet = Some(s.parse().unwrap());
+            options
+        };
+
+        // the answer for 192.0.2.0/24 is valid for 192.0.0.0/16
+        let mut response = v4_message().unwrap();
+        response
+            .extensions_mut()
+            .get_or_insert_with(Edns::new)
+            .options_mut()
+            .insert(EdnsOption::Subnet(ClientSubnet::new(
+                Ipv4Addr::new(192, 0, 2, 0).into(),
+                24,
+                16,
+            )));
+        let client = CachingClient::with_cache(cache.clone(), mock(vec![Ok(response)]), false);
+        block_on(CachingClient::inner_lookup(
+            Query::new(),
+            subnet("192.0.2.0/24"),
+            client,
+            vec![],
+        ))
+        .unwrap();
+
+        let client = CachingClient::with_cache(cache.clone(), mock(vec![empty()]), false);
+        assert!(block_on(CachingClient::inner_lookup(
+            Query::new(),
+            subnet("192.0.200.0/24"),
+            client,
+            vec![],
+        ))
+        .is_ok());
+
+        // other networks, and lookups without a subnet, are not answered from the cache
+        for options in [subnet("198.51.100.0/24"), DnsRequestOptions::default()] {
+            let client = CachingClient::with_cache(cache.clone(), mock(vec![empty()]), false);
+            assert!(block_on(CachingClient::inner_lookup(
+                Query::new(),
+                options,
+                client,
+                vec![]
+            ))
+            .is_err());
+        }
+    }
+
     #[allow(clippy::unnecessary_wraps)]
     pub(crate) fn cname_message() -> Result<DnsResponse, ResolveError> {
         let mut message = Message::new();
@@ -908,6 +1052,62 @@ mod tests {
         cname_ttl_test(2, 1);
     }
 
+    #[test]
+    fn test_cname_nodata_negative_ttl() {
+        let lru = DnsLru::new(1, dns_lru::TtlConfig::default());
+        // expecting no queries to be performed
+        let mut client = CachingClient::with_cache(lru, mock(vec![error()]), false);
+
+        let mut message = Message::new();
+        message.insert_answers(vec![Record::from_rdata(
+            Name::from_str("ttl.example.com.").unwrap(),
+            30,
+            RData::CNAME(Name::from_str("actual.example.com.").unwrap()),
+        )]);
+        message.insert_name_servers(vec![Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("hostmaster.example.com.").unwrap(),
+                1,
+                2,
+                3,
+                4,
+                60,
+            )),
+        )]);
+
+        let records = CachingClient::handle_noerror(
+            &mut client,
+            DnsRequestOptions::default(),
+            false,
+            &Query::query(Name::from_str("ttl.example.com.").unwrap(), RecordType::A),
+            message.into(),
+            vec![],
+        );
+
+        match records {
+            Err(ResolveError {
+                kind:
+                    ResolveErrorKind::NoRecordsFound {
+                        query,
+                        negative_ttl,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(
+                    query.name(),
+                    &Name::from_str("actual.example.com.").unwrap()
+                );
+                // the SOA minimum is limited by the TTL of the CNAME
+                assert_eq!(negative_ttl, Some(30));
+            }
+            _ => panic!("expected NoRecordsFound"),
+        }
+    }
+
     #[test]
     fn test_early_return_localhost() {
         let cache = DnsLru::new(0, dns_lru::TtlConfig::default());
@@ -11,15 +11,23 @@
 use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::{Deref, DerefMut};
-use std::time::Duration;
+#[cfg(feature = "dnssec")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "dns-over-https")]
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "dns-over-rustls")]
 use std::sync::Arc;
 
+use proto::rr::rdata::opt::ClientSubnet;
 use proto::rr::Name;
 #[cfg(feature = "dns-over-rustls")]
 use rustls::ClientConfig;
 
+#[cfg(feature = "dns-over-https")]
+use crate::error::ResolveResult;
+
 #[cfg(all(feature = "serde-config", feature = "dns-over-rustls"))]
 use serde::{
     de::{Deserialize as DeserializeT, Deserializer},
@@ -38,6 +46,17 @@ pub struct ResolverConfig {
     search: Vec<Name>,
     // nameservers to use for resolution.
     name_servers: NameServerConfigGroup,
+    // per-domain nameservers, for split-horizon DNS
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    forward_rules: Vec<ForwardRule>,
+    // managed DNSSEC trust anchors, see `crate::trust_anchor`
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    trust_anchor_file: Option<PathBuf>,
+    // per-domain exceptions to DNSSEC validation
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    dnssec_policies: DnssecPolicies,
 }
 
 impl ResolverConfig {
@@ -48,6 +67,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::new(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -62,6 +86,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -76,6 +105,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -92,6 +126,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_tls(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -108,6 +147,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_https(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -122,6 +166,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -138,6 +187,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9_tls(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -154,6 +208,11 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9_https(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -173,6 +232,11 @@ impl ResolverConfig {
             domain,
             search,
             name_servers: name_servers.into(),
+            forward_rules: vec![],
+            #[cfg(feature = "dnssec")]
+            trust_anchor_file: None,
+            #[cfg(feature = "dnssec")]
+            dnssec_policies: DnssecPolicies::default(),
         }
     }
 
@@ -212,6 +276,63 @@ impl ResolverConfig {
         &self.name_servers
     }
 
+    /// Adds a rule to send the queries for the names of a domain to specific name servers
+    ///
+    /// The rule with the most specific domain is used for each query, queries which don't match
+    ///  any rule are sent to the name servers of this configuration.
+    pub fn add_forward_rule(&mut self, rule: ForwardRule) {
+        self.forward_rules.push(rule);
+    }
+
+    /// Returns the per-domain forwarding rules
+    pub fn forward_rules(&self) -> &[ForwardRule] {
+        &self.forward_rules
+    }
+
+    /// Returns the file of the managed DNSSEC trust anchors
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn trust_anchor_file(&self) -> Option<&Path> {
+        self.trust_anchor_file.as_deref()
+    }
+
+    /// Use the trust anchors from the file to validate DNSSEC, instead of the compiled in root keys
+    ///
+    /// See [`crate::trust_anchor::TrustAnchors::parse`] for the supported formats, and
+    ///  [`crate::AsyncResolver::refresh_trust_anchors`] for the automated updates of the file.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn set_trust_anchor_file(&mut self, path: impl Into<PathBuf>) {
+        self.trust_anchor_file = Some(path.into());
+    }
+
+    /// Adds a negative trust anchor, [RFC 7646](https://tools.ietf.org/html/rfc7646)
+    ///
+    /// Responses for the domain, and all names below it, are not validated. This allows the
+    ///  resolution of a domain with broken DNSSEC, without disabling validation for all domains.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn add_negative_trust_anchor(&mut self, domain: Name) {
+        self.set_dnssec_policy(domain, DnssecPolicy::Skip);
+    }
+
+    /// Sets the DNSSEC validation policy of the domain, and all names below it
+    ///
+    /// This only applies when `ResolverOpts::validate` is enabled, the policy of the most specific
+    ///  domain is used for each name.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn set_dnssec_policy(&mut self, domain: Name, policy: DnssecPolicy) {
+        self.dnssec_policies.insert(domain, policy);
+    }
+
+    /// Returns the per-domain DNSSEC validation policies
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn dnssec_policies(&self) -> &DnssecPolicies {
+        &self.dnssec_policies
+    }
+
     /// return the associated TlsClientConfig
     #[cfg(feature = "dns-over-rustls")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
@@ -265,6 +386,120 @@ impl Default for ResolverConfig {
     }
 }
 
+/// A rule to send the queries for the names of a domain to specific name servers, e.g. to resolve
+///  `corp.example.` with internal name servers for split-horizon DNS
+///
+/// ```
+/// use std::net::{IpAddr, Ipv4Addr};
+/// use std::str::FromStr;
+///
+/// use trust_dns_resolver::config::{ForwardRule, NameServerConfigGroup, ResolverConfig};
+/// use trust_dns_resolver::Name;
+///
+/// let mut config = ResolverConfig::cloudflare();
+/// config.add_forward_rule(ForwardRule::new(
+///     Name::from_str("corp.example.").unwrap(),
+///     NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53))], 53, true),
+/// ));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct ForwardRule {
+    domain: Name,
+    name_servers: NameServerConfigGroup,
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    options: Option<ResolverOpts>,
+}
+
+impl ForwardRule {
+    /// Sends the queries for the domain, and all names below it, to the name servers
+    pub fn new(domain: Name, name_servers: NameServerConfigGroup) -> Self {
+        Self {
+            domain,
+            name_servers,
+            options: None,
+        }
+    }
+
+    /// Use different options for the name servers of this rule, e.g. a shorter timeout
+    ///
+    /// Only the options for sending requests apply, e.g. `timeout`, `attempts` or
+    ///  `num_concurrent_reqs`, the options of the resolver are used for everything else.
+    pub fn with_options(mut self, options: ResolverOpts) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// The domain of the rule
+    pub fn domain(&self) -> &Name {
+        &self.domain
+    }
+
+    /// The name servers for the domain
+    pub fn name_servers(&self) -> &NameServerConfigGroup {
+        &self.name_servers
+    }
+
+    /// The options for the name servers of the rule, if they differ from those of the resolver
+    pub fn options(&self) -> Option<&ResolverOpts> {
+        self.options.as_ref()
+    }
+}
+
+/// The DNSSEC validation policy for the names of a domain
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum DnssecPolicy {
+    /// Responses are validated, e.g. to validate a subdomain of a negative trust anchor
+    Validate,
+    /// Responses are not validated, i.e. a negative trust anchor
+    Skip,
+}
+
+/// Per-domain DNSSEC validation policies, see [`ResolverConfig::set_dnssec_policy`]
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub struct DnssecPolicies(Vec<(Name, DnssecPolicy)>);
+
+#[cfg(feature = "dnssec")]
+impl DnssecPolicies {
+    /// Sets the policy of the domain, replacing any existing policy for the same domain
+    pub fn insert(&mut self, domain: Name, policy: DnssecPolicy) {
+        match self.0.iter_mut().find(|(name, _)| *name == domain) {
+            Some(entry) => entry.1 = policy,
+            None => self.0.push((domain, policy)),
+        }
+    }
+
+    /// Returns the policy of the most specific domain containing the name, if any
+    pub fn get(&self, name: &Name) -> Option<DnssecPolicy> {
+        self.0
+            .iter()
+            .filter(|(domain, _)| domain.zone_of(name))
+            .max_by_key(|(domain, _)| domain.num_labels())
+            .map(|(_, policy)| *policy)
+    }
+
+    /// Returns true if the name should be validated, `Validate` is the default for all names
+    pub fn is_validated(&self, name: &Name) -> bool {
+        self.get(name).unwrap_or(DnssecPolicy::Validate) == DnssecPolicy::Validate
+    }
+
+    /// Returns true if there are no policies
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over all domains and their policies
+    pub fn iter(&self) -> impl Iterator<Item = &(Name, DnssecPolicy)> {
+        self.0.iter()
+    }
+}
+
 /// The protocol on which a NameServer should be communicated with
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[cfg_attr(
@@ -417,6 +652,9 @@ pub struct NameServerConfig {
     pub tls_config: Option<TlsClientConfig>,
     /// The client address (IP and port) to use for connecting to the server.
     pub bind_addr: Option<SocketAddr>,
+    /// The path of the DNS-over-HTTPS endpoint, only relevant for HTTPS connections, defaults to `/dns-query`
+    #[cfg_attr(feature = "serde-config", serde(default))]
+    pub http_endpoint: Option<String>,
 }
 
 impl NameServerConfig {
@@ -430,6 +668,7 @@ impl NameServerConfig {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         }
     }
 }
@@ -442,7 +681,13 @@ impl fmt::Display for NameServerConfig {
             write!(f, "{}@", tls_dns_name)?;
         }
 
-        write!(f, "{}", self.socket_addr)
+        write!(f, "{}", self.socket_addr)?;
+
+        if let Some(ref http_endpoint) = self.http_endpoint {
+            write!(f, "{}", http_endpoint)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -514,6 +759,7 @@ impl NameServerConfigGroup {
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
                 bind_addr: None,
+                http_endpoint: None,
             };
             let tcp = NameServerConfig {
                 socket_addr: SocketAddr::new(*ip, port),
@@ -523,6 +769,7 @@ impl NameServerConfigGroup {
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
                 bind_addr: None,
+                http_endpoint: None,
             };
 
             name_servers.push(udp);
@@ -532,7 +779,11 @@ impl NameServerConfigGroup {
         name_servers
     }
 
-    #[cfg(any(feature = "dns-over-tls", feature = "dns-over-https"))]
+    #[cfg(any(
+        feature = "dns-over-tls",
+        feature = "dns-over-https",
+        feature = "dns-over-quic"
+    ))]
     fn from_ips_encrypted(
         ips: &[IpAddr],
         port: u16,
@@ -553,6 +804,7 @@ impl NameServerConfigGroup {
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
                 bind_addr: None,
+                http_endpoint: None,
             };
 
             name_servers.push(config);
@@ -589,6 +841,101 @@ impl NameServerConfigGroup {
         Self::from_ips_encrypted(ips, port, tls_dns_name, Protocol::Https, trust_nx_responses)
     }
 
+    /// Configure NameServers for DNS-over-HTTPS from a URI template, e.g. `https://dns.google/dns-query{?dns}`
+    ///
+    /// The host of the template is used as the TLS dns name, and the path as the endpoint of the
+    ///  server. As the host would otherwise need to be resolved, the addresses of the server are
+    ///  taken from the `bootstrap_ips`. Any variables in the template, e.g. `{?dns}`, are ignored
+    ///  as all requests are sent with POST.
+    ///
+    /// ```
+    /// # #[cfg(feature = "dns-over-https")]
+    /// # {
+    /// use std::net::{IpAddr, Ipv4Addr};
+    /// use trust_dns_resolver::config::NameServerConfigGroup;
+    ///
+    /// let group = NameServerConfigGroup::from_https_template(
+    ///     "https://dns.example.com:8443/resolve{?dns}",
+    ///     &[IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))],
+    ///     true,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(group[0].socket_addr.port(), 8443);
+    /// assert_eq!(group[0].tls_dns_name.as_deref(), Some("dns.example.com"));
+    /// assert_eq!(group[0].http_endpoint.as_deref(), Some("/resolve"));
+    /// # }
+    /// ```
+    #[cfg(feature = "dns-over-https")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-https")))]
+    pub fn from_https_template(
+        template: &str,
+        bootstrap_ips: &[IpAddr],
+        trust_nx_responses: bool,
+    ) -> ResolveResult<Self> {
+        let rest = template
+            .strip_prefix("https://")
+            .ok_or_else(|| format!("DoH template must use the https scheme: {}", template))?;
+
+        // strip any variables from the template, e.g. `{?dns}`
+        let rest = rest.split('{').next().unwrap_or_default();
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => rest.split_at(idx),
+            None => (rest, ""),
+        };
+
+        let (host, port) = if let Some(authority) = authority.strip_prefix('[') {
+            // IPv6 literal, e.g. `[2001:db8::1]:443`
+            let (host, port) = authority
+                .split_once(']')
+                .ok_or_else(|| format!("bad IPv6 host in DoH template: {}", template))?;
+            (host, port.strip_prefix(':'))
+        } else {
+            match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            }
+        };
+
+        let port = port
+            .map(u16::from_str)
+            .transpose()
+            .map_err(|e| format!("bad port in DoH template: {}: {}", template, e))?
+            .unwrap_or(443);
+
+        if host.is_empty() {
+            return Err(format!("DoH template is missing a host: {}", template).into());
+        }
+        if bootstrap_ips.is_empty() {
+            return Err("DoH template requires at least one bootstrap address".into());
+        }
+
+        let mut name_servers =
+            Self::from_ips_https(bootstrap_ips, port, host.to_string(), trust_nx_responses);
+
+        if !path.is_empty() && path != "/dns-query" {
+            for name_server in name_servers.iter_mut() {
+                name_server.http_endpoint = Some(path.to_string());
+            }
+        }
+
+        Ok(name_servers)
+    }
+
+    /// Configure a NameServer address and port for DNS-over-QUIC
+    ///
+    /// This will create a QUIC connections.
+    #[cfg(feature = "dns-over-quic")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-quic")))]
+    pub fn from_ips_quic(
+        ips: &[IpAddr],
+        port: u16,
+        tls_dns_name: String,
+        trust_nx_responses: bool,
+    ) -> Self {
+        Self::from_ips_encrypted(ips, port, tls_dns_name, Protocol::Quic, trust_nx_responses)
+    }
+
     /// Creates a default configuration, using `8.8.8.8`, `8.8.4.4` and `2001:4860:4860::8888`, `2001:4860:4860::8844` (thank you, Google).
     ///
     /// Please see Google's [privacy statement](https://developers.google.com/speed/public-dns/privacy) for important information about what they track, many ISP's track similar information in DNS. To use the system configuration see: `Resolver::from_system_conf` and `AsyncResolver::from_system_conf`
@@ -605,6 +952,15 @@ impl NameServerConfigGroup {
         Self::from_ips_https(GOOGLE_IPS, 443, "dns.google".to_string(), true)
     }
 
+    /// Creates a default configuration, using `8.8.8.8`, `8.8.4.4` and `2001:4860:4860::8888`, `2001:4860:4860::8844` (thank you, Google). This limits the registered connections to just TLS lookups
+    ///
+    /// Please see Google's [privacy statement](https://developers.google.com/speed/public-dns/privacy) for important information about what they track, many ISP's track similar information in DNS. To use the system configuration see: `Resolver::from_system_conf` and `AsyncResolver::from_system_conf`
+    #[cfg(feature = "dns-over-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-tls")))]
+    pub fn google_tls() -> Self {
+        Self::from_ips_tls(GOOGLE_IPS, 853, "dns.google".to_string(), true)
+    }
+
     /// Creates a default configuration, using `1.1.1.1`, `1.0.0.1` and `2606:4700:4700::1111`, `2606:4700:4700::1001` (thank you, Cloudflare).
     ///
     /// Please see: <https://www.cloudflare.com/dns/>
@@ -655,6 +1011,15 @@ impl NameServerConfigGroup {
         Self::from_ips_https(QUAD9_IPS, 443, "dns.quad9.net".to_string(), true)
     }
 
+    /// Creates a configuration, using `94.140.14.14`, `94.140.15.15` and `2a10:50c0::ad1:ff`, `2a10:50c0::ad2:ff` (thank you, AdGuard). This limits the registered connections to just QUIC lookups
+    ///
+    /// Please see: <https://adguard-dns.io/en/public-dns.html>
+    #[cfg(feature = "dns-over-quic")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-quic")))]
+    pub fn adguard_quic() -> Self {
+        Self::from_ips_quic(ADGUARD_IPS, 853, "dns.adguard-dns.com".to_string(), true)
+    }
+
     /// Merges this set of [`NameServerConfig`]s with the other
     ///
     /// ```
@@ -742,6 +1107,16 @@ pub enum LookupIpStrategy {
     Ipv6thenIpv4,
     /// Query for Ipv4 if that fails, query for Ipv6 (default)
     Ipv4thenIpv6,
+    /// Query for Ipv4 and Ipv6 in parallel, returning as soon as Ipv4 has results, otherwise Ipv6
+    ///
+    /// The Ipv6 lookup still completes in the background when the runtime supports spawning tasks,
+    ///  see `ConnectionProvider::spawn_bg`, so that its results are cached.
+    Ipv4PreferredParallel,
+    /// Query for Ipv6 and Ipv4 in parallel, returning as soon as Ipv6 has results, otherwise Ipv4
+    ///
+    /// The Ipv4 lookup still completes in the background when the runtime supports spawning tasks,
+    ///  see `ConnectionProvider::spawn_bg`, so that its results are cached.
+    Ipv6PreferredParallel,
 }
 
 impl Default for LookupIpStrategy {
@@ -770,6 +1145,54 @@ impl Default for ServerOrderingStrategy {
     }
 }
 
+/// The policy for upgrading unencrypted name servers to their designated encrypted resolvers
+///
+/// See [`crate::ddr`] for Discovery of Designated Resolvers (DDR).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum EncryptionPolicy {
+    /// Use the name servers as they are configured, no discovery is performed (default)
+    AsConfigured,
+    /// Use the designated encrypted resolvers where they are discovered, otherwise fallback to
+    ///  the configured unencrypted name servers
+    PreferEncrypted,
+    /// Only use encrypted name servers, unencrypted name servers without designated resolvers
+    ///  are removed from the configuration
+    RequireEncrypted,
+}
+
+impl Default for EncryptionPolicy {
+    /// Returns [`EncryptionPolicy::AsConfigured`] as the default.
+    fn default() -> Self {
+        Self::AsConfigured
+    }
+}
+
+/// The mode of query name minimization for iterative lookups, [RFC 9156](https://tools.ietf.org/html/rfc9156)
+///
+/// With qname minimization, each name server on the path of delegations only receives the name
+///  with one more label than the zone it is authoritative for, instead of the full query name.
+///  This only applies to iterative lookups, as performed by the trust-dns-recursor; a stub resolver
+///  sends the full query to its recursive resolvers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-config", derive(Serialize, Deserialize))]
+pub enum QnameMinimization {
+    /// When a name server fails to answer a minimized query, e.g. with `SERVFAIL`, `REFUSED` or a
+    ///  timeout, fall back to sending the full query name to the last responsive zone (default)
+    Relaxed,
+    /// Only ever send minimized queries, lookups fail if a name server can not answer them
+    Strict,
+    /// Send the full query name to the name servers of each zone, following their referrals
+    Off,
+}
+
+impl Default for QnameMinimization {
+    /// Returns [`QnameMinimization::Relaxed`] as the default.
+    fn default() -> Self {
+        Self::Relaxed
+    }
+}
+
 /// Configuration for the Resolver
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[cfg_attr(
@@ -844,6 +1267,54 @@ pub struct ResolverOpts {
     pub recursion_desired: bool,
     /// This is true by default, disabling this is useful for requesting single records, but may prevent successful resolution.
     pub authentic_data: bool,
+    /// Randomize the case of the query names sent over UDP, DNS 0x20 encoding, and drop responses
+    ///  that don't echo the exact case, as an additional protection against off-path spoofing.
+    pub case_randomization: bool,
+    /// The policy for upgrading unencrypted name servers to encrypted resolvers, see [`crate::ddr`].
+    ///
+    /// Discovery is performed by [`crate::AsyncResolver::with_designated_resolvers`].
+    pub encryption_policy: EncryptionPolicy,
+    /// The mode of query name minimization for iterative lookups, see [`QnameMinimization`]
+    pub qname_minimization: QnameMinimization,
+    /// The local address to send queries from, e.g. on multi-homed hosts
+    ///
+    /// This only applies to name servers of the same address family, and without a
+    ///  `NameServerConfig::bind_addr`.
+    pub bind_addr: Option<IpAddr>,
+    /// The inclusive range of the random local ports for UDP queries, e.g. for firewalls which
+    ///  only allow specific ports
+    ///
+    /// Defaults to the IANA dynamic port range, 49152-65535. TCP connections use the ephemeral
+    ///  ports of the OS.
+    pub udp_port_range: Option<(u16, u16)>,
+    /// Order the addresses of `lookup_ip` by [RFC 6724](https://tools.ietf.org/html/rfc6724)
+    ///  destination address selection, default is `true`
+    ///
+    /// This prefers e.g. IPv4 over IPv6 when the host has no IPv6 route. The source address for
+    ///  each destination is queried from the OS by connecting a UDP socket, on the blocking
+    ///  threads of the runtime, see `ConnectionProvider::spawn_blocking`. When disabled the
+    ///  addresses keep the order of the name server.
+    pub sort_addresses: bool,
+    /// Request the DNSSEC chain from the root trust anchor with the [RFC 7901](https://tools.ietf.org/html/rfc7901)
+    ///  CHAIN option when `validate` is enabled, default is `false`
+    ///
+    /// The upstream recursive resolver must support CHAIN, the returned DNSKEY and DS records are
+    ///  then used for validation instead of querying each of them separately.
+    pub chain_query: bool,
+    /// The cool-down of a name server after a connection failure or a timeout, during which it is
+    ///  queried after the other name servers, default is 1 second
+    ///
+    /// The cool-down doubles with each consecutive failure, up to `max_failure_backoff`, and is
+    ///  jittered so that the name servers which failed together are not retried together.
+    pub failure_backoff: Duration,
+    /// The longest cool-down of a failing name server, default is 60 seconds
+    pub max_failure_backoff: Duration,
+    /// The consecutive failures after which a name server is not queried anymore until its
+    ///  cool-down ends, default is 5, 0 disables it
+    ///
+    /// Once the cool-down ends, one query probes the name server, a response restores it. If all
+    ///  the name servers are failing, they are all queried.
+    pub circuit_breaker_failures: usize,
 }
 
 impl Default for ResolverOpts {
@@ -875,6 +1346,123 @@ impl Default for ResolverOpts {
             server_ordering_strategy: ServerOrderingStrategy::default(),
             recursion_desired: true,
             authentic_data: false,
+            case_randomization: false,
+            encryption_policy: EncryptionPolicy::default(),
+            qname_minimization: QnameMinimization::default(),
+            bind_addr: None,
+            udp_port_range: None,
+            sort_addresses: true,
+            chain_query: false,
+            failure_backoff: Duration::from_secs(1),
+            max_failure_backoff: Duration::from_secs(60),
+            circuit_breaker_failures: 5,
+        }
+    }
+}
+
+/// Options for a single lookup, these override the [`ResolverOpts`] for only that lookup
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct LookupOptions {
+    /// The point in time by which the entire lookup, including all retries and search names, must complete.
+    ///
+    /// Once the deadline has passed, the lookup is cancelled, any outstanding requests are dropped
+    ///  (which frees their slots in the connection) and `ResolveErrorKind::Timeout` is returned.
+    pub deadline: Option<Instant>,
+    /// Timeout for each request sent to a name server, this can only shorten `ResolverOpts::timeout`
+    pub request_timeout: Option<Duration>,
+    /// Overrides `ResolverOpts::ip_strategy` for IP lookups
+    pub ip_strategy: Option<LookupIpStrategy>,
+    /// The network of the client the lookup is performed for, sent as the EDNS Client Subnet
+    ///  option of [RFC 7871](https://tools.ietf.org/html/rfc7871)
+    ///
+    /// Answers are cached for the network of the scope prefix length returned by the name server,
+    ///  so that lookups for other networks are not answered with them. Use a source prefix length
+    ///  of at most 24 for IPv4 and 56 for IPv6, as recommended for privacy.
+    pub client_subnet: Option<ClientSubnet>,
+}
+
+impl LookupOptions {
+    /// Creates options with a deadline of `timeout` from now
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            deadline: Some(Instant::now() + timeout),
+            ..Self::default()
+        }
+    }
+
+    /// Creates options with the specified deadline
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..Self::default()
+        }
+    }
+
+    /// Creates options for a lookup on behalf of a client in the network
+    pub fn with_client_subnet(client_subnet: ClientSubnet) -> Self {
+        Self {
+            client_subnet: Some(client_subnet),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the time remaining until the deadline, `None` if there is no deadline
+    ///
+    /// If the deadline has already passed, this will be zero.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// The type of socket a service is looked up for, this selects the protocol of the service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SocketType {
+    /// A stream socket, i.e. TCP
+    Stream,
+    /// A datagram socket, i.e. UDP
+    Datagram,
+}
+
+impl SocketType {
+    /// The name of the protocol, as used in SRV names and the services file
+    pub fn protocol(self) -> &'static str {
+        match self {
+            Self::Stream => "tcp",
+            Self::Datagram => "udp",
+        }
+    }
+}
+
+impl Default for SocketType {
+    /// Returns [`SocketType::Stream`] as the default.
+    fn default() -> Self {
+        Self::Stream
+    }
+}
+
+/// Hints for [`crate::AsyncResolver::lookup_service`], similar to the hints of `getaddrinfo`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ServiceHints {
+    /// The type of socket the addresses are used for
+    pub socket_type: SocketType,
+    /// The host must be an IP address, no lookup is performed, like `AI_NUMERICHOST`
+    pub numeric_host: bool,
+    /// The service must be a port number, like `AI_NUMERICSERV`
+    pub numeric_service: bool,
+    /// Look up the SRV records of named services first, `_service._proto.host`, and use the
+    ///  addresses and ports of their targets. The services file is used if there are no SRV records.
+    pub use_srv: bool,
+}
+
+impl ServiceHints {
+    /// Creates hints for the type of socket
+    pub fn with_socket_type(socket_type: SocketType) -> Self {
+        Self {
+            socket_type,
+            ..Self::default()
         }
     }
 }
@@ -902,3 +1490,96 @@ pub const QUAD9_IPS: &[IpAddr] = &[
     IpAddr::V6(Ipv6Addr::new(0x2620, 0x00fe, 0, 0, 0, 0, 0, 0x00fe)),
     IpAddr::V6(Ipv6Addr::new(0x2620, 0x00fe, 0, 0, 0, 0, 0x00fe, 0x0009)),
 ];
+
+/// IP addresses for the AdGuard DNS service
+pub const ADGUARD_IPS: &[IpAddr] = &[
+    IpAddr::V4(Ipv4Addr::new(94, 140, 14, 14)),
+    IpAddr::V4(Ipv4Addr::new(94, 140, 15, 15)),
+    IpAddr::V6(Ipv6Addr::new(0x2a10, 0x50c0, 0, 0, 0, 0, 0x0ad1, 0x00ff)),
+    IpAddr::V6(Ipv6Addr::new(0x2a10, 0x50c0, 0, 0, 0, 0, 0x0ad2, 0x00ff)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_options_remaining() {
+        assert_eq!(LookupOptions::default().remaining(), None);
+
+        let options = LookupOptions::with_timeout(Duration::from_secs(60));
+        let remaining = options.remaining().expect("deadline should be set");
+        assert!(remaining <= Duration::from_secs(60));
+        assert!(remaining > Duration::from_secs(0));
+
+        let options = LookupOptions::with_deadline(Instant::now());
+        assert_eq!(options.remaining(), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-https")]
+    fn test_from_https_template() {
+        let ips = &[IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))];
+
+        let group = NameServerConfigGroup::from_https_template(
+            "https://dns.google/dns-query{?dns}",
+            ips,
+            true,
+        )
+        .expect("failed to parse template");
+        assert_eq!(group.len(), 1);
+        assert_eq!(group[0].protocol, Protocol::Https);
+        assert_eq!(group[0].socket_addr, SocketAddr::new(ips[0], 443));
+        assert_eq!(group[0].tls_dns_name.as_deref(), Some("dns.google"));
+        assert_eq!(group[0].http_endpoint, None);
+
+        let group = NameServerConfigGroup::from_https_template(
+            "https://[2001:db8::1]:8443/custom/path",
+            ips,
+            true,
+        )
+        .expect("failed to parse template");
+        assert_eq!(group[0].socket_addr, SocketAddr::new(ips[0], 8443));
+        assert_eq!(group[0].tls_dns_name.as_deref(), Some("2001:db8::1"));
+        assert_eq!(group[0].http_endpoint.as_deref(), Some("/custom/path"));
+
+        assert!(
+            NameServerConfigGroup::from_https_template("http://dns.google/", ips, true).is_err()
+        );
+        assert!(
+            NameServerConfigGroup::from_https_template("https://dns.google:x/", ips, true).is_err()
+        );
+        assert!(
+            NameServerConfigGroup::from_https_template("https://dns.google/", &[], true).is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dnssec")]
+    fn test_dnssec_policies() {
+        use std::str::FromStr;
+
+        let mut config = ResolverConfig::new();
+        config.add_negative_trust_anchor(Name::from_str("example.com.").unwrap());
+        config.set_dnssec_policy(
+            Name::from_str("secure.example.com.").unwrap(),
+            DnssecPolicy::Validate,
+        );
+
+        let policies = config.dnssec_policies();
+        assert!(!policies.is_validated(&Name::from_str("www.example.com.").unwrap()));
+        assert!(!policies.is_validated(&Name::from_str("example.com.").unwrap()));
+        assert!(policies.is_validated(&Name::from_str("www.secure.example.com.").unwrap()));
+        assert!(policies.is_validated(&Name::from_str("example.org.").unwrap()));
+        assert_eq!(policies.get(&Name::from_str("example.org.").unwrap()), None);
+
+        config.set_dnssec_policy(
+            Name::from_str("example.com.").unwrap(),
+            DnssecPolicy::Validate,
+        );
+        assert_eq!(config.dnssec_policies().iter().count(), 2);
+        assert!(config
+            .dnssec_policies()
+            .is_validated(&Name::from_str("www.example.com.").unwrap()));
+    }
+}
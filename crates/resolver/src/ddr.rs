@@ -0,0 +1,271 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Discovery of Designated Resolvers (DDR), [RFC 9462](https://www.rfc-editor.org/rfc/rfc9462)
+//!
+//! An unencrypted resolver can advertise the encrypted resolvers (DoT, DoH, DoQ) which it has
+//!  designated, by answering a query for `_dns.resolver.arpa. SVCB`. The resolver uses this to
+//!  opportunistically upgrade its configured name servers, see `ResolverOpts::encryption_policy`.
+
+use std::net::{IpAddr, SocketAddr};
+
+#[cfg(feature = "dns-over-https")]
+use proto::rr::rdata::svcb::SvcParamKey;
+use proto::rr::rdata::svcb::{SvcParamValue, SVCB};
+use proto::rr::Name;
+use tracing::debug;
+
+use crate::config::{NameServerConfig, Protocol};
+
+lazy_static! {
+    /// The special use domain name for discovering designated resolvers, `_dns.resolver.arpa.`
+    pub static ref RESOLVER_ARPA: Name = Name::from_ascii("_dns.resolver.arpa.").unwrap();
+}
+
+/// Converts the SVCB records returned for `_dns.resolver.arpa.` by the `unencrypted` name server
+///  into configurations for its designated resolvers.
+///
+/// Only designations which can be validated are returned. As the TLS certificate of the
+///  designated resolver can not be checked for the IP address of the unencrypted resolver at this
+///  point (verified discovery, RFC 9462 section 4.2), this implements opportunistic discovery
+///  (RFC 9462 section 4.3): the designated resolver must be reachable at the same IP address as the
+///  unencrypted resolver, as advertised in the `ipv4hint` or `ipv6hint` parameters. The TLS
+///  certificate is then validated against the target name of the SVCB record.
+///
+/// The returned configurations are ordered by the SvcPriority of the records.
+pub fn designated_name_servers<'a>(
+    unencrypted: &NameServerConfig,
+    records: impl IntoIterator<Item = &'a SVCB>,
+) -> Vec<NameServerConfig> {
+    let mut records: Vec<&SVCB> = records.into_iter().collect();
+
+    // AliasMode is not allowed for DDR, and a set with any AliasMode records must ignore all ServiceMode records
+    if records.iter().any(|svcb| svcb.svc_priority() == 0) {
+        debug!("ignoring designated resolvers in AliasMode");
+        return vec![];
+    }
+
+    records.sort_by_key(|svcb| svcb.svc_priority());
+
+    let mut designated = Vec::new();
+    for svcb in records {
+        let target_name = svcb.target_name();
+
+        // the owner name, `_dns.resolver.arpa.`, can not be used to validate a certificate
+        if target_name.is_root() || RESOLVER_ARPA.zone_of(target_name) {
+            debug!("ignoring designated resolver without a target name");
+            continue;
+        }
+
+        if !advertises_ip(svcb, unencrypted.socket_addr.ip()) {
+            debug!(
+                "ignoring designated resolver {} which is not at {}",
+                target_name,
+                unencrypted.socket_addr.ip()
+            );
+            continue;
+        }
+
+        let mut tls_dns_name = target_name.to_ascii();
+        if target_name.is_fqdn() {
+            tls_dns_name.pop();
+        }
+
+        let port = svcb.svc_params().iter().find_map(|(_, value)| match value {
+            SvcParamValue::Port(port) => Some(*port),
+            _ => None,
+        });
+
+        for alpn in alpns(svcb) {
+            let (protocol, default_port) = match alpn_protocol(alpn) {
+                Some(protocol) => protocol,
+                None => continue,
+            };
+
+            // the dohpath is required for DoH designations, RFC 9461 section 5
+            #[cfg(feature = "dns-over-https")]
+            let http_endpoint = match (protocol, doh_path(svcb)) {
+                (Protocol::Https, None) => continue,
+                (Protocol::Https, path) => path,
+                _ => None,
+            };
+            #[cfg(not(feature = "dns-over-https"))]
+            let http_endpoint = None;
+
+            designated.push(NameServerConfig {
+                socket_addr: SocketAddr::new(
+                    unencrypted.socket_addr.ip(),
+                    port.unwrap_or(default_port),
+                ),
+                protocol,
+                tls_dns_name: Some(tls_dns_name.clone()),
+                trust_nx_responses: unencrypted.trust_nx_responses,
+                #[cfg(feature = "dns-over-rustls")]
+                tls_config: unencrypted.tls_config.clone(),
+                bind_addr: unencrypted.bind_addr,
+                http_endpoint,
+            });
+        }
+    }
+
+    designated
+}
+
+/// Returns true if the SVCB record has an address hint for the ip
+fn advertises_ip(svcb: &SVCB, ip: IpAddr) -> bool {
+    svcb.svc_params()
+        .iter()
+        .any(|(_, value)| match (value, ip) {
+            (SvcParamValue::Ipv4Hint(hint), IpAddr::V4(ip)) => hint.0.contains(&ip),
+            (SvcParamValue::Ipv6Hint(hint), IpAddr::V6(ip)) => hint.0.contains(&ip),
+            _ => false,
+        })
+}
+
+fn alpns(svcb: &SVCB) -> impl Iterator<Item = &str> {
+    svcb.svc_params()
+        .iter()
+        .filter_map(|(_, value)| match value {
+            SvcParamValue::Alpn(alpn) => Some(alpn.0.iter().map(String::as_str)),
+            _ => None,
+        })
+        .flatten()
+}
+
+/// Returns the protocol and the default port associated to the ALPN id, if supported
+fn alpn_protocol(alpn: &str) -> Option<(Protocol, u16)> {
+    match alpn {
+        #[cfg(feature = "dns-over-tls")]
+        "dot" => Some((Protocol::Tls, 853)),
+        #[cfg(feature = "dns-over-https")]
+        "h2" => Some((Protocol::Https, 443)),
+        #[cfg(feature = "dns-over-quic")]
+        "doq" => Some((Protocol::Quic, 853)),
+        _ => None,
+    }
+}
+
+/// Returns the path of the dohpath URI template, without any variables
+#[cfg(feature = "dns-over-https")]
+fn doh_path(svcb: &SVCB) -> Option<String> {
+    svcb.svc_params()
+        .iter()
+        .find_map(|(key, value)| match (key, value) {
+            (SvcParamKey::DohPath, SvcParamValue::DohPath(template)) => Some(template.path()),
+            _ => None,
+        })
+        .filter(|path| path.starts_with('/'))
+        .map(ToString::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[cfg(feature = "dns-over-https")]
+    use proto::rr::rdata::svcb::DohPath;
+    use proto::rr::rdata::svcb::{Alpn, IpHint, SvcParamKey};
+
+    use super::*;
+
+    fn unencrypted() -> NameServerConfig {
+        NameServerConfig::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 53),
+            Protocol::Udp,
+        )
+    }
+
+    fn svcb(priority: u16, target: &str, params: Vec<(SvcParamKey, SvcParamValue)>) -> SVCB {
+        SVCB::new(priority, Name::from_ascii(target).unwrap(), params)
+    }
+
+    #[test]
+    fn test_alias_mode_ignored() {
+        let records = [svcb(0, "dns.example.net.", vec![])];
+        assert!(designated_name_servers(&unencrypted(), &records).is_empty());
+    }
+
+    #[test]
+    fn test_different_ip_ignored() {
+        let records = [svcb(
+            1,
+            "dns.example.net.",
+            vec![
+                (
+                    SvcParamKey::Alpn,
+                    SvcParamValue::Alpn(Alpn(vec!["dot".to_string()])),
+                ),
+                (
+                    SvcParamKey::Ipv4Hint,
+                    SvcParamValue::Ipv4Hint(IpHint(vec![Ipv4Addr::new(192, 0, 2, 2)])),
+                ),
+                (
+                    SvcParamKey::Ipv6Hint,
+                    SvcParamValue::Ipv6Hint(IpHint(vec![Ipv6Addr::LOCALHOST])),
+                ),
+            ],
+        )];
+        assert!(designated_name_servers(&unencrypted(), &records).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-tls")]
+    fn test_designated_tls() {
+        let records = [svcb(
+            1,
+            "dns.example.net.",
+            vec![
+                (
+                    SvcParamKey::Alpn,
+                    SvcParamValue::Alpn(Alpn(vec!["dot".to_string()])),
+                ),
+                (SvcParamKey::Port, SvcParamValue::Port(8853)),
+                (
+                    SvcParamKey::Ipv4Hint,
+                    SvcParamValue::Ipv4Hint(IpHint(vec![Ipv4Addr::new(192, 0, 2, 1)])),
+                ),
+            ],
+        )];
+
+        let designated = designated_name_servers(&unencrypted(), &records);
+        assert_eq!(designated.len(), 1);
+        assert_eq!(designated[0].protocol, Protocol::Tls);
+        assert_eq!(designated[0].socket_addr.port(), 8853);
+        assert_eq!(
+            designated[0].tls_dns_name.as_deref(),
+            Some("dns.example.net")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-https")]
+    fn test_designated_https_requires_dohpath() {
+        let mut params = vec![
+            (
+                SvcParamKey::Alpn,
+                SvcParamValue::Alpn(Alpn(vec!["h2".to_string()])),
+            ),
+            (
+                SvcParamKey::Ipv4Hint,
+                SvcParamValue::Ipv4Hint(IpHint(vec![Ipv4Addr::new(192, 0, 2, 1)])),
+            ),
+        ];
+        let records = [svcb(1, "dns.example.net.", params.clone())];
+        assert!(designated_name_servers(&unencrypted(), &records).is_empty());
+
+        params.push((
+            SvcParamKey::DohPath,
+            SvcParamValue::DohPath(DohPath("/q{?dns}".to_string())),
+        ));
+        let records = [svcb(1, "dns.example.net.", params)];
+        let designated = designated_name_servers(&unencrypted(), &records);
+        assert_eq!(designated.len(), 1);
+        assert_eq!(designated[0].protocol, Protocol::Https);
+        assert_eq!(designated[0].socket_addr.port(), 443);
+        assert_eq!(designated[0].http_endpoint.as_deref(), Some("/q"));
+    }
+}
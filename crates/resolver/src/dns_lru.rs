@@ -15,17 +15,56 @@ use std::time::{Duration, Instant};
 use lru_cache::LruCache;
 use parking_lot::Mutex;
 
-use proto::op::Query;
-use proto::rr::Record;
+use proto::op::{Query, ResponseCode};
+use proto::rr::rdata::opt::ClientSubnet;
+use proto::rr::{DNSClass, Name, Record, RecordType};
 
 use crate::config;
 use crate::error::*;
 use crate::lookup::Lookup;
 
+/// The type of the RRset of the record, for RRSIGs the type they cover
+fn rrset_type(record: &Record) -> RecordType {
+    #[cfg(feature = "dnssec")]
+    {
+        use proto::rr::{dnssec::rdata::DNSSECRData, RData};
+
+        if let Some(RData::DNSSEC(DNSSECRData::SIG(rrsig))) = record.data() {
+            if record.record_type() == RecordType::RRSIG {
+                return rrsig.type_covered();
+            }
+        }
+    }
+
+    record.record_type()
+}
+
 /// Maximum TTL as defined in https://tools.ietf.org/html/rfc2181, 2147483647
 ///   Setting this to a value of 1 day, in seconds
 pub(crate) const MAX_TTL: u32 = 86400_u32;
 
+/// The key of a cache entry
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum CacheKey {
+    /// Positive responses and NODATA, for the name, type and class of the query
+    ///
+    /// Answers to EDNS Client Subnet queries are only valid for the network of their scope, see
+    ///  [`scoped`], `None` is valid for all clients.
+    Query(Query, Option<ClientSubnet>),
+    /// NXDOMAIN for the name and class, this denies all types at and below the name, see RFC 8020
+    NxDomain(Name, DNSClass),
+}
+
+impl CacheKey {
+    /// The name the entry is cached for
+    fn name(&self) -> &Name {
+        match self {
+            Self::Query(query, _) => query.name(),
+            Self::NxDomain(name, _) => name,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct LruValue {
     // In the None case, this represents an NXDomain
@@ -48,7 +87,7 @@ impl LruValue {
 /// And LRU eviction cache specifically for storing DNS records
 #[derive(Clone, Debug)]
 pub struct DnsLru {
-    cache: Arc<Mutex<LruCache<Query, LruValue>>>,
+    cache: Arc<Mutex<LruCache<CacheKey, LruValue>>>,
     /// A minimum TTL value for positive responses.
     ///
     /// Positive responses with TTLs under `positive_max_ttl` will use
@@ -156,13 +195,39 @@ impl DnsLru {
         }
     }
 
-    pub(crate) fn clear(&self) {
+    /// Removes all entries from the cache
+    pub fn clear(&self) {
         self.cache.lock().clear();
     }
 
+    /// Removes the entries cached for the name, of all types and client subnets
+    pub fn remove(&self, name: &Name) {
+        self.remove_matching(|key| key == name);
+    }
+
+    /// Removes the entries cached for the name and all names below it
+    pub fn remove_subtree(&self, name: &Name) {
+        self.remove_matching(|key| name.zone_of(key));
+    }
+
+    fn remove_matching(&self, matches: impl Fn(&Name) -> bool) {
+        let mut cache = self.cache.lock();
+        let keys = cache
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| matches(key.name()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for key in keys {
+            cache.remove(&key);
+        }
+    }
+
     pub(crate) fn insert(
         &self,
         query: Query,
+        scope: Option<ClientSubnet>,
         records_and_ttl: Vec<(Record, u32)>,
         now: Instant,
     ) -> Lookup {
@@ -186,7 +251,7 @@ impl DnsLru {
         // insert into the LRU
         let lookup = Lookup::new_with_deadline(query.clone(), Arc::from(records), valid_until);
         self.cache.lock().insert(
-            query,
+            CacheKey::Query(query, scope),
             LruValue {
                 lookup: Ok(lookup.clone()),
                 valid_until,
@@ -201,7 +266,8 @@ impl DnsLru {
     /// # Arguments
     ///
     /// * `original_query` - is used for matching the records that should be returned
-    /// * `records` - the records will be partitioned by type and name for storage in the cache
+    /// * `records` - the records will be partitioned by type and name for storage in the cache,
+    ///   RRSIGs are stored with the records of the type they cover
     /// * `now` - current time for use in associating TTLs
     ///
     /// # Return
@@ -217,7 +283,7 @@ impl DnsLru {
         let records = records.fold(
             HashMap::<Query, Vec<(Record, u32)>>::new(),
             |mut map, record| {
-                let mut query = Query::query(record.name().clone(), record.record_type());
+                let mut query = Query::query(record.name().clone(), rrset_type(&record));
                 query.set_query_class(record.dns_class());
 
                 let ttl = record.ttl();
@@ -234,7 +300,7 @@ impl DnsLru {
         let mut lookup = None;
         for (query, records_and_ttl) in records {
             let is_query = original_query == query;
-            let inserted = self.insert(query, records_and_ttl, now);
+            let inserted = self.insert(query, None, records_and_ttl, now);
 
             if is_query {
                 lookup = Some(inserted)
@@ -245,12 +311,19 @@ impl DnsLru {
     }
 
     /// Generally for inserting a set of records that have already been cached, but with a different Query.
-    pub(crate) fn duplicate(&self, query: Query, lookup: Lookup, ttl: u32, now: Instant) -> Lookup {
+    pub(crate) fn duplicate(
+        &self,
+        query: Query,
+        scope: Option<ClientSubnet>,
+        lookup: Lookup,
+        ttl: u32,
+        now: Instant,
+    ) -> Lookup {
         let ttl = Duration::from_secs(u64::from(ttl));
         let valid_until = now + ttl;
 
         self.cache.lock().insert(
-            query,
+            CacheKey::Query(query, scope),
             LruValue {
                 lookup: Ok(lookup.clone()),
                 valid_until,
@@ -276,9 +349,20 @@ impl DnsLru {
         }
     }
 
-    pub(crate) fn negative(
+    /// Caches the negative response, if it carries a negative TTL
+    ///
+    /// NODATA responses are cached for the name, type and class of the query. NXDOMAIN responses
+    ///  for the query name are cached for the name and class, and are then also returned for any
+    ///  name below it, the "NXDOMAIN cut" of [RFC 8020](https://tools.ietf.org/html/rfc8020),
+    ///  unless they are untrusted or only valid for the client subnet of the `scope`. Untrusted
+    ///  NXDOMAIN responses are only cached for the query.
+    ///
+    /// The negative TTL is clamped to the configured minimum and maximum, and the returned error
+    ///  carries the clamped TTL.
+    pub fn negative(
         &self,
         query: Query,
+        scope: Option<ClientSubnet>,
         mut error: ResolveError,
         now: Instant,
     ) -> ResolveError {
@@ -287,7 +371,10 @@ impl DnsLru {
         if let ResolveError {
             kind:
                 ResolveErrorKind::NoRecordsFound {
+                    query: ref nx_query,
                     negative_ttl: Some(ttl),
+                    response_code,
+                    trusted,
                     ..
                 },
             ..
@@ -299,11 +386,22 @@ impl DnsLru {
                 .clamp(self.negative_min_ttl, self.negative_max_ttl);
             let valid_until = now + ttl_duration;
 
+            // the NXDOMAIN might be for the target of a CNAME chain, which is only cached for the query
+            let key = if response_code == ResponseCode::NXDomain
+                && trusted
+                && nx_query.name() == query.name()
+                && scope.is_none()
+            {
+                CacheKey::NxDomain(query.name().clone(), query.query_class())
+            } else {
+                CacheKey::Query(query, scope)
+            };
+
             {
                 let error = error.clone();
 
                 self.cache.lock().insert(
-                    query,
+                    key,
                     LruValue {
                         lookup: Err(error),
                         valid_until,
@@ -318,10 +416,64 @@ impl DnsLru {
     }
 
     /// Based on the query, see if there are any records available
+    ///
+    /// If the name, or any of its parent names, was cached as NXDOMAIN, the NXDOMAIN error is
+    ///  returned for the query.
     pub fn get(&self, query: &Query, now: Instant) -> Option<Result<Lookup, ResolveError>> {
-        let mut out_of_date = false;
+        self.get_for_subnet(query, None, now)
+    }
+
+    /// Based on the query from a client in the subnet, see if there are any records available
+    ///
+    /// The answers for the most specific network containing the subnet are preferred, then the
+    ///  answers valid for all clients, see [`Self::get`].
+    pub(crate) fn get_for_subnet(
+        &self,
+        query: &Query,
+        subnet: Option<&ClientSubnet>,
+        now: Instant,
+    ) -> Option<Result<Lookup, ResolveError>> {
         let mut cache = self.cache.lock();
-        let lookup = cache.get_mut(query).and_then(|value| {
+
+        if let Some(subnet) = subnet {
+            for prefix in (1..=subnet.source_prefix()).rev() {
+                let key = CacheKey::Query(query.clone(), scoped(subnet, prefix));
+                if let Some(lookup) = Self::get_current(&mut cache, key, now) {
+                    return Some(lookup);
+                }
+            }
+        }
+
+        if let Some(lookup) =
+            Self::get_current(&mut cache, CacheKey::Query(query.clone(), None), now)
+        {
+            return Some(lookup);
+        }
+
+        // RFC 8020, an NXDOMAIN for a name means that nothing exists below that name
+        let mut name = query.name().clone();
+        while name.num_labels() > 0 {
+            let key = CacheKey::NxDomain(name.clone(), query.query_class());
+            if let Some(mut lookup) = Self::get_current(&mut cache, key, now) {
+                if let Err(ref mut err) = lookup {
+                    Self::nx_error_with_query(err, query);
+                }
+                return Some(lookup);
+            }
+
+            name = name.base_name();
+        }
+
+        None
+    }
+
+    fn get_current(
+        cache: &mut LruCache<CacheKey, LruValue>,
+        key: CacheKey,
+        now: Instant,
+    ) -> Option<Result<Lookup, ResolveError>> {
+        let mut out_of_date = false;
+        let lookup = cache.get_mut(&key).and_then(|value| {
             if value.is_current(now) {
                 out_of_date = false;
                 let mut result = value.lookup.clone();
@@ -340,11 +492,35 @@ impl DnsLru {
         // this assumes time is always moving forward, this would only not be true in contrived situations where now
         //  is not current time, like tests...
         if out_of_date {
-            cache.remove(query);
+            cache.remove(&key);
         }
 
         lookup
     }
+
+    /// Replaces the query of a cached NXDOMAIN error, which might be for a parent name of the query
+    fn nx_error_with_query(error: &mut ResolveError, new_query: &Query) {
+        if let ResolveError {
+            kind: ResolveErrorKind::NoRecordsFound { ref mut query, .. },
+            ..
+        } = error
+        {
+            **query = new_query.clone();
+        }
+    }
+}
+
+/// The network of the client subnet an answer with the scope prefix length is valid for, RFC 7871
+///
+/// The scope is limited to the source prefix length of the query, `None` if the answer is valid
+///  for all clients.
+pub(crate) fn scoped(subnet: &ClientSubnet, scope_prefix: u8) -> Option<ClientSubnet> {
+    let prefix = scope_prefix.min(subnet.source_prefix());
+    if prefix == 0 {
+        None
+    } else {
+        Some(ClientSubnet::new(subnet.address(), prefix, prefix))
+    }
 }
 
 // see also the lookup_tests.rs in integration-tests crate
@@ -359,6 +535,46 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    #[cfg(feature = "dnssec")]
+    fn test_insert_records_with_rrsigs() {
+        use proto::rr::dnssec::{rdata::DNSSECRData, rdata::SIG, Algorithm};
+
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let a = Record::from_rdata(name.clone(), 300, RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+        let rrsig = SIG::new(
+            RecordType::A,
+            Algorithm::ECDSAP256SHA256,
+            3,
+            300,
+            0,
+            0,
+            0,
+            Name::from_str("example.com.").unwrap(),
+            vec![0; 64],
+        );
+        let mut rrsig =
+            Record::from_rdata(name.clone(), 300, RData::DNSSEC(DNSSECRData::SIG(rrsig)));
+        rrsig.set_rr_type(RecordType::RRSIG);
+
+        let lru = DnsLru::new(2, TtlConfig::default());
+        let query = Query::query(name.clone(), RecordType::A);
+        let lookup = lru
+            .insert_records(query.clone(), vec![a, rrsig].into_iter(), now)
+            .expect("records should be inserted");
+
+        // the RRSIG is returned with the records it covers
+        assert_eq!(lookup.records().len(), 2);
+        assert_eq!(
+            lru.get(&query, now).unwrap().unwrap().records(),
+            lookup.records()
+        );
+        assert!(lru
+            .get(&Query::query(name, RecordType::RRSIG), now)
+            .is_none());
+    }
+
     #[test]
     fn test_is_current() {
         let now = Instant::now();
@@ -397,7 +613,7 @@ mod tests {
         };
         let lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was below the minimum.
@@ -409,7 +625,7 @@ mod tests {
             3,
         )];
 
-        let rc_ips = lru.insert(query, ips_ttl, now);
+        let rc_ips = lru.insert(query, None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // greater than the cache's minimum.
@@ -436,8 +652,9 @@ mod tests {
             negative_ttl: Some(1),
             response_code: ResponseCode::NoError,
             trusted: false,
+            authorities: None,
         };
-        let nx_error = lru.negative(name.clone(), err.into(), now);
+        let nx_error = lru.negative(name.clone(), None, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let valid_until = negative_ttl.expect("resolve error should have a deadline");
@@ -454,8 +671,9 @@ mod tests {
             negative_ttl: Some(3),
             response_code: ResponseCode::NoError,
             trusted: false,
+            authorities: None,
         };
-        let nx_error = lru.negative(name, err.into(), now);
+        let nx_error = lru.negative(name, None, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("ResolveError should have a deadline");
@@ -487,7 +705,7 @@ mod tests {
         };
         let lru = DnsLru::new(1, ttls);
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the cache's min TTL, since the
         // query's TTL was above the maximum.
@@ -499,7 +717,7 @@ mod tests {
             59,
         )];
 
-        let rc_ips = lru.insert(query, ips_ttl, now);
+        let rc_ips = lru.insert(query, None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
         // the returned lookup should use the record's TTL, since it's
         // below than the cache's maximum.
@@ -526,8 +744,9 @@ mod tests {
             negative_ttl: Some(62),
             response_code: ResponseCode::NoError,
             trusted: false,
+            authorities: None,
         };
-        let nx_error = lru.negative(name.clone(), err.into(), now);
+        let nx_error = lru.negative(name.clone(), None, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("resolve error should have a deadline");
@@ -544,8 +763,9 @@ mod tests {
             negative_ttl: Some(59),
             response_code: ResponseCode::NoError,
             trusted: false,
+            authorities: None,
         };
-        let nx_error = lru.negative(name, err.into(), now);
+        let nx_error = lru.negative(name, None, err.into(), now);
         match nx_error.kind() {
             &ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => {
                 let negative_ttl = negative_ttl.expect("resolve error should have a deadline");
@@ -570,7 +790,7 @@ mod tests {
         let ips = vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))];
         let lru = DnsLru::new(1, TtlConfig::default());
 
-        let rc_ips = lru.insert(query.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(query.clone(), None, ips_ttl, now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
 
         let rc_ips = lru.get(&query, now).unwrap().expect("records should exist");
@@ -599,7 +819,7 @@ mod tests {
         ];
         let lru = DnsLru::new(1, TtlConfig::default());
 
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), None, ips_ttl, now);
 
         // still valid
         let rc_ips = lru
@@ -641,7 +861,7 @@ mod tests {
             ..TtlConfig::default()
         };
         let lru = DnsLru::new(1, ttls);
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), None, ips_ttl, now);
 
         // still valid
         let rc_ips = lru
@@ -701,7 +921,7 @@ mod tests {
             ..TtlConfig::default()
         };
         let lru = DnsLru::new(1, ttls);
-        lru.insert(query.clone(), ips_ttl, now);
+        lru.insert(query.clone(), None, ips_ttl, now);
 
         // still valid
         let rc_ips = lru
@@ -724,4 +944,140 @@ mod tests {
         let rc_ips = lru.get(&query, now + Duration::from_secs(3));
         assert!(rc_ips.is_none());
     }
+
+    #[test]
+    fn test_nxdomain_cut() {
+        let now = Instant::now();
+        let name = Name::from_str("nx.example.com.").unwrap();
+        let query = Query::query(name, RecordType::A);
+        let lru = DnsLru::new(2, TtlConfig::default());
+
+        let err = ResolveErrorKind::NoRecordsFound {
+            query: Box::new(query.clone()),
+            soa: None,
+            negative_ttl: Some(10),
+            response_code: ResponseCode::NXDomain,
+            trusted: true,
+            authorities: None,
+        };
+        lru.negative(query, None, err.into(), now);
+
+        // any type at, or name below, the NXDOMAIN is denied
+        let below = Query::query(
+            Name::from_str("www.nx.example.com.").unwrap(),
+            RecordType::AAAA,
+        );
+        match lru
+            .get(&below, now)
+            .expect("should be cached")
+            .unwrap_err()
+            .kind()
+        {
+            ResolveErrorKind::NoRecordsFound {
+                query,
+                response_code,
+                ..
+            } => {
+                assert_eq!(**query, below);
+                assert_eq!(*response_code, ResponseCode::NXDomain);
+            }
+            other => panic!("expected ResolveErrorKind::NoRecordsFound, got {:?}", other),
+        }
+
+        // siblings and parents are not
+        let sibling = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        assert!(lru.get(&sibling, now).is_none());
+        let parent = Query::query(Name::from_str("example.com.").unwrap(), RecordType::A);
+        assert!(lru.get(&parent, now).is_none());
+
+        // and the cut expires with the negative ttl
+        assert!(lru.get(&below, now + Duration::from_secs(11)).is_none());
+    }
+
+    #[test]
+    fn test_untrusted_nxdomain_not_cut() {
+        let now = Instant::now();
+        let query = Query::query(Name::from_str("nx.example.com.").unwrap(), RecordType::A);
+        let lru = DnsLru::new(2, TtlConfig::default());
+
+        let err = ResolveErrorKind::NoRecordsFound {
+            query: Box::new(query.clone()),
+            soa: None,
+            negative_ttl: Some(10),
+            response_code: ResponseCode::NXDomain,
+            trusted: false,
+            authorities: None,
+        };
+        lru.negative(query.clone(), None, err.into(), now);
+
+        // only the query is cached, not the names below it or the other types
+        assert!(lru.get(&query, now).expect("should be cached").is_err());
+        let other_type = Query::query(query.name().clone(), RecordType::AAAA);
+        assert!(lru.get(&other_type, now).is_none());
+        let below = Query::query(
+            Name::from_str("www.nx.example.com.").unwrap(),
+            RecordType::A,
+        );
+        assert!(lru.get(&below, now).is_none());
+    }
+
+    #[test]
+    fn test_nodata_not_cut() {
+        let now = Instant::now();
+        let query = Query::query(Name::from_str("example.com.").unwrap(), RecordType::A);
+        let lru = DnsLru::new(2, TtlConfig::default());
+
+        let err = ResolveErrorKind::NoRecordsFound {
+            query: Box::new(query.clone()),
+            soa: None,
+            negative_ttl: Some(10),
+            response_code: ResponseCode::NoError,
+            trusted: false,
+            authorities: None,
+        };
+        lru.negative(query.clone(), None, err.into(), now);
+
+        assert!(lru.get(&query, now).expect("should be cached").is_err());
+        let other_type = Query::query(query.name().clone(), RecordType::AAAA);
+        assert!(lru.get(&other_type, now).is_none());
+        let below = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        assert!(lru.get(&below, now).is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        let now = Instant::now();
+        let lru = DnsLru::new(8, TtlConfig::default());
+        let insert = |name: &str, record_type| {
+            let name = Name::from_str(name).unwrap();
+            let record = Record::from_rdata(name.clone(), 10, RData::A(Ipv4Addr::LOCALHOST));
+            lru.insert(
+                Query::query(name, record_type),
+                None,
+                vec![(record, 10)],
+                now,
+            );
+        };
+        let cached = |name: &str, record_type| {
+            let query = Query::query(Name::from_str(name).unwrap(), record_type);
+            lru.get(&query, now).is_some()
+        };
+
+        insert("example.com.", RecordType::A);
+        insert("www.example.com.", RecordType::A);
+        insert("WWW.example.com.", RecordType::AAAA);
+        insert("a.www.example.com.", RecordType::A);
+        insert("example.net.", RecordType::A);
+
+        lru.remove(&Name::from_str("www.example.com.").unwrap());
+        assert!(!cached("www.example.com.", RecordType::A));
+        assert!(!cached("www.example.com.", RecordType::AAAA));
+        assert!(cached("a.www.example.com.", RecordType::A));
+        assert!(cached("example.com.", RecordType::A));
+
+        lru.remove_subtree(&Name::from_str("example.com.").unwrap());
+        assert!(!cached("a.www.example.com.", RecordType::A));
+        assert!(!cached("example.com.", RecordType::A));
+        assert!(cached("example.net.", RecordType::A));
+    }
 }
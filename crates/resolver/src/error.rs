@@ -8,13 +8,16 @@
 //! Error types for the crate
 
 use std::cmp::Ordering;
-use std::{fmt, io, sync};
+use std::{
+    fmt, io,
+    sync::{self, Arc},
+};
 
 use thiserror::Error;
 use tracing::debug;
 use trust_dns_proto::rr::Record;
 
-use crate::proto::error::{ProtoError, ProtoErrorKind};
+use crate::proto::error::{ErrorCategory, ProtoError, ProtoErrorKind};
 use crate::proto::op::{Query, ResponseCode};
 use crate::proto::xfer::retry_dns_handle::RetryableError;
 use crate::proto::xfer::DnsResponse;
@@ -56,6 +59,9 @@ pub enum ResolveErrorKind {
         response_code: ResponseCode,
         /// If we trust `NXDOMAIN` errors from this server
         trusted: bool,
+        /// The records of the authority section of the response, e.g. the NSEC or NSEC3 records
+        ///  proving the denial of existence, `None` if the error was not built from a response
+        authorities: Option<Arc<[Record]>>,
     },
 
     // foreign
@@ -72,6 +78,20 @@ pub enum ResolveErrorKind {
     Timeout,
 }
 
+impl ResolveErrorKind {
+    /// The broad category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Message(_) | Self::Msg(_) => ErrorCategory::Other,
+            Self::NoConnections => ErrorCategory::Busy,
+            Self::NoRecordsFound { .. } => ErrorCategory::NoRecords,
+            Self::Io(_) => ErrorCategory::Io,
+            Self::Proto(e) => e.category(),
+            Self::Timeout => ErrorCategory::Timeout,
+        }
+    }
+}
+
 impl Clone for ResolveErrorKind {
     fn clone(&self) -> Self {
         use self::ResolveErrorKind::*;
@@ -85,12 +105,14 @@ impl Clone for ResolveErrorKind {
                 negative_ttl,
                 response_code,
                 trusted,
+                authorities,
             } => NoRecordsFound {
                 query: query.clone(),
                 soa: soa.clone(),
                 negative_ttl: *negative_ttl,
                 response_code: *response_code,
                 trusted: *trusted,
+                authorities: authorities.clone(),
             },
             // foreign
             Io(io) => Self::from(std::io::Error::from(io.kind())),
@@ -122,6 +144,7 @@ impl ResolveError {
             negative_ttl,
             response_code,
             trusted,
+            authorities: None,
         }
         .into()
     }
@@ -144,68 +167,18 @@ impl ResolveError {
     }
 
     /// A conversion to determine if the response is an error
+    ///
+    /// See [`ProtoError::from_response`], negative responses are returned as
+    ///  [`ResolveErrorKind::NoRecordsFound`].
     pub fn from_response(response: DnsResponse, trust_nx: bool) -> Result<DnsResponse, Self> {
         debug!("Response:{}", *response);
 
-        match response.response_code() {
-            response_code @ ResponseCode::ServFail
-            | response_code @ ResponseCode::Refused
-            | response_code @ ResponseCode::FormErr
-            | response_code @ ResponseCode::NotImp
-            | response_code @ ResponseCode::YXDomain
-            | response_code @ ResponseCode::YXRRSet
-            | response_code @ ResponseCode::NXRRSet
-            | response_code @ ResponseCode::NotAuth
-            | response_code @ ResponseCode::NotZone
-            | response_code @ ResponseCode::BADVERS
-            | response_code @ ResponseCode::BADSIG
-            | response_code @ ResponseCode::BADKEY
-            | response_code @ ResponseCode::BADTIME
-            | response_code @ ResponseCode::BADMODE
-            | response_code @ ResponseCode::BADNAME
-            | response_code @ ResponseCode::BADALG
-            | response_code @ ResponseCode::BADTRUNC
-            | response_code @ ResponseCode::BADCOOKIE => {
-                let mut response = response;
-                let soa = response.soa().cloned();
-                let query = response.take_queries().drain(..).next().unwrap_or_default();
-                let error_kind = ResolveErrorKind::NoRecordsFound {
-                    query: Box::new(query),
-                    soa: soa.map(Box::new),
-                    negative_ttl: None,
-                    response_code,
-                    trusted: false,
-                };
-
-                Err(Self::from(error_kind))
-            }
-            // Some NXDOMAIN responses contain CNAME referrals, that will not be an error
-            response_code @ ResponseCode::NXDomain |
-            // No answers are available, CNAME referrals are not failures
-            response_code @ ResponseCode::NoError
-            if !response.contains_answer() && !response.truncated() => {
-                // TODO: if authoritative, this is cacheable, store a TTL (currently that requires time, need a "now" here)
-                // let valid_until = if response.is_authoritative() { now + response.get_negative_ttl() };
-
-                let mut response = response;
-                let soa = response.soa().cloned();
-                let negative_ttl = response.negative_ttl();
-                let trusted = if response_code == ResponseCode::NoError { false } else { trust_nx };
-                let query = response.take_queries().drain(..).next().unwrap_or_default();
-                let error_kind = ResolveErrorKind::NoRecordsFound {
-                    query: Box::new(query),
-                    soa: soa.map(Box::new),
-                    negative_ttl,
-                    response_code,
-                    trusted,
-                };
-
-                Err(Self::from(error_kind))
-            }
-            ResponseCode::NXDomain
-            | ResponseCode::NoError
-            | ResponseCode::Unknown(_) => Ok(response),
-        }
+        ProtoError::from_response(response, trust_nx).map_err(Self::from)
+    }
+
+    /// The broad category of the error
+    pub fn category(&self) -> ErrorCategory {
+        self.kind.category()
     }
 
     /// Compare two errors to see if one contains a server response.
@@ -256,9 +229,8 @@ impl RetryableError for ResolveError {
             | ResolveErrorKind::Msg(_)
             | ResolveErrorKind::NoConnections
             | ResolveErrorKind::NoRecordsFound { .. } => false,
-            ResolveErrorKind::Io(_) | ResolveErrorKind::Proto(_) | ResolveErrorKind::Timeout => {
-                true
-            }
+            ResolveErrorKind::Proto(e) => e.should_retry(),
+            ResolveErrorKind::Io(_) | ResolveErrorKind::Timeout => true,
         }
     }
 
@@ -329,9 +301,25 @@ impl From<io::Error> for ResolveError {
 
 impl From<ProtoError> for ResolveError {
     fn from(e: ProtoError) -> Self {
-        match *e.kind() {
+        match *e.kind {
             ProtoErrorKind::Timeout => ResolveErrorKind::Timeout.into(),
-            _ => ResolveErrorKind::from(e).into(),
+            ProtoErrorKind::NoRecordsFound {
+                query,
+                soa,
+                negative_ttl,
+                response_code,
+                trusted,
+                authorities,
+            } => ResolveErrorKind::NoRecordsFound {
+                query,
+                soa,
+                negative_ttl,
+                response_code,
+                trusted,
+                authorities,
+            }
+            .into(),
+            kind => ResolveErrorKind::from(ProtoError::from(kind)).into(),
         }
     }
 }
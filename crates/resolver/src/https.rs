@@ -7,12 +7,15 @@
 
 use std::net::SocketAddr;
 
-use crate::name_server::RuntimeProvider;
+use std::sync::Arc;
+
+use futures_util::future::FutureExt;
+
+use crate::name_server::{RuntimeProvider, Spawn};
 use crate::tls::CLIENT_CONFIG;
 
 use proto::https::{HttpsClientConnect, HttpsClientStream, HttpsClientStreamBuilder};
 use proto::xfer::{DnsExchange, DnsExchangeConnect};
-use proto::TokioTime;
 
 use crate::config::TlsClientConfig;
 
@@ -21,8 +24,10 @@ pub(crate) fn new_https_stream<R>(
     socket_addr: SocketAddr,
     bind_addr: Option<SocketAddr>,
     dns_name: String,
+    http_endpoint: Option<String>,
     client_config: Option<TlsClientConfig>,
-) -> DnsExchangeConnect<HttpsClientConnect<R::Tcp>, HttpsClientStream, TokioTime>
+    spawner: R::Handle,
+) -> DnsExchangeConnect<HttpsClientConnect<R::Tcp>, HttpsClientStream, R::Timer>
 where
     R: RuntimeProvider,
 {
@@ -35,6 +40,13 @@ where
     if let Some(bind_addr) = bind_addr {
         https_builder.bind_addr(bind_addr);
     }
+    if let Some(http_endpoint) = http_endpoint {
+        https_builder.query_path(http_endpoint);
+    }
+    // the h2 connection is driven on the runtime of the resolver
+    https_builder.spawner(Arc::new(move |connection| {
+        spawner.clone().spawn_bg(connection.map(Ok))
+    }));
     DnsExchange::connect(https_builder.build::<R::Tcp>(socket_addr, dns_name))
 }
 
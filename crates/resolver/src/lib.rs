@@ -255,9 +255,12 @@ extern crate lazy_static;
 extern crate serde;
 pub extern crate trust_dns_proto as proto;
 
+mod address_selection;
 mod async_resolver;
+pub mod blocklist;
 pub mod caching_client;
 pub mod config;
+pub mod ddr;
 pub mod dns_lru;
 pub mod dns_sd;
 pub mod error;
@@ -266,19 +269,27 @@ mod hosts;
 mod https;
 pub mod lookup;
 pub mod lookup_ip;
+mod metrics;
 // TODO: consider #[doc(hidden)]
 pub mod name_server;
 #[cfg(feature = "dns-over-quic")]
 mod quic;
 #[cfg(feature = "tokio-runtime")]
 mod resolver;
+mod services;
 pub mod system_conf;
 #[cfg(feature = "dns-over-tls")]
 mod tls;
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+pub mod trust_anchor;
 
 // reexports from proto
 pub use self::proto::rr::{IntoName, Name, TryParseIp};
 
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use self::metrics::describe_metrics;
 #[cfg(feature = "testing")]
 #[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
 pub use async_resolver::testing;
@@ -294,6 +305,7 @@ pub use name_server::{TokioConnection, TokioConnectionProvider, TokioHandle};
 #[cfg(feature = "tokio-runtime")]
 #[cfg_attr(docsrs, doc(cfg(feature = "tokio-runtime")))]
 pub use resolver::Resolver;
+pub use services::Services;
 
 /// This is an alias for [`AsyncResolver`], which replaced the type previously
 /// called `ResolverFuture`.
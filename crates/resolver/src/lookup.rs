@@ -29,6 +29,8 @@ use proto::DnssecDnsHandle;
 use proto::{DnsHandle, RetryDnsHandle};
 
 use crate::caching_client::CachingClient;
+#[cfg(feature = "dnssec")]
+use crate::config::DnssecPolicies;
 use crate::dns_lru::MAX_TTL;
 use crate::error::*;
 use crate::lookup_ip::LookupIpIter;
@@ -183,6 +185,13 @@ pub enum LookupEither<
     #[cfg(feature = "dnssec")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
     Secure(DnssecDnsHandle<RetryDnsHandle<NameServerPool<C, P>>>),
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    SecureWithPolicies {
+        secure: DnssecDnsHandle<RetryDnsHandle<NameServerPool<C, P>>>,
+        insecure: RetryDnsHandle<NameServerPool<C, P>>,
+        policies: Arc<DnssecPolicies>,
+    },
 }
 
 impl<C: DnsHandle<Error = ResolveError> + Sync, P: ConnectionProvider<Conn = C>> DnsHandle
@@ -196,6 +205,8 @@ impl<C: DnsHandle<Error = ResolveError> + Sync, P: ConnectionProvider<Conn = C>>
             Self::Retry(ref c) => c.is_verifying_dnssec(),
             #[cfg(feature = "dnssec")]
             Self::Secure(ref c) => c.is_verifying_dnssec(),
+            #[cfg(feature = "dnssec")]
+            Self::SecureWithPolicies { ref secure, .. } => secure.is_verifying_dnssec(),
         }
     }
 
@@ -204,6 +215,25 @@ impl<C: DnsHandle<Error = ResolveError> + Sync, P: ConnectionProvider<Conn = C>>
             Self::Retry(ref mut c) => c.send(request),
             #[cfg(feature = "dnssec")]
             Self::Secure(ref mut c) => c.send(request),
+            #[cfg(feature = "dnssec")]
+            Self::SecureWithPolicies {
+                ref mut secure,
+                ref mut insecure,
+                ref policies,
+            } => {
+                let request: DnsRequest = request.into();
+                let validate = request
+                    .queries()
+                    .first()
+                    .map_or(true, |query| policies.is_validated(query.name()));
+
+                if validate {
+                    secure.send(request)
+                } else {
+                    tracing::debug!("skipping DNSSEC validation for {:?}", request.queries());
+                    insecure.send(request)
+                }
+            }
         }
     }
 }
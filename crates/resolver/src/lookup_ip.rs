@@ -24,6 +24,7 @@ use proto::rr::{Name, RData, Record, RecordType};
 use proto::xfer::{DnsHandle, DnsRequestOptions};
 use tracing::debug;
 
+use crate::address_selection;
 use crate::caching_client::CachingClient;
 use crate::config::LookupIpStrategy;
 use crate::dns_lru::MAX_TTL;
@@ -59,6 +60,35 @@ impl LookupIp {
     pub fn as_lookup(&self) -> &Lookup {
         &self.0
     }
+
+    /// Orders the addresses by RFC 6724 destination address selection
+    ///
+    /// Other records, e.g. the CNAMEs of the lookup, are kept in front.
+    pub(crate) fn sort_by_destination(self) -> Self {
+        let (mut addrs, others): (Vec<Record>, Vec<Record>) = self
+            .0
+            .records()
+            .iter()
+            .cloned()
+            .partition(|record| record.data().and_then(RData::to_ip_addr).is_some());
+        if addrs.len() < 2 {
+            return self;
+        }
+
+        address_selection::sort_by_destination(&mut addrs, |record| {
+            record
+                .data()
+                .and_then(RData::to_ip_addr)
+                .expect("only records with addresses are sorted")
+        });
+
+        let records = others.into_iter().chain(addrs).collect::<Vec<_>>();
+        Self(Lookup::new_with_deadline(
+            self.0.query().clone(),
+            Arc::from(records),
+            self.0.valid_until(),
+        ))
+    }
 }
 
 impl From<Lookup> for LookupIp {
@@ -118,6 +148,10 @@ impl Iterator for LookupIpIntoIter {
     }
 }
 
+/// Spawns a future to complete in the background, see `ConnectionProvider::spawn_bg`
+pub(crate) type BackgroundSpawner =
+    Arc<dyn Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync>;
+
 /// The Future returned from [crate::AsyncResolver] when performing an A or AAAA lookup.
 ///
 /// This type isn't necessarily something that should be used by users, see the default TypeParameters are generally correct
@@ -133,6 +167,7 @@ where
     query: Pin<Box<dyn Future<Output = Result<Lookup, ResolveError>> + Send>>,
     hosts: Option<Arc<Hosts>>,
     finally_ip_addr: Option<RData>,
+    spawner: Option<BackgroundSpawner>,
 }
 
 impl<C, E> Future for LookupIpFuture<C, E>
@@ -169,6 +204,7 @@ where
                         self.client_cache.clone(),
                         self.options,
                         self.hosts.clone(),
+                        self.spawner.clone(),
                     )
                     .boxed();
                     // Continue looping with the new query. It will be polled
@@ -227,8 +263,15 @@ where
             options,
             hosts,
             finally_ip_addr,
+            spawner: None,
         }
     }
+
+    /// Sets the spawner for lookups which complete in the background, for the parallel strategies
+    pub(crate) fn with_spawner(mut self, spawner: BackgroundSpawner) -> Self {
+        self.spawner = Some(spawner);
+        self
+    }
 }
 
 /// returns a new future for lookup
@@ -238,6 +281,7 @@ async fn strategic_lookup<C, E>(
     client: CachingClient<C, E>,
     options: DnsRequestOptions,
     hosts: Option<Arc<Hosts>>,
+    spawner: Option<BackgroundSpawner>,
 ) -> Result<Lookup, ResolveError>
 where
     C: DnsHandle<Error = E> + 'static,
@@ -249,6 +293,30 @@ where
         LookupIpStrategy::Ipv4AndIpv6 => ipv4_and_ipv6(name, client, options, hosts).await,
         LookupIpStrategy::Ipv6thenIpv4 => ipv6_then_ipv4(name, client, options, hosts).await,
         LookupIpStrategy::Ipv4thenIpv6 => ipv4_then_ipv6(name, client, options, hosts).await,
+        LookupIpStrategy::Ipv4PreferredParallel => {
+            preferred_parallel(
+                name,
+                client,
+                RecordType::A,
+                RecordType::AAAA,
+                options,
+                hosts,
+                spawner,
+            )
+            .await
+        }
+        LookupIpStrategy::Ipv6PreferredParallel => {
+            preferred_parallel(
+                name,
+                client,
+                RecordType::AAAA,
+                RecordType::A,
+                options,
+                hosts,
+                spawner,
+            )
+            .await
+        }
     }
 }
 
@@ -447,6 +515,59 @@ where
     }
 }
 
+/// queries for preferred_type and other_type in parallel, returning as soon as preferred_type has results
+///
+/// If the preferred_type has results first, the other_type lookup is completed in the background,
+///  so that its results are cached.
+async fn preferred_parallel<C, E>(
+    name: Name,
+    client: CachingClient<C, E>,
+    preferred_type: RecordType,
+    other_type: RecordType,
+    options: DnsRequestOptions,
+    hosts: Option<Arc<Hosts>>,
+    spawner: Option<BackgroundSpawner>,
+) -> Result<Lookup, ResolveError>
+where
+    C: DnsHandle<Error = E> + 'static,
+    E: Into<ResolveError> + From<ProtoError> + Error + Clone + Send + Unpin + 'static,
+{
+    let preferred = hosts_lookup(
+        Query::query(name.clone(), preferred_type),
+        client.clone(),
+        options,
+        hosts.clone(),
+    )
+    .boxed();
+    let other = hosts_lookup(Query::query(name, other_type), client, options, hosts).boxed();
+
+    match future::select(preferred, other).await {
+        Either::Left((Ok(ips), other)) if !ips.is_empty() => {
+            if let Some(spawner) = spawner {
+                spawner(other.map(drop).boxed());
+            }
+            Ok(ips)
+        }
+        Either::Left((preferred_res, other)) => {
+            debug!(
+                "preferred {} lookup had no results in parallel strategy",
+                preferred_type
+            );
+            match other.await {
+                Ok(ips) if !ips.is_empty() => Ok(ips),
+                other_res => preferred_res.and(other_res),
+            }
+        }
+        Either::Right((other_res, preferred)) => match preferred.await {
+            Ok(ips) if !ips.is_empty() => Ok(ips),
+            preferred_res => match other_res {
+                Ok(ips) if !ips.is_empty() => Ok(ips),
+                _ => preferred_res,
+            },
+        },
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
@@ -557,6 +678,70 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_preferred_parallel_strategy() {
+        // ipv4 is preferred, and the ipv6 lookup is sent to the background
+        let spawned = Arc::new(Mutex::new(Vec::new()));
+        let spawned_cpy = spawned.clone();
+        let spawner: BackgroundSpawner = Arc::new(move |future| {
+            spawned_cpy.lock().unwrap().push(future);
+        });
+
+        assert_eq!(
+            block_on(preferred_parallel(
+                Name::root(),
+                CachingClient::new(0, mock(vec![v6_message(), v4_message()]), false),
+                RecordType::A,
+                RecordType::AAAA,
+                DnsRequestOptions::default(),
+                None,
+                Some(spawner),
+            ))
+            .unwrap()
+            .iter()
+            .map(|r| r.to_ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+        assert_eq!(spawned.lock().unwrap().len(), 1);
+
+        // no ipv4, ipv6 is returned
+        assert_eq!(
+            block_on(preferred_parallel(
+                Name::root(),
+                CachingClient::new(0, mock(vec![v6_message(), empty()]), false),
+                RecordType::A,
+                RecordType::AAAA,
+                DnsRequestOptions::default(),
+                None,
+                None,
+            ))
+            .unwrap()
+            .iter()
+            .map(|r| r.to_ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]
+        );
+
+        // error then ipv6
+        assert_eq!(
+            block_on(preferred_parallel(
+                Name::root(),
+                CachingClient::new(0, mock(vec![v6_message(), error()]), false),
+                RecordType::A,
+                RecordType::AAAA,
+                DnsRequestOptions::default(),
+                None,
+                None,
+            ))
+            .unwrap()
+            .iter()
+            .map(|r| r.to_ip_addr().unwrap())
+            .collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]
+        );
+    }
+
     #[test]
     fn test_ipv4_and_ipv6_strategy() {
         // ipv6 is consistently queried first (even though the select has it second)
@@ -0,0 +1,134 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Resolver instrumentation, exported through the `metrics` facade
+//!
+//! All functions are no-ops unless the `metrics` feature is enabled. Applications install a
+//!  recorder, e.g. a Prometheus or StatsD exporter, to collect them.
+
+use std::time::Duration;
+
+use proto::rr::RecordType;
+
+use crate::config::Protocol;
+use crate::error::ResolveError;
+#[cfg(feature = "metrics")]
+use crate::error::ResolveErrorKind;
+
+#[cfg(feature = "metrics")]
+const LOOKUPS: &str = "trust_dns_resolver_lookups_total";
+#[cfg(feature = "metrics")]
+const CACHE_HITS: &str = "trust_dns_resolver_cache_hits_total";
+#[cfg(feature = "metrics")]
+const CACHE_MISSES: &str = "trust_dns_resolver_cache_misses_total";
+#[cfg(feature = "metrics")]
+const UPSTREAM_RTT: &str = "trust_dns_resolver_upstream_rtt_seconds";
+#[cfg(feature = "metrics")]
+const RETRIES: &str = "trust_dns_resolver_retries_total";
+#[cfg(feature = "metrics")]
+const ERRORS: &str = "trust_dns_resolver_errors_total";
+#[cfg(feature = "metrics")]
+const CIRCUIT_OPENS: &str = "trust_dns_resolver_circuit_opens_total";
+
+/// Registers the descriptions of all metrics emitted by the resolver with the installed recorder
+///
+/// This is optional, recorders will still receive the metrics without it.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub fn describe_metrics() {
+    ::metrics::describe_counter!(LOOKUPS, "lookups performed, by record type");
+    ::metrics::describe_counter!(CACHE_HITS, "lookups answered from the cache");
+    ::metrics::describe_counter!(CACHE_MISSES, "lookups sent to an upstream name server");
+    ::metrics::describe_histogram!(
+        UPSTREAM_RTT,
+        ::metrics::Unit::Seconds,
+        "round trip time of requests to upstream name servers, by protocol"
+    );
+    ::metrics::describe_counter!(RETRIES, "requests retried over another connection");
+    ::metrics::describe_counter!(ERRORS, "failed lookups, by error kind");
+    ::metrics::describe_counter!(
+        CIRCUIT_OPENS,
+        "upstream name servers removed from selection after consecutive failures"
+    );
+}
+
+/// A lookup for the record type was started
+pub(crate) fn lookup(record_type: RecordType) {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(LOOKUPS, "record_type" => record_type.to_string());
+    #[cfg(not(feature = "metrics"))]
+    let _ = record_type;
+}
+
+/// The lookup was answered from the cache
+pub(crate) fn cache_hit() {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(CACHE_HITS);
+}
+
+/// The lookup was not in the cache, and will be sent upstream
+pub(crate) fn cache_miss() {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(CACHE_MISSES);
+}
+
+/// An upstream name server responded after `rtt`
+pub(crate) fn upstream_rtt(protocol: Protocol, rtt: Duration) {
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!(UPSTREAM_RTT, rtt, "protocol" => protocol.to_string());
+    #[cfg(not(feature = "metrics"))]
+    let _ = (protocol, rtt);
+}
+
+/// A request is retried, e.g. over TCP after a truncated UDP response, or after a busy name server
+pub(crate) fn retry() {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(RETRIES);
+}
+
+/// An upstream name server failed too often, it is not queried until its cool-down ends
+pub(crate) fn circuit_open() {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(CIRCUIT_OPENS);
+}
+
+/// The lookup failed with the error
+pub(crate) fn error(error: &ResolveError) {
+    #[cfg(feature = "metrics")]
+    ::metrics::increment_counter!(ERRORS, "kind" => error_kind(error.kind()));
+    #[cfg(not(feature = "metrics"))]
+    let _ = error;
+}
+
+#[cfg(feature = "metrics")]
+fn error_kind(kind: &ResolveErrorKind) -> &'static str {
+    match kind {
+        ResolveErrorKind::Message(_) | ResolveErrorKind::Msg(_) => "message",
+        ResolveErrorKind::NoConnections => "no_connections",
+        ResolveErrorKind::NoRecordsFound { .. } => "no_records_found",
+        ResolveErrorKind::Io(_) => "io",
+        ResolveErrorKind::Proto(_) => "proto",
+        ResolveErrorKind::Timeout => "timeout",
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            error_kind(ResolveError::from(ResolveErrorKind::Timeout).kind()),
+            "timeout"
+        );
+        assert_eq!(
+            error_kind(ResolveError::no_connections().kind()),
+            "no_connections"
+        );
+    }
+}
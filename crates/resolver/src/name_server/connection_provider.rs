@@ -6,10 +6,12 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::marker::Unpin;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use futures_util::future::{Future, FutureExt};
+use futures_channel::oneshot;
+use futures_util::future::{BoxFuture, Future, FutureExt};
 use futures_util::ready;
 use futures_util::stream::{Stream, StreamExt};
 #[cfg(feature = "tokio-runtime")]
@@ -69,12 +71,43 @@ pub trait ConnectionProvider: 'static + Clone + Send + Sync + Unpin {
     /// The returned handle should
     fn new_connection(&self, config: &NameServerConfig, options: &ResolverOpts)
         -> Self::FutureConn;
+
+    /// Spawns the future as a background task on the runtime of the connections
+    ///
+    /// This is used for work which should complete even though its result is not awaited, e.g.
+    ///  populating the cache. The default implementation does not support spawning, and drops
+    ///  the future.
+    fn spawn_bg<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        drop(future)
+    }
+
+    /// Runs the blocking function where it does not block the executor, and returns its result
+    ///
+    /// This is used for blocking system calls, e.g. querying the routing table for the source
+    ///  addresses of RFC 6724 address selection. The default implementation runs the function on
+    ///  a new thread.
+    fn spawn_blocking<F, T>(&self, f: F) -> BoxFuture<'static, Result<T, ResolveError>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        spawn_thread(f)
+            .map(|result| result.map_err(ResolveError::from))
+            .boxed()
+    }
 }
 
 /// RuntimeProvider defines which async runtime that handles IO and timers.
+///
+/// All connections of the resolver, and their background tasks and timeouts, are created through
+///  the provider, so implementing this for an executor other than Tokio, e.g. async-std or smol,
+///  is sufficient to run the resolver on it. See the `async-std-resolver` crate for an example.
 pub trait RuntimeProvider: Clone + 'static {
     /// Handle to the executor;
-    type Handle: Clone + Send + Spawn + Sync + Unpin;
+    type Handle: Clone + Send + Spawn + Sync + Unpin + 'static;
 
     /// Timer
     type Timer: Time + Send + Unpin;
@@ -92,6 +125,34 @@ pub trait Spawn {
     fn spawn_bg<F>(&mut self, future: F)
     where
         F: Future<Output = Result<(), ProtoError>> + Send + 'static;
+
+    /// Runs the blocking function on the blocking threads of the executor, and returns its result
+    ///
+    /// The default implementation runs the function on a new thread.
+    fn spawn_blocking<F, T>(&mut self, f: F) -> BoxFuture<'static, Result<T, ProtoError>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        spawn_thread(f)
+    }
+}
+
+/// Runs the function on a new thread, for executors without blocking threads
+fn spawn_thread<F, T>(f: F) -> BoxFuture<'static, Result<T, ProtoError>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        // the receiver is gone if the caller stopped waiting
+        let _ = sender.send(f());
+    });
+
+    receiver
+        .map(|result| result.map_err(|_| ProtoError::from("blocking function panicked")))
+        .boxed()
 }
 
 /// Standard connection implements the default mechanism for creating new Connections
@@ -123,17 +184,20 @@ where
     ) -> Self::FutureConn {
         let dns_connect = match config.protocol {
             Protocol::Udp => {
-                let stream = UdpClientStream::<R::Udp>::with_bind_addr_and_timeout(
+                let mut stream = UdpClientStream::<R::Udp>::with_bind_addr_and_timeout(
                     config.socket_addr,
-                    config.bind_addr,
+                    bind_addr_for(config, options),
                     options.timeout,
                 );
+                if let Some((start, end)) = options.udp_port_range {
+                    stream = stream.with_port_range(start..=end);
+                }
                 let exchange = DnsExchange::connect(stream);
                 ConnectionConnect::Udp(exchange)
             }
             Protocol::Tcp => {
                 let socket_addr = config.socket_addr;
-                let bind_addr = config.bind_addr;
+                let bind_addr = bind_addr_for(config, options);
                 let timeout = options.timeout;
 
                 let (stream, handle) = TcpClientStream::<R::Tcp>::with_bind_addr_and_timeout(
@@ -155,7 +219,7 @@ where
             #[cfg(feature = "dns-over-tls")]
             Protocol::Tls => {
                 let socket_addr = config.socket_addr;
-                let bind_addr = config.bind_addr;
+                let bind_addr = bind_addr_for(config, options);
                 let timeout = options.timeout;
                 let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
                 #[cfg(feature = "dns-over-rustls")]
@@ -179,7 +243,8 @@ where
                     handle,
                     timeout,
                     NoopMessageFinalizer::new(),
-                );
+                )
+                .with_padding(proto::op::PaddingPolicy::client());
 
                 let exchange = DnsExchange::connect(dns_conn);
                 ConnectionConnect::Tls(exchange)
@@ -187,7 +252,7 @@ where
             #[cfg(feature = "dns-over-https")]
             Protocol::Https => {
                 let socket_addr = config.socket_addr;
-                let bind_addr = config.bind_addr;
+                let bind_addr = bind_addr_for(config, options);
                 let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
                 #[cfg(feature = "dns-over-rustls")]
                 let client_config = config.tls_config.clone();
@@ -196,19 +261,21 @@ where
                     socket_addr,
                     bind_addr,
                     tls_dns_name,
+                    config.http_endpoint.clone(),
                     client_config,
+                    self.0.clone(),
                 );
                 ConnectionConnect::Https(exchange)
             }
             #[cfg(feature = "dns-over-quic")]
             Protocol::Quic => {
                 let socket_addr = config.socket_addr;
-                let bind_addr = config.bind_addr;
+                let bind_addr = bind_addr_for(config, options);
                 let tls_dns_name = config.tls_dns_name.clone().unwrap_or_default();
                 #[cfg(feature = "dns-over-rustls")]
                 let client_config = config.tls_config.clone();
 
-                let exchange = crate::quic::new_quic_stream(
+                let exchange = crate::quic::new_quic_stream::<R>(
                     socket_addr,
                     bind_addr,
                     tls_dns_name,
@@ -241,6 +308,37 @@ where
             spawner: self.0.clone(),
         }
     }
+
+    fn spawn_bg<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut spawner = self.0.clone();
+        spawner.spawn_bg(future.map(Ok));
+    }
+
+    fn spawn_blocking<F, T>(&self, f: F) -> BoxFuture<'static, Result<T, ResolveError>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut spawner = self.0.clone();
+        spawner
+            .spawn_blocking(f)
+            .map(|result| result.map_err(ResolveError::from))
+            .boxed()
+    }
+}
+
+/// The local address for connections to the name server, the `bind_addr` of the name server takes
+///  precedence over the `bind_addr` of the options
+fn bind_addr_for(config: &NameServerConfig, options: &ResolverOpts) -> Option<SocketAddr> {
+    config.bind_addr.or_else(|| {
+        options
+            .bind_addr
+            .filter(|ip| ip.is_ipv4() == config.socket_addr.is_ipv4())
+            .map(|ip| SocketAddr::new(ip, 0))
+    })
 }
 
 #[cfg(feature = "dns-over-tls")]
@@ -282,19 +380,19 @@ pub(crate) enum ConnectionConnect<R: RuntimeProvider> {
                 NoopMessageFinalizer,
             >,
             DnsMultiplexer<TlsClientStream<<R as RuntimeProvider>::Tcp>, NoopMessageFinalizer>,
-            TokioTime,
+            R::Timer,
         >,
     ),
     #[cfg(feature = "dns-over-https")]
-    Https(DnsExchangeConnect<HttpsClientConnect<R::Tcp>, HttpsClientStream, TokioTime>),
+    Https(DnsExchangeConnect<HttpsClientConnect<R::Tcp>, HttpsClientStream, R::Timer>),
     #[cfg(feature = "dns-over-quic")]
-    Quic(DnsExchangeConnect<QuicClientConnect, QuicClientStream, TokioTime>),
+    Quic(DnsExchangeConnect<QuicClientConnect, QuicClientStream, R::Timer>),
     #[cfg(feature = "mdns")]
     Mdns(
         DnsExchangeConnect<
             DnsMultiplexerConnect<MdnsClientConnect, MdnsClientStream, NoopMessageFinalizer>,
             DnsMultiplexer<MdnsClientStream, NoopMessageFinalizer>,
-            TokioTime,
+            R::Timer,
         >,
     ),
 }
@@ -396,6 +494,18 @@ pub mod tokio_runtime {
         {
             self.join_set.lock().unwrap().spawn(future);
         }
+
+        fn spawn_blocking<F, T>(&mut self, f: F) -> BoxFuture<'static, Result<T, ProtoError>>
+        where
+            F: FnOnce() -> T + Send + 'static,
+            T: Send + 'static,
+        {
+            tokio::task::spawn_blocking(f)
+                .map(|result| {
+                    result.map_err(|e| ProtoError::from(format!("blocking function failed: {}", e)))
+                })
+                .boxed()
+        }
     }
 
     /// The Tokio Runtime for async execution
@@ -414,3 +524,48 @@ pub mod tokio_runtime {
     /// An alias for Tokio use cases
     pub type TokioConnectionProvider = GenericConnectionProvider<TokioRuntime>;
 }
+
+#[cfg(feature = "smol-runtime")]
+#[cfg_attr(docsrs, doc(cfg(feature = "smol-runtime")))]
+#[allow(unreachable_pub)]
+pub mod smol_runtime {
+    use super::*;
+    use proto::smol::{SmolTcpStream, SmolTime, SmolUdpSocket};
+
+    /// A handle to the smol global executor
+    #[derive(Clone, Copy, Default)]
+    pub struct SmolHandle;
+
+    impl Spawn for SmolHandle {
+        fn spawn_bg<F>(&mut self, future: F)
+        where
+            F: Future<Output = Result<(), ProtoError>> + Send + 'static,
+        {
+            smol::spawn(future).detach();
+        }
+
+        fn spawn_blocking<F, T>(&mut self, f: F) -> BoxFuture<'static, Result<T, ProtoError>>
+        where
+            F: FnOnce() -> T + Send + 'static,
+            T: Send + 'static,
+        {
+            smol::unblock(f).map(Ok).boxed()
+        }
+    }
+
+    /// The smol Runtime for async execution
+    #[derive(Clone, Copy)]
+    pub struct SmolRuntime;
+    impl RuntimeProvider for SmolRuntime {
+        type Handle = SmolHandle;
+        type Tcp = SmolTcpStream;
+        type Timer = SmolTime;
+        type Udp = SmolUdpSocket;
+    }
+
+    /// An alias for smol use cases
+    pub type SmolConnection = GenericConnection;
+
+    /// An alias for smol use cases
+    pub type SmolConnectionProvider = GenericConnectionProvider<SmolRuntime>;
+}
@@ -10,6 +10,7 @@
 mod connection_provider;
 #[allow(clippy::module_inception)]
 mod name_server;
+mod name_server_backoff;
 mod name_server_pool;
 mod name_server_state;
 mod name_server_stats;
@@ -20,10 +21,16 @@ pub use self::connection_provider::{GenericConnection, GenericConnectionProvider
 #[cfg_attr(docsrs, doc(cfg(feature = "mdns")))]
 pub(crate) use self::name_server::mdns_nameserver;
 pub use self::name_server::NameServer;
+use self::name_server_backoff::{Health, NameServerBackoff};
 pub use self::name_server_pool::NameServerPool;
 use self::name_server_state::NameServerState;
 use self::name_server_stats::NameServerStats;
 
+#[cfg(feature = "smol-runtime")]
+#[cfg_attr(docsrs, doc(cfg(feature = "smol-runtime")))]
+pub use self::connection_provider::smol_runtime::{
+    SmolConnection, SmolConnectionProvider, SmolHandle, SmolRuntime,
+};
 #[cfg(feature = "tokio-runtime")]
 #[cfg_attr(docsrs, doc(cfg(feature = "tokio-runtime")))]
 pub use self::connection_provider::tokio_runtime::{
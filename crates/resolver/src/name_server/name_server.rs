@@ -22,8 +22,11 @@ use tracing::debug;
 #[cfg(feature = "mdns")]
 use crate::config::Protocol;
 use crate::config::{NameServerConfig, ResolverOpts};
-use crate::error::ResolveError;
-use crate::name_server::{ConnectionProvider, NameServerState, NameServerStats};
+use crate::error::{ResolveError, ResolveErrorKind};
+use crate::metrics;
+use crate::name_server::{
+    ConnectionProvider, Health, NameServerBackoff, NameServerState, NameServerStats,
+};
 #[cfg(feature = "tokio-runtime")]
 use crate::name_server::{TokioConnection, TokioConnectionProvider, TokioHandle};
 
@@ -38,6 +41,7 @@ pub struct NameServer<
     client: Arc<Mutex<Option<C>>>,
     state: Arc<NameServerState>,
     stats: Arc<NameServerStats>,
+    backoff: Arc<NameServerBackoff>,
     conn_provider: P,
 }
 
@@ -71,6 +75,7 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> NameSe
             client: Arc::new(Mutex::new(None)),
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            backoff: Arc::new(NameServerBackoff::default()),
             conn_provider,
         }
     }
@@ -88,6 +93,7 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> NameSe
             client: Arc::new(Mutex::new(Some(client))),
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            backoff: Arc::new(NameServerBackoff::default()),
             conn_provider,
         }
     }
@@ -138,10 +144,14 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> NameSe
         request: R,
     ) -> Result<DnsResponse, ResolveError> {
         let mut client = self.connected_mut_client().await?;
+        let now = Instant::now();
         let response = client.send(request).first_answer().await;
 
         match response {
             Ok(response) => {
+                metrics::upstream_rtt(self.config.protocol, now.elapsed());
+                self.backoff.success();
+
                 // First evaluate if the message succeeded.
                 let response =
                     ResolveError::from_response(response, self.config.trust_nx_responses)?;
@@ -165,12 +175,22 @@ impl<C: DnsHandle<Error = ResolveError>, P: ConnectionProvider<Conn = C>> NameSe
                 // record the failure
                 self.stats.next_failure();
 
+                // busy connections are not failing, they are tried again after the others
+                if !matches!(error.kind(), ResolveErrorKind::Proto(e) if e.is_busy()) {
+                    self.backoff.failure(Instant::now(), &self.options);
+                }
+
                 // These are connection failures, not lookup failures, that is handled in the resolver layer
                 Err(error)
             }
         }
     }
 
+    /// The health of the name server, whether it is queried and in which order, see [`Health`]
+    pub(crate) fn health(&self, now: Instant) -> Health {
+        self.backoff.select(now, &self.options)
+    }
+
     /// Specifies that thie NameServer will treat negative responses as permanent failures and will not retry
     pub fn trust_nx_responses(&self) -> bool {
         self.config.trust_nx_responses
@@ -258,6 +278,7 @@ where
         #[cfg(feature = "dns-over-rustls")]
         tls_config: None,
         bind_addr: None,
+        http_endpoint: None,
     };
     NameServer::new_with_provider(config, options, conn_provider)
 }
@@ -290,6 +311,7 @@ mod tests {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         };
         let io_loop = Runtime::new().unwrap();
         let runtime_handle = TokioHandle::default();
@@ -329,6 +351,7 @@ mod tests {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         };
         let io_loop = Runtime::new().unwrap();
         let runtime_handle = TokioHandle::default();
@@ -0,0 +1,204 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The backoff of failing name servers, and the circuit breaker which stops querying them
+
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rand::Rng;
+use tracing::debug;
+
+use crate::config::ResolverOpts;
+use crate::metrics;
+
+/// Whether a name server is queried, in the order they are queried
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Health {
+    /// The name server did not fail since its last response, or its cool-down ended
+    Healthy,
+    /// The cool-down of the open circuit ended, this query probes the name server
+    Probe,
+    /// The name server failed recently, it is queried after the healthy ones
+    Backoff,
+    /// The name server failed too often, it is not queried until its cool-down ends
+    Open,
+}
+
+#[derive(Debug, Default)]
+struct BackoffState {
+    /// The consecutive failures since the last response
+    failures: usize,
+    cool_down: Duration,
+    retry_at: Option<Instant>,
+}
+
+/// The backoff of a name server after connection failures and timeouts
+///
+/// Each consecutive failure doubles the cool-down of the name server, from
+///  [`ResolverOpts::failure_backoff`] up to [`ResolverOpts::max_failure_backoff`], jittered so that
+///  the name servers which failed together are not retried together. During the cool-down the name
+///  server is queried after the others. After [`ResolverOpts::circuit_breaker_failures`]
+///  consecutive failures the circuit is open, the name server is not queried at all until the
+///  cool-down ends, then one query probes it. A response closes the circuit.
+pub(crate) struct NameServerBackoff {
+    state: Mutex<BackoffState>,
+}
+
+impl Default for NameServerBackoff {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(BackoffState::default()),
+        }
+    }
+}
+
+impl NameServerBackoff {
+    /// The health of the name server, claims the probe if the cool-down of the open circuit ended
+    ///
+    /// Only one query probes the name server, the next ones see an open circuit until the probe
+    ///  succeeds, or until it is probed again after another cool-down.
+    pub(crate) fn select(&self, now: Instant, options: &ResolverOpts) -> Health {
+        let mut state = self.state.lock();
+        let is_open = options.circuit_breaker_failures > 0
+            && state.failures >= options.circuit_breaker_failures;
+
+        match state.retry_at {
+            Some(retry_at) if now < retry_at && is_open => Health::Open,
+            Some(retry_at) if now < retry_at => Health::Backoff,
+            Some(_) if is_open => {
+                state.retry_at = Some(now + jitter(state.cool_down));
+                Health::Probe
+            }
+            _ => Health::Healthy,
+        }
+    }
+
+    /// Records a response of the name server, which closes the circuit
+    pub(crate) fn success(&self) {
+        let mut state = self.state.lock();
+        if state.failures > 0 {
+            *state = BackoffState::default();
+        }
+    }
+
+    /// Records a failure of the name server, e.g. a timeout, and starts its next cool-down
+    pub(crate) fn failure(&self, now: Instant, options: &ResolverOpts) {
+        let mut state = self.state.lock();
+        state.failures += 1;
+        state.cool_down = if state.failures == 1 {
+            options.failure_backoff
+        } else {
+            state.cool_down * 2
+        }
+        .min(options.max_failure_backoff);
+        state.retry_at = Some(now + jitter(state.cool_down));
+
+        if state.failures == options.circuit_breaker_failures {
+            debug!(
+                "opening circuit after {} failures, for {:?}",
+                state.failures, state.cool_down
+            );
+            metrics::circuit_open();
+        }
+    }
+}
+
+/// A random duration between half and all of the cool-down
+fn jitter(cool_down: Duration) -> Duration {
+    if cool_down.is_zero() {
+        return cool_down;
+    }
+
+    rand::thread_rng().gen_range(cool_down / 2..=cool_down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> ResolverOpts {
+        ResolverOpts {
+            failure_backoff: Duration::from_secs(1),
+            max_failure_backoff: Duration::from_secs(4),
+            circuit_breaker_failures: 3,
+            ..ResolverOpts::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let options = options();
+        let now = Instant::now();
+        let backoff = NameServerBackoff::default();
+        assert_eq!(backoff.select(now, &options), Health::Healthy);
+
+        backoff.failure(now, &options);
+        assert_eq!(backoff.select(now, &options), Health::Backoff);
+        assert_eq!(
+            backoff.select(now + Duration::from_secs(1), &options),
+            Health::Healthy
+        );
+
+        // the cool-down doubles, at least half of it is waited
+        backoff.failure(now, &options);
+        assert_eq!(
+            backoff.select(now + Duration::from_millis(999), &options),
+            Health::Backoff
+        );
+        assert_eq!(
+            backoff.select(now + Duration::from_secs(2), &options),
+            Health::Healthy
+        );
+
+        backoff.success();
+        assert_eq!(backoff.select(now, &options), Health::Healthy);
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let options = options();
+        let now = Instant::now();
+        let backoff = NameServerBackoff::default();
+        for _ in 0..3 {
+            backoff.failure(now, &options);
+        }
+        assert_eq!(backoff.select(now, &options), Health::Open);
+
+        // one query probes the name server once the cool-down ends
+        let later = now + Duration::from_secs(4);
+        assert_eq!(backoff.select(later, &options), Health::Probe);
+        assert_eq!(backoff.select(later, &options), Health::Open);
+
+        // a failed probe reopens the circuit, with the longest cool-down
+        backoff.failure(later, &options);
+        assert_eq!(
+            backoff.select(later + Duration::from_millis(1999), &options),
+            Health::Open
+        );
+        let probe = later + Duration::from_secs(4);
+        assert_eq!(backoff.select(probe, &options), Health::Probe);
+
+        // a response closes the circuit
+        backoff.success();
+        assert_eq!(backoff.select(probe, &options), Health::Healthy);
+    }
+
+    #[test]
+    fn test_circuit_breaker_disabled() {
+        let options = ResolverOpts {
+            circuit_breaker_failures: 0,
+            ..options()
+        };
+        let now = Instant::now();
+        let backoff = NameServerBackoff::default();
+        for _ in 0..10 {
+            backoff.failure(now, &options);
+        }
+        assert_eq!(backoff.select(now, &options), Health::Backoff);
+    }
+}
@@ -9,21 +9,25 @@ use std::cmp::Ordering;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures_util::future::FutureExt;
 use futures_util::stream::{once, FuturesUnordered, Stream, StreamExt};
 use smallvec::SmallVec;
 
+use proto::rr::Name;
 use proto::xfer::{DnsHandle, DnsRequest, DnsResponse, FirstAnswer};
 use proto::Time;
 use tracing::debug;
 
+#[cfg(feature = "dns-over-rustls")]
+use crate::config::TlsClientConfig;
 use crate::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts, ServerOrderingStrategy};
 use crate::error::{ResolveError, ResolveErrorKind};
+use crate::metrics;
 #[cfg(feature = "mdns")]
 use crate::name_server;
-use crate::name_server::{ConnectionProvider, NameServer};
+use crate::name_server::{ConnectionProvider, Health, NameServer};
 #[cfg(test)]
 #[cfg(feature = "tokio-runtime")]
 use crate::name_server::{TokioConnection, TokioConnectionProvider, TokioHandle};
@@ -42,6 +46,8 @@ pub struct NameServerPool<
     #[cfg(feature = "mdns")]
     mdns_conns: NameServer<C, P>, /* All NameServers must be the same type */
     options: ResolverOpts,
+    // pools for the domains of the forward rules, see `ResolverConfig::add_forward_rule`
+    routes: Arc<[(Name, Self)]>,
 }
 
 #[cfg(test)]
@@ -102,12 +108,36 @@ where
             })
             .collect();
 
+        let routes: Vec<(Name, Self)> = config
+            .forward_rules()
+            .iter()
+            .map(|rule| {
+                #[allow(unused_mut)]
+                let mut rule_config =
+                    ResolverConfig::from_parts(None, vec![], rule.name_servers().clone());
+                #[cfg(feature = "dns-over-rustls")]
+                {
+                    if let (None, Some(TlsClientConfig(client_config))) =
+                        (rule_config.client_config(), config.client_config())
+                    {
+                        rule_config.set_tls_client_config(client_config.clone());
+                    }
+                }
+
+                let options = rule.options().unwrap_or(options);
+                let pool =
+                    Self::from_config_with_provider(&rule_config, options, conn_provider.clone());
+                (rule.domain().clone(), pool)
+            })
+            .collect();
+
         Self {
             datagram_conns: Arc::from(datagram_conns),
             stream_conns: Arc::from(stream_conns),
             #[cfg(feature = "mdns")]
             mdns_conns: name_server::mdns_nameserver(*options, conn_provider.clone(), false),
             options: *options,
+            routes: Arc::from(routes),
         }
     }
 
@@ -135,6 +165,7 @@ where
             #[cfg(feature = "mdns")]
             mdns_conns: name_server::mdns_nameserver(*options, conn_provider.clone(), false),
             options: *options,
+            routes: Arc::from(Vec::new()),
         }
     }
 
@@ -149,6 +180,7 @@ where
             datagram_conns: Arc::from(datagram_conns),
             stream_conns: Arc::from(stream_conns),
             options: *options,
+            routes: Arc::from(Vec::new()),
         }
     }
 
@@ -165,6 +197,7 @@ where
             stream_conns: Arc::from(stream_conns),
             mdns_conns,
             options: *options,
+            routes: Arc::from(Vec::new()),
         }
     }
 
@@ -180,6 +213,7 @@ where
             datagram_conns,
             stream_conns,
             options: *options,
+            routes: Arc::from(Vec::new()),
         }
     }
 
@@ -196,10 +230,21 @@ where
             stream_conns,
             mdns_conns,
             options: *options,
+            routes: Arc::from(Vec::new()),
             conn_provider,
         }
     }
 
+    /// Returns the pool of the most specific forward rule for the query, if any
+    fn route(&self, request: &DnsRequest) -> Option<Self> {
+        let name = request.queries().first()?.name();
+        self.routes
+            .iter()
+            .filter(|(domain, _)| domain.zone_of(name))
+            .max_by_key(|(domain, _)| domain.num_labels())
+            .map(|(_, pool)| pool.clone())
+    }
+
     async fn try_send(
         opts: ResolverOpts,
         conns: Arc<[NameServer<C, P>]>,
@@ -214,12 +259,35 @@ where
             ServerOrderingStrategy::QueryStatistics => conns.sort_unstable(),
             ServerOrderingStrategy::UserProvidedOrder => {}
         }
+
+        let now = Instant::now();
+        let conns = order_by_health(
+            conns
+                .into_iter()
+                .map(|conn| (conn.health(now), conn))
+                .collect(),
+        );
         let request_loop = request.clone();
 
         parallel_conn_loop(conns, request_loop, opts).await
     }
 }
 
+/// Orders the name servers by their health, the order is otherwise kept
+///
+/// The healthy name servers are queried first, then those whose circuit can close are probed,
+///  those in backoff are queried last, and those whose circuit is open are skipped, unless all of
+///  them are.
+fn order_by_health<T>(mut conns: Vec<(Health, T)>) -> Vec<T> {
+    conns.sort_by_key(|(health, _)| *health);
+    if conns.iter().any(|(health, _)| *health != Health::Open) {
+        conns.retain(|(health, _)| *health != Health::Open);
+    } else {
+        debug!("the circuits of all name servers are open");
+    }
+    conns.into_iter().map(|(_, conn)| conn).collect()
+}
+
 impl<C, P> DnsHandle for NameServerPool<C, P>
 where
     C: DnsHandle<Error = ResolveError> + Sync + 'static,
@@ -229,8 +297,13 @@ where
     type Error = ResolveError;
 
     fn send<R: Into<DnsRequest>>(&mut self, request: R) -> Self::Response {
-        let opts = self.options;
         let request = request.into();
+        if let Some(mut pool) = self.route(&request) {
+            debug!("forwarding request: {:?}", request.queries());
+            return pool.send(request);
+        }
+
+        let opts = self.options;
         let datagram_conns = Arc::clone(&self.datagram_conns);
         let stream_conns = Arc::clone(&self.stream_conns);
         // TODO: remove this clone, return the Message in the error?
@@ -274,6 +347,8 @@ where
                 return udp_res;
             }
 
+            metrics::retry();
+
             // Try query over TCP, as response to query over UDP was either truncated or was an
             // error.
             let tcp_res = Self::try_send(opts, stream_conns, tcp_message).await;
@@ -335,6 +410,7 @@ where
         if par_conns.is_empty() {
             if !busy.is_empty() && backoff < Duration::from_millis(300) {
                 P::Time::delay_for(backoff).await;
+                metrics::retry();
                 conns.extend(busy.drain(..));
                 backoff *= 2;
                 continue;
@@ -479,6 +555,7 @@ mod tests {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         };
 
         let config2 = NameServerConfig {
@@ -489,6 +566,7 @@ mod tests {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         };
 
         let mut resolver_config = ResolverConfig::new();
@@ -551,6 +629,7 @@ mod tests {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         };
 
         let opts = ResolverOpts {
@@ -622,4 +701,69 @@ mod tests {
             "if this is failing then the NameServers aren't being properly shared."
         );
     }
+
+    #[test]
+    fn test_order_by_health() {
+        let conns = vec![
+            (Health::Backoff, "backoff"),
+            (Health::Open, "open"),
+            (Health::Probe, "probe"),
+            (Health::Healthy, "healthy"),
+            (Health::Healthy, "second healthy"),
+        ];
+        assert_eq!(
+            order_by_health(conns),
+            vec!["healthy", "second healthy", "probe", "backoff"]
+        );
+
+        // all circuits are open, they are all queried
+        let conns = vec![(Health::Open, "open"), (Health::Open, "second open")];
+        assert_eq!(order_by_health(conns), vec!["open", "second open"]);
+    }
+
+    #[test]
+    fn test_forward_rule_routing() {
+        use crate::config::ForwardRule;
+        use proto::op::Message;
+
+        let mut resolver_config = ResolverConfig::google();
+        resolver_config.add_forward_rule(ForwardRule::new(
+            Name::from_str("corp.example.").unwrap(),
+            NameServerConfigGroup::from_ips_clear(
+                &[IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53))],
+                53,
+                true,
+            ),
+        ));
+        resolver_config.add_forward_rule(ForwardRule::new(
+            Name::from_str("lab.corp.example.").unwrap(),
+            NameServerConfigGroup::from_ips_clear(
+                &[
+                    IpAddr::V4(Ipv4Addr::new(10, 1, 0, 53)),
+                    IpAddr::V4(Ipv4Addr::new(10, 1, 1, 53)),
+                ],
+                53,
+                true,
+            ),
+        ));
+
+        let pool = NameServerPool::<_, TokioConnectionProvider>::tokio_from_config(
+            &resolver_config,
+            &ResolverOpts::default(),
+            TokioHandle::default(),
+        );
+
+        let route = |name: &str| {
+            let mut message = Message::new();
+            message.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+            pool.route(&DnsRequest::new(message, DnsRequestOptions::default()))
+                .map(|pool| pool.datagram_conns.len())
+        };
+
+        assert_eq!(route("www.example.com."), None);
+        assert_eq!(route("corp.example."), Some(1));
+        assert_eq!(route("www.corp.example."), Some(1));
+        assert_eq!(route("www.lab.corp.example."), Some(2));
+        assert_eq!(route("WWW.LAB.CORP.EXAMPLE."), Some(2));
+    }
 }
@@ -9,19 +9,19 @@ use rustls::ClientConfig as CryptoConfig;
 use std::net::SocketAddr;
 
 use proto::xfer::{DnsExchange, DnsExchangeConnect};
-use proto::TokioTime;
 use trust_dns_proto::quic::{QuicClientConnect, QuicClientStream};
 
 use crate::config::TlsClientConfig;
+use crate::name_server::RuntimeProvider;
 use crate::tls::CLIENT_CONFIG;
 
 #[allow(clippy::type_complexity)]
-pub(crate) fn new_quic_stream(
+pub(crate) fn new_quic_stream<R: RuntimeProvider>(
     socket_addr: SocketAddr,
     bind_addr: Option<SocketAddr>,
     dns_name: String,
     client_config: Option<TlsClientConfig>,
-) -> DnsExchangeConnect<QuicClientConnect, QuicClientStream, TokioTime> {
+) -> DnsExchangeConnect<QuicClientConnect, QuicClientStream, R::Timer> {
     let client_config = client_config.map_or_else(
         || CLIENT_CONFIG.clone(),
         |TlsClientConfig(client_config)| client_config,
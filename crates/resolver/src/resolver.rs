@@ -6,16 +6,19 @@
 // copied, modified, or distributed except according to those terms.
 
 //! Structs for creating and using a Resolver
+use std::future::Future;
 use std::io;
-use std::net::IpAddr;
-use std::sync::Mutex;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc;
 
+use proto::error::ProtoResult;
 use proto::rr::domain::TryParseIp;
-use proto::rr::IntoName;
 use proto::rr::RecordType;
+use proto::rr::{IntoName, Name, RData};
 use tokio::runtime::{self, Runtime};
 
-use crate::config::{ResolverConfig, ResolverOpts};
+use crate::blocklist::Blocklist;
+use crate::config::{LookupOptions, ResolverConfig, ResolverOpts, ServiceHints};
 use crate::error::*;
 use crate::lookup;
 use crate::lookup::Lookup;
@@ -27,14 +30,11 @@ use crate::AsyncResolver;
 ///
 /// For forward (A) lookups, hostname -> IP address, see: `Resolver::lookup_ip`
 ///
-/// Special note about resource consumption. The Resolver and all Trust-DNS software is built around the Tokio async-io library. This synchronous Resolver is intended to be a simpler wrapper for of the [`AsyncResolver`]. To allow the `Resolver` to be [`Send`] + [`Sync`], the construction of the `AsyncResolver` is lazy, this means some of the features of the `AsyncResolver`, like performance based resolution via the most efficient `NameServer` will be lost (the lookup cache is shared across invocations of the `Resolver`). If these other features of the Trust-DNS Resolver are desired, please use the tokio based [`AsyncResolver`].
+/// Special note about resource consumption. The Resolver and all Trust-DNS software is built around the Tokio async-io library. This synchronous Resolver is a wrapper of the [`AsyncResolver`], which runs on a background thread owned by the `Resolver`. Lookups are submitted to the background thread, and the calling thread blocks until the result is sent back, so connections, the lookup cache and the name server statistics are kept for the life of the `Resolver`.
 ///
-/// *Note: Threaded/Sync usage*: In multithreaded scenarios, the internal Tokio Runtime will block on an internal Mutex for the tokio Runtime in use. For higher performance, it's recommended to use the [`AsyncResolver`].
+/// *Note: Threaded/Sync usage*: The `Resolver` is [`Send`] + [`Sync`], and lookups from multiple threads run concurrently on the background thread. The background thread is stopped when the `Resolver` is dropped, which must not happen within an async context. For applications already running Tokio, it's recommended to use the [`AsyncResolver`].
 pub struct Resolver {
-    // TODO: Mutex allows this to be Sync, another option would be to instantiate a thread_local, but that has other
-    //   drawbacks. One major issues, is if this Resolver is shared across threads, it will cause all to block on any
-    //   query. A TLS on the other hand would not, at the cost of only allowing a Resolver to be configured once per Thread
-    runtime: Mutex<Runtime>,
+    runtime: Runtime,
     async_resolver: AsyncResolver<TokioConnection, TokioConnectionProvider>,
 }
 
@@ -48,8 +48,9 @@ macro_rules! lookup_fn {
         ///
         /// * `query` - a `&str` which parses to a domain name, failure to parse will return an error
         pub fn $p<N: IntoName>(&self, query: N) -> ResolveResult<$l> {
-            let lookup = self.async_resolver.$p(query);
-            self.runtime.lock()?.block_on(lookup)
+            let name = query.into_name()?;
+            let resolver = self.async_resolver.clone();
+            self.block_on(async move { resolver.$p(name).await })
         }
     };
     ($p:ident, $l:ty, $t:ty) => {
@@ -59,8 +60,8 @@ macro_rules! lookup_fn {
         ///
         /// * `query` - a type which can be converted to `Name` via `From`.
         pub fn $p(&self, query: $t) -> ResolveResult<$l> {
-            let lookup = self.async_resolver.$p(query);
-            self.runtime.lock()?.block_on(lookup)
+            let resolver = self.async_resolver.clone();
+            self.block_on(async move { resolver.$p(query).await })
         }
     };
 }
@@ -76,15 +77,17 @@ impl Resolver {
     ///
     /// A new `Resolver` or an error if there was an error with the configuration.
     pub fn new(config: ResolverConfig, options: ResolverOpts) -> io::Result<Self> {
-        let mut builder = runtime::Builder::new_current_thread();
-        builder.enable_all();
+        let mut builder = runtime::Builder::new_multi_thread();
+        builder
+            .worker_threads(1)
+            .thread_name("trust-dns-resolver")
+            .enable_all();
 
         let runtime = builder.build()?;
-        let async_resolver = AsyncResolver::new(config, options, TokioHandle::default())
-            .expect("failed to create resolver");
+        let async_resolver = AsyncResolver::new(config, options, TokioHandle::default())?;
 
         Ok(Self {
-            runtime: Mutex::new(runtime),
+            runtime,
             async_resolver,
         })
     }
@@ -119,6 +122,33 @@ impl Resolver {
         self.async_resolver.clear_cache();
     }
 
+    /// Filters all lookups by the blocklist, see [`AsyncResolver::set_blocklist`]
+    pub fn set_blocklist(&self, blocklist: Option<Blocklist>) {
+        self.async_resolver.set_blocklist(blocklist);
+    }
+
+    /// Reads the files of the blocklist again, see [`AsyncResolver::reload_blocklist`]
+    pub fn reload_blocklist(&self) -> ResolveResult<()> {
+        self.async_resolver.reload_blocklist()
+    }
+
+    /// Submits the lookup to the background thread, and blocks until the result is received
+    fn block_on<F, T>(&self, lookup: F) -> ResolveResult<T>
+    where
+        F: Future<Output = ResolveResult<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        self.runtime.spawn(async move {
+            // the receiver is only gone if the calling thread panicked
+            let _ = sender.send(lookup.await);
+        });
+
+        receiver
+            .recv()
+            .map_err(|_| ResolveErrorKind::Message("the resolver background thread stopped"))?
+    }
+
     /// Generic lookup for any RecordType
     ///
     /// *WARNING* This interface may change in the future, please use [`Self::lookup_ip`] or another variant for more stable interfaces.
@@ -128,8 +158,33 @@ impl Resolver {
     /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
     /// * `record_type` - type of record to lookup
     pub fn lookup<N: IntoName>(&self, name: N, record_type: RecordType) -> ResolveResult<Lookup> {
-        let lookup = self.async_resolver.lookup(name, record_type);
-        self.runtime.lock()?.block_on(lookup)
+        let name = name.into_name()?;
+        let resolver = self.async_resolver.clone();
+        self.block_on(async move { resolver.lookup(name, record_type).await })
+    }
+
+    /// Generic lookup for any RecordType, with options specific to this lookup
+    ///
+    /// See [`AsyncResolver::lookup_with_options`]
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
+    /// * `record_type` - type of record to lookup
+    /// * `lookup_options` - options overriding the `ResolverOpts` for this lookup
+    pub fn lookup_with_options<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+        lookup_options: LookupOptions,
+    ) -> ResolveResult<Lookup> {
+        let name = name.into_name()?;
+        let resolver = self.async_resolver.clone();
+        self.block_on(async move {
+            resolver
+                .lookup_with_options(name, record_type, lookup_options)
+                .await
+        })
     }
 
     /// Performs a dual-stack DNS lookup for the IP for the given hostname.
@@ -140,8 +195,48 @@ impl Resolver {
     ///
     /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
     pub fn lookup_ip<N: IntoName + TryParseIp>(&self, host: N) -> ResolveResult<LookupIp> {
-        let lookup = self.async_resolver.lookup_ip(host);
-        self.runtime.lock()?.block_on(lookup)
+        let host = Host::new(host);
+        let resolver = self.async_resolver.clone();
+        self.block_on(async move { resolver.lookup_ip(host).await })
+    }
+
+    /// Performs a dual-stack DNS lookup for the IP for the given hostname, with options specific to this lookup
+    ///
+    /// See [`AsyncResolver::lookup_ip_with_options`]
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    /// * `lookup_options` - options overriding the `ResolverOpts` for this lookup
+    pub fn lookup_ip_with_options<N: IntoName + TryParseIp>(
+        &self,
+        host: N,
+        lookup_options: LookupOptions,
+    ) -> ResolveResult<LookupIp> {
+        let host = Host::new(host);
+        let resolver = self.async_resolver.clone();
+        self.block_on(async move { resolver.lookup_ip_with_options(host, lookup_options).await })
+    }
+
+    /// Looks up the socket addresses of a service on the host, like `getaddrinfo`
+    ///
+    /// See [`AsyncResolver::lookup_service`]
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname or IP address, if this is an invalid hostname, an error will be returned.
+    /// * `service` - the port number or name of the service
+    /// * `hints` - the type of socket, and restrictions on the lookup
+    pub fn lookup_service<N: IntoName + TryParseIp>(
+        &self,
+        host: N,
+        service: &str,
+        hints: ServiceHints,
+    ) -> ResolveResult<Vec<SocketAddr>> {
+        let host = Host::new(host);
+        let service = service.to_owned();
+        let resolver = self.async_resolver.clone();
+        self.block_on(async move { resolver.lookup_service(host, &service, hints).await })
     }
 
     lookup_fn!(reverse_lookup, lookup::ReverseLookup, IpAddr);
@@ -155,6 +250,33 @@ impl Resolver {
     lookup_fn!(txt_lookup, lookup::TxtLookup);
 }
 
+/// An owned host for the background thread, this keeps IP addresses which are not valid names
+struct Host {
+    ip: Option<RData>,
+    name: ProtoResult<Name>,
+}
+
+impl Host {
+    fn new<N: IntoName + TryParseIp>(host: N) -> Self {
+        Self {
+            ip: host.try_parse_ip(),
+            name: host.into_name(),
+        }
+    }
+}
+
+impl IntoName for Host {
+    fn into_name(self) -> ProtoResult<Name> {
+        self.name
+    }
+}
+
+impl TryParseIp for Host {
+    fn try_parse_ip(&self) -> Option<RData> {
+        self.ip.clone()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
@@ -170,6 +292,17 @@ mod tests {
         require_send_sync::<Resolver>();
     }
 
+    #[test]
+    fn test_lookup_ip_literal() {
+        let resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default()).unwrap();
+
+        let response = resolver.lookup_ip("2001:db8::1").unwrap();
+        assert_eq!(
+            response.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))]
+        );
+    }
+
     #[test]
     fn test_lookup() {
         let resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default()).unwrap();
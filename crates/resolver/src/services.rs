@@ -0,0 +1,151 @@
+//! Services result from a configuration of the system services file
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::config::SocketType;
+
+lazy_static! {
+    /// The services of the system, read once on first use
+    pub(crate) static ref SYSTEM_SERVICES: Services = Services::new();
+}
+
+/// Configuration for the local services file, e.g. `/etc/services`
+#[derive(Debug, Default)]
+pub struct Services {
+    /// (name, socket type) -> port map, names are lowercase
+    by_name: HashMap<(String, SocketType), u16>,
+}
+
+impl Services {
+    /// Creates a new configuration from the system services file,
+    /// only works for Windows and Unix-like OSes,
+    /// will return empty configuration on others
+    #[cfg(any(unix, windows))]
+    pub fn new() -> Self {
+        read_services_conf(services_path()).unwrap_or_default()
+    }
+
+    /// Creates a default configuration for non Windows or Unix-like OSes
+    #[cfg(not(any(unix, windows)))]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up the port of the service, by name or alias
+    pub fn port(&self, service: &str, socket_type: SocketType) -> Option<u16> {
+        self.by_name
+            .get(&(service.to_lowercase(), socket_type))
+            .copied()
+    }
+
+    /// Insert the port of a service
+    pub fn insert(&mut self, service: &str, socket_type: SocketType, port: u16) {
+        self.by_name
+            .entry((service.to_lowercase(), socket_type))
+            .or_insert(port);
+    }
+
+    /// parse configuration from `src`
+    pub fn read_services_conf(mut self, src: impl io::Read) -> io::Result<Self> {
+        use std::io::{BufRead, BufReader};
+
+        // lines in the src should have the form `name port/protocol alias1 alias2 ...`
+        // line starts with `#` will be regarded with comments and ignored,
+        // also empty line also will be ignored,
+        // the first entry of a name takes precedence, as with getservbyname.
+
+        for line in BufReader::new(src).lines() {
+            // Remove comments from the line
+            let line = line?;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if fields.len() < 2 {
+                continue;
+            }
+
+            let (port, socket_type) = match fields[1].split_once('/') {
+                Some((port, "tcp")) => (port, SocketType::Stream),
+                Some((port, "udp")) => (port, SocketType::Datagram),
+                // e.g. sctp and ddp
+                Some(_) => continue,
+                None => {
+                    warn!("could not parse a port from services file: {}", fields[1]);
+                    continue;
+                }
+            };
+            let port = match port.parse::<u16>() {
+                Ok(port) => port,
+                Err(_) => {
+                    warn!("could not parse a port from services file: {}", port);
+                    continue;
+                }
+            };
+
+            self.insert(fields[0], socket_type, port);
+            for alias in fields.iter().skip(2) {
+                self.insert(alias, socket_type, port);
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+#[cfg(unix)]
+fn services_path() -> &'static str {
+    "/etc/services"
+}
+
+#[cfg(windows)]
+fn services_path() -> std::path::PathBuf {
+    let system_root =
+        std::env::var_os("SystemRoot").expect("Environtment variable SystemRoot not found");
+    let system_root = Path::new(&system_root);
+    system_root.join("System32\\drivers\\etc\\services")
+}
+
+/// parse configuration from `path`
+#[cfg(any(unix, windows))]
+#[cfg_attr(docsrs, doc(cfg(any(unix, windows))))]
+pub(crate) fn read_services_conf<P: AsRef<Path>>(path: P) -> io::Result<Services> {
+    use std::fs::File;
+
+    let file = File::open(path)?;
+    Services::default().read_services_conf(file)
+}
+
+#[cfg(any(unix, windows))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn tests_dir() -> String {
+        let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+        format! {"{}/crates/resolver/tests", server_path}
+    }
+
+    #[test]
+    fn test_read_services_conf() {
+        let path = format!("{}/services", tests_dir());
+        let services = read_services_conf(&path).unwrap();
+
+        assert_eq!(services.port("domain", SocketType::Stream), Some(53));
+        assert_eq!(services.port("domain", SocketType::Datagram), Some(53));
+        assert_eq!(services.port("http", SocketType::Stream), Some(80));
+        assert_eq!(services.port("WWW", SocketType::Stream), Some(80));
+        assert_eq!(services.port("http", SocketType::Datagram), None);
+        assert_eq!(services.port("https", SocketType::Datagram), Some(443));
+        assert_eq!(services.port("xmpp-client", SocketType::Stream), Some(5222));
+        assert_eq!(services.port("sctp-only", SocketType::Stream), None);
+        assert_eq!(services.port("unknown", SocketType::Stream), None);
+    }
+}
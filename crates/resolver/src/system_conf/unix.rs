@@ -72,6 +72,7 @@ fn into_resolver_config(
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         });
         nameservers.push(NameServerConfig {
             socket_addr: SocketAddr::new(ip.into(), DEFAULT_PORT),
@@ -81,6 +82,7 @@ fn into_resolver_config(
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         });
     }
     if nameservers.is_empty() {
@@ -133,6 +135,7 @@ mod tests {
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
                 bind_addr: None,
+                http_endpoint: None,
             },
             NameServerConfig {
                 socket_addr: addr,
@@ -142,6 +145,7 @@ mod tests {
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
                 bind_addr: None,
+                http_endpoint: None,
             },
         ]
     }
@@ -36,6 +36,7 @@ fn get_name_servers() -> ResolveResult<Vec<NameServerConfig>> {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         });
         name_servers.push(NameServerConfig {
             socket_addr,
@@ -45,6 +46,7 @@ fn get_name_servers() -> ResolveResult<Vec<NameServerConfig>> {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         });
     }
     Ok(name_servers)
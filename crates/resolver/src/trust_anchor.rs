@@ -0,0 +1,637 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Managed DNSSEC trust anchors, with automated updates as specified in
+//!  [RFC 5011](https://tools.ietf.org/html/rfc5011)
+//!
+//! The trust anchors can be loaded from the `bind.keys` format of BIND (`trust-anchors`,
+//!  `managed-keys` and `trusted-keys` statements) and from the zone file format used by
+//!  `unbound-anchor`, including the RFC 5011 state that unbound stores in its autotrust files.
+//!  Updated anchors are always persisted in the latter format.
+//!
+//! Only DNSKEY trust anchors are supported, DS trust anchors are ignored when loading.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use proto::rr::dnssec::rdata::{DNSKEY, SIG};
+use proto::rr::dnssec::{Algorithm, PublicKeyBuf, TrustAnchor, Verifier};
+use proto::rr::{DNSClass, Name, Record, RecordType};
+use tracing::{debug, info, warn};
+
+use crate::error::{ResolveError, ResolveResult};
+
+/// The hold-down time before a newly published key is trusted, RFC 5011 section 2.4.1
+pub const ADD_HOLD_DOWN: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The hold-down time before a revoked key is removed, RFC 5011 section 2.4.2
+pub const REMOVE_HOLD_DOWN: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+const FLAG_ZONE_KEY: u16 = 0b0000_0001_0000_0000;
+const FLAG_REVOKE: u16 = 0b0000_0000_1000_0000;
+const FLAG_SECURE_ENTRY_POINT: u16 = 0b0000_0000_0000_0001;
+
+/// The state of a trust anchor, RFC 5011 section 4.
+///
+/// The numeric values match the states stored in the autotrust files of unbound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    /// A new key has been seen, and is waiting for the add hold-down time to pass
+    AddPend,
+    /// The key is a trust anchor
+    Valid,
+    /// The key is a trust anchor, but was not seen in the last update
+    Missing,
+    /// The key has been revoked, and is no longer trusted
+    Revoked,
+}
+
+impl KeyState {
+    /// Returns true if keys in this state are used for validation
+    pub fn is_trusted(self) -> bool {
+        matches!(self, Self::Valid | Self::Missing)
+    }
+
+    fn from_autotrust(state: u8) -> Option<Self> {
+        match state {
+            // START is treated as a new, pending, key
+            0 | 1 => Some(Self::AddPend),
+            2 => Some(Self::Valid),
+            3 => Some(Self::Missing),
+            4 => Some(Self::Revoked),
+            _ => None,
+        }
+    }
+
+    fn to_autotrust(self) -> (u8, &'static str) {
+        match self {
+            Self::AddPend => (1, "ADDPEND"),
+            Self::Valid => (2, "VALID"),
+            Self::Missing => (3, "MISSING"),
+            Self::Revoked => (4, "REVOKED"),
+        }
+    }
+}
+
+/// A trust anchor, and its RFC 5011 state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManagedKey {
+    zone: Name,
+    dnskey: DNSKEY,
+    state: KeyState,
+    last_change: SystemTime,
+}
+
+impl ManagedKey {
+    /// The zone of the key, generally the root
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// The key
+    pub fn dnskey(&self) -> &DNSKEY {
+        &self.dnskey
+    }
+
+    /// The current state of the key
+    pub fn state(&self) -> KeyState {
+        self.state
+    }
+
+    /// The time of the last state change, this starts the hold-down timers
+    pub fn last_change(&self) -> SystemTime {
+        self.last_change
+    }
+
+    fn set_state(&mut self, state: KeyState, now: SystemTime) {
+        info!(
+            "trust anchor {} for {} changed from {:?} to {:?}",
+            self.dnskey.calculate_key_tag().unwrap_or_default(),
+            self.zone,
+            self.state,
+            state
+        );
+        self.state = state;
+        self.last_change = now;
+    }
+}
+
+/// A set of managed trust anchors
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustAnchors {
+    keys: Vec<ManagedKey>,
+}
+
+impl TrustAnchors {
+    /// Creates an empty set of trust anchors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the trust anchors from the file, see [`Self::parse`] for the supported formats
+    pub fn from_file(path: impl AsRef<Path>) -> ResolveResult<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text, SystemTime::now()).map_err(|e| {
+            ResolveError::from(format!(
+                "failed to read trust anchors from {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Parses trust anchors in the BIND `bind.keys` format, or the zone file format of
+    ///  `unbound-anchor`
+    ///
+    /// Keys without an RFC 5011 state, e.g. `initial-key` or `static-key` in the BIND format,
+    ///  are trusted as of `now`.
+    pub fn parse(text: &str, now: SystemTime) -> ResolveResult<Self> {
+        let mut anchors = Self::new();
+        let is_bind = ["trust-anchors", "managed-keys", "trusted-keys"]
+            .iter()
+            .any(|statement| text.contains(statement));
+
+        if is_bind {
+            anchors.parse_bind(text, now)?;
+        } else {
+            anchors.parse_zone(text, now)?;
+        }
+
+        if anchors.keys.is_empty() {
+            return Err("no DNSKEY trust anchors found".into());
+        }
+
+        Ok(anchors)
+    }
+
+    fn parse_bind(&mut self, text: &str, now: SystemTime) -> ResolveResult<()> {
+        // strip the comments
+        let text = text
+            .lines()
+            .map(|line| {
+                let line = line.trim_start();
+                if line.starts_with('#') || line.starts_with("//") {
+                    ""
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for statement in text.split('}') {
+            let body = match statement.split_once('{') {
+                Some((_, body)) => body,
+                None => continue,
+            };
+
+            for entry in body.split(';') {
+                let mut tokens = entry.split_whitespace();
+                let zone = match tokens.next() {
+                    Some(zone) => Name::from_ascii(zone.trim_matches('"'))?,
+                    None => continue,
+                };
+
+                let mut tokens = tokens.peekable();
+                match tokens.peek().copied() {
+                    Some("initial-key") | Some("static-key") => {
+                        tokens.next();
+                    }
+                    Some("initial-ds") | Some("static-ds") => {
+                        debug!("ignoring DS trust anchor for {}", zone);
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                let dnskey = parse_dnskey(tokens.map(|t| t.trim_matches('"')))?;
+                self.insert(zone, dnskey, KeyState::Valid, now);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_zone(&mut self, text: &str, now: SystemTime) -> ResolveResult<()> {
+        for line in text.lines() {
+            // directives, e.g. $ORIGIN, are not used in trust anchor files
+            if line.starts_with('$') {
+                continue;
+            }
+
+            let (data, comment) = line.split_once(';').unwrap_or((line, ""));
+            let mut tokens = data.split_whitespace();
+            let zone = match tokens.next() {
+                Some(zone) => Name::from_ascii(zone)?,
+                None => continue,
+            };
+
+            // skip the optional ttl and class, up to the type
+            let record_type = tokens.find(|t| t.parse::<u32>().is_err() && *t != "IN");
+            match record_type {
+                Some("DNSKEY") => (),
+                Some(record_type) => {
+                    debug!("ignoring {} trust anchor for {}", record_type, zone);
+                    continue;
+                }
+                None => continue,
+            }
+
+            let dnskey = parse_dnskey(tokens)?;
+
+            let state = autotrust_value(comment, "state")
+                .and_then(|state| state.parse::<u8>().ok())
+                .map(KeyState::from_autotrust);
+            let state = match state {
+                // REMOVED, or unknown states
+                Some(None) => continue,
+                Some(Some(state)) => state,
+                None => KeyState::Valid,
+            };
+            let last_change = autotrust_value(comment, "lastchange")
+                .and_then(|secs| secs.parse::<u64>().ok())
+                .map_or(now, |secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+            self.insert(zone, dnskey, state, last_change);
+        }
+
+        Ok(())
+    }
+
+    /// Adds the key to the trust anchors, in the specified state
+    pub fn insert(&mut self, zone: Name, dnskey: DNSKEY, state: KeyState, now: SystemTime) {
+        if let Some(key) = self.get_mut(&zone, &dnskey) {
+            key.set_state(state, now);
+            return;
+        }
+
+        self.keys.push(ManagedKey {
+            zone,
+            dnskey,
+            state,
+            last_change: now,
+        });
+    }
+
+    fn get_mut(&mut self, zone: &Name, dnskey: &DNSKEY) -> Option<&mut ManagedKey> {
+        // the revoke flag changes the key tag, so keys are compared by their key material
+        self.keys.iter_mut().find(|key| {
+            &key.zone == zone
+                && key.dnskey.algorithm() == dnskey.algorithm()
+                && key.dnskey.public_key() == dnskey.public_key()
+        })
+    }
+
+    /// All the managed keys, including those which are not trusted
+    pub fn keys(&self) -> &[ManagedKey] {
+        &self.keys
+    }
+
+    /// Returns the trust anchor for the validator, with all keys that are currently trusted
+    pub fn trust_anchor(&self) -> TrustAnchor {
+        let mut trust_anchor = TrustAnchor::new();
+        for key in self.keys.iter().filter(|key| key.state.is_trusted()) {
+            trust_anchor.insert_trust_anchor(&PublicKeyBuf::new(key.dnskey.public_key().to_vec()));
+        }
+
+        trust_anchor
+    }
+
+    /// Updates the state of the trust anchors of the zone with the DNSKEY RRset of the zone,
+    ///  as specified in RFC 5011 section 4
+    ///
+    /// The `dnskeys` must have been validated with the current trust anchors. A key with the REVOKE
+    ///  bit is only revoked if one of the `rrsigs` of the DNSKEY RRset is its own signature, RFC
+    ///  5011 section 2.1, otherwise anyone able to publish in the RRset could revoke the anchors.
+    ///  Returns true if the set of trusted keys changed.
+    pub fn update(
+        &mut self,
+        zone: &Name,
+        dnskeys: &[DNSKEY],
+        rrsigs: &[SIG],
+        now: SystemTime,
+    ) -> bool {
+        let trusted_before = self.trusted_key_count(zone);
+        let mut changed = false;
+
+        for dnskey in dnskeys
+            .iter()
+            .filter(|dnskey| dnskey.zone_key() && dnskey.secure_entry_point())
+        {
+            match self.get_mut(zone, dnskey) {
+                Some(_)
+                    if dnskey.revoke() && !is_self_signed(zone, dnskey, dnskeys, rrsigs, now) =>
+                {
+                    warn!(
+                        "ignoring revoked trust anchor for {}, it did not sign the DNSKEY RRset",
+                        zone
+                    );
+                }
+                Some(key) if dnskey.revoke() => {
+                    if key.state != KeyState::Revoked {
+                        key.set_state(KeyState::Revoked, now);
+                        changed = true;
+                    }
+                }
+                Some(key) => match key.state {
+                    KeyState::AddPend if hold_down_passed(key, ADD_HOLD_DOWN, now) => {
+                        key.set_state(KeyState::Valid, now);
+                        changed = true;
+                    }
+                    KeyState::Missing => {
+                        key.set_state(KeyState::Valid, now);
+                        changed = true;
+                    }
+                    _ => (),
+                },
+                // a revoked key that isn't known, is simply ignored
+                None if dnskey.revoke() => (),
+                None => {
+                    self.insert(zone.clone(), dnskey.clone(), KeyState::AddPend, now);
+                    changed = true;
+                }
+            }
+        }
+
+        for key in self.keys.iter_mut().filter(|key| &key.zone == zone) {
+            if key.state == KeyState::Valid && !is_published(key, dnskeys) {
+                key.set_state(KeyState::Missing, now);
+                changed = true;
+            }
+        }
+
+        // pending keys which are no longer published, and revoked keys after the hold-down, are
+        //  removed, RFC 5011 section 4.2
+        let count = self.keys.len();
+        self.keys.retain(|key| {
+            &key.zone != zone
+                || match key.state {
+                    KeyState::AddPend => is_published(key, dnskeys),
+                    KeyState::Revoked => !hold_down_passed(key, REMOVE_HOLD_DOWN, now),
+                    KeyState::Valid | KeyState::Missing => true,
+                }
+        });
+        changed |= self.keys.len() != count;
+
+        if self.trusted_key_count(zone) == 0 && trusted_before > 0 {
+            warn!("all trust anchors for {} have been revoked", zone);
+        }
+
+        changed
+    }
+
+    fn trusted_key_count(&self, zone: &Name) -> usize {
+        self.keys
+            .iter()
+            .filter(|key| &key.zone == zone && key.state.is_trusted())
+            .count()
+    }
+
+    /// Returns the trust anchors in the zone file format, with the autotrust comments of unbound
+    pub fn to_autotrust(&self) -> String {
+        let mut text = String::from("; autotrust trust anchor file\n");
+        for key in &self.keys {
+            let (state, state_name) = key.state.to_autotrust();
+            let last_change = key
+                .last_change
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+
+            // write! to a String is infallible
+            let _ = writeln!(
+                text,
+                "{zone}\t86400\tIN\tDNSKEY\t{dnskey} ;{{id = {tag}}} ;;state={state} [ {state_name} ] ;;count=0 ;;lastchange={last_change}",
+                zone = key.zone,
+                dnskey = key.dnskey,
+                tag = key.dnskey.calculate_key_tag().unwrap_or_default(),
+                state = state,
+                state_name = state_name,
+                last_change = last_change,
+            );
+        }
+
+        text
+    }
+
+    /// Persists the trust anchors to the file, see [`Self::to_autotrust`]
+    ///
+    /// The file is replaced atomically, so that a failure can not lose the anchors.
+    pub fn save(&self, path: impl AsRef<Path>) -> ResolveResult<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_autotrust())?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn is_published(key: &ManagedKey, dnskeys: &[DNSKEY]) -> bool {
+    dnskeys.iter().any(|dnskey| {
+        dnskey.algorithm() == key.dnskey.algorithm()
+            && dnskey.public_key() == key.dnskey.public_key()
+    })
+}
+
+/// True if the revoked key signed the DNSKEY RRset, with a signature valid at `now`
+fn is_self_signed(
+    zone: &Name,
+    revoked: &DNSKEY,
+    dnskeys: &[DNSKEY],
+    rrsigs: &[SIG],
+    now: SystemTime,
+) -> bool {
+    // the key tag covers the REVOKE bit, it differs from the tag of the key before its revocation
+    let key_tag = match revoked.calculate_key_tag() {
+        Ok(key_tag) => key_tag,
+        Err(_) => return false,
+    };
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    let rrset = dnskeys
+        .iter()
+        .map(|dnskey| Record::from_rdata(zone.clone(), 0, dnskey.clone().into()))
+        .collect::<Vec<_>>();
+
+    rrsigs
+        .iter()
+        .filter(|sig| {
+            sig.type_covered() == RecordType::DNSKEY
+                && sig.key_tag() == key_tag
+                && sig.algorithm() == revoked.algorithm()
+                && sig.signer_name() == zone
+                && u64::from(sig.sig_inception()) <= now
+                && now <= u64::from(sig.sig_expiration())
+        })
+        .any(|sig| {
+            revoked
+                .verify_rrsig(zone, DNSClass::IN, sig, &rrset)
+                .map_err(|e| debug!("revoked key did not sign the DNSKEY RRset: {}", e))
+                .is_ok()
+        })
+}
+
+fn hold_down_passed(key: &ManagedKey, hold_down: Duration, now: SystemTime) -> bool {
+    now.duration_since(key.last_change)
+        .map_or(false, |elapsed| elapsed >= hold_down)
+}
+
+/// Returns the value of a `;;name=value` autotrust comment
+fn autotrust_value<'a>(comment: &'a str, name: &str) -> Option<&'a str> {
+    comment
+        .split(";;")
+        .filter_map(|field| field.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.split_whitespace().next().unwrap_or_default())
+}
+
+/// Parses the DNSKEY presentation format, `flags protocol algorithm key`, the key may be split
+///  into multiple tokens
+fn parse_dnskey<'a>(mut tokens: impl Iterator<Item = &'a str>) -> ResolveResult<DNSKEY> {
+    let mut next_number = |field: &'static str| -> ResolveResult<u16> {
+        tokens
+            .next()
+            .and_then(|t| t.parse::<u16>().ok())
+            .ok_or_else(|| ResolveError::from(format!("invalid DNSKEY {}", field)))
+    };
+
+    let flags = next_number("flags")?;
+    let protocol = next_number("protocol")?;
+    let algorithm = next_number("algorithm")?;
+    if protocol != 3 {
+        return Err(format!("invalid DNSKEY protocol: {}", protocol).into());
+    }
+
+    let key: String = tokens
+        .filter(|t| *t != "(" && *t != ")")
+        .collect::<Vec<_>>()
+        .concat();
+    let public_key = data_encoding::BASE64
+        .decode(key.as_bytes())
+        .map_err(|e| ResolveError::from(format!("invalid DNSKEY key: {}", e)))?;
+
+    Ok(DNSKEY::new(
+        flags & FLAG_ZONE_KEY != 0,
+        flags & FLAG_SECURE_ENTRY_POINT != 0,
+        flags & FLAG_REVOKE != 0,
+        Algorithm::from_u8(algorithm as u8),
+        public_key,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KSK_2017: &str = "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=";
+
+    #[test]
+    fn test_parse_bind() {
+        let bind_keys = format!(
+            r#"
+# The root key
+trust-anchors {{
+        # This key (20326) was published in the root zone in 2017.
+        . initial-key 257 3 8 "{}";
+        . initial-ds 20326 8 2 "E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D";
+}};
+"#,
+            KSK_2017
+        );
+
+        let anchors = TrustAnchors::parse(&bind_keys, SystemTime::now()).unwrap();
+        assert_eq!(anchors.keys().len(), 1);
+        assert_eq!(anchors.keys()[0].zone(), &Name::root());
+        assert_eq!(
+            anchors.keys()[0].dnskey().calculate_key_tag().unwrap(),
+            20326
+        );
+        assert_eq!(anchors.keys()[0].state(), KeyState::Valid);
+        assert_eq!(anchors.trust_anchor().len(), 1);
+    }
+
+    #[test]
+    fn test_autotrust_round_trip() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let root_key = format!(". 172800 IN DNSKEY 257 3 8 {} ;{{id = 20326 (ksk), size = 2048b}} ;;state=1 [  ADDPEND  ] ;;count=2 ;;lastchange=1502307342", KSK_2017);
+
+        let anchors = TrustAnchors::parse(&root_key, now).unwrap();
+        assert_eq!(anchors.keys()[0].state(), KeyState::AddPend);
+        assert_eq!(
+            anchors.keys()[0].last_change(),
+            UNIX_EPOCH + Duration::from_secs(1_502_307_342)
+        );
+        assert!(anchors.trust_anchor().is_empty());
+
+        let reparsed = TrustAnchors::parse(&anchors.to_autotrust(), now).unwrap();
+        assert_eq!(reparsed, anchors);
+    }
+
+    #[test]
+    fn test_rfc5011_rollover() {
+        let start = SystemTime::now();
+        let root = Name::root();
+        let old_key = DNSKEY::new(true, true, false, Algorithm::RSASHA256, vec![1, 2, 3]);
+        let new_key = DNSKEY::new(true, true, false, Algorithm::RSASHA256, vec![4, 5, 6]);
+
+        let mut anchors = TrustAnchors::new();
+        anchors.insert(root.clone(), old_key.clone(), KeyState::Valid, start);
+
+        // the new key is published, and pending
+        assert!(anchors.update(&root, &[old_key.clone(), new_key.clone()], &[], start));
+        assert_eq!(anchors.keys()[1].state(), KeyState::AddPend);
+        assert_eq!(anchors.trust_anchor().len(), 1);
+
+        // still pending before the hold-down
+        let later = start + ADD_HOLD_DOWN / 2;
+        assert!(!anchors.update(&root, &[old_key.clone(), new_key.clone()], &[], later));
+        assert_eq!(anchors.keys()[1].state(), KeyState::AddPend);
+
+        // trusted after the hold-down
+        let later = start + ADD_HOLD_DOWN;
+        assert!(anchors.update(&root, &[old_key, new_key.clone()], &[], later));
+        assert_eq!(anchors.keys()[1].state(), KeyState::Valid);
+        assert_eq!(anchors.trust_anchor().len(), 2);
+        assert!(anchors.trust_anchor().contains_dnskey_bytes(&[4, 5, 6]));
+
+        // and a valid key which is no longer published is missing, but still trusted
+        assert!(anchors.update(&root, &[new_key], &[], later));
+        assert_eq!(anchors.keys()[0].state(), KeyState::Missing);
+        assert_eq!(anchors.trust_anchor().len(), 2);
+    }
+
+    #[test]
+    fn test_revoke_without_self_signature() {
+        let now = SystemTime::now();
+        let root = Name::root();
+        let key = DNSKEY::new(true, true, false, Algorithm::RSASHA256, vec![1, 2, 3]);
+        let revoked_key = DNSKEY::new(true, true, true, Algorithm::RSASHA256, vec![1, 2, 3]);
+
+        let mut anchors = TrustAnchors::new();
+        anchors.insert(root.clone(), key, KeyState::Valid, now);
+
+        // a REVOKE bit which isn't signed by the revoked key itself is ignored
+        let sig = SIG::new(
+            RecordType::DNSKEY,
+            Algorithm::RSASHA256,
+            0,
+            3600,
+            u32::MAX,
+            0,
+            revoked_key.calculate_key_tag().unwrap(),
+            root.clone(),
+            vec![0; 8],
+        );
+        assert!(!anchors.update(&root, &[revoked_key], &[sig], now));
+        assert_eq!(anchors.keys()[0].state(), KeyState::Valid);
+        assert_eq!(anchors.trust_anchor().len(), 1);
+    }
+}
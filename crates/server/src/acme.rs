@@ -0,0 +1,835 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Certificates of the TLS, HTTPS and QUIC listeners from an ACME server, e.g. Let's Encrypt
+//!
+//! The certificate is obtained with DNS-01 challenges, whose TXT records are served by the
+//!  authoritative zones of the server itself, and renewed before it expires. The renewed
+//!  certificate replaces the `ReloadableCertificate` of the listeners, which serve it to new
+//!  connections right away.
+//!
+//! <https://www.rfc-editor.org/rfc/rfc8555>
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use data_encoding::BASE64URL_NOPAD;
+use hyper::{body::Bytes, client::HttpConnector, header, Body, Client, Request, Response};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::{
+    authority::{Catalog, View},
+    client::{
+        op::ResponseCode,
+        rr::{LowerName, Name},
+    },
+    config::dnssec::{load_cert, CertType, TlsCertConfig},
+    proto::{error::ProtoError, rustls::tls_server},
+    server::{ReloadHandle, ReloadableCertificate},
+};
+
+/// The wait before retrying to obtain a certificate after a failure
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The longest wait before the time to renew the certificate is checked again
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The wait between polls of the status of an authorization or order
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The polls of an authorization or order before it's given up on
+const POLL_ATTEMPTS: usize = 60;
+
+/// An error obtaining a certificate from the ACME server
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum AcmeError {
+    /// The ACME server returned an error, see RFC 8555 Section 6.7
+    #[error("ACME server returned {status}: {detail}")]
+    Server {
+        /// The HTTP status of the response
+        status: u16,
+        /// The type and detail of the problem document
+        detail: String,
+    },
+
+    /// The response of the ACME server was not understood
+    #[error("bad response from the ACME server: {0}")]
+    Protocol(String),
+
+    /// The ACME server couldn't validate the challenge, or refused to issue the certificate
+    #[error("{url} is invalid: {detail}")]
+    Invalid {
+        /// The authorization or order
+        url: String,
+        /// The problem which made it invalid
+        detail: String,
+    },
+
+    /// None of the zones of the server is authoritative for the challenge name
+    #[error("no zone to answer the challenge at {0}")]
+    NoZone(Name),
+
+    /// The zone failed to set the TXT records of the challenge
+    #[error("failed to set the challenge at {0}: {1}")]
+    Zone(Name, ResponseCode),
+
+    /// The account key couldn't be read, generated or used
+    #[error("account key error: {0}")]
+    Key(String),
+
+    // foreign
+    /// An error reading or writing the account key, certificate or private key
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    /// An error reading the certificate or private key
+    #[error("proto error: {0}")]
+    Proto(#[from] ProtoError),
+
+    /// An error of the requests to the ACME server
+    #[error("http error: {0}")]
+    Http(#[from] hyper::Error),
+
+    /// An invalid request to the ACME server, e.g. a bad URL
+    #[error("request error: {0}")]
+    Request(#[from] hyper::http::Error),
+
+    /// An error generating the key or request of the certificate
+    #[error("certificate request error: {0}")]
+    Csr(#[from] rcgen::RcgenError),
+}
+
+/// The zones which answer the DNS-01 challenges
+#[async_trait::async_trait]
+pub trait ChallengeZones: Send + Sync {
+    /// Replaces the TXT records at the challenge name with the values, removes them if there are
+    ///  none
+    async fn set_challenge(&self, name: &LowerName, values: &[String]) -> Result<(), AcmeError>;
+}
+
+/// The challenges are set in the zones of the current catalog, and of all of its views
+#[async_trait::async_trait]
+impl ChallengeZones for ReloadHandle<Catalog> {
+    async fn set_challenge(&self, name: &LowerName, values: &[String]) -> Result<(), AcmeError> {
+        let catalog = self.current().await;
+        let catalogs = iter::once(&*catalog).chain(catalog.views().iter().map(View::catalog));
+
+        let mut found = false;
+        for catalog in catalogs {
+            if let Some(authority) = catalog.find(name) {
+                authority
+                    .set_challenge_txt(name, values)
+                    .await
+                    .map_err(|code| AcmeError::Zone(name.into(), code))?;
+                found = true;
+            }
+        }
+
+        if found {
+            Ok(())
+        } else {
+            Err(AcmeError::NoZone(name.into()))
+        }
+    }
+}
+
+/// Obtains the certificate of the listeners with ACME, and renews it before it expires
+pub struct CertificateManager {
+    directory: String,
+    contact: Vec<String>,
+    names: Vec<String>,
+    account_key: PathBuf,
+    renew_before: Duration,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    certificate: Arc<ReloadableCertificate>,
+}
+
+impl CertificateManager {
+    /// Creates the manager of the certificate which is configured with ACME
+    ///
+    /// The certificate is loaded from its files if they exist, otherwise the listeners fail the
+    ///  handshakes until it's first obtained.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_dir` - the directory which relative paths of the configuration are in
+    /// * `tls_cert_config` - the certificate, with the `acme` configuration
+    pub fn new(zone_dir: &Path, tls_cert_config: &TlsCertConfig) -> Result<Self, String> {
+        let acme = tls_cert_config
+            .get_acme()
+            .ok_or("the certificate is not configured with ACME")?;
+        let key_path = tls_cert_config
+            .get_private_key()
+            .ok_or("a certificate from ACME needs the private_key path")?;
+        if tls_cert_config.get_cert_type() != CertType::Pem {
+            return Err("a certificate from ACME is written as PEM".to_string());
+        }
+
+        let mut names = vec![tls_cert_config.get_endpoint_name().to_string()];
+        names.extend(acme.names.iter().cloned());
+
+        let cert_path = zone_dir.join(tls_cert_config.get_path());
+        let certificate = if cert_path.exists() {
+            let (chain, key) = load_cert(zone_dir, tls_cert_config)?;
+            ReloadableCertificate::new(chain, key).map_err(|e| e.to_string())?
+        } else {
+            info!(
+                "no certificate at {}, it will be obtained from {}",
+                cert_path.display(),
+                acme.directory()
+            );
+            ReloadableCertificate::default()
+        };
+
+        Ok(Self {
+            directory: acme.directory().to_string(),
+            contact: acme.contact.clone(),
+            names,
+            account_key: zone_dir.join(&acme.account_key),
+            renew_before: acme.renew_before(),
+            cert_path,
+            key_path: zone_dir.join(key_path),
+            certificate: Arc::new(certificate),
+        })
+    }
+
+    /// The certificate to register with the listeners, which is replaced when it's renewed
+    pub fn certificate(&self) -> Arc<ReloadableCertificate> {
+        Arc::clone(&self.certificate)
+    }
+
+    /// Obtains the certificate whenever it's due for renewal, answering the challenges in the zones
+    ///
+    /// This never returns, failures are logged and retried after an hour.
+    pub async fn run(self, zones: impl ChallengeZones) {
+        loop {
+            let renew_in = self.renew_in();
+            if renew_in > Duration::ZERO {
+                debug!("renewing the certificate in {:?}", renew_in);
+                tokio::time::sleep(renew_in.min(CHECK_INTERVAL)).await;
+                continue;
+            }
+
+            match self.renew(&zones).await {
+                Ok(()) => info!("obtained the certificate of {}", self.names.join(", ")),
+                Err(e) => {
+                    warn!(
+                        "failed to obtain the certificate, retrying in {:?}: {}",
+                        RETRY_INTERVAL, e
+                    );
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    /// The time until the certificate is renewed, zero if it has none or it's due
+    fn renew_in(&self) -> Duration {
+        let not_after = self
+            .certificate
+            .chain()
+            .and_then(|chain| chain.first().and_then(|cert| not_after(&cert.0)));
+
+        not_after
+            .and_then(|not_after| {
+                (UNIX_EPOCH + Duration::from_secs(not_after)).checked_sub(self.renew_before)
+            })
+            .and_then(|renew_at| renew_at.duration_since(SystemTime::now()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Obtains a new certificate, writes it to its files and replaces the served one
+    async fn renew(&self, zones: &dyn ChallengeZones) -> Result<(), AcmeError> {
+        let key = AccountKey::load_or_generate(&self.account_key)?;
+        let mut client = AcmeClient::new(&self.directory, key).await?;
+        let (chain, key) = client.obtain(&self.names, &self.contact, zones).await?;
+
+        // the key is written first, so that the files never hold a certificate of another key
+        write_file(&self.key_path, key.as_bytes(), true)?;
+        write_file(&self.cert_path, chain.as_bytes(), false)?;
+
+        let chain = tls_server::read_cert(&self.cert_path)?;
+        let key = tls_server::read_key(&self.key_path)?;
+        self.certificate.replace(chain, key)?;
+        Ok(())
+    }
+}
+
+/// The key of the ACME account, which signs the requests as ES256 JWS, RFC 7515
+struct AccountKey {
+    key_pair: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl AccountKey {
+    /// Reads the PKCS8 key from the path, or generates a P-256 key there if there is none
+    fn load_or_generate(path: &Path) -> Result<Self, AcmeError> {
+        let pkcs8 = match fs::read(path) {
+            Ok(pkcs8) => pkcs8,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("generating ACME account key: {}", path.display());
+                let pkcs8 = EcdsaKeyPair::generate_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    &SystemRandom::new(),
+                )
+                .map_err(|_| AcmeError::Key("failed to generate the key".to_string()))?;
+                write_file(path, pkcs8.as_ref(), true)?;
+                pkcs8.as_ref().to_vec()
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Self::from_pkcs8(&pkcs8).map_err(|e| AcmeError::Key(format!("{}: {}", path.display(), e)))
+    }
+
+    fn from_pkcs8(pkcs8: &[u8]) -> Result<Self, String> {
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8)
+            .map_err(|e| format!("not a P-256 PKCS8 key: {}", e))?;
+
+        Ok(Self {
+            key_pair,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// The public key as JWK, RFC 7517, with its members in the order of the thumbprint
+    fn jwk(&self) -> String {
+        // the uncompressed point, 0x04 followed by the coordinates
+        let point = self.key_pair.public_key().as_ref();
+        format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            BASE64URL_NOPAD.encode(&point[1..33]),
+            BASE64URL_NOPAD.encode(&point[33..65])
+        )
+    }
+
+    /// The value of the TXT record of the challenge with the token, RFC 8555 Section 8.4
+    fn challenge_value(&self, token: &str) -> String {
+        // the key authorization is the token and the JWK thumbprint, RFC 7638
+        let thumbprint = digest::digest(&digest::SHA256, self.jwk().as_bytes());
+        let key_authorization =
+            format!("{}.{}", token, BASE64URL_NOPAD.encode(thumbprint.as_ref()));
+
+        BASE64URL_NOPAD
+            .encode(digest::digest(&digest::SHA256, key_authorization.as_bytes()).as_ref())
+    }
+
+    /// The JWS in flattened JSON serialization, the payload is empty for POST-as-GET requests
+    fn sign(&self, protected: &Value, payload: Option<&Value>) -> Result<Value, AcmeError> {
+        let protected = BASE64URL_NOPAD.encode(protected.to_string().as_bytes());
+        let payload = payload.map_or_else(String::new, |payload| {
+            BASE64URL_NOPAD.encode(payload.to_string().as_bytes())
+        });
+
+        let signature = self
+            .key_pair
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|_| AcmeError::Key("failed to sign the request".to_string()))?;
+
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": BASE64URL_NOPAD.encode(signature.as_ref()),
+        }))
+    }
+}
+
+/// The URLs of the resources of the ACME server
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+/// A response of the ACME server
+struct AcmeResponse {
+    location: Option<String>,
+    body: Bytes,
+}
+
+impl AcmeResponse {
+    fn json(&self) -> Result<Value, AcmeError> {
+        serde_json::from_slice(&self.body)
+            .map_err(|e| AcmeError::Protocol(format!("bad JSON: {}", e)))
+    }
+
+    fn location(&self) -> Result<String, AcmeError> {
+        self.location
+            .clone()
+            .ok_or_else(|| AcmeError::Protocol("no Location header".to_string()))
+    }
+}
+
+/// A client of an ACME server, which orders certificates for its account
+struct AcmeClient {
+    http: Client<HttpsConnector<HttpConnector>>,
+    key: AccountKey,
+    directory: Directory,
+    nonce: Option<String>,
+    kid: Option<String>,
+}
+
+impl AcmeClient {
+    async fn new(directory: &str, key: AccountKey) -> Result<Self, AcmeError> {
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_only()
+            .enable_http1()
+            .build();
+        let http = Client::builder().build(connector);
+
+        let response = http
+            .request(Request::get(directory).body(Body::empty())?)
+            .await?;
+        let directory = read_response(response).await?.json()?;
+
+        let url = |name: &str| string_field(&directory, name).map(str::to_string);
+        let directory = Directory {
+            new_nonce: url("newNonce")?,
+            new_account: url("newAccount")?,
+            new_order: url("newOrder")?,
+        };
+
+        Ok(Self {
+            http,
+            key,
+            directory,
+            nonce: None,
+            kid: None,
+        })
+    }
+
+    /// Orders the certificate of the names, returns its chain and private key as PEM
+    async fn obtain(
+        &mut self,
+        names: &[String],
+        contact: &[String],
+        zones: &dyn ChallengeZones,
+    ) -> Result<(String, String), AcmeError> {
+        // an existing account is returned for the key, see RFC 8555 Section 7.3.1
+        let new_account = self.directory.new_account.clone();
+        let account = json!({ "termsOfServiceAgreed": true, "contact": contact });
+        let response = self.post(&new_account, Some(&account)).await?;
+        self.kid = Some(response.location()?);
+
+        let identifiers = names
+            .iter()
+            .map(|name| json!({ "type": "dns", "value": name }))
+            .collect::<Vec<_>>();
+        let new_order = self.directory.new_order.clone();
+        let response = self
+            .post(&new_order, Some(&json!({ "identifiers": identifiers })))
+            .await?;
+        let order_url = response.location()?;
+        let order = response.json()?;
+
+        // the challenges are set all at once, as a name and its wildcard share the challenge name
+        let mut challenges = BTreeMap::<LowerName, Vec<String>>::new();
+        let mut pending = Vec::new();
+        for authorization_url in string_array(&order, "authorizations")? {
+            let authorization = self.post(&authorization_url, None).await?.json()?;
+            if authorization["status"] == "valid" {
+                continue;
+            }
+
+            let domain = string_field(&authorization["identifier"], "value")?;
+            let challenge = authorization["challenges"]
+                .as_array()
+                .and_then(|challenges| challenges.iter().find(|c| c["type"] == "dns-01"))
+                .ok_or_else(|| {
+                    AcmeError::Protocol(format!("no dns-01 challenge for {}", domain))
+                })?;
+
+            challenges
+                .entry(challenge_name(domain)?)
+                .or_default()
+                .push(self.key.challenge_value(string_field(challenge, "token")?));
+            pending.push((
+                string_field(challenge, "url")?.to_string(),
+                authorization_url,
+            ));
+        }
+
+        for (name, values) in &challenges {
+            debug!("setting the ACME challenge at {}", name);
+            zones.set_challenge(name, values).await?;
+        }
+        let validated = self.validate(&pending).await;
+        for name in challenges.keys() {
+            if let Err(e) = zones.set_challenge(name, &[]).await {
+                warn!("failed to remove the ACME challenge at {}: {}", name, e);
+            }
+        }
+        validated?;
+
+        // a new key for every certificate
+        let mut params = rcgen::CertificateParams::new(names.to_vec());
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        let certificate = rcgen::Certificate::from_params(params)?;
+
+        let order = self.poll(&order_url, "ready").await?;
+        let csr = json!({ "csr": BASE64URL_NOPAD.encode(&certificate.serialize_request_der()?) });
+        self.post(string_field(&order, "finalize")?, Some(&csr))
+            .await?;
+        let order = self.poll(&order_url, "valid").await?;
+
+        let chain = self
+            .post(string_field(&order, "certificate")?, None)
+            .await?
+            .body;
+        let chain = String::from_utf8(chain.to_vec())
+            .map_err(|_| AcmeError::Protocol("the certificate is not PEM".to_string()))?;
+
+        Ok((chain, certificate.serialize_private_key_pem()))
+    }
+
+    /// Responds to the challenges, and waits until their authorizations are valid
+    async fn validate(&mut self, pending: &[(String, String)]) -> Result<(), AcmeError> {
+        for (challenge_url, _) in pending {
+            self.post(challenge_url, Some(&json!({}))).await?;
+        }
+        for (_, authorization_url) in pending {
+            self.poll(authorization_url, "valid").await?;
+        }
+
+        Ok(())
+    }
+
+    /// Polls the authorization or order until it has the status, fails if it's invalid
+    async fn poll(&mut self, url: &str, status: &str) -> Result<Value, AcmeError> {
+        for _ in 0..POLL_ATTEMPTS {
+            let object = self.post(url, None).await?.json()?;
+            match object["status"].as_str() {
+                Some(s) if s == status => return Ok(object),
+                Some("invalid") => {
+                    // the problem is of the order, or of the challenges of the authorization
+                    let detail = iter::once(&object["error"])
+                        .chain(
+                            object["challenges"]
+                                .as_array()
+                                .into_iter()
+                                .flatten()
+                                .map(|c| &c["error"]),
+                        )
+                        .find_map(|error| error["detail"].as_str())
+                        .unwrap_or("no detail");
+                    return Err(AcmeError::Invalid {
+                        url: url.to_string(),
+                        detail: detail.to_string(),
+                    });
+                }
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+
+        Err(AcmeError::Protocol(format!("timed out polling {}", url)))
+    }
+
+    /// POSTs the payload signed with the account key, or POST-as-GET without a payload
+    async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<AcmeResponse, AcmeError> {
+        let mut retried = false;
+        loop {
+            let mut protected = json!({ "alg": "ES256", "nonce": self.nonce().await?, "url": url });
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => {
+                    protected["jwk"] = serde_json::from_str(&self.key.jwk())
+                        .map_err(|e| AcmeError::Key(e.to_string()))?
+                }
+            }
+
+            let request = Request::post(url)
+                .header(header::CONTENT_TYPE, "application/jose+json")
+                .body(Body::from(self.key.sign(&protected, payload)?.to_string()))?;
+            let response = self.http.request(request).await?;
+            self.nonce = response
+                .headers()
+                .get("Replay-Nonce")
+                .and_then(|nonce| nonce.to_str().ok())
+                .map(str::to_string);
+
+            match read_response(response).await {
+                // nonces may be rejected, e.g. when they expire, the request is retried with a new one
+                Err(AcmeError::Server { detail, .. })
+                    if !retried && detail.starts_with("urn:ietf:params:acme:error:badNonce") =>
+                {
+                    retried = true;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The nonce of the last response, or a new one
+    async fn nonce(&mut self) -> Result<String, AcmeError> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+
+        let request = Request::head(&self.directory.new_nonce).body(Body::empty())?;
+        let response = self.http.request(request).await?;
+        response
+            .headers()
+            .get("Replay-Nonce")
+            .and_then(|nonce| nonce.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| AcmeError::Protocol("no Replay-Nonce header".to_string()))
+    }
+}
+
+/// Reads the body of the response, fails with the problem document of an error response
+async fn read_response(response: Response<Body>) -> Result<AcmeResponse, AcmeError> {
+    let status = response.status();
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string);
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+
+    if !status.is_success() {
+        let problem = serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null);
+        return Err(AcmeError::Server {
+            status: status.as_u16(),
+            detail: format!(
+                "{}: {}",
+                problem["type"].as_str().unwrap_or("unknown"),
+                problem["detail"].as_str().unwrap_or("no detail")
+            ),
+        });
+    }
+
+    Ok(AcmeResponse { location, body })
+}
+
+fn string_field<'v>(value: &'v Value, name: &str) -> Result<&'v str, AcmeError> {
+    value[name]
+        .as_str()
+        .ok_or_else(|| AcmeError::Protocol(format!("missing {}", name)))
+}
+
+fn string_array(value: &Value, name: &str) -> Result<Vec<String>, AcmeError> {
+    value[name]
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| AcmeError::Protocol(format!("missing {}", name)))
+}
+
+/// The name of the TXT records of the challenge of the domain, also of its wildcard
+fn challenge_name(domain: &str) -> Result<LowerName, AcmeError> {
+    let domain = domain.trim_start_matches("*.").trim_end_matches('.');
+    Name::from_str(&format!("_acme-challenge.{}.", domain))
+        .map(LowerName::from)
+        .map_err(|e| AcmeError::Protocol(format!("bad identifier {}: {}", domain, e)))
+}
+
+/// Replaces the file with the contents, which are readable only by the owner if they're private
+fn write_file(path: &Path, contents: &[u8], private: bool) -> io::Result<()> {
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(".tmp");
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+
+    let mut file = options.open(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// The end of the validity of the DER certificate, in seconds since the unix epoch
+fn not_after(der: &[u8]) -> Option<u64> {
+    let (_, certificate, _) = der_element(der)?;
+    let (_, mut tbs_certificate, _) = der_element(certificate)?;
+
+    // the version is optional, with the explicit tag [0]
+    let (tag, _, rest) = der_element(tbs_certificate)?;
+    if tag == 0xa0 {
+        tbs_certificate = rest;
+    }
+    // the serialNumber, signature and issuer precede the validity
+    for _ in 0..3 {
+        tbs_certificate = der_element(tbs_certificate)?.2;
+    }
+
+    let (_, validity, _) = der_element(tbs_certificate)?;
+    let (_, _, validity) = der_element(validity)?;
+    let (tag, time, _) = der_element(validity)?;
+    parse_time(tag, time)
+}
+
+/// Splits the DER element at the start of the bytes into its tag, contents and the bytes after it
+fn der_element(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, bytes) = bytes.split_first()?;
+    let (&len, mut bytes) = bytes.split_first()?;
+
+    let len = if len & 0x80 == 0 {
+        usize::from(len)
+    } else {
+        // the long form, the number of octets of the length follows
+        let octets = usize::from(len & 0x7f);
+        if octets == 0 || octets > 4 || bytes.len() < octets {
+            return None;
+        }
+        let (len, rest) = bytes.split_at(octets);
+        bytes = rest;
+        len.iter().fold(0, |len, &b| len << 8 | usize::from(b))
+    };
+
+    if bytes.len() < len {
+        return None;
+    }
+    let (contents, rest) = bytes.split_at(len);
+    Some((tag, contents, rest))
+}
+
+/// Parses a UTCTime, YYMMDDHHMMSSZ, or a GeneralizedTime, YYYYMMDDHHMMSSZ, RFC 5280 Section 4.1.2.5
+fn parse_time(tag: u8, time: &[u8]) -> Option<u64> {
+    let time = time.strip_suffix(b"Z")?;
+    if !time.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let number = |digits: &[u8]| digits.iter().fold(0, |n, &d| n * 10 + i32::from(d - b'0'));
+
+    let (year, rest) = match (tag, time.len()) {
+        // UTCTime, years from 1950 to 2049
+        (0x17, 12) => match number(&time[..2]) {
+            year @ 50..=99 => (1900 + year, &time[2..]),
+            year => (2000 + year, &time[2..]),
+        },
+        // GeneralizedTime
+        (0x18, 14) => (number(&time[..4]), &time[4..]),
+        _ => return None,
+    };
+    let field = |i: usize| u8::try_from(number(&rest[i..i + 2])).ok();
+
+    let month = time::Month::try_from(field(0)?).ok()?;
+    let date = time::Date::from_calendar_date(year, month, field(2)?).ok()?;
+    let date_time = date.with_hms(field(4)?, field(6)?, field(8)?).ok()?;
+    u64::try_from(date_time.assume_utc().unix_timestamp()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+
+    use super::*;
+
+    fn account_key() -> AccountKey {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+                .unwrap();
+        AccountKey::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    fn unix_time(year: i32, month: time::Month, day: u8) -> u64 {
+        let date = time::Date::from_calendar_date(year, month, day).unwrap();
+        date.midnight().assume_utc().unix_timestamp() as u64
+    }
+
+    #[test]
+    fn test_not_after() {
+        // UTCTime before 2050, GeneralizedTime from 2050
+        for year in &[2030, 2051] {
+            let mut params = rcgen::CertificateParams::new(vec!["ns.example.com".to_string()]);
+            params.not_after = rcgen::date_time_ymd(*year, 1, 2);
+            let der = rcgen::Certificate::from_params(params)
+                .unwrap()
+                .serialize_der()
+                .unwrap();
+
+            assert_eq!(
+                not_after(&der),
+                Some(unix_time(*year, time::Month::January, 2))
+            );
+        }
+
+        assert_eq!(not_after(b"\x30\x82\x01"), None);
+    }
+
+    #[test]
+    fn test_jws() {
+        let key = account_key();
+        let protected = json!({ "alg": "ES256", "nonce": "abc", "url": "https://example.com/" });
+        let jws = key.sign(&protected, Some(&json!({}))).unwrap();
+
+        assert_eq!(jws["payload"], "e30");
+        let signed = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap()
+        );
+        let signature = BASE64URL_NOPAD
+            .decode(jws["signature"].as_str().unwrap().as_bytes())
+            .unwrap();
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, key.key_pair.public_key().as_ref())
+            .verify(signed.as_bytes(), &signature)
+            .expect("signature does not verify");
+
+        // POST-as-GET has an empty payload
+        let jws = key.sign(&protected, None).unwrap();
+        assert_eq!(jws["payload"], "");
+    }
+
+    #[test]
+    fn test_challenge() {
+        let key = account_key();
+        let jwk: Value = serde_json::from_str(&key.jwk()).unwrap();
+        assert_eq!(jwk["kty"], "EC");
+        assert_eq!(
+            BASE64URL_NOPAD
+                .decode(jwk["x"].as_str().unwrap().as_bytes())
+                .unwrap()
+                .len(),
+            32
+        );
+
+        // the SHA-256 digest, base64url encoded
+        assert_eq!(key.challenge_value("token").len(), 43);
+        assert_ne!(key.challenge_value("token"), key.challenge_value("other"));
+
+        let name = LowerName::from(Name::from_str("_acme-challenge.example.com.").unwrap());
+        assert_eq!(challenge_name("example.com").unwrap(), name);
+        assert_eq!(challenge_name("*.example.com").unwrap(), name);
+    }
+}
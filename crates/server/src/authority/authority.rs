@@ -7,18 +7,25 @@
 
 //! All authority related types
 
+use std::net::IpAddr;
+
 use cfg_if::cfg_if;
 
+#[cfg(feature = "dnssec")]
+use crate::authority::{KeyRole, KeyTimings};
 #[cfg(feature = "dnssec")]
 use crate::client::{
-    proto::rr::dnssec::rdata::key::KEY,
-    rr::dnssec::{DnsSecResult, SigSigner, SupportedAlgorithms},
-    rr::Name,
+    proto::rr::dnssec::rdata::{key::KEY, NSEC3PARAM},
+    rr::dnssec::{tsig::TSigner, DnsSecResult, SigSigner, SupportedAlgorithms},
 };
 use crate::{
-    authority::{LookupError, MessageRequest, UpdateResult, ZoneType},
-    client::rr::{LowerName, RecordSet, RecordType},
-    proto::rr::RrsetRecords,
+    authority::{LookupError, MessageRequest, UpdateResult, ZoneDiff, ZoneType},
+    client::{
+        op::ResponseCode,
+        rr::{LowerName, Name, RecordSet, RecordType},
+    },
+    error::PersistenceResult,
+    proto::rr::{rdata::opt::ClientSubnet, RrsetRecords},
     server::RequestInfo,
 };
 
@@ -31,6 +38,7 @@ pub struct LookupOptions {
     is_dnssec: bool,
     #[cfg(feature = "dnssec")]
     supported_algorithms: SupportedAlgorithms,
+    client_subnet: Option<ClientSubnet>,
 }
 
 /// Lookup Options for the request to the authority
@@ -42,6 +50,7 @@ impl LookupOptions {
         Self {
             is_dnssec,
             supported_algorithms,
+            client_subnet: None,
         }
     }
 
@@ -76,6 +85,22 @@ impl LookupOptions {
         self.supported_algorithms
     }
 
+    /// Specify the network of the client, from the EDNS Client Subnet option of the request
+    pub fn set_client_subnet(self, val: Option<ClientSubnet>) -> Self {
+        Self {
+            client_subnet: val,
+            ..self
+        }
+    }
+
+    /// The network of the client the lookup is for, see [RFC 7871](https://tools.ietf.org/html/rfc7871)
+    ///
+    /// Authorities may tailor their answers to the network, e.g. by its location, in which case
+    ///  they must also return the scope of the answers from `Authority::client_subnet_scope`.
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.client_subnet.as_ref()
+    }
+
     /// Returns the subset of the rrset limited to the supported_algorithms
     pub fn rrset_with_supported_algorithms<'r>(
         &self,
@@ -95,6 +120,19 @@ impl LookupOptions {
 }
 
 /// Authority implementations can be used with a `Catalog`
+///
+/// This is the extension point for custom backends, e.g. zones stored in a database or answers
+///  from service discovery. An implementation must provide `zone_type`, `is_axfr_allowed`,
+///  `update`, `origin`, `lookup`, `search` and `get_nsec_records`, the other methods have
+///  defaults which suit a zone without dynamic updates, transfers or client subnet specific
+///  answers. Register the authority with `Catalog::add_authority`, which serves it for its origin.
+///
+/// `Lookup` is any `LookupObject`, e.g. `AuthLookup` built from `RecordSet`s. An authoritative
+///  `search` returns `LookupError::ResponseCode(ResponseCode::NXDomain)` for names which don't
+///  exist, and `LookupError::NameExists` for names without records of the type, the `Catalog`
+///  adds the SOA to those responses. Wildcards and ANAMEs can be answered with
+///  `synthesize_wildcard` and `synthesize_aname`, and answers which are computed per query can
+///  also be provided by a `LookupHook` in front of a static zone.
 #[async_trait::async_trait]
 pub trait Authority: Send + Sync {
     /// Result of a lookup
@@ -109,9 +147,91 @@ pub trait Authority: Send + Sync {
     /// Perform a dynamic update of a zone
     async fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
 
+    /// The TSIG key which signed the dynamic update, for signing the response
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    async fn update_tsigner(&self, _update: &MessageRequest) -> Option<TSigner> {
+        None
+    }
+
+    /// Handle a NOTIFY of a change to the zone, see RFC 1996
+    ///
+    /// Only secondary zones act on a NOTIFY, by refreshing the zone from the primary.
+    async fn notify(&self, _request_info: RequestInfo<'_>) -> UpdateResult<()> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Checks that the client is permitted to transfer the zone with AXFR or IXFR
+    ///
+    /// By default transfers are permitted to all clients if AXFR is allowed, see
+    ///  `is_axfr_allowed`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the AXFR or IXFR request
+    /// * `src` - the address of the client
+    async fn authorize_transfer(
+        &self,
+        _request: &MessageRequest,
+        _src: IpAddr,
+    ) -> UpdateResult<()> {
+        if self.is_axfr_allowed() {
+            Ok(())
+        } else {
+            Err(ResponseCode::Refused)
+        }
+    }
+
+    /// The TSIG key which signed the zone transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    async fn transfer_tsigner(&self, _request: &MessageRequest) -> Option<TSigner> {
+        None
+    }
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR, see RFC 1995
+    ///
+    /// Returns `None` if the changes are not known, in which case the full zone is transferred.
+    async fn ixfr_diffs(&self, _serial: u32) -> Option<Vec<ZoneDiff>> {
+        None
+    }
+
+    /// Replaces the TXT records at the name with the values, for answering the DNS-01 challenges
+    ///  of ACME, see RFC 8555
+    ///
+    /// The records are removed if there are no values. Authorities which don't hold their own
+    ///  records return `NotImp`.
+    async fn set_challenge_txt(&self, _name: &LowerName, _values: &[String]) -> UpdateResult<()> {
+        Err(ResponseCode::NotImp)
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName;
 
+    /// The scope prefix length of answers to lookups for the client subnet, see RFC 7871
+    ///
+    /// This is the number of bits of the client address which the answers depend on, it is
+    ///  returned to the client in the EDNS Client Subnet option of the response. The default
+    ///  of 0 is correct for authorities which give the same answers to all clients.
+    fn client_subnet_scope(&self, _client_subnet: &ClientSubnet) -> u8 {
+        0
+    }
+
+    /// Clears the answers cached from other servers, e.g. by forwarding or recursive authorities
+    fn clear_cache(&self) {}
+
+    /// Removes the answers cached for the name from other servers
+    fn flush_name(&self, _name: &Name) {}
+
+    /// Removes the answers cached for the name, and for all names below it, from other servers
+    fn flush_subtree(&self, _name: &Name) {}
+
+    /// Writes the current state of the zone to its storage, e.g. the journaled updates into the
+    ///  zone file, so that the storage can be edited while updates to the zone are frozen
+    async fn persist(&self) -> PersistenceResult<()> {
+        Ok(())
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -194,9 +314,56 @@ pub trait DnssecAuthority: Authority {
     /// Add a (Sig0) key that is authorized to perform updates against this authority
     async fn add_update_auth_key(&self, name: Name, key: KEY) -> DnsSecResult<()>;
 
+    /// Add a TSIG key that is authorized to perform updates against this authority
+    async fn add_update_tsig_key(&self, _signer: TSigner) -> DnsSecResult<()> {
+        Err("TSIG authorization of updates is not supported by this authority".into())
+    }
+
+    /// Add a TSIG key which is authorized to transfer the zone from this authority
+    ///
+    /// Once a key is added, zone transfers must be signed with one of the keys.
+    async fn add_transfer_tsig_key(&self, _signer: TSigner) -> DnsSecResult<()> {
+        Err("TSIG authorization of zone transfers is not supported by this authority".into())
+    }
+
     /// Add Signer
     async fn add_zone_signing_key(&self, signer: SigSigner) -> DnsSecResult<()>;
 
+    /// Add a key which signs the record sets of its role, while it's active in its schedule
+    ///
+    /// The DNSKEY of the signer must have the SEP flag of the role. Keys with the SEP flag are
+    ///  also published as CDS and CDNSKEY while active, for the parent to update its DS.
+    async fn add_scheduled_signing_key(
+        &self,
+        _signer: SigSigner,
+        _role: KeyRole,
+        _timings: KeyTimings,
+    ) -> DnsSecResult<()> {
+        Err("scheduled signing keys are not supported by this authority".into())
+    }
+
+    /// Publishes and removes the keys of the zone as scheduled, and re-signs the zone if the keys
+    ///  changed or the signatures are nearing expiration
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - the current time, in seconds since the unix epoch
+    ///
+    /// # Return value
+    ///
+    /// True if the zone was re-signed
+    async fn maintain_keys(&self, _now: u64) -> DnsSecResult<bool> {
+        Ok(false)
+    }
+
+    /// Signs the zone with NSEC3 instead of NSEC, with the hash parameters of the NSEC3PARAM,
+    ///  which is published at the apex, on the next signing
+    ///
+    /// With opt-out, unsigned delegations are left out of the NSEC3 chain, RFC 5155 Section 6.
+    async fn set_nsec3(&self, _nsec3param: NSEC3PARAM, _opt_out: bool) -> DnsSecResult<()> {
+        Err("NSEC3 is not supported by this authority".into())
+    }
+
     /// Sign the zone for DNSSEC
     async fn secure_zone(&self) -> DnsSecResult<()>;
 }
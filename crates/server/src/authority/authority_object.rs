@@ -7,13 +7,19 @@
 
 //! All authority related types
 
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 use tracing::debug;
 
+#[cfg(feature = "dnssec")]
+use crate::client::rr::dnssec::tsig::TSigner;
 use crate::{
-    authority::{Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneType},
-    client::rr::{LowerName, Record, RecordType},
+    authority::{
+        Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneDiff, ZoneType,
+    },
+    client::rr::{LowerName, Name, Record, RecordType},
+    error::PersistenceResult,
+    proto::rr::rdata::opt::{ClientSubnet, EdnsOption},
     server::RequestInfo,
 };
 
@@ -32,9 +38,47 @@ pub trait AuthorityObject: Send + Sync {
     /// Perform a dynamic update of a zone
     async fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
 
+    /// The TSIG key which signed the dynamic update, for signing the response
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    async fn update_tsigner(&self, update: &MessageRequest) -> Option<TSigner>;
+
+    /// Handle a NOTIFY of a change to the zone, see RFC 1996
+    async fn notify(&self, request_info: RequestInfo<'_>) -> UpdateResult<()>;
+
+    /// Checks that the client is permitted to transfer the zone with AXFR or IXFR
+    async fn authorize_transfer(&self, request: &MessageRequest, src: IpAddr) -> UpdateResult<()>;
+
+    /// The TSIG key which signed the zone transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    async fn transfer_tsigner(&self, request: &MessageRequest) -> Option<TSigner>;
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR
+    async fn ixfr_diffs(&self, serial: u32) -> Option<Vec<ZoneDiff>>;
+
+    /// Replaces the TXT records at the name with the values, for answering ACME challenges
+    async fn set_challenge_txt(&self, name: &LowerName, values: &[String]) -> UpdateResult<()>;
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName;
 
+    /// The scope prefix length of answers to lookups for the client subnet, see RFC 7871
+    fn client_subnet_scope(&self, client_subnet: &ClientSubnet) -> u8;
+
+    /// Clears the answers cached from other servers, e.g. by forwarding or recursive authorities
+    fn clear_cache(&self);
+
+    /// Removes the answers cached for the name from other servers
+    fn flush_name(&self, name: &Name);
+
+    /// Removes the answers cached for the name, and for all names below it, from other servers
+    fn flush_subtree(&self, name: &Name);
+
+    /// Writes the current state of the zone to its storage, e.g. the journaled updates into the
+    ///  zone file
+    async fn persist(&self) -> PersistenceResult<()>;
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -140,11 +184,67 @@ where
         Authority::update(self.as_ref(), update).await
     }
 
+    /// The TSIG key which signed the dynamic update, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn update_tsigner(&self, update: &MessageRequest) -> Option<TSigner> {
+        Authority::update_tsigner(self.as_ref(), update).await
+    }
+
+    /// Handle a NOTIFY of a change to the zone, see RFC 1996
+    async fn notify(&self, request_info: RequestInfo<'_>) -> UpdateResult<()> {
+        Authority::notify(self.as_ref(), request_info).await
+    }
+
+    /// Checks that the client is permitted to transfer the zone with AXFR or IXFR
+    async fn authorize_transfer(&self, request: &MessageRequest, src: IpAddr) -> UpdateResult<()> {
+        Authority::authorize_transfer(self.as_ref(), request, src).await
+    }
+
+    /// The TSIG key which signed the zone transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn transfer_tsigner(&self, request: &MessageRequest) -> Option<TSigner> {
+        Authority::transfer_tsigner(self.as_ref(), request).await
+    }
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR
+    async fn ixfr_diffs(&self, serial: u32) -> Option<Vec<ZoneDiff>> {
+        Authority::ixfr_diffs(self.as_ref(), serial).await
+    }
+
+    /// Replaces the TXT records at the name with the values, for answering ACME challenges
+    async fn set_challenge_txt(&self, name: &LowerName, values: &[String]) -> UpdateResult<()> {
+        Authority::set_challenge_txt(self.as_ref(), name, values).await
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
         Authority::origin(self.as_ref())
     }
 
+    /// The scope prefix length of answers to lookups for the client subnet, see RFC 7871
+    fn client_subnet_scope(&self, client_subnet: &ClientSubnet) -> u8 {
+        Authority::client_subnet_scope(self.as_ref(), client_subnet)
+    }
+
+    /// Clears the answers cached from other servers, e.g. by forwarding or recursive authorities
+    fn clear_cache(&self) {
+        Authority::clear_cache(self.as_ref())
+    }
+
+    fn flush_name(&self, name: &Name) {
+        Authority::flush_name(self.as_ref(), name)
+    }
+
+    fn flush_subtree(&self, name: &Name) {
+        Authority::flush_subtree(self.as_ref(), name)
+    }
+
+    /// Writes the current state of the zone to its storage, e.g. the journaled updates into the
+    ///  zone file
+    async fn persist(&self) -> PersistenceResult<()> {
+        Authority::persist(self.as_ref()).await
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -221,6 +321,16 @@ pub trait LookupObject: Send {
     ///
     /// it is acceptable for this to return None after the first call.
     fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>>;
+
+    /// True if the records were validated with DNSSEC, i.e. the response is Authentic Data
+    fn is_authentic(&self) -> bool {
+        false
+    }
+
+    /// The Extended DNS Error of the answer, RFC 8914, e.g. that it is stale
+    fn extended_error(&self) -> Option<EdnsOption> {
+        None
+    }
 }
 
 /// A lookup that returns no records
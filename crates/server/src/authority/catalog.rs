@@ -8,33 +8,70 @@
 // TODO, I've implemented this as a separate entity from the cache, but I wonder if the cache
 //  should be the only "front-end" for lookups, where if that misses, then we go to the catalog
 //  then, if requested, do a recursive lookup... i.e. the catalog would only point to files.
-use std::{borrow::Borrow, collections::HashMap, future::Future, io};
+#[cfg(feature = "dnssec")]
+use std::time::UNIX_EPOCH;
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    future::Future,
+    io,
+    net::IpAddr,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
 
-use cfg_if::cfg_if;
-use tracing::{debug, error, info, trace, warn};
+use tracing::{debug, error, field, info, trace, warn, Span};
 use trust_dns_proto::rr::Record;
 
-#[cfg(feature = "dnssec")]
-use crate::client::rr::{
-    dnssec::{Algorithm, SupportedAlgorithms},
-    rdata::opt::{EdnsCode, EdnsOption},
-};
 use crate::{
     authority::{
-        AuthLookup, AuthorityObject, EmptyLookup, LookupError, LookupObject, LookupOptions,
-        MessageResponse, MessageResponseBuilder, ZoneType,
+        zone_transfer, AuthLookup, Authority, AuthorityObject, EmptyLookup, FrozenZones,
+        LookupError, LookupHook, LookupObject, LookupOptions, MessageResponse,
+        MessageResponseBuilder, PolicyAction, PolicyHit, ResponsePolicyZone, View, ZoneDiff,
+        ZoneType,
+    },
+    client::{
+        op::{Edns, Header, LowerQuery, MessageType, OpCode, Query, ResponseCode},
+        rr::{
+            rdata::opt::{ClientSubnet, EdnsCode, EdnsOption},
+            LowerName, Name, RData, RecordType,
+        },
+    },
+    metrics,
+    server::{
+        AccessControl, AclOperation, Protocol, QueryLog, QueryRecord, QuerySource,
+        RateLimitedResponseHandle, Request, RequestHandler, RequestInfo, RequestStats,
+        ResponseHandler, ResponseInfo, ResponseRateLimiter,
     },
+    store::file::serial_lt,
+};
+#[cfg(feature = "dnssec")]
+use crate::{
     client::{
-        op::{Edns, Header, LowerQuery, MessageType, OpCode, ResponseCode},
-        rr::{LowerName, RecordType},
+        op::Message,
+        rr::dnssec::{tsig::TSigner, Algorithm, SupportedAlgorithms},
+    },
+    proto::{
+        error::{ProtoError, ProtoResult},
+        rr::dnssec::rdata::DNSSECRData,
+        serialize::binary::BinEncoder,
     },
-    server::{Request, RequestHandler, RequestInfo, ResponseHandler, ResponseInfo},
 };
 
 /// Set of authorities, zones, available to this server.
 #[derive(Default)]
 pub struct Catalog {
     authorities: HashMap<LowerName, Box<dyn AuthorityObject>>,
+    response_policies: Vec<ResponsePolicyZone>,
+    lookup_hooks: Vec<Box<dyn LookupHook>>,
+    views: Vec<View>,
+    rate_limiter: Option<ResponseRateLimiter>,
+    zone_rate_limiters: HashMap<LowerName, ResponseRateLimiter>,
+    access_control: AccessControl,
+    zone_access_controls: HashMap<LowerName, AccessControl>,
+    frozen_zones: FrozenZones,
+    request_stats: Option<Arc<RequestStats>>,
+    query_log: Option<QueryLog>,
 }
 
 #[allow(unused_mut, unused_variables)]
@@ -50,26 +87,63 @@ async fn send_response<'a, R: ResponseHandler>(
     >,
     mut response_handle: R,
 ) -> io::Result<ResponseInfo> {
-    #[cfg(feature = "dnssec")]
-    if let Some(mut resp_edns) = response_edns {
-        // set edns DAU and DHU
-        // send along the algorithms which are supported by this authority
-        let mut algorithms = SupportedAlgorithms::default();
-        algorithms.set(Algorithm::RSASHA256);
-        algorithms.set(Algorithm::ECDSAP256SHA256);
-        algorithms.set(Algorithm::ECDSAP384SHA384);
-        algorithms.set(Algorithm::ED25519);
+    if let Some(resp_edns) = response_edns {
+        #[cfg(feature = "dnssec")]
+        let resp_edns = with_supported_algorithms(resp_edns);
+        response.set_edns(resp_edns);
+    }
+
+    response_handle.send_response(response).await
+}
 
-        let dau = EdnsOption::DAU(algorithms);
-        let dhu = EdnsOption::DHU(algorithms);
+/// Set edns DAU and DHU, to send along the algorithms which are supported by this authority
+#[cfg(feature = "dnssec")]
+fn with_supported_algorithms(mut resp_edns: Edns) -> Edns {
+    let mut algorithms = SupportedAlgorithms::default();
+    algorithms.set(Algorithm::RSASHA256);
+    algorithms.set(Algorithm::RSASHA512);
+    algorithms.set(Algorithm::ECDSAP256SHA256);
+    algorithms.set(Algorithm::ECDSAP384SHA384);
+    algorithms.set(Algorithm::ED25519);
+    algorithms.set(Algorithm::ED448);
+
+    let dau = EdnsOption::DAU(algorithms);
+    let dhu = EdnsOption::DHU(algorithms);
+
+    resp_edns.options_mut().insert(dau);
+    resp_edns.options_mut().insert(dhu);
+    resp_edns
+}
 
-        resp_edns.options_mut().insert(dau);
-        resp_edns.options_mut().insert(dhu);
+/// Computes the TSIG record for the response to an update which was signed by `signer`
+///
+/// The response must be signed with the MAC of the request, see RFC 8945, section 5.3
+#[cfg(feature = "dnssec")]
+fn sign_update_response(
+    signer: &TSigner,
+    update: &Request,
+    header: Header,
+    edns: Option<Edns>,
+) -> ProtoResult<Record> {
+    let request_mac = match update.sig0().last().and_then(Record::data) {
+        Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) => tsig.mac(),
+        _ => return Err("update is not signed with TSIG".into()),
+    };
 
-        response.set_edns(resp_edns);
+    let mut message = Message::new();
+    message
+        .set_header(header)
+        .add_query(update.query().original().clone());
+    if let Some(edns) = edns {
+        message.set_edns(edns);
     }
 
-    response_handle.send_response(response).await
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| ProtoError::from("current time is before the unix epoch"))?
+        .as_secs() as u32;
+
+    signer.sign_response(request_mac, &message, now)
 }
 
 #[async_trait::async_trait]
@@ -83,10 +157,89 @@ impl RequestHandler for Catalog {
     async fn handle_request<R: ResponseHandler>(
         &self,
         request: &Request,
-        mut response_handle: R,
+        response_handle: R,
+    ) -> ResponseInfo {
+        let started = Instant::now();
+
+        // the zone of the request, on the span of the request of the server
+        let span = Span::current();
+        if !span.is_disabled() {
+            if let (_, Some(authority)) = self.authority_for(request) {
+                span.record("authority", &field::display(authority.origin()));
+            }
+        }
+
+        let response_info = self.handle(request, response_handle).await;
+
+        if let Some(ref request_stats) = self.request_stats {
+            request_stats.record(
+                request.protocol(),
+                request.op_code(),
+                response_info.response_code(),
+            );
+        }
+        metrics::request(
+            request.protocol(),
+            request.op_code(),
+            request.query(),
+            response_info.response_code(),
+            started.elapsed(),
+        );
+        if let Some(ref query_log) = self.query_log {
+            if query_log.is_sampled(response_info.response_code()) {
+                let (view, authority) = self.authority_for(request);
+                query_log.log(&QueryRecord {
+                    time: SystemTime::now(),
+                    client: request.src(),
+                    protocol: request.protocol(),
+                    id: request.id(),
+                    op_code: request.op_code(),
+                    query: request.query(),
+                    response_code: response_info.response_code(),
+                    latency: started.elapsed(),
+                    view,
+                    zone: authority.map(|authority| authority.origin()),
+                    source: QuerySource::from_zone_type(
+                        authority.map(|authority| authority.zone_type()),
+                    ),
+                });
+            }
+        }
+
+        response_info
+    }
+
+    /// Writes the journaled updates of the zones to their zone files, see `persist_zones`
+    async fn shutdown(&self) {
+        self.persist_zones().await;
+    }
+}
+
+impl Catalog {
+    /// Handles the request with the zones of the matching view, or of this catalog
+    async fn handle<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
     ) -> ResponseInfo {
         trace!("request: {:?}", request);
 
+        if let Some(view) = self.views.iter().find(|view| view.matches(request)) {
+            debug!("request: {} handled by view: {}", request.id(), view.name());
+            return view.catalog().handle_zones(request, response_handle).await;
+        }
+
+        self.handle_zones(request, response_handle).await
+    }
+
+    /// Handles the request with the zones of this catalog, ignoring the views
+    async fn handle_zones<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        let mut response_handle = self.rate_limited(request, response_handle);
+
         let response_edns: Option<Edns>;
 
         // check if it's edns
@@ -133,6 +286,32 @@ impl RequestHandler for Catalog {
             response_edns = None;
         }
 
+        if let Some(operation) = AclOperation::of(request) {
+            if !self.is_allowed(operation, request) {
+                warn!(
+                    "{:?} of {} refused by acl for: {}",
+                    operation,
+                    request.query().name(),
+                    request.src().ip()
+                );
+                let response = MessageResponseBuilder::new(Some(request.raw_query()));
+                let result = send_response(
+                    response_edns,
+                    response.error_msg(request.header(), ResponseCode::Refused),
+                    response_handle,
+                )
+                .await;
+
+                return match result {
+                    Err(e) => {
+                        error!("request error: {}", e);
+                        ResponseInfo::serve_failed()
+                    }
+                    Ok(info) => info,
+                };
+            }
+        }
+
         let result = match request.message_type() {
             // TODO think about threading query lookups for multiple lookups, this could be a huge improvement
             //  especially for recursive lookups
@@ -147,6 +326,10 @@ impl RequestHandler for Catalog {
                     debug!("update received: {}", request.id());
                     self.update(request, response_edns, response_handle).await
                 }
+                OpCode::Notify => {
+                    debug!("notify received: {}", request.id());
+                    self.notify(request, response_edns, response_handle).await
+                }
                 c => {
                     warn!("unimplemented op_code: {:?}", c);
                     let response = MessageResponseBuilder::new(Some(request.raw_query()));
@@ -174,16 +357,36 @@ impl RequestHandler for Catalog {
             Ok(info) => info,
         }
     }
-}
 
-impl Catalog {
     /// Constructs a new Catalog
     pub fn new() -> Self {
         Self {
             authorities: HashMap::new(),
+            response_policies: Vec::new(),
+            lookup_hooks: Vec::new(),
+            views: Vec::new(),
+            rate_limiter: None,
+            zone_rate_limiters: HashMap::new(),
+            access_control: AccessControl::default(),
+            zone_access_controls: HashMap::new(),
+            frozen_zones: FrozenZones::new(),
+            request_stats: None,
+            query_log: None,
         }
     }
 
+    /// Insert or update the zone of an authority, e.g. a custom implementation of `Authority`
+    ///
+    /// The zone name is the origin of the authority.
+    pub fn add_authority<A, L>(&mut self, authority: A)
+    where
+        A: Authority<Lookup = L> + Send + Sync + 'static,
+        L: LookupObject + Send + Sync + 'static,
+    {
+        let name = authority.origin().clone();
+        self.upsert(name, Box::new(Arc::new(authority)));
+    }
+
     /// Insert or update a zone authority
     ///
     /// # Arguments
@@ -199,6 +402,187 @@ impl Catalog {
         self.authorities.remove(name)
     }
 
+    /// Add a zone of response policies, which are applied to the responses of all zones
+    ///
+    /// Policy zones are checked in the order they are added, the first with a matching rule
+    ///  decides the response. Within a zone, the query name is checked first, then the addresses
+    ///  of the answer, then the names and addresses of the name servers of the queried name.
+    pub fn add_response_policy(&mut self, policy: ResponsePolicyZone) {
+        self.response_policies.push(policy);
+    }
+
+    /// Add a hook which may answer queries for any zone, before the authority of the zone
+    ///
+    /// Hooks are consulted in the order they are added, the first to answer decides the answer.
+    pub fn add_lookup_hook(&mut self, hook: Box<dyn LookupHook>) {
+        self.lookup_hooks.push(hook);
+    }
+
+    /// Add a view, requests from the clients of the view are handled by the zones of the view
+    ///
+    /// Views are checked in the order they are added, the first which matches the client handles
+    ///  the request. Requests from clients which match no view are handled by the zones of this
+    ///  catalog.
+    pub fn add_view(&mut self, view: View) {
+        self.views.push(view);
+    }
+
+    /// Set the limiter of the rate of responses over UDP, for zones without their own limiter
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<ResponseRateLimiter>) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    /// Set the limiter of the rate of responses over UDP for names in the zone
+    pub fn set_zone_rate_limiter(&mut self, name: LowerName, rate_limiter: ResponseRateLimiter) {
+        self.zone_rate_limiters.insert(name, rate_limiter);
+    }
+
+    /// Set the ACLs of the operations on zones without their own ACL for the operation
+    pub fn set_access_control(&mut self, access_control: AccessControl) {
+        self.access_control = access_control;
+    }
+
+    /// Set the ACLs of the operations on the zone, which override those of the catalog
+    pub fn set_zone_access_control(&mut self, name: LowerName, access_control: AccessControl) {
+        self.zone_access_controls.insert(name, access_control);
+    }
+
+    /// Set the zones for which updates are refused, the set is shared with its clones
+    pub fn set_frozen_zones(&mut self, frozen_zones: FrozenZones) {
+        self.frozen_zones = frozen_zones;
+    }
+
+    /// The zones for which updates are refused
+    pub fn frozen_zones(&self) -> &FrozenZones {
+        &self.frozen_zones
+    }
+
+    /// Set the counters of the requests handled by this catalog, including those handled by its
+    ///  views
+    pub fn set_request_stats(&mut self, request_stats: Option<Arc<RequestStats>>) {
+        self.request_stats = request_stats;
+    }
+
+    /// Set the log of the requests handled by this catalog, including those handled by its views
+    pub fn set_query_log(&mut self, query_log: Option<QueryLog>) {
+        self.query_log = query_log;
+    }
+
+    /// The authorities of the zones of this catalog, not including those of its views
+    pub fn authorities(&self) -> impl Iterator<Item = &(dyn AuthorityObject + 'static)> {
+        self.authorities.values().map(|authority| &**authority)
+    }
+
+    /// The views of this catalog
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    /// Clears the answers cached by the authorities of all zones, including those of the views
+    pub fn clear_caches(&self) {
+        for authority in self.authorities.values() {
+            authority.clear_cache();
+        }
+
+        for view in &self.views {
+            view.catalog().clear_caches();
+        }
+    }
+
+    /// Writes the state of all zones, including those of the views, to their storage, see
+    ///  `AuthorityObject::persist`
+    ///
+    /// Zones which can't be written are logged, the others are still written.
+    pub async fn persist_zones(&self) {
+        let views = self.views.iter().map(View::catalog);
+        for catalog in std::iter::once(self).chain(views) {
+            for authority in catalog.authorities.values() {
+                if let Err(e) = authority.persist().await {
+                    warn!("could not write zone {}: {}", authority.origin(), e);
+                }
+            }
+        }
+    }
+
+    /// Removes the answers cached for the name by the authorities of all zones, including those
+    ///  of the views, and if `subtree` is true also those for all names below it
+    pub fn flush_caches(&self, name: &Name, subtree: bool) {
+        for authority in self.authorities.values() {
+            if subtree {
+                authority.flush_subtree(name);
+            } else {
+                authority.flush_name(name);
+            }
+        }
+
+        for view in &self.views {
+            view.catalog().flush_caches(name, subtree);
+        }
+    }
+
+    /// Reports the serial of each zone, including those of the views, to the metrics recorder
+    ///
+    /// Serials change with updates and transfers, so this is called periodically.
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    pub async fn record_zone_metrics(&self) {
+        let views = self
+            .views
+            .iter()
+            .map(|view| (Some(view.name()), view.catalog()));
+        for (view, catalog) in std::iter::once((None, self)).chain(views) {
+            for authority in catalog.authorities.values() {
+                let serial = authority.soa().await.ok().and_then(|soa| {
+                    soa.iter()
+                        .find_map(|record| record.data().and_then(RData::as_soa))
+                        .map(|soa| soa.serial())
+                });
+
+                if let Some(serial) = serial {
+                    metrics::zone_serial(authority.origin(), view, serial);
+                }
+            }
+        }
+    }
+
+    /// Wraps the handle to limit the rate of responses to requests over UDP, the source address
+    ///  of which may be spoofed
+    fn rate_limited<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> RateLimitedResponseHandle<R> {
+        let query = request.query();
+        let zone = self.find(query.name()).map(|authority| authority.origin());
+        let rate_limiter = match request.protocol() {
+            Protocol::Udp => zone
+                .and_then(|zone| self.zone_rate_limiters.get(zone))
+                .or(self.rate_limiter.as_ref())
+                .cloned(),
+            _ => None,
+        };
+
+        RateLimitedResponseHandle::new(
+            response_handle,
+            rate_limiter,
+            request.src().ip(),
+            query.name().clone(),
+            query.query_type(),
+            zone.cloned(),
+        )
+    }
+
+    /// Checks the ACL of the operation of the zone of the query name, or of this catalog
+    fn is_allowed(&self, operation: AclOperation, request: &Request) -> bool {
+        let zone = self
+            .find(request.query().name())
+            .map(|authority| authority.origin());
+        zone.and_then(|zone| self.zone_access_controls.get(zone))
+            .and_then(|access_control| access_control.acl(operation))
+            .or_else(|| self.access_control.acl(operation))
+            .map_or(true, |acl| acl.allows(request))
+    }
+
     /// Update the zone given the Update request.
     ///
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -248,11 +632,12 @@ impl Catalog {
     ///
     /// * `request` - an update message
     /// * `response_handle` - sink for the response message to be sent
+    #[cfg_attr(not(feature = "dnssec"), allow(unused_mut))]
     pub async fn update<R: ResponseHandler>(
         &self,
         update: &Request,
         response_edns: Option<Edns>,
-        response_handle: R,
+        mut response_handle: R,
     ) -> io::Result<ResponseInfo> {
         let request_info = update.request_info();
 
@@ -285,7 +670,11 @@ impl Catalog {
                 .ok_or(ResponseCode::Refused)
         });
 
-        let response_code = match authority {
+        let response_code = match &authority {
+            Ok(authority) if self.frozen_zones.is_frozen(authority.origin()) => {
+                warn!("update refused, zone is frozen: {}", authority.origin());
+                ResponseCode::Refused
+            }
             Ok(authority) => {
                 #[allow(deprecated)]
                 match authority.zone_type() {
@@ -304,16 +693,102 @@ impl Catalog {
                     _ => ResponseCode::NotAuth,
                 }
             }
-            Err(response_code) => response_code,
+            Err(response_code) => *response_code,
         };
 
-        let response = MessageResponseBuilder::new(Some(update.raw_query()));
+        let mut response = MessageResponseBuilder::new(Some(update.raw_query()));
         let mut response_header = Header::default();
         response_header.set_id(update.id());
         response_header.set_op_code(OpCode::Update);
         response_header.set_message_type(MessageType::Response);
         response_header.set_response_code(response_code);
 
+        // updates authorized with TSIG must have signed responses
+        #[cfg(feature = "dnssec")]
+        {
+            let signer = match &authority {
+                Ok(authority) => authority.update_tsigner(update).await,
+                Err(_) => None,
+            };
+
+            if let Some(signer) = signer {
+                let response_edns = response_edns.map(with_supported_algorithms);
+                let tsig =
+                    sign_update_response(&signer, update, response_header, response_edns.clone());
+                match tsig {
+                    Ok(tsig) => {
+                        response.sig0(vec![tsig]);
+                    }
+                    Err(e) => warn!("failed to sign update response: {}", e),
+                }
+                if let Some(edns) = response_edns {
+                    response.edns(edns);
+                }
+
+                return response_handle
+                    .send_response(response.build_no_records(response_header))
+                    .await;
+            }
+        }
+
+        send_response(
+            response_edns,
+            response.build_no_records(response_header),
+            response_handle,
+        )
+        .await
+    }
+
+    /// Handle a NOTIFY of a change to a zone
+    ///
+    /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
+    ///
+    /// ```text
+    /// 3.7. A NOTIFY request has QDCOUNT>0, ANCOUNT>=0, AUCOUNT>=0,
+    ///      ADCOUNT>=0.  If ANCOUNT>0, then the answer section represents an
+    ///      unsecure hint at the new RRset for this <QNAME,QCLASS,QTYPE>.
+    ///
+    /// 4.7  Zone has Updated on Primary Primary
+    ///
+    ///      Secondary receives a NOTIFY request from the primary ... and
+    ///      returns a NOTIFY response ...
+    /// ```
+    ///
+    /// The zone is looked up by the exact name of the query, the authority decides whether the
+    ///  NOTIFY is accepted, see `Authority::notify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - a notify message
+    /// * `response_handle` - sink for the response message to be sent
+    pub async fn notify<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_edns: Option<Edns>,
+        response_handle: R,
+    ) -> io::Result<ResponseInfo> {
+        let request_info = request.request_info();
+
+        let response_code = if request_info.query.query_type() != RecordType::SOA {
+            warn!(
+                "invalid notify request type must be SOA, type: {}",
+                request_info.query.query_type()
+            );
+            ResponseCode::FormErr
+        } else {
+            match self.authorities.get(request_info.query.name()) {
+                Some(authority) => match authority.notify(request_info).await {
+                    Ok(()) => ResponseCode::NoError,
+                    Err(response_code) => response_code,
+                },
+                None => ResponseCode::NotAuth,
+            }
+        };
+
+        let response = MessageResponseBuilder::new(Some(request.raw_query()));
+        let mut response_header = Header::response_from_request(request.header());
+        response_header.set_response_code(response_code);
+
         send_response(
             response_edns,
             response.build_no_records(response_header),
@@ -350,17 +825,51 @@ impl Catalog {
         let request_info = request.request_info();
         let authority = self.find(request_info.query.name());
 
+        let mut policies = match request_info.query.query_type() {
+            RecordType::AXFR | RecordType::IXFR => &[][..],
+            _ => &self.response_policies[..],
+        };
+        for policy in policies {
+            if let Some(hit) = policy.check_qname(request_info.query.name()).await {
+                log_policy_hit(&request_info, &hit);
+                if *hit.action() == PolicyAction::Passthru {
+                    policies = &[];
+                    break;
+                }
+
+                return send_policy_response(&hit, request, response_edns, response_handle).await;
+            }
+        }
+
         if let Some(authority) = authority {
-            lookup(
-                request_info,
-                authority,
-                request,
-                response_edns
-                    .as_ref()
-                    .map(|arc| Borrow::<Edns>::borrow(arc).clone()),
-                response_handle.clone(),
-            )
-            .await
+            let response_edns = response_edns
+                .as_ref()
+                .map(|arc| Borrow::<Edns>::borrow(arc).clone());
+
+            match request_info.query.query_type() {
+                RecordType::AXFR | RecordType::IXFR => {
+                    transfer(
+                        request_info,
+                        authority,
+                        request,
+                        response_edns,
+                        response_handle.clone(),
+                    )
+                    .await
+                }
+                _ => {
+                    lookup(
+                        request_info,
+                        authority,
+                        policies,
+                        &self.lookup_hooks,
+                        request,
+                        response_edns,
+                        response_handle.clone(),
+                    )
+                    .await
+                }
+            }
         } else {
             // if this is empty then the there are no authorities registered that can handle the request
             let response = MessageResponseBuilder::new(Some(request.raw_query()));
@@ -382,6 +891,17 @@ impl Catalog {
         }
     }
 
+    /// The name of the view which handles the request, if any, and the authority of the zone of
+    ///  its query name in the view or in this catalog
+    fn authority_for(
+        &self,
+        request: &Request,
+    ) -> (Option<&str>, Option<&(dyn AuthorityObject + 'static)>) {
+        let view = self.views.iter().find(|view| view.matches(request));
+        let catalog = view.map_or(self, View::catalog);
+        (view.map(View::name), catalog.find(request.query().name()))
+    }
+
     /// Recursively searches the catalog for a matching authority
     pub fn find(&self, name: &LowerName) -> Option<&(dyn AuthorityObject + 'static)> {
         debug!("searching authorities for: {}", name);
@@ -402,6 +922,8 @@ impl Catalog {
 async fn lookup<'a, R: ResponseHandler + Unpin>(
     request_info: RequestInfo<'_>,
     authority: &dyn AuthorityObject,
+    policies: &[ResponsePolicyZone],
+    hooks: &[Box<dyn LookupHook>],
     request: &Request,
     response_edns: Option<Edns>,
     response_handle: R,
@@ -415,7 +937,8 @@ async fn lookup<'a, R: ResponseHandler + Unpin>(
 
     let (response_header, sections) = build_response(
         authority,
-        request_info,
+        hooks,
+        request_info.clone(),
         request.id(),
         request.header(),
         query,
@@ -423,6 +946,24 @@ async fn lookup<'a, R: ResponseHandler + Unpin>(
     )
     .await;
 
+    let mut response_edns =
+        response_edns.map(|edns| with_client_subnet(edns, request.edns(), authority));
+    if let (Some(edns), Some(extended_error)) =
+        (response_edns.as_mut(), sections.extended_error.clone())
+    {
+        edns.options_mut().insert(extended_error);
+    }
+
+    if !policies.is_empty() {
+        let answer_ips = addresses(sections.answers.iter());
+        if let Some(hit) = check_response_policies(policies, authority, query, &answer_ips).await {
+            log_policy_hit(&request_info, &hit);
+            if *hit.action() != PolicyAction::Passthru {
+                return send_policy_response(&hit, request, response_edns, response_handle).await;
+            }
+        }
+    }
+
     let response = MessageResponseBuilder::new(Some(request.raw_query())).build(
         response_header,
         sections.answers.iter(),
@@ -442,32 +983,286 @@ async fn lookup<'a, R: ResponseHandler + Unpin>(
     }
 }
 
-#[allow(unused_variables)]
+/// Serves a zone transfer, AXFR (RFC 5936) or IXFR (RFC 1995), to an authorized client
+///
+/// Over TCP the records are split across as many messages as needed, each of which is signed
+///  with TSIG if the request was signed with one of the transfer keys of the zone.
+async fn transfer<R: ResponseHandler>(
+    request_info: RequestInfo<'_>,
+    authority: &dyn AuthorityObject,
+    request: &Request,
+    response_edns: Option<Edns>,
+    mut response_handle: R,
+) -> ResponseInfo {
+    let protocol = request_info.protocol;
+    let records = match transfer_records(request_info, authority, request).await {
+        Ok(records) => records,
+        Err(response_code) => {
+            let response = MessageResponseBuilder::new(Some(request.raw_query()));
+            let result = send_response(
+                response_edns,
+                response.error_msg(request.header(), response_code),
+                response_handle,
+            )
+            .await;
+
+            return result.unwrap_or_else(|e| {
+                error!("failed to send response: {}", e);
+                ResponseInfo::serve_failed()
+            });
+        }
+    };
+
+    #[cfg(feature = "dnssec")]
+    let response_edns = response_edns.map(with_supported_algorithms);
+    #[cfg(feature = "dnssec")]
+    let signer = authority.transfer_tsigner(request).await;
+    #[cfg(feature = "dnssec")]
+    let mut previous_mac = match request.sig0().last().and_then(Record::data) {
+        Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) => tsig.mac().to_vec(),
+        _ => Vec::new(),
+    };
+
+    // only TCP and TLS carry more than one message in response to a request
+    let messages = if is_multi_message(protocol) {
+        zone_transfer::split_messages(&records)
+    } else {
+        vec![records.as_slice()]
+    };
+
+    let mut response_info = ResponseInfo::serve_failed();
+    for (i, message) in messages.into_iter().enumerate() {
+        let mut response_header = Header::response_from_request(request.header());
+        response_header.set_authoritative(true);
+
+        let mut response = MessageResponseBuilder::new(Some(request.raw_query()));
+        if let Some(edns) = response_edns.clone() {
+            response.edns(edns);
+        }
+
+        #[cfg(feature = "dnssec")]
+        if let Some(signer) = signer.as_ref() {
+            match sign_transfer_response(signer, &previous_mac, i == 0, || {
+                let mut response = MessageResponseBuilder::new(Some(request.raw_query()));
+                if let Some(edns) = response_edns.clone() {
+                    response.edns(edns);
+                }
+
+                response.build(response_header, message.iter(), None, None, None)
+            }) {
+                Ok(tsig) => {
+                    if let Some(RData::DNSSEC(DNSSECRData::TSIG(rdata))) = tsig.data() {
+                        previous_mac = rdata.mac().to_vec();
+                    }
+                    response.sig0(vec![tsig]);
+                }
+                Err(e) => {
+                    error!("failed to sign transfer response: {}", e);
+                    return ResponseInfo::serve_failed();
+                }
+            }
+        }
+        #[cfg(not(feature = "dnssec"))]
+        let _ = i;
+
+        let response = response.build(response_header, message.iter(), None, None, None);
+        response_info = match response_handle.send_response(response).await {
+            Ok(info) => info,
+            Err(e) => {
+                error!("error sending transfer response: {}", e);
+                return ResponseInfo::serve_failed();
+            }
+        };
+    }
+
+    response_info
+}
+
+/// The records of the transfer, starting and ending with the current SOA of the zone
+async fn transfer_records(
+    request_info: RequestInfo<'_>,
+    authority: &dyn AuthorityObject,
+    request: &Request,
+) -> Result<Vec<Record>, ResponseCode> {
+    // transfers are only of entire zones
+    if request_info.query.name() != authority.origin() {
+        return Err(ResponseCode::NotAuth);
+    }
+
+    #[allow(deprecated)]
+    match authority.zone_type() {
+        ZoneType::Primary | ZoneType::Secondary | ZoneType::Master | ZoneType::Slave => (),
+        _ => return Err(ResponseCode::NotAuth),
+    }
+
+    authority
+        .authorize_transfer(request, request_info.src.ip())
+        .await?;
+
+    let lookup_options = lookup_options_for_edns(request.edns());
+    let soa = authority
+        .soa()
+        .await
+        .ok()
+        .and_then(|soa| soa.iter().next().cloned())
+        .ok_or(ResponseCode::ServFail)?;
+    let serial = zone_transfer::soa_serial(&soa).ok_or(ResponseCode::ServFail)?;
+
+    if request_info.query.query_type() == RecordType::IXFR {
+        // the SOA of the client's version of the zone is in the authority section
+        let client_serial = request
+            .name_servers()
+            .iter()
+            .find(|record| record.rr_type() == RecordType::SOA)
+            .and_then(zone_transfer::soa_serial)
+            .ok_or(ResponseCode::FormErr)?;
+
+        // the client is up to date, or the response must fit in a single UDP message
+        if !serial_lt(client_serial, serial) || !is_multi_message(request_info.protocol) {
+            return Ok(vec![soa]);
+        }
+
+        if let Some(diffs) = authority.ixfr_diffs(client_serial).await {
+            info!(
+                "incremental transfer of {} from serial: {} to: {}",
+                authority.origin(),
+                client_serial,
+                serial
+            );
+
+            let mut records = vec![soa.clone()];
+            records.extend(
+                diffs
+                    .iter()
+                    .flat_map(ZoneDiff::records)
+                    .filter(|r| lookup_options.is_dnssec() || r.rr_type() != RecordType::RRSIG)
+                    .cloned(),
+            );
+            records.push(soa);
+            return Ok(records);
+        }
+
+        debug!(
+            "no changes of {} known since serial: {}, transferring the full zone",
+            authority.origin(),
+            client_serial
+        );
+    }
+
+    info!(
+        "full transfer of {} at serial: {}",
+        authority.origin(),
+        serial
+    );
+    let query = LowerQuery::query(Query::query(authority.origin().into(), RecordType::AXFR));
+    let axfr_info = RequestInfo::new(
+        request_info.src,
+        request_info.protocol,
+        request_info.header,
+        &query,
+    );
+
+    match authority.search(axfr_info, lookup_options).await {
+        Ok(lookup) => Ok(lookup.iter().cloned().collect()),
+        Err(LookupError::ResponseCode(response_code)) => Err(response_code),
+        Err(e) => {
+            error!("failed to transfer {}: {}", authority.origin(), e);
+            Err(ResponseCode::ServFail)
+        }
+    }
+}
+
+fn is_multi_message(protocol: Protocol) -> bool {
+    matches!(protocol, Protocol::Tcp | Protocol::Tls)
+}
+
+/// Computes the TSIG record for one message of the response to a zone transfer
+///
+/// The first message is signed with the MAC of the request, each subsequent message with the
+///  MAC of the previous message, see RFC 8945, section 5.3.1
+#[cfg(feature = "dnssec")]
+fn sign_transfer_response<'a>(
+    signer: &TSigner,
+    previous_mac: &[u8],
+    first_message: bool,
+    build: impl FnOnce() -> MessageResponse<
+        'a,
+        'a,
+        std::slice::Iter<'a, Record>,
+        std::option::IntoIter<&'a Record>,
+        std::option::IntoIter<&'a Record>,
+        std::option::IntoIter<&'a Record>,
+    >,
+) -> ProtoResult<Record> {
+    let mut bytes = Vec::with_capacity(512);
+    build().destructive_emit(&mut BinEncoder::new(&mut bytes))?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| ProtoError::from("current time is before the unix epoch"))?
+        .as_secs() as u32;
+
+    signer.sign_response_bytes(previous_mac, &bytes, first_message, now)
+}
+
 fn lookup_options_for_edns(edns: Option<&Edns>) -> LookupOptions {
     let edns = match edns {
         Some(edns) => edns,
         None => return LookupOptions::default(),
     };
 
-    cfg_if! {
-        if #[cfg(feature = "dnssec")] {
-            let supported_algorithms = if let Some(&EdnsOption::DAU(algs)) = edns.option(EdnsCode::DAU)
-            {
-               algs
-            } else {
-               debug!("no DAU in request, used default SupportAlgorithms");
-               SupportedAlgorithms::default()
-            };
-
-            LookupOptions::for_dnssec(edns.dnssec_ok(), supported_algorithms)
+    #[cfg(feature = "dnssec")]
+    let lookup_options = {
+        let supported_algorithms = if let Some(&EdnsOption::DAU(algs)) = edns.option(EdnsCode::DAU)
+        {
+            algs
         } else {
-            LookupOptions::default()
-        }
+            debug!("no DAU in request, used default SupportAlgorithms");
+            SupportedAlgorithms::default()
+        };
+
+        LookupOptions::for_dnssec(edns.dnssec_ok(), supported_algorithms)
+    };
+    #[cfg(not(feature = "dnssec"))]
+    let lookup_options = LookupOptions::default();
+
+    lookup_options.set_client_subnet(client_subnet(Some(edns)))
+}
+
+/// The EDNS Client Subnet option of the request, see RFC 7871
+fn client_subnet(edns: Option<&Edns>) -> Option<ClientSubnet> {
+    match edns?.option(EdnsCode::Subnet) {
+        Some(&EdnsOption::Subnet(client_subnet)) => Some(client_subnet),
+        _ => None,
     }
 }
 
+/// Echoes the client subnet of the request in the response, with the scope of the answer
+///
+/// RFC 7871 requires the family, source prefix length and address of the response option to be
+///  those of the request, so that the client can match them.
+fn with_client_subnet(
+    mut response_edns: Edns,
+    request_edns: Option<&Edns>,
+    authority: &dyn AuthorityObject,
+) -> Edns {
+    if let Some(client_subnet) = client_subnet(request_edns) {
+        let scope_prefix = authority.client_subnet_scope(&client_subnet);
+        response_edns
+            .options_mut()
+            .insert(EdnsOption::Subnet(ClientSubnet::new(
+                client_subnet.address(),
+                client_subnet.source_prefix(),
+                scope_prefix,
+            )));
+    }
+
+    response_edns
+}
+
 async fn build_response(
     authority: &dyn AuthorityObject,
+    hooks: &[Box<dyn LookupHook>],
     request_info: RequestInfo<'_>,
     request_id: u16,
     request_header: &Header,
@@ -488,7 +1283,16 @@ async fn build_response(
     response_header.set_authoritative(authority.zone_type().is_authoritative());
 
     debug!("performing {} on {}", query, authority.origin());
-    let future = authority.search(request_info, lookup_options);
+    let future = async move {
+        for hook in hooks {
+            if let Some(result) = hook.lookup(request_info.clone(), lookup_options).await {
+                debug!("request: {} answered by lookup hook", request_id);
+                return result;
+            }
+        }
+
+        authority.search(request_info, lookup_options).await
+    };
 
     #[allow(deprecated)]
     let sections = match authority.zone_type() {
@@ -504,7 +1308,8 @@ async fn build_response(
             .await
         }
         ZoneType::Forward | ZoneType::Hint => {
-            send_forwarded_response(future, request_header, &mut response_header).await
+            send_forwarded_response(future, request_header, &mut response_header, lookup_options)
+                .await
         }
     };
 
@@ -538,6 +1343,7 @@ async fn send_authoritative_response(
                 ns: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
                 soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
                 additionals: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+                extended_error: None,
             };
         }
         Err(e) => {
@@ -612,17 +1418,25 @@ async fn send_authoritative_response(
         ns: ns.unwrap_or_else(|| Box::new(AuthLookup::default()) as Box<dyn LookupObject>),
         soa: soa.unwrap_or_else(|| Box::new(AuthLookup::default()) as Box<dyn LookupObject>),
         additionals,
+        extended_error: None,
     }
 }
 
+/// Sends the response of the recursive lookup
+///
+/// Answers validated with DNSSEC are Authentic Data if the request had the AD or DO bit,
+///  RFC 6840 section 5.8.
 async fn send_forwarded_response(
     future: impl Future<Output = Result<Box<dyn LookupObject>, LookupError>>,
     request_header: &Header,
     response_header: &mut Header,
+    lookup_options: LookupOptions,
 ) -> LookupSections {
     response_header.set_recursion_available(true);
     response_header.set_authoritative(false);
 
+    let mut extended_error = None;
+
     // Don't perform the recursive query if this is disabled...
     let answers = if !request_header.recursion_desired() {
         // cancel the future??
@@ -637,6 +1451,19 @@ async fn send_forwarded_response(
         Box::new(EmptyLookup)
     } else {
         match future.await {
+            Err(LookupError::ExtendedError {
+                response_code,
+                info_code,
+                extra_text,
+            }) => {
+                debug!("error resolving: {}: {}", info_code, extra_text);
+                response_header.set_response_code(response_code);
+                extended_error = Some(EdnsOption::ExtendedError {
+                    info_code,
+                    extra_text,
+                });
+                Box::new(EmptyLookup)
+            }
             Err(e) => {
                 if e.is_nx_domain() {
                     response_header.set_response_code(ResponseCode::NXDomain);
@@ -644,7 +1471,15 @@ async fn send_forwarded_response(
                 debug!("error resolving: {}", e);
                 Box::new(EmptyLookup)
             }
-            Ok(rsp) => rsp,
+            Ok(rsp) => {
+                if rsp.is_authentic()
+                    && (request_header.authentic_data() || lookup_options.is_dnssec())
+                {
+                    response_header.set_authentic_data(true);
+                }
+                extended_error = rsp.extended_error();
+                rsp
+            }
         }
     };
 
@@ -653,6 +1488,143 @@ async fn send_forwarded_response(
         ns: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
         soa: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
         additionals: Box::new(AuthLookup::default()) as Box<dyn LookupObject>,
+        extended_error,
+    }
+}
+
+/// Checks the answer, then the name servers of the queried name, against the response policies
+async fn check_response_policies(
+    policies: &[ResponsePolicyZone],
+    authority: &dyn AuthorityObject,
+    query: &LowerQuery,
+    answer_ips: &[IpAddr],
+) -> Option<PolicyHit> {
+    let mut name_servers = None;
+    for policy in policies {
+        if let Some(hit) = policy.check_ips(answer_ips).await {
+            return Some(hit);
+        }
+
+        if !policy.has_ns_triggers().await {
+            continue;
+        }
+
+        if name_servers.is_none() {
+            name_servers = Some(find_name_servers(authority, query.name()).await);
+        }
+        let (names, ips) = name_servers.as_ref().expect("name servers were looked up");
+        if let Some(hit) = policy.check_name_servers(names, ips).await {
+            return Some(hit);
+        }
+    }
+
+    None
+}
+
+/// Looks up the names and addresses of the name servers of the closest enclosing zone of the name
+async fn find_name_servers(
+    authority: &dyn AuthorityObject,
+    name: &LowerName,
+) -> (Vec<LowerName>, Vec<IpAddr>) {
+    let mut zone = name.clone();
+    let names = loop {
+        let names = match authority
+            .lookup(&zone, RecordType::NS, LookupOptions::default())
+            .await
+        {
+            Ok(lookup) => lookup
+                .iter()
+                .filter_map(|r| match r.data() {
+                    Some(RData::NS(ns)) => Some(LowerName::from(ns)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            Err(_) => Vec::new(),
+        };
+
+        if !names.is_empty() || zone.is_root() {
+            break names;
+        }
+        zone = zone.base_name();
+    };
+
+    let mut ips = Vec::new();
+    for ns in &names {
+        for record_type in [RecordType::A, RecordType::AAAA] {
+            if let Ok(lookup) = authority
+                .lookup(ns, record_type, LookupOptions::default())
+                .await
+            {
+                ips.extend(addresses(lookup.iter()));
+            }
+        }
+    }
+
+    (names, ips)
+}
+
+fn addresses<'a>(records: impl Iterator<Item = &'a Record>) -> Vec<IpAddr> {
+    records
+        .filter_map(|r| match r.data() {
+            Some(RData::A(ip)) => Some(IpAddr::V4(*ip)),
+            Some(RData::AAAA(ip)) => Some(IpAddr::V6(*ip)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn log_policy_hit(request_info: &RequestInfo<'_>, hit: &PolicyHit) {
+    info!(
+        "response policy: {} from {} matched {:?} rule {} of {}: {:?}",
+        request_info.query,
+        request_info.src,
+        hit.trigger(),
+        hit.rule(),
+        hit.zone(),
+        hit.action(),
+    );
+}
+
+/// Responds as the rule of the response policy requires, or not at all for DROP
+async fn send_policy_response<R: ResponseHandler>(
+    hit: &PolicyHit,
+    request: &Request,
+    response_edns: Option<Edns>,
+    response_handle: R,
+) -> ResponseInfo {
+    let query = request.request_info().query;
+    let mut response_header = Header::response_from_request(request.header());
+    response_header.set_recursion_available(true);
+
+    let answers = match hit.action() {
+        PolicyAction::Drop => {
+            debug!("dropping request: {}", request.id());
+            return response_header.into();
+        }
+        PolicyAction::NxDomain => {
+            response_header.set_response_code(ResponseCode::NXDomain);
+            Vec::new()
+        }
+        PolicyAction::NoData | PolicyAction::Passthru => Vec::new(),
+        action @ PolicyAction::LocalData(_) => {
+            action.local_data(&Name::from(query.name()), query.query_type())
+        }
+    };
+
+    let response = MessageResponseBuilder::new(Some(request.raw_query())).build(
+        response_header,
+        answers.iter(),
+        None.iter(),
+        None.iter(),
+        None.iter(),
+    );
+
+    match send_response(response_edns, response, response_handle).await {
+        Err(e) => {
+            error!("error sending response: {}", e);
+            ResponseInfo::serve_failed()
+        }
+        Ok(i) => i,
     }
 }
 
@@ -661,4 +1633,6 @@ struct LookupSections {
     ns: Box<dyn LookupObject>,
     soa: Box<dyn LookupObject>,
     additionals: Box<dyn LookupObject>,
+    /// The Extended DNS Error of a failed lookup, RFC 8914
+    extended_error: Option<EdnsOption>,
 }
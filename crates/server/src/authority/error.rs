@@ -10,7 +10,7 @@ use std::io;
 use enum_as_inner::EnumAsInner;
 use thiserror::Error;
 
-use crate::client::op::ResponseCode;
+use crate::client::{op::ResponseCode, rr::rdata::opt::ExtendedErrorCode};
 #[cfg(feature = "trust-dns-resolver")]
 use crate::resolver::error::ResolveError;
 
@@ -36,6 +36,16 @@ pub enum LookupError {
     #[cfg_attr(docsrs, doc(cfg(feature = "recursor")))]
     #[error("Recursive resolution error: {0}")]
     RecursiveError(#[from] trust_dns_recursor::Error),
+    /// The lookup failed with the response code, and the Extended DNS Error of RFC 8914
+    #[error("Error performing lookup: {response_code}, {info_code}: {extra_text}")]
+    ExtendedError {
+        /// The response code of the response
+        response_code: ResponseCode,
+        /// The code of the Extended DNS Error
+        info_code: ExtendedErrorCode,
+        /// The text of the Extended DNS Error
+        extra_text: String,
+    },
     /// An underlying IO error occurred
     #[error("io error: {0}")]
     Io(io::Error),
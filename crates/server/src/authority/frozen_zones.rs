@@ -0,0 +1,57 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The zones for which dynamic updates are suspended
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
+
+use crate::client::rr::LowerName;
+
+/// The set of zones which are frozen, updates to these zones are refused
+///
+/// Freezing a zone allows its zone file to be edited by hand while the server is running, see
+///  `Authority::persist`. The set is shared by clones, so that it is kept when the `Catalog` is
+///  reloaded, see `Catalog::set_frozen_zones`.
+#[derive(Clone, Default)]
+pub struct FrozenZones {
+    zones: Arc<RwLock<HashSet<LowerName>>>,
+}
+
+impl FrozenZones {
+    /// Constructs an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Freezes the zone, returns false if it was already frozen
+    pub fn freeze(&self, zone: LowerName) -> bool {
+        self.zones.write().expect("lock poisoned").insert(zone)
+    }
+
+    /// Thaws the zone, returns false if it was not frozen
+    pub fn thaw(&self, zone: &LowerName) -> bool {
+        self.zones.write().expect("lock poisoned").remove(zone)
+    }
+
+    /// Returns true if the zone is frozen
+    pub fn is_frozen(&self, zone: &LowerName) -> bool {
+        self.zones.read().expect("lock poisoned").contains(zone)
+    }
+
+    /// The frozen zones
+    pub fn zones(&self) -> Vec<LowerName> {
+        self.zones
+            .read()
+            .expect("lock poisoned")
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Roles and lifetimes of the DNSSEC keys which sign a zone, for rolling over keys
+
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::authority::DnssecAuthority;
+use crate::client::rr::RecordType;
+
+/// The record sets of the zone which a key signs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyRole {
+    /// A key signing key (KSK), the secure entry point referenced by the DS of the parent.
+    ///
+    /// It only signs the DNSKEY, CDS and CDNSKEY record sets.
+    KeySigning,
+    /// A zone signing key (ZSK), signs all record sets except those signed by the KSK
+    ZoneSigning,
+    /// A combined signing key (CSK), the secure entry point which also signs all record sets
+    Combined,
+}
+
+impl KeyRole {
+    /// True if the key is referenced by the DS of the parent, i.e. the SEP flag is set
+    pub fn is_secure_entry_point(self) -> bool {
+        !matches!(self, Self::ZoneSigning)
+    }
+
+    /// True if the key signs record sets of the type
+    pub fn signs(self, record_type: RecordType) -> bool {
+        let is_key_set = matches!(
+            record_type,
+            RecordType::DNSKEY | RecordType::CDS | RecordType::CDNSKEY
+        );
+
+        match self {
+            Self::KeySigning => is_key_set,
+            Self::ZoneSigning => !is_key_set,
+            Self::Combined => true,
+        }
+    }
+}
+
+/// The point in its lifetime which a key has reached, see `KeyTimings::state`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyState {
+    /// The key is not yet published in the zone
+    Pending,
+    /// The DNSKEY is published, but the key doesn't sign the zone yet
+    Published,
+    /// The DNSKEY is published and the key signs the zone
+    Active,
+    /// The DNSKEY is still published, but the key no longer signs the zone
+    Retired,
+    /// The DNSKEY is removed from the zone
+    Removed,
+}
+
+impl KeyState {
+    /// True if the DNSKEY is in the zone
+    pub fn is_published(self) -> bool {
+        matches!(self, Self::Published | Self::Active | Self::Retired)
+    }
+
+    /// True if the key signs the zone
+    pub fn is_active(self) -> bool {
+        self == Self::Active
+    }
+}
+
+/// The times, in seconds since the unix epoch, at which a key moves through its lifetime
+///
+/// A key without any times is published and signs the zone from when it's added, forever.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyTimings {
+    publish: Option<u64>,
+    activate: Option<u64>,
+    inactive: Option<u64>,
+    delete: Option<u64>,
+}
+
+impl KeyTimings {
+    /// Creates the timings of a key
+    ///
+    /// # Arguments
+    ///
+    /// * `publish` - when the DNSKEY is added to the zone, defaults to immediately
+    /// * `activate` - when the key starts signing the zone, defaults to `publish`
+    /// * `inactive` - when the key stops signing the zone, defaults to never
+    /// * `delete` - when the DNSKEY is removed from the zone, defaults to never
+    pub fn new(
+        publish: Option<u64>,
+        activate: Option<u64>,
+        inactive: Option<u64>,
+        delete: Option<u64>,
+    ) -> Self {
+        Self {
+            publish,
+            activate,
+            inactive,
+            delete,
+        }
+    }
+
+    /// When the DNSKEY is added to the zone
+    pub fn publish(&self) -> Option<u64> {
+        self.publish
+    }
+
+    /// When the key starts signing the zone
+    pub fn activate(&self) -> Option<u64> {
+        self.activate.or(self.publish)
+    }
+
+    /// When the key stops signing the zone
+    pub fn inactive(&self) -> Option<u64> {
+        self.inactive
+    }
+
+    /// When the DNSKEY is removed from the zone
+    pub fn delete(&self) -> Option<u64> {
+        self.delete
+    }
+
+    /// Returns the state of the key at the time
+    pub fn state(&self, now: u64) -> KeyState {
+        let reached = |time: Option<u64>| time.map_or(false, |time| now >= time);
+
+        if reached(self.delete) {
+            KeyState::Removed
+        } else if reached(self.inactive) {
+            KeyState::Retired
+        } else if self.activate().map_or(true, |time| now >= time) {
+            KeyState::Active
+        } else if self.publish.map_or(true, |time| now >= time) {
+            KeyState::Published
+        } else {
+            KeyState::Pending
+        }
+    }
+}
+
+/// The method by which a key is replaced with its successor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RolloverMethod {
+    /// The successor is published before the rollover and activated at it, the usual method for
+    ///  ZSKs as only the DNSKEY set grows.
+    ///
+    /// The current key is removed once its signatures have expired from caches.
+    PrePublish,
+    /// The successor is published and signs alongside the current key before the rollover, the
+    ///  usual method for KSKs as the parent's DS can be switched at any time in between.
+    ///
+    /// The current key is removed at the rollover.
+    DoubleSignature,
+}
+
+impl RolloverMethod {
+    /// Schedules the replacement of a key with a successor
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - timings of the key being replaced
+    /// * `roll_at` - when the successor takes over from the current key
+    /// * `propagation` - seconds for changes to the zone to expire from caches, generally the
+    ///                   largest TTL of the zone plus the time for secondaries to transfer it
+    ///
+    /// # Return value
+    ///
+    /// The timings of the current key and of its successor
+    pub fn schedule(
+        self,
+        current: KeyTimings,
+        roll_at: u64,
+        propagation: u64,
+    ) -> (KeyTimings, KeyTimings) {
+        let introduce_at = roll_at.saturating_sub(propagation);
+
+        match self {
+            Self::PrePublish => (
+                KeyTimings {
+                    inactive: Some(roll_at),
+                    delete: Some(roll_at + propagation),
+                    ..current
+                },
+                KeyTimings::new(Some(introduce_at), Some(roll_at), None, None),
+            ),
+            Self::DoubleSignature => (
+                KeyTimings {
+                    inactive: Some(roll_at),
+                    delete: Some(roll_at),
+                    ..current
+                },
+                KeyTimings::new(Some(introduce_at), Some(introduce_at), None, None),
+            ),
+        }
+    }
+}
+
+/// Returns the current time in seconds since the unix epoch
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Periodically publishes, activates and retires the keys of the zone as scheduled, and re-signs
+///  the zone before its signatures expire, see `DnssecAuthority::maintain_keys`
+///
+/// The task ends once the authority is dropped.
+pub fn spawn_key_maintenance<A>(authority: &Arc<A>, interval: Duration) -> JoinHandle<()>
+where
+    A: DnssecAuthority + 'static,
+{
+    tokio::spawn(maintenance_loop(Arc::downgrade(authority), interval))
+}
+
+async fn maintenance_loop<A>(authority: Weak<A>, interval: Duration)
+where
+    A: DnssecAuthority + 'static,
+{
+    loop {
+        match authority.upgrade() {
+            Some(authority) => match authority.maintain_keys(unix_now()).await {
+                Ok(true) => info!("re-signed zone: {}", authority.origin()),
+                Ok(false) => debug!("keys of zone are current: {}", authority.origin()),
+                Err(e) => warn!("failed to maintain keys of {}: {}", authority.origin(), e),
+            },
+            None => return,
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_states() {
+        let timings = KeyTimings::new(Some(100), Some(200), Some(300), Some(400));
+
+        assert_eq!(timings.state(99), KeyState::Pending);
+        assert_eq!(timings.state(100), KeyState::Published);
+        assert_eq!(timings.state(200), KeyState::Active);
+        assert_eq!(timings.state(300), KeyState::Retired);
+        assert_eq!(timings.state(400), KeyState::Removed);
+
+        assert_eq!(KeyTimings::default().state(0), KeyState::Active);
+        assert_eq!(
+            KeyTimings::new(Some(100), None, None, None).state(100),
+            KeyState::Active
+        );
+    }
+
+    #[test]
+    fn test_roles() {
+        assert!(KeyRole::KeySigning.signs(RecordType::DNSKEY));
+        assert!(!KeyRole::KeySigning.signs(RecordType::A));
+        assert!(!KeyRole::ZoneSigning.signs(RecordType::CDS));
+        assert!(KeyRole::ZoneSigning.signs(RecordType::SOA));
+        assert!(KeyRole::Combined.signs(RecordType::CDNSKEY));
+        assert!(!KeyRole::ZoneSigning.is_secure_entry_point());
+    }
+
+    #[test]
+    fn test_pre_publish() {
+        let (current, next) = RolloverMethod::PrePublish.schedule(KeyTimings::default(), 1000, 100);
+
+        assert_eq!(current.state(899), KeyState::Active);
+        assert_eq!(next.state(899), KeyState::Pending);
+        assert_eq!(next.state(900), KeyState::Published);
+        assert_eq!(current.state(1000), KeyState::Retired);
+        assert_eq!(next.state(1000), KeyState::Active);
+        assert_eq!(current.state(1100), KeyState::Removed);
+    }
+
+    #[test]
+    fn test_double_signature() {
+        let (current, next) =
+            RolloverMethod::DoubleSignature.schedule(KeyTimings::default(), 1000, 100);
+
+        assert_eq!(current.state(900), KeyState::Active);
+        assert_eq!(next.state(900), KeyState::Active);
+        assert_eq!(current.state(1000), KeyState::Removed);
+        assert_eq!(next.state(1000), KeyState::Active);
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Hooks which answer queries dynamically, in place of the authority of the zone
+
+use crate::{
+    authority::{LookupError, LookupObject, LookupOptions},
+    server::RequestInfo,
+};
+
+/// A hook which may answer a query before the authority of the zone is searched
+///
+/// Hooks are added to the `Catalog`, and are consulted for queries of all zones of the catalog,
+///  in the order they were added. They allow answers to be computed at lookup time, e.g. from
+///  service discovery or health checks, while the zone still provides the SOA, NS and other
+///  records. The response is built from the answer of the hook as if it came from the authority.
+#[async_trait::async_trait]
+pub trait LookupHook: Send + Sync {
+    /// Answers the query, or returns `None` to leave the query to later hooks and the authority
+    ///
+    /// # Arguments
+    ///
+    /// * `request_info` - the query and the client which sent it
+    /// * `lookup_options` - the DNSSEC options and client subnet of the request
+    async fn lookup(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> Option<Result<Box<dyn LookupObject>, LookupError>>;
+}
@@ -15,7 +15,7 @@ use crate::{
             message::{self, EmitAndCount},
             Edns, Header, Message, MessageType, OpCode, ResponseCode,
         },
-        rr::Record,
+        rr::{Record, RecordType},
         serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder},
     },
 };
@@ -30,6 +30,7 @@ pub struct MessageRequest {
     additionals: Vec<Record>,
     sig0: Vec<Record>,
     edns: Option<Edns>,
+    signed_bytes: Option<Box<[u8]>>,
 }
 
 impl MessageRequest {
@@ -160,6 +161,14 @@ impl MessageRequest {
         &self.sig0
     }
 
+    /// The message as it was received, if it's signed with TSIG
+    ///
+    /// The TSIG MAC covers the message in the form it was sent, which can't be recovered by
+    ///  emitting the message again, as the sender may have compressed the names differently.
+    pub fn signed_bytes(&self) -> Option<&[u8]> {
+        self.signed_bytes.as_deref()
+    }
+
     /// # Return value
     ///
     /// the max payload value as it's defined in the EDNS section.
@@ -189,6 +198,7 @@ impl<'q> BinDecodable<'q> for MessageRequest {
     // TODO: generify this with Message?
     /// Reads a MessageRequest from the decoder
     fn read(decoder: &mut BinDecoder<'q>) -> ProtoResult<Self> {
+        let start = decoder.index();
         let mut header = Header::read(decoder)?;
 
         let mut try_parse_rest = move || {
@@ -210,6 +220,16 @@ impl<'q> BinDecodable<'q> for MessageRequest {
                 header.merge_response_code(high_response_code);
             }
 
+            // only kept for TSIG, so that the bytes aren't copied for every other request
+            let signed_bytes = if sig0
+                .last()
+                .map_or(false, |sig| sig.rr_type() == RecordType::TSIG)
+            {
+                Some(decoder.slice_from(start)?.to_vec().into_boxed_slice())
+            } else {
+                None
+            };
+
             Ok(Self {
                 header,
                 query,
@@ -218,6 +238,7 @@ impl<'q> BinDecodable<'q> for MessageRequest {
                 additionals,
                 sig0,
                 edns,
+                signed_bytes,
             })
         };
 
@@ -16,7 +16,7 @@ use crate::{
             message::{self, EmitAndCount},
             Edns, Header, ResponseCode,
         },
-        rr::Record,
+        rr::{Record, RecordType},
         serialize::binary::BinEncoder,
     },
     server::ResponseInfo,
@@ -41,6 +41,8 @@ where
     additionals: Additionals,
     sig0: Vec<Record>,
     edns: Option<Edns>,
+    max_size: Option<u16>,
+    minimal: bool,
 }
 
 enum EmptyOrQueries<'q> {
@@ -96,8 +98,67 @@ where
         self
     }
 
+    /// Get a mutable reference to the EDNS options of the Response, if it has any
+    pub fn edns_mut(&mut self) -> Option<&mut Edns> {
+        self.edns.as_mut()
+    }
+
+    /// Limits the size of the emitted Response, e.g. to the payload size of the client over UDP
+    ///
+    /// The answers and name servers which do not fit are omitted an RRset at a time, and the
+    ///  Response is marked as truncated so that the client retries over TCP. Signed Responses are
+    ///  not limited, as the signature covers all of their records.
+    pub fn set_max_size(&mut self, max_size: u16) -> &mut Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Omits the records of the authority and additional sections which are not required
+    ///
+    /// Responses with answers only keep the NSEC and NSEC3 records proving them, e.g. the
+    ///  expansion of a wildcard, responses without answers are not changed, as their authority
+    ///  section is the answer, and their additional section holds the glue of referrals. Signed
+    ///  Responses are not changed, as the signature covers all of their records.
+    pub fn set_minimal(&mut self, minimal: bool) -> &mut Self {
+        self.minimal = minimal;
+        self
+    }
+
+    /// Drops all records of the response and sets the truncated flag, so that the client retries
+    ///  over TCP
+    pub(crate) fn into_truncated(
+        self,
+    ) -> MessageResponse<
+        'q,
+        'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+        impl Iterator<Item = &'a Record> + Send + 'a,
+    > {
+        let mut header = self.header;
+        header.set_truncated(true);
+
+        MessageResponse {
+            header,
+            query: self.query,
+            answers: None.into_iter(),
+            name_servers: None.into_iter(),
+            soa: None.into_iter(),
+            additionals: None.into_iter(),
+            sig0: Vec::new(),
+            edns: self.edns,
+            max_size: self.max_size,
+            minimal: self.minimal,
+        }
+    }
+
     /// Consumes self, and emits to the encoder.
     pub fn destructive_emit(mut self, encoder: &mut BinEncoder<'_>) -> ProtoResult<ResponseInfo> {
+        if self.sig0.is_empty() && (self.minimal || self.max_size.is_some()) {
+            return self.emit_limited(encoder);
+        }
+
         // soa records are part of the nameserver section
         let mut name_servers = self.name_servers.chain(self.soa);
 
@@ -113,6 +174,62 @@ where
         )
         .map(Into::into)
     }
+
+    /// Emits the response with the records which are required and fit into the maximum size
+    fn emit_limited(self, encoder: &mut BinEncoder<'_>) -> ProtoResult<ResponseInfo> {
+        let answers = self.answers.collect::<Vec<_>>();
+        // soa records are part of the nameserver section
+        let mut name_servers = self.name_servers.chain(self.soa).collect::<Vec<_>>();
+        let mut additionals = self.additionals.collect::<Vec<_>>();
+
+        if self.minimal && !answers.is_empty() {
+            name_servers.retain(|record| is_proof(record));
+            additionals.clear();
+        }
+
+        let mut queries = EmptyOrQueries::from(self.query);
+        match self.max_size {
+            Some(max_size) => message::emit_message_parts_with_max_size(
+                &self.header,
+                &mut queries,
+                &answers,
+                &name_servers,
+                &additionals,
+                self.edns.as_ref(),
+                &self.sig0,
+                encoder,
+                max_size,
+            ),
+            None => message::emit_message_parts(
+                &self.header,
+                &mut queries,
+                &mut answers.into_iter(),
+                &mut name_servers.into_iter(),
+                &mut additionals.into_iter(),
+                self.edns.as_ref(),
+                &self.sig0,
+                encoder,
+            ),
+        }
+        .map(Into::into)
+    }
+}
+
+/// Whether the record of the authority section proves the answers, i.e. is an NSEC or NSEC3
+///  record or the RRSIG of one
+fn is_proof(record: &Record) -> bool {
+    match record.record_type() {
+        RecordType::NSEC | RecordType::NSEC3 => true,
+        #[cfg(feature = "dnssec")]
+        RecordType::RRSIG => record
+            .data()
+            .and_then(crate::proto::rr::RData::as_dnssec)
+            .and_then(|rdata| rdata.as_sig())
+            .map_or(false, |sig| {
+                matches!(sig.type_covered(), RecordType::NSEC | RecordType::NSEC3)
+            }),
+        _ => false,
+    }
 }
 
 /// A builder for MessageResponses
@@ -151,6 +268,12 @@ impl<'q> MessageResponseBuilder<'q> {
         self
     }
 
+    /// Associate SIG0 or TSIG records with the Response, these will be the final records
+    pub fn sig0(&mut self, sig0: Vec<Record>) -> &mut Self {
+        self.sig0 = Some(sig0);
+        self
+    }
+
     /// Constructs the new MessageResponse with associated Header
     ///
     /// # Arguments
@@ -183,6 +306,8 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: additionals.into_iter(),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            max_size: None,
+            minimal: false,
         }
     }
 
@@ -207,6 +332,8 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            max_size: None,
+            minimal: false,
         }
     }
 
@@ -241,6 +368,8 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            max_size: None,
+            minimal: false,
         }
     }
 }
@@ -279,6 +408,8 @@ mod tests {
                 additionals: iter::once(&answer),
                 sig0: vec![],
                 edns: None,
+                max_size: None,
+                minimal: false,
             };
 
             message
@@ -315,6 +446,8 @@ mod tests {
                 additionals: iter::repeat(&answer),
                 sig0: vec![],
                 edns: None,
+                max_size: None,
+                minimal: false,
             };
 
             message
@@ -327,4 +460,99 @@ mod tests {
         assert_eq!(response.answer_count(), 0);
         assert!(response.name_server_count() > 1);
     }
+
+    fn a_record(name: &str, last_octet: u8) -> Record {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            86400,
+            RData::A(Ipv4Addr::new(192, 0, 2, last_octet)),
+        )
+    }
+
+    #[test]
+    fn test_max_size_truncates_rrsets() {
+        let www = (0..5).map(|i| a_record("www.example.com.", i));
+        let mail = (0..40).map(|i| a_record("mail.example.com.", i));
+        let answers = www.chain(mail).collect::<Vec<_>>();
+        let additional = a_record("ns.example.com.", 1);
+
+        let mut buf = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut buf);
+            let mut message = MessageResponse {
+                header: Header::new(),
+                query: None,
+                answers: answers.iter(),
+                name_servers: iter::empty(),
+                soa: iter::empty(),
+                additionals: iter::once(&additional),
+                sig0: vec![],
+                edns: Some(Edns::new()),
+                max_size: None,
+                minimal: false,
+            };
+            message.set_max_size(512);
+
+            message
+                .destructive_emit(&mut encoder)
+                .expect("failed to encode");
+        }
+
+        assert!(buf.len() <= 512);
+        let response = Message::from_vec(&buf).expect("failed to decode");
+        assert!(response.header().truncated());
+        // the RRset of mail.example.com. does not fit, so none of it is sent
+        assert_eq!(response.answer_count(), 5);
+        assert!(response.additionals().is_empty());
+        assert!(response.extensions().is_some());
+    }
+
+    #[test]
+    fn test_minimal() {
+        let answer = a_record("www.example.com.", 1);
+        let ns = Record::with(
+            Name::from_str("example.com.").unwrap(),
+            RecordType::NS,
+            86400,
+        );
+        let nsec = Record::with(
+            Name::from_str("example.com.").unwrap(),
+            RecordType::NSEC,
+            86400,
+        );
+        let glue = a_record("ns.example.com.", 1);
+
+        let emit = |answers: &[Record]| {
+            let mut buf = Vec::new();
+            let mut encoder = BinEncoder::new(&mut buf);
+            let mut message = MessageResponse {
+                header: Header::new(),
+                query: None,
+                answers: answers.iter(),
+                name_servers: vec![&ns, &nsec].into_iter(),
+                soa: iter::empty(),
+                additionals: iter::once(&glue),
+                sig0: vec![],
+                edns: None,
+                max_size: None,
+                minimal: false,
+            };
+            message.set_minimal(true);
+
+            message
+                .destructive_emit(&mut encoder)
+                .expect("failed to encode")
+        };
+
+        // only the proofs of the answers are required
+        let info = emit(&[answer]);
+        assert_eq!(info.answer_count(), 1);
+        assert_eq!(info.name_server_count(), 1);
+        assert_eq!(info.additional_count(), 0);
+
+        // referrals keep their glue
+        let info = emit(&[]);
+        assert_eq!(info.name_server_count(), 2);
+        assert_eq!(info.additional_count(), 1);
+    }
 }
@@ -18,8 +18,16 @@ mod authority;
 pub(crate) mod authority_object;
 mod catalog;
 mod error;
+mod frozen_zones;
+#[cfg(feature = "dnssec")]
+mod key_schedule;
+mod lookup_hook;
 pub(crate) mod message_request;
 mod message_response;
+mod response_policy;
+pub(crate) mod synthesis;
+mod view;
+pub(crate) mod zone_transfer;
 mod zone_type;
 
 pub use self::auth_lookup::{
@@ -29,10 +37,23 @@ pub use self::authority::{Authority, LookupOptions};
 pub use self::authority_object::{AuthorityObject, EmptyLookup, LookupObject};
 pub use self::catalog::Catalog;
 pub use self::error::{LookupError, LookupResult};
+pub use self::frozen_zones::FrozenZones;
+pub use self::lookup_hook::LookupHook;
 pub use self::message_request::{MessageRequest, Queries, UpdateRequest};
 pub use self::message_response::{MessageResponse, MessageResponseBuilder};
+pub use self::response_policy::{PolicyAction, PolicyHit, PolicyTrigger, ResponsePolicyZone};
+pub use self::synthesis::{synthesize_aname, synthesize_wildcard, wildcard_of};
+pub use self::view::View;
+pub use self::zone_transfer::{send_notify, ZoneDiff};
 pub use self::zone_type::ZoneType;
 
 #[cfg(feature = "dnssec")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
 pub use self::authority::DnssecAuthority;
+#[cfg(feature = "dnssec")]
+pub(crate) use self::key_schedule::unix_now;
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+pub use self::key_schedule::{
+    spawn_key_maintenance, KeyRole, KeyState, KeyTimings, RolloverMethod,
+};
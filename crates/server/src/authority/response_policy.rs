@@ -0,0 +1,557 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Response Policy Zones (RPZ), rewriting responses for a DNS firewall
+//!
+//! See [draft-vixie-dnsop-dns-rpz](https://tools.ietf.org/html/draft-vixie-dnsop-dns-rpz-00).
+//!  The rules of a policy zone are owner names encoding the trigger, with records encoding the
+//!  action:
+//!
+//! ```text
+//! bad.example.com.rpz.          CNAME .                  ; NXDOMAIN
+//! *.bad.example.com.rpz.        CNAME *.                 ; NODATA
+//! good.example.com.rpz.         CNAME rpz-passthru.      ; PASSTHRU
+//! 24.0.2.0.192.rpz-ip.rpz.      CNAME rpz-drop.          ; DROP, answers in 192.0.2.0/24
+//! ns.bad.example.rpz-nsdname.rpz. A 192.0.2.1            ; Local-Data, by name server
+//! 32.1.2.0.192.rpz-nsip.rpz.    CNAME .                  ; NXDOMAIN, by name server address
+//! ```
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use ipnet::IpNet;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::{
+    authority::{zone_transfer, AuthorityObject, LookupOptions},
+    client::rr::{LowerName, Name, RData, Record, RecordType},
+};
+
+const RPZ_IP: &[u8] = b"rpz-ip";
+const RPZ_NSDNAME: &[u8] = b"rpz-nsdname";
+const RPZ_NSIP: &[u8] = b"rpz-nsip";
+const RPZ_CLIENT_IP: &[u8] = b"rpz-client-ip";
+
+/// What is done with a response which triggered a policy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyAction {
+    /// Respond that the name doesn't exist
+    NxDomain,
+    /// Respond that the name has no records of the type
+    NoData,
+    /// Respond normally, no further policies are applied
+    Passthru,
+    /// Don't respond at all
+    Drop,
+    /// Respond with these records instead, their owner names are those of the rule
+    LocalData(Vec<Record>),
+}
+
+impl PolicyAction {
+    /// Returns the answers for the query name and type, for `LocalData`
+    ///
+    /// A CNAME answers queries of all types.
+    pub fn local_data(&self, name: &Name, query_type: RecordType) -> Vec<Record> {
+        let records = match self {
+            Self::LocalData(records) => records,
+            _ => return Vec::new(),
+        };
+
+        records
+            .iter()
+            .filter(|r| {
+                query_type == RecordType::ANY
+                    || r.rr_type() == query_type
+                    || r.rr_type() == RecordType::CNAME
+            })
+            .map(|r| {
+                let mut record = r.clone();
+                record.set_name(name.clone());
+                record
+            })
+            .collect()
+    }
+}
+
+/// The data of a response which triggered a policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyTrigger {
+    /// The query name
+    QName,
+    /// An address in the answer
+    Ip,
+    /// The name of a name server of the queried name
+    NsDname,
+    /// The address of a name server of the queried name
+    NsIp,
+}
+
+/// A rule of a policy zone which was triggered
+#[derive(Clone, Debug)]
+pub struct PolicyHit {
+    zone: LowerName,
+    trigger: PolicyTrigger,
+    rule: Name,
+    action: PolicyAction,
+}
+
+impl PolicyHit {
+    /// The origin of the policy zone
+    pub fn zone(&self) -> &LowerName {
+        &self.zone
+    }
+
+    /// The data of the response which triggered the policy
+    pub fn trigger(&self) -> PolicyTrigger {
+        self.trigger
+    }
+
+    /// The owner name of the rule in the policy zone
+    pub fn rule(&self) -> &Name {
+        &self.rule
+    }
+
+    /// The action of the rule
+    pub fn action(&self) -> &PolicyAction {
+        &self.action
+    }
+}
+
+/// A zone of response policies
+///
+/// The rules are compiled from the records of the zone, and recompiled when its serial changes,
+///  so that the zone may be updated or transferred from a policy provider.
+pub struct ResponsePolicyZone {
+    authority: Box<dyn AuthorityObject>,
+    rules: RwLock<Arc<PolicyRules>>,
+}
+
+impl ResponsePolicyZone {
+    /// Uses the zone of the authority for response policies
+    pub fn new(authority: Box<dyn AuthorityObject>) -> Self {
+        Self {
+            authority,
+            rules: RwLock::new(Arc::new(PolicyRules::default())),
+        }
+    }
+
+    /// The origin of the policy zone
+    pub fn origin(&self) -> &LowerName {
+        self.authority.origin()
+    }
+
+    /// Returns the rules for the current version of the zone
+    async fn rules(&self) -> Arc<PolicyRules> {
+        let serial = match self.authority.soa().await {
+            Ok(soa) => soa.iter().next().and_then(zone_transfer::soa_serial),
+            Err(_) => None,
+        };
+
+        let rules = self.rules.read().await;
+        if rules.serial == serial {
+            return Arc::clone(&rules);
+        }
+        drop(rules);
+
+        let records = match self
+            .authority
+            .lookup(self.origin(), RecordType::AXFR, LookupOptions::default())
+            .await
+        {
+            Ok(lookup) => lookup.iter().cloned().collect::<Vec<_>>(),
+            Err(e) => {
+                warn!(
+                    "failed to read response policy zone {}: {}",
+                    self.origin(),
+                    e
+                );
+                Vec::new()
+            }
+        };
+
+        debug!(
+            "compiling response policy zone {} at serial: {:?}",
+            self.origin(),
+            serial
+        );
+        let rules = Arc::new(PolicyRules::from_records(
+            &self.origin().into(),
+            serial,
+            &records,
+        ));
+        *self.rules.write().await = Arc::clone(&rules);
+        rules
+    }
+
+    /// True if the zone has name server triggers, which require looking up the name servers
+    pub async fn has_ns_triggers(&self) -> bool {
+        let rules = self.rules().await;
+        !rules.ns_names.is_empty() || !rules.ns_ips.is_empty()
+    }
+
+    /// Returns the rule triggered by the query name
+    pub async fn check_qname(&self, name: &LowerName) -> Option<PolicyHit> {
+        let rules = self.rules().await;
+        self.hit(PolicyTrigger::QName, rules.qnames.find(name))
+    }
+
+    /// Returns the rule triggered by the addresses of the answer, the longest prefix matches
+    pub async fn check_ips(&self, ips: &[IpAddr]) -> Option<PolicyHit> {
+        let rules = self.rules().await;
+        self.hit(PolicyTrigger::Ip, find_ip(&rules.ips, ips))
+    }
+
+    /// Returns the rule triggered by the name servers of the queried name, names before addresses
+    pub async fn check_name_servers(
+        &self,
+        names: &[LowerName],
+        ips: &[IpAddr],
+    ) -> Option<PolicyHit> {
+        let rules = self.rules().await;
+
+        names
+            .iter()
+            .find_map(|name| self.hit(PolicyTrigger::NsDname, rules.ns_names.find(name)))
+            .or_else(|| self.hit(PolicyTrigger::NsIp, find_ip(&rules.ns_ips, ips)))
+    }
+
+    fn hit(&self, trigger: PolicyTrigger, rule: Option<&Rule>) -> Option<PolicyHit> {
+        rule.map(|rule| PolicyHit {
+            zone: self.origin().clone(),
+            trigger,
+            rule: rule.owner.clone(),
+            action: rule.action.clone(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    owner: Name,
+    action: PolicyAction,
+}
+
+#[derive(Default)]
+struct NameRules {
+    exact: HashMap<LowerName, Rule>,
+    // keyed by the name below the wildcard label
+    wildcards: HashMap<LowerName, Rule>,
+}
+
+impl NameRules {
+    fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.wildcards.is_empty()
+    }
+
+    fn insert(&mut self, name: Name, rule: Rule) {
+        if name.is_wildcard() {
+            self.wildcards
+                .insert(LowerName::new(&name.base_name()), rule);
+        } else {
+            self.exact.insert(LowerName::new(&name), rule);
+        }
+    }
+
+    /// Exact matches take precedence over wildcards, then the closest wildcard matches
+    fn find(&self, name: &LowerName) -> Option<&Rule> {
+        if let Some(rule) = self.exact.get(name) {
+            return Some(rule);
+        }
+
+        let mut name = name.clone();
+        while !name.is_root() {
+            name = name.base_name();
+            if let Some(rule) = self.wildcards.get(&name) {
+                return Some(rule);
+            }
+        }
+
+        None
+    }
+}
+
+/// The rules of a policy zone, by trigger
+#[derive(Default)]
+struct PolicyRules {
+    serial: Option<u32>,
+    qnames: NameRules,
+    ips: Vec<(IpNet, Rule)>,
+    ns_names: NameRules,
+    ns_ips: Vec<(IpNet, Rule)>,
+}
+
+impl PolicyRules {
+    fn from_records(origin: &Name, serial: Option<u32>, records: &[Record]) -> Self {
+        let mut owners: HashMap<&Name, Vec<&Record>> = HashMap::new();
+        for record in records {
+            match record.rr_type() {
+                // the records of the zone itself are not rules
+                RecordType::SOA | RecordType::NS if record.name() == origin => continue,
+                RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3 => continue,
+                _ => owners.entry(record.name()).or_default().push(record),
+            }
+        }
+
+        let mut rules = Self {
+            serial,
+            ..Self::default()
+        };
+
+        for (owner, records) in owners {
+            let action = match action(&records) {
+                Some(action) => action,
+                None => {
+                    warn!("unsupported response policy action of {}", owner);
+                    continue;
+                }
+            };
+
+            let rule = Rule {
+                owner: owner.clone(),
+                action,
+            };
+            if let Err(e) = rules.insert(origin, owner, rule) {
+                warn!("ignoring response policy rule {}: {}", owner, e);
+            }
+        }
+
+        rules
+    }
+
+    fn insert(&mut self, origin: &Name, owner: &Name, rule: Rule) -> Result<(), String> {
+        // num_labels() doesn't count the wildcard label
+        let trigger_len = owner.iter().len().saturating_sub(origin.iter().len());
+        if !origin.zone_of(owner) || trigger_len == 0 {
+            return Err("not below the origin of the policy zone".to_string());
+        }
+
+        // the trigger, without the origin of the policy zone
+        let labels = owner.iter().take(trigger_len).collect::<Vec<_>>();
+
+        let (last, trigger) = labels.split_last().expect("labels are not empty");
+        let last = last.to_ascii_lowercase();
+        match last.as_slice() {
+            RPZ_IP => self.ips.push((ip_trigger(trigger)?, rule)),
+            RPZ_NSIP => self.ns_ips.push((ip_trigger(trigger)?, rule)),
+            RPZ_NSDNAME => self.ns_names.insert(name_trigger(trigger)?, rule),
+            RPZ_CLIENT_IP => return Err("client IP triggers are not supported".to_string()),
+            _ => self.qnames.insert(name_trigger(&labels)?, rule),
+        }
+
+        Ok(())
+    }
+}
+
+fn name_trigger(labels: &[&[u8]]) -> Result<Name, String> {
+    Name::from_labels(labels.iter().copied()).map_err(|e| e.to_string())
+}
+
+/// Decodes the network of an IP trigger, i.e. the prefix length and the address in reverse order
+///
+/// IPv6 addresses are in groups of 16 bits, `zz` stands for the longest run of zero groups.
+fn ip_trigger(labels: &[&[u8]]) -> Result<IpNet, String> {
+    let labels = labels
+        .iter()
+        .map(|label| std::str::from_utf8(label).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (prefix, address) = labels
+        .split_first()
+        .ok_or_else(|| "missing prefix length".to_string())?;
+    let prefix: u8 = prefix
+        .parse()
+        .map_err(|e| format!("bad prefix length: {}", e))?;
+    let mut address = address.to_vec();
+    address.reverse();
+
+    let ip = if address.len() == 4 && !address.iter().any(|group| group.eq_ignore_ascii_case("zz"))
+    {
+        address
+            .join(".")
+            .parse::<Ipv4Addr>()
+            .map(IpAddr::V4)
+            .map_err(|e| e.to_string())?
+    } else {
+        let address = match address
+            .iter()
+            .position(|group| group.eq_ignore_ascii_case("zz"))
+        {
+            Some(zeros) => format!(
+                "{}::{}",
+                address[..zeros].join(":"),
+                address[zeros + 1..].join(":")
+            ),
+            None => address.join(":"),
+        };
+
+        address
+            .parse::<Ipv6Addr>()
+            .map(IpAddr::V6)
+            .map_err(|e| e.to_string())?
+    };
+
+    IpNet::new(ip, prefix)
+        .map(|net| net.trunc())
+        .map_err(|e| e.to_string())
+}
+
+fn find_ip<'r>(rules: &'r [(IpNet, Rule)], ips: &[IpAddr]) -> Option<&'r Rule> {
+    rules
+        .iter()
+        .filter(|(net, _)| ips.iter().any(|ip| net.contains(ip)))
+        .max_by_key(|(net, _)| net.prefix_len())
+        .map(|(_, rule)| rule)
+}
+
+/// The action encoded by the records of a rule
+fn action(records: &[&Record]) -> Option<PolicyAction> {
+    let cname = records.iter().find_map(|r| match r.data() {
+        Some(RData::CNAME(target)) => Some(target),
+        _ => None,
+    });
+
+    if let Some(target) = cname {
+        if target.is_root() {
+            return Some(PolicyAction::NxDomain);
+        }
+
+        if let [label] = target.iter().collect::<Vec<_>>().as_slice() {
+            match label.to_ascii_lowercase().as_slice() {
+                b"*" => return Some(PolicyAction::NoData),
+                b"rpz-passthru" => return Some(PolicyAction::Passthru),
+                b"rpz-drop" => return Some(PolicyAction::Drop),
+                b"rpz-tcp-only" => return None,
+                _ => (),
+            }
+        }
+    }
+
+    Some(PolicyAction::LocalData(
+        records.iter().map(|r| (*r).clone()).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn cname(owner: &str, target: &str) -> Record {
+        Record::from_rdata(
+            Name::from_str(owner).unwrap(),
+            300,
+            RData::CNAME(Name::from_str(target).unwrap()),
+        )
+    }
+
+    fn rules(records: &[Record]) -> PolicyRules {
+        PolicyRules::from_records(&Name::from_str("rpz.").unwrap(), Some(1), records)
+    }
+
+    fn lower(name: &str) -> LowerName {
+        LowerName::from(Name::from_str(name).unwrap())
+    }
+
+    #[test]
+    fn test_actions() {
+        let rules = rules(&[
+            cname("nx.example.com.rpz.", "."),
+            cname("nodata.example.com.rpz.", "*."),
+            cname("pass.example.com.rpz.", "rpz-passthru."),
+            cname("drop.example.com.rpz.", "rpz-drop."),
+            cname("garden.example.com.rpz.", "walled.garden."),
+            Record::from_rdata(
+                Name::from_str("local.example.com.rpz.").unwrap(),
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ),
+        ]);
+
+        let action = |name| rules.qnames.find(&lower(name)).map(|r| r.action.clone());
+        assert_eq!(action("nx.example.com."), Some(PolicyAction::NxDomain));
+        assert_eq!(action("nodata.example.com."), Some(PolicyAction::NoData));
+        assert_eq!(action("pass.example.com."), Some(PolicyAction::Passthru));
+        assert_eq!(action("drop.example.com."), Some(PolicyAction::Drop));
+        assert_eq!(action("other.example.com."), None);
+
+        let local = action("local.example.com.").unwrap();
+        let answers = local.local_data(
+            &Name::from_str("local.example.com.").unwrap(),
+            RecordType::A,
+        );
+        assert_eq!(answers.len(), 1);
+        assert_eq!(
+            answers[0].name(),
+            &Name::from_str("local.example.com.").unwrap()
+        );
+        assert!(local
+            .local_data(
+                &Name::from_str("local.example.com.").unwrap(),
+                RecordType::AAAA
+            )
+            .is_empty());
+
+        // the CNAME answers every type
+        let garden = action("garden.example.com.").unwrap();
+        assert_eq!(
+            garden
+                .local_data(
+                    &Name::from_str("garden.example.com.").unwrap(),
+                    RecordType::MX
+                )
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let rules = rules(&[
+            cname("*.example.com.rpz.", "."),
+            cname("www.example.com.rpz.", "rpz-passthru."),
+        ]);
+
+        let action = |name| rules.qnames.find(&lower(name)).map(|r| r.action.clone());
+        assert_eq!(action("a.b.example.com."), Some(PolicyAction::NxDomain));
+        assert_eq!(action("www.example.com."), Some(PolicyAction::Passthru));
+        assert_eq!(action("example.com."), None);
+    }
+
+    #[test]
+    fn test_ip_triggers() {
+        let rules = rules(&[
+            cname("24.0.2.0.192.rpz-ip.rpz.", "."),
+            cname("32.1.2.0.192.rpz-ip.rpz.", "rpz-passthru."),
+            cname("48.zz.db8.2001.rpz-ip.rpz.", "rpz-drop."),
+            cname("32.1.2.0.192.rpz-nsip.rpz.", "*."),
+        ]);
+
+        let ip =
+            |ip: &str| find_ip(&rules.ips, &[ip.parse().unwrap()]).map(|rule| rule.action.clone());
+        assert_eq!(ip("192.0.2.1"), Some(PolicyAction::Passthru));
+        assert_eq!(ip("192.0.2.2"), Some(PolicyAction::NxDomain));
+        assert_eq!(ip("2001:db8::1"), Some(PolicyAction::Drop));
+        assert_eq!(ip("192.0.3.1"), None);
+
+        assert_eq!(rules.ns_ips.len(), 1);
+        assert_eq!(
+            ip_trigger(&[b"128", b"1", b"zz", b"2001"]).unwrap(),
+            "2001::1/128".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_ns_triggers() {
+        let rules = rules(&[cname("ns.bad.example.rpz-nsdname.rpz.", ".")]);
+
+        assert!(rules.ns_names.find(&lower("ns.bad.example.")).is_some());
+        assert!(rules.qnames.is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Helpers for authorities which synthesize answers, from wildcards or ANAME records
+//!
+//! These are used by the in-memory authority, and are public so that custom `Authority`
+//!  implementations can answer the same way.
+
+use crate::{
+    authority::LookupOptions,
+    client::rr::{LowerName, RData, Record, RecordSet, RecordType},
+};
+
+/// The wildcard name which covers the name, i.e. `*.example.com.` for `www.example.com.`
+///
+/// Returns `None` for the root and for wildcard names, which are not covered by another wildcard.
+pub fn wildcard_of(name: &LowerName) -> Option<LowerName> {
+    if name.is_wildcard() || name.is_root() {
+        None
+    } else {
+        Some(name.clone().into_wildcard())
+    }
+}
+
+/// Synthesizes the answer for the name from the records of the wildcard which covers it, see
+///  RFC 4592
+///
+/// The records are copied to the name, RRSIGs are copied as well if the lookup is for DNSSEC.
+pub fn synthesize_wildcard(
+    name: &LowerName,
+    wildcard: &RecordSet,
+    lookup_options: LookupOptions,
+) -> RecordSet {
+    let mut new_answer = RecordSet::with_ttl(name.into(), wildcard.record_type(), wildcard.ttl());
+
+    let (records, _rrsigs): (Vec<&Record>, Vec<&Record>) = lookup_options
+        .rrset_with_supported_algorithms(wildcard)
+        .partition(|r| r.record_type() != RecordType::RRSIG);
+
+    for record in records {
+        if let Some(rdata) = record.data() {
+            new_answer.add_rdata(rdata.clone());
+        }
+    }
+
+    #[cfg(feature = "dnssec")]
+    for rrsig in _rrsigs {
+        new_answer.insert_rrsig(rrsig.clone())
+    }
+
+    new_answer
+}
+
+/// Synthesizes the address records of an ANAME, from the addresses of its target
+///
+/// The answer has the name of the ANAME record, and the smaller TTL of the ANAME and the
+///  addresses. Returns an empty set if `addresses` are not of the `query_type`, which must be
+///  `A` or `AAAA`. Any RRSIGs of the addresses don't cover the new set, it must be signed again.
+pub fn synthesize_aname(
+    aname: &RecordSet,
+    query_type: RecordType,
+    addresses: Option<&RecordSet>,
+) -> RecordSet {
+    let a_aaaa_ttl = addresses.map_or(u32::max_value(), |r| r.ttl());
+
+    // grab the rdatas
+    let rdatas: Option<Vec<RData>> = addresses
+        .filter(|record| {
+            matches!(record.record_type(), RecordType::A | RecordType::AAAA)
+                && record.record_type() == query_type
+        })
+        .map(|record| {
+            record
+                .records_without_rrsigs()
+                .filter_map(Record::data)
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+
+    // now build up a new RecordSet
+    //   the name comes from the ANAME record
+    //   according to the rfc the ttl is from the ANAME
+    //   TODO: technically we should take the min of the potential CNAME chain
+    let ttl = aname.ttl().min(a_aaaa_ttl);
+    let mut new_answer = RecordSet::with_ttl(aname.name().clone(), query_type, ttl);
+
+    for rdata in rdatas.into_iter().flatten() {
+        new_answer.add_rdata(rdata);
+    }
+
+    new_answer
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::client::rr::Name;
+
+    #[test]
+    fn test_wildcard_of() {
+        let name = LowerName::from(Name::from_str("www.example.com.").unwrap());
+        assert_eq!(
+            wildcard_of(&name),
+            Some(LowerName::from(Name::from_str("*.example.com.").unwrap()))
+        );
+
+        assert_eq!(wildcard_of(&wildcard_of(&name).unwrap()), None);
+        assert_eq!(wildcard_of(&LowerName::from(Name::root())), None);
+    }
+
+    #[test]
+    fn test_synthesize_wildcard() {
+        let wildcard = Name::from_str("*.example.com.").unwrap();
+        let mut rrset = RecordSet::new(&wildcard, RecordType::A, 300);
+        rrset.insert(
+            Record::from_rdata(wildcard, 300, RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+            0,
+        );
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let answer = synthesize_wildcard(&name.clone().into(), &rrset, LookupOptions::default());
+        assert_eq!(answer.name(), &name);
+        assert_eq!(answer.ttl(), 300);
+        assert_eq!(
+            answer
+                .records_without_rrsigs()
+                .filter_map(Record::data)
+                .collect::<Vec<_>>(),
+            vec![&RData::A(Ipv4Addr::new(192, 0, 2, 1))]
+        );
+    }
+
+    #[test]
+    fn test_synthesize_aname() {
+        let apex = Name::from_str("example.com.").unwrap();
+        let mut aname = RecordSet::new(&apex, RecordType::ANAME, 3600);
+        aname.insert(
+            Record::from_rdata(
+                apex.clone(),
+                3600,
+                RData::ANAME(Name::from_str("www.example.net.").unwrap()),
+            ),
+            0,
+        );
+
+        let target = Name::from_str("www.example.net.").unwrap();
+        let mut addresses = RecordSet::new(&target, RecordType::A, 60);
+        addresses.insert(
+            Record::from_rdata(target, 60, RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+            0,
+        );
+
+        let answer = synthesize_aname(&aname, RecordType::A, Some(&addresses));
+        assert_eq!(answer.name(), &apex);
+        assert_eq!(answer.record_type(), RecordType::A);
+        assert_eq!(answer.ttl(), 60);
+        assert_eq!(answer.records_without_rrsigs().count(), 1);
+
+        // the addresses are not of the query type
+        let answer = synthesize_aname(&aname, RecordType::AAAA, Some(&addresses));
+        assert!(answer.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Views, which serve different zones to different clients, i.e. split-horizon DNS
+
+use cfg_if::cfg_if;
+use ipnet::IpNet;
+use tracing::debug;
+
+#[cfg(feature = "dnssec")]
+use crate::client::rr::dnssec::tsig::TSigner;
+use crate::{authority::Catalog, server::Request};
+
+/// A set of zones, served to the clients which match the view
+///
+/// Clients match by their source address, or by signing their requests with one of the TSIG keys
+///  of the view. A view without any addresses or keys matches all clients.
+pub struct View {
+    name: String,
+    match_clients: Vec<IpNet>,
+    #[cfg(feature = "dnssec")]
+    match_keys: Vec<TSigner>,
+    catalog: Catalog,
+}
+
+impl View {
+    /// Creates a view which serves the zones of the catalog, and matches all clients
+    pub fn new(name: String, catalog: Catalog) -> Self {
+        Self {
+            name,
+            match_clients: Vec::new(),
+            #[cfg(feature = "dnssec")]
+            match_keys: Vec::new(),
+            catalog,
+        }
+    }
+
+    /// The name of the view, for logging
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The zones of the view
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Mutable access to the zones of the view
+    pub fn catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.catalog
+    }
+
+    /// Sets the networks of the clients which match the view
+    pub fn set_match_clients(&mut self, match_clients: Vec<IpNet>) {
+        self.match_clients = match_clients;
+    }
+
+    /// Adds a key, requests signed with the key match the view
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn add_match_key(&mut self, signer: TSigner) {
+        self.match_keys.push(signer);
+    }
+
+    fn has_match_keys(&self) -> bool {
+        cfg_if! {
+            if #[cfg(feature = "dnssec")] {
+                !self.match_keys.is_empty()
+            } else {
+                false
+            }
+        }
+    }
+
+    /// True if the request is from a client of the view
+    pub fn matches(&self, request: &Request) -> bool {
+        if self.match_clients.is_empty() && !self.has_match_keys() {
+            return true;
+        }
+
+        let src = request.src().ip();
+        if self.match_clients.iter().any(|net| net.contains(&src)) {
+            debug!("client {} matched view: {}", src, self.name);
+            return true;
+        }
+
+        #[cfg(feature = "dnssec")]
+        if let Some(signer) = crate::store::in_memory::verified_tsigner(&self.match_keys, request) {
+            debug!(
+                "key {} of client {} matched view: {}",
+                signer.signer_name(),
+                src,
+                self.name
+            );
+            return true;
+        }
+
+        false
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zone transfers to secondaries, and NOTIFY of changes to a zone
+
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+use crate::{
+    client::{
+        client::{AsyncClient, ClientHandle},
+        error::ClientResult,
+        op::ResponseCode,
+        rr::{DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey},
+        udp::UdpClientStream,
+    },
+    proto::{
+        error::{ProtoError, ProtoResult},
+        serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder},
+    },
+};
+
+/// The maximum length of the records in each message of a zone transfer
+///
+/// Messages over TCP may be up to 64KiB, smaller messages are easier on the receiver and leave
+///  room for a TSIG record.
+pub(crate) const MAX_TRANSFER_MESSAGE_LEN: usize = 16 * 1024;
+
+/// The changes between two versions of a zone, one difference sequence of an IXFR, see RFC 1995
+///
+/// The SOA records are not part of the deleted and added records, `from` and `to` are the SOA of
+///  the zone before and after the changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZoneDiff {
+    from: Record,
+    deleted: Vec<Record>,
+    to: Record,
+    added: Vec<Record>,
+}
+
+impl ZoneDiff {
+    /// Creates a new difference from its parts
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - the SOA of the zone before the changes
+    /// * `deleted` - the records removed from the zone
+    /// * `to` - the SOA of the zone after the changes
+    /// * `added` - the records added to the zone
+    pub fn new(from: Record, deleted: Vec<Record>, to: Record, added: Vec<Record>) -> Self {
+        Self {
+            from,
+            deleted,
+            to,
+            added,
+        }
+    }
+
+    /// Computes the difference between two versions of the records of a zone
+    ///
+    /// RRSIGs are compared like all other records. Returns `None` if either version has no SOA at
+    ///  the origin.
+    pub fn between(
+        origin: &LowerName,
+        old: &BTreeMap<RrKey, Arc<RecordSet>>,
+        new: &BTreeMap<RrKey, Arc<RecordSet>>,
+    ) -> Option<Self> {
+        let soa_key = RrKey::new(origin.clone(), RecordType::SOA);
+        let from = old.get(&soa_key)?.records_without_rrsigs().next()?.clone();
+        let to = new.get(&soa_key)?.records_without_rrsigs().next()?.clone();
+
+        let mut deleted = Vec::new();
+        let mut added = Vec::new();
+        for (key, old_rrset) in old {
+            if *key == soa_key {
+                continue;
+            }
+
+            match new.get(key) {
+                Some(new_rrset) if Arc::ptr_eq(old_rrset, new_rrset) => (),
+                Some(new_rrset) => {
+                    deleted.extend(difference(old_rrset, new_rrset));
+                    added.extend(difference(new_rrset, old_rrset));
+                }
+                None => deleted.extend(all_records(old_rrset).cloned()),
+            }
+        }
+
+        for (key, new_rrset) in new {
+            if *key != soa_key && !old.contains_key(key) {
+                added.extend(all_records(new_rrset).cloned());
+            }
+        }
+
+        Some(Self::new(from, deleted, to, added))
+    }
+
+    /// The SOA of the zone before the changes
+    pub fn from(&self) -> &Record {
+        &self.from
+    }
+
+    /// The serial of the zone before the changes
+    pub fn from_serial(&self) -> u32 {
+        soa_serial(&self.from).unwrap_or_default()
+    }
+
+    /// The records removed from the zone
+    pub fn deleted(&self) -> &[Record] {
+        &self.deleted
+    }
+
+    /// The SOA of the zone after the changes
+    pub fn to(&self) -> &Record {
+        &self.to
+    }
+
+    /// The serial of the zone after the changes
+    pub fn to_serial(&self) -> u32 {
+        soa_serial(&self.to).unwrap_or_default()
+    }
+
+    /// The records added to the zone
+    pub fn added(&self) -> &[Record] {
+        &self.added
+    }
+
+    /// The records of the difference sequence, in the order of an IXFR response
+    pub fn records(&self) -> impl Iterator<Item = &Record> + '_ {
+        Some(&self.from)
+            .into_iter()
+            .chain(self.deleted.iter())
+            .chain(Some(&self.to))
+            .chain(self.added.iter())
+    }
+}
+
+impl BinEncodable for ZoneDiff {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        self.from.emit(encoder)?;
+        encoder.emit_u32(self.deleted.len() as u32)?;
+        encoder.emit_all(self.deleted.iter())?;
+        self.to.emit(encoder)?;
+        encoder.emit_u32(self.added.len() as u32)?;
+        encoder.emit_all(self.added.iter())?;
+        Ok(())
+    }
+}
+
+impl<'r> BinDecodable<'r> for ZoneDiff {
+    fn read(decoder: &mut BinDecoder<'r>) -> ProtoResult<Self> {
+        let read_records = |decoder: &mut BinDecoder<'r>| -> ProtoResult<Vec<Record>> {
+            let count = decoder
+                .read_u32()?
+                .verify_unwrap(|count| *count as usize <= decoder.len())
+                .map_err(|count| ProtoError::from(format!("too many records: {}", count)))?;
+
+            (0..count).map(|_| Record::read(decoder)).collect()
+        };
+
+        let from = Record::read(decoder)?;
+        let deleted = read_records(decoder)?;
+        let to = Record::read(decoder)?;
+        let added = read_records(decoder)?;
+
+        Ok(Self::new(from, deleted, to, added))
+    }
+}
+
+fn all_records(rrset: &RecordSet) -> impl Iterator<Item = &Record> {
+    rrset.records_without_rrsigs().chain(rrset.rrsigs())
+}
+
+/// Records of `rrset` which are not in `other`
+fn difference<'r>(rrset: &'r RecordSet, other: &'r RecordSet) -> impl Iterator<Item = Record> + 'r {
+    all_records(rrset)
+        .filter(move |record| !all_records(other).any(|o| o == *record && o.ttl() == record.ttl()))
+        .cloned()
+}
+
+/// The serial of an SOA record
+pub(crate) fn soa_serial(record: &Record) -> Option<u32> {
+    record
+        .data()
+        .and_then(RData::as_soa)
+        .map(|soa| soa.serial())
+}
+
+/// Splits the records of a zone transfer into the messages of the response
+pub(crate) fn split_messages(records: &[Record]) -> Vec<&[Record]> {
+    let mut messages = Vec::new();
+    let mut start = 0;
+    let mut len = 0;
+
+    for (i, record) in records.iter().enumerate() {
+        // the uncompressed length is an upper bound of the length in the message
+        let record_len = record.to_bytes().map_or(0, |bytes| bytes.len());
+        if i > start && len + record_len > MAX_TRANSFER_MESSAGE_LEN {
+            messages.push(&records[start..i]);
+            start = i;
+            len = 0;
+        }
+        len += record_len;
+    }
+
+    if start < records.len() || messages.is_empty() {
+        messages.push(&records[start..]);
+    }
+
+    messages
+}
+
+/// Sends a NOTIFY for the SOA of a zone to a secondary, see RFC 1996
+///
+/// # Arguments
+///
+/// * `origin` - the name of the zone
+/// * `soa` - the current SOA record of the zone
+/// * `secondary` - the address of the secondary
+pub async fn send_notify(origin: Name, soa: Record, secondary: SocketAddr) -> ClientResult<()> {
+    let stream = UdpClientStream::<UdpSocket>::new(secondary);
+    let (mut client, bg) = AsyncClient::connect(stream).await?;
+    let bg = tokio::spawn(bg);
+
+    let mut rrset = RecordSet::new(&origin, RecordType::SOA, 0);
+    rrset.insert(soa, 0);
+
+    let result = client
+        .notify(origin.clone(), DNSClass::IN, RecordType::SOA, Some(rrset))
+        .await;
+    bg.abort();
+
+    let response = result?;
+    match response.response_code() {
+        ResponseCode::NoError => {
+            debug!("secondary {} acknowledged notify of {}", secondary, origin);
+            Ok(())
+        }
+        code => Err(format!("secondary {} responded to notify with: {}", secondary, code).into()),
+    }
+}
+
+/// Notifies all of the secondaries of a change of the zone, in the background
+///
+/// Nothing is sent if there is no tokio runtime, e.g. when the zone is only used in tests.
+pub(crate) fn spawn_notify(origin: Name, soa: Record, secondaries: &[SocketAddr]) {
+    if secondaries.is_empty() {
+        return;
+    }
+
+    let runtime = match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => runtime,
+        Err(_) => {
+            debug!("no runtime to send notify of: {}", origin);
+            return;
+        }
+    };
+
+    for secondary in secondaries.iter().copied() {
+        let origin = origin.clone();
+        let soa = soa.clone();
+        runtime.spawn(async move {
+            info!("notifying {} of change to: {}", secondary, origin);
+            if let Err(e) = send_notify(origin.clone(), soa, secondary).await {
+                warn!(
+                    "failed to notify {} of change to {}: {}",
+                    secondary, origin, e
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::client::rr::rdata::SOA;
+
+    fn soa(serial: u32) -> Record {
+        Record::from_rdata(
+            Name::from_ascii("example.com.").unwrap(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_ascii("ns.example.com.").unwrap(),
+                Name::from_ascii("root.example.com.").unwrap(),
+                serial,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )),
+        )
+    }
+
+    fn a(name: &str, ip: [u8; 4]) -> Record {
+        Record::from_rdata(
+            Name::from_ascii(name).unwrap(),
+            3600,
+            RData::A(Ipv4Addr::from(ip)),
+        )
+    }
+
+    fn records(records: &[Record]) -> BTreeMap<RrKey, Arc<RecordSet>> {
+        let mut map = BTreeMap::<RrKey, RecordSet>::new();
+        for record in records {
+            map.entry(RrKey::new(record.name().into(), record.rr_type()))
+                .or_insert_with(|| RecordSet::new(record.name(), record.rr_type(), 0))
+                .insert(record.clone(), 0);
+        }
+
+        map.into_iter().map(|(k, v)| (k, Arc::new(v))).collect()
+    }
+
+    #[test]
+    fn test_between() {
+        let origin = LowerName::from(Name::from_ascii("example.com.").unwrap());
+        let unchanged = a("www.example.com.", [127, 0, 0, 1]);
+        let old = records(&[
+            soa(1),
+            unchanged.clone(),
+            a("old.example.com.", [127, 0, 0, 2]),
+            a("mail.example.com.", [127, 0, 0, 3]),
+        ]);
+        let new = records(&[
+            soa(2),
+            unchanged,
+            a("new.example.com.", [127, 0, 0, 4]),
+            a("mail.example.com.", [127, 0, 0, 5]),
+        ]);
+
+        let diff = ZoneDiff::between(&origin, &old, &new).unwrap();
+        assert_eq!(diff.from_serial(), 1);
+        assert_eq!(diff.to_serial(), 2);
+        assert_eq!(
+            diff.deleted(),
+            &[
+                a("mail.example.com.", [127, 0, 0, 3]),
+                a("old.example.com.", [127, 0, 0, 2])
+            ]
+        );
+        assert_eq!(
+            diff.added(),
+            &[
+                a("mail.example.com.", [127, 0, 0, 5]),
+                a("new.example.com.", [127, 0, 0, 4])
+            ]
+        );
+
+        assert!(ZoneDiff::between(&origin, &old, &records(&[])).is_none());
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let diff = ZoneDiff::new(
+            soa(1),
+            vec![a("old.example.com.", [127, 0, 0, 2])],
+            soa(2),
+            vec![
+                a("new.example.com.", [127, 0, 0, 3]),
+                a("new.example.com.", [127, 0, 0, 4]),
+            ],
+        );
+
+        let bytes = diff.to_bytes().unwrap();
+        assert_eq!(ZoneDiff::from_bytes(&bytes).unwrap(), diff);
+        assert_eq!(diff.records().count(), 5);
+    }
+
+    #[test]
+    fn test_split_messages() {
+        let records = (0..2000)
+            .map(|i| a("www.example.com.", (i as u32).to_be_bytes()))
+            .collect::<Vec<_>>();
+
+        let messages = split_messages(&records);
+        assert!(messages.len() > 1);
+        assert_eq!(messages.iter().map(|m| m.len()).sum::<usize>(), 2000);
+        assert!(messages.iter().all(|m| m
+            .iter()
+            .map(|r| r.to_bytes().unwrap().len())
+            .sum::<usize>()
+            <= MAX_TRANSFER_MESSAGE_LEN));
+
+        assert_eq!(split_messages(&[]).len(), 1);
+    }
+}
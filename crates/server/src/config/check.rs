@@ -0,0 +1,303 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Validation of a configuration and the files it references, without serving it
+
+use std::{
+    collections::HashSet,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{dnssec, AccessControlConfig, Config, ZoneConfig};
+use crate::client::rr::Name;
+use crate::store::{file::ZoneCheck, StoreConfig};
+
+/// A problem of a configuration, found by [`Config::check`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// Where the problem is, e.g. `zones[1] (example.com).keys[0]`
+    pub location: String,
+    /// What the problem is
+    pub message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+impl Config {
+    /// Checks the configuration, and the zone files, keys and certificates it references
+    ///
+    /// All problems are returned, none if the configuration can be served. Nothing is bound nor
+    ///  written, e.g. to validate a configuration before it is deployed. Relative paths are
+    ///  resolved against `zone_dir`, which overrides the configured `directory` as it does for
+    ///  the server.
+    pub fn check(&self, zone_dir: &Path) -> Vec<ConfigProblem> {
+        let mut check = Check {
+            zone_dir,
+            now: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs() as u32),
+            problems: Vec::new(),
+        };
+
+        check.listeners(self);
+
+        if !zone_dir.is_dir() {
+            check.problem(
+                "directory",
+                format!("{} is not a directory", zone_dir.display()),
+            );
+        }
+        if let Some(level) = &self.log_level {
+            if tracing::Level::from_str(level).is_err() {
+                check.problem("log_level", format!("invalid log level: {}", level));
+            }
+        }
+        if let Some(control_socket) = self.get_control_socket() {
+            if let Some(parent) = control_socket
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+            {
+                if !parent.is_dir() {
+                    check.problem(
+                        "control_socket",
+                        format!("{} is not a directory", parent.display()),
+                    );
+                }
+            }
+        }
+        if let Some(acl) = &self.acl {
+            check.acl("acl", acl);
+        }
+        #[cfg(feature = "dnssec")]
+        if let Some(tls_cert) = &self.tls_cert {
+            check.tls_cert(tls_cert);
+        }
+
+        check.zones("zones", &self.zones);
+        for (i, view) in self.views.iter().enumerate() {
+            let location = format!("views[{}] ({})", i, view.name);
+            check.tsig_keys(&format!("{}.match_keys", location), &view.match_keys);
+            check.zones(&format!("{}.zones", location), &view.zones);
+        }
+
+        check.problems
+    }
+}
+
+struct Check<'a> {
+    zone_dir: &'a Path,
+    now: u32,
+    problems: Vec<ConfigProblem>,
+}
+
+impl Check<'_> {
+    fn problem(&mut self, location: &str, message: impl Into<String>) {
+        self.problems.push(ConfigProblem {
+            location: location.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// The addresses must parse, and the TCP listeners may not share a port
+    fn listeners(&mut self, config: &Config) {
+        let mut addrs = HashSet::new();
+        for (i, addr) in config.listen_addrs_ipv4.iter().enumerate() {
+            match addr.parse::<Ipv4Addr>() {
+                Ok(ip) if !addrs.insert(IpAddr::V4(ip)) => {
+                    self.problem(&format!("listen_addrs_ipv4[{}]", i), "duplicate address")
+                }
+                Ok(_) => (),
+                Err(e) => self.problem(
+                    &format!("listen_addrs_ipv4[{}]", i),
+                    format!("invalid address {}: {}", addr, e),
+                ),
+            }
+        }
+        for (i, addr) in config.listen_addrs_ipv6.iter().enumerate() {
+            match addr.parse::<Ipv6Addr>() {
+                Ok(ip) if !addrs.insert(IpAddr::V6(ip)) => {
+                    self.problem(&format!("listen_addrs_ipv6[{}]", i), "duplicate address")
+                }
+                Ok(_) => (),
+                Err(e) => self.problem(
+                    &format!("listen_addrs_ipv6[{}]", i),
+                    format!("invalid address {}: {}", addr, e),
+                ),
+            }
+        }
+
+        // TLS and HTTPS are only served with a certificate
+        #[cfg(feature = "dnssec")]
+        if config.tls_cert.is_some() {
+            let tcp_ports = [
+                ("listen_port", config.get_listen_port()),
+                #[cfg(feature = "dns-over-tls")]
+                ("tls_listen_port", config.get_tls_listen_port()),
+                #[cfg(feature = "dns-over-https")]
+                ("https_listen_port", config.get_https_listen_port()),
+            ];
+
+            for (i, (location, port)) in tcp_ports.iter().enumerate() {
+                if let Some((other, _)) = tcp_ports[..i].iter().find(|(_, other)| other == port) {
+                    self.problem(location, format!("port {} is also the {}", port, other));
+                }
+            }
+        }
+    }
+
+    fn zones(&mut self, location: &str, zones: &[ZoneConfig]) {
+        let mut names = HashSet::new();
+        for (i, zone_config) in zones.iter().enumerate() {
+            let location = format!("{}[{}] ({})", location, i, zone_config.zone);
+            match zone_config.get_zone() {
+                Ok(name) => {
+                    if !names.insert(name.clone()) {
+                        self.problem(&location, "duplicate zone");
+                    }
+                    self.zone(&location, &name, zone_config);
+                }
+                Err(e) => self.problem(&location, format!("invalid zone name: {}", e)),
+            }
+        }
+    }
+
+    fn zone(&mut self, location: &str, name: &Name, zone_config: &ZoneConfig) {
+        let stores = format!("{}.stores", location);
+        match &zone_config.stores {
+            Some(StoreConfig::File(config)) => {
+                self.zone_file(&stores, name, &config.zone_file_path)
+            }
+            #[cfg(feature = "sqlite")]
+            Some(StoreConfig::Sqlite(config)) => {
+                // the journal is the zone once it exists
+                if !self.zone_dir.join(&config.journal_file_path).exists() {
+                    self.zone_file(&stores, name, &config.zone_file_path)
+                }
+            }
+            Some(StoreConfig::Secondary(config)) => {
+                if let Some(tsig_key) = &config.tsig_key {
+                    self.tsig_key(&format!("{}.tsig_key", stores), tsig_key);
+                }
+            }
+            #[cfg(feature = "trust-dns-recursor")]
+            Some(StoreConfig::Recursor(config)) => {
+                if let Err(e) = config.read_roots(Some(self.zone_dir)) {
+                    self.problem(
+                        &stores,
+                        format!("failed to read roots {}: {}", config.roots.display(), e),
+                    );
+                }
+            }
+            #[cfg(feature = "blocklist")]
+            Some(StoreConfig::Blocklist(config)) => {
+                for problem in config.problems(self.zone_dir) {
+                    self.problem(&stores, problem);
+                }
+            }
+            #[allow(unreachable_patterns)]
+            Some(_) => (),
+            None => match &zone_config.file {
+                Some(file) => self.zone_file(location, name, file),
+                None => self.problem(location, "no file, nor stores"),
+            },
+        }
+
+        if let Some(acl) = &zone_config.acl {
+            self.acl(&format!("{}.acl", location), acl);
+        }
+        self.tsig_keys(&format!("{}.tsig_keys", location), &zone_config.tsig_keys);
+        self.tsig_keys(
+            &format!("{}.transfer_tsig_keys", location),
+            &zone_config.transfer_tsig_keys,
+        );
+
+        #[cfg(feature = "dnssec")]
+        if zone_config.is_dnssec_enabled() {
+            for (i, key_config) in zone_config.get_keys().iter().enumerate() {
+                let location = format!("{}.keys[{}]", location, i);
+                if let Err(e) = key_config.try_into_signer(name.clone()) {
+                    self.problem(&location, e);
+                }
+                if let Some(Err(e)) = key_config.schedule().map(|schedule| schedule.role()) {
+                    self.problem(&location, format!("bad key schedule: {}", e));
+                }
+            }
+
+            if let Some(Err(e)) = zone_config.get_nsec3().map(|nsec3| nsec3.to_nsec3param()) {
+                self.problem(
+                    &format!("{}.nsec3", location),
+                    format!("bad NSEC3 parameters: {}", e),
+                );
+            }
+        }
+    }
+
+    fn zone_file(&mut self, location: &str, name: &Name, file: &str) {
+        let path = self.zone_dir.join(file);
+        match ZoneCheck::from_file(&path, Some(name.clone())) {
+            Ok(zone) => {
+                for problem in zone.check(self.now) {
+                    self.problem(location, format!("{}: {}", file, problem));
+                }
+            }
+            Err(e) => self.problem(location, format!("failed to parse {}: {}", file, e)),
+        }
+    }
+
+    fn acl(&mut self, location: &str, acl: &AccessControlConfig) {
+        let acls = [
+            ("query", &acl.query),
+            ("update", &acl.update),
+            ("transfer", &acl.transfer),
+            ("notify", &acl.notify),
+        ];
+        for (operation, acl) in acls.iter() {
+            if let Some(acl) = acl {
+                self.tsig_keys(&format!("{}.{}.keys", location, operation), &acl.keys);
+            }
+        }
+    }
+
+    fn tsig_keys(&mut self, location: &str, keys: &[dnssec::TsigKeyConfig]) {
+        for (i, key_config) in keys.iter().enumerate() {
+            self.tsig_key(&format!("{}[{}]", location, i), key_config);
+        }
+    }
+
+    #[cfg(feature = "dnssec")]
+    fn tsig_key(&mut self, location: &str, key_config: &dnssec::TsigKeyConfig) {
+        if let Err(e) = key_config.try_into_signer() {
+            self.problem(location, e);
+        }
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    fn tsig_key(&mut self, location: &str, _key_config: &dnssec::TsigKeyConfig) {
+        self.problem(location, "TSIG keys require the dnssec feature");
+    }
+
+    #[cfg(feature = "dnssec")]
+    fn tls_cert(&mut self, tls_cert: &dnssec::TlsCertConfig) {
+        // the certificate is obtained with ACME if it does not exist yet
+        if tls_cert.get_acme().is_some() && !self.zone_dir.join(tls_cert.get_path()).exists() {
+            return;
+        }
+
+        #[cfg(any(feature = "dns-over-openssl", feature = "dns-over-rustls"))]
+        if let Err(e) = dnssec::load_cert(self.zone_dir, tls_cert) {
+            self.problem("tls_cert", e);
+        }
+    }
+}
@@ -15,13 +15,20 @@ use openssl::{pkey::PKey, stack::Stack, x509::X509};
 use rustls::{Certificate, PrivateKey};
 use serde::Deserialize;
 
+#[cfg(feature = "dnssec")]
+use crate::authority::{KeyRole, KeyTimings};
 use crate::client::error::ParseResult;
 use crate::client::rr::domain::Name;
 #[cfg(feature = "dnssec")]
 use crate::client::rr::{
-    dnssec::{Algorithm, KeyFormat, KeyPair, Private, SigSigner},
+    dnssec::{tsig::TSigner, Algorithm, KeyFormat, KeyPair, Private, SigSigner},
     domain::IntoName,
 };
+#[cfg(feature = "dnssec")]
+use crate::proto::rr::dnssec::{
+    rdata::{tsig::TsigAlgorithm, NSEC3PARAM},
+    Nsec3HashAlgorithm,
+};
 
 /// Key pair configuration for DNSSec keys for signing a zone
 #[derive(Deserialize, PartialEq, Eq, Debug)]
@@ -38,6 +45,8 @@ pub struct KeyConfig {
     pub is_zone_signing_key: Option<bool>,
     /// specifies that this key can be used for dynamic updates in the zone
     pub is_zone_update_auth: Option<bool>,
+    /// the role and lifetime of the key when signing the zone, for rolling over keys
+    pub schedule: Option<KeyScheduleConfig>,
 }
 
 impl KeyConfig {
@@ -68,6 +77,7 @@ impl KeyConfig {
             signer_name: Some(signer_name),
             is_zone_signing_key: Some(is_zone_signing_key),
             is_zone_update_auth: Some(is_zone_update_auth),
+            schedule: None,
         }
     }
 
@@ -121,6 +131,7 @@ impl KeyConfig {
             "ECDSAP256SHA256" => Ok(Algorithm::ECDSAP256SHA256),
             "ECDSAP384SHA384" => Ok(Algorithm::ECDSAP384SHA384),
             "ED25519" => Ok(Algorithm::ED25519),
+            "ED448" => Ok(Algorithm::ED448),
             s => Err(format!("unrecognized string {}", s).into()),
         }
     }
@@ -150,6 +161,13 @@ impl KeyConfig {
         self.is_zone_update_auth.unwrap_or(false)
     }
 
+    /// The role and lifetime of the key when signing the zone, if it's scheduled
+    ///
+    /// Keys without a schedule sign the whole zone for as long as they are configured.
+    pub fn schedule(&self) -> Option<&KeyScheduleConfig> {
+        self.schedule.as_ref()
+    }
+
     /// Tries to read the defined key into a Signer
     #[cfg(feature = "dnssec")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
@@ -167,6 +185,129 @@ impl KeyConfig {
     }
 }
 
+/// Role and lifetime of a zone signing key
+///
+/// The times are in seconds since the unix epoch. To roll over a key with pre-publication, as is
+///  usual for ZSKs, the successor is published a propagation delay before the current key becomes
+///  inactive, and activated at that time; with double signatures, as is usual for KSKs, the
+///  successor is published and activated a propagation delay before the current key is deleted.
+///  See `RolloverMethod` to compute the times.
+#[derive(Deserialize, PartialEq, Eq, Debug, Default)]
+pub struct KeyScheduleConfig {
+    /// the role of the key, "ksk", "zsk" or "csk", defaults to "csk"
+    pub role: Option<String>,
+    /// when the DNSKEY is added to the zone, defaults to immediately
+    pub publish: Option<u64>,
+    /// when the key starts signing the zone, defaults to `publish`
+    pub activate: Option<u64>,
+    /// when the key stops signing the zone, defaults to never
+    pub inactive: Option<u64>,
+    /// when the DNSKEY is removed from the zone, defaults to never
+    pub delete: Option<u64>,
+}
+
+impl KeyScheduleConfig {
+    /// The records of the zone the key signs
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn role(&self) -> ParseResult<KeyRole> {
+        match self.role.as_deref() {
+            Some("ksk") => Ok(KeyRole::KeySigning),
+            Some("zsk") => Ok(KeyRole::ZoneSigning),
+            Some("csk") | None => Ok(KeyRole::Combined),
+            Some(s) => Err(format!("unrecognized key role {}", s).into()),
+        }
+    }
+
+    /// The times at which the key is published, activated, retired and deleted
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn timings(&self) -> KeyTimings {
+        KeyTimings::new(self.publish, self.activate, self.inactive, self.delete)
+    }
+}
+
+/// Hash parameters of the NSEC3 records of a signed zone, which is signed with NSEC without them
+///
+/// The defaults of no salt and no additional iterations are those recommended by RFC 9276.
+#[derive(Deserialize, PartialEq, Eq, Debug, Default)]
+#[serde(default)]
+pub struct Nsec3Config {
+    /// the salt of the hash, as hex, e.g. "aabbccdd"
+    pub salt: String,
+    /// the number of additional iterations of the hash
+    pub iterations: u16,
+    /// leave unsigned delegations out of the NSEC3 chain, RFC 5155 Section 6
+    pub opt_out: bool,
+}
+
+impl Nsec3Config {
+    /// The NSEC3PARAM which is published at the apex of the zone
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn to_nsec3param(&self) -> ParseResult<NSEC3PARAM> {
+        let salt = data_encoding::HEXLOWER_PERMISSIVE
+            .decode(self.salt.as_bytes())
+            .map_err(|e| format!("bad NSEC3 salt {}: {}", self.salt, e))?;
+        if salt.len() > usize::from(u8::max_value()) {
+            return Err(format!("NSEC3 salt is longer than 255 bytes: {}", self.salt).into());
+        }
+
+        Ok(NSEC3PARAM::new(
+            Nsec3HashAlgorithm::SHA1,
+            false,
+            self.iterations,
+            salt,
+        ))
+    }
+}
+
+/// Shared secret configuration for a TSIG key which is authorized to perform dynamic updates
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct TsigKeyConfig {
+    /// name of the key, this must match the name used by the client, e.g. update.example.com
+    pub key_name: String,
+    /// the mac algorithm of the key, e.g. hmac-sha256
+    pub algorithm: String,
+    /// file path to the raw shared secret
+    pub key_path: String,
+    /// permitted difference in seconds between the signing time and the time of the server
+    pub fudge: Option<u16>,
+}
+
+impl TsigKeyConfig {
+    /// path to the shared secret
+    pub fn key_path(&self) -> &Path {
+        Path::new(&self.key_path)
+    }
+
+    /// the permitted time difference, defaults to 300 seconds as recommended in RFC 8945
+    pub fn fudge(&self) -> u16 {
+        self.fudge.unwrap_or(300)
+    }
+
+    /// Tries to read the shared secret into a TSigner
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn try_into_signer(&self) -> Result<TSigner, String> {
+        let key_name = Name::parse(&self.key_name, Some(&Name::root()))
+            .map_err(|e| format!("error loading key name: {}", e))?;
+        let mut algorithm = Name::from_ascii(&self.algorithm)
+            .map_err(|e| format!("error loading algorithm: {}", e))?;
+        algorithm.set_fqdn(false);
+        let algorithm = TsigAlgorithm::from_name(algorithm);
+        let key = std::fs::read(self.key_path())
+            .map_err(|e| format!("failed to read key: {:?} msg: {}", self.key_path(), e))?;
+
+        TSigner::new(key, algorithm, key_name, self.fudge()).map_err(|e| {
+            format!(
+                "failed to create signer for key: {} msg: {}",
+                self.key_name, e
+            )
+        })
+    }
+}
+
 /// Certificate format of the file being read
 #[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
@@ -210,6 +351,7 @@ pub struct TlsCertConfig {
     password: Option<String>,
     private_key: Option<String>,
     private_key_type: Option<PrivateKeyType>,
+    acme: Option<AcmeConfig>,
 }
 
 impl TlsCertConfig {
@@ -242,8 +384,59 @@ impl TlsCertConfig {
     pub fn get_private_key_type(&self) -> PrivateKeyType {
         self.private_key_type.unwrap_or_default()
     }
+
+    /// returns the configuration for obtaining the certificate with ACME, if it's managed
+    pub fn get_acme(&self) -> Option<&AcmeConfig> {
+        self.acme.as_ref()
+    }
+}
+
+/// Obtaining and renewing the TLS certificate with ACME, e.g. from Let's Encrypt, see RFC 8555
+///
+/// The DNS-01 challenges are answered by the zones of the server, so all names of the certificate
+///  must be in its authoritative zones. The certificate and its key are written as PEM to the
+///  `path` and `private_key` of the `tls_cert`, from which they're loaded on startup.
+#[derive(Deserialize, PartialEq, Eq, Debug)]
+pub struct AcmeConfig {
+    /// the directory URL of the ACME server, defaults to the one of Let's Encrypt
+    pub directory: Option<String>,
+    /// the contact URLs of the account, e.g. "mailto:hostmaster@example.com"
+    #[serde(default)]
+    pub contact: Vec<String>,
+    /// the names of the certificate besides the `endpoint_name`
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// path to the PKCS8 key of the ACME account, which is generated if it doesn't exist
+    pub account_key: String,
+    /// seconds before the certificate expires at which it's renewed, defaults to 30 days
+    pub renew_before: Option<u64>,
+}
+
+impl AcmeConfig {
+    /// The directory URL of the production ACME server of Let's Encrypt
+    pub const LETS_ENCRYPT: &'static str = "https://acme-v02.api.letsencrypt.org/directory";
+
+    /// The directory URL of the ACME server
+    pub fn directory(&self) -> &str {
+        self.directory.as_deref().unwrap_or(Self::LETS_ENCRYPT)
+    }
+
+    /// How long before the certificate expires it's renewed
+    pub fn renew_before(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.renew_before.unwrap_or(30 * 24 * 60 * 60))
+    }
 }
 
+/// A certificate chain and its private key, as loaded by `load_cert`
+#[cfg(all(feature = "dns-over-openssl", not(feature = "dns-over-rustls")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "dns-over-openssl")))]
+pub type TlsCert = ((X509, Option<Stack<X509>>), PKey<Private>);
+
+/// A certificate chain and its private key, as loaded by `load_cert`
+#[cfg(feature = "dns-over-rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
+pub type TlsCert = (Vec<Certificate>, PrivateKey);
+
 /// set of DNSSEC algorithms to use to sign the zone. enable_dnssec must be true.
 /// these will be lookedup by $file.{key_name}.pem, for backward compatibility
 /// with previous versions of Trust-DNS, if enable_dnssec is enabled but
@@ -253,7 +446,7 @@ impl TlsCertConfig {
 /// for custom keys can be found elsewhere.
 ///
 /// the currently supported set of supported_algorithms are
-/// ["RSASHA256", "RSASHA512", "ECDSAP256SHA256", "ECDSAP384SHA384", "ED25519"]
+/// ["RSASHA256", "RSASHA512", "ECDSAP256SHA256", "ECDSAP384SHA384", "ED25519", "ED448"]
 ///
 /// keys are listed in pairs of key_name and algorithm, the search path is the
 /// same directory has the zone $file:
@@ -268,6 +461,8 @@ fn load_key(zone_name: Name, key_config: &KeyConfig) -> Result<SigSigner, String
 
     use time::Duration;
 
+    use crate::client::rr::rdata::DNSKEY;
+
     let key_path = key_config.key_path();
     let algorithm = key_config
         .algorithm()
@@ -297,10 +492,20 @@ fn load_key(zone_name: Name, key_config: &KeyConfig) -> Result<SigSigner, String
         .map_err(|e| format!("error reading name: {}", e))?
         .unwrap_or(zone_name);
 
+    // ZSKs are not secure entry points, the key tag of the signatures depends on the flag
+    let is_secure_entry_point = match key_config.schedule() {
+        Some(schedule) => schedule
+            .role()
+            .map_err(|e| format!("bad key role: {}", e))?
+            .is_secure_entry_point(),
+        None => true,
+    };
+
     // add the key to the zone
     // TODO: allow the duration of signatures to be customized
     let dnskey = key
-        .to_dnskey(algorithm)
+        .to_public_bytes()
+        .map(|bytes| DNSKEY::new(true, is_secure_entry_point, false, algorithm, bytes))
         .map_err(|e| format!("error converting to dnskey: {}", e))?;
     Ok(SigSigner::dnssec(
         dnskey,
@@ -314,10 +519,7 @@ fn load_key(zone_name: Name, key_config: &KeyConfig) -> Result<SigSigner, String
 
 /// Load a Certificate from the path (with openssl)
 #[cfg(all(feature = "dns-over-openssl", not(feature = "dns-over-rustls")))]
-pub fn load_cert(
-    zone_dir: &Path,
-    tls_cert_config: &TlsCertConfig,
-) -> Result<((X509, Option<Stack<X509>>), PKey<Private>), String> {
+pub fn load_cert(zone_dir: &Path, tls_cert_config: &TlsCertConfig) -> Result<TlsCert, String> {
     use tracing::{info, warn};
 
     use crate::proto::openssl::tls_server::{
@@ -373,10 +575,7 @@ pub fn load_cert(
 /// Load a Certificate from the path (with rustls)
 #[cfg(feature = "dns-over-rustls")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
-pub fn load_cert(
-    zone_dir: &Path,
-    tls_cert_config: &TlsCertConfig,
-) -> Result<(Vec<Certificate>, PrivateKey), String> {
+pub fn load_cert(zone_dir: &Path, tls_cert_config: &TlsCertConfig) -> Result<TlsCert, String> {
     use tracing::{info, warn};
 
     use crate::proto::rustls::tls_server::{read_cert, read_key, read_key_from_der};
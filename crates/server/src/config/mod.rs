@@ -7,16 +7,18 @@
 
 //! Configuration module for the server binary, `named`.
 
+mod check;
 pub mod dnssec;
 
 use std::fs::File;
 use std::io::Read;
-use std::net::{AddrParseError, Ipv4Addr, Ipv6Addr};
+use std::net::{AddrParseError, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 
 use cfg_if::cfg_if;
+use ipnet::IpNet;
 use serde::{self, Deserialize};
 use toml;
 
@@ -24,15 +26,20 @@ use crate::client::rr::Name;
 use crate::proto::error::ProtoResult;
 
 use crate::authority::ZoneType;
+use crate::dnstap::DnstapConfig;
 use crate::error::{ConfigError, ConfigResult};
+use crate::server::{ConnectionLimits, CookieConfig, ProxyProtocol, QueryLogConfig, RateLimits};
 use crate::store::StoreConfig;
 
+pub use self::check::ConfigProblem;
+
 static DEFAULT_PATH: &str = "/var/named"; // TODO what about windows (do I care? ;)
 static DEFAULT_PORT: u16 = 53;
 static DEFAULT_TLS_PORT: u16 = 853;
 static DEFAULT_HTTPS_PORT: u16 = 443;
 static DEFAULT_QUIC_PORT: u16 = 853; // https://www.ietf.org/archive/id/draft-ietf-dprive-dnsoquic-11.html#name-reservation-of-dedicated-po
 static DEFAULT_TCP_REQUEST_TIMEOUT: u64 = 5;
+static DEFAULT_SHUTDOWN_TIMEOUT: u64 = 10;
 
 /// Server configuration
 #[derive(Deserialize, Debug)]
@@ -51,8 +58,24 @@ pub struct Config {
     https_listen_port: Option<u16>,
     /// QUIC port to listen on
     quic_listen_port: Option<u16>,
+    /// Seconds after which idle QUIC connections are closed
+    quic_idle_timeout: Option<u64>,
+    /// Maximum number of QUIC connections open at once on each listener
+    quic_max_connections: Option<usize>,
     /// Timeout associated to a request before it is closed.
     tcp_request_timeout: Option<u64>,
+    /// Seconds for which the requests in flight are answered once shutting down
+    shutdown_timeout: Option<u64>,
+    /// Limits of the TCP, TLS, HTTPS and QUIC connections open at once
+    connection_limits: Option<ConnectionLimits>,
+    /// Omit the records of the authority and additional sections which are not required
+    minimal_responses: Option<bool>,
+    /// Maximum size of the responses over UDP, larger responses are truncated
+    max_udp_payload: Option<u16>,
+    /// DNS Cookies, with which to require that clients over UDP are not spoofing their address
+    cookies: Option<CookieConfig>,
+    /// Proxies whose TCP, TLS and HTTPS connections start with a PROXY protocol v2 header
+    proxy_protocol: Option<ProxyProtocol>,
     /// Level at which to log, default is INFO
     log_level: Option<String>,
     /// Base configuration directory, i.e. root path for zones
@@ -60,6 +83,22 @@ pub struct Config {
     /// List of configurations for zones
     #[serde(default)]
     zones: Vec<ZoneConfig>,
+    /// List of views, which serve their own zones to the clients they match
+    #[serde(default)]
+    views: Vec<ViewConfig>,
+    /// Limits of the rate of responses over UDP, for zones without their own limits
+    rate_limit: Option<RateLimits>,
+    /// ACLs of the operations on zones, for zones without their own ACLs
+    acl: Option<AccessControlConfig>,
+    /// Path of the unix domain socket on which to accept control commands, e.g. from `dnsctl`
+    #[serde(default, deserialize_with = "deserialize_control_socket")]
+    control_socket: Option<PathBuf>,
+    /// Address on which to serve metrics over HTTP, for Prometheus
+    metrics_listen_addr: Option<SocketAddr>,
+    /// Logging of the DNS messages with dnstap
+    dnstap: Option<DnstapConfig>,
+    /// Structured log of the queries handled
+    query_log: Option<QueryLogConfig>,
     /// Certificate to associate to TLS connections (currently the same is used for HTTPS and TLS)
     #[cfg(feature = "dnssec")]
     tls_cert: Option<dnssec::TlsCertConfig>,
@@ -104,6 +143,17 @@ impl Config {
         self.quic_listen_port.unwrap_or(DEFAULT_QUIC_PORT)
     }
 
+    /// timeout after which idle QUIC connections are closed, defaults to the TCP request timeout
+    pub fn get_quic_idle_timeout(&self) -> Duration {
+        self.quic_idle_timeout
+            .map_or_else(|| self.get_tcp_request_timeout(), Duration::from_secs)
+    }
+
+    /// maximum number of QUIC connections open at once on each listener, unlimited if none
+    pub fn get_quic_max_connections(&self) -> Option<usize> {
+        self.quic_max_connections
+    }
+
     /// default timeout for all TCP connections before forceably shutdown
     pub fn get_tcp_request_timeout(&self) -> Duration {
         Duration::from_secs(
@@ -112,6 +162,37 @@ impl Config {
         )
     }
 
+    /// the time for which the requests in flight are answered once shutting down
+    pub fn get_shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT))
+    }
+
+    /// the limits of the TCP, TLS, HTTPS and QUIC connections open at once, unlimited if none
+    pub fn get_connection_limits(&self) -> Option<&ConnectionLimits> {
+        self.connection_limits.as_ref()
+    }
+
+    /// omit the records of the authority and additional sections which are not required, false by
+    ///  default
+    pub fn get_minimal_responses(&self) -> bool {
+        self.minimal_responses.unwrap_or(false)
+    }
+
+    /// maximum size of the responses over UDP, the server default if none
+    pub fn get_max_udp_payload(&self) -> Option<u16> {
+        self.max_udp_payload
+    }
+
+    /// the DNS Cookies of the server, cookies of the requests are ignored if none
+    pub fn get_cookies(&self) -> Option<&CookieConfig> {
+        self.cookies.as_ref()
+    }
+
+    /// the proxies using the PROXY protocol, connections don't start with its header if none
+    pub fn get_proxy_protocol(&self) -> Option<&ProxyProtocol> {
+        self.proxy_protocol.as_ref()
+    }
+
     /// specify the log level which should be used, ["Trace", "Debug", "Info", "Warn", "Error"]
     pub fn get_log_level(&self) -> tracing::Level {
         if let Some(ref level_str) = self.log_level {
@@ -133,6 +214,41 @@ impl Config {
         &self.zones
     }
 
+    /// the views, checked in order, clients which match no view are served the zones above
+    pub fn get_views(&self) -> &[ViewConfig] {
+        &self.views
+    }
+
+    /// the limits of the rate of responses over UDP, responses are not limited if none
+    pub fn get_rate_limit(&self) -> Option<&RateLimits> {
+        self.rate_limit.as_ref()
+    }
+
+    /// the ACLs of the operations on zones, for zones without their own ACL of an operation
+    pub fn get_acl(&self) -> Option<&AccessControlConfig> {
+        self.acl.as_ref()
+    }
+
+    /// the path of the control socket, there is no control channel if none
+    pub fn get_control_socket(&self) -> Option<&Path> {
+        self.control_socket.as_deref()
+    }
+
+    /// the address of the Prometheus metrics endpoint, there is no endpoint if none
+    pub fn get_metrics_listen_addr(&self) -> Option<SocketAddr> {
+        self.metrics_listen_addr
+    }
+
+    /// the dnstap log of the DNS messages, there is none if none
+    pub fn get_dnstap(&self) -> Option<&DnstapConfig> {
+        self.dnstap.as_ref()
+    }
+
+    /// the log of the queries handled, there is none if none
+    pub fn get_query_log(&self) -> Option<&QueryLogConfig> {
+        self.query_log.as_ref()
+    }
+
     /// the tls certificate to use for accepting tls connections
     pub fn get_tls_cert(&self) -> Option<&dnssec::TlsCertConfig> {
         cfg_if! {
@@ -145,6 +261,23 @@ impl Config {
     }
 }
 
+/// The control channel has no authentication, a TCP address would accept commands from anyone who
+///  can reach it
+fn deserialize_control_socket<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let path = PathBuf::deserialize(deserializer)?;
+    if let Some(addr) = path.to_str().and_then(|p| p.parse::<SocketAddr>().ok()) {
+        return Err(serde::de::Error::custom(format!(
+            "the control channel is only served on a unix domain socket, not over TCP: {}",
+            addr
+        )));
+    }
+
+    Ok(Some(path))
+}
+
 impl FromStr for Config {
     type Err = ConfigError;
 
@@ -153,6 +286,75 @@ impl FromStr for Config {
     }
 }
 
+/// Configuration of the ACLs of the operations on zones, operations without an ACL are allowed to
+///  all clients
+#[derive(Deserialize, Default, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AccessControlConfig {
+    /// Clients allowed to query, except transfers
+    pub query: Option<AclConfig>,
+    /// Clients allowed to send dynamic updates
+    pub update: Option<AclConfig>,
+    /// Clients allowed to request AXFR and IXFR
+    pub transfer: Option<AclConfig>,
+    /// Clients allowed to send NOTIFY
+    pub notify: Option<AclConfig>,
+}
+
+/// Configuration of an ACL, clients are allowed if they are in any of the networks or sign their
+///  requests with any of the keys, an empty list allows no client
+#[derive(Deserialize, Default, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AclConfig {
+    /// Networks of the clients allowed
+    #[serde(default)]
+    pub networks: Vec<IpNet>,
+    /// TSIG keys, requests signed with any of them are allowed
+    #[serde(default)]
+    pub keys: Vec<dnssec::TsigKeyConfig>,
+}
+
+/// Configuration for a view, the zones served to the clients which match it
+#[derive(Deserialize, PartialEq, Eq, Debug)]
+pub struct ViewConfig {
+    /// name of the view, for logging
+    pub name: String,
+    /// Networks of the clients which match the view
+    #[serde(default)]
+    pub match_clients: Vec<IpNet>,
+    /// TSIG keys, requests signed with any of them match the view
+    #[serde(default)]
+    pub match_keys: Vec<dnssec::TsigKeyConfig>,
+    /// Zones served to the clients of the view
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+}
+
+impl ViewConfig {
+    /// the name of the view
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// the networks of the clients which match the view, if neither these nor keys are
+    ///  configured, all clients match
+    pub fn get_match_clients(&self) -> &[IpNet] {
+        &self.match_clients
+    }
+
+    /// the TSIG keys of the clients which match the view
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn get_match_keys(&self) -> &[dnssec::TsigKeyConfig] {
+        &self.match_keys
+    }
+
+    /// the zones of the view
+    pub fn get_zones(&self) -> &[ZoneConfig] {
+        &self.zones
+    }
+}
+
 /// Configuration for a zone
 #[derive(Deserialize, PartialEq, Eq, Debug)]
 pub struct ZoneConfig {
@@ -164,13 +366,33 @@ pub struct ZoneConfig {
     pub file: Option<String>,
     /// Deprecated allow_update, this is a Store option
     pub allow_update: Option<bool>,
-    /// Allow AXFR (TODO: need auth)
+    /// Allow AXFR and IXFR, see `allow_transfer` and `transfer_tsig_keys` to restrict them
     pub allow_axfr: Option<bool>,
+    /// Networks from which transfers are allowed, any address if empty
+    #[serde(default)]
+    pub allow_transfer: Vec<IpNet>,
+    /// TSIG keys authorized to transfer the zone, if any are configured transfers must be signed
+    #[serde(default)]
+    pub transfer_tsig_keys: Vec<dnssec::TsigKeyConfig>,
+    /// Secondaries which are sent a NOTIFY when the zone changes
+    #[serde(default)]
+    pub also_notify: Vec<SocketAddr>,
+    /// Apply the zone as response policies (RPZ) to all other zones, instead of serving it
+    pub response_policy: Option<bool>,
+    /// Limits of the rate of responses over UDP for names in the zone
+    pub rate_limit: Option<RateLimits>,
+    /// ACLs of the operations on the zone, overriding the global ACLs
+    pub acl: Option<AccessControlConfig>,
     /// Enable DnsSec TODO: should this move to StoreConfig?
     pub enable_dnssec: Option<bool>,
     /// Keys for use by the zone
     #[serde(default)]
     pub keys: Vec<dnssec::KeyConfig>,
+    /// TSIG keys authorized to perform dynamic updates of the zone
+    #[serde(default)]
+    pub tsig_keys: Vec<dnssec::TsigKeyConfig>,
+    /// Sign the zone with NSEC3 with these parameters, instead of NSEC
+    pub nsec3: Option<dnssec::Nsec3Config>,
     /// Store configurations, TODO: allow chained Stores
     #[serde(default)]
     pub stores: Option<StoreConfig>,
@@ -203,8 +425,16 @@ impl ZoneConfig {
             file: Some(file),
             allow_update,
             allow_axfr,
+            allow_transfer: Vec::new(),
+            transfer_tsig_keys: Vec::new(),
+            also_notify: Vec::new(),
+            response_policy: None,
+            rate_limit: None,
+            acl: None,
             enable_dnssec,
             keys,
+            tsig_keys: Vec::new(),
+            nsec3: None,
             stores: None,
         }
     }
@@ -256,4 +486,50 @@ impl ZoneConfig {
     pub fn get_keys(&self) -> &[dnssec::KeyConfig] {
         &self.keys
     }
+
+    /// the TSIG keys authorized to perform dynamic updates of the zone
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn get_tsig_keys(&self) -> &[dnssec::TsigKeyConfig] {
+        &self.tsig_keys
+    }
+
+    /// the parameters of the NSEC3 records, if the zone is signed with NSEC3 instead of NSEC
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn get_nsec3(&self) -> Option<&dnssec::Nsec3Config> {
+        self.nsec3.as_ref()
+    }
+
+    /// the networks from which zone transfers are allowed, any address if empty
+    pub fn get_allow_transfer(&self) -> &[IpNet] {
+        &self.allow_transfer
+    }
+
+    /// the TSIG keys authorized to transfer the zone
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn get_transfer_tsig_keys(&self) -> &[dnssec::TsigKeyConfig] {
+        &self.transfer_tsig_keys
+    }
+
+    /// the secondaries which are notified of changes to the zone
+    pub fn get_also_notify(&self) -> &[SocketAddr] {
+        &self.also_notify
+    }
+
+    /// the zone holds response policies, which are applied to the responses of other zones
+    pub fn is_response_policy(&self) -> bool {
+        self.response_policy.unwrap_or(false)
+    }
+
+    /// the limits of the rate of responses for names in the zone, overriding the global limits
+    pub fn get_rate_limit(&self) -> Option<&RateLimits> {
+        self.rate_limit.as_ref()
+    }
+
+    /// the ACLs of the operations on the zone, overriding the global ACLs
+    pub fn get_acl(&self) -> Option<&AccessControlConfig> {
+        self.acl.as_ref()
+    }
 }
@@ -0,0 +1,258 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Protocol Buffers encoding of the `Dnstap` message of [dnstap.proto](https://github.com/dnstap/dnstap.pb/blob/master/dnstap.proto)
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::dnstap::{DnstapMessage, DnstapMessageType, SocketProtocol};
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+// fields of Dnstap
+const DNSTAP_IDENTITY: u64 = 1;
+const DNSTAP_VERSION: u64 = 2;
+const DNSTAP_MESSAGE: u64 = 14;
+const DNSTAP_TYPE: u64 = 15;
+
+/// `Dnstap.Type.MESSAGE`, the only type of `Dnstap`
+const DNSTAP_TYPE_MESSAGE: u64 = 1;
+
+// fields of Message
+const MESSAGE_TYPE: u64 = 1;
+const MESSAGE_SOCKET_FAMILY: u64 = 2;
+const MESSAGE_SOCKET_PROTOCOL: u64 = 3;
+const MESSAGE_QUERY_ADDRESS: u64 = 4;
+const MESSAGE_RESPONSE_ADDRESS: u64 = 5;
+const MESSAGE_QUERY_PORT: u64 = 6;
+const MESSAGE_RESPONSE_PORT: u64 = 7;
+const MESSAGE_QUERY_TIME_SEC: u64 = 8;
+const MESSAGE_QUERY_TIME_NSEC: u64 = 9;
+const MESSAGE_QUERY_MESSAGE: u64 = 10;
+const MESSAGE_QUERY_ZONE: u64 = 11;
+const MESSAGE_RESPONSE_TIME_SEC: u64 = 12;
+const MESSAGE_RESPONSE_TIME_NSEC: u64 = 13;
+const MESSAGE_RESPONSE_MESSAGE: u64 = 14;
+
+// values of SocketFamily
+const SOCKET_FAMILY_INET: u64 = 1;
+const SOCKET_FAMILY_INET6: u64 = 2;
+
+impl DnstapMessageType {
+    /// The value of the type in `Message.Type`
+    fn value(self) -> u64 {
+        match self {
+            Self::ResolverQuery => 3,
+            Self::ResolverResponse => 4,
+            Self::ClientQuery => 5,
+            Self::ClientResponse => 6,
+            Self::ForwarderQuery => 7,
+            Self::ForwarderResponse => 8,
+        }
+    }
+}
+
+impl SocketProtocol {
+    /// The value of the protocol in `SocketProtocol`
+    fn value(self) -> u64 {
+        match self {
+            Self::Udp => 1,
+            Self::Tcp => 2,
+            Self::Dot => 3,
+            Self::Doh => 4,
+            Self::Doq => 7,
+        }
+    }
+}
+
+/// Encodes the message as a `Dnstap` of type `MESSAGE`
+pub(super) fn encode_dnstap(
+    identity: Option<&[u8]>,
+    version: Option<&[u8]>,
+    message: &DnstapMessage<'_>,
+) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(512);
+
+    if let Some(identity) = identity {
+        bytes_field(&mut buffer, DNSTAP_IDENTITY, identity);
+    }
+    if let Some(version) = version {
+        bytes_field(&mut buffer, DNSTAP_VERSION, version);
+    }
+    bytes_field(&mut buffer, DNSTAP_MESSAGE, &encode_message(message));
+    varint_field(&mut buffer, DNSTAP_TYPE, DNSTAP_TYPE_MESSAGE);
+
+    buffer
+}
+
+fn encode_message(message: &DnstapMessage<'_>) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(512);
+
+    varint_field(&mut buffer, MESSAGE_TYPE, message.message_type.value());
+
+    // both addresses are of the same family
+    let family = message.query_address.or(message.response_address);
+    if let Some(address) = family {
+        let family = match address {
+            SocketAddr::V4(_) => SOCKET_FAMILY_INET,
+            SocketAddr::V6(_) => SOCKET_FAMILY_INET6,
+        };
+        varint_field(&mut buffer, MESSAGE_SOCKET_FAMILY, family);
+    }
+    if let Some(protocol) = message.protocol {
+        varint_field(&mut buffer, MESSAGE_SOCKET_PROTOCOL, protocol.value());
+    }
+    if let Some(address) = message.query_address {
+        bytes_field(&mut buffer, MESSAGE_QUERY_ADDRESS, &ip_bytes(address.ip()));
+    }
+    if let Some(address) = message.response_address {
+        bytes_field(
+            &mut buffer,
+            MESSAGE_RESPONSE_ADDRESS,
+            &ip_bytes(address.ip()),
+        );
+    }
+    if let Some(address) = message.query_address {
+        varint_field(&mut buffer, MESSAGE_QUERY_PORT, u64::from(address.port()));
+    }
+    if let Some(address) = message.response_address {
+        varint_field(
+            &mut buffer,
+            MESSAGE_RESPONSE_PORT,
+            u64::from(address.port()),
+        );
+    }
+    if let Some(time) = message.query_time {
+        time_fields(
+            &mut buffer,
+            MESSAGE_QUERY_TIME_SEC,
+            MESSAGE_QUERY_TIME_NSEC,
+            time,
+        );
+    }
+    if let Some(query) = message.query_message {
+        bytes_field(&mut buffer, MESSAGE_QUERY_MESSAGE, query);
+    }
+    if let Some(zone) = message.query_zone {
+        bytes_field(&mut buffer, MESSAGE_QUERY_ZONE, zone);
+    }
+    if let Some(time) = message.response_time {
+        time_fields(
+            &mut buffer,
+            MESSAGE_RESPONSE_TIME_SEC,
+            MESSAGE_RESPONSE_TIME_NSEC,
+            time,
+        );
+    }
+    if let Some(response) = message.response_message {
+        bytes_field(&mut buffer, MESSAGE_RESPONSE_MESSAGE, response);
+    }
+
+    buffer
+}
+
+fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+fn time_fields(buffer: &mut Vec<u8>, sec_field: u64, nsec_field: u64, time: SystemTime) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    varint_field(buffer, sec_field, since_epoch.as_secs());
+    varint(buffer, nsec_field << 3 | WIRE_FIXED32);
+    buffer.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+}
+
+fn varint_field(buffer: &mut Vec<u8>, field: u64, value: u64) {
+    varint(buffer, field << 3 | WIRE_VARINT);
+    varint(buffer, value);
+}
+
+fn bytes_field(buffer: &mut Vec<u8>, field: u64, value: &[u8]) {
+    varint(buffer, field << 3 | WIRE_LENGTH_DELIMITED);
+    varint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value);
+}
+
+fn varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        let mut buffer = Vec::new();
+        varint(&mut buffer, 1);
+        varint(&mut buffer, 300);
+        varint(&mut buffer, u64::from(u32::MAX));
+        assert_eq!(buffer, [0x01, 0xac, 0x02, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+
+    #[test]
+    fn test_encode_client_query() {
+        let mut message = DnstapMessage::new(DnstapMessageType::ClientQuery);
+        message.protocol = Some(SocketProtocol::Udp);
+        message.query_address = Some(([192, 0, 2, 1], 53000).into());
+        message.query_time = Some(UNIX_EPOCH + Duration::new(1_000, 5));
+        message.query_message = Some(&[0xab, 0xcd]);
+
+        let encoded = encode_dnstap(Some(b"ns1"), None, &message);
+
+        #[rustfmt::skip]
+        let expected_message = [
+            0x08, 5, // type: CLIENT_QUERY
+            0x10, 1, // socket_family: INET
+            0x18, 1, // socket_protocol: UDP
+            0x22, 4, 192, 0, 2, 1, // query_address
+            0x30, 0x88, 0x9e, 0x03, // query_port: 53000
+            0x40, 0xe8, 0x07, // query_time_sec: 1000
+            0x4d, 5, 0, 0, 0, // query_time_nsec: 5
+            0x52, 2, 0xab, 0xcd, // query_message
+        ];
+        // identity, then the message
+        let mut expected = b"\x0a\x03ns1\x72".to_vec();
+        expected.push(expected_message.len() as u8);
+        expected.extend_from_slice(&expected_message);
+        expected.extend_from_slice(&[0x78, 1]);
+
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn test_encode_forwarder_response() {
+        let mut message = DnstapMessage::new(DnstapMessageType::ForwarderResponse);
+        message.protocol = Some(SocketProtocol::Dot);
+        message.response_address = Some("[2001:db8::1]:853".parse().unwrap());
+        message.query_zone = Some(&[7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0]);
+
+        let encoded = encode_message(&message);
+
+        let mut expected = vec![0x08, 8, 0x10, 2, 0x18, 3, 0x2a, 16];
+        expected.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        expected.extend_from_slice(&[0x38, 0xd5, 0x06]);
+        expected.extend_from_slice(b"\x5a\x09\x07example\x00");
+
+        assert_eq!(encoded, expected);
+    }
+}
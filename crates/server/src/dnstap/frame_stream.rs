@@ -0,0 +1,274 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Writers of [Frame Streams](https://farsightsec.github.io/fstrm/), the transport of dnstap
+//!
+//! The writers run on their own thread, so that a slow file system or collector never blocks the
+//!  server. They receive the encoded messages over a bounded queue, the messages which don't fit
+//!  in the queue are dropped by `Dnstap::log`.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+};
+
+use tokio::sync::mpsc::Receiver;
+use tracing::warn;
+
+/// The content type of the frames, as expected by dnstap collectors
+pub(super) const CONTENT_TYPE: &[u8] = b"protobuf:dnstap.Dnstap";
+
+#[cfg(unix)]
+const CONTROL_ACCEPT: u32 = 0x01;
+const CONTROL_START: u32 = 0x02;
+const CONTROL_STOP: u32 = 0x03;
+#[cfg(unix)]
+const CONTROL_READY: u32 = 0x04;
+#[cfg(unix)]
+const CONTROL_FINISH: u32 = 0x05;
+
+const CONTROL_FIELD_CONTENT_TYPE: u32 = 0x01;
+
+/// The maximum length of a control frame which is read
+#[cfg(any(unix, test))]
+const MAX_CONTROL_LEN: u32 = 512;
+
+fn write_control<W: Write>(writer: &mut W, control_type: u32) -> io::Result<()> {
+    let mut frame = control_type.to_be_bytes().to_vec();
+    // the stop frame doesn't carry the content type
+    if control_type != CONTROL_STOP {
+        frame.extend_from_slice(&CONTROL_FIELD_CONTENT_TYPE.to_be_bytes());
+        frame.extend_from_slice(&(CONTENT_TYPE.len() as u32).to_be_bytes());
+        frame.extend_from_slice(CONTENT_TYPE);
+    }
+
+    // the escape sequence, a data frame of length zero, announces a control frame
+    writer.write_all(&0u32.to_be_bytes())?;
+    writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+    writer.write_all(&frame)
+}
+
+fn write_data<W: Write>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(data)
+}
+
+#[cfg(any(unix, test))]
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+/// Reads a control frame, returns its type
+#[cfg(any(unix, test))]
+fn read_control<R: Read>(reader: &mut R) -> io::Result<u32> {
+    if read_u32(reader)? != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "expected a control frame",
+        ));
+    }
+
+    let len = read_u32(reader)?;
+    if !(4..=MAX_CONTROL_LEN).contains(&len) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad length of control frame: {}", len),
+        ));
+    }
+
+    let mut frame = vec![0; len as usize];
+    reader.read_exact(&mut frame)?;
+    Ok(u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]))
+}
+
+/// Writes the frames received to the stream, until the queue is closed or a write fails
+///
+/// The writes are flushed whenever the queue is empty.
+fn write_frames<W: Write>(writer: &mut W, receiver: &mut Receiver<Vec<u8>>) -> io::Result<()> {
+    while let Some(data) = receiver.blocking_recv() {
+        write_data(writer, &data)?;
+        while let Ok(data) = receiver.try_recv() {
+            write_data(writer, &data)?;
+        }
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Writes the frames to the file as a unidirectional stream, until the queue is closed
+pub(super) fn write_file(file: File, mut receiver: Receiver<Vec<u8>>) {
+    let mut writer = BufWriter::new(file);
+
+    let result = write_control(&mut writer, CONTROL_START)
+        .and_then(|_| write_frames(&mut writer, &mut receiver))
+        .and_then(|_| write_control(&mut writer, CONTROL_STOP))
+        .and_then(|_| writer.flush());
+
+    if let Err(e) = result {
+        warn!("dnstap file failed, no more messages are logged: {}", e);
+    }
+}
+
+#[cfg(unix)]
+pub(super) use self::socket::write_socket;
+
+#[cfg(unix)]
+mod socket {
+    use std::{
+        os::unix::net::UnixStream,
+        path::Path,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use tokio::sync::mpsc::error::TryRecvError;
+    use tracing::info;
+
+    use super::*;
+
+    /// The time to wait for the collector to reconnect to it, or for its handshake
+    const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+    /// Writes the frames to the collector listening on the socket as a bidirectional stream, until
+    ///  the queue is closed
+    ///
+    /// The collector is reconnected when it fails, the frames received in the meantime are dropped.
+    pub(in crate::dnstap) fn write_socket(path: &Path, mut receiver: Receiver<Vec<u8>>) {
+        loop {
+            match connect(path) {
+                Ok(mut stream) => {
+                    info!("dnstap connected to: {}", path.display());
+                    let mut writer = BufWriter::new(&stream);
+                    match write_frames(&mut writer, &mut receiver) {
+                        Ok(()) => {
+                            // the queue is closed, the server is shutting down
+                            drop(writer);
+                            if let Err(e) = finish(&mut stream) {
+                                warn!("dnstap socket failed to finish: {}", e);
+                            }
+                            return;
+                        }
+                        Err(e) => warn!("dnstap socket failed: {}: {}", path.display(), e),
+                    }
+                }
+                Err(e) => warn!("dnstap socket unavailable: {}: {}", path.display(), e),
+            }
+
+            if !drop_frames(&mut receiver, RECONNECT_DELAY) {
+                return;
+            }
+        }
+    }
+
+    /// Connects to the collector, and negotiates the content type with it
+    fn connect(path: &Path) -> io::Result<UnixStream> {
+        let mut stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(RECONNECT_DELAY))?;
+        stream.set_write_timeout(Some(RECONNECT_DELAY))?;
+
+        write_control(&mut stream, CONTROL_READY)?;
+        if read_control(&mut stream)? != CONTROL_ACCEPT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "collector didn't accept the content type",
+            ));
+        }
+        write_control(&mut stream, CONTROL_START)?;
+
+        Ok(stream)
+    }
+
+    fn finish(stream: &mut UnixStream) -> io::Result<()> {
+        write_control(stream, CONTROL_STOP)?;
+        if read_control(stream)? != CONTROL_FINISH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected the finish frame",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Drops the frames received for the duration, returns false if the queue is closed
+    fn drop_frames(receiver: &mut Receiver<Vec<u8>>, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline {
+            loop {
+                match receiver.try_recv() {
+                    Ok(_) => continue,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return false,
+                }
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        true
+    }
+}
+
+/// Reads a unidirectional stream, returns the data frames
+#[cfg(test)]
+pub(super) fn read_frames<R: Read>(reader: &mut R) -> io::Result<Vec<Vec<u8>>> {
+    if read_control(reader)? != CONTROL_START {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected start"));
+    }
+
+    let mut frames = Vec::new();
+    loop {
+        let len = read_u32(reader)?;
+        if len == 0 {
+            let mut frame = vec![0; read_u32(reader)? as usize];
+            reader.read_exact(&mut frame)?;
+            if frame[..4] != CONTROL_STOP.to_be_bytes() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "expected stop"));
+            }
+            return Ok(frames);
+        }
+
+        let mut frame = vec![0; len as usize];
+        reader.read_exact(&mut frame)?;
+        frames.push(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_frame() {
+        let mut buffer = Vec::new();
+        write_control(&mut buffer, CONTROL_START).unwrap();
+
+        assert_eq!(&buffer[..4], &[0, 0, 0, 0]);
+        assert_eq!(
+            &buffer[4..8],
+            &(12 + CONTENT_TYPE.len() as u32).to_be_bytes()
+        );
+        assert_eq!(&buffer[8..12], &CONTROL_START.to_be_bytes());
+        assert_eq!(&buffer[20..], CONTENT_TYPE);
+
+        assert_eq!(read_control(&mut buffer.as_slice()).unwrap(), CONTROL_START);
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let mut buffer = Vec::new();
+        write_control(&mut buffer, CONTROL_START).unwrap();
+        write_data(&mut buffer, b"first").unwrap();
+        write_data(&mut buffer, b"second").unwrap();
+        write_control(&mut buffer, CONTROL_STOP).unwrap();
+
+        let frames = read_frames(&mut buffer.as_slice()).unwrap();
+        assert_eq!(frames, vec![b"first".to_vec(), b"second".to_vec()]);
+    }
+}
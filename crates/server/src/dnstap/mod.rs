@@ -0,0 +1,494 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Logging of the DNS messages of the server with [dnstap](https://dnstap.info)
+//!
+//! The queries and responses of the clients, and the messages exchanged with upstream servers by
+//!  the forwarder and the recursor, are encoded as dnstap protobuf messages and written as a Frame
+//!  Stream to a file, or to the unix domain socket of a collector, e.g. `fstrm_capture`.
+
+mod encoding;
+mod frame_stream;
+
+use std::{
+    fs::File,
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+use tokio::sync::mpsc::{self, error::TrySendError, Sender};
+use tracing::{info, warn};
+
+#[cfg(any(feature = "trust-dns-resolver", feature = "trust-dns-recursor"))]
+use crate::proto::{op::Message, rr::Name, serialize::binary::BinEncodable};
+#[cfg(feature = "trust-dns-recursor")]
+use crate::recursor::QueryObserver;
+use crate::server::Protocol;
+
+/// The number of messages which are queued for the writer, the messages beyond are dropped
+const QUEUE_LEN: usize = 10_000;
+
+/// The type of a message logged with dnstap
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DnstapMessageType {
+    /// A query received from a client
+    ClientQuery,
+    /// A response sent to a client
+    ClientResponse,
+    /// A query sent by a forwarder to an upstream resolver
+    ForwarderQuery,
+    /// A response received by a forwarder from an upstream resolver
+    ForwarderResponse,
+    /// A query sent by the recursor to an authoritative name server
+    ResolverQuery,
+    /// A response received by the recursor from an authoritative name server
+    ResolverResponse,
+}
+
+impl DnstapMessageType {
+    /// All the types of messages
+    pub const ALL: [Self; 6] = [
+        Self::ClientQuery,
+        Self::ClientResponse,
+        Self::ForwarderQuery,
+        Self::ForwarderResponse,
+        Self::ResolverQuery,
+        Self::ResolverResponse,
+    ];
+}
+
+fn all_message_types() -> Vec<DnstapMessageType> {
+    DnstapMessageType::ALL.to_vec()
+}
+
+/// Configuration of the dnstap log, which is written to either a socket or a file
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DnstapConfig {
+    /// Path of the unix domain socket of the collector, which is reconnected when it fails
+    pub socket: Option<PathBuf>,
+    /// Path of the file to write, which is truncated when the server starts
+    pub file: Option<PathBuf>,
+    /// Identity of the server in the messages, e.g. its host name
+    pub identity: Option<String>,
+    /// Version of the server in the messages, the version of trust-dns by default
+    pub version: Option<String>,
+    /// Types of the messages which are logged, all by default
+    #[serde(default = "all_message_types")]
+    pub message_types: Vec<DnstapMessageType>,
+}
+
+/// Logs DNS messages with dnstap
+///
+/// The messages are written by a thread of their own, the messages which it can't keep up with
+///  are dropped rather than slowing down the server. Clones share the same writer, which finishes
+///  the stream once all of them are dropped.
+#[derive(Clone)]
+pub struct Dnstap {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    identity: Option<Vec<u8>>,
+    version: Option<Vec<u8>>,
+    message_types: Vec<DnstapMessageType>,
+    sender: Sender<Vec<u8>>,
+    dropped: AtomicU64,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Dnstap {
+    /// Opens the file or connects to the socket of the configuration, and starts the writer
+    pub fn from_config(config: &DnstapConfig) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel(QUEUE_LEN);
+        let writer = thread::Builder::new().name("dnstap".to_string());
+
+        let writer = match (&config.file, &config.socket) {
+            (Some(path), None) => {
+                info!("logging dnstap messages to file: {}", path.display());
+                let file = File::create(path)?;
+                writer.spawn(move || frame_stream::write_file(file, receiver))?
+            }
+            #[cfg(unix)]
+            (None, Some(path)) => {
+                info!("logging dnstap messages to socket: {}", path.display());
+                let path = path.clone();
+                writer.spawn(move || frame_stream::write_socket(&path, receiver))?
+            }
+            #[cfg(not(unix))]
+            (None, Some(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "dnstap sockets are only supported on unix",
+                ))
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "dnstap requires either a socket or a file",
+                ))
+            }
+        };
+
+        let version = config
+            .version
+            .clone()
+            .unwrap_or_else(|| format!("trust-dns {}", env!("CARGO_PKG_VERSION")));
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                identity: config.identity.clone().map(String::into_bytes),
+                version: Some(version.into_bytes()),
+                message_types: config.message_types.clone(),
+                sender,
+                dropped: AtomicU64::new(0),
+                writer: Some(writer),
+            }),
+        })
+    }
+
+    /// Returns true if the messages of the type are logged
+    pub fn is_logged(&self, message_type: DnstapMessageType) -> bool {
+        self.inner.message_types.contains(&message_type)
+    }
+
+    /// The number of messages which were dropped because the writer couldn't keep up
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queues the message for the writer, if its type is logged
+    pub(crate) fn log(&self, message: &DnstapMessage<'_>) {
+        if !self.is_logged(message.message_type) {
+            return;
+        }
+
+        let frame = encoding::encode_dnstap(
+            self.inner.identity.as_deref(),
+            self.inner.version.as_deref(),
+            message,
+        );
+
+        match self.inner.sender.try_send(frame) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                if self.inner.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!("dnstap writer can't keep up, messages are dropped");
+                }
+            }
+            // the writer failed, and warned about it
+            Err(TrySendError::Closed(_)) => (),
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // closing the queue ends the stream, wait for it to be written
+        let (closed, _) = mpsc::channel(1);
+        drop(std::mem::replace(&mut self.sender, closed));
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+    }
+}
+
+/// The transport of a message, see `SocketProtocol` of dnstap
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum SocketProtocol {
+    Udp,
+    Tcp,
+    Dot,
+    Doh,
+    Doq,
+}
+
+impl SocketProtocol {
+    /// The transport of a request received by the server, if dnstap has one for it
+    pub(crate) fn from_protocol(protocol: Protocol) -> Option<Self> {
+        match protocol {
+            Protocol::Udp => Some(Self::Udp),
+            Protocol::Tcp => Some(Self::Tcp),
+            Protocol::Tls => Some(Self::Dot),
+            Protocol::Https => Some(Self::Doh),
+            Protocol::Quic => Some(Self::Doq),
+            Protocol::Dtls => None,
+        }
+    }
+}
+
+/// A message to log, the fields which are `None` are left out
+pub(crate) struct DnstapMessage<'a> {
+    pub(crate) message_type: DnstapMessageType,
+    pub(crate) protocol: Option<SocketProtocol>,
+    /// The address of the sender of the query
+    pub(crate) query_address: Option<SocketAddr>,
+    /// The address of the sender of the response
+    pub(crate) response_address: Option<SocketAddr>,
+    pub(crate) query_time: Option<SystemTime>,
+    pub(crate) query_message: Option<&'a [u8]>,
+    /// The zone of the upstream server in wire format, for the forwarder and recursor
+    pub(crate) query_zone: Option<&'a [u8]>,
+    pub(crate) response_time: Option<SystemTime>,
+    pub(crate) response_message: Option<&'a [u8]>,
+}
+
+impl DnstapMessage<'_> {
+    pub(crate) fn new(message_type: DnstapMessageType) -> Self {
+        Self {
+            message_type,
+            protocol: None,
+            query_address: None,
+            response_address: None,
+            query_time: None,
+            query_message: None,
+            query_zone: None,
+            response_time: None,
+            response_message: None,
+        }
+    }
+}
+
+/// Logs the query received from a client, and the response sent to it
+#[derive(Clone)]
+pub(crate) struct ClientTap {
+    dnstap: Dnstap,
+    protocol: Protocol,
+    client: SocketAddr,
+    query_time: SystemTime,
+}
+
+impl ClientTap {
+    /// Logs the query, returns the tap which logs the response to it
+    pub(crate) fn query(
+        dnstap: &Dnstap,
+        protocol: Protocol,
+        client: SocketAddr,
+        query: &[u8],
+    ) -> Self {
+        let tap = Self {
+            dnstap: dnstap.clone(),
+            protocol,
+            client,
+            query_time: SystemTime::now(),
+        };
+
+        let mut message = tap.message(DnstapMessageType::ClientQuery);
+        message.query_message = Some(query);
+        tap.dnstap.log(&message);
+
+        tap
+    }
+
+    /// Logs the response sent to the client
+    pub(crate) fn response(&self, response: &[u8]) {
+        let mut message = self.message(DnstapMessageType::ClientResponse);
+        message.response_time = Some(SystemTime::now());
+        message.response_message = Some(response);
+        self.dnstap.log(&message);
+    }
+
+    fn message(&self, message_type: DnstapMessageType) -> DnstapMessage<'_> {
+        let mut message = DnstapMessage::new(message_type);
+        message.protocol = SocketProtocol::from_protocol(self.protocol);
+        message.query_address = Some(self.client);
+        message.query_time = Some(self.query_time);
+        message
+    }
+}
+
+/// Logs the messages exchanged with an upstream server by a forwarder or the recursor
+#[cfg(any(feature = "trust-dns-resolver", feature = "trust-dns-recursor"))]
+#[derive(Clone)]
+pub(crate) struct UpstreamTap {
+    dnstap: Dnstap,
+    query_type: DnstapMessageType,
+    response_type: DnstapMessageType,
+    zone: Arc<[u8]>,
+    server: Option<SocketAddr>,
+    protocol: Option<SocketProtocol>,
+}
+
+#[cfg(any(feature = "trust-dns-resolver", feature = "trust-dns-recursor"))]
+impl UpstreamTap {
+    /// A tap for the forwarder of the zone, connected to the server
+    #[cfg(feature = "trust-dns-resolver")]
+    pub(crate) fn forwarder(
+        dnstap: Dnstap,
+        zone: &Name,
+        server: SocketAddr,
+        protocol: Option<SocketProtocol>,
+    ) -> Self {
+        Self {
+            dnstap,
+            query_type: DnstapMessageType::ForwarderQuery,
+            response_type: DnstapMessageType::ForwarderResponse,
+            zone: zone.to_bytes().unwrap_or_default().into(),
+            server: Some(server),
+            protocol,
+        }
+    }
+
+    /// A tap for the queries of the recursor to the name servers of the zone
+    #[cfg(feature = "trust-dns-recursor")]
+    pub(crate) fn resolver(dnstap: Dnstap, zone: &Name) -> Self {
+        Self {
+            dnstap,
+            query_type: DnstapMessageType::ResolverQuery,
+            response_type: DnstapMessageType::ResolverResponse,
+            zone: zone.to_bytes().unwrap_or_default().into(),
+            server: None,
+            protocol: None,
+        }
+    }
+
+    /// Logs the query sent upstream
+    pub(crate) fn query(&self, query_time: SystemTime, query: &Message) {
+        if let Some(bytes) = self.encode(self.query_type, query) {
+            let mut message = self.message(self.query_type);
+            message.query_time = Some(query_time);
+            message.query_message = Some(&bytes);
+            self.dnstap.log(&message);
+        }
+    }
+
+    /// Logs the response received from upstream, to the query sent at `query_time`
+    pub(crate) fn response(&self, query_time: Option<SystemTime>, response: &Message) {
+        if let Some(bytes) = self.encode(self.response_type, response) {
+            let mut message = self.message(self.response_type);
+            message.query_time = query_time;
+            message.response_time = Some(SystemTime::now());
+            message.response_message = Some(&bytes);
+            self.dnstap.log(&message);
+        }
+    }
+
+    /// Encodes the message if messages of the type are logged
+    fn encode(&self, message_type: DnstapMessageType, message: &Message) -> Option<Vec<u8>> {
+        if !self.dnstap.is_logged(message_type) {
+            return None;
+        }
+
+        message
+            .to_vec()
+            .map_err(|e| warn!("failed to encode message for dnstap: {}", e))
+            .ok()
+    }
+
+    fn message(&self, message_type: DnstapMessageType) -> DnstapMessage<'_> {
+        let mut message = DnstapMessage::new(message_type);
+        message.protocol = self.protocol;
+        message.response_address = self.server;
+        message.query_zone = Some(&self.zone);
+        message
+    }
+}
+
+/// Logs the queries of the recursor to the name servers of zones, and their responses
+#[cfg(feature = "trust-dns-recursor")]
+pub(crate) struct RecursorTap(pub(crate) Dnstap);
+
+#[cfg(feature = "trust-dns-recursor")]
+impl QueryObserver for RecursorTap {
+    fn query(&self, zone: &Name, query: &Message) {
+        UpstreamTap::resolver(self.0.clone(), zone).query(SystemTime::now(), query);
+    }
+
+    fn response(&self, zone: &Name, response: &Message) {
+        UpstreamTap::resolver(self.0.clone(), zone).response(None, response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "trust-dns-dnstap-{}-{}.fstrm",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: DnstapConfig = toml::from_str(
+            "socket = \"/var/run/dnstap.sock\"\n\
+             identity = \"ns1\"\n\
+             message_types = [\"client_query\", \"forwarder_response\"]",
+        )
+        .unwrap();
+
+        assert_eq!(config.socket, Some(PathBuf::from("/var/run/dnstap.sock")));
+        assert_eq!(config.file, None);
+        assert_eq!(config.identity.as_deref(), Some("ns1"));
+        assert_eq!(
+            config.message_types,
+            vec![
+                DnstapMessageType::ClientQuery,
+                DnstapMessageType::ForwarderResponse
+            ]
+        );
+
+        let config: DnstapConfig = toml::from_str("file = \"dnstap.fstrm\"").unwrap();
+        assert_eq!(config.message_types, DnstapMessageType::ALL.to_vec());
+
+        assert!(toml::from_str::<DnstapConfig>("path = \"dnstap.fstrm\"").is_err());
+    }
+
+    #[test]
+    fn test_requires_one_sink() {
+        let config: DnstapConfig = toml::from_str("identity = \"ns1\"").unwrap();
+        assert!(Dnstap::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_log_to_file() {
+        let path = temp_path("file");
+        let config = DnstapConfig {
+            socket: None,
+            file: Some(path.clone()),
+            identity: Some("ns1".to_string()),
+            version: None,
+            message_types: vec![DnstapMessageType::ClientResponse],
+        };
+
+        let dnstap = Dnstap::from_config(&config).unwrap();
+        assert!(!dnstap.is_logged(DnstapMessageType::ClientQuery));
+
+        let tap = ClientTap::query(
+            &dnstap,
+            Protocol::Tcp,
+            ([192, 0, 2, 1], 1053).into(),
+            b"query",
+        );
+        tap.response(b"response");
+        drop(tap);
+
+        // the stream is finished once the last clone is dropped
+        drop(dnstap);
+        let frames = frame_stream::read_frames(&mut File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // only the response is logged
+        assert_eq!(frames.len(), 1);
+        let frame = &frames[0];
+        assert!(frame.starts_with(b"\x0a\x03ns1\x12"));
+        assert!(frame.windows(8).any(|w| w == b"response"));
+        assert!(!frame.windows(5).any(|w| w == b"query"));
+    }
+}
@@ -5,7 +5,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::fmt;
+use std::{fmt, io};
 
 use crate::proto::error::*;
 use thiserror::Error;
@@ -34,6 +34,10 @@ pub enum ErrorKind {
     },
 
     // foreign
+    /// An error reading or writing the journal or zone file
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
     /// An error got returned by the trust-dns-proto crate
     #[error("proto error: {0}")]
     Proto(#[from] ProtoError),
@@ -44,6 +48,16 @@ pub enum ErrorKind {
     #[error("sqlite error: {0}")]
     Sqlite(#[from] rusqlite::Error),
 
+    /// An error got returned from the sqlx crate
+    #[cfg(feature = "sql")]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "postgres", feature = "mysql"))))]
+    #[error("sql error: {0}")]
+    Sql(#[from] sqlx::Error),
+
+    /// The database is not supported, or the feature for it is not enabled
+    #[error("unsupported database")]
+    UnsupportedDatabase,
+
     /// A request timed out
     #[error("request timed out")]
     Timeout,
@@ -100,6 +114,12 @@ impl From<ProtoError> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        ErrorKind::from(e).into()
+    }
+}
+
 #[cfg(feature = "sqlite")]
 #[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
 impl From<rusqlite::Error> for Error {
@@ -107,3 +127,11 @@ impl From<rusqlite::Error> for Error {
         ErrorKind::from(e).into()
     }
 }
+
+#[cfg(feature = "sql")]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "postgres", feature = "mysql"))))]
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        ErrorKind::from(e).into()
+    }
+}
@@ -55,12 +55,20 @@ pub use trust_dns_recursor as recursor;
 #[cfg_attr(docsrs, doc(cfg(feature = "resolver")))]
 pub use trust_dns_resolver as resolver;
 
+#[cfg(feature = "acme")]
+#[cfg_attr(docsrs, doc(cfg(feature = "acme")))]
+pub mod acme;
 pub mod authority;
 pub mod config;
+pub mod dnstap;
 pub mod error;
+mod metrics;
 pub mod server;
 pub mod store;
 
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use self::metrics::describe_metrics;
 pub use self::server::ServerFuture;
 
 /// Returns the current version of Trust-DNS
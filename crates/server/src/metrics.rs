@@ -0,0 +1,96 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Server instrumentation, exported through the `metrics` facade
+//!
+//! All functions are no-ops unless the `metrics` feature is enabled. The `named` binary installs
+//!  a Prometheus exporter, other applications may install any recorder to collect them.
+
+use std::time::Duration;
+
+use crate::client::op::{LowerQuery, OpCode, ResponseCode};
+#[cfg(feature = "metrics")]
+use crate::client::rr::{LowerName, RecordType};
+use crate::server::Protocol;
+
+#[cfg(feature = "metrics")]
+const REQUESTS: &str = "trust_dns_server_requests_total";
+#[cfg(feature = "metrics")]
+const RESPONSE_DURATION: &str = "trust_dns_server_response_duration_seconds";
+#[cfg(feature = "metrics")]
+const ZONE_TRANSFERS: &str = "trust_dns_server_zone_transfers_total";
+#[cfg(feature = "metrics")]
+const ZONE_SERIAL: &str = "trust_dns_server_zone_serial";
+
+/// Registers the descriptions of all metrics emitted by the server with the installed recorder
+///
+/// This is optional, recorders will still receive the metrics without it.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub fn describe_metrics() {
+    ::metrics::describe_counter!(
+        REQUESTS,
+        "requests handled, by protocol, op code, query type and response code"
+    );
+    ::metrics::describe_histogram!(
+        RESPONSE_DURATION,
+        ::metrics::Unit::Seconds,
+        "time taken to respond to requests, by protocol"
+    );
+    ::metrics::describe_counter!(ZONE_TRANSFERS, "AXFR and IXFR transfers served, by zone");
+    ::metrics::describe_gauge!(ZONE_SERIAL, "serial number of the SOA of each zone");
+}
+
+/// The request was answered with the response code, after `duration`
+///
+/// Successful AXFR and IXFR queries are also counted as transfers of the zone they name.
+pub(crate) fn request(
+    protocol: Protocol,
+    op_code: OpCode,
+    query: &LowerQuery,
+    response_code: ResponseCode,
+    duration: Duration,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let query_type = query.query_type();
+        let protocol = protocol.to_string();
+        ::metrics::increment_counter!(
+            REQUESTS,
+            "protocol" => protocol.clone(),
+            "opcode" => format!("{:?}", op_code),
+            "type" => query_type.to_string(),
+            "rcode" => format!("{:?}", response_code),
+        );
+        ::metrics::histogram!(RESPONSE_DURATION, duration, "protocol" => protocol);
+
+        if op_code == OpCode::Query
+            && response_code == ResponseCode::NoError
+            && matches!(query_type, RecordType::AXFR | RecordType::IXFR)
+        {
+            ::metrics::increment_counter!(
+                ZONE_TRANSFERS,
+                "zone" => query.name().to_string(),
+                "type" => query_type.to_string(),
+            );
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (protocol, op_code, query, response_code, duration);
+}
+
+/// The current serial of the zone, served in the view if any
+#[cfg(feature = "metrics")]
+pub(crate) fn zone_serial(zone: &LowerName, view: Option<&str>, serial: u32) {
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!(
+        ZONE_SERIAL,
+        f64::from(serial),
+        "zone" => zone.to_string(),
+        "view" => view.unwrap_or_default().to_string(),
+    );
+}
@@ -0,0 +1,214 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Access control lists (ACLs), which restrict the clients allowed to query, update, transfer or
+//!  notify zones
+//!
+//! The ACLs are checked before requests are dispatched to the authorities. They are in addition to
+//!  the checks of the authorities themselves, e.g. SIG(0) keys for updates, or the transfer ACL of
+//!  a zone.
+
+use ipnet::IpNet;
+#[cfg(feature = "dnssec")]
+use tracing::debug;
+
+#[cfg(feature = "dnssec")]
+use crate::client::rr::dnssec::tsig::TSigner;
+use crate::{
+    client::{op::OpCode, rr::RecordType},
+    server::Request,
+};
+
+/// An operation on a zone which is controlled by an ACL
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AclOperation {
+    /// Queries of names in the zone, except transfers
+    Query,
+    /// Dynamic updates of the zone
+    Update,
+    /// AXFR and IXFR of the zone
+    Transfer,
+    /// NOTIFY of changes to the zone
+    Notify,
+}
+
+impl AclOperation {
+    /// The operation of the request, if it is controlled by ACLs
+    pub fn of(request: &Request) -> Option<Self> {
+        match request.op_code() {
+            OpCode::Query => match request.query().query_type() {
+                RecordType::AXFR | RecordType::IXFR => Some(Self::Transfer),
+                _ => Some(Self::Query),
+            },
+            OpCode::Update => Some(Self::Update),
+            OpCode::Notify => Some(Self::Notify),
+            _ => None,
+        }
+    }
+}
+
+/// A list of the clients allowed, by network or by TSIG key
+///
+/// Clients are allowed if their source address is in any of the networks, or if they sign their
+///  requests with any of the keys. An empty list allows no client.
+#[derive(Clone, Default)]
+pub struct Acl {
+    networks: Vec<IpNet>,
+    #[cfg(feature = "dnssec")]
+    keys: Vec<TSigner>,
+}
+
+impl Acl {
+    /// Creates a list which allows the clients in the networks
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        Self {
+            networks,
+            #[cfg(feature = "dnssec")]
+            keys: Vec::new(),
+        }
+    }
+
+    /// Adds a key, requests signed with the key are allowed
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn add_key(&mut self, signer: TSigner) {
+        self.keys.push(signer);
+    }
+
+    /// True if the client of the request is allowed
+    pub fn allows(&self, request: &Request) -> bool {
+        let src = request.src().ip();
+        if self.networks.iter().any(|net| net.contains(&src)) {
+            return true;
+        }
+
+        #[cfg(feature = "dnssec")]
+        if let Some(signer) = crate::store::in_memory::verified_tsigner(&self.keys, request) {
+            debug!("key {} of client {} allowed", signer.signer_name(), src);
+            return true;
+        }
+
+        false
+    }
+}
+
+/// The ACLs of each operation, operations without an ACL are allowed to all clients
+#[derive(Clone, Default)]
+pub struct AccessControl {
+    query: Option<Acl>,
+    update: Option<Acl>,
+    transfer: Option<Acl>,
+    notify: Option<Acl>,
+}
+
+impl AccessControl {
+    /// Sets the ACL of the operation, all clients are allowed if none
+    pub fn set_acl(&mut self, operation: AclOperation, acl: Option<Acl>) {
+        *self.acl_mut(operation) = acl;
+    }
+
+    /// The ACL of the operation, if any
+    pub fn acl(&self, operation: AclOperation) -> Option<&Acl> {
+        match operation {
+            AclOperation::Query => self.query.as_ref(),
+            AclOperation::Update => self.update.as_ref(),
+            AclOperation::Transfer => self.transfer.as_ref(),
+            AclOperation::Notify => self.notify.as_ref(),
+        }
+    }
+
+    fn acl_mut(&mut self, operation: AclOperation) -> &mut Option<Acl> {
+        match operation {
+            AclOperation::Query => &mut self.query,
+            AclOperation::Update => &mut self.update,
+            AclOperation::Transfer => &mut self.transfer,
+            AclOperation::Notify => &mut self.notify,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr};
+
+    use super::*;
+    use crate::{
+        authority::MessageRequest,
+        client::{
+            op::{Message, Query},
+            rr::Name,
+            serialize::binary::{BinDecodable, BinEncodable},
+        },
+        server::Protocol,
+    };
+
+    fn request(op_code: OpCode, query_type: RecordType, src: [u8; 4]) -> Request {
+        let mut message = Message::new();
+        message.set_op_code(op_code);
+        message.add_query(Query::query(
+            Name::from_str("example.com.").unwrap(),
+            query_type,
+        ));
+
+        let bytes = message.to_bytes().unwrap();
+        let request = MessageRequest::from_bytes(&bytes).unwrap();
+        Request::new(request, SocketAddr::from((src, 5353)), Protocol::Udp)
+    }
+
+    #[test]
+    fn test_operation_of_request() {
+        let src = [192, 0, 2, 1];
+        let operation = |op_code, query_type| AclOperation::of(&request(op_code, query_type, src));
+
+        assert_eq!(
+            operation(OpCode::Query, RecordType::A),
+            Some(AclOperation::Query)
+        );
+        assert_eq!(
+            operation(OpCode::Query, RecordType::AXFR),
+            Some(AclOperation::Transfer)
+        );
+        assert_eq!(
+            operation(OpCode::Query, RecordType::IXFR),
+            Some(AclOperation::Transfer)
+        );
+        assert_eq!(
+            operation(OpCode::Update, RecordType::SOA),
+            Some(AclOperation::Update)
+        );
+        assert_eq!(
+            operation(OpCode::Notify, RecordType::SOA),
+            Some(AclOperation::Notify)
+        );
+        assert_eq!(operation(OpCode::Status, RecordType::A), None);
+    }
+
+    #[test]
+    fn test_acl_allows_networks() {
+        let acl = Acl::new(vec![
+            "192.0.2.0/24".parse().unwrap(),
+            "10.0.0.1/32".parse().unwrap(),
+        ]);
+
+        assert!(acl.allows(&request(OpCode::Query, RecordType::A, [192, 0, 2, 7])));
+        assert!(acl.allows(&request(OpCode::Query, RecordType::A, [10, 0, 0, 1])));
+        assert!(!acl.allows(&request(OpCode::Query, RecordType::A, [10, 0, 0, 2])));
+        assert!(!Acl::default().allows(&request(OpCode::Query, RecordType::A, [10, 0, 0, 1])));
+    }
+
+    #[test]
+    fn test_access_control() {
+        let mut access_control = AccessControl::default();
+        access_control.set_acl(AclOperation::Update, Some(Acl::default()));
+
+        assert!(access_control.acl(AclOperation::Query).is_none());
+        assert!(access_control.acl(AclOperation::Update).is_some());
+
+        access_control.set_acl(AclOperation::Update, None);
+        assert!(access_control.acl(AclOperation::Update).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Limits on the TCP, TLS, HTTPS and QUIC connections which are open at once
+//!
+//! Every connection holds resources until it is closed, so without limits a client can exhaust
+//!  the server by opening connections which it then leaves idle. Connections over the limits are
+//!  closed as soon as they are accepted.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use serde::Deserialize;
+
+/// The limits of the connections of a `ServerFuture`, over all of its listeners
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct ConnectionLimits {
+    /// Connections open at once, unlimited if none
+    pub max_connections: Option<usize>,
+    /// Connections open at once from the same client address, unlimited if none
+    pub max_connections_per_client: Option<usize>,
+}
+
+#[derive(Default)]
+struct Connections {
+    total: usize,
+    per_client: HashMap<IpAddr, usize>,
+}
+
+/// Counts the open connections against the `ConnectionLimits`
+#[derive(Clone)]
+pub(crate) struct ConnectionTracker {
+    limits: ConnectionLimits,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl ConnectionTracker {
+    pub(crate) fn new(limits: ConnectionLimits) -> Self {
+        Self {
+            limits,
+            connections: Arc::default(),
+        }
+    }
+
+    /// Counts a new connection from the client, which is counted until the guard is dropped
+    ///
+    /// Returns an error describing the limit if the connection is over it.
+    pub(crate) fn open(&self, client: IpAddr) -> Result<ConnectionGuard, &'static str> {
+        let mut connections = self.connections.lock().expect("connections poisoned");
+        if self
+            .limits
+            .max_connections
+            .map_or(false, |max| connections.total >= max)
+        {
+            return Err("too many connections");
+        }
+
+        let per_client = connections.per_client.get(&client).copied().unwrap_or(0);
+        if self
+            .limits
+            .max_connections_per_client
+            .map_or(false, |max| per_client >= max)
+        {
+            return Err("too many connections from the client");
+        }
+
+        connections.total += 1;
+        connections.per_client.insert(client, per_client + 1);
+        Ok(ConnectionGuard {
+            client,
+            connections: Arc::clone(&self.connections),
+        })
+    }
+}
+
+/// An open connection, which is no longer counted once this is dropped
+pub(crate) struct ConnectionGuard {
+    client: IpAddr,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = self.connections.lock().expect("connections poisoned");
+        connections.total -= 1;
+        if let Some(count) = connections.per_client.get_mut(&self.client) {
+            *count -= 1;
+            if *count == 0 {
+                connections.per_client.remove(&self.client);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let tracker = ConnectionTracker::new(ConnectionLimits {
+            max_connections: Some(3),
+            max_connections_per_client: Some(2),
+        });
+        let client1 = IpAddr::from([192, 0, 2, 1]);
+        let client2 = IpAddr::from([192, 0, 2, 2]);
+
+        let first = tracker.open(client1).unwrap();
+        let _second = tracker.open(client1).unwrap();
+        assert!(tracker.open(client1).is_err());
+
+        let third = tracker.open(client2).unwrap();
+        assert!(tracker.open(client2).is_err());
+
+        // closing a connection makes room for the client
+        drop(first);
+        let _fourth = tracker.open(client1).unwrap();
+        assert!(tracker.open(client2).is_err());
+
+        drop(third);
+        let _fifth = tracker.open(client2).unwrap();
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let tracker = ConnectionTracker::new(ConnectionLimits::default());
+        let client = IpAddr::from([192, 0, 2, 1]);
+        let guards = (0..100)
+            .map(|_| tracker.open(client).unwrap())
+            .collect::<Vec<_>>();
+
+        drop(guards);
+        assert!(tracker.connections.lock().unwrap().per_client.is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The control channel, for administering the server while it is running
+//!
+//! The channel is a unix domain socket, access to it is controlled by the permissions of the
+//!  socket file, which is only accessible by its owner. A client connects, sends one command on a
+//!  single line, and reads the response until the server closes the connection. The first line of
+//!  the response is `ok` or `error`, the following lines are the output of the command or the
+//!  error message.
+
+use std::{
+    fmt, fs, io,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+use tracing::{debug, info, warn};
+
+use crate::client::rr::Name;
+
+/// The maximum length of a command
+const MAX_COMMAND_LEN: u64 = 1024;
+
+/// The time a client has to send its command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A command sent over the control channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ControlCommand {
+    /// Reload the configuration and the zones which changed
+    Reload,
+    /// Clear the caches of the recursive and forwarding zones
+    Flush,
+    /// Remove the name from the caches of the recursive zones
+    FlushName(Name),
+    /// Remove the name and all names below it from the caches of the recursive zones
+    FlushTree(Name),
+    /// Report the counters of the requests, and the zones
+    Stats,
+    /// Suspend updates to the zone, after writing the journaled updates to the zone file
+    Freeze(Name),
+    /// Resume updates to the zone
+    Thaw(Name),
+    /// Change the level of the log, e.g. `debug` or `trust_dns_server=trace`
+    LogLevel(String),
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(command: &str) -> Result<Self, Self::Err> {
+        let mut words = command.split_whitespace();
+        let name = words.next().ok_or_else(|| "empty command".to_string())?;
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err(format!("too many arguments: {}", command));
+        }
+
+        let zone = || -> Result<Name, String> {
+            let zone = argument.ok_or_else(|| format!("{} requires a zone", name))?;
+            Name::from_str(zone).map_err(|e| format!("bad zone name {}: {}", zone, e))
+        };
+        let domain = || -> Result<Name, String> {
+            let domain = argument.ok_or_else(|| format!("{} requires a name", name))?;
+            Name::from_str(domain).map_err(|e| format!("bad name {}: {}", domain, e))
+        };
+
+        let command = match (name, argument) {
+            ("reload", None) => Self::Reload,
+            ("flush", None) => Self::Flush,
+            ("flushname", _) => Self::FlushName(domain()?),
+            ("flushtree", _) => Self::FlushTree(domain()?),
+            ("stats", None) => Self::Stats,
+            ("freeze", _) => Self::Freeze(zone()?),
+            ("thaw", _) => Self::Thaw(zone()?),
+            ("loglevel", Some(level)) => Self::LogLevel(level.to_string()),
+            ("loglevel", None) => return Err("loglevel requires a level".to_string()),
+            ("reload", _) | ("flush", _) | ("stats", _) => {
+                return Err(format!("{} takes no arguments", name))
+            }
+            _ => return Err(format!("unknown command: {}", name)),
+        };
+
+        Ok(command)
+    }
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reload => f.write_str("reload"),
+            Self::Flush => f.write_str("flush"),
+            Self::FlushName(name) => write!(f, "flushname {}", name),
+            Self::FlushTree(name) => write!(f, "flushtree {}", name),
+            Self::Stats => f.write_str("stats"),
+            Self::Freeze(zone) => write!(f, "freeze {}", zone),
+            Self::Thaw(zone) => write!(f, "thaw {}", zone),
+            Self::LogLevel(level) => write!(f, "loglevel {}", level),
+        }
+    }
+}
+
+/// Executes the commands received over the control channel
+#[async_trait::async_trait]
+pub trait ControlHandler: Send + Sync + 'static {
+    /// Executes the command, returns its output or an error message
+    async fn handle_command(&self, command: ControlCommand) -> Result<String, String>;
+}
+
+/// Binds the unix domain socket of the control channel
+///
+/// A stale socket file of a previous run is replaced. The socket is only accessible by the owner
+///  of the process, see `serve_control`. This must be called from within a tokio runtime.
+///
+/// The socket is bound in a directory next to `path` which only the owner can access, its
+///  permissions are restricted, then it is renamed to `path`. Bound at `path` directly, it would
+///  be accessible with the permissions of the umask until they are restricted. A directory left
+///  by a previous process with the same id is removed first.
+pub fn bind_control_socket(path: &Path) -> io::Result<UnixListener> {
+    // a socket file which accepts connections belongs to a running server, one left by a process
+    //  which didn't exit cleanly is replaced by the rename
+    if path.exists() && std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("control socket is in use: {}", path.display()),
+        ));
+    }
+
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("control socket is not a file: {}", path.display()),
+        )
+    })?;
+    let mut dir_name = std::ffi::OsString::from(".");
+    dir_name.push(file_name);
+    dir_name.push(format!(".{}", std::process::id()));
+    let dir = path.with_file_name(dir_name);
+
+    // the directory of a process with the same id which didn't exit cleanly, creating it fails if
+    //  it can't be removed
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => (),
+    }
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let result = bind_private(&dir.join("control.sock"), path);
+    fs::remove_dir_all(&dir)?;
+    result
+}
+
+/// Binds the socket in the private directory, and moves it to `path` once only the owner can
+///  access it
+fn bind_private(private_path: &Path, path: &Path) -> io::Result<UnixListener> {
+    let listener = UnixListener::bind(private_path)?;
+    fs::set_permissions(private_path, fs::Permissions::from_mode(0o600))?;
+    fs::rename(private_path, path)?;
+    Ok(listener)
+}
+
+/// Accepts the connections to the control channel, and executes their commands with the handler
+///
+/// This runs until the listener fails.
+pub async fn serve_control<H: ControlHandler>(listener: UnixListener, handler: Arc<H>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("control socket failed: {}", e);
+                return;
+            }
+        };
+
+        let handler = Arc::clone(&handler);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &*handler).await {
+                debug!("control connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection<H: ControlHandler>(stream: UnixStream, handler: &H) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader.take(MAX_COMMAND_LEN));
+
+    let mut line = String::new();
+    tokio::time::timeout(COMMAND_TIMEOUT, reader.read_line(&mut line))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out reading command"))??;
+
+    let result = match line.trim().parse::<ControlCommand>() {
+        Ok(command) => {
+            info!("control command: {}", command);
+            handler.handle_command(command).await
+        }
+        Err(e) => Err(e),
+    };
+
+    let response = match result {
+        Ok(output) => format_response("ok", &output),
+        Err(message) => {
+            warn!("control command failed: {}: {}", line.trim(), message);
+            format_response("error", &message)
+        }
+    };
+
+    writer.write_all(response.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+fn format_response(status: &str, output: &str) -> String {
+    if output.is_empty() {
+        format!("{}\n", status)
+    } else {
+        format!("{}\n{}\n", status, output.trim_end())
+    }
+}
+
+/// Sends the command to the server listening on the control socket, returns the output of the
+///  command, or the error message of the server in the inner `Err`
+pub async fn send_control_command(
+    path: &Path,
+    command: &ControlCommand,
+) -> io::Result<Result<String, String>> {
+    let mut stream = UnixStream::connect(path).await?;
+    stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    let (status, output) = response.split_once('\n').unwrap_or((&response, ""));
+    let output = output.trim_end().to_string();
+    match status {
+        "ok" => Ok(Ok(output)),
+        "error" => Ok(Err(output)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad response from control socket: {}", status),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!("reload".parse(), Ok(ControlCommand::Reload));
+        assert_eq!(" stats \n".parse(), Ok(ControlCommand::Stats));
+        assert_eq!(
+            "freeze example.com.".parse(),
+            Ok(ControlCommand::Freeze(
+                Name::from_str("example.com.").unwrap()
+            ))
+        );
+        assert_eq!(
+            "loglevel debug".parse(),
+            Ok(ControlCommand::LogLevel("debug".to_string()))
+        );
+
+        assert!("".parse::<ControlCommand>().is_err());
+        assert!("thaw".parse::<ControlCommand>().is_err());
+        assert!("flushname".parse::<ControlCommand>().is_err());
+        assert!("flush now".parse::<ControlCommand>().is_err());
+        assert!("loglevel".parse::<ControlCommand>().is_err());
+        assert!("restart".parse::<ControlCommand>().is_err());
+    }
+
+    #[test]
+    fn test_command_round_trip() {
+        for command in [
+            ControlCommand::Reload,
+            ControlCommand::Flush,
+            ControlCommand::FlushName(Name::from_str("www.example.com.").unwrap()),
+            ControlCommand::FlushTree(Name::from_str("example.com.").unwrap()),
+            ControlCommand::Stats,
+            ControlCommand::Freeze(Name::from_str("example.com.").unwrap()),
+            ControlCommand::Thaw(Name::from_str("example.com.").unwrap()),
+            ControlCommand::LogLevel("trust_dns_server=trace".to_string()),
+        ] {
+            assert_eq!(command.to_string().parse(), Ok(command));
+        }
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS Cookies (RFC 7873), with which clients prove that they received an earlier response
+//!
+//! The server cookie sent to a client is the keyed hash of the client cookie and address, as
+//!  specified by RFC 9018, so that a client which returns it can't be spoofing its address.
+//!  Clients sending more queries over UDP than allowed without a valid server cookie are answered
+//!  with BADCOOKIE and a new server cookie, or if they don't support cookies, with a truncated
+//!  response so that they retry over TCP.
+
+use std::{
+    collections::HashMap,
+    hash::Hasher,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+use siphasher::sip::SipHasher24;
+
+use crate::{
+    proto::{
+        op::Edns,
+        rr::rdata::opt::{EdnsCode, EdnsOption},
+    },
+    server::Protocol,
+};
+
+/// The version of the server cookies, RFC 9018 section 4.2
+const VERSION: u8 = 1;
+/// Server cookies are valid for an hour after they are created, RFC 9018 section 4.3
+const MAX_AGE: u32 = 3600;
+/// Server cookies created up to five minutes in the future are valid, RFC 9018 section 4.3
+const MAX_SKEW: u32 = 300;
+/// Clients tracked at once, the counts are forgotten beyond this
+const MAX_CLIENTS: usize = 100_000;
+
+/// The configuration of the DNS Cookies of a `ServerFuture`
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct CookieConfig {
+    /// Seconds after which the secret of the server cookies is replaced, the cookies of the
+    ///  previous secret are still accepted until the next replacement
+    pub secret_rotation: u64,
+    /// Queries per second over UDP from a client without a valid server cookie, beyond which
+    ///  the queries are refused, `0` requires valid server cookies of all queries over UDP, all
+    ///  queries are answered if none
+    pub max_queries_per_second: Option<u32>,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            secret_rotation: 86400,
+            max_queries_per_second: None,
+        }
+    }
+}
+
+/// What is done with a request, given its DNS Cookies
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum CookieCheck {
+    /// The request has no cookie, and is answered
+    Answer,
+    /// The request is answered, with the cookie in the response
+    AnswerWith(EdnsOption),
+    /// The request has no valid server cookie, and is answered with BADCOOKIE and the cookie
+    BadCookie(EdnsOption),
+    /// The request has no cookie, and is answered with a truncated response
+    Truncate,
+    /// The cookie of the request is malformed, and is answered with FORMERR
+    FormErr,
+}
+
+struct Secrets {
+    current: [u8; 16],
+    previous: [u8; 16],
+    rotated: Instant,
+}
+
+struct Counts {
+    second: Instant,
+    queries: HashMap<IpAddr, u32>,
+}
+
+/// Creates and validates the server cookies, and counts the queries without them
+#[derive(Clone)]
+pub(crate) struct ServerCookies {
+    config: CookieConfig,
+    secrets: Arc<Mutex<Secrets>>,
+    counts: Arc<Mutex<Counts>>,
+}
+
+impl ServerCookies {
+    pub(crate) fn new(config: CookieConfig) -> Self {
+        let secret = rand::random();
+        Self {
+            config,
+            secrets: Arc::new(Mutex::new(Secrets {
+                current: secret,
+                previous: secret,
+                rotated: Instant::now(),
+            })),
+            counts: Arc::new(Mutex::new(Counts {
+                second: Instant::now(),
+                queries: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Checks the cookie of the request from the client
+    pub(crate) fn check(
+        &self,
+        edns: Option<&Edns>,
+        client: IpAddr,
+        protocol: Protocol,
+    ) -> CookieCheck {
+        let now = timestamp();
+        let (current, previous) = self.secrets();
+
+        let (client_cookie, server_cookie) =
+            match edns.and_then(|edns| edns.option(EdnsCode::Cookie)) {
+                Some(EdnsOption::Cookie { client, server }) => (*client, server),
+                Some(_) => return CookieCheck::FormErr,
+                None if self.is_over_limit(client, protocol) => return CookieCheck::Truncate,
+                None => return CookieCheck::Answer,
+            };
+
+        let is_valid = [current, previous]
+            .iter()
+            .any(|secret| is_valid(server_cookie, &client_cookie, client, secret, now));
+        let cookie = EdnsOption::Cookie {
+            client: client_cookie,
+            server: server_cookie_for(&client_cookie, client, &current, now),
+        };
+
+        if !is_valid && self.is_over_limit(client, protocol) {
+            CookieCheck::BadCookie(cookie)
+        } else {
+            CookieCheck::AnswerWith(cookie)
+        }
+    }
+
+    /// The current and previous secrets, which are rotated first if due
+    fn secrets(&self) -> ([u8; 16], [u8; 16]) {
+        let mut secrets = self.secrets.lock().expect("secrets poisoned");
+        if secrets.rotated.elapsed() >= Duration::from_secs(self.config.secret_rotation) {
+            secrets.previous = secrets.current;
+            secrets.current = rand::random();
+            secrets.rotated = Instant::now();
+        }
+
+        (secrets.current, secrets.previous)
+    }
+
+    /// Counts the query without a valid server cookie, queries over TCP are never over the limit
+    fn is_over_limit(&self, client: IpAddr, protocol: Protocol) -> bool {
+        let max = match self.config.max_queries_per_second {
+            Some(max) if protocol == Protocol::Udp => max,
+            _ => return false,
+        };
+
+        let mut counts = self.counts.lock().expect("counts poisoned");
+        if counts.second.elapsed() >= Duration::from_secs(1) || counts.queries.len() >= MAX_CLIENTS
+        {
+            counts.second = Instant::now();
+            counts.queries.clear();
+        }
+
+        let queries = counts.queries.entry(client).or_insert(0);
+        *queries = queries.saturating_add(1);
+        *queries > max
+    }
+}
+
+/// Seconds since the UNIX epoch, in serial number arithmetic
+fn timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as u32)
+}
+
+/// The server cookie for the client at the time, RFC 9018 section 4
+fn server_cookie_for(
+    client_cookie: &[u8; 8],
+    client: IpAddr,
+    secret: &[u8; 16],
+    time: u32,
+) -> Vec<u8> {
+    let mut cookie = Vec::with_capacity(16);
+    cookie.push(VERSION);
+    cookie.extend_from_slice(&[0; 3]);
+    cookie.extend_from_slice(&time.to_be_bytes());
+    let hash = hash(client_cookie, &cookie, client, secret);
+    cookie.extend_from_slice(&hash.to_be_bytes());
+    cookie
+}
+
+/// Whether the server cookie was created for the client, within the validity of its timestamp
+fn is_valid(
+    server_cookie: &[u8],
+    client_cookie: &[u8; 8],
+    client: IpAddr,
+    secret: &[u8; 16],
+    now: u32,
+) -> bool {
+    if server_cookie.len() != 16 || server_cookie[0] != VERSION {
+        return false;
+    }
+
+    let time = u32::from_be_bytes([
+        server_cookie[4],
+        server_cookie[5],
+        server_cookie[6],
+        server_cookie[7],
+    ]);
+    let age = now.wrapping_sub(time);
+    if age > MAX_AGE && time.wrapping_sub(now) > MAX_SKEW {
+        return false;
+    }
+
+    let hash = hash(client_cookie, &server_cookie[..8], client, secret);
+    server_cookie[8..] == hash.to_be_bytes()
+}
+
+/// SipHash-2-4 of the client cookie, the version, reserved and timestamp of the server cookie,
+///  and the address of the client
+fn hash(client_cookie: &[u8; 8], header: &[u8], client: IpAddr, secret: &[u8; 16]) -> u64 {
+    let mut hasher = SipHasher24::new_with_key(secret);
+    hasher.write(client_cookie);
+    hasher.write(header);
+    match client {
+        IpAddr::V4(ip) => hasher.write(&ip.octets()),
+        IpAddr::V6(ip) => hasher.write(&ip.octets()),
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_COOKIE: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn edns(server: Vec<u8>) -> Edns {
+        let mut edns = Edns::new();
+        edns.options_mut().insert(EdnsOption::Cookie {
+            client: CLIENT_COOKIE,
+            server,
+        });
+        edns
+    }
+
+    fn server_cookie(check: CookieCheck) -> Vec<u8> {
+        match check {
+            CookieCheck::AnswerWith(EdnsOption::Cookie { client, server })
+            | CookieCheck::BadCookie(EdnsOption::Cookie { client, server }) => {
+                assert_eq!(client, CLIENT_COOKIE);
+                server
+            }
+            check => panic!("unexpected check: {:?}", check),
+        }
+    }
+
+    #[test]
+    fn test_server_cookie() {
+        let cookies = ServerCookies::new(CookieConfig {
+            max_queries_per_second: Some(0),
+            ..CookieConfig::default()
+        });
+        let client = IpAddr::from([192, 0, 2, 1]);
+
+        // clients without a server cookie get one
+        let check = cookies.check(Some(&edns(vec![])), client, Protocol::Udp);
+        assert!(matches!(check, CookieCheck::BadCookie(_)));
+        let server = server_cookie(check);
+        assert_eq!(server.len(), 16);
+        assert_eq!(server[0], VERSION);
+
+        // which is valid for them only
+        let check = cookies.check(Some(&edns(server.clone())), client, Protocol::Udp);
+        assert!(matches!(check, CookieCheck::AnswerWith(_)));
+        let other = IpAddr::from([192, 0, 2, 2]);
+        let check = cookies.check(Some(&edns(server)), other, Protocol::Udp);
+        assert!(matches!(check, CookieCheck::BadCookie(_)));
+
+        // and queries without cookies are truncated
+        assert_eq!(
+            cookies.check(None, client, Protocol::Udp),
+            CookieCheck::Truncate
+        );
+        assert_eq!(
+            cookies.check(None, client, Protocol::Tcp),
+            CookieCheck::Answer
+        );
+    }
+
+    #[test]
+    fn test_rate() {
+        let cookies = ServerCookies::new(CookieConfig {
+            max_queries_per_second: Some(2),
+            ..CookieConfig::default()
+        });
+        let client = IpAddr::from([192, 0, 2, 1]);
+
+        assert_eq!(
+            cookies.check(None, client, Protocol::Udp),
+            CookieCheck::Answer
+        );
+        let check = cookies.check(Some(&edns(vec![])), client, Protocol::Udp);
+        assert!(matches!(check, CookieCheck::AnswerWith(_)));
+        let check = cookies.check(Some(&edns(vec![])), client, Protocol::Udp);
+        assert!(matches!(check, CookieCheck::BadCookie(_)));
+        assert_eq!(
+            cookies.check(None, client, Protocol::Udp),
+            CookieCheck::Truncate
+        );
+    }
+
+    #[test]
+    fn test_expired_and_rotated() {
+        let client = IpAddr::from([192, 0, 2, 1]);
+        let secret = [7; 16];
+        let now = timestamp();
+
+        let cookie = server_cookie_for(&CLIENT_COOKIE, client, &secret, now - MAX_AGE - 1);
+        assert!(!is_valid(&cookie, &CLIENT_COOKIE, client, &secret, now));
+        let cookie = server_cookie_for(&CLIENT_COOKIE, client, &secret, now - 60);
+        assert!(is_valid(&cookie, &CLIENT_COOKIE, client, &secret, now));
+        assert!(!is_valid(&cookie, &CLIENT_COOKIE, client, &[8; 16], now));
+
+        // cookies of the previous secret are still accepted
+        let cookies = ServerCookies::new(CookieConfig {
+            secret_rotation: 0,
+            max_queries_per_second: Some(0),
+        });
+        let server = server_cookie(cookies.check(Some(&edns(vec![])), client, Protocol::Udp));
+        let check = cookies.check(Some(&edns(server)), client, Protocol::Udp);
+        assert!(matches!(check, CookieCheck::AnswerWith(_)));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let cookies = ServerCookies::new(CookieConfig::default());
+        let mut edns = Edns::new();
+        edns.options_mut()
+            .insert(EdnsOption::Unknown(u16::from(EdnsCode::Cookie), vec![1, 2]));
+
+        let client = IpAddr::from([192, 0, 2, 1]);
+        assert_eq!(
+            cookies.check(Some(&edns), client, Protocol::Udp),
+            CookieCheck::FormErr
+        );
+    }
+}
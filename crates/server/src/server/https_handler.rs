@@ -5,20 +5,26 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{io, net::SocketAddr, sync::Arc};
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
 
 use bytes::{Bytes, BytesMut};
 use futures_util::lock::Mutex;
 use h2::server;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::watch,
+};
 use tracing::{debug, warn};
 use trust_dns_proto::rr::Record;
 
 use crate::{
     authority::MessageResponse,
+    dnstap::{ClientTap, Dnstap},
     proto::https::https_server,
     server::{
-        request_handler::RequestHandler, response_handler::ResponseHandler, server_future,
+        request_handler::RequestHandler,
+        response_handler::ResponseHandler,
+        server_future::{self, ResponseOptions},
         Protocol, ResponseInfo,
     },
 };
@@ -28,6 +34,10 @@ pub(crate) async fn h2_handler<T, I>(
     io: I,
     src_addr: SocketAddr,
     dns_hostname: Arc<str>,
+    dnstap: Option<Dnstap>,
+    timeout: Duration,
+    options: ResponseOptions,
+    shutting_down: watch::Receiver<bool>,
 ) where
     T: RequestHandler,
     I: AsyncRead + AsyncWrite + Unpin,
@@ -35,17 +45,47 @@ pub(crate) async fn h2_handler<T, I>(
     let dns_hostname = dns_hostname.clone();
 
     // Start the HTTP/2.0 connection handshake
-    let mut h2 = match server::handshake(io).await {
-        Ok(h2) => h2,
-        Err(err) => {
+    let mut h2 = match server_future::with_timeout(server::handshake(io), timeout).await {
+        Some(Ok(h2)) => h2,
+        Some(Err(err)) => {
             warn!("handshake error from {}: {}", src_addr, err);
             return;
         }
+        None => {
+            warn!("handshake from {} timed out", src_addr);
+            return;
+        }
     };
 
     // Accept all inbound HTTP/2.0 streams sent over the
     // connection.
-    while let Some(next_request) = h2.accept().await {
+    let mut closing = false;
+    loop {
+        let next_request = if closing {
+            // the streams which were already opened are still answered
+            h2.accept().await
+        } else {
+            let next_request = server_future::with_timeout(h2.accept(), timeout);
+            match server_future::unless_shutting_down(next_request, &shutting_down).await {
+                Some(Some(next_request)) => next_request,
+                Some(None) => {
+                    debug!("https connection from {} is idle, closing", src_addr);
+                    h2.graceful_shutdown();
+                    closing = true;
+                    continue;
+                }
+                None => {
+                    h2.graceful_shutdown();
+                    closing = true;
+                    continue;
+                }
+            }
+        };
+        let next_request = match next_request {
+            Some(next_request) => next_request,
+            None => break,
+        };
+
         let (request, respond) = match next_request {
             Ok(next_request) => next_request,
             Err(err) => {
@@ -57,10 +97,18 @@ pub(crate) async fn h2_handler<T, I>(
         debug!("Received request: {:#?}", request);
         let dns_hostname = dns_hostname.clone();
         let handler = handler.clone();
-        let responder = HttpsResponseHandle(Arc::new(Mutex::new(respond)));
+        let mut responder = HttpsResponseHandle {
+            respond: Arc::new(Mutex::new(respond)),
+            tap: None,
+        };
 
         match https_server::message_from(dns_hostname, request).await {
-            Ok(bytes) => handle_request(bytes, src_addr, handler, responder).await,
+            Ok(bytes) => {
+                responder.tap = dnstap
+                    .as_ref()
+                    .map(|dnstap| ClientTap::query(dnstap, Protocol::Https, src_addr, &bytes));
+                handle_request(bytes, src_addr, handler, responder, options.clone()).await
+            }
             Err(err) => warn!("error while handling request from {}: {}", src_addr, err),
         };
 
@@ -73,14 +121,26 @@ async fn handle_request<T>(
     src_addr: SocketAddr,
     handler: Arc<T>,
     responder: HttpsResponseHandle,
+    options: ResponseOptions,
 ) where
     T: RequestHandler,
 {
-    server_future::handle_request(&bytes, src_addr, Protocol::Https, handler, responder).await
+    server_future::handle_request(
+        &bytes,
+        src_addr,
+        Protocol::Https,
+        handler,
+        responder,
+        options,
+    )
+    .await
 }
 
 #[derive(Clone)]
-struct HttpsResponseHandle(Arc<Mutex<::h2::server::SendResponse<Bytes>>>);
+struct HttpsResponseHandle {
+    respond: Arc<Mutex<::h2::server::SendResponse<Bytes>>>,
+    tap: Option<ClientTap>,
+}
 
 #[async_trait::async_trait]
 impl ResponseHandler for HttpsResponseHandle {
@@ -105,12 +165,15 @@ impl ResponseHandler for HttpsResponseHandle {
             let mut encoder = BinEncoder::new(&mut bytes);
             response.destructive_emit(&mut encoder)?
         };
+        if let Some(ref tap) = self.tap {
+            tap.response(&bytes);
+        }
         let bytes = Bytes::from(bytes);
         let response = response::new(bytes.len())?;
 
         debug!("sending response: {:#?}", response);
         let mut stream = self
-            .0
+            .respond
             .lock()
             .await
             .send_response(response, false)
@@ -0,0 +1,332 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Listener sockets inherited from the service manager, i.e. systemd socket activation
+//!
+//! The service manager binds the sockets, so that the server needs no privileges to listen on
+//!  port 53, and keeps them open while the server restarts, so that no queries are refused. The
+//!  sockets are passed as the file descriptors from 3 on, their number in `LISTEN_FDS` and their
+//!  names in `LISTEN_FDNAMES`, see `sd_listen_fds(3)`. The names tell the listeners the sockets
+//!  are for, e.g. with `FileDescriptorName=tls` in the systemd socket unit.
+//!
+//! The server passes its sockets on in the same way when it hands them off to the process which
+//!  replaces it, see `ShutdownHandle::hand_off`.
+
+use std::{
+    io,
+    net::{TcpListener, UdpSocket},
+};
+#[cfg(unix)]
+use std::{
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    process::Command,
+};
+
+/// The first file descriptor passed, after stdin, stdout and stderr
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+enum Socket {
+    Udp(UdpSocket),
+    Tcp(TcpListener),
+}
+
+#[cfg(unix)]
+impl Socket {
+    /// The socket of the listener with the name, which must own the file descriptor
+    unsafe fn from_raw_fd(name: &str, fd: RawFd) -> Self {
+        match name {
+            ListenFds::UDP | ListenFds::QUIC => Self::Udp(UdpSocket::from_raw_fd(fd)),
+            _ => Self::Tcp(TcpListener::from_raw_fd(fd)),
+        }
+    }
+
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Self::Udp(socket) => socket.as_raw_fd(),
+            Self::Tcp(listener) => listener.as_raw_fd(),
+        }
+    }
+}
+
+/// The listener sockets passed to the process, by the name of their listener
+#[derive(Default)]
+pub struct ListenFds {
+    sockets: Vec<(String, Socket)>,
+}
+
+impl ListenFds {
+    /// Name of the UDP sockets
+    pub const UDP: &'static str = "udp";
+    /// Name of the TCP listeners
+    pub const TCP: &'static str = "tcp";
+    /// Name of the TLS listeners
+    pub const TLS: &'static str = "tls";
+    /// Name of the HTTPS listeners
+    pub const HTTPS: &'static str = "https";
+    /// Name of the QUIC sockets
+    pub const QUIC: &'static str = "quic";
+
+    /// Takes the sockets passed to this process, none if it was not socket activated
+    ///
+    /// The environment variables are removed, so that they are not passed on to child processes.
+    ///  The sockets of the UDP and QUIC listeners are UDP sockets, the others TCP listeners.
+    ///
+    /// # Safety
+    ///
+    /// The file descriptors which are passed must not be used otherwise, this must be called once.
+    #[cfg(unix)]
+    pub unsafe fn from_env() -> io::Result<Self> {
+        use std::env;
+
+        let names = listen_fd_names(
+            env::var("LISTEN_PID").ok().as_deref(),
+            env::var("LISTEN_FDS").ok().as_deref(),
+            env::var("LISTEN_FDNAMES").ok().as_deref(),
+            std::process::id(),
+        )?;
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+
+        let sockets = names
+            .into_iter()
+            .zip(LISTEN_FDS_START..)
+            .map(|(name, fd)| {
+                let socket = Socket::from_raw_fd(&name, fd);
+                (name, socket)
+            })
+            .collect();
+        Ok(Self { sockets })
+    }
+
+    /// Sockets are only passed to processes on unix
+    #[cfg(not(unix))]
+    pub unsafe fn from_env() -> io::Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Whether no sockets are left
+    pub fn is_empty(&self) -> bool {
+        self.sockets.is_empty()
+    }
+
+    /// The names of the sockets which are left
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.sockets.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Takes the UDP sockets with the name, set to non-blocking for `tokio`
+    pub fn take_udp(&mut self, name: &str) -> io::Result<Vec<UdpSocket>> {
+        self.take(name)
+            .into_iter()
+            .map(|socket| match socket {
+                Socket::Udp(socket) => socket.set_nonblocking(true).map(|_| socket),
+                Socket::Tcp(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("the {} socket is not a UDP socket", name),
+                )),
+            })
+            .collect()
+    }
+
+    /// Takes the TCP listeners with the name, set to non-blocking for `tokio`
+    pub fn take_tcp(&mut self, name: &str) -> io::Result<Vec<TcpListener>> {
+        self.take(name)
+            .into_iter()
+            .map(|socket| match socket {
+                Socket::Tcp(listener) => listener.set_nonblocking(true).map(|_| listener),
+                Socket::Udp(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("the {} socket is not a TCP listener", name),
+                )),
+            })
+            .collect()
+    }
+
+    /// Adds a duplicate of the socket, e.g. of a listener of the server to hand off to another
+    ///  process
+    #[cfg(unix)]
+    pub(crate) fn push_duplicate<S: AsRawFd>(&mut self, name: &str, socket: &S) -> io::Result<()> {
+        // the duplicate is closed on exec, only `pass_to` passes it on
+        let fd = unsafe { libc::fcntl(socket.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let socket = unsafe { Socket::from_raw_fd(name, fd) };
+        self.sockets.push((name.to_string(), socket));
+        Ok(())
+    }
+
+    /// Passes the sockets on to the program which replaces this process when the command is
+    ///  executed with `CommandExt::exec`, as the service manager does
+    ///
+    /// The process keeps its pid, which is `LISTEN_PID`, so the service manager keeps tracking it.
+    #[cfg(unix)]
+    pub fn pass_to(self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let names = self.names().collect::<Vec<_>>().join(":");
+        command
+            .env("LISTEN_PID", std::process::id().to_string())
+            .env("LISTEN_FDS", self.sockets.len().to_string())
+            .env("LISTEN_FDNAMES", names);
+
+        let fds = self
+            .sockets
+            .iter()
+            .map(|(_, socket)| socket.as_raw_fd())
+            .collect::<Vec<_>>();
+        let mut moved = vec![0; fds.len()];
+        let sockets = self.sockets;
+
+        // runs right before the program is executed, where nothing may be allocated
+        let pass = move || {
+            // the sockets are held until the command is dropped
+            let _sockets = &sockets;
+
+            // the sockets are moved out of the way first, so that none is closed by another's dup2
+            let above = LISTEN_FDS_START + fds.len() as RawFd;
+            for (fd, moved) in fds.iter().zip(moved.iter_mut()) {
+                *moved = unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, above) };
+                if *moved < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            // dup2 clears close-on-exec of the copies
+            for (moved, fd) in moved.iter().zip(LISTEN_FDS_START..) {
+                if unsafe { libc::dup2(*moved, fd) } < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        };
+        unsafe {
+            command.pre_exec(pass);
+        }
+    }
+
+    fn take(&mut self, name: &str) -> Vec<Socket> {
+        let (taken, left) = std::mem::take(&mut self.sockets)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(socket_name, _)| socket_name == name);
+        self.sockets = left;
+        taken.into_iter().map(|(_, socket)| socket).collect()
+    }
+}
+
+/// The names of the file descriptors passed to the process, empty if they are for another one
+///
+/// Sockets without a name are named `unknown` by systemd.
+#[cfg(any(unix, test))]
+fn listen_fd_names(
+    pid: Option<&str>,
+    fds: Option<&str>,
+    names: Option<&str>,
+    own_pid: u32,
+) -> io::Result<Vec<String>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_string());
+
+    let (pid, fds) = match (pid, fds) {
+        (Some(pid), Some(fds)) => (pid, fds),
+        _ => return Ok(Vec::new()),
+    };
+    if pid.parse::<u32>().map_err(|_| invalid("invalid LISTEN_PID"))? != own_pid {
+        return Ok(Vec::new());
+    }
+    let fds = fds
+        .parse::<usize>()
+        .map_err(|_| invalid("invalid LISTEN_FDS"))?;
+
+    let mut names = names
+        .map(|names| names.split(':').map(ToString::to_string).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if names.len() > fds {
+        return Err(invalid("more LISTEN_FDNAMES than LISTEN_FDS"));
+    }
+    names.resize(fds, "unknown".to_string());
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_fd_names() {
+        assert_eq!(
+            listen_fd_names(Some("42"), Some("3"), Some("udp:tcp:tls"), 42).unwrap(),
+            vec!["udp", "tcp", "tls"]
+        );
+        assert_eq!(
+            listen_fd_names(Some("42"), Some("2"), None, 42).unwrap(),
+            vec!["unknown", "unknown"]
+        );
+
+        // the sockets of another process, e.g. the parent
+        assert!(listen_fd_names(Some("41"), Some("2"), None, 42)
+            .unwrap()
+            .is_empty());
+        assert!(listen_fd_names(None, None, None, 42).unwrap().is_empty());
+
+        assert!(listen_fd_names(Some("42"), Some("two"), None, 42).is_err());
+        assert!(listen_fd_names(Some("42"), Some("1"), Some("udp:tcp"), 42).is_err());
+    }
+
+    #[test]
+    fn test_take() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut listen_fds = ListenFds {
+            sockets: vec![
+                (ListenFds::UDP.to_string(), Socket::Udp(udp)),
+                (ListenFds::TCP.to_string(), Socket::Tcp(tcp)),
+            ],
+        };
+
+        assert!(listen_fds.take_udp(ListenFds::TLS).unwrap().is_empty());
+        assert_eq!(listen_fds.take_udp(ListenFds::UDP).unwrap().len(), 1);
+        assert_eq!(listen_fds.names().collect::<Vec<_>>(), vec!["tcp"]);
+        assert_eq!(listen_fds.take_tcp(ListenFds::TCP).unwrap().len(), 1);
+        assert!(listen_fds.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_push_duplicate() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = udp.local_addr().unwrap();
+        let mut listen_fds = ListenFds::default();
+        listen_fds.push_duplicate(ListenFds::UDP, &udp).unwrap();
+        drop(udp);
+
+        // the socket is still open, bound to the same address
+        let udp = listen_fds.take_udp(ListenFds::UDP).unwrap().remove(0);
+        assert_eq!(udp.local_addr().unwrap(), addr);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pass_to() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut listen_fds = ListenFds::default();
+        listen_fds.push_duplicate(ListenFds::TCP, &tcp).unwrap();
+        listen_fds.push_duplicate(ListenFds::UDP, &udp).unwrap();
+
+        // fails if the descriptors 3 and 4 are not open
+        let mut command = Command::new("sh");
+        command.args(&["-c", "exec 5<&3 6<&4 && echo $LISTEN_FDS $LISTEN_FDNAMES"]);
+        listen_fds.pass_to(&mut command);
+        let output = command.output().unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"2 tcp:udp\n");
+    }
+}
@@ -7,18 +7,51 @@
 
 //! `Server` component for hosting a domain name servers operations.
 
+mod access_control;
+mod connection_limits;
+mod cookies;
+#[cfg(unix)]
+mod control;
 #[cfg(feature = "dns-over-https")]
 mod https_handler;
+mod listen_fds;
 mod protocol;
+mod proxy_protocol;
 #[cfg(feature = "dns-over-quic")]
 mod quic_handler;
+mod query_log;
+mod rate_limit;
+mod reloadable_handler;
 mod request_handler;
+mod request_stats;
 mod response_handler;
 mod server_future;
 mod timeout_stream;
+#[cfg(feature = "dns-over-rustls")]
+mod tls_certificate;
 
+pub use self::access_control::{AccessControl, Acl, AclOperation};
+pub use self::connection_limits::ConnectionLimits;
+pub use self::cookies::CookieConfig;
+pub use self::listen_fds::ListenFds;
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub use self::control::{
+    bind_control_socket, send_control_command, serve_control, ControlCommand, ControlHandler,
+};
 pub use self::protocol::Protocol;
+pub use self::proxy_protocol::ProxyProtocol;
+pub(crate) use self::query_log::{QueryRecord, QuerySource};
+pub use self::query_log::{QueryLog, QueryLogConfig, QueryLogFormat};
+pub use self::rate_limit::{
+    RateLimitAction, RateLimitedResponseHandle, RateLimits, ResponseRateLimiter,
+};
+pub use self::reloadable_handler::{ReloadHandle, ReloadableHandler};
 pub use self::request_handler::{Request, RequestHandler, RequestInfo, ResponseInfo};
+pub use self::request_stats::RequestStats;
 pub use self::response_handler::{ResponseHandle, ResponseHandler};
-pub use self::server_future::ServerFuture;
+pub use self::server_future::{ServerFuture, ShutdownHandle};
 pub use self::timeout_stream::TimeoutStream;
+#[cfg(feature = "dns-over-rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
+pub use self::tls_certificate::{ReloadableCertificate, TlsCertificate};
@@ -0,0 +1,177 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The PROXY protocol (version 2) of HAProxy, with which load balancers pass on the address of
+//!  the clients of the connections they forward
+//!
+//! Connections from the configured proxies start with a binary header holding the addresses of
+//!  the original connection, the source address of which is then used as the client address, for
+//!  ACLs, rate limits, ECS and logging. Connections from other addresses are not expected to send
+//!  the header, so that clients can't claim the address of another client.
+//!
+//! <https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt>
+
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use ipnet::IpNet;
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The signature which starts the header
+const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The proxies of a `ServerFuture`, whose TCP, TLS and HTTPS connections use the PROXY protocol
+#[derive(Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct ProxyProtocol {
+    /// Networks of the proxies, whose connections must start with a PROXY protocol v2 header
+    pub proxies: Vec<IpNet>,
+}
+
+impl ProxyProtocol {
+    /// Whether connections from the address must start with the header
+    pub fn is_proxy(&self, addr: SocketAddr) -> bool {
+        self.proxies.iter().any(|net| net.contains(&addr.ip()))
+    }
+
+    /// Reads the header of the connection from a proxy, returns the address of the client
+    ///
+    /// The address of connections which are not from a proxy is returned as is, as it is for
+    ///  connections of the proxy itself, e.g. its health checks.
+    pub(crate) async fn client_addr<S: AsyncRead + Unpin>(
+        &self,
+        stream: &mut S,
+        peer: SocketAddr,
+    ) -> io::Result<SocketAddr> {
+        if !self.is_proxy(peer) {
+            return Ok(peer);
+        }
+
+        let mut header = [0_u8; 16];
+        stream.read_exact(&mut header).await?;
+        if header[..12] != SIGNATURE {
+            return Err(invalid_data("no PROXY protocol v2 header"));
+        }
+        if header[12] >> 4 != 2 {
+            return Err(invalid_data("unsupported PROXY protocol version"));
+        }
+
+        // the addresses are followed by TLVs, which are ignored
+        let mut addresses = vec![0_u8; usize::from(u16::from_be_bytes([header[14], header[15]]))];
+        stream.read_exact(&mut addresses).await?;
+
+        match header[12] & 0x0f {
+            // LOCAL, the connection was opened by the proxy itself
+            0x0 => Ok(peer),
+            // PROXY
+            0x1 => Ok(source_addr(header[13], &addresses)?.unwrap_or(peer)),
+            _ => Err(invalid_data("unsupported PROXY protocol command")),
+        }
+    }
+}
+
+/// The source address of the original connection, none for other families than TCP over IP
+fn source_addr(family: u8, addresses: &[u8]) -> io::Result<Option<SocketAddr>> {
+    match family {
+        // TCP over IPv4
+        0x11 if addresses.len() >= 12 => {
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // TCP over IPv6
+        0x21 if addresses.len() >= 36 => {
+            let mut ip = [0_u8; 16];
+            ip.copy_from_slice(&addresses[..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port)))
+        }
+        0x11 | 0x21 => Err(invalid_data("truncated PROXY protocol addresses")),
+        // UNSPEC, UDP and UNIX sockets, the address of the connection is kept
+        _ => Ok(None),
+    }
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+
+    fn proxy_protocol() -> ProxyProtocol {
+        ProxyProtocol {
+            proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        }
+    }
+
+    fn header(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    #[test]
+    fn test_ipv4() {
+        let proxy = SocketAddr::from(([10, 0, 0, 1], 4000));
+        let mut bytes = header(0x1, 0x11, &[192, 0, 2, 1, 192, 0, 2, 53, 0x30, 0x39, 0, 53]);
+        bytes.extend_from_slice(b"query");
+
+        let mut stream = &bytes[..];
+        let client = block_on(proxy_protocol().client_addr(&mut stream, proxy)).unwrap();
+        assert_eq!(client, SocketAddr::from(([192, 0, 2, 1], 12345)));
+        // the rest of the stream is left for the requests
+        assert_eq!(stream, b"query");
+    }
+
+    #[test]
+    fn test_ipv6_with_tlvs() {
+        let proxy = SocketAddr::from(([10, 0, 0, 1], 4000));
+        let mut addresses = Ipv6Addr::LOCALHOST.octets().to_vec();
+        addresses.extend_from_slice(&Ipv6Addr::UNSPECIFIED.octets());
+        addresses.extend_from_slice(&[0x30, 0x39, 0, 53]);
+        // a NOOP TLV
+        addresses.extend_from_slice(&[0x04, 0, 1, 0]);
+        let bytes = header(0x1, 0x21, &addresses);
+
+        let client = block_on(proxy_protocol().client_addr(&mut &bytes[..], proxy)).unwrap();
+        assert_eq!(client, SocketAddr::from((Ipv6Addr::LOCALHOST, 12345)));
+    }
+
+    #[test]
+    fn test_local_and_direct() {
+        let proxy = SocketAddr::from(([10, 0, 0, 1], 4000));
+        let bytes = header(0x0, 0x00, &[]);
+        let client = block_on(proxy_protocol().client_addr(&mut &bytes[..], proxy)).unwrap();
+        assert_eq!(client, proxy);
+
+        // clients which are not proxies can't send the header
+        let direct = SocketAddr::from(([192, 0, 2, 1], 4000));
+        let bytes = header(0x1, 0x11, &[10, 0, 0, 2, 192, 0, 2, 53, 0, 1, 0, 53]);
+        let client = block_on(proxy_protocol().client_addr(&mut &bytes[..], direct)).unwrap();
+        assert_eq!(client, direct);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let proxy = SocketAddr::from(([10, 0, 0, 1], 4000));
+        let bytes = b"PROXY TCP4 192.0.2.1 192.0.2.53 12345 53\r\n";
+        assert!(block_on(proxy_protocol().client_addr(&mut &bytes[..], proxy)).is_err());
+
+        let bytes = header(0x1, 0x11, &[192, 0, 2, 1]);
+        assert!(block_on(proxy_protocol().client_addr(&mut &bytes[..], proxy)).is_err());
+    }
+}
@@ -0,0 +1,579 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Query log, a structured record of each request handled by the server
+//!
+//! Each record holds the client, the query, the response code, the time taken to respond and the
+//!  zone which answered. Records are written as JSON lines, or as the compact text lines of
+//!  `dnstap -q`, to a file which is rotated by size or age. A fraction of the requests may be
+//!  sampled, with a separate rate for the requests which are answered with an error.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tracing::{info, warn};
+
+use crate::{
+    authority::ZoneType,
+    client::{
+        op::{LowerQuery, OpCode, ResponseCode},
+        rr::LowerName,
+    },
+    server::Protocol,
+};
+
+/// The number of records which are queued for the writer, the records beyond are dropped
+const QUEUE_LEN: usize = 10_000;
+
+/// The format of the records of the query log
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryLogFormat {
+    /// One JSON object per line
+    Json,
+    /// One line of space separated fields, in the style of the quiet output of `dnstap`
+    DnstapLite,
+}
+
+impl Default for QueryLogFormat {
+    fn default() -> Self {
+        Self::Json
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+/// Configuration of the query log
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct QueryLogConfig {
+    /// Path of the file to which records are appended
+    pub path: PathBuf,
+    /// Format of the records, JSON by default
+    #[serde(default)]
+    pub format: QueryLogFormat,
+    /// Fraction of the requests which are logged, between 0 and 1, all by default
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Fraction of the requests answered with an error which are logged, all by default
+    ///
+    /// NXDOMAIN is not an error, those requests are sampled with `sample_rate`.
+    #[serde(default = "default_sample_rate")]
+    pub error_sample_rate: f64,
+    /// Size in bytes beyond which the file is rotated, it is never rotated by size if none
+    pub max_size: Option<u64>,
+    /// Seconds after which the file is rotated, it is never rotated by age if none
+    pub rotate_interval: Option<u64>,
+    /// Number of rotated files which are kept, as `path.1` (the newest) to `path.N`
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+/// Logs a record of each request handled by the server
+///
+/// The records are written by a thread of their own, the records which it can't keep up with are
+///  dropped rather than slowing down the server. Clones share the same writer.
+#[derive(Clone)]
+pub struct QueryLog {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    format: QueryLogFormat,
+    sample_rate: f64,
+    error_sample_rate: f64,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    sender: Sender<String>,
+    dropped: AtomicU64,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl QueryLog {
+    /// Opens the file of the configuration, and starts the writer
+    pub fn from_config(config: &QueryLogConfig) -> io::Result<Self> {
+        for rate in [config.sample_rate, config.error_sample_rate] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("sample rates must be between 0 and 1: {}", rate),
+                ));
+            }
+        }
+
+        info!("logging queries to: {}", config.path.display());
+        let file = RotatingFile::open(
+            config.path.clone(),
+            config.max_size,
+            config.rotate_interval.map(Duration::from_secs),
+            config.max_files,
+        )?;
+
+        let (sender, receiver) = mpsc::channel(QUEUE_LEN);
+        let writer = thread::Builder::new()
+            .name("query-log".to_string())
+            .spawn(move || write_records(file, receiver))?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                format: config.format,
+                sample_rate: config.sample_rate,
+                error_sample_rate: config.error_sample_rate,
+                requests: AtomicU64::new(0),
+                errors: AtomicU64::new(0),
+                sender,
+                dropped: AtomicU64::new(0),
+                writer: Some(writer),
+            }),
+        })
+    }
+
+    /// The number of records which were dropped because the writer couldn't keep up
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if the request answered with the response code is to be logged
+    ///
+    /// Requests are sampled evenly, e.g. every tenth one at a rate of 0.1.
+    pub(crate) fn is_sampled(&self, response_code: ResponseCode) -> bool {
+        let (counter, rate) = match response_code {
+            ResponseCode::NoError | ResponseCode::NXDomain => {
+                (&self.inner.requests, self.inner.sample_rate)
+            }
+            _ => (&self.inner.errors, self.inner.error_sample_rate),
+        };
+
+        let count = counter.fetch_add(1, Ordering::Relaxed) as f64;
+        ((count + 1.0) * rate).floor() > (count * rate).floor()
+    }
+
+    /// Queues the record for the writer
+    pub(crate) fn log(&self, record: &QueryRecord<'_>) {
+        let line = match self.inner.format {
+            QueryLogFormat::Json => record.to_json(),
+            QueryLogFormat::DnstapLite => record.to_dnstap_lite(),
+        };
+
+        match self.inner.sender.try_send(line) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                if self.inner.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!("query log writer can't keep up, records are dropped");
+                }
+            }
+            // the writer failed, and warned about it
+            Err(TrySendError::Closed(_)) => (),
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // closing the queue stops the writer, wait for the queued records to be written
+        let (closed, _) = mpsc::channel(1);
+        drop(std::mem::replace(&mut self.sender, closed));
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+    }
+}
+
+/// The kind of zone which the query name belongs to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum QuerySource {
+    /// A zone of which the server is the primary or a secondary
+    Authoritative,
+    /// A zone which is forwarded to upstream resolvers
+    Forward,
+    /// A zone which is resolved recursively
+    Recursive,
+    /// No zone of the server, the query was refused
+    None,
+}
+
+impl QuerySource {
+    pub(crate) fn from_zone_type(zone_type: Option<ZoneType>) -> Self {
+        match zone_type {
+            Some(ZoneType::Forward) => Self::Forward,
+            Some(ZoneType::Hint) => Self::Recursive,
+            Some(_) => Self::Authoritative,
+            None => Self::None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Authoritative => "authoritative",
+            Self::Forward => "forward",
+            Self::Recursive => "recursive",
+            Self::None => "none",
+        }
+    }
+}
+
+/// The record of a request handled by the server
+pub(crate) struct QueryRecord<'a> {
+    pub(crate) time: SystemTime,
+    pub(crate) client: SocketAddr,
+    pub(crate) protocol: Protocol,
+    pub(crate) id: u16,
+    pub(crate) op_code: OpCode,
+    pub(crate) query: &'a LowerQuery,
+    pub(crate) response_code: ResponseCode,
+    pub(crate) latency: Duration,
+    pub(crate) view: Option<&'a str>,
+    pub(crate) zone: Option<&'a LowerName>,
+    pub(crate) source: QuerySource,
+}
+
+impl QueryRecord<'_> {
+    fn to_json(&self) -> String {
+        let mut line = String::with_capacity(256);
+        line.push('{');
+        json_field(&mut line, "time", &timestamp(self.time));
+        json_field(&mut line, "client", &self.client.to_string());
+        json_field(&mut line, "protocol", &self.protocol.to_string());
+        write!(line, "\"id\":{},", self.id).expect("write to string");
+        json_field(&mut line, "opcode", &format!("{:?}", self.op_code));
+        json_field(&mut line, "qname", &self.query.name().to_string());
+        json_field(&mut line, "qclass", &self.query.query_class().to_string());
+        json_field(&mut line, "qtype", &self.query.query_type().to_string());
+        json_field(&mut line, "rcode", &format!("{:?}", self.response_code));
+        write!(line, "\"latency_us\":{},", self.latency.as_micros()).expect("write to string");
+        if let Some(view) = self.view {
+            json_field(&mut line, "view", view);
+        }
+        if let Some(zone) = self.zone {
+            json_field(&mut line, "zone", &zone.to_string());
+        }
+        json_string(&mut line, "source");
+        line.push(':');
+        json_string(&mut line, self.source.as_str());
+        line.push('}');
+        line
+    }
+
+    fn to_dnstap_lite(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} {} {} {:.3}ms {} {} {}",
+            timestamp(self.time),
+            self.client,
+            self.protocol,
+            self.id,
+            self.query.name(),
+            self.query.query_class(),
+            self.query.query_type(),
+            format!("{:?}", self.response_code).to_uppercase(),
+            self.latency.as_secs_f64() * 1000.0,
+            self.zone
+                .map_or_else(|| "-".to_string(), ToString::to_string),
+            self.source.as_str(),
+            self.view.unwrap_or("-"),
+        )
+    }
+}
+
+/// Formats the time as RFC 3339 in UTC, with microseconds
+fn timestamp(time: SystemTime) -> String {
+    let time = OffsetDateTime::from(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.microsecond(),
+    )
+}
+
+fn json_field(line: &mut String, name: &str, value: &str) {
+    json_string(line, name);
+    line.push(':');
+    json_string(line, value);
+    line.push(',');
+}
+
+fn json_string(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            c if c.is_control() => write!(line, "\\u{:04x}", c as u32).expect("write to string"),
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+/// Writes the records received to the file, until the queue is closed or a write fails
+///
+/// The writes are flushed whenever the queue is empty.
+fn write_records(mut file: RotatingFile, mut receiver: Receiver<String>) {
+    let result = (|| {
+        while let Some(line) = receiver.blocking_recv() {
+            file.write_line(&line)?;
+            while let Ok(line) = receiver.try_recv() {
+                file.write_line(&line)?;
+            }
+            file.flush()?;
+        }
+        Ok::<_, io::Error>(())
+    })();
+
+    if let Err(e) = result {
+        warn!("query log failed, no more queries are logged: {}", e);
+    }
+}
+
+/// A file which is renamed to `path.1` when it grows too large or too old, after the older files
+///  are renamed in turn, up to `path.N`
+struct RotatingFile {
+    path: PathBuf,
+    max_size: Option<u64>,
+    rotate_interval: Option<Duration>,
+    max_files: usize,
+    writer: BufWriter<File>,
+    size: u64,
+    opened: Instant,
+}
+
+impl RotatingFile {
+    fn open(
+        path: PathBuf,
+        max_size: Option<u64>,
+        rotate_interval: Option<Duration>,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let (writer, size) = Self::append(&path)?;
+        Ok(Self {
+            path,
+            max_size,
+            rotate_interval,
+            max_files,
+            writer,
+            size,
+            opened: Instant::now(),
+        })
+    }
+
+    fn append(path: &Path) -> io::Result<(BufWriter<File>, u64)> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok((BufWriter::new(file), size))
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        let too_large = self.max_size.map_or(false, |max_size| {
+            self.size > 0 && self.size + len > max_size
+        });
+        let too_old = self
+            .rotate_interval
+            .map_or(false, |interval| self.opened.elapsed() >= interval);
+        if too_large || too_old {
+            self.rotate()?;
+        }
+
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        let (writer, size) = Self::append(&self.path)?;
+        self.writer = writer;
+        self.size = size;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{}", n));
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::client::{
+        op::Query,
+        rr::{Name, RecordType},
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("query_log_{}_{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(path: PathBuf) -> QueryLogConfig {
+        toml::from_str::<QueryLogConfig>(&format!("path = {:?}", path)).unwrap()
+    }
+
+    fn record<'a>(query: &'a LowerQuery, zone: Option<&'a LowerName>) -> QueryRecord<'a> {
+        QueryRecord {
+            time: SystemTime::UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789),
+            client: ([192, 0, 2, 1], 5353).into(),
+            protocol: Protocol::Udp,
+            id: 4660,
+            op_code: OpCode::Query,
+            query,
+            response_code: ResponseCode::NoError,
+            latency: Duration::from_micros(1_500),
+            view: None,
+            zone,
+            source: QuerySource::Authoritative,
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: QueryLogConfig = toml::from_str(
+            "
+path = \"/var/log/trust-dns/queries.log\"
+format = \"dnstap_lite\"
+sample_rate = 0.1
+max_size = 1048576
+rotate_interval = 86400
+",
+        )
+        .unwrap();
+
+        assert_eq!(config.format, QueryLogFormat::DnstapLite);
+        assert_eq!(config.sample_rate, 0.1);
+        assert_eq!(config.error_sample_rate, 1.0);
+        assert_eq!(config.max_size, Some(1_048_576));
+        assert_eq!(config.rotate_interval, Some(86_400));
+        assert_eq!(config.max_files, 5);
+
+        assert!(toml::from_str::<QueryLogConfig>("format = \"json\"").is_err());
+    }
+
+    #[test]
+    fn test_record_formats() {
+        let query = LowerQuery::from(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let zone = LowerName::from_str("example.com.").unwrap();
+        let record = record(&query, Some(&zone));
+
+        assert_eq!(
+            record.to_json(),
+            "{\"time\":\"2020-09-13T12:26:40.123456Z\",\"client\":\"192.0.2.1:5353\",\
+             \"protocol\":\"UDP\",\"id\":4660,\"opcode\":\"Query\",\
+             \"qname\":\"www.example.com.\",\"qclass\":\"IN\",\"qtype\":\"A\",\
+             \"rcode\":\"NoError\",\"latency_us\":1500,\"zone\":\"example.com.\",\
+             \"source\":\"authoritative\"}"
+        );
+        assert_eq!(
+            record.to_dnstap_lite(),
+            "2020-09-13T12:26:40.123456Z 192.0.2.1:5353 UDP 4660 www.example.com. IN A \
+             NOERROR 1.500ms example.com. authoritative -"
+        );
+    }
+
+    #[test]
+    fn test_json_escaping() {
+        let mut line = String::new();
+        json_string(&mut line, "a\"b\\c\n");
+        assert_eq!(line, "\"a\\\"b\\\\c\\u000a\"");
+    }
+
+    #[test]
+    fn test_sampling() {
+        let dir = test_dir("sampling");
+        let mut config = config(dir.join("queries.log"));
+        config.sample_rate = 0.25;
+        config.error_sample_rate = 0.0;
+        let query_log = QueryLog::from_config(&config).unwrap();
+
+        let sampled = (0..100)
+            .filter(|_| query_log.is_sampled(ResponseCode::NoError))
+            .count();
+        assert_eq!(sampled, 25);
+        assert!(!query_log.is_sampled(ResponseCode::ServFail));
+
+        drop(query_log);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = test_dir("rotate");
+        let path = dir.join("queries.log");
+        let mut config = config(path.clone());
+        config.max_size = Some(500);
+        config.max_files = 2;
+
+        let query = LowerQuery::from(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let query_log = QueryLog::from_config(&config).unwrap();
+        for _ in 0..10 {
+            query_log.log(&record(&query, None));
+        }
+        drop(query_log);
+
+        let lines = |path: &Path| fs::read_to_string(path).unwrap().lines().count();
+        assert_eq!(lines(&path), 2);
+        assert_eq!(lines(&rotated_path(&path, 1)), 2);
+        assert_eq!(lines(&rotated_path(&path, 2)), 2);
+        assert!(!rotated_path(&path, 3).exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -9,6 +9,7 @@ use std::{io, net::SocketAddr, sync::Arc};
 
 use bytes::{Bytes, BytesMut};
 use futures_util::lock::Mutex;
+use tokio::sync::watch;
 use tracing::{debug, warn};
 use trust_dns_proto::{
     error::ProtoError,
@@ -18,9 +19,12 @@ use trust_dns_proto::{
 
 use crate::{
     authority::MessageResponse,
+    dnstap::{ClientTap, Dnstap},
     proto::quic::QuicStreams,
     server::{
-        request_handler::RequestHandler, response_handler::ResponseHandler, server_future,
+        request_handler::RequestHandler,
+        response_handler::ResponseHandler,
+        server_future::{self, ResponseOptions},
         Protocol, ResponseInfo,
     },
 };
@@ -30,6 +34,9 @@ pub(crate) async fn quic_handler<T>(
     mut quic_streams: QuicStreams,
     src_addr: SocketAddr,
     _dns_hostname: Arc<str>,
+    dnstap: Option<Dnstap>,
+    options: ResponseOptions,
+    shutting_down: watch::Receiver<bool>,
 ) -> Result<(), ProtoError>
 where
     T: RequestHandler,
@@ -37,8 +44,11 @@ where
     // TODO: we should make this configurable
     let mut max_requests = 100u32;
 
-    // Accept all inbound quic streams sent over the connection.
-    while let Some(next_request) = quic_streams.next().await {
+    // Accept all inbound quic streams sent over the connection, the connection is closed once
+    //  the server is shutting down
+    while let Some(Some(next_request)) =
+        server_future::unless_shutting_down(quic_streams.next(), &shutting_down).await
+    {
         let mut request_stream = match next_request {
             Ok(next_request) => next_request,
             Err(err) => {
@@ -55,9 +65,15 @@ where
         );
         let handler = handler.clone();
         let stream = Arc::new(Mutex::new(request_stream));
-        let responder = QuicResponseHandle(stream.clone());
+        let tap = dnstap
+            .as_ref()
+            .map(|dnstap| ClientTap::query(dnstap, Protocol::Quic, src_addr, &request));
+        let responder = QuicResponseHandle {
+            stream: stream.clone(),
+            tap,
+        };
 
-        handle_request(request, src_addr, handler, responder).await;
+        handle_request(request, src_addr, handler, responder, options.clone()).await;
 
         max_requests -= 1;
         if max_requests == 0 {
@@ -77,14 +93,26 @@ async fn handle_request<T>(
     src_addr: SocketAddr,
     handler: Arc<T>,
     responder: QuicResponseHandle,
+    options: ResponseOptions,
 ) where
     T: RequestHandler,
 {
-    server_future::handle_request(&bytes, src_addr, Protocol::Quic, handler, responder).await
+    server_future::handle_request(
+        &bytes,
+        src_addr,
+        Protocol::Quic,
+        handler,
+        responder,
+        options,
+    )
+    .await
 }
 
 #[derive(Clone)]
-struct QuicResponseHandle(Arc<Mutex<QuicStream>>);
+struct QuicResponseHandle {
+    stream: Arc<Mutex<QuicStream>>,
+    tap: Option<ClientTap>,
+}
 
 #[async_trait::async_trait]
 impl ResponseHandler for QuicResponseHandle {
@@ -110,10 +138,13 @@ impl ResponseHandler for QuicResponseHandle {
             let mut encoder = BinEncoder::new(&mut bytes);
             response.destructive_emit(&mut encoder)?
         };
+        if let Some(ref tap) = self.tap {
+            tap.response(&bytes);
+        }
         let bytes = Bytes::from(bytes);
 
         debug!("sending quic response: {}", bytes.len());
-        let mut lock = self.0.lock().await;
+        let mut lock = self.stream.lock().await;
         lock.send_bytes(bytes).await?;
         lock.finish().await?;
 
@@ -0,0 +1,522 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Response Rate Limiting (RRL), which limits the identical responses sent to a network over UDP
+//!
+//! Responses to spoofed source addresses can be used to flood a victim with traffic, amplified
+//!  by the size of the responses. RRL limits the rate of responses which are identical from the
+//!  point of view of such an attack, i.e. with the same response code and name, sent to the same
+//!  network. Over the limit, responses are dropped, except for every `slip`th one which is sent
+//!  truncated without records, so that legitimate clients behind the network retry over TCP.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use ipnet::IpNet;
+use serde::Deserialize;
+use tracing::debug;
+use trust_dns_proto::rr::Record;
+
+use crate::{
+    authority::MessageResponse,
+    client::{
+        op::ResponseCode,
+        rr::{LowerName, RecordType},
+    },
+    server::{ResponseHandler, ResponseInfo},
+};
+
+/// The limits of a `ResponseRateLimiter`
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct RateLimits {
+    /// Responses per second with answers, or without for names which exist, for each name and type
+    pub responses_per_second: u32,
+    /// NXDOMAIN responses per second, for each zone
+    pub nxdomains_per_second: u32,
+    /// Responses per second with any other response code
+    pub errors_per_second: u32,
+    /// Seconds over which the rate is averaged, i.e. the burst of responses which is allowed
+    pub window: u32,
+    /// Every `slip`th response over the limit is sent truncated, the others are dropped, `0` drops
+    ///  all of them
+    pub slip: u32,
+    /// Length of the prefix of IPv4 clients which share their limits
+    pub ipv4_prefix_len: u8,
+    /// Length of the prefix of IPv6 clients which share their limits
+    pub ipv6_prefix_len: u8,
+    /// Limits tracked at once, limits which have recovered are forgotten beyond this, then the
+    ///  least recently updated ones
+    pub max_entries: usize,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            responses_per_second: 5,
+            nxdomains_per_second: 5,
+            errors_per_second: 5,
+            window: 15,
+            slip: 2,
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 56,
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// What is done with a response
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RateLimitAction {
+    /// The response is within the limit and is sent
+    Send,
+    /// The response is over the limit and is sent truncated, without records
+    Slip,
+    /// The response is over the limit and is not sent
+    Drop,
+}
+
+/// The kind of response, which have separate limits
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum ResponseKind {
+    Response,
+    NxDomain,
+    Error,
+}
+
+impl From<ResponseCode> for ResponseKind {
+    fn from(response_code: ResponseCode) -> Self {
+        match response_code {
+            ResponseCode::NoError => Self::Response,
+            ResponseCode::NXDomain => Self::NxDomain,
+            _ => Self::Error,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct BucketKey {
+    network: IpNet,
+    kind: ResponseKind,
+    name: Option<LowerName>,
+    record_type: Option<RecordType>,
+}
+
+/// Responses which may be sent, the balance is negative once the limit is reached
+struct Bucket {
+    balance: f64,
+    updated: Instant,
+    limited: u32,
+}
+
+/// Limits the rate of identical responses to each network, see the module docs
+#[derive(Clone)]
+pub struct ResponseRateLimiter {
+    limits: RateLimits,
+    buckets: Arc<Mutex<HashMap<BucketKey, Bucket>>>,
+}
+
+impl ResponseRateLimiter {
+    /// Creates a limiter without any responses sent yet
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The limits which are applied
+    pub fn limits(&self) -> &RateLimits {
+        &self.limits
+    }
+
+    /// Counts the response against the limits, returns what should be done with it
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the address to which the response is sent
+    /// * `response_code` - the response code of the response
+    /// * `query_name` - the name which was queried
+    /// * `query_type` - the type which was queried
+    /// * `zone` - the zone of the name, NXDOMAIN responses for a zone share their limit so that
+    ///            random names don't evade it
+    pub fn check(
+        &self,
+        client: IpAddr,
+        response_code: ResponseCode,
+        query_name: &LowerName,
+        query_type: RecordType,
+        zone: Option<&LowerName>,
+    ) -> RateLimitAction {
+        self.check_at(
+            Instant::now(),
+            client,
+            response_code,
+            query_name,
+            query_type,
+            zone,
+        )
+    }
+
+    fn check_at(
+        &self,
+        now: Instant,
+        client: IpAddr,
+        response_code: ResponseCode,
+        query_name: &LowerName,
+        query_type: RecordType,
+        zone: Option<&LowerName>,
+    ) -> RateLimitAction {
+        let kind = ResponseKind::from(response_code);
+        let rate = match kind {
+            ResponseKind::Response => self.limits.responses_per_second,
+            ResponseKind::NxDomain => self.limits.nxdomains_per_second,
+            ResponseKind::Error => self.limits.errors_per_second,
+        };
+        if rate == 0 {
+            return RateLimitAction::Send;
+        }
+
+        let (name, record_type) = match kind {
+            ResponseKind::Response => (Some(query_name.clone()), Some(query_type)),
+            ResponseKind::NxDomain => (Some(zone.unwrap_or(query_name).clone()), None),
+            ResponseKind::Error => (None, None),
+        };
+        let key = BucketKey {
+            network: self.network(client),
+            kind,
+            name,
+            record_type,
+        };
+
+        let rate = f64::from(rate);
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        if buckets.len() >= self.limits.max_entries && !buckets.contains_key(&key) {
+            self.forget_recovered(&mut buckets, now);
+            if buckets.len() >= self.limits.max_entries {
+                Self::forget_oldest(&mut buckets);
+            }
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            balance: rate,
+            updated: now,
+            limited: 0,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        // a flood is forgotten within the window once it stops
+        let floor = -rate * f64::from(self.limits.window.max(1));
+        bucket.balance = (bucket.balance + elapsed * rate).min(rate) - 1.0;
+        bucket.balance = bucket.balance.max(floor);
+        bucket.updated = now;
+
+        if bucket.balance >= 0.0 {
+            bucket.limited = 0;
+            return RateLimitAction::Send;
+        }
+
+        bucket.limited = bucket.limited.wrapping_add(1);
+        if self.limits.slip > 0 && bucket.limited % self.limits.slip == 0 {
+            RateLimitAction::Slip
+        } else {
+            RateLimitAction::Drop
+        }
+    }
+
+    /// Removes the buckets which would have a full balance again
+    fn forget_recovered(&self, buckets: &mut HashMap<BucketKey, Bucket>, now: Instant) {
+        let window = f64::from(self.limits.window.max(1));
+        buckets.retain(|_, bucket| {
+            now.saturating_duration_since(bucket.updated).as_secs_f64() < window
+        });
+    }
+
+    /// Removes the least recently updated tenth of the buckets, during a flood of distinct responses
+    ///
+    /// The limited buckets of an ongoing flood are updated by it, and are kept. Removing a batch
+    ///  spares scanning all the buckets for each new one.
+    fn forget_oldest(buckets: &mut HashMap<BucketKey, Bucket>) {
+        let mut updated = buckets
+            .values()
+            .map(|bucket| bucket.updated)
+            .collect::<Vec<_>>();
+        if updated.is_empty() {
+            return;
+        }
+
+        let forgotten = (updated.len() / 10).max(1);
+        let (older, newest_forgotten, _) = updated.select_nth_unstable(forgotten - 1);
+        let newest_forgotten = *newest_forgotten;
+        // buckets updated at the same instant as the newest forgotten one are only partly forgotten
+        let mut ties = forgotten - older.iter().filter(|u| **u < newest_forgotten).count();
+        buckets.retain(|_, bucket| {
+            if bucket.updated == newest_forgotten && ties > 0 {
+                ties -= 1;
+                return false;
+            }
+            bucket.updated > newest_forgotten
+        });
+    }
+
+    fn network(&self, client: IpAddr) -> IpNet {
+        let prefix_len = match client {
+            IpAddr::V4(_) => self.limits.ipv4_prefix_len.min(32),
+            IpAddr::V6(_) => self.limits.ipv6_prefix_len.min(128),
+        };
+
+        IpNet::new(client, prefix_len)
+            .expect("prefix length is in range")
+            .trunc()
+    }
+}
+
+/// Applies a `ResponseRateLimiter` to the response to a request
+#[derive(Clone)]
+pub struct RateLimitedResponseHandle<R: ResponseHandler> {
+    inner: R,
+    limiter: Option<ResponseRateLimiter>,
+    client: IpAddr,
+    query_name: LowerName,
+    query_type: RecordType,
+    zone: Option<LowerName>,
+}
+
+impl<R: ResponseHandler> RateLimitedResponseHandle<R> {
+    /// Wraps the handle, responses are limited if there is a limiter
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - the handle which sends the responses within the limit
+    /// * `limiter` - the limits of the responses, none to send all responses
+    /// * `client` - the address of the client
+    /// * `query_name` - the name which was queried
+    /// * `query_type` - the type which was queried
+    /// * `zone` - the zone of the name, if any is served
+    pub fn new(
+        inner: R,
+        limiter: Option<ResponseRateLimiter>,
+        client: IpAddr,
+        query_name: LowerName,
+        query_type: RecordType,
+        zone: Option<LowerName>,
+    ) -> Self {
+        Self {
+            inner,
+            limiter,
+            client,
+            query_name,
+            query_type,
+            zone,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: ResponseHandler> ResponseHandler for RateLimitedResponseHandle<R> {
+    async fn send_response<'a>(
+        &mut self,
+        response: MessageResponse<
+            '_,
+            'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+        >,
+    ) -> io::Result<ResponseInfo> {
+        let action = self
+            .limiter
+            .as_ref()
+            .map_or(RateLimitAction::Send, |limiter| {
+                limiter.check(
+                    self.client,
+                    response.header().response_code(),
+                    &self.query_name,
+                    self.query_type,
+                    self.zone.as_ref(),
+                )
+            });
+
+        match action {
+            RateLimitAction::Send => self.inner.send_response(response).await,
+            RateLimitAction::Slip => {
+                debug!(
+                    "rate limited, truncating response: {} to: {}",
+                    response.header().id(),
+                    self.client
+                );
+                self.inner.send_response(response.into_truncated()).await
+            }
+            RateLimitAction::Drop => {
+                debug!(
+                    "rate limited, dropping response: {} to: {}",
+                    response.header().id(),
+                    self.client
+                );
+                Ok(ResponseInfo::from(*response.header()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::client::rr::Name;
+
+    fn name(name: &str) -> LowerName {
+        LowerName::new(&Name::from_str(name).unwrap())
+    }
+
+    fn limiter(slip: u32) -> ResponseRateLimiter {
+        ResponseRateLimiter::new(RateLimits {
+            responses_per_second: 2,
+            nxdomains_per_second: 1,
+            window: 2,
+            slip,
+            ..RateLimits::default()
+        })
+    }
+
+    #[test]
+    fn test_limit_and_recover() {
+        let limiter = limiter(2);
+        let now = Instant::now();
+        let client = IpAddr::from([192, 0, 2, 1]);
+        let check = |now, client| {
+            limiter.check_at(
+                now,
+                client,
+                ResponseCode::NoError,
+                &name("www.example.com."),
+                RecordType::A,
+                None,
+            )
+        };
+
+        assert_eq!(check(now, client), RateLimitAction::Send);
+        assert_eq!(check(now, client), RateLimitAction::Send);
+        assert_eq!(check(now, client), RateLimitAction::Drop);
+        assert_eq!(check(now, client), RateLimitAction::Slip);
+        assert_eq!(check(now, client), RateLimitAction::Drop);
+
+        // the same network shares the limit, others don't
+        assert_eq!(
+            check(now, IpAddr::from([192, 0, 2, 200])),
+            RateLimitAction::Slip
+        );
+        assert_eq!(
+            check(now, IpAddr::from([192, 0, 3, 1])),
+            RateLimitAction::Send
+        );
+
+        // the balance recovers at the rate
+        assert_eq!(
+            check(now + Duration::from_secs(10), client),
+            RateLimitAction::Send
+        );
+    }
+
+    #[test]
+    fn test_nxdomain_by_zone() {
+        let limiter = limiter(0);
+        let now = Instant::now();
+        let client = IpAddr::from([192, 0, 2, 1]);
+        let zone = name("example.com.");
+        let check = |query_name| {
+            limiter.check_at(
+                now,
+                client,
+                ResponseCode::NXDomain,
+                &name(query_name),
+                RecordType::A,
+                Some(&zone),
+            )
+        };
+
+        assert_eq!(check("a.example.com."), RateLimitAction::Send);
+        assert_eq!(check("b.example.com."), RateLimitAction::Drop);
+
+        // answers have their own limits
+        assert_eq!(
+            limiter.check_at(
+                now,
+                client,
+                ResponseCode::NoError,
+                &name("www.example.com."),
+                RecordType::A,
+                Some(&zone),
+            ),
+            RateLimitAction::Send
+        );
+    }
+
+    #[test]
+    fn test_forget_recovered() {
+        let limiter = ResponseRateLimiter::new(RateLimits {
+            max_entries: 2,
+            ..RateLimits::default()
+        });
+        let now = Instant::now();
+        for i in 0..4u8 {
+            limiter.check_at(
+                now + Duration::from_secs(u64::from(i) * 60),
+                IpAddr::from([192, 0, i, 1]),
+                ResponseCode::NoError,
+                &name("www.example.com."),
+                RecordType::A,
+                None,
+            );
+        }
+
+        assert!(limiter.buckets.lock().unwrap().len() <= 2);
+    }
+
+    #[test]
+    fn test_max_entries_under_flood() {
+        let limiter = ResponseRateLimiter::new(RateLimits {
+            responses_per_second: 2,
+            window: 2,
+            slip: 0,
+            max_entries: 10,
+            ..RateLimits::default()
+        });
+        let now = Instant::now();
+        let victim = IpAddr::from([192, 0, 2, 1]);
+        let check = |now, client| {
+            limiter.check_at(
+                now,
+                client,
+                ResponseCode::NoError,
+                &name("www.example.com."),
+                RecordType::A,
+                None,
+            )
+        };
+
+        assert_eq!(check(now, victim), RateLimitAction::Send);
+        assert_eq!(check(now, victim), RateLimitAction::Send);
+
+        // none of the limits recover, the least recently updated ones are forgotten
+        for i in 0..1_000_u16 {
+            let now = now + Duration::from_millis(u64::from(i));
+            let [high, low] = i.to_be_bytes();
+            check(now, IpAddr::from([10, high, low, 1]));
+            assert_eq!(check(now, victim), RateLimitAction::Drop);
+            assert!(limiter.buckets.lock().unwrap().len() <= 10);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A request handler which may be replaced while the server is running
+
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+
+use crate::server::{Request, RequestHandler, ResponseHandler, ResponseInfo};
+
+/// Handles requests with a handler which can be replaced at any time, e.g. to reload the `Catalog`
+///  after the configuration changed
+///
+/// Each request is handled by the handler which was current when it arrived, so requests in
+///  flight are completed by the old handler while new requests go to the new one. The listeners,
+///  and so the open TCP and TLS connections, are not affected by a replacement.
+pub struct ReloadableHandler<T: RequestHandler> {
+    current: Arc<RwLock<Arc<T>>>,
+}
+
+impl<T: RequestHandler> ReloadableHandler<T> {
+    /// Creates a new handler, which handles requests with `handler` until it is replaced
+    pub fn new(handler: T) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(handler))),
+        }
+    }
+
+    /// Returns a handle to replace the handler, which can be kept after the `ReloadableHandler`
+    ///  is moved into the `ServerFuture`
+    pub fn reload_handle(&self) -> ReloadHandle<T> {
+        ReloadHandle {
+            current: Arc::clone(&self.current),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: RequestHandler> RequestHandler for ReloadableHandler<T> {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        // the lock is not held while the request is handled, so a reload never waits for requests
+        let handler = Arc::clone(&*self.current.read().await);
+        handler.handle_request(request, response_handle).await
+    }
+
+    /// Shuts the current handler down, the replaced ones are not used anymore
+    async fn shutdown(&self) {
+        let handler = Arc::clone(&*self.current.read().await);
+        handler.shutdown().await;
+    }
+}
+
+/// A handle to replace the handler of a `ReloadableHandler`
+pub struct ReloadHandle<T: RequestHandler> {
+    current: Arc<RwLock<Arc<T>>>,
+}
+
+impl<T: RequestHandler> ReloadHandle<T> {
+    /// The handler of new requests
+    pub async fn current(&self) -> Arc<T> {
+        Arc::clone(&*self.current.read().await)
+    }
+
+    /// Replaces the handler, returning the previous one
+    ///
+    /// New requests are handled by `handler`, the previous handler is dropped once the requests
+    ///  it is handling are complete and the returned `Arc` is dropped.
+    pub async fn replace(&self, handler: T) -> Arc<T> {
+        std::mem::replace(&mut *self.current.write().await, Arc::new(handler))
+    }
+}
+
+impl<T: RequestHandler> Clone for ReloadHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: Arc::clone(&self.current),
+        }
+    }
+}
@@ -148,6 +148,10 @@ pub trait RequestHandler: Send + Sync + Unpin + 'static {
         request: &Request,
         response_handle: R,
     ) -> ResponseInfo;
+
+    /// Called once the server has shut down gracefully, after the requests in flight were
+    ///  answered, e.g. to write the journaled updates of the zones to their zone files
+    async fn shutdown(&self) {}
 }
 
 #[cfg(test)]
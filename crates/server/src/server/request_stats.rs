@@ -0,0 +1,187 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Counters of the requests handled by the server
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client::op::{OpCode, ResponseCode},
+    server::Protocol,
+};
+
+const PROTOCOLS: [Protocol; 6] = [
+    Protocol::Udp,
+    Protocol::Tcp,
+    Protocol::Tls,
+    Protocol::Dtls,
+    Protocol::Https,
+    Protocol::Quic,
+];
+
+const OP_CODES: [OpCode; 3] = [OpCode::Query, OpCode::Update, OpCode::Notify];
+
+const RESPONSE_CODES: [ResponseCode; 6] = [
+    ResponseCode::NoError,
+    ResponseCode::FormErr,
+    ResponseCode::ServFail,
+    ResponseCode::NXDomain,
+    ResponseCode::NotImp,
+    ResponseCode::Refused,
+];
+
+/// Counts the requests handled by the server, by transport protocol, op code and response code
+///
+/// The counters are shared by all catalogs of the server, see `Catalog::set_request_stats`, so
+///  they are kept when the catalog is reloaded.
+pub struct RequestStats {
+    started: Instant,
+    protocols: [AtomicU64; PROTOCOLS.len()],
+    // the last counter is for all other op codes
+    op_codes: [AtomicU64; OP_CODES.len() + 1],
+    // the last counter is for all other response codes
+    response_codes: [AtomicU64; RESPONSE_CODES.len() + 1],
+}
+
+impl RequestStats {
+    /// Constructs stats with all counters at zero
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            protocols: Default::default(),
+            op_codes: Default::default(),
+            response_codes: Default::default(),
+        }
+    }
+
+    /// Counts a request, and the response to it
+    pub fn record(&self, protocol: Protocol, op_code: OpCode, response_code: ResponseCode) {
+        if let Some(i) = PROTOCOLS.iter().position(|p| *p == protocol) {
+            self.protocols[i].fetch_add(1, Ordering::Relaxed);
+        }
+
+        let i = OP_CODES
+            .iter()
+            .position(|o| *o == op_code)
+            .unwrap_or(OP_CODES.len());
+        self.op_codes[i].fetch_add(1, Ordering::Relaxed);
+
+        let i = RESPONSE_CODES
+            .iter()
+            .position(|r| *r == response_code)
+            .unwrap_or(RESPONSE_CODES.len());
+        self.response_codes[i].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The time since the stats were created
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The number of requests handled
+    pub fn requests(&self) -> u64 {
+        self.protocols
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// The number of requests received over the protocol
+    pub fn protocol_requests(&self, protocol: Protocol) -> u64 {
+        PROTOCOLS
+            .iter()
+            .position(|p| *p == protocol)
+            .map_or(0, |i| self.protocols[i].load(Ordering::Relaxed))
+    }
+
+    /// The number of requests with the op code
+    pub fn op_code_requests(&self, op_code: OpCode) -> u64 {
+        OP_CODES
+            .iter()
+            .position(|o| *o == op_code)
+            .map_or(0, |i| self.op_codes[i].load(Ordering::Relaxed))
+    }
+
+    /// The number of responses with the response code
+    pub fn response_code_responses(&self, response_code: ResponseCode) -> u64 {
+        RESPONSE_CODES
+            .iter()
+            .position(|r| *r == response_code)
+            .map_or(0, |i| self.response_codes[i].load(Ordering::Relaxed))
+    }
+}
+
+impl Default for RequestStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RequestStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "uptime: {}s", self.uptime().as_secs())?;
+        writeln!(f, "requests: {}", self.requests())?;
+
+        for (protocol, count) in PROTOCOLS.iter().zip(&self.protocols) {
+            writeln!(
+                f,
+                "protocol {}: {}",
+                protocol,
+                count.load(Ordering::Relaxed)
+            )?;
+        }
+
+        for (op_code, count) in OP_CODES.iter().zip(&self.op_codes) {
+            writeln!(f, "opcode {:?}: {}", op_code, count.load(Ordering::Relaxed))?;
+        }
+        let other = self.op_codes[OP_CODES.len()].load(Ordering::Relaxed);
+        writeln!(f, "opcode other: {}", other)?;
+
+        for (response_code, count) in RESPONSE_CODES.iter().zip(&self.response_codes) {
+            writeln!(
+                f,
+                "rcode {}: {}",
+                response_code,
+                count.load(Ordering::Relaxed)
+            )?;
+        }
+        let other = self.response_codes[RESPONSE_CODES.len()].load(Ordering::Relaxed);
+        write!(f, "rcode other: {}", other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let stats = RequestStats::new();
+        stats.record(Protocol::Udp, OpCode::Query, ResponseCode::NoError);
+        stats.record(Protocol::Udp, OpCode::Query, ResponseCode::NXDomain);
+        stats.record(Protocol::Tcp, OpCode::Update, ResponseCode::Refused);
+        stats.record(Protocol::Tcp, OpCode::Status, ResponseCode::BADVERS);
+
+        assert_eq!(stats.requests(), 4);
+        assert_eq!(stats.protocol_requests(Protocol::Udp), 2);
+        assert_eq!(stats.protocol_requests(Protocol::Tcp), 2);
+        assert_eq!(stats.protocol_requests(Protocol::Quic), 0);
+        assert_eq!(stats.op_code_requests(OpCode::Query), 2);
+        assert_eq!(stats.op_code_requests(OpCode::Update), 1);
+        assert_eq!(stats.response_code_responses(ResponseCode::NXDomain), 1);
+        assert_eq!(stats.response_code_responses(ResponseCode::BADVERS), 0);
+
+        let dump = stats.to_string();
+        assert!(dump.contains("requests: 4\n"));
+        assert!(dump.contains("protocol UDP: 2\n"));
+        assert!(dump.ends_with("rcode other: 1"));
+    }
+}
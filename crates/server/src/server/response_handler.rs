@@ -13,6 +13,7 @@ use trust_dns_proto::rr::Record;
 use crate::{
     authority::MessageResponse,
     client::serialize::binary::BinEncoder,
+    dnstap::ClientTap,
     proto::{xfer::SerialMessage, BufDnsStreamHandle, DnsStreamHandle},
     server::ResponseInfo,
 };
@@ -45,12 +46,23 @@ pub trait ResponseHandler: Clone + Send + Sync + Unpin + 'static {
 pub struct ResponseHandle {
     dst: SocketAddr,
     stream_handle: BufDnsStreamHandle,
+    tap: Option<ClientTap>,
 }
 
 impl ResponseHandle {
     /// Returns a new `ResponseHandle` for sending a response message
     pub fn new(dst: SocketAddr, stream_handle: BufDnsStreamHandle) -> Self {
-        Self { dst, stream_handle }
+        Self {
+            dst,
+            stream_handle,
+            tap: None,
+        }
+    }
+
+    /// Logs the response with dnstap
+    pub(crate) fn with_client_tap(mut self, tap: Option<ClientTap>) -> Self {
+        self.tap = tap;
+        self
     }
 }
 
@@ -88,6 +100,10 @@ impl ResponseHandler for ResponseHandle {
             )
         })?;
 
+        if let Some(ref tap) = self.tap {
+            tap.response(&buffer);
+        }
+
         self.stream_handle
             .send(SerialMessage::new(buffer, self.dst))
             .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))?;
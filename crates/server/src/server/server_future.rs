@@ -4,36 +4,55 @@
 // http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
 use std::{
+    convert::TryFrom,
+    future::Future,
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
-    sync::Arc,
+    sync::{Arc, Mutex, Weak},
     time::Duration,
 };
 
-use futures_util::StreamExt;
-#[cfg(feature = "dns-over-rustls")]
-use rustls::{Certificate, PrivateKey};
-use tokio::{net, task::JoinSet};
-use tracing::{debug, info, warn};
+use futures_util::{
+    future::{self, Either},
+    pin_mut, FutureExt, Stream, StreamExt,
+};
+#[cfg(feature = "dns-over-quic")]
+use tokio::sync::Semaphore;
+use tokio::{net, sync::watch, task::JoinSet};
+use tracing::{debug, field, info, info_span, warn, Instrument, Span};
 use trust_dns_proto::rr::Record;
 
 #[cfg(all(feature = "dns-over-openssl", not(feature = "dns-over-rustls")))]
 use crate::proto::openssl::tls_server::*;
+#[cfg(feature = "dns-over-rustls")]
+use crate::server::TlsCertificate;
 use crate::{
     authority::{MessageRequest, MessageResponseBuilder},
-    client::op::LowerQuery,
+    client::{
+        op::LowerQuery,
+        rr::{LowerName, RecordType},
+    },
+    dnstap::{ClientTap, Dnstap},
     proto::{
         error::ProtoError,
         iocompat::AsyncIoTokioAsStd,
         op::{Edns, Header, Query, ResponseCode},
+        rr::rdata::opt::{EdnsCode, EdnsOption},
         serialize::binary::{BinDecodable, BinDecoder},
         tcp::TcpStream,
         udp::UdpStream,
         xfer::SerialMessage,
         BufDnsStreamHandle,
     },
-    server::{Protocol, Request, RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream},
+    server::{
+        connection_limits::{ConnectionLimits, ConnectionTracker},
+        cookies::{CookieCheck, CookieConfig, ServerCookies},
+        proxy_protocol::ProxyProtocol,
+        ListenFds, Protocol, Request, RequestHandler, ResponseHandle, ResponseHandler, TimeoutStream,
+    },
 };
 
 // TODO, would be nice to have a Slab for buffers here...
@@ -41,20 +60,123 @@ use crate::{
 pub struct ServerFuture<T: RequestHandler> {
     handler: Arc<T>,
     join_set: JoinSet<Result<(), ProtoError>>,
+    dnstap: Option<Dnstap>,
+    connections: ConnectionTracker,
+    minimal_responses: bool,
+    max_udp_payload: u16,
+    cookies: Option<ServerCookies>,
+    proxy_protocol: Arc<ProxyProtocol>,
+    shutdown_timeout: Option<Duration>,
+    shutdown: Arc<watch::Sender<bool>>,
+    shutting_down: watch::Receiver<bool>,
+    /// Duplicates of the sockets of the listeners, which are kept open for `hand_off`
+    listeners: Arc<Mutex<ListenFds>>,
 }
 
 impl<T: RequestHandler> ServerFuture<T> {
     /// Creates a new ServerFuture with the specified Handler.
     pub fn new(handler: T) -> Self {
+        let (shutdown, shutting_down) = watch::channel(false);
+
         Self {
             handler: Arc::new(handler),
             join_set: JoinSet::new(),
+            dnstap: None,
+            connections: ConnectionTracker::new(ConnectionLimits::default()),
+            minimal_responses: false,
+            max_udp_payload: DEFAULT_MAX_UDP_PAYLOAD,
+            cookies: None,
+            proxy_protocol: Arc::default(),
+            shutdown_timeout: None,
+            shutdown: Arc::new(shutdown),
+            shutting_down,
+            listeners: Arc::default(),
         }
     }
 
+    /// Logs the queries of the clients and the responses to them with dnstap
+    ///
+    /// This applies to the sockets and listeners which are registered afterwards.
+    pub fn set_dnstap(&mut self, dnstap: Option<Dnstap>) {
+        self.dnstap = dnstap;
+    }
+
+    /// Limits the TCP, TLS, HTTPS and QUIC connections which are open at once
+    ///
+    /// This applies to the listeners which are registered afterwards, which share the limits.
+    ///  Connections over the limits are closed as soon as they are accepted.
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.connections = ConnectionTracker::new(limits);
+    }
+
+    /// Omits the records of the authority and additional sections of the responses which are not
+    ///  required, see `MessageResponse::set_minimal`
+    ///
+    /// This applies to the sockets and listeners which are registered afterwards.
+    pub fn set_minimal_responses(&mut self, minimal_responses: bool) {
+        self.minimal_responses = minimal_responses;
+    }
+
+    /// Limits the size of the responses over UDP, 1232 by default
+    ///
+    /// Responses are limited to the smaller of this and the payload size advertised by the client
+    ///  with EDNS, or 512 without it. Responses which do not fit are truncated at the boundaries of
+    ///  their RRsets. This applies to the sockets which are registered afterwards.
+    pub fn set_max_udp_payload(&mut self, max_udp_payload: u16) {
+        self.max_udp_payload = max_udp_payload;
+    }
+
+    /// Answers the DNS Cookies of the requests, and requires valid cookies of the clients over
+    ///  the rate of the configuration, see `CookieConfig`
+    ///
+    /// This applies to the sockets and listeners which are registered afterwards, which share the
+    ///  secret of the server cookies.
+    pub fn set_cookies(&mut self, config: Option<CookieConfig>) {
+        self.cookies = config.map(ServerCookies::new);
+    }
+
+    /// Reads the client address of the TCP, TLS and HTTPS connections from the proxies from the
+    ///  PROXY protocol header they start with, see `ProxyProtocol`
+    ///
+    /// This applies to the listeners which are registered afterwards. The connection limits apply
+    ///  to the address of the proxy.
+    pub fn set_proxy_protocol(&mut self, proxy_protocol: ProxyProtocol) {
+        self.proxy_protocol = Arc::new(proxy_protocol);
+    }
+
+    /// Limits the time for which the requests in flight are answered once the server is shutting
+    ///  down, unlimited by default
+    ///
+    /// Connections with requests which are not answered in time are closed.
+    pub fn set_shutdown_timeout(&mut self, shutdown_timeout: Option<Duration>) {
+        self.shutdown_timeout = shutdown_timeout;
+    }
+
+    /// Returns a handle with which to shut the server down gracefully, see `ShutdownHandle`
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            shutdown: Arc::clone(&self.shutdown),
+            listeners: Arc::downgrade(&self.listeners),
+        }
+    }
+
+    /// Keeps a duplicate of the socket of a listener, for `ShutdownHandle::hand_off`
+    #[cfg(unix)]
+    fn keep_for_hand_off<S: AsRawFd>(&self, name: &str, socket: &S) {
+        let mut listeners = self.listeners.lock().expect("listeners poisoned");
+        if let Err(e) = listeners.push_duplicate(name, socket) {
+            warn!("the {} socket can not be handed off: {}", name, e);
+        }
+    }
+
+    /// Sockets are only handed off on unix
+    #[cfg(not(unix))]
+    fn keep_for_hand_off<S>(&self, _name: &str, _socket: &S) {}
+
     /// Register a UDP socket. Should be bound before calling this function.
     pub fn register_socket(&mut self, socket: net::UdpSocket) {
         debug!("registering udp: {:?}", socket);
+        self.keep_for_hand_off(ListenFds::UDP, &socket);
 
         // create the new UdpStream, the IP address isn't relevant, and ideally goes essentially no where.
         //   the address used is acquired from the inbound queries
@@ -62,12 +184,29 @@ impl<T: RequestHandler> ServerFuture<T> {
             UdpStream::with_bound(socket, ([127, 255, 255, 254], 0).into());
         //let request_stream = RequestStream::new(buf_stream, stream_handle);
         let handler = self.handler.clone();
+        let dnstap = self.dnstap.clone();
+        let options = ResponseOptions {
+            idle_timeout: None,
+            minimal: self.minimal_responses,
+            max_udp_payload: Some(self.max_udp_payload),
+            cookies: self.cookies.clone(),
+        };
+        let shutting_down = self.shutting_down.clone();
 
         // this spawns a ForEach future which handles all the requests into a Handler.
         self.join_set.spawn({
             async move {
                 let mut inner_join_set = JoinSet::new();
-                while let Some(message) = buf_stream.next().await {
+                loop {
+                    let message =
+                        match unless_shutting_down(buf_stream.next(), &shutting_down).await {
+                            Some(Some(message)) => message,
+                            Some(None) => break,
+                            None => {
+                                drain(&mut inner_join_set, &mut buf_stream).await;
+                                return Ok(());
+                            }
+                        };
                     let message = match message {
                         Err(e) => {
                             warn!("error receiving message on udp_socket: {}", e);
@@ -90,11 +229,20 @@ impl<T: RequestHandler> ServerFuture<T> {
                     }
 
                     let handler = handler.clone();
+                    let dnstap = dnstap.clone();
+                    let options = options.clone();
                     let stream_handle = stream_handle.with_remote_addr(src_addr);
 
                     inner_join_set.spawn(async move {
-                        self::handle_raw_request(message, Protocol::Udp, handler, stream_handle)
-                            .await;
+                        self::handle_raw_request(
+                            message,
+                            Protocol::Udp,
+                            handler,
+                            stream_handle,
+                            dnstap.as_ref(),
+                            options,
+                        )
+                        .await;
                     });
                 }
 
@@ -124,16 +272,31 @@ impl<T: RequestHandler> ServerFuture<T> {
     ///               only, this would require some type of whitelisting.
     pub fn register_listener(&mut self, listener: net::TcpListener, timeout: Duration) {
         debug!("register tcp: {:?}", listener);
+        self.keep_for_hand_off(ListenFds::TCP, &listener);
 
         let handler = self.handler.clone();
+        let dnstap = self.dnstap.clone();
+        let options = ResponseOptions {
+            idle_timeout: Some(timeout),
+            minimal: self.minimal_responses,
+            max_udp_payload: None,
+            cookies: self.cookies.clone(),
+        };
+        let connections = self.connections.clone();
+        let proxy_protocol = Arc::clone(&self.proxy_protocol);
+        let shutting_down = self.shutting_down.clone();
 
         // for each incoming request...
         self.join_set.spawn({
             async move {
                 let mut inner_join_set = JoinSet::new();
                 loop {
-                    let tcp_stream = listener.accept().await;
-                    let (tcp_stream, src_addr) = match tcp_stream {
+                    let tcp_stream =
+                        match unless_shutting_down(listener.accept(), &shutting_down).await {
+                            Some(tcp_stream) => tcp_stream,
+                            None => break,
+                        };
+                    let (mut tcp_stream, src_addr) = match tcp_stream {
                         Ok((t, s)) => (t, s),
                         Err(e) => {
                             debug!("error receiving TCP tcp_stream error: {}", e);
@@ -152,9 +315,29 @@ impl<T: RequestHandler> ServerFuture<T> {
                     }
 
                     let handler = handler.clone();
+                    let dnstap = dnstap.clone();
+                    let options = options.clone();
+                    let proxy_protocol = Arc::clone(&proxy_protocol);
+                    let connections = connections.clone();
+                    let shutting_down = shutting_down.clone();
 
                     // and spawn to the io_loop
                     inner_join_set.spawn(async move {
+                        let src_addr =
+                            match client_addr(&proxy_protocol, &mut tcp_stream, src_addr, timeout)
+                                .await
+                            {
+                                Some(src_addr) => src_addr,
+                                None => return,
+                            };
+                        // the connections of proxied clients are counted for the client
+                        let _connection = match connections.open(src_addr.ip()) {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                warn!("{e}, closing TCP connection from: {src_addr}");
+                                return;
+                            }
+                        };
                         debug!("accepted request from: {}", src_addr);
                         // take the created stream...
                         let (buf_stream, stream_handle) =
@@ -162,7 +345,11 @@ impl<T: RequestHandler> ServerFuture<T> {
                         let mut timeout_stream = TimeoutStream::new(buf_stream, timeout);
                         //let request_stream = RequestStream::new(timeout_stream, stream_handle);
 
-                        while let Some(message) = timeout_stream.next().await {
+                        // once shutting down, the connection is closed after the last response
+                        //  is sent, which happens while polling for the next request
+                        while let Some(Some(message)) =
+                            unless_shutting_down(timeout_stream.next(), &shutting_down).await
+                        {
                             let message = match message {
                                 Ok(message) => message,
                                 Err(e) => {
@@ -181,11 +368,17 @@ impl<T: RequestHandler> ServerFuture<T> {
                                 Protocol::Tcp,
                                 handler.clone(),
                                 stream_handle.clone(),
+                                dnstap.as_ref(),
+                                options.clone(),
                             )
                             .await;
                         }
                     });
                 }
+
+                // the connections are closed once their in-flight requests are answered
+                while inner_join_set.join_next().await.is_some() {}
+                Ok(())
             }
         });
     }
@@ -243,17 +436,33 @@ impl<T: RequestHandler> ServerFuture<T> {
         let ((cert, chain), key) = certificate_and_key;
 
         let handler = self.handler.clone();
+        let dnstap = self.dnstap.clone();
+        let options = ResponseOptions {
+            idle_timeout: Some(timeout),
+            minimal: self.minimal_responses,
+            max_udp_payload: None,
+            cookies: self.cookies.clone(),
+        };
         debug!("registered tcp: {:?}", listener);
+        self.keep_for_hand_off(ListenFds::TLS, &listener);
 
         let tls_acceptor = Box::pin(tls_server::new_acceptor(cert, chain, key)?);
 
+        let connections = self.connections.clone();
+        let proxy_protocol = Arc::clone(&self.proxy_protocol);
+        let shutting_down = self.shutting_down.clone();
+
         // for each incoming request...
         self.join_set.spawn({
             async move {
                 let mut inner_join_set = JoinSet::new();
                 loop {
-                    let tcp_stream = listener.accept().await;
-                    let (tcp_stream, src_addr) = match tcp_stream {
+                    let tcp_stream =
+                        match unless_shutting_down(listener.accept(), &shutting_down).await {
+                            Some(tcp_stream) => tcp_stream,
+                            None => break,
+                        };
+                    let (mut tcp_stream, src_addr) = match tcp_stream {
                         Ok((t, s)) => (t, s),
                         Err(e) => {
                             debug!("error receiving TLS tcp_stream error: {}", e);
@@ -272,10 +481,30 @@ impl<T: RequestHandler> ServerFuture<T> {
                     }
 
                     let handler = handler.clone();
+                    let dnstap = dnstap.clone();
+                    let options = options.clone();
+                    let proxy_protocol = Arc::clone(&proxy_protocol);
+                    let connections = connections.clone();
                     let tls_acceptor = tls_acceptor.clone();
+                    let shutting_down = shutting_down.clone();
 
                     // kick out to a different task immediately, let them do the TLS handshake
                     inner_join_set.spawn(async move {
+                        let src_addr =
+                            match client_addr(&proxy_protocol, &mut tcp_stream, src_addr, timeout)
+                                .await
+                            {
+                                Some(src_addr) => src_addr,
+                                None => return,
+                            };
+                        // the connections of proxied clients are counted for the client
+                        let _connection = match connections.open(src_addr.ip()) {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                warn!("{e}, closing TLS connection from: {src_addr}");
+                                return;
+                            }
+                        };
                         debug!("starting TLS request from: {}", src_addr);
 
                         // perform the TLS
@@ -288,18 +517,24 @@ impl<T: RequestHandler> ServerFuture<T> {
                                 return ();
                             }
                         };
-                        match Pin::new(&mut tls_stream).accept().await {
-                            Ok(()) => {}
-                            Err(e) => {
+                        match with_timeout(Pin::new(&mut tls_stream).accept(), timeout).await {
+                            Some(Ok(())) => {}
+                            Some(Err(e)) => {
                                 debug!("tls handshake src: {} error: {}", src_addr, e);
                                 return ();
                             }
+                            None => {
+                                debug!("tls handshake src: {} timed out", src_addr);
+                                return ();
+                            }
                         };
                         debug!("accepted TLS request from: {}", src_addr);
                         let (buf_stream, stream_handle) =
                             TlsStream::from_stream(AsyncIoTokioAsStd(tls_stream), src_addr);
                         let mut timeout_stream = TimeoutStream::new(buf_stream, timeout);
-                        while let Some(message) = timeout_stream.next().await {
+                        while let Some(Some(message)) =
+                            unless_shutting_down(timeout_stream.next(), &shutting_down).await
+                        {
                             let message = match message {
                                 Ok(message) => message,
                                 Err(e) => {
@@ -318,11 +553,17 @@ impl<T: RequestHandler> ServerFuture<T> {
                                 Protocol::Tls,
                                 handler.clone(),
                                 stream_handle.clone(),
+                                dnstap.as_ref(),
+                                options.clone(),
                             )
                             .await;
                         }
                     });
                 }
+
+                // the connections are closed once their in-flight requests are answered
+                while inner_join_set.join_next().await.is_some() {}
+                Ok(())
             }
         });
 
@@ -372,38 +613,50 @@ impl<T: RequestHandler> ServerFuture<T> {
     ///               requests within this time period will be closed. In the future it should be
     ///               possible to create long-lived queries, but these should be from trusted sources
     ///               only, this would require some type of whitelisting.
-    /// * `pkcs12` - certificate used to announce to clients
+    /// * `certificate_and_key` - certificate and key used to announce to clients, either fixed or a
+    ///                           `ReloadableCertificate` which may be replaced while in use
     #[cfg(feature = "dns-over-rustls")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-rustls")))]
     pub fn register_tls_listener(
         &mut self,
         listener: net::TcpListener,
         timeout: Duration,
-        certificate_and_key: (Vec<Certificate>, PrivateKey),
+        certificate_and_key: impl Into<TlsCertificate>,
     ) -> io::Result<()> {
         use crate::proto::rustls::{tls_from_stream, tls_server};
         use tokio_rustls::TlsAcceptor;
 
         let handler = self.handler.clone();
+        let dnstap = self.dnstap.clone();
+        let options = ResponseOptions {
+            idle_timeout: Some(timeout),
+            minimal: self.minimal_responses,
+            max_udp_payload: None,
+            cookies: self.cookies.clone(),
+        };
 
         debug!("registered tcp: {:?}", listener);
+        self.keep_for_hand_off(ListenFds::TLS, &listener);
 
-        let tls_acceptor = tls_server::new_acceptor(certificate_and_key.0, certificate_and_key.1)
-            .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("error creating TLS acceptor: {}", e),
-            )
-        })?;
+        let resolver = certificate_and_key.into().into_resolver()?;
+        let tls_acceptor = tls_server::new_acceptor_with_cert_resolver(resolver);
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls_acceptor));
 
+        let connections = self.connections.clone();
+        let proxy_protocol = Arc::clone(&self.proxy_protocol);
+        let shutting_down = self.shutting_down.clone();
+
         // for each incoming request...
         self.join_set.spawn({
             async move {
                 let mut inner_join_set = JoinSet::new();
                 loop {
-                    let tcp_stream = listener.accept().await;
-                    let (tcp_stream, src_addr) = match tcp_stream {
+                    let tcp_stream =
+                        match unless_shutting_down(listener.accept(), &shutting_down).await {
+                            Some(tcp_stream) => tcp_stream,
+                            None => break,
+                        };
+                    let (mut tcp_stream, src_addr) = match tcp_stream {
                         Ok((t, s)) => (t, s),
                         Err(e) => {
                             debug!("error receiving TLS tcp_stream error: {}", e);
@@ -422,26 +675,53 @@ impl<T: RequestHandler> ServerFuture<T> {
                     }
 
                     let handler = handler.clone();
+                    let dnstap = dnstap.clone();
+                    let options = options.clone();
+                    let proxy_protocol = Arc::clone(&proxy_protocol);
+                    let connections = connections.clone();
                     let tls_acceptor = tls_acceptor.clone();
+                    let shutting_down = shutting_down.clone();
 
                     // kick out to a different task immediately, let them do the TLS handshake
                     inner_join_set.spawn(async move {
+                        let src_addr =
+                            match client_addr(&proxy_protocol, &mut tcp_stream, src_addr, timeout)
+                                .await
+                            {
+                                Some(src_addr) => src_addr,
+                                None => return,
+                            };
+                        // the connections of proxied clients are counted for the client
+                        let _connection = match connections.open(src_addr.ip()) {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                warn!("{e}, closing TLS connection from: {src_addr}");
+                                return;
+                            }
+                        };
                         debug!("starting TLS request from: {}", src_addr);
 
                         // perform the TLS
-                        let tls_stream = tls_acceptor.accept(tcp_stream).await;
+                        let tls_stream =
+                            with_timeout(tls_acceptor.accept(tcp_stream), timeout).await;
 
                         let tls_stream = match tls_stream {
-                            Ok(tls_stream) => AsyncIoTokioAsStd(tls_stream),
-                            Err(e) => {
+                            Some(Ok(tls_stream)) => AsyncIoTokioAsStd(tls_stream),
+                            Some(Err(e)) => {
                                 debug!("tls handshake src: {} error: {}", src_addr, e);
                                 return;
                             }
+                            None => {
+                                debug!("tls handshake src: {} timed out", src_addr);
+                                return;
+                            }
                         };
                         debug!("accepted TLS request from: {}", src_addr);
                         let (buf_stream, stream_handle) = tls_from_stream(tls_stream, src_addr);
                         let mut timeout_stream = TimeoutStream::new(buf_stream, timeout);
-                        while let Some(message) = timeout_stream.next().await {
+                        while let Some(Some(message)) =
+                            unless_shutting_down(timeout_stream.next(), &shutting_down).await
+                        {
                             let message = match message {
                                 Ok(message) => message,
                                 Err(e) => {
@@ -460,11 +740,17 @@ impl<T: RequestHandler> ServerFuture<T> {
                                 Protocol::Tls,
                                 handler.clone(),
                                 stream_handle.clone(),
+                                dnstap.as_ref(),
+                                options.clone(),
                             )
                             .await;
                         }
                     });
                 }
+
+                // the connections are closed once their in-flight requests are answered
+                while inner_join_set.join_next().await.is_some() {}
+                Ok(())
             }
         });
 
@@ -516,15 +802,15 @@ impl<T: RequestHandler> ServerFuture<T> {
     ///               requests within this time period will be closed. In the future it should be
     ///               possible to create long-lived queries, but these should be from trusted sources
     ///               only, this would require some type of whitelisting.
-    /// * `certificate_and_key` - certificate and key used to announce to clients
+    /// * `certificate_and_key` - certificate and key used to announce to clients, either fixed or a
+    ///                           `ReloadableCertificate` which may be replaced while in use
     #[cfg(feature = "dns-over-https-rustls")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-https-rustls")))]
     pub fn register_https_listener(
         &mut self,
         listener: net::TcpListener,
-        // TODO: need to set a timeout between requests.
-        _timeout: Duration,
-        certificate_and_key: (Vec<Certificate>, PrivateKey),
+        timeout: Duration,
+        certificate_and_key: impl Into<TlsCertificate>,
         dns_hostname: String,
     ) -> io::Result<()> {
         use tokio_rustls::TlsAcceptor;
@@ -534,15 +820,20 @@ impl<T: RequestHandler> ServerFuture<T> {
 
         let dns_hostname: Arc<str> = Arc::from(dns_hostname);
         let handler = self.handler.clone();
+        let dnstap = self.dnstap.clone();
+        let options = ResponseOptions {
+            minimal: self.minimal_responses,
+            cookies: self.cookies.clone(),
+            ..ResponseOptions::default()
+        };
+        let connections = self.connections.clone();
+        let proxy_protocol = Arc::clone(&self.proxy_protocol);
+        let shutting_down = self.shutting_down.clone();
         debug!("registered https: {:?}", listener);
+        self.keep_for_hand_off(ListenFds::HTTPS, &listener);
 
-        let tls_acceptor = tls_server::new_acceptor(certificate_and_key.0, certificate_and_key.1)
-            .map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("error creating TLS acceptor: {}", e),
-            )
-        })?;
+        let resolver = certificate_and_key.into().into_resolver()?;
+        let tls_acceptor = tls_server::new_acceptor_with_cert_resolver(resolver);
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls_acceptor));
 
         // for each incoming request...
@@ -552,8 +843,12 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let mut inner_join_set = JoinSet::new();
                 let dns_hostname = dns_hostname;
                 loop {
-                    let tcp_stream = listener.accept().await;
-                    let (tcp_stream, src_addr) = match tcp_stream {
+                    let tcp_stream =
+                        match unless_shutting_down(listener.accept(), &shutting_down).await {
+                            Some(tcp_stream) => tcp_stream,
+                            None => break,
+                        };
+                    let (mut tcp_stream, src_addr) = match tcp_stream {
                         Ok((t, s)) => (t, s),
                         Err(e) => {
                             debug!("error receiving HTTPS tcp_stream error: {}", e);
@@ -572,28 +867,66 @@ impl<T: RequestHandler> ServerFuture<T> {
                     }
 
                     let handler = handler.clone();
+                    let dnstap = dnstap.clone();
+                    let options = options.clone();
+                    let proxy_protocol = Arc::clone(&proxy_protocol);
+                    let connections = connections.clone();
                     let tls_acceptor = tls_acceptor.clone();
                     let dns_hostname = dns_hostname.clone();
+                    let shutting_down = shutting_down.clone();
 
                     inner_join_set.spawn(async move {
+                        let src_addr =
+                            match client_addr(&proxy_protocol, &mut tcp_stream, src_addr, timeout)
+                                .await
+                            {
+                                Some(src_addr) => src_addr,
+                                None => return,
+                            };
+                        // the connections of proxied clients are counted for the client
+                        let _connection = match connections.open(src_addr.ip()) {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                warn!("{e}, closing HTTPS connection from: {src_addr}");
+                                return;
+                            }
+                        };
                         debug!("starting HTTPS request from: {}", src_addr);
 
-                        // TODO: need to consider timeout of total connect...
                         // take the created stream...
-                        let tls_stream = tls_acceptor.accept(tcp_stream).await;
+                        let tls_stream =
+                            with_timeout(tls_acceptor.accept(tcp_stream), timeout).await;
 
                         let tls_stream = match tls_stream {
-                            Ok(tls_stream) => tls_stream,
-                            Err(e) => {
+                            Some(Ok(tls_stream)) => tls_stream,
+                            Some(Err(e)) => {
                                 debug!("https handshake src: {} error: {}", src_addr, e);
                                 return;
                             }
+                            None => {
+                                debug!("https handshake src: {} timed out", src_addr);
+                                return;
+                            }
                         };
                         debug!("accepted HTTPS request from: {}", src_addr);
 
-                        h2_handler(handler, tls_stream, src_addr, dns_hostname).await;
+                        h2_handler(
+                            handler,
+                            tls_stream,
+                            src_addr,
+                            dns_hostname,
+                            dnstap,
+                            timeout,
+                            options,
+                            shutting_down,
+                        )
+                        .await;
                     });
                 }
+
+                // the connections are closed once their in-flight requests are answered
+                while inner_join_set.join_next().await.is_some() {}
+                Ok(())
             }
         });
 
@@ -608,30 +941,85 @@ impl<T: RequestHandler> ServerFuture<T> {
     ///
     /// # Arguments
     /// * `listener` - a bound TCP (needs to be on a different port from standard TCP connections) socket
-    /// * `timeout` - timeout duration of incoming requests, any connection that does not send
+    /// * `timeout` - idle timeout of connections, any connection that does not send
     ///               requests within this time period will be closed. In the future it should be
     ///               possible to create long-lived queries, but these should be from trusted sources
     ///               only, this would require some type of whitelisting.
-    /// * `pkcs12` - certificate used to announce to clients
+    /// * `certificate_and_key` - certificate and key used to announce to clients, either fixed or a
+    ///                           `ReloadableCertificate` which may be replaced while in use
     #[cfg(feature = "dns-over-quic")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-quic")))]
     pub fn register_quic_listener(
         &mut self,
         socket: net::UdpSocket,
-        // TODO: need to set a timeout between requests.
-        _timeout: Duration,
-        certificate_and_key: (Vec<Certificate>, PrivateKey),
+        timeout: Duration,
+        certificate_and_key: impl Into<TlsCertificate>,
+        dns_hostname: String,
+    ) -> io::Result<()> {
+        self.register_quic(
+            socket,
+            timeout,
+            certificate_and_key.into(),
+            dns_hostname,
+            None,
+        )
+    }
+
+    /// Register a UdpSocket to the Server for supporting DoQ (dns-over-quic), with a limit on the
+    ///  connections which are open at once.
+    ///
+    /// See `register_quic_listener`, connections beyond `max_connections` are closed as soon as
+    ///  they are established.
+    #[cfg(feature = "dns-over-quic")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dns-over-quic")))]
+    pub fn register_quic_listener_with_limit(
+        &mut self,
+        socket: net::UdpSocket,
+        timeout: Duration,
+        certificate_and_key: impl Into<TlsCertificate>,
         dns_hostname: String,
+        max_connections: usize,
+    ) -> io::Result<()> {
+        self.register_quic(
+            socket,
+            timeout,
+            certificate_and_key.into(),
+            dns_hostname,
+            Some(max_connections),
+        )
+    }
+
+    #[cfg(feature = "dns-over-quic")]
+    fn register_quic(
+        &mut self,
+        socket: net::UdpSocket,
+        timeout: Duration,
+        certificate: TlsCertificate,
+        dns_hostname: String,
+        max_connections: Option<usize>,
     ) -> io::Result<()> {
         use crate::proto::quic::QuicServer;
         use crate::server::quic_handler::quic_handler;
 
         let dns_hostname: Arc<str> = Arc::from(dns_hostname);
         let handler = self.handler.clone();
+        let dnstap = self.dnstap.clone();
+        let options = ResponseOptions {
+            minimal: self.minimal_responses,
+            cookies: self.cookies.clone(),
+            ..ResponseOptions::default()
+        };
+        let listener_connections = max_connections.map(|max| Arc::new(Semaphore::new(max)));
+        let connections = self.connections.clone();
+        let shutting_down = self.shutting_down.clone();
 
         debug!("registered quic: {:?}", socket);
-        let mut server =
-            QuicServer::with_socket(socket, certificate_and_key.0, certificate_and_key.1)?;
+        self.keep_for_hand_off(ListenFds::QUIC, &socket);
+        let mut server = QuicServer::with_socket_cert_resolver_and_idle_timeout(
+            socket,
+            certificate.into_resolver()?,
+            timeout,
+        )?;
 
         // for each incoming request...
         let dns_hostname = dns_hostname;
@@ -640,7 +1028,11 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let mut inner_join_set = JoinSet::new();
                 let dns_hostname = dns_hostname;
                 loop {
-                    let (streams, src_addr) = match server.next().await {
+                    let next = match unless_shutting_down(server.next(), &shutting_down).await {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let (streams, src_addr) = match next {
                         Ok(Some(c)) => c,
                         Ok(None) => continue,
                         Err(e) => {
@@ -660,20 +1052,58 @@ impl<T: RequestHandler> ServerFuture<T> {
                         continue;
                     }
 
+                    // the connection is closed once its streams are dropped
+                    let permit = match listener_connections
+                        .clone()
+                        .map(Semaphore::try_acquire_owned)
+                    {
+                        Some(Err(_)) => {
+                            warn!("too many quic connections, closing connection from: {src_addr}");
+                            continue;
+                        }
+                        Some(Ok(permit)) => Some(permit),
+                        None => None,
+                    };
+                    let connection = match connections.open(src_addr.ip()) {
+                        Ok(connection) => connection,
+                        Err(e) => {
+                            warn!("{e}, closing quic connection from: {src_addr}");
+                            continue;
+                        }
+                    };
+
                     let handler = handler.clone();
+                    let dnstap = dnstap.clone();
+                    let options = options.clone();
                     let dns_hostname = dns_hostname.clone();
+                    let shutting_down = shutting_down.clone();
 
                     inner_join_set.spawn(async move {
+                        let _permit = permit;
+                        let _connection = connection;
                         debug!("starting quic stream request from: {src_addr}");
 
                         // TODO: need to consider timeout of total connect...
-                        let result = quic_handler(handler, streams, src_addr, dns_hostname).await;
+                        let result = quic_handler(
+                            handler,
+                            streams,
+                            src_addr,
+                            dns_hostname,
+                            dnstap,
+                            options,
+                            shutting_down,
+                        )
+                        .await;
 
                         if let Err(e) = result {
                             warn!("quic stream processing failed from {src_addr}: {e}")
                         }
                     });
                 }
+
+                // the connections are closed once their in-flight requests are answered
+                while inner_join_set.join_next().await.is_some() {}
+                Ok(())
             }
         });
 
@@ -681,28 +1111,212 @@ impl<T: RequestHandler> ServerFuture<T> {
     }
 
     /// This will run until a background task of the trust_dns_server ends.
+    ///
+    /// Once the server is shutting down, this runs until all of the sockets and listeners are
+    ///  done, at most for the shutdown timeout, and the handler is shut down, see
+    ///  `ShutdownHandle`.
     pub async fn block_until_done(mut self) -> Result<(), ProtoError> {
-        let result = self.join_set.join_next().await;
-
-        match result {
-            None => {
+        let next = self.join_set.join_next();
+        let result = match unless_shutting_down(next, &self.shutting_down).await {
+            Some(None) => {
                 tracing::warn!("block_until_done called with no pending tasks");
                 Ok(())
             }
-            Some(Ok(x)) => x,
-            Some(Err(e)) => Err(ProtoError::from(format!("Internal error in spawn: {}", e))),
+            Some(Some(Ok(x))) => x,
+            Some(Some(Err(e))) => {
+                Err(ProtoError::from(format!("Internal error in spawn: {}", e)))
+            }
+            None => Ok(()),
+        };
+
+        if *self.shutting_down.borrow() {
+            self.finish_shutdown().await;
         }
+
+        result
+    }
+
+    /// Waits for the sockets and listeners to answer the requests in flight, then shuts the
+    ///  handler down
+    async fn finish_shutdown(&mut self) {
+        let join_set = &mut self.join_set;
+        let done = async {
+            while let Some(other) = join_set.join_next().await {
+                match other {
+                    Ok(Err(e)) => warn!("error while shutting down: {}", e),
+                    Err(e) => warn!("internal error while shutting down: {}", e),
+                    Ok(Ok(())) => (),
+                }
+            }
+        };
+
+        let done = match self.shutdown_timeout {
+            Some(timeout) => with_timeout(done, timeout).await.is_some(),
+            None => {
+                done.await;
+                true
+            }
+        };
+        if !done {
+            warn!("shutdown timed out, closing the connections with requests in flight");
+            self.join_set.abort_all();
+            while self.join_set.join_next().await.is_some() {}
+        }
+
+        self.handler.shutdown().await;
+    }
+
+    /// Shuts the server down gracefully, and runs until it is done, see `ShutdownHandle`
+    pub async fn shutdown_gracefully(self) -> Result<(), ProtoError> {
+        self.shutdown_handle().shutdown();
+        self.block_until_done().await
     }
 }
 
+/// A handle with which to shut a `ServerFuture` down gracefully
+///
+/// Once shut down, the sockets and listeners no longer accept queries and connections, and the
+///  open connections are closed once their in-flight requests are answered, or once the shutdown
+///  timeout passed. The handler is then shut down, e.g. the `Catalog` writes the journaled updates
+///  of its zones to their zone files.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shutdown: Arc<watch::Sender<bool>>,
+    /// The sockets are closed once the server is done
+    listeners: Weak<Mutex<ListenFds>>,
+}
+
+impl ShutdownHandle {
+    /// Starts shutting the server down, `ServerFuture::block_until_done` returns once it is done
+    pub fn shutdown(&self) {
+        // the sockets are closed once the listeners are done
+        drop(self.take_listeners());
+
+        // the server keeps a receiver, if it is gone there is nothing left to shut down
+        let _ = self.shutdown.send(true);
+    }
+
+    /// Starts shutting the server down, and returns duplicates of its sockets and listeners, to
+    ///  pass them on to the program which replaces this process, see `ListenFds::pass_to`
+    ///
+    /// Queries and connections which are not accepted by this server anymore are queued by the
+    ///  sockets until the other process accepts them. Sockets are handed off only on unix.
+    pub fn hand_off(&self) -> ListenFds {
+        let listeners = self.take_listeners();
+        let _ = self.shutdown.send(true);
+        listeners
+    }
+
+    fn take_listeners(&self) -> ListenFds {
+        self.listeners
+            .upgrade()
+            .map(|listeners| std::mem::take(&mut *listeners.lock().expect("listeners poisoned")))
+            .unwrap_or_default()
+    }
+}
+
+/// Completes once the server is shutting down
+async fn shutting_down(mut shutting_down: watch::Receiver<bool>) {
+    while !*shutting_down.borrow() {
+        if shutting_down.changed().await.is_err() {
+            // the server is gone, and with it the tasks waiting on this
+            future::pending::<()>().await;
+        }
+    }
+}
+
+/// Waits for the future, unless the server starts shutting down first
+///
+/// The future is polled first, so that it makes progress even once shutting down, e.g. the
+///  pending responses of a connection are sent before it is closed.
+pub(crate) async fn unless_shutting_down<F: Future>(
+    future: F,
+    shutting_down: &watch::Receiver<bool>,
+) -> Option<F::Output> {
+    let shutdown = self::shutting_down(shutting_down.clone());
+    pin_mut!(future, shutdown);
+
+    match future::select(future, shutdown).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(..) => None,
+    }
+}
+
+/// Waits for the in-flight requests to be answered, polling the stream to send the responses
+async fn drain<S: Stream + Unpin>(requests: &mut JoinSet<()>, stream: &mut S) {
+    {
+        let answered = async { while requests.join_next().await.is_some() {} };
+        pin_mut!(answered);
+
+        // further requests are dropped
+        loop {
+            match future::select(answered.as_mut(), stream.next()).await {
+                Either::Left(..) => break,
+                Either::Right((Some(_), _)) => continue,
+                Either::Right((None, _)) => {
+                    answered.await;
+                    break;
+                }
+            }
+        }
+    }
+
+    // send the last of the responses
+    let _ = stream.next().now_or_never();
+}
+
+/// The address of the client of the connection, read from the PROXY protocol header of
+///  connections from proxies, `None` if the connection is to be closed
+async fn client_addr(
+    proxy_protocol: &ProxyProtocol,
+    tcp_stream: &mut net::TcpStream,
+    src_addr: SocketAddr,
+    timeout: Duration,
+) -> Option<SocketAddr> {
+    match with_timeout(proxy_protocol.client_addr(tcp_stream, src_addr), timeout).await {
+        Some(Ok(client_addr)) => {
+            if client_addr != src_addr {
+                debug!("connection from: {} proxied by: {}", client_addr, src_addr);
+            }
+            Some(client_addr)
+        }
+        Some(Err(e)) => {
+            warn!(
+                "invalid PROXY protocol header from: {} error: {}",
+                src_addr, e
+            );
+            None
+        }
+        None => {
+            debug!("PROXY protocol header from: {} timed out", src_addr);
+            None
+        }
+    }
+}
+
+/// Waits for the future, e.g. the handshake of a new connection, `None` if it times out
+///
+/// There is no timeout if the `timeout` is zero, as for `TimeoutStream`.
+pub(crate) async fn with_timeout<F: Future>(future: F, timeout: Duration) -> Option<F::Output> {
+    if timeout == Duration::from_secs(0) {
+        return Some(future.await);
+    }
+
+    tokio::time::timeout(timeout, future).await.ok()
+}
+
 pub(crate) async fn handle_raw_request<T: RequestHandler>(
     message: SerialMessage,
     protocol: Protocol,
     request_handler: Arc<T>,
     response_handler: BufDnsStreamHandle,
+    dnstap: Option<&Dnstap>,
+    options: ResponseOptions,
 ) {
     let src_addr = message.addr();
-    let response_handler = ResponseHandle::new(message.addr(), response_handler);
+    let tap = dnstap.map(|dnstap| ClientTap::query(dnstap, protocol, src_addr, message.bytes()));
+    let response_handler =
+        ResponseHandle::new(message.addr(), response_handler).with_client_tap(tap);
 
     self::handle_request(
         message.bytes(),
@@ -710,6 +1324,7 @@ pub(crate) async fn handle_raw_request<T: RequestHandler>(
         protocol,
         request_handler,
         response_handler,
+        options,
     )
     .await;
 }
@@ -720,6 +1335,12 @@ struct ReportingResponseHandler<R: ResponseHandler> {
     query: LowerQuery,
     protocol: Protocol,
     src_addr: SocketAddr,
+    keepalive: Option<u16>,
+    cookie: Option<EdnsOption>,
+    minimal: bool,
+    max_size: Option<u16>,
+    /// The span of the request, which records its outcome
+    span: Span,
     handler: R,
 }
 
@@ -727,7 +1348,7 @@ struct ReportingResponseHandler<R: ResponseHandler> {
 impl<R: ResponseHandler> ResponseHandler for ReportingResponseHandler<R> {
     async fn send_response<'a>(
         &mut self,
-        response: crate::authority::MessageResponse<
+        mut response: crate::authority::MessageResponse<
             '_,
             'a,
             impl Iterator<Item = &'a Record> + Send + 'a,
@@ -736,6 +1357,18 @@ impl<R: ResponseHandler> ResponseHandler for ReportingResponseHandler<R> {
             impl Iterator<Item = &'a Record> + Send + 'a,
         >,
     ) -> io::Result<super::ResponseInfo> {
+        if let (Some(timeout), Some(edns)) = (self.keepalive, response.edns_mut()) {
+            edns.options_mut()
+                .insert(EdnsOption::TcpKeepalive(Some(timeout)));
+        }
+        if let (Some(cookie), Some(edns)) = (self.cookie.take(), response.edns_mut()) {
+            edns.options_mut().insert(cookie);
+        }
+        response.set_minimal(self.minimal);
+        if let Some(max_size) = self.max_size {
+            response.set_max_size(max_size);
+        }
+
         let response_info = self.handler.send_response(response).await?;
 
         let id = self.request_header.id();
@@ -750,6 +1383,7 @@ impl<R: ResponseHandler> ResponseHandler for ReportingResponseHandler<R> {
         let authority_count = response_info.name_server_count();
         let additional_count = response_info.additional_count();
         let response_code = response_info.response_code();
+        self.span.record("outcome", &field::display(response_code));
 
         info!("request:{id} src:{proto}://{addr}#{port} {op}:{query}:{qtype}:{class} qflags:{qflags} response:{code:?} rr:{answers}/{authorities}/{additionals} rflags:{rflags}",
             id = rid,
@@ -779,6 +1413,7 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
     protocol: Protocol,
     request_handler: Arc<T>,
     response_handler: R,
+    options: ResponseOptions,
 ) {
     let mut decoder = BinDecoder::new(message_bytes);
 
@@ -789,6 +1424,14 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
         let qop_code = message.op_code();
         let message_type = message.message_type();
         let is_dnssec = message.edns().map_or(false, Edns::dnssec_ok);
+        let keepalive = message
+            .edns()
+            .and_then(|edns| edns.option(EdnsCode::Keepalive))
+            .and_then(|_| keepalive(options.idle_timeout?));
+        // the payload size of the client is 512 without EDNS, RFC 6891
+        let max_size = options
+            .max_udp_payload
+            .map(|max_udp_payload| message.max_payload().min(max_udp_payload).max(512));
 
         let request = Request::new(message, src_addr, protocol);
 
@@ -798,7 +1441,8 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
         let query_type = info.query.query_type();
         let query_class = info.query.query_class();
 
-        debug!(
+        let span = request_span(id, protocol, src_addr, query_name, query_type);
+        span.in_scope(|| debug!(
             "request:{id} src:{proto}://{addr}#{port} type:{message_type} dnssec:{is_dnssec} {op}:{query}:{qtype}:{class} qflags:{qflags}",
             id = id,
             proto = protocol,
@@ -811,18 +1455,42 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
             qtype = query_type,
             class = query_class,
             qflags = qflags,
-        );
+        ));
 
         // The reporter will handle making sure to log the result of the request
-        let reporter = ReportingResponseHandler {
+        let mut reporter = ReportingResponseHandler {
             request_header: *request.header(),
             query,
             protocol,
             src_addr,
+            keepalive,
+            cookie: None,
+            minimal: options.minimal,
+            max_size,
+            span: span.clone(),
             handler: response_handler,
         };
 
-        request_handler.handle_request(&request, reporter).await;
+        let check = options
+            .cookies
+            .as_ref()
+            .map(|cookies| cookies.check(request.edns(), src_addr.ip(), protocol));
+        match check {
+            None | Some(CookieCheck::Answer) => (),
+            Some(CookieCheck::AnswerWith(cookie)) => reporter.cookie = Some(cookie),
+            Some(check) => {
+                span.in_scope(|| debug!("request:{id} refused for its cookie: {check:?}"));
+                refuse_for_cookie(&request, check, reporter)
+                    .instrument(span)
+                    .await;
+                return;
+            }
+        }
+
+        request_handler
+            .handle_request(&request, reporter)
+            .instrument(span)
+            .await;
     };
 
     // Attempt to decode the message
@@ -855,6 +1523,11 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
                 query,
                 protocol,
                 src_addr,
+                keepalive: None,
+                cookie: None,
+                minimal: false,
+                max_size: None,
+                span: Span::none(),
                 handler: response_handler,
             };
 
@@ -871,6 +1544,101 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
     }
 }
 
+/// The span of a request, the parent of the spans of its lookups, e.g. of the queries of the
+///  forwarders and recursors
+///
+/// The trace id is random, so that the events of a request can be found in the logs of many
+///  servers. The authority of the request and its outcome, the response code, are recorded once
+///  they are known.
+fn request_span(
+    id: u16,
+    protocol: Protocol,
+    src_addr: SocketAddr,
+    query_name: &LowerName,
+    query_type: RecordType,
+) -> Span {
+    info_span!(
+        "request",
+        trace_id = %format_args!("{:016x}", rand::random::<u64>()),
+        id,
+        transport = %protocol,
+        client = %src_addr,
+        qname = %query_name,
+        qtype = %query_type,
+        authority = field::Empty,
+        outcome = field::Empty,
+    )
+}
+
+/// Answers the request which is refused for its DNS Cookie, RFC 7873 section 5.2
+async fn refuse_for_cookie<R: ResponseHandler>(
+    request: &Request,
+    check: CookieCheck,
+    mut response_handler: R,
+) {
+    let mut response = MessageResponseBuilder::new(Some(request.raw_query()));
+    let mut header = Header::response_from_request(request.header());
+
+    let result = match check {
+        CookieCheck::BadCookie(cookie) => {
+            // the client retries with the new server cookie
+            let mut edns = Edns::new();
+            edns.set_max_payload(request.max_payload());
+            edns.set_rcode_high(ResponseCode::BADCOOKIE.high());
+            edns.options_mut().insert(cookie);
+            header.set_response_code(ResponseCode::BADCOOKIE);
+            response.edns(edns);
+
+            response_handler
+                .send_response(response.build_no_records(header))
+                .await
+        }
+        CookieCheck::Truncate => {
+            // the client retries over TCP
+            header.set_truncated(true);
+            response_handler
+                .send_response(response.build_no_records(header))
+                .await
+        }
+        _ => {
+            response_handler
+                .send_response(response.error_msg(request.header(), ResponseCode::FormErr))
+                .await
+        }
+    };
+
+    if let Err(e) = result {
+        warn!("failed to refuse request for its cookie: {}", e);
+    }
+}
+
+/// The default limit of the size of the responses over UDP, which avoids IP fragmentation
+const DEFAULT_MAX_UDP_PAYLOAD: u16 = 1232;
+
+/// How the responses to the requests of a listener are sent
+#[derive(Clone, Default)]
+pub(crate) struct ResponseOptions {
+    /// The idle timeout of the connection, advertised with edns-tcp-keepalive
+    pub(crate) idle_timeout: Option<Duration>,
+    /// Omit the records which are not required, see `MessageResponse::set_minimal`
+    pub(crate) minimal: bool,
+    /// The limit of the size of the responses over UDP
+    pub(crate) max_udp_payload: Option<u16>,
+    /// The DNS Cookies of the requests are checked and answered
+    pub(crate) cookies: Option<ServerCookies>,
+}
+
+/// The idle timeout advertised with edns-tcp-keepalive, in units of 100 milliseconds, RFC 7828
+///
+/// There is nothing to advertise if there is no timeout, a timeout of zero would ask the client to
+///  close the connection.
+fn keepalive(idle_timeout: Duration) -> Option<u16> {
+    match idle_timeout.as_millis() / 100 {
+        0 => None,
+        timeout => Some(u16::try_from(timeout).unwrap_or(u16::MAX)),
+    }
+}
+
 /// Checks if the IP address is safe for returning messages
 ///
 /// Examples of unsafe addresses are any with a port of `0`
@@ -917,9 +1685,31 @@ fn sanitize_src_address(src: SocketAddr) -> Result<(), String> {
 mod tests {
 
     use super::*;
-    use crate::authority::Catalog;
+    use crate::{
+        authority::{Catalog, ZoneType},
+        proto::{
+            op::Message,
+            rr::{Name, RData},
+            serialize::binary::BinEncoder,
+        },
+        store::in_memory::InMemoryAuthority,
+    };
     use futures_util::future;
-    use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+    use std::{
+        collections::HashMap,
+        fmt,
+        net::{Ipv4Addr, SocketAddr, UdpSocket},
+        str::FromStr,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{self, Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, Layer, SubscriberExt},
+        util::SubscriberInitExt,
+    };
 
     #[test]
     fn cleanup_after_shutdown() {
@@ -946,6 +1736,101 @@ mod tests {
         UdpSocket::bind(bind_addr).unwrap();
     }
 
+    /// The fields of the request span
+    #[derive(Clone, Default)]
+    struct RequestFields(Arc<Mutex<HashMap<&'static str, String>>>);
+
+    impl Visit for RequestFields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for RequestFields {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() == "request" {
+                attrs.record(&mut self.clone());
+            }
+        }
+
+        fn on_record(&self, _id: &Id, values: &span::Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    #[derive(Clone)]
+    struct EncodingResponseHandler;
+
+    #[async_trait::async_trait]
+    impl ResponseHandler for EncodingResponseHandler {
+        async fn send_response<'a>(
+            &mut self,
+            response: crate::authority::MessageResponse<
+                '_,
+                'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+            >,
+        ) -> io::Result<super::super::ResponseInfo> {
+            let mut buf = Vec::new();
+            let mut encoder = BinEncoder::new(&mut buf);
+            response
+                .destructive_emit(&mut encoder)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_span() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, false);
+        authority.upsert_mut(
+            Record::from_rdata(
+                Name::from_str("www.example.com.").unwrap(),
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ),
+            0,
+        );
+        let mut catalog = Catalog::new();
+        catalog.upsert(origin.into(), Box::new(Arc::new(authority)));
+
+        let mut message = Message::new();
+        message.set_id(7).add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+
+        let fields = RequestFields::default();
+        let _guard = tracing_subscriber::registry()
+            .with(fields.clone())
+            .set_default();
+        handle_request(
+            &message.to_vec().unwrap(),
+            SocketAddr::from(([192, 0, 2, 53], 4096)),
+            Protocol::Udp,
+            Arc::new(catalog),
+            EncodingResponseHandler,
+            ResponseOptions::default(),
+        )
+        .await;
+
+        let fields = fields.0.lock().unwrap();
+        assert_eq!(fields["trace_id"].len(), 16);
+        assert_eq!(fields["id"], "7");
+        assert_eq!(fields["transport"], "UDP");
+        assert_eq!(fields["client"], "192.0.2.53:4096");
+        assert_eq!(fields["qname"], "www.example.com.");
+        assert_eq!(fields["qtype"], "A");
+        assert_eq!(fields["authority"], "example.com.");
+        assert_eq!(fields["outcome"], "No Error");
+    }
+
     #[test]
     fn test_sanitize_src_addr() {
         // ipv4 tests
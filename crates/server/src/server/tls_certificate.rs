@@ -0,0 +1,96 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Certificates of the TLS, HTTPS and QUIC listeners, which may be replaced while in use
+//!
+//! A renewed certificate is served to new connections as soon as it replaces the previous one,
+//!  without restarting the listeners or closing the open connections.
+
+use std::{
+    io,
+    sync::{Arc, RwLock},
+};
+
+use rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::{self, CertifiedKey},
+    Certificate, PrivateKey,
+};
+
+/// The certificate of a TLS, HTTPS or QUIC listener
+pub enum TlsCertificate {
+    /// A certificate chain and its key, which are served as long as the listener
+    Fixed(Vec<Certificate>, PrivateKey),
+    /// A certificate which may be replaced while the listener is in use, e.g. when it's renewed
+    Reloadable(Arc<ReloadableCertificate>),
+}
+
+impl TlsCertificate {
+    /// The resolver of the certificate of each connection
+    pub(crate) fn into_resolver(self) -> io::Result<Arc<dyn ResolvesServerCert>> {
+        match self {
+            Self::Fixed(chain, key) => Ok(Arc::new(ReloadableCertificate::new(chain, key)?)),
+            Self::Reloadable(certificate) => Ok(certificate),
+        }
+    }
+}
+
+impl From<(Vec<Certificate>, PrivateKey)> for TlsCertificate {
+    fn from((chain, key): (Vec<Certificate>, PrivateKey)) -> Self {
+        Self::Fixed(chain, key)
+    }
+}
+
+impl From<Arc<ReloadableCertificate>> for TlsCertificate {
+    fn from(certificate: Arc<ReloadableCertificate>) -> Self {
+        Self::Reloadable(certificate)
+    }
+}
+
+/// A certificate which can be replaced while the listeners serve it
+///
+/// Each connection is served the certificate which is current at the time of its handshake. Until
+///  a certificate is set, handshakes fail.
+#[derive(Default)]
+pub struct ReloadableCertificate {
+    current: RwLock<Option<Arc<CertifiedKey>>>,
+}
+
+impl ReloadableCertificate {
+    /// A certificate which is initially the chain and its key
+    pub fn new(chain: Vec<Certificate>, key: PrivateKey) -> io::Result<Self> {
+        let certificate = Self::default();
+        certificate.replace(chain, key)?;
+        Ok(certificate)
+    }
+
+    /// Replaces the certificate, which is served to all following handshakes
+    pub fn replace(&self, chain: Vec<Certificate>, key: PrivateKey) -> io::Result<()> {
+        let key = sign::any_supported_type(&key).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "unsupported private key type")
+        })?;
+
+        *self.current.write().expect("certificate poisoned") =
+            Some(Arc::new(CertifiedKey::new(chain, key)));
+        Ok(())
+    }
+
+    /// The certificate chain which is currently served, none if it was never set
+    pub fn chain(&self) -> Option<Vec<Certificate>> {
+        self.current
+            .read()
+            .expect("certificate poisoned")
+            .as_ref()
+            .map(|certified| certified.cert.clone())
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertificate {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.current.read().expect("certificate poisoned").clone()
+    }
+}
@@ -0,0 +1,459 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::{
+    fmt, fs, io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock, Weak,
+    },
+    time::Duration,
+};
+
+use hyper::{Body, Client, Request};
+use hyper_rustls::HttpsConnectorBuilder;
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+use crate::{
+    authority::{
+        Authority, LookupError, LookupObject, LookupOptions, MessageRequest, UpdateResult, ZoneType,
+    },
+    client::{
+        op::ResponseCode,
+        rr::{rdata::opt::ExtendedErrorCode, LowerName, Name, RData, Record, RecordType},
+    },
+    dnstap::Dnstap,
+    proto::rr::rdata::opt::{ClientSubnet, EdnsOption},
+    server::RequestInfo,
+    store::{
+        blocklist::{
+            filter::{Filter, NameSet, NameSetBuilder},
+            BlocklistAction, BlocklistConfig,
+        },
+        forwarder::{ForwardAuthority, ForwardLookup},
+    },
+};
+
+/// The time before the lists are read again, after some of them could not be
+const RETRY_INTERVAL: Duration = Duration::from_secs(300);
+
+/// The time to download a list
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An authority that filters the names of blocklists, and forwards the other names upstream
+///
+/// Blocked names are answered with NXDOMAIN, or with the addresses of a sinkhole, as are the names
+///  whose answers are CNAMEs to blocked names. The lists are read once `reload()` is called, and
+///  periodically once `spawn_reload()` is. Until the lists are read, no name is blocked.
+pub struct BlocklistAuthority {
+    origin: LowerName,
+    lists: Vec<ListSource>,
+    allow_lists: Vec<ListSource>,
+    block: Vec<String>,
+    allow: Vec<String>,
+    action: BlocklistAction,
+    sinkhole: [IpAddr; 2],
+    ttl: u32,
+    reload_interval: Option<Duration>,
+    upstream: Option<ForwardAuthority>,
+    /// The names filtered, none until the lists are read
+    filter: RwLock<Option<Arc<Filter>>>,
+    /// True if all the lists were read by the last reload
+    complete: AtomicBool,
+    /// Only one reload runs at a time
+    reloading: Mutex<()>,
+}
+
+impl BlocklistAuthority {
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// The lists are not read until `reload()` is called.
+    pub fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        config: &BlocklistConfig,
+        root_dir: Option<&Path>,
+    ) -> Result<Self, String> {
+        info!("loading blocklist config: {}", origin);
+
+        // the entries of the configuration are validated once, the lists each time they are read
+        let mut builder = NameSetBuilder::new();
+        for entry in config.block.iter().chain(&config.allow) {
+            builder.add_line(entry)?;
+        }
+
+        let sources = |lists: &[String]| {
+            lists
+                .iter()
+                .map(|list| ListSource::new(list, root_dir))
+                .collect::<Vec<_>>()
+        };
+
+        let upstream = config
+            .upstream
+            .as_ref()
+            .map(|upstream| ForwardAuthority::try_from_config(origin.clone(), zone_type, upstream))
+            .transpose()?;
+
+        Ok(Self {
+            origin: origin.into(),
+            lists: sources(&config.lists),
+            allow_lists: sources(&config.allow_lists),
+            block: config.block.clone(),
+            allow: config.allow.clone(),
+            action: config.get_action(),
+            sinkhole: [
+                IpAddr::V4(config.get_sinkhole_ipv4()),
+                IpAddr::V6(config.get_sinkhole_ipv6()),
+            ],
+            ttl: config.get_ttl(),
+            reload_interval: config.get_reload_interval(),
+            upstream,
+            filter: RwLock::new(None),
+            complete: AtomicBool::new(false),
+            reloading: Mutex::new(()),
+        })
+    }
+
+    /// Logs the queries sent to the upstream resolvers, and their responses, with dnstap
+    pub fn set_dnstap(&mut self, dnstap: Option<Dnstap>) {
+        if let Some(upstream) = &mut self.upstream {
+            upstream.set_dnstap(dnstap);
+        }
+    }
+
+    /// Reads the lists, and filters their names from then on
+    ///
+    /// If a list can't be read, the names of the lists which were read before are filtered until
+    ///  the next reload, unless none were, then the names of the lists which could be read are.
+    pub async fn reload(&self) -> Result<(), String> {
+        let _reloading = self.reloading.lock().await;
+
+        let mut errors = Vec::new();
+        let blocked = read_lists(&self.lists, &mut errors).await;
+        let allowed = read_lists(&self.allow_lists, &mut errors).await;
+        self.complete.store(errors.is_empty(), Ordering::Relaxed);
+
+        let loaded = self.filter.read().expect("filter poisoned").is_some();
+        if !errors.is_empty() && loaded {
+            return Err(errors.join(", "));
+        }
+
+        let (block, allow) = (self.block.clone(), self.allow.clone());
+        let filter = tokio::task::spawn_blocking(move || {
+            Filter::new(
+                build_name_set(&blocked, &block),
+                build_name_set(&allowed, &allow),
+            )
+        })
+        .await
+        .map_err(|e| format!("failed to build the filter: {}", e))?;
+
+        info!(
+            "blocklist {} loaded: {} blocked names, {} regexes, {} allowed names, {} regexes",
+            self.origin,
+            filter.blocked().names(),
+            filter.blocked().regexes(),
+            filter.allowed().names(),
+            filter.allowed().regexes(),
+        );
+        *self.filter.write().expect("filter poisoned") = Some(Arc::new(filter));
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+
+    /// Spawns the task reloading the lists, which runs until the authority is dropped
+    ///
+    /// None if the lists are never reloaded. This must be called from within a tokio runtime.
+    pub fn spawn_reload(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let interval = self.reload_interval?;
+        Some(tokio::spawn(reload_loop(Arc::downgrade(self), interval)))
+    }
+
+    /// True if the name is blocked
+    pub fn is_blocked(&self, name: &LowerName) -> bool {
+        let filter = self.filter.read().expect("filter poisoned").clone();
+        filter.map_or(false, |filter| filter.is_blocked(name))
+    }
+
+    /// The answer of a blocked name
+    fn blocked(&self, name: &LowerName, rtype: RecordType) -> Result<BlocklistLookup, LookupError> {
+        debug!("blocked: {} {}", name, rtype);
+        if self.action == BlocklistAction::NxDomain {
+            return Err(LookupError::ExtendedError {
+                response_code: ResponseCode::NXDomain,
+                info_code: ExtendedErrorCode::Blocked,
+                extra_text: format!("{} is blocked", name),
+            });
+        }
+
+        let records = self
+            .sinkhole
+            .iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V4(ip) if matches!(rtype, RecordType::A | RecordType::ANY) => {
+                    Some(RData::A(*ip))
+                }
+                IpAddr::V6(ip) if matches!(rtype, RecordType::AAAA | RecordType::ANY) => {
+                    Some(RData::AAAA(*ip))
+                }
+                _ => None,
+            })
+            .map(|rdata| Record::from_rdata(name.into(), self.ttl, rdata))
+            .collect();
+
+        Ok(BlocklistLookup::Sinkhole(records))
+    }
+}
+
+async fn reload_loop(authority: Weak<BlocklistAuthority>, interval: Duration) {
+    loop {
+        let wait = match authority.upgrade() {
+            Some(authority) if authority.complete.load(Ordering::Relaxed) => interval,
+            Some(_) => RETRY_INTERVAL.min(interval),
+            None => return,
+        };
+
+        debug!("next reload of the blocklist in {:?}", wait);
+        tokio::time::sleep(wait).await;
+
+        match authority.upgrade() {
+            Some(authority) => {
+                if let Err(e) = authority.reload().await {
+                    warn!("failed to reload blocklist {}: {}", authority.origin, e);
+                }
+            }
+            None => return,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Authority for BlocklistAuthority {
+    type Lookup = BlocklistLookup;
+
+    /// Always Forward
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Forward
+    }
+
+    /// Always false for Blocklist zones
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    fn client_subnet_scope(&self, client_subnet: &ClientSubnet) -> u8 {
+        self.upstream
+            .as_ref()
+            .map_or(0, |upstream| upstream.client_subnet_scope(client_subnet))
+    }
+
+    /// Clears the caches of the upstream resolvers
+    fn clear_cache(&self) {
+        if let Some(upstream) = &self.upstream {
+            upstream.clear_cache();
+        }
+    }
+
+    /// Answers blocked names, and forwards the others upstream
+    ///
+    /// The upstream answers are blocked as well if one of their names is, i.e. the target of a
+    ///  CNAME.
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        if self.is_blocked(name) {
+            return self.blocked(name, rtype);
+        }
+
+        let upstream = match &self.upstream {
+            Some(upstream) => upstream,
+            None => return Err(LookupError::from(ResponseCode::Refused)),
+        };
+
+        let lookup = upstream.lookup(name, rtype, lookup_options).await?;
+        if let Some(target) = lookup
+            .iter()
+            .map(|record| LowerName::from(record.name()))
+            .find(|target| self.is_blocked(target))
+        {
+            debug!("{} is an alias of {}", name, target);
+            return self.blocked(name, rtype);
+        }
+
+        Ok(BlocklistLookup::Forwarded(lookup))
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.lookup(
+            request_info.query.name(),
+            request_info.query.query_type(),
+            lookup_options,
+        )
+        .await
+    }
+
+    async fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        Err(LookupError::from(io::Error::new(
+            io::ErrorKind::Other,
+            "Getting NSEC records is unimplemented for the blocklist",
+        )))
+    }
+}
+
+/// The answer of a blocklist zone
+pub enum BlocklistLookup {
+    /// The sinkhole addresses of a blocked name
+    Sinkhole(Vec<Record>),
+    /// The answer of the upstream resolvers to a name which is not blocked
+    Forwarded(ForwardLookup),
+}
+
+impl LookupObject for BlocklistLookup {
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Sinkhole(records) => records.is_empty(),
+            Self::Forwarded(lookup) => lookup.is_empty(),
+        }
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        match self {
+            Self::Sinkhole(records) => Box::new(records.iter()),
+            Self::Forwarded(lookup) => lookup.iter(),
+        }
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+
+    fn extended_error(&self) -> Option<EdnsOption> {
+        match self {
+            Self::Sinkhole(_) => None,
+            Self::Forwarded(lookup) => lookup.extended_error(),
+        }
+    }
+}
+
+/// A list, in a file or at a URL
+enum ListSource {
+    File(PathBuf),
+    Url(String),
+}
+
+impl ListSource {
+    fn new(list: &str, root_dir: Option<&Path>) -> Self {
+        if list.starts_with("http://") || list.starts_with("https://") {
+            return Self::Url(list.to_string());
+        }
+
+        match root_dir {
+            Some(root_dir) => Self::File(root_dir.join(list)),
+            None => Self::File(PathBuf::from(list)),
+        }
+    }
+
+    async fn read(&self) -> Result<String, String> {
+        match self {
+            Self::File(path) => fs::read_to_string(path).map_err(|e| e.to_string()),
+            Self::Url(url) => tokio::time::timeout(DOWNLOAD_TIMEOUT, download(url))
+                .await
+                .map_err(|_| "timed out".to_string())?,
+        }
+    }
+}
+
+impl fmt::Display for ListSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Url(url) => f.write_str(url),
+        }
+    }
+}
+
+async fn download(url: &str) -> Result<String, String> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = Client::builder().build::<_, Body>(connector);
+
+    let request = Request::get(url)
+        .body(Body::empty())
+        .map_err(|e| e.to_string())?;
+    let response = client.request(request).await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("status {}", response.status()));
+    }
+
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Reads the lists which can be read, the errors of the others are added to `errors`
+async fn read_lists(lists: &[ListSource], errors: &mut Vec<String>) -> Vec<(String, String)> {
+    let mut texts = Vec::with_capacity(lists.len());
+    for list in lists {
+        match list.read().await {
+            Ok(text) => {
+                debug!("read list {}", list);
+                texts.push((list.to_string(), text));
+            }
+            Err(e) => errors.push(format!("failed to read list {}: {}", list, e)),
+        }
+    }
+
+    texts
+}
+
+fn build_name_set(lists: &[(String, String)], entries: &[String]) -> NameSet {
+    let mut builder = NameSetBuilder::new();
+    for (list, text) in lists {
+        let invalid = builder.add_list(text);
+        if !invalid.is_empty() {
+            debug!("invalid entries of list {}: {:?}", list, invalid);
+            warn!("skipped {} invalid entries of list {}", invalid.len(), list);
+        }
+    }
+    for entry in entries {
+        // validated with the configuration
+        let _ = builder.add_line(entry);
+    }
+
+    builder.build()
+}
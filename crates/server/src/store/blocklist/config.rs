@@ -0,0 +1,120 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::{
+    fs,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::store::{blocklist::filter::NameSetBuilder, forwarder::ForwardConfig};
+
+/// Configuration of a zone filtering the names of blocklists, e.g. of ads and trackers
+///
+/// Lists are files, relative to the directory of the zones, or `http://` and `https://` URLs. The
+///  names of the allowlists are never blocked. The names which are not blocked are forwarded to
+///  the `upstream` resolvers, and refused without them.
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct BlocklistConfig {
+    /// The lists of the blocked names
+    pub lists: Vec<String>,
+    /// The lists of the names which are never blocked
+    #[serde(default)]
+    pub allow_lists: Vec<String>,
+    /// Names blocked in addition to the lists, in the form of their entries
+    #[serde(default)]
+    pub block: Vec<String>,
+    /// Names allowed in addition to the allowlists, in the form of their entries
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// How blocked names are answered, `nxdomain` by default
+    pub action: Option<BlocklistAction>,
+    /// The address of the A records of blocked names, with the `sinkhole` action, 0.0.0.0 by
+    ///  default
+    pub sinkhole_ipv4: Option<Ipv4Addr>,
+    /// The address of the AAAA records of blocked names, with the `sinkhole` action, :: by default
+    pub sinkhole_ipv6: Option<Ipv6Addr>,
+    /// The TTL of the answers of blocked names, 60 seconds by default
+    pub ttl: Option<u32>,
+    /// Seconds between the reloads of the lists, one day by default, 0 to never reload them
+    pub reload_interval: Option<u64>,
+    /// The resolvers of the names which are not blocked
+    pub upstream: Option<ForwardConfig>,
+}
+
+impl BlocklistConfig {
+    /// How blocked names are answered
+    pub fn get_action(&self) -> BlocklistAction {
+        self.action.unwrap_or(BlocklistAction::NxDomain)
+    }
+
+    /// The address of the A records of blocked names
+    pub fn get_sinkhole_ipv4(&self) -> Ipv4Addr {
+        self.sinkhole_ipv4.unwrap_or(Ipv4Addr::UNSPECIFIED)
+    }
+
+    /// The address of the AAAA records of blocked names
+    pub fn get_sinkhole_ipv6(&self) -> Ipv6Addr {
+        self.sinkhole_ipv6.unwrap_or(Ipv6Addr::UNSPECIFIED)
+    }
+
+    /// The TTL of the answers of blocked names
+    pub fn get_ttl(&self) -> u32 {
+        self.ttl.unwrap_or(60)
+    }
+
+    /// The time between the reloads of the lists, none if they are never reloaded
+    pub fn get_reload_interval(&self) -> Option<Duration> {
+        match self.reload_interval.unwrap_or(86_400) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// The problems of the entries of the configuration and of the lists in files, the lists at
+    ///  URLs are not downloaded
+    pub(crate) fn problems(&self, root_dir: &Path) -> Vec<String> {
+        let mut builder = NameSetBuilder::new();
+        let mut problems = self
+            .block
+            .iter()
+            .chain(&self.allow)
+            .filter_map(|entry| builder.add_line(entry).err())
+            .collect::<Vec<_>>();
+
+        for list in self.lists.iter().chain(&self.allow_lists) {
+            if list.starts_with("http://") || list.starts_with("https://") {
+                continue;
+            }
+
+            match fs::read_to_string(root_dir.join(list)) {
+                Ok(text) => {
+                    let invalid = builder.add_list(&text);
+                    if !invalid.is_empty() {
+                        problems.push(format!("{}: {} invalid entries", list, invalid.len()));
+                    }
+                }
+                Err(e) => problems.push(format!("failed to read list {}: {}", list, e)),
+            }
+        }
+
+        problems
+    }
+}
+
+/// How the names of the blocklists are answered
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BlocklistAction {
+    /// The name does not exist, with the Blocked extended DNS error of RFC 8914
+    NxDomain,
+    /// The name has the sinkhole addresses, and no other records
+    Sinkhole,
+}
@@ -0,0 +1,393 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The names of blocklists and allowlists, matched with a suffix trie and regular expressions
+
+use std::{borrow::Borrow, collections::HashMap, net::IpAddr};
+
+use regex::{RegexBuilder, RegexSet, RegexSetBuilder};
+
+use crate::client::rr::{LowerName, Name};
+
+/// The name itself matches
+const EXACT: u8 = 0b01;
+/// All the names below the name match
+const BELOW: u8 = 0b10;
+
+/// The names of hosts files which are not blocked, e.g. `127.0.0.1 localhost`
+const HOSTS_NAMES: [&str; 4] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+];
+
+/// The names which are blocked, except for those which are allowed
+pub(crate) struct Filter {
+    blocked: NameSet,
+    allowed: NameSet,
+}
+
+impl Filter {
+    pub(crate) fn new(blocked: NameSet, allowed: NameSet) -> Self {
+        Self { blocked, allowed }
+    }
+
+    /// True if the name is blocked and not allowed
+    pub(crate) fn is_blocked(&self, name: &LowerName) -> bool {
+        self.blocked.matches(name) && !self.allowed.matches(name)
+    }
+
+    pub(crate) fn blocked(&self) -> &NameSet {
+        &self.blocked
+    }
+
+    pub(crate) fn allowed(&self) -> &NameSet {
+        &self.allowed
+    }
+}
+
+/// A set of names, of exact names, of the names below a name and of regular expressions
+///
+/// The names are in a trie of their labels from the root, whose labels are interned, so that the
+///  millions of names of large blocklists take little memory and are matched in as many steps as
+///  the name has labels.
+pub(crate) struct NameSet {
+    labels: HashMap<Box<[u8]>, u32>,
+    /// The flags of each node, the root is the first node
+    flags: Vec<u8>,
+    /// The first edge of each node, the edges of a node end at the first edge of the next one
+    first_edges: Vec<u32>,
+    /// The label and the child node of each edge, sorted by label for each node
+    edges: Vec<(u32, u32)>,
+    regexes: RegexSet,
+    names: usize,
+}
+
+impl NameSet {
+    /// The number of names, of the entries of the lists which are not regular expressions
+    pub(crate) fn names(&self) -> usize {
+        self.names
+    }
+
+    /// The number of regular expressions
+    pub(crate) fn regexes(&self) -> usize {
+        self.regexes.len()
+    }
+
+    /// True if the name is in the set, or is below a name of the set whose subdomains are, or
+    ///  matches a regular expression
+    pub(crate) fn matches(&self, name: &LowerName) -> bool {
+        let name: &Name = name.borrow();
+        let mut labels = name.iter().rev().peekable();
+        let mut node = 0;
+        while let Some(label) = labels.next() {
+            node = match self.child(node, label) {
+                Some(child) => child,
+                None => break,
+            };
+
+            let flags = self.flags[node as usize];
+            match labels.peek() {
+                Some(_) if flags & BELOW != 0 => return true,
+                None if flags & EXACT != 0 => return true,
+                _ => (),
+            }
+        }
+
+        if self.regexes.is_empty() {
+            return false;
+        }
+
+        let name = name.to_ascii();
+        self.regexes.is_match(name.trim_end_matches('.'))
+    }
+
+    fn child(&self, node: u32, label: &[u8]) -> Option<u32> {
+        let label = *self.labels.get(label)?;
+        let edges = &self.edges[self.first_edges[node as usize] as usize
+            ..self.first_edges[node as usize + 1] as usize];
+
+        edges
+            .binary_search_by_key(&label, |(label, _)| *label)
+            .ok()
+            .map(|index| edges[index].1)
+    }
+}
+
+/// Builds a [`NameSet`] from the entries of lists
+///
+/// Each line of a list is one of:
+///
+/// * `ads.example.com`, the name only
+/// * `*.example.com`, the names below example.com, not example.com itself
+/// * `||example.com^`, in the form of Adblock lists, example.com and the names below it
+/// * `/^ads?[0-9]*\./`, a regular expression matched against the names, without the final dot
+/// * `0.0.0.0 ads.example.com tracker.example.com`, in the form of hosts files, the names only
+///
+/// Comments start with `#`, or with `!` in Adblock lists.
+#[derive(Default)]
+pub(crate) struct NameSetBuilder {
+    labels: HashMap<Box<[u8]>, u32>,
+    flags: Vec<u8>,
+    edges: HashMap<(u32, u32), u32>,
+    regexes: Vec<String>,
+    names: usize,
+}
+
+impl NameSetBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            flags: vec![0],
+            ..Self::default()
+        }
+    }
+
+    /// Adds the entries of a list, returns the lines which are not valid entries
+    pub(crate) fn add_list<'l>(&mut self, list: &'l str) -> Vec<&'l str> {
+        list.lines()
+            .filter(|line| self.add_line(line).is_err())
+            .collect()
+    }
+
+    /// Adds the entry of a line of a list, if it isn't empty nor a comment
+    pub(crate) fn add_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.starts_with('/') {
+            return match line.strip_suffix('/') {
+                Some(regex) if line.len() > 1 => self.add_regex(&regex[1..]),
+                _ => Err(format!("unterminated regular expression: {}", line)),
+            };
+        }
+
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+            return Ok(());
+        }
+
+        if let Some(name) = line.strip_prefix("||") {
+            let name = name
+                .strip_suffix('^')
+                .ok_or_else(|| format!("unsupported Adblock rule: {}", line))?;
+            return self.add_name(name, EXACT | BELOW);
+        }
+
+        let mut tokens = line.split_whitespace();
+        let first = tokens.next().unwrap_or_default();
+        if first.parse::<IpAddr>().is_ok() {
+            for name in tokens {
+                if !HOSTS_NAMES.contains(&name) && !name.starts_with("ip6-") {
+                    self.add_name(name, EXACT)?;
+                }
+            }
+            return Ok(());
+        }
+
+        if tokens.next().is_some() {
+            return Err(format!("not a name: {}", line));
+        }
+        match first.strip_prefix("*.") {
+            Some(name) => self.add_name(name, BELOW),
+            None => self.add_name(first, EXACT),
+        }
+    }
+
+    fn add_regex(&mut self, regex: &str) -> Result<(), String> {
+        RegexBuilder::new(regex)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("invalid regular expression {}: {}", regex, e))?;
+        self.regexes.push(regex.to_string());
+        Ok(())
+    }
+
+    fn add_name(&mut self, name: &str, flags: u8) -> Result<(), String> {
+        let name = if name.is_ascii() {
+            name.to_ascii_lowercase()
+        } else {
+            Name::from_utf8(name)
+                .map_err(|e| format!("invalid name {}: {}", name, e))?
+                .to_ascii()
+                .to_ascii_lowercase()
+        };
+
+        let name = name.strip_suffix('.').unwrap_or(&name);
+        let labels = name.split('.').collect::<Vec<_>>();
+        if name.is_empty() || labels.iter().any(|label| !is_label(label)) {
+            return Err(format!("invalid name: {}", name));
+        }
+
+        let mut node = 0;
+        for label in labels.iter().rev() {
+            let label = self.label(label.as_bytes());
+            let next = self.flags.len() as u32;
+            node = *self.edges.entry((node, label)).or_insert(next);
+            if node == next {
+                self.flags.push(0);
+            }
+        }
+
+        self.flags[node as usize] |= flags;
+        self.names += 1;
+        Ok(())
+    }
+
+    fn label(&mut self, label: &[u8]) -> u32 {
+        if let Some(id) = self.labels.get(label) {
+            return *id;
+        }
+
+        let id = self.labels.len() as u32;
+        self.labels.insert(label.into(), id);
+        id
+    }
+
+    /// Freezes the trie, with the edges of each node next to each other
+    pub(crate) fn build(self) -> NameSet {
+        let mut edges = self
+            .edges
+            .into_iter()
+            .map(|((parent, label), child)| (parent, label, child))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+
+        let mut first_edges = Vec::with_capacity(self.flags.len() + 1);
+        let mut parents = edges.iter().map(|(parent, _, _)| *parent).peekable();
+        let mut index = 0;
+        for node in 0..=self.flags.len() as u32 {
+            while parents.next_if(|parent| *parent < node).is_some() {
+                index += 1;
+            }
+            first_edges.push(index);
+        }
+
+        let regexes = RegexSetBuilder::new(&self.regexes)
+            .case_insensitive(true)
+            .build()
+            .expect("regular expressions were validated");
+
+        NameSet {
+            labels: self.labels,
+            flags: self.flags,
+            first_edges,
+            edges: edges
+                .into_iter()
+                .map(|(_, label, child)| (label, child))
+                .collect(),
+            regexes,
+            names: self.names,
+        }
+    }
+}
+
+/// Labels of host names, with underscores which are common in blocklists
+fn is_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 63
+        && label
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn name_set(list: &str) -> NameSet {
+        let mut builder = NameSetBuilder::new();
+        assert!(builder.add_list(list).is_empty());
+        builder.build()
+    }
+
+    fn matches(set: &NameSet, name: &str) -> bool {
+        set.matches(&LowerName::from_str(name).unwrap())
+    }
+
+    #[test]
+    fn test_names_and_wildcards() {
+        let set = name_set(
+            "# comment\n\
+             ads.example.com\n\
+             *.tracker.example.net\n\
+             ||Metrics.Example.org^\n\
+             \n\
+             ! Adblock comment\n",
+        );
+        assert_eq!(set.names(), 3);
+
+        assert!(matches(&set, "ads.example.com."));
+        assert!(matches(&set, "ADS.example.com."));
+        assert!(!matches(&set, "www.ads.example.com."));
+        assert!(!matches(&set, "example.com."));
+
+        assert!(matches(&set, "a.tracker.example.net."));
+        assert!(matches(&set, "a.b.tracker.example.net."));
+        assert!(!matches(&set, "tracker.example.net."));
+
+        assert!(matches(&set, "metrics.example.org."));
+        assert!(matches(&set, "www.metrics.example.org."));
+        assert!(!matches(&set, "example.org."));
+    }
+
+    #[test]
+    fn test_hosts_file() {
+        let set = name_set(
+            "127.0.0.1 localhost\n\
+             ::1 ip6-localhost ip6-loopback\n\
+             0.0.0.0 ads.example.com tracker.example.com # trailing comment\n",
+        );
+        assert_eq!(set.names(), 2);
+
+        assert!(matches(&set, "ads.example.com."));
+        assert!(matches(&set, "tracker.example.com."));
+        assert!(!matches(&set, "localhost."));
+    }
+
+    #[test]
+    fn test_regexes() {
+        let set = name_set("/^ads?[0-9]*\\./\n/\\.doubleclick\\.net$/\n");
+        assert_eq!(set.regexes(), 2);
+
+        assert!(matches(&set, "ad1.example.com."));
+        assert!(matches(&set, "Ads.example.com."));
+        assert!(matches(&set, "stats.g.doubleclick.net."));
+        assert!(!matches(&set, "bad.example.com."));
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        let mut builder = NameSetBuilder::new();
+        let invalid = builder.add_list(
+            "good.example.com\nnot a name\n/[/\n||rule.example.com$third-party\nbad..example.com\n",
+        );
+        assert_eq!(
+            invalid,
+            vec![
+                "not a name",
+                "/[/",
+                "||rule.example.com$third-party",
+                "bad..example.com"
+            ]
+        );
+        assert_eq!(builder.build().names(), 1);
+    }
+
+    #[test]
+    fn test_allowed() {
+        let blocked = name_set("||example.com^\n");
+        let allowed = name_set("www.example.com\n");
+        let filter = Filter::new(blocked, allowed);
+
+        let lower = |name| LowerName::from(Name::from_str(name).unwrap());
+        assert!(filter.is_blocked(&lower("example.com.")));
+        assert!(filter.is_blocked(&lower("ads.example.com.")));
+        assert!(!filter.is_blocked(&lower("www.example.com.")));
+        assert!(!filter.is_blocked(&lower("example.net.")));
+    }
+}
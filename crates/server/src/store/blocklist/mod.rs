@@ -0,0 +1,17 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "blocklist")]
+
+//! Zones filtering the names of blocklists, e.g. of ads and trackers, before forwarding them
+
+mod authority;
+mod config;
+mod filter;
+
+pub use self::authority::{BlocklistAuthority, BlocklistLookup};
+pub use self::config::{BlocklistAction, BlocklistConfig};
@@ -9,11 +9,16 @@
 
 use serde::Deserialize;
 
+#[cfg(feature = "blocklist")]
+use crate::store::blocklist::BlocklistConfig;
 use crate::store::file::FileConfig;
 #[cfg(feature = "trust-dns-resolver")]
 use crate::store::forwarder::ForwardConfig;
 #[cfg(feature = "trust-dns-recursor")]
 use crate::store::recursor::RecursiveConfig;
+use crate::store::secondary::SecondaryConfig;
+#[cfg(feature = "sql")]
+use crate::store::sql::SqlConfig;
 #[cfg(feature = "sqlite")]
 use crate::store::sqlite::SqliteConfig;
 
@@ -25,10 +30,16 @@ use crate::store::sqlite::SqliteConfig;
 pub enum StoreConfig {
     /// File based configuration
     File(FileConfig),
+    /// Secondary zone, transferred from a primary
+    Secondary(SecondaryConfig),
     /// Sqlite based configuration file
     #[cfg(feature = "sqlite")]
     #[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
     Sqlite(SqliteConfig),
+    /// PostgreSQL or MySQL database, shared by many servers
+    #[cfg(feature = "sql")]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "postgres", feature = "mysql"))))]
+    Sql(SqlConfig),
     /// Forwarding Resolver
     #[cfg(feature = "trust-dns-resolver")]
     #[cfg_attr(docsrs, doc(cfg(feature = "resolver")))]
@@ -37,4 +48,8 @@ pub enum StoreConfig {
     #[cfg(feature = "trust-dns-recursor")]
     #[cfg_attr(docsrs, doc(cfg(feature = "recursor")))]
     Recursor(RecursiveConfig),
+    /// Blocklist, filtering names before forwarding them
+    #[cfg(feature = "blocklist")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "blocklist")))]
+    Blocklist(BlocklistConfig),
 }
@@ -1,74 +1,62 @@
-// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
-//
-// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
-// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
-// http://opensource.org/licenses/MIT>, at your option. This file may not be
-// copied, modified, or distributed except according to those terms.
-
 //! All authority related types
 
 use std::{
     collections::BTreeMap,
-    fs::File,
-    io::{BufRead, BufReader},
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    net::IpAddr,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
 };
 
-use tracing::{debug, info};
-
+use futures_util::lock::Mutex;
 #[cfg(feature = "dnssec")]
+use tracing::error;
+use tracing::{debug, info, warn};
+
 use crate::{
-    authority::DnssecAuthority,
+    authority::{
+        Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneDiff, ZoneType,
+    },
     client::{
-        proto::rr::dnssec::rdata::key::KEY,
-        rr::dnssec::{DnsSecResult, SigSigner},
+        op::ResponseCode,
+        rr::{LowerName, Name, RecordSet, RecordType, RrKey},
+        serialize::txt::{Parser, Writer},
+    },
+    error::{PersistenceErrorKind, PersistenceResult},
+    proto::rr::rdata::opt::ClientSubnet,
+    server::RequestInfo,
+    store::{
+        file::{FileConfig, Journal, DEFAULT_MAX_JOURNAL_ENTRIES},
+        in_memory::InMemoryAuthority,
     },
 };
+#[cfg(feature = "dnssec")]
 use crate::{
-    authority::{Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneType},
+    authority::{DnssecAuthority, KeyRole, KeyTimings, UpdateRequest},
     client::{
-        rr::{LowerName, Name, RecordSet, RecordType, RrKey},
-        serialize::txt::{Lexer, Parser, Token},
+        proto::rr::dnssec::rdata::{key::KEY, NSEC3PARAM},
+        rr::dnssec::{tsig::TSigner, DnsSecResult, SigSigner},
     },
-    server::RequestInfo,
-    store::{file::FileConfig, in_memory::InMemoryAuthority},
+    proto::rr::Record,
 };
 
 /// FileAuthority is responsible for storing the resource records for a particular zone.
 ///
 /// Authorities default to DNSClass IN. The ZoneType specifies if this should be treated as the
 /// start of authority for the zone, is a Secondary, or a cached zone.
-pub struct FileAuthority(InMemoryAuthority);
-
-/// Max traversal depth for $INCLUDE files
-const MAX_INCLUDE_LEVEL: u16 = 256;
-
-/// Inner state of zone file loader, tracks depth of $INCLUDE
-/// loads as well as visited previously files, so the loader
-/// is able to abort e.g. when cycle is detected
 ///
-/// Note, that tracking max depth level explicitly covers also
-/// cycles in $INCLUDEs. The error description in this case would
-/// not be very helpful to detect the root cause of the problem
-/// though. The way to improve diagnose experience would be to
-/// traverse $INCLUDE files in topologically sorted order which
-/// requires quite some re-arrangements in the code and in the
-/// way loader is currently implemented.
-struct FileReaderState {
-    level: u16,
-}
-
-impl FileReaderState {
-    fn new() -> Self {
-        Self { level: 0 }
-    }
-
-    fn next_level(&self) -> Self {
-        Self {
-            level: self.level + 1,
-        }
-    }
+/// When dynamic updates are allowed, each update is appended to a journal before it is applied,
+///  and the zone file is rewritten once the journal reaches `max_journal_entries`, see `compact()`.
+pub struct FileAuthority {
+    in_memory: InMemoryAuthority,
+    journal: Mutex<Option<Journal>>,
+    zone_path: Option<PathBuf>,
+    #[cfg_attr(not(feature = "dnssec"), allow(dead_code))]
+    allow_update: bool,
+    #[cfg_attr(not(feature = "dnssec"), allow(dead_code))]
+    max_journal_entries: usize,
 }
 
 impl FileAuthority {
@@ -80,102 +68,29 @@ impl FileAuthority {
     ///              record.
     /// * `records` - The map of the initial set of records in the zone.
     /// * `zone_type` - The type of zone, i.e. is this authoritative?
-    /// * `allow_update` - If true, then this zone accepts dynamic updates.
-    /// * `is_dnssec_enabled` - If true, then the zone will sign the zone with all registered keys,
-    ///                         (see `add_zone_signing_key()`)
+    /// * `allow_axfr` - If true, then this zone allows zone transfers.
     ///
     /// # Return value
     ///
-    /// The new `Authority`.
+    /// The new `Authority`, it does not accept dynamic updates.
     pub fn new(
         origin: Name,
         records: BTreeMap<RrKey, RecordSet>,
         zone_type: ZoneType,
         allow_axfr: bool,
     ) -> Result<Self, String> {
-        InMemoryAuthority::new(origin, records, zone_type, allow_axfr).map(Self)
-    }
-
-    /// Read given file line by line and recursively invokes reader for
-    /// $INCLUDE directives
-    ///
-    /// TODO: it looks hacky as far we effectively duplicate parser's functionallity
-    /// (at least partially) and performing lexing twice.
-    /// Better solution requires us to change lexer to deal
-    /// with Lines-like iterator instead of String buf (or capability to combine a few
-    /// lexer instances into a single lexer).
-    ///
-    /// TODO: $INCLUDE could specify domain name -- to support on-flight swap for Origin
-    /// value we definitely need to rethink and rework loader/parser/lexer
-    fn read_file(
-        zone_path: PathBuf,
-        buf: &mut String,
-        state: FileReaderState,
-    ) -> Result<(), String> {
-        let file = File::open(&zone_path)
-            .map_err(|e| format!("failed to read {}: {:?}", zone_path.display(), e))?;
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let content = line.map_err(|err| format!("failed to read line: {:?}", err))?;
-            let mut lexer = Lexer::new(&content);
-
-            match (lexer.next_token(), lexer.next_token(), lexer.next_token()) {
-                (
-                    Ok(Some(Token::Include)),
-                    Ok(Some(Token::CharData(include_path))),
-                    Ok(Some(Token::CharData(_domain))),
-                ) => {
-                    return Err(format!(
-                        "Domain name for $INCLUDE is not supported at {}, trying to include {}",
-                        zone_path.display(),
-                        include_path
-                    ));
-                }
-                (Ok(Some(Token::Include)), Ok(Some(Token::CharData(include_path))), _) => {
-                    // RFC1035 (section 5) does not specify how filename for $INCLUDE
-                    // should be resolved into file path. The underlying code implements the
-                    // following:
-                    // * if the path is absolute (relies on Path::is_absolute), it uses normalized path
-                    // * otherwise, it joins the path with parent root of the current file
-                    //
-                    // TODO: Inlining files specified using non-relative path might potentially introduce
-                    // security issue in some cases (e.g. when working with zone files from untrusted sources)
-                    // and should probably be configurable by user.
-                    let include_path = Path::new(&include_path);
-                    let include_zone_path = if include_path.is_absolute() {
-                        include_path.to_path_buf()
-                    } else {
-                        let parent_dir =
-                            zone_path.parent().expect("file has to have parent folder");
-                        parent_dir.join(include_path)
-                    };
-
-                    if state.level >= MAX_INCLUDE_LEVEL {
-                        return Err(format!("Max depth level for nested $INCLUDE is reached at {}, trying to include {}", zone_path.display(), include_zone_path.display()));
-                    }
-
-                    let mut include_buf = String::new();
-
-                    info!(
-                        "including file {} into {}",
-                        include_zone_path.display(),
-                        zone_path.display()
-                    );
-
-                    Self::read_file(include_zone_path, &mut include_buf, state.next_level())?;
-                    buf.push_str(&include_buf);
-                }
-                _ => {
-                    buf.push_str(&content);
-                }
-            }
-
-            buf.push('\n');
-        }
-        Ok(())
+        InMemoryAuthority::new(origin, records, zone_type, allow_axfr).map(|in_memory| Self {
+            in_memory,
+            journal: Mutex::new(None),
+            zone_path: None,
+            allow_update: false,
+            max_journal_entries: DEFAULT_MAX_JOURNAL_ENTRIES,
+        })
     }
 
     /// Read the Authority for the origin from the specified configuration
+    ///
+    /// Any updates in the journal which are not yet part of the zone file are applied to the zone.
     pub fn try_from_config(
         origin: Name,
         zone_type: ZoneType,
@@ -185,19 +100,18 @@ impl FileAuthority {
     ) -> Result<Self, String> {
         let root_dir_path = root_dir.map(PathBuf::from).unwrap_or_else(PathBuf::new);
         let zone_path = root_dir_path.join(&config.zone_file_path);
+        let journal_path = config
+            .journal_file_path
+            .as_ref()
+            .map(|path| root_dir_path.join(path))
+            .unwrap_or_else(|| zone_path.with_extension("jnl"));
 
         info!("loading zone file: {:?}", zone_path);
 
-        let mut buf = String::new();
-
         // TODO: this should really use something to read line by line or some other method to
         //  keep the usage down. and be a custom lexer...
-        Self::read_file(zone_path, &mut buf, FileReaderState::new())
-            .map_err(|e| format!("failed to read {}: {:?}", &config.zone_file_path, e))?;
-
-        let lexer = Lexer::new(&buf);
         let (origin, records) = Parser::new()
-            .parse(lexer, Some(origin), None)
+            .parse_file(&zone_path, Some(origin), None)
             .map_err(|e| format!("failed to parse {}: {:?}", config.zone_file_path, e))?;
 
         info!(
@@ -207,26 +121,209 @@ impl FileAuthority {
         );
         debug!("zone: {:#?}", records);
 
-        Self::new(origin, records, zone_type, allow_axfr)
+        let mut authority = Self::new(origin, records, zone_type, allow_axfr)?;
+        authority.zone_path = Some(zone_path);
+        authority.allow_update = config.allow_update;
+        authority.max_journal_entries = config.max_journal_entries();
+
+        #[cfg(feature = "trust-dns-resolver")]
+        if let Some(aname) = &config.aname {
+            authority.in_memory.set_aname_config(aname)?;
+        }
+
+        #[cfg(feature = "geoip")]
+        if let Some(geo) = &config.geo {
+            authority.in_memory.set_geo_config(geo, root_dir)?;
+        }
+
+        authority.in_memory.set_pools_config(&config.pools)?;
+
+        if !config.allow_update && !journal_path.exists() {
+            return Ok(authority);
+        }
+
+        let mut journal = Journal::from_file(&journal_path)
+            .map_err(|e| format!("failed to open journal {:?}: {}", journal_path, e))?;
+
+        if !journal.is_empty() {
+            info!("recovering zone from journal: {:?}", journal_path);
+            if !config.allow_update {
+                warn!("updates are not allowed, but the journal will be applied");
+            }
+
+            // nothing else has a reference to the authority yet, so this will not block
+            futures_executor::block_on(authority.recover_with_journal(&mut journal))
+                .map_err(|e| format!("error recovering from journal: {}", e))?;
+        }
+
+        if config.allow_update {
+            *authority.journal.get_mut() = Some(journal);
+        }
+
+        Ok(authority)
+    }
+
+    /// Applies the updates in the journal which are not yet part of the zone
+    ///
+    /// The journal is only cleared after the zone file is rewritten, so entries with a serial older
+    ///  than that of the zone were written to the zone file before the journal could be cleared.
+    async fn recover_with_journal(&self, journal: &mut Journal) -> PersistenceResult<()> {
+        for (serial, records) in journal.entries()? {
+            let zone_serial = self.in_memory.serial().await;
+            if serial_lt(serial, zone_serial) {
+                debug!(
+                    "skipping journal entry: {} older than zone: {}",
+                    serial, zone_serial
+                );
+                continue;
+            }
+
+            let previous = self.in_memory.records().await;
+            let updated = self
+                .in_memory
+                .update_records(&records)
+                .await
+                .map_err(|_| PersistenceErrorKind::Recovery("failed to apply update"))?;
+
+            if updated {
+                self.in_memory.increment_soa_serial().await;
+
+                // keep the recovered changes for IXFR, secondaries are notified once serving
+                let records = self.in_memory.records().await;
+                if let Some(diff) = ZoneDiff::between(self.origin(), &previous, &records) {
+                    self.in_memory.push_diff(diff).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the update records to the zone, after appending them to the journal
+    #[cfg(feature = "dnssec")]
+    async fn update_records(&self, records: &[Record]) -> UpdateResult<bool> {
+        // hold the journal for the entire update, so that updates are journaled in the order
+        //  they are applied
+        let mut journal = self.journal.lock().await;
+        let serial = self.in_memory.serial().await;
+
+        if let Some(journal) = journal.as_mut() {
+            if let Err(error) = journal.append(serial, records) {
+                error!("could not persist update records: {}", error);
+                return Err(ResponseCode::ServFail);
+            }
+        }
+
+        let previous = self.in_memory.records().await;
+        let updated = self.in_memory.update_records(records).await?;
+
+        if updated {
+            if self.in_memory.is_signed().await {
+                // the secure_zone() function increments the SOA during it's operation
+                DnssecAuthority::secure_zone(&self.in_memory)
+                    .await
+                    .map_err(|e| {
+                        error!("failure securing zone: {}", e);
+                        ResponseCode::ServFail
+                    })?;
+            } else {
+                self.in_memory.increment_soa_serial().await;
+            }
+
+            self.in_memory.zone_changed(&previous).await;
+        }
+
+        if let Some(journal) = journal.as_mut() {
+            if journal.len() >= self.max_journal_entries {
+                // the update is in the journal, so it's not lost if this fails
+                if let Err(error) = self.write_zone_file(journal).await {
+                    warn!("could not rewrite zone file: {}", error);
+                }
+            }
+        }
+
+        Ok(updated)
+    }
+
+    /// Rewrites the zone file with the current records of the zone, and clears the journal
+    ///
+    /// This is done automatically once `max_journal_entries` updates have been journaled. RRSIGs
+    ///  are not written, nor are NSEC or NSEC3 records if the zone is signed, these are generated
+    ///  when the zone is loaded.
+    pub async fn compact(&self) -> PersistenceResult<()> {
+        match self.journal.lock().await.as_mut() {
+            Some(journal) => self.write_zone_file(journal).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn write_zone_file(&self, journal: &mut Journal) -> PersistenceResult<()> {
+        let zone_path = match self.zone_path {
+            Some(ref zone_path) => zone_path,
+            None => return Ok(()),
+        };
+
+        #[cfg(feature = "dnssec")]
+        let is_signed = self.in_memory.is_signed().await;
+        #[cfg(not(feature = "dnssec"))]
+        let is_signed = false;
+
+        let records = self
+            .in_memory
+            .records()
+            .await
+            .into_iter()
+            .filter(|(key, _)| {
+                !(is_signed && matches!(key.record_type, RecordType::NSEC | RecordType::NSEC3))
+            })
+            .map(|(key, rrset)| {
+                let mut rrset = RecordSet::clone(&rrset);
+                rrset.clear_rrsigs();
+                (key, rrset)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut writer = Writer::new(String::new());
+        writer
+            .write_zone(&Name::from(self.in_memory.origin()), &records)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "failed to format zone"))?;
+
+        // write to a temporary file first, so that the zone file is always complete
+        let mut tmp_path = OsString::from(zone_path.as_os_str());
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(writer.into_inner().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, zone_path)?;
+
+        info!("rewrote zone file: {:?}", zone_path);
+        journal.clear()
     }
 
     /// Unwrap the InMemoryAuthority
     pub fn unwrap(self) -> InMemoryAuthority {
-        self.0
+        self.in_memory
     }
 }
 
+/// Compares serials with serial number arithmetic, see RFC 1982
+pub(crate) fn serial_lt(serial: u32, other: u32) -> bool {
+    serial != other && other.wrapping_sub(serial) < 1 << 31
+}
+
 impl Deref for FileAuthority {
     type Target = InMemoryAuthority;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.in_memory
     }
 }
 
 impl DerefMut for FileAuthority {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.in_memory
     }
 }
 
@@ -236,23 +333,84 @@ impl Authority for FileAuthority {
 
     /// What type is this zone
     fn zone_type(&self) -> ZoneType {
-        self.0.zone_type()
+        self.in_memory.zone_type()
     }
 
     /// Return true if AXFR is allowed
     fn is_axfr_allowed(&self) -> bool {
-        self.0.is_axfr_allowed()
+        self.in_memory.is_axfr_allowed()
     }
 
     /// Perform a dynamic update of a zone
+    ///
+    /// The update is authorized with TSIG or SIG(0), see `add_update_tsig_key` and
+    ///  `add_update_auth_key`, and journaled before it is applied.
+    #[cfg(feature = "dnssec")]
+    async fn update(&self, update: &MessageRequest) -> UpdateResult<bool> {
+        if !self.allow_update {
+            warn!("update attempted on non-updatable zone: {}", self.origin());
+            return Err(ResponseCode::Refused);
+        }
+
+        // the spec says to authorize after prereqs, seems better to auth first.
+        self.in_memory.authorize_update(update).await?;
+        self.in_memory
+            .verify_prerequisites(update.prerequisites())
+            .await?;
+        self.in_memory.pre_scan(update.updates()).await?;
+
+        self.update_records(update.updates()).await
+    }
+
+    /// Always fail when DNSSEC is disabled.
+    #[cfg(not(feature = "dnssec"))]
     async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
-        use crate::proto::op::ResponseCode;
         Err(ResponseCode::NotImp)
     }
 
+    /// The TSIG key which signed the update, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn update_tsigner(&self, update: &MessageRequest) -> Option<TSigner> {
+        self.in_memory.update_tsigner(update).await
+    }
+
+    /// Checks that the client is permitted to transfer the zone
+    async fn authorize_transfer(&self, request: &MessageRequest, src: IpAddr) -> UpdateResult<()> {
+        self.in_memory.authorize_transfer(request, src).await
+    }
+
+    /// The TSIG key which signed the transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn transfer_tsigner(&self, request: &MessageRequest) -> Option<TSigner> {
+        self.in_memory.transfer_tsigner(request).await
+    }
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR
+    async fn ixfr_diffs(&self, serial: u32) -> Option<Vec<ZoneDiff>> {
+        self.in_memory.ixfr_diffs(serial).await
+    }
+
+    /// Replaces the TXT records at the name with the values, for answering ACME challenges
+    async fn set_challenge_txt(&self, name: &LowerName, values: &[String]) -> UpdateResult<()> {
+        self.in_memory.set_challenge_txt(name, values).await
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
-        self.0.origin()
+        self.in_memory.origin()
+    }
+
+    /// The scope of the answers for the client subnet, see `InMemoryAuthority`
+    fn client_subnet_scope(&self, client_subnet: &ClientSubnet) -> u8 {
+        self.in_memory.client_subnet_scope(client_subnet)
+    }
+
+    /// Rewrites the zone file with the journaled updates, see `compact()`, unless there are none
+    async fn persist(&self) -> PersistenceResult<()> {
+        match self.journal.lock().await.as_mut() {
+            Some(journal) if !journal.is_empty() => self.write_zone_file(journal).await,
+            _ => Ok(()),
+        }
     }
 
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
@@ -275,7 +433,7 @@ impl Authority for FileAuthority {
         rtype: RecordType,
         lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
-        self.0.lookup(name, rtype, lookup_options).await
+        self.in_memory.lookup(name, rtype, lookup_options).await
     }
 
     /// Using the specified query, perform a lookup against this zone.
@@ -294,12 +452,12 @@ impl Authority for FileAuthority {
         request_info: RequestInfo<'_>,
         lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
-        self.0.search(request_info, lookup_options).await
+        self.in_memory.search(request_info, lookup_options).await
     }
 
     /// Get the NS, NameServer, record for the zone
     async fn ns(&self, lookup_options: LookupOptions) -> Result<Self::Lookup, LookupError> {
-        self.0.ns(lookup_options).await
+        self.in_memory.ns(lookup_options).await
     }
 
     /// Return the NSEC records based on the given name
@@ -314,7 +472,7 @@ impl Authority for FileAuthority {
         name: &LowerName,
         lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
-        self.0.get_nsec_records(name, lookup_options).await
+        self.in_memory.get_nsec_records(name, lookup_options).await
     }
 
     /// Returns the SOA of the authority.
@@ -322,12 +480,12 @@ impl Authority for FileAuthority {
     /// *Note*: This will only return the SOA, if this is fulfilling a request, a standard lookup
     ///  should be used, see `soa_secure()`, which will optionally return RRSIGs.
     async fn soa(&self) -> Result<Self::Lookup, LookupError> {
-        self.0.soa().await
+        self.in_memory.soa().await
     }
 
     /// Returns the SOA record for the zone
     async fn soa_secure(&self, lookup_options: LookupOptions) -> Result<Self::Lookup, LookupError> {
-        self.0.soa_secure(lookup_options).await
+        self.in_memory.soa_secure(lookup_options).await
     }
 }
 
@@ -337,17 +495,58 @@ impl Authority for FileAuthority {
 impl DnssecAuthority for FileAuthority {
     /// Add a (Sig0) key that is authorized to perform updates against this authority
     async fn add_update_auth_key(&self, name: Name, key: KEY) -> DnsSecResult<()> {
-        self.0.add_update_auth_key(name, key).await
+        self.in_memory.add_update_auth_key(name, key).await
+    }
+
+    /// Add a TSIG key that is authorized to perform updates against this authority
+    async fn add_update_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.in_memory.add_update_tsig_key(signer).await
+    }
+
+    /// Add a TSIG key that is authorized to transfer the zone from this authority
+    async fn add_transfer_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.in_memory.add_transfer_tsig_key(signer).await
     }
 
     /// Add Signer
     async fn add_zone_signing_key(&self, signer: SigSigner) -> DnsSecResult<()> {
-        self.0.add_zone_signing_key(signer).await
+        self.in_memory.add_zone_signing_key(signer).await
+    }
+
+    /// Add a Signer which signs the zone as scheduled
+    async fn add_scheduled_signing_key(
+        &self,
+        signer: SigSigner,
+        role: KeyRole,
+        timings: KeyTimings,
+    ) -> DnsSecResult<()> {
+        self.in_memory
+            .add_scheduled_signing_key(signer, role, timings)
+            .await
+    }
+
+    /// Publishes the keys as scheduled and re-signs the zone, notifying secondaries of changes
+    async fn maintain_keys(&self, now: u64) -> DnsSecResult<bool> {
+        // keeps updates from being applied in between
+        let _journal = self.journal.lock().await;
+
+        let previous = self.in_memory.records().await;
+        let changed = self.in_memory.maintain_keys(now).await?;
+        if changed {
+            self.in_memory.zone_changed(&previous).await;
+        }
+
+        Ok(changed)
+    }
+
+    /// Signs the zone with NSEC3 instead of NSEC
+    async fn set_nsec3(&self, nsec3param: NSEC3PARAM, opt_out: bool) -> DnsSecResult<()> {
+        self.in_memory.set_nsec3(nsec3param, opt_out).await
     }
 
     /// Sign the zone for DNSSEC
     async fn secure_zone(&self) -> DnsSecResult<()> {
-        DnssecAuthority::secure_zone(&self.0).await
+        DnssecAuthority::secure_zone(&self.in_memory).await
     }
 }
 
@@ -356,23 +555,34 @@ mod tests {
     use std::net::Ipv4Addr;
     use std::str::FromStr;
 
-    use crate::client::rr::RData;
+    use crate::client::rr::{RData, Record};
     use futures_executor::block_on;
 
     use super::*;
     use crate::authority::ZoneType;
 
+    const ZONE: &str = "$TTL 86400
+@       IN  SOA ns.example.com. root.example.com. (
+                2021010100 ; Serial
+                28800      ; Refresh
+                7200       ; Retry
+                604800     ; Expire
+                86400 )    ; Minimum TTL
+        IN  NS  ns.example.com.
+ns      IN  A   127.0.0.1
+www     IN  A   127.0.0.1
+";
+
     #[test]
     fn test_load_zone() {
         #[cfg(feature = "dnssec")]
-        let config = FileConfig {
-            zone_file_path: "../../tests/test-data/named_test_configs/dnssec/example.com.zone"
-                .to_string(),
-        };
+        let config = FileConfig::new(
+            "../../tests/test-data/named_test_configs/dnssec/example.com.zone".to_string(),
+        );
         #[cfg(not(feature = "dnssec"))]
-        let config = FileConfig {
-            zone_file_path: "../../tests/test-data/named_test_configs/example.com.zone".to_string(),
-        };
+        let config = FileConfig::new(
+            "../../tests/test-data/named_test_configs/example.com.zone".to_string(),
+        );
         let authority = FileAuthority::try_from_config(
             Name::from_str("example.com.").unwrap(),
             ZoneType::Primary,
@@ -418,4 +628,70 @@ mod tests {
             _ => panic!("wrong rdata type returned"),
         }
     }
+
+    #[test]
+    fn test_journal_recovery_and_compact() {
+        let dir =
+            std::env::temp_dir().join(format!("trust-dns-file-authority-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("example.com.zone"), ZONE).unwrap();
+        let _ = fs::remove_file(dir.join("example.com.jnl"));
+
+        let origin = Name::from_str("example.com.").unwrap();
+        let www = LowerName::from_str("www.example.com.").unwrap();
+
+        // an update which was journaled, but the zone file was not rewritten
+        let mut journal = Journal::from_file(&dir.join("example.com.jnl")).unwrap();
+        journal
+            .append(
+                2021010100,
+                &[Record::from_rdata(
+                    Name::from(&www),
+                    86400,
+                    RData::A(Ipv4Addr::new(127, 0, 0, 2)),
+                )],
+            )
+            .unwrap();
+        drop(journal);
+
+        let mut config = FileConfig::new("example.com.zone".to_string());
+        config.allow_update = true;
+
+        let load = || {
+            FileAuthority::try_from_config(
+                origin.clone(),
+                ZoneType::Primary,
+                false,
+                Some(&dir),
+                &config,
+            )
+            .expect("failed to load zone")
+        };
+        let lookup_www = |authority: &FileAuthority| {
+            block_on(Authority::lookup(
+                authority,
+                &www,
+                RecordType::A,
+                LookupOptions::default(),
+            ))
+            .expect("lookup failed")
+            .into_iter()
+            .count()
+        };
+
+        let authority = load();
+        assert_eq!(block_on(authority.serial()), 2021010101);
+        assert_eq!(lookup_www(&authority), 2);
+
+        block_on(authority.compact()).expect("failed to compact");
+        drop(authority);
+        assert_eq!(fs::metadata(dir.join("example.com.jnl")).unwrap().len(), 0);
+
+        // the update is now part of the zone file
+        let authority = load();
+        assert_eq!(block_on(authority.serial()), 2021010101);
+        assert_eq!(lookup_www(&authority), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
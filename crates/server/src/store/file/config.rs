@@ -7,9 +7,64 @@
 
 use serde::Deserialize;
 
+#[cfg(feature = "trust-dns-resolver")]
+use crate::store::in_memory::AnameConfig;
+#[cfg(feature = "geoip")]
+use crate::store::in_memory::GeoConfig;
+use crate::store::in_memory::PoolConfig;
+
+/// The default number of updates to journal before the zone file is rewritten
+pub const DEFAULT_MAX_JOURNAL_ENTRIES: usize = 100;
+
 /// Configuration for file based zones
 #[derive(Deserialize, PartialEq, Eq, Debug)]
 pub struct FileConfig {
     /// path to the zone file
     pub zone_file_path: String,
+    /// path to the journal of updates, defaults to the zone file path with a `jnl` extension
+    #[serde(default)]
+    pub journal_file_path: Option<String>,
+    /// Are updates allowed to this zone
+    #[serde(default)]
+    pub allow_update: bool,
+    /// number of updates to journal before the zone file is rewritten, defaults to
+    ///  `DEFAULT_MAX_JOURNAL_ENTRIES`
+    #[serde(default)]
+    pub max_journal_entries: Option<usize>,
+    /// Resolution of ANAME (or ALIAS) targets outside of the zone, into A and AAAA answers
+    #[cfg(feature = "trust-dns-resolver")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolver")))]
+    #[serde(default)]
+    pub aname: Option<AnameConfig>,
+    /// Answers which depend on the location of the client
+    #[cfg(feature = "geoip")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "geoip")))]
+    #[serde(default)]
+    pub geo: Option<GeoConfig>,
+    /// Pools of addresses answered by weight and health, in place of the records of the zone
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
+}
+
+impl FileConfig {
+    /// Configuration for a zone file which does not accept updates
+    pub fn new(zone_file_path: String) -> Self {
+        Self {
+            zone_file_path,
+            journal_file_path: None,
+            allow_update: false,
+            max_journal_entries: None,
+            #[cfg(feature = "trust-dns-resolver")]
+            aname: None,
+            #[cfg(feature = "geoip")]
+            geo: None,
+            pools: Vec::new(),
+        }
+    }
+
+    /// number of updates to journal before the zone file is rewritten
+    pub fn max_journal_entries(&self) -> usize {
+        self.max_journal_entries
+            .unwrap_or(DEFAULT_MAX_JOURNAL_ENTRIES)
+    }
 }
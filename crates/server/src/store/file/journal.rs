@@ -0,0 +1,234 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Write-ahead log of dynamic updates to a zone file
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use tracing::warn;
+
+use crate::{
+    error::PersistenceResult,
+    proto::{
+        error::ProtoError,
+        rr::Record,
+        serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder},
+    },
+};
+
+/// The Journal is the log of all updates to a zone since its zone file was last written.
+///
+/// Each entry holds the records of a single update, along with the serial of the zone before the
+///  update was applied. Entries are prefixed with their length, so that an entry which was only
+///  partially written, e.g. on a crash, can be detected and dropped.
+pub struct Journal {
+    file: File,
+    len: usize,
+}
+
+impl Journal {
+    /// Opens the journal at the specified path, creating it if it does not exist
+    pub fn from_file(journal_file: &Path) -> PersistenceResult<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(journal_file)?;
+
+        let mut journal = Self { file, len: 0 };
+        journal.len = journal.entries()?.len();
+        Ok(journal)
+    }
+
+    /// Returns all the entries of the journal, in the order they were appended
+    ///
+    /// An incomplete entry at the end of the journal is discarded.
+    pub fn entries(&mut self) -> PersistenceResult<Vec<(u32, Vec<Record>)>> {
+        let mut bytes = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut bytes)?;
+
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let remaining = &bytes[offset..];
+            if remaining.len() < 4 {
+                break;
+            }
+
+            let mut len = [0u8; 4];
+            len.copy_from_slice(&remaining[..4]);
+            let len = u32::from_be_bytes(len) as usize;
+            if remaining.len() - 4 < len {
+                break;
+            }
+
+            entries.push(Self::read_entry(&remaining[4..4 + len])?);
+            offset += 4 + len;
+        }
+
+        if offset < bytes.len() {
+            warn!(
+                "discarding incomplete entry of {} bytes at the end of the journal",
+                bytes.len() - offset
+            );
+            self.file.set_len(offset as u64)?;
+        }
+
+        Ok(entries)
+    }
+
+    fn read_entry(bytes: &[u8]) -> PersistenceResult<(u32, Vec<Record>)> {
+        let mut decoder = BinDecoder::new(bytes);
+        let serial = decoder
+            .read_u32()
+            .map_err(ProtoError::from)?
+            .unverified(/*any u32 is a valid serial*/);
+        let count = decoder
+            .read_u16()
+            .map_err(ProtoError::from)?
+            .unverified(/*any count is valid*/);
+
+        let mut records = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            records.push(Record::read(&mut decoder)?);
+        }
+
+        Ok((serial, records))
+    }
+
+    /// Appends the records of an update to the journal, this returns once they are on disk
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the serial of the zone before the update is applied
+    /// * `records` - the update records, see `UpdateMessage::updates`
+    pub fn append(&mut self, serial: u32, records: &[Record]) -> PersistenceResult<()> {
+        let mut body = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut body);
+            encoder.emit_u32(serial)?;
+            encoder.emit_u16(records.len() as u16)?;
+            for record in records {
+                record.emit(&mut encoder)?;
+            }
+        }
+
+        let mut entry = Vec::with_capacity(4 + body.len());
+        entry.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        entry.extend_from_slice(&body);
+
+        self.file.write_all(&entry)?;
+        self.file.sync_data()?;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// The number of entries in the journal
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no entries in the journal
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all entries, this should be done after the zone file has been rewritten
+    pub fn clear(&mut self) -> PersistenceResult<()> {
+        self.file.set_len(0)?;
+        self.file.sync_data()?;
+        self.len = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use crate::proto::rr::{DNSClass, Name, RData, RecordType};
+
+    use super::*;
+
+    fn journal_path(test_name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "trust-dns-file-journal-{}-{}.jnl",
+            test_name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn records() -> Vec<Record> {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut delete = Record::with(name.clone(), RecordType::A, 0);
+        delete.set_dns_class(DNSClass::ANY);
+
+        vec![
+            delete,
+            Record::from_rdata(name, 86400, RData::A(Ipv4Addr::new(127, 0, 0, 2))),
+        ]
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let path = journal_path("append");
+
+        let mut journal = Journal::from_file(&path).expect("failed to create journal");
+        assert!(journal.is_empty());
+        journal.append(1, &records()).unwrap();
+        journal.append(2, &records()[1..]).unwrap();
+        drop(journal);
+
+        let mut journal = Journal::from_file(&path).expect("failed to open journal");
+        assert_eq!(journal.len(), 2);
+
+        let entries = journal.entries().unwrap();
+        assert_eq!(entries[0], (1, records()));
+        assert_eq!(entries[1], (2, records()[1..].to_vec()));
+
+        journal.clear().unwrap();
+        assert!(journal.entries().unwrap().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_incomplete_entry_is_discarded() {
+        let path = journal_path("incomplete");
+
+        let mut journal = Journal::from_file(&path).expect("failed to create journal");
+        journal.append(1, &records()).unwrap();
+        drop(journal);
+
+        // simulate a crash while writing the second entry
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0, 0, 0, 64, 0, 0]).unwrap();
+        drop(file);
+
+        let mut journal = Journal::from_file(&path).expect("failed to open journal");
+        assert_eq!(journal.len(), 1);
+
+        journal.append(2, &records()).unwrap();
+        let entries = journal.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].0, 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -9,6 +9,11 @@
 
 mod authority;
 mod config;
+mod journal;
+mod zone_check;
 
+pub(crate) use self::authority::serial_lt;
 pub use self::authority::FileAuthority;
-pub use self::config::FileConfig;
+pub use self::config::{FileConfig, DEFAULT_MAX_JOURNAL_ENTRIES};
+pub use self::journal::Journal;
+pub use self::zone_check::{ZoneCheck, ZoneProblem};
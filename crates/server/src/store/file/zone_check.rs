@@ -0,0 +1,267 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Checks of zone files before they are served, like `named-checkzone` of BIND
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use crate::client::{
+    error::ParseResult,
+    rr::{LowerName, Name, RecordSet, RecordType, RrKey},
+    serialize::txt::{Lexer, Parser, Writer},
+};
+#[cfg(feature = "dnssec")]
+use crate::{
+    client::rr::RData,
+    proto::rr::{dnssec::rdata::DNSSECRData, Record},
+    store::file::serial_lt,
+};
+
+/// A problem of a zone, found by [`ZoneCheck::check`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ZoneProblem {
+    /// There is no SOA record at the origin
+    MissingSoa,
+    /// There are no NS records at the origin
+    MissingNs,
+    /// A CNAME record has the name of other records, other than its RRSIGs and NSEC
+    CnameAndOtherData(Name),
+    /// A record is not in the zone
+    OutOfZone(Name, RecordType),
+    /// An RRSIG has expired, or expires before the time of the check
+    SignatureExpired {
+        /// The name of the signed records
+        name: Name,
+        /// The type of the signed records
+        type_covered: RecordType,
+        /// The expiration time, in seconds since the epoch
+        expiration: u32,
+    },
+    /// An RRSIG is not valid before a time later than the time of the check
+    SignatureNotYetValid {
+        /// The name of the signed records
+        name: Name,
+        /// The type of the signed records
+        type_covered: RecordType,
+        /// The inception time, in seconds since the epoch
+        inception: u32,
+    },
+}
+
+impl fmt::Display for ZoneProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSoa => write!(f, "no SOA record at the origin"),
+            Self::MissingNs => write!(f, "no NS records at the origin"),
+            Self::CnameAndOtherData(name) => write!(f, "{}: CNAME and other data", name),
+            Self::OutOfZone(name, record_type) => {
+                write!(f, "{} {}: out of zone data", name, record_type)
+            }
+            Self::SignatureExpired {
+                name,
+                type_covered,
+                expiration,
+            } => write!(
+                f,
+                "{} {}: RRSIG expired at {}",
+                name, type_covered, expiration
+            ),
+            Self::SignatureNotYetValid {
+                name,
+                type_covered,
+                inception,
+            } => write!(
+                f,
+                "{} {}: RRSIG not valid before {}",
+                name, type_covered, inception
+            ),
+        }
+    }
+}
+
+/// The records of a zone, e.g. of a zone file, to check for problems before they are served
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use trust_dns_server::client::rr::Name;
+/// use trust_dns_server::store::file::{ZoneCheck, ZoneProblem};
+///
+/// let zone = ZoneCheck::parse(
+///     "@ 3600 IN SOA ns.example.com. hostmaster.example.com. 1 3600 600 86400 300\n\
+///      www 300 IN CNAME example.com.\n\
+///      www 300 IN A 192.0.2.1\n",
+///     Some(Name::from_str("example.com.").unwrap()),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(
+///     zone.check(0),
+///     vec![
+///         ZoneProblem::MissingNs,
+///         ZoneProblem::CnameAndOtherData(Name::from_str("www.example.com.").unwrap()),
+///     ]
+/// );
+/// ```
+pub struct ZoneCheck {
+    origin: Name,
+    records: BTreeMap<RrKey, RecordSet>,
+}
+
+impl ZoneCheck {
+    /// The zone of the records, e.g. as returned from the [`Parser`]
+    pub fn new(origin: Name, records: BTreeMap<RrKey, RecordSet>) -> Self {
+        Self { origin, records }
+    }
+
+    /// Parses the zone file, including the files it includes
+    ///
+    /// The origin is required unless the file sets it with `$ORIGIN`.
+    pub fn from_file(path: &Path, origin: Option<Name>) -> ParseResult<Self> {
+        let (origin, records) = Parser::new().parse_file(path, origin, None)?;
+        Ok(Self::new(origin, records))
+    }
+
+    /// Parses the text of a zone file, which may not include other files
+    pub fn parse(zone: &str, origin: Option<Name>) -> ParseResult<Self> {
+        let (origin, records) = Parser::new().parse(Lexer::new(zone), origin, None)?;
+        Ok(Self::new(origin, records))
+    }
+
+    /// The origin of the zone
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// All the records of the zone
+    pub fn records(&self) -> &BTreeMap<RrKey, RecordSet> {
+        &self.records
+    }
+
+    /// Checks the zone, returns all its problems, none if it can be served as is
+    ///
+    /// The validity periods of the RRSIGs are checked against `now`, in seconds since the epoch.
+    ///  Signatures are only checked with the `dnssec` feature.
+    #[cfg_attr(not(feature = "dnssec"), allow(unused_variables))]
+    pub fn check(&self, now: u32) -> Vec<ZoneProblem> {
+        let origin = LowerName::from(&self.origin);
+        let mut problems = Vec::new();
+
+        if !self
+            .records
+            .contains_key(&RrKey::new(origin.clone(), RecordType::SOA))
+        {
+            problems.push(ZoneProblem::MissingSoa);
+        }
+        if !self
+            .records
+            .contains_key(&RrKey::new(origin.clone(), RecordType::NS))
+        {
+            problems.push(ZoneProblem::MissingNs);
+        }
+
+        // the records of a name are adjacent in the map, so the CNAME and other data of the name
+        //  are counted until the next name
+        let mut current: Option<&LowerName> = None;
+        let mut cname: Option<&Name> = None;
+        let mut other_data = false;
+        for (key, rrset) in &self.records {
+            if !origin.zone_of(&key.name) {
+                problems.push(ZoneProblem::OutOfZone(
+                    rrset.name().clone(),
+                    key.record_type,
+                ));
+            }
+
+            if current != Some(&key.name) {
+                if let (Some(cname), true) = (cname.take(), other_data) {
+                    problems.push(ZoneProblem::CnameAndOtherData(cname.clone()));
+                }
+                current = Some(&key.name);
+                other_data = false;
+            }
+            match key.record_type {
+                RecordType::CNAME => cname = Some(rrset.name()),
+                RecordType::RRSIG | RecordType::NSEC => (),
+                _ => other_data = true,
+            }
+
+            #[cfg(feature = "dnssec")]
+            for rrsig in rrset.records_without_rrsigs().chain(rrset.rrsigs()) {
+                problems.extend(check_signature(rrsig, now));
+            }
+        }
+
+        if let (Some(cname), true) = (cname, other_data) {
+            problems.push(ZoneProblem::CnameAndOtherData(cname.clone()));
+        }
+
+        problems
+    }
+
+    /// Writes the zone in canonical form, with the records in the canonical order of RFC 4034
+    ///
+    /// The owner names are lowercased and the records of each set are sorted by their RData, the
+    ///  SOA is written first. This is stable across changes to the formatting and order of the
+    ///  zone file, so that zone files can be compared.
+    pub fn canonical(&self) -> String {
+        let records = self
+            .records
+            .iter()
+            .map(|(key, rrset)| {
+                let name = Name::from(&key.name);
+                let mut records = rrset.records_without_rrsigs().cloned().collect::<Vec<_>>();
+                records.sort_by(|a, b| a.data().cmp(&b.data()));
+                let mut rrsigs = rrset.rrsigs().to_vec();
+                rrsigs.sort_by(|a, b| a.data().cmp(&b.data()));
+
+                let mut canonical = RecordSet::new(&name, key.record_type, 0);
+                for mut record in records {
+                    record.set_name(name.clone());
+                    canonical.insert(record, 0);
+                }
+                for mut rrsig in rrsigs {
+                    rrsig.set_name(name.clone());
+                    canonical.insert_rrsig(rrsig);
+                }
+                (key.clone(), canonical)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut writer = Writer::new(String::new());
+        writer
+            .write_zone(&self.origin.to_lowercase(), &records)
+            .expect("writing to a String failed");
+        writer.into_inner()
+    }
+}
+
+/// Checks the validity period of an RRSIG, none if the record is not an RRSIG
+#[cfg(feature = "dnssec")]
+fn check_signature(record: &Record, now: u32) -> Option<ZoneProblem> {
+    let sig = match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::SIG(sig))) if record.rr_type() == RecordType::RRSIG => sig,
+        _ => return None,
+    };
+
+    if !serial_lt(now, sig.sig_expiration()) {
+        Some(ZoneProblem::SignatureExpired {
+            name: record.name().clone(),
+            type_covered: sig.type_covered(),
+            expiration: sig.sig_expiration(),
+        })
+    } else if serial_lt(now, sig.sig_inception()) {
+        Some(ZoneProblem::SignatureNotYetValid {
+            name: record.name().clone(),
+            type_covered: sig.type_covered(),
+            inception: sig.sig_inception(),
+        })
+    } else {
+        None
+    }
+}
@@ -5,9 +5,11 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::io;
+#[cfg(feature = "dnssec")]
+use std::sync::Arc;
+use std::{io, net::IpAddr, time::Instant};
 
-use tracing::{debug, info};
+use tracing::{debug, debug_span, info, Instrument};
 
 use crate::{
     authority::{
@@ -17,19 +19,41 @@ use crate::{
         op::ResponseCode,
         rr::{LowerName, Name, Record, RecordType},
     },
+    dnstap::Dnstap,
+    proto::rr::rdata::opt::{ClientSubnet, EdnsOption, ExtendedErrorCode},
     resolver::{
-        config::ResolverConfig, lookup::Lookup as ResolverLookup, TokioAsyncResolver, TokioHandle,
+        config::{LookupOptions as ResolverLookupOptions, ResolverConfig, ResolverOpts},
+        error::{ResolveError, ResolveErrorKind},
+        lookup::Lookup as ResolverLookup,
+        AsyncResolver, TokioConnectionProvider, TokioHandle,
     },
     server::RequestInfo,
-    store::forwarder::ForwardConfig,
+    store::forwarder::{
+        dnstap::{DnstapConnection, DnstapConnectionProvider},
+        stale::StaleCache,
+        ForwardConfig, ForwardRule,
+    },
 };
 
+#[cfg(feature = "dnssec")]
+use crate::store::forwarder::dnstap::QuerySigner;
+
+type ForwardResolver = AsyncResolver<DnstapConnection, DnstapConnectionProvider>;
+
 /// An authority that will forward resolutions to upstream resolvers.
 ///
 /// This uses the trust-dns-resolver for resolving requests.
 pub struct ForwardAuthority {
     origin: LowerName,
-    resolver: TokioAsyncResolver,
+    resolver: ForwardResolver,
+    /// The rules of the domains forwarded to other resolvers, the most specific domain first
+    rules: Vec<DomainForwarder>,
+    conn_provider: DnstapConnectionProvider,
+    forward_client_subnet: bool,
+    client_subnet_ipv4_prefix: u8,
+    client_subnet_ipv6_prefix: u8,
+    /// The answers served if the upstream resolvers fail, if stale answers are served
+    stale: Option<StaleCache>,
 }
 
 impl ForwardAuthority {
@@ -37,12 +61,20 @@ impl ForwardAuthority {
     #[allow(clippy::new_without_default)]
     #[doc(hidden)]
     pub fn new(runtime: TokioHandle) -> Result<Self, String> {
-        let resolver = TokioAsyncResolver::from_system_conf(runtime)
+        let conn_provider =
+            DnstapConnectionProvider::new(TokioConnectionProvider::new(runtime), Name::root());
+        let resolver = AsyncResolver::from_system_conf_with_provider(conn_provider.clone())
             .map_err(|e| format!("error constructing new Resolver: {}", e))?;
 
         Ok(Self {
             origin: Name::root().into(),
             resolver,
+            rules: Vec::new(),
+            conn_provider,
+            forward_client_subnet: false,
+            client_subnet_ipv4_prefix: 24,
+            client_subnet_ipv6_prefix: 56,
+            stale: None,
         })
     }
 
@@ -55,40 +87,82 @@ impl ForwardAuthority {
         info!("loading forwarder config: {}", origin);
 
         let name_servers = config.name_servers.clone();
-        let mut options = config.options.unwrap_or_default();
-
-        // See RFC 1034, Section 4.3.2:
-        // "If the data at the node is a CNAME, and QTYPE doesn't match
-        // CNAME, copy the CNAME RR into the answer section of the response,
-        // change QNAME to the canonical name in the CNAME RR, and go
-        // back to step 1."
-        //
-        // Essentially, it's saying that servers (including forwarders)
-        // should emit any found CNAMEs in a response ("copy the CNAME
-        // RR into the answer section"). This is the behavior that
-        // preserve_intemediates enables when set to true, and disables
-        // when set to false. So we set it to true.
-        if !options.preserve_intermediates {
-            tracing::warn!(
-                "preserve_intermediates set to false, which is invalid \
-                for a forwarder; switching to true"
-            );
-            options.preserve_intermediates = true;
-        }
+        let options = forwarder_options(config.options.unwrap_or_default());
+
+        let forward_client_subnet = config.is_forward_client_subnet();
+        let client_subnet_ipv4_prefix = config.get_client_subnet_ipv4_prefix();
+        let client_subnet_ipv6_prefix = config.get_client_subnet_ipv6_prefix();
+        let stale = config
+            .get_max_stale()
+            .map(|max_stale| StaleCache::new(options.cache_size, max_stale));
+        let rules = &config.rules;
 
         let config = ResolverConfig::from_parts(None, vec![], name_servers);
 
-        let resolver = TokioAsyncResolver::new(config, options, TokioHandle::default())
+        let conn_provider = DnstapConnectionProvider::new(
+            TokioConnectionProvider::new(TokioHandle::default()),
+            origin.clone(),
+        );
+        let resolver = AsyncResolver::new_with_conn(config, options, conn_provider.clone())
             .map_err(|e| format!("error constructing new Resolver: {}", e))?;
 
+        let origin = LowerName::from(origin);
+        let mut rules = rules
+            .iter()
+            .map(|rule| DomainForwarder::try_from_rule(&origin, rule, options, &conn_provider))
+            .collect::<Result<Vec<_>, String>>()?;
+        rules.sort_by(|a, b| b.domain.num_labels().cmp(&a.domain.num_labels()));
+
         info!("forward resolver configured: {}: ", origin);
 
         // TODO: this might be infallible?
         Ok(Self {
-            origin: origin.into(),
+            origin,
             resolver,
+            rules,
+            conn_provider,
+            forward_client_subnet,
+            client_subnet_ipv4_prefix,
+            client_subnet_ipv6_prefix,
+            stale,
         })
     }
+
+    /// Logs the queries sent to the upstream resolvers, and their responses, with dnstap
+    ///
+    /// The connections of the rules share the dnstap of the zone.
+    pub fn set_dnstap(&mut self, dnstap: Option<Dnstap>) {
+        self.conn_provider.set_dnstap(dnstap);
+    }
+
+    /// The resolver forwarding the name, and whether the resolver of the zone is the fallback
+    fn forwarder_of(&self, name: &LowerName) -> (&ForwardResolver, bool) {
+        match self.rules.iter().find(|rule| rule.domain.zone_of(name)) {
+            Some(rule) => {
+                debug!("forwarding {} with the rule of {}", name, rule.domain);
+                (&rule.resolver, rule.fallback)
+            }
+            None => (&self.resolver, false),
+        }
+    }
+
+    /// The client subnet sent upstream, truncated to the configured source prefix length for privacy
+    fn forwarded_client_subnet(&self, client_subnet: &ClientSubnet) -> Option<ClientSubnet> {
+        if !self.forward_client_subnet {
+            return None;
+        }
+
+        let max_prefix = match client_subnet.address() {
+            IpAddr::V4(_) => self.client_subnet_ipv4_prefix,
+            IpAddr::V6(_) => self.client_subnet_ipv6_prefix,
+        };
+
+        Some(ClientSubnet::new(
+            client_subnet.address(),
+            client_subnet.source_prefix().min(max_prefix),
+            0,
+        ))
+    }
 }
 
 #[async_trait::async_trait]
@@ -118,21 +192,74 @@ impl Authority for ForwardAuthority {
         &self.origin
     }
 
+    /// The answers of the upstream resolvers are for the forwarded client subnet
+    fn client_subnet_scope(&self, client_subnet: &ClientSubnet) -> u8 {
+        self.forwarded_client_subnet(client_subnet)
+            .map_or(0, |subnet| subnet.source_prefix())
+    }
+
+    /// Clears the caches of the resolvers, and the stale answers
+    fn clear_cache(&self) {
+        self.resolver.clear_cache();
+        for rule in &self.rules {
+            rule.resolver.clear_cache();
+        }
+        if let Some(stale) = &self.stale {
+            stale.clear();
+        }
+    }
+
     /// Forwards a lookup given the resolver configuration for this Forwarded zone
+    ///
+    /// If the upstream resolvers fail or time out, an answer which expired less than the max stale
+    ///  window ago is answered, if there is one.
     async fn lookup(
         &self,
         name: &LowerName,
         rtype: RecordType,
-        _lookup_options: LookupOptions,
+        lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
         // TODO: make this an error?
         debug_assert!(self.origin.zone_of(name));
 
         debug!("forwarding lookup: {} {}", name, rtype);
-        let name: LowerName = name.clone();
-        let resolve = self.resolver.lookup(name, rtype).await;
+        let client_subnet = lookup_options
+            .client_subnet()
+            .and_then(|subnet| self.forwarded_client_subnet(subnet));
+
+        let (resolver, fallback) = self.forwarder_of(name);
+        let resolve = match forward(resolver, name.clone(), rtype, client_subnet).await {
+            // negative answers of the upstream resolvers are final
+            Err(e) if fallback && !matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                debug!(
+                    "forwarding {} with the zone {} after: {}",
+                    name, self.origin, e
+                );
+                forward(&self.resolver, name.clone(), rtype, client_subnet).await
+            }
+            resolve => resolve,
+        };
 
-        resolve.map(ForwardLookup).map_err(LookupError::from)
+        let stale = match (&self.stale, client_subnet) {
+            // the answers for client subnets are only cached by the resolvers
+            (Some(stale), None) => stale,
+            _ => return resolve.map(ForwardLookup::new).map_err(LookupError::from),
+        };
+
+        match resolve {
+            Ok(lookup) => {
+                stale.insert(name, rtype, &lookup);
+                Ok(ForwardLookup::new(lookup))
+            }
+            Err(e) if is_upstream_failure(&e) => match stale.get(name, rtype, Instant::now()) {
+                Some(lookup) => {
+                    debug!("answering stale {} {} after: {}", name, rtype, e);
+                    Ok(ForwardLookup::stale(lookup))
+                }
+                None => Err(LookupError::from(e)),
+            },
+            Err(e) => Err(LookupError::from(e)),
+        }
     }
 
     async fn search(
@@ -160,21 +287,177 @@ impl Authority for ForwardAuthority {
     }
 }
 
+/// The upstream resolvers of a domain, configured by a rule of the zone
+struct DomainForwarder {
+    domain: LowerName,
+    resolver: ForwardResolver,
+    fallback: bool,
+}
+
+impl DomainForwarder {
+    fn try_from_rule(
+        origin: &LowerName,
+        rule: &ForwardRule,
+        options: ResolverOpts,
+        conn_provider: &DnstapConnectionProvider,
+    ) -> Result<Self, String> {
+        let domain = rule
+            .get_domain()
+            .map_err(|e| format!("error parsing forwarded domain {}: {}", rule.domain, e))?;
+        let domain = LowerName::from(domain);
+        if !origin.zone_of(&domain) {
+            return Err(format!(
+                "forwarded domain {} is not in the zone {}",
+                domain, origin
+            ));
+        }
+
+        let conn_provider = match rule.tsig_key {
+            #[cfg(feature = "dnssec")]
+            Some(ref key) => {
+                let signer = key.try_into_signer()?;
+                conn_provider.with_signer(Arc::new(QuerySigner(signer)))
+            }
+            #[cfg(not(feature = "dnssec"))]
+            Some(_) => {
+                return Err(format!(
+                    "tsig_key of forwarded domain {} requires the dnssec feature",
+                    domain
+                ))
+            }
+            None => conn_provider.clone(),
+        };
+
+        let options = rule.options.map_or(options, forwarder_options);
+        let config = ResolverConfig::from_parts(None, vec![], rule.name_servers.clone());
+        let resolver = AsyncResolver::new_with_conn(config, options, conn_provider)
+            .map_err(|e| format!("error constructing new Resolver: {}", e))?;
+
+        info!("forwarding {} to {:?}", domain, rule.name_servers);
+        Ok(Self {
+            domain,
+            resolver,
+            fallback: rule.is_fallback(),
+        })
+    }
+}
+
+/// The options of the resolvers of the forwarder
+fn forwarder_options(mut options: ResolverOpts) -> ResolverOpts {
+    // See RFC 1034, Section 4.3.2:
+    // "If the data at the node is a CNAME, and QTYPE doesn't match
+    // CNAME, copy the CNAME RR into the answer section of the response,
+    // change QNAME to the canonical name in the CNAME RR, and go
+    // back to step 1."
+    //
+    // Essentially, it's saying that servers (including forwarders)
+    // should emit any found CNAMEs in a response ("copy the CNAME
+    // RR into the answer section"). This is the behavior that
+    // preserve_intemediates enables when set to true, and disables
+    // when set to false. So we set it to true.
+    if !options.preserve_intermediates {
+        tracing::warn!(
+            "preserve_intermediates set to false, which is invalid \
+            for a forwarder; switching to true"
+        );
+        options.preserve_intermediates = true;
+    }
+
+    options
+}
+
+/// True if the upstream resolvers failed, rather than answered that there are no records
+fn is_upstream_failure(error: &ResolveError) -> bool {
+    match error.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+            matches!(
+                *response_code,
+                ResponseCode::ServFail | ResponseCode::Refused
+            )
+        }
+        _ => true,
+    }
+}
+
+async fn forward(
+    resolver: &ForwardResolver,
+    name: LowerName,
+    rtype: RecordType,
+    client_subnet: Option<ClientSubnet>,
+) -> Result<ResolverLookup, ResolveError> {
+    // the queries to the upstream resolvers are in the span of the request
+    let span = debug_span!("forward", qname = %name, qtype = %rtype);
+    match client_subnet {
+        Some(client_subnet) => {
+            debug!(parent: &span, "forwarding client subnet: {}", client_subnet);
+            let options = ResolverLookupOptions::with_client_subnet(client_subnet);
+            resolver
+                .lookup_with_options(name, rtype, options)
+                .instrument(span)
+                .await
+        }
+        None => resolver.lookup(name, rtype).instrument(span).await,
+    }
+}
+
 /// A structure that holds the results of a forwarding lookup.
 ///
 /// This exposes an interator interface for consumption downstream.
-pub struct ForwardLookup(pub ResolverLookup);
+pub struct ForwardLookup {
+    lookup: ResolverLookup,
+    stale: bool,
+}
+
+impl ForwardLookup {
+    /// The answer of the upstream resolvers
+    pub fn new(lookup: ResolverLookup) -> Self {
+        Self {
+            lookup,
+            stale: false,
+        }
+    }
+
+    /// An answer which expired, answered as the upstream resolvers failed
+    pub fn stale(lookup: ResolverLookup) -> Self {
+        Self {
+            lookup,
+            stale: true,
+        }
+    }
+
+    /// The answer
+    pub fn lookup(&self) -> &ResolverLookup {
+        &self.lookup
+    }
+
+    /// True if the answer expired, see RFC 8767
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+}
 
 impl LookupObject for ForwardLookup {
     fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.lookup.is_empty()
     }
 
     fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
-        Box::new(self.0.record_iter())
+        Box::new(self.lookup.record_iter())
     }
 
     fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
         None
     }
+
+    /// Stale answers are marked with the Stale Answer extended DNS error
+    fn extended_error(&self) -> Option<EdnsOption> {
+        if !self.stale {
+            return None;
+        }
+
+        Some(EdnsOption::ExtendedError {
+            info_code: ExtendedErrorCode::StaleAnswer,
+            extra_text: String::new(),
+        })
+    }
 }
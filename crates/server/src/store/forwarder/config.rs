@@ -5,8 +5,13 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::time::Duration;
+
 use serde::Deserialize;
 
+use crate::client::rr::Name;
+use crate::config::dnssec::TsigKeyConfig;
+use crate::proto::error::ProtoResult;
 use crate::resolver::config::{NameServerConfigGroup, ResolverOpts};
 
 /// Configuration for file based zones
@@ -16,4 +21,74 @@ pub struct ForwardConfig {
     pub name_servers: NameServerConfigGroup,
     /// Resolver options
     pub options: Option<ResolverOpts>,
+    /// Forward the EDNS Client Subnet option of requests to the upstream resolvers, see RFC 7871
+    ///
+    /// Answers are cached for the scope of the subnet returned by the upstream resolvers.
+    pub forward_client_subnet: Option<bool>,
+    /// The maximum source prefix length of forwarded IPv4 client subnets, 24 by default
+    pub client_subnet_ipv4_prefix: Option<u8>,
+    /// The maximum source prefix length of forwarded IPv6 client subnets, 56 by default
+    pub client_subnet_ipv6_prefix: Option<u8>,
+    /// Domains forwarded to other upstream resolvers than the `name_servers` of the zone
+    #[serde(default)]
+    pub rules: Vec<ForwardRule>,
+    /// Seconds past their TTL that answers are kept, and answered if the upstream resolvers fail
+    ///  or time out, see RFC 8767. Disabled by default, the RFC suggests 1 to 3 days.
+    pub max_stale: Option<u64>,
+}
+
+impl ForwardConfig {
+    /// True if the client subnet of requests is forwarded
+    pub fn is_forward_client_subnet(&self) -> bool {
+        self.forward_client_subnet.unwrap_or(false)
+    }
+
+    /// The maximum source prefix length of forwarded IPv4 client subnets
+    pub fn get_client_subnet_ipv4_prefix(&self) -> u8 {
+        self.client_subnet_ipv4_prefix.unwrap_or(24)
+    }
+
+    /// The maximum source prefix length of forwarded IPv6 client subnets
+    pub fn get_client_subnet_ipv6_prefix(&self) -> u8 {
+        self.client_subnet_ipv6_prefix.unwrap_or(56)
+    }
+
+    /// The time past their TTL that answers are served stale, none if they are not
+    pub fn get_max_stale(&self) -> Option<Duration> {
+        match self.max_stale.unwrap_or(0) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+}
+
+/// A rule forwarding the names in a domain to their own upstream resolvers
+///
+/// The rule with the longest matching domain applies, names which match no rule are forwarded to
+///  the `name_servers` of the zone.
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct ForwardRule {
+    /// The domain forwarded by the rule, with all names below it, e.g. corp.example.com
+    pub domain: String,
+    /// upstream name_server configurations, each with its protocol
+    pub name_servers: NameServerConfigGroup,
+    /// Resolver options, by default those of the zone
+    pub options: Option<ResolverOpts>,
+    /// TSIG key signing the queries sent to the upstream resolvers, requires the dnssec feature
+    pub tsig_key: Option<TsigKeyConfig>,
+    /// Forward to the `name_servers` of the zone if the upstream resolvers of the rule fail,
+    ///  false by default
+    pub fallback: Option<bool>,
+}
+
+impl ForwardRule {
+    /// Returns the domain of the rule
+    pub fn get_domain(&self) -> ProtoResult<Name> {
+        Name::parse(&self.domain, Some(&Name::root()))
+    }
+
+    /// True if the `name_servers` of the zone are queried if the rule fails
+    pub fn is_fallback(&self) -> bool {
+        self.fallback.unwrap_or(false)
+    }
 }
@@ -0,0 +1,196 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Connections of the forwarder which sign and log the messages exchanged with the upstream
+//!  resolvers
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use futures_util::{future::FutureExt, stream::Stream, StreamExt};
+
+#[cfg(feature = "dnssec")]
+use crate::{
+    client::{
+        op::Message,
+        rr::{dnssec::tsig::TSigner, Record},
+    },
+    proto::{error::ProtoResult, op::MessageVerifier},
+};
+
+use crate::{
+    client::rr::Name,
+    dnstap::{Dnstap, SocketProtocol, UpstreamTap},
+    proto::{
+        op::MessageFinalizer,
+        xfer::{DnsHandle, DnsRequest, DnsResponse},
+    },
+    resolver::{
+        config::{NameServerConfig, Protocol, ResolverOpts},
+        error::ResolveError,
+        name_server::{ConnectionProvider, TokioConnection, TokioConnectionProvider},
+    },
+};
+
+/// Provides the connections to the upstream resolvers, which log their messages with dnstap once
+///  it is set
+#[derive(Clone)]
+pub(crate) struct DnstapConnectionProvider {
+    inner: TokioConnectionProvider,
+    zone: Name,
+    dnstap: Arc<RwLock<Option<Dnstap>>>,
+    signer: Option<Arc<dyn MessageFinalizer>>,
+}
+
+impl DnstapConnectionProvider {
+    pub(crate) fn new(inner: TokioConnectionProvider, zone: Name) -> Self {
+        Self {
+            inner,
+            zone,
+            dnstap: Arc::default(),
+            signer: None,
+        }
+    }
+
+    /// A provider of connections which sign all queries with the signer, e.g. TSIG
+    ///
+    /// The dnstap is shared with this provider.
+    #[cfg(feature = "dnssec")]
+    pub(crate) fn with_signer(&self, signer: Arc<dyn MessageFinalizer>) -> Self {
+        Self {
+            signer: Some(signer),
+            ..self.clone()
+        }
+    }
+
+    /// Sets the dnstap of the connections which are opened afterwards
+    pub(crate) fn set_dnstap(&self, dnstap: Option<Dnstap>) {
+        *self.dnstap.write().expect("lock poisoned") = dnstap;
+    }
+}
+
+impl ConnectionProvider for DnstapConnectionProvider {
+    type Conn = DnstapConnection;
+    type FutureConn =
+        Pin<Box<dyn Future<Output = Result<Self::Conn, ResolveError>> + Send + 'static>>;
+    type Time = <TokioConnectionProvider as ConnectionProvider>::Time;
+
+    fn new_connection(
+        &self,
+        config: &NameServerConfig,
+        options: &ResolverOpts,
+    ) -> Self::FutureConn {
+        let tap = self
+            .dnstap
+            .read()
+            .expect("lock poisoned")
+            .clone()
+            .map(|dnstap| {
+                UpstreamTap::forwarder(
+                    dnstap,
+                    &self.zone,
+                    config.socket_addr,
+                    socket_protocol(config.protocol),
+                )
+            });
+
+        let signer = self.signer.clone();
+        Box::pin(
+            self.inner
+                .new_connection(config, options)
+                .map(|inner| inner.map(|inner| DnstapConnection { inner, tap, signer })),
+        )
+    }
+
+    fn spawn_bg<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.inner.spawn_bg(future)
+    }
+}
+
+#[allow(unreachable_patterns)]
+fn socket_protocol(protocol: Protocol) -> Option<SocketProtocol> {
+    match protocol {
+        Protocol::Udp => Some(SocketProtocol::Udp),
+        Protocol::Tcp => Some(SocketProtocol::Tcp),
+        #[cfg(any(feature = "dns-over-openssl", feature = "dns-over-rustls"))]
+        Protocol::Tls => Some(SocketProtocol::Dot),
+        #[cfg(feature = "dns-over-https-rustls")]
+        Protocol::Https => Some(SocketProtocol::Doh),
+        #[cfg(feature = "dns-over-quic")]
+        Protocol::Quic => Some(SocketProtocol::Doq),
+        _ => None,
+    }
+}
+
+/// A connection to an upstream resolver, which logs the queries sent and the responses received
+#[derive(Clone)]
+pub(crate) struct DnstapConnection {
+    inner: TokioConnection,
+    tap: Option<UpstreamTap>,
+    signer: Option<Arc<dyn MessageFinalizer>>,
+}
+
+impl DnsHandle for DnstapConnection {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ResolveError>> + Send>>;
+    type Error = ResolveError;
+
+    fn is_verifying_dnssec(&self) -> bool {
+        self.inner.is_verifying_dnssec()
+    }
+
+    fn is_using_edns(&self) -> bool {
+        self.inner.is_using_edns()
+    }
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&mut self, request: R) -> Self::Response {
+        let mut request = request.into();
+        if let Some(ref signer) = self.signer {
+            request.set_finalizer(Arc::clone(signer));
+        }
+
+        let tap = match self.tap {
+            Some(ref tap) => tap.clone(),
+            None => return Box::pin(self.inner.send(request)),
+        };
+
+        let query_time = SystemTime::now();
+        tap.query(query_time, &request);
+
+        Box::pin(self.inner.send(request).map(move |response| {
+            if let Ok(ref response) = response {
+                tap.response(Some(query_time), response);
+            }
+            response
+        }))
+    }
+}
+
+/// Signs all queries with the TSIG key, which by default only signs transfers and updates
+#[cfg(feature = "dnssec")]
+pub(crate) struct QuerySigner(pub(crate) TSigner);
+
+#[cfg(feature = "dnssec")]
+impl MessageFinalizer for QuerySigner {
+    fn finalize_message(
+        &self,
+        message: &Message,
+        current_time: u32,
+    ) -> ProtoResult<(Vec<Record>, Option<MessageVerifier>)> {
+        self.0.finalize_message(message, current_time)
+    }
+
+    fn should_finalize_message(&self, _message: &Message) -> bool {
+        true
+    }
+}
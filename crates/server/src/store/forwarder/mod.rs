@@ -11,7 +11,9 @@
 
 mod authority;
 mod config;
+mod dnstap;
+mod stale;
 
 pub use self::authority::ForwardAuthority;
 pub use self::authority::ForwardLookup;
-pub use self::config::ForwardConfig;
+pub use self::config::{ForwardConfig, ForwardRule};
@@ -0,0 +1,123 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Answers kept past their TTL, served when the upstream resolvers fail, see RFC 8767
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use lru_cache::LruCache;
+
+use crate::{
+    client::rr::{LowerName, Record, RecordType},
+    resolver::lookup::Lookup,
+};
+
+/// The TTL of stale answers, as recommended by RFC 8767 section 4
+const STALE_ANSWER_TTL: u32 = 30;
+
+/// The answers of the upstream resolvers, by name and type, until they are stale for longer than
+///  the max stale window
+///
+/// The answers share their records with the cache of the resolver.
+pub(crate) struct StaleCache {
+    max_stale: Duration,
+    answers: Mutex<LruCache<(LowerName, RecordType), Lookup>>,
+}
+
+impl StaleCache {
+    pub(crate) fn new(capacity: usize, max_stale: Duration) -> Self {
+        Self {
+            max_stale,
+            answers: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Keeps the answer, replacing the previous one
+    pub(crate) fn insert(&self, name: &LowerName, rtype: RecordType, lookup: &Lookup) {
+        self.answers
+            .lock()
+            .expect("stale cache poisoned")
+            .insert((name.clone(), rtype), lookup.clone());
+    }
+
+    /// The answer, with the TTL of stale answers, unless it has been stale for longer than the
+    ///  max stale window
+    pub(crate) fn get(&self, name: &LowerName, rtype: RecordType, now: Instant) -> Option<Lookup> {
+        let mut answers = self.answers.lock().expect("stale cache poisoned");
+        let key = (name.clone(), rtype);
+        let lookup = answers.get_mut(&key)?;
+        if lookup.valid_until() + self.max_stale < now {
+            answers.remove(&key);
+            return None;
+        }
+
+        let records = lookup
+            .records()
+            .iter()
+            .map(|record| {
+                let mut record = record.clone();
+                record.set_ttl(STALE_ANSWER_TTL);
+                record
+            })
+            .collect::<Vec<Record>>();
+
+        Some(Lookup::new_with_deadline(
+            lookup.query().clone(),
+            Arc::from(records),
+            now + Duration::from_secs(u64::from(STALE_ANSWER_TTL)),
+        ))
+    }
+
+    pub(crate) fn clear(&self) {
+        self.answers.lock().expect("stale cache poisoned").clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, str::FromStr};
+
+    use super::*;
+    use crate::client::{
+        op::Query,
+        rr::{Name, RData},
+    };
+
+    #[test]
+    fn test_max_stale() {
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let lookup = Lookup::new_with_deadline(
+            Query::query(name.clone(), RecordType::A),
+            Arc::from(vec![Record::from_rdata(
+                name.clone(),
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            )]),
+            now + Duration::from_secs(300),
+        );
+
+        let cache = StaleCache::new(2, Duration::from_secs(3600));
+        let name = LowerName::from(name);
+        cache.insert(&name, RecordType::A, &lookup);
+        assert!(cache.get(&name, RecordType::AAAA, now).is_none());
+
+        let later = now + Duration::from_secs(1800);
+        let stale = cache.get(&name, RecordType::A, later).unwrap();
+        assert_eq!(stale.records()[0].ttl(), STALE_ANSWER_TTL);
+        assert_eq!(stale.records()[0].data(), lookup.records()[0].data());
+
+        assert!(cache
+            .get(&name, RecordType::A, now + Duration::from_secs(3901))
+            .is_none());
+        // expired answers are removed
+        assert!(cache.get(&name, RecordType::A, later).is_none());
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Resolution of ANAME targets outside of the zone
+
+#![cfg(feature = "trust-dns-resolver")]
+
+use std::time::Instant;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    authority::LookupError,
+    client::{
+        op::ResponseCode,
+        rr::{LowerName, Name, RecordSet, RecordType},
+    },
+    resolver::{
+        config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+        error::ResolveErrorKind,
+        TokioAsyncResolver,
+    },
+};
+
+/// Configuration of the resolution of ANAME (or ALIAS) targets outside of the zone
+///
+/// The addresses of the targets are resolved when they are queried, and are cached for their TTL.
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct AnameConfig {
+    /// upstream name_server configurations
+    pub name_servers: NameServerConfigGroup,
+    /// Resolver options, the `cache_size` bounds the number of targets cached
+    pub options: Option<ResolverOpts>,
+}
+
+impl AnameConfig {
+    pub(crate) fn try_into_resolver(&self) -> Result<AnameResolver, String> {
+        let config = ResolverConfig::from_parts(None, vec![], self.name_servers.clone());
+        let options = self.options.unwrap_or_default();
+
+        TokioAsyncResolver::tokio(config, options)
+            .map(AnameResolver)
+            .map_err(|e| format!("error constructing the ANAME resolver: {}", e))
+    }
+}
+
+/// The resolver of the addresses of ANAME targets outside of the zone
+pub(crate) struct AnameResolver(TokioAsyncResolver);
+
+impl AnameResolver {
+    /// The addresses of the `query_type` of the target, `None` if it has none
+    ///
+    /// The TTL of the addresses is the time they remain cached.
+    pub(crate) async fn resolve(
+        &self,
+        target: &LowerName,
+        query_type: RecordType,
+    ) -> Result<Option<RecordSet>, LookupError> {
+        let target = Name::from(target);
+        let lookup = match self.0.lookup(target.clone(), query_type).await {
+            Ok(lookup) => lookup,
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                return Ok(None)
+            }
+            Err(e) => {
+                warn!("failed to resolve ANAME target {}: {}", target, e);
+                return Err(LookupError::from(ResponseCode::ServFail));
+            }
+        };
+
+        let ttl = lookup
+            .valid_until()
+            .saturating_duration_since(Instant::now())
+            .as_secs() as u32;
+
+        let mut addresses = RecordSet::with_ttl(target, query_type, ttl);
+        for rdata in lookup
+            .records()
+            .iter()
+            .filter(|r| r.record_type() == query_type)
+            .filter_map(|r| r.data())
+        {
+            addresses.add_rdata(rdata.clone());
+        }
+
+        Ok(Some(addresses))
+    }
+}
@@ -7,41 +7,52 @@
 
 //! All authority related types
 
+#[cfg(feature = "dnssec")]
+use std::borrow::Borrow;
 use std::{
-    borrow::Borrow,
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    net::{IpAddr, SocketAddr},
     ops::DerefMut,
     sync::Arc,
 };
 
-use cfg_if::cfg_if;
 use futures_util::future::{self, TryFutureExt};
-use tracing::{debug, error, warn};
+use ipnet::IpNet;
+use tracing::{debug, error, info, warn};
 
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-#[cfg(feature = "dnssec")]
-use crate::{
-    authority::DnssecAuthority,
-    client::rr::{
-        dnssec::{DnsSecResult, SigSigner, SupportedAlgorithms},
-        rdata::{key::KEY, DNSSECRData},
-    },
-};
+#[cfg(feature = "geoip")]
+use std::path::Path;
+
+#[cfg(feature = "trust-dns-resolver")]
+use crate::store::in_memory::aname::{AnameConfig, AnameResolver};
+#[cfg(feature = "geoip")]
+use crate::store::in_memory::geo::{Geo, GeoConfig};
+use crate::store::in_memory::pool::{Pool, PoolConfig};
 use crate::{
     authority::{
-        AnyRecords, AuthLookup, Authority, LookupError, LookupOptions, LookupRecords, LookupResult,
-        MessageRequest, UpdateResult, ZoneType,
+        synthesis, zone_transfer, AnyRecords, AuthLookup, Authority, LookupError, LookupOptions,
+        LookupRecords, LookupResult, MessageRequest, UpdateResult, ZoneDiff, ZoneType,
     },
     client::{
         op::ResponseCode,
         rr::{
-            rdata::SOA,
+            rdata::{SOA, TXT},
             {DNSClass, LowerName, Name, RData, Record, RecordSet, RecordType, RrKey},
         },
     },
+    proto::rr::rdata::opt::ClientSubnet,
     server::RequestInfo,
 };
+#[cfg(feature = "dnssec")]
+use crate::{
+    authority::{unix_now, DnssecAuthority, KeyRole, KeyState, KeyTimings},
+    client::rr::{
+        dnssec::{tsig::TSigner, DigestType, DnsSecResult, SigSigner, SupportedAlgorithms},
+        rdata::{key::KEY, DNSSECRData, DNSKEY, DS, NSEC3PARAM},
+    },
+};
 #[cfg(all(feature = "dnssec", feature = "testing"))]
 use std::ops::Deref;
 
@@ -54,9 +65,22 @@ pub struct InMemoryAuthority {
     class: DNSClass,
     zone_type: ZoneType,
     allow_axfr: bool,
+    transfer_acl: Vec<IpNet>,
+    also_notify: Vec<SocketAddr>,
+    #[cfg(feature = "trust-dns-resolver")]
+    aname_resolver: Option<AnameResolver>,
+    #[cfg(feature = "geoip")]
+    geo: Option<Geo>,
+    pools: HashMap<RrKey, Arc<Pool>>,
     inner: RwLock<InnerInMemory>,
 }
 
+/// The number of changes to the zone which are kept for serving IXFR
+const MAX_ZONE_DIFFS: usize = 64;
+
+/// The TTL of the TXT records of ACME challenges, which are only needed for the validation
+const CHALLENGE_TTL: u32 = 60;
+
 impl InMemoryAuthority {
     /// Creates a new Authority.
     ///
@@ -123,6 +147,13 @@ impl InMemoryAuthority {
             class: DNSClass::IN,
             zone_type,
             allow_axfr,
+            transfer_acl: Vec::new(),
+            also_notify: Vec::new(),
+            #[cfg(feature = "trust-dns-resolver")]
+            aname_resolver: None,
+            #[cfg(feature = "geoip")]
+            geo: None,
+            pools: HashMap::new(),
             inner: RwLock::new(InnerInMemory::default()),
         }
     }
@@ -139,6 +170,203 @@ impl InMemoryAuthority {
         self.allow_axfr = allow_axfr;
     }
 
+    /// Restricts zone transfers to clients in these networks, if AXFR is allowed
+    ///
+    /// An empty list permits transfers from any address.
+    pub fn set_transfer_acl(&mut self, transfer_acl: Vec<IpNet>) {
+        self.transfer_acl = transfer_acl;
+    }
+
+    /// Secondaries which are sent a NOTIFY when the zone changes, see RFC 1996
+    pub fn set_also_notify(&mut self, also_notify: Vec<SocketAddr>) {
+        self.also_notify = also_notify;
+    }
+
+    /// The secondaries which are sent a NOTIFY when the zone changes
+    pub fn also_notify(&self) -> &[SocketAddr] {
+        &self.also_notify
+    }
+
+    /// Resolves the addresses of ANAME targets outside of the zone with these upstream resolvers
+    ///
+    /// Without them, A and AAAA queries of such an ANAME are answered with the ANAME record.
+    #[cfg(feature = "trust-dns-resolver")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolver")))]
+    pub fn set_aname_config(&mut self, config: &AnameConfig) -> Result<(), String> {
+        self.aname_resolver = Some(config.try_into_resolver()?);
+        Ok(())
+    }
+
+    /// Answers an A or AAAA query of an ANAME whose target is outside of the zone, from the
+    ///  addresses of the target resolved upstream
+    ///
+    /// Returns `None` if the name has no such ANAME, the query is then answered from the zone.
+    #[cfg(feature = "trust-dns-resolver")]
+    async fn lookup_external_aname(
+        &self,
+        resolver: &AnameResolver,
+        name: &LowerName,
+        query_type: RecordType,
+        lookup_options: LookupOptions,
+    ) -> Result<Option<AuthLookup>, LookupError> {
+        if !matches!(query_type, RecordType::A | RecordType::AAAA) {
+            return Ok(None);
+        }
+
+        // the lock is not held while resolving
+        let (aname, chain, target) = {
+            let inner = self.inner.read().await;
+            let aname = match inner.inner_lookup(name, query_type, lookup_options) {
+                Some(rrset) if rrset.record_type() == RecordType::ANAME => rrset,
+                _ => return Ok(None),
+            };
+
+            let target = match maybe_next_name(&aname, query_type) {
+                Some((target, _)) => target,
+                None => return Ok(None),
+            };
+
+            // follow the chain through the zone, until it leaves the zone
+            let chain = inner
+                .additional_search(
+                    name,
+                    query_type,
+                    target.clone(),
+                    RecordType::ANAME,
+                    lookup_options,
+                )
+                .unwrap_or_default();
+            let target = match chain.last() {
+                Some(last) => match maybe_next_name(last, query_type) {
+                    Some((target, _)) => target,
+                    None => return Ok(None),
+                },
+                None => target,
+            };
+
+            if self.origin.zone_of(&target) {
+                return Ok(None);
+            }
+
+            (aname, chain, target)
+        };
+
+        let addresses = resolver.resolve(&target, query_type).await?;
+
+        #[allow(unused_mut)]
+        let mut answer = synthesis::synthesize_aname(&aname, query_type, addresses.as_ref());
+
+        #[cfg(feature = "dnssec")]
+        if lookup_options.is_dnssec() {
+            self.inner
+                .read()
+                .await
+                .sign_synthesized(&mut answer, self.origin(), self.class());
+        }
+
+        let additionals = std::iter::once(aname).chain(chain).collect();
+
+        Ok(Some(AuthLookup::answers(
+            LookupRecords::new(lookup_options, Arc::new(answer)),
+            Some(LookupRecords::many(lookup_options, additionals)),
+        )))
+    }
+
+    /// Answers A, AAAA and CNAME queries with records which depend on the location of the client
+    ///
+    /// The database is opened relative to `root_dir`, see `GeoConfig`.
+    #[cfg(feature = "geoip")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "geoip")))]
+    pub fn set_geo_config(
+        &mut self,
+        config: &GeoConfig,
+        root_dir: Option<&Path>,
+    ) -> Result<(), String> {
+        self.geo = Some(config.try_into_geo(&Name::from(&self.origin), root_dir)?);
+        Ok(())
+    }
+
+    /// Answers a query with the records of the location of the client
+    ///
+    /// Returns `None` if no location of the client has records at the name, the query is then
+    ///  answered from the zone.
+    #[cfg(feature = "geoip")]
+    async fn lookup_geo(
+        &self,
+        geo: &Geo,
+        name: &LowerName,
+        query_type: RecordType,
+        client: IpAddr,
+        lookup_options: LookupOptions,
+    ) -> Option<AuthLookup> {
+        let answer = geo.lookup(name, query_type, client)?;
+        debug!(
+            "answering {} {} for the location of {}",
+            name, query_type, client
+        );
+
+        let inner = self.inner.read().await;
+
+        #[cfg(feature = "dnssec")]
+        let answer = if lookup_options.is_dnssec() {
+            let mut signed = RecordSet::clone(&answer);
+            inner.sign_synthesized(&mut signed, self.origin(), self.class());
+            Arc::new(signed)
+        } else {
+            answer
+        };
+
+        // the records of a CNAME target in the zone
+        let additionals = match maybe_next_name(&answer, query_type) {
+            Some((target, search_type)) => {
+                inner.additional_search(name, query_type, target, search_type, lookup_options)
+            }
+            None => None,
+        };
+
+        Some(AuthLookup::answers(
+            LookupRecords::new(lookup_options, answer),
+            additionals.map(|additionals| LookupRecords::many(lookup_options, additionals)),
+        ))
+    }
+
+    /// Answers A and AAAA queries of the names of the pools with their members, by weight and
+    ///  health, in place of the records of the zone
+    ///
+    /// This must be called from within a tokio runtime if any pool has a health check, the
+    ///  checks run until the authority is dropped.
+    pub fn set_pools_config(&mut self, configs: &[PoolConfig]) -> Result<(), String> {
+        let origin = Name::from(&self.origin);
+        let pools = configs
+            .iter()
+            .map(|config| config.try_into_pool(&origin).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.pools.clear();
+        for pool in pools {
+            pool.spawn_health_checks();
+            self.pools.insert(pool.key(), pool);
+        }
+
+        Ok(())
+    }
+
+    /// Answers a query with the next members of the pool
+    async fn lookup_pool(&self, pool: &Pool, lookup_options: LookupOptions) -> AuthLookup {
+        #[allow(unused_mut)]
+        let mut answer = pool.answer();
+
+        #[cfg(feature = "dnssec")]
+        if lookup_options.is_dnssec() {
+            self.inner
+                .read()
+                .await
+                .sign_synthesized(&mut answer, self.origin(), self.class());
+        }
+
+        AuthLookup::answers(LookupRecords::new(lookup_options, Arc::new(answer)), None)
+    }
+
     /// Clears all records (including SOA, etc)
     pub fn clear(&mut self) {
         self.inner.get_mut().records.clear()
@@ -178,8 +406,6 @@ impl InMemoryAuthority {
         self.inner.read().await.serial(self.origin())
     }
 
-    #[cfg(any(feature = "dnssec", feature = "sqlite"))]
-    #[allow(unused)]
     pub(crate) async fn increment_soa_serial(&self) -> u32 {
         self.inner
             .write()
@@ -244,6 +470,26 @@ impl InMemoryAuthority {
         Self::inner_add_update_auth_key(inner.get_mut(), name, key, origin, *class)
     }
 
+    /// Non-async method of add_update_tsig_key when behind a mutable reference
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn add_update_tsig_key_mut(&mut self, signer: TSigner) {
+        self.inner.get_mut().tsig_keys.push(signer);
+    }
+
+    /// Non-async method of add_transfer_tsig_key when behind a mutable reference
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn add_transfer_tsig_key_mut(&mut self, signer: TSigner) {
+        self.inner.get_mut().transfer_tsig_keys.push(signer);
+    }
+
+    /// True if the zone has keys to sign it with, see `add_zone_signing_key`
+    #[cfg(feature = "dnssec")]
+    pub(crate) async fn is_signed(&self) -> bool {
+        self.inner.read().await.is_signed()
+    }
+
     /// By adding a secure key, this will implicitly enable dnssec for the zone.
     ///
     /// # Arguments
@@ -286,6 +532,36 @@ impl InMemoryAuthority {
         Self::inner_add_zone_signing_key(inner.get_mut(), signer, origin, *class)
     }
 
+    /// Non-async method of set_nsec3 when behind a mutable reference
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn set_nsec3_mut(&mut self, nsec3param: NSEC3PARAM, opt_out: bool) {
+        let Self {
+            ref origin,
+            ref mut inner,
+            class,
+            ..
+        } = self;
+
+        inner
+            .get_mut()
+            .set_nsec3(origin, *class, nsec3param, opt_out)
+    }
+
+    /// Non-async method of add_scheduled_signing_key when behind a mutable reference
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub fn add_scheduled_signing_key_mut(
+        &mut self,
+        signer: SigSigner,
+        role: KeyRole,
+        timings: KeyTimings,
+    ) -> DnsSecResult<()> {
+        self.inner
+            .get_mut()
+            .add_scheduled_signing_key(signer, role, timings)
+    }
+
     /// (Re)generates the nsec records, increments the serial number and signs the zone
     #[cfg(feature = "dnssec")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
@@ -304,6 +580,654 @@ impl InMemoryAuthority {
     pub fn secure_zone_mut(&mut self) -> Result<(), &str> {
         Err("DNSSEC was not enabled during compilation.")
     }
+
+    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    ///
+    /// ```text
+    ///
+    /// 3.2 - Process Prerequisite Section
+    ///
+    ///   Next, the Prerequisite Section is checked to see that all
+    ///   prerequisites are satisfied by the current state of the zone.  Using
+    ///   the definitions expressed in Section 1.2, if any RR's NAME is not
+    ///   within the zone specified in the Zone Section, signal NOTZONE to the
+    ///   requestor.
+    ///
+    /// 3.2.1. For RRs in this section whose CLASS is ANY, test to see that
+    ///   TTL and RDLENGTH are both zero (0), else signal FORMERR to the
+    ///   requestor.  If TYPE is ANY, test to see that there is at least one RR
+    ///   in the zone whose NAME is the same as that of the Prerequisite RR,
+    ///   else signal NXDOMAIN to the requestor.  If TYPE is not ANY, test to
+    ///   see that there is at least one RR in the zone whose NAME and TYPE are
+    ///   the same as that of the Prerequisite RR, else signal NXRRSET to the
+    ///   requestor.
+    ///
+    /// 3.2.2. For RRs in this section whose CLASS is NONE, test to see that
+    ///   the TTL and RDLENGTH are both zero (0), else signal FORMERR to the
+    ///   requestor.  If the TYPE is ANY, test to see that there are no RRs in
+    ///   the zone whose NAME is the same as that of the Prerequisite RR, else
+    ///   signal YXDOMAIN to the requestor.  If the TYPE is not ANY, test to
+    ///   see that there are no RRs in the zone whose NAME and TYPE are the
+    ///   same as that of the Prerequisite RR, else signal YXRRSET to the
+    ///   requestor.
+    ///
+    /// 3.2.3. For RRs in this section whose CLASS is the same as the ZCLASS,
+    ///   test to see that the TTL is zero (0), else signal FORMERR to the
+    ///   requestor.  Then, build an RRset for each unique <NAME,TYPE> and
+    ///   compare each resulting RRset for set equality (same members, no more,
+    ///   no less) with RRsets in the zone.  If any Prerequisite RRset is not
+    ///   entirely and exactly matched by a zone RRset, signal NXRRSET to the
+    ///   requestor.  If any RR in this section has a CLASS other than ZCLASS
+    ///   or NONE or ANY, signal FORMERR to the requestor.
+    ///
+    /// 3.2.4 - Table Of Metavalues Used In Prerequisite Section
+    ///
+    ///   CLASS    TYPE     RDATA    Meaning
+    ///   ------------------------------------------------------------
+    ///   ANY      ANY      empty    Name is in use
+    ///   ANY      rrset    empty    RRset exists (value independent)
+    ///   NONE     ANY      empty    Name is not in use
+    ///   NONE     rrset    empty    RRset does not exist
+    ///   zone     rrset    rr       RRset exists (value dependent)
+    /// ```
+    pub async fn verify_prerequisites(&self, pre_requisites: &[Record]) -> UpdateResult<()> {
+        //   3.2.5 - Pseudocode for Prerequisite Section Processing
+        //
+        //      for rr in prerequisites
+        //           if (rr.ttl != 0)
+        //                return (FORMERR)
+        //           if (zone_of(rr.name) != ZNAME)
+        //                return (NOTZONE);
+        //           if (rr.class == ANY)
+        //                if (rr.rdlength != 0)
+        //                     return (FORMERR)
+        //                if (rr.type == ANY)
+        //                     if (!zone_name<rr.name>)
+        //                          return (NXDOMAIN)
+        //                else
+        //                     if (!zone_rrset<rr.name, rr.type>)
+        //                          return (NXRRSET)
+        //           if (rr.class == NONE)
+        //                if (rr.rdlength != 0)
+        //                     return (FORMERR)
+        //                if (rr.type == ANY)
+        //                     if (zone_name<rr.name>)
+        //                          return (YXDOMAIN)
+        //                else
+        //                     if (zone_rrset<rr.name, rr.type>)
+        //                          return (YXRRSET)
+        //           if (rr.class == zclass)
+        //                temp<rr.name, rr.type> += rr
+        //           else
+        //                return (FORMERR)
+        //
+        //      for rrset in temp
+        //           if (zone_rrset<rrset.name, rrset.type> != rrset)
+        //                return (NXRRSET)
+        for require in pre_requisites {
+            let required_name = LowerName::from(require.name());
+
+            if require.ttl() != 0 {
+                warn!("ttl must be 0 for: {:?}", require);
+                return Err(ResponseCode::FormErr);
+            }
+
+            let origin = self.origin();
+            if !origin.zone_of(&require.name().into()) {
+                warn!("{} is not a zone_of {}", require.name(), origin);
+                return Err(ResponseCode::NotZone);
+            }
+
+            match require.dns_class() {
+                DNSClass::ANY => {
+                    if let None | Some(RData::NULL(..)) = require.data() {
+                        match require.rr_type() {
+                            // ANY      ANY      empty    Name is in use
+                            RecordType::ANY => {
+                                if self
+                                    .lookup(
+                                        &required_name,
+                                        RecordType::ANY,
+                                        LookupOptions::default(),
+                                    )
+                                    .await
+                                    .unwrap_or_default()
+                                    .was_empty()
+                                {
+                                    return Err(ResponseCode::NXDomain);
+                                } else {
+                                    continue;
+                                }
+                            }
+                            // ANY      rrset    empty    RRset exists (value independent)
+                            rrset => {
+                                if self
+                                    .lookup(&required_name, rrset, LookupOptions::default())
+                                    .await
+                                    .unwrap_or_default()
+                                    .was_empty()
+                                {
+                                    return Err(ResponseCode::NXRRSet);
+                                } else {
+                                    continue;
+                                }
+                            }
+                        }
+                    } else {
+                        return Err(ResponseCode::FormErr);
+                    }
+                }
+                DNSClass::NONE => {
+                    if let None | Some(RData::NULL(..)) = require.data() {
+                        match require.rr_type() {
+                            // NONE     ANY      empty    Name is not in use
+                            RecordType::ANY => {
+                                if !self
+                                    .lookup(
+                                        &required_name,
+                                        RecordType::ANY,
+                                        LookupOptions::default(),
+                                    )
+                                    .await
+                                    .unwrap_or_default()
+                                    .was_empty()
+                                {
+                                    return Err(ResponseCode::YXDomain);
+                                } else {
+                                    continue;
+                                }
+                            }
+                            // NONE     rrset    empty    RRset does not exist
+                            rrset => {
+                                if !self
+                                    .lookup(&required_name, rrset, LookupOptions::default())
+                                    .await
+                                    .unwrap_or_default()
+                                    .was_empty()
+                                {
+                                    return Err(ResponseCode::YXRRSet);
+                                } else {
+                                    continue;
+                                }
+                            }
+                        }
+                    } else {
+                        return Err(ResponseCode::FormErr);
+                    }
+                }
+                class if class == self.class =>
+                // zone     rrset    rr       RRset exists (value dependent)
+                {
+                    if !self
+                        .lookup(&required_name, require.rr_type(), LookupOptions::default())
+                        .await
+                        .unwrap_or_default()
+                        .iter()
+                        .any(|rr| rr == require)
+                    {
+                        return Err(ResponseCode::NXRRSet);
+                    } else {
+                        continue;
+                    }
+                }
+                _ => return Err(ResponseCode::FormErr),
+            }
+        }
+
+        // if we didn't bail everything checked out...
+        Ok(())
+    }
+
+    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    ///
+    /// ```text
+    ///
+    /// 3.3 - Check Requestor's Permissions
+    ///
+    /// 3.3.1. Next, the requestor's permission to update the RRs named in
+    ///   the Update Section may be tested in an implementation dependent
+    ///   fashion or using mechanisms specified in a subsequent Secure DNS
+    ///   Update protocol.  If the requestor does not have permission to
+    ///   perform these updates, the server may write a warning message in its
+    ///   operations log, and may either signal REFUSED to the requestor, or
+    ///   ignore the permission problem and proceed with the update.
+    ///
+    /// 3.3.2. While the exact processing is implementation defined, if these
+    ///   verification activities are to be performed, this is the point in the
+    ///   server's processing where such performance should take place, since
+    ///   if a REFUSED condition is encountered after an update has been
+    ///   partially applied, it will be necessary to undo the partial update
+    ///   and restore the zone to its original state before answering the
+    ///   requestor.
+    /// ```
+    ///
+    ///
+    /// The update must be signed with TSIG by one of the keys added with `add_update_tsig_key`,
+    ///  or with SIG(0) by one of the KEY records of the zone, see `add_update_auth_key`.
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    #[allow(clippy::blocks_in_if_conditions)]
+    pub async fn authorize_update(&self, update_message: &MessageRequest) -> UpdateResult<()> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        use crate::client::rr::dnssec::MessageSigner;
+
+        // 3.3.3 - Pseudocode for Permission Checking
+        //
+        //      if (security policy exists)
+        //           if (this update is not permitted)
+        //                if (local option)
+        //                     log a message about permission problem
+        //                if (local option)
+        //                     return (REFUSED)
+
+        let sig0s: &[Record] = update_message.sig0();
+        debug!("authorizing with: {:?}", sig0s);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| ResponseCode::ServFail)?
+            .as_secs() as u32;
+
+        // verify tsig, with the keys registered for the zone
+        if let Some(signed_bytes) = update_message.signed_bytes() {
+            let inner = self.inner.read().await;
+            let verified = inner.tsig_keys.iter().any(|signer| {
+                match signer.verify_message_byte(None, signed_bytes, true) {
+                    Ok((_, range, _)) if range.contains(&(now as u64)) => {
+                        info!("verified tsig with key: {}", signer.signer_name());
+                        true
+                    }
+                    Ok(_) => {
+                        debug!(
+                            "tsig time is outside of the fudge of key: {}",
+                            signer.signer_name()
+                        );
+                        false
+                    }
+                    Err(e) => {
+                        debug!(
+                            "did not verify tsig with key: {}: {}",
+                            signer.signer_name(),
+                            e
+                        );
+                        false
+                    }
+                }
+            });
+
+            return if verified {
+                Ok(())
+            } else {
+                warn!(
+                    "no tsig key verified the update: id {}",
+                    update_message.id()
+                );
+                Err(ResponseCode::Refused)
+            };
+        }
+
+        // verify sig0, with the KEY records in the zone
+        if !sig0s.is_empty() {
+            let mut found_key = false;
+            for sig in sig0s.iter().filter_map(|sig0| {
+                sig0.data()
+                    .and_then(RData::as_dnssec)
+                    .and_then(DNSSECRData::as_sig)
+            }) {
+                let name = LowerName::from(sig.signer_name());
+                let keys = self
+                    .lookup(&name, RecordType::KEY, LookupOptions::default())
+                    .await;
+
+                let keys = match keys {
+                    Ok(keys) => keys,
+                    Err(_) => continue, // error trying to lookup a key by that name, try the next one.
+                };
+
+                debug!("found keys {:?}", keys);
+                // TODO: check key usage flags and restrictions
+                found_key = keys
+                    .iter()
+                    .filter_map(|rr_set| {
+                        rr_set
+                            .data()
+                            .and_then(RData::as_dnssec)
+                            .and_then(DNSSECRData::as_key)
+                    })
+                    .any(|key| {
+                        // checks the key tag, algorithm and the inception and expiration of the sig
+                        MessageSigner::verifier(key.clone(), sig.signer_name().clone())
+                            .verify_sig0(update_message, sig, now)
+                            .map(|_| {
+                                info!("verified sig: {:?} with key: {:?}", sig, key);
+                                true
+                            })
+                            .unwrap_or_else(|_| {
+                                debug!("did not verify sig: {:?} with key: {:?}", sig, key);
+                                false
+                            })
+                    });
+
+                if found_key {
+                    break; // stop searching for matching keys, we found one
+                }
+            }
+
+            if found_key {
+                return Ok(());
+            }
+        } else {
+            warn!(
+                "no sig0 matched registered records: id {}",
+                update_message.id()
+            );
+        }
+
+        // getting here, we will always default to rejecting the request
+        //  the code will only ever explicitly return authorized actions.
+        Err(ResponseCode::Refused)
+    }
+
+    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    ///
+    /// ```text
+    ///
+    /// 3.4 - Process Update Section
+    ///
+    ///   Next, the Update Section is processed as follows.
+    ///
+    /// 3.4.1 - Prescan
+    ///
+    ///   The Update Section is parsed into RRs and each RR's CLASS is checked
+    ///   to see if it is ANY, NONE, or the same as the Zone Class, else signal
+    ///   a FORMERR to the requestor.  Using the definitions in Section 1.2,
+    ///   each RR's NAME must be in the zone specified by the Zone Section,
+    ///   else signal NOTZONE to the requestor.
+    ///
+    /// 3.4.1.2. For RRs whose CLASS is not ANY, check the TYPE and if it is
+    ///   ANY, AXFR, MAILA, MAILB, or any other QUERY metatype, or any
+    ///   unrecognized type, then signal FORMERR to the requestor.  For RRs
+    ///   whose CLASS is ANY or NONE, check the TTL to see that it is zero (0),
+    ///   else signal a FORMERR to the requestor.  For any RR whose CLASS is
+    ///   ANY, check the RDLENGTH to make sure that it is zero (0) (that is,
+    ///   the RDATA field is empty), and that the TYPE is not AXFR, MAILA,
+    ///   MAILB, or any other QUERY metatype besides ANY, or any unrecognized
+    ///   type, else signal FORMERR to the requestor.
+    /// ```
+    #[allow(clippy::unused_unit)]
+    pub async fn pre_scan(&self, records: &[Record]) -> UpdateResult<()> {
+        // 3.4.1.3 - Pseudocode For Update Section Prescan
+        //
+        //      [rr] for rr in updates
+        //           if (zone_of(rr.name) != ZNAME)
+        //                return (NOTZONE);
+        //           if (rr.class == zclass)
+        //                if (rr.type & ANY|AXFR|MAILA|MAILB)
+        //                     return (FORMERR)
+        //           elsif (rr.class == ANY)
+        //                if (rr.ttl != 0 || rr.rdlength != 0
+        //                    || rr.type & AXFR|MAILA|MAILB)
+        //                     return (FORMERR)
+        //           elsif (rr.class == NONE)
+        //                if (rr.ttl != 0 || rr.type & ANY|AXFR|MAILA|MAILB)
+        //                     return (FORMERR)
+        //           else
+        //                return (FORMERR)
+        for rr in records {
+            if !self.origin().zone_of(&rr.name().into()) {
+                return Err(ResponseCode::NotZone);
+            }
+
+            let class: DNSClass = rr.dns_class();
+            if class == self.class {
+                match rr.rr_type() {
+                    RecordType::ANY | RecordType::AXFR | RecordType::IXFR => {
+                        return Err(ResponseCode::FormErr);
+                    }
+                    _ => (),
+                }
+            } else {
+                match class {
+                    DNSClass::ANY => {
+                        if rr.ttl() != 0 {
+                            return Err(ResponseCode::FormErr);
+                        }
+                        if let None | Some(RData::NULL(..)) = rr.data() {
+                            ()
+                        } else {
+                            return Err(ResponseCode::FormErr);
+                        }
+                        match rr.rr_type() {
+                            RecordType::AXFR | RecordType::IXFR => {
+                                return Err(ResponseCode::FormErr);
+                            }
+                            _ => (),
+                        }
+                    }
+                    DNSClass::NONE => {
+                        if rr.ttl() != 0 {
+                            return Err(ResponseCode::FormErr);
+                        }
+                        match rr.rr_type() {
+                            RecordType::ANY | RecordType::AXFR | RecordType::IXFR => {
+                                return Err(ResponseCode::FormErr);
+                            }
+                            _ => (),
+                        }
+                    }
+                    _ => return Err(ResponseCode::FormErr),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Updates the specified records according to the update section, without incrementing the
+    ///  serial or signing the zone.
+    ///
+    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    ///
+    /// ```text
+    ///
+    /// 3.4.2.6 - Table Of Metavalues Used In Update Section
+    ///
+    ///   CLASS    TYPE     RDATA    Meaning
+    ///   ---------------------------------------------------------
+    ///   ANY      ANY      empty    Delete all RRsets from a name
+    ///   ANY      rrset    empty    Delete an RRset
+    ///   NONE     rrset    rr       Delete an RR from an RRset
+    ///   zone     rrset    rr       Add to an RRset
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - set of record instructions for update following above rules
+    pub async fn update_records(&self, records: &[Record]) -> UpdateResult<bool> {
+        let mut updated = false;
+        let serial: u32 = self.serial().await;
+
+        // 3.4.2.7 - Pseudocode For Update Section Processing
+        //
+        //      [rr] for rr in updates
+        //           if (rr.class == zclass)
+        //                if (rr.type == CNAME)
+        //                     if (zone_rrset<rr.name, ~CNAME>)
+        //                          next [rr]
+        //                elsif (zone_rrset<rr.name, CNAME>)
+        //                     next [rr]
+        //                if (rr.type == SOA)
+        //                     if (!zone_rrset<rr.name, SOA> ||
+        //                         zone_rr<rr.name, SOA>.serial > rr.soa.serial)
+        //                          next [rr]
+        //                for zrr in zone_rrset<rr.name, rr.type>
+        //                     if (rr.type == CNAME || rr.type == SOA ||
+        //                         (rr.type == WKS && rr.proto == zrr.proto &&
+        //                          rr.address == zrr.address) ||
+        //                         rr.rdata == zrr.rdata)
+        //                          zrr = rr
+        //                          next [rr]
+        //                zone_rrset<rr.name, rr.type> += rr
+        //           elsif (rr.class == ANY)
+        //                if (rr.type == ANY)
+        //                     if (rr.name == zname)
+        //                          zone_rrset<rr.name, ~(SOA|NS)> = Nil
+        //                     else
+        //                          zone_rrset<rr.name, *> = Nil
+        //                elsif (rr.name == zname &&
+        //                       (rr.type == SOA || rr.type == NS))
+        //                     next [rr]
+        //                else
+        //                     zone_rrset<rr.name, rr.type> = Nil
+        //           elsif (rr.class == NONE)
+        //                if (rr.type == SOA)
+        //                     next [rr]
+        //                if (rr.type == NS && zone_rrset<rr.name, NS> == rr)
+        //                     next [rr]
+        //                zone_rr<rr.name, rr.type, rr.data> = Nil
+        //      return (NOERROR)
+        for rr in records {
+            let rr_name = LowerName::from(rr.name());
+            let rr_key = RrKey::new(rr_name.clone(), rr.rr_type());
+
+            match rr.dns_class() {
+                class if class == self.class => {
+                    // RFC 2136 - 3.4.2.2. Any Update RR whose CLASS is the same as ZCLASS is added to
+                    //  the zone.  In case of duplicate RDATAs (which for SOA RRs is always
+                    //  the case, and for WKS RRs is the case if the ADDRESS and PROTOCOL
+                    //  fields both match), the Zone RR is replaced by Update RR.  If the
+                    //  TYPE is SOA and there is no Zone SOA RR, or the new SOA.SERIAL is
+                    //  lower (according to [RFC1982]) than or equal to the current Zone SOA
+                    //  RR's SOA.SERIAL, the Update RR is ignored.  In the case of a CNAME
+                    //  Update RR and a non-CNAME Zone RRset or vice versa, ignore the CNAME
+                    //  Update RR, otherwise replace the CNAME Zone RR with the CNAME Update
+                    //  RR.
+
+                    // zone     rrset    rr       Add to an RRset
+                    info!("upserting record: {:?}", rr);
+                    updated = self.upsert(rr.clone(), serial).await || updated;
+                }
+                DNSClass::ANY => {
+                    // This is a delete of entire RRSETs, either many or one. In either case, the spec is clear:
+                    match rr.rr_type() {
+                        t @ RecordType::SOA | t @ RecordType::NS if rr_name == *self.origin() => {
+                            // SOA and NS records are not to be deleted if they are the origin records
+                            info!("skipping delete of {:?} see RFC 2136 - 3.4.2.3", t);
+                            continue;
+                        }
+                        RecordType::ANY => {
+                            // RFC 2136 - 3.4.2.3. For any Update RR whose CLASS is ANY and whose TYPE is ANY,
+                            //   all Zone RRs with the same NAME are deleted, unless the NAME is the
+                            //   same as ZNAME in which case only those RRs whose TYPE is other than
+                            //   SOA or NS are deleted.
+
+                            // ANY      ANY      empty    Delete all RRsets from a name
+                            info!(
+                                "deleting all records at name (not SOA or NS at origin): {:?}",
+                                rr_name
+                            );
+                            let origin = self.origin();
+                            let to_delete = self
+                                .records()
+                                .await
+                                .keys()
+                                .filter(|k| {
+                                    !((k.record_type == RecordType::SOA
+                                        || k.record_type == RecordType::NS)
+                                        && k.name != *origin)
+                                })
+                                .filter(|k| k.name == rr_name)
+                                .cloned()
+                                .collect::<Vec<RrKey>>();
+
+                            for delete in to_delete {
+                                self.records_mut().await.remove(&delete);
+                                updated = true;
+                            }
+                        }
+                        _ => {
+                            // RFC 2136 - 3.4.2.3. For any Update RR whose CLASS is ANY and
+                            //   whose TYPE is not ANY all Zone RRs with the same NAME and TYPE are
+                            //   deleted, unless the NAME is the same as ZNAME in which case neither
+                            //   SOA or NS RRs will be deleted.
+
+                            // ANY      rrset    empty    Delete an RRset
+                            if let None | Some(RData::NULL(..)) = rr.data() {
+                                let deleted = self.records_mut().await.remove(&rr_key);
+                                info!("deleted rrset: {:?}", deleted);
+                                updated = updated || deleted.is_some();
+                            } else {
+                                info!("expected empty rdata: {:?}", rr);
+                                return Err(ResponseCode::FormErr);
+                            }
+                        }
+                    }
+                }
+                DNSClass::NONE => {
+                    info!("deleting specific record: {:?}", rr);
+                    // NONE     rrset    rr       Delete an RR from an RRset
+                    if let Some(rrset) = self.records_mut().await.get_mut(&rr_key) {
+                        // b/c this is an Arc, we need to clone, then remove, and replace the node.
+                        let mut rrset_clone: RecordSet = RecordSet::clone(&*rrset);
+                        let deleted = rrset_clone.remove(rr, serial);
+                        info!("deleted ({}) specific record: {:?}", deleted, rr);
+                        updated = updated || deleted;
+
+                        if deleted {
+                            *rrset = Arc::new(rrset_clone);
+                        }
+                    }
+                }
+                class => {
+                    info!("unexpected DNS Class: {:?}", class);
+                    return Err(ResponseCode::FormErr);
+                }
+            }
+        }
+
+        Ok(updated)
+    }
+
+    /// Records the changes to the zone since `previous` for IXFR, and notifies the secondaries
+    ///
+    /// This should be called once the serial of the zone has been incremented.
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - the records of the zone before the changes, see `records()`
+    ///
+    /// # Return value
+    ///
+    /// The changes to the zone, None if the SOA is missing or the serial didn't change.
+    pub(crate) async fn zone_changed(
+        &self,
+        previous: &BTreeMap<RrKey, Arc<RecordSet>>,
+    ) -> Option<ZoneDiff> {
+        let mut inner = self.inner.write().await;
+        let diff = ZoneDiff::between(self.origin(), previous, &inner.records)?;
+        if diff.from_serial() == diff.to_serial() {
+            return None;
+        }
+
+        if inner.diffs.len() >= MAX_ZONE_DIFFS {
+            inner.diffs.pop_front();
+        }
+        inner.diffs.push_back(diff.clone());
+        drop(inner);
+
+        zone_transfer::spawn_notify(self.origin().into(), diff.to().clone(), &self.also_notify);
+
+        Some(diff)
+    }
+
+    /// Adds previously recorded changes of the zone, e.g. from a journal, for serving IXFR
+    pub(crate) async fn push_diff(&self, diff: ZoneDiff) {
+        let mut inner = self.inner.write().await;
+        if inner.diffs.len() >= MAX_ZONE_DIFFS {
+            inner.diffs.pop_front();
+        }
+        inner.diffs.push_back(diff);
+    }
 }
 
 #[derive(Default)]
@@ -316,13 +1240,135 @@ struct InnerInMemory {
     //   for this, in some form, perhaps alternate root zones...
     #[cfg(feature = "dnssec")]
     secure_keys: Vec<SigSigner>,
+    // keys which sign the zone according to their role, while active in their schedule
+    #[cfg(feature = "dnssec")]
+    scheduled_keys: Vec<ScheduledKey>,
+    // the states of the scheduled keys when the zone was last signed
+    #[cfg(feature = "dnssec")]
+    key_states: Vec<KeyState>,
+    // when the zone must be re-signed before signatures expire, seconds since the unix epoch
+    #[cfg(feature = "dnssec")]
+    resign_at: Option<u64>,
+    // TSIG keys which are authorized to perform updates
+    #[cfg(feature = "dnssec")]
+    tsig_keys: Vec<TSigner>,
+    // TSIG keys which are authorized to transfer the zone
+    #[cfg(feature = "dnssec")]
+    transfer_tsig_keys: Vec<TSigner>,
+    // leave the unsigned delegations out of the NSEC3 chain
+    #[cfg(feature = "dnssec")]
+    nsec3_opt_out: bool,
+    // the most recent changes to the zone, oldest first
+    diffs: VecDeque<ZoneDiff>,
+}
+
+/// A signing key with its role and lifetime in the zone
+#[cfg(feature = "dnssec")]
+struct ScheduledKey {
+    signer: SigSigner,
+    dnskey: DNSKEY,
+    role: KeyRole,
+    timings: KeyTimings,
 }
 
 impl InnerInMemory {
-    /// Retrieve the Signer, which contains the private keys, for this zone
+    /// True if there are any keys to sign the zone with
     #[cfg(feature = "dnssec")]
-    fn secure_keys(&self) -> &[SigSigner] {
-        &self.secure_keys
+    fn is_signed(&self) -> bool {
+        !self.secure_keys.is_empty() || !self.scheduled_keys.is_empty()
+    }
+
+    /// Retrieve the Signers, which contain the private keys, which sign the record type at the time
+    #[cfg(feature = "dnssec")]
+    fn signers(&self, record_type: RecordType, now: u64) -> Vec<&SigSigner> {
+        Self::active_signers(&self.secure_keys, &self.scheduled_keys, record_type, now)
+    }
+
+    /// Signs records which are synthesized on demand, e.g. the addresses of an ANAME
+    #[cfg(feature = "dnssec")]
+    fn sign_synthesized(&self, rr_set: &mut RecordSet, origin: &LowerName, zone_class: DNSClass) {
+        let signers = self.signers(rr_set.record_type(), unix_now());
+
+        Self::sign_rrset(rr_set, &signers, self.minimum_ttl(origin), zone_class)
+            // rather than failing the request, we'll just warn
+            .map_err(|e| {
+                warn!(
+                    "failed to sign {} {}: {}",
+                    rr_set.name(),
+                    rr_set.record_type(),
+                    e
+                )
+            })
+            .ok();
+    }
+
+    /// Keys without a schedule sign everything, scheduled keys only what their role covers
+    #[cfg(feature = "dnssec")]
+    fn active_signers<'a>(
+        secure_keys: &'a [SigSigner],
+        scheduled_keys: &'a [ScheduledKey],
+        record_type: RecordType,
+        now: u64,
+    ) -> Vec<&'a SigSigner> {
+        secure_keys
+            .iter()
+            .chain(
+                scheduled_keys
+                    .iter()
+                    .filter(|key| key.timings.state(now).is_active())
+                    .filter(|key| key.role.signs(record_type))
+                    .map(|key| &key.signer),
+            )
+            .collect()
+    }
+
+    /// Adds a key which is published and signs the zone as scheduled, on the next signing
+    #[cfg(feature = "dnssec")]
+    fn add_scheduled_signing_key(
+        &mut self,
+        signer: SigSigner,
+        role: KeyRole,
+        timings: KeyTimings,
+    ) -> DnsSecResult<()> {
+        let dnskey = DNSKEY::new(
+            signer.is_zone_signing_key(),
+            role.is_secure_entry_point(),
+            false,
+            signer.algorithm(),
+            signer.key().to_public_bytes()?,
+        );
+
+        // the key tag of the signatures is that of the signer's DNSKEY, it must be the published one
+        if dnskey.calculate_key_tag()? != signer.calculate_key_tag()? {
+            return Err(format!(
+                "the SEP flag of the signer's DNSKEY does not match the role: {:?}",
+                role
+            )
+            .into());
+        }
+
+        self.scheduled_keys.push(ScheduledKey {
+            signer,
+            dnskey,
+            role,
+            timings,
+        });
+        Ok(())
+    }
+
+    /// Removes the record from its record set, and the record set if it's then empty
+    #[cfg(feature = "dnssec")]
+    fn remove(&mut self, record: &Record, serial: u32) {
+        let rr_key = RrKey::new(record.name().into(), record.rr_type());
+
+        if let Some(rrset) = self.records.get_mut(&rr_key) {
+            let rrset = Arc::make_mut(rrset);
+            rrset.remove(record, serial);
+
+            if rrset.is_empty() {
+                self.records.remove(&rr_key);
+            }
+        }
     }
 
     // /// Get all the records
@@ -402,7 +1448,6 @@ impl InnerInMemory {
             })
             .map(|(_key, rr_set)| rr_set);
 
-        // TODO: maybe unwrap this recursion.
         match lookup {
             None => self.inner_lookup_wildcard(name, record_type, lookup_options),
             l => l.cloned(),
@@ -415,49 +1460,57 @@ impl InnerInMemory {
         record_type: RecordType,
         lookup_options: LookupOptions,
     ) -> Option<Arc<RecordSet>> {
-        // if this is a wildcard or a root, both should break continued lookups
-        let wildcard = if name.is_wildcard() || name.is_root() {
-            return None;
-        } else {
-            name.clone().into_wildcard()
-        };
+        let wildcard = self.source_of_synthesis(name)?;
 
-        #[allow(clippy::needless_late_init)]
         self.inner_lookup(&wildcard, record_type, lookup_options)
             // we need to change the name to the query name in the result set since this was a wildcard
-            .map(|rrset| {
-                let mut new_answer =
-                    RecordSet::new(name.borrow(), rrset.record_type(), rrset.ttl());
-
-                let records;
-                let _rrsigs: Vec<&Record>;
-                cfg_if! {
-                    if #[cfg(feature = "dnssec")] {
-                        let (records_tmp, rrsigs_tmp) = rrset
-                            .records(lookup_options.is_dnssec(), lookup_options.supported_algorithms())
-                            .partition(|r| r.record_type() != RecordType::RRSIG);
-                        records = records_tmp;
-                        _rrsigs = rrsigs_tmp;
-                    } else {
-                        let (records_tmp, rrsigs_tmp) = (rrset.records_without_rrsigs(), Vec::with_capacity(0));
-                        records = records_tmp;
-                        _rrsigs = rrsigs_tmp;
-                    }
-                };
+            .map(|rrset| Arc::new(synthesis::synthesize_wildcard(name, &rrset, lookup_options)))
+    }
 
-                for record in records {
-                    if let Some(rdata) = record.data() {
-                        new_answer.add_rdata(rdata.clone());
-                    }
-                }
+    /// True if the name has records, or is an empty non-terminal with records below it
+    fn name_exists(&self, name: &LowerName) -> bool {
+        // in the canonical order the names below a name directly follow it
+        let start_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::min_value()));
 
-                #[cfg(feature = "dnssec")]
-                for rrsig in _rrsigs {
-                    new_answer.insert_rrsig(rrsig.clone())
-                }
+        self.records
+            .range(&start_range_key..)
+            .next()
+            .map_or(false, |(key, _)| name.zone_of(key.name()))
+    }
 
-                Arc::new(new_answer)
-            })
+    /// The wildcard at the closest encloser of the name, which synthesizes its records, see
+    ///  RFC 4592
+    ///
+    /// Wildcards don't apply to names which exist, not even as empty non-terminals, nor below
+    ///  a delegation. Returns `None` if there is no such wildcard in the zone.
+    fn source_of_synthesis(&self, name: &LowerName) -> Option<LowerName> {
+        if name.is_wildcard() || self.name_exists(name) {
+            return None;
+        }
+
+        // the closest encloser is the longest ancestor which exists
+        let mut child = name.clone();
+        let mut encloser = name.base_name();
+        while !self.name_exists(&encloser) {
+            if encloser.is_root() {
+                return None;
+            }
+
+            child = encloser;
+            encloser = child.base_name();
+        }
+
+        // names below a delegation are not answered from this zone
+        let delegation = RrKey::new(encloser, RecordType::NS);
+        if self.records.contains_key(&delegation)
+            && !self
+                .records
+                .contains_key(&RrKey::new(delegation.name, RecordType::SOA))
+        {
+            return None;
+        }
+
+        synthesis::wildcard_of(&child).filter(|wildcard| self.name_exists(wildcard))
     }
 
     /// Search for additional records to include in the response
@@ -528,7 +1581,6 @@ impl InnerInMemory {
         }
     }
 
-    #[cfg(any(feature = "dnssec", feature = "sqlite"))]
     fn increment_soa_serial(&mut self, origin: &LowerName, dns_class: DNSClass) -> u32 {
         // we'll remove the SOA and then replace it
         let rr_key = RrKey::new(origin.clone(), RecordType::SOA);
@@ -649,34 +1701,156 @@ impl InnerInMemory {
     #[cfg(feature = "dnssec")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
     fn secure_zone_mut(&mut self, origin: &LowerName, dns_class: DNSClass) -> DnsSecResult<()> {
+        self.secure_zone_at(origin, dns_class, unix_now())
+    }
+
+    /// (Re)publishes the scheduled keys and signs the zone with the keys active at the time
+    #[cfg(feature = "dnssec")]
+    fn secure_zone_at(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        now: u64,
+    ) -> DnsSecResult<()> {
+        self.publish_keys(origin, dns_class, now)?;
+
         // TODO: only call nsec_zone after adds/deletes
         // needs to be called before incrementing the soa serial, to make sure IXFR works properly
-        self.nsec_zone(origin, dns_class);
+        self.nsec_zone(origin, dns_class)?;
 
         // need to resign any records at the current serial number and bump the number.
         // first bump the serial number on the SOA, so that it is resigned with the new serial.
         self.increment_soa_serial(origin, dns_class);
 
         // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone(origin, dns_class)
+        self.sign_zone(origin, dns_class, now)?;
+
+        self.key_states = self
+            .scheduled_keys
+            .iter()
+            .map(|key| key.timings.state(now))
+            .collect();
+        Ok(())
+    }
+
+    /// Re-signs the zone if a scheduled key changed state, or the signatures are expiring
+    #[cfg(feature = "dnssec")]
+    fn maintain_keys(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        now: u64,
+    ) -> DnsSecResult<bool> {
+        if !self.is_signed() {
+            return Ok(false);
+        }
+
+        let key_states_changed = self
+            .scheduled_keys
+            .iter()
+            .map(|key| key.timings.state(now))
+            .ne(self.key_states.iter().copied());
+        let is_expiring = self.resign_at.map_or(true, |resign_at| now >= resign_at);
+
+        if !key_states_changed && !is_expiring {
+            return Ok(false);
+        }
+
+        debug!(
+            "re-signing zone: {}, keys changed: {}",
+            origin, key_states_changed
+        );
+        self.secure_zone_at(origin, dns_class, now)?;
+        Ok(true)
+    }
+
+    /// Publishes the DNSKEYs of the scheduled keys in the zone, removing those which have been
+    ///  deleted, and the CDS and CDNSKEY of the active secure entry points
+    #[cfg(feature = "dnssec")]
+    fn publish_keys(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        now: u64,
+    ) -> DnsSecResult<()> {
+        if self.scheduled_keys.is_empty() {
+            return Ok(());
+        }
+
+        let zone_ttl = self.minimum_ttl(origin);
+        let serial = self.serial(origin);
+        let name = Name::from(origin);
+
+        let mut published = Vec::new();
+        let mut removed = Vec::new();
+        for key in &self.scheduled_keys {
+            let state = key.timings.state(now);
+            let dnskey = Record::from_rdata(
+                name.clone(),
+                zone_ttl,
+                RData::DNSSEC(DNSSECRData::DNSKEY(key.dnskey.clone())),
+            );
+
+            if state.is_published() {
+                published.push(dnskey);
+            } else {
+                removed.push(dnskey);
+            }
+
+            // RFC 7344, the parent updates its DS to the secure entry points signing the DNSKEYs
+            if state.is_active() && key.role.is_secure_entry_point() {
+                let digest = key.dnskey.to_digest(&name, DigestType::SHA256)?;
+                let cds = DS::new(
+                    key.dnskey.calculate_key_tag()?,
+                    key.dnskey.algorithm(),
+                    DigestType::SHA256,
+                    digest.as_ref().to_owned(),
+                );
+
+                published.push(Record::from_rdata(
+                    name.clone(),
+                    zone_ttl,
+                    RData::DNSSEC(DNSSECRData::CDS(cds)),
+                ));
+                published.push(Record::from_rdata(
+                    name.clone(),
+                    zone_ttl,
+                    RData::DNSSEC(DNSSECRData::CDNSKEY(key.dnskey.clone())),
+                ));
+            }
+        }
+
+        // the CDS and CDNSKEY sets are entirely managed here
+        self.records
+            .remove(&RrKey::new(origin.clone(), RecordType::CDS));
+        self.records
+            .remove(&RrKey::new(origin.clone(), RecordType::CDNSKEY));
+
+        for record in removed {
+            self.remove(&record, serial);
+        }
+        for record in published {
+            self.upsert(record, serial, dns_class);
+        }
+
+        Ok(())
     }
 
-    /// Dummy implementation for when DNSSEC is disabled.
+    /// (Re)generates the NSEC records, or NSEC3 records if there is an NSEC3PARAM at the origin
     #[cfg(feature = "dnssec")]
-    fn nsec_zone(&mut self, origin: &LowerName, dns_class: DNSClass) {
+    fn nsec_zone(&mut self, origin: &LowerName, dns_class: DNSClass) -> DnsSecResult<()> {
         use crate::client::rr::rdata::NSEC;
 
         // only create nsec records for secure zones
-        if self.secure_keys.is_empty() {
-            return;
+        if !self.is_signed() {
+            return Ok(());
         }
-        debug!("generating nsec records: {}", origin);
 
         // first remove all existing nsec records
         let delete_keys: Vec<RrKey> = self
             .records
             .keys()
-            .filter(|k| k.record_type == RecordType::NSEC)
+            .filter(|k| k.record_type == RecordType::NSEC || k.record_type == RecordType::NSEC3)
             .cloned()
             .collect();
 
@@ -684,6 +1858,11 @@ impl InnerInMemory {
             self.records.remove(&key);
         }
 
+        if let Some(nsec3param) = self.nsec3param(origin).cloned() {
+            return self.nsec3_zone(origin, dns_class, &nsec3param);
+        }
+        debug!("generating nsec records: {}", origin);
+
         // now go through and generate the nsec records
         let ttl = self.minimum_ttl(origin);
         let serial = self.serial(origin);
@@ -725,6 +1904,186 @@ impl InnerInMemory {
             let upserted = self.upsert(record, serial, dns_class);
             debug_assert!(upserted);
         }
+
+        Ok(())
+    }
+
+    /// Returns the NSEC3PARAM of the zone, if the zone is to be signed with NSEC3
+    #[cfg(feature = "dnssec")]
+    fn nsec3param(&self, origin: &LowerName) -> Option<&NSEC3PARAM> {
+        let rr_key = RrKey::new(origin.clone(), RecordType::NSEC3PARAM);
+
+        self.records
+            .get(&rr_key)
+            .and_then(|rrset| rrset.records_without_rrsigs().next())
+            .and_then(Record::data)
+            .and_then(RData::as_dnssec)
+            .and_then(DNSSECRData::as_nsec3param)
+    }
+
+    /// Replaces the NSEC3PARAM of the zone, which is then signed with NSEC3 instead of NSEC
+    #[cfg(feature = "dnssec")]
+    fn set_nsec3(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        nsec3param: NSEC3PARAM,
+        opt_out: bool,
+    ) {
+        // the flags of the NSEC3PARAM must be zero, opt-out is only flagged in the NSEC3 records
+        let nsec3param = NSEC3PARAM::new(
+            nsec3param.hash_algorithm(),
+            false,
+            nsec3param.iterations(),
+            nsec3param.salt().to_vec(),
+        );
+        let record = Record::from_rdata(
+            origin.clone().into(),
+            self.minimum_ttl(origin),
+            RData::DNSSEC(DNSSECRData::NSEC3PARAM(nsec3param)),
+        );
+
+        let serial = self.serial(origin);
+        self.records
+            .remove(&RrKey::new(origin.clone(), RecordType::NSEC3PARAM));
+        self.upsert(record, serial, dns_class);
+        self.nsec3_opt_out = opt_out;
+    }
+
+    /// Generates the NSEC3 chain for the zone, see RFC 5155 Section 7.1
+    ///
+    /// All names in the zone, including empty non-terminals, get an NSEC3 record with the hash
+    ///  parameters from the NSEC3PARAM. With opt-out, the unsigned delegations, the names below
+    ///  them and the empty non-terminals only above those are left out of the chain, and all the
+    ///  NSEC3 records are flagged as opt-out, RFC 5155 Section 7.1.
+    #[cfg(feature = "dnssec")]
+    fn nsec3_zone(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        nsec3param: &NSEC3PARAM,
+    ) -> DnsSecResult<()> {
+        use crate::client::rr::dnssec::nsec3_proof::{hash_name, hashed_owner_name};
+        use crate::client::rr::rdata::NSEC3;
+
+        debug!("generating nsec3 records: {}", origin);
+
+        let ttl = self.minimum_ttl(origin);
+        let serial = self.serial(origin);
+        let origin_name = Name::from(origin.clone());
+
+        // delegations without a DS are unsigned
+        let opt_out = self.nsec3_opt_out;
+        let unsigned_delegations = self
+            .records
+            .keys()
+            .filter(|key| opt_out && key.record_type == RecordType::NS && key.name != *origin)
+            .filter(|key| {
+                !self
+                    .records
+                    .contains_key(&RrKey::new(key.name.clone(), RecordType::DS))
+            })
+            .map(|key| Name::from(key.name.clone()))
+            .collect::<Vec<_>>();
+
+        // collect all the names and their types, empty non-terminals have no types
+        let mut names: BTreeMap<Name, Vec<RecordType>> = BTreeMap::new();
+        for key in self.records.keys() {
+            let name = Name::from(key.name.clone());
+            if unsigned_delegations
+                .iter()
+                .any(|delegation| delegation.zone_of(&name))
+            {
+                continue;
+            }
+
+            let mut parent = name.base_name();
+            while parent != origin_name && origin_name.zone_of(&parent) {
+                names.entry(parent.clone()).or_default();
+                parent = parent.base_name();
+            }
+
+            names.entry(name).or_default().push(key.record_type);
+        }
+
+        let mut hashed = Vec::with_capacity(names.len());
+        for (name, mut types) in names {
+            if !types.is_empty() {
+                types.push(RecordType::RRSIG);
+            }
+
+            let hash = hash_name(
+                nsec3param.hash_algorithm(),
+                nsec3param.salt(),
+                nsec3param.iterations(),
+                &name,
+            )?;
+            hashed.push((hash, types));
+        }
+        hashed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut records: Vec<Record> = Vec::with_capacity(hashed.len());
+        for (i, (hash, types)) in hashed.iter().enumerate() {
+            // the last record wraps to the first
+            let (next, _) = &hashed[(i + 1) % hashed.len()];
+            let rdata = NSEC3::new(
+                nsec3param.hash_algorithm(),
+                opt_out,
+                nsec3param.iterations(),
+                nsec3param.salt().to_vec(),
+                next.clone(),
+                types.clone(),
+            );
+
+            let mut record = Record::with(
+                hashed_owner_name(hash, &origin_name)?,
+                RecordType::NSEC3,
+                ttl,
+            );
+            record.set_data(Some(RData::DNSSEC(DNSSECRData::NSEC3(rdata))));
+            records.push(record);
+        }
+
+        for record in records {
+            let upserted = self.upsert(record, serial, dns_class);
+            debug_assert!(upserted);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the NSEC3 records which prove the name doesn't exist, or has no data
+    #[cfg(feature = "dnssec")]
+    fn get_nsec3_records(&self, origin: &LowerName, name: &LowerName) -> Vec<Arc<RecordSet>> {
+        use crate::client::rr::dnssec::nsec3_proof::Nsec3Chain;
+
+        let nsec3s = self
+            .records
+            .values()
+            .filter(|rr_set| rr_set.record_type() == RecordType::NSEC3)
+            .collect::<Vec<_>>();
+
+        let origin = Name::from(origin.clone());
+        let chain = Nsec3Chain::new(
+            &origin,
+            nsec3s
+                .iter()
+                .flat_map(|rr_set| rr_set.records_without_rrsigs()),
+        );
+
+        let owners = match chain.and_then(|chain| chain.proof_owners(&name.clone().into())) {
+            Ok((_, owners)) => owners,
+            Err(e) => {
+                warn!("could not find NSEC3 proof for {}: {}", name, e);
+                return vec![];
+            }
+        };
+
+        nsec3s
+            .iter()
+            .filter(|rr_set| owners.contains(&rr_set.name()))
+            .map(|rr_set| Arc::clone(rr_set))
+            .collect()
     }
 
     /// Signs an RecordSet, and stores the RRSIGs in the RecordSet
@@ -740,7 +2099,7 @@ impl InnerInMemory {
     #[cfg(feature = "dnssec")]
     fn sign_rrset(
         rr_set: &mut RecordSet,
-        secure_keys: &[SigSigner],
+        secure_keys: &[&SigSigner],
         zone_ttl: u32,
         zone_class: DNSClass,
     ) -> DnsSecResult<()> {
@@ -831,15 +2190,19 @@ impl InnerInMemory {
 
     /// Signs any records in the zone that have serial numbers greater than or equal to `serial`
     #[cfg(feature = "dnssec")]
-    fn sign_zone(&mut self, origin: &LowerName, dns_class: DNSClass) -> DnsSecResult<()> {
+    fn sign_zone(&mut self, origin: &LowerName, dns_class: DNSClass, now: u64) -> DnsSecResult<()> {
         debug!("signing zone: {}", origin);
 
         let minimum_ttl = self.minimum_ttl(origin);
-        let secure_keys = &self.secure_keys;
-        let records = &mut self.records;
+        let Self {
+            ref secure_keys,
+            ref scheduled_keys,
+            ref mut records,
+            ..
+        } = self;
 
         // TODO: should this be an error?
-        if secure_keys.is_empty() {
+        if secure_keys.is_empty() && scheduled_keys.is_empty() {
             warn!(
                 "attempt to sign_zone {} for dnssec, but no keys available!",
                 origin
@@ -847,16 +2210,46 @@ impl InnerInMemory {
         }
 
         // sign all record_sets, as of 0.12.1 this includes DNSKEY
+        let mut sig_duration = None;
         for rr_set_orig in records.values_mut() {
+            let signers =
+                Self::active_signers(secure_keys, scheduled_keys, rr_set_orig.record_type(), now);
+            sig_duration = signers
+                .iter()
+                .map(|signer| signer.sig_duration().as_secs())
+                .chain(sig_duration)
+                .min();
+
             // because the rrset is an Arc, it must be cloned before mutated
             let rr_set = Arc::make_mut(rr_set_orig);
-            Self::sign_rrset(rr_set, secure_keys, minimum_ttl, dns_class)?;
+            Self::sign_rrset(rr_set, &signers, minimum_ttl, dns_class)?;
         }
 
+        // refresh the signatures half way through their validity
+        self.resign_at = sig_duration.map(|sig_duration| now + sig_duration / 2);
+
         Ok(())
     }
 }
 
+/// The key which signed the request with a valid TSIG within its time fudge
+#[cfg(feature = "dnssec")]
+pub(crate) fn verified_tsigner(keys: &[TSigner], request: &MessageRequest) -> Option<TSigner> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let signed_bytes = request.signed_bytes()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+    keys.iter()
+        .find(|signer| {
+            matches!(
+                signer.verify_message_byte(None, signed_bytes, true),
+                Ok((_, range, _)) if range.contains(&now)
+            )
+        })
+        .cloned()
+}
+
 /// Gets the next search name, and returns the RecordType that it originated from
 fn maybe_next_name(
     record_set: &RecordSet,
@@ -986,11 +2379,145 @@ impl Authority for InMemoryAuthority {
         Err(ResponseCode::NotImp)
     }
 
+    /// The TSIG key which signed the update, if it's one of the keys authorized to perform updates
+    ///  and the MAC is valid, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn update_tsigner(&self, update: &MessageRequest) -> Option<TSigner> {
+        let signed_bytes = update.signed_bytes()?;
+
+        self.inner
+            .read()
+            .await
+            .tsig_keys
+            .iter()
+            .find(|signer| signer.verify_message_byte(None, signed_bytes, true).is_ok())
+            .cloned()
+    }
+
+    /// Checks that the client is permitted to transfer the zone
+    ///
+    /// AXFR must be allowed, the client must be in the transfer ACL if one is configured, and if
+    ///  any transfer TSIG keys are configured the request must be signed by one of them.
+    async fn authorize_transfer(&self, request: &MessageRequest, src: IpAddr) -> UpdateResult<()> {
+        if !self.is_axfr_allowed() {
+            debug!("transfers are not allowed for: {}", self.origin);
+            return Err(ResponseCode::Refused);
+        }
+
+        if !self.transfer_acl.is_empty() && !self.transfer_acl.iter().any(|n| n.contains(&src)) {
+            warn!("transfer of {} refused for: {}", self.origin, src);
+            return Err(ResponseCode::Refused);
+        }
+
+        #[cfg(feature = "dnssec")]
+        {
+            let inner = self.inner.read().await;
+            if !inner.transfer_tsig_keys.is_empty()
+                && verified_tsigner(&inner.transfer_tsig_keys, request).is_none()
+            {
+                warn!(
+                    "no tsig key verified the transfer of {}: id {}",
+                    self.origin,
+                    request.id()
+                );
+                return Err(ResponseCode::Refused);
+            }
+        }
+        #[cfg(not(feature = "dnssec"))]
+        let _ = request;
+
+        Ok(())
+    }
+
+    /// The TSIG key which signed the transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn transfer_tsigner(&self, request: &MessageRequest) -> Option<TSigner> {
+        verified_tsigner(&self.inner.read().await.transfer_tsig_keys, request)
+    }
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR
+    async fn ixfr_diffs(&self, serial: u32) -> Option<Vec<ZoneDiff>> {
+        let inner = self.inner.read().await;
+        let start = inner.diffs.iter().position(|d| d.from_serial() == serial)?;
+
+        // the diffs must form an unbroken chain up to the current version
+        let diffs = inner.diffs.iter().skip(start).cloned().collect::<Vec<_>>();
+        let chained = diffs
+            .windows(2)
+            .all(|w| w[0].to_serial() == w[1].from_serial());
+        let current = diffs.last().map(ZoneDiff::to_serial) == Some(inner.serial(self.origin()));
+
+        if chained && current {
+            Some(diffs)
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the TXT records at the name with the values, for answering ACME challenges
+    ///
+    /// The serial of the zone is incremented, and the zone is re-signed if it's signed, so that the
+    ///  records are transferred to the secondaries, which the validation may query as well.
+    async fn set_challenge_txt(&self, name: &LowerName, values: &[String]) -> UpdateResult<()> {
+        if !self.origin.zone_of(name) {
+            return Err(ResponseCode::NotZone);
+        }
+
+        let mut inner = self.inner.write().await;
+        let previous = inner.records.clone();
+        let serial = inner.serial(self.origin());
+
+        inner
+            .records
+            .remove(&RrKey::new(name.clone(), RecordType::TXT));
+        for value in values {
+            let mut record = Record::from_rdata(
+                name.into(),
+                CHALLENGE_TTL,
+                RData::TXT(TXT::new(vec![value.clone()])),
+            );
+            record.set_dns_class(self.class);
+            inner.upsert(record, serial, self.class);
+        }
+
+        #[cfg(feature = "dnssec")]
+        let signed = inner.is_signed();
+        #[cfg(not(feature = "dnssec"))]
+        let signed = false;
+
+        if signed {
+            #[cfg(feature = "dnssec")]
+            inner
+                .secure_zone_mut(self.origin(), self.class)
+                .map_err(|e| {
+                    error!("failure securing zone: {}", e);
+                    ResponseCode::ServFail
+                })?;
+        } else {
+            inner.increment_soa_serial(self.origin(), self.class);
+        }
+        drop(inner);
+
+        self.zone_changed(&previous).await;
+        Ok(())
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
         &self.origin
     }
 
+    /// The length of the network of the client subnet in the geoip database, if the answers
+    ///  depend on the location of the client
+    fn client_subnet_scope(&self, _client_subnet: &ClientSubnet) -> u8 {
+        #[cfg(feature = "geoip")]
+        if let Some(geo) = &self.geo {
+            return geo.client_subnet_scope(_client_subnet);
+        }
+
+        0
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -1011,6 +2538,16 @@ impl Authority for InMemoryAuthority {
         query_type: RecordType,
         lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
+        #[cfg(feature = "trust-dns-resolver")]
+        if let Some(resolver) = &self.aname_resolver {
+            if let Some(lookup) = self
+                .lookup_external_aname(resolver, name, query_type, lookup_options)
+                .await?
+            {
+                return Ok(lookup);
+            }
+        }
+
         let inner = self.inner.read().await;
 
         // Collect the records from each rr_set
@@ -1062,58 +2599,21 @@ impl Authority for InMemoryAuthority {
                                 debug_assert_eq!(answer.record_type(), RecordType::ANAME);
 
                                 // in the case of ANAME the final record should be the A or AAAA record
-                                let (rdatas, a_aaaa_ttl) = {
-                                    let last_record = additionals.last();
-                                    let a_aaaa_ttl =
-                                        last_record.map_or(u32::max_value(), |r| r.ttl());
-
-                                    // grap the rdatas
-                                    let rdatas: Option<Vec<RData>> = last_record
-                                        .and_then(|record| match record.record_type() {
-                                            RecordType::A | RecordType::AAAA => {
-                                                // the RRSIGS will be useless since we're changing the record type
-                                                Some(record.records_without_rrsigs())
-                                            }
-                                            _ => None,
-                                        })
-                                        .map(|records| {
-                                            records
-                                                .filter_map(Record::data)
-                                                .cloned()
-                                                .collect::<Vec<_>>()
-                                        });
-
-                                    (rdatas, a_aaaa_ttl)
-                                };
-
-                                // now build up a new RecordSet
-                                //   the name comes from the ANAME record
-                                //   according to the rfc the ttl is from the ANAME
-                                //   TODO: technically we should take the min of the potential CNAME chain
-                                let ttl = answer.ttl().min(a_aaaa_ttl);
-                                let mut new_answer = RecordSet::new(answer.name(), query_type, ttl);
-
-                                for rdata in rdatas.into_iter().flatten() {
-                                    new_answer.add_rdata(rdata);
-                                }
+                                #[allow(unused_mut)]
+                                let mut new_answer = synthesis::synthesize_aname(
+                                    &answer,
+                                    query_type,
+                                    additionals.last().map(Arc::as_ref),
+                                );
 
                                 // if DNSSEC is enabled, and the request had the DO set, sign the recordset
                                 #[cfg(feature = "dnssec")]
-                                {
-                                    use tracing::warn;
-
-                                    // ANAME's are constructed on demand, so need to be signed before return
-                                    if lookup_options.is_dnssec() {
-                                        InnerInMemory::sign_rrset(
-                                            &mut new_answer,
-                                            inner.secure_keys(),
-                                            inner.minimum_ttl(self.origin()),
-                                            self.class(),
-                                        )
-                                        // rather than failing the request, we'll just warn
-                                        .map_err(|e| warn!("failed to sign ANAME record: {}", e))
-                                        .ok();
-                                    }
+                                if lookup_options.is_dnssec() {
+                                    inner.sign_synthesized(
+                                        &mut new_answer,
+                                        self.origin(),
+                                        self.class(),
+                                    );
                                 }
 
                                 // prepend answer to additionals here (answer is the ANAME record)
@@ -1148,11 +2648,8 @@ impl Authority for InMemoryAuthority {
         // TODO: can we get rid of this?
         let result = match result {
             Err(LookupError::ResponseCode(ResponseCode::NXDomain)) => {
-                if inner
-                    .records
-                    .keys()
-                    .any(|key| key.name() == name || name.zone_of(key.name()))
-                {
+                // a wildcard matching the name makes it exist, without the type
+                if inner.name_exists(name) || inner.source_of_synthesis(name).is_some() {
                     return Err(LookupError::NameExists);
                 } else {
                     let code = if self.origin().zone_of(name) {
@@ -1180,6 +2677,19 @@ impl Authority for InMemoryAuthority {
         let lookup_name = request_info.query.name();
         let record_type: RecordType = request_info.query.query_type();
 
+        #[cfg(feature = "geoip")]
+        if let Some(geo) = &self.geo {
+            let client = lookup_options
+                .client_subnet()
+                .map_or_else(|| request_info.src.ip(), ClientSubnet::address);
+            if let Some(lookup) = self
+                .lookup_geo(geo, lookup_name, record_type, client, lookup_options)
+                .await
+            {
+                return Ok(lookup);
+            }
+        }
+
         // if this is an AXFR zone transfer, verify that this is either the Secondary or Primary
         //  for AXFR the first and last record must be the SOA
         if RecordType::AXFR == record_type {
@@ -1222,7 +2732,16 @@ impl Authority for InMemoryAuthority {
                 lookup.await
             }
             // A standard Lookup path
-            _ => self.lookup(lookup_name, record_type, lookup_options).await,
+            _ => {
+                if let Some(pool) = self
+                    .pools
+                    .get(&RrKey::new(lookup_name.clone(), record_type))
+                {
+                    return Ok(self.lookup_pool(pool, lookup_options).await);
+                }
+
+                self.lookup(lookup_name, record_type, lookup_options).await
+            }
         }
     }
 
@@ -1244,6 +2763,12 @@ impl Authority for InMemoryAuthority {
             rr_set.record_type() == RecordType::NSEC
         }
 
+        // zones with an NSEC3PARAM are signed with NSEC3, see `nsec3_zone`
+        if inner.nsec3param(self.origin()).is_some() {
+            let proofs = inner.get_nsec3_records(self.origin(), name);
+            return Ok(LookupRecords::many(lookup_options, proofs).into());
+        }
+
         // TODO: need a BorrowdRrKey
         let rr_key = RrKey::new(name.clone(), RecordType::NSEC);
         let no_data = inner
@@ -1337,6 +2862,18 @@ impl DnssecAuthority for InMemoryAuthority {
         Self::inner_add_update_auth_key(&mut inner, name, key, self.origin(), self.class)
     }
 
+    /// Add a TSIG key that is authorized to perform updates against this authority
+    async fn add_update_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.inner.write().await.tsig_keys.push(signer);
+        Ok(())
+    }
+
+    /// Add a TSIG key that is authorized to transfer the zone from this authority
+    async fn add_transfer_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.inner.write().await.transfer_tsig_keys.push(signer);
+        Ok(())
+    }
+
     /// By adding a secure key, this will implicitly enable dnssec for the zone.
     ///
     /// # Arguments
@@ -1348,6 +2885,31 @@ impl DnssecAuthority for InMemoryAuthority {
         Self::inner_add_zone_signing_key(&mut inner, signer, self.origin(), self.class)
     }
 
+    async fn add_scheduled_signing_key(
+        &self,
+        signer: SigSigner,
+        role: KeyRole,
+        timings: KeyTimings,
+    ) -> DnsSecResult<()> {
+        self.inner
+            .write()
+            .await
+            .add_scheduled_signing_key(signer, role, timings)
+    }
+
+    async fn maintain_keys(&self, now: u64) -> DnsSecResult<bool> {
+        let mut inner = self.inner.write().await;
+
+        inner.maintain_keys(self.origin(), self.class, now)
+    }
+
+    async fn set_nsec3(&self, nsec3param: NSEC3PARAM, opt_out: bool) -> DnsSecResult<()> {
+        let mut inner = self.inner.write().await;
+
+        inner.set_nsec3(self.origin(), self.class, nsec3param, opt_out);
+        Ok(())
+    }
+
     /// Sign the zone for DNSSEC
     async fn secure_zone(&self) -> DnsSecResult<()> {
         let mut inner = self.inner.write().await;
@@ -0,0 +1,439 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Answers which depend on the location of the client, from a MaxMind database
+
+#![cfg(feature = "geoip")]
+
+use std::{collections::HashMap, net::IpAddr, path::Path, str::FromStr, sync::Arc};
+
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{
+    client::{
+        rr::{LowerName, Name, RData, Record, RecordSet, RecordType},
+        serialize::txt::RDataParser,
+    },
+    proto::rr::rdata::opt::ClientSubnet,
+};
+
+/// The TTL of the records of locations, if it's not configured
+const DEFAULT_TTL: u32 = 300;
+
+/// Configuration of the answers of a zone which depend on the location of the client
+///
+/// The location of the client, or of the network in the EDNS Client Subnet option of its
+///  request, is looked up in a MaxMind database, e.g. GeoLite2 Country or City. Locations are
+///  paths of the continent code, the ISO country code and the ISO subdivision code, e.g. `EU`,
+///  `EU/DE` or `NA/US/CA`. A, AAAA and CNAME queries are answered with the records of the most
+///  specific location of the client which has records of the type, or a CNAME, at the name, and
+///  from the zone if no location of the client has.
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct GeoConfig {
+    /// path to the MaxMind database, relative to the directory of the zones
+    pub database: String,
+    /// The records answered to the clients in each location
+    #[serde(default)]
+    pub records: Vec<GeoRecordConfig>,
+}
+
+/// A record answered to the clients in a location
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct GeoRecordConfig {
+    /// name of the record, relative to the origin of the zone unless it ends with `.`
+    pub name: String,
+    /// location of the clients, e.g. `EU` or `EU/DE`
+    pub location: String,
+    /// type of the record, A, AAAA or CNAME
+    pub record_type: String,
+    /// TTL of the record, default 300 seconds
+    pub ttl: Option<u32>,
+    /// data of the record in the zone file format, e.g. `192.0.2.1` or `cdn.example.net.`
+    pub data: String,
+}
+
+impl GeoConfig {
+    pub(crate) fn try_into_geo(
+        &self,
+        origin: &Name,
+        root_dir: Option<&Path>,
+    ) -> Result<Geo, String> {
+        let path = root_dir
+            .map(|dir| dir.join(&self.database))
+            .unwrap_or_else(|| Path::new(&self.database).to_path_buf());
+        let reader = Reader::open_readfile(&path)
+            .map_err(|e| format!("failed to open geoip database {:?}: {}", path, e))?;
+
+        Geo::new(reader, origin, &self.records)
+    }
+}
+
+/// The records of locations, and the database of the locations of clients
+pub(crate) struct Geo {
+    reader: Reader<Vec<u8>>,
+    records: HashMap<(LowerName, String), Vec<Arc<RecordSet>>>,
+}
+
+impl Geo {
+    fn new(
+        reader: Reader<Vec<u8>>,
+        origin: &Name,
+        configs: &[GeoRecordConfig],
+    ) -> Result<Self, String> {
+        let mut rrsets: HashMap<(LowerName, String, RecordType), RecordSet> = HashMap::new();
+        for config in configs {
+            let name = Name::parse(&config.name, Some(origin))
+                .map_err(|e| format!("bad geoip record name {}: {}", config.name, e))?;
+            if !origin.zone_of(&name) {
+                return Err(format!("geoip record {} is not in zone {}", name, origin));
+            }
+
+            let record_type = RecordType::from_str(&config.record_type)
+                .map_err(|e| format!("bad geoip record type {}: {}", config.record_type, e))?;
+            if !matches!(
+                record_type,
+                RecordType::A | RecordType::AAAA | RecordType::CNAME
+            ) {
+                return Err(format!("unsupported geoip record type: {}", record_type));
+            }
+
+            let rdata = RData::parse(record_type, config.data.split_whitespace(), Some(origin))
+                .map_err(|e| format!("bad geoip record data {}: {}", config.data, e))?;
+            let ttl = config.ttl.unwrap_or(DEFAULT_TTL);
+            let location = config.location.to_ascii_uppercase();
+
+            rrsets
+                .entry((LowerName::new(&name), location, record_type))
+                .or_insert_with(|| RecordSet::with_ttl(name.clone(), record_type, ttl))
+                .insert(Record::from_rdata(name, ttl, rdata), 0);
+        }
+
+        let mut records: HashMap<(LowerName, String), Vec<Arc<RecordSet>>> = HashMap::new();
+        for ((name, location, _), rrset) in rrsets {
+            records
+                .entry((name, location))
+                .or_default()
+                .push(Arc::new(rrset));
+        }
+
+        Ok(Self { reader, records })
+    }
+
+    /// The records of the most specific location of the client at the name, `None` if the query
+    ///  is answered from the zone
+    ///
+    /// The records are of the query type, or a CNAME.
+    pub(crate) fn lookup(
+        &self,
+        name: &LowerName,
+        query_type: RecordType,
+        client: IpAddr,
+    ) -> Option<Arc<RecordSet>> {
+        if !matches!(
+            query_type,
+            RecordType::A | RecordType::AAAA | RecordType::CNAME
+        ) {
+            return None;
+        }
+
+        self.locations(client).into_iter().find_map(|location| {
+            let rrsets = self.records.get(&(name.clone(), location))?;
+            rrsets
+                .iter()
+                .find(|rrset| rrset.record_type() == query_type)
+                .or_else(|| {
+                    rrsets
+                        .iter()
+                        .find(|rrset| rrset.record_type() == RecordType::CNAME)
+                })
+                .cloned()
+        })
+    }
+
+    /// The scope prefix length of the answers for the client subnet, the length of its network
+    ///  in the database
+    pub(crate) fn client_subnet_scope(&self, client_subnet: &ClientSubnet) -> u8 {
+        match self
+            .reader
+            .lookup_prefix::<geoip2::City<'_>>(client_subnet.address())
+        {
+            Ok((_, prefix_len)) => prefix_len as u8,
+            Err(_) => client_subnet.source_prefix(),
+        }
+    }
+
+    /// The locations of the client, the most specific first
+    fn locations(&self, client: IpAddr) -> Vec<String> {
+        let city = match self.reader.lookup::<geoip2::City<'_>>(client) {
+            Ok(city) => city,
+            Err(MaxMindDBError::AddressNotFoundError(_)) => return vec![],
+            Err(e) => {
+                debug!("failed to look up the location of {}: {}", client, e);
+                return vec![];
+            }
+        };
+
+        let continent = match city.continent.and_then(|c| c.code) {
+            Some(continent) => continent.to_ascii_uppercase(),
+            None => return vec![],
+        };
+
+        let mut locations = vec![continent];
+        if let Some(country) = city.country.and_then(|c| c.iso_code) {
+            let country = format!("{}/{}", locations[0], country.to_ascii_uppercase());
+            let subdivision = city
+                .subdivisions
+                .as_ref()
+                .and_then(|s| s.first())
+                .and_then(|s| s.iso_code)
+                .map(|subdivision| format!("{}/{}", country, subdivision.to_ascii_uppercase()));
+
+            locations.insert(0, country);
+            if let Some(subdivision) = subdivision {
+                locations.insert(0, subdivision);
+            }
+        }
+
+        locations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    /// Encodes the control byte of a field of the MaxMind DB format
+    fn field(data: &mut Vec<u8>, field_type: u8, size: usize) {
+        assert!(size < 29);
+        data.push(field_type << 5 | size as u8);
+    }
+
+    fn string(data: &mut Vec<u8>, s: &str) {
+        field(data, 2, s.len());
+        data.extend_from_slice(s.as_bytes());
+    }
+
+    fn uint32(data: &mut Vec<u8>, value: u32) {
+        let bytes = value.to_be_bytes();
+        let bytes = &bytes[bytes.iter().take_while(|b| **b == 0).count()..];
+        field(data, 6, bytes.len());
+        data.extend_from_slice(bytes);
+    }
+
+    fn location(data: &mut Vec<u8>, continent: &str, country: &str) {
+        field(data, 7, 2);
+        string(data, "continent");
+        field(data, 7, 1);
+        string(data, "code");
+        string(data, continent);
+        string(data, "country");
+        field(data, 7, 1);
+        string(data, "iso_code");
+        string(data, country);
+    }
+
+    /// A database of the continent and country of IPv4 networks
+    fn database(networks: &[(&str, &str, &str)]) -> Reader<Vec<u8>> {
+        // the search tree, records are nodes, data offsets or empty
+        enum Child {
+            Node(usize),
+            Data(usize),
+            Empty,
+        }
+
+        let mut nodes = vec![[Child::Empty, Child::Empty]];
+        let mut data = Vec::new();
+        for (network, continent, country) in networks {
+            let network: ipnet::Ipv4Net = network.parse().unwrap();
+            let address = u32::from(network.network());
+
+            let mut node = 0;
+            for bit in 0..network.prefix_len() {
+                let side = (address >> (31 - bit) & 1) as usize;
+                if bit + 1 == network.prefix_len() {
+                    nodes[node][side] = Child::Data(data.len());
+                } else if let Child::Node(next) = nodes[node][side] {
+                    node = next;
+                } else {
+                    nodes.push([Child::Empty, Child::Empty]);
+                    nodes[node][side] = Child::Node(nodes.len() - 1);
+                    node = nodes.len() - 1;
+                }
+            }
+
+            location(&mut data, continent, country);
+        }
+
+        let node_count = nodes.len();
+        let mut db = Vec::new();
+        for node in &nodes {
+            for child in node {
+                let record = match *child {
+                    Child::Node(next) => next,
+                    Child::Data(offset) => node_count + 16 + offset,
+                    Child::Empty => node_count,
+                };
+                db.extend_from_slice(&(record as u32).to_be_bytes()[1..]);
+            }
+        }
+        db.extend_from_slice(&[0; 16]);
+        db.extend_from_slice(&data);
+
+        db.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+        field(&mut db, 7, 9);
+        string(&mut db, "binary_format_major_version");
+        uint32(&mut db, 2);
+        string(&mut db, "binary_format_minor_version");
+        uint32(&mut db, 0);
+        string(&mut db, "build_epoch");
+        uint32(&mut db, 0);
+        string(&mut db, "database_type");
+        string(&mut db, "Test-Country");
+        string(&mut db, "description");
+        field(&mut db, 7, 0);
+        string(&mut db, "ip_version");
+        uint32(&mut db, 4);
+        string(&mut db, "languages");
+        db.extend_from_slice(&[0, 4]);
+        string(&mut db, "node_count");
+        uint32(&mut db, node_count as u32);
+        string(&mut db, "record_size");
+        uint32(&mut db, 24);
+
+        Reader::from_source(db).unwrap()
+    }
+
+    fn record(name: &str, location: &str, record_type: &str, data: &str) -> GeoRecordConfig {
+        GeoRecordConfig {
+            name: name.to_string(),
+            location: location.to_string(),
+            record_type: record_type.to_string(),
+            ttl: Some(60),
+            data: data.to_string(),
+        }
+    }
+
+    fn geo() -> Geo {
+        let reader = database(&[
+            ("192.0.2.0/24", "EU", "DE"),
+            ("198.51.100.0/24", "EU", "FR"),
+            ("203.0.113.0/25", "NA", "US"),
+        ]);
+
+        Geo::new(
+            reader,
+            &Name::from_str("example.com.").unwrap(),
+            &[
+                record("www", "eu", "A", "192.0.2.10"),
+                record("www", "EU/DE", "A", "192.0.2.20"),
+                record("www", "EU/DE", "A", "192.0.2.21"),
+                record("www", "NA", "CNAME", "us.cdn.example.net."),
+                record("www.example.com.", "EU/FR", "AAAA", "2001:db8::1"),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn addresses(rrset: &RecordSet) -> Vec<String> {
+        let mut addresses = rrset
+            .records_without_rrsigs()
+            .filter_map(Record::data)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        addresses.sort();
+        addresses
+    }
+
+    #[test]
+    fn test_locations() {
+        let geo = geo();
+
+        assert_eq!(
+            geo.locations(Ipv4Addr::new(192, 0, 2, 1).into()),
+            ["EU/DE", "EU"]
+        );
+        assert_eq!(
+            geo.locations(Ipv4Addr::new(203, 0, 113, 1).into()),
+            ["NA/US", "NA"]
+        );
+        assert!(geo
+            .locations(Ipv4Addr::new(203, 0, 113, 129).into())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let geo = geo();
+        let www = LowerName::from_str("www.example.com.").unwrap();
+
+        // the most specific location
+        let rrset = geo
+            .lookup(&www, RecordType::A, Ipv4Addr::new(192, 0, 2, 1).into())
+            .unwrap();
+        assert_eq!(addresses(&rrset), ["192.0.2.20", "192.0.2.21"]);
+        assert_eq!(rrset.ttl(), 60);
+
+        // the continent, the country has no A records
+        let rrset = geo
+            .lookup(&www, RecordType::A, Ipv4Addr::new(198, 51, 100, 1).into())
+            .unwrap();
+        assert_eq!(addresses(&rrset), ["192.0.2.10"]);
+        let rrset = geo
+            .lookup(
+                &www,
+                RecordType::AAAA,
+                Ipv4Addr::new(198, 51, 100, 1).into(),
+            )
+            .unwrap();
+        assert_eq!(addresses(&rrset), ["2001:db8::1"]);
+
+        // a CNAME for all types
+        let rrset = geo
+            .lookup(&www, RecordType::AAAA, Ipv4Addr::new(203, 0, 113, 1).into())
+            .unwrap();
+        assert_eq!(rrset.record_type(), RecordType::CNAME);
+
+        // answered from the zone
+        assert!(geo
+            .lookup(&www, RecordType::AAAA, Ipv4Addr::new(192, 0, 2, 1).into())
+            .is_none());
+        assert!(geo
+            .lookup(&www, RecordType::A, Ipv4Addr::new(203, 0, 113, 129).into())
+            .is_none());
+        assert!(geo
+            .lookup(&www, RecordType::TXT, Ipv4Addr::new(192, 0, 2, 1).into())
+            .is_none());
+    }
+
+    #[test]
+    fn test_client_subnet_scope() {
+        let geo = geo();
+
+        let client_subnet = ClientSubnet::new(Ipv4Addr::new(192, 0, 2, 0).into(), 24, 0);
+        assert_eq!(geo.client_subnet_scope(&client_subnet), 24);
+        let client_subnet = ClientSubnet::new(Ipv4Addr::new(203, 0, 113, 128).into(), 28, 0);
+        assert_eq!(geo.client_subnet_scope(&client_subnet), 28);
+    }
+
+    #[test]
+    fn test_bad_records() {
+        let origin = Name::from_str("example.com.").unwrap();
+
+        assert!(Geo::new(database(&[]), &origin, &[record("www", "EU", "TXT", "a")]).is_err());
+        assert!(Geo::new(
+            database(&[]),
+            &origin,
+            &[record("www.example.net.", "EU", "A", "192.0.2.1")]
+        )
+        .is_err());
+        assert!(Geo::new(database(&[]), &origin, &[record("www", "EU", "A", "bad")]).is_err());
+    }
+}
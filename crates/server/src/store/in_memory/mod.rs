@@ -7,6 +7,16 @@
 
 //! Zone file based serving with Dynamic DNS and journaling support
 
+mod aname;
 mod authority;
+mod geo;
+mod pool;
 
+#[cfg(feature = "trust-dns-resolver")]
+pub use self::aname::AnameConfig;
+#[cfg(feature = "geoip")]
+pub use self::geo::{GeoConfig, GeoRecordConfig};
 pub use self::authority::InMemoryAuthority;
+pub use self::pool::{HealthCheckConfig, PoolConfig, PoolMemberConfig};
+#[cfg(feature = "dnssec")]
+pub(crate) use self::authority::verified_tsigner;
@@ -0,0 +1,515 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Pools of addresses which are answered by weight and health, for load balancing
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use futures_util::future;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tracing::{debug, info, warn};
+
+use crate::client::rr::{LowerName, Name, RData, RecordSet, RecordType, RrKey};
+
+/// The TTL of the addresses of a pool, if it's not configured
+const DEFAULT_TTL: u32 = 30;
+/// The seconds between the health checks of the members, if it's not configured
+const DEFAULT_INTERVAL: u64 = 10;
+/// The seconds to wait for a member to pass the health check, if it's not configured
+const DEFAULT_TIMEOUT: u64 = 2;
+/// The consecutive failed checks after which a member is unhealthy, if it's not configured
+const DEFAULT_UNHEALTHY_THRESHOLD: u32 = 3;
+/// The consecutive passed checks after which a member is healthy again, if it's not configured
+const DEFAULT_HEALTHY_THRESHOLD: u32 = 2;
+
+/// Configuration of a pool of addresses, which are answered in place of the A or AAAA records
+///  of the name in the zone
+///
+/// Each answer has up to `max_answers` of the healthy members, the first chosen by smooth
+///  weighted round robin, so that members are first in proportion to their weight. If no member
+///  is healthy, all members are answered as if they were.
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct PoolConfig {
+    /// name of the records, relative to the origin of the zone unless it ends with `.`
+    pub name: String,
+    /// type of the records, A or AAAA
+    pub record_type: String,
+    /// TTL of the records, default 30 seconds
+    pub ttl: Option<u32>,
+    /// number of members in each answer, default 1
+    pub max_answers: Option<usize>,
+    /// the members of the pool
+    pub members: Vec<PoolMemberConfig>,
+    /// health check of the members, all members are healthy without it
+    #[serde(default)]
+    pub health_check: Option<HealthCheckConfig>,
+}
+
+/// A member of a pool
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq, Debug)]
+pub struct PoolMemberConfig {
+    /// address of the member
+    pub address: IpAddr,
+    /// weight of the member relative to the others, default 1, 0 drains the member
+    pub weight: Option<u32>,
+}
+
+/// Configuration of the health checks of the members of a pool
+///
+/// A member passes the check if a TCP connection to the port is accepted, and if `http_path` is
+///  set, the response to an HTTP GET of the path has a 2xx or 3xx status.
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct HealthCheckConfig {
+    /// port of the members to connect to
+    pub port: u16,
+    /// path to GET over HTTP, only the TCP connection is checked if none
+    pub http_path: Option<String>,
+    /// Host header of the HTTP request, the address of the member if none
+    pub http_host: Option<String>,
+    /// seconds between checks, default 10
+    pub interval: Option<u64>,
+    /// seconds to wait for the check to pass, default 2
+    pub timeout: Option<u64>,
+    /// consecutive failed checks after which a member is unhealthy, default 3
+    pub unhealthy_threshold: Option<u32>,
+    /// consecutive passed checks after which an unhealthy member is healthy again, default 2
+    pub healthy_threshold: Option<u32>,
+}
+
+impl PoolConfig {
+    pub(crate) fn try_into_pool(&self, origin: &Name) -> Result<Pool, String> {
+        let name = Name::parse(&self.name, Some(origin))
+            .map_err(|e| format!("bad pool name {}: {}", self.name, e))?;
+        if !origin.zone_of(&name) {
+            return Err(format!("pool {} is not in zone {}", name, origin));
+        }
+
+        let record_type = RecordType::from_str(&self.record_type)
+            .map_err(|e| format!("bad pool record type {}: {}", self.record_type, e))?;
+        let members = self
+            .members
+            .iter()
+            .map(|member| {
+                match (record_type, member.address) {
+                    (RecordType::A, IpAddr::V4(_)) | (RecordType::AAAA, IpAddr::V6(_)) => (),
+                    _ => {
+                        return Err(format!(
+                            "pool {} {} can't have member {}",
+                            name, record_type, member.address
+                        ))
+                    }
+                }
+
+                Ok(Member {
+                    address: member.address,
+                    weight: i64::from(member.weight.unwrap_or(1)),
+                    healthy: AtomicBool::new(true),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if members.is_empty() {
+            return Err(format!("pool {} {} has no members", name, record_type));
+        }
+
+        Ok(Pool {
+            name,
+            record_type,
+            ttl: self.ttl.unwrap_or(DEFAULT_TTL),
+            max_answers: self.max_answers.unwrap_or(1).max(1),
+            current_weights: Mutex::new(vec![0; members.len()]),
+            members,
+            health_check: self.health_check.clone(),
+        })
+    }
+}
+
+struct Member {
+    address: IpAddr,
+    weight: i64,
+    healthy: AtomicBool,
+}
+
+/// A pool of addresses, see `PoolConfig`
+pub(crate) struct Pool {
+    name: Name,
+    record_type: RecordType,
+    ttl: u32,
+    max_answers: usize,
+    members: Vec<Member>,
+    /// the state of the smooth weighted round robin
+    current_weights: Mutex<Vec<i64>>,
+    health_check: Option<HealthCheckConfig>,
+}
+
+impl Pool {
+    /// The key of the records which the pool answers
+    pub(crate) fn key(&self) -> RrKey {
+        RrKey::new(LowerName::new(&self.name), self.record_type)
+    }
+
+    /// The addresses of the next answer
+    pub(crate) fn answer(&self) -> RecordSet {
+        let mut candidates = self
+            .members
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.weight > 0 && m.healthy.load(Ordering::Relaxed))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            debug!("no healthy members of pool {}", self.name);
+            candidates = (0..self.members.len())
+                .filter(|i| self.members[*i].weight > 0)
+                .collect();
+        }
+
+        // smooth weighted round robin of the first answer, the others follow by current weight
+        {
+            let mut current_weights = self.current_weights.lock().expect("poisoned");
+            let total: i64 = candidates.iter().map(|i| self.members[*i].weight).sum();
+            for i in &candidates {
+                current_weights[*i] += self.members[*i].weight;
+            }
+
+            candidates.sort_by_key(|i| std::cmp::Reverse(current_weights[*i]));
+            if let Some(first) = candidates.first() {
+                current_weights[*first] -= total;
+            }
+        }
+
+        let mut answer = RecordSet::with_ttl(self.name.clone(), self.record_type, self.ttl);
+        for i in candidates.into_iter().take(self.max_answers) {
+            answer.add_rdata(match self.members[i].address {
+                IpAddr::V4(address) => RData::A(address),
+                IpAddr::V6(address) => RData::AAAA(address),
+            });
+        }
+
+        answer
+    }
+
+    /// Spawns the task checking the health of the members, which runs until the pool is dropped
+    ///
+    /// This must be called from within a tokio runtime, it does nothing if the pool has no
+    ///  health check.
+    pub(crate) fn spawn_health_checks(self: &Arc<Self>) {
+        let interval = match &self.health_check {
+            Some(health_check) => {
+                Duration::from_secs(health_check.interval.unwrap_or(DEFAULT_INTERVAL))
+            }
+            None => return,
+        };
+
+        let mut checker = HealthChecker::new(self);
+        tokio::spawn(async move {
+            while checker.check().await {
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+}
+
+/// Checks the health of the members of a pool
+struct HealthChecker {
+    pool: Weak<Pool>,
+    /// consecutive passed, if positive, or failed checks of each member
+    streaks: Vec<i64>,
+}
+
+impl HealthChecker {
+    fn new(pool: &Arc<Pool>) -> Self {
+        Self {
+            pool: Arc::downgrade(pool),
+            streaks: vec![0; pool.members.len()],
+        }
+    }
+
+    /// Checks all members once, returns false if the pool was dropped
+    async fn check(&mut self) -> bool {
+        let pool = match self.pool.upgrade() {
+            Some(pool) => pool,
+            None => return false,
+        };
+        let health_check = match &pool.health_check {
+            Some(health_check) => health_check,
+            None => return false,
+        };
+
+        let results = future::join_all(
+            pool.members
+                .iter()
+                .map(|member| probe(member.address, health_check)),
+        )
+        .await;
+
+        let unhealthy_threshold = health_check
+            .unhealthy_threshold
+            .unwrap_or(DEFAULT_UNHEALTHY_THRESHOLD);
+        let healthy_threshold = health_check
+            .healthy_threshold
+            .unwrap_or(DEFAULT_HEALTHY_THRESHOLD);
+        for ((member, streak), result) in pool.members.iter().zip(&mut self.streaks).zip(results) {
+            match result {
+                Ok(()) => *streak = (*streak).max(0) + 1,
+                Err(e) => {
+                    debug!("pool {} member {} failed: {}", pool.name, member.address, e);
+                    *streak = (*streak).min(0) - 1;
+                }
+            }
+
+            let healthy = member.healthy.load(Ordering::Relaxed);
+            if !healthy && *streak >= i64::from(healthy_threshold) {
+                info!("pool {} member {} is healthy", pool.name, member.address);
+                member.healthy.store(true, Ordering::Relaxed);
+            } else if healthy && -*streak >= i64::from(unhealthy_threshold) {
+                warn!("pool {} member {} is unhealthy", pool.name, member.address);
+                member.healthy.store(false, Ordering::Relaxed);
+            }
+        }
+
+        true
+    }
+}
+
+/// Checks the health of the member
+async fn probe(address: IpAddr, health_check: &HealthCheckConfig) -> Result<(), String> {
+    let timeout = Duration::from_secs(health_check.timeout.unwrap_or(DEFAULT_TIMEOUT));
+
+    tokio::time::timeout(timeout, async {
+        let mut stream = TcpStream::connect(SocketAddr::new(address, health_check.port))
+            .await
+            .map_err(|e| format!("connect failed: {}", e))?;
+
+        let path = match &health_check.http_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let host = health_check
+            .http_host
+            .clone()
+            .unwrap_or_else(|| match address {
+                IpAddr::V4(address) => address.to_string(),
+                IpAddr::V6(address) => format!("[{}]", address),
+            });
+
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: trust-dns\r\nConnection: close\r\n\r\n",
+            path, host
+        );
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| format!("request failed: {}", e))?;
+
+        // the status line, e.g. `HTTP/1.1 200 OK`
+        let mut response = Vec::new();
+        let mut buf = [0_u8; 512];
+        while !response.windows(2).any(|w| w == b"\r\n") && response.len() < 4096 {
+            let read = stream
+                .read(&mut buf)
+                .await
+                .map_err(|e| format!("response failed: {}", e))?;
+            if read == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf[..read]);
+        }
+
+        let status = String::from_utf8_lossy(&response)
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or_else(|| "bad HTTP response".to_string())?;
+        if (200..400).contains(&status) {
+            Ok(())
+        } else {
+            Err(format!("HTTP status {}", status))
+        }
+    })
+    .await
+    .map_err(|_| "timed out".to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn pool(members: &[(&str, u32)], max_answers: usize) -> Pool {
+        PoolConfig {
+            name: "www".to_string(),
+            record_type: "A".to_string(),
+            ttl: None,
+            max_answers: Some(max_answers),
+            members: members
+                .iter()
+                .map(|(address, weight)| PoolMemberConfig {
+                    address: address.parse().unwrap(),
+                    weight: Some(*weight),
+                })
+                .collect(),
+            health_check: None,
+        }
+        .try_into_pool(&Name::from_str("example.com.").unwrap())
+        .unwrap()
+    }
+
+    fn addresses(answer: &RecordSet) -> Vec<Ipv4Addr> {
+        answer
+            .records_without_rrsigs()
+            .filter_map(|r| r.data().and_then(RData::as_a))
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_weights() {
+        let pool = pool(&[("192.0.2.1", 3), ("192.0.2.2", 1), ("192.0.2.3", 0)], 1);
+
+        let firsts = (0..8)
+            .map(|_| addresses(&pool.answer())[0])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            firsts.iter().filter(|a| a.octets()[3] == 1).count(),
+            6,
+            "{:?}",
+            firsts
+        );
+        assert_eq!(firsts.iter().filter(|a| a.octets()[3] == 2).count(), 2);
+        assert_eq!(pool.answer().ttl(), DEFAULT_TTL);
+    }
+
+    #[test]
+    fn test_health() {
+        let pool = pool(&[("192.0.2.1", 1), ("192.0.2.2", 1)], 2);
+        assert_eq!(addresses(&pool.answer()).len(), 2);
+
+        pool.members[0].healthy.store(false, Ordering::Relaxed);
+        for _ in 0..4 {
+            assert_eq!(addresses(&pool.answer()), [Ipv4Addr::new(192, 0, 2, 2)]);
+        }
+
+        // all members are answered if none is healthy
+        pool.members[1].healthy.store(false, Ordering::Relaxed);
+        assert_eq!(addresses(&pool.answer()).len(), 2);
+    }
+
+    #[test]
+    fn test_bad_config() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let config = |record_type: &str, address: &str| PoolConfig {
+            name: "www".to_string(),
+            record_type: record_type.to_string(),
+            ttl: None,
+            max_answers: None,
+            members: vec![PoolMemberConfig {
+                address: address.parse().unwrap(),
+                weight: None,
+            }],
+            health_check: None,
+        };
+
+        assert!(config("A", "192.0.2.1").try_into_pool(&origin).is_ok());
+        assert!(config("AAAA", "192.0.2.1").try_into_pool(&origin).is_err());
+        assert!(config("TXT", "192.0.2.1").try_into_pool(&origin).is_err());
+    }
+
+    /// Serves HTTP responses with the status, returns the port
+    async fn http_server(status: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                // reads the request up to the end of its headers
+                let mut request = Vec::new();
+                let mut buf = [0_u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(len) => request.extend_from_slice(&buf[..len]),
+                    }
+                }
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+                stream.write_all(response.as_bytes()).await.ok();
+            }
+        });
+
+        port
+    }
+
+    fn health_check(port: u16, http_path: Option<&str>) -> HealthCheckConfig {
+        HealthCheckConfig {
+            port,
+            http_path: http_path.map(ToString::to_string),
+            http_host: None,
+            interval: None,
+            timeout: None,
+            unhealthy_threshold: Some(2),
+            healthy_threshold: Some(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_checks() {
+        let port = http_server("200 OK").await;
+        let failing_port = http_server("503 Service Unavailable").await;
+
+        // 127.0.0.2 refuses connections to the port
+        let mut pool = pool(&[("127.0.0.1", 1), ("127.0.0.2", 1)], 2);
+        pool.health_check = Some(health_check(port, None));
+        let pool = Arc::new(pool);
+        let mut checker = HealthChecker::new(&pool);
+
+        assert!(checker.check().await);
+        assert!(pool.members[1].healthy.load(Ordering::Relaxed));
+        assert!(checker.check().await);
+        assert!(pool.members[0].healthy.load(Ordering::Relaxed));
+        assert!(!pool.members[1].healthy.load(Ordering::Relaxed));
+
+        let mut pool = Arc::try_unwrap(pool).ok().unwrap();
+        pool.health_check = Some(health_check(failing_port, Some("/health")));
+        let pool = Arc::new(pool);
+        let mut checker = HealthChecker::new(&pool);
+        assert!(checker.check().await);
+        assert!(checker.check().await);
+        assert!(!pool.members[0].healthy.load(Ordering::Relaxed));
+
+        // the checks stop with the pool
+        drop(pool);
+        assert!(!checker.check().await);
+    }
+
+    #[tokio::test]
+    async fn test_http_probe() {
+        let port = http_server("204 No Content").await;
+        let localhost = IpAddr::from(Ipv4Addr::LOCALHOST);
+
+        assert!(probe(localhost, &health_check(port, Some("/health")))
+            .await
+            .is_ok());
+
+        let port = http_server("500 Internal Server Error").await;
+        assert!(probe(localhost, &health_check(port, Some("/health")))
+            .await
+            .is_err());
+    }
+}
@@ -7,11 +7,18 @@
 
 //! All persistent store implementations
 
+#[cfg(feature = "blocklist")]
+#[cfg_attr(docsrs, doc(cfg(feature = "blocklist")))]
+pub mod blocklist;
 mod config;
 pub mod file;
 pub mod forwarder;
 pub mod in_memory;
 pub mod recursor;
+pub mod secondary;
+#[cfg(feature = "sql")]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "postgres", feature = "mysql"))))]
+pub mod sql;
 #[cfg(feature = "sqlite")]
 #[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
 pub mod sqlite;
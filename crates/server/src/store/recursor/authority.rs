@@ -5,7 +5,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{io, path::Path, time::Instant};
+use std::{io, path::Path, sync::Arc, time::Instant};
 
 use tracing::{debug, info};
 use trust_dns_client::op::Query;
@@ -20,6 +20,7 @@ use crate::{
         op::ResponseCode,
         rr::{LowerName, Name, Record, RecordType},
     },
+    dnstap::{Dnstap, RecursorTap},
     recursor::Recursor,
     resolver::config::{NameServerConfig, NameServerConfigGroup, Protocol},
     server::RequestInfo,
@@ -32,6 +33,7 @@ use crate::{
 pub struct RecursiveAuthority {
     origin: LowerName,
     recursor: Recursor,
+    validate: bool,
 }
 
 impl RecursiveAuthority {
@@ -60,6 +62,7 @@ impl RecursiveAuthority {
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
                 bind_addr: None, // TODO: need to support bind addresses
+                http_endpoint: None,
             });
 
             roots.push(NameServerConfig {
@@ -70,17 +73,37 @@ impl RecursiveAuthority {
                 #[cfg(feature = "dns-over-rustls")]
                 tls_config: None,
                 bind_addr: None,
+                http_endpoint: None,
             });
         }
 
-        let recursor =
-            Recursor::new(roots).map_err(|e| format!("failed to initialize recursor: {}", e))?;
+        let options = config.cache_options()?;
+        let mut recursor = Recursor::new_with_options(roots, options)
+            .map_err(|e| format!("failed to initialize recursor: {}", e))?;
+        recursor.set_name_server_cache_size(config.ns_cache_size);
+        recursor.set_max_chain_length(config.max_chain_length);
+        recursor.set_limits(config.limits());
+
+        #[cfg(feature = "dnssec")]
+        {
+            if let Some(trust_anchor) = config.read_trust_anchor(root_dir)? {
+                recursor.set_trust_anchor(trust_anchor);
+            }
+            recursor.set_aggressive_nsec(config.aggressive_nsec);
+        }
 
         Ok(Self {
             origin: origin.into(),
             recursor,
+            validate: config.validate,
         })
     }
+
+    /// Logs the queries sent to the name servers of zones, and their responses, with dnstap
+    pub fn set_dnstap(&mut self, dnstap: Option<Dnstap>) {
+        self.recursor
+            .set_query_observer(dnstap.map(|dnstap| Arc::new(RecursorTap(dnstap)) as _));
+    }
 }
 
 #[async_trait::async_trait]
@@ -110,36 +133,76 @@ impl Authority for RecursiveAuthority {
         &self.origin
     }
 
+    /// Clears the cache of the recursor
+    fn clear_cache(&self) {
+        self.recursor.clear_cache();
+    }
+
+    /// Removes the name from the cache of the recursor
+    fn flush_name(&self, name: &Name) {
+        self.recursor.flush_name(name);
+    }
+
+    /// Removes the name and all names below it from the cache of the recursor
+    fn flush_subtree(&self, name: &Name) {
+        self.recursor.flush_subtree(name);
+    }
+
     /// Forwards a lookup given the resolver configuration for this Forwarded zone
+    ///
+    /// If validation is enabled, answers which fail to validate are returned as `SERVFAIL` with
+    ///  an Extended DNS Error, and secure answers are authentic.
     async fn lookup(
         &self,
         name: &LowerName,
         rtype: RecordType,
-        _lookup_options: LookupOptions,
+        lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
         debug!("recursive lookup: {} {}", name, rtype);
 
         let query = Query::query(name.into(), rtype);
         let now = Instant::now();
 
+        #[cfg(feature = "dnssec")]
+        if self.validate {
+            return match self.recursor.resolve_with_proof(query, now).await {
+                Ok((lookup, proof)) => Ok(RecursiveLookup::new(
+                    lookup,
+                    lookup_options,
+                    proof.is_secure(),
+                )),
+                Err(e) => Err(lookup_error(e)),
+            };
+        }
+
         self.recursor
             .resolve(query, now)
             .await
-            .map(RecursiveLookup)
-            .map_err(Into::into)
+            .map(|lookup| RecursiveLookup::new(lookup, lookup_options, false))
+            .map_err(lookup_error)
     }
 
+    /// Looks up the query, without validation if the request has the Checking Disabled bit
     async fn search(
         &self,
         request_info: RequestInfo<'_>,
         lookup_options: LookupOptions,
     ) -> Result<Self::Lookup, LookupError> {
-        self.lookup(
-            request_info.query.name(),
-            request_info.query.query_type(),
-            lookup_options,
-        )
-        .await
+        let query = request_info.query;
+        if self.validate && request_info.header.checking_disabled() {
+            debug!("checking disabled, recursive lookup: {}", query);
+            let query = Query::query(query.name().into(), query.query_type());
+
+            return self
+                .recursor
+                .resolve_unvalidated(query, Instant::now())
+                .await
+                .map(|lookup| RecursiveLookup::new(lookup, lookup_options, false))
+                .map_err(lookup_error);
+        }
+
+        self.lookup(query.name(), query.query_type(), lookup_options)
+            .await
     }
 
     async fn get_nsec_records(
@@ -154,18 +217,77 @@ impl Authority for RecursiveAuthority {
     }
 }
 
-pub struct RecursiveLookup(Lookup);
+/// Converts validation failures, and the queries which exceed the limits of the recursor, to
+///  `SERVFAIL` with an Extended DNS Error, RFC 8914
+fn lookup_error(error: crate::recursor::Error) -> LookupError {
+    use crate::{proto::rr::rdata::opt::ExtendedErrorCode, recursor::ErrorKind};
+
+    match *error.kind() {
+        #[cfg(feature = "dnssec")]
+        ErrorKind::ValidationFailed { ref query, proof } => {
+            use crate::proto::rr::dnssec::Proof;
+
+            let info_code = match proof {
+                Proof::Indeterminate => ExtendedErrorCode::DnssecIndeterminate,
+                _ => ExtendedErrorCode::DnssecBogus,
+            };
+
+            LookupError::ExtendedError {
+                response_code: ResponseCode::ServFail,
+                info_code,
+                extra_text: format!("validation of {} failed", query),
+            }
+        }
+        ErrorKind::LimitExceeded { .. } => LookupError::ExtendedError {
+            response_code: ResponseCode::ServFail,
+            info_code: ExtendedErrorCode::Other,
+            extra_text: error.to_string(),
+        },
+        ErrorKind::Timeout => LookupError::ExtendedError {
+            response_code: ResponseCode::ServFail,
+            info_code: ExtendedErrorCode::NoReachableAuthority,
+            extra_text: error.to_string(),
+        },
+        _ => error.into(),
+    }
+}
+
+pub struct RecursiveLookup {
+    lookup: Lookup,
+    dnssec: bool,
+    authentic: bool,
+}
+
+impl RecursiveLookup {
+    /// The RRSIGs are only returned if they were requested
+    fn new(lookup: Lookup, lookup_options: LookupOptions, authentic: bool) -> Self {
+        Self {
+            lookup,
+            dnssec: lookup_options.is_dnssec(),
+            authentic,
+        }
+    }
+}
 
 impl LookupObject for RecursiveLookup {
     fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.lookup.is_empty()
     }
 
     fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
-        Box::new(self.0.record_iter())
+        let dnssec = self.dnssec;
+        Box::new(
+            self.lookup
+                .record_iter()
+                .filter(move |record| dnssec || record.record_type() != RecordType::RRSIG),
+        )
     }
 
     fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
         None
     }
+
+    fn is_authentic(&self) -> bool {
+        self.authentic
+    }
 }
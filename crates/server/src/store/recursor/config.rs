@@ -11,6 +11,7 @@ use std::{
     io::Read,
     net::SocketAddr,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use serde::Deserialize;
@@ -18,27 +19,149 @@ use trust_dns_client::{
     rr::{DNSClass, RData, Record, RecordSet},
     serialize::txt::{Lexer, Parser},
 };
-use trust_dns_resolver::Name;
+use trust_dns_resolver::{config::ResolverOpts, Name};
+#[cfg(feature = "dnssec")]
+use trust_dns_resolver::{proto::rr::dnssec::TrustAnchor, trust_anchor::TrustAnchors};
 
-use crate::error::ConfigError;
+use crate::{error::ConfigError, recursor::RecursorLimits};
 
 /// Configuration for file based zones
 #[derive(Clone, Deserialize, Eq, PartialEq, Debug)]
 pub struct RecursiveConfig {
     /// File with roots, aka hints
     pub roots: PathBuf,
+    /// Maximum number of cached record sets, the least recently used are removed beyond it
+    #[serde(default = "default_cache_size")]
+    pub cache_size: usize,
+    /// Maximum number of zones whose name servers are cached
+    #[serde(default = "default_ns_cache_size")]
+    pub ns_cache_size: usize,
+    /// Maximum number of CNAMEs, and CNAMEs synthesized from DNAMEs, followed to answer a query
+    #[serde(default = "default_max_chain_length")]
+    pub max_chain_length: usize,
+    /// Maximum number of queries sent to name servers to answer a query, 64 by default
+    pub max_queries: Option<usize>,
+    /// Maximum depth of the resolutions of the addresses of name servers without glue, 7 by default
+    pub max_depth: Option<usize>,
+    /// Maximum number of name servers of a zone whose addresses are resolved, when its delegation
+    ///  has no glue, 2 by default
+    pub max_ns_fanout: Option<usize>,
+    /// Maximum time in seconds to answer a query, 10 by default
+    pub timeout: Option<u64>,
+    /// Minimum TTL in seconds of cached records, shorter TTLs are raised to it
+    pub positive_min_ttl: Option<u64>,
+    /// Maximum TTL in seconds of cached records, one day by default
+    pub positive_max_ttl: Option<u64>,
+    /// Minimum TTL in seconds of cached NXDOMAIN and NODATA responses
+    pub negative_min_ttl: Option<u64>,
+    /// Maximum TTL in seconds of cached NXDOMAIN and NODATA responses, one day by default
+    pub negative_max_ttl: Option<u64>,
+    /// Validate the answers with DNSSEC, requires the `dnssec` feature
+    #[serde(default)]
+    pub validate: bool,
+    /// File with the trust anchors of the validation, the keys of the root zone by default
+    ///
+    /// The file is in the `bind.keys` format of BIND, or in the zone file format of
+    ///  `unbound-anchor`.
+    pub trust_anchor: Option<PathBuf>,
+    /// Synthesize NXDOMAIN and NODATA answers from the validated NSEC and NSEC3 records in the
+    ///  cache, RFC 8198, true by default
+    #[serde(default = "default_aggressive_nsec")]
+    pub aggressive_nsec: bool,
+}
+
+fn default_cache_size() -> usize {
+    1024
+}
+
+fn default_aggressive_nsec() -> bool {
+    true
+}
+
+fn default_ns_cache_size() -> usize {
+    crate::recursor::DEFAULT_NAME_SERVER_CACHE_SIZE
+}
+
+fn default_max_chain_length() -> usize {
+    crate::recursor::DEFAULT_MAX_CHAIN_LENGTH
 }
 
 impl RecursiveConfig {
+    /// The options of the recursor's cache, and of the validation
+    ///
+    /// Returns an error if a minimum TTL is greater than the maximum, or if validation is enabled
+    ///  without the `dnssec` feature.
+    pub(crate) fn cache_options(&self) -> Result<ResolverOpts, String> {
+        for (kind, min, max) in [
+            ("positive", self.positive_min_ttl, self.positive_max_ttl),
+            ("negative", self.negative_min_ttl, self.negative_max_ttl),
+        ] {
+            if let (Some(min), Some(max)) = (min, max) {
+                if min > max {
+                    return Err(format!(
+                        "{}_min_ttl {} is greater than {}_max_ttl {}",
+                        kind, min, kind, max
+                    ));
+                }
+            }
+        }
+
+        if self.validate && cfg!(not(feature = "dnssec")) {
+            return Err("validate requires the dnssec feature".to_string());
+        }
+
+        let mut options = ResolverOpts::default();
+        options.cache_size = self.cache_size;
+        options.positive_min_ttl = self.positive_min_ttl.map(Duration::from_secs);
+        options.positive_max_ttl = self.positive_max_ttl.map(Duration::from_secs);
+        options.negative_min_ttl = self.negative_min_ttl.map(Duration::from_secs);
+        options.negative_max_ttl = self.negative_max_ttl.map(Duration::from_secs);
+        options.validate = self.validate;
+        Ok(options)
+    }
+
+    /// The limits of the work done to answer a query, the defaults of the recursor if not set
+    pub(crate) fn limits(&self) -> RecursorLimits {
+        let defaults = RecursorLimits::default();
+        RecursorLimits {
+            max_queries: self.max_queries.unwrap_or(defaults.max_queries),
+            max_depth: self.max_depth.unwrap_or(defaults.max_depth),
+            max_ns_fanout: self.max_ns_fanout.unwrap_or(defaults.max_ns_fanout),
+            timeout: self.timeout.map_or(defaults.timeout, Duration::from_secs),
+        }
+    }
+
+    /// The path of the file, relative to the root directory
+    fn path<'a>(root_dir: Option<&Path>, file: &'a Path) -> Cow<'a, Path> {
+        if let Some(root_dir) = root_dir {
+            Cow::Owned(root_dir.join(file))
+        } else {
+            Cow::Borrowed(file)
+        }
+    }
+
+    /// Reads the trust anchors for the validation, `None` if the default anchors are used
+    #[cfg(feature = "dnssec")]
+    pub(crate) fn read_trust_anchor(
+        &self,
+        root_dir: Option<&Path>,
+    ) -> Result<Option<TrustAnchor>, String> {
+        let file = match self.trust_anchor {
+            Some(ref file) => file,
+            None => return Ok(None),
+        };
+
+        let path = Self::path(root_dir, file);
+        TrustAnchors::from_file(&path)
+            .map(|anchors| Some(anchors.trust_anchor()))
+            .map_err(|e| format!("failed to read trust anchor {}: {}", path.display(), e))
+    }
+
     pub(crate) fn read_roots(
         &self,
         root_dir: Option<&Path>,
     ) -> Result<Vec<SocketAddr>, ConfigError> {
-        let path = if let Some(root_dir) = root_dir {
-            Cow::Owned(root_dir.join(&self.roots))
-        } else {
-            Cow::Borrowed(&self.roots)
-        };
+        let path = Self::path(root_dir, &self.roots);
 
         let mut roots = File::open(path.as_ref())?;
         let mut roots_str = String::new();
@@ -0,0 +1,736 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    mem,
+    net::{IpAddr, SocketAddr},
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex as StdMutex, Weak},
+    time::{Duration, Instant},
+};
+
+use futures_util::{
+    future::{self, Either},
+    lock::Mutex,
+    StreamExt,
+};
+use tokio::{net::TcpStream as TokioTcpStream, sync::Notify, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "dnssec")]
+use crate::client::rr::dnssec::tsig::TSigner;
+use crate::{
+    authority::{
+        Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneDiff, ZoneType,
+    },
+    client::{
+        client::{AsyncClient, ClientHandle, Signer, XfrBatch},
+        error::{ClientError, ClientResult},
+        op::ResponseCode,
+        rr::{LowerName, Name, RData, Record, RecordSet, RecordType, RrKey},
+        serialize::txt::{Parser, Writer},
+        tcp::TcpClientStream,
+    },
+    error::PersistenceResult,
+    proto::iocompat::AsyncIoTokioAsStd,
+    server::RequestInfo,
+    store::{file::serial_lt, in_memory::InMemoryAuthority, secondary::SecondaryConfig},
+};
+
+/// Interval between attempts to transfer a zone which was never loaded, the SOA of the zone
+///  determines the interval once it is loaded
+const INITIAL_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Timeout for connecting to a primary
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SecondaryAuthority serves a zone which is transferred from a primary.
+///
+/// The zone is refreshed with the refresh, retry and expire timers of its SOA, see RFC 1034
+///  section 4.3.5, once `spawn_refresh()` is called. A NOTIFY from one of the primaries, see
+///  RFC 1996, triggers an immediate refresh. IXFR is used once the zone is loaded, primaries may
+///  still respond with a full transfer. Until the zone is loaded, or after it expired, lookups
+///  fail with SERVFAIL.
+pub struct SecondaryAuthority {
+    in_memory: InMemoryAuthority,
+    primaries: Vec<SocketAddr>,
+    signer: Option<Arc<Signer>>,
+    zone_path: Option<PathBuf>,
+    /// The time the zone expires, `None` if the zone is not loaded
+    expires: StdMutex<Option<Instant>>,
+    /// Only one refresh runs at a time
+    refreshing: Mutex<()>,
+    refresh_now: Arc<Notify>,
+    /// Notified when a transfer changed the zone
+    changed: Option<Arc<Notify>>,
+}
+
+impl SecondaryAuthority {
+    /// Creates a new secondary Authority, the zone is empty until it is transferred.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The zone `Name` being served, the SOA of the primary must match
+    /// * `zone_type` - The type of zone, i.e. `ZoneType::Secondary`
+    /// * `allow_axfr` - If true, then this zone allows zone transfers to further secondaries
+    /// * `primaries` - The addresses of the primaries to transfer the zone from
+    pub fn new(
+        origin: Name,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+        primaries: Vec<SocketAddr>,
+    ) -> Self {
+        Self {
+            in_memory: InMemoryAuthority::empty(origin, zone_type, allow_axfr),
+            primaries,
+            signer: None,
+            zone_path: None,
+            expires: StdMutex::new(None),
+            refreshing: Mutex::new(()),
+            refresh_now: Arc::new(Notify::new()),
+            changed: None,
+        }
+    }
+
+    /// Read the Authority for the origin from the specified configuration
+    ///
+    /// If the zone was stored by an earlier transfer, it is served from that file until it expires
+    ///  or the first refresh succeeds. The expire timer starts when the file is loaded.
+    pub fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+        root_dir: Option<&Path>,
+        config: &SecondaryConfig,
+    ) -> Result<Self, String> {
+        if config.primaries.is_empty() {
+            return Err(format!("no primaries configured for zone: {}", origin));
+        }
+
+        let mut authority = Self::new(origin, zone_type, allow_axfr, config.primaries.clone());
+
+        if let Some(ref tsig_key) = config.tsig_key {
+            authority.signer = Some(Arc::new(Self::tsig_signer(tsig_key)?));
+        }
+
+        if let Some(ref zone_file_path) = config.zone_file_path {
+            let zone_path = root_dir
+                .map(PathBuf::from)
+                .unwrap_or_else(PathBuf::new)
+                .join(zone_file_path);
+
+            if zone_path.exists() {
+                info!("loading stored secondary zone: {:?}", zone_path);
+                let (_, records) = Parser::new()
+                    .parse_file(&zone_path, Some(Name::from(authority.origin())), None)
+                    .map_err(|e| format!("failed to parse {}: {:?}", zone_file_path, e))?;
+
+                let records = records
+                    .into_values()
+                    .flat_map(|rrset| {
+                        rrset
+                            .records_without_rrsigs()
+                            .chain(rrset.rrsigs())
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                // nothing else has a reference to the authority yet, so this will not block
+                futures_executor::block_on(async {
+                    authority.replace_zone(records).await?;
+                    authority.reset_expire().await;
+                    ClientResult::Ok(())
+                })
+                .map_err(|e| format!("failed to load {}: {}", zone_file_path, e))?;
+            }
+
+            authority.zone_path = Some(zone_path);
+        }
+
+        Ok(authority)
+    }
+
+    #[cfg(feature = "dnssec")]
+    fn tsig_signer(config: &crate::config::dnssec::TsigKeyConfig) -> Result<Signer, String> {
+        config.try_into_signer().map(Signer::from)
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    fn tsig_signer(_config: &crate::config::dnssec::TsigKeyConfig) -> Result<Signer, String> {
+        Err("TSIG requires the dnssec feature".to_string())
+    }
+
+    /// Notifies `changed` each time a transfer changes the zone, e.g. to update the member zones
+    ///  of a catalog zone
+    pub fn set_changed_notify(&mut self, changed: Arc<Notify>) {
+        self.changed = Some(changed);
+    }
+
+    /// Returns true if the zone is loaded and has not expired
+    pub fn is_current(&self) -> bool {
+        self.expires
+            .lock()
+            .expect("expires lock poisoned")
+            .map_or(false, |expires| Instant::now() < expires)
+    }
+
+    /// Spawns the task refreshing the zone, which runs until the authority is dropped
+    ///
+    /// This must be called from within a tokio runtime.
+    pub fn spawn_refresh(self: &Arc<Self>) -> JoinHandle<()> {
+        let authority = Arc::downgrade(self);
+        let refresh_now = Arc::clone(&self.refresh_now);
+
+        tokio::spawn(refresh_loop(authority, refresh_now))
+    }
+
+    /// Refreshes the zone from the first primary which responds
+    ///
+    /// The SOA serial of the primary is queried first, the zone is only transferred if it is
+    ///  newer than that of the zone. The zone is transferred with IXFR if it is loaded, otherwise
+    ///  with AXFR.
+    pub async fn refresh(&self) -> ClientResult<()> {
+        let _refreshing = self.refreshing.lock().await;
+
+        let mut last_error = None;
+        for primary in &self.primaries {
+            match self.refresh_from(*primary).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    warn!(
+                        "failed to refresh zone {} from {}: {}",
+                        self.origin(),
+                        primary,
+                        e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| ClientError::from("no primaries configured")))
+    }
+
+    async fn refresh_from(&self, primary: SocketAddr) -> ClientResult<()> {
+        let (stream, sender) = TcpClientStream::<AsyncIoTokioAsStd<TokioTcpStream>>::with_timeout(
+            primary,
+            CONNECT_TIMEOUT,
+        );
+        let (mut client, bg) = AsyncClient::new(stream, sender, self.signer.clone()).await?;
+        let bg = tokio::spawn(bg);
+
+        let result = self.transfer(&mut client).await;
+        bg.abort();
+        result
+    }
+
+    async fn transfer(&self, client: &mut AsyncClient) -> ClientResult<()> {
+        let origin = Name::from(self.origin());
+        let previous = self.in_memory.records().await;
+        let serial = if self.is_loaded() {
+            Some(self.in_memory.serial().await)
+        } else {
+            None
+        };
+
+        if let Some(serial) = serial {
+            let response = client
+                .query(origin.clone(), self.in_memory.class(), RecordType::SOA)
+                .await?;
+            let primary_serial = response
+                .answers()
+                .iter()
+                .filter_map(Record::data)
+                .find_map(RData::as_soa)
+                .map(|soa| soa.serial())
+                .ok_or_else(|| ClientError::from("primary did not return the SOA of the zone"))?;
+
+            if !serial_lt(serial, primary_serial) {
+                debug!("zone {} is up to date at serial {}", origin, serial);
+                self.reset_expire().await;
+                return Ok(());
+            }
+
+            info!(
+                "transferring zone {} from serial {} to {}",
+                origin, serial, primary_serial
+            );
+        } else {
+            info!("transferring zone {}", origin);
+        }
+
+        let mut transfer = client.zone_transfer(origin, serial, None);
+        let mut full = Vec::new();
+        while let Some(batch) = transfer.next().await {
+            match batch? {
+                XfrBatch::Full(records) => full.extend(records),
+                XfrBatch::Restart => full.clear(),
+                XfrBatch::Incremental {
+                    deleted, to, added, ..
+                } => self.apply_difference(&deleted, to, added).await?,
+                _ => return Err(ClientError::from("unsupported zone transfer batch")),
+            }
+        }
+
+        if !full.is_empty() {
+            self.replace_zone(full).await?;
+        }
+
+        info!(
+            "zone {} transferred at serial {}",
+            self.origin(),
+            self.in_memory.serial().await
+        );
+        self.reset_expire().await;
+
+        // further secondaries can transfer the changes, and are notified of them
+        self.in_memory.zone_changed(&previous).await;
+        if let Some(ref changed) = self.changed {
+            changed.notify_one();
+        }
+
+        if let Err(e) = self.write_zone_file().await {
+            warn!("could not store zone {}: {}", self.origin(), e);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the records of the zone, the records must contain the SOA of the zone
+    async fn replace_zone(&self, records: Vec<Record>) -> ClientResult<()> {
+        let mut zone = InMemoryAuthority::empty(
+            Name::from(self.origin()),
+            self.zone_type(),
+            self.is_axfr_allowed(),
+        );
+
+        let serial = records
+            .iter()
+            .filter(|record| record.name() == &Name::from(self.origin()))
+            .filter_map(Record::data)
+            .find_map(RData::as_soa)
+            .map(|soa| soa.serial())
+            .ok_or_else(|| ClientError::from("zone transfer did not contain the SOA"))?;
+
+        // RRSIGs are attached to the records they cover, so insert them last
+        let (rrsigs, records): (Vec<_>, Vec<_>) = records
+            .into_iter()
+            .partition(|record| record.record_type() == RecordType::RRSIG);
+
+        for record in records {
+            if record.dns_class() != self.in_memory.class() {
+                debug!(
+                    "skipping record of class {}: {}",
+                    record.dns_class(),
+                    record
+                );
+                continue;
+            }
+            if !zone.upsert_mut(record.clone(), serial) {
+                warn!(
+                    "failed to insert record into zone {}: {}",
+                    self.origin(),
+                    record
+                );
+            }
+        }
+
+        let zone_records = zone.records_get_mut();
+        attach_rrsigs(zone_records, rrsigs);
+
+        *self.in_memory.records_mut().await = mem::take(zone_records);
+        Ok(())
+    }
+
+    /// Applies a difference sequence of an incremental zone transfer
+    async fn apply_difference(
+        &self,
+        deleted: &[Record],
+        to: Record,
+        added: Vec<Record>,
+    ) -> ClientResult<()> {
+        fn same_record(a: &Record, b: &Record) -> bool {
+            a.name() == b.name()
+                && a.record_type() == b.record_type()
+                && a.dns_class() == b.dns_class()
+                && a.data() == b.data()
+        }
+
+        let mut records = self
+            .in_memory
+            .records()
+            .await
+            .into_values()
+            .flat_map(|rrset| {
+                rrset
+                    .records_without_rrsigs()
+                    .chain(rrset.rrsigs())
+                    .filter(|record| record.record_type() != RecordType::SOA)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .filter(|record| !deleted.iter().any(|d| same_record(record, d)))
+            .collect::<Vec<_>>();
+
+        records.push(to);
+        records.extend(added);
+
+        self.replace_zone(records).await
+    }
+
+    /// Writes the zone to the configured zone file, if any
+    async fn write_zone_file(&self) -> PersistenceResult<()> {
+        let zone_path = match self.zone_path {
+            Some(ref zone_path) => zone_path,
+            None => return Ok(()),
+        };
+
+        let records = self
+            .in_memory
+            .records()
+            .await
+            .into_iter()
+            .map(|(key, rrset)| (key, RecordSet::clone(&rrset)))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut writer = Writer::new(String::new());
+        writer
+            .write_zone(&Name::from(self.origin()), &records)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "failed to format zone"))?;
+
+        // write to a temporary file first, so that the zone file is always complete
+        let mut tmp_path = OsString::from(zone_path.as_os_str());
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(writer.into_inner().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, zone_path)?;
+
+        debug!("stored zone {} in {:?}", self.origin(), zone_path);
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.expires
+            .lock()
+            .expect("expires lock poisoned")
+            .is_some()
+    }
+
+    /// The zone was refreshed, restart the expire timer
+    async fn reset_expire(&self) {
+        let expire = self.timers().await.map_or(Duration::ZERO, |t| t.expire);
+        *self.expires.lock().expect("expires lock poisoned") = Some(Instant::now() + expire);
+    }
+
+    /// The refresh, retry and expire timers from the SOA of the zone
+    async fn timers(&self) -> Option<Timers> {
+        let soa = self
+            .in_memory
+            .lookup(self.origin(), RecordType::SOA, LookupOptions::default())
+            .await
+            .ok()?;
+        let soa = soa
+            .iter()
+            .filter_map(Record::data)
+            .find_map(RData::as_soa)?;
+
+        let secs = |value: i32| Duration::from_secs(value.max(0) as u64);
+        Some(Timers {
+            refresh: secs(soa.refresh()),
+            retry: secs(soa.retry()),
+            expire: secs(soa.expire()),
+        })
+    }
+
+    /// Returns an error if the zone is not loaded or expired
+    fn check_current(&self) -> Result<(), LookupError> {
+        if self.is_current() {
+            Ok(())
+        } else {
+            debug!("secondary zone {} is not loaded or expired", self.origin());
+            Err(LookupError::from(ResponseCode::ServFail))
+        }
+    }
+
+    /// Unwrap the InMemoryAuthority
+    pub fn unwrap(self) -> InMemoryAuthority {
+        self.in_memory
+    }
+}
+
+/// Adds the RRSIGs to the record sets they cover
+#[cfg(feature = "dnssec")]
+fn attach_rrsigs(records: &mut BTreeMap<RrKey, Arc<RecordSet>>, rrsigs: Vec<Record>) {
+    for rrsig in rrsigs {
+        let type_covered = match rrsig
+            .data()
+            .and_then(RData::as_dnssec)
+            .and_then(|rdata| rdata.as_sig())
+        {
+            Some(sig) => sig.type_covered(),
+            None => continue,
+        };
+
+        let key = RrKey::new(rrsig.name().into(), type_covered);
+        if let Some(rrset) = records.get_mut(&key) {
+            let mut rrset_clone = RecordSet::clone(rrset);
+            rrset_clone.insert_rrsig(rrsig);
+            *rrset = Arc::new(rrset_clone);
+        }
+    }
+}
+
+/// RRSIGs are only served with DNSSEC
+#[cfg(not(feature = "dnssec"))]
+fn attach_rrsigs(_records: &mut BTreeMap<RrKey, Arc<RecordSet>>, _rrsigs: Vec<Record>) {}
+
+struct Timers {
+    refresh: Duration,
+    retry: Duration,
+    expire: Duration,
+}
+
+/// Refreshes the zone at the refresh interval, or the retry interval after failures, until the
+///  authority is dropped
+async fn refresh_loop(authority: Weak<SecondaryAuthority>, refresh_now: Arc<Notify>) {
+    loop {
+        let wait = match authority.upgrade() {
+            Some(authority) => {
+                let result = authority.refresh().await;
+                let timers = authority.timers().await;
+
+                match (result, timers) {
+                    (Ok(()), Some(timers)) => timers.refresh,
+                    (Err(_), Some(timers)) => timers.retry,
+                    (_, None) => INITIAL_RETRY_INTERVAL,
+                }
+            }
+            None => return,
+        };
+
+        debug!("next refresh in {:?}", wait);
+        let sleep = Box::pin(tokio::time::sleep(wait));
+        let notified = Box::pin(refresh_now.notified());
+        if let Either::Right(_) = future::select(sleep, notified).await {
+            debug!("refreshing after NOTIFY");
+        }
+    }
+}
+
+impl Deref for SecondaryAuthority {
+    type Target = InMemoryAuthority;
+
+    fn deref(&self) -> &Self::Target {
+        &self.in_memory
+    }
+}
+
+impl DerefMut for SecondaryAuthority {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.in_memory
+    }
+}
+
+#[async_trait::async_trait]
+impl Authority for SecondaryAuthority {
+    type Lookup = <InMemoryAuthority as Authority>::Lookup;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.in_memory.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.in_memory.is_axfr_allowed()
+    }
+
+    /// Updates are not forwarded to the primary
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// A NOTIFY from one of the primaries triggers a refresh of the zone
+    async fn notify(&self, request_info: RequestInfo<'_>) -> UpdateResult<()> {
+        if !self
+            .primaries
+            .iter()
+            .any(|primary| primary.ip() == request_info.src.ip())
+        {
+            warn!(
+                "refusing NOTIFY for {} from {}, not a primary",
+                self.origin(),
+                request_info.src
+            );
+            return Err(ResponseCode::Refused);
+        }
+
+        info!(
+            "NOTIFY for {} from {}",
+            self.origin(),
+            request_info.src.ip()
+        );
+        self.refresh_now.notify_one();
+        Ok(())
+    }
+
+    /// Checks that the client is permitted to transfer the zone
+    async fn authorize_transfer(&self, request: &MessageRequest, src: IpAddr) -> UpdateResult<()> {
+        self.check_current().map_err(|_| ResponseCode::ServFail)?;
+        self.in_memory.authorize_transfer(request, src).await
+    }
+
+    /// The TSIG key which signed the transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn transfer_tsigner(&self, request: &MessageRequest) -> Option<TSigner> {
+        self.in_memory.transfer_tsigner(request).await
+    }
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR
+    async fn ixfr_diffs(&self, serial: u32) -> Option<Vec<ZoneDiff>> {
+        self.in_memory.ixfr_diffs(serial).await
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.in_memory.origin()
+    }
+
+    /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.check_current()?;
+        self.in_memory.lookup(name, rtype, lookup_options).await
+    }
+
+    /// Using the specified query, perform a lookup against this zone.
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.check_current()?;
+        self.in_memory.search(request_info, lookup_options).await
+    }
+
+    /// Return the NSEC records based on the given name
+    async fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.check_current()?;
+        self.in_memory.get_nsec_records(name, lookup_options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use futures_executor::block_on;
+
+    use super::*;
+    use crate::client::rr::rdata::SOA;
+
+    fn soa(serial: u32) -> Record {
+        Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            3600,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("root.example.com.").unwrap(),
+                serial,
+                7200,
+                600,
+                86400,
+                300,
+            )),
+        )
+    }
+
+    fn a(name: &str, ip: [u8; 4]) -> Record {
+        Record::from_rdata(Name::from_str(name).unwrap(), 3600, RData::A(ip.into()))
+    }
+
+    fn authority() -> SecondaryAuthority {
+        SecondaryAuthority::new(
+            Name::from_str("example.com.").unwrap(),
+            ZoneType::Secondary,
+            false,
+            vec!["127.0.0.1:53".parse().unwrap()],
+        )
+    }
+
+    fn lookup_a(authority: &SecondaryAuthority, name: &str) -> Result<usize, LookupError> {
+        block_on(authority.lookup(
+            &LowerName::from_str(name).unwrap(),
+            RecordType::A,
+            LookupOptions::default(),
+        ))
+        .map(|lookup| lookup.iter().count())
+    }
+
+    #[test]
+    fn test_not_loaded() {
+        let authority = authority();
+        assert!(!authority.is_current());
+        assert!(matches!(
+            lookup_a(&authority, "www.example.com."),
+            Err(LookupError::ResponseCode(ResponseCode::ServFail))
+        ));
+    }
+
+    #[test]
+    fn test_replace_and_apply_difference() {
+        let authority = authority();
+
+        block_on(authority.replace_zone(vec![
+            soa(1),
+            a("www.example.com.", [127, 0, 0, 1]),
+            a("www.example.com.", [127, 0, 0, 2]),
+        ]))
+        .unwrap();
+        block_on(authority.reset_expire());
+
+        assert!(authority.is_current());
+        assert_eq!(block_on(authority.in_memory.serial()), 1);
+        assert_eq!(lookup_a(&authority, "www.example.com.").unwrap(), 2);
+
+        block_on(authority.apply_difference(
+            &[a("www.example.com.", [127, 0, 0, 2])],
+            soa(2),
+            vec![a("ftp.example.com.", [127, 0, 0, 3])],
+        ))
+        .unwrap();
+
+        assert_eq!(block_on(authority.in_memory.serial()), 2);
+        assert_eq!(lookup_a(&authority, "www.example.com.").unwrap(), 1);
+        assert_eq!(lookup_a(&authority, "ftp.example.com.").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_replace_without_soa() {
+        let authority = authority();
+        assert!(
+            block_on(authority.replace_zone(vec![a("www.example.com.", [127, 0, 0, 1])])).is_err()
+        );
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use tracing::warn;
+
+use crate::client::rr::{LowerName, Name, RData, Record, RecordSet, RecordType, RrKey};
+
+/// The version of the schema of catalog zones which is supported
+const SUPPORTED_VERSION: &str = "2";
+
+/// A member zone listed in a catalog zone
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CatalogMember {
+    /// The unique id of the member, the label of its entry below `zones`
+    pub unique_id: String,
+    /// The name of the member zone
+    pub zone: Name,
+    /// The groups of the member, from its `group` property
+    pub groups: Vec<String>,
+    /// The catalog zone to which the member may move, from its `coo` property
+    pub change_of_ownership: Option<Name>,
+}
+
+/// The member zones listed in a catalog zone, see RFC 9432
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct CatalogZone {
+    members: Vec<CatalogMember>,
+}
+
+impl CatalogZone {
+    /// Reads the member zones from the records of the catalog zone
+    ///
+    /// Returns an error if the version of the catalog zone is missing or not supported. Entries
+    ///  with other than one PTR record are ignored, as are further entries of a listed zone.
+    pub fn from_records(
+        origin: &LowerName,
+        records: &BTreeMap<RrKey, Arc<RecordSet>>,
+    ) -> Result<Self, String> {
+        let origin = Name::from(origin);
+        let version = txt_strings(records, &child(&origin, "version")?);
+        if version.len() != 1 || version[0] != SUPPORTED_VERSION {
+            return Err(format!(
+                "unsupported version of catalog zone {}: {:?}",
+                origin, version
+            ));
+        }
+
+        let zones = LowerName::from(child(&origin, "zones")?);
+        let mut members: Vec<CatalogMember> = Vec::new();
+        for (key, rrset) in records {
+            if key.record_type != RecordType::PTR
+                || key.name.num_labels() != zones.num_labels() + 1
+                || !zones.zone_of(&key.name)
+            {
+                continue;
+            }
+
+            let entry = Name::from(&key.name);
+            let zone = match rrset.records_without_rrsigs().collect::<Vec<_>>()[..] {
+                [record] => match record.data().and_then(RData::as_ptr) {
+                    Some(zone) => zone.clone(),
+                    None => continue,
+                },
+                _ => {
+                    warn!("ignoring catalog entry without a single PTR: {}", entry);
+                    continue;
+                }
+            };
+
+            if members
+                .iter()
+                .any(|m| LowerName::from(&m.zone) == LowerName::from(&zone))
+            {
+                warn!("ignoring further catalog entry of zone {}: {}", zone, entry);
+                continue;
+            }
+
+            let unique_id = entry
+                .iter()
+                .next()
+                .map(|label| String::from_utf8_lossy(label).to_ascii_lowercase())
+                .unwrap_or_default();
+            let groups = txt_strings(records, &child(&entry, "group")?);
+            let change_of_ownership = records
+                .get(&RrKey::new(child(&entry, "coo")?.into(), RecordType::PTR))
+                .and_then(|rrset| rrset.records_without_rrsigs().next())
+                .and_then(Record::data)
+                .and_then(RData::as_ptr)
+                .cloned();
+
+            members.push(CatalogMember {
+                unique_id,
+                zone,
+                groups,
+                change_of_ownership,
+            });
+        }
+
+        Ok(Self { members })
+    }
+
+    /// The member zones, in the canonical order of their entries
+    pub fn members(&self) -> &[CatalogMember] {
+        &self.members
+    }
+
+    /// The member of the zone, if it is listed
+    pub fn member(&self, zone: &LowerName) -> Option<&CatalogMember> {
+        self.members
+            .iter()
+            .find(|m| LowerName::from(&m.zone) == *zone)
+    }
+}
+
+fn child(name: &Name, label: &str) -> Result<Name, String> {
+    Name::parse(label, Some(name)).map_err(|e| format!("bad name {}.{}: {}", label, name, e))
+}
+
+/// The strings of the TXT records of the name, each record as one string
+fn txt_strings(records: &BTreeMap<RrKey, Arc<RecordSet>>, name: &Name) -> Vec<String> {
+    records
+        .get(&RrKey::new(name.into(), RecordType::TXT))
+        .map(|rrset| {
+            rrset
+                .records_without_rrsigs()
+                .filter_map(Record::data)
+                .filter_map(RData::as_txt)
+                .map(|txt| {
+                    txt.txt_data()
+                        .iter()
+                        .map(|data| String::from_utf8_lossy(data))
+                        .collect::<String>()
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::client::rr::rdata::TXT;
+
+    fn catalog(records: &[(&str, RData)]) -> Result<CatalogZone, String> {
+        let mut rrsets: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+        for (name, rdata) in records {
+            let name = Name::from_str(name).unwrap();
+            let key = RrKey::new(name.clone().into(), rdata.to_record_type());
+            rrsets
+                .entry(key)
+                .or_insert_with(|| RecordSet::new(&name, rdata.to_record_type(), 0))
+                .insert(Record::from_rdata(name, 0, rdata.clone()), 0);
+        }
+
+        let rrsets = rrsets
+            .into_iter()
+            .map(|(key, rrset)| (key, Arc::new(rrset)))
+            .collect();
+        CatalogZone::from_records(&LowerName::from_str("catz.").unwrap(), &rrsets)
+    }
+
+    fn txt(text: &str) -> RData {
+        RData::TXT(TXT::new(vec![text.to_string()]))
+    }
+
+    fn ptr(name: &str) -> RData {
+        RData::PTR(Name::from_str(name).unwrap())
+    }
+
+    #[test]
+    fn test_members() {
+        let catalog = catalog(&[
+            ("version.catz.", txt("2")),
+            ("id1.zones.catz.", ptr("example.com.")),
+            ("group.id1.zones.catz.", txt("external")),
+            ("coo.id1.zones.catz.", ptr("other-catz.")),
+            ("id2.zones.catz.", ptr("example.net.")),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            catalog.members(),
+            &[
+                CatalogMember {
+                    unique_id: "id1".to_string(),
+                    zone: Name::from_str("example.com.").unwrap(),
+                    groups: vec!["external".to_string()],
+                    change_of_ownership: Some(Name::from_str("other-catz.").unwrap()),
+                },
+                CatalogMember {
+                    unique_id: "id2".to_string(),
+                    zone: Name::from_str("example.net.").unwrap(),
+                    groups: vec![],
+                    change_of_ownership: None,
+                },
+            ]
+        );
+        assert!(catalog
+            .member(&LowerName::from_str("EXAMPLE.net.").unwrap())
+            .is_some());
+    }
+
+    #[test]
+    fn test_version() {
+        assert!(catalog(&[("id1.zones.catz.", ptr("example.com."))]).is_err());
+        assert!(catalog(&[
+            ("version.catz.", txt("1")),
+            ("id1.zones.catz.", ptr("example.com.")),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_bad_entries() {
+        let catalog = catalog(&[
+            ("version.catz.", txt("2")),
+            // more than one PTR
+            ("id1.zones.catz.", ptr("example.com.")),
+            ("id1.zones.catz.", ptr("example.org.")),
+            // the zone is already listed
+            ("id2.zones.catz.", ptr("example.net.")),
+            ("id3.zones.catz.", ptr("example.net.")),
+            // not a member entry
+            ("other.catz.", ptr("example.edu.")),
+        ])
+        .unwrap();
+
+        assert_eq!(catalog.members().len(), 1);
+        assert_eq!(catalog.members()[0].unique_id, "id2");
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::SocketAddr;
+
+use serde::Deserialize;
+
+use crate::{config::dnssec::TsigKeyConfig, store::secondary::CatalogMember};
+
+/// Configuration for secondary zones
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct SecondaryConfig {
+    /// addresses of the primaries, they are tried in order for each refresh of the zone
+    pub primaries: Vec<SocketAddr>,
+    /// TSIG key to sign the SOA queries and zone transfers with
+    #[serde(default)]
+    pub tsig_key: Option<TsigKeyConfig>,
+    /// path to store the transferred zone, it is served from this file until the first refresh
+    #[serde(default)]
+    pub zone_file_path: Option<String>,
+    /// The zone is a catalog zone, its member zones are served as secondaries, see RFC 9432
+    #[serde(default)]
+    pub catalog: Option<CatalogZoneConfig>,
+}
+
+impl SecondaryConfig {
+    /// Configuration for a secondary zone transferred from the `primaries`, which is not stored
+    pub fn new(primaries: Vec<SocketAddr>) -> Self {
+        Self {
+            primaries,
+            tsig_key: None,
+            zone_file_path: None,
+            catalog: None,
+        }
+    }
+}
+
+/// Configuration of the member zones of a catalog zone
+#[derive(Clone, Default, Deserialize, PartialEq, Eq, Debug)]
+pub struct CatalogZoneConfig {
+    /// addresses of the primaries of the member zones, those of the catalog zone if empty
+    #[serde(default)]
+    pub primaries: Vec<SocketAddr>,
+    /// TSIG key to sign the transfers of the member zones with, that of the catalog zone if none
+    #[serde(default)]
+    pub tsig_key: Option<TsigKeyConfig>,
+    /// directory to store the transferred member zones in, they are not stored if none
+    #[serde(default)]
+    pub zone_file_dir: Option<String>,
+    /// Allow AXFR and IXFR of the member zones, default false
+    #[serde(default)]
+    pub allow_axfr: Option<bool>,
+    /// Configurations of the members of groups, the first group of a member which is configured
+    ///  applies to it
+    #[serde(default)]
+    pub groups: Vec<CatalogGroupConfig>,
+}
+
+impl CatalogZoneConfig {
+    /// The configuration of the member zone, a secondary of the primaries of its group
+    ///
+    /// # Arguments
+    ///
+    /// * `catalog` - the configuration of the catalog zone
+    /// * `member` - the member zone, as listed in the catalog zone
+    pub fn member_config(
+        &self,
+        catalog: &SecondaryConfig,
+        member: &CatalogMember,
+    ) -> SecondaryConfig {
+        let group = self.group_of(member);
+        let primaries = group
+            .map(|g| &g.primaries)
+            .filter(|primaries| !primaries.is_empty())
+            .or_else(|| Some(&self.primaries).filter(|primaries| !primaries.is_empty()))
+            .unwrap_or(&catalog.primaries)
+            .clone();
+        let tsig_key = group
+            .and_then(|g| g.tsig_key.as_ref())
+            .or(self.tsig_key.as_ref())
+            .or(catalog.tsig_key.as_ref())
+            .cloned();
+        let zone_file_path = self
+            .zone_file_dir
+            .as_ref()
+            .map(|dir| format!("{}/{}zone", dir, member.zone.to_lowercase()));
+
+        SecondaryConfig {
+            primaries,
+            tsig_key,
+            zone_file_path,
+            catalog: None,
+        }
+    }
+
+    /// Returns true if transfers of the member zone are allowed
+    pub fn is_member_axfr_allowed(&self, member: &CatalogMember) -> bool {
+        self.group_of(member)
+            .and_then(|g| g.allow_axfr)
+            .or(self.allow_axfr)
+            .unwrap_or(false)
+    }
+
+    fn group_of(&self, member: &CatalogMember) -> Option<&CatalogGroupConfig> {
+        member
+            .groups
+            .iter()
+            .find_map(|name| self.groups.iter().find(|g| g.name == *name))
+    }
+}
+
+/// Configuration of the members of a group of a catalog zone, see the `group` property of RFC 9432
+#[derive(Clone, Deserialize, PartialEq, Eq, Debug)]
+pub struct CatalogGroupConfig {
+    /// name of the group, as in the `group` property of the members
+    pub name: String,
+    /// addresses of the primaries of the members, those of the catalog if empty
+    #[serde(default)]
+    pub primaries: Vec<SocketAddr>,
+    /// TSIG key to sign the transfers of the members with, that of the catalog if none
+    #[serde(default)]
+    pub tsig_key: Option<TsigKeyConfig>,
+    /// Allow AXFR and IXFR of the members, that of the catalog if not set
+    #[serde(default)]
+    pub allow_axfr: Option<bool>,
+}
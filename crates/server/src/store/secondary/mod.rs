@@ -0,0 +1,16 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Secondary zones, transferred from a primary with AXFR or IXFR
+
+mod authority;
+mod catalog_zone;
+mod config;
+
+pub use self::authority::SecondaryAuthority;
+pub use self::catalog_zone::{CatalogMember, CatalogZone};
+pub use self::config::{CatalogGroupConfig, CatalogZoneConfig, SecondaryConfig};
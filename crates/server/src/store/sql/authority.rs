@@ -0,0 +1,564 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! All authority related types
+
+use std::{
+    net::IpAddr,
+    ops::{Deref, DerefMut},
+    path::Path,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use futures_util::lock::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    authority::{
+        Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneDiff, ZoneType,
+    },
+    client::rr::LowerName,
+    error::{PersistenceErrorKind, PersistenceResult},
+    proto::{
+        op::ResponseCode,
+        rr::{Name, Record, RecordType},
+    },
+    server::RequestInfo,
+    store::{
+        in_memory::InMemoryAuthority,
+        sql::{SqlConfig, SqlJournal},
+    },
+};
+#[cfg(feature = "dnssec")]
+use crate::{
+    authority::{DnssecAuthority, KeyRole, KeyTimings, UpdateRequest},
+    client::rr::dnssec::{tsig::TSigner, DnsSecResult, SigSigner},
+    proto::rr::dnssec::rdata::{key::KEY, NSEC3PARAM},
+};
+
+/// The number of changes to the zone which are restored from the journal for serving IXFR
+const MAX_JOURNAL_DIFFS: usize = 64;
+
+/// The number of journal records which are read at once
+const JOURNAL_BATCH: usize = 1024;
+
+/// SqlAuthority serves a zone from a journal in a PostgreSQL or MySQL database
+///
+/// The zone is held in memory, and recovered from the journal on startup. Dynamic updates are
+///  written to the journal before they are applied. Any number of servers may serve the zone
+///  from the same database, each follows the changes written by the others, see `refresh()`.
+///  Concurrent updates of the same zone on different servers are applied by each server in the
+///  order in which they were written, but their SOA serials may collide, so updates should be
+///  sent to one server at a time. DNSSEC signing zones should be updated on one server only, as
+///  the signatures are not journaled.
+pub struct SqlAuthority {
+    in_memory: InMemoryAuthority,
+    journal: SqlJournal,
+    // the id of the last journal record applied to the zone, held while applying changes
+    last_record: Mutex<i64>,
+    allow_update: bool,
+    #[allow(dead_code)]
+    is_dnssec_enabled: bool,
+    refresh_interval: Option<Duration>,
+}
+
+impl SqlAuthority {
+    /// Creates a new Authority, the zone is not loaded from the journal, see `refresh()`
+    ///
+    /// # Arguments
+    ///
+    /// * `in_memory` - InMemoryAuthority for all records.
+    /// * `journal` - the journal of the zone in the database
+    /// * `allow_update` - If true, then this zone accepts dynamic updates.
+    /// * `is_dnssec_enabled` - If true, then the zone will sign the zone with all registered keys,
+    ///                         (see `add_zone_signing_key()`)
+    pub fn new(
+        in_memory: InMemoryAuthority,
+        journal: SqlJournal,
+        allow_update: bool,
+        is_dnssec_enabled: bool,
+    ) -> Self {
+        Self {
+            in_memory,
+            journal,
+            last_record: Mutex::new(0),
+            allow_update,
+            is_dnssec_enabled,
+            refresh_interval: None,
+        }
+    }
+
+    /// load the authority from the configuration
+    ///
+    /// The zone is recovered from the database, if the database has no records of the zone it is
+    ///  loaded from the zone file and written to the database.
+    pub async fn try_from_config(
+        origin: Name,
+        zone_type: ZoneType,
+        allow_axfr: bool,
+        enable_dnssec: bool,
+        root_dir: Option<&Path>,
+        config: &SqlConfig,
+    ) -> Result<Self, String> {
+        use crate::store::file::{FileAuthority, FileConfig};
+
+        let zone_name: Name = origin;
+        let journal = SqlJournal::connect(&config.url, &zone_name, config.get_max_connections())
+            .await
+            .map_err(|e| format!("error connecting to database for {}: {}", zone_name, e))?;
+
+        info!("recovering zone from database: {}", zone_name);
+        let in_memory = InMemoryAuthority::empty(zone_name.clone(), zone_type, allow_axfr);
+        let mut authority = Self::new(in_memory, journal, config.allow_update, enable_dnssec);
+        authority.refresh_interval = config.get_refresh_interval();
+
+        authority
+            .recover()
+            .await
+            .map_err(|e| format!("error recovering from database: {}", e))?;
+
+        if *authority.last_record.get_mut() != 0 {
+            info!("recovered zone: {}", zone_name);
+            return Ok(authority);
+        }
+
+        // the database has no records of the zone
+        let zone_file_path = config.zone_file_path.as_ref().ok_or_else(|| {
+            format!(
+                "no records of zone {} in database, and no zone_file_path",
+                zone_name
+            )
+        })?;
+
+        info!("loading zone file: {:?}", zone_file_path);
+        let file_config = FileConfig::new(zone_file_path.clone());
+        authority.in_memory = FileAuthority::try_from_config(
+            zone_name.clone(),
+            zone_type,
+            allow_axfr,
+            root_dir,
+            &file_config,
+        )?
+        .unwrap();
+
+        authority
+            .persist_to_journal()
+            .await
+            .map_err(|e| format!("error persisting zone to database: {}", e))?;
+
+        // skip the records just written, and any written concurrently by other servers
+        authority
+            .refresh()
+            .await
+            .map_err(|e| format!("error refreshing zone from database: {}", e))?;
+
+        info!("zone file loaded: {}", zone_name);
+        Ok(authority)
+    }
+
+    /// Recovers the zone from the journal, and the history of changes for IXFR
+    async fn recover(&self) -> PersistenceResult<()> {
+        let mut last_record = self.last_record.lock().await;
+        self.apply_journal(&mut last_record).await?;
+
+        // restore the history of changes for IXFR
+        for diff in self.journal.select_diffs(MAX_JOURNAL_DIFFS).await? {
+            self.in_memory.push_diff(diff).await;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the records of the journal after the last applied record, returns true if any
+    ///  records were applied
+    async fn apply_journal(&self, last_record: &mut i64) -> PersistenceResult<bool> {
+        let mut applied = false;
+
+        loop {
+            let records = self
+                .journal
+                .select_records(*last_record, JOURNAL_BATCH)
+                .await?;
+            let done = records.len() < JOURNAL_BATCH;
+
+            for (id, record) in records {
+                // AXFR is special, it is used to mark the dump of a full zone.
+                if record.rr_type() == RecordType::AXFR {
+                    self.in_memory.records_mut().await.clear();
+                } else if let Err(error) = self.in_memory.update_records(&[record]).await {
+                    return Err(PersistenceErrorKind::Recovery(error.to_str()).into());
+                }
+
+                *last_record = id;
+                applied = true;
+            }
+
+            if done {
+                return Ok(applied);
+            }
+        }
+    }
+
+    /// Applies the changes to the zone written to the journal by other servers
+    ///
+    /// The changes of this server are applied again, which has no effect as updates are
+    ///  idempotent. If the zone changed, the change is kept for IXFR and secondaries are notified.
+    pub async fn refresh(&self) -> PersistenceResult<()> {
+        let mut last_record = self.last_record.lock().await;
+        let previous = self.in_memory.records().await;
+
+        if self.apply_journal(&mut last_record).await? {
+            if let Some(diff) = self.in_memory.zone_changed(&previous).await {
+                info!(
+                    "zone {} changed in database, serial: {}",
+                    self.origin(),
+                    diff.to_serial()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawns the task applying the changes written to the journal by other servers, which runs
+    ///  until the authority is dropped
+    ///
+    /// Returns None if refreshing is disabled. This must be called from within a tokio runtime.
+    pub fn spawn_refresh(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let interval = self.refresh_interval?;
+        let authority = Arc::downgrade(self);
+
+        Some(tokio::spawn(refresh_loop(authority, interval)))
+    }
+
+    /// Sets the interval of the checks for changes written by other servers, see `spawn_refresh()`
+    pub fn set_refresh_interval(&mut self, refresh_interval: Option<Duration>) {
+        self.refresh_interval = refresh_interval;
+    }
+
+    /// Persist the state of the current zone to the journal
+    ///
+    /// Returns an error if there was an issue writing to the database.
+    pub async fn persist_to_journal(&self) -> PersistenceResult<()> {
+        let serial = self.in_memory.serial().await;
+
+        info!("persisting zone to database at SOA.serial: {}", serial);
+        let records = self
+            .in_memory
+            .records()
+            .await
+            .values()
+            .flat_map(|rr_set| rr_set.records_without_rrsigs().cloned())
+            .collect::<Vec<_>>();
+
+        self.journal.insert_zone(serial, &records).await
+    }
+
+    /// Returns the associated Journal
+    pub fn journal(&self) -> &SqlJournal {
+        &self.journal
+    }
+
+    /// Enables the zone for dynamic DNS updates
+    pub fn set_allow_update(&mut self, allow_update: bool) {
+        self.allow_update = allow_update;
+    }
+
+    /// Checks that the update is allowed, and authorized by the keys of the zone, see RFC 2136
+    #[cfg(feature = "dnssec")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+    pub async fn authorize(&self, update_message: &MessageRequest) -> UpdateResult<()> {
+        // does this authority allow_updates?
+        if !self.allow_update {
+            warn!(
+                "update attempted on non-updatable Authority: {}",
+                self.origin()
+            );
+            return Err(ResponseCode::Refused);
+        }
+
+        self.in_memory.authorize_update(update_message).await
+    }
+
+    /// Updates the specified records according to the update section, see RFC 2136
+    ///
+    /// The changes written by other servers are applied first, then the update is written to the
+    ///  journal and applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - set of record instructions for update following above rules
+    /// * `auto_signing_and_increment` - if true, the zone will sign and increment the SOA
+    pub async fn update_records(
+        &self,
+        records: &[Record],
+        auto_signing_and_increment: bool,
+    ) -> UpdateResult<bool> {
+        let mut last_record = self.last_record.lock().await;
+        if let Err(error) = self.apply_journal(&mut last_record).await {
+            error!("could not apply changes from database: {}", error);
+            return Err(ResponseCode::ServFail);
+        }
+
+        // the journal acts as a write-ahead log, shared with the other servers
+        let serial: u32 = self.in_memory.serial().await;
+        if let Err(error) = self.journal.insert_records(serial, records).await {
+            error!("could not persist update records: {}", error);
+            return Err(ResponseCode::ServFail);
+        }
+
+        let previous = self.in_memory.records().await;
+        let updated = self.in_memory.update_records(records).await?;
+
+        if updated && auto_signing_and_increment {
+            if self.is_dnssec_enabled {
+                cfg_if::cfg_if! {
+                    if #[cfg(feature = "dnssec")] {
+                        self.in_memory.secure_zone().await.map_err(|e| {
+                            error!("failure securing zone: {}", e);
+                            ResponseCode::ServFail
+                        })?
+                    } else {
+                        error!("failure securing zone, dnssec feature not enabled");
+                        return Err(ResponseCode::ServFail)
+                    }
+                }
+            } else {
+                self.in_memory.increment_soa_serial().await;
+            }
+
+            if let Some(diff) = self.in_memory.zone_changed(&previous).await {
+                self.persist_diff(&diff).await;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    /// Journals the new SOA, so that the serial is restored on recovery, and the diff for IXFR
+    async fn persist_diff(&self, diff: &ZoneDiff) {
+        let result = match self
+            .journal
+            .insert_records(diff.to_serial(), &[diff.to().clone()])
+            .await
+        {
+            Ok(()) => self.journal.insert_diff(diff).await,
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = result {
+            error!("could not persist zone changes: {}", error);
+        }
+    }
+}
+
+async fn refresh_loop(authority: Weak<SqlAuthority>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let authority = match authority.upgrade() {
+            Some(authority) => authority,
+            None => return,
+        };
+
+        debug!("refreshing zone from database: {}", authority.origin());
+        if let Err(error) = authority.refresh().await {
+            warn!(
+                "failed to refresh zone {} from database: {}",
+                authority.origin(),
+                error
+            );
+        }
+    }
+}
+
+impl Deref for SqlAuthority {
+    type Target = InMemoryAuthority;
+
+    fn deref(&self) -> &Self::Target {
+        &self.in_memory
+    }
+}
+
+impl DerefMut for SqlAuthority {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.in_memory
+    }
+}
+
+#[async_trait::async_trait]
+impl Authority for SqlAuthority {
+    type Lookup = <InMemoryAuthority as Authority>::Lookup;
+
+    /// What type is this zone
+    fn zone_type(&self) -> ZoneType {
+        self.in_memory.zone_type()
+    }
+
+    /// Return true if AXFR is allowed
+    fn is_axfr_allowed(&self) -> bool {
+        self.in_memory.is_axfr_allowed()
+    }
+
+    /// Takes the UpdateMessage, extracts the Records, and applies the changes to the record set,
+    ///  see RFC 2136
+    ///
+    /// # Return value
+    ///
+    /// true if any of additions, updates or deletes were made to the zone, false otherwise. Err is
+    ///  returned in the case of bad data, etc.
+    #[cfg(feature = "dnssec")]
+    async fn update(&self, update: &MessageRequest) -> UpdateResult<bool> {
+        self.authorize(update).await?;
+        self.in_memory
+            .verify_prerequisites(update.prerequisites())
+            .await?;
+        self.in_memory.pre_scan(update.updates()).await?;
+
+        self.update_records(update.updates(), true).await
+    }
+
+    /// Always fail when DNSSEC is disabled.
+    #[cfg(not(feature = "dnssec"))]
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// The TSIG key which signed the update, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn update_tsigner(&self, update: &MessageRequest) -> Option<TSigner> {
+        self.in_memory.update_tsigner(update).await
+    }
+
+    /// Checks that the client is permitted to transfer the zone
+    async fn authorize_transfer(&self, request: &MessageRequest, src: IpAddr) -> UpdateResult<()> {
+        self.in_memory.authorize_transfer(request, src).await
+    }
+
+    /// The TSIG key which signed the transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn transfer_tsigner(&self, request: &MessageRequest) -> Option<TSigner> {
+        self.in_memory.transfer_tsigner(request).await
+    }
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR
+    async fn ixfr_diffs(&self, serial: u32) -> Option<Vec<ZoneDiff>> {
+        self.in_memory.ixfr_diffs(serial).await
+    }
+
+    /// Replaces the TXT records at the name with the values, for answering ACME challenges
+    async fn set_challenge_txt(&self, name: &LowerName, values: &[String]) -> UpdateResult<()> {
+        self.in_memory.set_challenge_txt(name, values).await
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    fn origin(&self) -> &LowerName {
+        self.in_memory.origin()
+    }
+
+    /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.in_memory.lookup(name, rtype, lookup_options).await
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.in_memory.search(request_info, lookup_options).await
+    }
+
+    /// Return the NSEC records based on the given name
+    async fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.in_memory.get_nsec_records(name, lookup_options).await
+    }
+}
+
+#[cfg(feature = "dnssec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
+#[async_trait::async_trait]
+impl DnssecAuthority for SqlAuthority {
+    async fn add_update_auth_key(&self, name: Name, key: KEY) -> DnsSecResult<()> {
+        self.in_memory.add_update_auth_key(name, key).await
+    }
+
+    async fn add_update_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.in_memory.add_update_tsig_key(signer).await
+    }
+
+    async fn add_transfer_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.in_memory.add_transfer_tsig_key(signer).await
+    }
+
+    /// By adding a secure key, this will implicitly enable dnssec for the zone.
+    async fn add_zone_signing_key(&self, signer: SigSigner) -> DnsSecResult<()> {
+        self.in_memory.add_zone_signing_key(signer).await
+    }
+
+    async fn add_scheduled_signing_key(
+        &self,
+        signer: SigSigner,
+        role: KeyRole,
+        timings: KeyTimings,
+    ) -> DnsSecResult<()> {
+        self.in_memory
+            .add_scheduled_signing_key(signer, role, timings)
+            .await
+    }
+
+    /// Publishes the keys as scheduled and re-signs the zone, journaling the new serial
+    async fn maintain_keys(&self, now: u64) -> DnsSecResult<bool> {
+        let _last_record = self.last_record.lock().await;
+        let previous = self.in_memory.records().await;
+        let changed = self.in_memory.maintain_keys(now).await?;
+        if !changed {
+            return Ok(false);
+        }
+
+        if let Some(diff) = self.in_memory.zone_changed(&previous).await {
+            self.persist_diff(&diff).await;
+        }
+
+        Ok(true)
+    }
+
+    /// Signs the zone with NSEC3 instead of NSEC
+    async fn set_nsec3(&self, nsec3param: NSEC3PARAM, opt_out: bool) -> DnsSecResult<()> {
+        self.in_memory.set_nsec3(nsec3param, opt_out).await
+    }
+
+    /// (Re)generates the nsec records, increments the serial number and signs the zone
+    async fn secure_zone(&self) -> DnsSecResult<()> {
+        self.in_memory.secure_zone().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::sql::SqlAuthority;
+
+    #[test]
+    fn test_is_send_sync() {
+        fn send_sync<T: Send + Sync>() -> bool {
+            true
+        }
+
+        assert!(send_sync::<SqlAuthority>());
+    }
+}
@@ -0,0 +1,17 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! PostgreSQL and MySQL serving with Dynamic DNS and journaling support, for servers sharing a
+//!  database
+
+pub mod authority;
+mod config;
+pub mod persistence;
+
+pub use self::authority::SqlAuthority;
+pub use self::config::SqlConfig;
+pub use self::persistence::SqlJournal;
@@ -8,21 +8,23 @@
 //! All authority related types
 
 use std::{
+    net::IpAddr,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
-    sync::Arc,
 };
 
 use futures_util::lock::Mutex;
-use tracing::{error, info, warn};
+use tracing::{error, info};
 
 use crate::{
-    authority::{Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneType},
-    client::rr::{LowerName, RrKey},
+    authority::{
+        Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneDiff, ZoneType,
+    },
+    client::rr::LowerName,
     error::{PersistenceErrorKind, PersistenceResult},
     proto::{
         op::ResponseCode,
-        rr::{DNSClass, Name, RData, Record, RecordSet, RecordType},
+        rr::{rdata::opt::ClientSubnet, Name, Record, RecordType},
     },
     server::RequestInfo,
     store::{
@@ -32,11 +34,14 @@ use crate::{
 };
 #[cfg(feature = "dnssec")]
 use crate::{
-    authority::{DnssecAuthority, UpdateRequest},
-    client::rr::dnssec::{DnsSecResult, SigSigner},
-    proto::rr::dnssec::rdata::key::KEY,
+    authority::{DnssecAuthority, KeyRole, KeyTimings, UpdateRequest},
+    client::rr::dnssec::{tsig::TSigner, DnsSecResult, SigSigner},
+    proto::rr::dnssec::rdata::{key::KEY, NSEC3PARAM},
 };
 
+/// The number of changes to the zone which are restored from the journal for serving IXFR
+const MAX_JOURNAL_DIFFS: usize = 64;
+
 /// SqliteAuthority is responsible for storing the resource records for a particular zone.
 ///
 /// Authorities default to DNSClass IN. The ZoneType specifies if this should be treated as the
@@ -99,6 +104,18 @@ impl SqliteAuthority {
             let in_memory = InMemoryAuthority::empty(zone_name.clone(), zone_type, allow_axfr);
             let mut authority = Self::new(in_memory, config.allow_update, enable_dnssec);
 
+            #[cfg(feature = "trust-dns-resolver")]
+            if let Some(aname) = &config.aname {
+                authority.set_aname_config(aname)?;
+            }
+
+            #[cfg(feature = "geoip")]
+            if let Some(geo) = &config.geo {
+                authority.set_geo_config(geo, root_dir)?;
+            }
+
+            authority.set_pools_config(&config.pools)?;
+
             authority
                 .recover_with_journal(&journal)
                 .await
@@ -112,9 +129,7 @@ impl SqliteAuthority {
             // TODO: deprecate this portion of loading, instantiate the journal through a separate tool
             info!("loading zone file: {:?}", zone_path);
 
-            let file_config = FileConfig {
-                zone_file_path: config.zone_file_path.clone(),
-            };
+            let file_config = FileConfig::new(config.zone_file_path.clone());
 
             let in_memory = FileAuthority::try_from_config(
                 zone_name.clone(),
@@ -127,6 +142,18 @@ impl SqliteAuthority {
 
             let mut authority = Self::new(in_memory, config.allow_update, enable_dnssec);
 
+            #[cfg(feature = "trust-dns-resolver")]
+            if let Some(aname) = &config.aname {
+                authority.set_aname_config(aname)?;
+            }
+
+            #[cfg(feature = "geoip")]
+            if let Some(geo) = &config.geo {
+                authority.set_geo_config(geo, root_dir)?;
+            }
+
+            authority.set_pools_config(&config.pools)?;
+
             // if dynamic update is enabled, enable the journal
             info!("creating new journal: {:?}", journal_path);
             let journal = Journal::from_file(&journal_path)
@@ -173,6 +200,11 @@ impl SqliteAuthority {
             }
         }
 
+        // restore the history of changes for IXFR
+        for diff in journal.select_diffs(MAX_JOURNAL_DIFFS)? {
+            self.in_memory.push_diff(diff).await;
+        }
+
         Ok(())
     }
 
@@ -276,151 +308,7 @@ impl SqliteAuthority {
     ///   zone     rrset    rr       RRset exists (value dependent)
     /// ```
     pub async fn verify_prerequisites(&self, pre_requisites: &[Record]) -> UpdateResult<()> {
-        //   3.2.5 - Pseudocode for Prerequisite Section Processing
-        //
-        //      for rr in prerequisites
-        //           if (rr.ttl != 0)
-        //                return (FORMERR)
-        //           if (zone_of(rr.name) != ZNAME)
-        //                return (NOTZONE);
-        //           if (rr.class == ANY)
-        //                if (rr.rdlength != 0)
-        //                     return (FORMERR)
-        //                if (rr.type == ANY)
-        //                     if (!zone_name<rr.name>)
-        //                          return (NXDOMAIN)
-        //                else
-        //                     if (!zone_rrset<rr.name, rr.type>)
-        //                          return (NXRRSET)
-        //           if (rr.class == NONE)
-        //                if (rr.rdlength != 0)
-        //                     return (FORMERR)
-        //                if (rr.type == ANY)
-        //                     if (zone_name<rr.name>)
-        //                          return (YXDOMAIN)
-        //                else
-        //                     if (zone_rrset<rr.name, rr.type>)
-        //                          return (YXRRSET)
-        //           if (rr.class == zclass)
-        //                temp<rr.name, rr.type> += rr
-        //           else
-        //                return (FORMERR)
-        //
-        //      for rrset in temp
-        //           if (zone_rrset<rrset.name, rrset.type> != rrset)
-        //                return (NXRRSET)
-        for require in pre_requisites {
-            let required_name = LowerName::from(require.name());
-
-            if require.ttl() != 0 {
-                warn!("ttl must be 0 for: {:?}", require);
-                return Err(ResponseCode::FormErr);
-            }
-
-            let origin = self.origin();
-            if !origin.zone_of(&require.name().into()) {
-                warn!("{} is not a zone_of {}", require.name(), origin);
-                return Err(ResponseCode::NotZone);
-            }
-
-            match require.dns_class() {
-                DNSClass::ANY => {
-                    if let None | Some(RData::NULL(..)) = require.data() {
-                        match require.rr_type() {
-                            // ANY      ANY      empty    Name is in use
-                            RecordType::ANY => {
-                                if self
-                                    .lookup(
-                                        &required_name,
-                                        RecordType::ANY,
-                                        LookupOptions::default(),
-                                    )
-                                    .await
-                                    .unwrap_or_default()
-                                    .was_empty()
-                                {
-                                    return Err(ResponseCode::NXDomain);
-                                } else {
-                                    continue;
-                                }
-                            }
-                            // ANY      rrset    empty    RRset exists (value independent)
-                            rrset => {
-                                if self
-                                    .lookup(&required_name, rrset, LookupOptions::default())
-                                    .await
-                                    .unwrap_or_default()
-                                    .was_empty()
-                                {
-                                    return Err(ResponseCode::NXRRSet);
-                                } else {
-                                    continue;
-                                }
-                            }
-                        }
-                    } else {
-                        return Err(ResponseCode::FormErr);
-                    }
-                }
-                DNSClass::NONE => {
-                    if let None | Some(RData::NULL(..)) = require.data() {
-                        match require.rr_type() {
-                            // NONE     ANY      empty    Name is not in use
-                            RecordType::ANY => {
-                                if !self
-                                    .lookup(
-                                        &required_name,
-                                        RecordType::ANY,
-                                        LookupOptions::default(),
-                                    )
-                                    .await
-                                    .unwrap_or_default()
-                                    .was_empty()
-                                {
-                                    return Err(ResponseCode::YXDomain);
-                                } else {
-                                    continue;
-                                }
-                            }
-                            // NONE     rrset    empty    RRset does not exist
-                            rrset => {
-                                if !self
-                                    .lookup(&required_name, rrset, LookupOptions::default())
-                                    .await
-                                    .unwrap_or_default()
-                                    .was_empty()
-                                {
-                                    return Err(ResponseCode::YXRRSet);
-                                } else {
-                                    continue;
-                                }
-                            }
-                        }
-                    } else {
-                        return Err(ResponseCode::FormErr);
-                    }
-                }
-                class if class == self.in_memory.class() =>
-                // zone     rrset    rr       RRset exists (value dependent)
-                {
-                    if !self
-                        .lookup(&required_name, require.rr_type(), LookupOptions::default())
-                        .await
-                        .unwrap_or_default()
-                        .iter()
-                        .any(|rr| rr == require)
-                    {
-                        return Err(ResponseCode::NXRRSet);
-                    } else {
-                        continue;
-                    }
-                }
-                _ => return Err(ResponseCode::FormErr),
-            }
-        }
-
-        // if we didn't bail everything checked out...
-        Ok(())
+        self.in_memory.verify_prerequisites(pre_requisites).await
     }
 
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -448,21 +336,8 @@ impl SqliteAuthority {
     ///
     #[cfg(feature = "dnssec")]
     #[cfg_attr(docsrs, doc(cfg(feature = "dnssec")))]
-    #[allow(clippy::blocks_in_if_conditions)]
     pub async fn authorize(&self, update_message: &MessageRequest) -> UpdateResult<()> {
-        use tracing::debug;
-
-        use crate::client::rr::rdata::DNSSECRData;
-        use crate::proto::rr::dnssec::Verifier;
-
-        // 3.3.3 - Pseudocode for Permission Checking
-        //
-        //      if (security policy exists)
-        //           if (this update is not permitted)
-        //                if (local option)
-        //                     log a message about permission problem
-        //                if (local option)
-        //                     return (REFUSED)
+        use tracing::warn;
 
         // does this authority allow_updates?
         if !self.allow_update {
@@ -473,66 +348,7 @@ impl SqliteAuthority {
             return Err(ResponseCode::Refused);
         }
 
-        // verify sig0, currently the only authorization that is accepted.
-        let sig0s: &[Record] = update_message.sig0();
-        debug!("authorizing with: {:?}", sig0s);
-        if !sig0s.is_empty() {
-            let mut found_key = false;
-            for sig in sig0s.iter().filter_map(|sig0| {
-                sig0.data()
-                    .and_then(RData::as_dnssec)
-                    .and_then(DNSSECRData::as_sig)
-            }) {
-                let name = LowerName::from(sig.signer_name());
-                let keys = self
-                    .lookup(&name, RecordType::KEY, LookupOptions::default())
-                    .await;
-
-                let keys = match keys {
-                    Ok(keys) => keys,
-                    Err(_) => continue, // error trying to lookup a key by that name, try the next one.
-                };
-
-                debug!("found keys {:?}", keys);
-                // TODO: check key usage flags and restrictions
-                found_key = keys
-                    .iter()
-                    .filter_map(|rr_set| {
-                        rr_set
-                            .data()
-                            .and_then(RData::as_dnssec)
-                            .and_then(DNSSECRData::as_key)
-                    })
-                    .any(|key| {
-                        key.verify_message(update_message, sig.sig(), sig)
-                            .map(|_| {
-                                info!("verified sig: {:?} with key: {:?}", sig, key);
-                                true
-                            })
-                            .unwrap_or_else(|_| {
-                                debug!("did not verify sig: {:?} with key: {:?}", sig, key);
-                                false
-                            })
-                    });
-
-                if found_key {
-                    break; // stop searching for matching keys, we found one
-                }
-            }
-
-            if found_key {
-                return Ok(());
-            }
-        } else {
-            warn!(
-                "no sig0 matched registered records: id {}",
-                update_message.id()
-            );
-        }
-
-        // getting here, we will always default to rejecting the request
-        //  the code will only ever explicitly return authorized actions.
-        Err(ResponseCode::Refused)
+        self.in_memory.authorize_update(update_message).await
     }
 
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -561,73 +377,8 @@ impl SqliteAuthority {
     ///   MAILB, or any other QUERY metatype besides ANY, or any unrecognized
     ///   type, else signal FORMERR to the requestor.
     /// ```
-    #[allow(clippy::unused_unit)]
     pub async fn pre_scan(&self, records: &[Record]) -> UpdateResult<()> {
-        // 3.4.1.3 - Pseudocode For Update Section Prescan
-        //
-        //      [rr] for rr in updates
-        //           if (zone_of(rr.name) != ZNAME)
-        //                return (NOTZONE);
-        //           if (rr.class == zclass)
-        //                if (rr.type & ANY|AXFR|MAILA|MAILB)
-        //                     return (FORMERR)
-        //           elsif (rr.class == ANY)
-        //                if (rr.ttl != 0 || rr.rdlength != 0
-        //                    || rr.type & AXFR|MAILA|MAILB)
-        //                     return (FORMERR)
-        //           elsif (rr.class == NONE)
-        //                if (rr.ttl != 0 || rr.type & ANY|AXFR|MAILA|MAILB)
-        //                     return (FORMERR)
-        //           else
-        //                return (FORMERR)
-        for rr in records {
-            if !self.origin().zone_of(&rr.name().into()) {
-                return Err(ResponseCode::NotZone);
-            }
-
-            let class: DNSClass = rr.dns_class();
-            if class == self.in_memory.class() {
-                match rr.rr_type() {
-                    RecordType::ANY | RecordType::AXFR | RecordType::IXFR => {
-                        return Err(ResponseCode::FormErr);
-                    }
-                    _ => (),
-                }
-            } else {
-                match class {
-                    DNSClass::ANY => {
-                        if rr.ttl() != 0 {
-                            return Err(ResponseCode::FormErr);
-                        }
-                        if let None | Some(RData::NULL(..)) = rr.data() {
-                            ()
-                        } else {
-                            return Err(ResponseCode::FormErr);
-                        }
-                        match rr.rr_type() {
-                            RecordType::AXFR | RecordType::IXFR => {
-                                return Err(ResponseCode::FormErr);
-                            }
-                            _ => (),
-                        }
-                    }
-                    DNSClass::NONE => {
-                        if rr.ttl() != 0 {
-                            return Err(ResponseCode::FormErr);
-                        }
-                        match rr.rr_type() {
-                            RecordType::ANY | RecordType::AXFR | RecordType::IXFR => {
-                                return Err(ResponseCode::FormErr);
-                            }
-                            _ => (),
-                        }
-                    }
-                    _ => return Err(ResponseCode::FormErr),
-                }
-            }
-        }
-
-        Ok(())
+        self.in_memory.pre_scan(records).await
     }
 
     /// Updates the specified records according to the update section.
@@ -656,7 +407,6 @@ impl SqliteAuthority {
         records: &[Record],
         auto_signing_and_increment: bool,
     ) -> UpdateResult<bool> {
-        let mut updated = false;
         let serial: u32 = self.in_memory.serial().await;
 
         // the persistence act as a write-ahead log. The WAL will also be used for recovery of a zone
@@ -668,144 +418,8 @@ impl SqliteAuthority {
             }
         }
 
-        // 3.4.2.7 - Pseudocode For Update Section Processing
-        //
-        //      [rr] for rr in updates
-        //           if (rr.class == zclass)
-        //                if (rr.type == CNAME)
-        //                     if (zone_rrset<rr.name, ~CNAME>)
-        //                          next [rr]
-        //                elsif (zone_rrset<rr.name, CNAME>)
-        //                     next [rr]
-        //                if (rr.type == SOA)
-        //                     if (!zone_rrset<rr.name, SOA> ||
-        //                         zone_rr<rr.name, SOA>.serial > rr.soa.serial)
-        //                          next [rr]
-        //                for zrr in zone_rrset<rr.name, rr.type>
-        //                     if (rr.type == CNAME || rr.type == SOA ||
-        //                         (rr.type == WKS && rr.proto == zrr.proto &&
-        //                          rr.address == zrr.address) ||
-        //                         rr.rdata == zrr.rdata)
-        //                          zrr = rr
-        //                          next [rr]
-        //                zone_rrset<rr.name, rr.type> += rr
-        //           elsif (rr.class == ANY)
-        //                if (rr.type == ANY)
-        //                     if (rr.name == zname)
-        //                          zone_rrset<rr.name, ~(SOA|NS)> = Nil
-        //                     else
-        //                          zone_rrset<rr.name, *> = Nil
-        //                elsif (rr.name == zname &&
-        //                       (rr.type == SOA || rr.type == NS))
-        //                     next [rr]
-        //                else
-        //                     zone_rrset<rr.name, rr.type> = Nil
-        //           elsif (rr.class == NONE)
-        //                if (rr.type == SOA)
-        //                     next [rr]
-        //                if (rr.type == NS && zone_rrset<rr.name, NS> == rr)
-        //                     next [rr]
-        //                zone_rr<rr.name, rr.type, rr.data> = Nil
-        //      return (NOERROR)
-        for rr in records {
-            let rr_name = LowerName::from(rr.name());
-            let rr_key = RrKey::new(rr_name.clone(), rr.rr_type());
-
-            match rr.dns_class() {
-                class if class == self.in_memory.class() => {
-                    // RFC 2136 - 3.4.2.2. Any Update RR whose CLASS is the same as ZCLASS is added to
-                    //  the zone.  In case of duplicate RDATAs (which for SOA RRs is always
-                    //  the case, and for WKS RRs is the case if the ADDRESS and PROTOCOL
-                    //  fields both match), the Zone RR is replaced by Update RR.  If the
-                    //  TYPE is SOA and there is no Zone SOA RR, or the new SOA.SERIAL is
-                    //  lower (according to [RFC1982]) than or equal to the current Zone SOA
-                    //  RR's SOA.SERIAL, the Update RR is ignored.  In the case of a CNAME
-                    //  Update RR and a non-CNAME Zone RRset or vice versa, ignore the CNAME
-                    //  Update RR, otherwise replace the CNAME Zone RR with the CNAME Update
-                    //  RR.
-
-                    // zone     rrset    rr       Add to an RRset
-                    info!("upserting record: {:?}", rr);
-                    updated = self.in_memory.upsert(rr.clone(), serial).await || updated;
-                }
-                DNSClass::ANY => {
-                    // This is a delete of entire RRSETs, either many or one. In either case, the spec is clear:
-                    match rr.rr_type() {
-                        t @ RecordType::SOA | t @ RecordType::NS if rr_name == *self.origin() => {
-                            // SOA and NS records are not to be deleted if they are the origin records
-                            info!("skipping delete of {:?} see RFC 2136 - 3.4.2.3", t);
-                            continue;
-                        }
-                        RecordType::ANY => {
-                            // RFC 2136 - 3.4.2.3. For any Update RR whose CLASS is ANY and whose TYPE is ANY,
-                            //   all Zone RRs with the same NAME are deleted, unless the NAME is the
-                            //   same as ZNAME in which case only those RRs whose TYPE is other than
-                            //   SOA or NS are deleted.
-
-                            // ANY      ANY      empty    Delete all RRsets from a name
-                            info!(
-                                "deleting all records at name (not SOA or NS at origin): {:?}",
-                                rr_name
-                            );
-                            let origin = self.origin();
-                            let to_delete = self
-                                .in_memory
-                                .records()
-                                .await
-                                .keys()
-                                .filter(|k| {
-                                    !((k.record_type == RecordType::SOA
-                                        || k.record_type == RecordType::NS)
-                                        && k.name != *origin)
-                                })
-                                .filter(|k| k.name == rr_name)
-                                .cloned()
-                                .collect::<Vec<RrKey>>();
-
-                            for delete in to_delete {
-                                self.in_memory.records_mut().await.remove(&delete);
-                                updated = true;
-                            }
-                        }
-                        _ => {
-                            // RFC 2136 - 3.4.2.3. For any Update RR whose CLASS is ANY and
-                            //   whose TYPE is not ANY all Zone RRs with the same NAME and TYPE are
-                            //   deleted, unless the NAME is the same as ZNAME in which case neither
-                            //   SOA or NS RRs will be deleted.
-
-                            // ANY      rrset    empty    Delete an RRset
-                            if let None | Some(RData::NULL(..)) = rr.data() {
-                                let deleted = self.in_memory.records_mut().await.remove(&rr_key);
-                                info!("deleted rrset: {:?}", deleted);
-                                updated = updated || deleted.is_some();
-                            } else {
-                                info!("expected empty rdata: {:?}", rr);
-                                return Err(ResponseCode::FormErr);
-                            }
-                        }
-                    }
-                }
-                DNSClass::NONE => {
-                    info!("deleting specific record: {:?}", rr);
-                    // NONE     rrset    rr       Delete an RR from an RRset
-                    if let Some(rrset) = self.in_memory.records_mut().await.get_mut(&rr_key) {
-                        // b/c this is an Arc, we need to clone, then remove, and replace the node.
-                        let mut rrset_clone: RecordSet = RecordSet::clone(&*rrset);
-                        let deleted = rrset_clone.remove(rr, serial);
-                        info!("deleted ({}) specific record: {:?}", deleted, rr);
-                        updated = updated || deleted;
-
-                        if deleted {
-                            *rrset = Arc::new(rrset_clone);
-                        }
-                    }
-                }
-                class => {
-                    info!("unexpected DNS Class: {:?}", class);
-                    return Err(ResponseCode::FormErr);
-                }
-            }
-        }
+        let previous = self.in_memory.records().await;
+        let updated = self.in_memory.update_records(records).await?;
 
         // update the serial...
         if updated && auto_signing_and_increment {
@@ -826,6 +440,18 @@ impl SqliteAuthority {
                 //  dnssec, then we need to do it here...
                 self.in_memory.increment_soa_serial().await;
             }
+
+            if let Some(diff) = self.in_memory.zone_changed(&previous).await {
+                // the new SOA is journaled so that the serial is restored on recovery
+                if let Some(ref journal) = *self.journal.lock().await {
+                    if let Err(error) = journal
+                        .insert_record(diff.to_serial(), diff.to())
+                        .and_then(|_| journal.insert_diff(&diff))
+                    {
+                        error!("could not persist zone changes: {}", error);
+                    }
+                }
+            }
         }
 
         Ok(updated)
@@ -934,11 +560,43 @@ impl Authority for SqliteAuthority {
         Err(ResponseCode::NotImp)
     }
 
+    /// The TSIG key which signed the update, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn update_tsigner(&self, update: &MessageRequest) -> Option<TSigner> {
+        self.in_memory.update_tsigner(update).await
+    }
+
+    /// Checks that the client is permitted to transfer the zone
+    async fn authorize_transfer(&self, request: &MessageRequest, src: IpAddr) -> UpdateResult<()> {
+        self.in_memory.authorize_transfer(request, src).await
+    }
+
+    /// The TSIG key which signed the transfer request, for signing the response
+    #[cfg(feature = "dnssec")]
+    async fn transfer_tsigner(&self, request: &MessageRequest) -> Option<TSigner> {
+        self.in_memory.transfer_tsigner(request).await
+    }
+
+    /// The changes to the zone since the version with `serial`, for serving IXFR
+    async fn ixfr_diffs(&self, serial: u32) -> Option<Vec<ZoneDiff>> {
+        self.in_memory.ixfr_diffs(serial).await
+    }
+
+    /// Replaces the TXT records at the name with the values, for answering ACME challenges
+    async fn set_challenge_txt(&self, name: &LowerName, values: &[String]) -> UpdateResult<()> {
+        self.in_memory.set_challenge_txt(name, values).await
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     fn origin(&self) -> &LowerName {
         self.in_memory.origin()
     }
 
+    /// The scope of the answers for the client subnet, see `InMemoryAuthority`
+    fn client_subnet_scope(&self, client_subnet: &ClientSubnet) -> u8 {
+        self.in_memory.client_subnet_scope(client_subnet)
+    }
+
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
     ///
     /// # Arguments
@@ -994,6 +652,14 @@ impl DnssecAuthority for SqliteAuthority {
         self.in_memory.add_update_auth_key(name, key).await
     }
 
+    async fn add_update_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.in_memory.add_update_tsig_key(signer).await
+    }
+
+    async fn add_transfer_tsig_key(&self, signer: TSigner) -> DnsSecResult<()> {
+        self.in_memory.add_transfer_tsig_key(signer).await
+    }
+
     /// By adding a secure key, this will implicitly enable dnssec for the zone.
     ///
     /// # Arguments
@@ -1003,6 +669,44 @@ impl DnssecAuthority for SqliteAuthority {
         self.in_memory.add_zone_signing_key(signer).await
     }
 
+    async fn add_scheduled_signing_key(
+        &self,
+        signer: SigSigner,
+        role: KeyRole,
+        timings: KeyTimings,
+    ) -> DnsSecResult<()> {
+        self.in_memory
+            .add_scheduled_signing_key(signer, role, timings)
+            .await
+    }
+
+    /// Publishes the keys as scheduled and re-signs the zone, journaling the new serial
+    async fn maintain_keys(&self, now: u64) -> DnsSecResult<bool> {
+        let previous = self.in_memory.records().await;
+        let changed = self.in_memory.maintain_keys(now).await?;
+        if !changed {
+            return Ok(false);
+        }
+
+        if let Some(diff) = self.in_memory.zone_changed(&previous).await {
+            if let Some(ref journal) = *self.journal.lock().await {
+                if let Err(error) = journal
+                    .insert_record(diff.to_serial(), diff.to())
+                    .and_then(|_| journal.insert_diff(&diff))
+                {
+                    error!("could not persist zone changes: {}", error);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Signs the zone with NSEC3 instead of NSEC
+    async fn set_nsec3(&self, nsec3param: NSEC3PARAM, opt_out: bool) -> DnsSecResult<()> {
+        self.in_memory.set_nsec3(nsec3param, opt_out).await
+    }
+
     /// (Re)generates the nsec records, increments the serial number and signs the zone
     async fn secure_zone(&self) -> DnsSecResult<()> {
         self.in_memory.secure_zone().await
@@ -7,6 +7,12 @@
 
 use serde::Deserialize;
 
+#[cfg(feature = "trust-dns-resolver")]
+use crate::store::in_memory::AnameConfig;
+#[cfg(feature = "geoip")]
+use crate::store::in_memory::GeoConfig;
+use crate::store::in_memory::PoolConfig;
+
 /// Configuration for zone file for sqlite based zones
 #[derive(Deserialize, PartialEq, Eq, Debug)]
 pub struct SqliteConfig {
@@ -17,4 +23,17 @@ pub struct SqliteConfig {
     /// Are updates allowed to this zone
     #[serde(default)]
     pub allow_update: bool,
+    /// Resolution of ANAME (or ALIAS) targets outside of the zone, into A and AAAA answers
+    #[cfg(feature = "trust-dns-resolver")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolver")))]
+    #[serde(default)]
+    pub aname: Option<AnameConfig>,
+    /// Answers which depend on the location of the client
+    #[cfg(feature = "geoip")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "geoip")))]
+    #[serde(default)]
+    pub geo: Option<GeoConfig>,
+    /// Pools of addresses answered by weight and health, in place of the records of the zone
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
 }
@@ -16,12 +16,13 @@ use rusqlite::{self, Connection};
 use time;
 use tracing::error;
 
+use crate::authority::ZoneDiff;
 use crate::error::{PersistenceErrorKind, PersistenceResult};
 use crate::proto::rr::Record;
 use crate::proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
 
 /// The current Journal version of the application
-pub const CURRENT_VERSION: i64 = 1;
+pub const CURRENT_VERSION: i64 = 2;
 
 /// The Journal is the audit log of all changes to a zone after initial creation.
 pub struct Journal {
@@ -123,6 +124,82 @@ impl Journal {
         Ok(())
     }
 
+    /// Inserts the changes of an update to the zone, these are used to serve IXFR
+    ///
+    /// # Argument
+    ///
+    /// * `diff` - the changes between two versions of the zone
+    pub fn insert_diff(&self, diff: &ZoneDiff) -> PersistenceResult<()> {
+        assert!(
+            self.version == CURRENT_VERSION,
+            "schema version mismatch, schema_up() resolves this"
+        );
+
+        let diff_bytes = diff.to_bytes()?;
+        let timestamp = time::OffsetDateTime::now_utc();
+        let from_serial = i64::from(diff.from_serial());
+        let to_serial = i64::from(diff.to_serial());
+
+        let count = self.conn.lock().expect("conn poisoned").execute(
+            "INSERT INTO zone_diffs (from_serial, to_serial, timestamp, diff)
+                VALUES ($1, $2, $3, $4)",
+            &[
+                &from_serial as &dyn ToSql,
+                &to_serial,
+                &timestamp,
+                &diff_bytes,
+            ],
+        )?;
+
+        if count != 1 {
+            return Err(PersistenceErrorKind::WrongInsertCount {
+                got: count,
+                expect: 1,
+            }
+            .into());
+        };
+
+        Ok(())
+    }
+
+    /// Selects the most recent changes to the zone, oldest first
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - the maximum number of changes to return
+    pub fn select_diffs(&self, limit: usize) -> PersistenceResult<Vec<ZoneDiff>> {
+        assert!(
+            self.version == CURRENT_VERSION,
+            "schema version mismatch, schema_up() resolves this"
+        );
+
+        let conn = self.conn.lock().expect("conn poisoned");
+        let mut stmt = conn.prepare(
+            "SELECT diff FROM zone_diffs
+                ORDER BY _rowid_ DESC
+                LIMIT $1",
+        )?;
+
+        let mut diffs = stmt
+            .query_and_then(
+                &[&(limit as i64)],
+                |row| -> Result<ZoneDiff, rusqlite::Error> {
+                    let diff_bytes: Vec<u8> = row.get(0)?;
+
+                    ZoneDiff::from_bytes(&diff_bytes).map_err(|decode_error| {
+                        rusqlite::Error::InvalidParameterName(format!(
+                            "could not decode: {}",
+                            decode_error
+                        ))
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        diffs.reverse();
+        Ok(diffs)
+    }
+
     /// Selects a record from the given row_id.
     ///
     /// This allows for the entire set of records to be iterated through, by starting at 0, and
@@ -239,6 +316,7 @@ impl Journal {
             match self.version + 1 {
                 0 => self.version = self.init_up()?,
                 1 => self.version = self.records_up()?,
+                2 => self.version = self.diffs_up()?,
                 _ => panic!("incorrect version somewhere"), // valid panic, non-recoverable state
             }
 
@@ -295,6 +373,21 @@ impl Journal {
 
         Ok(1)
     }
+
+    /// adds the zone_diffs table, the changes of each update to the zone for serving IXFR
+    fn diffs_up(&self) -> PersistenceResult<i64> {
+        self.conn.lock().expect("conn poisoned").execute(
+            "CREATE TABLE zone_diffs (
+                from_serial    INTEGER NOT NULL,
+                to_serial      INTEGER NOT NULL,
+                timestamp      TEXT NOT NULL,
+                diff           BLOB NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(2)
+    }
 }
 
 /// Returns an iterator over all items in a Journal
@@ -6,9 +6,10 @@ use std::str::FromStr;
 use futures_executor::block_on;
 
 use trust_dns_client::op::{Header, Query};
-use trust_dns_client::rr::dnssec::{Algorithm, SupportedAlgorithms, Verifier};
+use trust_dns_client::rr::dnssec::{Algorithm, Nsec3HashAlgorithm, SupportedAlgorithms, Verifier};
 use trust_dns_client::rr::{DNSClass, Name, RData, Record, RecordType};
-use trust_dns_proto::rr::dnssec::rdata::DNSKEY;
+use trust_dns_proto::rr::dnssec::nsec3_proof::{Nsec3Chain, Nsec3Proof};
+use trust_dns_proto::rr::dnssec::rdata::{DNSKEY, NSEC3PARAM};
 use trust_dns_proto::xfer;
 use trust_dns_server::authority::{AuthLookup, Authority, DnssecAuthority, LookupOptions};
 use trust_dns_server::server::{Protocol, RequestInfo};
@@ -303,6 +304,54 @@ pub fn test_nsec_nxdomain_wraps_end<A: Authority<Lookup = AuthLookup>>(
     ));
 }
 
+pub fn test_nsec3_nxdomain<A: DnssecAuthority<Lookup = AuthLookup>>(authority: A, keys: &[DNSKEY]) {
+    let nsec3param = NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, false, 1, vec![0xaa, 0xbb]);
+    block_on(authority.set_nsec3(nsec3param.clone(), false)).expect("failed to set NSEC3");
+    block_on(authority.secure_zone()).expect("failed to sign zone");
+
+    // the NSEC3PARAM is published and signed at the apex
+    let origin = authority.origin().clone();
+    let lookup = block_on(authority.lookup(
+        &origin,
+        RecordType::NSEC3PARAM,
+        LookupOptions::for_dnssec(true, SupportedAlgorithms::new()),
+    ))
+    .unwrap();
+
+    let (nsec3param_records, other_records): (Vec<_>, Vec<_>) = lookup
+        .into_iter()
+        .cloned()
+        .partition(|r| r.record_type() == RecordType::NSEC3PARAM);
+
+    assert_eq!(nsec3param_records.len(), 1);
+    assert_eq!(
+        nsec3param_records[0]
+            .data()
+            .and_then(RData::as_dnssec)
+            .and_then(|rdata| rdata.as_nsec3param()),
+        Some(&nsec3param)
+    );
+    verify(&nsec3param_records, &other_records, keys);
+
+    // the name is proven not to exist by the NSEC3 records, which replace the NSEC records
+    let name = Name::from_str("aaa.example.com.").unwrap();
+    let lookup = block_on(authority.get_nsec_records(
+        &name.clone().into(),
+        LookupOptions::for_dnssec(true, SupportedAlgorithms::all()),
+    ))
+    .unwrap();
+
+    let records = lookup.into_iter().cloned().collect::<Vec<_>>();
+    assert!(records.iter().any(|r| r.record_type() == RecordType::NSEC3));
+    assert!(records.iter().all(|r| r.record_type() != RecordType::NSEC));
+
+    let chain = Nsec3Chain::new(&Name::from_str("example.com.").unwrap(), &records).unwrap();
+    assert_eq!(
+        chain.verify(&name, RecordType::A).unwrap(),
+        Nsec3Proof::NameError
+    );
+}
+
 pub fn test_rfc_6975_supported_algorithms<A: Authority<Lookup = AuthLookup>>(
     authority: A,
     keys: &[DNSKEY],
@@ -385,6 +434,7 @@ pub fn add_signers<A: DnssecAuthority>(authority: &mut A) -> Vec<DNSKEY> {
             signer_name: Some(signer_name.to_string()),
             is_zone_signing_key: Some(true),
             is_zone_update_auth: Some(false),
+            schedule: None,
         };
 
         let signer = key_config
@@ -405,6 +455,7 @@ pub fn add_signers<A: DnssecAuthority>(authority: &mut A) -> Vec<DNSKEY> {
     //         signer_name: Some(signer_name.clone().to_string()),
     //         is_zone_signing_key: Some(true),
     //         is_zone_update_auth: Some(false),
+    //         schedule: None,
     //     };
 
     //     let signer = key_config.try_into_signer(signer_name.clone()).expect("failed to read key_config");
@@ -422,6 +473,7 @@ pub fn add_signers<A: DnssecAuthority>(authority: &mut A) -> Vec<DNSKEY> {
     //         signer_name: Some(signer_name.clone().to_string()),
     //         is_zone_signing_key: Some(true),
     //         is_zone_update_auth: Some(false),
+    //         schedule: None,
     //     };
 
     //     let signer = key_config.try_into_signer(signer_name.clone()).expect("failed to read key_config");
@@ -440,6 +492,7 @@ pub fn add_signers<A: DnssecAuthority>(authority: &mut A) -> Vec<DNSKEY> {
             signer_name: Some(signer_name.to_string()),
             is_zone_signing_key: Some(true),
             is_zone_update_auth: Some(false),
+            schedule: None,
         };
 
         let signer = key_config
@@ -481,6 +534,7 @@ macro_rules! dnssec_battery {
                     test_nsec_nxdomain_start,
                     test_nsec_nxdomain_middle,
                     test_nsec_nxdomain_wraps_end,
+                    test_nsec3_nxdomain,
                     test_rfc_6975_supported_algorithms,
                 );
             }
@@ -4,6 +4,7 @@ use std::{
     future::Future,
     net::{Ipv4Addr, Ipv6Addr},
     str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use futures_executor::block_on;
@@ -29,7 +30,11 @@ fn update_authority<A: Authority<Lookup = AuthLookup>>(
     key: &SigSigner,
     authority: &mut A,
 ) -> UpdateResult<bool> {
-    message.finalize(key, 1).expect("failed to sign message");
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    message.finalize(key, now).expect("failed to sign message");
     let message = message.to_bytes().unwrap();
     let request = MessageRequest::from_bytes(&message).unwrap();
 
@@ -797,6 +802,7 @@ pub fn add_auth<A: DnssecAuthority>(authority: &mut A) -> Vec<SigSigner> {
             signer_name: Some(update_name.to_string()),
             is_zone_signing_key: Some(true),
             is_zone_update_auth: Some(false),
+            schedule: None,
         };
 
         let signer = key_config
@@ -822,6 +828,7 @@ pub fn add_auth<A: DnssecAuthority>(authority: &mut A) -> Vec<SigSigner> {
     //         signer_name: Some(signer_name.clone().to_string()),
     //         is_zone_signing_key: Some(true),
     //         is_zone_update_auth: Some(false),
+    //         schedule: None,
     //     };
 
     //     let signer = key_config.try_into_signer(signer_name.clone()).expect("failed to read key_config");
@@ -839,6 +846,7 @@ pub fn add_auth<A: DnssecAuthority>(authority: &mut A) -> Vec<SigSigner> {
     //         signer_name: Some(signer_name.clone().to_string()),
     //         is_zone_signing_key: Some(true),
     //         is_zone_update_auth: Some(false),
+    //         schedule: None,
     //     };
 
     //     let signer = key_config.try_into_signer(signer_name.clone()).expect("failed to read key_config");
@@ -857,6 +865,7 @@ pub fn add_auth<A: DnssecAuthority>(authority: &mut A) -> Vec<SigSigner> {
             signer_name: Some(update_name.to_string()),
             is_zone_signing_key: Some(true),
             is_zone_update_auth: Some(false),
+            schedule: None,
         };
 
         let signer = key_config
@@ -0,0 +1,146 @@
+#![cfg(feature = "blocklist")]
+
+use std::env;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use tokio::runtime::Runtime;
+
+use trust_dns_client::rr::rdata::opt::ExtendedErrorCode;
+use trust_dns_client::rr::{LowerName, RData, RecordType};
+use trust_dns_server::{
+    authority::{Authority, LookupError, LookupObject, ZoneType},
+    config::Config,
+    store::{
+        blocklist::{BlocklistAuthority, BlocklistConfig},
+        StoreConfig,
+    },
+};
+
+fn zone_dir() -> PathBuf {
+    let workspace = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    PathBuf::from(workspace).join("tests/test-data/named_test_configs")
+}
+
+fn example_config() -> BlocklistConfig {
+    let config = Config::read_config(&zone_dir().join("example_blocklist.toml")).unwrap();
+    match config.get_zones()[0].stores {
+        Some(StoreConfig::Blocklist(ref config)) => config.clone(),
+        ref stores => panic!("not a blocklist: {:?}", stores),
+    }
+}
+
+fn name(name: &str) -> LowerName {
+    LowerName::from_str(name).unwrap()
+}
+
+#[test]
+fn test_blocked_names() {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let config = example_config();
+    let authority = BlocklistAuthority::try_from_config(
+        ".".parse().unwrap(),
+        ZoneType::Forward,
+        &config,
+        Some(&zone_dir()),
+    )
+    .unwrap();
+
+    // nothing is blocked until the lists are read
+    assert!(!authority.is_blocked(&name("ads.example.com.")));
+    runtime.block_on(authority.reload()).unwrap();
+
+    assert!(authority.is_blocked(&name("ads.example.com.")));
+    assert!(authority.is_blocked(&name("tracker.example.com.")));
+    assert!(!authority.is_blocked(&name("www.ads.example.com.")));
+    assert!(!authority.is_blocked(&name("localhost.")));
+
+    // wildcards, and the names of the configuration
+    assert!(authority.is_blocked(&name("a.ads.example.net.")));
+    assert!(!authority.is_blocked(&name("ads.example.net.")));
+    assert!(!authority.is_blocked(&name("www.ads.example.net.")));
+    assert!(authority.is_blocked(&name("metrics.example.org.")));
+    assert!(authority.is_blocked(&name("eu.metrics.example.org.")));
+    assert!(authority.is_blocked(&name("doubleclick.example.")));
+
+    // regular expressions, and the allowlist
+    assert!(authority.is_blocked(&name("ad2.example.com.")));
+    assert!(!authority.is_blocked(&name("ad1.example.com.")));
+
+    let error = runtime
+        .block_on(authority.lookup(&name("ads.example.com."), RecordType::A, Default::default()))
+        .err()
+        .expect("blocked name was answered");
+    match error {
+        LookupError::ExtendedError { info_code, .. } => {
+            assert_eq!(info_code, ExtendedErrorCode::Blocked)
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn test_sinkhole() {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let config: BlocklistConfig = toml::from_str(
+        r#"
+lists = []
+block = ["ads.example.com"]
+action = "sinkhole"
+sinkhole_ipv4 = "192.0.2.1"
+ttl = 300
+"#,
+    )
+    .unwrap();
+    let authority =
+        BlocklistAuthority::try_from_config(".".parse().unwrap(), ZoneType::Forward, &config, None)
+            .unwrap();
+    runtime.block_on(authority.reload()).unwrap();
+
+    let lookup = |name, record_type| {
+        runtime
+            .block_on(authority.lookup(name, record_type, Default::default()))
+            .map(|lookup| {
+                lookup
+                    .iter()
+                    .map(|r| (r.ttl(), r.data().cloned()))
+                    .collect::<Vec<_>>()
+            })
+    };
+
+    let blocked = name("ads.example.com.");
+    assert_eq!(
+        lookup(&blocked, RecordType::A).unwrap(),
+        vec![(300, Some(RData::A(Ipv4Addr::new(192, 0, 2, 1))))]
+    );
+    assert_eq!(
+        lookup(&blocked, RecordType::AAAA).unwrap(),
+        vec![(300, Some(RData::AAAA(Ipv6Addr::UNSPECIFIED)))]
+    );
+    assert!(lookup(&blocked, RecordType::MX).unwrap().is_empty());
+
+    // the names which are not blocked are refused without upstream resolvers
+    assert!(lookup(&name("www.example.com."), RecordType::A)
+        .unwrap_err()
+        .is_refused());
+}
+
+#[test]
+fn test_invalid_entries() {
+    let config: BlocklistConfig = toml::from_str(
+        r#"
+lists = []
+block = ["/[/"]
+"#,
+    )
+    .unwrap();
+
+    assert!(BlocklistAuthority::try_from_config(
+        ".".parse().unwrap(),
+        ZoneType::Forward,
+        &config,
+        None
+    )
+    .is_err());
+}
@@ -18,8 +18,12 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use ipnet::IpNet;
+
 use trust_dns_server::authority::ZoneType;
 use trust_dns_server::config::*;
+use trust_dns_server::dnstap::DnstapMessageType;
+use trust_dns_server::server::QueryLogFormat;
 
 #[test]
 fn test_read_config() {
@@ -139,6 +143,14 @@ fn test_parse_toml() {
 
     let config: Config = "tcp_request_timeout = 25".parse().unwrap();
     assert_eq!(config.get_tcp_request_timeout(), Duration::from_secs(25));
+    assert_eq!(config.get_quic_idle_timeout(), Duration::from_secs(25));
+    assert_eq!(config.get_quic_max_connections(), None);
+
+    let config: Config = "quic_idle_timeout = 60\nquic_max_connections = 1000"
+        .parse()
+        .unwrap();
+    assert_eq!(config.get_quic_idle_timeout(), Duration::from_secs(60));
+    assert_eq!(config.get_quic_max_connections(), Some(1000));
 
     let config: Config = "log_level = \"Debug\"".parse().unwrap();
     assert_eq!(config.get_log_level(), tracing::Level::DEBUG);
@@ -214,6 +226,118 @@ signer_name = \"ns.example.com.\"
     assert!(!config.get_zones()[0].get_keys()[1].is_zone_update_auth(),);
 }
 
+#[cfg(feature = "dnssec")]
+#[test]
+fn test_parse_zone_key_schedule() {
+    use trust_dns_server::authority::{KeyRole, KeyTimings};
+
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+
+[[zones.keys]]
+key_path = \"/path/to/ksk.pem\"
+algorithm = \"ECDSAP256SHA256\"
+is_zone_signing_key = true
+schedule = { role = \"ksk\", publish = 1000, inactive = 3000, delete = 4000 }
+
+[[zones.keys]]
+key_path = \"/path/to/zsk.pem\"
+algorithm = \"ECDSAP256SHA256\"
+is_zone_signing_key = true
+schedule = { role = \"zsk\" }
+
+[[zones.keys]]
+key_path = \"/path/to/legacy.pem\"
+algorithm = \"ECDSAP256SHA256\"
+is_zone_signing_key = true
+"
+    .parse()
+    .unwrap();
+
+    let keys = config.get_zones()[0].get_keys();
+    let schedule = keys[0].schedule().unwrap();
+    assert_eq!(schedule.role().unwrap(), KeyRole::KeySigning);
+    assert_eq!(
+        schedule.timings(),
+        KeyTimings::new(Some(1000), None, Some(3000), Some(4000))
+    );
+
+    let schedule = keys[1].schedule().unwrap();
+    assert_eq!(schedule.role().unwrap(), KeyRole::ZoneSigning);
+    assert_eq!(schedule.timings(), KeyTimings::default());
+
+    assert!(keys[2].schedule().is_none());
+}
+
+#[cfg(feature = "dnssec")]
+#[test]
+fn test_parse_zone_nsec3() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+nsec3 = { salt = \"AABBccdd\", iterations = 1, opt_out = true }
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Primary\"
+file = \"example.net.zone\"
+nsec3 = {}
+
+[[zones]]
+zone = \"example.org\"
+zone_type = \"Primary\"
+file = \"example.org.zone\"
+"
+    .parse()
+    .unwrap();
+
+    let nsec3 = config.get_zones()[0].get_nsec3().unwrap();
+    assert!(nsec3.opt_out);
+    let nsec3param = nsec3.to_nsec3param().unwrap();
+    assert_eq!(nsec3param.salt(), &[0xaa, 0xbb, 0xcc, 0xdd]);
+    assert_eq!(nsec3param.iterations(), 1);
+    assert!(!nsec3param.opt_out());
+
+    let nsec3 = config.get_zones()[1].get_nsec3().unwrap();
+    assert!(!nsec3.opt_out);
+    let nsec3param = nsec3.to_nsec3param().unwrap();
+    assert!(nsec3param.salt().is_empty());
+    assert_eq!(nsec3param.iterations(), 0);
+
+    assert!(config.get_zones()[2].get_nsec3().is_none());
+}
+
+#[test]
+fn test_parse_zone_transfers() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+allow_axfr = true
+allow_transfer = [\"192.0.2.0/24\", \"2001:db8::/32\"]
+also_notify = [\"192.0.2.2:53\"]
+"
+    .parse()
+    .unwrap();
+
+    let zone = &config.get_zones()[0];
+    assert!(zone.is_axfr_allowed());
+    assert_eq!(
+        zone.get_allow_transfer(),
+        &[
+            "192.0.2.0/24".parse().unwrap(),
+            "2001:db8::/32".parse().unwrap()
+        ]
+    );
+    assert_eq!(zone.get_also_notify(), &["192.0.2.2:53".parse().unwrap()]);
+}
+
 #[test]
 #[cfg(feature = "dnssec")]
 fn test_parse_tls() {
@@ -237,6 +361,37 @@ tls_listen_port = 8853
     );
 }
 
+#[test]
+#[cfg(feature = "dnssec")]
+fn test_parse_tls_acme() {
+    let config: Config = "
+[tls_cert]
+path = \"cert.pem\"
+endpoint_name = \"ns.example.com\"
+private_key = \"key.pem\"
+
+[tls_cert.acme]
+contact = [\"mailto:hostmaster@example.com\"]
+names = [\"dns.example.com\"]
+account_key = \"acme_account.pk8\"
+  "
+    .parse()
+    .unwrap();
+
+    let tls_cert = config.get_tls_cert().unwrap();
+    assert_eq!(tls_cert.get_private_key(), Some(Path::new("key.pem")));
+
+    let acme = tls_cert.get_acme().unwrap();
+    assert_eq!(acme.directory(), dnssec::AcmeConfig::LETS_ENCRYPT);
+    assert_eq!(
+        acme.contact,
+        vec!["mailto:hostmaster@example.com".to_string()]
+    );
+    assert_eq!(acme.names, vec!["dns.example.com".to_string()]);
+    assert_eq!(acme.account_key, "acme_account.pk8");
+    assert_eq!(acme.renew_before(), Duration::from_secs(30 * 24 * 60 * 60));
+}
+
 fn test_config(path: &str) {
     let workspace = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
     let path = PathBuf::from(workspace)
@@ -272,3 +427,647 @@ define_test_config!(openssl_dnssec);
 define_test_config!(ring_dnssec);
 #[cfg(feature = "trust-dns-resolver")]
 define_test_config!(example_forwarder);
+#[cfg(feature = "trust-dns-recursor")]
+define_test_config!(example_recursor);
+#[cfg(feature = "blocklist")]
+define_test_config!(example_blocklist);
+
+#[test]
+fn test_parse_response_policy() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+
+[[zones]]
+zone = \"rpz\"
+zone_type = \"Primary\"
+file = \"rpz.zone\"
+response_policy = true
+"
+    .parse()
+    .unwrap();
+
+    assert!(!config.get_zones()[0].is_response_policy());
+    assert!(config.get_zones()[1].is_response_policy());
+}
+
+#[cfg(feature = "sql")]
+#[test]
+fn test_parse_sql_store() {
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+stores = { type = \"sql\", url = \"postgres://localhost/trust_dns\", zone_file_path = \"example.com.zone\", allow_update = true, refresh_interval = 0 }
+"
+    .parse()
+    .unwrap();
+
+    match config.get_zones()[0].stores {
+        Some(StoreConfig::Sql(ref sql)) => {
+            assert_eq!(sql.url, "postgres://localhost/trust_dns");
+            assert_eq!(sql.zone_file_path.as_deref(), Some("example.com.zone"));
+            assert!(sql.allow_update);
+            assert_eq!(sql.get_refresh_interval(), None);
+            assert_eq!(sql.get_max_connections(), 5);
+        }
+        ref stores => panic!("expected sql store: {:?}", stores),
+    }
+}
+
+#[cfg(feature = "trust-dns-recursor")]
+#[test]
+fn test_parse_recursor_cache() {
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \".\"
+zone_type = \"Hint\"
+stores = { type = \"recursor\", roots = \"root.zone\" }
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Hint\"
+stores = { type = \"recursor\", roots = \"root.zone\", cache_size = 4096, ns_cache_size = 512, max_chain_length = 16, positive_min_ttl = 30, negative_max_ttl = 300 }
+"
+    .parse()
+    .unwrap();
+
+    match config.get_zones()[0].stores {
+        Some(StoreConfig::Recursor(ref recursor)) => {
+            assert_eq!(recursor.cache_size, 1024);
+            assert_eq!(recursor.ns_cache_size, 100);
+            assert_eq!(recursor.max_chain_length, 8);
+            assert_eq!(recursor.positive_min_ttl, None);
+            assert_eq!(recursor.negative_max_ttl, None);
+        }
+        ref stores => panic!("expected recursor store: {:?}", stores),
+    }
+
+    match config.get_zones()[1].stores {
+        Some(StoreConfig::Recursor(ref recursor)) => {
+            assert_eq!(recursor.cache_size, 4096);
+            assert_eq!(recursor.ns_cache_size, 512);
+            assert_eq!(recursor.max_chain_length, 16);
+            assert_eq!(recursor.positive_min_ttl, Some(30));
+            assert_eq!(recursor.positive_max_ttl, None);
+            assert_eq!(recursor.negative_min_ttl, None);
+            assert_eq!(recursor.negative_max_ttl, Some(300));
+        }
+        ref stores => panic!("expected recursor store: {:?}", stores),
+    }
+}
+
+#[cfg(feature = "trust-dns-recursor")]
+#[test]
+fn test_parse_recursor_limits() {
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \".\"
+zone_type = \"Hint\"
+stores = { type = \"recursor\", roots = \"root.zone\", max_queries = 32, max_ns_fanout = 1, timeout = 5 }
+"
+    .parse()
+    .unwrap();
+
+    match config.get_zones()[0].stores {
+        Some(StoreConfig::Recursor(ref recursor)) => {
+            assert_eq!(recursor.max_queries, Some(32));
+            assert_eq!(recursor.max_depth, None);
+            assert_eq!(recursor.max_ns_fanout, Some(1));
+            assert_eq!(recursor.timeout, Some(5));
+        }
+        ref stores => panic!("expected recursor store: {:?}", stores),
+    }
+}
+
+#[cfg(feature = "trust-dns-recursor")]
+#[test]
+fn test_parse_recursor_validation() {
+    use std::path::Path;
+
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \".\"
+zone_type = \"Hint\"
+stores = { type = \"recursor\", roots = \"root.zone\" }
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Hint\"
+stores = { type = \"recursor\", roots = \"root.zone\", validate = true, trust_anchor = \"root.key\", aggressive_nsec = false }
+"
+    .parse()
+    .unwrap();
+
+    match config.get_zones()[0].stores {
+        Some(StoreConfig::Recursor(ref recursor)) => {
+            assert!(!recursor.validate);
+            assert_eq!(recursor.trust_anchor, None);
+            assert!(recursor.aggressive_nsec);
+        }
+        ref stores => panic!("expected recursor store: {:?}", stores),
+    }
+
+    match config.get_zones()[1].stores {
+        Some(StoreConfig::Recursor(ref recursor)) => {
+            assert!(recursor.validate);
+            assert!(!recursor.aggressive_nsec);
+            assert_eq!(
+                recursor.trust_anchor.as_deref(),
+                Some(Path::new("root.key"))
+            );
+        }
+        ref stores => panic!("expected recursor store: {:?}", stores),
+    }
+}
+
+#[cfg(feature = "trust-dns-resolver")]
+#[test]
+fn test_parse_forward_rules() {
+    use std::str::FromStr;
+
+    use trust_dns_client::rr::Name;
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \".\"
+zone_type = \"Forward\"
+
+[zones.stores]
+type = \"forward\"
+name_servers = [{ socket_addr = \"192.0.2.1:53\", protocol = \"tcp\" }]
+
+[[zones.stores.rules]]
+domain = \"corp.example.com\"
+name_servers = [{ socket_addr = \"10.0.0.53:53\", protocol = \"udp\" }]
+tsig_key = { key_name = \"corp\", algorithm = \"hmac-sha256\", key_path = \"corp.key\" }
+fallback = true
+
+[[zones.stores.rules]]
+domain = \"lab.example.com\"
+name_servers = [{ socket_addr = \"10.1.0.53:53\", protocol = \"tcp\" }]
+"
+    .parse()
+    .unwrap();
+
+    let forward = match config.get_zones()[0].stores {
+        Some(StoreConfig::Forward(ref forward)) => forward,
+        ref stores => panic!("expected forward store: {:?}", stores),
+    };
+
+    assert_eq!(forward.rules.len(), 2);
+    let corp = &forward.rules[0];
+    assert_eq!(
+        corp.get_domain().unwrap(),
+        Name::from_str("corp.example.com.").unwrap()
+    );
+    assert_eq!(
+        corp.name_servers[0].socket_addr,
+        "10.0.0.53:53".parse().unwrap()
+    );
+    assert_eq!(corp.tsig_key.as_ref().unwrap().key_name, "corp");
+    assert!(corp.is_fallback());
+
+    let lab = &forward.rules[1];
+    assert!(lab.tsig_key.is_none());
+    assert!(!lab.is_fallback());
+}
+
+#[cfg(feature = "trust-dns-resolver")]
+#[test]
+fn test_parse_aname() {
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Primary\"
+
+[zones.stores]
+type = \"file\"
+zone_file_path = \"example.net.zone\"
+aname = { name_servers = [{ socket_addr = \"192.0.2.1:53\", protocol = \"udp\" }] }
+"
+    .parse()
+    .unwrap();
+
+    let file = match config.get_zones()[0].stores {
+        Some(StoreConfig::File(ref file)) => file,
+        ref stores => panic!("expected file store: {:?}", stores),
+    };
+
+    let aname = file.aname.as_ref().expect("no aname config");
+    assert_eq!(
+        aname.name_servers[0].socket_addr,
+        "192.0.2.1:53".parse().unwrap()
+    );
+    assert!(aname.options.is_none());
+}
+
+#[cfg(feature = "geoip")]
+#[test]
+fn test_parse_geo() {
+    use trust_dns_server::store::StoreConfig;
+
+    let config: Config = "
+[[zones]]
+zone = \"example.org\"
+zone_type = \"Primary\"
+
+[zones.stores]
+type = \"file\"
+zone_file_path = \"example.org.zone\"
+geo = { database = \"GeoLite2-Country.mmdb\", records = [{ name = \"www\", location = \"EU/DE\", record_type = \"A\", data = \"192.0.2.10\" }] }
+"
+    .parse()
+    .unwrap();
+
+    let file = match config.get_zones()[0].stores {
+        Some(StoreConfig::File(ref file)) => file,
+        ref stores => panic!("expected file store: {:?}", stores),
+    };
+
+    let geo = file.geo.as_ref().expect("no geo config");
+    assert_eq!(geo.database, "GeoLite2-Country.mmdb");
+    assert_eq!(geo.records[0].location, "EU/DE");
+    assert_eq!(geo.records[0].ttl, None);
+}
+
+#[test]
+fn test_parse_catalog_zone() {
+    use std::str::FromStr;
+
+    use trust_dns_client::rr::Name;
+    use trust_dns_server::store::{secondary::CatalogMember, StoreConfig};
+
+    let config: Config = "
+[[zones]]
+zone = \"catz.example\"
+zone_type = \"Secondary\"
+
+[zones.stores]
+type = \"secondary\"
+primaries = [\"192.0.2.1:53\"]
+catalog = { zone_file_dir = \"members\", groups = [{ name = \"external\", primaries = [\"192.0.2.2:53\"], allow_axfr = true }] }
+"
+    .parse()
+    .unwrap();
+
+    let secondary = match config.get_zones()[0].stores {
+        Some(StoreConfig::Secondary(ref secondary)) => secondary,
+        ref stores => panic!("expected secondary store: {:?}", stores),
+    };
+    let catalog = secondary.catalog.as_ref().expect("no catalog config");
+
+    let mut member = CatalogMember {
+        unique_id: "id1".to_string(),
+        zone: Name::from_str("Example.com.").unwrap(),
+        groups: vec![],
+        change_of_ownership: None,
+    };
+    let member_config = catalog.member_config(secondary, &member);
+    assert_eq!(member_config.primaries, ["192.0.2.1:53".parse().unwrap()]);
+    assert_eq!(
+        member_config.zone_file_path.as_deref(),
+        Some("members/example.com.zone")
+    );
+    assert!(member_config.catalog.is_none());
+    assert!(!catalog.is_member_axfr_allowed(&member));
+
+    member.groups = vec!["internal".to_string(), "external".to_string()];
+    let member_config = catalog.member_config(secondary, &member);
+    assert_eq!(member_config.primaries, ["192.0.2.2:53".parse().unwrap()]);
+    assert!(catalog.is_member_axfr_allowed(&member));
+}
+
+#[test]
+fn test_parse_control_socket() {
+    let config: Config = "control_socket = \"/var/run/trust-dns/control.sock\""
+        .parse()
+        .unwrap();
+    assert_eq!(
+        config.get_control_socket(),
+        Some(Path::new("/var/run/trust-dns/control.sock"))
+    );
+
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_control_socket(), None);
+
+    // there is no TCP control channel
+    assert!("control_socket = \"127.0.0.1:953\""
+        .parse::<Config>()
+        .is_err());
+}
+
+#[test]
+fn test_parse_metrics_listen_addr() {
+    let config: Config = "metrics_listen_addr = \"127.0.0.1:9153\"".parse().unwrap();
+    assert_eq!(
+        config.get_metrics_listen_addr(),
+        Some(([127, 0, 0, 1], 9153).into())
+    );
+
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_metrics_listen_addr(), None);
+}
+
+#[test]
+fn test_parse_dnstap() {
+    let config: Config = "
+[dnstap]
+socket = \"/var/run/trust-dns/dnstap.sock\"
+identity = \"ns1.example.com\"
+message_types = [\"client_query\", \"client_response\"]
+"
+    .parse()
+    .unwrap();
+
+    let dnstap = config.get_dnstap().expect("dnstap not parsed");
+    assert_eq!(
+        dnstap.socket.as_deref(),
+        Some(Path::new("/var/run/trust-dns/dnstap.sock"))
+    );
+    assert_eq!(dnstap.identity.as_deref(), Some("ns1.example.com"));
+    assert_eq!(
+        dnstap.message_types,
+        vec![
+            DnstapMessageType::ClientQuery,
+            DnstapMessageType::ClientResponse
+        ]
+    );
+
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_dnstap(), None);
+}
+
+#[test]
+fn test_parse_acl() {
+    let config: Config = "
+acl = { query = { networks = [\"192.0.2.0/24\", \"2001:db8::/32\"] } }
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+acl = { update = { keys = [{ key_name = \"update-key\", algorithm = \"hmac-sha256\", key_path = \"update.key\" }] }, transfer = {} }
+"
+    .parse()
+    .unwrap();
+
+    let acl = config.get_acl().expect("acl not parsed");
+    assert_eq!(
+        acl.query.as_ref().unwrap().networks,
+        vec![
+            "192.0.2.0/24".parse::<IpNet>().unwrap(),
+            "2001:db8::/32".parse().unwrap()
+        ]
+    );
+    assert_eq!(acl.update, None);
+
+    let acl = config.get_zones()[0]
+        .get_acl()
+        .expect("zone acl not parsed");
+    assert_eq!(acl.query, None);
+    let update = acl.update.as_ref().unwrap();
+    assert!(update.networks.is_empty());
+    assert_eq!(update.keys[0].key_name, "update-key");
+    assert_eq!(acl.transfer, Some(AclConfig::default()));
+
+    assert!("acl = { query = { addresses = [] } }"
+        .parse::<Config>()
+        .is_err());
+}
+
+#[test]
+fn test_parse_query_log() {
+    let config: Config = "
+[query_log]
+path = \"/var/log/trust-dns/queries.log\"
+format = \"dnstap_lite\"
+sample_rate = 0.5
+rotate_interval = 3600
+"
+    .parse()
+    .unwrap();
+
+    let query_log = config.get_query_log().expect("query_log not parsed");
+    assert_eq!(query_log.path, Path::new("/var/log/trust-dns/queries.log"));
+    assert_eq!(query_log.format, QueryLogFormat::DnstapLite);
+    assert_eq!(query_log.sample_rate, 0.5);
+    assert_eq!(query_log.error_sample_rate, 1.0);
+    assert_eq!(query_log.max_size, None);
+    assert_eq!(query_log.rotate_interval, Some(3600));
+
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_query_log(), None);
+}
+
+#[test]
+fn test_parse_views() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+
+[[views]]
+name = \"internal\"
+match_clients = [\"10.0.0.0/8\", \"fd00::/8\"]
+match_keys = [{ key_name = \"internal-key\", algorithm = \"hmac-sha256\", key_path = \"internal.key\" }]
+
+[[views.zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"internal/example.com.zone\"
+"
+    .parse()
+    .unwrap();
+
+    assert_eq!(config.get_zones().len(), 1);
+
+    let view = &config.get_views()[0];
+    assert_eq!(view.get_name(), "internal");
+    assert_eq!(
+        view.get_match_clients(),
+        &[
+            "10.0.0.0/8".parse::<IpNet>().unwrap(),
+            "fd00::/8".parse().unwrap()
+        ]
+    );
+    assert_eq!(view.match_keys[0].key_name, "internal-key");
+    assert_eq!(
+        view.get_zones()[0].get_file(),
+        PathBuf::from("internal/example.com.zone")
+    );
+}
+
+#[test]
+fn test_parse_connection_limits() {
+    use trust_dns_server::server::ConnectionLimits;
+
+    let config: Config = "connection_limits = { max_connections_per_client = 10 }"
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        config.get_connection_limits(),
+        Some(&ConnectionLimits {
+            max_connections: None,
+            max_connections_per_client: Some(10),
+        })
+    );
+    assert_eq!("".parse::<Config>().unwrap().get_connection_limits(), None);
+}
+
+#[test]
+fn test_parse_response_size() {
+    let config: Config = "minimal_responses = true\nmax_udp_payload = 1400"
+        .parse()
+        .unwrap();
+
+    assert!(config.get_minimal_responses());
+    assert_eq!(config.get_max_udp_payload(), Some(1400));
+
+    let config: Config = "".parse().unwrap();
+    assert!(!config.get_minimal_responses());
+    assert_eq!(config.get_max_udp_payload(), None);
+}
+
+#[test]
+fn test_parse_shutdown_timeout() {
+    let config: Config = "shutdown_timeout = 30".parse().unwrap();
+    assert_eq!(config.get_shutdown_timeout(), Duration::from_secs(30));
+
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_shutdown_timeout(), Duration::from_secs(10));
+}
+
+#[test]
+fn test_parse_cookies() {
+    use trust_dns_server::server::CookieConfig;
+
+    let config: Config = "cookies = { max_queries_per_second = 10 }".parse().unwrap();
+
+    assert_eq!(
+        config.get_cookies(),
+        Some(&CookieConfig {
+            secret_rotation: 86400,
+            max_queries_per_second: Some(10),
+        })
+    );
+    assert_eq!("".parse::<Config>().unwrap().get_cookies(), None);
+}
+
+#[test]
+fn test_parse_proxy_protocol() {
+    let config: Config = "proxy_protocol = { proxies = [\"10.0.0.0/8\", \"fd00::/8\"] }"
+        .parse()
+        .unwrap();
+
+    let proxy_protocol = config.get_proxy_protocol().unwrap();
+    assert!(proxy_protocol.is_proxy("10.1.2.3:4000".parse().unwrap()));
+    assert!(proxy_protocol.is_proxy("[fd00::1]:4000".parse().unwrap()));
+    assert!(!proxy_protocol.is_proxy("192.0.2.1:4000".parse().unwrap()));
+    assert_eq!("".parse::<Config>().unwrap().get_proxy_protocol(), None);
+}
+
+#[test]
+fn test_parse_rate_limit() {
+    use trust_dns_server::server::RateLimits;
+
+    let config: Config = "
+rate_limit = { responses_per_second = 10, slip = 1 }
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+rate_limit = { nxdomains_per_second = 2, ipv4_prefix_len = 32 }
+"
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        config.get_rate_limit(),
+        Some(&RateLimits {
+            responses_per_second: 10,
+            slip: 1,
+            ..RateLimits::default()
+        })
+    );
+    assert_eq!(
+        config.get_zones()[0].get_rate_limit(),
+        Some(&RateLimits {
+            nxdomains_per_second: 2,
+            ipv4_prefix_len: 32,
+            ..RateLimits::default()
+        })
+    );
+}
+
+#[test]
+fn test_check_config() {
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    let zone_dir = PathBuf::from(server_path).join("tests/test-data/named_test_configs");
+
+    let config = Config::read_config(&zone_dir.join("example.toml")).unwrap();
+    assert_eq!(config.check(&zone_dir), vec![]);
+
+    let config: Config = "
+listen_addrs_ipv4 = [\"0.0.0.0\", \"300.1.1.1\", \"0.0.0.0\"]
+log_level = \"loud\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"missing.zone\"
+
+[[views]]
+name = \"internal\"
+
+[[views.match_keys]]
+key_name = \"internal\"
+key_path = \"missing.key\"
+algorithm = \"hmac-sha256\"
+
+[[views.zones]]
+zone = \"localhost\"
+zone_type = \"Primary\"
+stores = { type = \"file\", zone_file_path = \"default/localhost.zone\" }
+"
+    .parse()
+    .unwrap();
+
+    let problems = config
+        .check(&zone_dir)
+        .into_iter()
+        .map(|problem| problem.location)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        [
+            "listen_addrs_ipv4[1]",
+            "listen_addrs_ipv4[2]",
+            "log_level",
+            "zones[1] (example.com)",
+            "zones[1] (example.com)",
+            "views[0] (internal).match_keys[0]",
+        ]
+    );
+}
@@ -1,16 +1,22 @@
 #![recursion_limit = "128"]
 #![cfg(feature = "trust-dns-resolver")]
 
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr};
 use std::str::FromStr;
+use std::time::Duration;
 
+use tokio::net::UdpSocket;
 use tokio::runtime::Runtime;
 
-use trust_dns_client::rr::{Name, RData, RecordType};
+use trust_dns_client::op::{Edns, Message, MessageType};
+use trust_dns_client::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption, ExtendedErrorCode};
+use trust_dns_client::rr::{Name, RData, Record, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_resolver::config::{NameServerConfigGroup, Protocol, ResolverOpts};
 use trust_dns_resolver::TokioHandle;
 use trust_dns_server::{
-    authority::{Authority, LookupObject},
-    store::forwarder::ForwardAuthority,
+    authority::{Authority, LookupObject, LookupOptions, ZoneType},
+    store::forwarder::{ForwardAuthority, ForwardConfig, ForwardRule},
 };
 
 #[ignore]
@@ -35,3 +41,327 @@ fn test_lookup() {
         .expect("not an A record");
     assert_eq!(*address, Ipv4Addr::new(93, 184, 216, 34));
 }
+
+fn response_subnet(message: &Message) -> Option<ClientSubnet> {
+    match message.extensions().as_ref()?.option(EdnsCode::Subnet) {
+        Some(&EdnsOption::Subnet(subnet)) => Some(subnet),
+        _ => None,
+    }
+}
+
+/// Answers two queries for the scope of their client subnet, returns the client subnets
+async fn upstream(socket: UdpSocket) -> Vec<Option<ClientSubnet>> {
+    let mut subnets = Vec::new();
+    let mut buf = [0_u8; 4096];
+
+    while subnets.len() < 2 {
+        let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+        let request = Message::from_bytes(&buf[..len]).unwrap();
+        let subnet = response_subnet(&request);
+        subnets.push(subnet);
+
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_recursion_available(true)
+            .add_queries(request.queries().to_vec())
+            .add_answer(Record::from_rdata(
+                Name::from_str("www.example.com.").unwrap(),
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ));
+        if let Some(subnet) = subnet {
+            let mut edns = Edns::new();
+            edns.options_mut()
+                .insert(EdnsOption::Subnet(ClientSubnet::new(
+                    subnet.address(),
+                    subnet.source_prefix(),
+                    subnet.source_prefix(),
+                )));
+            response.set_edns(edns);
+        }
+        socket
+            .send_to(&response.to_bytes().unwrap(), src)
+            .await
+            .unwrap();
+    }
+
+    subnets
+}
+
+#[tokio::test]
+async fn test_forward_client_subnet() {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let upstream = tokio::spawn(upstream(socket));
+
+    let mut name_servers =
+        NameServerConfigGroup::from_ips_clear(&[IpAddr::from([127, 0, 0, 1])], port, true);
+    name_servers.retain(|ns| ns.protocol == Protocol::Udp);
+    let config = ForwardConfig {
+        name_servers,
+        options: None,
+        forward_client_subnet: Some(true),
+        client_subnet_ipv4_prefix: Some(16),
+        client_subnet_ipv6_prefix: None,
+        rules: vec![],
+        max_stale: None,
+    };
+    let forwarder = ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config)
+        .expect("failed to create forwarder");
+
+    assert_eq!(
+        forwarder.client_subnet_scope(&ClientSubnet::from_str("192.0.2.0/24").unwrap()),
+        16
+    );
+
+    // the last lookup is answered from the cache, for the scope of the first
+    for client_subnet in &["192.0.2.0/24", "198.51.100.0/24", "192.0.3.0/24"] {
+        let lookup_options = LookupOptions::default()
+            .set_client_subnet(Some(ClientSubnet::from_str(client_subnet).unwrap()));
+        let lookup = forwarder
+            .lookup(
+                &Name::from_str("www.example.com.").unwrap().into(),
+                RecordType::A,
+                lookup_options,
+            )
+            .await
+            .unwrap();
+        assert!(!lookup.is_empty());
+    }
+
+    assert_eq!(
+        upstream.await.unwrap(),
+        vec![
+            Some(ClientSubnet::new([192, 0, 0, 0].into(), 16, 0)),
+            Some(ClientSubnet::new([198, 51, 0, 0].into(), 16, 0)),
+        ]
+    );
+}
+
+/// Answers all queries with the address
+async fn answer(socket: UdpSocket, address: Ipv4Addr) {
+    let mut buf = [0_u8; 4096];
+
+    loop {
+        let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+        let request = Message::from_bytes(&buf[..len]).unwrap();
+
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_recursion_available(true)
+            .add_queries(request.queries().to_vec())
+            .add_answer(Record::from_rdata(
+                request.queries()[0].name().clone(),
+                300,
+                RData::A(address),
+            ));
+        socket
+            .send_to(&response.to_bytes().unwrap(), src)
+            .await
+            .unwrap();
+    }
+}
+
+fn udp_name_servers(port: u16) -> NameServerConfigGroup {
+    let mut name_servers =
+        NameServerConfigGroup::from_ips_clear(&[IpAddr::from([127, 0, 0, 1])], port, true);
+    name_servers.retain(|ns| ns.protocol == Protocol::Udp);
+    name_servers
+}
+
+async fn lookup_address(forwarder: &ForwardAuthority, name: &str) -> Option<Ipv4Addr> {
+    let lookup = forwarder
+        .lookup(
+            &Name::from_str(name).unwrap().into(),
+            RecordType::A,
+            LookupOptions::default(),
+        )
+        .await
+        .ok()?;
+    let address = lookup.iter().next()?.data().and_then(RData::as_a).copied();
+    address
+}
+
+#[tokio::test]
+async fn test_forward_rules() {
+    let zone = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let zone_port = zone.local_addr().unwrap().port();
+    tokio::spawn(answer(zone, Ipv4Addr::new(192, 0, 2, 1)));
+
+    let corp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let corp_port = corp.local_addr().unwrap().port();
+    tokio::spawn(answer(corp, Ipv4Addr::new(192, 0, 2, 2)));
+
+    // nothing answers the name servers of the lab
+    let lab = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let lab_port = lab.local_addr().unwrap().port();
+
+    let mut options = ResolverOpts::default();
+    options.timeout = Duration::from_millis(100);
+    options.attempts = 1;
+
+    let rule = |domain: &str, port, fallback| ForwardRule {
+        domain: domain.to_string(),
+        name_servers: udp_name_servers(port),
+        options: Some(options),
+        tsig_key: None,
+        fallback: Some(fallback),
+    };
+
+    let config = ForwardConfig {
+        name_servers: udp_name_servers(zone_port),
+        options: None,
+        forward_client_subnet: None,
+        client_subnet_ipv4_prefix: None,
+        client_subnet_ipv6_prefix: None,
+        rules: vec![
+            rule("corp.example.com", corp_port, false),
+            rule("lab.corp.example.com", lab_port, true),
+            rule("dev.corp.example.com", lab_port, false),
+        ],
+        max_stale: None,
+    };
+    let forwarder = ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config)
+        .expect("failed to create forwarder");
+
+    assert_eq!(
+        lookup_address(&forwarder, "www.example.com.").await,
+        Some(Ipv4Addr::new(192, 0, 2, 1))
+    );
+    assert_eq!(
+        lookup_address(&forwarder, "www.corp.example.com.").await,
+        Some(Ipv4Addr::new(192, 0, 2, 2))
+    );
+    assert_eq!(
+        lookup_address(&forwarder, "corp.example.com.").await,
+        Some(Ipv4Addr::new(192, 0, 2, 2))
+    );
+
+    // the most specific rule applies, and falls back to the name servers of the zone
+    assert_eq!(
+        lookup_address(&forwarder, "www.lab.corp.example.com.").await,
+        Some(Ipv4Addr::new(192, 0, 2, 1))
+    );
+    assert_eq!(
+        lookup_address(&forwarder, "www.dev.corp.example.com.").await,
+        None
+    );
+    drop(lab);
+}
+
+#[test]
+fn test_forward_rule_outside_zone() {
+    let config = ForwardConfig {
+        name_servers: udp_name_servers(53),
+        options: None,
+        forward_client_subnet: None,
+        client_subnet_ipv4_prefix: None,
+        client_subnet_ipv6_prefix: None,
+        rules: vec![ForwardRule {
+            domain: "example.net".to_string(),
+            name_servers: udp_name_servers(53),
+            options: None,
+            tsig_key: None,
+            fallback: None,
+        }],
+        max_stale: None,
+    };
+
+    assert!(ForwardAuthority::try_from_config(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Forward,
+        &config
+    )
+    .is_err());
+}
+
+/// Answers the first query with the address for a second, then stops answering
+async fn answer_once(socket: UdpSocket, address: Ipv4Addr) -> UdpSocket {
+    let mut buf = [0_u8; 4096];
+    let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+    let request = Message::from_bytes(&buf[..len]).unwrap();
+
+    let mut response = Message::new();
+    response
+        .set_id(request.id())
+        .set_message_type(MessageType::Response)
+        .set_recursion_available(true)
+        .add_queries(request.queries().to_vec())
+        .add_answer(Record::from_rdata(
+            request.queries()[0].name().clone(),
+            1,
+            RData::A(address),
+        ));
+    socket
+        .send_to(&response.to_bytes().unwrap(), src)
+        .await
+        .unwrap();
+
+    socket
+}
+
+#[tokio::test]
+async fn test_serve_stale() {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let upstream = tokio::spawn(answer_once(socket, Ipv4Addr::new(192, 0, 2, 1)));
+
+    let mut options = ResolverOpts::default();
+    options.timeout = Duration::from_millis(100);
+    options.attempts = 1;
+
+    let config = ForwardConfig {
+        name_servers: udp_name_servers(port),
+        options: Some(options),
+        forward_client_subnet: None,
+        client_subnet_ipv4_prefix: None,
+        client_subnet_ipv6_prefix: None,
+        rules: vec![],
+        max_stale: Some(60),
+    };
+    let forwarder = ForwardAuthority::try_from_config(Name::root(), ZoneType::Forward, &config)
+        .expect("failed to create forwarder");
+
+    let name = Name::from_str("www.example.com.").unwrap().into();
+    let lookup = forwarder
+        .lookup(&name, RecordType::A, LookupOptions::default())
+        .await
+        .unwrap();
+    assert!(lookup.extended_error().is_none());
+
+    // the answer expires, and the upstream resolver no longer answers
+    let _socket = upstream.await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    let lookup = forwarder
+        .lookup(&name, RecordType::A, LookupOptions::default())
+        .await
+        .unwrap();
+    let record = lookup.iter().next().expect("no stale answer");
+    assert_eq!(
+        record.data().and_then(RData::as_a),
+        Some(&Ipv4Addr::new(192, 0, 2, 1))
+    );
+    assert_eq!(record.ttl(), 30);
+    assert_eq!(
+        lookup.extended_error(),
+        Some(EdnsOption::ExtendedError {
+            info_code: ExtendedErrorCode::StaleAnswer,
+            extra_text: String::new(),
+        })
+    );
+
+    // names which were never answered still fail
+    assert!(forwarder
+        .lookup(
+            &Name::from_str("www.example.net.").unwrap().into(),
+            RecordType::A,
+            LookupOptions::default()
+        )
+        .await
+        .is_err());
+}
@@ -1,9 +1,12 @@
+use std::net::Ipv4Addr;
 use std::str::FromStr;
 
 use tokio::runtime::Runtime;
+use trust_dns_client::op::ResponseCode;
+use trust_dns_client::rr::rdata::{SOA, TXT};
 use trust_dns_client::rr::{Name, RData, Record, RecordType};
 use trust_dns_server::{
-    authority::{Authority, ZoneType},
+    authority::{Authority, LookupError, ZoneType},
     store::in_memory::InMemoryAuthority,
 };
 
@@ -147,3 +150,359 @@ fn test_cname_loop() {
         Some(&RData::CNAME(Name::from_str("baz.example.com.").unwrap()))
     );
 }
+
+fn upsert(auth: &mut InMemoryAuthority, name: &str, rdata: RData) {
+    auth.upsert_mut(
+        Record::from_rdata(Name::from_str(name).unwrap(), 300, rdata),
+        0,
+    );
+}
+
+#[test]
+fn test_wildcard_closest_encloser() {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let mut auth = InMemoryAuthority::empty(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Primary,
+        false,
+    );
+
+    upsert(
+        &mut auth,
+        "*.example.com.",
+        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+    );
+    upsert(
+        &mut auth,
+        "host.example.com.",
+        RData::TXT(TXT::new(vec!["host".to_string()])),
+    );
+    // b.example.com. is an empty non-terminal
+    upsert(
+        &mut auth,
+        "a.b.example.com.",
+        RData::A(Ipv4Addr::new(192, 0, 2, 2)),
+    );
+
+    let lookup = |name: &str, record_type| {
+        runtime.block_on(auth.lookup(
+            &Name::from_str(name).unwrap().into(),
+            record_type,
+            Default::default(),
+        ))
+    };
+
+    // the closest encloser of x.y.example.com. is example.com., not y.example.com.
+    let answer = lookup("x.y.example.com.", RecordType::A).expect("wildcard did not match");
+    let records: Vec<&Record> = answer.iter().collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(
+        records[0].name(),
+        &Name::from_str("x.y.example.com.").unwrap()
+    );
+    assert_eq!(
+        records[0].data(),
+        Some(&RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+
+    // names which exist are not matched, not even empty non-terminals
+    assert!(matches!(
+        lookup("host.example.com.", RecordType::A),
+        Err(LookupError::NameExists)
+    ));
+    assert!(matches!(
+        lookup("b.example.com.", RecordType::A),
+        Err(LookupError::NameExists)
+    ));
+
+    // the closest encloser b.example.com. has no wildcard
+    assert!(matches!(
+        lookup("c.b.example.com.", RecordType::A),
+        Err(LookupError::ResponseCode(ResponseCode::NXDomain))
+    ));
+
+    // the wildcard has no records of the type
+    assert!(matches!(
+        lookup("x.example.com.", RecordType::MX),
+        Err(LookupError::NameExists)
+    ));
+}
+
+#[test]
+fn test_set_challenge_txt() {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let origin = Name::from_str("example.com.").unwrap();
+    let mut auth = InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, false);
+    upsert(
+        &mut auth,
+        "example.com.",
+        RData::SOA(SOA::new(
+            Name::from_str("ns.example.com.").unwrap(),
+            Name::from_str("hostmaster.example.com.").unwrap(),
+            1,
+            3600,
+            600,
+            86400,
+            300,
+        )),
+    );
+
+    let name = Name::from_str("_acme-challenge.example.com.")
+        .unwrap()
+        .into();
+    let txt = |auth: &InMemoryAuthority| {
+        runtime
+            .block_on(auth.lookup(&name, RecordType::TXT, Default::default()))
+            .map(|lookup| {
+                lookup
+                    .iter()
+                    .map(|record| record.data().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+    };
+
+    let values = ["first".to_string(), "second".to_string()];
+    runtime
+        .block_on(auth.set_challenge_txt(&name, &values))
+        .unwrap();
+    let mut found = txt(&auth).unwrap();
+    found.sort();
+    assert_eq!(found, vec!["first", "second"]);
+    assert_eq!(runtime.block_on(auth.serial()), 2);
+
+    // the records are replaced, and removed without values
+    runtime
+        .block_on(auth.set_challenge_txt(&name, &values[1..]))
+        .unwrap();
+    assert_eq!(txt(&auth).unwrap(), vec!["second"]);
+    runtime
+        .block_on(auth.set_challenge_txt(&name, &[]))
+        .unwrap();
+    assert!(txt(&auth).is_err());
+    assert_eq!(runtime.block_on(auth.serial()), 4);
+
+    let other = Name::from_str("_acme-challenge.example.net.")
+        .unwrap()
+        .into();
+    assert_eq!(
+        runtime.block_on(auth.set_challenge_txt(&other, &values)),
+        Err(ResponseCode::NotZone)
+    );
+}
+
+#[cfg(feature = "dnssec-ring")]
+#[test]
+fn test_nsec3_opt_out() {
+    use trust_dns_client::rr::dnssec::{Algorithm, DigestType, Nsec3HashAlgorithm};
+    use trust_dns_client::rr::rdata::SOA;
+    use trust_dns_proto::rr::dnssec::nsec3_proof::{Nsec3Chain, Nsec3Proof};
+    use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, DS, NSEC3PARAM};
+    use trust_dns_proto::rr::dnssec::SupportedAlgorithms;
+    use trust_dns_server::authority::LookupOptions;
+    use trust_dns_server::config::dnssec::KeyConfig;
+
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let origin = Name::from_str("example.com.").unwrap();
+    let mut auth = InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, false);
+
+    upsert(
+        &mut auth,
+        "example.com.",
+        RData::SOA(SOA::new(
+            Name::from_str("ns.example.com.").unwrap(),
+            Name::from_str("root.example.com.").unwrap(),
+            1,
+            3600,
+            600,
+            86400,
+            300,
+        )),
+    );
+    upsert(
+        &mut auth,
+        "www.example.com.",
+        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+    );
+    // an unsigned delegation with glue, below the empty non-terminal deep.example.com.
+    upsert(
+        &mut auth,
+        "unsigned.deep.example.com.",
+        RData::NS(Name::from_str("ns.unsigned.deep.example.com.").unwrap()),
+    );
+    upsert(
+        &mut auth,
+        "ns.unsigned.deep.example.com.",
+        RData::A(Ipv4Addr::new(192, 0, 2, 2)),
+    );
+    // a signed delegation
+    upsert(
+        &mut auth,
+        "signed.example.com.",
+        RData::NS(Name::from_str("ns.example.net.").unwrap()),
+    );
+    upsert(
+        &mut auth,
+        "signed.example.com.",
+        RData::DNSSEC(DNSSECRData::DS(DS::new(
+            1,
+            Algorithm::ED25519,
+            DigestType::SHA256,
+            vec![0; 32],
+        ))),
+    );
+
+    let key_config = KeyConfig {
+        key_path: "../../tests/test-data/named_test_configs/dnssec/ed25519.pk8".to_string(),
+        password: None,
+        algorithm: Algorithm::ED25519.to_string(),
+        signer_name: Some(origin.to_string()),
+        is_zone_signing_key: Some(true),
+        is_zone_update_auth: Some(false),
+        schedule: None,
+    };
+    let signer = key_config.try_into_signer(origin.clone()).unwrap();
+    auth.add_zone_signing_key_mut(signer).unwrap();
+    auth.set_nsec3_mut(
+        NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, false, 0, vec![]),
+        true,
+    );
+    auth.secure_zone_mut().unwrap();
+
+    // only the apex, www and the signed delegation are in the chain
+    let nsec3s = auth
+        .records_get_mut()
+        .values()
+        .filter(|rr_set| rr_set.record_type() == RecordType::NSEC3)
+        .flat_map(|rr_set| rr_set.records_without_rrsigs().cloned())
+        .collect::<Vec<_>>();
+    assert_eq!(nsec3s.len(), 3);
+    assert!(nsec3s.iter().all(|record| record
+        .data()
+        .and_then(RData::as_dnssec)
+        .and_then(DNSSECRData::as_nsec3)
+        .unwrap()
+        .opt_out()));
+
+    let chain = Nsec3Chain::new(&origin, &nsec3s).unwrap();
+    assert_eq!(
+        chain
+            .verify(
+                &Name::from_str("www.example.com.").unwrap(),
+                RecordType::TXT
+            )
+            .unwrap(),
+        Nsec3Proof::NoData
+    );
+    assert!(!chain.proves_insecure_delegation(&Name::from_str("signed.example.com.").unwrap()));
+
+    // the proof of the unsigned delegation is the opt-out span covering it
+    let name = Name::from_str("unsigned.deep.example.com.").unwrap();
+    let proof = runtime
+        .block_on(auth.get_nsec_records(
+            &name.clone().into(),
+            LookupOptions::for_dnssec(true, SupportedAlgorithms::all()),
+        ))
+        .unwrap();
+    let records = proof.iter().cloned().collect::<Vec<_>>();
+    let chain = Nsec3Chain::new(&origin, &records).unwrap();
+    assert!(chain.proves_insecure_delegation(&name));
+}
+
+/// Answers all queries with the address
+#[cfg(feature = "resolver")]
+async fn answer(socket: tokio::net::UdpSocket, address: Ipv4Addr) {
+    use trust_dns_client::op::{Message, MessageType};
+    use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+
+    let mut buf = [0_u8; 4096];
+
+    loop {
+        let (len, src) = socket.recv_from(&mut buf).await.unwrap();
+        let request = Message::from_bytes(&buf[..len]).unwrap();
+
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_recursion_available(true)
+            .add_queries(request.queries().to_vec())
+            .add_answer(Record::from_rdata(
+                request.queries()[0].name().clone(),
+                60,
+                RData::A(address),
+            ));
+        socket
+            .send_to(&response.to_bytes().unwrap(), src)
+            .await
+            .unwrap();
+    }
+}
+
+#[cfg(feature = "resolver")]
+#[tokio::test]
+async fn test_aname_external_target() {
+    use std::net::IpAddr;
+
+    use trust_dns_resolver::config::{NameServerConfigGroup, Protocol};
+    use trust_dns_server::store::in_memory::AnameConfig;
+
+    let upstream = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = upstream.local_addr().unwrap().port();
+    tokio::spawn(answer(upstream, Ipv4Addr::new(192, 0, 2, 10)));
+
+    let mut name_servers =
+        NameServerConfigGroup::from_ips_clear(&[IpAddr::from([127, 0, 0, 1])], port, true);
+    name_servers.retain(|ns| ns.protocol == Protocol::Udp);
+
+    let mut auth = InMemoryAuthority::empty(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Primary,
+        false,
+    );
+    upsert(
+        &mut auth,
+        "example.com.",
+        RData::ANAME(Name::from_str("cdn.example.net.").unwrap()),
+    );
+    auth.set_aname_config(&AnameConfig {
+        name_servers,
+        options: None,
+    })
+    .unwrap();
+
+    let mut lookup = auth
+        .lookup(
+            &Name::from_str("example.com.").unwrap().into(),
+            RecordType::A,
+            Default::default(),
+        )
+        .await
+        .expect("ANAME was not resolved");
+
+    let records: Vec<&Record> = lookup.iter().collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].name(), &Name::from_str("example.com.").unwrap());
+    assert_eq!(records[0].record_type(), RecordType::A);
+    assert!(records[0].ttl() <= 60);
+    assert_eq!(
+        records[0].data(),
+        Some(&RData::A(Ipv4Addr::new(192, 0, 2, 10)))
+    );
+
+    let additionals = lookup.take_additionals().expect("no ANAME in additionals");
+    assert_eq!(
+        additionals.iter().next().map(Record::record_type),
+        Some(RecordType::ANAME)
+    );
+
+    // the target has no AAAA addresses
+    let lookup = auth
+        .lookup(
+            &Name::from_str("example.com.").unwrap().into(),
+            RecordType::AAAA,
+            Default::default(),
+        )
+        .await
+        .expect("ANAME was not resolved");
+    assert_eq!(lookup.iter().count(), 0);
+}
@@ -1,17 +1,22 @@
+use std::net::Ipv4Addr;
+use std::path::Path;
 use std::str::FromStr;
 
-use trust_dns_client::rr::{LowerName, RecordType};
+use futures_executor::block_on;
+
+use trust_dns_client::op::{Header, Query};
+use trust_dns_client::rr::{LowerName, RData, RecordType};
 use trust_dns_client::rr::{Name, RrKey};
-use trust_dns_server::authority::ZoneType;
-use trust_dns_server::store::file::{FileAuthority, FileConfig};
+use trust_dns_server::authority::{Authority, LookupOptions, ZoneType};
+use trust_dns_server::server::{Protocol, RequestInfo};
+use trust_dns_server::store::file::{FileAuthority, FileConfig, ZoneCheck, ZoneProblem};
+use trust_dns_server::store::in_memory::{PoolConfig, PoolMemberConfig};
 
 #[macro_use]
 mod authority_battery;
 
 fn file(master_file_path: &str, _module: &str, _test_name: &str) -> FileAuthority {
-    let config = FileConfig {
-        zone_file_path: master_file_path.to_string(),
-    };
+    let config = FileConfig::new(master_file_path.to_string());
 
     FileAuthority::try_from_config(
         Name::from_str("example.com.").unwrap(),
@@ -29,10 +34,9 @@ dnssec_battery!(file);
 
 #[test]
 fn test_all_lines_are_loaded() {
-    let config = FileConfig {
-        zone_file_path: "../../tests/test-data/named_test_configs/default/nonewline.zone"
-            .to_string(),
-    };
+    let config = FileConfig::new(
+        "../../tests/test-data/named_test_configs/default/nonewline.zone".to_string(),
+    );
 
     let mut authority = FileAuthority::try_from_config(
         Name::from_str("example.com.").unwrap(),
@@ -48,3 +52,152 @@ fn test_all_lines_are_loaded() {
     };
     assert!(authority.records_get_mut().get(&rrkey).is_some())
 }
+
+#[test]
+fn test_pool() {
+    let mut config =
+        FileConfig::new("../../tests/test-data/named_test_configs/example.com.zone".to_string());
+    config.pools = vec![PoolConfig {
+        name: "www".to_string(),
+        record_type: "A".to_string(),
+        ttl: Some(10),
+        max_answers: None,
+        members: vec![
+            PoolMemberConfig {
+                address: "192.0.2.1".parse().unwrap(),
+                weight: Some(2),
+            },
+            PoolMemberConfig {
+                address: "192.0.2.2".parse().unwrap(),
+                weight: None,
+            },
+        ],
+        health_check: None,
+    }];
+
+    let authority = FileAuthority::try_from_config(
+        Name::from_str("example.com.").unwrap(),
+        ZoneType::Primary,
+        false,
+        None,
+        &config,
+    )
+    .expect("failed to load");
+
+    let header = Header::new();
+    let lookup = |record_type| {
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), record_type).into();
+        let request_info = RequestInfo::new(
+            "127.0.0.1:53".parse().unwrap(),
+            Protocol::Udp,
+            &header,
+            &query,
+        );
+
+        block_on(authority.search(request_info, LookupOptions::default()))
+            .unwrap()
+            .iter()
+            .map(|r| (r.ttl(), r.data().cloned()))
+            .collect::<Vec<_>>()
+    };
+
+    let answers = (0..3).map(|_| lookup(RecordType::A)).collect::<Vec<_>>();
+    let member = |last| vec![(10, Some(RData::A(Ipv4Addr::new(192, 0, 2, last))))];
+    assert_eq!(answers, [member(1), member(2), member(1)]);
+
+    // other types are answered from the zone
+    assert_eq!(
+        lookup(RecordType::AAAA)[0].1,
+        Some(RData::AAAA("::1".parse().unwrap()))
+    );
+}
+
+#[test]
+fn test_zone_check() {
+    let zone = ZoneCheck::from_file(
+        Path::new("../../tests/test-data/named_test_configs/example.com.zone"),
+        Some(Name::from_str("example.com.").unwrap()),
+    )
+    .expect("failed to parse");
+    assert_eq!(zone.check(0), vec![]);
+
+    let zone = ZoneCheck::parse(
+        "$ORIGIN example.com.\n\
+         @ 3600 IN NS ns\n\
+         ns 3600 IN A 192.0.2.53\n\
+         alias 3600 IN CNAME ns\n\
+         mail 3600 IN MX 10 ns\n\
+         mail 3600 IN CNAME ns\n\
+         example.net. 3600 IN A 192.0.2.1\n",
+        None,
+    )
+    .expect("failed to parse");
+    assert_eq!(
+        zone.check(0),
+        vec![
+            ZoneProblem::MissingSoa,
+            ZoneProblem::CnameAndOtherData(Name::from_str("mail.example.com.").unwrap()),
+            ZoneProblem::OutOfZone(Name::from_str("example.net.").unwrap(), RecordType::A),
+        ]
+    );
+}
+
+#[cfg(feature = "dnssec")]
+#[test]
+fn test_zone_check_signatures() {
+    let zone = ZoneCheck::parse(
+        "$ORIGIN example.com.\n\
+         @ 3600 IN SOA ns hostmaster 1 3600 600 86400 300\n\
+         @ 3600 IN NS ns\n\
+         @ 3600 IN RRSIG NS 13 2 3600 20300101000000 20200101000000 1 example.com. AAEC\n\
+         ns 3600 IN A 192.0.2.53\n\
+         ns 3600 IN RRSIG A 13 3 3600 20300101000000 20250101000000 1 example.com. AAEC\n",
+        None,
+    )
+    .expect("failed to parse");
+
+    // 2024-01-01, before the inception of the RRSIG of ns
+    assert_eq!(
+        zone.check(1_704_067_200),
+        vec![ZoneProblem::SignatureNotYetValid {
+            name: Name::from_str("ns.example.com.").unwrap(),
+            type_covered: RecordType::A,
+            inception: 1_735_689_600,
+        }]
+    );
+    assert_eq!(zone.check(1_735_689_600), vec![]);
+
+    // 2030-01-01, all have expired
+    assert_eq!(zone.check(1_893_456_000).len(), 2);
+}
+
+#[test]
+fn test_zone_canonical() {
+    let zone = ZoneCheck::parse(
+        "$ORIGIN Example.COM.\n\
+         $TTL 3600\n\
+         WWW IN A 192.0.2.2\n\
+         www IN A 192.0.2.1\n\
+         @ IN NS b.ns\n\
+         @ IN NS a.ns\n\
+         @ IN SOA ns hostmaster 1 3600 600 86400 300\n",
+        None,
+    )
+    .expect("failed to parse");
+
+    let canonical = zone.canonical();
+    assert_eq!(
+        canonical,
+        "$ORIGIN example.com.\n\
+         $TTL 300\n\
+         @\t86400\tIN\tSOA\tns.example.com. hostmaster.example.com. 1 3600 600 86400 300\n\
+         \t3600\tIN\tNS\ta.ns.example.com.\n\
+         \t3600\tIN\tNS\tb.ns.example.com.\n\
+         www\t3600\tIN\tA\t192.0.2.1\n\
+         \t3600\tIN\tA\t192.0.2.2\n"
+    );
+
+    // the canonical zone is parsed to the same zone
+    let reparsed = ZoneCheck::parse(&canonical, None).expect("failed to parse");
+    assert_eq!(reparsed.canonical(), canonical);
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "sql")]
+
+//! These tests need a database, e.g. run them with
+//!  `TDNS_SQL_URL=postgres://localhost/trust_dns cargo test --features postgres -- --ignored`
+
+use std::env;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use trust_dns_client::rr::{Name, RData, Record, RecordType};
+use trust_dns_server::{
+    authority::{Authority, LookupOptions, ZoneType},
+    store::sql::{SqlAuthority, SqlConfig},
+};
+
+const ZONE_FILE: &str = "../../tests/test-data/named_test_configs/example.com.zone";
+
+fn sql_config(zone_file_path: Option<&str>) -> SqlConfig {
+    SqlConfig {
+        url: env::var("TDNS_SQL_URL").expect("TDNS_SQL_URL must be the URL of a test database"),
+        zone_file_path: zone_file_path.map(String::from),
+        allow_update: true,
+        refresh_interval: Some(0),
+        max_connections: Some(2),
+    }
+}
+
+/// A zone which is not in the database, so that runs don't see the records of previous runs
+fn unique_origin(test: &str) -> Name {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    Name::from_str(&format!("{}-{}.sql.example.com.", test, now.as_nanos())).unwrap()
+}
+
+async fn load(origin: &Name, zone_file_path: Option<&str>) -> Result<SqlAuthority, String> {
+    SqlAuthority::try_from_config(
+        origin.clone(),
+        ZoneType::Primary,
+        false,
+        false,
+        None,
+        &sql_config(zone_file_path),
+    )
+    .await
+}
+
+async fn lookup_a(authority: &SqlAuthority, name: &Name) -> Vec<RData> {
+    authority
+        .lookup(&name.into(), RecordType::A, LookupOptions::default())
+        .await
+        .map(|lookup| lookup.iter().filter_map(Record::data).cloned().collect())
+        .unwrap_or_default()
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_load_and_recover() {
+    let origin = unique_origin("recover");
+
+    // nothing in the database, and no zone file
+    assert!(load(&origin, None).await.is_err());
+
+    let authority = load(&origin, Some(ZONE_FILE))
+        .await
+        .expect("failed to load");
+    let serial = authority.serial().await;
+    let www = Name::from_str("www")
+        .unwrap()
+        .append_domain(&origin)
+        .unwrap();
+    assert_eq!(
+        lookup_a(&authority, &www).await,
+        vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+    );
+
+    // the zone file is only read once, the zone is recovered from the database
+    let recovered = load(&origin, Some("does_not_exist.zone"))
+        .await
+        .expect("failed to recover");
+    assert_eq!(recovered.serial().await, serial);
+    assert_eq!(
+        lookup_a(&recovered, &www).await,
+        vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+    );
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_shared_database() {
+    let origin = unique_origin("shared");
+
+    let first = load(&origin, Some(ZONE_FILE))
+        .await
+        .expect("failed to load");
+    let second = load(&origin, Some(ZONE_FILE))
+        .await
+        .expect("failed to load");
+    let serial = first.serial().await;
+
+    let new_name = Name::from_str("new")
+        .unwrap()
+        .append_domain(&origin)
+        .unwrap();
+    let record = Record::from_rdata(
+        new_name.clone(),
+        86400,
+        RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+    );
+    assert!(first
+        .update_records(&[record], true)
+        .await
+        .expect("update failed"));
+    assert_eq!(first.serial().await, serial + 1);
+
+    // the second server sees the update after it refreshes
+    assert!(lookup_a(&second, &new_name).await.is_empty());
+    second.refresh().await.expect("refresh failed");
+    assert_eq!(
+        lookup_a(&second, &new_name).await,
+        vec![RData::A(Ipv4Addr::new(10, 0, 0, 1))]
+    );
+    assert_eq!(second.serial().await, serial + 1);
+
+    // and can serve IXFR for it
+    assert_eq!(second.ixfr_diffs(serial).await.map(|d| d.len()), Some(1));
+
+    // a server started later recovers all changes
+    let third = load(&origin, Some(ZONE_FILE))
+        .await
+        .expect("failed to load");
+    assert_eq!(third.serial().await, serial + 1);
+    assert_eq!(
+        lookup_a(&third, &new_name).await,
+        vec![RData::A(Ipv4Addr::new(10, 0, 0, 1))]
+    );
+}
@@ -29,6 +29,11 @@ fn sqlite(master_file_path: &str, module: &str, test_name: &str) -> SqliteAuthor
         zone_file_path: master_file_path.to_string(),
         journal_file_path: journal_path.to_str().unwrap().to_string(),
         allow_update: true,
+        #[cfg(feature = "resolver")]
+        aname: None,
+        #[cfg(feature = "geoip")]
+        geo: None,
+        pools: Vec::new(),
     };
 
     block_on(SqliteAuthority::try_from_config(
@@ -57,6 +62,11 @@ fn sqlite_update(master_file_path: &str, module: &str, test_name: &str) -> Sqlit
         zone_file_path: master_file_path.to_string(),
         journal_file_path: journal_path.to_str().unwrap().to_string(),
         allow_update: true,
+        #[cfg(feature = "resolver")]
+        aname: None,
+        #[cfg(feature = "geoip")]
+        geo: None,
+        pools: Vec::new(),
     };
 
     block_on(SqliteAuthority::try_from_config(
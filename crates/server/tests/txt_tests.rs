@@ -1,4 +1,7 @@
+use std::collections::BTreeMap;
+use std::env;
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use futures_executor::block_on;
@@ -54,6 +57,14 @@ _443._tcp.www.example.com. IN TLSA (
       0 0 1 d2abde240d7cd3ee6b4b28c54df034b9
             7983a1d16e8a410e4561cb106618e971)
 
+_ftp._tcp    URI 10 1 "ftp://ftp1.example.com/public"
+
+mac          EUI48 00-00-5e-00-53-2a
+
+loc          LOC 42 21 54 N 71 06 18 W -24m 30m
+
+generic      TYPE65280 \# 4 0A000001
+
 tech.   3600    in      soa     ns0.centralnic.net.     hostmaster.centralnic.net.      271851  900     1800    6048000 3600
 "###,
     );
@@ -405,6 +416,78 @@ tech.   3600    in      soa     ns0.centralnic.net.     hostmaster.centralnic.ne
     } else {
         panic!();
     }
+
+    // URI
+    let uri_record: Record = block_on(authority.lookup(
+        &Name::from_str("_ftp._tcp.isi.edu.").unwrap().into(),
+        RecordType::URI,
+        LookupOptions::default(),
+    ))
+    .unwrap()
+    .iter()
+    .next()
+    .cloned()
+    .expect("uri record not found");
+    if let Some(RData::URI(ref rdata)) = uri_record.data() {
+        assert_eq!(rdata.priority(), 10);
+        assert_eq!(rdata.weight(), 1);
+        assert_eq!(rdata.target(), "ftp://ftp1.example.com/public");
+    } else {
+        panic!();
+    }
+
+    // EUI48
+    let eui48_record: Record = block_on(authority.lookup(
+        &Name::from_str("mac.isi.edu.").unwrap().into(),
+        RecordType::EUI48,
+        LookupOptions::default(),
+    ))
+    .unwrap()
+    .iter()
+    .next()
+    .cloned()
+    .expect("eui48 record not found");
+    if let Some(RData::EUI48(ref rdata)) = eui48_record.data() {
+        assert_eq!(rdata.address(), &[0x00, 0x00, 0x5e, 0x00, 0x53, 0x2a]);
+    } else {
+        panic!();
+    }
+
+    // LOC
+    let loc_record: Record = block_on(authority.lookup(
+        &Name::from_str("loc.isi.edu.").unwrap().into(),
+        RecordType::LOC,
+        LookupOptions::default(),
+    ))
+    .unwrap()
+    .iter()
+    .next()
+    .cloned()
+    .expect("loc record not found");
+    if let Some(RData::LOC(ref rdata)) = loc_record.data() {
+        assert_eq!(
+            rdata.to_string(),
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30m 10000m 10m"
+        );
+    } else {
+        panic!();
+    }
+
+    // TYPE65280
+    let generic_record: Record = block_on(authority.lookup(
+        &Name::from_str("generic.isi.edu.").unwrap().into(),
+        RecordType::Unknown(65280),
+        LookupOptions::default(),
+    ))
+    .unwrap()
+    .iter()
+    .next()
+    .cloned()
+    .expect("generic record not found");
+    assert_eq!(
+        generic_record.data().map(ToString::to_string).as_deref(),
+        Some("\\# 4 0A000001")
+    );
 }
 
 #[test]
@@ -490,6 +573,35 @@ a       A       127.0.0.1
     assert!(InMemoryAuthority::new(origin, records, ZoneType::Primary, false).is_ok());
 }
 
+#[test]
+fn test_alias_at_soa() {
+    let lexer = Lexer::new(
+        r###"
+@   IN  SOA     venera      action\.domains (
+                            20     ; SERIAL
+                            7200   ; REFRESH
+                            600    ; RETRY
+                            3600000; EXPIRE
+                            60)    ; MINIMUM
+
+        ALIAS   cdn.example.net.
+"###,
+    );
+
+    let (origin, records) = Parser::new()
+        .parse(lexer, Some(Name::from_str("isi.edu").unwrap()), None)
+        .expect("failed to parse");
+
+    let key = RrKey::new(LowerName::from(origin), RecordType::ANAME);
+    let aname = records[&key]
+        .records_without_rrsigs()
+        .next()
+        .and_then(Record::data)
+        .and_then(RData::as_aname)
+        .expect("ALIAS is not an ANAME");
+    assert_eq!(*aname, Name::from_str("cdn.example.net.").unwrap());
+}
+
 #[test]
 fn test_named_root() {
     let lexer = Lexer::new(
@@ -510,3 +622,130 @@ fn test_named_root() {
     assert!(records.contains_key(&key));
     assert_eq!(records[&key].dns_class(), DNSClass::IN)
 }
+
+fn include_zone_path(file: &str) -> PathBuf {
+    let workspace = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    PathBuf::from(workspace)
+        .join("tests/test-data/named_test_configs/include")
+        .join(file)
+}
+
+fn a_record(records: &BTreeMap<RrKey, RecordSet>, name: &str) -> Option<Ipv4Addr> {
+    let key = RrKey::new(
+        LowerName::from(Name::from_str(name).unwrap()),
+        RecordType::A,
+    );
+    records
+        .get(&key)?
+        .records_without_rrsigs()
+        .next()
+        .and_then(Record::data)
+        .and_then(|rdata| match rdata {
+            RData::A(ip) => Some(*ip),
+            _ => None,
+        })
+}
+
+#[test]
+fn test_include() {
+    let (origin, records) = Parser::new()
+        .parse_file(&include_zone_path("example.com.zone"), None, None)
+        .expect("failed to parse");
+
+    assert_eq!(origin, Name::from_str("example.com.").unwrap());
+    assert_eq!(
+        a_record(&records, "ns.example.com."),
+        Some(Ipv4Addr::new(127, 0, 0, 1))
+    );
+
+    // included with the origin sub.example.com.
+    assert_eq!(
+        a_record(&records, "www.sub.example.com."),
+        Some(Ipv4Addr::new(127, 0, 0, 2))
+    );
+    assert_eq!(
+        a_record(&records, "host-2.sub.example.com."),
+        Some(Ipv4Addr::new(127, 0, 1, 2))
+    );
+
+    // the origin of the including file is unchanged
+    assert_eq!(
+        a_record(&records, "after.example.com."),
+        Some(Ipv4Addr::new(127, 0, 0, 4))
+    );
+    assert!(a_record(&records, "www.example.com.").is_none());
+}
+
+#[test]
+fn test_include_cycle() {
+    assert!(Parser::new()
+        .parse_file(&include_zone_path("cycle.zone"), None, None)
+        .is_err());
+}
+
+#[test]
+fn test_include_without_file() {
+    let lexer = Lexer::new(
+        r###"
+$ORIGIN example.com.
+$INCLUDE hosts.zone
+"###,
+    );
+
+    assert!(Parser::new()
+        .parse(lexer, None, Some(DNSClass::IN))
+        .is_err());
+}
+
+#[test]
+fn test_generate() {
+    let lexer = Lexer::new(
+        r###"
+$ORIGIN 0.0.10.in-addr.arpa.
+$TTL 3600
+$GENERATE 1-10/3 $ PTR host-${0,2}.example.com.
+$GENERATE 250-251 @ 300 IN A 10.0.0.$
+$GENERATE 0-1 ${10,3,x} 60 CNAME $
+"###,
+    );
+
+    let (_, records) = Parser::new()
+        .parse(lexer, None, Some(DNSClass::IN))
+        .expect("failed to parse");
+
+    for value in &[1, 4, 7, 10] {
+        let name = Name::from_str(&format!("{}.0.0.10.in-addr.arpa.", value)).unwrap();
+        let rrset = &records[&RrKey::new(LowerName::from(&name), RecordType::PTR)];
+        let record = rrset.records_without_rrsigs().next().unwrap();
+
+        assert_eq!(record.ttl(), 3600);
+        assert_eq!(
+            record.data(),
+            Some(&RData::PTR(
+                Name::from_str(&format!("host-{:02}.example.com.", value)).unwrap()
+            ))
+        );
+    }
+    assert!(!records.contains_key(&RrKey::new(
+        LowerName::from(Name::from_str("2.0.0.10.in-addr.arpa.").unwrap()),
+        RecordType::PTR
+    )));
+
+    let origin = Name::from_str("0.0.10.in-addr.arpa.").unwrap();
+    let rrset = &records[&RrKey::new(LowerName::from(&origin), RecordType::A)];
+    assert_eq!(rrset.records_without_rrsigs().count(), 2);
+    assert_eq!(rrset.ttl(), 300);
+
+    let rrset = &records[&RrKey::new(
+        LowerName::from(Name::from_str("00b.0.0.10.in-addr.arpa.").unwrap()),
+        RecordType::CNAME,
+    )];
+    let record = rrset.records_without_rrsigs().next().unwrap();
+    assert_eq!(record.ttl(), 60);
+    assert_eq!(
+        record.data(),
+        Some(&RData::CNAME(
+            Name::from_str("1.0.0.10.in-addr.arpa.").unwrap()
+        ))
+    );
+}
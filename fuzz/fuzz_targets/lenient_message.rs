@@ -0,0 +1,24 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use trust_dns_proto::op::{DecodeOptions, Message};
+
+fuzz_target!(|data: &[u8]| {
+    let options = DecodeOptions::lenient();
+
+    match (
+        Message::from_vec(data),
+        Message::from_vec_with_options(data, options),
+    ) {
+        (Ok(strict), Ok((lenient, diagnostics))) => {
+            // lenient decoding of a valid message must be the same as strict decoding
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            assert_eq!(strict, lenient);
+        }
+        (Ok(_), Err(e)) => panic!("lenient decoding failed where strict succeeded: {:?}", e),
+        (Err(_), Ok((_, diagnostics))) => {
+            assert!(!diagnostics.is_empty() && diagnostics.len() <= options.max_errors);
+        }
+        (Err(_), Err(_)) => (),
+    }
+});
@@ -0,0 +1,81 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use trust_dns_client::op::{Message, Query, ResponseCode};
+use trust_dns_client::rr::{Name, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_server::authority::{Authority, Catalog, MessageRequest};
+use trust_dns_server::server::{
+    AccessControl, Acl, AclOperation, Protocol, Request, RequestHandler,
+};
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+const ALLOWED: [u8; 4] = [192, 0, 2, 1];
+const OTHER: [u8; 4] = [198, 51, 100, 1];
+
+fn catalog() -> Catalog {
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+    catalog
+}
+
+fn access_control(operation: AclOperation, network: &str) -> AccessControl {
+    let mut access_control = AccessControl::default();
+    access_control.set_acl(operation, Some(Acl::new(vec![network.parse().unwrap()])));
+    access_control
+}
+
+async fn query(catalog: &Catalog, src: [u8; 4]) -> ResponseCode {
+    let mut message = Message::new();
+    message.add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+
+    let bytes = message.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(request, SocketAddr::from((src, 5553)), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&request, response_handler.clone())
+        .await;
+    response_handler.into_message().await.response_code()
+}
+
+#[tokio::test]
+async fn test_global_query_acl() {
+    let mut catalog = catalog();
+    catalog.set_access_control(access_control(AclOperation::Query, "192.0.2.0/24"));
+
+    assert_eq!(query(&catalog, ALLOWED).await, ResponseCode::NoError);
+    assert_eq!(query(&catalog, OTHER).await, ResponseCode::Refused);
+}
+
+#[tokio::test]
+async fn test_zone_acl_overrides_global_acl() {
+    let mut catalog = catalog();
+    catalog.set_access_control(access_control(AclOperation::Query, "192.0.2.0/24"));
+    catalog.set_zone_access_control(
+        Name::from_str("example.com.").unwrap().into(),
+        access_control(AclOperation::Query, "198.51.100.0/24"),
+    );
+
+    assert_eq!(query(&catalog, ALLOWED).await, ResponseCode::Refused);
+    assert_eq!(query(&catalog, OTHER).await, ResponseCode::NoError);
+}
+
+#[tokio::test]
+async fn test_acls_of_other_operations_allow_queries() {
+    let mut catalog = catalog();
+    catalog.set_access_control(access_control(AclOperation::Transfer, "192.0.2.0/24"));
+    catalog.set_zone_access_control(
+        Name::from_str("example.com.").unwrap().into(),
+        access_control(AclOperation::Update, "192.0.2.0/24"),
+    );
+
+    assert_eq!(query(&catalog, OTHER).await, ResponseCode::NoError);
+}
@@ -8,7 +8,7 @@ use trust_dns_client::{
 
 use trust_dns_server::{
     authority::{Authority, Catalog, MessageRequest, ZoneType},
-    server::{Protocol, Request},
+    server::{Protocol, Request, RequestHandler},
     store::in_memory::InMemoryAuthority,
 };
 
@@ -628,3 +628,48 @@ async fn test_multiple_cname_additionals() {
         &RData::A(Ipv4Addr::new(93, 184, 216, 34))
     );
 }
+
+async fn update_response_code(catalog: &Catalog, zone: &LowerName) -> ResponseCode {
+    let mut update: Message = Message::new();
+    update
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Update)
+        .add_zone(Query::query(zone.into(), RecordType::SOA));
+
+    let update_bytes = update.to_bytes().unwrap();
+    let update_req = MessageRequest::from_bytes(&update_bytes).unwrap();
+    let update_req = Request::new(update_req, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&update_req, response_handler.clone())
+        .await;
+    response_handler.into_message().await.response_code()
+}
+
+#[tokio::test]
+async fn test_update_frozen_zone() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), Box::new(Arc::new(example)));
+
+    // the in memory authority doesn't implement updates, but a frozen zone refuses them first
+    assert_eq!(
+        update_response_code(&catalog, &origin).await,
+        ResponseCode::NotImp
+    );
+
+    assert!(catalog.frozen_zones().freeze(origin.clone()));
+    assert_eq!(
+        update_response_code(&catalog, &origin).await,
+        ResponseCode::Refused
+    );
+
+    assert!(catalog.frozen_zones().thaw(&origin));
+    assert_eq!(
+        update_response_code(&catalog, &origin).await,
+        ResponseCode::NotImp
+    );
+}
@@ -276,11 +276,8 @@ fn test_notify() {
         io_loop.block_on(client.notify(name, DNSClass::IN, RecordType::A, None::<RecordSet>));
     assert!(message.is_ok());
     let message = message.unwrap();
-    assert_eq!(
-        message.response_code(),
-        ResponseCode::NotImp,
-        "the catalog must support Notify now, update this"
-    );
+    // a NOTIFY must be for the SOA of a zone, see RFC 1996
+    assert_eq!(message.response_code(), ResponseCode::FormErr);
 }
 
 // update tests
@@ -0,0 +1,192 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use trust_dns_client::op::{Edns, Message, Query};
+use trust_dns_client::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
+use trust_dns_client::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_proto::op::ResponseCode;
+use trust_dns_server::authority::{
+    Authority, Catalog, LookupError, LookupObject, LookupOptions, MessageRequest, UpdateResult,
+    ZoneType,
+};
+use trust_dns_server::server::{Protocol, Request, RequestHandler, RequestInfo};
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+const INTERNAL_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+const EXTERNAL_ADDR: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+
+fn www() -> Name {
+    Name::from_str("www.example.com.").unwrap()
+}
+
+struct Answers(Vec<Record>);
+
+impl LookupObject for Answers {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        Box::new(self.0.iter())
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+}
+
+/// Answers clients in 10.0.0.0/8 with the internal address, all others with the external one
+struct GeoAuthority {
+    origin: LowerName,
+}
+
+#[async_trait::async_trait]
+impl Authority for GeoAuthority {
+    type Lookup = Answers;
+
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Primary
+    }
+
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    fn client_subnet_scope(&self, _client_subnet: &ClientSubnet) -> u8 {
+        8
+    }
+
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        _rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        let internal = lookup_options.client_subnet().map_or(false, |subnet| {
+            subnet.address() == IpAddr::from([10, 0, 0, 0])
+        });
+        let address = if internal {
+            INTERNAL_ADDR
+        } else {
+            EXTERNAL_ADDR
+        };
+
+        Ok(Answers(vec![Record::from_rdata(
+            name.into(),
+            300,
+            RData::A(address),
+        )]))
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.lookup(
+            request_info.query.name(),
+            request_info.query.query_type(),
+            lookup_options,
+        )
+        .await
+    }
+
+    async fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        Ok(Answers(vec![]))
+    }
+}
+
+fn query_www(client_subnet: Option<&str>) -> Message {
+    let mut message = Message::new();
+    message.add_query(Query::query(www(), RecordType::A));
+
+    if let Some(client_subnet) = client_subnet {
+        let mut edns = Edns::new();
+        edns.options_mut().insert(EdnsOption::Subnet(
+            ClientSubnet::from_str(client_subnet).unwrap(),
+        ));
+        message.set_edns(edns);
+    }
+
+    message
+}
+
+async fn lookup(catalog: &Catalog, message: Message) -> Message {
+    let bytes = message.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(request, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&request, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+fn response_subnet(response: &Message) -> Option<ClientSubnet> {
+    match response.extensions().as_ref()?.option(EdnsCode::Subnet) {
+        Some(&EdnsOption::Subnet(subnet)) => Some(subnet),
+        _ => None,
+    }
+}
+
+fn answer(response: &Message) -> Option<&RData> {
+    response.answers().first().and_then(Record::data)
+}
+
+#[tokio::test]
+async fn test_client_subnet_echoed_with_zero_scope() {
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+
+    let response = lookup(&catalog, query_www(Some("192.0.2.0/24"))).await;
+    assert_eq!(
+        response_subnet(&response),
+        Some(ClientSubnet::new([192, 0, 2, 0].into(), 24, 0))
+    );
+
+    // no option in the response unless the request had one
+    let response = lookup(&catalog, query_www(None)).await;
+    assert_eq!(response_subnet(&response), None);
+}
+
+#[tokio::test]
+async fn test_client_subnet_aware_authority() {
+    let mut catalog = Catalog::new();
+    catalog.upsert(
+        www().base_name().into(),
+        Box::new(Arc::new(GeoAuthority {
+            origin: www().base_name().into(),
+        })),
+    );
+
+    let response = lookup(&catalog, query_www(Some("10.0.0.0/8"))).await;
+    assert_eq!(answer(&response), Some(&RData::A(INTERNAL_ADDR)));
+    assert_eq!(
+        response_subnet(&response),
+        Some(ClientSubnet::new([10, 0, 0, 0].into(), 8, 8))
+    );
+
+    let response = lookup(&catalog, query_www(Some("198.51.100.0/24"))).await;
+    assert_eq!(answer(&response), Some(&RData::A(EXTERNAL_ADDR)));
+    assert_eq!(
+        response_subnet(&response),
+        Some(ClientSubnet::new([198, 51, 100, 0].into(), 24, 8))
+    );
+}
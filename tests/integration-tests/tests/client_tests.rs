@@ -182,7 +182,7 @@ where
             .unwrap()
             .option(EdnsCode::Subnet)
             .unwrap(),
-        &EdnsOption::Unknown(EdnsCode::Subnet.into(), vec![0, 1, 16, 0, 1, 2])
+        &EdnsOption::Subnet("1.2.0.0/16".parse().unwrap())
     );
 
     if let RData::A(ref address) = *record.data().unwrap() {
@@ -0,0 +1,70 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use trust_dns_client::rr::Name;
+use trust_dns_server::server::{
+    bind_control_socket, send_control_command, serve_control, ControlCommand, ControlHandler,
+};
+
+/// Echos the commands, and fails to thaw any zone
+struct EchoHandler;
+
+#[async_trait::async_trait]
+impl ControlHandler for EchoHandler {
+    async fn handle_command(&self, command: ControlCommand) -> Result<String, String> {
+        match command {
+            ControlCommand::Thaw(zone) => Err(format!("zone is not frozen: {}", zone)),
+            ControlCommand::Flush => Ok(String::new()),
+            command => Ok(format!("executed: {}\nsecond line", command)),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_control_socket() {
+    let path = std::env::temp_dir().join(format!("trust-dns-control-{}.sock", std::process::id()));
+
+    let private_dir = std::env::temp_dir().join(format!(
+        ".trust-dns-control-{}.sock.{}",
+        std::process::id(),
+        std::process::id()
+    ));
+
+    // a stale socket file, and private directory, of a previous run are replaced
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    std::fs::create_dir(&private_dir).unwrap();
+    std::fs::write(private_dir.join("control.sock"), b"").unwrap();
+    let listener = bind_control_socket(&path).unwrap();
+    tokio::spawn(serve_control(listener, Arc::new(EchoHandler)));
+
+    // only the owner may connect
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // the socket is bound in a private directory, which is removed once the socket is moved
+    assert!(!private_dir.exists());
+
+    let output = send_control_command(&path, &ControlCommand::Stats)
+        .await
+        .unwrap();
+    assert_eq!(output, Ok("executed: stats\nsecond line".to_string()));
+
+    let output = send_control_command(&path, &ControlCommand::Flush)
+        .await
+        .unwrap();
+    assert_eq!(output, Ok(String::new()));
+
+    let zone = Name::from_str("example.com.").unwrap();
+    let output = send_control_command(&path, &ControlCommand::Thaw(zone))
+        .await
+        .unwrap();
+    assert_eq!(output, Err("zone is not frozen: example.com.".to_string()));
+
+    // the socket of a running server isn't replaced
+    assert!(bind_control_socket(&path).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+use tokio::runtime::Runtime;
+
+use trust_dns_client::op::{Message, Query};
+use trust_dns_client::rr::{Name, RecordType};
+use trust_dns_client::serialize::binary::BinEncodable;
+use trust_dns_server::authority::{Authority, Catalog};
+use trust_dns_server::dnstap::{Dnstap, DnstapConfig, DnstapMessageType};
+use trust_dns_server::ServerFuture;
+
+use trust_dns_integration::example_authority::create_example;
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[test]
+fn test_client_messages_logged_to_file() {
+    let path = std::env::temp_dir().join(format!("dnstap_tests_{}.fstrm", std::process::id()));
+    let dnstap = Dnstap::from_config(&DnstapConfig {
+        socket: None,
+        file: Some(path.clone()),
+        identity: Some("ns1.example.com".to_string()),
+        version: None,
+        message_types: vec![
+            DnstapMessageType::ClientQuery,
+            DnstapMessageType::ClientResponse,
+        ],
+    })
+    .expect("failed to start dnstap");
+
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let (query, response) = runtime.block_on(async move {
+        let example = create_example();
+        let mut catalog = Catalog::new();
+        catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let mut server = ServerFuture::new(catalog);
+        server.set_dnstap(Some(dnstap));
+        server.register_socket(server_socket);
+
+        let mut message = Message::new();
+        message.set_id(0x1234).set_recursion_desired(true);
+        message.add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let query = message.to_bytes().unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(&query, server_addr).await.unwrap();
+        let mut buffer = [0; 512];
+        let (len, from): (usize, SocketAddr) =
+            tokio::time::timeout(Duration::from_secs(5), client.recv_from(&mut buffer))
+                .await
+                .expect("no response")
+                .unwrap();
+        assert_eq!(from, server_addr);
+
+        (query, buffer[..len].to_vec())
+    });
+
+    // the server tasks hold the last clones of the dnstap, the file is finished once they are dropped
+    drop(runtime);
+
+    let logged = fs::read(&path).unwrap();
+    fs::remove_file(&path).ok();
+
+    assert!(contains(&logged, b"protobuf:dnstap.Dnstap"));
+    assert!(contains(&logged, b"ns1.example.com"));
+    assert!(contains(&logged, &query), "query not logged");
+    assert!(contains(&logged, &response), "response not logged");
+}
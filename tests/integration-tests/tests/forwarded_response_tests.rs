@@ -0,0 +1,198 @@
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use trust_dns_client::op::{Edns, Message, Query};
+use trust_dns_client::rr::rdata::opt::{EdnsCode, EdnsOption, ExtendedErrorCode};
+use trust_dns_client::rr::{LowerName, Name, RData, Record, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_proto::op::ResponseCode;
+use trust_dns_server::authority::{
+    Authority, Catalog, LookupError, LookupObject, LookupOptions, MessageRequest, UpdateResult,
+    ZoneType,
+};
+use trust_dns_server::server::{Protocol, Request, RequestHandler, RequestInfo};
+
+use trust_dns_integration::TestResponseHandler;
+
+struct Answers {
+    records: Vec<Record>,
+    authentic: bool,
+}
+
+impl LookupObject for Answers {
+    fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        Box::new(self.records.iter())
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+
+    fn is_authentic(&self) -> bool {
+        self.authentic
+    }
+}
+
+/// A validating resolver, names starting with `secure` are authentic, `bogus` fail to validate
+struct ValidatingAuthority {
+    origin: LowerName,
+}
+
+#[async_trait::async_trait]
+impl Authority for ValidatingAuthority {
+    type Lookup = Answers;
+
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Hint
+    }
+
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        _rtype: RecordType,
+        _lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        let name = Name::from(name);
+        let label = String::from_utf8_lossy(name.iter().next().unwrap()).into_owned();
+        if label == "bogus" {
+            return Err(LookupError::ExtendedError {
+                response_code: ResponseCode::ServFail,
+                info_code: ExtendedErrorCode::DnssecBogus,
+                extra_text: format!("validation of {} failed", name),
+            });
+        }
+
+        Ok(Answers {
+            records: vec![Record::from_rdata(
+                name,
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            )],
+            authentic: label == "secure",
+        })
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        self.lookup(
+            request_info.query.name(),
+            request_info.query.query_type(),
+            lookup_options,
+        )
+        .await
+    }
+
+    async fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        Ok(Answers {
+            records: vec![],
+            authentic: false,
+        })
+    }
+}
+
+fn catalog() -> Catalog {
+    let mut catalog = Catalog::new();
+    catalog.upsert(
+        Name::root().into(),
+        Box::new(Arc::new(ValidatingAuthority {
+            origin: Name::root().into(),
+        })),
+    );
+    catalog
+}
+
+async fn lookup(catalog: &Catalog, name: &str, dnssec_ok: bool, authentic_data: bool) -> Message {
+    let mut message = Message::new();
+    message
+        .add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A))
+        .set_recursion_desired(true)
+        .set_authentic_data(authentic_data);
+    let mut edns = Edns::new();
+    edns.set_dnssec_ok(dnssec_ok);
+    message.set_edns(edns);
+
+    let bytes = message.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(request, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&request, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+#[tokio::test]
+async fn test_authentic_data() {
+    let catalog = catalog();
+
+    let response = lookup(&catalog, "secure.example.com.", false, true).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.authentic_data());
+
+    // the DO bit is only known to the server with DNSSEC support
+    #[cfg(feature = "dnssec")]
+    {
+        let response = lookup(&catalog, "secure.example.com.", true, false).await;
+        assert!(response.authentic_data());
+    }
+
+    // AD is only set for clients which requested it, or DNSSEC records
+    let response = lookup(&catalog, "secure.example.com.", false, false).await;
+    assert_eq!(response.answers().len(), 1);
+    assert!(!response.authentic_data());
+
+    let response = lookup(&catalog, "insecure.example.com.", true, true).await;
+    assert_eq!(response.answers().len(), 1);
+    assert!(!response.authentic_data());
+}
+
+#[tokio::test]
+async fn test_extended_error() {
+    let catalog = catalog();
+
+    let response = lookup(&catalog, "bogus.example.com.", true, true).await;
+    assert_eq!(response.response_code(), ResponseCode::ServFail);
+    assert!(response.answers().is_empty());
+    assert!(!response.authentic_data());
+
+    match response
+        .extensions()
+        .as_ref()
+        .unwrap()
+        .option(EdnsCode::ExtendedError)
+    {
+        Some(EdnsOption::ExtendedError {
+            info_code,
+            extra_text,
+        }) => {
+            assert_eq!(*info_code, ExtendedErrorCode::DnssecBogus);
+            assert_eq!(extra_text, "validation of bogus.example.com. failed");
+        }
+        option => panic!("expected an extended error: {:?}", option),
+    }
+}
@@ -0,0 +1,228 @@
+#![cfg(feature = "dnssec-openssl")]
+
+use std::convert::TryInto;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use time::Duration;
+
+use trust_dns_client::rr::rdata::{DNSSECRData, DNSKEY};
+use trust_dns_client::rr::dnssec::{Algorithm, KeyPair, SigSigner};
+use trust_dns_client::rr::{Name, RData, RecordSet, RecordType, RrKey};
+use trust_dns_server::authority::{DnssecAuthority, KeyRole, KeyTimings, RolloverMethod};
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+
+use trust_dns_integration::example_authority::create_example;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn signer(role: KeyRole) -> SigSigner {
+    let key = KeyPair::generate(Algorithm::ECDSAP256SHA256).unwrap();
+    let dnskey = DNSKEY::new(
+        true,
+        role.is_secure_entry_point(),
+        false,
+        Algorithm::ECDSAP256SHA256,
+        key.to_public_bytes().unwrap(),
+    );
+
+    SigSigner::dnssec(
+        dnskey,
+        key,
+        Name::from_str("example.com.").unwrap(),
+        Duration::weeks(1).try_into().unwrap(),
+    )
+}
+
+async fn rrset(authority: &InMemoryAuthority, name: &str, record_type: RecordType) -> RecordSet {
+    let key = RrKey::new(Name::from_str(name).unwrap().into(), record_type);
+
+    authority
+        .records()
+        .await
+        .get(&key)
+        .map(|rrset| RecordSet::clone(rrset))
+        .unwrap_or_else(|| RecordSet::new(&Name::from_str(name).unwrap(), record_type, 0))
+}
+
+/// The key tags of the keys which signed the record set
+async fn signed_by(authority: &InMemoryAuthority, name: &str, record_type: RecordType) -> Vec<u16> {
+    let mut key_tags = rrset(authority, name, record_type)
+        .await
+        .rrsigs()
+        .iter()
+        .filter_map(|rrsig| match rrsig.data() {
+            Some(RData::DNSSEC(DNSSECRData::SIG(sig))) => Some(sig.key_tag()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    key_tags.sort_unstable();
+    key_tags
+}
+
+async fn key_count(authority: &InMemoryAuthority, record_type: RecordType) -> usize {
+    rrset(authority, "example.com.", record_type)
+        .await
+        .records_without_rrsigs()
+        .count()
+}
+
+#[tokio::test]
+async fn test_split_key_roles() {
+    let mut authority = create_example();
+    let ksk = signer(KeyRole::KeySigning);
+    let zsk = signer(KeyRole::ZoneSigning);
+    let ksk_tag = ksk.calculate_key_tag().unwrap();
+    let zsk_tag = zsk.calculate_key_tag().unwrap();
+
+    authority
+        .add_scheduled_signing_key_mut(ksk, KeyRole::KeySigning, KeyTimings::default())
+        .unwrap();
+    authority
+        .add_scheduled_signing_key_mut(zsk, KeyRole::ZoneSigning, KeyTimings::default())
+        .unwrap();
+    authority.secure_zone_mut().unwrap();
+
+    assert_eq!(key_count(&authority, RecordType::DNSKEY).await, 2);
+    assert_eq!(key_count(&authority, RecordType::CDS).await, 1);
+    assert_eq!(key_count(&authority, RecordType::CDNSKEY).await, 1);
+
+    assert_eq!(
+        signed_by(&authority, "example.com.", RecordType::DNSKEY).await,
+        vec![ksk_tag]
+    );
+    assert_eq!(
+        signed_by(&authority, "example.com.", RecordType::CDS).await,
+        vec![ksk_tag]
+    );
+    assert_eq!(
+        signed_by(&authority, "example.com.", RecordType::SOA).await,
+        vec![zsk_tag]
+    );
+    assert_eq!(
+        signed_by(&authority, "www.example.com.", RecordType::A).await,
+        vec![zsk_tag]
+    );
+}
+
+#[tokio::test]
+async fn test_mismatched_role_is_rejected() {
+    let mut authority = create_example();
+
+    // the DNSKEY of the signer is a secure entry point
+    assert!(authority
+        .add_scheduled_signing_key_mut(
+            signer(KeyRole::KeySigning),
+            KeyRole::ZoneSigning,
+            KeyTimings::default()
+        )
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_zsk_pre_publish_rollover() {
+    let now = now();
+    let (current, next) =
+        RolloverMethod::PrePublish.schedule(KeyTimings::default(), now + 1000, 500);
+
+    let mut authority = create_example();
+    let ksk = signer(KeyRole::KeySigning);
+    let current_zsk = signer(KeyRole::ZoneSigning);
+    let next_zsk = signer(KeyRole::ZoneSigning);
+    let current_tag = current_zsk.calculate_key_tag().unwrap();
+    let next_tag = next_zsk.calculate_key_tag().unwrap();
+
+    authority
+        .add_scheduled_signing_key_mut(ksk, KeyRole::KeySigning, KeyTimings::default())
+        .unwrap();
+    authority
+        .add_scheduled_signing_key_mut(current_zsk, KeyRole::ZoneSigning, current)
+        .unwrap();
+    authority
+        .add_scheduled_signing_key_mut(next_zsk, KeyRole::ZoneSigning, next)
+        .unwrap();
+    authority.secure_zone_mut().unwrap();
+    let serial = authority.serial().await;
+
+    // nothing is scheduled yet
+    assert!(!authority.maintain_keys(now).await.unwrap());
+    assert_eq!(authority.serial().await, serial);
+    assert_eq!(key_count(&authority, RecordType::DNSKEY).await, 2);
+
+    // the successor is published ahead of the rollover
+    assert!(authority.maintain_keys(now + 500).await.unwrap());
+    assert!(authority.serial().await > serial);
+    assert_eq!(key_count(&authority, RecordType::DNSKEY).await, 3);
+    assert_eq!(
+        signed_by(&authority, "www.example.com.", RecordType::A).await,
+        vec![current_tag]
+    );
+
+    // the successor takes over signing
+    assert!(authority.maintain_keys(now + 1000).await.unwrap());
+    assert_eq!(key_count(&authority, RecordType::DNSKEY).await, 3);
+    assert_eq!(
+        signed_by(&authority, "www.example.com.", RecordType::A).await,
+        vec![next_tag]
+    );
+
+    // and the retired key is removed once its signatures have expired from caches
+    assert!(authority.maintain_keys(now + 1500).await.unwrap());
+    assert_eq!(key_count(&authority, RecordType::DNSKEY).await, 2);
+    assert!(!authority.maintain_keys(now + 1600).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_ksk_double_signature_rollover() {
+    let now = now();
+    let (current, next) =
+        RolloverMethod::DoubleSignature.schedule(KeyTimings::default(), now + 1000, 500);
+
+    let mut authority = create_example();
+    let current_ksk = signer(KeyRole::KeySigning);
+    let next_ksk = signer(KeyRole::KeySigning);
+    let current_tag = current_ksk.calculate_key_tag().unwrap();
+    let next_tag = next_ksk.calculate_key_tag().unwrap();
+
+    authority
+        .add_scheduled_signing_key_mut(current_ksk, KeyRole::KeySigning, current)
+        .unwrap();
+    authority
+        .add_scheduled_signing_key_mut(next_ksk, KeyRole::KeySigning, next)
+        .unwrap();
+    authority
+        .add_scheduled_signing_key_mut(
+            signer(KeyRole::ZoneSigning),
+            KeyRole::ZoneSigning,
+            KeyTimings::default(),
+        )
+        .unwrap();
+    authority.secure_zone_mut().unwrap();
+
+    assert_eq!(key_count(&authority, RecordType::CDS).await, 1);
+
+    // both keys sign the DNSKEYs, and the parent may switch its DS to either
+    assert!(authority.maintain_keys(now + 500).await.unwrap());
+    let mut both = vec![current_tag, next_tag];
+    both.sort_unstable();
+    assert_eq!(
+        signed_by(&authority, "example.com.", RecordType::DNSKEY).await,
+        both
+    );
+    assert_eq!(key_count(&authority, RecordType::CDS).await, 2);
+    assert_eq!(key_count(&authority, RecordType::CDNSKEY).await, 2);
+
+    // the current key is removed at the rollover
+    assert!(authority.maintain_keys(now + 1000).await.unwrap());
+    assert_eq!(
+        signed_by(&authority, "example.com.", RecordType::DNSKEY).await,
+        vec![next_tag]
+    );
+    assert_eq!(key_count(&authority, RecordType::DNSKEY).await, 2);
+    assert_eq!(key_count(&authority, RecordType::CDS).await, 1);
+}
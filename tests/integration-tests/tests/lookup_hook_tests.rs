@@ -0,0 +1,95 @@
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use trust_dns_client::op::{Message, Query, ResponseCode};
+use trust_dns_client::rr::{Name, RData, Record, RecordSet, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_server::authority::{
+    AuthLookup, Catalog, LookupError, LookupHook, LookupObject, LookupOptions, LookupRecords,
+    MessageRequest,
+};
+use trust_dns_server::server::{Protocol, Request, RequestHandler, RequestInfo};
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+/// Answers `svc.example.com.` with a different address for each query, round-robin
+struct RoundRobin {
+    next: AtomicU8,
+}
+
+#[async_trait::async_trait]
+impl LookupHook for RoundRobin {
+    async fn lookup(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> Option<Result<Box<dyn LookupObject>, LookupError>> {
+        let name = Name::from(request_info.query.name());
+        if name != Name::from_str("svc.example.com.").unwrap() {
+            return None;
+        }
+
+        let next = self.next.fetch_add(1, Ordering::Relaxed) % 2;
+        let mut rrset = RecordSet::new(&name, RecordType::A, 0);
+        rrset.insert(
+            Record::from_rdata(name, 60, RData::A(Ipv4Addr::new(192, 0, 2, next))),
+            0,
+        );
+
+        let lookup = AuthLookup::answers(LookupRecords::new(lookup_options, rrset.into()), None);
+        Some(Ok(Box::new(lookup)))
+    }
+}
+
+async fn lookup(catalog: &Catalog, name: &str) -> Message {
+    let mut question = Message::new();
+    question.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+
+    let bytes = question.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(request, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&request, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+fn answers(response: &Message) -> Vec<RData> {
+    response
+        .answers()
+        .iter()
+        .filter_map(|r| r.data().cloned())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_lookup_hook() {
+    let mut catalog = Catalog::new();
+    catalog.add_authority(create_example());
+    catalog.add_lookup_hook(Box::new(RoundRobin {
+        next: AtomicU8::new(0),
+    }));
+
+    for i in 0..3 {
+        let response = lookup(&catalog, "svc.example.com.").await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.header().authoritative());
+        assert_eq!(
+            answers(&response),
+            vec![RData::A(Ipv4Addr::new(192, 0, 2, i % 2))]
+        );
+    }
+
+    // other names are answered by the zone
+    let response = lookup(&catalog, "www.example.com.").await;
+    assert_eq!(
+        answers(&response),
+        vec![RData::A(Ipv4Addr::new(93, 184, 216, 34))]
+    );
+
+    let response = lookup(&catalog, "nothing.example.com.").await;
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+}
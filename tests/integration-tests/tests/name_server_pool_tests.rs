@@ -114,6 +114,7 @@ fn mock_nameserver_on_send_nx<O: OnSend + Unpin>(
             #[cfg(any(feature = "dns-over-rustls", feature = "dns-over-https-rustls"))]
             tls_config: None,
             bind_addr: None,
+            http_endpoint: None,
         },
         options,
         client,
@@ -554,6 +555,72 @@ fn test_return_error_from_highest_priority_nameserver() {
     }
 }
 
+// === Backoff ===
+
+#[derive(Clone, Default)]
+struct OnSendCounter {
+    count: Arc<AtomicIsize>,
+}
+
+impl OnSend for OnSendCounter {
+    fn on_send<E>(
+        &mut self,
+        response: Result<DnsResponse, E>,
+    ) -> Pin<Box<dyn Future<Output = Result<DnsResponse, E>> + Send>>
+    where
+        E: From<ProtoError> + Send + 'static,
+    {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        Box::pin(future::ready(response))
+    }
+}
+
+#[test]
+fn test_failing_server_backoff() {
+    use trust_dns_proto::error::ProtoErrorKind;
+
+    let mut options = ResolverOpts::default();
+    options.num_concurrent_reqs = 1;
+    options.server_ordering_strategy = ServerOrderingStrategy::UserProvidedOrder;
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+
+    let failing = OnSendCounter::default();
+    let failing_nameserver = mock_nameserver_on_send_nx(
+        vec![Err(ResolveError::from(ProtoError::from(
+            ProtoErrorKind::Timeout,
+        )))],
+        options,
+        failing.clone(),
+        Ipv4Addr::new(128, 0, 0, 1).into(),
+        false,
+    );
+    let working_nameserver = mock_nameserver_on_send_nx(
+        vec![Ok(message(query.clone(), vec![record.clone()], vec![], vec![]).into()); 2],
+        options,
+        OnSendCounter::default(),
+        Ipv4Addr::new(129, 0, 0, 1).into(),
+        false,
+    );
+
+    let mut pool = mock_nameserver_pool_on_send(
+        vec![failing_nameserver, working_nameserver],
+        vec![],
+        None,
+        options,
+    );
+
+    for _ in 0..2 {
+        let request = message(query.clone(), vec![], vec![], vec![]);
+        let response = block_on(pool.send(request).first_answer()).unwrap();
+        assert_eq!(response.answers()[0], record);
+    }
+
+    // the failing name server is queried after the working one during its cool-down
+    assert_eq!(failing.count.load(Ordering::Relaxed), 1);
+}
+
 // === Concurrent requests ===
 
 #[derive(Clone)]
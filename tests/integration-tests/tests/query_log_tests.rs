@@ -0,0 +1,76 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use trust_dns_client::op::{Message, Query};
+use trust_dns_client::rr::{Name, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_server::authority::{Authority, Catalog, MessageRequest};
+use trust_dns_server::server::{
+    Protocol, QueryLog, QueryLogConfig, QueryLogFormat, Request, RequestHandler,
+};
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+async fn query(catalog: &Catalog, name: &str) {
+    let mut message = Message::new();
+    message.set_id(4660);
+    message.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+
+    let bytes = message.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(
+        request,
+        SocketAddr::from(([192, 0, 2, 1], 5353)),
+        Protocol::Udp,
+    );
+
+    catalog
+        .handle_request(&request, TestResponseHandler::new())
+        .await;
+}
+
+#[tokio::test]
+async fn test_queries_logged_as_json() {
+    let dir = std::env::temp_dir().join(format!("query_log_tests_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("queries.log");
+    fs::remove_file(&path).ok();
+
+    let config = QueryLogConfig {
+        path: path.clone(),
+        format: QueryLogFormat::Json,
+        sample_rate: 1.0,
+        error_sample_rate: 1.0,
+        max_size: None,
+        rotate_interval: None,
+        max_files: 0,
+    };
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+    catalog.set_query_log(Some(QueryLog::from_config(&config).unwrap()));
+
+    query(&catalog, "www.example.com.").await;
+    query(&catalog, "www.example.net.").await;
+
+    // the writer finishes once the last clone of the log is dropped
+    drop(catalog);
+
+    let logged = fs::read_to_string(&path).unwrap();
+    fs::remove_dir_all(&dir).ok();
+
+    let lines: Vec<&str> = logged.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", logged);
+
+    assert!(lines[0].contains("\"client\":\"192.0.2.1:5353\""));
+    assert!(lines[0].contains("\"protocol\":\"UDP\",\"id\":4660,"));
+    assert!(lines[0].contains("\"qname\":\"www.example.com.\""));
+    assert!(lines[0].contains("\"rcode\":\"NoError\""));
+    assert!(lines[0].contains("\"zone\":\"example.com.\",\"source\":\"authoritative\""));
+
+    assert!(lines[1].contains("\"qname\":\"www.example.net.\""));
+    assert!(lines[1].contains("\"rcode\":\"Refused\""));
+    assert!(lines[1].contains("\"source\":\"none\""));
+}
@@ -0,0 +1,99 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use trust_dns_client::op::{Message, Query, ResponseCode};
+use trust_dns_client::rr::{Name, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_server::authority::{Authority, Catalog, MessageRequest};
+use trust_dns_server::server::{
+    Protocol, RateLimits, Request, RequestHandler, ResponseRateLimiter,
+};
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+fn catalog(limits: RateLimits) -> Catalog {
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+    catalog.set_rate_limiter(Some(ResponseRateLimiter::new(limits)));
+    catalog
+}
+
+/// Sends the query to the catalog, returns None if the response was dropped
+async fn query(catalog: &Catalog, name: &str, src: [u8; 4], protocol: Protocol) -> Option<Message> {
+    let mut message = Message::new();
+    message.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+
+    let bytes = message.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(request, SocketAddr::from((src, 5553)), protocol);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&request, response_handler.clone())
+        .await;
+
+    tokio::time::timeout(Duration::from_millis(100), response_handler.into_message())
+        .await
+        .ok()
+}
+
+#[tokio::test]
+async fn test_responses_limited_over_udp() {
+    let catalog = catalog(RateLimits {
+        responses_per_second: 2,
+        slip: 2,
+        ..RateLimits::default()
+    });
+    let src = [192, 0, 2, 1];
+
+    for _ in 0..2 {
+        let response = query(&catalog, "www.example.com.", src, Protocol::Udp)
+            .await
+            .expect("response within the limit");
+        assert!(!response.truncated());
+        assert!(!response.answers().is_empty());
+    }
+
+    // over the limit, every other response is dropped, the others are truncated
+    assert!(query(&catalog, "www.example.com.", src, Protocol::Udp)
+        .await
+        .is_none());
+    let response = query(&catalog, "www.example.com.", src, Protocol::Udp)
+        .await
+        .expect("slipped response");
+    assert!(response.truncated());
+    assert!(response.answers().is_empty());
+    assert_eq!(response.queries().len(), 1);
+
+    // other names and protocols are unaffected
+    assert!(query(&catalog, "example.com.", src, Protocol::Udp)
+        .await
+        .is_some());
+    let response = query(&catalog, "www.example.com.", src, Protocol::Tcp)
+        .await
+        .unwrap();
+    assert!(!response.truncated());
+}
+
+#[tokio::test]
+async fn test_nxdomains_limited_by_zone() {
+    let catalog = catalog(RateLimits {
+        nxdomains_per_second: 1,
+        slip: 0,
+        ..RateLimits::default()
+    });
+    let src = [192, 0, 2, 1];
+
+    let response = query(&catalog, "a.example.com.", src, Protocol::Udp)
+        .await
+        .unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+    // a different name in the same zone doesn't evade the limit
+    assert!(query(&catalog, "b.example.com.", src, Protocol::Udp)
+        .await
+        .is_none());
+}
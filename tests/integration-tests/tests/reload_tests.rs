@@ -0,0 +1,60 @@
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use trust_dns_client::op::{Message, Query, ResponseCode};
+use trust_dns_client::rr::{Name, RData, Record, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_server::authority::{Catalog, MessageRequest};
+use trust_dns_server::server::{Protocol, ReloadableHandler, Request, RequestHandler};
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+async fn lookup<T: RequestHandler>(handler: &T, name: &str) -> Message {
+    let mut question = Message::new();
+    question.add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+
+    let bytes = question.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(request, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    handler
+        .handle_request(&request, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+#[tokio::test]
+async fn test_reload_catalog() {
+    let mut catalog = Catalog::new();
+    catalog.add_authority(create_example());
+
+    let handler = ReloadableHandler::new(catalog);
+    let reload_handle = handler.reload_handle();
+
+    let response = lookup(&handler, "new.example.com.").await;
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+    // the new zone has a record which the previous one doesn't
+    let new_name = Name::from_str("new.example.com.").unwrap();
+    let mut authority = create_example();
+    authority.upsert_mut(
+        Record::from_rdata(new_name, 86400, RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+        0,
+    );
+    let mut catalog = Catalog::new();
+    catalog.add_authority(authority);
+
+    let previous = reload_handle.replace(catalog).await;
+
+    let response = lookup(&handler, "new.example.com.").await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        response.answers()[0].data(),
+        Some(&RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+
+    // the previous catalog is still usable, e.g. by requests which were in flight
+    let response = lookup(&*previous, "new.example.com.").await;
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+}
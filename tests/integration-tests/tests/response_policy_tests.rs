@@ -0,0 +1,175 @@
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use trust_dns_client::op::{Message, Query, ResponseCode};
+use trust_dns_client::rr::rdata::SOA;
+use trust_dns_client::rr::{Name, RData, Record, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_server::authority::{
+    Authority, Catalog, MessageRequest, ResponsePolicyZone, ZoneType,
+};
+use trust_dns_server::server::{Protocol, Request};
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+fn record(name: &str, rdata: RData) -> Record {
+    Record::from_rdata(Name::from_str(name).unwrap(), 300, rdata)
+}
+
+fn cname(name: &str, target: &str) -> Record {
+    record(name, RData::CNAME(Name::from_str(target).unwrap()))
+}
+
+/// A policy zone with the rules, named `rpz.`
+fn policy_zone(rules: Vec<Record>) -> ResponsePolicyZone {
+    let origin = Name::from_str("rpz.").unwrap();
+    let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, false);
+    authority.upsert_mut(
+        Record::from_rdata(
+            origin,
+            300,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.rpz.").unwrap(),
+                Name::from_str("admin.rpz.").unwrap(),
+                1,
+                3600,
+                600,
+                86400,
+                300,
+            )),
+        ),
+        0,
+    );
+    for rule in rules {
+        authority.upsert_mut(rule, 0);
+    }
+
+    ResponsePolicyZone::new(Box::new(Arc::new(authority)))
+}
+
+fn catalog(policies: Vec<ResponsePolicyZone>) -> Catalog {
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+    for policy in policies {
+        catalog.add_response_policy(policy);
+    }
+
+    catalog
+}
+
+/// Sends the query to the catalog, returns None if no response was sent
+async fn lookup(catalog: &Catalog, name: &str, record_type: RecordType) -> Option<Message> {
+    let mut question = Message::new();
+    question.add_query(Query::query(Name::from_str(name).unwrap(), record_type));
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+    let question_req = Request::new(question_req, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(&question_req, None, response_handler.clone())
+        .await;
+
+    tokio::time::timeout(Duration::from_millis(100), response_handler.into_message())
+        .await
+        .ok()
+}
+
+#[tokio::test]
+async fn test_qname_nxdomain() {
+    let catalog = catalog(vec![policy_zone(vec![cname("www.example.com.rpz.", ".")])]);
+
+    let response = lookup(&catalog, "www.example.com.", RecordType::A)
+        .await
+        .unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    assert!(response.answers().is_empty());
+    assert!(!response.header().authoritative());
+
+    // other names are unaffected
+    let response = lookup(&catalog, "example.com.", RecordType::A)
+        .await
+        .unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+}
+
+#[tokio::test]
+async fn test_qname_local_data() {
+    let catalog = catalog(vec![policy_zone(vec![record(
+        "*.example.com.rpz.",
+        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+    )])]);
+
+    let response = lookup(&catalog, "www.example.com.", RecordType::A)
+        .await
+        .unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers().len(), 1);
+    assert_eq!(
+        response.answers()[0].name(),
+        &Name::from_str("www.example.com.").unwrap()
+    );
+    assert_eq!(
+        response.answers()[0].data(),
+        Some(&RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+
+    // no local data of the type, NODATA
+    let response = lookup(&catalog, "www.example.com.", RecordType::AAAA)
+        .await
+        .unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.answers().is_empty());
+}
+
+#[tokio::test]
+async fn test_response_ip_nodata() {
+    let catalog = catalog(vec![policy_zone(vec![cname(
+        "24.0.216.184.93.rpz-ip.rpz.",
+        "*.",
+    )])]);
+
+    let response = lookup(&catalog, "www.example.com.", RecordType::A)
+        .await
+        .unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.answers().is_empty());
+
+    // the AAAA answer doesn't match the trigger
+    let response = lookup(&catalog, "www.example.com.", RecordType::AAAA)
+        .await
+        .unwrap();
+    assert!(!response.answers().is_empty());
+}
+
+#[tokio::test]
+async fn test_nsdname_drop() {
+    let catalog = catalog(vec![policy_zone(vec![cname(
+        "a.iana-servers.net.rpz-nsdname.rpz.",
+        "rpz-drop.",
+    )])]);
+
+    assert!(lookup(&catalog, "www.example.com.", RecordType::A)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_passthru_skips_later_zones() {
+    let catalog = catalog(vec![
+        policy_zone(vec![cname("www.example.com.rpz.", "rpz-passthru.")]),
+        policy_zone(vec![cname("*.example.com.rpz.", ".")]),
+    ]);
+
+    let response = lookup(&catalog, "www.example.com.", RecordType::A)
+        .await
+        .unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+}
@@ -1,3 +1,4 @@
+use std::io::{Read, Write};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -16,9 +17,13 @@ use trust_dns_client::rr::*;
 use trust_dns_client::tcp::TcpClientConnection;
 use trust_dns_client::udp::UdpClientConnection;
 use trust_dns_proto::error::ProtoError;
+use trust_dns_proto::rr::rdata::opt::{EdnsCode, EdnsOption};
 use trust_dns_proto::xfer::DnsRequestSender;
 
 use trust_dns_server::authority::{Authority, Catalog};
+#[cfg(unix)]
+use trust_dns_server::server::ListenFds;
+use trust_dns_server::server::{ConnectionLimits, CookieConfig, ProxyProtocol, ShutdownHandle};
 use trust_dns_server::ServerFuture;
 
 use trust_dns_integration::example_authority::create_example;
@@ -27,6 +32,8 @@ use trust_dns_integration::example_authority::create_example;
 use rustls::RootCertStore;
 #[cfg(feature = "dns-over-rustls")]
 use trust_dns_integration::tls_client_connection::TlsClientConnection;
+#[cfg(feature = "dns-over-rustls")]
+use trust_dns_server::server::{ReloadableCertificate, TlsCertificate};
 
 #[test]
 fn test_server_www_udp() {
@@ -178,6 +185,214 @@ fn test_server_form_error_on_multiple_queries() {
     server_thread.join().unwrap();
 }
 
+#[test]
+fn test_server_tcp_keepalive() {
+    let (addr, shutdown, server_thread) = start_tcp_server(ConnectionLimits::default());
+
+    let mut stream = connect_tcp(addr);
+    let response = tcp_exchange(&mut stream, &www_query(true)).expect("no response");
+    assert_eq!(
+        response
+            .extensions()
+            .as_ref()
+            .and_then(|edns| edns.option(EdnsCode::Keepalive)),
+        Some(&EdnsOption::TcpKeepalive(Some(300)))
+    );
+
+    // the timeout is only advertised to clients which support the option
+    let response = tcp_exchange(&mut stream, &www_query(false)).expect("no response");
+    assert!(response
+        .extensions()
+        .as_ref()
+        .and_then(|edns| edns.option(EdnsCode::Keepalive))
+        .is_none());
+
+    shutdown.shutdown();
+    server_thread.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_server_tcp_connection_limits() {
+    let (addr, shutdown, server_thread) = start_tcp_server(ConnectionLimits {
+        max_connections: None,
+        max_connections_per_client: Some(1),
+    });
+
+    let mut first = connect_tcp(addr);
+    assert!(tcp_exchange(&mut first, &www_query(false)).is_some());
+
+    // the second connection of the client is closed as soon as it is accepted
+    let mut second = connect_tcp(addr);
+    assert!(tcp_exchange(&mut second, &www_query(false)).is_none());
+
+    // closing the first makes room for another
+    drop(first);
+    let mut third = None;
+    for _ in 0..50 {
+        let mut stream = connect_tcp(addr);
+        if tcp_exchange(&mut stream, &www_query(false)).is_some() {
+            third = Some(stream);
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert!(third.is_some(), "connection was not accepted");
+
+    shutdown.shutdown();
+    server_thread.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_server_graceful_shutdown() {
+    let (addr, shutdown, server_thread) = start_tcp_server(ConnectionLimits::default());
+
+    let mut stream = connect_tcp(addr);
+    assert!(tcp_exchange(&mut stream, &www_query(false)).is_some());
+
+    // the idle connection is closed, and the server stops
+    shutdown.shutdown();
+    server_thread.join().unwrap().unwrap();
+
+    let mut buf = [0_u8; 2];
+    assert_eq!(stream.read(&mut buf).unwrap_or(0), 0);
+    assert!(std::net::TcpStream::connect(addr).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_server_hand_off() {
+    let (addr, shutdown, server_thread) = start_tcp_server(ConnectionLimits::default());
+
+    let mut listen_fds = shutdown.hand_off();
+    server_thread.join().unwrap().unwrap();
+
+    // the listener stays open, its connections wait for the process taking over
+    let stream = connect_tcp(addr);
+    let listener = listen_fds.take_tcp(ListenFds::TCP).unwrap().remove(0);
+    assert!(listen_fds.is_empty());
+
+    listener.set_nonblocking(false).unwrap();
+    let (_accepted, peer) = listener.accept().unwrap();
+    assert_eq!(peer, stream.local_addr().unwrap());
+}
+
+#[test]
+fn test_server_tcp_proxy_protocol() {
+    let (addr, shutdown, server_thread) = start_tcp_server_with(|server| {
+        server.set_proxy_protocol(ProxyProtocol {
+            proxies: vec!["127.0.0.0/8".parse().unwrap()],
+        })
+    });
+
+    // the header of a connection from 192.0.2.1:12345
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c".to_vec();
+    header.extend_from_slice(&[192, 0, 2, 1, 127, 0, 0, 1, 0x30, 0x39, 0, 53]);
+
+    let mut stream = connect_tcp(addr);
+    stream.write_all(&header).unwrap();
+    let response = tcp_exchange(&mut stream, &www_query(false)).expect("no response");
+    assert_eq!(response.answer_count(), 1);
+
+    // connections from the proxies without the header are closed
+    let mut stream = connect_tcp(addr);
+    assert!(tcp_exchange(&mut stream, &www_query(false)).is_none());
+
+    shutdown.shutdown();
+    server_thread.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_server_tcp_proxy_protocol_connection_limits() {
+    let (addr, shutdown, server_thread) = start_tcp_server_with(|server| {
+        server.set_connection_limits(ConnectionLimits {
+            max_connections: None,
+            max_connections_per_client: Some(1),
+        });
+        server.set_proxy_protocol(ProxyProtocol {
+            proxies: vec!["127.0.0.0/8".parse().unwrap()],
+        })
+    });
+    let proxied = |client: u8| {
+        let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c".to_vec();
+        header.extend_from_slice(&[192, 0, 2, client, 127, 0, 0, 1, 0x30, 0x39, 0, 53]);
+
+        let mut stream = connect_tcp(addr);
+        stream.write_all(&header).unwrap();
+        stream
+    };
+
+    // the connections are counted for the clients behind the proxy
+    let mut first = proxied(1);
+    assert!(tcp_exchange(&mut first, &www_query(false)).is_some());
+    let mut second = proxied(2);
+    assert!(tcp_exchange(&mut second, &www_query(false)).is_some());
+
+    let mut third = proxied(1);
+    assert!(tcp_exchange(&mut third, &www_query(false)).is_none());
+
+    shutdown.shutdown();
+    server_thread.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_server_udp_cookies() {
+    let (addr, shutdown, server_thread) = start_udp_server(CookieConfig {
+        max_queries_per_second: Some(0),
+        ..CookieConfig::default()
+    });
+    let client_cookie = [1, 2, 3, 4, 5, 6, 7, 8];
+    let cookie = |response: &Message| match response
+        .extensions()
+        .as_ref()
+        .and_then(|edns| edns.option(EdnsCode::Cookie))
+    {
+        Some(EdnsOption::Cookie { client, server }) => {
+            assert_eq!(*client, client_cookie);
+            server.clone()
+        }
+        option => panic!("unexpected cookie: {:?}", option),
+    };
+
+    // clients without a server cookie are refused, with one to retry with
+    let mut query = www_query(false);
+    query
+        .extensions_mut()
+        .as_mut()
+        .unwrap()
+        .options_mut()
+        .insert(EdnsOption::Cookie {
+            client: client_cookie,
+            server: vec![],
+        });
+    let response = udp_exchange(addr, &query);
+    assert_eq!(response.response_code(), ResponseCode::BADCOOKIE);
+    assert_eq!(response.answer_count(), 0);
+    let server_cookie = cookie(&response);
+    assert_eq!(server_cookie.len(), 16);
+
+    query
+        .extensions_mut()
+        .as_mut()
+        .unwrap()
+        .options_mut()
+        .insert(EdnsOption::Cookie {
+            client: client_cookie,
+            server: server_cookie,
+        });
+    let response = udp_exchange(addr, &query);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answer_count(), 1);
+    assert_eq!(cookie(&response).len(), 16);
+
+    // clients without cookies retry over TCP
+    let response = udp_exchange(addr, &www_query(false));
+    assert!(response.truncated());
+    assert_eq!(response.answer_count(), 0);
+
+    shutdown.shutdown();
+    server_thread.join().unwrap().unwrap();
+}
+
 #[cfg(feature = "dns-over-rustls")]
 #[allow(unused)]
 fn read_file(path: &str) -> Vec<u8> {
@@ -252,6 +467,50 @@ fn test_server_www_tls() {
     server_thread.join().unwrap();
 }
 
+#[cfg(feature = "dns-over-rustls")]
+#[test]
+fn test_server_www_tls_reloaded() {
+    let dns_name = "ns.example.com";
+    let generated = rcgen::generate_simple_self_signed(vec![dns_name.to_string()]).unwrap();
+    let cert = vec![rustls::Certificate(generated.serialize_der().unwrap())];
+    let key = rustls::PrivateKey(generated.serialize_private_key_der());
+    let ca = cert.clone();
+
+    // the listener has no certificate yet, e.g. before it's first obtained with ACME
+    let certificate = Arc::new(ReloadableCertificate::default());
+
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = runtime.block_on(TcpListener::bind(&addr)).unwrap();
+    let ipaddr = tcp_listener.local_addr().unwrap();
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+    let server_certificate = Arc::clone(&certificate);
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:tls_reloaded:server".to_string())
+        .spawn(move || {
+            server_thread_tls(tcp_listener, server_continue2, server_certificate, runtime)
+        })
+        .unwrap();
+
+    let client = SyncClient::new(lazy_tls_client(ipaddr, dns_name.to_string(), ca.clone()));
+    let name = Name::from_str("www.example.com").unwrap();
+    assert!(client.query(&name, DNSClass::IN, RecordType::A).is_err());
+
+    // the connections after the certificate is replaced are served with it
+    certificate.replace(cert, key).unwrap();
+    let client_thread = thread::Builder::new()
+        .name("test_server:tls_reloaded:client".to_string())
+        .spawn(move || client_thread_www(lazy_tls_client(ipaddr, dns_name.to_string(), ca)))
+        .unwrap();
+
+    let client_result = client_thread.join();
+    assert!(client_result.is_ok(), "client failed: {:?}", client_result);
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
 fn lazy_udp_client(ipaddr: SocketAddr) -> UdpClientConnection {
     UdpClientConnection::new(ipaddr).unwrap()
 }
@@ -364,7 +623,7 @@ fn server_thread_tcp(
 fn server_thread_tls(
     tls_listener: TcpListener,
     server_continue: Arc<AtomicBool>,
-    cert_chain: (Vec<rustls::Certificate>, rustls::PrivateKey),
+    cert_chain: impl Into<TlsCertificate>,
     io_loop: Runtime,
 ) {
     use std::path::Path;
@@ -388,3 +647,120 @@ fn server_thread_tls(
         io_loop.block_on(future::lazy(|_| tokio::time::sleep(Duration::from_millis(10))).flatten());
     }
 }
+
+fn start_tcp_server(
+    limits: ConnectionLimits,
+) -> (
+    SocketAddr,
+    ShutdownHandle,
+    thread::JoinHandle<Result<(), ProtoError>>,
+) {
+    start_tcp_server_with(|server| server.set_connection_limits(limits))
+}
+
+fn start_tcp_server_with(
+    configure: impl FnOnce(&mut ServerFuture<Catalog>),
+) -> (
+    SocketAddr,
+    ShutdownHandle,
+    thread::JoinHandle<Result<(), ProtoError>>,
+) {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = runtime.block_on(TcpListener::bind(&addr)).unwrap();
+    let addr = tcp_listener.local_addr().unwrap();
+
+    let mut server = ServerFuture::new(new_catalog());
+    configure(&mut server);
+    {
+        let _guard = runtime.enter();
+        server.register_listener(tcp_listener, Duration::from_secs(30));
+    }
+
+    let shutdown = server.shutdown_handle();
+    let server_thread = thread::Builder::new()
+        .name("test_server:tcp:server".to_string())
+        .spawn(move || runtime.block_on(server.block_until_done()))
+        .unwrap();
+
+    (addr, shutdown, server_thread)
+}
+
+fn start_udp_server(
+    cookies: CookieConfig,
+) -> (
+    SocketAddr,
+    ShutdownHandle,
+    thread::JoinHandle<Result<(), ProtoError>>,
+) {
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = runtime.block_on(UdpSocket::bind(&addr)).unwrap();
+    let addr = udp_socket.local_addr().unwrap();
+
+    let mut server = ServerFuture::new(new_catalog());
+    server.set_cookies(Some(cookies));
+    {
+        let _guard = runtime.enter();
+        server.register_socket(udp_socket);
+    }
+
+    let shutdown = server.shutdown_handle();
+    let server_thread = thread::Builder::new()
+        .name("test_server:udp:server".to_string())
+        .spawn(move || runtime.block_on(server.block_until_done()))
+        .unwrap();
+
+    (addr, shutdown, server_thread)
+}
+
+/// Sends the message to the server over UDP, and waits for the response
+fn udp_exchange(addr: SocketAddr, message: &Message) -> Message {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    socket.send_to(&message.to_vec().unwrap(), addr).unwrap();
+
+    let mut buf = [0_u8; 4096];
+    let (len, _) = socket.recv_from(&mut buf).expect("no response");
+    Message::from_vec(&buf[..len]).unwrap()
+}
+
+fn connect_tcp(addr: SocketAddr) -> std::net::TcpStream {
+    let stream = std::net::TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+}
+
+fn www_query(keepalive: bool) -> Message {
+    let mut message = Message::new();
+    message.add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+
+    let mut edns = Edns::new();
+    if keepalive {
+        edns.options_mut().insert(EdnsOption::TcpKeepalive(None));
+    }
+    message.set_edns(edns);
+    message
+}
+
+/// Sends the message over the connection, `None` if the connection is closed without a response
+fn tcp_exchange(stream: &mut std::net::TcpStream, message: &Message) -> Option<Message> {
+    let bytes = message.to_vec().unwrap();
+    stream
+        .write_all(&(bytes.len() as u16).to_be_bytes())
+        .and_then(|_| stream.write_all(&bytes))
+        .ok()?;
+
+    let mut len = [0_u8; 2];
+    stream.read_exact(&mut len).ok()?;
+    let mut bytes = vec![0; usize::from(u16::from_be_bytes(len))];
+    stream.read_exact(&mut bytes).ok()?;
+    Some(Message::from_vec(&bytes).unwrap())
+}
@@ -0,0 +1,195 @@
+#![cfg(feature = "dnssec")]
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use openssl::rsa::Rsa;
+use tokio::net::UdpSocket;
+
+use trust_dns_client::rr::dnssec::{tbs, Algorithm, KeyPair, Private};
+use trust_dns_client::rr::rdata::{DNSSECRData, DNSKEY, SIG};
+use trust_dns_client::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns_proto::op::{Message, MessageBuilder};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::trust_anchor::{KeyState, TrustAnchors};
+use trust_dns_resolver::TokioAsyncResolver;
+
+fn key() -> (KeyPair<Private>, DNSKEY) {
+    let key_pair = KeyPair::from_rsa(Rsa::generate(1024).unwrap()).unwrap();
+    let dnskey = key_pair.to_dnskey(Algorithm::RSASHA256).unwrap();
+    (key_pair, dnskey)
+}
+
+fn revoked(dnskey: &DNSKEY) -> DNSKEY {
+    DNSKEY::new(
+        true,
+        true,
+        true,
+        dnskey.algorithm(),
+        dnskey.public_key().to_vec(),
+    )
+}
+
+/// The root DNSKEY RRset
+fn dnskey_rrset(dnskeys: &[DNSKEY]) -> Vec<Record> {
+    dnskeys
+        .iter()
+        .map(|dnskey| {
+            Record::from_rdata(
+                Name::root(),
+                3600,
+                RData::DNSSEC(DNSSECRData::DNSKEY(dnskey.clone())),
+            )
+        })
+        .collect()
+}
+
+/// The RRSIG of the root DNSKEY RRset, signed by the key published as `signer`
+fn rrsig(dnskeys: &[DNSKEY], key_pair: &KeyPair<Private>, signer: &DNSKEY) -> Record {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let sig = |signature| {
+        SIG::new(
+            RecordType::DNSKEY,
+            Algorithm::RSASHA256,
+            0,
+            3600,
+            now + 3600,
+            now - 3600,
+            signer.calculate_key_tag().unwrap(),
+            Name::root(),
+            signature,
+        )
+    };
+
+    let tbs = tbs::rrset_tbs_with_sig(
+        &Name::root(),
+        DNSClass::IN,
+        &sig(vec![]),
+        &dnskey_rrset(dnskeys),
+    )
+    .unwrap();
+    let signature = key_pair.sign(Algorithm::RSASHA256, &tbs).unwrap();
+
+    let mut rrsig = Record::from_rdata(
+        Name::root(),
+        3600,
+        RData::DNSSEC(DNSSECRData::SIG(sig(signature))),
+    );
+    rrsig.set_rr_type(RecordType::RRSIG);
+    rrsig
+}
+
+/// Answers the DNSKEY queries of the root, and all other queries without any records
+async fn root_server(answers: Vec<Record>) -> u16 {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        let mut buf = [0_u8; 4096];
+        while let Ok((len, src)) = socket.recv_from(&mut buf).await {
+            let request = Message::from_vec(&buf[..len]).unwrap();
+            let mut response = MessageBuilder::response(&request);
+            response.authoritative(true);
+            if request.queries()[0].query_type() == RecordType::DNSKEY {
+                response.add_answers(answers.clone());
+            }
+
+            let response = response.to_vec().unwrap();
+            socket.send_to(&response, src).await.unwrap();
+        }
+    });
+
+    port
+}
+
+fn anchors_path(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("trust_dns_{}_{}.anchors", test, std::process::id()))
+}
+
+/// A validating resolver of the root server, which trusts the key
+fn resolver(port: u16, path: &PathBuf, dnskey: &DNSKEY) -> TokioAsyncResolver {
+    let mut anchors = TrustAnchors::new();
+    anchors.insert(
+        Name::root(),
+        dnskey.clone(),
+        KeyState::Valid,
+        SystemTime::now(),
+    );
+    anchors.save(path).unwrap();
+
+    let mut config = ResolverConfig::from_parts(
+        None,
+        vec![],
+        NameServerConfigGroup::from_ips_clear(&[IpAddr::V4(Ipv4Addr::LOCALHOST)], port, true),
+    );
+    config.set_trust_anchor_file(path);
+    let mut options = ResolverOpts::default();
+    options.validate = true;
+
+    TokioAsyncResolver::tokio(config, options).unwrap()
+}
+
+#[tokio::test]
+async fn test_refresh_revokes_self_signed_key() {
+    let (old_key_pair, old_key) = key();
+    let (new_key_pair, new_key) = key();
+    let revoked_old_key = revoked(&old_key);
+
+    // the old key signs its own revocation
+    let dnskeys = [revoked_old_key.clone(), new_key.clone()];
+    let mut answers = dnskey_rrset(&dnskeys);
+    answers.push(rrsig(&dnskeys, &old_key_pair, &revoked_old_key));
+    answers.push(rrsig(&dnskeys, &new_key_pair, &new_key));
+
+    let port = root_server(answers).await;
+    let path = anchors_path("revokes_self_signed_key");
+    let resolver = resolver(port, &path, &old_key);
+    resolver
+        .lookup(Name::root(), RecordType::DNSKEY)
+        .await
+        .expect("the DNSKEY RRset is anchored by the old key");
+
+    assert!(resolver.refresh_trust_anchors().await.unwrap());
+    let anchors = TrustAnchors::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(anchors.keys()[0].state(), KeyState::Revoked);
+    assert_eq!(anchors.keys()[1].state(), KeyState::AddPend);
+
+    // the validator of the resolver no longer trusts the revoked key, nor the pending one
+    resolver.clear_cache();
+    assert!(resolver
+        .lookup(Name::root(), RecordType::DNSKEY)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_refresh_ignores_revoke_not_self_signed() {
+    let (_, old_key) = key();
+    let (new_key_pair, new_key) = key();
+
+    // the REVOKE bit of the old key is only signed by the new key
+    let dnskeys = [revoked(&old_key), new_key.clone()];
+    let mut answers = dnskey_rrset(&dnskeys);
+    answers.push(rrsig(&dnskeys, &new_key_pair, &new_key));
+
+    let port = root_server(answers).await;
+    let path = anchors_path("ignores_revoke_not_self_signed");
+    let resolver = resolver(port, &path, &old_key);
+
+    assert!(resolver.refresh_trust_anchors().await.unwrap());
+    let anchors = TrustAnchors::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(anchors.keys()[0].state(), KeyState::Valid);
+    assert_eq!(anchors.keys()[1].state(), KeyState::AddPend);
+
+    resolver.clear_cache();
+    resolver
+        .lookup(Name::root(), RecordType::DNSKEY)
+        .await
+        .expect("the old key is still trusted");
+}
@@ -0,0 +1,149 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use trust_dns_client::op::{Message, Query};
+use trust_dns_client::rr::{Name, RData, Record, RecordType, RrKey};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+use trust_dns_server::authority::{Authority, Catalog, MessageRequest, View};
+use trust_dns_server::server::{Protocol, Request, RequestHandler};
+
+use trust_dns_integration::{example_authority::create_example, TestResponseHandler};
+
+const INTERNAL_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+const EXTERNAL_ADDR: Ipv4Addr = Ipv4Addr::new(93, 184, 216, 34);
+
+fn www() -> Name {
+    Name::from_str("www.example.com.").unwrap()
+}
+
+/// The example zone, as served to internal clients
+fn internal_catalog() -> Catalog {
+    let mut example = create_example();
+    example
+        .records_get_mut()
+        .remove(&RrKey::new(www().into(), RecordType::A));
+    example.upsert_mut(Record::from_rdata(www(), 86400, RData::A(INTERNAL_ADDR)), 0);
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+    catalog
+}
+
+fn external_catalog() -> Catalog {
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), Box::new(Arc::new(example)));
+    catalog
+}
+
+fn query_www() -> Message {
+    let mut message = Message::new();
+    message.add_query(Query::query(www(), RecordType::A));
+    message
+}
+
+async fn lookup(catalog: &Catalog, src: IpAddr, message: Message) -> Vec<RData> {
+    let bytes = message.to_bytes().unwrap();
+    let request = MessageRequest::from_bytes(&bytes).unwrap();
+    let request = Request::new(request, SocketAddr::new(src, 5553), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .handle_request(&request, response_handler.clone())
+        .await;
+    response_handler
+        .into_message()
+        .await
+        .answers()
+        .iter()
+        .filter_map(|r| r.data().cloned())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_view_by_client_address() {
+    let mut internal = View::new("internal".to_string(), internal_catalog());
+    internal.set_match_clients(vec!["10.0.0.0/8".parse().unwrap()]);
+
+    let mut catalog = external_catalog();
+    catalog.add_view(internal);
+
+    assert_eq!(
+        lookup(&catalog, [10, 1, 2, 3].into(), query_www()).await,
+        vec![RData::A(INTERNAL_ADDR)]
+    );
+    assert_eq!(
+        lookup(&catalog, [192, 0, 2, 1].into(), query_www()).await,
+        vec![RData::A(EXTERNAL_ADDR)]
+    );
+}
+
+#[tokio::test]
+async fn test_first_matching_view() {
+    let mut internal = View::new("internal".to_string(), internal_catalog());
+    internal.set_match_clients(vec!["10.0.0.0/8".parse().unwrap()]);
+    // matches all clients
+    let external = View::new("external".to_string(), external_catalog());
+
+    let mut catalog = Catalog::new();
+    catalog.add_view(internal);
+    catalog.add_view(external);
+
+    assert_eq!(
+        lookup(&catalog, [10, 1, 2, 3].into(), query_www()).await,
+        vec![RData::A(INTERNAL_ADDR)]
+    );
+    assert_eq!(
+        lookup(&catalog, [192, 0, 2, 1].into(), query_www()).await,
+        vec![RData::A(EXTERNAL_ADDR)]
+    );
+}
+
+#[cfg(feature = "dnssec")]
+#[tokio::test]
+async fn test_view_by_tsig_key() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use trust_dns_client::rr::dnssec::tsig::TSigner;
+    use trust_dns_proto::rr::dnssec::rdata::tsig::TsigAlgorithm;
+
+    let signer = |key: &[u8]| {
+        TSigner::new(
+            key.to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_str("internal-key.").unwrap(),
+            300,
+        )
+        .unwrap()
+    };
+    let signed_query = |key: &[u8]| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let mut message = query_www();
+        message.finalize(&signer(key), now).unwrap();
+        message
+    };
+
+    let mut internal = View::new("internal".to_string(), internal_catalog());
+    internal.add_match_key(signer(b"internal secret"));
+
+    let mut catalog = external_catalog();
+    catalog.add_view(internal);
+
+    let src = IpAddr::from([192, 0, 2, 1]);
+    assert_eq!(
+        lookup(&catalog, src, signed_query(b"internal secret")).await,
+        vec![RData::A(INTERNAL_ADDR)]
+    );
+    assert_eq!(
+        lookup(&catalog, src, signed_query(b"wrong secret")).await,
+        vec![RData::A(EXTERNAL_ADDR)]
+    );
+    assert_eq!(
+        lookup(&catalog, src, query_www()).await,
+        vec![RData::A(EXTERNAL_ADDR)]
+    );
+}
@@ -0,0 +1,237 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::net::{TcpListener, TcpStream as TokioTcpStream};
+
+use trust_dns_client::client::{AsyncClient, ClientHandle, Signer, XfrBatch};
+use trust_dns_client::error::ClientResult;
+use trust_dns_client::rr::{Name, RData, Record, RecordType};
+use trust_dns_client::tcp::TcpClientStream;
+use trust_dns_proto::iocompat::AsyncIoTokioAsStd;
+use trust_dns_server::authority::{AuthorityObject, Catalog};
+use trust_dns_server::server::ServerFuture;
+use trust_dns_server::store::in_memory::InMemoryAuthority;
+
+use trust_dns_integration::example_authority::create_example;
+
+/// Serves the authority over TCP, returns the address of the server
+async fn serve(authority: InMemoryAuthority) -> SocketAddr {
+    serve_authority(Box::new(Arc::new(authority))).await
+}
+
+async fn serve_authority(authority: Box<dyn AuthorityObject>) -> SocketAddr {
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.origin().clone(), authority);
+
+    let tcp_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = tcp_listener.local_addr().unwrap();
+
+    let mut server = ServerFuture::new(catalog);
+    server.register_listener(tcp_listener, Duration::from_secs(30));
+    tokio::spawn(async move {
+        // the server runs until the end of the test
+        let _ = server.block_until_done().await;
+    });
+
+    addr
+}
+
+async fn transfer(
+    addr: SocketAddr,
+    ixfr_serial: Option<u32>,
+    signer: Option<Arc<Signer>>,
+) -> ClientResult<Vec<XfrBatch>> {
+    let (stream, sender) = TcpClientStream::<AsyncIoTokioAsStd<TokioTcpStream>>::new(addr);
+    let (mut client, bg) = AsyncClient::new(stream, sender, None).await?;
+    tokio::spawn(bg);
+
+    client
+        .zone_transfer(Name::from_str("example.com.").unwrap(), ixfr_serial, signer)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Adds enough records to the zone that the transfer spans several messages
+fn large_example() -> InMemoryAuthority {
+    let mut authority = create_example();
+    authority.set_allow_axfr(true);
+
+    for i in 0..2000u32 {
+        let name = Name::from_str(&format!("host{}.example.com.", i)).unwrap();
+        let record = Record::from_rdata(name, 86400, RData::A(Ipv4Addr::from(i)));
+        assert!(authority.upsert_mut(record, 0));
+    }
+
+    authority
+}
+
+fn full_records(batches: &[XfrBatch]) -> Vec<&Record> {
+    batches
+        .iter()
+        .flat_map(|batch| match batch {
+            XfrBatch::Full(records) => records.iter(),
+            batch => panic!("expected a full transfer: {:?}", batch),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_axfr_multiple_messages() {
+    let addr = serve(large_example()).await;
+
+    let batches = transfer(addr, None, None).await.expect("transfer failed");
+    assert!(batches.len() > 1, "expected several messages");
+
+    let records = full_records(&batches);
+    assert_eq!(records[0].record_type(), RecordType::SOA);
+    assert_eq!(
+        records
+            .iter()
+            .filter(|r| r.name().to_string().starts_with("host"))
+            .count(),
+        2000
+    );
+}
+
+#[tokio::test]
+async fn test_axfr_refused_by_acl() {
+    let mut authority = large_example();
+    authority.set_transfer_acl(vec!["192.0.2.0/24".parse().unwrap()]);
+    let addr = serve(authority).await;
+
+    assert!(transfer(addr, None, None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_ixfr_up_to_date() {
+    let authority = large_example();
+    let serial = authority.serial().await;
+    let addr = serve(authority).await;
+
+    // the client's version is current, only the SOA is sent
+    let batches = transfer(addr, Some(serial), None)
+        .await
+        .expect("transfer failed");
+    assert!(batches.is_empty(), "unexpected batches: {:?}", batches);
+}
+
+#[tokio::test]
+async fn test_ixfr_without_history_is_full() {
+    let authority = large_example();
+    let serial = authority.serial().await;
+    let addr = serve(authority).await;
+
+    // no changes are known since the client's version, the full zone is sent
+    let batches = transfer(addr, Some(serial - 1), None)
+        .await
+        .expect("transfer failed");
+    assert_eq!(
+        full_records(&batches)
+            .iter()
+            .filter(|r| r.name().to_string().starts_with("host"))
+            .count(),
+        2000
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_ixfr_after_update() {
+    use trust_dns_server::store::sqlite::SqliteAuthority;
+
+    let authority = SqliteAuthority::new(large_example(), true, false);
+    let serial = authority.serial().await;
+
+    let added = Record::from_rdata(
+        Name::from_str("new.example.com.").unwrap(),
+        86400,
+        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+    );
+    assert!(authority
+        .update_records(&[added.clone()], true)
+        .await
+        .unwrap());
+    let addr = serve_authority(Box::new(Arc::new(authority))).await;
+
+    // only the change is transferred
+    let batches = transfer(addr, Some(serial), None)
+        .await
+        .expect("transfer failed");
+    match batches.as_slice() {
+        [XfrBatch::Incremental {
+            deleted, added: a, ..
+        }] => {
+            assert!(deleted.is_empty());
+            assert_eq!(a, &[added]);
+        }
+        batches => panic!("expected one difference sequence: {:?}", batches),
+    }
+}
+
+#[cfg(feature = "dnssec")]
+mod tsig {
+    use trust_dns_client::rr::dnssec::tsig::TSigner;
+    use trust_dns_proto::rr::dnssec::rdata::tsig::TsigAlgorithm;
+    use trust_dns_server::authority::DnssecAuthority;
+
+    use super::*;
+
+    fn signer(key: &[u8]) -> TSigner {
+        TSigner::new(
+            key.to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_str("transfer-key.").unwrap(),
+            300,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_axfr_signed() {
+        let authority = large_example();
+        authority
+            .add_transfer_tsig_key(signer(b"transfer secret"))
+            .await
+            .unwrap();
+        let addr = serve(authority).await;
+
+        // every message of the response is verified with the chained MACs
+        let batches = transfer(
+            addr,
+            None,
+            Some(Arc::new(signer(b"transfer secret").into())),
+        )
+        .await
+        .expect("transfer failed");
+        assert!(batches.len() > 1, "expected several messages");
+        assert_eq!(
+            full_records(&batches)
+                .iter()
+                .filter(|r| r.name().to_string().starts_with("host"))
+                .count(),
+            2000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_axfr_unsigned_refused() {
+        let authority = large_example();
+        authority
+            .add_transfer_tsig_key(signer(b"transfer secret"))
+            .await
+            .unwrap();
+        let addr = serve(authority).await;
+
+        assert!(transfer(addr, None, None).await.is_err());
+        assert!(
+            transfer(addr, None, Some(Arc::new(signer(b"wrong secret").into())))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2015-2021 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The dnsctl program
+
+// BINARY WARNINGS
+#![warn(
+    clippy::default_trait_access,
+    clippy::dbg_macro,
+    clippy::unimplemented,
+    missing_copy_implementations,
+    missing_docs,
+    non_snake_case,
+    non_upper_case_globals,
+    rust_2018_idioms,
+    unreachable_pub
+)]
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use console::style;
+
+#[cfg(unix)]
+use trust_dns_server::server::{send_control_command, ControlCommand};
+
+/// A CLI interface to the control socket of a running trust-dns server, `named`.
+///
+/// The server listens for commands on the socket configured with `control_socket` in its config
+/// file. The commands are:
+///
+///   reload              reload the configuration and the zones which changed
+///   flush               clear the caches of the recursive and forwarding zones
+///   flushname <name>    remove the name from the caches of the recursive zones
+///   flushtree <name>    remove the name and all names below it from the recursive caches
+///   stats               show the request counters, and the zones with their serials
+///   freeze <zone>       write the journal of a dynamic zone to its file, and refuse updates
+///   thaw <zone>         accept updates to a frozen zone again
+///   loglevel <level>    change the log level, e.g. debug, or a filter like trust_dns_server=trace
+#[derive(Debug, Parser)]
+#[clap(name = "dnsctl", verbatim_doc_comment)]
+struct Opts {
+    /// Path of the control socket of the server
+    #[clap(
+        short = 's',
+        long = "socket",
+        default_value = "/var/run/trust-dns/control.sock"
+    )]
+    socket: PathBuf,
+
+    /// The command, and its argument
+    #[clap(required = true)]
+    command: Vec<String>,
+}
+
+/// Run the dnsctl program
+#[cfg(unix)]
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts: Opts = Opts::parse();
+
+    let command: ControlCommand = opts.command.join(" ").parse()?;
+    match send_control_command(&opts.socket, &command).await {
+        Ok(Ok(output)) => {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            Ok(())
+        }
+        Ok(Err(message)) => {
+            eprintln!("{}: {}", style("error").red(), message);
+            std::process::exit(1);
+        }
+        Err(e) => Err(format!("could not send command to {}: {}", opts.socket.display(), e).into()),
+    }
+}
+
+/// Run the dnsctl program
+#[cfg(not(unix))]
+pub fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let _opts: Opts = Opts::parse();
+    Err("the control socket is only supported on unix".into())
+}
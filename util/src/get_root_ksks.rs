@@ -69,6 +69,7 @@ pub fn main() {
                         String::from("ecdsa")
                     }
                     Algorithm::ED25519 => String::from("ed25519"),
+                    Algorithm::ED448 => String::from("ed448"),
                     Algorithm::Unknown(v) => format!("unknown_{}", v),
                     alg => panic!("unknown Algorithm {:?}", alg),
                 };
@@ -133,6 +133,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
+            http_endpoint: None,
         });
 
         roots.push(NameServerConfig {
@@ -143,6 +144,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
+            http_endpoint: None,
         });
     }
 
@@ -156,6 +156,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
+            http_endpoint: None,
         });
 
         name_servers.push(NameServerConfig {
@@ -166,6 +167,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             #[cfg(feature = "dns-over-rustls")]
             tls_config: None,
             bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
+            http_endpoint: None,
         });
     }
 
@@ -0,0 +1,92 @@
+// Copyright 2015-2023 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The zone-check program
+
+// BINARY WARNINGS
+#![warn(
+    clippy::default_trait_access,
+    clippy::dbg_macro,
+    clippy::unimplemented,
+    missing_copy_implementations,
+    missing_docs,
+    non_snake_case,
+    non_upper_case_globals,
+    rust_2018_idioms,
+    unreachable_pub
+)]
+
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::Parser;
+use console::style;
+
+use trust_dns_client::rr::Name;
+use trust_dns_server::store::file::ZoneCheck;
+
+/// Checks a zone file before it is served, like named-checkzone of BIND.
+///
+/// The zone file is parsed, and it is checked for an SOA and NS records at the origin, for CNAMEs
+/// with other data, for records which are not in the zone and, when built with DNSSEC, for RRSIGs
+/// which are expired or not yet valid. The exit status is 1 if any problem is found.
+///
+/// With --output the zone is also written in canonical form, sorted in the canonical order of
+/// DNSSEC, so that zone files can be compared.
+#[derive(Debug, Parser)]
+#[clap(name = "zone-check", verbatim_doc_comment)]
+struct Opts {
+    /// Origin of the zone, required unless the zone file sets it with $ORIGIN
+    #[clap(short = 'z', long = "origin")]
+    origin: Option<Name>,
+
+    /// Write the canonical zone file to FILE, or to stdout for -
+    #[clap(short = 'o', long = "output", value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Path of the zone file
+    zone_file: PathBuf,
+}
+
+/// Run the zone-check program
+pub fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts: Opts = Opts::parse();
+
+    let zone_file = &opts.zone_file;
+    let zone = ZoneCheck::from_file(zone_file, opts.origin.clone())
+        .map_err(|e| format!("failed to parse {}: {}", zone_file.display(), e))?;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
+    let problems = zone.check(now);
+    for problem in &problems {
+        eprintln!("{}: {}", style("error").red(), problem);
+    }
+
+    if let Some(output) = &opts.output {
+        let canonical = zone.canonical();
+        if output.as_os_str() == "-" {
+            print!("{}", canonical);
+        } else {
+            fs::write(output, canonical)
+                .map_err(|e| format!("failed to write {}: {}", output.display(), e))?;
+        }
+    }
+
+    if !problems.is_empty() {
+        std::process::exit(1);
+    }
+
+    eprintln!(
+        "zone {} loaded, {} record sets",
+        zone.origin(),
+        zone.records().len()
+    );
+    Ok(())
+}